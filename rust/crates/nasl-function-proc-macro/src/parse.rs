@@ -3,6 +3,7 @@ use std::collections::HashSet;
 use crate::error::{Error, ErrorKind, Result};
 use crate::types::*;
 use crate::utils::{get_subty_if_name_is, ty_is_context, ty_is_register, ty_name_is};
+use syn::ext::IdentExt;
 use syn::punctuated::Punctuated;
 use syn::token::Comma;
 use syn::{parenthesized, parse::Parse, spanned::Spanned, FnArg, Ident, ItemFn, Token, Type};
@@ -54,8 +55,9 @@ impl Attrs {
             .iter()
             .find(|attr| attr.idents.contains(ident))
             .map(|attr| &attr.kind);
+        // raw identifiers allow arguments named like keywords, e.g. r#type for type
         let make_named = || NamedArg {
-            name: ident.to_string(),
+            name: ident.unraw().to_string(),
         };
         let make_positional = || PositionalArg { position };
         match attr_kind {
@@ -0,0 +1,261 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Static audit of the builtins a script calls and the endpoints it contacts.
+//!
+//! Scripts are parsed, not executed. Each call of a raw IP, process execution or file builtin
+//! is reported together with the host and port literals passed to any call, so that operators
+//! of locked-down environments can review the behavior of a feed before approving it. Values
+//! computed at runtime, e.g. the port of a KB item, are not visible to the audit.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::nasl::syntax::{IdentifierType, Statement, StatementKind, TokenCategory};
+
+/// Builtins crafting or capturing packets on the raw socket
+pub const RAW_IP: &[&str] = &[
+    "arp_probe",
+    "forge_arp_frame",
+    "forge_arpframe",
+    "forge_frame",
+    "forge_icmp_packet",
+    "forge_icmp_v6_packet",
+    "forge_igmp_packet",
+    "forge_ip_packet",
+    "forge_ip_v6_packet",
+    "forge_ipv6_packet",
+    "forge_tcp_packet",
+    "forge_tcp_v6_packet",
+    "forge_udp_packet",
+    "forge_udp_v6_packet",
+    "icmp_echo",
+    "icmp_timestamp",
+    "pcap_next",
+    "send_arp_request",
+    "send_capture",
+    "send_frame",
+    "send_packet",
+    "send_v6packet",
+    "tcp_fin_probe",
+    "tcp_ping",
+    "tcp_probe",
+    "tcp_syn_probe",
+    "tcp_v6_ping",
+    "tcp_xmas_probe",
+];
+
+/// Builtins executing processes locally or on the target
+pub const PROCESS: &[&str] = &[
+    "find_in_path",
+    "pread",
+    "ssh_cmd",
+    "ssh_request_exec",
+    "ssh_shell_open",
+    "ssh_shell_write",
+    "win_cmd_exec",
+];
+
+/// Builtins accessing the local file system
+pub const FILE: &[&str] = &[
+    "file_close",
+    "file_open",
+    "file_read",
+    "file_seek",
+    "file_stat",
+    "file_write",
+    "fread",
+    "fwrite",
+    "get_tmp_dir",
+    "unlink",
+];
+
+/// Named parameters containing a host
+const HOST_PARAMETERS: &[&str] = &["host", "hostname", "ip_dst", "ip6_dst", "dst", "target"];
+
+/// Named parameters containing a port
+const PORT_PARAMETERS: &[&str] = &["port", "dport", "th_dport", "uh_dport"];
+
+/// Builtins expecting the port as first positional parameter
+const PORT_POSITIONAL: &[&str] = &["open_sock_tcp", "open_sock_udp"];
+
+/// Findings of a single script
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Audit {
+    /// Name of the audited file
+    pub filename: String,
+    /// Called raw IP builtins
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub raw_ip: BTreeSet<String>,
+    /// Called process execution builtins
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub process: BTreeSet<String>,
+    /// Called file builtins
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub file: BTreeSet<String>,
+    /// Host literals passed to any call
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub hosts: BTreeSet<String>,
+    /// Port literals passed to any call
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub ports: BTreeSet<i64>,
+}
+
+impl Audit {
+    /// Creates an empty audit of the given file
+    pub fn new(filename: &str) -> Self {
+        Self {
+            filename: filename.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds the findings of a statement of the script.
+    ///
+    /// Included files are not followed, they are expected to be audited on their own.
+    pub fn add(&mut self, statement: &Statement) {
+        for call in calls(statement) {
+            self.add_call(call);
+        }
+    }
+
+    /// Returns true when the script neither calls an audited builtin nor contains endpoints.
+    pub fn is_empty(&self) -> bool {
+        self.raw_ip.is_empty()
+            && self.process.is_empty()
+            && self.file.is_empty()
+            && self.hosts.is_empty()
+            && self.ports.is_empty()
+    }
+
+    fn add_call(&mut self, call: &Statement) {
+        let name = match call.as_token().category() {
+            TokenCategory::Identifier(IdentifierType::Undefined(x)) => x.as_str(),
+            _ => return,
+        };
+        for (list, found) in [
+            (RAW_IP, &mut self.raw_ip),
+            (PROCESS, &mut self.process),
+            (FILE, &mut self.file),
+        ] {
+            if list.contains(&name) {
+                found.insert(name.to_string());
+            }
+        }
+        for (i, parameter) in call.children().iter().enumerate() {
+            match parameter.kind() {
+                StatementKind::NamedParameter(value) => {
+                    let parameter = match parameter.as_token().category() {
+                        TokenCategory::Identifier(IdentifierType::Undefined(x)) => x.as_str(),
+                        _ => continue,
+                    };
+                    if HOST_PARAMETERS.contains(&parameter) {
+                        self.add_host(value);
+                    } else if PORT_PARAMETERS.contains(&parameter) {
+                        self.add_port(value);
+                    }
+                }
+                _ if i == 0 && PORT_POSITIONAL.contains(&name) => self.add_port(parameter),
+                _ => {}
+            }
+        }
+    }
+
+    fn add_host(&mut self, value: &Statement) {
+        if !matches!(value.kind(), StatementKind::Primitive) {
+            return;
+        }
+        match value.as_token().category() {
+            TokenCategory::String(x) | TokenCategory::IPv4Address(x) => {
+                self.hosts.insert(x.clone());
+            }
+            TokenCategory::Data(x) => {
+                self.hosts.insert(String::from_utf8_lossy(x).to_string());
+            }
+            _ => {}
+        }
+    }
+
+    fn add_port(&mut self, value: &Statement) {
+        if let (StatementKind::Primitive, TokenCategory::Number(x)) =
+            (value.kind(), value.as_token().category())
+        {
+            self.ports.insert(*x);
+        }
+    }
+}
+
+/// Returns all calls within the statement including calls nested in parameters
+fn calls(statement: &Statement) -> Vec<&Statement> {
+    let mut results = vec![];
+    for call in statement.find(&|x| matches!(x.kind(), StatementKind::Call(..))) {
+        results.push(call);
+        if let StatementKind::Call(parameter) = call.kind() {
+            results.extend(calls(parameter));
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::nasl::syntax::parse;
+
+    use super::Audit;
+
+    fn audit(code: &str) -> Audit {
+        let mut result = Audit::new("test.nasl");
+        for statement in parse(code) {
+            result.add(&statement.unwrap());
+        }
+        result
+    }
+
+    fn set<T: Ord + Clone>(x: &[T]) -> BTreeSet<T> {
+        x.iter().cloned().collect()
+    }
+
+    #[test]
+    fn findings() {
+        let code = r#"
+if (description) {
+  script_require_ports("Services/www", 80);
+  exit(0);
+}
+function check(port) {
+  soc = open_sock_tcp(port);
+  if (!soc) soc = open_sock_udp(161);
+  return soc;
+}
+ip = forge_ip_packet(ip_dst:"192.0.2.1", ip_p:IPPROTO_TCP);
+send_packet(forge_tcp_packet(ip:ip, th_dport:8443), pcap_active:FALSE);
+r = http_get(item:"/", port:8080);
+pread(cmd:"id", argv:make_list("id"));
+fwrite(data:r, file:get_tmp_dir() + "x");
+soc = open_sock_tcp(get_http_port(default:80));
+"#;
+        let result = audit(code);
+        assert_eq!(result.filename, "test.nasl");
+        assert_eq!(
+            result.raw_ip,
+            set(&["forge_ip_packet", "forge_tcp_packet", "send_packet"].map(String::from))
+        );
+        assert_eq!(result.process, set(&["pread".to_string()]));
+        assert_eq!(
+            result.file,
+            set(&["fwrite", "get_tmp_dir"].map(String::from))
+        );
+        assert_eq!(result.hosts, set(&["192.0.2.1".to_string()]));
+        assert_eq!(result.ports, set(&[161, 8080, 8443]));
+        assert!(!result.is_empty());
+    }
+
+    #[test]
+    fn empty() {
+        assert!(audit("display(strlen(\"abc\"));").is_empty());
+    }
+}
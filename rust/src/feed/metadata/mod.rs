@@ -0,0 +1,348 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Extracts the metadata of a VT without executing the script.
+//!
+//! Only the `if (description)` block and constant assignments before it are evaluated. Before
+//! the evaluation each statement is checked to consist solely of constant expressions, control
+//! flow and calls of the description functions or of side-effect free builtins like
+//! `make_list`. Includes, loops, user defined functions and every other builtin are rejected,
+//! so that extracting the metadata neither touches the network nor the file system. Scripts
+//! that are rejected can still be handled by running them in description mode.
+
+use std::sync::Mutex;
+
+use thiserror::Error;
+
+use crate::nasl::builtin::description::Description;
+use crate::nasl::interpreter::{InterpretError, Interpreter};
+use crate::nasl::prelude::*;
+use crate::nasl::syntax::{IdentifierType, Statement, StatementKind, SyntaxError, TokenCategory};
+use crate::nasl::utils::Executor;
+use crate::nasl::{nasl_std_functions, NoOpLoader};
+use crate::storage::{item::Nvt, ContextKey, Dispatcher, Field, NoOpRetriever, StorageError};
+
+/// Builtins without side effects that may be used within a description block
+const PURE_FUNCTIONS: &[&str] = &[
+    "defined_func",
+    "make_array",
+    "make_list",
+    "raw_string",
+    "strcat",
+    "string",
+];
+
+/// Errors while extracting the metadata
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+    /// NASL script contains an SyntaxError
+    #[error("Syntax error: {0}")]
+    SyntaxError(Box<SyntaxError>),
+    /// The description block contains a statement that cannot be evaluated statically
+    #[error("Unsupported statement in line {line}: {statement}")]
+    Unsupported {
+        /// Line of the statement
+        line: usize,
+        /// The rejected statement
+        statement: String,
+    },
+    /// The evaluation of the description block failed
+    #[error("Interpreter error: {0}")]
+    InterpretError(Box<InterpretError>),
+    /// The script does not contain a description block
+    #[error("Missing description block")]
+    MissingDescription,
+}
+
+impl From<SyntaxError> for Error {
+    fn from(value: SyntaxError) -> Self {
+        Self::SyntaxError(Box::new(value))
+    }
+}
+
+impl From<InterpretError> for Error {
+    fn from(value: InterpretError) -> Self {
+        Self::InterpretError(Box::new(value))
+    }
+}
+
+/// Collects the dispatched fields of a single VT
+struct Collector {
+    nvt: Mutex<Nvt>,
+}
+
+impl Dispatcher for Collector {
+    fn dispatch(&self, _: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        if let Field::NVT(field) = scope {
+            // a version is only set by plugin_feed_info.inc
+            let _ = self.nvt.lock().unwrap().set_from_field(field);
+        }
+        Ok(())
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.dispatch(key, scope)
+    }
+
+    fn on_exit(&self, _: &ContextKey) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+fn identifier(statement: &Statement) -> Option<&str> {
+    match statement.as_token().category() {
+        TokenCategory::Identifier(IdentifierType::Undefined(x)) => Some(x),
+        _ => None,
+    }
+}
+
+/// Returns true for `if (description)` and similar conditions referencing description
+fn is_description_block(statement: &Statement) -> bool {
+    match statement.kind() {
+        StatementKind::If(condition, ..) => !condition
+            .find(&|x| {
+                matches!(x.kind(), StatementKind::Variable) && identifier(x) == Some("description")
+            })
+            .is_empty(),
+        _ => false,
+    }
+}
+
+/// Extracts the metadata of VTs by evaluating their description block
+pub struct MetadataExtractor {
+    description: Executor,
+    functions: Executor,
+    initial: Vec<(String, ContextType)>,
+}
+
+impl MetadataExtractor {
+    /// Creates a new extractor setting OPENVAS_VERSION like a feed update
+    pub fn new(openvas_version: &str) -> Self {
+        Self {
+            description: Executor::single(Description),
+            functions: nasl_std_functions(),
+            initial: vec![
+                ("description".to_owned(), true.into()),
+                ("OPENVAS_VERSION".to_owned(), openvas_version.into()),
+            ],
+        }
+    }
+
+    fn is_allowed_call(&self, name: &str) -> bool {
+        self.description.contains(name) || PURE_FUNCTIONS.contains(&name)
+    }
+
+    /// Returns the first statement that cannot be evaluated without side effects
+    fn unsupported<'a>(&self, statement: &'a Statement) -> Option<&'a Statement> {
+        let supported = match statement.kind() {
+            StatementKind::Primitive
+            | StatementKind::Variable
+            | StatementKind::NoOp
+            | StatementKind::EoF
+            | StatementKind::Array(_)
+            | StatementKind::Index(..)
+            | StatementKind::Exit(_)
+            | StatementKind::Block(_)
+            | StatementKind::If(..)
+            | StatementKind::Operator(..)
+            | StatementKind::Parameter(_)
+            | StatementKind::Struct(_)
+            | StatementKind::NamedParameter(_)
+            | StatementKind::Declare(_)
+            | StatementKind::Assign(..)
+            | StatementKind::AttackCategory => true,
+            StatementKind::Call(_) => identifier(statement)
+                .map(|x| self.is_allowed_call(x))
+                .unwrap_or_default(),
+            StatementKind::Include(_)
+            | StatementKind::FunctionDeclaration(..)
+            | StatementKind::Return(_)
+            | StatementKind::For(..)
+            | StatementKind::ForEach(..)
+            | StatementKind::While(..)
+            | StatementKind::Repeat(..)
+            | StatementKind::Break
+            | StatementKind::Continue => false,
+        };
+        if !supported {
+            return Some(statement);
+        }
+        let mut children: Vec<&Statement> = match statement.kind() {
+            StatementKind::Call(x) => vec![x],
+            _ => statement.children().iter().collect(),
+        };
+        match statement.kind() {
+            StatementKind::Array(Some(x))
+            | StatementKind::Exit(x)
+            | StatementKind::NamedParameter(x) => children.push(x),
+            StatementKind::Assign(_, _, x, y) | StatementKind::Index(x, y) => {
+                children.extend([x.as_ref(), y.as_ref()])
+            }
+            StatementKind::If(x, y, _, z) => {
+                children.extend([x.as_ref(), y.as_ref()]);
+                children.extend(z.as_deref());
+            }
+            _ => {}
+        }
+        children.into_iter().find_map(|x| self.unsupported(x))
+    }
+
+    fn check(&self, code: &str, statement: &Statement) -> Result<(), Error> {
+        match self.unsupported(statement) {
+            Some(x) => {
+                let line = x.as_token().line_column.0;
+                Err(Error::Unsupported {
+                    line,
+                    statement: code
+                        .lines()
+                        .nth(line - 1)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the metadata of the given script.
+    ///
+    /// The filename is stored within the metadata like in a feed update.
+    pub async fn extract(&self, filename: &str, code: &str) -> Result<Nvt, Error> {
+        let collector = Collector {
+            nvt: Mutex::new(Nvt {
+                filename: filename.to_string(),
+                ..Default::default()
+            }),
+        };
+        let retriever = NoOpRetriever::default();
+        let loader = NoOpLoader::default();
+        let context = Context::new(
+            ContextKey::FileName(filename.to_string()),
+            String::default(),
+            &collector,
+            &retriever,
+            &loader,
+            &self.functions,
+        );
+        let mut interpreter = Interpreter::new(Register::root_initial(&self.initial), &context);
+        let mut found = false;
+        for statement in crate::nasl::syntax::parse(code) {
+            let statement = statement?;
+            if is_description_block(&statement) {
+                self.check(code, &statement)?;
+                found = true;
+                interpreter.retry_resolve_next(&statement, 0).await?;
+                break;
+            }
+            // constants used within the description block
+            if matches!(statement.kind(), StatementKind::Assign(..))
+                && self.unsupported(&statement).is_none()
+            {
+                interpreter.retry_resolve_next(&statement, 0).await?;
+            }
+        }
+        if !found {
+            return Err(Error::MissingDescription);
+        }
+        drop(interpreter);
+        drop(context);
+        Ok(collector.nvt.into_inner().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::item::{NvtPreference, NvtRef, PreferenceType, TagKey, TagValue, ACT};
+
+    use super::{Error, MetadataExtractor};
+
+    #[tokio::test]
+    async fn extract() {
+        let code = r#"
+include("compat.inc");
+PREFIX = "1.3.6.1.4.1.25623.1.0.";
+
+if (description) {
+  script_oid(PREFIX + "100001");
+  script_version("2024-01-01T00:00:00+0000");
+  script_tag(name:"summary", value:"A test.");
+  script_name("Test VT");
+  script_category(ACT_GATHER_INFO);
+  script_family("Product detection");
+  script_dependencies("a.nasl", "b.nasl");
+  script_require_ports("Services/www", 80);
+  script_xref(name:"URL", value:"https://example.com");
+  script_add_preference(name:"Timeout", type:"entry", value:"5", id:1);
+  if (defined_func("script_cve_id"))
+    script_cve_id("CVE-2024-0001");
+  exit(0);
+}
+
+soc = open_sock_tcp(80);
+send(socket:soc, data:"GET / HTTP/1.0\r\n\r\n");
+"#;
+        let nvt = MetadataExtractor::new("1")
+            .extract("test.nasl", code)
+            .await
+            .unwrap();
+        assert_eq!(nvt.oid, "1.3.6.1.4.1.25623.1.0.100001");
+        assert_eq!(nvt.filename, "test.nasl");
+        assert_eq!(nvt.name, "Test VT");
+        assert_eq!(nvt.category, ACT::GatherInfo);
+        assert_eq!(nvt.family, "Product detection");
+        assert_eq!(nvt.dependencies, vec!["a.nasl", "b.nasl"]);
+        assert_eq!(nvt.required_ports, vec!["Services/www", "80"]);
+        assert_eq!(
+            nvt.tag.get(&TagKey::Summary),
+            Some(&TagValue::String("A test.".to_string()))
+        );
+        assert_eq!(
+            nvt.preferences,
+            vec![NvtPreference {
+                id: Some(1),
+                class: PreferenceType::Entry,
+                name: "Timeout".to_string(),
+                default: "5".to_string()
+            }]
+        );
+        assert!(nvt.references.contains(&NvtRef {
+            class: "https://example.com".to_string(),
+            id: "URL".to_string()
+        }));
+        assert!(nvt.references.iter().any(|x| x.id == "CVE-2024-0001"));
+    }
+
+    #[tokio::test]
+    async fn reject_side_effects() {
+        let extractor = MetadataExtractor::new("1");
+        let code = r#"
+if (description) {
+  script_oid("1.2.3");
+  script_name(get_kb_item("name"));
+  exit(0);
+}
+"#;
+        assert_eq!(
+            extractor.extract("test.nasl", code).await,
+            Err(Error::Unsupported {
+                line: 4,
+                statement: "script_name(get_kb_item(\"name\"));".to_string()
+            })
+        );
+        let code = r#"
+if (description) {
+  include("x.inc");
+}
+"#;
+        assert!(matches!(
+            extractor.extract("test.nasl", code).await,
+            Err(Error::Unsupported { line: 3, .. })
+        ));
+        assert_eq!(
+            extractor.extract("test.nasl", "display(1);").await,
+            Err(Error::MissingDescription)
+        );
+    }
+}
@@ -3,6 +3,9 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 #![doc = include_str!("README.md")]
+mod audit;
+mod metadata;
+mod mutate;
 mod oid;
 mod transpile;
 mod update;
@@ -11,11 +14,24 @@ mod verify;
 #[cfg(test)]
 mod update_tests;
 
+pub use audit::Audit;
+pub use metadata::Error as MetadataError;
+pub use metadata::MetadataExtractor;
+pub use mutate::mutations;
+pub use mutate::MutateError;
+pub use mutate::Mutation;
+pub use mutate::MutationKind;
+pub use mutate::MutationTester;
+pub use mutate::Outcome as MutationOutcome;
+pub use mutate::Report as MutationReport;
+pub use mutate::Verdict as MutationVerdict;
 pub use oid::Oid;
 pub use update::feed_version as version;
+pub use update::DescriptionCache;
 pub use update::Error as UpdateError;
 pub use update::ErrorKind as UpdateErrorKind;
 pub use update::Update;
+pub use update::{validate, InvalidVt, Validation, ValidationMode, Violation};
 pub use verify::check_signature;
 pub use verify::Error as VerifyError;
 pub use verify::FileNameLoader;
@@ -26,3 +42,4 @@ pub use verify::SignatureChecker;
 
 pub use transpile::FeedReplacer;
 pub use transpile::ReplaceCommand;
+pub use transpile::ReplaceError;
@@ -0,0 +1,567 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Mutation testing of NASL scripts.
+//!
+//! Comparison operators and number constants within the execution part of a script are changed
+//! one at a time. Each of those mutants is executed against the same target as the unchanged
+//! script. When the observable behavior, the results, the written KB items, the exit code or the
+//! error, does not differ, the mutant survived and the changed condition is not covered by the
+//! target. To get reproducible runs the target should be a test system or a replay of recorded
+//! traffic.
+
+use std::{
+    fmt::Display,
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures::StreamExt;
+use thiserror::Error;
+
+use super::transpile::{find_operator, CodeReplacer, Find, Replace, ReplaceCommand, ReplaceError};
+use crate::nasl::interpreter::{
+    CodeInterpreter, InterpretError, InterpretErrorKind, InterpreterHook,
+};
+use crate::nasl::nasl_std_functions;
+use crate::nasl::prelude::*;
+use crate::nasl::syntax::{IdentifierType, Statement, StatementKind, SyntaxError, TokenCategory};
+use crate::nasl::utils::{traffic::Recording, Executor, Traffic};
+use crate::storage::{ContextKey, DefaultDispatcher, Field, Retrieve, Retriever, Storage};
+
+/// Minimal number of statements a mutant may execute before it is considered to not terminate
+const MIN_STATEMENT_BUDGET: usize = 10_000;
+
+/// Error while creating or applying mutations
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MutateError {
+    /// The script is not valid
+    #[error("{0}")]
+    Syntax(Box<SyntaxError>),
+    /// A mutation could not be applied
+    #[error("{0}")]
+    Replace(Box<ReplaceError>),
+}
+
+impl From<SyntaxError> for MutateError {
+    fn from(value: SyntaxError) -> Self {
+        Self::Syntax(Box::new(value))
+    }
+}
+
+impl From<ReplaceError> for MutateError {
+    fn from(value: ReplaceError) -> Self {
+        Self::Replace(Box::new(value))
+    }
+}
+
+/// Kind of the change of a mutation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MutationKind {
+    /// A comparison operator is replaced, e.g. `<` with `<=`
+    Comparison,
+    /// A number constant is incremented or decremented
+    Constant,
+}
+
+/// A single change of the code
+#[derive(Clone, Debug)]
+pub struct Mutation {
+    /// Kind of the change
+    pub kind: MutationKind,
+    /// Line of the change, starting with 1
+    pub line: usize,
+    /// Column of the change, starting with 1
+    pub column: usize,
+    /// The original code
+    pub original: String,
+    /// The replacement
+    pub replacement: String,
+    /// The command of the transpiler applying the change
+    pub command: ReplaceCommand,
+}
+
+impl Mutation {
+    fn new(
+        code: &str,
+        kind: MutationKind,
+        (start, end): (usize, usize),
+        find: Find,
+        replacement: impl Into<String>,
+    ) -> Self {
+        let replacement = replacement.into();
+        let before = &code[..start];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rfind('\n').map_or(start, |x| start - x - 1) + 1;
+        let with = match kind {
+            MutationKind::Comparison => Replace::Operator(replacement.clone()),
+            MutationKind::Constant => Replace::Constant(replacement.clone()),
+        };
+        Self {
+            kind,
+            line,
+            column,
+            original: code[start..end].to_string(),
+            replacement,
+            command: ReplaceCommand { find, with },
+        }
+    }
+
+    /// Returns the code with this mutation applied
+    pub fn apply(&self, code: &str) -> Result<String, MutateError> {
+        Ok(CodeReplacer::replace(
+            code,
+            std::slice::from_ref(&self.command),
+        )?)
+    }
+}
+
+impl Display for Mutation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} {} -> {}",
+            self.line, self.column, self.original, self.replacement
+        )
+    }
+}
+
+fn comparison_replacements(category: &TokenCategory) -> &'static [&'static str] {
+    match category {
+        TokenCategory::EqualEqual => &["!="],
+        TokenCategory::BangEqual => &["=="],
+        TokenCategory::Less => &["<=", ">="],
+        TokenCategory::LessEqual => &["<", ">"],
+        TokenCategory::Greater => &[">=", "<="],
+        TokenCategory::GreaterEqual => &[">", "<"],
+        TokenCategory::EqualTilde => &["!~"],
+        TokenCategory::BangTilde => &["=~"],
+        TokenCategory::GreaterLess => &[">!<"],
+        TokenCategory::GreaterBangLess => &["><"],
+        _ => &[],
+    }
+}
+
+fn is_comparison(statement: &Statement) -> bool {
+    match statement.kind() {
+        StatementKind::Operator(category, operands) => {
+            operands.len() == 2 && !comparison_replacements(category).is_empty()
+        }
+        _ => false,
+    }
+}
+
+fn is_number(statement: &Statement) -> bool {
+    matches!(statement.kind(), StatementKind::Primitive)
+        && matches!(statement.as_token().category(), TokenCategory::Number(_))
+}
+
+/// Returns true for `if (description)`, which contains the metadata of a VT
+fn is_description_block(statement: &Statement) -> bool {
+    match statement.kind() {
+        StatementKind::If(condition, ..) => {
+            matches!(condition.kind(), StatementKind::Variable)
+                && matches!(
+                    condition.as_token().category(),
+                    TokenCategory::Identifier(IdentifierType::Undefined(x)) if x == "description"
+                )
+        }
+        _ => false,
+    }
+}
+
+fn comparisons<'a>(statement: &'a Statement, found: &mut Vec<&'a Statement>) {
+    for comparison in statement.find(&is_comparison) {
+        found.push(comparison);
+        for operand in comparison.children() {
+            comparisons(operand, found);
+        }
+    }
+}
+
+/// Returns the possible mutations of the execution part of a script.
+///
+/// Mutations resulting in invalid code are omitted.
+pub fn mutations(code: &str) -> Result<Vec<Mutation>, MutateError> {
+    let mut result = vec![];
+    for statement in crate::nasl::syntax::parse(code) {
+        let statement = statement?;
+        if is_description_block(&statement) {
+            continue;
+        }
+        let mut found = vec![];
+        comparisons(&statement, &mut found);
+        for comparison in found {
+            let StatementKind::Operator(category, operands) = comparison.kind() else {
+                continue;
+            };
+            let operands = (operands[0].position().0, operands[1].position().0);
+            let Some(position) = find_operator(code, category, operands) else {
+                continue;
+            };
+            let (start, end) = comparison.position();
+            for replacement in comparison_replacements(category) {
+                result.push(Mutation::new(
+                    code,
+                    MutationKind::Comparison,
+                    position,
+                    Find::OperationAt(start, end),
+                    *replacement,
+                ));
+            }
+        }
+        for number in statement.find(&is_number) {
+            let TokenCategory::Number(value) = number.as_token().category() else {
+                continue;
+            };
+            // the range of a statement may contain the following token
+            let position = number.as_token().position;
+            let mut replacements = vec![value.saturating_add(1)];
+            // negative numbers would be parsed as operators, e.g. a-0 as a--1
+            if *value > 0 {
+                replacements.push(value - 1);
+            }
+            for replacement in replacements {
+                result.push(Mutation::new(
+                    code,
+                    MutationKind::Constant,
+                    position,
+                    Find::NumberAt(position.0),
+                    replacement.to_string(),
+                ));
+            }
+        }
+    }
+    result.sort_by_key(|x| (x.line, x.column));
+    result.retain(|x| {
+        x.apply(code)
+            .is_ok_and(|x| crate::nasl::syntax::parse(&x).all(|x| x.is_ok()))
+    });
+    Ok(result)
+}
+
+/// Records the KB items written by a script and limits the number of executed statements.
+struct Recorder {
+    budget: usize,
+    statements: AtomicUsize,
+    exhausted: AtomicBool,
+    kb: Mutex<Vec<(String, String)>>,
+}
+
+impl Recorder {
+    fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            statements: AtomicUsize::default(),
+            exhausted: AtomicBool::default(),
+            kb: Mutex::default(),
+        }
+    }
+}
+
+impl InterpreterHook for Recorder {
+    fn before_statement(&self, _: &Statement) -> Result<(), Box<InterpretError>> {
+        if self.statements.fetch_add(1, Ordering::Relaxed) >= self.budget {
+            self.exhausted.store(true, Ordering::Relaxed);
+            return Err(InterpretError::new(
+                InterpretErrorKind::IOError(io::ErrorKind::TimedOut),
+                None,
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn before_builtin(&self, name: &str, register: &Register) -> Result<(), Box<InterpretError>> {
+        if matches!(name, "set_kb_item" | "replace_kb_item") {
+            let argument = |key| match register.named(key) {
+                Some(ContextType::Value(x)) => x.to_string(),
+                _ => String::default(),
+            };
+            let mut kb = self.kb.lock().unwrap();
+            kb.push((argument("name"), argument("value")));
+        }
+        Ok(())
+    }
+}
+
+/// The observable behavior of a script run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// The results as type, port and message
+    pub results: Vec<String>,
+    /// The written KB items
+    pub kb: Vec<(String, String)>,
+    /// The exit code
+    pub exit: Option<i64>,
+    /// The error aborting the script
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    /// Returns the first difference to the other outcome
+    fn difference(&self, other: &Outcome) -> Option<&'static str> {
+        if self.results != other.results {
+            Some("results")
+        } else if self.kb != other.kb {
+            Some("kb items")
+        } else if self.exit != other.exit {
+            Some("exit code")
+        } else if self.error != other.error {
+            Some("error")
+        } else {
+            None
+        }
+    }
+}
+
+/// Verdict about a single mutant
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The behavior differs in the given aspect
+    Killed(&'static str),
+    /// The mutant did not terminate within the statement budget
+    Timeout,
+    /// The behavior is the same as of the unchanged script
+    Survived,
+}
+
+/// Result of the mutation testing of a script
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// The behavior of the unchanged script
+    pub original: Outcome,
+    /// The mutations with their verdicts
+    pub mutants: Vec<(Mutation, Verdict)>,
+}
+
+impl Report {
+    /// Returns the mutations that survived
+    pub fn survived(&self) -> impl Iterator<Item = &Mutation> {
+        self.mutants
+            .iter()
+            .filter(|(_, v)| v == &Verdict::Survived)
+            .map(|(m, _)| m)
+    }
+
+    /// Returns the share of killed mutants or 1 when there are no mutants
+    pub fn score(&self) -> f64 {
+        if self.mutants.is_empty() {
+            return 1.0;
+        }
+        let survived = self.survived().count();
+        (self.mutants.len() - survived) as f64 / self.mutants.len() as f64
+    }
+}
+
+/// Executes scripts and their mutants against a target
+pub struct MutationTester<'a> {
+    loader: &'a dyn Loader,
+    executor: Executor,
+    target: String,
+    replay: Option<Recording>,
+}
+
+impl<'a> MutationTester<'a> {
+    /// Creates a new tester using the loader for includes and the given target
+    pub fn new(loader: &'a dyn Loader, target: impl Into<String>) -> Self {
+        Self {
+            loader,
+            executor: nasl_std_functions(),
+            target: target.into(),
+            replay: None,
+        }
+    }
+
+    /// Replays the recorded traffic in each run instead of connecting to the target
+    pub fn with_replay(mut self, recording: Recording) -> Self {
+        self.replay = Some(recording);
+        self
+    }
+
+    /// Executes the code and returns its behavior and the number of executed statements.
+    ///
+    /// Returns None when the budget is exhausted.
+    async fn execute(&self, code: &str, budget: usize) -> Option<(Outcome, usize)> {
+        let storage = DefaultDispatcher::new();
+        let key = ContextKey::Scan("mutate".to_string(), Some(self.target.clone()));
+        let context = Context::new(
+            key.clone(),
+            self.target.clone(),
+            storage.as_dispatcher(),
+            storage.as_retriever(),
+            self.loader,
+            &self.executor,
+        )
+        // the replay consumes the connections, so each run gets its own copy
+        .with_traffic(self.replay.clone().map(Traffic::replay));
+        let recorder = Arc::new(Recorder::new(budget));
+        let mut interpreter = CodeInterpreter::new(code, RegisterBuilder::build(), &context);
+        interpreter.add_hook(recorder.clone());
+        let mut outcome = Outcome::default();
+        let mut results = Box::pin(interpreter.stream());
+        while let Some(result) = results.next().await {
+            match result {
+                Ok(NaslValue::Exit(x)) => {
+                    outcome.exit = Some(x);
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    outcome.error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        drop(results);
+        if recorder.exhausted.load(Ordering::Relaxed) {
+            return None;
+        }
+        outcome.results = storage
+            .retrieve(&key, Retrieve::Result(None))
+            .map(|x| {
+                x.filter_map(|x| match x {
+                    Field::Result(r) => Some(format!(
+                        "{:?} {:?} {}",
+                        r.r_type,
+                        r.port,
+                        r.message.unwrap_or_default()
+                    )),
+                    _ => None,
+                })
+                .collect()
+            })
+            .unwrap_or_default();
+        outcome.kb = std::mem::take(&mut *recorder.kb.lock().unwrap());
+        Some((outcome, recorder.statements.load(Ordering::Relaxed)))
+    }
+
+    /// Executes the code and each of its mutants and compares their behavior.
+    pub async fn run(&self, code: &str) -> Result<Report, MutateError> {
+        let mutations = mutations(code)?;
+        let Some((original, statements)) = self.execute(code, usize::MAX).await else {
+            unreachable!("the budget of the original script is unlimited");
+        };
+        // mutants may loop forever, e.g. when a loop condition is negated
+        let budget = statements.saturating_mul(10).max(MIN_STATEMENT_BUDGET);
+        let mut mutants = Vec::with_capacity(mutations.len());
+        for mutation in mutations {
+            let verdict = match self.execute(&mutation.apply(code)?, budget).await {
+                None => Verdict::Timeout,
+                Some((outcome, _)) => match outcome.difference(&original) {
+                    Some(x) => Verdict::Killed(x),
+                    None => Verdict::Survived,
+                },
+            };
+            tracing::debug!(%mutation, ?verdict);
+            mutants.push((mutation, verdict));
+        }
+        Ok(Report { original, mutants })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nasl::utils::traffic::{Connection, Exchange, Payload, Recording, Transport};
+    use crate::nasl::NoOpLoader;
+
+    use super::{mutations, MutationKind, MutationTester, Verdict};
+
+    #[test]
+    fn find_mutations() {
+        let code = r#"
+if (description) {
+  script_oid("1.2.3");
+  exit(0);
+}
+if (a >= (b + 1)) x = 0;
+"#;
+        let mutations = mutations(code).unwrap();
+        let found: Vec<_> = mutations.iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "6:7 >= -> >",
+                "6:7 >= -> <",
+                "6:15 1 -> 2",
+                "6:15 1 -> 0",
+                "6:23 0 -> 1"
+            ]
+        );
+        assert_eq!(mutations[0].kind, MutationKind::Comparison);
+        assert_eq!(mutations[2].kind, MutationKind::Constant);
+        assert_eq!(
+            mutations[1].apply(code).unwrap().lines().nth(5),
+            Some("if (a < (b + 1)) x = 0;")
+        );
+    }
+
+    #[tokio::test]
+    async fn verdicts() {
+        let code = r#"
+a = 2;
+if (a > 1) log_message(data: "big");
+for (i = 0; i < 3; i++) set_kb_item(name: "i", value: i);
+"#;
+        let loader = NoOpLoader::default();
+        let report = MutationTester::new(&loader, "localhost")
+            .run(code)
+            .await
+            .unwrap();
+        assert_eq!(report.original.results.len(), 1);
+        assert_eq!(report.original.kb.len(), 3);
+        let verdict = |x: &str| {
+            report
+                .mutants
+                .iter()
+                .find(|(m, _)| m.to_string() == x)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        assert_eq!(verdict("2:5 2 -> 1"), Verdict::Killed("results"));
+        assert_eq!(verdict("3:7 > -> >="), Verdict::Survived);
+        assert_eq!(verdict("4:15 < -> <="), Verdict::Killed("kb items"));
+        assert_eq!(verdict("4:15 < -> >="), Verdict::Killed("kb items"));
+        assert_eq!(verdict("4:10 0 -> 1"), Verdict::Killed("kb items"));
+        assert!(report.score() < 1.0);
+        assert!(report.survived().count() >= 2);
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let code = r#"
+soc = open_sock_tcp(80);
+r = recv(socket: soc, length: 10);
+if ("hello" >< r) log_message(data: r);
+"#;
+        let recording = Recording {
+            connections: vec![Connection {
+                transport: Transport::Tcp,
+                port: 80,
+                exchanges: vec![Exchange::Received(Payload(b"hello".to_vec()))],
+            }],
+        };
+        let loader = NoOpLoader::default();
+        let report = MutationTester::new(&loader, "localhost")
+            .with_replay(recording)
+            .run(code)
+            .await
+            .unwrap();
+        assert_eq!(report.original.results.len(), 1);
+        let verdict = |x: &str| {
+            report
+                .mutants
+                .iter()
+                .find(|(m, _)| m.to_string() == x)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        assert_eq!(verdict("2:21 80 -> 81"), Verdict::Killed("results"));
+        assert_eq!(verdict("3:31 10 -> 11"), Verdict::Survived);
+        assert_eq!(verdict("4:13 >< -> >!<"), Verdict::Killed("results"));
+    }
+}
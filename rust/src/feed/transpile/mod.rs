@@ -6,11 +6,12 @@
 
 mod error;
 
-use crate::nasl::syntax::{Statement, StatementKind};
+use crate::nasl::syntax::{Statement, StatementKind, TokenCategory, Tokenizer};
 
 use crate::feed::{verify, NaslFileFinder};
 
-use self::error::{ReplaceError, TranspileError};
+pub use self::error::ReplaceError;
+use self::error::TranspileError;
 
 /// Is used to find parameter by either name or index within a ReplaceCommand
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -35,6 +36,10 @@ pub enum Find {
     FunctionByParameter(Vec<FindParameter>),
     /// Finds a function by name and parameter.
     FunctionByNameAndParameter(String, Vec<FindParameter>),
+    /// Finds an operation with two operands by the byte range of the statement.
+    OperationAt(usize, usize),
+    /// Finds a number constant by the byte offset of its token.
+    NumberAt(usize),
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
@@ -118,6 +123,10 @@ pub enum Replace {
     Remove,
     /// Replace parameter
     Parameter(ParameterOperation),
+    /// Replaces the operator of an operation, e.g. `<` with `<=`
+    Operator(String),
+    /// Replaces a constant, e.g. a number
+    Constant(String),
 }
 
 impl std::fmt::Display for Replace {
@@ -128,6 +137,8 @@ impl std::fmt::Display for Replace {
                 write!(f, "Replace parameter: {}", p)
             }
             Replace::Remove => write!(f, "Remove found statement"),
+            Replace::Operator(x) => write!(f, "Replace operator: {x}"),
+            Replace::Constant(x) => write!(f, "Replace constant: {x}"),
         }
     }
 }
@@ -290,6 +301,15 @@ impl Find {
             Find::FunctionByName(name) => (Some(name as &str), None),
             Find::FunctionByParameter(x) => (None, Some(x as &[_])),
             Find::FunctionByNameAndParameter(x, y) => (Some(x as &str), Some(y as &[_])),
+            Find::OperationAt(start, end) => {
+                return matches!(s.kind(), StatementKind::Operator(_, x) if x.len() == 2)
+                    && s.position() == (*start, *end)
+            }
+            Find::NumberAt(start) => {
+                return matches!(s.kind(), StatementKind::Primitive)
+                    && matches!(s.as_token().category(), TokenCategory::Number(_))
+                    && s.as_token().position.0 == *start
+            }
         };
 
         FunctionNameMatcher { name, parameter }.matches(s)
@@ -305,6 +325,21 @@ pub struct ReplaceCommand {
     pub with: Replace,
 }
 
+/// Returns the position of the operator within the given range of the code.
+///
+/// The operator is not part of an operation statement, it is the last token of its category
+/// between the start of the operands.
+pub(crate) fn find_operator(
+    code: &str,
+    category: &TokenCategory,
+    (start, end): (usize, usize),
+) -> Option<(usize, usize)> {
+    Tokenizer::new(code.get(start..end)?)
+        .filter(|x| x.category() == category)
+        .last()
+        .map(|x| (start + x.position.0, start + x.position.1))
+}
+
 /// Handles the inplace replacements
 pub struct CodeReplacer {
     // since the first position we need to add offset
@@ -393,6 +428,24 @@ impl CodeReplacer {
                 }
                 _ => Err(ReplaceError::Unsupported(r.clone(), s.clone())),
             },
+            Replace::Operator(operator) => match s.kind() {
+                StatementKind::Operator(category, operands) if operands.len() == 2 => {
+                    let range = self
+                        .range_with_offset(&(operands[0].position().0, operands[1].position().0));
+                    let position = find_operator(&self.code, category, range)
+                        .ok_or_else(|| ReplaceError::Unsupported(r.clone(), s.clone()))?;
+                    self.replace_range(&position, operator, &position);
+                    Ok(())
+                }
+                _ => Err(ReplaceError::Unsupported(r.clone(), s.clone())),
+            },
+            Replace::Constant(value) => match s.kind() {
+                StatementKind::Primitive => {
+                    self.replace_range_with_offset(value, &s.as_token().position);
+                    Ok(())
+                }
+                _ => Err(ReplaceError::Unsupported(r.clone(), s.clone())),
+            },
             Replace::Parameter(params) => {
                 let parameter = match s.kind() {
                     StatementKind::FunctionDeclaration(_, stmt, ..)
@@ -1064,6 +1117,29 @@ if (user_ports = get_kb_list("sophos/xg_firewall/http-user/port")) {
         );
     }
 
+    #[test]
+    fn replace_operation_and_number() {
+        let code = "if (a < (b < 1)) x = 10;";
+        // each command is applied to the result of the previous one
+        let replaces = [
+            ReplaceCommand {
+                find: Find::NumberAt(21),
+                with: Replace::Constant("9".into()),
+            },
+            ReplaceCommand {
+                find: Find::OperationAt(9, 16),
+                with: Replace::Operator(">=".into()),
+            },
+        ];
+        let result = CodeReplacer::replace(code, &replaces).unwrap();
+        assert_eq!(result, "if (a < (b >= 1)) x = 9;");
+        let unsupported = ReplaceCommand {
+            find: Find::FunctionByName("f".into()),
+            with: Replace::Operator(">=".into()),
+        };
+        assert!(CodeReplacer::replace("f();", &[unsupported]).is_err());
+    }
+
     #[test]
     fn find_parameter() {
         let code = r#"
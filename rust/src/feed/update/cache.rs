@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Caches the results of the description run by the hash of a script.
+//!
+//! A feed update verifies each script against the sha256sums file anyway, so the hash is
+//! used to detect unchanged scripts. For those the stored metadata is dispatched directly,
+//! without parsing or executing the script. The cache is persisted within an infisto store
+//! so that it survives restarts.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{
+    infisto::{self, IndexedByteStorage, Range, Serialization},
+    item::Nvt,
+    ContextKey, Dispatcher, Field, StorageError,
+};
+
+/// Key under which the cache is stored within an infisto store
+const STORAGE_KEY: &str = "description_cache";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    nvt: Nvt,
+}
+
+/// Description results of scripts by their hash
+#[derive(Debug, Default)]
+pub struct DescriptionCache {
+    openvas_version: String,
+    known: RwLock<HashMap<String, Nvt>>,
+    used: Mutex<HashMap<String, Nvt>>,
+}
+
+impl DescriptionCache {
+    /// Creates an empty cache.
+    ///
+    /// The OPENVAS_VERSION is part of the key as description blocks may depend on it.
+    pub fn new(openvas_version: &str) -> Self {
+        Self {
+            openvas_version: openvas_version.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Loads a cache stored by [store](Self::store).
+    ///
+    /// A missing or unreadable cache results in an empty cache.
+    pub fn load<S>(openvas_version: &str, storage: &S) -> Self
+    where
+        S: IndexedByteStorage,
+    {
+        let cache = Self::new(openvas_version);
+        let entries: Vec<Serialization<Entry>> = match storage.by_range(STORAGE_KEY, Range::All) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(error=%e, "no description cache loaded");
+                return cache;
+            }
+        };
+        {
+            let mut known = cache.known.write().unwrap();
+            for entry in entries {
+                if let Serialization::Deserialized(entry) = entry {
+                    known.insert(entry.key, entry.nvt);
+                }
+            }
+        }
+        cache
+    }
+
+    /// Overrides the stored cache with the entries used since loading it.
+    ///
+    /// Entries of scripts that were removed or changed are dropped this way.
+    pub fn store<S>(&self, storage: &mut S) -> Result<(), infisto::Error>
+    where
+        S: IndexedByteStorage,
+    {
+        let entries = self
+            .used
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, nvt)| {
+                Serialization::serialize(Entry {
+                    key: key.clone(),
+                    nvt: nvt.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // remove fails when there is no cache stored yet
+        let _ = storage.remove(STORAGE_KEY);
+        storage.append_all(STORAGE_KEY, &entries)
+    }
+
+    fn key(&self, hash: &str) -> String {
+        format!("{}:{hash}", self.openvas_version)
+    }
+
+    /// Returns the metadata of a script with the given hash and marks it as used.
+    ///
+    /// The filename is replaced as the same script may be stored under different names.
+    pub fn get(&self, hash: &str, filename: &str) -> Option<Nvt> {
+        let key = self.key(hash);
+        let mut nvt = self.known.read().unwrap().get(&key)?.clone();
+        nvt.filename = filename.to_string();
+        self.used.lock().unwrap().insert(key, nvt.clone());
+        Some(nvt)
+    }
+
+    /// Adds the metadata of a script with the given hash
+    pub fn insert(&self, hash: &str, nvt: Nvt) {
+        let key = self.key(hash);
+        self.known.write().unwrap().insert(key.clone(), nvt.clone());
+        self.used.lock().unwrap().insert(key, nvt);
+    }
+
+    /// Returns the amount of cached scripts
+    pub fn len(&self) -> usize {
+        self.known.read().unwrap().len()
+    }
+
+    /// Returns true when no script is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Forwards everything to a dispatcher while collecting the metadata of the script
+pub(super) struct Recorder<'a> {
+    inner: &'a dyn Dispatcher,
+    nvt: Mutex<Nvt>,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn new(inner: &'a dyn Dispatcher) -> Self {
+        Self {
+            inner,
+            nvt: Default::default(),
+        }
+    }
+
+    pub fn into_nvt(self) -> Nvt {
+        self.nvt.into_inner().unwrap()
+    }
+
+    fn record(&self, scope: &Field) {
+        if let Field::NVT(field) = scope {
+            // the feed version is not part of a script
+            let _ = self.nvt.lock().unwrap().set_from_field(field.clone());
+        }
+    }
+}
+
+impl Dispatcher for Recorder<'_> {
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.record(&scope);
+        self.inner.dispatch(key, scope)
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.record(&scope);
+        self.inner.dispatch_replace(key, scope)
+    }
+
+    fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError> {
+        self.inner.on_exit(key)
+    }
+}
@@ -9,6 +9,8 @@ use thiserror::Error;
 
 use crate::feed::{verify, VerifyError};
 
+use super::Violation;
+
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 /// Errors within feed handling
 pub enum ErrorKind {
@@ -30,6 +32,9 @@ pub enum ErrorKind {
     /// Describes an error while verifying the file
     #[error("Verify error: {0}")]
     VerifyError(#[from] verify::Error),
+    /// The metadata of the script is rejected by the validation
+    #[error("Invalid metadata: {}", .0.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", "))]
+    InvalidMetadata(Vec<Violation>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
@@ -2,10 +2,14 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+mod cache;
 mod error;
+mod validate;
 
+pub use cache::DescriptionCache;
 pub use error::Error;
 pub use error::ErrorKind;
+pub use validate::{validate, InvalidVt, Validation, ValidationMode, Violation};
 
 use futures::{stream, Stream, StreamExt};
 use std::fs::File;
@@ -16,12 +20,17 @@ use crate::nasl::nasl_std_functions;
 use crate::nasl::prelude::*;
 use crate::nasl::syntax::AsBufReader;
 use crate::nasl::ContextType;
-use crate::storage::{item::NVTField, ContextKey, Dispatcher, NoOpRetriever};
+use crate::storage::{
+    item::{NVTField, Nvt},
+    ContextKey, Dispatcher, NoOpRetriever,
+};
 
 use crate::feed::verify::check_signature;
 use crate::feed::verify::{HashSumFileItem, SignatureChecker};
 
 use super::verify;
+use cache::Recorder;
+use validate::Buffer;
 
 /// Updates runs nasl plugin with description true and uses given storage to store the descriptive
 /// information
@@ -36,6 +45,10 @@ pub struct Update<'a, S, L, V> {
     max_retry: usize,
     verifier: V,
     feed_version_set: bool,
+    /// Skips the description run of unchanged scripts when set
+    cache: Option<&'a DescriptionCache>,
+    /// Validates the metadata of each script before it is stored when set
+    validation: Option<&'a Validation>,
 }
 
 /// Loads the plugin_feed_info and returns the feed version
@@ -105,9 +118,29 @@ where
             dispatcher: storage,
             verifier,
             feed_version_set: false,
+            cache: None,
+            validation: None,
         }
     }
 
+    /// Uses the given cache for the description results.
+    ///
+    /// Scripts whose hash is found within the cache are not executed, instead the cached
+    /// metadata is dispatched. The results of the other scripts are added to the cache.
+    pub fn with_cache(mut self, cache: &'a DescriptionCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Validates the metadata of each script before it is stored.
+    ///
+    /// Depending on the mode of the validation, an invalid script fails the update or is
+    /// skipped and added to the quarantined scripts of the validation.
+    pub fn with_validation(mut self, validation: &'a Validation) -> Self {
+        self.validation = Some(validation);
+        self
+    }
+
     /// Loads the plugin_feed_info and returns the feed version
     pub async fn feed_version(&self) -> Result<String, ErrorKind> {
         feed_version(self.loader, self.dispatcher).await
@@ -143,6 +176,67 @@ where
 
     /// Runs a single plugin in description mode.
     async fn single(&self, key: &ContextKey) -> Result<i64, ErrorKind> {
+        self.run(key, self.dispatcher).await
+    }
+
+    /// Returns the cached description results or runs the plugin and caches its results.
+    async fn single_cached(
+        &self,
+        key: &ContextKey,
+        hash: &str,
+        cache: &DescriptionCache,
+    ) -> Result<i64, ErrorKind> {
+        if let Some(nvt) = cache.get(hash, &key.value()) {
+            trace!(key = key.value(), "using cached description");
+            if self
+                .is_valid(key, &nvt)
+                .map_err(ErrorKind::InvalidMetadata)?
+            {
+                self.dispatcher
+                    .retry_dispatch(self.max_retry, key, nvt.into())?;
+                self.dispatcher.on_exit(key)?;
+            }
+            return Ok(0);
+        }
+        let recorder = Recorder::new(self.dispatcher);
+        let result = self.run(key, &recorder).await?;
+        let nvt = recorder.into_nvt();
+        // quarantined scripts are run again on the next update
+        if !nvt.oid.is_empty() {
+            cache.insert(hash, nvt);
+        }
+        Ok(result)
+    }
+
+    /// Returns true when the metadata may be stored or the violations when it is rejected.
+    fn is_valid(&self, key: &ContextKey, nvt: &Nvt) -> Result<bool, Vec<Violation>> {
+        match self.validation {
+            Some(validation) => validation.check(key, nvt),
+            None => Ok(true),
+        }
+    }
+
+    /// Runs the plugin in description mode and dispatches its results when they are valid.
+    async fn run(&self, key: &ContextKey, dispatcher: &dyn Dispatcher) -> Result<i64, ErrorKind> {
+        if self.validation.is_none() {
+            return self.execute(key, dispatcher).await;
+        }
+        let buffer = Buffer::default();
+        let result = self.execute(key, &buffer).await?;
+        if self
+            .is_valid(key, &buffer.nvt())
+            .map_err(ErrorKind::InvalidMetadata)?
+        {
+            buffer.forward(dispatcher, self.max_retry, key)?;
+        }
+        Ok(result)
+    }
+
+    async fn execute(
+        &self,
+        key: &ContextKey,
+        dispatcher: &dyn Dispatcher,
+    ) -> Result<i64, ErrorKind> {
         let code = self.loader.load(&key.value())?;
 
         let register = Register::root_initial(&self.initial);
@@ -153,7 +247,7 @@ where
         let context = Context::new(
             key.clone(),
             target,
-            self.dispatcher,
+            dispatcher,
             &fr,
             self.loader,
             &functions,
@@ -162,7 +256,7 @@ where
         while let Some(stmt) = results.next().await {
             match stmt {
                 Ok(NaslValue::Exit(i)) => {
-                    self.dispatcher.on_exit(context.key())?;
+                    dispatcher.on_exit(context.key())?;
                     return Ok(i);
                 }
                 Ok(_) => {}
@@ -210,9 +304,13 @@ where
                     // within nasl scripts usually don't entail them.
                     filename = filename[2..].to_string();
                 }
+                let hash = k.get_hashsum();
                 let k = ContextKey::FileName(filename.clone());
-                self.single(&k)
-                    .await
+                let result = match self.cache {
+                    Some(cache) => self.single_cached(&k, &hash, cache).await,
+                    None => self.single(&k).await,
+                };
+                result
                     .map(|_| k.value())
                     .map_err(|kind| Error {
                         kind,
@@ -0,0 +1,376 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Validates the metadata of a script before it is stored.
+//!
+//! The fields dispatched by the description run are held back until the script exits. Then the
+//! assembled metadata is checked and only dispatched when it is valid, so that malformed OIDs,
+//! tags or references never reach the storage and the APIs serving it.
+
+use std::{collections::HashSet, fmt::Display, str::FromStr, sync::Mutex};
+
+use lazy_regex::{lazy_regex, Lazy, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{
+    item::{Nvt, TagKey, TagValue},
+    ContextKey, Dispatcher, Field, StorageError,
+};
+
+static OID: Lazy<Regex> = lazy_regex!(r"^[0-9]+(\.[0-9]+)+$");
+static CVE: Lazy<Regex> = lazy_regex!(r"^CVE-[0-9]{4}-[0-9]{4,}$");
+static CVSS2: Lazy<Regex> =
+    lazy_regex!(r"^AV:[LAN]/AC:[HML]/Au:[MSN]/C:[NPC]/I:[NPC]/A:[NPC](/[A-Za-z]+:[A-Za-z]+)*$");
+static CVSS3: Lazy<Regex> = lazy_regex!(
+    r"^CVSS:3\.[01]/AV:[NALP]/AC:[LH]/PR:[NLH]/UI:[NR]/S:[UC]/C:[HLN]/I:[HLN]/A:[HLN](/[A-Za-z]+:[A-Za-z]+)*$"
+);
+static CVSS4: Lazy<Regex> = lazy_regex!(
+    r"^CVSS:4\.0/AV:[NALP]/AC:[LH]/AT:[NP]/PR:[NLH]/UI:[NPA]/VC:[HLN]/VI:[HLN]/VA:[HLN]/SC:[HLN]/SI:[HLN]/SA:[HLN](/[A-Za-z]+:[A-Za-z]+)*$"
+);
+
+/// How scripts with invalid metadata are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// The metadata is stored as is
+    #[default]
+    Off,
+    /// The update fails with the violations of the first invalid script
+    Reject,
+    /// Invalid scripts are skipped and reported, the update continues
+    Quarantine,
+}
+
+impl FromStr for ValidationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "reject" => Ok(Self::Reject),
+            "quarantine" => Ok(Self::Quarantine),
+            _ => Err(format!(
+                "unknown validation mode {s}, expected off, reject or quarantine"
+            )),
+        }
+    }
+}
+
+impl Display for ValidationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Reject => write!(f, "reject"),
+            Self::Quarantine => write!(f, "quarantine"),
+        }
+    }
+}
+
+/// A field of the metadata that is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Name of the field, e.g. oid or the key of a tag
+    pub field: String,
+    /// Why the value is rejected
+    pub reason: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+/// A skipped script and the violations of its metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidVt {
+    pub filename: String,
+    pub oid: String,
+    pub violations: Vec<Violation>,
+}
+
+/// Validation of the metadata of a feed update
+#[derive(Debug, Default)]
+pub struct Validation {
+    mode: ValidationMode,
+    quarantined: Mutex<Vec<InvalidVt>>,
+}
+
+fn is_cvss_vector(value: &str) -> bool {
+    CVSS2.is_match(value) || CVSS3.is_match(value) || CVSS4.is_match(value)
+}
+
+/// Returns the violations of the metadata, empty when it is valid.
+pub fn validate(nvt: &Nvt) -> Vec<Violation> {
+    let mut result = vec![];
+    let mut violation = |field: &str, reason: String| {
+        result.push(Violation {
+            field: field.to_string(),
+            reason,
+        })
+    };
+    if !OID.is_match(&nvt.oid) {
+        violation("oid", format!("{:?} is not a dotted numeric OID", nvt.oid));
+    }
+    if nvt.name.trim().is_empty() {
+        violation("name", "is empty".to_string());
+    }
+    if nvt.family.trim().is_empty() {
+        violation("family", "is empty".to_string());
+    }
+    for (key, value) in &nvt.tag {
+        let reason = match (key, value) {
+            (
+                TagKey::CreationDate | TagKey::LastModification | TagKey::SeverityDate,
+                TagValue::Number(x),
+            ) if *x > 0 => None,
+            (TagKey::CreationDate | TagKey::LastModification | TagKey::SeverityDate, x) => {
+                Some(format!("{x:?} is not a valid date"))
+            }
+            (TagKey::Qod, TagValue::Number(x)) if (0..=100).contains(x) => None,
+            (TagKey::Qod, x) => Some(format!("{x:?} is not a percentage")),
+            (TagKey::Deprecated, TagValue::Boolean(_)) => None,
+            (TagKey::Deprecated, x) => Some(format!("{x:?} is not a boolean")),
+            (TagKey::CvssBaseVector | TagKey::SeverityVector, TagValue::String(x))
+                if is_cvss_vector(x) =>
+            {
+                None
+            }
+            (TagKey::CvssBaseVector | TagKey::SeverityVector, x) => {
+                Some(format!("{x:?} is not a CVSS vector"))
+            }
+            (_, TagValue::String(_)) => None,
+            (_, x) => Some(format!("{x:?} is not a string")),
+        };
+        if let Some(reason) = reason {
+            violation(key.as_ref(), reason);
+        }
+    }
+    if let (Some(TagValue::Number(created)), Some(TagValue::Number(modified))) = (
+        nvt.tag.get(&TagKey::CreationDate),
+        nvt.tag.get(&TagKey::LastModification),
+    ) {
+        if modified < created {
+            violation(
+                TagKey::LastModification.as_ref(),
+                "is before the creation_date".to_string(),
+            );
+        }
+    }
+    for reference in &nvt.references {
+        if reference.class == "cve" && !CVE.is_match(&reference.id) {
+            violation("cve", format!("{:?} is not a CVE id", reference.id));
+        }
+    }
+    let mut ids = HashSet::new();
+    for id in nvt.preferences.iter().filter_map(|x| x.id) {
+        if !ids.insert(id) {
+            violation("preference", format!("id {id} is used more than once"));
+        }
+    }
+    result
+}
+
+impl Validation {
+    pub fn new(mode: ValidationMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> ValidationMode {
+        self.mode
+    }
+
+    /// Returns the scripts skipped because of invalid metadata.
+    pub fn quarantined(&self) -> Vec<InvalidVt> {
+        self.quarantined.lock().unwrap().clone()
+    }
+
+    /// Checks the metadata of a script.
+    ///
+    /// Returns true when it may be stored. When the metadata is rejected its violations are
+    /// returned, quarantined scripts are added to the report instead.
+    pub fn check(&self, key: &ContextKey, nvt: &Nvt) -> Result<bool, Vec<Violation>> {
+        if self.mode == ValidationMode::Off {
+            return Ok(true);
+        }
+        let violations = validate(nvt);
+        if violations.is_empty() {
+            return Ok(true);
+        }
+        match self.mode {
+            ValidationMode::Reject => Err(violations),
+            _ => {
+                tracing::warn!(
+                    key = key.value(),
+                    oid = nvt.oid,
+                    violations = violations
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    "quarantined script with invalid metadata"
+                );
+                self.quarantined.lock().unwrap().push(InvalidVt {
+                    filename: key.value(),
+                    oid: nvt.oid.clone(),
+                    violations,
+                });
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Holds back everything dispatched by a script until its metadata is validated
+#[derive(Default)]
+pub(super) struct Buffer {
+    fields: Mutex<Vec<(ContextKey, Field, bool)>>,
+    nvt: Mutex<Nvt>,
+}
+
+impl Buffer {
+    pub fn nvt(&self) -> Nvt {
+        self.nvt.lock().unwrap().clone()
+    }
+
+    /// Dispatches the held back fields in their original order and signals the exit.
+    pub fn forward(
+        self,
+        dispatcher: &dyn Dispatcher,
+        retries: usize,
+        key: &ContextKey,
+    ) -> Result<(), StorageError> {
+        for (key, field, replace) in self.fields.into_inner().unwrap() {
+            match replace {
+                true => dispatcher.dispatch_replace(&key, field)?,
+                false => dispatcher.retry_dispatch(retries, &key, field)?,
+            }
+        }
+        dispatcher.on_exit(key)
+    }
+
+    fn hold(&self, key: &ContextKey, scope: Field, replace: bool) {
+        if let Field::NVT(field) = &scope {
+            // the feed version is not part of a script
+            let _ = self.nvt.lock().unwrap().set_from_field(field.clone());
+        }
+        self.fields
+            .lock()
+            .unwrap()
+            .push((key.clone(), scope, replace));
+    }
+}
+
+impl Dispatcher for Buffer {
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.hold(key, scope, false);
+        Ok(())
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.hold(key, scope, true);
+        Ok(())
+    }
+
+    fn on_exit(&self, _: &ContextKey) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::item::{NvtPreference, NvtRef, PreferenceType};
+
+    use super::*;
+
+    fn nvt() -> Nvt {
+        Nvt {
+            oid: "1.3.6.1.4.1.25623.1.0.100001".to_string(),
+            name: "Test".to_string(),
+            family: "General".to_string(),
+            tag: [
+                (TagKey::CreationDate, TagValue::Number(1366091481)),
+                (TagKey::LastModification, TagValue::Number(1366091500)),
+                (
+                    TagKey::CvssBaseVector,
+                    TagValue::String("AV:N/AC:L/Au:N/C:P/I:P/A:P".to_string()),
+                ),
+                (
+                    TagKey::SeverityVector,
+                    TagValue::String("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H".to_string()),
+                ),
+                (TagKey::Qod, TagValue::Number(80)),
+            ]
+            .into(),
+            references: vec![("cve", "CVE-2024-12345").into()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(validate(&nvt()), vec![]);
+        assert!(is_cvss_vector(
+            "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N"
+        ));
+    }
+
+    #[test]
+    fn violations() {
+        let mut nvt = nvt();
+        nvt.oid = "1.3.6.1.4.1.25623.1.0.x".to_string();
+        nvt.tag.insert(
+            TagKey::SeverityVector,
+            TagValue::String("CVSS:3.1/AV:X/AC:L".to_string()),
+        );
+        nvt.tag
+            .insert(TagKey::LastModification, TagValue::Number(1000));
+        nvt.tag.insert(TagKey::Qod, TagValue::Number(101));
+        nvt.references.push(NvtRef::from(("cve", "CVE-24-1")));
+        let preference = NvtPreference {
+            id: Some(1),
+            class: PreferenceType::Entry,
+            name: "a".to_string(),
+            default: String::new(),
+        };
+        nvt.preferences = vec![preference.clone(), preference];
+        let fields: Vec<String> = validate(&nvt).into_iter().map(|x| x.field).collect();
+        assert_eq!(
+            fields,
+            [
+                "oid",
+                "qod",
+                "severity_vector",
+                "last_modification",
+                "cve",
+                "preference"
+            ]
+        );
+    }
+
+    #[test]
+    fn quarantine() {
+        let key = ContextKey::FileName("test.nasl".to_string());
+        let mut invalid = nvt();
+        invalid.name = String::new();
+        let validation = Validation::new(ValidationMode::Quarantine);
+        assert_eq!(validation.check(&key, &nvt()), Ok(true));
+        assert_eq!(validation.check(&key, &invalid), Ok(false));
+        assert_eq!(validation.quarantined()[0].filename, "test.nasl");
+        assert_eq!(
+            Validation::new(ValidationMode::Reject).check(&key, &invalid),
+            Err(vec![Violation {
+                field: "name".to_string(),
+                reason: "is empty".to_string()
+            }])
+        );
+        assert_eq!(
+            Validation::new(ValidationMode::Off).check(&key, &invalid),
+            Ok(true)
+        );
+    }
+}
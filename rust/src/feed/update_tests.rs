@@ -4,9 +4,10 @@
 
 use std::{env, path::PathBuf};
 
-use crate::storage::DefaultDispatcher;
+use crate::storage::infisto::IndexedFileStorer;
+use crate::storage::{item::Nvt, DefaultDispatcher, Retriever};
 use crate::{
-    feed::{HashSumNameLoader, Update},
+    feed::{DescriptionCache, HashSumNameLoader, Update},
     nasl::syntax::FSPluginLoader,
 };
 use futures::StreamExt;
@@ -53,3 +54,54 @@ async fn verify_feed() {
         &["test.nasl".to_owned(), "plugin_feed_info.inc".to_owned()]
     );
 }
+
+const TEST_NASL_HASH: &str = "d9414deb6d3f9c9f07b09cb9496ff2b6a2ae1c7f805b4a7edd31faefd9a7c8a8";
+
+async fn update_with_cache(cache: &DescriptionCache) -> Vec<Nvt> {
+    let loader = loader();
+    let storage = DefaultDispatcher::new();
+    let verifier = HashSumNameLoader::sha256(&loader).expect("sha256sums should be available");
+    Update::init("1", 1, &loader, &storage, verifier)
+        .with_cache(cache)
+        .perform_update()
+        .await
+        .unwrap();
+    storage.vts().unwrap().collect()
+}
+
+#[tokio::test]
+async fn description_cache() {
+    let cache = DescriptionCache::new("1");
+    let uncached = update_with_cache(&cache).await;
+    assert_eq!(uncached.len(), 1);
+    assert_eq!(cache.len(), 1);
+
+    let path = "/tmp/openvasd/unittest/description_cache";
+    let mut ifs = IndexedFileStorer::init(path).unwrap();
+    cache.store(&mut ifs).unwrap();
+    let cache = DescriptionCache::load("1", &ifs);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(update_with_cache(&cache).await, uncached);
+    // a different OPENVAS_VERSION must not use the cached results
+    assert_eq!(
+        DescriptionCache::load("2", &ifs).get(TEST_NASL_HASH, "test.nasl"),
+        None
+    );
+}
+
+#[tokio::test]
+async fn description_cache_skips_execution() {
+    let cache = DescriptionCache::new("1");
+    cache.insert(
+        TEST_NASL_HASH,
+        Nvt {
+            oid: "1.2.3".to_string(),
+            name: "cached".to_string(),
+            ..Default::default()
+        },
+    );
+    let vts = update_with_cache(&cache).await;
+    assert_eq!(vts.len(), 1);
+    assert_eq!(vts[0].name, "cached");
+    assert_eq!(vts[0].filename, "test.nasl");
+}
@@ -1,4 +1,5 @@
 pub mod feed;
+pub mod logging;
 pub mod models;
 pub mod nasl;
 pub mod notus;
@@ -0,0 +1,463 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Sends log messages to a syslog server as described in RFC 5424.
+//!
+//! Messages are sent via UDP (RFC 5426), TCP (RFC 6587) or TLS (RFC 5425). For TCP and TLS
+//! octet counting is used for framing. The messages are handed over to a background thread, so
+//! that a slow or unreachable server does not block the caller. When the queue is full,
+//! messages are dropped.
+
+use std::{
+    fmt as std_fmt, io,
+    io::Write,
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde::{Deserialize, Serialize};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::{
+    fmt::{
+        self,
+        format::{DefaultFields, Writer},
+        FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter,
+    },
+    registry::LookupSpan,
+};
+
+/// Number of messages that are queued while the server is unreachable
+const QUEUE_SIZE: usize = 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transport protocol used to reach the syslog server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Udp,
+    Tcp,
+    Tls,
+}
+
+/// Syslog facility as defined in RFC 5424
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facility {
+    User,
+    #[default]
+    Daemon,
+    Auth,
+    Syslog,
+    Authpriv,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    fn code(self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Auth => 4,
+            Facility::Syslog => 5,
+            Facility::Authpriv => 10,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+fn default_app_name() -> String {
+    "openvasd".to_string()
+}
+
+/// Configuration of the syslog sink
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Syslog {
+    /// Address of the server, e.g. "localhost:514"
+    pub address: String,
+    #[serde(default)]
+    pub transport: Transport,
+    #[serde(default)]
+    pub facility: Facility,
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+    /// PEM file containing the certificates that are trusted to verify the server when using
+    /// TLS
+    #[serde(default)]
+    pub ca_file: Option<PathBuf>,
+}
+
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// Returns the hostname of this system or the nil value of RFC 5424.
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for the given length
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    let end = buf.iter().position(|x| *x == 0).unwrap_or(buf.len());
+    match std::str::from_utf8(&buf[..end]) {
+        Ok(name) if result == 0 && !name.is_empty() => name.to_string(),
+        _ => "-".to_string(),
+    }
+}
+
+/// Formats events as RFC 5424 messages without structured data.
+#[derive(Debug)]
+pub struct Rfc5424 {
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+}
+
+impl Rfc5424 {
+    pub fn new(facility: Facility, app_name: &str) -> Self {
+        Self {
+            facility,
+            hostname: hostname(),
+            app_name: app_name.to_string(),
+            pid: std::process::id(),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for Rfc5424
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std_fmt::Result {
+        let metadata = event.metadata();
+        let priority = self.facility.code() * 8 + severity(metadata.level());
+        write!(
+            writer,
+            "<{priority}>1 {} {} {} {} - - {}: ",
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            self.hostname,
+            self.app_name,
+            self.pid,
+            metadata.target(),
+        )?;
+        ctx.format_fields(writer.by_ref(), event)?;
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                    if !fields.is_empty() {
+                        write!(writer, " {fields}")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Connection {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        let stream: &mut dyn Write = match self {
+            Connection::Udp(socket) => return socket.send(message).map(|_| ()),
+            Connection::Tcp(stream) => stream,
+            Connection::Tls(stream) => stream,
+        };
+        write!(stream, "{} ", message.len())?;
+        stream.write_all(message)?;
+        stream.flush()
+    }
+}
+
+struct Connector {
+    address: String,
+    transport: Transport,
+    tls: Option<Arc<ClientConfig>>,
+}
+
+impl Connector {
+    fn new(config: &Syslog) -> io::Result<Self> {
+        let tls = match config.transport {
+            Transport::Tls => Some(Arc::new(tls_config(config.ca_file.as_ref())?)),
+            _ => None,
+        };
+        Ok(Self {
+            address: config.address.clone(),
+            transport: config.transport,
+            tls,
+        })
+    }
+
+    fn tcp(&self) -> io::Result<TcpStream> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "address not resolvable");
+        for address in self.address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        match (&self.transport, &self.tls) {
+            (Transport::Udp, _) => {
+                let address = self
+                    .address
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+                let local = if address.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(address)?;
+                Ok(Connection::Udp(socket))
+            }
+            (Transport::Tls, Some(config)) => {
+                let host = self
+                    .address
+                    .rsplit_once(':')
+                    .map_or(self.address.as_str(), |(host, _)| host)
+                    .trim_start_matches('[')
+                    .trim_end_matches(']');
+                let name = ServerName::try_from(host.to_string())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                let connection =
+                    ClientConnection::new(config.clone(), name).map_err(io::Error::other)?;
+                Ok(Connection::Tls(Box::new(StreamOwned::new(
+                    connection,
+                    self.tcp()?,
+                ))))
+            }
+            _ => Ok(Connection::Tcp(self.tcp()?)),
+        }
+    }
+
+    /// Sends the queued messages and reconnects once per message on failure.
+    fn run(self, receiver: Receiver<Vec<u8>>) {
+        let mut connection = None;
+        for message in receiver {
+            for _ in 0..2 {
+                if connection.is_none() {
+                    match self.connect() {
+                        Ok(x) => connection = Some(x),
+                        Err(e) => {
+                            // logging the error would end up in this sink again
+                            eprintln!("unable to connect to syslog server {}: {e}", self.address);
+                            break;
+                        }
+                    }
+                }
+                if let Some(conn) = connection.as_mut() {
+                    match conn.send(&message) {
+                        Ok(()) => break,
+                        Err(_) => connection = None,
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn tls_config(ca_file: Option<&PathBuf>) -> io::Result<ClientConfig> {
+    let ca_file = ca_file.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "log.syslog.ca_file is required for TLS",
+        )
+    })?;
+    let mut reader = io::BufReader::new(std::fs::File::open(ca_file)?);
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut reader) {
+        roots
+            .add(cert?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Hands over formatted messages to the background thread sending them to the server.
+#[derive(Debug, Clone)]
+pub struct SyslogWriter {
+    sender: SyncSender<Vec<u8>>,
+}
+
+impl SyslogWriter {
+    /// Starts the background thread sending the messages to the configured server.
+    pub fn new(config: &Syslog) -> io::Result<Self> {
+        let connector = Connector::new(config)?;
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        std::thread::Builder::new()
+            .name("syslog".to_string())
+            .spawn(move || connector.run(receiver))?;
+        Ok(Self { sender })
+    }
+}
+
+/// A single message that is sent when dropped
+pub struct SyslogMessage {
+    buffer: Vec<u8>,
+    sender: SyncSender<Vec<u8>>,
+}
+
+impl Write for SyslogMessage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SyslogMessage {
+    fn drop(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let message = std::mem::take(&mut self.buffer);
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(message) {
+            eprintln!("syslog queue is full, dropping message");
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for SyslogWriter {
+    type Writer = SyslogMessage;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SyslogMessage {
+            buffer: Vec::new(),
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+/// Creates a layer sending the log messages to the configured syslog server.
+pub fn layer<S>(config: &Syslog) -> io::Result<fmt::Layer<S, DefaultFields, Rfc5424, SyslogWriter>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    Ok(fmt::layer()
+        .with_ansi(false)
+        .event_format(Rfc5424::new(config.facility, &config.app_name))
+        .with_writer(SyslogWriter::new(config)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::{TcpListener, UdpSocket},
+        time::Duration,
+    };
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::{Facility, Syslog, Transport};
+
+    fn subscriber(config: &Syslog) -> impl tracing::Subscriber {
+        tracing_subscriber::registry().with(super::layer(config).unwrap())
+    }
+
+    #[test]
+    fn udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let config = Syslog {
+            address: server.local_addr().unwrap().to_string(),
+            transport: Transport::Udp,
+            facility: Facility::Local0,
+            app_name: "test".to_string(),
+            ca_file: None,
+        };
+        tracing::subscriber::with_default(subscriber(&config), || {
+            let _span = tracing::info_span!("scan", scan_id = "s1").entered();
+            tracing::warn!(port = 22, "closed");
+        });
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let message = String::from_utf8_lossy(&buf[..len]);
+        // local0 * 8 + warning
+        assert!(message.starts_with("<132>1 "), "{message}");
+        assert!(message.contains(" test "), "{message}");
+        assert!(message.contains("closed port=22"), "{message}");
+        assert!(message.ends_with("scan_id=\"s1\""), "{message}");
+    }
+
+    #[test]
+    fn tcp_octet_counting() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = Syslog {
+            address: server.local_addr().unwrap().to_string(),
+            transport: Transport::Tcp,
+            facility: Facility::Daemon,
+            app_name: "test".to_string(),
+            ca_file: None,
+        };
+        tracing::subscriber::with_default(subscriber(&config), || {
+            tracing::info!("first");
+            tracing::error!("second");
+        });
+        let (mut stream, _) = server.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut received = String::new();
+        let mut buf = [0; 1024];
+        while received.matches("<").count() < 2 || !received.ends_with("second") {
+            let len = stream.read(&mut buf).unwrap();
+            assert_ne!(len, 0, "connection closed after {received}");
+            received.push_str(&String::from_utf8_lossy(&buf[..len]));
+        }
+        let (len, rest) = received.split_once(' ').unwrap();
+        let len: usize = len.parse().unwrap();
+        assert!(rest[..len].starts_with("<30>1 "));
+        assert!(rest[..len].ends_with("first"));
+        let (_, second) = rest[len..].split_once(' ').unwrap();
+        assert!(second.starts_with("<27>1 "));
+    }
+}
@@ -0,0 +1,184 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::fmt::Display;
+
+use super::result::{Detail, Result, ResultType, Source};
+
+/// Protocol used for credentialed access to a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AuthProtocol {
+    Ssh,
+    Smb,
+    Snmp,
+}
+
+impl AuthProtocol {
+    /// All protocols whose authentication is reported
+    pub const ALL: [Self; 3] = [Self::Ssh, Self::Smb, Self::Snmp];
+
+    /// Returns the KB key set by the authorization VTs for the state, e.g. login/SSH/success.
+    pub fn kb_key(&self, state: &str) -> String {
+        format!("login/{self}/{state}")
+    }
+}
+
+impl Display for AuthProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ssh => write!(f, "SSH"),
+            Self::Smb => write!(f, "SMB"),
+            Self::Snmp => write!(f, "SNMP"),
+        }
+    }
+}
+
+/// Outcome of the login into a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AuthStatus {
+    /// Logged in with all privileges required by the local security checks
+    Success,
+    /// Logged in, but some checks could not run, e.g. because sudo was rejected
+    Partial,
+    /// The credentials were rejected or the service was not reachable
+    Failed,
+}
+
+impl Display for AuthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Success => write!(f, "Success"),
+            Self::Partial => write!(f, "Partial"),
+            Self::Failed => write!(f, "Failure"),
+        }
+    }
+}
+
+/// Health of the credentialed access to a host via a protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Authentication {
+    pub protocol: AuthProtocol,
+    pub status: AuthStatus,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Why the login failed or only partially succeeded, as given by the VT
+    pub reason: Option<String>,
+}
+
+impl Authentication {
+    /// Determines the authentication status from the KB items login/<protocol>/success,
+    /// login/<protocol>/partial and login/<protocol>/failed set by the authorization VTs.
+    ///
+    /// The value of the partial or failed item is used as reason. Returns None when no login
+    /// was attempted.
+    pub fn from_kb<F>(protocol: AuthProtocol, kb: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let reason = |state| {
+            kb(&protocol.kb_key(state)).filter(|x| !x.is_empty() && x != "1" && x != "TRUE")
+        };
+        let (status, reason) = match (
+            kb(&protocol.kb_key("success")).is_some(),
+            kb(&protocol.kb_key("partial")).is_some(),
+            kb(&protocol.kb_key("failed")).is_some(),
+        ) {
+            (_, true, _) => (AuthStatus::Partial, reason("partial")),
+            (true, false, _) => (AuthStatus::Success, None),
+            (false, false, true) => (AuthStatus::Failed, reason("failed")),
+            (false, false, false) => return None,
+        };
+        Some(Self {
+            protocol,
+            status,
+            reason,
+        })
+    }
+
+    /// Creates the result reporting the authentication status of the host.
+    ///
+    /// The detail is named like the host details of authorization VTs, e.g. Auth-SSH-Success.
+    pub fn to_result(&self, host: &str) -> Result {
+        let mut message = match self.status {
+            AuthStatus::Success => format!("{} login succeeded.", self.protocol),
+            AuthStatus::Partial => format!("{} login partially succeeded.", self.protocol),
+            AuthStatus::Failed => format!("{} login failed.", self.protocol),
+        };
+        if let Some(reason) = &self.reason {
+            message.push_str(&format!(" Reason: {reason}"));
+        }
+        Result {
+            r_type: ResultType::Authentication,
+            ip_address: Some(host.to_string()),
+            message: Some(message),
+            detail: Some(Detail {
+                name: format!("Auth-{}-{}", self.protocol, self.status),
+                value: self.reason.clone().unwrap_or_default(),
+                source: Source {
+                    s_type: "scanner".to_string(),
+                    name: self.protocol.kb_key(match self.status {
+                        AuthStatus::Success => "success",
+                        AuthStatus::Partial => "partial",
+                        AuthStatus::Failed => "failed",
+                    }),
+                    description: "Authentication status".to_string(),
+                },
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{AuthProtocol, AuthStatus, Authentication};
+
+    #[test]
+    fn from_kb() {
+        let kb: HashMap<&str, &str> = [
+            ("login/SSH/success", "1"),
+            ("login/SSH/partial", "sudo rejected the password"),
+            ("login/SMB/success", "1"),
+            ("login/SMB/failed", "1"),
+            ("login/SNMP/failed", "1"),
+        ]
+        .into_iter()
+        .collect();
+        let kb = |key: &str| kb.get(key).map(|x| x.to_string());
+        let ssh = Authentication::from_kb(AuthProtocol::Ssh, kb).unwrap();
+        assert_eq!(ssh.status, AuthStatus::Partial);
+        assert_eq!(ssh.reason.as_deref(), Some("sudo rejected the password"));
+        // a retry with other credentials succeeded
+        let smb = Authentication::from_kb(AuthProtocol::Smb, kb).unwrap();
+        assert_eq!((smb.status, smb.reason), (AuthStatus::Success, None));
+        let snmp = Authentication::from_kb(AuthProtocol::Snmp, kb).unwrap();
+        assert_eq!((snmp.status, snmp.reason), (AuthStatus::Failed, None));
+        assert_eq!(Authentication::from_kb(AuthProtocol::Ssh, |_| None), None);
+
+        let result = ssh.to_result("127.0.0.1");
+        assert_eq!(result.detail.unwrap().name, "Auth-SSH-Partial");
+        assert_eq!(
+            result.message.as_deref(),
+            Some("SSH login partially succeeded. Reason: sudo rejected the password")
+        );
+    }
+}
@@ -36,7 +36,9 @@ impl Credential {
         match &self.credential_type {
             CredentialType::UP { password, .. } => password,
             CredentialType::USK { password, .. } => password,
+            CredentialType::Agent { .. } => "",
             CredentialType::SNMP { password, .. } => password,
+            CredentialType::Certificate { password, .. } => password,
         }
     }
 }
@@ -75,7 +77,8 @@ pub struct PrivilegeInformation {
 )]
 pub enum Service {
     #[cfg_attr(feature = "serde_support", serde(rename = "ssh"))]
-    /// SSH, supports [UP](CredentialType::UP) and [USK](CredentialType::USK) as credential types
+    /// SSH, supports [UP](CredentialType::UP), [USK](CredentialType::USK) and
+    /// [Agent](CredentialType::Agent) as credential types
     SSH,
     #[cfg_attr(feature = "serde_support", serde(rename = "smb"))]
     /// SMB, supports [UP](CredentialType::UP)
@@ -86,6 +89,9 @@ pub enum Service {
     #[cfg_attr(feature = "serde_support", serde(rename = "snmp"))]
     /// SNMP, supports [SNMP](CredentialType::SNMP)
     SNMP,
+    #[cfg_attr(feature = "serde_support", serde(rename = "tls"))]
+    /// TLS client authentication, supports [Certificate](CredentialType::Certificate)
+    TLS,
 }
 
 impl AsRef<str> for Service {
@@ -95,6 +101,7 @@ impl AsRef<str> for Service {
             Service::SMB => "smb",
             Service::ESXi => "esxi",
             Service::SNMP => "snmp",
+            Service::TLS => "tls",
         }
     }
 }
@@ -137,6 +144,24 @@ pub enum CredentialType {
         )]
         privilege: Option<PrivilegeInformation>,
     },
+    #[cfg_attr(feature = "serde_support", serde(rename = "agent"))]
+    /// User credentials authenticated by the keys of a SSH agent.
+    Agent {
+        /// The username for authentication.
+        username: String,
+        /// The path of the agent socket. If missing SSH_AUTH_SOCK of the scanner is used.
+        #[cfg_attr(
+            feature = "serde_support",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        socket: Option<String>,
+        /// privilege credential only use for SSH service
+        #[cfg_attr(
+            feature = "serde_support",
+            serde(default, flatten, skip_serializing_if = "Option::is_none")
+        )]
+        privilege: Option<PrivilegeInformation>,
+    },
     #[cfg_attr(feature = "serde_support", serde(rename = "snmp"))]
     /// SNMP credentials.
     SNMP {
@@ -153,6 +178,18 @@ pub enum CredentialType {
         /// The SNMP privacy algorithm.
         privacy_algorithm: String,
     },
+    #[cfg_attr(feature = "serde_support", serde(rename = "cert"))]
+    /// Client certificate credentials.
+    Certificate {
+        /// The PEM encoded certificate, optionally followed by its chain.
+        certificate: String,
+        #[cfg_attr(feature = "serde_support", serde(rename = "private"))]
+        /// The PEM encoded private key of the certificate.
+        private_key: String,
+        /// The passphrase of an encrypted private key.
+        #[cfg_attr(feature = "serde_support", serde(default))]
+        password: String,
+    },
 }
 
 impl CredentialType {
@@ -194,6 +231,21 @@ impl CredentialType {
                     None => None,
                 },
             },
+            CredentialType::Agent {
+                username,
+                socket,
+                privilege,
+            } => CredentialType::Agent {
+                username,
+                socket,
+                privilege: match privilege {
+                    Some(p) => Some(PrivilegeInformation {
+                        username: p.username,
+                        password: f(p.password)?,
+                    }),
+                    None => None,
+                },
+            },
             CredentialType::SNMP {
                 username,
                 password,
@@ -209,6 +261,15 @@ impl CredentialType {
                 privacy_password: f(privacy_password)?,
                 privacy_algorithm,
             },
+            CredentialType::Certificate {
+                certificate,
+                private_key,
+                password,
+            } => CredentialType::Certificate {
+                certificate,
+                private_key: f(private_key)?,
+                password: f(password)?,
+            },
         })
     }
 }
@@ -218,7 +279,9 @@ impl AsRef<str> for CredentialType {
         match self {
             CredentialType::UP { .. } => "up",
             CredentialType::USK { .. } => "usk",
+            CredentialType::Agent { .. } => "agent",
             CredentialType::SNMP { .. } => "snmp",
+            CredentialType::Certificate { .. } => "cert",
         }
     }
 }
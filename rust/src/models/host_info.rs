@@ -4,7 +4,7 @@
 
 use std::collections::HashMap;
 
-use crate::models::Host;
+use crate::models::{Authentication, Host};
 
 #[derive(Default)]
 pub struct HostInfoBuilder {
@@ -28,6 +28,7 @@ impl HostInfoBuilder {
             finished: self.finished,
             scanning: self.scanning,
             remaining_vts_per_host: HashMap::new(),
+            authentication: HashMap::new(),
         }
     }
 }
@@ -55,6 +56,12 @@ pub struct HostInfo {
     // Hosts that are currently being scanned. The second entry is the number of
     // remaining VTs for this host.
     remaining_vts_per_host: HashMap<String, usize>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "HashMap::is_empty", default)
+    )]
+    /// Outcome of the logins into the finished hosts, hosts without a login attempt are missing
+    authentication: HashMap<String, Vec<Authentication>>,
 }
 
 impl HostInfo {
@@ -67,15 +74,28 @@ impl HostInfo {
         }
     }
 
-    pub fn register_finished_script(&mut self, target: &Host) {
+    /// Counts a finished script of the target, returns true when it was the last one.
+    pub fn register_finished_script(&mut self, target: &Host) -> bool {
         if let Some(num_vts) = self.remaining_vts_per_host.get_mut(target) {
             *num_vts -= 1;
             if *num_vts == 0 {
                 self.finished += 1;
                 self.queued -= 1;
                 self.remaining_vts_per_host.remove(target);
+                return true;
             }
         }
+        false
+    }
+
+    pub fn register_authentication(&mut self, target: &Host, authentication: Vec<Authentication>) {
+        if !authentication.is_empty() {
+            self.authentication.insert(target.clone(), authentication);
+        }
+    }
+
+    pub fn authentication(&self) -> &HashMap<String, Vec<Authentication>> {
+        &self.authentication
     }
 
     pub fn finish(&mut self) {
@@ -91,6 +111,25 @@ impl HostInfo {
         self.finished
     }
 
+    /// Adds the hosts of a scan of another target, e.g. of another part of a distributed scan.
+    pub fn merge(mut self, other: &HostInfo) -> Self {
+        self.all += other.all;
+        self.excluded += other.excluded;
+        self.dead += other.dead;
+        self.alive += other.alive;
+        self.queued += other.queued;
+        self.finished += other.finished;
+        if let Some(scanning) = &other.scanning {
+            self.scanning
+                .get_or_insert_with(HashMap::new)
+                .extend(scanning.clone());
+        }
+        self.remaining_vts_per_host
+            .extend(other.remaining_vts_per_host.clone());
+        self.authentication.extend(other.authentication.clone());
+        self
+    }
+
     pub fn update_with(mut self, other: &HostInfo) -> Self {
         // total hosts value is sent once and only once must be updated
         if other.all != 0 {
@@ -119,6 +158,7 @@ impl HostInfo {
             }
         }
         self.scanning = Some(hs);
+        self.authentication.extend(other.authentication.clone());
         self
     }
 }
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 mod advisories;
+mod authentication;
 mod credential;
 mod host_info;
 mod parameter;
@@ -14,11 +15,15 @@ mod scan;
 mod scan_action;
 pub mod scanner;
 mod scanner_preference;
+mod schedule;
+mod severity;
 mod status;
 mod target;
 mod vt;
+mod watch;
 
 pub use advisories::*;
+pub use authentication::*;
 pub use credential::*;
 pub use host_info::*;
 pub use parameter::*;
@@ -28,9 +33,12 @@ pub use result::*;
 pub use scan::*;
 pub use scan_action::*;
 pub use scanner_preference::*;
+pub use schedule::*;
+pub use severity::*;
 pub use status::*;
 pub use target::*;
 pub use vt::*;
+pub use watch::*;
 
 #[cfg(test)]
 mod tests {
@@ -100,6 +108,14 @@ mod tests {
           "private": "ssh-key..."
         }
       },
+      {
+        "service": "ssh",
+        "port": 2222,
+        "agent": {
+          "username": "user",
+          "socket": "/run/user/1000/ssh-agent.socket"
+        }
+      },
       {
         "service": "smb",
         "up": {
@@ -4,7 +4,7 @@
 
 use std::collections::HashMap;
 
-use crate::models::Specifier;
+use crate::models::{SeverityClass, Specifier};
 
 use super::port::Protocol;
 
@@ -61,8 +61,69 @@ pub struct Result {
         feature = "serde_support",
         serde(skip_serializing_if = "Option::is_none", default)
     )]
-    /// Details are only set on status and can be ignored
+    /// Details are only set on host details and authentication results
     pub detail: Option<Detail>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Runtime statistics of the script, only set when enabled in the scan preferences
+    pub stats: Option<ScriptStats>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Flows of network data into dangerous builtins observed before the result was created,
+    /// only set when taint tracking is enabled in the scan preferences
+    pub taint_flows: Option<Vec<TaintFlow>>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Labels of the host as given in the target of the scan
+    pub labels: Option<HashMap<String, String>>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Severity class of a finding, set when the result is stored
+    pub severity: Option<SeverityClass>,
+}
+
+/// Runtime statistics of the script that created a result
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ScriptStats {
+    /// Milliseconds since the start of the script
+    pub duration_ms: u64,
+    /// Number of bytes sent on sockets
+    pub bytes_sent: u64,
+    /// Number of bytes received on sockets
+    pub bytes_received: u64,
+    /// Number of retransmitted packets
+    pub retransmissions: u64,
+}
+
+/// Data received by a source builtin that was passed to a sink builtin
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct TaintFlow {
+    /// Builtin the data was received by, e.g. recv
+    pub source: String,
+    /// Builtin the data was passed to, e.g. pread
+    pub sink: String,
+    /// Name of the argument containing the data, positional arguments are named by their index
+    pub argument: String,
 }
 
 /// Host Details information
@@ -129,6 +190,8 @@ pub enum ResultType {
     DeadHost,
     /// Detail information about the host
     HostDetail,
+    /// Outcome of the login into the host with the credentials of the scan
+    Authentication,
 }
 
 /// Notus Results are a Map from OIDs to vulnerable Packages
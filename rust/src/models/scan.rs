@@ -2,7 +2,14 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use super::{scanner_preference::ScanPreference, target::Target, vt::VT};
+use super::{
+    scanner_preference::ScanPreference,
+    schedule::Schedule,
+    severity::Classification,
+    target::Target,
+    vt::{VTFilter, VT},
+    watch::Watch,
+};
 
 pub type ScanId = String;
 
@@ -25,6 +32,31 @@ pub struct Scan {
     )]
     /// Configuration options for a scan
     pub scan_preferences: Vec<ScanPreference>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
     /// List of VTs to execute for the target
     pub vts: Vec<VT>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Selects additional VTs by their metadata when the scan is started
+    pub vt_filter: Option<VTFilter>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Re-runs the scan periodically and emits new or changed findings
+    pub watch: Option<Watch>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Adjusts the severity classes of the findings
+    pub classification: Option<Classification>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Restricts the execution to time windows
+    pub schedule: Option<Schedule>,
 }
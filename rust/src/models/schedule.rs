@@ -0,0 +1,242 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use chrono::{DateTime, Datelike, TimeZone, Timelike};
+
+/// Restricts the execution of a scan to time windows.
+///
+/// A scan that is outside of its windows or within a blackout is not started. A running scan is
+/// paused and resumed at the next window, skipping the hosts that were already finished.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Schedule {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// Windows the scan is allowed to run in, any time when empty
+    pub windows: Vec<Window>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// Periods the scan must not run in, even within a window
+    pub blackouts: Vec<Blackout>,
+}
+
+/// A daily window in the local time of the scanner
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Window {
+    /// Begin as `HH:MM`
+    pub start: String,
+    /// End as `HH:MM`, a window ending before its start lasts until the next day
+    pub end: String,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// Days the window starts on, every day when empty
+    pub days: Vec<Weekday>,
+}
+
+/// A period the scan must not run in, e.g. a change freeze
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Blackout {
+    /// Seconds since UNIX epoch
+    pub start: u64,
+    /// Seconds since UNIX epoch, exclusive
+    pub end: u64,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Reason of the blackout
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(value: chrono::Weekday) -> Self {
+        match value {
+            chrono::Weekday::Mon => Self::Monday,
+            chrono::Weekday::Tue => Self::Tuesday,
+            chrono::Weekday::Wed => Self::Wednesday,
+            chrono::Weekday::Thu => Self::Thursday,
+            chrono::Weekday::Fri => Self::Friday,
+            chrono::Weekday::Sat => Self::Saturday,
+            chrono::Weekday::Sun => Self::Sunday,
+        }
+    }
+}
+
+/// Parses `HH:MM` into the minutes since midnight
+fn minutes(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl Window {
+    fn range(&self) -> Option<(u32, u32)> {
+        Some((minutes(&self.start)?, minutes(&self.end)?))
+    }
+
+    fn starts_on(&self, day: chrono::Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day.into())
+    }
+
+    /// Returns true when the time is within the window.
+    ///
+    /// A window with the same start and end lasts 24 hours.
+    pub fn contains<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let Some((start, end)) = self.range() else {
+            return false;
+        };
+        let minute = time.hour() * 60 + time.minute();
+        let day = time.weekday();
+        if start < end {
+            start <= minute && minute < end && self.starts_on(day)
+        } else if minute >= start {
+            self.starts_on(day)
+        } else {
+            // the window started the day before
+            minute < end && self.starts_on(day.pred())
+        }
+    }
+}
+
+impl Blackout {
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.start as i64 <= timestamp && timestamp < self.end as i64
+    }
+}
+
+impl Schedule {
+    /// Returns true when the scan is allowed to run at the given time.
+    pub fn allows<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        if self.blackouts.iter().any(|x| x.contains(time.timestamp())) {
+            return false;
+        }
+        self.windows.is_empty() || self.windows.iter().any(|x| x.contains(time))
+    }
+
+    /// Returns a description of the first invalid window or blackout.
+    pub fn validate(&self) -> Result<(), String> {
+        for window in self.windows.iter() {
+            if window.range().is_none() {
+                return Err(format!(
+                    "invalid scan window {}-{}, expected HH:MM",
+                    window.start, window.end
+                ));
+            }
+        }
+        for blackout in self.blackouts.iter() {
+            if blackout.end <= blackout.start {
+                return Err(format!(
+                    "blackout ending at {} before its start {}",
+                    blackout.end, blackout.start
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn window(start: &str, end: &str, days: Vec<Weekday>) -> Window {
+        Window {
+            start: start.to_string(),
+            end: end.to_string(),
+            days,
+        }
+    }
+
+    #[test]
+    fn overnight_window() {
+        let schedule = Schedule {
+            windows: vec![window("22:00", "06:00", vec![Weekday::Friday])],
+            ..Default::default()
+        };
+        // 2024-06-07 is a Friday
+        let at = |day, hour, minute| Utc.with_ymd_and_hms(2024, 6, day, hour, minute, 0).unwrap();
+        assert!(!schedule.allows(&at(7, 21, 59)));
+        assert!(schedule.allows(&at(7, 22, 0)));
+        assert!(schedule.allows(&at(8, 5, 59)));
+        assert!(!schedule.allows(&at(8, 6, 0)));
+        assert!(!schedule.allows(&at(8, 22, 30)));
+        assert!(!schedule.allows(&at(7, 3, 0)));
+    }
+
+    #[test]
+    fn blackout() {
+        let start = Utc.with_ymd_and_hms(2024, 12, 24, 0, 0, 0).unwrap();
+        let schedule = Schedule {
+            windows: vec![window("08:00", "08:00", vec![])],
+            blackouts: vec![Blackout {
+                start: start.timestamp() as u64,
+                end: start.timestamp() as u64 + 2 * 86400,
+                description: Some("holidays".to_string()),
+            }],
+        };
+        assert!(schedule.allows(&Utc.with_ymd_and_hms(2024, 12, 23, 12, 0, 0).unwrap()));
+        assert!(!schedule.allows(&Utc.with_ymd_and_hms(2024, 12, 25, 23, 59, 59).unwrap()));
+        assert!(schedule.allows(&Utc.with_ymd_and_hms(2024, 12, 26, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn validate() {
+        assert!(Schedule::default().validate().is_ok());
+        for (start, end) in [("24:00", "06:00"), ("22:00", "6"), ("22:60", "06:00")] {
+            let schedule = Schedule {
+                windows: vec![window(start, end, vec![])],
+                ..Default::default()
+            };
+            assert!(schedule.validate().is_err(), "{start}-{end}");
+        }
+        let schedule = Schedule {
+            blackouts: vec![Blackout {
+                start: 2,
+                end: 1,
+                description: None,
+            }],
+            ..Default::default()
+        };
+        assert!(schedule.validate().is_err());
+    }
+}
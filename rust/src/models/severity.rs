@@ -0,0 +1,238 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{fmt::Display, str::FromStr};
+
+use super::result::{Result, ResultType};
+
+/// Severity class of a finding
+///
+/// The classes are ordered, so that filters can select findings of at least a class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SeverityClass {
+    /// Informational finding without a score
+    Log,
+    /// CVSS score below 4.0
+    Low,
+    /// CVSS score from 4.0 to below 7.0
+    Medium,
+    /// CVSS score from 7.0 to below 9.0
+    High,
+    /// CVSS score of at least 9.0
+    Critical,
+}
+
+impl SeverityClass {
+    /// Returns the class of a CVSS score as defined by CVSS v3.
+    pub fn from_score(score: f32) -> Self {
+        match score {
+            x if x >= 9.0 => Self::Critical,
+            x if x >= 7.0 => Self::High,
+            x if x >= 4.0 => Self::Medium,
+            x if x > 0.0 => Self::Low,
+            _ => Self::Log,
+        }
+    }
+}
+
+impl Display for SeverityClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Log => write!(f, "log"),
+            Self::Low => write!(f, "low"),
+            Self::Medium => write!(f, "medium"),
+            Self::High => write!(f, "high"),
+            Self::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+impl FromStr for SeverityClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "log" => Ok(Self::Log),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            _ => Err(format!("unknown severity class {s}")),
+        }
+    }
+}
+
+/// Sets the severity class of the findings of a VT
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct SeverityOverride {
+    /// OID of the VT
+    pub oid: String,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// IP addresses or hostnames the override is limited to, all hosts when empty
+    pub hosts: Vec<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Port the override is limited to
+    pub port: Option<i16>,
+    /// Severity class of the matching findings
+    pub severity: SeverityClass,
+}
+
+impl SeverityOverride {
+    fn matches(&self, result: &Result) -> bool {
+        result.oid.as_deref() == Some(self.oid.as_str())
+            && (self.hosts.is_empty()
+                || [&result.ip_address, &result.hostname]
+                    .into_iter()
+                    .flatten()
+                    .any(|x| self.hosts.contains(x)))
+            && (self.port.is_none() || self.port == result.port)
+    }
+}
+
+/// Adjusts how the severity class of the findings of a scan is determined
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Classification {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Alarms of VTs with a lower quality of detection are classified as log
+    pub min_qod: Option<u8>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// Overrides, the first matching one determines the class of a finding
+    pub overrides: Vec<SeverityOverride>,
+}
+
+impl Classification {
+    /// Returns the severity class of a finding.
+    ///
+    /// The class of an alarm is derived from the CVSS base score of its VT, alarms without a
+    /// score are classified as medium. Log messages are classified as log. An alarm of a VT
+    /// whose quality of detection is below the minimum is classified as log as well. A
+    /// matching override takes precedence. Results that are not findings, e.g. errors, have
+    /// no class.
+    pub fn classify(
+        &self,
+        result: &Result,
+        score: Option<f32>,
+        qod: Option<u8>,
+    ) -> Option<SeverityClass> {
+        let class = match result.r_type {
+            ResultType::Alarm => match (self.min_qod, qod) {
+                (Some(min), Some(qod)) if qod < min => SeverityClass::Log,
+                _ => score.map_or(SeverityClass::Medium, SeverityClass::from_score),
+            },
+            ResultType::Log => SeverityClass::Log,
+            _ => return None,
+        };
+        Some(
+            self.overrides
+                .iter()
+                .find(|x| x.matches(result))
+                .map_or(class, |x| x.severity),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Result, ResultType};
+
+    use super::{Classification, SeverityClass, SeverityOverride};
+
+    #[test]
+    fn classify() {
+        let alarm = Result {
+            r_type: ResultType::Alarm,
+            oid: Some("1".to_string()),
+            ip_address: Some("192.168.0.1".to_string()),
+            port: Some(443),
+            ..Default::default()
+        };
+        let default = Classification::default();
+        for (score, class) in [
+            (Some(9.8), SeverityClass::Critical),
+            (Some(7.5), SeverityClass::High),
+            (Some(5.0), SeverityClass::Medium),
+            (Some(2.6), SeverityClass::Low),
+            (Some(0.0), SeverityClass::Log),
+            (None, SeverityClass::Medium),
+        ] {
+            assert_eq!(default.classify(&alarm, score, Some(30)), Some(class));
+        }
+        let log = Result {
+            r_type: ResultType::Log,
+            ..alarm.clone()
+        };
+        assert_eq!(
+            default.classify(&log, Some(9.8), None),
+            Some(SeverityClass::Log)
+        );
+        let error = Result {
+            r_type: ResultType::Error,
+            ..Default::default()
+        };
+        assert_eq!(default.classify(&error, Some(9.8), None), None);
+
+        let classification = Classification {
+            min_qod: Some(70),
+            overrides: vec![
+                SeverityOverride {
+                    oid: "1".to_string(),
+                    hosts: vec!["192.168.0.2".to_string()],
+                    port: None,
+                    severity: SeverityClass::Critical,
+                },
+                SeverityOverride {
+                    oid: "1".to_string(),
+                    hosts: vec![],
+                    port: Some(443),
+                    severity: SeverityClass::Low,
+                },
+            ],
+        };
+        assert_eq!(
+            classification.classify(&alarm, Some(7.5), Some(30)),
+            Some(SeverityClass::Low)
+        );
+        let other_port = Result {
+            port: Some(80),
+            ..alarm.clone()
+        };
+        assert_eq!(
+            classification.classify(&other_port, Some(7.5), Some(30)),
+            Some(SeverityClass::Log)
+        );
+        assert_eq!(
+            classification.classify(&other_port, Some(7.5), Some(70)),
+            Some(SeverityClass::High)
+        );
+        assert!(SeverityClass::Critical > SeverityClass::High);
+        assert_eq!("HIGH".parse(), Ok(SeverityClass::High));
+    }
+}
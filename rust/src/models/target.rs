@@ -2,7 +2,9 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use super::{credential::Credential, port::Port};
+use std::{collections::HashMap, net::IpAddr};
+
+use super::{credential::Credential, port::Port, result::Result};
 
 pub type Host = String;
 
@@ -36,6 +38,63 @@ pub struct Target {
     #[cfg_attr(feature = "serde_support", serde(default))]
     /// Only scan IP addresses that can be resolved into a DNS name.
     pub reverse_lookup_only: Option<bool>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "HashMap::is_empty")
+    )]
+    /// Labels per host, e.g. owner or environment, that are attached to each result of the host
+    pub host_labels: HashMap<Host, HashMap<String, String>>,
+}
+
+impl Target {
+    /// Returns the labels of the host a result belongs to.
+    ///
+    /// The host is looked up by the IP address and hostname of the result.
+    pub fn labels_of(&self, result: &Result) -> Option<&HashMap<String, String>> {
+        [&result.ip_address, &result.hostname]
+            .into_iter()
+            .flatten()
+            .find_map(|x| self.host_labels.get(x))
+    }
+
+    /// Returns true when the host is covered by the excluded hosts.
+    ///
+    /// Besides hostnames and IP addresses, excluded hosts may contain networks in CIDR notation
+    /// and patterns like `*.example.com` matching all subdomains of a domain.
+    pub fn excludes(&self, host: &str) -> bool {
+        self.excluded_hosts.iter().any(|x| covers(x, host))
+    }
+}
+
+fn normalize(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Returns true when the excluded host covers the host.
+fn covers(excluded: &str, host: &str) -> bool {
+    let (excluded, host) = (normalize(excluded), normalize(host));
+    if let Some(domain) = excluded.strip_prefix("*.") {
+        return host
+            .strip_suffix(domain)
+            .is_some_and(|x| x.len() > 1 && x.ends_with('.'));
+    }
+    let network = excluded
+        .split_once('/')
+        .and_then(|(address, prefix)| Some((address.parse().ok()?, prefix.parse().ok()?)));
+    match (network, host.parse::<IpAddr>()) {
+        (Some((network, prefix)), Ok(address)) => contains(network, prefix, address),
+        _ => excluded == host,
+    }
+}
+
+/// Returns true when the address is part of the network.
+fn contains(network: IpAddr, prefix: u32, address: IpAddr) -> bool {
+    let (width, network, address) = match (network, address) {
+        (IpAddr::V4(n), IpAddr::V4(a)) => (32, u32::from(n) as u128, u32::from(a) as u128),
+        (IpAddr::V6(n), IpAddr::V6(a)) => (128, u128::from(n), u128::from(a)),
+        _ => return false,
+    };
+    prefix <= width && (network ^ address).checked_shr(width - prefix).unwrap_or(0) == 0
 }
 
 /// Enum of possible alive test methods
@@ -52,3 +111,41 @@ pub enum AliveTestMethods {
     ConsiderAlive = 0x08,
     TcpSyn = 0x10,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Target;
+
+    #[test]
+    fn excludes() {
+        let target = Target {
+            excluded_hosts: [
+                "10.0.0.0/8",
+                "2001:db8::/32",
+                "*.example.com",
+                "Host.local.",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ..Default::default()
+        };
+        for host in [
+            "10.1.2.3",
+            "2001:db8::1",
+            "www.example.com",
+            "a.b.EXAMPLE.com",
+            "host.local",
+        ] {
+            assert!(target.excludes(host), "{host}");
+        }
+        for host in [
+            "11.0.0.1",
+            "::ffff:a00:1",
+            "example.com",
+            "badexample.com",
+            "www.host.local",
+        ] {
+            assert!(!target.excludes(host), "{host}");
+        }
+    }
+}
@@ -17,3 +17,42 @@ pub struct VT {
     /// The list of parameters for the VT
     pub parameters: Vec<Parameter>,
 }
+
+/// Selects VTs by their metadata instead of their OIDs.
+///
+/// The filter is resolved when the scan is started, so that VTs added by a feed update in the
+/// meantime are included. A VT is selected when it matches every given criterion, criteria
+/// that are not set match every VT.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct VTFilter {
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// The VT must belong to one of these families
+    pub families: Vec<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// The VT must have each of these tags with the given value, e.g. solution_type VendorFix
+    pub tags: Vec<VTTagFilter>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// The VT must reference one of these CVEs
+    pub cves: Vec<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// The last modification of the VT must not be before this unix timestamp
+    pub modified_since: Option<i64>,
+}
+
+/// A tag a VT must have to be selected by a [VTFilter]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct VTTagFilter {
+    /// The name of the tag
+    pub name: String,
+    /// The value of the tag
+    pub value: String,
+}
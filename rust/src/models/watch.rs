@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use super::result::Result;
+
+/// Keeps monitoring the target of a scan by re-running it periodically.
+///
+/// After each run only findings that are new or changed compared to the previous run are
+/// emitted as [WatchEvent].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Watch {
+    /// Seconds between the end of a run and the start of the next one
+    pub interval: u64,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// URLs each event is posted to as JSON
+    pub webhooks: Vec<String>,
+}
+
+/// How a finding differs from the previous run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FindingChange {
+    /// The finding was not reported by the previous run
+    New,
+    /// The finding was reported by the previous run with a different message
+    Changed,
+}
+
+/// A new or changed finding of a watched scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct WatchEvent {
+    /// ID of the watched scan
+    pub scan_id: String,
+    /// Number of the run that reported the finding, starting with 1
+    pub run: u64,
+    /// How the finding differs from the previous run
+    pub change: FindingChange,
+    /// The finding as reported by the run
+    pub result: Result,
+}
@@ -10,7 +10,7 @@
 #[cfg(test)]
 mod tests;
 
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use crate::nasl::prelude::*;
 
@@ -20,8 +20,8 @@ use crate::nasl::prelude::*;
 /// When there is an uneven number of elements the last key will be dropped, as there is no corresponding value.
 /// So `make_array(1, 0, 1)` will return the same response as `make_array(1, 0)`.
 #[nasl_function]
-fn make_array(positionals: CheckedPositionals<NaslValue>) -> HashMap<String, NaslValue> {
-    let mut values = HashMap::new();
+fn make_array(positionals: CheckedPositionals<NaslValue>) -> IndexMap<String, NaslValue> {
+    let mut values = IndexMap::new();
     for (idx, val) in positionals.iter().enumerate() {
         if idx % 2 == 1 {
             values.insert(positionals[idx - 1].to_string(), val.clone());
@@ -80,6 +80,100 @@ fn max_index(arr: &NaslValue) -> Option<usize> {
     }
 }
 
+/// Returns the element of a dict or list stored under the key.
+fn element<'a>(value: &'a NaslValue, key: &str) -> Option<&'a NaslValue> {
+    match value {
+        NaslValue::Dict(x) => x.get(key),
+        NaslValue::Array(x) => key.parse::<usize>().ok().and_then(|i| x.get(i)),
+        _ => None,
+    }
+}
+
+/// NASL function to sort a list of dicts by the value stored under a key.
+///
+/// The values of a dict are sorted when a dict is given. Elements without the key are sorted
+/// first, elements with equal values keep their order. With reverse set to TRUE the list is
+/// sorted in descending order, elements with equal values still keep their order.
+#[nasl_function(named(key, reverse))]
+fn sort_by(
+    arr: &NaslValue,
+    key: NaslValue,
+    reverse: Option<bool>,
+) -> Result<Vec<NaslValue>, FunctionErrorKind> {
+    let mut values = match arr {
+        NaslValue::Array(x) => x.clone(),
+        NaslValue::Dict(x) => x.values().cloned().collect(),
+        NaslValue::Null => vec![],
+        x => {
+            return Err(FunctionErrorKind::wrong_unnamed_argument(
+                "an array",
+                &format!("{x:?}"),
+            ))
+        }
+    };
+    let key = key.to_string();
+    let reverse = reverse.unwrap_or_default();
+    values.sort_by(|a, b| {
+        let a = element(a, &key).unwrap_or(&NaslValue::Null);
+        let b = element(b, &key).unwrap_or(&NaslValue::Null);
+        if reverse {
+            b.cmp(a)
+        } else {
+            a.cmp(b)
+        }
+    });
+    Ok(values)
+}
+
+/// Merges value into the dict, nested dicts are merged recursively.
+fn merge_into(result: &mut IndexMap<String, NaslValue>, value: IndexMap<String, NaslValue>) {
+    for (key, value) in value {
+        match (result.get_mut(&key), value) {
+            (Some(NaslValue::Dict(existing)), NaslValue::Dict(value)) => {
+                merge_into(existing, value)
+            }
+            (_, value) => {
+                result.insert(key, value);
+            }
+        }
+    }
+}
+
+/// NASL function to merge arrays and lists.
+///
+/// Lists are concatenated. When a dict is given the result is a dict, the elements of lists are
+/// stored under their index. Later keys replace earlier ones, nested dicts are merged
+/// recursively. NULL arguments are skipped.
+#[nasl_function]
+fn merge(positionals: CheckedPositionals<NaslValue>) -> Result<NaslValue, FunctionErrorKind> {
+    if positionals
+        .iter()
+        .all(|x| matches!(x, NaslValue::Array(_) | NaslValue::Null))
+    {
+        return Ok(NaslValue::Array(create_list(positionals)));
+    }
+    let mut result = IndexMap::new();
+    for value in positionals.iter() {
+        let value = match value {
+            NaslValue::Dict(x) => x.clone(),
+            NaslValue::Array(x) => x
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i.to_string(), v.clone()))
+                .collect(),
+            NaslValue::Null => continue,
+            x => {
+                return Err(FunctionErrorKind::wrong_unnamed_argument(
+                    "arrays",
+                    &format!("{x:?}"),
+                ))
+            }
+        };
+        merge_into(&mut result, value);
+    }
+    Ok(NaslValue::Dict(result))
+}
+
 pub struct Array;
 
 function_set! {
@@ -91,5 +185,7 @@ function_set! {
         (nasl_sort, "sort"),
         keys,
         max_index,
+        sort_by,
+        merge,
     )
 }
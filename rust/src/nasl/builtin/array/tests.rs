@@ -89,4 +89,85 @@ mod tests {
         t.ok(r#"max_index(make_list(1, 0));"#, 2);
         t.ok(r#"max_index(make_list());"#, 0);
     }
+
+    #[test]
+    fn insertion_order() {
+        let mut t = TestBuilder::default();
+        t.run(r#"a = make_array("z", 1, "b", 2, "m", 3);"#);
+        t.run(r#"a["c"] = 4;"#);
+        t.ok(
+            r#"keys(a);"#,
+            NaslValue::Array(vec![
+                NaslValue::String("z".to_string()),
+                NaslValue::String("b".to_string()),
+                NaslValue::String("m".to_string()),
+                NaslValue::String("c".to_string()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let mut t = TestBuilder::default();
+        t.run(r#"inner = [1, make_array("z", 3)];"#);
+        t.run(r#"a = make_array("x", make_array("y", inner));"#);
+        t.run(r#"outer = a["x"];"#);
+        t.run(r#"y = outer["y"];"#);
+        t.ok(r#"y[0];"#, 1);
+        t.run(r#"z = y[1];"#);
+        t.ok(r#"z["z"];"#, 3);
+    }
+
+    #[test]
+    fn sort_by() {
+        let mut t = TestBuilder::default();
+        t.run(r#"l = [make_array("n", "b", "v", 2), make_array("n", "a", "v", 3), make_array("n", "c")];"#);
+        t.run(r#"s = sort_by(l, key: "v");"#);
+        t.ok(r#"n = "";"#, "");
+        t.run(r#"foreach e (s) n = n + e["n"];"#);
+        t.ok(r#"n;"#, "cba");
+        t.run(r#"s = sort_by(l, key: "n", reverse: TRUE);"#);
+        t.ok(r#"n = "";"#, "");
+        t.run(r#"foreach e (s) n = n + e["n"];"#);
+        t.ok(r#"n;"#, "cba");
+        // ties keep their order when reversed
+        t.run(r#"l = [make_array("n", "a", "v", 1), make_array("n", "b", "v", 2), make_array("n", "c", "v", 1)];"#);
+        t.run(r#"s = sort_by(l, key: "v", reverse: TRUE);"#);
+        t.ok(r#"n = "";"#, "");
+        t.run(r#"foreach e (s) n = n + e["n"];"#);
+        t.ok(r#"n;"#, "bac");
+        t.run(r#"p = [2, "x"];"#);
+        t.run(r#"q = [1, "y"];"#);
+        t.run(r#"l = [p, q];"#);
+        t.run(r#"s = sort_by(l, key: 0);"#);
+        t.run(r#"e = s[0];"#);
+        t.ok(r#"e[1];"#, "y");
+    }
+
+    #[test]
+    fn merge() {
+        let mut t = TestBuilder::default();
+        t.ok(
+            r#"merge(make_list(1, 2), NULL, make_list(3));"#,
+            vec![1, 2, 3u32],
+        );
+        t.run(r#"a = make_array("a", 1, "n", make_array("x", 1, "y", 2));"#);
+        t.run(r#"b = make_array("b", 2, "a", 3, "n", make_array("y", 4));"#);
+        t.run(r#"m = merge(a, b);"#);
+        t.ok(
+            r#"keys(m);"#,
+            NaslValue::Array(vec![
+                NaslValue::String("a".to_string()),
+                NaslValue::String("n".to_string()),
+                NaslValue::String("b".to_string()),
+            ]),
+        );
+        t.ok(r#"m["a"];"#, 3);
+        t.run(r#"n = m["n"];"#);
+        t.ok(r#"n["x"];"#, 1);
+        t.ok(r#"n["y"];"#, 4);
+        t.run(r#"l = merge(a, make_list(5));"#);
+        t.ok(r#"l["0"];"#, 5);
+        check_err_matches!(t, r#"merge(a, 1);"#, FunctionErrorKind::WrongArgument(_));
+    }
 }
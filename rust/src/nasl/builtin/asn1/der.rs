@@ -0,0 +1,309 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Parser and encoder of BER and DER encoded data.
+
+use std::fmt::Display;
+
+/// Maximum nesting depth of constructed elements.
+const MAX_DEPTH: usize = 64;
+
+/// Class of a tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Universal,
+    Application,
+    Context,
+    Private,
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Class::Universal => "universal",
+                Class::Application => "application",
+                Class::Context => "context",
+                Class::Private => "private",
+            }
+        )
+    }
+}
+
+/// A parsed element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element<'a> {
+    pub class: Class,
+    pub tag: u64,
+    pub constructed: bool,
+    /// Offset of the identifier octets within the parsed data
+    pub offset: usize,
+    /// Length of the identifier and length octets
+    pub header_length: usize,
+    /// The content octets, without the end-of-contents octets of the indefinite form
+    pub content: &'a [u8],
+    /// The elements within a constructed element
+    pub children: Vec<Element<'a>>,
+}
+
+/// Error while parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub offset: usize,
+    pub reason: &'static str,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.reason, self.offset)
+    }
+}
+
+fn error<T>(offset: usize, reason: &'static str) -> Result<T, Error> {
+    Err(Error { offset, reason })
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn byte(&mut self) -> Result<u8, Error> {
+        let result = self.data.get(self.position).copied();
+        self.position += 1;
+        result.map_or_else(|| error(self.position - 1, "unexpected end of data"), Ok)
+    }
+
+    fn identifier(&mut self) -> Result<(Class, bool, u64), Error> {
+        let first = self.byte()?;
+        let class = match first >> 6 {
+            0 => Class::Universal,
+            1 => Class::Application,
+            2 => Class::Context,
+            _ => Class::Private,
+        };
+        let constructed = first & 0x20 != 0;
+        let mut tag = (first & 0x1f) as u64;
+        if tag == 0x1f {
+            // high tag number form
+            tag = 0;
+            loop {
+                let b = self.byte()?;
+                if tag > u64::MAX >> 7 {
+                    return error(self.position - 1, "tag number too large");
+                }
+                tag = (tag << 7) | (b & 0x7f) as u64;
+                if b & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+        Ok((class, constructed, tag))
+    }
+
+    /// Returns the length of the content or None for the indefinite form.
+    fn length(&mut self) -> Result<Option<usize>, Error> {
+        let first = self.byte()?;
+        if first < 0x80 {
+            return Ok(Some(first as usize));
+        }
+        let octets = (first & 0x7f) as usize;
+        if octets == 0 {
+            return Ok(None);
+        }
+        if octets > std::mem::size_of::<usize>() {
+            return error(self.position - 1, "length too large");
+        }
+        let mut length = 0usize;
+        for _ in 0..octets {
+            length = (length << 8) | self.byte()? as usize;
+        }
+        Ok(Some(length))
+    }
+
+    fn element(&mut self, depth: usize) -> Result<Element<'a>, Error> {
+        if depth > MAX_DEPTH {
+            return error(self.position, "nesting too deep");
+        }
+        let offset = self.position;
+        let (class, constructed, tag) = self.identifier()?;
+        let length = self.length()?;
+        let header_length = self.position - offset;
+        let start = self.position;
+        match length {
+            Some(length) => {
+                let end = start
+                    .checked_add(length)
+                    .filter(|x| *x <= self.data.len())
+                    .map_or_else(|| error(offset, "length exceeds data"), Ok)?;
+                let content = &self.data[start..end];
+                let children = if constructed {
+                    let mut inner = Parser {
+                        data: &self.data[..end],
+                        position: start,
+                    };
+                    inner.elements(depth + 1, false)?
+                } else {
+                    vec![]
+                };
+                self.position = end;
+                Ok(Element {
+                    class,
+                    tag,
+                    constructed,
+                    offset,
+                    header_length,
+                    content,
+                    children,
+                })
+            }
+            None if constructed => {
+                let children = self.elements(depth + 1, true)?;
+                // the end-of-contents octets are not part of the content
+                let content = &self.data[start..self.position - 2];
+                Ok(Element {
+                    class,
+                    tag,
+                    constructed,
+                    offset,
+                    header_length,
+                    content,
+                    children,
+                })
+            }
+            None => error(offset, "indefinite length of a primitive element"),
+        }
+    }
+
+    /// Parses elements until the end of the data or the end-of-contents octets.
+    fn elements(&mut self, depth: usize, indefinite: bool) -> Result<Vec<Element<'a>>, Error> {
+        let mut result = vec![];
+        loop {
+            if self.position >= self.data.len() {
+                if indefinite {
+                    return error(self.position, "missing end-of-contents");
+                }
+                return Ok(result);
+            }
+            if indefinite && self.data[self.position..].starts_with(&[0, 0]) {
+                self.position += 2;
+                return Ok(result);
+            }
+            result.push(self.element(depth)?);
+        }
+    }
+}
+
+/// Parses all elements within the data.
+pub fn parse(data: &[u8]) -> Result<Vec<Element<'_>>, Error> {
+    Parser { data, position: 0 }.elements(0, false)
+}
+
+/// Decodes the content of an object identifier into the dotted notation.
+pub fn oid(content: &[u8]) -> Option<String> {
+    if content.is_empty() || content.last().is_some_and(|x| x & 0x80 != 0) {
+        return None;
+    }
+    let mut arcs: Vec<u64> = vec![];
+    let mut value = 0u64;
+    for b in content {
+        if value > u64::MAX >> 7 {
+            return None;
+        }
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        }
+    }
+    Some(
+        arcs.iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+}
+
+/// Decodes the content of an integer when it fits into an i64.
+pub fn integer(content: &[u8]) -> Option<i64> {
+    if content.is_empty() || content.len() > 8 {
+        return None;
+    }
+    let negative = content[0] & 0x80 != 0;
+    let mut bytes = if negative { [0xff; 8] } else { [0; 8] };
+    bytes[8 - content.len()..].copy_from_slice(content);
+    Some(i64::from_be_bytes(bytes))
+}
+
+/// Encodes the element with the length in the definite form.
+pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut result = vec![tag];
+    match content.len() {
+        x if x < 0x80 => result.push(x as u8),
+        x => {
+            let bytes = x.to_be_bytes();
+            let skip = bytes.iter().take_while(|b| **b == 0).count();
+            result.push(0x80 | (bytes.len() - skip) as u8);
+            result.extend_from_slice(&bytes[skip..]);
+        }
+    }
+    result.extend_from_slice(content);
+    result
+}
+
+/// Encodes the integer with the minimal number of octets.
+pub fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indefinite_length() {
+        // SEQUENCE (indefinite) { INTEGER 5, [0] { NULL } }
+        let data = [
+            0x30, 0x80, 0x02, 0x01, 0x05, 0xa0, 0x02, 0x05, 0x00, 0x00, 0x00,
+        ];
+        let elements = parse(&data).unwrap();
+        assert_eq!(elements.len(), 1);
+        let sequence = &elements[0];
+        assert_eq!(sequence.content, &data[2..9]);
+        assert_eq!(sequence.children.len(), 2);
+        assert_eq!(sequence.children[1].class, Class::Context);
+        assert_eq!(sequence.children[1].children[0].tag, 5);
+        assert!(parse(&data[..10]).is_err());
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b]).as_deref(),
+            Some("1.2.840.113549.1.1.11")
+        );
+        assert_eq!(oid(&[0x88, 0x37]).as_deref(), Some("2.999"));
+        assert_eq!(oid(&[0x86]), None);
+        assert_eq!(integer(&[0x00, 0x80]), Some(128));
+        assert_eq!(integer(&[0xff, 0x7f]), Some(-129));
+        assert_eq!(integer(&[1; 9]), None);
+    }
+}
@@ -0,0 +1,169 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to convert between PEM and DER and to inspect ASN.1 structures.
+
+pub(crate) mod der;
+mod pem;
+#[cfg(test)]
+mod tests;
+
+use indexmap::IndexMap;
+
+use crate::nasl::prelude::*;
+
+use der::{Class, Element};
+
+// Universal tag numbers as defined in X.680
+const BOOLEAN: u64 = 1;
+const INTEGER: u64 = 2;
+const BIT_STRING: u64 = 3;
+const OBJECT_IDENTIFIER: u64 = 6;
+const ENUMERATED: u64 = 10;
+const BMP_STRING: u64 = 30;
+
+/// Returns the name of a universal tag.
+fn type_name(tag: u64) -> Option<&'static str> {
+    Some(match tag {
+        BOOLEAN => "BOOLEAN",
+        INTEGER => "INTEGER",
+        BIT_STRING => "BIT STRING",
+        4 => "OCTET STRING",
+        5 => "NULL",
+        OBJECT_IDENTIFIER => "OBJECT IDENTIFIER",
+        ENUMERATED => "ENUMERATED",
+        12 => "UTF8String",
+        16 => "SEQUENCE",
+        17 => "SET",
+        18 => "NumericString",
+        19 => "PrintableString",
+        20 => "T61String",
+        22 => "IA5String",
+        23 => "UTCTime",
+        24 => "GeneralizedTime",
+        26 => "VisibleString",
+        28 => "UniversalString",
+        BMP_STRING => "BMPString",
+        _ => return None,
+    })
+}
+
+/// Decodes the content of primitive universal elements.
+fn decode(element: &Element) -> NaslValue {
+    if element.class != Class::Universal || element.constructed {
+        return NaslValue::Null;
+    }
+    let content = element.content;
+    match element.tag {
+        BOOLEAN if content.len() == 1 => NaslValue::Boolean(content[0] != 0),
+        INTEGER | ENUMERATED => der::integer(content).map_or(NaslValue::Null, NaslValue::Number),
+        BIT_STRING if !content.is_empty() => NaslValue::Data(content[1..].to_vec()),
+        OBJECT_IDENTIFIER => der::oid(content).map_or(NaslValue::Null, NaslValue::String),
+        BMP_STRING => {
+            let units = content
+                .chunks(2)
+                .map(|x| u16::from_be_bytes([x[0], x.get(1).copied().unwrap_or_default()]));
+            NaslValue::String(char::decode_utf16(units).filter_map(|x| x.ok()).collect())
+        }
+        12 | 18 | 19 | 20 | 22 | 23 | 24 | 26 => {
+            NaslValue::String(String::from_utf8_lossy(content).to_string())
+        }
+        _ => NaslValue::Null,
+    }
+}
+
+fn to_nasl_value(element: &Element) -> NaslValue {
+    let mut result = IndexMap::new();
+    let mut insert = |key: &str, value: NaslValue| {
+        result.insert(key.to_string(), value);
+    };
+    insert("class", NaslValue::String(element.class.to_string()));
+    insert("tag", NaslValue::Number(element.tag as i64));
+    insert("constructed", NaslValue::Boolean(element.constructed));
+    let name = match element.class {
+        Class::Universal => type_name(element.tag),
+        _ => None,
+    };
+    insert("type", name.map_or(NaslValue::Null, |x| x.into()));
+    insert("offset", NaslValue::Number(element.offset as i64));
+    insert(
+        "header-length",
+        NaslValue::Number(element.header_length as i64),
+    );
+    insert("length", NaslValue::Number(element.content.len() as i64));
+    insert("value", NaslValue::Data(element.content.to_vec()));
+    insert("decoded", decode(element));
+    insert(
+        "children",
+        NaslValue::Array(element.children.iter().map(to_nasl_value).collect()),
+    );
+    NaslValue::Dict(result)
+}
+
+/// Decode a PEM block into DER.
+///
+/// Takes the PEM encoded text as unnamed argument and the optional named arguments label, to
+/// only consider blocks of the given type, e.g. "CERTIFICATE" or "PRIVATE KEY", and index, to
+/// select the n-th of those blocks, starting with 0. Returns NULL when there is no such block.
+#[nasl_function(named(label, index))]
+fn pem_to_der(data: &NaslValue, label: Option<&str>, index: Option<usize>) -> Option<Vec<u8>> {
+    let data: Vec<u8> = data.into();
+    pem::decode(&String::from_utf8_lossy(&data))
+        .into_iter()
+        .filter(|x| label.is_none_or(|label| x.label == label))
+        .nth(index.unwrap_or_default())
+        .map(|x| x.data)
+}
+
+/// Encode DER data as PEM.
+///
+/// Takes the data as unnamed argument and the named argument label, the type of the data used
+/// within the encapsulation boundaries, e.g. "CERTIFICATE".
+#[nasl_function(named(label))]
+fn der_to_pem(data: &NaslValue, label: &str) -> String {
+    let data: Vec<u8> = data.into();
+    pem::encode(label, &data)
+}
+
+/// Parse BER or DER encoded data.
+///
+/// Takes the data as unnamed argument and returns an array of the top level elements or NULL
+/// when the data is malformed. Each element is a dict containing:
+/// - class: universal, application, context or private
+/// - tag: the tag number
+/// - constructed: TRUE for constructed elements like SEQUENCE
+/// - type: the name of a universal tag, e.g. "OBJECT IDENTIFIER", or NULL
+/// - offset: the offset of the element within the data
+/// - header-length: the length of the identifier and length octets
+/// - length: the length of the content
+/// - value: the content as data
+/// - decoded: the decoded content of BOOLEAN, INTEGER, ENUMERATED, OBJECT IDENTIFIER, string
+///   and time elements, the bits of BIT STRING elements without the unused bits octet or NULL
+/// - children: array of the elements within a constructed element
+///
+/// The content of OCTET STRING and BIT STRING elements often is DER encoded itself and can be
+/// passed to asn1_parse again.
+#[nasl_function]
+fn asn1_parse(data: &NaslValue) -> NaslValue {
+    let data: Vec<u8> = data.into();
+    match der::parse(&data) {
+        Ok(elements) => NaslValue::Array(elements.iter().map(to_nasl_value).collect()),
+        Err(e) => {
+            tracing::debug!(%e, "asn1_parse failed");
+            NaslValue::Null
+        }
+    }
+}
+
+pub struct NaslAsn1;
+
+function_set! {
+    NaslAsn1,
+    sync_stateless,
+    (
+        pem_to_der,
+        der_to_pem,
+        asn1_parse
+    )
+}
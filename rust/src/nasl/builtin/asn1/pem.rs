@@ -0,0 +1,71 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding and decoding of PEM as described in RFC 7468.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+const LINE_LENGTH: usize = 64;
+
+/// A decoded PEM block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// The label of the encapsulation boundaries, e.g. CERTIFICATE
+    pub label: String,
+    pub data: Vec<u8>,
+}
+
+/// Decodes all PEM blocks within the text.
+///
+/// Text outside of the blocks as well as blocks that cannot be decoded are ignored. Headers
+/// as used by encrypted private keys in the legacy format are skipped.
+pub fn decode(text: &str) -> Vec<Block> {
+    let mut result = vec![];
+    let mut current: Option<(&str, String)> = None;
+    for line in text.lines().map(|x| x.trim()) {
+        match current.as_mut() {
+            None => {
+                current = line
+                    .strip_prefix("-----BEGIN ")
+                    .and_then(|x| x.strip_suffix("-----"))
+                    .map(|label| (label, String::new()));
+            }
+            Some((label, base64)) => {
+                let end = line
+                    .strip_prefix("-----END ")
+                    .and_then(|x| x.strip_suffix("-----"));
+                match end {
+                    Some(end) => {
+                        if end == *label {
+                            match STANDARD.decode(base64.as_bytes()) {
+                                Ok(data) => result.push(Block {
+                                    label: label.to_string(),
+                                    data,
+                                }),
+                                Err(e) => tracing::debug!(%e, label, "ignoring invalid PEM block"),
+                            }
+                        }
+                        current = None;
+                    }
+                    None if line.contains(':') => {}
+                    None => base64.push_str(line),
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Encodes the data as PEM block with the given label.
+pub fn encode(label: &str, data: &[u8]) -> String {
+    let base64 = STANDARD.encode(data);
+    let mut result = format!("-----BEGIN {label}-----\n");
+    for line in base64.as_bytes().chunks(LINE_LENGTH) {
+        // base64 is always valid ASCII
+        result.push_str(std::str::from_utf8(line).unwrap_or_default());
+        result.push('\n');
+    }
+    result.push_str(&format!("-----END {label}-----\n"));
+    result
+}
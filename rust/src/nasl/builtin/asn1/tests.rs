@@ -0,0 +1,83 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+
+/// SEQUENCE { OBJECT IDENTIFIER 1.2.840.113549.1.1.11, NULL, [0] { INTEGER -1 },
+/// UTF8String "hi", BOOLEAN TRUE }
+const SEQUENCE: &str = "301906092a864886f70d01010b0500a0030201ff0c0268690101ff";
+
+#[test]
+fn der_to_pem() {
+    let mut t = TestBuilder::default();
+    t.ok(
+        "der_to_pem(\"abc\", label: \"TEST\");",
+        "-----BEGIN TEST-----\nYWJj\n-----END TEST-----\n",
+    );
+    // lines are wrapped after 64 characters
+    t.ok(
+        "der_to_pem(crap(length: 49, data: \"a\"), label: \"TEST\");",
+        format!(
+            "-----BEGIN TEST-----\n{}\nYQ==\n-----END TEST-----\n",
+            "YWFh".repeat(16)
+        ),
+    );
+}
+
+#[test]
+fn pem_to_der() {
+    let mut t = TestBuilder::default();
+    t.run(format!("der = hexstr_to_data(\"{SEQUENCE}\");"));
+    t.run(
+        "pem = \"text before\n\" + der_to_pem(\"first\", label: \"TEST\") + der_to_pem(der, label: \"CERTIFICATE\");",
+    );
+    t.ok("pem_to_der(pem);", "first".as_bytes());
+    t.ok("pem_to_der(pem, index: 1) == der;", true);
+    t.ok("pem_to_der(pem, label: \"CERTIFICATE\") == der;", true);
+    t.ok(
+        "pem_to_der(pem, label: \"CERTIFICATE\", index: 1);",
+        NaslValue::Null,
+    );
+    t.ok("pem_to_der(\"no pem\");", NaslValue::Null);
+    t.ok(
+        "pem_to_der(\"-----BEGIN A-----\nYWJj\n-----END B-----\n\");",
+        NaslValue::Null,
+    );
+}
+
+#[test]
+fn asn1_parse() {
+    let mut t = TestBuilder::default();
+    t.run(format!("r = asn1_parse(hexstr_to_data(\"{SEQUENCE}\"));"));
+    t.ok("max_index(r);", 1);
+    t.run("seq = r[0];");
+    t.ok("seq[\"type\"];", "SEQUENCE");
+    t.ok("seq[\"constructed\"];", true);
+    t.ok("seq[\"header-length\"];", 2);
+    t.ok("seq[\"length\"];", 25);
+    t.run("children = seq[\"children\"];");
+    t.ok("max_index(children);", 5);
+    t.run("oid = children[0];");
+    t.ok("oid[\"decoded\"];", "1.2.840.113549.1.1.11");
+    t.ok("oid[\"offset\"];", 2);
+    t.run("null = children[1];");
+    t.ok("null[\"type\"];", "NULL");
+    t.run("explicit = children[2];");
+    t.ok("explicit[\"class\"];", "context");
+    t.ok("explicit[\"tag\"];", 0);
+    t.ok("explicit[\"type\"];", NaslValue::Null);
+    t.run("int = explicit[\"children\"];");
+    t.run("int = int[0];");
+    t.ok("int[\"decoded\"];", -1);
+    t.ok("int[\"value\"];", vec![0xffu8]);
+    t.run("str = children[3];");
+    t.ok("str[\"decoded\"];", "hi");
+    t.run("bool = children[4];");
+    t.ok("bool[\"decoded\"];", true);
+    t.ok(
+        "asn1_parse(hexstr_to_data(\"3005020101\"));",
+        NaslValue::Null,
+    );
+    t.ok("asn1_parse(\"\");", Vec::<NaslValue>::new());
+}
@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Verification of PKCS#7 and CMS signatures.
+
+use openssl::{
+    cms::{CMSOptions, CmsContentInfo},
+    pkcs7::{Pkcs7, Pkcs7Flags},
+    stack::Stack,
+    x509::{verify::X509VerifyParam, X509},
+};
+
+use crate::nasl::prelude::*;
+
+use super::{openssl_error, verify::trust_store};
+
+/// A parsed signed message.
+pub(super) struct Signed {
+    cms: CmsContentInfo,
+    pkcs7: Pkcs7,
+    /// The content of a S/MIME multipart/signed message.
+    content: Option<Vec<u8>>,
+}
+
+/// Parses a DER or PEM encoded PKCS#7 / CMS structure or a S/MIME message.
+pub(super) fn parse(data: &[u8]) -> Result<Signed, FunctionErrorKind> {
+    let (pkcs7, content) = if data.starts_with(b"-----BEGIN") {
+        (Pkcs7::from_pem(data), None)
+    } else if data.first() == Some(&0x30) {
+        (Pkcs7::from_der(data), None)
+    } else {
+        Pkcs7::from_smime(data)
+            .map(|(pkcs7, content)| (Ok(pkcs7), content))
+            .map_err(openssl_error)?
+    };
+    let pkcs7 = pkcs7.map_err(|e| {
+        FunctionErrorKind::Diagnostic(format!("Unable to parse signed data: {e}"), None)
+    })?;
+    let cms = pkcs7
+        .to_der()
+        .and_then(|x| CmsContentInfo::from_der(&x))
+        .map_err(openssl_error)?;
+    Ok(Signed {
+        cms,
+        pkcs7,
+        content,
+    })
+}
+
+/// The trust store and checks used to verify a signature.
+pub(super) struct VerifyOptions<'a> {
+    /// Content of a detached signature.
+    pub content: Option<&'a [u8]>,
+    /// Additional certificates used to find the signer certificates and build their chains.
+    pub certs: Vec<X509>,
+    /// Additional trusted certificates.
+    pub trusted: Vec<X509>,
+    /// Point in time as unix timestamp to check the validity periods against, defaults to now.
+    pub time: Option<i64>,
+    /// Only verify the signature without validating the certificates of the signers.
+    pub signature_only: bool,
+}
+
+/// The outcome of a verification.
+pub(super) struct Verification {
+    /// The error when the verification failed.
+    pub error: Option<String>,
+    /// The signed content.
+    pub content: Vec<u8>,
+    /// The certificates of the signers.
+    pub signers: Vec<X509>,
+}
+
+/// Verifies the signatures of a signed message.
+pub(super) fn verify(
+    mut signed: Signed,
+    options: VerifyOptions,
+) -> Result<Verification, FunctionErrorKind> {
+    let mut store = trust_store(None, options.trusted)?;
+    if let Some(time) = options.time {
+        let mut param = X509VerifyParam::new().map_err(openssl_error)?;
+        param.set_time(time as _);
+        store.set_param(&param).map_err(openssl_error)?;
+    }
+    let store = store.build();
+    let mut certs = Stack::new().map_err(openssl_error)?;
+    for cert in options.certs {
+        certs.push(cert).map_err(openssl_error)?;
+    }
+    let flags = if options.signature_only {
+        CMSOptions::NO_SIGNER_CERT_VERIFY
+    } else {
+        CMSOptions::empty()
+    };
+
+    let signers = signed
+        .pkcs7
+        .signers(&certs, Pkcs7Flags::empty())
+        .map(|x| x.into_iter().collect())
+        .unwrap_or_default();
+    let detached = options.content.or(signed.content.as_deref());
+    let mut content = vec![];
+    let error = signed
+        .cms
+        .verify(
+            Some(&certs),
+            Some(&store),
+            detached,
+            Some(&mut content),
+            flags,
+        )
+        .err()
+        .map(|e| e.to_string());
+    Ok(Verification {
+        error,
+        content,
+        signers,
+    })
+}
@@ -0,0 +1,662 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to parse and inspect X.509 certificates.
+
+mod cms;
+mod ocsp;
+mod pkcs12;
+#[cfg(test)]
+mod tests;
+mod verify;
+
+use std::{collections::HashMap, str::FromStr, sync::RwLock};
+
+use indexmap::IndexMap;
+
+use openssl::{
+    asn1::{Asn1Time, Asn1TimeRef},
+    error::ErrorStack,
+    hash::MessageDigest,
+    nid::Nid,
+    stack::Stack,
+    x509::{GeneralName, X509NameRef, X509},
+};
+
+use crate::nasl::prelude::*;
+
+fn openssl_error(e: ErrorStack) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(e.to_string(), None)
+}
+
+/// Parses a DER or PEM encoded certificate.
+pub(crate) fn parse_cert(data: &[u8]) -> Result<X509, FunctionErrorKind> {
+    if data.starts_with(b"-----BEGIN") {
+        X509::from_pem(data)
+    } else {
+        X509::from_der(data)
+    }
+    .map_err(|e| FunctionErrorKind::Diagnostic(format!("Unable to parse certificate: {e}"), None))
+}
+
+/// Formats a distinguished name as described in RFC 2253.
+pub(crate) fn rfc2253(name: &X509NameRef) -> String {
+    let escape = |value: &str| {
+        let mut escaped = String::with_capacity(value.len());
+        let last = value.chars().count().saturating_sub(1);
+        for (i, c) in value.chars().enumerate() {
+            if matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+                || (i == 0 && matches!(c, '#' | ' '))
+                || (i == last && c == ' ')
+            {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    };
+    let mut entries = name
+        .entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or_default();
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|x| escape(x.as_ref()))
+                .unwrap_or_default();
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>();
+    entries.reverse();
+    entries.join(",")
+}
+
+/// Converts an ASN.1 time into the isotime format used by NASL.
+pub(crate) fn isotime(time: &Asn1TimeRef) -> Result<String, FunctionErrorKind> {
+    let diff = Asn1Time::from_unix(0)
+        .and_then(|epoch| epoch.diff(time))
+        .map_err(openssl_error)?;
+    let seconds = diff.days as i64 * 86400 + diff.secs as i64;
+    chrono::DateTime::from_timestamp(seconds, 0)
+        .map(|x| x.format("%Y%m%dT%H%M%S").to_string())
+        .ok_or_else(|| FunctionErrorKind::Diagnostic(format!("Invalid time {time}"), None))
+}
+
+/// Formats the alternative names the same way as libksba does for the C implementation.
+///
+/// Mail addresses are enclosed in angle brackets, other names are returned as S-expressions.
+fn alt_names(names: Option<Stack<GeneralName>>) -> Vec<String> {
+    let sexp = |kind: &str, value: &str| format!("({}:{kind}{}:{value})", kind.len(), value.len());
+    names
+        .into_iter()
+        .flatten()
+        .filter_map(|name| {
+            if let Some(email) = name.email() {
+                Some(format!("<{email}>"))
+            } else if let Some(dns) = name.dnsname() {
+                Some(sexp("dns-name", dns))
+            } else {
+                name.uri().map(|uri| sexp("uri", uri))
+            }
+        })
+        .collect()
+}
+
+/// The hostnames a certificate is valid for: the common name of the subject and the DNS names
+/// of the subject alternative names.
+pub(crate) fn hostnames(cert: &X509) -> Vec<String> {
+    let common_names = cert
+        .subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .filter_map(|entry| entry.data().as_utf8().ok().map(|x| x.to_string()));
+    let dns_names = cert
+        .subject_alt_names()
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.dnsname().map(|x| x.to_string()));
+    common_names.chain(dns_names).collect()
+}
+
+/// The commands understood by cert_query.
+enum CertCommand {
+    Serial,
+    Issuer,
+    Subject,
+    NotBefore,
+    NotAfter,
+    FprSha1,
+    FprSha256,
+    Image,
+    Hostnames,
+    AlgorithmName,
+    PublicKeyAlgorithmName,
+    Modulus,
+    Exponent,
+    KeySize,
+}
+
+impl FromStr for CertCommand {
+    type Err = FunctionErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "serial" => Self::Serial,
+            "issuer" => Self::Issuer,
+            "subject" => Self::Subject,
+            "not-before" => Self::NotBefore,
+            "not-after" => Self::NotAfter,
+            "fpr-sha-1" => Self::FprSha1,
+            "fpr-sha-256" => Self::FprSha256,
+            "image" => Self::Image,
+            "hostnames" => Self::Hostnames,
+            "algorithm-name" | "signature-algorithm-name" => Self::AlgorithmName,
+            "public-key-algorithm-name" => Self::PublicKeyAlgorithmName,
+            "modulus" => Self::Modulus,
+            "exponent" => Self::Exponent,
+            "key-size" => Self::KeySize,
+            _ => {
+                return Err(FunctionErrorKind::WrongArgument(format!(
+                    "Unknown cert_query command {s}"
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Default)]
+struct Handles {
+    certs: HashMap<usize, X509>,
+    last_id: usize,
+}
+
+/// Converts an isotime argument to a unix timestamp.
+fn timestamp(time: &str) -> Result<i64, FunctionErrorKind> {
+    chrono::NaiveDateTime::parse_from_str(time, "%Y%m%dT%H%M%S")
+        .map(|x| x.and_utc().timestamp())
+        .map_err(|_| FunctionErrorKind::wrong_argument("time", "isotime", time))
+}
+
+/// Holds the certificates opened by a script.
+#[derive(Default)]
+pub struct NaslCerts {
+    handles: RwLock<Handles>,
+}
+
+impl NaslCerts {
+    fn add(&self, cert: X509) -> usize {
+        let mut handles = self.handles.write().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.certs.insert(id, cert);
+        id
+    }
+
+    /// Returns a copy of the certificate with the given id.
+    pub(crate) fn get(&self, id: usize) -> Result<X509, FunctionErrorKind> {
+        self.handles
+            .read()
+            .unwrap()
+            .certs
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(format!("Unknown certificate object {id}"), None)
+            })
+    }
+
+    /// Collects certificates given as object ids returned by cert_open or as DER or PEM encoded
+    /// data. PEM encoded data may contain multiple certificates.
+    fn collect(&self, value: &NaslValue, certs: &mut Vec<X509>) -> Result<(), FunctionErrorKind> {
+        match value {
+            NaslValue::Array(values) => {
+                for value in values {
+                    self.collect(value, certs)?;
+                }
+            }
+            NaslValue::Number(id) => certs.push(self.get(*id as usize)?),
+            NaslValue::String(_) | NaslValue::Data(_) => {
+                let data: Vec<u8> = value.into();
+                if data.starts_with(b"-----BEGIN") {
+                    certs.extend(X509::stack_from_pem(&data).map_err(openssl_error)?);
+                } else {
+                    certs.push(parse_cert(&data)?);
+                }
+            }
+            _ => return Err(("certificate", "data, string, number or array", value).into()),
+        }
+        Ok(())
+    }
+
+    /// Returns the first certificate given like for collect.
+    fn first(&self, value: &NaslValue, name: &str) -> Result<X509, FunctionErrorKind> {
+        let mut certs = vec![];
+        self.collect(value, &mut certs)?;
+        certs
+            .into_iter()
+            .next()
+            .ok_or_else(|| FunctionErrorKind::WrongArgument(format!("{name} is missing")))
+    }
+
+    /// Create a certificate object.
+    ///
+    /// Takes a DER or PEM encoded certificate as unnamed argument and returns the id of the
+    /// object, which can be passed to cert_query. NULL is returned when the certificate cannot
+    /// be parsed.
+    #[nasl_function]
+    fn cert_open(&self, cert: &NaslValue) -> NaslValue {
+        let data: Vec<u8> = cert.into();
+        match parse_cert(&data) {
+            Ok(cert) => NaslValue::Number(self.add(cert) as i64),
+            Err(e) => {
+                tracing::debug!(%e, "cert_open failed");
+                NaslValue::Null
+            }
+        }
+    }
+
+    /// Release a certificate object.
+    ///
+    /// Takes the id returned by cert_open as unnamed argument.
+    #[nasl_function]
+    fn cert_close(&self, cert: usize) -> Result<NaslValue, FunctionErrorKind> {
+        match self.handles.write().unwrap().certs.remove(&cert) {
+            Some(_) => Ok(NaslValue::Null),
+            None => Err(FunctionErrorKind::Diagnostic(
+                format!("Unknown certificate object {cert}"),
+                None,
+            )),
+        }
+    }
+
+    /// Query information from a certificate object.
+    ///
+    /// Takes the id returned by cert_open and a command as unnamed arguments. The optional named
+    /// argument idx selects an alternative name for the issuer and subject commands.
+    /// The commands are:
+    /// - serial: the serial number as hex string
+    /// - issuer: the issuer as RFC 2253 string, with idx > 0 the alternative names of the issuer
+    /// - subject: the subject as RFC 2253 string, with idx > 0 the alternative names of the subject
+    /// - not-before: start of the validity period as isotime
+    /// - not-after: end of the validity period as isotime
+    /// - fpr-sha-1: the SHA-1 fingerprint as hex string
+    /// - fpr-sha-256: the SHA-256 fingerprint as hex string
+    /// - image: the DER encoded certificate
+    /// - hostnames: array of the common name and the DNS alternative names of the subject
+    /// - algorithm-name, signature-algorithm-name: the name of the signature algorithm
+    /// - public-key-algorithm-name: the name of the public key algorithm
+    /// - modulus: the modulus of a RSA public key
+    /// - exponent: the exponent of a RSA public key
+    /// - key-size: the size of the public key in bits
+    #[nasl_function(named(idx))]
+    fn cert_query(
+        &self,
+        cert: usize,
+        command: &str,
+        idx: Option<usize>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let command = CertCommand::from_str(command)?;
+        let cert = self.get(cert)?;
+        let idx = idx.unwrap_or_default();
+        let alt_name = |names| {
+            alt_names(names)
+                .into_iter()
+                .nth(idx - 1)
+                .map_or(NaslValue::Null, NaslValue::String)
+        };
+        let fingerprint = |digest| {
+            cert.digest(digest)
+                .map(|x| NaslValue::String(hex::encode_upper(x)))
+                .map_err(openssl_error)
+        };
+        let rsa = || {
+            cert.public_key()
+                .and_then(|key| key.rsa())
+                .map_err(|_| FunctionErrorKind::Diagnostic("Not a RSA key".to_string(), None))
+        };
+        Ok(match command {
+            CertCommand::Serial => cert
+                .serial_number()
+                .to_bn()
+                .and_then(|x| x.to_hex_str().map(|x| NaslValue::String(x.to_string())))
+                .map_err(openssl_error)?,
+            CertCommand::Issuer if idx == 0 => NaslValue::String(rfc2253(cert.issuer_name())),
+            CertCommand::Issuer => alt_name(cert.issuer_alt_names()),
+            CertCommand::Subject if idx == 0 => NaslValue::String(rfc2253(cert.subject_name())),
+            CertCommand::Subject => alt_name(cert.subject_alt_names()),
+            CertCommand::NotBefore => NaslValue::String(isotime(cert.not_before())?),
+            CertCommand::NotAfter => NaslValue::String(isotime(cert.not_after())?),
+            CertCommand::FprSha1 => fingerprint(MessageDigest::sha1())?,
+            CertCommand::FprSha256 => fingerprint(MessageDigest::sha256())?,
+            CertCommand::Image => NaslValue::Data(cert.to_der().map_err(openssl_error)?),
+            CertCommand::Hostnames => NaslValue::Array(
+                hostnames(&cert)
+                    .into_iter()
+                    .map(NaslValue::String)
+                    .collect(),
+            ),
+            CertCommand::AlgorithmName => {
+                NaslValue::String(cert.signature_algorithm().object().to_string())
+            }
+            CertCommand::PublicKeyAlgorithmName => {
+                let key = cert.public_key().map_err(openssl_error)?;
+                Nid::from_raw(key.id().as_raw())
+                    .long_name()
+                    .map(|x| NaslValue::String(x.to_string()))
+                    .unwrap_or(NaslValue::Null)
+            }
+            CertCommand::Modulus => NaslValue::Data(rsa()?.n().to_vec()),
+            CertCommand::Exponent => NaslValue::Data(rsa()?.e().to_vec()),
+            CertCommand::KeySize => {
+                NaslValue::Number(cert.public_key().map_err(openssl_error)?.bits() as i64)
+            }
+        })
+    }
+
+    /// Validate a certificate chain.
+    ///
+    /// Takes the following named arguments:
+    /// - chain: the presented chain starting with the leaf certificate. Either an array or a
+    ///   single value of certificate objects returned by cert_open or DER or PEM encoded
+    ///   certificates.
+    /// - hostname: optional hostname or IP address the leaf certificate must be valid for.
+    /// - cafile: optional path to a PEM file containing the trusted certificates.
+    /// - trusted: optional trusted certificates given the same way as the chain.
+    /// - time: optional isotime to check the validity periods against, defaults to now.
+    ///
+    /// When neither cafile nor trusted is given, the default trust store of the system is used.
+    /// Returns a dict with the boolean `valid`, an array `errors` with the kind of each error
+    /// found and an array `messages` with a description of each error. The kinds of errors
+    /// are expired, not-yet-valid, untrusted-root, self-signed, invalid-signature, revoked,
+    /// invalid-ca, invalid-purpose, invalid-chain and hostname-mismatch.
+    #[nasl_function(named(chain, hostname, cafile, trusted, time))]
+    fn cert_verify_chain(
+        &self,
+        chain: &NaslValue,
+        hostname: Option<&str>,
+        cafile: Option<&str>,
+        trusted: Option<&NaslValue>,
+        time: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let mut certs = vec![];
+        self.collect(chain, &mut certs)?;
+        let mut trusted_certs = vec![];
+        if let Some(trusted) = trusted {
+            self.collect(trusted, &mut trusted_certs)?;
+        }
+        verify::verify_chain(
+            &certs,
+            verify::VerifyOptions {
+                hostname,
+                cafile,
+                trusted: trusted_certs,
+                time: time.map(timestamp).transpose()?,
+            },
+        )
+    }
+
+    /// Open a PKCS#12 container (.p12 or .pfx file).
+    ///
+    /// Takes the DER encoded container as unnamed argument and the optional named argument
+    /// password, which defaults to an empty password. NULL is returned when the container cannot
+    /// be parsed or the password is wrong.
+    ///
+    /// Otherwise a dict is returned containing:
+    /// - cert: the certificate object of the private key, which can be passed to cert_query, or
+    ///   NULL
+    /// - ca: array of the certificate objects of the additional certificates
+    /// - key: the private key PEM encoded in the PKCS#8 format or NULL
+    /// - key-algorithm: the name of the algorithm of the private key
+    /// - key-size: the size of the private key in bits
+    #[nasl_function(named(password))]
+    fn pkcs12_open(
+        &self,
+        data: &NaslValue,
+        password: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let data: Vec<u8> = data.into();
+        let content = match pkcs12::parse(&data, password.unwrap_or_default()) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::debug!(%e, "pkcs12_open failed");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut result = IndexMap::new();
+        let cert = content
+            .cert
+            .map_or(NaslValue::Null, |x| NaslValue::Number(self.add(x) as i64));
+        result.insert("cert".to_string(), cert);
+        let ca = content
+            .ca
+            .into_iter()
+            .map(|x| NaslValue::Number(self.add(x) as i64))
+            .collect();
+        result.insert("ca".to_string(), NaslValue::Array(ca));
+        let (key, algorithm, size) = match content.key {
+            Some(key) => (
+                NaslValue::String(
+                    String::from_utf8_lossy(
+                        &key.private_key_to_pem_pkcs8().map_err(openssl_error)?,
+                    )
+                    .to_string(),
+                ),
+                pkcs12::key_algorithm(&key).map_or(NaslValue::Null, |x| x.into()),
+                NaslValue::Number(key.bits() as i64),
+            ),
+            None => (NaslValue::Null, NaslValue::Null, NaslValue::Null),
+        };
+        result.insert("key".to_string(), key);
+        result.insert("key-algorithm".to_string(), algorithm);
+        result.insert("key-size".to_string(), size);
+        Ok(NaslValue::Dict(result))
+    }
+
+    /// Verify a PKCS#7 / CMS signature.
+    ///
+    /// Takes the signed data as unnamed argument, either DER or PEM encoded or as S/MIME
+    /// message, and the named arguments:
+    /// - content: optional content of a detached signature. The content of a S/MIME
+    ///   multipart/signed message is used when omitted.
+    /// - certs: optional additional certificates used to find the signers, given as object ids
+    ///   or DER or PEM encoded data like the chain of cert_verify_chain.
+    /// - trusted: optional trusted certificates given the same way as certs. When omitted, the
+    ///   default trust store of the system is used.
+    /// - time: optional isotime to check the validity periods against, defaults to now.
+    /// - signature_only: when TRUE the certificates of the signers are not validated.
+    ///
+    /// Returns a dict with the boolean `valid`, the signed `content`, which is NULL when the
+    /// verification failed, an array `signers` with the certificate objects of the signers and
+    /// `error` describing why the verification failed.
+    #[nasl_function(named(content, certs, trusted, time, signature_only))]
+    fn cms_verify(
+        &self,
+        data: &NaslValue,
+        content: Option<&NaslValue>,
+        certs: Option<&NaslValue>,
+        trusted: Option<&NaslValue>,
+        time: Option<&str>,
+        signature_only: Option<bool>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let data: Vec<u8> = data.into();
+        let signed = cms::parse(&data)?;
+        let content: Option<Vec<u8>> = content.map(|x| x.into());
+        let mut extra_certs = vec![];
+        if let Some(certs) = certs {
+            self.collect(certs, &mut extra_certs)?;
+        }
+        let mut trusted_certs = vec![];
+        if let Some(trusted) = trusted {
+            self.collect(trusted, &mut trusted_certs)?;
+        }
+        let verification = cms::verify(
+            signed,
+            cms::VerifyOptions {
+                content: content.as_deref(),
+                certs: extra_certs,
+                trusted: trusted_certs,
+                time: time.map(timestamp).transpose()?,
+                signature_only: signature_only.unwrap_or_default(),
+            },
+        )?;
+        let mut result = IndexMap::new();
+        let valid = verification.error.is_none();
+        result.insert("valid".to_string(), NaslValue::Boolean(valid));
+        let content = if valid {
+            NaslValue::Data(verification.content)
+        } else {
+            NaslValue::Null
+        };
+        result.insert("content".to_string(), content);
+        let signers = verification
+            .signers
+            .into_iter()
+            .map(|x| NaslValue::Number(self.add(x) as i64))
+            .collect();
+        result.insert("signers".to_string(), NaslValue::Array(signers));
+        result.insert(
+            "error".to_string(),
+            verification
+                .error
+                .map_or(NaslValue::Null, NaslValue::String),
+        );
+        Ok(NaslValue::Dict(result))
+    }
+
+    /// Create an OCSP request.
+    ///
+    /// Takes the certificate to check as unnamed argument and the named arguments issuer, the
+    /// certificate of its issuer, and the optional digest used to identify the certificate,
+    /// defaulting to "sha1" as most responders expect. Both certificates can be given as object
+    /// ids or DER or PEM encoded data.
+    ///
+    /// Returns the DER encoded request, which can be sent to the responder via HTTP POST with
+    /// the content type application/ocsp-request.
+    #[nasl_function(named(issuer, digest))]
+    fn ocsp_request(
+        &self,
+        cert: &NaslValue,
+        issuer: &NaslValue,
+        digest: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let cert = self.first(cert, "cert")?;
+        let issuer = self.first(issuer, "issuer")?;
+        let digest = digest.unwrap_or("sha1");
+        let digest = MessageDigest::from_name(digest)
+            .ok_or_else(|| FunctionErrorKind::wrong_argument("digest", "digest name", digest))?;
+        Ok(NaslValue::Data(ocsp::request(&cert, &issuer, digest)?))
+    }
+
+    /// Parse an OCSP response.
+    ///
+    /// Takes the DER encoded response, as returned by a responder or stapled to a TLS handshake,
+    /// as unnamed argument and the optional named arguments:
+    /// - cert: the certificate to look up the status of.
+    /// - issuer: the issuer of cert. It is also trusted to authorize the responder.
+    /// - trusted: additional trusted certificates given the same way as the chain of
+    ///   cert_verify_chain. When neither issuer nor trusted is given, the default trust store
+    ///   of the system is used.
+    ///
+    /// Returns a dict containing:
+    /// - response-status: successful, malformedRequest, internalError, tryLater, sigRequired or
+    ///   unauthorized
+    /// - produced-at: the isotime the response was signed
+    /// - verified: TRUE when the signature is valid and the responder is authorized
+    /// - error: the reason why the verification failed or NULL
+    /// - responses: array of dicts with the serial, cert-status, revocation-time,
+    ///   revocation-reason, this-update and next-update of each certificate within the response
+    /// - cert-status: good, revoked or unknown for cert or NULL when the response does not
+    ///   contain it
+    /// - revocation-time, revocation-reason, this-update and next-update of cert
+    #[nasl_function(named(cert, issuer, trusted))]
+    fn ocsp_response_parse(
+        &self,
+        data: &NaslValue,
+        cert: Option<&NaslValue>,
+        issuer: Option<&NaslValue>,
+        trusted: Option<&NaslValue>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let data: Vec<u8> = data.into();
+        let response = ocsp::parse(&data)?;
+        let mut trusted_certs = vec![];
+        if let Some(trusted) = trusted {
+            self.collect(trusted, &mut trusted_certs)?;
+        }
+        let issuer = issuer.map(|x| self.first(x, "issuer")).transpose()?;
+        trusted_certs.extend(issuer.clone());
+        let cert = cert.map(|x| self.first(x, "cert")).transpose()?;
+
+        let optional = |x: Option<String>| x.map_or(NaslValue::Null, NaslValue::String);
+        let single_fields = |single: Option<&ocsp::SingleResponse>| {
+            vec![
+                (
+                    "cert-status",
+                    single.map_or(NaslValue::Null, |x| x.status.into()),
+                ),
+                (
+                    "revocation-time",
+                    optional(single.and_then(|x| x.revocation_time.clone())),
+                ),
+                (
+                    "revocation-reason",
+                    optional(single.and_then(|x| x.revocation_reason.map(|x| x.to_string()))),
+                ),
+                (
+                    "this-update",
+                    optional(single.map(|x| x.this_update.clone())),
+                ),
+                (
+                    "next-update",
+                    optional(single.and_then(|x| x.next_update.clone())),
+                ),
+            ]
+        };
+
+        let mut result = IndexMap::new();
+        result.insert("response-status".to_string(), response.status.into());
+        result.insert(
+            "produced-at".to_string(),
+            optional(response.produced_at.clone()),
+        );
+        let error = response.verify(trusted_certs)?;
+        result.insert("verified".to_string(), NaslValue::Boolean(error.is_none()));
+        result.insert("error".to_string(), optional(error));
+        let mut responses = vec![];
+        let mut matching = None;
+        for single in &response.responses {
+            let mut entry: IndexMap<String, NaslValue> = single_fields(Some(single))
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect();
+            entry.insert("serial".to_string(), NaslValue::String(single.serial()?));
+            responses.push(NaslValue::Dict(entry));
+            if let (Some(cert), Some(issuer), None) = (&cert, &issuer, matching) {
+                if single.matches(cert, issuer)? {
+                    matching = Some(single);
+                }
+            }
+        }
+        result.insert("responses".to_string(), NaslValue::Array(responses));
+        result.extend(
+            single_fields(matching)
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v)),
+        );
+        Ok(NaslValue::Dict(result))
+    }
+}
+
+function_set! {
+    NaslCerts,
+    sync_stateful,
+    (
+        (NaslCerts::cert_open, "cert_open"),
+        (NaslCerts::cert_close, "cert_close"),
+        (NaslCerts::cert_query, "cert_query"),
+        (NaslCerts::cert_verify_chain, "cert_verify_chain"),
+        (NaslCerts::pkcs12_open, "pkcs12_open"),
+        (NaslCerts::cms_verify, "cms_verify"),
+        (NaslCerts::ocsp_request, "ocsp_request"),
+        (NaslCerts::ocsp_response_parse, "ocsp_response_parse"),
+    )
+}
@@ -0,0 +1,261 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Construction of OCSP requests and parsing of OCSP responses as described in RFC 6960.
+
+use openssl::{
+    asn1::Asn1Object,
+    bn::BigNum,
+    hash::{hash, MessageDigest},
+    ocsp::{OcspCertId, OcspFlag, OcspRequest, OcspResponse, OcspResponseStatus},
+    stack::Stack,
+    x509::X509,
+};
+
+use crate::nasl::{
+    builtin::asn1::der::{self, Class, Element},
+    prelude::*,
+};
+
+use super::{openssl_error, verify::trust_store};
+
+// Universal tag numbers as defined in X.680
+const ENUMERATED: u64 = 10;
+const GENERALIZED_TIME: u64 = 24;
+
+/// Creates a DER encoded OCSP request for the certificate issued by issuer.
+pub(super) fn request(
+    cert: &X509,
+    issuer: &X509,
+    digest: MessageDigest,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let id = OcspCertId::from_cert(digest, cert, issuer).map_err(openssl_error)?;
+    let mut request = OcspRequest::new().map_err(openssl_error)?;
+    request.add_id(id).map_err(openssl_error)?;
+    request.to_der().map_err(openssl_error)
+}
+
+/// Returns the name of the status of a response as defined in RFC 6960.
+fn response_status(status: OcspResponseStatus) -> &'static str {
+    match status {
+        OcspResponseStatus::SUCCESSFUL => "successful",
+        OcspResponseStatus::MALFORMED_REQUEST => "malformedRequest",
+        OcspResponseStatus::INTERNAL_ERROR => "internalError",
+        OcspResponseStatus::TRY_LATER => "tryLater",
+        OcspResponseStatus::SIG_REQUIRED => "sigRequired",
+        OcspResponseStatus::UNAUTHORIZED => "unauthorized",
+        _ => "unknown",
+    }
+}
+
+/// Returns the name of a CRL reason as defined in RFC 5280.
+fn revocation_reason(reason: i64) -> &'static str {
+    match reason {
+        0 => "unspecified",
+        1 => "keyCompromise",
+        2 => "cACompromise",
+        3 => "affiliationChanged",
+        4 => "superseded",
+        5 => "cessationOfOperation",
+        6 => "certificateHold",
+        8 => "removeFromCRL",
+        9 => "privilegeWithdrawn",
+        10 => "aACompromise",
+        _ => "unknown",
+    }
+}
+
+fn malformed() -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic("Malformed OCSP response".to_string(), None)
+}
+
+fn child<'a, 'b>(
+    element: &'b Element<'a>,
+    index: usize,
+) -> Result<&'b Element<'a>, FunctionErrorKind> {
+    element.children.get(index).ok_or_else(malformed)
+}
+
+/// Converts a GeneralizedTime into the isotime format used by NASL.
+fn generalized_time(element: &Element) -> Result<String, FunctionErrorKind> {
+    let time = std::str::from_utf8(element.content)
+        .ok()
+        .and_then(|x| x.get(..14))
+        .and_then(|x| chrono::NaiveDateTime::parse_from_str(x, "%Y%m%d%H%M%S").ok())
+        .ok_or_else(malformed)?;
+    Ok(time.format("%Y%m%dT%H%M%S").to_string())
+}
+
+/// The status of a single certificate within a response.
+pub(super) struct SingleResponse {
+    hash_algorithm: String,
+    issuer_name_hash: Vec<u8>,
+    issuer_key_hash: Vec<u8>,
+    serial: Vec<u8>,
+    /// good, revoked or unknown
+    pub status: &'static str,
+    pub revocation_time: Option<String>,
+    pub revocation_reason: Option<&'static str>,
+    pub this_update: String,
+    pub next_update: Option<String>,
+}
+
+impl SingleResponse {
+    fn parse(element: &Element) -> Result<Self, FunctionErrorKind> {
+        let id = child(element, 0)?;
+        let hash_algorithm = der::oid(child(child(id, 0)?, 0)?.content).ok_or_else(malformed)?;
+        let status = child(element, 1)?;
+        let (status, revocation_time, revocation_reason) = match (status.class, status.tag) {
+            (Class::Context, 0) => ("good", None, None),
+            (Class::Context, 1) => {
+                let reason = status
+                    .children
+                    .get(1)
+                    .and_then(|x| x.children.first())
+                    .filter(|x| x.tag == ENUMERATED)
+                    .and_then(|x| der::integer(x.content))
+                    .map(revocation_reason);
+                (
+                    "revoked",
+                    Some(generalized_time(child(status, 0)?)?),
+                    reason,
+                )
+            }
+            _ => ("unknown", None, None),
+        };
+        let next_update = element
+            .children
+            .iter()
+            .skip(3)
+            .find(|x| x.class == Class::Context && x.tag == 0)
+            .map(|x| generalized_time(child(x, 0)?))
+            .transpose()?;
+        Ok(Self {
+            hash_algorithm,
+            issuer_name_hash: child(id, 1)?.content.to_vec(),
+            issuer_key_hash: child(id, 2)?.content.to_vec(),
+            serial: child(id, 3)?.content.to_vec(),
+            status,
+            revocation_time,
+            revocation_reason,
+            this_update: generalized_time(child(element, 2)?)?,
+            next_update,
+        })
+    }
+
+    /// Returns the serial number of the certificate as hex string.
+    pub fn serial(&self) -> Result<String, FunctionErrorKind> {
+        BigNum::from_slice(&self.serial)
+            .and_then(|x| x.to_hex_str().map(|x| x.to_string()))
+            .map_err(openssl_error)
+    }
+
+    /// Checks if the response is about the certificate issued by issuer.
+    pub fn matches(&self, cert: &X509, issuer: &X509) -> Result<bool, FunctionErrorKind> {
+        let serial = cert
+            .serial_number()
+            .to_bn()
+            .map_err(openssl_error)?
+            .to_vec();
+        let response_serial = BigNum::from_slice(&self.serial)
+            .map_err(openssl_error)?
+            .to_vec();
+        if serial != response_serial {
+            return Ok(false);
+        }
+        let Some(digest) = Asn1Object::from_str(&self.hash_algorithm)
+            .ok()
+            .and_then(|x| MessageDigest::from_nid(x.nid()))
+        else {
+            return Ok(false);
+        };
+        let name = issuer.subject_name().to_der().map_err(openssl_error)?;
+        let name_hash = hash(digest, &name).map_err(openssl_error)?;
+        // the key hash covers the value of the BIT STRING within the SubjectPublicKeyInfo
+        let key = issuer
+            .public_key()
+            .and_then(|x| x.public_key_to_der())
+            .map_err(openssl_error)?;
+        let key = der::parse(&key).map_err(|_| malformed())?;
+        let bits = key
+            .first()
+            .and_then(|x| x.children.get(1))
+            .and_then(|x| x.content.get(1..))
+            .ok_or_else(malformed)?;
+        let key_hash = hash(digest, bits).map_err(openssl_error)?;
+        Ok(*name_hash == *self.issuer_name_hash && *key_hash == *self.issuer_key_hash)
+    }
+}
+
+/// A parsed OCSP response.
+pub(super) struct Response {
+    /// The status of the response as defined in RFC 6960
+    pub status: &'static str,
+    pub produced_at: Option<String>,
+    pub responses: Vec<SingleResponse>,
+    response: OcspResponse,
+}
+
+/// Parses a DER encoded OCSP response.
+pub(super) fn parse(data: &[u8]) -> Result<Response, FunctionErrorKind> {
+    let response = OcspResponse::from_der(data).map_err(|e| {
+        FunctionErrorKind::Diagnostic(format!("Unable to parse OCSP response: {e}"), None)
+    })?;
+    let status = response_status(response.status());
+    if response.status() != OcspResponseStatus::SUCCESSFUL {
+        return Ok(Response {
+            status,
+            produced_at: None,
+            responses: vec![],
+            response,
+        });
+    }
+    // OCSPResponse -> responseBytes -> ResponseBytes -> response
+    let elements = der::parse(data).map_err(|_| malformed())?;
+    let outer = elements.first().ok_or_else(malformed)?;
+    let bytes = child(child(child(outer, 1)?, 0)?, 1)?;
+    let basic = der::parse(bytes.content).map_err(|_| malformed())?;
+    let data = child(basic.first().ok_or_else(malformed)?, 0)?;
+    // the version and the responder id are context specific
+    let mut fields = data
+        .children
+        .iter()
+        .skip_while(|x| x.class == Class::Context);
+    let produced_at = fields
+        .next()
+        .filter(|x| x.class == Class::Universal && x.tag == GENERALIZED_TIME)
+        .ok_or_else(malformed)?;
+    let responses = fields
+        .next()
+        .ok_or_else(malformed)?
+        .children
+        .iter()
+        .map(SingleResponse::parse)
+        .collect::<Result<_, _>>()?;
+    Ok(Response {
+        status,
+        produced_at: Some(generalized_time(produced_at)?),
+        responses,
+        response,
+    })
+}
+
+impl Response {
+    /// Verifies the signature of the response and that the responder is authorized by one of
+    /// the trusted certificates.
+    ///
+    /// Returns the reason why the verification failed.
+    pub fn verify(&self, trusted: Vec<X509>) -> Result<Option<String>, FunctionErrorKind> {
+        let basic = match self.response.basic() {
+            Ok(basic) => basic,
+            Err(_) => return Ok(Some(format!("response status is {}", self.status))),
+        };
+        let store = trust_store(None, trusted)?.build();
+        let certs = Stack::new().map_err(openssl_error)?;
+        Ok(basic
+            .verify(&certs, &store, OcspFlag::empty())
+            .err()
+            .map(|e| e.to_string()))
+    }
+}
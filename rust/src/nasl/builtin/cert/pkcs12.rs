@@ -0,0 +1,65 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Parsing of PKCS#12 containers.
+
+use std::sync::OnceLock;
+
+use openssl::{
+    nid::Nid,
+    pkcs12::Pkcs12,
+    pkey::{PKey, Private},
+    provider::Provider,
+    x509::X509,
+};
+
+use crate::nasl::prelude::*;
+
+use super::openssl_error;
+
+/// The content of a PKCS#12 container.
+pub(super) struct Content {
+    pub key: Option<PKey<Private>>,
+    pub cert: Option<X509>,
+    pub ca: Vec<X509>,
+}
+
+/// Loads the legacy provider of OpenSSL.
+///
+/// Containers created by older tools are usually encrypted with RC2 or 3DES, which are only
+/// available in the legacy provider.
+fn load_legacy_provider() {
+    static PROVIDERS: OnceLock<Option<(Provider, Provider)>> = OnceLock::new();
+    PROVIDERS.get_or_init(|| {
+        // loading a provider disables the implicitly loaded default provider
+        match (
+            Provider::load(None, "legacy"),
+            Provider::load(None, "default"),
+        ) {
+            (Ok(legacy), Ok(default)) => Some((legacy, default)),
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::debug!(%e, "unable to load the legacy provider");
+                None
+            }
+        }
+    });
+}
+
+/// Decrypts a DER encoded PKCS#12 container.
+pub(super) fn parse(data: &[u8], password: &str) -> Result<Content, FunctionErrorKind> {
+    load_legacy_provider();
+    let parsed = Pkcs12::from_der(data)
+        .and_then(|x| x.parse2(password))
+        .map_err(openssl_error)?;
+    Ok(Content {
+        key: parsed.pkey,
+        cert: parsed.cert,
+        ca: parsed.ca.into_iter().flatten().collect(),
+    })
+}
+
+/// Returns the long name of the algorithm of a key.
+pub(super) fn key_algorithm(key: &PKey<Private>) -> Option<&'static str> {
+    Nid::from_raw(key.id().as_raw()).long_name().ok()
+}
@@ -0,0 +1,228 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Validation of certificate chains.
+
+use std::net::IpAddr;
+
+use indexmap::IndexMap;
+
+use openssl::{
+    asn1::Asn1Time,
+    ssl::SslFiletype,
+    stack::Stack,
+    x509::{
+        store::{X509Lookup, X509StoreBuilder},
+        verify::X509VerifyFlags,
+        X509StoreContext, X509,
+    },
+};
+
+use crate::nasl::prelude::*;
+
+use super::{hostnames, openssl_error};
+
+// Error codes of X509_verify_cert as defined in openssl/x509_vfy.h
+const X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT: i32 = 2;
+const X509_V_ERR_CERT_SIGNATURE_FAILURE: i32 = 7;
+const X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT: i32 = 18;
+const X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN: i32 = 19;
+const X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY: i32 = 20;
+const X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE: i32 = 21;
+const X509_V_ERR_CERT_REVOKED: i32 = 23;
+const X509_V_ERR_INVALID_CA: i32 = 24;
+const X509_V_ERR_INVALID_PURPOSE: i32 = 26;
+
+/// Maps an error code of X509_verify_cert to the error reported to the script.
+fn error_kind(code: i32) -> &'static str {
+    match code {
+        X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT
+        | X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN
+        | X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY
+        | X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE => "untrusted-root",
+        X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT => "self-signed",
+        X509_V_ERR_CERT_SIGNATURE_FAILURE => "invalid-signature",
+        X509_V_ERR_CERT_REVOKED => "revoked",
+        X509_V_ERR_INVALID_CA => "invalid-ca",
+        X509_V_ERR_INVALID_PURPOSE => "invalid-purpose",
+        _ => "invalid-chain",
+    }
+}
+
+/// Matches a DNS name of a certificate, which may start with a wildcard label, against a
+/// hostname.
+fn dns_name_matches(pattern: &str, hostname: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_lowercase();
+    let hostname = hostname.trim_end_matches('.').to_lowercase();
+    match pattern.strip_prefix("*.") {
+        // the wildcard must match exactly one label and must not cover a public suffix
+        Some(suffix) if suffix.contains('.') => hostname
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == suffix),
+        _ => pattern == hostname,
+    }
+}
+
+/// Checks if the certificate is valid for the given hostname or IP address.
+///
+/// As described in RFC 6125 the common name is only used when there are no DNS names within
+/// the subject alternative names.
+fn hostname_matches(cert: &X509, hostname: &str) -> bool {
+    let alt_names = cert.subject_alt_names();
+    if let Ok(ip) = hostname.parse::<IpAddr>() {
+        let ip = match ip {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        return alt_names
+            .into_iter()
+            .flatten()
+            .any(|name| name.ipaddress() == Some(&ip));
+    }
+    let dns_names = alt_names
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.dnsname().map(|x| x.to_string()))
+        .collect::<Vec<_>>();
+    let candidates = if dns_names.is_empty() {
+        hostnames(cert)
+    } else {
+        dns_names
+    };
+    candidates.iter().any(|x| dns_name_matches(x, hostname))
+}
+
+/// Creates a store of the trusted certificates.
+///
+/// The certificates are loaded from cafile and added from trusted. When neither is given, the
+/// default trust store of the system is used.
+pub(super) fn trust_store(
+    cafile: Option<&str>,
+    trusted: Vec<X509>,
+) -> Result<X509StoreBuilder, FunctionErrorKind> {
+    let mut store = X509StoreBuilder::new().map_err(openssl_error)?;
+    match cafile {
+        Some(cafile) => store
+            .add_lookup(X509Lookup::file())
+            .and_then(|lookup| lookup.load_cert_file(cafile, SslFiletype::PEM))
+            .map_err(|e| {
+                FunctionErrorKind::Diagnostic(format!("Unable to load {cafile}: {e}"), None)
+            })?,
+        None if trusted.is_empty() => store.set_default_paths().map_err(openssl_error)?,
+        None => {}
+    }
+    for cert in trusted {
+        store.add_cert(cert).map_err(openssl_error)?;
+    }
+    Ok(store)
+}
+
+/// The trust store and checks used to validate a chain.
+pub(super) struct VerifyOptions<'a> {
+    /// The hostname or IP address the leaf certificate must be valid for.
+    pub hostname: Option<&'a str>,
+    /// Path to a PEM file containing the trusted certificates.
+    pub cafile: Option<&'a str>,
+    /// Additional trusted certificates.
+    pub trusted: Vec<X509>,
+    /// Point in time as unix timestamp to check the validity periods against, defaults to now.
+    pub time: Option<i64>,
+}
+
+/// Validates a chain, starting with the leaf certificate.
+///
+/// Returns a dict with the key `valid`, and the arrays `errors` containing the kinds of errors
+/// found and `messages` containing a description of each error.
+pub(super) fn verify_chain(
+    chain: &[X509],
+    options: VerifyOptions,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let (leaf, intermediates) = chain.split_first().ok_or_else(|| {
+        FunctionErrorKind::WrongArgument("the chain must contain a certificate".to_string())
+    })?;
+    let mut errors: Vec<(&'static str, String)> = vec![];
+
+    let time = match options.time {
+        Some(x) => Asn1Time::from_unix(x),
+        None => Asn1Time::days_from_now(0),
+    }
+    .map_err(openssl_error)?;
+    for (depth, cert) in chain.iter().enumerate() {
+        if cert.not_after() < time {
+            errors.push((
+                "expired",
+                format!("certificate at depth {depth} expired {}", cert.not_after()),
+            ));
+        } else if cert.not_before() > time {
+            errors.push((
+                "not-yet-valid",
+                format!(
+                    "certificate at depth {depth} is not valid before {}",
+                    cert.not_before()
+                ),
+            ));
+        }
+    }
+
+    let mut store = trust_store(options.cafile, options.trusted)?;
+    // the validity periods are checked above, so that they do not hide other errors
+    store
+        .set_flags(X509VerifyFlags::NO_CHECK_TIME)
+        .map_err(openssl_error)?;
+    let store = store.build();
+    let mut untrusted = Stack::new().map_err(openssl_error)?;
+    for cert in intermediates {
+        untrusted.push(cert.clone()).map_err(openssl_error)?;
+    }
+    let mut context = X509StoreContext::new().map_err(openssl_error)?;
+    let (verified, error, depth) = context
+        .init(&store, leaf, &untrusted, |ctx| {
+            Ok((ctx.verify_cert()?, ctx.error(), ctx.error_depth()))
+        })
+        .map_err(openssl_error)?;
+    if !verified {
+        errors.push((
+            error_kind(error.as_raw()),
+            format!("certificate at depth {depth}: {}", error.error_string()),
+        ));
+    }
+
+    if let Some(hostname) = options.hostname {
+        if !hostname_matches(leaf, hostname) {
+            errors.push((
+                "hostname-mismatch",
+                format!("certificate is not valid for {hostname}"),
+            ));
+        }
+    }
+
+    let mut result = IndexMap::new();
+    result.insert("valid".to_string(), NaslValue::Boolean(errors.is_empty()));
+    let (kinds, messages): (Vec<_>, Vec<_>) = errors
+        .into_iter()
+        .map(|(kind, message)| {
+            (
+                NaslValue::String(kind.to_string()),
+                NaslValue::String(message),
+            )
+        })
+        .unzip();
+    result.insert("errors".to_string(), NaslValue::Array(kinds));
+    result.insert("messages".to_string(), NaslValue::Array(messages));
+    Ok(NaslValue::Dict(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dns_name_matches;
+
+    #[test]
+    fn dns_names() {
+        assert!(dns_name_matches("www.example.com", "WWW.example.com."));
+        assert!(dns_name_matches("*.example.com", "www.example.com"));
+        assert!(!dns_name_matches("*.example.com", "example.com"));
+        assert!(!dns_name_matches("*.example.com", "a.www.example.com"));
+        assert!(!dns_name_matches("*.com", "example.com"));
+    }
+}
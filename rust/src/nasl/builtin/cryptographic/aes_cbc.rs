@@ -4,7 +4,7 @@
 
 use aes::{
     cipher::{
-        block_padding::{NoPadding, ZeroPadding},
+        block_padding::{NoPadding, Pkcs7, ZeroPadding},
         BlockCipher, BlockDecrypt, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit,
         KeyIvInit,
     },
@@ -15,10 +15,41 @@ use cbc::{Decryptor, Encryptor};
 use crate::function_set;
 use crate::nasl::syntax::NaslValue;
 use crate::nasl::utils::error::FunctionErrorKind;
+use crate::nasl::utils::function::utils::get_optional_named_arg;
 use crate::nasl::utils::{Context, Register};
 
 use super::{get_data, get_iv, get_key, get_len, Crypt};
 
+/// Block size of AES in bytes
+const BLOCK_SIZE: usize = 16;
+
+/// Padding of the last block
+enum Padding {
+    /// The data must be a multiple of the block size
+    None,
+    /// Each padding byte contains the number of padding bytes as described in RFC 5652
+    Pkcs7,
+    /// The last block is filled with zeroes
+    Zero,
+}
+
+/// Get the optional padding argument or error.
+fn get_padding(register: &Register) -> Result<Option<Padding>, FunctionErrorKind> {
+    let padding: Option<&str> = get_optional_named_arg(register, "padding")?;
+    padding
+        .map(|x| match x.to_lowercase().as_str() {
+            "none" => Ok(Padding::None),
+            "pkcs7" => Ok(Padding::Pkcs7),
+            "zero" => Ok(Padding::Zero),
+            _ => Err(FunctionErrorKind::wrong_argument(
+                "padding",
+                "none, pkcs7 or zero",
+                x,
+            )),
+        })
+        .transpose()
+}
+
 /// Base function for en- and decrypting Cipher Block Chaining (CBC) mode
 fn cbc<D>(register: &Register, crypt: Crypt) -> Result<NaslValue, FunctionErrorKind>
 where
@@ -28,15 +59,38 @@ where
     let key = get_key(register)?;
     let data = get_data(register)?;
     let iv = get_iv(register)?;
+    let padding = get_padding(register)?;
 
     // Mode Encrypt or Decrypt
     match crypt {
         Crypt::Encrypt => {
-            let res = Encryptor::<D>::new_from_slices(key, iv);
-            match res {
-                Ok(encryptor) => Ok(encryptor.encrypt_padded_vec_mut::<ZeroPadding>(data).into()),
-                Err(e) => Err(FunctionErrorKind::WrongArgument(e.to_string())),
+            let encryptor = Encryptor::<D>::new_from_slices(key, iv)
+                .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+            Ok(match padding.unwrap_or(Padding::Zero) {
+                Padding::None => {
+                    if data.len() % BLOCK_SIZE != 0 {
+                        return Err(FunctionErrorKind::wrong_argument(
+                            "data",
+                            &format!("a multiple of {BLOCK_SIZE} bytes without padding"),
+                            &format!("{} bytes", data.len()),
+                        ));
+                    }
+                    encryptor.encrypt_padded_vec_mut::<NoPadding>(data)
+                }
+                Padding::Pkcs7 => encryptor.encrypt_padded_vec_mut::<Pkcs7>(data),
+                Padding::Zero => encryptor.encrypt_padded_vec_mut::<ZeroPadding>(data),
             }
+            .into())
+        }
+        Crypt::Decrypt if matches!(padding, Some(Padding::Pkcs7 | Padding::Zero)) => {
+            let decryptor = Decryptor::<D>::new_from_slices(key, iv)
+                .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+            let result = match padding {
+                Some(Padding::Pkcs7) => decryptor.decrypt_padded_vec_mut::<Pkcs7>(data),
+                _ => decryptor.decrypt_padded_vec_mut::<ZeroPadding>(data),
+            };
+            // invalid padding is a regular result when emulating protocols
+            Ok(result.map_or(NaslValue::Null, |x| x.into()))
         }
         Crypt::Decrypt => {
             // length for encrypted data
@@ -70,9 +124,11 @@ where
 ///
 /// This function expects 3 named arguments key, data and iv either in a string or data type.
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
-///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
+///   By default the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
 /// - The iv must have a length of 16 bytes
+/// - The optional named argument padding is one of "zero" (default), "pkcs7" or "none". With
+///   "none" the length of the data must be a multiple of 16 bytes.
 fn aes128_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     cbc::<Aes128>(register, Crypt::Encrypt)
 }
@@ -82,9 +138,12 @@ fn aes128_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// This function expects 4 named arguments key, data and iv either in a string or data type. The
 /// len argument is a number.
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
-///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
+///   By default the padding is kept. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
 /// - The iv must have a length of 16 bytes
+/// - The optional named argument padding is one of "none" (default), "pkcs7" or "zero". With
+///   "pkcs7" or "zero" the padding is removed and len is ignored. NULL is returned if the pkcs7
+///   padding is invalid.
 fn aes128_cbc_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     cbc::<Aes128>(register, Crypt::Decrypt)
 }
@@ -93,9 +152,11 @@ fn aes128_cbc_decrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 ///
 /// This function expects 3 named arguments key, data and iv either in a string or data type.
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
-///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
+///   By default the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
 /// - The iv must have a length of 16 bytes
+/// - The optional named argument padding is one of "zero" (default), "pkcs7" or "none". With
+///   "none" the length of the data must be a multiple of 16 bytes.
 fn aes192_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     cbc::<Aes192>(register, Crypt::Encrypt)
 }
@@ -105,9 +166,12 @@ fn aes192_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// This function expects 4 named arguments key, data and iv either in a string or data type. The
 /// len argument is a number.
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
-///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
+///   By default the padding is kept. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
 /// - The iv must have a length of 16 bytes
+/// - The optional named argument padding is one of "none" (default), "pkcs7" or "zero". With
+///   "pkcs7" or "zero" the padding is removed and len is ignored. NULL is returned if the pkcs7
+///   padding is invalid.
 fn aes192_cbc_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     cbc::<Aes192>(register, Crypt::Decrypt)
 }
@@ -116,9 +180,11 @@ fn aes192_cbc_decrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 ///
 /// This function expects 3 named arguments key, data and iv either in a string or data type.
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
-///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
+///   By default the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
 /// - The iv must have a length of 16 bytes
+/// - The optional named argument padding is one of "zero" (default), "pkcs7" or "none". With
+///   "none" the length of the data must be a multiple of 16 bytes.
 fn aes256_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     cbc::<Aes256>(register, Crypt::Encrypt)
 }
@@ -128,9 +194,12 @@ fn aes256_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// This function expects 4 named arguments key, data and iv either in a string or data type. The
 /// len argument is a number.
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
-///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
+///   By default the padding is kept. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
 /// - The iv must have a length of 16 bytes
+/// - The optional named argument padding is one of "none" (default), "pkcs7" or "zero". With
+///   "pkcs7" or "zero" the padding is removed and len is ignored. NULL is returned if the pkcs7
+///   padding is invalid.
 fn aes256_cbc_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     cbc::<Aes256>(register, Crypt::Decrypt)
 }
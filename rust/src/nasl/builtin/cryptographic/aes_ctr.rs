@@ -13,7 +13,18 @@ use digest::typenum::U16;
 
 use crate::nasl::prelude::*;
 
-use super::{get_data, get_iv, get_key, get_len, Crypt};
+use super::{get_data, get_iv, get_key, get_len, get_optional_named_number, Crypt};
+
+/// Get an optional named argument that must not be negative.
+fn get_position(register: &Register, key: &str) -> Result<Option<u64>, FunctionErrorKind> {
+    get_optional_named_number(register, key)?
+        .map(|x| {
+            u64::try_from(x).map_err(|_| {
+                FunctionErrorKind::wrong_argument(key, "a positive number", &x.to_string())
+            })
+        })
+        .transpose()
+}
 
 fn ctr<D>(register: &Register, crypt: Crypt) -> Result<NaslValue, FunctionErrorKind>
 where
@@ -32,20 +43,26 @@ where
         Some(x) => x,
         None => data_len,
     };
+    let counter = get_position(register, "counter")?;
+    let offset = get_position(register, "offset")?.unwrap_or_default();
 
-    let mut cipher = ctr::Ctr64BE::<D>::new(key.into(), iv.into());
+    let mut iv: [u8; 16] = iv.try_into().map_err(|_| {
+        FunctionErrorKind::wrong_argument("iv", "16 bytes", &format!("{} bytes", iv.len()))
+    })?;
+    // the counter occupies the lower 64 bits of the counter block
+    if let Some(counter) = counter {
+        iv[8..].copy_from_slice(&counter.to_be_bytes());
+    }
+    let mut cipher = ctr::Ctr64BE::<D>::new(key.into(), &iv.into());
+    cipher
+        .try_seek(offset)
+        .map_err(|e| FunctionErrorKind::WrongArgument(format!("offset {offset}: {e}")))?;
     let mut buf = data.to_vec();
+    cipher.apply_keystream(&mut buf);
     // Mode Encrypt or Decrypt
     match crypt {
-        Crypt::Encrypt => {
-            cipher.apply_keystream(&mut buf);
-            Ok(buf.to_vec().into())
-        }
-        Crypt::Decrypt => {
-            cipher.seek(0u32);
-            cipher.apply_keystream(&mut buf);
-            Ok(buf[..len].to_vec().into())
-        }
+        Crypt::Encrypt => Ok(buf.into()),
+        Crypt::Decrypt => Ok(buf[..len].to_vec().into()),
     }
 }
 
@@ -55,7 +72,12 @@ where
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
 ///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
-/// - The iv must have a length of 16 bytes. It is used as the initial counter.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter block.
+/// - The optional named argument counter replaces the lower 64 bits of the initial counter block
+///   with the given value.
+/// - The optional named argument offset is the position within the keystream in bytes, at which
+///   the en- or decryption starts. This allows to continue a stream, e.g. offset 32 skips the
+///   first two blocks.
 fn aes128_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     ctr::<Aes128>(register, Crypt::Encrypt)
 }
@@ -67,7 +89,12 @@ fn aes128_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
 ///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
-/// - The iv must have a length of 16 bytes. It is used as the initial counter.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter block.
+/// - The optional named argument counter replaces the lower 64 bits of the initial counter block
+///   with the given value.
+/// - The optional named argument offset is the position within the keystream in bytes, at which
+///   the en- or decryption starts. This allows to continue a stream, e.g. offset 32 skips the
+///   first two blocks.
 fn aes128_ctr_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     ctr::<Aes128>(register, Crypt::Decrypt)
 }
@@ -78,7 +105,12 @@ fn aes128_ctr_decrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
 ///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
-/// - The iv must have a length of 16 bytes. It is used as the initial counter.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter block.
+/// - The optional named argument counter replaces the lower 64 bits of the initial counter block
+///   with the given value.
+/// - The optional named argument offset is the position within the keystream in bytes, at which
+///   the en- or decryption starts. This allows to continue a stream, e.g. offset 32 skips the
+///   first two blocks.
 fn aes192_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     ctr::<Aes192>(register, Crypt::Encrypt)
 }
@@ -90,7 +122,12 @@ fn aes192_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
 ///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
-/// - The iv must have a length of 16 bytes. It is used as the initial counter.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter block.
+/// - The optional named argument counter replaces the lower 64 bits of the initial counter block
+///   with the given value.
+/// - The optional named argument offset is the position within the keystream in bytes, at which
+///   the en- or decryption starts. This allows to continue a stream, e.g. offset 32 skips the
+///   first two blocks.
 fn aes192_ctr_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     ctr::<Aes192>(register, Crypt::Decrypt)
 }
@@ -101,7 +138,12 @@ fn aes192_ctr_decrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
 ///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
-/// - The iv must have a length of 16 bytes. It is used as the initial counter.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter block.
+/// - The optional named argument counter replaces the lower 64 bits of the initial counter block
+///   with the given value.
+/// - The optional named argument offset is the position within the keystream in bytes, at which
+///   the en- or decryption starts. This allows to continue a stream, e.g. offset 32 skips the
+///   first two blocks.
 fn aes256_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     ctr::<Aes256>(register, Crypt::Encrypt)
 }
@@ -113,7 +155,12 @@ fn aes256_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, Fun
 /// - The data is divided into blocks of 16 bytes. The last block is filled so it also has 16 bytes.
 ///   Currently the data is filled with zeroes. Therefore the length of the encrypted data must be
 ///   known for decryption. If no length is given, the last block is decrypted as a whole.
-/// - The iv must have a length of 16 bytes. It is used as the initial counter.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter block.
+/// - The optional named argument counter replaces the lower 64 bits of the initial counter block
+///   with the given value.
+/// - The optional named argument offset is the position within the keystream in bytes, at which
+///   the en- or decryption starts. This allows to continue a stream, e.g. offset 32 skips the
+///   first two blocks.
 fn aes256_ctr_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     ctr::<Aes256>(register, Crypt::Decrypt)
 }
@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Arithmetic on big unsigned numbers.
+//!
+//! Numbers are passed as big-endian data, as they are used within protocols and keys.
+
+use std::cmp::Ordering;
+
+use rsa::BigUint;
+
+use crate::nasl::prelude::*;
+
+/// Compares two big-endian numbers.
+///
+/// Takes the named arguments key1 and key2 and returns -1 when key1 is smaller, 0 when both are
+/// equal and 1 when key1 is larger than key2. Leading zero bytes are ignored.
+#[nasl_function(named(key1, key2))]
+fn bn_cmp(key1: &[u8], key2: &[u8]) -> i64 {
+    match BigUint::from_bytes_be(key1).cmp(&BigUint::from_bytes_be(key2)) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+/// Converts a hex string to a big-endian number.
+///
+/// Takes the hex string as unnamed argument, an odd number of digits and a leading 0x are
+/// allowed. Returns the number as data without leading zero bytes.
+#[nasl_function]
+fn bn_hex2raw(hex: &str) -> Result<Vec<u8>, FunctionErrorKind> {
+    let digits = hex.trim();
+    let digits = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .unwrap_or(digits);
+    BigUint::parse_bytes(digits.as_bytes(), 16)
+        .map(|x| x.to_bytes_be())
+        .ok_or_else(|| FunctionErrorKind::wrong_argument("hex", "hex string", hex))
+}
+
+/// Converts a big-endian number to a lowercase hex string without leading zeros.
+#[nasl_function]
+fn bn_raw2hex(data: &[u8]) -> String {
+    BigUint::from_bytes_be(data).to_str_radix(16)
+}
+
+/// Returns a random number with the given amount of bits.
+///
+/// Takes the named argument need and returns a big-endian number whose most significant bit
+/// is set. The bytes are taken from the random source of the context.
+#[nasl_function(named(need))]
+fn bn_random(context: &Context, need: usize) -> Result<Vec<u8>, FunctionErrorKind> {
+    if need == 0 {
+        return Err(FunctionErrorKind::WrongArgument(
+            "need must be greater than 0".to_string(),
+        ));
+    }
+    let mut result = context.random().bytes(need.div_ceil(8));
+    let unused = result.len() * 8 - need;
+    result[0] &= 0xff >> unused;
+    result[0] |= 0x80 >> unused;
+    Ok(result)
+}
+
+/// Calculates base ^ exponent mod modulus.
+///
+/// Takes the named arguments base, exponent and modulus as big-endian numbers and returns the
+/// result as data without leading zero bytes. It is the building block for Diffie-Hellman and
+/// RSA calculations.
+#[nasl_function(named(base, exponent, modulus))]
+fn modexp(base: &[u8], exponent: &[u8], modulus: &[u8]) -> Result<Vec<u8>, FunctionErrorKind> {
+    let modulus = BigUint::from_bytes_be(modulus);
+    if modulus == BigUint::default() {
+        return Err(FunctionErrorKind::WrongArgument(
+            "modulus must not be 0".to_string(),
+        ));
+    }
+    let base = BigUint::from_bytes_be(base);
+    let exponent = BigUint::from_bytes_be(exponent);
+    Ok(base.modpow(&exponent, &modulus).to_bytes_be())
+}
+
+pub struct Bn;
+
+function_set! {
+    Bn,
+    sync_stateless,
+    (
+        bn_cmp,
+        bn_hex2raw,
+        bn_raw2hex,
+        bn_random,
+        modexp
+    )
+}
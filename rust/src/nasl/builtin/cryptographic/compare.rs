@@ -0,0 +1,30 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Comparison of secrets.
+
+use subtle::ConstantTimeEq;
+
+use crate::nasl::prelude::*;
+
+/// Compares two secrets, e.g. a received and a calculated MAC, in constant time.
+///
+/// Takes two unnamed arguments and returns true when both contain the same bytes. The duration
+/// only depends on the length of the arguments, not on the position of the first difference.
+#[nasl_function]
+fn secure_memcmp(a: &NaslValue, b: &NaslValue) -> bool {
+    let a = Vec::<u8>::from(a);
+    let b = Vec::<u8>::from(b);
+    a.ct_eq(&b).into()
+}
+
+pub struct Compare;
+
+function_set! {
+    Compare,
+    sync_stateless,
+    (
+        secure_memcmp
+    )
+}
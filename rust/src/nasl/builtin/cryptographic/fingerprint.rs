@@ -0,0 +1,72 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Fingerprints of SSH public keys as shown by OpenSSH.
+
+use base64::{engine::general_purpose::STANDARD, engine::general_purpose::STANDARD_NO_PAD, Engine};
+use digest::Digest;
+use md5::Md5;
+use sha2::Sha256;
+
+use crate::nasl::prelude::*;
+
+/// Returns the public key blob of the wire format (RFC 4253).
+///
+/// Besides the raw blob a line of an authorized_keys or known_hosts file without options, e.g.
+/// "ssh-ed25519 AAAA... comment", is accepted.
+fn key_blob(key: &NaslValue) -> Result<Vec<u8>, FunctionErrorKind> {
+    let key = Vec::<u8>::from(key);
+    let blob = match std::str::from_utf8(&key)
+        .ok()
+        .and_then(|x| x.split_whitespace().nth(1))
+        .and_then(|x| STANDARD.decode(x).ok())
+    {
+        Some(blob) => blob,
+        None => key,
+    };
+    // the blob starts with the length prefixed key type
+    let valid = blob.len() >= 4 && {
+        let len = u32::from_be_bytes([blob[0], blob[1], blob[2], blob[3]]) as usize;
+        len > 0 && blob.len() > 4 + len && blob[4..4 + len].is_ascii()
+    };
+    if valid {
+        Ok(blob)
+    } else {
+        Err(FunctionErrorKind::wrong_argument(
+            "key",
+            "SSH public key blob",
+            &format!("{} bytes", blob.len()),
+        ))
+    }
+}
+
+/// Returns the MD5 fingerprint of an SSH public key, e.g. "MD5:c2:9c:...:70".
+///
+/// Takes the public key blob as unnamed argument.
+#[nasl_function]
+fn ssh_fingerprint_md5(key: &NaslValue) -> Result<String, FunctionErrorKind> {
+    let hash = Md5::digest(key_blob(key)?);
+    let hex: Vec<String> = hash.iter().map(|x| format!("{x:02x}")).collect();
+    Ok(format!("MD5:{}", hex.join(":")))
+}
+
+/// Returns the SHA-256 fingerprint of an SSH public key, e.g. "SHA256:1QBW...PJ4".
+///
+/// Takes the public key blob as unnamed argument.
+#[nasl_function]
+fn ssh_fingerprint_sha256(key: &NaslValue) -> Result<String, FunctionErrorKind> {
+    let hash = Sha256::digest(key_blob(key)?);
+    Ok(format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+}
+
+pub struct Fingerprint;
+
+function_set! {
+    Fingerprint,
+    sync_stateless,
+    (
+        ssh_fingerprint_md5,
+        ssh_fingerprint_sha256
+    )
+}
@@ -0,0 +1,175 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! GOST R 34.12-2015 128-bit block cipher "Kuznyechik" as specified in RFC 7801.
+
+use aes::cipher::{
+    consts::{U16, U32},
+    generic_array::GenericArray,
+    BlockCipher, Key, KeyInit, KeySizeUser,
+};
+
+/// Substitution box of Kuznyechik. It is shared with Streebog.
+pub(super) const PI: [u8; 256] = [
+    0xfc, 0xee, 0xdd, 0x11, 0xcf, 0x6e, 0x31, 0x16, 0xfb, 0xc4, 0xfa, 0xda, 0x23, 0xc5, 0x04, 0x4d,
+    0xe9, 0x77, 0xf0, 0xdb, 0x93, 0x2e, 0x99, 0xba, 0x17, 0x36, 0xf1, 0xbb, 0x14, 0xcd, 0x5f, 0xc1,
+    0xf9, 0x18, 0x65, 0x5a, 0xe2, 0x5c, 0xef, 0x21, 0x81, 0x1c, 0x3c, 0x42, 0x8b, 0x01, 0x8e, 0x4f,
+    0x05, 0x84, 0x02, 0xae, 0xe3, 0x6a, 0x8f, 0xa0, 0x06, 0x0b, 0xed, 0x98, 0x7f, 0xd4, 0xd3, 0x1f,
+    0xeb, 0x34, 0x2c, 0x51, 0xea, 0xc8, 0x48, 0xab, 0xf2, 0x2a, 0x68, 0xa2, 0xfd, 0x3a, 0xce, 0xcc,
+    0xb5, 0x70, 0x0e, 0x56, 0x08, 0x0c, 0x76, 0x12, 0xbf, 0x72, 0x13, 0x47, 0x9c, 0xb7, 0x5d, 0x87,
+    0x15, 0xa1, 0x96, 0x29, 0x10, 0x7b, 0x9a, 0xc7, 0xf3, 0x91, 0x78, 0x6f, 0x9d, 0x9e, 0xb2, 0xb1,
+    0x32, 0x75, 0x19, 0x3d, 0xff, 0x35, 0x8a, 0x7e, 0x6d, 0x54, 0xc6, 0x80, 0xc3, 0xbd, 0x0d, 0x57,
+    0xdf, 0xf5, 0x24, 0xa9, 0x3e, 0xa8, 0x43, 0xc9, 0xd7, 0x79, 0xd6, 0xf6, 0x7c, 0x22, 0xb9, 0x03,
+    0xe0, 0x0f, 0xec, 0xde, 0x7a, 0x94, 0xb0, 0xbc, 0xdc, 0xe8, 0x28, 0x50, 0x4e, 0x33, 0x0a, 0x4a,
+    0xa7, 0x97, 0x60, 0x73, 0x1e, 0x00, 0x62, 0x44, 0x1a, 0xb8, 0x38, 0x82, 0x64, 0x9f, 0x26, 0x41,
+    0xad, 0x45, 0x46, 0x92, 0x27, 0x5e, 0x55, 0x2f, 0x8c, 0xa3, 0xa5, 0x7d, 0x69, 0xd5, 0x95, 0x3b,
+    0x07, 0x58, 0xb3, 0x40, 0x86, 0xac, 0x1d, 0xf7, 0x30, 0x37, 0x6b, 0xe4, 0x88, 0xd9, 0xe7, 0x89,
+    0xe1, 0x1b, 0x83, 0x49, 0x4c, 0x3f, 0xf8, 0xfe, 0x8d, 0x53, 0xaa, 0x90, 0xca, 0xd8, 0x85, 0x61,
+    0x20, 0x71, 0x67, 0xa4, 0x2d, 0x2b, 0x09, 0x5b, 0xcb, 0x9b, 0x25, 0xd0, 0xbe, 0xe5, 0x6c, 0x52,
+    0x59, 0xa6, 0x74, 0xd2, 0xe6, 0xf4, 0xb4, 0xc0, 0xd1, 0x66, 0xaf, 0xc2, 0x39, 0x4b, 0x63, 0xb6,
+];
+
+/// Coefficients of the linear transformation.
+const L_VEC: [u8; 16] = [
+    0x94, 0x20, 0x85, 0x10, 0xc2, 0xc0, 0x01, 0xfb, 0x01, 0xc0, 0xc2, 0x10, 0x85, 0x20, 0x94, 0x01,
+];
+
+type Block = [u8; 16];
+
+/// Multiplication in GF(2^8) with the reduction polynomial x^8 + x^7 + x^6 + x + 1.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            result ^= a;
+        }
+        let high = a & 0x80 != 0;
+        a <<= 1;
+        if high {
+            a ^= 0xc3;
+        }
+        b >>= 1;
+    }
+    result
+}
+
+fn pi_inv() -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    for (i, v) in PI.iter().enumerate() {
+        inv[*v as usize] = i as u8;
+    }
+    inv
+}
+
+fn xor(a: &Block, b: &Block) -> Block {
+    let mut result = [0u8; 16];
+    for (r, (a, b)) in result.iter_mut().zip(a.iter().zip(b.iter())) {
+        *r = a ^ b;
+    }
+    result
+}
+
+fn substitute(block: &mut Block, sbox: &[u8; 256]) {
+    for b in block.iter_mut() {
+        *b = sbox[*b as usize];
+    }
+}
+
+fn linear(block: &mut Block) {
+    for _ in 0..16 {
+        let a = block
+            .iter()
+            .zip(L_VEC.iter())
+            .fold(0, |acc, (b, l)| acc ^ gf_mul(*b, *l));
+        block.copy_within(0..15, 1);
+        block[0] = a;
+    }
+}
+
+fn linear_inv(block: &mut Block) {
+    for _ in 0..16 {
+        let a = block[1..]
+            .iter()
+            .zip(L_VEC.iter())
+            .fold(block[0], |acc, (b, l)| acc ^ gf_mul(*b, *l));
+        block.copy_within(1..16, 0);
+        block[15] = a;
+    }
+}
+
+/// Kuznyechik block cipher with a 256 bit key.
+#[derive(Clone)]
+pub struct Kuznyechik {
+    round_keys: [Block; 10],
+    pi_inv: [u8; 256],
+}
+
+impl KeySizeUser for Kuznyechik {
+    type KeySize = U32;
+}
+
+impl KeyInit for Kuznyechik {
+    fn new(key: &Key<Self>) -> Self {
+        let mut round_keys = [[0u8; 16]; 10];
+        let mut k1: Block = key[..16].try_into().unwrap();
+        let mut k2: Block = key[16..].try_into().unwrap();
+        round_keys[0] = k1;
+        round_keys[1] = k2;
+        for i in 0..4 {
+            for j in 0..8 {
+                let mut c = [0u8; 16];
+                c[15] = (8 * i + j + 1) as u8;
+                linear(&mut c);
+                let mut t = xor(&k1, &c);
+                substitute(&mut t, &PI);
+                linear(&mut t);
+                let next = xor(&t, &k2);
+                k2 = k1;
+                k1 = next;
+            }
+            round_keys[2 * i + 2] = k1;
+            round_keys[2 * i + 3] = k2;
+        }
+        Self {
+            round_keys,
+            pi_inv: pi_inv(),
+        }
+    }
+}
+
+impl BlockCipher for Kuznyechik {}
+
+impl Kuznyechik {
+    fn encrypt(&self, block: &[u8]) -> Block {
+        let mut state: Block = block.try_into().unwrap();
+        for key in self.round_keys[..9].iter() {
+            state = xor(&state, key);
+            substitute(&mut state, &PI);
+            linear(&mut state);
+        }
+        xor(&state, &self.round_keys[9])
+    }
+
+    fn decrypt(&self, block: &[u8]) -> Block {
+        let mut state = xor(&block.try_into().unwrap(), &self.round_keys[9]);
+        for key in self.round_keys[..9].iter().rev() {
+            linear_inv(&mut state);
+            substitute(&mut state, &self.pi_inv);
+            state = xor(&state, key);
+        }
+        state
+    }
+}
+
+aes::cipher::impl_simple_block_encdec!(
+    Kuznyechik, U16, cipher, block,
+    encrypt: {
+        let result = cipher.encrypt(block.get_in());
+        *block.get_out() = GenericArray::from(result);
+    }
+    decrypt: {
+        let result = cipher.decrypt(block.get_in());
+        *block.get_out() = GenericArray::from(result);
+    }
+);
@@ -0,0 +1,99 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! GOST R 34.12-2015 64-bit block cipher "Magma" as specified in RFC 8891.
+
+use aes::cipher::{
+    consts::{U32, U8},
+    generic_array::GenericArray,
+    BlockCipher, Key, KeyInit, KeySizeUser,
+};
+
+/// Substitution boxes as defined in GOST R 34.12-2015 (id-tc26-gost-28147-param-Z).
+const SBOX: [[u8; 16]; 8] = [
+    [12, 4, 6, 2, 10, 5, 11, 9, 14, 8, 13, 7, 0, 3, 15, 1],
+    [6, 8, 2, 3, 9, 10, 5, 12, 1, 14, 4, 7, 11, 13, 0, 15],
+    [11, 3, 5, 8, 2, 15, 10, 13, 14, 1, 7, 4, 12, 9, 6, 0],
+    [12, 8, 2, 1, 13, 4, 15, 6, 7, 0, 10, 5, 3, 14, 9, 11],
+    [7, 15, 5, 10, 8, 1, 6, 13, 0, 9, 3, 14, 11, 4, 2, 12],
+    [5, 13, 15, 6, 9, 2, 12, 10, 11, 7, 8, 1, 4, 3, 14, 0],
+    [8, 14, 2, 5, 6, 9, 1, 12, 15, 4, 11, 0, 13, 10, 3, 7],
+    [1, 7, 14, 13, 0, 5, 8, 3, 4, 15, 10, 6, 9, 12, 11, 2],
+];
+
+fn round_fn(key: u32, a: u32) -> u32 {
+    let x = a.wrapping_add(key);
+    let mut y = 0u32;
+    for (i, sbox) in SBOX.iter().enumerate() {
+        y |= (sbox[(x >> (4 * i) & 0xf) as usize] as u32) << (4 * i);
+    }
+    y.rotate_left(11)
+}
+
+/// Magma block cipher with a 256 bit key.
+#[derive(Clone)]
+pub struct Magma {
+    keys: [u32; 8],
+}
+
+impl KeySizeUser for Magma {
+    type KeySize = U32;
+}
+
+impl KeyInit for Magma {
+    fn new(key: &Key<Self>) -> Self {
+        let mut keys = [0u32; 8];
+        for (k, chunk) in keys.iter_mut().zip(key.chunks_exact(4)) {
+            *k = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        Self { keys }
+    }
+}
+
+impl BlockCipher for Magma {}
+
+impl Magma {
+    fn key_at(&self, round: usize) -> u32 {
+        if round < 24 {
+            self.keys[round % 8]
+        } else {
+            self.keys[31 - round]
+        }
+    }
+
+    fn process(&self, block: &[u8], key_at: impl Fn(usize) -> u32) -> [u8; 8] {
+        let mut a1 = u32::from_be_bytes(block[..4].try_into().unwrap());
+        let mut a0 = u32::from_be_bytes(block[4..].try_into().unwrap());
+        for round in 0..31 {
+            let next = a1 ^ round_fn(key_at(round), a0);
+            a1 = a0;
+            a0 = next;
+        }
+        a1 ^= round_fn(key_at(31), a0);
+        let mut result = [0u8; 8];
+        result[..4].copy_from_slice(&a1.to_be_bytes());
+        result[4..].copy_from_slice(&a0.to_be_bytes());
+        result
+    }
+
+    fn encrypt(&self, block: &[u8]) -> [u8; 8] {
+        self.process(block, |round| self.key_at(round))
+    }
+
+    fn decrypt(&self, block: &[u8]) -> [u8; 8] {
+        self.process(block, |round| self.key_at(31 - round))
+    }
+}
+
+aes::cipher::impl_simple_block_encdec!(
+    Magma, U8, cipher, block,
+    encrypt: {
+        let result = cipher.encrypt(block.get_in());
+        *block.get_out() = GenericArray::from(result);
+    }
+    decrypt: {
+        let result = cipher.decrypt(block.get_in());
+        *block.get_out() = GenericArray::from(result);
+    }
+);
@@ -0,0 +1,198 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! GOST block ciphers (GOST R 34.12-2015) in CBC and CTR mode.
+//!
+//! The Streebog hash functions are registered together with the other hash functions.
+
+pub mod kuznyechik;
+pub mod magma;
+pub mod streebog;
+
+use aes::cipher::{
+    block_padding::{NoPadding, ZeroPadding},
+    BlockCipher, BlockDecrypt, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit,
+    StreamCipher,
+};
+use cbc::{Decryptor, Encryptor};
+use ctr::{Ctr32BE, Ctr64BE};
+
+use crate::function_set;
+use crate::nasl::syntax::NaslValue;
+use crate::nasl::utils::error::FunctionErrorKind;
+use crate::nasl::utils::{Context, Register};
+
+use super::{get_data, get_iv, get_key, get_len, Crypt};
+use kuznyechik::Kuznyechik;
+use magma::Magma;
+
+/// Returns the requested length of the decrypted data, which must not exceed the data length.
+fn decrypted_len(register: &Register, data: &[u8]) -> Result<usize, FunctionErrorKind> {
+    let len = get_len(register)?.unwrap_or(data.len());
+    if len > data.len() {
+        return Err(FunctionErrorKind::wrong_argument(
+            "len",
+            format!("<={:?}", data.len()).as_str(),
+            len.to_string().as_str(),
+        ));
+    }
+    Ok(len)
+}
+
+/// Base function for en- and decrypting Cipher Block Chaining (CBC) mode
+fn cbc<D>(register: &Register, crypt: Crypt) -> Result<NaslValue, FunctionErrorKind>
+where
+    D: BlockCipher + BlockEncrypt + BlockDecrypt + KeyInit,
+{
+    let key = get_key(register)?;
+    let data = get_data(register)?;
+    let iv = get_iv(register)?;
+
+    match crypt {
+        Crypt::Encrypt => Encryptor::<D>::new_from_slices(key, iv)
+            .map(|encryptor| encryptor.encrypt_padded_vec_mut::<ZeroPadding>(data).into())
+            .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string())),
+        Crypt::Decrypt => {
+            let len = decrypted_len(register, data)?;
+            let decryptor = Decryptor::<D>::new_from_slices(key, iv)
+                .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+            Ok(decryptor
+                .decrypt_padded_vec_mut::<NoPadding>(data)
+                .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?[..len]
+                .to_vec()
+                .into())
+        }
+    }
+}
+
+/// Base function for en- and decrypting Counter (CTR) mode
+fn ctr<C>(register: &Register, crypt: Crypt) -> Result<NaslValue, FunctionErrorKind>
+where
+    C: KeyIvInit + StreamCipher,
+{
+    let key = get_key(register)?;
+    let data = get_data(register)?;
+    let iv = get_iv(register)?;
+    let len = match crypt {
+        Crypt::Encrypt => data.len(),
+        Crypt::Decrypt => decrypted_len(register, data)?,
+    };
+
+    let mut cipher =
+        C::new_from_slices(key, iv).map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+    let mut buf = data.to_vec();
+    cipher.apply_keystream(&mut buf);
+    Ok(buf[..len].to_vec().into())
+}
+
+/// NASL function to encrypt data with Kuznyechik (GOST R 34.12-2015) in CBC mode.
+///
+/// This function expects 3 named arguments key, data and iv either in a string or data type.
+/// - The key must have a length of 32 bytes.
+/// - The data is divided into blocks of 16 bytes. The last block is filled with zeroes,
+///   therefore the length of the plain text must be known for decryption.
+/// - The iv must have a length of 16 bytes.
+fn kuznyechik_cbc_encrypt(
+    register: &Register,
+    _: &Context,
+) -> Result<NaslValue, FunctionErrorKind> {
+    cbc::<Kuznyechik>(register, Crypt::Encrypt)
+}
+
+/// NASL function to decrypt data with Kuznyechik (GOST R 34.12-2015) in CBC mode.
+///
+/// This function expects the named arguments key, data and iv either in a string or data type and
+/// an optional len argument with the length of the plain text.
+/// - The key must have a length of 32 bytes.
+/// - The data must be a multiple of 16 bytes long.
+/// - The iv must have a length of 16 bytes.
+fn kuznyechik_cbc_decrypt(
+    register: &Register,
+    _: &Context,
+) -> Result<NaslValue, FunctionErrorKind> {
+    cbc::<Kuznyechik>(register, Crypt::Decrypt)
+}
+
+/// NASL function to encrypt data with Kuznyechik (GOST R 34.12-2015) in CTR mode.
+///
+/// This function expects 3 named arguments key, data and iv either in a string or data type.
+/// - The key must have a length of 32 bytes.
+/// - The iv must have a length of 16 bytes. It is used as the initial counter, of which the lower
+///   64 bits are incremented. GOST R 34.13-2015 uses an 8 byte IV followed by 8 zero bytes.
+fn kuznyechik_ctr_encrypt(
+    register: &Register,
+    _: &Context,
+) -> Result<NaslValue, FunctionErrorKind> {
+    ctr::<Ctr64BE<Kuznyechik>>(register, Crypt::Encrypt)
+}
+
+/// NASL function to decrypt data with Kuznyechik (GOST R 34.12-2015) in CTR mode.
+///
+/// Takes the same arguments as kuznyechik_ctr_encrypt and an optional len argument to truncate
+/// the result.
+fn kuznyechik_ctr_decrypt(
+    register: &Register,
+    _: &Context,
+) -> Result<NaslValue, FunctionErrorKind> {
+    ctr::<Ctr64BE<Kuznyechik>>(register, Crypt::Decrypt)
+}
+
+/// NASL function to encrypt data with Magma (GOST R 34.12-2015, formerly GOST 28147-89) in CBC
+/// mode.
+///
+/// This function expects 3 named arguments key, data and iv either in a string or data type.
+/// - The key must have a length of 32 bytes.
+/// - The data is divided into blocks of 8 bytes. The last block is filled with zeroes,
+///   therefore the length of the plain text must be known for decryption.
+/// - The iv must have a length of 8 bytes.
+fn magma_cbc_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    cbc::<Magma>(register, Crypt::Encrypt)
+}
+
+/// NASL function to decrypt data with Magma (GOST R 34.12-2015, formerly GOST 28147-89) in CBC
+/// mode.
+///
+/// This function expects the named arguments key, data and iv either in a string or data type and
+/// an optional len argument with the length of the plain text.
+/// - The key must have a length of 32 bytes.
+/// - The data must be a multiple of 8 bytes long.
+/// - The iv must have a length of 8 bytes.
+fn magma_cbc_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    cbc::<Magma>(register, Crypt::Decrypt)
+}
+
+/// NASL function to encrypt data with Magma (GOST R 34.12-2015) in CTR mode.
+///
+/// This function expects 3 named arguments key, data and iv either in a string or data type.
+/// - The key must have a length of 32 bytes.
+/// - The iv must have a length of 8 bytes. It is used as the initial counter, of which the lower
+///   32 bits are incremented. GOST R 34.13-2015 uses a 4 byte IV followed by 4 zero bytes.
+fn magma_ctr_encrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    ctr::<Ctr32BE<Magma>>(register, Crypt::Encrypt)
+}
+
+/// NASL function to decrypt data with Magma (GOST R 34.12-2015) in CTR mode.
+///
+/// Takes the same arguments as magma_ctr_encrypt and an optional len argument to truncate the
+/// result.
+fn magma_ctr_decrypt(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    ctr::<Ctr32BE<Magma>>(register, Crypt::Decrypt)
+}
+
+pub struct Gost;
+
+function_set! {
+    Gost,
+    sync_stateless,
+    (
+        kuznyechik_cbc_encrypt,
+        kuznyechik_cbc_decrypt,
+        kuznyechik_ctr_encrypt,
+        kuznyechik_ctr_decrypt,
+        magma_cbc_encrypt,
+        magma_cbc_decrypt,
+        magma_ctr_encrypt,
+        magma_ctr_decrypt,
+    )
+}
@@ -2,7 +2,11 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
 use crate::function_set;
+use crate::nasl::prelude::*;
 use crate::nasl::utils::error::FunctionErrorKind;
 use digest::Digest;
 use md2::Md2;
@@ -10,11 +14,17 @@ use md4::Md4;
 use md5::Md5;
 use ripemd::Ripemd160;
 use sha1::Sha1;
-use sha2::{Sha256, Sha512};
+use sha2::{Sha256, Sha384, Sha512};
 
 use crate::nasl::syntax::NaslValue;
 use crate::nasl::utils::{Context, Register};
 
+use super::gost::streebog::{Streebog256, Streebog512};
+use super::hmac::new_streaming_hmac;
+
+/// Largest number of unfinished calculations, the oldest one is dropped by further inits
+const MAX_HANDLES: usize = 1024;
+
 fn nasl_hash<D: Digest>(register: &Register) -> Result<NaslValue, FunctionErrorKind>
 where
     D::OutputSize: std::ops::Add,
@@ -66,6 +76,16 @@ pub fn hash_sha512(register: &Register, _: &Context) -> Result<NaslValue, Functi
     nasl_hash::<Sha512>(register)
 }
 
+/// NASL function to get GOST R 34.11-2012 (Streebog) 256 bit hash
+pub fn hash_streebog256(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    nasl_hash::<Streebog256>(register)
+}
+
+/// NASL function to get GOST R 34.11-2012 (Streebog) 512 bit hash
+pub fn hash_streebog512(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    nasl_hash::<Streebog512>(register)
+}
+
 /// NASL function to get RIPemd160 hash
 pub fn hash_ripemd160(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
     nasl_hash::<Ripemd160>(register)
@@ -84,5 +104,149 @@ function_set! {
         (hash_sha1, "SHA1"),
         (hash_sha256, "SHA256"),
         (hash_sha512, "SHA512"),
+        (hash_streebog256, "STREEBOG256"),
+        (hash_streebog512, "STREEBOG512"),
+    )
+}
+
+/// A hash or HMAC calculation in progress
+pub(super) trait StreamingHash: Send + Sync {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+}
+
+impl<D: Digest + Send + Sync> StreamingHash for D {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Digest::finalize(*self).to_vec()
+    }
+}
+
+fn new_streaming_hash(algorithm: &str) -> Result<Box<dyn StreamingHash>, FunctionErrorKind> {
+    Ok(match algorithm.to_lowercase().as_str() {
+        "md2" => Box::new(Md2::new()),
+        "md4" => Box::new(Md4::new()),
+        "md5" => Box::new(Md5::new()),
+        "ripemd160" => Box::new(Ripemd160::new()),
+        "sha1" => Box::new(Sha1::new()),
+        "sha256" => Box::new(Sha256::new()),
+        "sha384" => Box::new(Sha384::new()),
+        "sha512" => Box::new(Sha512::new()),
+        "streebog256" => Box::new(Streebog256::new()),
+        "streebog512" => Box::new(Streebog512::new()),
+        _ => return Err(FunctionErrorKind::wrong_argument(
+            "algorithm",
+            "md2, md4, md5, ripemd160, sha1, sha256, sha384, sha512, streebog256 or streebog512",
+            algorithm,
+        )),
+    })
+}
+
+#[derive(Default)]
+struct HashHandles {
+    hashes: BTreeMap<usize, Box<dyn StreamingHash>>,
+    last_id: usize,
+}
+
+/// Holds the hash and HMAC calculations started by scripts.
+///
+/// The handlers live as long as the executor, so calculations that are never finished are
+/// dropped once [MAX_HANDLES] newer ones are started.
+#[derive(Default)]
+pub struct HashHandlers {
+    handles: RwLock<HashHandles>,
+}
+
+impl HashHandlers {
+    fn insert(&self, hash: Box<dyn StreamingHash>) -> NaslValue {
+        let mut handles = self.handles.write().unwrap();
+        if handles.hashes.len() >= MAX_HANDLES {
+            handles.hashes.pop_first();
+        }
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.hashes.insert(id, hash);
+        NaslValue::Number(id as i64)
+    }
+
+    /// Starts an incremental hash calculation.
+    ///
+    /// Takes the named argument algorithm, which is one of md2, md4, md5, ripemd160, sha1,
+    /// sha256, sha384, sha512, streebog256 or streebog512, and returns the id of the
+    /// calculation. The data is added with hash_update and the hash is returned by hash_final.
+    /// With the optional named argument key the HMAC is calculated instead, see hmac_init.
+    #[nasl_function(named(algorithm, key))]
+    fn hash_init(
+        &self,
+        algorithm: &str,
+        key: Option<&NaslValue>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let hash = match key {
+            Some(key) => new_streaming_hmac(algorithm, &Vec::<u8>::from(key))?,
+            None => new_streaming_hash(algorithm)?,
+        };
+        Ok(self.insert(hash))
+    }
+
+    /// Starts an incremental HMAC calculation.
+    ///
+    /// Takes the named arguments algorithm, which is one of md2, md5, ripemd160, sha1, sha256,
+    /// sha384 or sha512, and key. Returns the id of the calculation, which is used with
+    /// hmac_update and hmac_final like the one of hash_init.
+    #[nasl_function(named(algorithm, key))]
+    fn hmac_init(&self, algorithm: &str, key: &NaslValue) -> Result<NaslValue, FunctionErrorKind> {
+        let key: Vec<u8> = key.into();
+        Ok(self.insert(new_streaming_hmac(algorithm, &key)?))
+    }
+
+    /// Adds data to a hash calculation.
+    ///
+    /// Takes the id returned by hash_init as named argument hd and the string or data as named
+    /// argument data. It can be called repeatedly so that large data does not need to be held
+    /// at once.
+    #[nasl_function(named(hd, data))]
+    fn hash_update(&self, hd: usize, data: &NaslValue) -> Result<NaslValue, FunctionErrorKind> {
+        let data: Vec<u8> = data.into();
+        match self.handles.write().unwrap().hashes.get_mut(&hd) {
+            Some(hash) => {
+                hash.update(&data);
+                Ok(NaslValue::Null)
+            }
+            None => Err(FunctionErrorKind::Diagnostic(
+                format!("Unknown hash object {hd}"),
+                None,
+            )),
+        }
+    }
+
+    /// Finishes a hash calculation.
+    ///
+    /// Takes the id returned by hash_init as named argument hd and returns the hash of all data
+    /// added by hash_update. Afterwards the id is no longer valid.
+    #[nasl_function(named(hd))]
+    fn hash_final(&self, hd: usize) -> Result<NaslValue, FunctionErrorKind> {
+        match self.handles.write().unwrap().hashes.remove(&hd) {
+            Some(hash) => Ok(NaslValue::Data(hash.finalize())),
+            None => Err(FunctionErrorKind::Diagnostic(
+                format!("Unknown hash object {hd}"),
+                None,
+            )),
+        }
+    }
+}
+
+function_set! {
+    HashHandlers,
+    sync_stateful,
+    (
+        (HashHandlers::hash_init, "hash_init"),
+        (HashHandlers::hash_update, "hash_update"),
+        (HashHandlers::hash_final, "hash_final"),
+        (HashHandlers::hmac_init, "hmac_init"),
+        (HashHandlers::hash_update, "hmac_update"),
+        (HashHandlers::hash_final, "hmac_final"),
     )
 }
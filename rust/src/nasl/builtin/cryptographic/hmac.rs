@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use std::io::{self, Read};
+
 use digest::{
     block_buffer::Eager,
     core_api::{BufferKindUser, CoreProxy, FixedOutputCore, UpdateCore},
@@ -17,7 +19,12 @@ use ripemd::Ripemd160;
 use sha1::Sha1;
 use sha2::{Sha256, Sha384, Sha512};
 
-use crate::nasl::prelude::*;
+use crate::nasl::{prelude::*, syntax::LoadError};
+
+use super::hash::StreamingHash;
+
+/// Size of the chunks a file is read in by hmac_file
+const FILE_CHUNK: usize = 64 * 1024;
 
 fn hmac<D>(register: &Register) -> Result<NaslValue, FunctionErrorKind>
 where
@@ -104,5 +111,76 @@ function_set! {
         (hmac_sha256, "HMAC_SHA256"),
         (hmac_sha384, "HMAC_SHA384"),
         (hmac_sha512, "HMAC_SHA512"),
+        hmac_file,
     )
 }
+
+/// A HMAC calculation in progress
+struct StreamingMac<M>(M);
+
+impl<M: Mac + Send + Sync> StreamingHash for StreamingMac<M> {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.0.finalize().into_bytes().to_vec()
+    }
+}
+
+pub(super) fn new_streaming_hmac(
+    algorithm: &str,
+    key: &[u8],
+) -> Result<Box<dyn StreamingHash>, FunctionErrorKind> {
+    // HMAC accepts keys of any length
+    Ok(match algorithm.to_lowercase().as_str() {
+        "md2" => Box::new(StreamingMac(Hmac::<Md2>::new_from_slice(key).unwrap())),
+        "md5" => Box::new(StreamingMac(Hmac::<Md5>::new_from_slice(key).unwrap())),
+        "ripemd160" => Box::new(StreamingMac(
+            Hmac::<Ripemd160>::new_from_slice(key).unwrap(),
+        )),
+        "sha1" => Box::new(StreamingMac(Hmac::<Sha1>::new_from_slice(key).unwrap())),
+        "sha256" => Box::new(StreamingMac(Hmac::<Sha256>::new_from_slice(key).unwrap())),
+        "sha384" => Box::new(StreamingMac(Hmac::<Sha384>::new_from_slice(key).unwrap())),
+        "sha512" => Box::new(StreamingMac(Hmac::<Sha512>::new_from_slice(key).unwrap())),
+        _ => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "algorithm",
+                "md2, md5, ripemd160, sha1, sha256, sha384 or sha512",
+                algorithm,
+            ))
+        }
+    })
+}
+
+/// Returns the HMAC of a file read through the loader.
+///
+/// Takes the named arguments algorithm, which is one of md2, md5, ripemd160, sha1, sha256,
+/// sha384 or sha512, key and file, the name of the file relative to the root of the loader.
+/// The file is read in chunks, so that large files like firmware images are not held in memory
+/// at once. Returns NULL when the file does not exist.
+#[nasl_function(named(algorithm, key, file))]
+fn hmac_file(
+    context: &Context,
+    algorithm: &str,
+    key: &NaslValue,
+    file: &str,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let key: Vec<u8> = key.into();
+    let mut hmac = new_streaming_hmac(algorithm, &key)?;
+    let mut reader = match context.loader().open(file) {
+        Ok(x) => x,
+        Err(LoadError::NotFound(_)) => return Ok(NaslValue::Null),
+        Err(e) => return Err(FunctionErrorKind::Diagnostic(e.to_string(), None)),
+    };
+    let mut chunk = vec![0; FILE_CHUNK];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => hmac.update(&chunk[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(FunctionErrorKind::Diagnostic(e.to_string(), None)),
+        }
+    }
+    Ok(NaslValue::Data(hmac.finalize()))
+}
@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Decoding and verification of JSON Web Tokens as specified in RFC 7519.
+//!
+//! Supported are the signature algorithms HS256, RS256 and ES256 of RFC 7518.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use openssl::{
+    bn::BigNum,
+    ecdsa::EcdsaSig,
+    hash::MessageDigest,
+    pkey::{Id, PKey, Public},
+    sign::Verifier,
+    x509::X509,
+};
+use sha2::Sha256;
+
+use crate::nasl::prelude::*;
+
+/// The parts of a token
+struct Token<'a> {
+    header: serde_json::Value,
+    payload: serde_json::Value,
+    signature: Vec<u8>,
+    /// Encoded header and payload, which are signed
+    signing_input: &'a str,
+}
+
+fn decode_part(part: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(part.trim_end_matches('=')).ok()
+}
+
+impl<'a> Token<'a> {
+    fn parse(token: &'a str) -> Option<Self> {
+        let token = token.trim();
+        let (signing_input, signature) = token.rsplit_once('.')?;
+        let (header, payload) = signing_input.split_once('.')?;
+        Some(Self {
+            header: serde_json::from_slice(&decode_part(header)?).ok()?,
+            payload: serde_json::from_slice(&decode_part(payload)?).ok()?,
+            signature: decode_part(signature)?,
+            signing_input,
+        })
+    }
+
+    fn alg(&self) -> &str {
+        self.header["alg"].as_str().unwrap_or_default()
+    }
+}
+
+fn to_nasl_value(value: serde_json::Value) -> NaslValue {
+    match value {
+        serde_json::Value::Null => NaslValue::Null,
+        serde_json::Value::Bool(x) => NaslValue::Boolean(x),
+        serde_json::Value::Number(x) => match x.as_i64() {
+            Some(x) => NaslValue::Number(x),
+            None => NaslValue::String(x.to_string()),
+        },
+        serde_json::Value::String(x) => NaslValue::String(x),
+        serde_json::Value::Array(x) => NaslValue::Array(x.into_iter().map(to_nasl_value).collect()),
+        serde_json::Value::Object(x) => {
+            NaslValue::Dict(x.into_iter().map(|(k, v)| (k, to_nasl_value(v))).collect())
+        }
+    }
+}
+
+/// Parses a PEM or DER encoded public key or certificate
+fn public_key(data: &[u8]) -> Result<PKey<Public>, FunctionErrorKind> {
+    PKey::public_key_from_pem(data)
+        .or_else(|_| PKey::public_key_from_der(data))
+        .or_else(|_| {
+            X509::from_pem(data)
+                .or_else(|_| X509::from_der(data))
+                .and_then(|x| x.public_key())
+        })
+        .map_err(|e| {
+            FunctionErrorKind::Diagnostic(format!("Unable to parse public key: {e}"), None)
+        })
+}
+
+/// Parses the public key for an algorithm, which must be of the key type the algorithm is
+/// defined for
+fn algorithm_key(
+    data: &[u8],
+    alg: &str,
+    id: Id,
+    key_type: &str,
+) -> Result<PKey<Public>, FunctionErrorKind> {
+    let key = public_key(data)?;
+    if key.id() != id {
+        return Err(FunctionErrorKind::WrongArgument(format!(
+            "{alg} requires an {key_type} key"
+        )));
+    }
+    Ok(key)
+}
+
+fn verify_openssl(key: &PKey<Public>, data: &[u8], signature: &[u8]) -> bool {
+    Verifier::new(MessageDigest::sha256(), key)
+        .and_then(|mut x| x.verify_oneshot(signature, data))
+        .unwrap_or_default()
+}
+
+/// Converts the signature of ES256, which is the concatenation of r and s, to DER
+fn es256_signature(signature: &[u8]) -> Option<Vec<u8>> {
+    if signature.len() != 64 {
+        return None;
+    }
+    let r = BigNum::from_slice(&signature[..32]).ok()?;
+    let s = BigNum::from_slice(&signature[32..]).ok()?;
+    EcdsaSig::from_private_components(r, s).ok()?.to_der().ok()
+}
+
+/// Decodes a JSON Web Token without verifying it.
+///
+/// Takes the token as unnamed argument and returns an array with the decoded JSON objects
+/// `header` and `payload` as well as the `signature` as data. Returns NULL when the token is
+/// malformed.
+#[nasl_function]
+fn jwt_decode(token: &str) -> NaslValue {
+    let Some(token) = Token::parse(token) else {
+        return NaslValue::Null;
+    };
+    NaslValue::Dict(
+        [
+            ("header".to_string(), to_nasl_value(token.header)),
+            ("payload".to_string(), to_nasl_value(token.payload)),
+            ("signature".to_string(), NaslValue::Data(token.signature)),
+        ]
+        .into(),
+    )
+}
+
+/// Verifies the signature of a JSON Web Token.
+///
+/// Takes the named arguments token and key as well as the optional alg. The algorithm is taken
+/// from the header of the token, for HS256 the key is the shared secret, for RS256 and ES256 a
+/// PEM or DER encoded public key or certificate of the type of the algorithm. When alg is given, tokens using another
+/// algorithm are rejected. Returns FALSE for malformed tokens and tokens with the algorithm
+/// none.
+#[nasl_function(named(token, key, alg))]
+fn jwt_verify(token: &str, key: &NaslValue, alg: Option<&str>) -> Result<bool, FunctionErrorKind> {
+    let Some(token) = Token::parse(token) else {
+        return Ok(false);
+    };
+    if alg.is_some_and(|x| !x.eq_ignore_ascii_case(token.alg())) {
+        return Ok(false);
+    }
+    let key = Vec::<u8>::from(key);
+    let data = token.signing_input.as_bytes();
+    match token.alg() {
+        "none" => Ok(false),
+        "HS256" => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(&key)
+                .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+            mac.update(data);
+            Ok(mac.verify_slice(&token.signature).is_ok())
+        }
+        "RS256" => {
+            let key = algorithm_key(&key, "RS256", Id::RSA, "RSA")?;
+            Ok(verify_openssl(&key, data, &token.signature))
+        }
+        "ES256" => {
+            let key = algorithm_key(&key, "ES256", Id::EC, "EC")?;
+            Ok(es256_signature(&token.signature)
+                .is_some_and(|signature| verify_openssl(&key, data, &signature)))
+        }
+        alg => Err(FunctionErrorKind::WrongArgument(format!(
+            "Unsupported JWT algorithm {alg}"
+        ))),
+    }
+}
+
+pub struct Jwt;
+
+function_set! {
+    Jwt,
+    sync_stateless,
+    (
+        jwt_decode,
+        jwt_verify
+    )
+}
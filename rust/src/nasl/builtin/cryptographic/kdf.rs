@@ -0,0 +1,313 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Key derivation functions.
+
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::nasl::prelude::*;
+
+/// Derives a key of the given length in bits using the KDF in counter mode with HMAC-SHA256 as
+/// PRF as specified in NIST SP 800-108.
+///
+/// The fixed input data consists of the label, a zero byte, the context and the length in bits,
+/// which is the layout used by SMB 3.
+pub fn sp800_108_counter_hmac_sha256(
+    key: &[u8],
+    label: &[u8],
+    context: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let length = bits.div_ceil(8) as usize;
+    let mut result = Vec::with_capacity(length);
+    let mut counter: u32 = 1;
+    while result.len() < length {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key)
+            .map_err(|e| FunctionErrorKind::Diagnostic(format!("SP800-108 KDF: {e}"), None))?;
+        mac.update(&counter.to_be_bytes());
+        mac.update(label);
+        mac.update(&[0]);
+        mac.update(context);
+        mac.update(&bits.to_be_bytes());
+        result.extend_from_slice(&mac.finalize().into_bytes());
+        counter += 1;
+    }
+    result.truncate(length);
+    Ok(result)
+}
+
+/// Derives the SMB3 signing, encryption or decryption key from the session key.
+///
+/// Takes the named arguments:
+/// - key: the session key
+/// - label: the label, e.g. "SMB2AESCMAC" followed by a zero byte for the SMB 3.0 signing key
+/// - ctx: the context, e.g. "SmbSign" followed by a zero byte or the preauthentication hash
+/// - lvalue: the length of the derived key in bits, either 128 or 256
+#[nasl_function(named(key, label, ctx, lvalue))]
+fn smb3kdf(
+    key: &NaslValue,
+    label: &NaslValue,
+    ctx: &NaslValue,
+    lvalue: i64,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    if lvalue != 128 && lvalue != 256 {
+        return Err(FunctionErrorKind::WrongArgument(format!(
+            "lvalue must be 128 or 256 but was {lvalue}"
+        )));
+    }
+    let key: Vec<u8> = key.into();
+    let label: Vec<u8> = label.into();
+    let ctx: Vec<u8> = ctx.into();
+    sp800_108_counter_hmac_sha256(&key, &label, &ctx, lvalue as u32)
+}
+
+/// Length of the SMB2 session key used for signing
+const SMB2_SESSION_KEY_LENGTH: usize = 16;
+
+/// Returns the first 16 bytes of the key, padded with zeros when it is shorter.
+fn smb2_session_key(key: &[u8]) -> Vec<u8> {
+    let mut result = key[..key.len().min(SMB2_SESSION_KEY_LENGTH)].to_vec();
+    result.resize(SMB2_SESSION_KEY_LENGTH, 0);
+    result
+}
+
+/// Derives the key used to sign SMB2 messages as specified in MS-SMB2 section 3.2.5.3.1.
+///
+/// Takes the named arguments:
+/// - session_key: the key queried from the authentication, e.g. the NTLM session key
+/// - dialect: the negotiated dialect, e.g. 0x0210, 0x0302 or 0x0311
+/// - preauth_hash: the preauthentication integrity hash of the session setup, required for
+///   dialect 0x0311. When binding a session to a new channel it is the hash of the binding
+///   session setup.
+///
+/// For the dialects 0x0202 and 0x0210 the session key is used directly.
+#[nasl_function(named(session_key, dialect, preauth_hash))]
+fn smb2_signing_key(
+    session_key: &NaslValue,
+    dialect: i64,
+    preauth_hash: Option<&NaslValue>,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let key = smb2_session_key(&Vec::<u8>::from(session_key));
+    let preauth_hash: Option<Vec<u8>> = preauth_hash.map(|x| x.into());
+    match (dialect, preauth_hash) {
+        (0x0202 | 0x0210, _) => Ok(key),
+        (0x0300 | 0x0302, _) => {
+            sp800_108_counter_hmac_sha256(&key, b"SMB2AESCMAC\0", b"SmbSign\0", 128)
+        }
+        (0x0311, Some(hash)) => sp800_108_counter_hmac_sha256(&key, b"SMBSigningKey\0", &hash, 128),
+        (0x0311, None) => Err(FunctionErrorKind::missing_argument("preauth_hash")),
+        (dialect, _) => Err(FunctionErrorKind::WrongArgument(format!(
+            "unsupported SMB2 dialect {dialect:#06x}"
+        ))),
+    }
+}
+
+/// Updates the SMB 3.1.1 preauthentication integrity hash with a message.
+///
+/// Takes the named arguments data, the complete SMB2 message including its header, and hash,
+/// the result of the previous call. Without hash the calculation starts with 64 zero bytes
+/// as done for the negotiate request. Returns the SHA-512 of the hash followed by the data.
+#[nasl_function(named(data, hash))]
+fn smb2_preauth_hash(data: &NaslValue, hash: Option<&NaslValue>) -> Vec<u8> {
+    let previous: Vec<u8> = hash.map(|x| x.into()).unwrap_or_else(|| vec![0; 64]);
+    let data: Vec<u8> = data.into();
+    Sha512::new()
+        .chain_update(previous)
+        .chain_update(data)
+        .finalize()
+        .to_vec()
+}
+
+fn new_mac<M: Mac + hmac::digest::KeyInit>(key: &[u8]) -> Result<M, FunctionErrorKind> {
+    <M as Mac>::new_from_slice(key)
+        .map_err(|e| FunctionErrorKind::Diagnostic(format!("HMAC: {e}"), None))
+}
+
+/// The data expansion function P_hash as specified in RFC 5246 section 5.
+pub fn p_hash<M: Mac + hmac::digest::KeyInit + Clone>(
+    secret: &[u8],
+    seed: &[u8],
+    length: usize,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let mac = new_mac::<M>(secret)?;
+    let mut result = Vec::with_capacity(length);
+    let mut a = seed.to_vec();
+    while result.len() < length {
+        a = mac
+            .clone()
+            .chain_update(&a)
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        let block = mac.clone().chain_update(&a).chain_update(seed).finalize();
+        result.extend_from_slice(&block.into_bytes());
+    }
+    result.truncate(length);
+    Ok(result)
+}
+
+/// The PRF of TLS 1.0 and 1.1 as specified in RFC 2246 section 5.
+///
+/// The secret is split into two halves, which share the middle byte when the length is odd.
+/// The result of P_MD5 with the first half is XORed with the result of P_SHA1 with the second.
+pub fn tls10_prf(secret: &[u8], seed: &[u8], length: usize) -> Result<Vec<u8>, FunctionErrorKind> {
+    let half = secret.len().div_ceil(2);
+    let md5 = p_hash::<Hmac<Md5>>(&secret[..half], seed, length)?;
+    let sha1 = p_hash::<Hmac<Sha1>>(&secret[secret.len() - half..], seed, length)?;
+    Ok(md5.iter().zip(sha1).map(|(a, b)| a ^ b).collect())
+}
+
+/// HKDF-Expand as specified in RFC 5869 section 2.3.
+pub fn hkdf_expand<M: Mac + hmac::digest::KeyInit + Clone>(
+    prk: &[u8],
+    info: &[u8],
+    length: usize,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let mac = new_mac::<M>(prk)?;
+    let mut result = Vec::with_capacity(length);
+    let mut block = vec![];
+    let mut counter: u8 = 1;
+    while result.len() < length {
+        if counter == 0 {
+            return Err(FunctionErrorKind::WrongArgument(format!(
+                "HKDF-Expand cannot derive {length} bytes"
+            )));
+        }
+        block = mac
+            .clone()
+            .chain_update(&block)
+            .chain_update(info)
+            .chain_update([counter])
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        result.extend_from_slice(&block);
+        counter = counter.wrapping_add(1);
+    }
+    result.truncate(length);
+    Ok(result)
+}
+
+/// The HkdfLabel structure of TLS 1.3 as specified in RFC 8446 section 7.1.
+fn hkdf_label(label: &[u8], context: &[u8], length: usize) -> Result<Vec<u8>, FunctionErrorKind> {
+    let label = [b"tls13 ", label].concat();
+    let (Ok(length), Ok(label_length), Ok(context_length)) = (
+        u16::try_from(length),
+        u8::try_from(label.len()),
+        u8::try_from(context.len()),
+    ) else {
+        return Err(FunctionErrorKind::WrongArgument(
+            "length, label or context exceed the limits of HkdfLabel".to_string(),
+        ));
+    };
+    let mut result = length.to_be_bytes().to_vec();
+    result.push(label_length);
+    result.extend_from_slice(&label);
+    result.push(context_length);
+    result.extend_from_slice(context);
+    Ok(result)
+}
+
+fn prf_length(outlen: i64) -> Result<usize, FunctionErrorKind> {
+    usize::try_from(outlen)
+        .map_err(|_| FunctionErrorKind::WrongArgument(format!("invalid outlen {outlen}")))
+}
+
+fn prf_seed(label: &NaslValue, seed: &NaslValue) -> Vec<u8> {
+    let label: Vec<u8> = label.into();
+    let seed: Vec<u8> = seed.into();
+    [label, seed].concat()
+}
+
+/// Computes the PRF of TLS 1.0 and 1.1, which combines P_MD5 and P_SHA1.
+///
+/// Takes the named arguments secret, seed, label and outlen, the number of bytes to derive.
+/// For TLS 1.2 use prf_sha256 or prf_sha384 instead.
+#[nasl_function(named(secret, seed, label, outlen))]
+fn tls1_prf(
+    secret: &NaslValue,
+    seed: &NaslValue,
+    label: &NaslValue,
+    outlen: i64,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let secret: Vec<u8> = secret.into();
+    tls10_prf(&secret, &prf_seed(label, seed), prf_length(outlen)?)
+}
+
+/// Computes the PRF of TLS 1.2 with SHA-256.
+///
+/// Takes the named arguments secret, seed, label and outlen, the number of bytes to derive.
+#[nasl_function(named(secret, seed, label, outlen))]
+fn prf_sha256(
+    secret: &NaslValue,
+    seed: &NaslValue,
+    label: &NaslValue,
+    outlen: i64,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let secret: Vec<u8> = secret.into();
+    p_hash::<Hmac<Sha256>>(&secret, &prf_seed(label, seed), prf_length(outlen)?)
+}
+
+/// Computes the PRF of TLS 1.2 with SHA-384.
+///
+/// Takes the named arguments secret, seed, label and outlen, the number of bytes to derive.
+#[nasl_function(named(secret, seed, label, outlen))]
+fn prf_sha384(
+    secret: &NaslValue,
+    seed: &NaslValue,
+    label: &NaslValue,
+    outlen: i64,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let secret: Vec<u8> = secret.into();
+    p_hash::<Hmac<Sha384>>(&secret, &prf_seed(label, seed), prf_length(outlen)?)
+}
+
+/// Computes HKDF-Expand-Label of TLS 1.3 as specified in RFC 8446 section 7.1.
+///
+/// Takes the named arguments:
+/// - secret: the secret, e.g. the handshake traffic secret
+/// - label: the label without the "tls13 " prefix, e.g. "key" or "iv"
+/// - context: the context, e.g. a transcript hash, defaults to an empty string
+/// - length: the number of bytes to derive
+/// - hash: the hash of the cipher suite, either "sha256" (default) or "sha384"
+#[nasl_function(named(secret, label, context, length, hash))]
+fn tls13_hkdf_expand_label(
+    secret: &NaslValue,
+    label: &NaslValue,
+    context: Option<&NaslValue>,
+    length: i64,
+    hash: Option<&str>,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let secret: Vec<u8> = secret.into();
+    let label: Vec<u8> = label.into();
+    let context: Vec<u8> = context.map(|x| x.into()).unwrap_or_default();
+    let length = prf_length(length)?;
+    let info = hkdf_label(&label, &context, length)?;
+    match hash.unwrap_or("sha256").to_lowercase().as_str() {
+        "sha256" => hkdf_expand::<Hmac<Sha256>>(&secret, &info, length),
+        "sha384" => hkdf_expand::<Hmac<Sha384>>(&secret, &info, length),
+        hash => Err(FunctionErrorKind::WrongArgument(format!(
+            "unsupported hash {hash}, expected sha256 or sha384"
+        ))),
+    }
+}
+
+pub struct Kdf;
+
+function_set! {
+    Kdf,
+    sync_stateless,
+    (
+        smb3kdf,
+        smb2_signing_key,
+        smb2_preauth_hash,
+        tls1_prf,
+        prf_sha256,
+        prf_sha384,
+        tls13_hkdf_expand_label
+    )
+}
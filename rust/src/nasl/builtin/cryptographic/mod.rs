@@ -15,9 +15,16 @@ pub mod aes_ctr;
 pub mod aes_gcm;
 pub mod aes_gmac;
 pub mod bf_cbc;
+pub mod bn;
+pub mod compare;
 pub mod des;
+pub mod fingerprint;
+pub mod gost;
 pub mod hash;
 pub mod hmac;
+pub mod jwt;
+pub mod kdf;
+pub mod rc2;
 pub mod rc4;
 pub mod rsa;
 
@@ -123,6 +130,13 @@ impl IntoFunctionSet for Cryptographic {
         set.add_set(des::Des);
         set.add_set(rsa::Rsa);
         set.add_set(bf_cbc::BfCbc);
+        set.add_set(bn::Bn);
+        set.add_set(gost::Gost);
+        set.add_set(kdf::Kdf);
+        set.add_set(rc2::Rc2Cbc);
+        set.add_set(compare::Compare);
+        set.add_set(jwt::Jwt);
+        set.add_set(fingerprint::Fingerprint);
         set
     }
 }
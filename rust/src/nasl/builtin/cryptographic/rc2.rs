@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! RC2 in CBC mode as specified in RFC 2268.
+//!
+//! RC2 is still found in legacy PKCS#12 containers and CMS objects, e.g. the export-grade
+//! pbeWithSHAAnd40BitRC2-CBC, so the effective key length can be given separately of the key.
+
+use cbc::{
+    cipher::{
+        block_padding::{NoPadding, ZeroPadding},
+        BlockDecryptMut, BlockEncryptMut, InnerIvInit,
+    },
+    Decryptor, Encryptor,
+};
+use rc2::Rc2;
+
+use crate::nasl::prelude::*;
+
+const BLOCK_SIZE: usize = 8;
+
+/// Returns the cipher for the key limited to the effective bits and the iv
+fn prepare(
+    key: &NaslValue,
+    iv: &NaslValue,
+    bits: Option<usize>,
+) -> Result<(Rc2, [u8; BLOCK_SIZE]), FunctionErrorKind> {
+    let key = Vec::<u8>::from(key);
+    if key.is_empty() || key.len() > 128 {
+        return Err(FunctionErrorKind::wrong_argument(
+            "key",
+            "1 to 128 bytes",
+            &format!("{} bytes", key.len()),
+        ));
+    }
+    let bits = bits.unwrap_or(key.len() * 8);
+    if bits == 0 || bits > 1024 {
+        return Err(FunctionErrorKind::wrong_argument(
+            "bits",
+            "1 to 1024",
+            &bits.to_string(),
+        ));
+    }
+    let iv = Vec::<u8>::from(iv);
+    let iv: [u8; BLOCK_SIZE] = iv.as_slice().try_into().map_err(|_| {
+        FunctionErrorKind::wrong_argument("iv", "8 bytes", &format!("{} bytes", iv.len()))
+    })?;
+    Ok((Rc2::new_with_eff_key_len(&key, bits), iv))
+}
+
+/// NASL function to encrypt data with RC2 in CBC mode.
+///
+/// Takes the named arguments key, iv and data as well as the optional bits. The key must be 1 to
+/// 128 bytes long and the iv 8 bytes. Bits is the effective key length, e.g. 40 for export-grade
+/// RC2, and defaults to the length of the key. Data is padded with zeros to a multiple of 8
+/// bytes.
+#[nasl_function(named(key, iv, data, bits))]
+fn rc2_cbc_encrypt(
+    key: &NaslValue,
+    iv: &NaslValue,
+    data: &NaslValue,
+    bits: Option<usize>,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let (cipher, iv) = prepare(key, iv, bits)?;
+    let data = Vec::<u8>::from(data);
+    Ok(Encryptor::inner_iv_init(cipher, &iv.into()).encrypt_padded_vec_mut::<ZeroPadding>(&data))
+}
+
+/// NASL function to decrypt data with RC2 in CBC mode.
+///
+/// Takes the same arguments as rc2_cbc_encrypt, data must be a multiple of 8 bytes long. The
+/// optional len truncates the decrypted data, otherwise padding is not removed.
+#[nasl_function(named(key, iv, data, bits, len))]
+fn rc2_cbc_decrypt(
+    key: &NaslValue,
+    iv: &NaslValue,
+    data: &NaslValue,
+    bits: Option<usize>,
+    len: Option<usize>,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let (cipher, iv) = prepare(key, iv, bits)?;
+    let data = Vec::<u8>::from(data);
+    if data.len() % BLOCK_SIZE != 0 {
+        return Err(FunctionErrorKind::wrong_argument(
+            "data",
+            "a multiple of 8 bytes",
+            &format!("{} bytes", data.len()),
+        ));
+    }
+    let len = len.unwrap_or(data.len());
+    if len > data.len() {
+        return Err(FunctionErrorKind::wrong_argument(
+            "len",
+            &format!("<={}", data.len()),
+            &len.to_string(),
+        ));
+    }
+    let mut data = Decryptor::inner_iv_init(cipher, &iv.into())
+        .decrypt_padded_vec_mut::<NoPadding>(&data)
+        .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+    data.truncate(len);
+    Ok(data)
+}
+
+pub struct Rc2Cbc;
+
+function_set! {
+    Rc2Cbc,
+    sync_stateless,
+    (
+        rc2_cbc_encrypt,
+        rc2_cbc_decrypt
+    )
+}
@@ -70,3 +70,47 @@ fn padding() {
     let results = t.results();
     assert_eq!(results[results.len() - 2], results[results.len() - 1]);
 }
+
+#[test]
+fn padding_argument() {
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("00000000000000000000000000000000");"#);
+    t.run(r#"iv = hexstr_to_data("00000000000000000000000000000000");"#);
+    t.run(r#"data = hexstr_to_data("f34481ec3cc627bacd5dc3fb08f2");"#);
+    t.ok(
+        r#"crypt = aes128_cbc_encrypt(key: key, data: data, iv: iv, padding: "pkcs7");"#,
+        decode_hex("02951a21ec28f356c52471d3e52d618e").unwrap(),
+    );
+    t.ok(
+        r#"aes128_cbc_decrypt(key: key, data: crypt, iv: iv, padding: "pkcs7");"#,
+        decode_hex("f34481ec3cc627bacd5dc3fb08f2").unwrap(),
+    );
+    t.ok(
+        r#"aes128_cbc_decrypt(key: key, data: crypt, iv: iv);"#,
+        decode_hex("f34481ec3cc627bacd5dc3fb08f20202").unwrap(),
+    );
+    t.ok(
+        r#"aes128_cbc_decrypt(key: key, data: aes128_cbc_encrypt(key: key, data: data, iv: iv), iv: iv, padding: "zero");"#,
+        decode_hex("f34481ec3cc627bacd5dc3fb08f2").unwrap(),
+    );
+    // a full block of padding is added to aligned data
+    t.ok(
+        r#"aes128_cbc_encrypt(key: key, data: hexstr_to_data("80000000000000000000000000000000"), iv: iv, padding: "pkcs7");"#,
+        decode_hex("3ad78e726c1ec02b7ebfe92b23d9ec34727c67c126475847a8994c0e82fb777e").unwrap(),
+    );
+    // invalid padding
+    t.ok(
+        r#"aes128_cbc_decrypt(key: key, data: aes128_cbc_encrypt(key: key, data: data, iv: iv), iv: iv, padding: "pkcs7");"#,
+        NaslValue::Null,
+    );
+    check_err_matches!(
+        t,
+        r#"aes128_cbc_encrypt(key: key, data: data, iv: iv, padding: "none");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    check_err_matches!(
+        t,
+        r#"aes128_cbc_encrypt(key: key, data: data, iv: iv, padding: "iso");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 use super::helper::decode_hex;
-use crate::nasl::test_utils::TestBuilder;
+use crate::nasl::test_prelude::*;
 
 #[test]
 fn aes128_ctr_crypt() {
@@ -52,3 +52,35 @@ fn aes256_ctr_crypt() {
         decode_hex("6bc1bee22e409f96e93d7e117393172a").unwrap(),
     );
 }
+
+#[test]
+fn counter_and_offset() {
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("2b7e151628aed2a6abf7158809cf4f3c");"#);
+    t.run(r#"iv = hexstr_to_data("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");"#);
+    // second block of the test vector in NIST SP 800-38A F.5.1
+    t.run(r#"data = hexstr_to_data("ae2d8a571e03ac9c9eb76fac45af8e51");"#);
+    t.ok(
+        r#"aes128_ctr_encrypt(key: key, data: data, iv: iv, offset: 16);"#,
+        decode_hex("9806f66b7970fdff8617187bb9fffdff").unwrap(),
+    );
+    // continue in the middle of a block
+    t.ok(
+        r#"aes128_ctr_decrypt(key: key, data: hexstr_to_data("7970fdff8617187bb9fffdff"), iv: iv, offset: 20);"#,
+        decode_hex("1e03ac9c9eb76fac45af8e51").unwrap(),
+    );
+    t.run(r#"nonce = hexstr_to_data("f0f1f2f3f4f5f6f70000000000000000");"#);
+    t.ok(
+        r#"aes128_ctr_encrypt(key: key, data: data, iv: nonce, counter: 3);"#,
+        decode_hex("773a23238a191c08a5e4cb268e951009").unwrap(),
+    );
+    t.ok(
+        r#"aes128_ctr_encrypt(key: key, data: data, iv: nonce, counter: 1, offset: 32);"#,
+        decode_hex("773a23238a191c08a5e4cb268e951009").unwrap(),
+    );
+    check_err_matches!(
+        t,
+        r#"aes128_ctr_encrypt(key: key, data: data, iv: iv, counter: -1);"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+
+#[test]
+fn bn_cmp() {
+    let mut t = TestBuilder::default();
+    t.ok(
+        "bn_cmp(key1: raw_string(0x00, 0x01), key2: raw_string(0x02));",
+        -1,
+    );
+    t.ok(
+        "bn_cmp(key1: raw_string(0x00, 0x02), key2: raw_string(0x02));",
+        0,
+    );
+    t.ok(
+        "bn_cmp(key1: raw_string(0x01, 0x00), key2: raw_string(0xff));",
+        1,
+    );
+}
+
+#[test]
+fn conversion() {
+    let mut t = TestBuilder::default();
+    t.ok(r#"bn_hex2raw("0x000102");"#, vec![1u8, 2]);
+    t.ok(r#"bn_hex2raw("abc");"#, vec![0x0au8, 0xbc]);
+    t.ok("bn_raw2hex(raw_string(0x00, 0x01, 0x02));", "102");
+    check_err_matches!(
+        t,
+        r#"bn_hex2raw("xyz");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn modexp() {
+    let mut t = TestBuilder::default();
+    t.ok(
+        "modexp(base: raw_string(4), exponent: raw_string(13), modulus: raw_string(0x01, 0xf1));",
+        vec![0x01u8, 0xbd],
+    );
+    // leading zero bytes are not returned
+    t.ok(
+        "modexp(base: raw_string(0x00, 0x02), exponent: raw_string(1), modulus: raw_string(0xff, 0xff));",
+        vec![2u8],
+    );
+    check_err_matches!(
+        t,
+        "modexp(base: raw_string(2), exponent: raw_string(1), modulus: raw_string(0));",
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn bn_random() {
+    let mut t = TestBuilder::default();
+    t.ok("strlen(bn_raw2hex(bn_random(need: 12)));", 3);
+    t.ok("strlen(hexstr(bn_random(need: 256)));", 64);
+    check_err_matches!(
+        t,
+        "bn_random(need: 0);",
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
@@ -0,0 +1,16 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+
+#[test]
+fn secure_memcmp() {
+    let mut t = TestBuilder::default();
+    t.run(r#"mac = hexstr_to_data("a1b2c3d4");"#);
+    t.ok(r#"secure_memcmp(mac, hexstr_to_data("a1b2c3d4"));"#, true);
+    t.ok(r#"secure_memcmp(mac, hexstr_to_data("a1b2c3d5"));"#, false);
+    t.ok(r#"secure_memcmp(mac, hexstr_to_data("a1b2c3"));"#, false);
+    t.ok(r#"secure_memcmp("secret", "secret");"#, true);
+    t.ok(r#"secure_memcmp("", "");"#, true);
+}
@@ -0,0 +1,45 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+
+const BLOB: &str = "0000000b7373682d65643235353139000000202d63ad9257ce49cff3d477d45dae9ada2382a9981f3ff9bdcbd9a4efd8f76e55";
+const LINE: &str =
+    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIC1jrZJXzknP89R31F2umtojgqmYHz/5vcvZpO/Y925V test";
+
+#[test]
+fn ssh_fingerprint_md5() {
+    let mut t = TestBuilder::default();
+    t.ok(
+        format!(r#"ssh_fingerprint_md5(hexstr_to_data("{BLOB}"));"#),
+        "MD5:c2:9c:e2:0a:6d:fe:7b:70:e7:7c:92:67:09:61:24:70",
+    );
+    t.ok(
+        format!(r#"ssh_fingerprint_md5("{LINE}");"#),
+        "MD5:c2:9c:e2:0a:6d:fe:7b:70:e7:7c:92:67:09:61:24:70",
+    );
+    check_err_matches!(
+        t,
+        r#"ssh_fingerprint_md5("no key");"#,
+        FunctionErrorKind::WrongArgument { .. }
+    );
+}
+
+#[test]
+fn ssh_fingerprint_sha256() {
+    let mut t = TestBuilder::default();
+    t.ok(
+        format!(r#"ssh_fingerprint_sha256(hexstr_to_data("{BLOB}"));"#),
+        "SHA256:1QBWDPOBWFkLPx7B9Ef0HCQxirymx8nIstOd09joPJ4",
+    );
+    t.ok(
+        format!(r#"ssh_fingerprint_sha256("{LINE}");"#),
+        "SHA256:1QBWDPOBWFkLPx7B9Ef0HCQxirymx8nIstOd09joPJ4",
+    );
+    check_err_matches!(
+        t,
+        r#"ssh_fingerprint_sha256(hexstr_to_data("0000000b7373682d"));"#,
+        FunctionErrorKind::WrongArgument { .. }
+    );
+}
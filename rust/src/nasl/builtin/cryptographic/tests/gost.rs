@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+
+use super::helper::decode_hex;
+
+#[test]
+fn streebog() {
+    // M1 from RFC 6986
+    let mut t = TestBuilder::default();
+    t.run(r#"m = "012345678901234567890123456789012345678901234567890123456789012";"#);
+    t.ok(
+        "STREEBOG512(m);",
+        decode_hex("1b54d01a4af5b9d5cc3d86d68d285462b19abc2475222f35c085122be4ba1ffa00ad30f8767b3a82384c6574f024c311e2a481332b08ef7f41797891c1646f48").unwrap(),
+    );
+    t.ok(
+        "STREEBOG256(m);",
+        decode_hex("9d151eefd8590b89daa6ba6cb74af9275dd051026bb149a452fd84e5e57b5500").unwrap(),
+    );
+    t.ok(
+        r#"STREEBOG256("");"#,
+        decode_hex("3f539a213e97c802cc229d474c6aa32a825a360b2a933a949fd925208d9ce1bb").unwrap(),
+    );
+    t.ok("STREEBOG512();", NaslValue::Null);
+}
+
+#[test]
+fn kuznyechik_cbc_crypt() {
+    // Single block test vector from RFC 7801
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("8899aabbccddeeff0011223344556677fedcba98765432100123456789abcdef");"#);
+    t.run(r#"data = hexstr_to_data("1122334455667700ffeeddccbbaa9988");"#);
+    t.run(r#"iv = hexstr_to_data("00000000000000000000000000000000");"#);
+    t.ok(
+        r#"crypt = kuznyechik_cbc_encrypt(key: key, data: data, iv: iv);"#,
+        decode_hex("7f679d90bebc24305a468d42b9d4edcd").unwrap(),
+    );
+    t.ok(
+        r#"kuznyechik_cbc_decrypt(key: key, data: crypt, iv: iv);"#,
+        decode_hex("1122334455667700ffeeddccbbaa9988").unwrap(),
+    );
+    t.ok(
+        r#"kuznyechik_cbc_decrypt(key: key, data: kuznyechik_cbc_encrypt(key: key, data: "hola mundo", iv: iv), iv: iv, len: 10);"#,
+        "hola mundo".as_bytes().to_vec(),
+    );
+}
+
+#[test]
+fn kuznyechik_ctr_crypt() {
+    // Test vector from GOST R 34.13-2015
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("8899aabbccddeeff0011223344556677fedcba98765432100123456789abcdef");"#);
+    t.run(r#"data = hexstr_to_data("1122334455667700ffeeddccbbaa9988");"#);
+    t.run(r#"iv = hexstr_to_data("1234567890abcef00000000000000000");"#);
+    t.ok(
+        r#"crypt = kuznyechik_ctr_encrypt(key: key, data: data, iv: iv);"#,
+        decode_hex("f195d8bec10ed1dbd57b5fa240bda1b8").unwrap(),
+    );
+    t.ok(
+        r#"kuznyechik_ctr_decrypt(key: key, data: crypt, iv: iv);"#,
+        decode_hex("1122334455667700ffeeddccbbaa9988").unwrap(),
+    );
+}
+
+#[test]
+fn magma_crypt() {
+    // Test vectors from RFC 8891 and GOST R 34.13-2015
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("ffeeddccbbaa99887766554433221100f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");"#);
+    t.run(r#"data = hexstr_to_data("fedcba9876543210");"#);
+    t.run(r#"iv = hexstr_to_data("0000000000000000");"#);
+    t.ok(
+        r#"crypt = magma_cbc_encrypt(key: key, data: data, iv: iv);"#,
+        decode_hex("4ee901e5c2d8ca3d").unwrap(),
+    );
+    t.ok(
+        r#"magma_cbc_decrypt(key: key, data: crypt, iv: iv);"#,
+        decode_hex("fedcba9876543210").unwrap(),
+    );
+    t.run(r#"iv = hexstr_to_data("1234567800000000");"#);
+    t.ok(
+        r#"crypt = magma_ctr_encrypt(key: key, data: hexstr_to_data("92def06b3c130a59"), iv: iv);"#,
+        decode_hex("4e98110c97b7b93c").unwrap(),
+    );
+    t.ok(
+        r#"magma_ctr_decrypt(key: key, data: crypt, iv: iv);"#,
+        decode_hex("92def06b3c130a59").unwrap(),
+    );
+}
@@ -86,3 +86,37 @@ fn hash_ripemd160() {
         ],
     );
 }
+
+#[test]
+fn streaming_hash() {
+    let mut t = TestBuilder::default();
+    t.run(r#"hd = hash_init(algorithm: "sha256");"#);
+    t.ok(r#"hash_update(hd: hd, data: "hola ");"#, NaslValue::Null);
+    t.ok(
+        r#"hash_update(hd: hd, data: raw_string(0x6d, 0x75, 0x6e, 0x64, 0x6f));"#,
+        NaslValue::Null,
+    );
+    t.ok(r#"hash_final(hd: hd) == SHA256("hola mundo");"#, true);
+    check_err_matches!(
+        t,
+        r#"hash_final(hd: hd);"#,
+        FunctionErrorKind::Diagnostic(..)
+    );
+    t.ok(
+        r#"hash_final(hd: hash_init(algorithm: "MD5")) == MD5("");"#,
+        true,
+    );
+    check_err_matches!(
+        t,
+        r#"hash_init(algorithm: "sha3");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    // unfinished calculations are dropped once enough newer ones are started
+    t.run(r#"hd = hash_init(algorithm: "md5");"#);
+    t.run(r#"for (i = 0; i < 1024; i++) hash_init(algorithm: "md5");"#);
+    check_err_matches!(
+        t,
+        r#"hash_final(hd: hd);"#,
+        FunctionErrorKind::Diagnostic(..)
+    );
+}
@@ -2,7 +2,10 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use crate::nasl::test_prelude::*;
 use crate::nasl::test_utils::check_code_result;
+use crate::nasl::FSPluginLoader;
+use crate::storage::DefaultDispatcher;
 
 #[test]
 fn hmac_md2() {
@@ -53,3 +56,75 @@ fn hmac_sha384() {
 fn hmac_sha512() {
     check_code_result(r#"HMAC_SHA512(key: "my_shared?key", data: "so much wow");"#, "7e251167d67f7f29fc978048d338f6ebe0d8bb5213f5ccacca50359b3435df19e60fa709241b98b0ed9e1aeb994df6f900c5fa87201c3fc971b0120968c96cb3");
 }
+
+#[test]
+fn streaming_hmac() {
+    let mut t = TestBuilder::default();
+    t.run(r#"hd = hmac_init(algorithm: "sha256", key: "my_shared?key");"#);
+    t.ok(r#"hmac_update(hd: hd, data: "so much ");"#, NaslValue::Null);
+    t.ok(
+        r#"hmac_update(hd: hd, data: raw_string(0x77, 0x6f, 0x77));"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        r#"hexstr(hmac_final(hd: hd)) == HMAC_SHA256(key: "my_shared?key", data: "so much wow");"#,
+        true,
+    );
+    check_err_matches!(
+        t,
+        r#"hmac_final(hd: hd);"#,
+        FunctionErrorKind::Diagnostic(..)
+    );
+    t.ok(
+        r#"hexstr(hmac_final(hd: hmac_init(algorithm: "MD5", key: "k"))) == HMAC_MD5(key: "k", data: "");"#,
+        true,
+    );
+    check_err_matches!(
+        t,
+        r#"hmac_init(algorithm: "md4", key: "k");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    // hash_init with a key shares the calculations of hmac_init
+    t.run(r#"hd = hash_init(algorithm: "sha1", key: "k");"#);
+    t.ok(r#"hash_update(hd: hd, data: "wow");"#, NaslValue::Null);
+    t.ok(
+        r#"hexstr(hmac_final(hd: hd)) == HMAC_SHA1(key: "k", data: "wow");"#,
+        true,
+    );
+}
+
+#[test]
+fn hmac_of_file() {
+    let root = std::env::temp_dir().join(format!("nasl-hmac-test-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    // larger than a chunk and not valid UTF-8
+    let firmware: Vec<u8> = (0..200_000u32).map(|x| (x % 251) as u8).collect();
+    std::fs::write(root.join("firmware.bin"), &firmware).unwrap();
+    let loader = FSPluginLoader::new(&root);
+    let mut t = TestBuilder::default()
+        .with_context(ContextFactory::new(loader, DefaultDispatcher::default()));
+    t.run(r#"hd = hmac_init(algorithm: "sha256", key: "secret");"#);
+    for chunk in firmware.chunks(50_000) {
+        let bytes: Vec<String> = chunk.iter().map(|x| x.to_string()).collect();
+        t.run(format!(
+            "hmac_update(hd: hd, data: raw_string({}));",
+            bytes.join(",")
+        ));
+    }
+    t.run("expected = hmac_final(hd: hd);");
+    t.ok(
+        r#"hmac_file(algorithm: "sha256", key: "secret", file: "firmware.bin") == expected;"#,
+        true,
+    );
+    t.ok(
+        r#"hmac_file(algorithm: "sha256", key: "secret", file: "missing.bin");"#,
+        NaslValue::Null,
+    );
+    check_err_matches!(
+        t,
+        r#"hmac_file(algorithm: "sha3", key: "secret", file: "firmware.bin");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    drop(t);
+    std::fs::remove_dir_all(root).unwrap();
+}
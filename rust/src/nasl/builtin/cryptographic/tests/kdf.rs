@@ -0,0 +1,119 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use super::helper::decode_hex;
+use crate::nasl::builtin::cryptographic::kdf::sp800_108_counter_hmac_sha256;
+use crate::nasl::test_prelude::*;
+use sha2::{Digest, Sha512};
+
+#[test]
+fn smb3kdf() {
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("7cd451825d0450d235424e44ba6e78cc");"#);
+    t.ok(
+        r#"smb3kdf(key: key, label: "SMB2AESCMAC" + raw_string(0x00), ctx: "SmbSign" + raw_string(0x00), lvalue: 128);"#,
+        decode_hex("0b7e9c5cac36c0f6ea9ab275298cedce").unwrap(),
+    );
+    t.ok(
+        r#"smb3kdf(key: key, label: "SMBSigningKey" + raw_string(0x00), ctx: crap(length: 64, data: raw_string(0x00)), lvalue: 256);"#,
+        decode_hex("832e20002e00dbe0d6996e52a23e3549ea67f69f9e1b5890fa149dea4857f11d").unwrap(),
+    );
+    check_err_matches!(
+        t,
+        r#"smb3kdf(key: key, label: "a", ctx: "b", lvalue: 64);"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn longer_output() {
+    let first = sp800_108_counter_hmac_sha256(b"key", b"label", b"context", 512).unwrap();
+    assert_eq!(first.len(), 64);
+    let short = sp800_108_counter_hmac_sha256(b"key", b"label", b"context", 100).unwrap();
+    assert_eq!(short.len(), 13);
+}
+
+#[test]
+fn tls_prf() {
+    let mut t = TestBuilder::default();
+    t.run(r#"secret = hexstr_to_data("9bbe436ba940f017b17652849a71db35");"#);
+    t.run(r#"seed = hexstr_to_data("a0ba9f936cda311827a6f796ffd5198c");"#);
+    t.ok(
+        r#"prf_sha256(secret: secret, seed: seed, label: "test label", outlen: 100);"#,
+        decode_hex("e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff70187347b66").unwrap(),
+    );
+    t.ok(
+        r#"tls1_prf(secret: secret, seed: seed, label: "test label", outlen: 48);"#,
+        decode_hex("661740e6f98bc901efd2738502a71c03f76dd2f86298549b1148eff06714cf0f6b7c532cd8c69f1530e0bb680eec34c4").unwrap(),
+    );
+    check_err_matches!(
+        t,
+        r#"tls1_prf(secret: secret, seed: seed, label: "test label", outlen: -1);"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn tls13_hkdf_expand_label() {
+    let mut t = TestBuilder::default();
+    // derived secret of the simple 1-RTT handshake in RFC 8448
+    t.run(r#"early = hexstr_to_data("33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a");"#);
+    t.ok(
+        r#"tls13_hkdf_expand_label(secret: early, label: "derived", context: SHA256(""), length: 32);"#,
+        decode_hex("6f2615a108c702c5678f54fc9dbab69716c076189c48250cebeac3576c3611ba").unwrap(),
+    );
+    check_err_matches!(
+        t,
+        r#"tls13_hkdf_expand_label(secret: early, label: "key", length: 16, hash: "md5");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn smb2_signing_key() {
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("7cd451825d0450d235424e44ba6e78cc");"#);
+    t.ok(
+        r#"smb2_signing_key(session_key: key, dialect: 0x0210);"#,
+        decode_hex("7cd451825d0450d235424e44ba6e78cc").unwrap(),
+    );
+    t.ok(
+        r#"smb2_signing_key(session_key: key, dialect: 0x0302);"#,
+        decode_hex("0b7e9c5cac36c0f6ea9ab275298cedce").unwrap(),
+    );
+    t.ok(
+        r#"smb2_signing_key(session_key: hexstr_to_data("7cd451825d0450d235424e44ba6e78cc0102"), dialect: 0x0300);"#,
+        decode_hex("0b7e9c5cac36c0f6ea9ab275298cedce").unwrap(),
+    );
+    t.ok(
+        r#"smb2_signing_key(session_key: key, dialect: 0x0311, preauth_hash: crap(length: 64, data: raw_string(0x00))) == smb3kdf(key: key, label: "SMBSigningKey" + raw_string(0x00), ctx: crap(length: 64, data: raw_string(0x00)), lvalue: 128);"#,
+        true,
+    );
+    check_err_matches!(
+        t,
+        r#"smb2_signing_key(session_key: key, dialect: 0x0311);"#,
+        FunctionErrorKind::MissingArguments(_)
+    );
+    check_err_matches!(
+        t,
+        r#"smb2_signing_key(session_key: key, dialect: 0x0100);"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn smb2_preauth_hash() {
+    let mut t = TestBuilder::default();
+    t.run(r#"zero = crap(length: 64, data: raw_string(0x00));"#);
+    t.ok(
+        r#"smb2_preauth_hash(data: "negotiate") == SHA512(zero + "negotiate");"#,
+        true,
+    );
+    t.run(r#"h = smb2_preauth_hash(data: "negotiate");"#);
+    let h: Vec<u8> = Sha512::digest([[0; 64].as_slice(), b"negotiate"].concat()).to_vec();
+    t.ok(
+        r#"smb2_preauth_hash(hash: h, data: "response");"#,
+        Sha512::digest([h, b"response".to_vec()].concat()).to_vec(),
+    );
+}
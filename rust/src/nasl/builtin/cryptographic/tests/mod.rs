@@ -4,9 +4,16 @@ mod aes_cmac;
 mod aes_ctr;
 mod aes_gcm;
 mod bf_cbc;
+mod bn;
+mod compare;
 mod des;
+mod fingerprint;
+mod gost;
 mod hash;
 mod helper;
 mod hmac;
+mod jwt;
+mod kdf;
+mod rc2;
 mod rc4;
 mod rsa;
@@ -0,0 +1,58 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+
+use super::helper::decode_hex;
+
+#[test]
+fn rc2_cbc_rfc2268() {
+    let mut t = TestBuilder::default();
+    t.run(r#"iv = raw_string(0, 0, 0, 0, 0, 0, 0, 0);"#);
+    t.ok(
+        r#"rc2_cbc_encrypt(key: hexstr_to_data("0000000000000000"), bits: 63, iv: iv, data: iv);"#,
+        decode_hex("ebb773f993278eff").unwrap(),
+    );
+    t.ok(
+        r#"rc2_cbc_encrypt(key: hexstr_to_data("3000000000000000"), iv: iv, data: hexstr_to_data("1000000000000001"));"#,
+        decode_hex("30649edf9be7d2c2").unwrap(),
+    );
+    t.ok(
+        r#"rc2_cbc_encrypt(key: hexstr_to_data("88bca90e90875a7f0f79c384627bafb216f80a6f85920584c42fceb0be255daf1e"), bits: 129, iv: iv, data: iv);"#,
+        decode_hex("5b78d3a43dfff1f1").unwrap(),
+    );
+}
+
+#[test]
+fn rc2_cbc_crypt() {
+    let mut t = TestBuilder::default();
+    t.run(r#"key = hexstr_to_data("88bca90e90875a7f0f79c384627bafb2");"#);
+    t.run(r#"iv = hexstr_to_data("0001020304050607");"#);
+    t.ok(
+        r#"crypt = rc2_cbc_encrypt(key: key, bits: 40, iv: iv, data: "Hello, RC2 world");"#,
+        decode_hex("b6bf6e1e11439ab3d58cba1404522a37").unwrap(),
+    );
+    t.ok(
+        r#"rc2_cbc_decrypt(key: key, bits: 40, iv: iv, data: crypt);"#,
+        "Hello, RC2 world".as_bytes().to_vec(),
+    );
+    t.ok(
+        r#"crypt = rc2_cbc_encrypt(key: key, iv: iv, data: "legacy");"#,
+        decode_hex("d31f7593be50b4bd").unwrap(),
+    );
+    t.ok(
+        r#"rc2_cbc_decrypt(key: key, iv: iv, data: crypt, len: 6);"#,
+        "legacy".as_bytes().to_vec(),
+    );
+    check_err_matches!(
+        t,
+        r#"rc2_cbc_decrypt(key: key, iv: "short", data: crypt);"#,
+        FunctionErrorKind::WrongArgument { .. }
+    );
+    check_err_matches!(
+        t,
+        r#"rc2_cbc_decrypt(key: key, iv: iv, data: "odd");"#,
+        FunctionErrorKind::WrongArgument { .. }
+    );
+}
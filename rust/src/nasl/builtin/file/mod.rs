@@ -0,0 +1,70 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions working with files on the scanner host.
+//!
+//! Unlike the C implementation the files are not written to arbitrary paths but to the
+//! temporary directory of the scan, see [TempDir](crate::nasl::utils::TempDir). Names are
+//! relative to that directory, absolute paths are only accepted when they point into the
+//! directory returned by get_tmp_dir.
+
+#[cfg(test)]
+mod tests;
+
+use std::io;
+
+use crate::nasl::prelude::*;
+
+fn to_error(e: io::Error) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(e.to_string(), None)
+}
+
+/// Returns the temporary directory of the scan with a trailing slash.
+#[nasl_function]
+fn get_tmp_dir(context: &Context) -> Result<String, FunctionErrorKind> {
+    let path = context.temp_dir().path().map_err(to_error)?;
+    Ok(format!("{}/", path.display()))
+}
+
+/// Writes the named argument data into the file given by the named argument file.
+///
+/// An existing file is replaced. Returns the number of bytes written.
+#[nasl_function(named(data, file))]
+fn fwrite(context: &Context, data: NaslValue, file: &str) -> Result<usize, FunctionErrorKind> {
+    context
+        .temp_dir()
+        .write(file, &Vec::<u8>::from(data))
+        .map_err(to_error)
+}
+
+/// Reads the whole file given as unnamed argument.
+///
+/// Returns NULL when the file does not exist.
+#[nasl_function]
+fn fread(context: &Context, file: &str) -> Result<NaslValue, FunctionErrorKind> {
+    match context.temp_dir().read(file) {
+        Ok(data) => Ok(NaslValue::Data(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(NaslValue::Null),
+        Err(e) => Err(to_error(e)),
+    }
+}
+
+/// Removes the file given as unnamed argument.
+#[nasl_function]
+fn unlink(context: &Context, file: &str) -> Result<(), FunctionErrorKind> {
+    context.temp_dir().remove(file).map_err(to_error)
+}
+
+pub struct Files;
+
+function_set! {
+    Files,
+    sync_stateless,
+    (
+        get_tmp_dir,
+        fwrite,
+        fread,
+        unlink,
+    )
+}
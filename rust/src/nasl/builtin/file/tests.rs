@@ -0,0 +1,42 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::test_prelude::*;
+use crate::nasl::utils::TempDir;
+
+#[test]
+fn write_read_unlink() {
+    let base = std::env::temp_dir().join(format!("nasl-file-test-{}", std::process::id()));
+    let mut t = TestBuilder::default()
+        .with_context(ContextFactory::default().temp_dir(TempDir::new(&base, 16)));
+    t.check(
+        "dir = get_tmp_dir();",
+        |x| matches!(x, Ok(NaslValue::String(dir)) if dir.ends_with('/')),
+        Some("temporary directory with trailing slash"),
+    );
+    t.ok(r#"fwrite(data: "hello", file: "a");"#, 5);
+    t.ok(r#"fwrite(data: raw_string(0, 1), file: dir + "b");"#, 2);
+    t.ok(r#"fread("a");"#, NaslValue::Data(b"hello".to_vec()));
+    t.ok(r#"fread(dir + "b");"#, NaslValue::Data(vec![0, 1]));
+    check_err_matches!(
+        t,
+        r#"fwrite(data: "0123456789", file: "c");"#,
+        FunctionErrorKind::Diagnostic(_, _)
+    );
+    check_err_matches!(
+        t,
+        r#"fwrite(data: "x", file: "../x");"#,
+        FunctionErrorKind::Diagnostic(_, _)
+    );
+    check_err_matches!(
+        t,
+        r#"fread("/etc/hostname");"#,
+        FunctionErrorKind::Diagnostic(_, _)
+    );
+    t.ok(r#"unlink("a");"#, NaslValue::Null);
+    t.ok(r#"fread("a");"#, NaslValue::Null);
+    t.ok(r#"fwrite(data: "0123456789", file: "c");"#, 10);
+    drop(t);
+    std::fs::remove_dir(base).unwrap();
+}
@@ -5,11 +5,19 @@
 #[cfg(test)]
 mod tests;
 
-use std::{net::IpAddr, str::FromStr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
 
+use indexmap::IndexMap;
+
+use super::network::unix::target_socket_path;
 use crate::function_set;
+use crate::nasl::utils::resolver::{RecordData, RecordType, ResourceRecord, DNS_PORT};
 use crate::nasl::utils::{error::FunctionErrorKind, lookup_keys::TARGET};
 
+use crate::nasl::prelude::nasl_function;
 use crate::nasl::syntax::NaslValue;
 use crate::nasl::utils::{Context, ContextType, Register};
 
@@ -17,9 +25,7 @@ use crate::nasl::utils::{Context, ContextType, Register};
 ///
 /// It does lookup TARGET and when not found falls back to 127.0.0.1 to resolve.
 /// If the TARGET is not a IP address than we assume that it already is a fqdn or a hostname and will return that instead.
-fn resolve_hostname(register: &Register) -> Result<String, FunctionErrorKind> {
-    use std::net::ToSocketAddrs;
-
+fn resolve_hostname(register: &Register, context: &Context) -> Result<String, FunctionErrorKind> {
     let default_ip = "127.0.0.1";
     // currently we use shadow variables as _FC_ANON_ARGS; the original openvas uses redis for that purpose.
     let target = register.named(TARGET).map_or_else(
@@ -30,8 +36,8 @@ fn resolve_hostname(register: &Register) -> Result<String, FunctionErrorKind> {
         },
     );
 
-    match target.to_socket_addrs() {
-        Ok(mut addr) => Ok(addr.next().map_or_else(String::new, |x| x.to_string())),
+    match IpAddr::from_str(&target) {
+        Ok(ip) => Ok(context.dns_cache().lookup_addr(&ip).unwrap_or(target)),
         // assumes that target is already a hostname
         Err(_) => Ok(target),
     }
@@ -41,22 +47,164 @@ fn resolve_hostname(register: &Register) -> Result<String, FunctionErrorKind> {
 ///
 /// As of now (2023-01-20) there is no vhost handling.
 /// Therefore this function does load the registered TARGET and if it is an IP Address resolves it via DNS instead.
-fn get_host_names(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
-    resolve_hostname(register).map(|x| NaslValue::Array(vec![NaslValue::String(x)]))
+fn get_host_names(register: &Register, context: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    resolve_hostname(register, context).map(|x| NaslValue::Array(vec![NaslValue::String(x)]))
 }
 
 /// NASL function to get the current hostname
 ///
 /// As of now (2023-01-20) there is no vhost handling.
 /// Therefore this function does load the registered TARGET and if it is an IP Address resolves it via DNS instead.
-fn get_host_name(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
-    resolve_hostname(register).map(NaslValue::String)
+fn get_host_name(register: &Register, context: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    resolve_hostname(register, context).map(NaslValue::String)
+}
+
+/// Returns the addresses of the hostname ordered by the preferred address family.
+///
+/// The lookup is done by the resolver of the scan, an IP address is returned as is.
+fn lookup_host(context: &Context, hostname: &str) -> Vec<IpAddr> {
+    if let Ok(ip) = IpAddr::from_str(hostname) {
+        return vec![ip];
+    }
+    context
+        .dns_cache()
+        .lookup_host(hostname)
+        .map(|x| context.address_family().sort(x))
+        .unwrap_or_default()
+}
+
+/// NASL function to resolve a hostname to its preferred address
+///
+/// Returns NULL when the hostname cannot be resolved.
+#[nasl_function(named(hostname))]
+fn resolve_host_name(context: &Context, hostname: &str) -> Option<String> {
+    lookup_host(context, hostname)
+        .first()
+        .map(|x| x.to_string())
+}
+
+/// NASL function to resolve a hostname to all of its addresses
+///
+/// Returns an empty array when the hostname cannot be resolved.
+#[nasl_function(named(hostname))]
+fn resolve_hostname_to_multiple_ips(context: &Context, hostname: &str) -> Vec<String> {
+    lookup_host(context, hostname)
+        .iter()
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// NASL function to get the hostname of an IP address from its PTR record
+///
+/// Returns NULL when the address has no PTR record.
+#[nasl_function(named(ip))]
+fn resolve_ip_to_hostname(
+    context: &Context,
+    ip: &str,
+) -> Result<Option<String>, FunctionErrorKind> {
+    let ip = IpAddr::from_str(ip)
+        .map_err(|_| FunctionErrorKind::wrong_argument("ip", "IP address", ip))?;
+    Ok(context.dns_cache().lookup_addr(&ip))
+}
+
+/// Returns the record as array with the keys name, type and ttl and the fields of its type.
+pub(crate) fn record_to_nasl(record: ResourceRecord) -> NaslValue {
+    let mut result = IndexMap::new();
+    let mut set = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    set("name", NaslValue::String(record.name));
+    set("type", NaslValue::String(record.record_type.to_string()));
+    set("ttl", NaslValue::Number(record.ttl as i64));
+    match record.data {
+        RecordData::Address(x) => set("address", NaslValue::String(x.to_string())),
+        RecordData::Name(x) => set("target", NaslValue::String(x)),
+        RecordData::Mx {
+            preference,
+            exchange,
+        } => {
+            set("preference", NaslValue::Number(preference as i64));
+            set("exchange", NaslValue::String(exchange))
+        }
+        RecordData::Txt(x) => {
+            set("text", NaslValue::String(x.concat()));
+            set(
+                "strings",
+                NaslValue::Array(x.into_iter().map(NaslValue::String).collect()),
+            )
+        }
+        RecordData::Srv {
+            priority,
+            weight,
+            port,
+            target,
+        } => {
+            set("priority", NaslValue::Number(priority as i64));
+            set("weight", NaslValue::Number(weight as i64));
+            set("port", NaslValue::Number(port as i64));
+            set("target", NaslValue::String(target))
+        }
+        RecordData::Caa { flags, tag, value } => {
+            set("flags", NaslValue::Number(flags as i64));
+            set("tag", NaslValue::String(tag));
+            set("value", NaslValue::String(value))
+        }
+    };
+    NaslValue::Dict(result)
+}
+
+/// Parses the type argument of a query, A when it is not given.
+pub(crate) fn parse_record_type(value: Option<&str>) -> Result<RecordType, FunctionErrorKind> {
+    match value {
+        Some(x) => RecordType::from_str(x).map_err(|_| {
+            FunctionErrorKind::wrong_argument(
+                "type",
+                "A, AAAA, CNAME, NS, PTR, MX, TXT, SRV or CAA",
+                x,
+            )
+        }),
+        None => Ok(RecordType::A),
+    }
+}
+
+/// NASL function to query the records of a name
+///
+/// Each record is returned as array with the keys name, type and ttl and the fields of its
+/// type. The query is sent to the given nameserver, otherwise to the resolver of the scan.
+/// Returns NULL when no nameserver answers.
+#[nasl_function(named(name, r#type, server))]
+fn dns_query(
+    context: &Context,
+    name: &str,
+    r#type: Option<&str>,
+    server: Option<&str>,
+) -> Result<Option<Vec<NaslValue>>, FunctionErrorKind> {
+    let record_type = parse_record_type(r#type)?;
+    let server = match server {
+        Some(x) => Some(
+            SocketAddr::from_str(x)
+                .or_else(|_| IpAddr::from_str(x).map(|ip| SocketAddr::new(ip, DNS_PORT)))
+                .map_err(|_| FunctionErrorKind::wrong_argument("server", "IP address", x))?,
+        ),
+        None => None,
+    };
+    match context
+        .dns_cache()
+        .resolver()
+        .query_records(name, record_type, server)
+    {
+        Ok(records) => Ok(Some(records.into_iter().map(record_to_nasl).collect())),
+        Err(e) => {
+            tracing::debug!(name, %record_type, %e, "DNS query failed");
+            Ok(None)
+        }
+    }
 }
 
 /// Return the target's IP address as IpAddr.
 pub fn get_host_ip(context: &Context) -> Result<IpAddr, FunctionErrorKind> {
     let default_ip = "127.0.0.1";
     let r_sock_addr = match context.target() {
+        // a target given as UNIX socket is the local host
+        x if target_socket_path(x).is_some() => IpAddr::from_str(default_ip),
         x if !x.is_empty() => IpAddr::from_str(x),
         _ => IpAddr::from_str(default_ip),
     };
@@ -87,6 +235,15 @@ function_set! {
     (
         get_host_name,
         get_host_names,
-        (nasl_get_host_ip, "get_host_ip")
+        (nasl_get_host_ip, "get_host_ip"),
+        resolve_host_name,
+        (resolve_host_name, "resolve_hostname"),
+        resolve_hostname_to_multiple_ips,
+        (
+            resolve_hostname_to_multiple_ips,
+            "resolve_host_name_to_multiple_ips"
+        ),
+        resolve_ip_to_hostname,
+        dns_query,
     )
 }
@@ -4,6 +4,13 @@
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, UdpSocket},
+        thread,
+    };
+
+    use crate::nasl::test_prelude::*;
     use crate::{check_code_result_matches, nasl::prelude::*};
 
     #[test]
@@ -11,4 +18,80 @@ mod tests {
         check_code_result_matches!("get_host_name();", NaslValue::String(_));
         check_code_result_matches!("get_host_names();", NaslValue::Array(_));
     }
+
+    #[test]
+    fn resolve_host_name() {
+        let mut t = TestBuilder::default();
+        t.ok(r#"resolve_host_name(hostname: "127.0.0.1");"#, "127.0.0.1");
+        t.check(
+            r#"resolve_hostname(hostname: "localhost.");"#,
+            |x| matches!(x, Ok(NaslValue::String(_))),
+            Some("String"),
+        );
+        t.ok(
+            r#"resolve_hostname_to_multiple_ips(hostname: "::1");"#,
+            vec!["::1".to_string()],
+        );
+        t.ok(
+            r#"resolve_host_name_to_multiple_ips(hostname: "does-not-exist.invalid");"#,
+            NaslValue::Array(vec![]),
+        );
+        t.ok(
+            r#"resolve_host_name(hostname: "does-not-exist.invalid");"#,
+            NaslValue::Null,
+        );
+        check_err_matches!(
+            t,
+            r#"resolve_ip_to_hostname(ip: "localhost");"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+
+    /// Starts a nameserver answering queries with an A record, the UDP response is truncated.
+    fn nameserver() -> u16 {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = tcp.local_addr().unwrap().port();
+        let udp = UdpSocket::bind(("127.0.0.1", port)).unwrap();
+        thread::spawn(move || {
+            let mut buf = [0; 512];
+            let (n, peer) = udp.recv_from(&mut buf).unwrap();
+            let mut response = buf[..n].to_vec();
+            response[2..4].copy_from_slice(&[0x83, 0x80]);
+            udp.send_to(&response, peer).unwrap();
+        });
+        thread::spawn(move || {
+            let (mut stream, _) = tcp.accept().unwrap();
+            let mut length = [0; 2];
+            stream.read_exact(&mut length).unwrap();
+            let mut response = vec![0; u16::from_be_bytes(length) as usize];
+            stream.read_exact(&mut response).unwrap();
+            response[2..4].copy_from_slice(&[0x81, 0x80]);
+            response[6..8].copy_from_slice(&[0, 1]);
+            response.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 1]);
+            stream
+                .write_all(&(response.len() as u16).to_be_bytes())
+                .unwrap();
+            stream.write_all(&response).unwrap();
+        });
+        port
+    }
+
+    #[test]
+    fn dns_query() {
+        let port = nameserver();
+        let mut t = TestBuilder::default();
+        t.run(format!(
+            r#"r = dns_query(name: "www.example.com", type: "a", server: "127.0.0.1:{port}");"#
+        ));
+        t.run("a = r[0];");
+        t.ok("a['name'];", "www.example.com");
+        t.ok("a['type'];", "A");
+        t.ok("a['ttl'];", 60);
+        t.ok("a['address'];", "192.0.2.1");
+        check_err_matches!(
+            t,
+            r#"dns_query(name: "example.com", type: "AXFR");"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
 }
@@ -5,6 +5,7 @@
 //! Defines NASL functions to perform HTTP/2 request.
 // TODO: implement http functions once socket handling is available
 
+use super::network::{network_utils::target_service, socket::NaslSockets};
 use crate::nasl::prelude::*;
 use crate::nasl::utils::ContextType;
 
@@ -125,15 +126,13 @@ impl ServerCertVerifier for NoVerifier {
 impl NaslHttp {
     async fn request(
         &self,
+        stream: TcpStream,
         ip_str: &String,
-        port: u16,
         uri: String,
         data: String,
         method: Method,
         handle: &mut Handle,
     ) -> Result<(Parts, String), FunctionErrorKind> {
-        // Establish TCP connection to the server.
-
         let mut config = ClientConfig::builder()
             .dangerous()
             .with_custom_certificate_verifier(Arc::new(NoVerifier))
@@ -145,16 +144,6 @@ impl NaslHttp {
         let server_name = ip_str.clone().to_owned().try_into().unwrap();
 
         let connector = TlsConnector::from(Arc::new(config));
-        let stream = match TcpStream::connect(format!("{}:{}", ip_str, port)).await {
-            Ok(a) => a,
-            Err(e) => {
-                return Err(FunctionErrorKind::Diagnostic(
-                    e.to_string(),
-                    Some(NaslValue::Null),
-                ));
-            }
-        };
-
         let stream = match connector.connect(server_name, stream).await {
             Ok(a) => a,
             Err(e) => {
@@ -308,7 +297,20 @@ impl NaslHttp {
 
         uri = format!("{}{}", uri, item);
 
-        match self.request(&ip_str, port, uri, data, method, handle).await {
+        // connects like the socket builtins, bound to the source and through the proxy
+        let (addrs, port, timeout) = target_service(ctx, Some(port as i64), port, None)?;
+        let stream = NaslSockets::connect_tcp(ctx, &addrs, port, None, timeout, None)
+            .and_then(|conn| conn.into_tcp())
+            .and_then(|tcp| {
+                tcp.set_nonblocking(true)?;
+                TcpStream::from_std(tcp)
+            })
+            .map_err(|e| FunctionErrorKind::Diagnostic(e.to_string(), Some(NaslValue::Null)))?;
+
+        match self
+            .request(stream, &ip_str, uri, data, method, handle)
+            .await
+        {
             Ok((head, body)) => {
                 handle.http_code = head.status.as_u16();
                 let mut header_str = String::new();
@@ -0,0 +1,256 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding of search filters given in their string representation (RFC 4515).
+
+use super::super::asn1::der::tlv;
+use super::message::{OCTET_STRING, SEQUENCE};
+
+const AND: u8 = 0xa0;
+const OR: u8 = 0xa1;
+const NOT: u8 = 0xa2;
+const EQUALITY: u8 = 0xa3;
+const SUBSTRINGS: u8 = 0xa4;
+const GREATER_OR_EQUAL: u8 = 0xa5;
+const LESS_OR_EQUAL: u8 = 0xa6;
+const PRESENT: u8 = 0x87;
+const APPROX: u8 = 0xa8;
+const EXTENSIBLE: u8 = 0xa9;
+
+/// Maximum nesting depth of and, or and not filters.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn expect(&mut self, c: u8) -> Result<(), &'static str> {
+        match self.data.get(self.position) {
+            Some(x) if *x == c => {
+                self.position += 1;
+                Ok(())
+            }
+            _ if c == b'(' => Err("missing opening parenthesis"),
+            _ => Err("missing closing parenthesis"),
+        }
+    }
+
+    fn filter(&mut self, depth: usize) -> Result<Vec<u8>, &'static str> {
+        if depth > MAX_DEPTH {
+            return Err("filter nested too deep");
+        }
+        self.expect(b'(')?;
+        let result = match self.data.get(self.position) {
+            Some(b'&') => self.list(AND, depth)?,
+            Some(b'|') => self.list(OR, depth)?,
+            Some(b'!') => {
+                self.position += 1;
+                tlv(NOT, &self.filter(depth + 1)?)
+            }
+            _ => self.item()?,
+        };
+        self.expect(b')')?;
+        Ok(result)
+    }
+
+    fn list(&mut self, tag: u8, depth: usize) -> Result<Vec<u8>, &'static str> {
+        self.position += 1;
+        let mut filters = vec![];
+        // an empty list is the absolute true or false filter of RFC 4526
+        while self.data.get(self.position) == Some(&b'(') {
+            filters.extend(self.filter(depth + 1)?);
+        }
+        Ok(tlv(tag, &filters))
+    }
+
+    fn item(&mut self) -> Result<Vec<u8>, &'static str> {
+        let start = self.position;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|x| *x != b'(' && *x != b')')
+        {
+            self.position += 1;
+        }
+        let item = &self.data[start..self.position];
+        let equals = item
+            .iter()
+            .position(|x| *x == b'=')
+            .ok_or("missing operator")?;
+        let (attribute, value) = (&item[..equals], &item[equals + 1..]);
+        let (tag, attribute) = match attribute.last() {
+            Some(b'~') => (APPROX, &attribute[..attribute.len() - 1]),
+            Some(b'>') => (GREATER_OR_EQUAL, &attribute[..attribute.len() - 1]),
+            Some(b'<') => (LESS_OR_EQUAL, &attribute[..attribute.len() - 1]),
+            Some(b':') => return extensible(&attribute[..attribute.len() - 1], value),
+            _ => (EQUALITY, attribute),
+        };
+        if attribute.is_empty() {
+            return Err("missing attribute");
+        }
+        if tag != EQUALITY {
+            return Ok(assertion(tag, attribute, &unescape(value)?));
+        }
+        match value {
+            b"*" => Ok(tlv(PRESENT, attribute)),
+            x if x.contains(&b'*') => substrings(attribute, x),
+            x => Ok(assertion(EQUALITY, attribute, &unescape(x)?)),
+        }
+    }
+}
+
+/// Replaces the escaped octets, e.g. `\2a` for `*`.
+fn unescape(value: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut result = Vec::with_capacity(value.len());
+    let mut iter = value.iter();
+    while let Some(x) = iter.next() {
+        match x {
+            b'\\' => {
+                let hex = [
+                    *iter.next().ok_or("invalid escape")?,
+                    *iter.next().ok_or("invalid escape")?,
+                ];
+                let hex = std::str::from_utf8(&hex).map_err(|_| "invalid escape")?;
+                result.push(u8::from_str_radix(hex, 16).map_err(|_| "invalid escape")?);
+            }
+            x => result.push(*x),
+        }
+    }
+    Ok(result)
+}
+
+fn assertion(tag: u8, attribute: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut result = tlv(OCTET_STRING, attribute);
+    result.extend(tlv(OCTET_STRING, value));
+    tlv(tag, &result)
+}
+
+fn substrings(attribute: &[u8], value: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let parts: Vec<&[u8]> = value.split(|x| *x == b'*').collect();
+    let last = parts.len() - 1;
+    let mut substrings = vec![];
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        let tag = match i {
+            0 => 0x80,
+            x if x == last => 0x82,
+            _ => 0x81,
+        };
+        substrings.extend(tlv(tag, &unescape(part)?));
+    }
+    let mut result = tlv(OCTET_STRING, attribute);
+    result.extend(tlv(SEQUENCE, &substrings));
+    Ok(tlv(SUBSTRINGS, &result))
+}
+
+/// Encodes an extensible match like `cn:dn:2.5.13.5:=John`, the description is the part
+/// before `:=`.
+fn extensible(description: &[u8], value: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut parts = description.split(|x| *x == b':');
+    let attribute = parts.next().unwrap_or_default();
+    let mut dn = false;
+    let mut rule = None;
+    for part in parts {
+        match part {
+            x if x.eq_ignore_ascii_case(b"dn") && !dn && rule.is_none() => dn = true,
+            x if !x.is_empty() && rule.is_none() => rule = Some(x),
+            _ => return Err("invalid extensible match"),
+        }
+    }
+    if attribute.is_empty() && rule.is_none() {
+        return Err("extensible match without attribute and matching rule");
+    }
+    let mut result = vec![];
+    if let Some(x) = rule {
+        result.extend(tlv(0x81, x));
+    }
+    if !attribute.is_empty() {
+        result.extend(tlv(0x82, attribute));
+    }
+    result.extend(tlv(0x83, &unescape(value)?));
+    if dn {
+        result.extend(tlv(0x84, &[0xff]));
+    }
+    Ok(tlv(EXTENSIBLE, &result))
+}
+
+/// Encodes the filter, a filter without enclosing parentheses is accepted as well.
+pub fn encode(filter: &str) -> Result<Vec<u8>, &'static str> {
+    let filter = filter.trim();
+    let filter = match filter.starts_with('(') {
+        true => filter.to_string(),
+        false => format!("({filter})"),
+    };
+    let mut parser = Parser {
+        data: filter.as_bytes(),
+        position: 0,
+    };
+    let result = parser.filter(0)?;
+    match parser.position == filter.len() {
+        true => Ok(result),
+        false => Err("unexpected data after the filter"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple() {
+        assert_eq!(
+            encode("(objectClass=*)").unwrap(),
+            [0x87, 0x0b, b'o', b'b', b'j', b'e', b'c', b't', b'C', b'l', b'a', b's', b's']
+        );
+        assert_eq!(
+            encode("cn=a\\2a").unwrap(),
+            [0xa3, 0x08, 0x04, 0x02, b'c', b'n', 0x04, 0x02, b'a', b'*']
+        );
+        assert_eq!(
+            encode("(cn=*a*b)").unwrap(),
+            [0xa4, 0x0c, 0x04, 0x02, b'c', b'n', 0x30, 0x06, 0x81, 0x01, b'a', 0x82, 0x01, b'b']
+        );
+        assert_eq!(
+            encode("(uid>=5)").unwrap(),
+            [0xa5, 0x08, 0x04, 0x03, b'u', b'i', b'd', 0x04, 0x01, b'5']
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            encode("(&(!(a=1))(|(b=*)))").unwrap(),
+            [
+                0xa0, 0x0f, 0xa2, 0x08, 0xa3, 0x06, 0x04, 0x01, b'a', 0x04, 0x01, b'1', 0xa1, 0x03,
+                0x87, 0x01, b'b'
+            ]
+        );
+        assert_eq!(encode("(&)").unwrap(), [0xa0, 0x00]);
+    }
+
+    #[test]
+    fn extensible_match() {
+        assert_eq!(
+            encode("(cn:dn:2.5:=x)").unwrap(),
+            [
+                0xa9, 0x0f, 0x81, 0x03, b'2', b'.', b'5', 0x82, 0x02, b'c', b'n', 0x83, 0x01, b'x',
+                0x84, 0x01, 0xff
+            ]
+        );
+        assert!(encode("(:=x)").is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(encode("(cn=a").is_err());
+        assert!(encode("(cn)").is_err());
+        assert!(encode("(=a)").is_err());
+        assert!(encode("(cn=\\zz)").is_err());
+        assert!(encode("(cn=a))").is_err());
+    }
+}
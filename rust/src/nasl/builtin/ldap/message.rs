@@ -0,0 +1,347 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding and decoding of LDAPv3 messages (RFC 4511).
+
+use super::super::asn1::der::{self, encode_integer, tlv, Class, Element};
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+pub const OCTET_STRING: u8 = 0x04;
+const ENUMERATED: u8 = 0x0a;
+pub const SEQUENCE: u8 = 0x30;
+
+const BIND_REQUEST: u8 = 0x60;
+const UNBIND_REQUEST: u8 = 0x42;
+const SEARCH_REQUEST: u8 = 0x63;
+const SIMPLE: u8 = 0x80;
+const SASL: u8 = 0xa3;
+
+const BIND_RESPONSE: u64 = 1;
+const SEARCH_RESULT_ENTRY: u64 = 4;
+const SEARCH_RESULT_DONE: u64 = 5;
+const SEARCH_RESULT_REFERENCE: u64 = 19;
+const EXTENDED_RESPONSE: u64 = 24;
+
+/// Protocol version sent in the bind request
+const VERSION: i64 = 3;
+
+/// Credentials of a bind request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authentication<'a> {
+    Simple(&'a [u8]),
+    Sasl {
+        mechanism: &'a str,
+        credentials: Option<&'a [u8]>,
+    },
+}
+
+/// Part of the tree a search is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Base = 0,
+    One = 1,
+    Sub = 2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search<'a> {
+    pub base: &'a str,
+    pub scope: Scope,
+    pub size_limit: i64,
+    pub time_limit: i64,
+    pub types_only: bool,
+    /// The encoded filter, see [super::filter::encode]
+    pub filter: Vec<u8>,
+    pub attributes: &'a [String],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request<'a> {
+    Bind {
+        name: &'a str,
+        authentication: Authentication<'a>,
+    },
+    Unbind,
+    Search(Search<'a>),
+}
+
+/// Result of an operation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LdapResult {
+    pub code: i64,
+    pub matched_dn: String,
+    pub message: String,
+    pub referrals: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub dn: String,
+    pub attributes: Vec<(String, Vec<Vec<u8>>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Bind {
+        result: LdapResult,
+        credentials: Option<Vec<u8>>,
+    },
+    Entry(Entry),
+    Reference(Vec<String>),
+    Done(LdapResult),
+    /// Unsolicited notifications, e.g. the notice of disconnection
+    Extended(LdapResult),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub id: i32,
+    pub response: Response,
+}
+
+/// Returns the name of the result code.
+pub fn result_name(code: i64) -> &'static str {
+    match code {
+        0 => "success",
+        1 => "operationsError",
+        2 => "protocolError",
+        3 => "timeLimitExceeded",
+        4 => "sizeLimitExceeded",
+        5 => "compareFalse",
+        6 => "compareTrue",
+        7 => "authMethodNotSupported",
+        8 => "strongerAuthRequired",
+        10 => "referral",
+        11 => "adminLimitExceeded",
+        12 => "unavailableCriticalExtension",
+        13 => "confidentialityRequired",
+        14 => "saslBindInProgress",
+        16 => "noSuchAttribute",
+        17 => "undefinedAttributeType",
+        18 => "inappropriateMatching",
+        19 => "constraintViolation",
+        20 => "attributeOrValueExists",
+        21 => "invalidAttributeSyntax",
+        32 => "noSuchObject",
+        33 => "aliasProblem",
+        34 => "invalidDNSyntax",
+        36 => "aliasDereferencingProblem",
+        48 => "inappropriateAuthentication",
+        49 => "invalidCredentials",
+        50 => "insufficientAccessRights",
+        51 => "busy",
+        52 => "unavailable",
+        53 => "unwillingToPerform",
+        54 => "loopDetect",
+        64 => "namingViolation",
+        65 => "objectClassViolation",
+        66 => "notAllowedOnNonLeaf",
+        67 => "notAllowedOnRDN",
+        68 => "entryAlreadyExists",
+        69 => "objectClassModsProhibited",
+        71 => "affectsMultipleDSAs",
+        80 => "other",
+        _ => "unknown",
+    }
+}
+
+impl Request<'_> {
+    pub fn encode(&self, id: i32) -> Vec<u8> {
+        let operation = match self {
+            Request::Bind {
+                name,
+                authentication,
+            } => {
+                let mut bind = tlv(INTEGER, &encode_integer(VERSION));
+                bind.extend(tlv(OCTET_STRING, name.as_bytes()));
+                bind.extend(match authentication {
+                    Authentication::Simple(password) => tlv(SIMPLE, password),
+                    Authentication::Sasl {
+                        mechanism,
+                        credentials,
+                    } => {
+                        let mut sasl = tlv(OCTET_STRING, mechanism.as_bytes());
+                        if let Some(x) = credentials {
+                            sasl.extend(tlv(OCTET_STRING, x));
+                        }
+                        tlv(SASL, &sasl)
+                    }
+                });
+                tlv(BIND_REQUEST, &bind)
+            }
+            Request::Unbind => tlv(UNBIND_REQUEST, &[]),
+            Request::Search(search) => {
+                let mut request = tlv(OCTET_STRING, search.base.as_bytes());
+                request.extend(tlv(ENUMERATED, &encode_integer(search.scope as i64)));
+                // aliases are never dereferenced
+                request.extend(tlv(ENUMERATED, &encode_integer(0)));
+                request.extend(tlv(INTEGER, &encode_integer(search.size_limit)));
+                request.extend(tlv(INTEGER, &encode_integer(search.time_limit)));
+                request.extend(tlv(BOOLEAN, &[if search.types_only { 0xff } else { 0 }]));
+                request.extend_from_slice(&search.filter);
+                let attributes: Vec<u8> = search
+                    .attributes
+                    .iter()
+                    .flat_map(|x| tlv(OCTET_STRING, x.as_bytes()))
+                    .collect();
+                request.extend(tlv(SEQUENCE, &attributes));
+                tlv(SEARCH_REQUEST, &request)
+            }
+        };
+        let mut message = tlv(INTEGER, &encode_integer(id as i64));
+        message.extend(operation);
+        tlv(SEQUENCE, &message)
+    }
+}
+
+fn string(element: &Element) -> String {
+    String::from_utf8_lossy(element.content).into_owned()
+}
+
+impl LdapResult {
+    /// Decodes the components of a result, returning the remaining elements.
+    fn decode<'a, 'b>(elements: &'b [Element<'a>]) -> Option<(Self, &'b [Element<'a>])> {
+        let [code, matched_dn, message, rest @ ..] = elements else {
+            return None;
+        };
+        let (referrals, rest) = match rest {
+            [x, rest @ ..] if x.class == Class::Context && x.tag == 3 => {
+                (x.children.iter().map(string).collect(), rest)
+            }
+            rest => (vec![], rest),
+        };
+        Some((
+            Self {
+                code: der::integer(code.content)?,
+                matched_dn: string(matched_dn),
+                message: string(message),
+                referrals,
+            },
+            rest,
+        ))
+    }
+}
+
+impl Entry {
+    fn decode(elements: &[Element]) -> Option<Self> {
+        let [dn, attributes] = elements else {
+            return None;
+        };
+        let attributes = attributes
+            .children
+            .iter()
+            .map(|attribute| match attribute.children.as_slice() {
+                [name, values] => Some((
+                    string(name),
+                    values.children.iter().map(|x| x.content.to_vec()).collect(),
+                )),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            dn: string(dn),
+            attributes,
+        })
+    }
+}
+
+impl Message {
+    /// Decodes a message, None when it is malformed or no known response.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let elements = der::parse(data).ok()?;
+        let [message] = elements.as_slice() else {
+            return None;
+        };
+        // the controls following the operation are ignored
+        let [id, operation, ..] = message.children.as_slice() else {
+            return None;
+        };
+        if operation.class != Class::Application {
+            return None;
+        }
+        let response = match operation.tag {
+            BIND_RESPONSE => {
+                let (result, rest) = LdapResult::decode(&operation.children)?;
+                let credentials = rest
+                    .iter()
+                    .find(|x| x.class == Class::Context && x.tag == 7)
+                    .map(|x| x.content.to_vec());
+                Response::Bind {
+                    result,
+                    credentials,
+                }
+            }
+            SEARCH_RESULT_ENTRY => Response::Entry(Entry::decode(&operation.children)?),
+            SEARCH_RESULT_REFERENCE => {
+                Response::Reference(operation.children.iter().map(string).collect())
+            }
+            SEARCH_RESULT_DONE => Response::Done(LdapResult::decode(&operation.children)?.0),
+            EXTENDED_RESPONSE => Response::Extended(LdapResult::decode(&operation.children)?.0),
+            _ => return None,
+        };
+        Some(Self {
+            id: i32::try_from(der::integer(id.content)?).ok()?,
+            response,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_bind() {
+        let request = Request::Bind {
+            name: "cn=admin",
+            authentication: Authentication::Simple(b"secret"),
+        };
+        let expected = [
+            0x30, 0x1a, 0x02, 0x01, 0x01, 0x60, 0x15, 0x02, 0x01, 0x03, 0x04, 0x08, b'c', b'n',
+            b'=', b'a', b'd', b'm', b'i', b'n', 0x80, 0x06, b's', b'e', b'c', b'r', b'e', b't',
+        ];
+        assert_eq!(request.encode(1), expected);
+        assert_eq!(
+            Request::Unbind.encode(2),
+            [0x30, 0x05, 0x02, 0x01, 0x02, 0x42, 0x00]
+        );
+    }
+
+    #[test]
+    fn decode_responses() {
+        // bind response with saslBindInProgress and server credentials
+        let data = [
+            0x30, 0x10, 0x02, 0x01, 0x01, 0x61, 0x0b, 0x0a, 0x01, 0x0e, 0x04, 0x00, 0x04, 0x00,
+            0x87, 0x02, 0xab, 0xcd,
+        ];
+        assert_eq!(
+            Message::decode(&data),
+            Some(Message {
+                id: 1,
+                response: Response::Bind {
+                    result: LdapResult {
+                        code: 14,
+                        ..Default::default()
+                    },
+                    credentials: Some(vec![0xab, 0xcd]),
+                },
+            })
+        );
+        // search result entry with the attribute cn: a, b
+        let data = [
+            0x30, 0x1b, 0x02, 0x01, 0x02, 0x64, 0x16, 0x04, 0x04, b'c', b'n', b'=', b'a', 0x30,
+            0x0e, 0x30, 0x0c, 0x04, 0x02, b'c', b'n', 0x31, 0x06, 0x04, 0x01, b'a', 0x04, 0x01,
+            b'b',
+        ];
+        assert_eq!(
+            Message::decode(&data).unwrap().response,
+            Response::Entry(Entry {
+                dn: "cn=a".to_string(),
+                attributes: vec![("cn".to_string(), vec![b"a".to_vec(), b"b".to_vec()])],
+            })
+        );
+        assert_eq!(Message::decode(&data[..10]), None);
+    }
+}
@@ -0,0 +1,398 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to bind to and search LDAP directories, e.g. to check whether an
+//! Active Directory allows anonymous binds or exposes its root DSE.
+
+mod filter;
+mod message;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, io, sync::Mutex, time::Duration};
+
+use indexmap::IndexMap;
+use message::{result_name, Authentication, LdapResult, Message, Request, Response, Scope, Search};
+
+use crate::nasl::prelude::*;
+
+use super::network::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    ssl::SslOptions,
+    tcp::TcpConnection,
+};
+
+const LDAP_PORT: u16 = 389;
+const LDAPS_PORT: u16 = 636;
+/// Largest message accepted from the server
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+const DEFAULT_FILTER: &str = "(objectClass=*)";
+
+struct Connection {
+    conn: TcpConnection,
+    last_id: i32,
+    timeout: Duration,
+}
+
+#[derive(Default)]
+struct Handles {
+    connections: HashMap<usize, Connection>,
+    last_id: usize,
+}
+
+/// Holds the LDAP connections opened by a script.
+#[derive(Default)]
+pub struct Ldap {
+    handles: Mutex<Handles>,
+}
+
+fn io_error(e: io::Error) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(format!("LDAP: {e}"), Some(NaslValue::Null))
+}
+
+impl Connection {
+    /// Reads a message, which is not framed besides its BER length.
+    fn read_message(&mut self) -> io::Result<Message> {
+        let mut message = vec![0; 2];
+        self.conn
+            .read_exact_with_timeout(&mut message, self.timeout)?;
+        let length = match message[1] {
+            x if x < 0x80 => x as usize,
+            x => {
+                let mut octets = vec![0; (x & 0x7f) as usize];
+                if octets.is_empty() || octets.len() > 4 {
+                    return Err(invalid_data("invalid message length"));
+                }
+                self.conn
+                    .read_exact_with_timeout(&mut octets, self.timeout)?;
+                message.extend_from_slice(&octets);
+                octets.iter().fold(0, |acc, b| (acc << 8) | *b as usize)
+            }
+        };
+        if length > MAX_MESSAGE {
+            return Err(invalid_data("message too large"));
+        }
+        let start = message.len();
+        message.resize(start + length, 0);
+        self.conn
+            .read_exact_with_timeout(&mut message[start..], self.timeout)?;
+        Message::decode(&message).ok_or_else(|| invalid_data("malformed message"))
+    }
+
+    /// Sends the request and returns the responses up to the final one of the operation.
+    fn exchange(&mut self, request: &Request) -> io::Result<Vec<Response>> {
+        self.last_id = self.last_id.checked_add(1).unwrap_or(1);
+        let id = self.last_id;
+        io::Write::write_all(&mut self.conn, &request.encode(id))?;
+        let mut responses = vec![];
+        loop {
+            let message = self.read_message()?;
+            match message.response {
+                Response::Extended(result) if message.id == 0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        format!("disconnected by the server: {}", result.message),
+                    ));
+                }
+                _ if message.id != id => {}
+                response @ (Response::Entry(_) | Response::Reference(_)) => {
+                    responses.push(response)
+                }
+                response => {
+                    responses.push(response);
+                    return Ok(responses);
+                }
+            }
+        }
+    }
+}
+
+fn result_to_nasl(result: LdapResult) -> IndexMap<String, NaslValue> {
+    let mut dict = IndexMap::new();
+    dict.insert("code".to_string(), NaslValue::Number(result.code));
+    dict.insert(
+        "result".to_string(),
+        NaslValue::String(result_name(result.code).to_string()),
+    );
+    dict.insert(
+        "matched_dn".to_string(),
+        NaslValue::String(result.matched_dn),
+    );
+    dict.insert("message".to_string(), NaslValue::String(result.message));
+    if !result.referrals.is_empty() {
+        dict.insert(
+            "referrals".to_string(),
+            NaslValue::Array(
+                result
+                    .referrals
+                    .into_iter()
+                    .map(NaslValue::String)
+                    .collect(),
+            ),
+        );
+    }
+    dict
+}
+
+/// Returns the value as string when it is valid UTF-8, e.g. binary SIDs are returned as data.
+fn value_to_nasl(value: Vec<u8>) -> NaslValue {
+    match String::from_utf8(value) {
+        Ok(x) => NaslValue::String(x),
+        Err(e) => NaslValue::Data(e.into_bytes()),
+    }
+}
+
+impl Ldap {
+    fn exchange(
+        &self,
+        handle: usize,
+        request: &Request,
+    ) -> Result<Vec<Response>, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let connection = handles.connections.get_mut(&handle).ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(format!("Unknown LDAP connection {handle}"), None)
+        })?;
+        connection.exchange(request).map_err(io_error)
+    }
+
+    fn bind(
+        &self,
+        handle: usize,
+        name: &str,
+        authentication: Authentication,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let request = Request::Bind {
+            name,
+            authentication,
+        };
+        match self.exchange(handle, &request)?.pop() {
+            Some(Response::Bind {
+                result,
+                credentials,
+            }) => {
+                let mut dict = result_to_nasl(result);
+                if let Some(x) = credentials {
+                    dict.insert("credentials".to_string(), NaslValue::Data(x));
+                }
+                Ok(NaslValue::Dict(dict))
+            }
+            _ => Err(io_error(invalid_data("unexpected response"))),
+        }
+    }
+
+    /// Connects to the LDAP service of the target and returns the handle of the connection.
+    ///
+    /// - port: Port of the service, 389 or 636 when ssl is set by default
+    /// - ssl: TRUE to negotiate TLS directly after connecting (LDAPS)
+    /// - timeout: Time in seconds to wait for each response, the network timeout of the scan by
+    ///   default
+    ///
+    /// Returns NULL when the connection fails.
+    #[nasl_function(named(port, ssl, timeout))]
+    fn ldap_connect(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        ssl: Option<bool>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let ssl = ssl.unwrap_or_default();
+        let default_port = if ssl { LDAPS_PORT } else { LDAP_PORT };
+        let (addrs, port, timeout) = target_service(context, port, default_port, timeout)?;
+        let conn = NaslSockets::connect_tcp(context, &addrs, port, None, timeout, None).and_then(
+            |mut conn| {
+                if ssl {
+                    let options = SslOptions {
+                        sni: Some(context.target().to_string())
+                            .filter(|x| x.parse::<std::net::IpAddr>().is_err()),
+                        ..Default::default()
+                    };
+                    conn.negotiate_ssl(&options, timeout)?;
+                }
+                Ok(conn)
+            },
+        );
+        let conn = match conn {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, port, "unable to connect to the LDAP service");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.connections.insert(
+            id,
+            Connection {
+                conn,
+                last_id: 0,
+                timeout,
+            },
+        );
+        Ok(NaslValue::Number(id as i64))
+    }
+
+    /// Authenticates with a simple bind, anonymously when dn and password are empty.
+    ///
+    /// Returns an array with the result code and its name, the matched DN and the diagnostic
+    /// message of the server.
+    #[nasl_function(named(handle, dn, password))]
+    fn ldap_bind(
+        &self,
+        handle: usize,
+        dn: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.bind(
+            handle,
+            dn.unwrap_or_default(),
+            Authentication::Simple(password.unwrap_or_default().as_bytes()),
+        )
+    }
+
+    /// Authenticates with the SASL mechanism, e.g. EXTERNAL or GSS-SPNEGO.
+    ///
+    /// Returns the result like ldap_bind, including the credentials sent by the server,
+    /// e.g. the challenge of a multi-step mechanism with the result saslBindInProgress.
+    #[nasl_function(named(handle, mechanism, credentials, dn))]
+    fn ldap_sasl_bind(
+        &self,
+        handle: usize,
+        mechanism: &str,
+        credentials: Option<&NaslValue>,
+        dn: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let credentials: Option<Vec<u8>> = credentials.map(|x| x.into());
+        self.bind(
+            handle,
+            dn.unwrap_or_default(),
+            Authentication::Sasl {
+                mechanism,
+                credentials: credentials.as_deref(),
+            },
+        )
+    }
+
+    /// Searches the directory and returns the result together with the found entries.
+    ///
+    /// - base: DN of the base object, empty for the root DSE
+    /// - scope: "base", "one" or "sub" (default)
+    /// - filter: Filter in the string representation, "(objectClass=*)" by default
+    /// - attributes: List of the requested attributes, all user attributes by default
+    /// - size_limit, time_limit: Limits requested from the server, 0 for none
+    /// - types_only: TRUE to return the attribute names without values
+    #[nasl_function(named(
+        handle, base, scope, filter, attributes, size_limit, time_limit, types_only
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn ldap_search(
+        &self,
+        handle: usize,
+        base: Option<&str>,
+        scope: Option<&str>,
+        filter: Option<&str>,
+        attributes: Option<Vec<String>>,
+        size_limit: Option<i64>,
+        time_limit: Option<i64>,
+        types_only: Option<bool>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let scope = match scope.map(|x| x.to_lowercase()).as_deref() {
+            Some("base") => Scope::Base,
+            Some("one") => Scope::One,
+            None | Some("sub") => Scope::Sub,
+            Some(x) => {
+                return Err(FunctionErrorKind::wrong_argument(
+                    "scope",
+                    "base, one or sub",
+                    x,
+                ))
+            }
+        };
+        let filter = filter.unwrap_or(DEFAULT_FILTER);
+        let encoded = filter::encode(filter).map_err(|e| {
+            FunctionErrorKind::wrong_argument(
+                "filter",
+                "a valid LDAP filter",
+                &format!("{filter} ({e})"),
+            )
+        })?;
+        let attributes = attributes.unwrap_or_default();
+        let request = Request::Search(Search {
+            base: base.unwrap_or_default(),
+            scope,
+            size_limit: size_limit.unwrap_or_default().max(0),
+            time_limit: time_limit.unwrap_or_default().max(0),
+            types_only: types_only.unwrap_or_default(),
+            filter: encoded,
+            attributes: &attributes,
+        });
+        let mut entries = vec![];
+        let mut references = vec![];
+        let mut result = None;
+        for response in self.exchange(handle, &request)? {
+            match response {
+                Response::Entry(entry) => {
+                    let attributes = entry
+                        .attributes
+                        .into_iter()
+                        .map(|(name, values)| {
+                            (
+                                name,
+                                NaslValue::Array(values.into_iter().map(value_to_nasl).collect()),
+                            )
+                        })
+                        .collect();
+                    let mut dict = IndexMap::new();
+                    dict.insert("dn".to_string(), NaslValue::String(entry.dn));
+                    dict.insert("attributes".to_string(), NaslValue::Dict(attributes));
+                    entries.push(NaslValue::Dict(dict));
+                }
+                Response::Reference(uris) => {
+                    references.extend(uris.into_iter().map(NaslValue::String))
+                }
+                Response::Done(x) => result = Some(x),
+                _ => return Err(io_error(invalid_data("unexpected response"))),
+            }
+        }
+        let mut dict =
+            result_to_nasl(result.ok_or_else(|| io_error(invalid_data("missing result")))?);
+        dict.insert("entries".to_string(), NaslValue::Array(entries));
+        dict.insert("references".to_string(), NaslValue::Array(references));
+        Ok(NaslValue::Dict(dict))
+    }
+
+    /// Unbinds and closes the connection.
+    #[nasl_function(named(handle))]
+    fn ldap_close(&self, handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let mut connection = self
+            .handles
+            .lock()
+            .unwrap()
+            .connections
+            .remove(&handle)
+            .ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(format!("Unknown LDAP connection {handle}"), None)
+            })?;
+        // the server closes the connection without an answer
+        let id = connection.last_id.checked_add(1).unwrap_or(1);
+        if let Err(e) = io::Write::write_all(&mut connection.conn, &Request::Unbind.encode(id)) {
+            tracing::debug!(%e, "unable to unbind from the LDAP service");
+        }
+        Ok(NaslValue::Null)
+    }
+}
+
+function_set! {
+    Ldap,
+    sync_stateful,
+    (
+        (Ldap::ldap_connect, "ldap_connect"),
+        (Ldap::ldap_bind, "ldap_bind"),
+        (Ldap::ldap_sasl_bind, "ldap_sasl_bind"),
+        (Ldap::ldap_search, "ldap_search"),
+        (Ldap::ldap_close, "ldap_close"),
+    )
+}
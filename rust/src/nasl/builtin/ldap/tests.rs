@@ -0,0 +1,155 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use crate::nasl::builtin::asn1::der::{self, encode_integer, tlv, Class};
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+fn read_request(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut request = vec![0; 2];
+    stream.read_exact(&mut request).ok()?;
+    // the requests of the tests are shorter than 128 octets
+    let start = request.len();
+    request.resize(start + request[1] as usize, 0);
+    stream.read_exact(&mut request[start..]).ok()?;
+    Some(request)
+}
+
+fn message(id: &[u8], tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut message = tlv(0x02, id);
+    message.extend(tlv(tag, content));
+    tlv(0x30, &message)
+}
+
+fn result(code: i64, matched_dn: &str, diagnostic: &str) -> Vec<u8> {
+    let mut result = tlv(0x0a, &encode_integer(code));
+    result.extend(tlv(0x04, matched_dn.as_bytes()));
+    result.extend(tlv(0x04, diagnostic.as_bytes()));
+    result
+}
+
+/// Answers like a directory with the root DSE, accepting the password "secret" of "cn=admin"
+/// and anonymous binds. The SASL mechanism "TEST" succeeds in the second step.
+fn ldap_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        while let Some(request) = read_request(&mut stream) {
+            let elements = der::parse(&request).unwrap();
+            let [id, operation] = elements[0].children.as_slice() else {
+                panic!("unexpected message");
+            };
+            assert_eq!(operation.class, Class::Application);
+            let response = match operation.tag {
+                0 => {
+                    let [_, name, authentication] = operation.children.as_slice() else {
+                        panic!("unexpected bind request");
+                    };
+                    let response = match (authentication.tag, authentication.children.len()) {
+                        (0, _) => match (name.content, authentication.content) {
+                            (b"", b"") | (b"cn=admin", b"secret") => result(0, "", ""),
+                            _ => result(49, "", "invalid credentials"),
+                        },
+                        // the first step of the SASL mechanism without credentials
+                        (3, 1) => {
+                            let mut response = result(14, "", "");
+                            response.extend(tlv(0x87, b"challenge"));
+                            response
+                        }
+                        _ => result(0, "", ""),
+                    };
+                    message(id.content, 0x61, &response)
+                }
+                2 => break,
+                3 => {
+                    let base = operation.children[0].content;
+                    if !base.is_empty() {
+                        let done = result(32, "DC=example,DC=com", "");
+                        stream.write_all(&message(id.content, 0x65, &done)).unwrap();
+                        continue;
+                    }
+                    let values: Vec<u8> =
+                        ["DC=example,DC=com", "CN=Configuration,DC=example,DC=com"]
+                            .iter()
+                            .flat_map(|x| tlv(0x04, x.as_bytes()))
+                            .collect();
+                    let mut attribute = tlv(0x04, b"namingContexts");
+                    attribute.extend(tlv(0x31, &values));
+                    let mut sid = tlv(0x04, b"objectSid");
+                    sid.extend(tlv(0x31, &tlv(0x04, &[1, 0xff])));
+                    let mut attributes = tlv(0x30, &attribute);
+                    attributes.extend(tlv(0x30, &sid));
+                    let mut entry = tlv(0x04, b"");
+                    entry.extend(tlv(0x30, &attributes));
+                    let mut response = message(id.content, 0x64, &entry);
+                    response.extend(message(
+                        id.content,
+                        0x73,
+                        &tlv(0x04, b"ldap://other.example.com/"),
+                    ));
+                    response.extend(message(id.content, 0x65, &result(0, "", "")));
+                    response
+                }
+                x => panic!("unexpected operation {x}"),
+            };
+            stream.write_all(&response).unwrap();
+        }
+    });
+    port
+}
+
+#[test]
+fn bind_and_search() {
+    let port = ldap_server();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(format!("h = ldap_connect(port: {port});"), 1);
+    t.ok(r#"ldap_bind(handle: h)["result"];"#, "success");
+    t.run(r#"r = ldap_bind(handle: h, dn: "cn=admin", password: "wrong");"#);
+    t.ok(r#"r["code"];"#, 49);
+    t.ok(r#"r["message"];"#, "invalid credentials");
+    t.ok(
+        r#"ldap_bind(handle: h, dn: "cn=admin", password: "secret")["code"];"#,
+        0,
+    );
+    t.run(r#"r = ldap_sasl_bind(handle: h, mechanism: "TEST");"#);
+    t.ok(r#"r["result"];"#, "saslBindInProgress");
+    t.ok(r#"r["credentials"];"#, b"challenge".to_vec());
+    t.ok(
+        r#"ldap_sasl_bind(handle: h, mechanism: "TEST", credentials: "response")["code"];"#,
+        0,
+    );
+    t.run(r#"s = ldap_search(handle: h, scope: "base");"#);
+    t.ok(r#"s["code"];"#, 0);
+    t.ok(
+        r#"s["entries"][0]["attributes"]["namingContexts"][1];"#,
+        "CN=Configuration,DC=example,DC=com",
+    );
+    t.ok(
+        r#"s["entries"][0]["attributes"]["objectSid"][0];"#,
+        vec![1u8, 0xff],
+    );
+    t.ok(r#"s["references"][0];"#, "ldap://other.example.com/");
+    t.run(r#"s = ldap_search(handle: h, base: "DC=missing", filter: "(cn=*)");"#);
+    t.ok(r#"s["result"];"#, "noSuchObject");
+    t.ok(r#"s["matched_dn"];"#, "DC=example,DC=com");
+    check_err_matches!(
+        t,
+        r#"ldap_search(handle: h, filter: "(cn=");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    check_err_matches!(
+        t,
+        r#"ldap_search(handle: h, scope: "tree");"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    t.ok("ldap_close(handle: h);", NaslValue::Null);
+}
@@ -0,0 +1,307 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Inventory of Linux and other Unix hosts: release, installed packages, kernel and running
+//! services.
+
+use std::collections::BTreeMap;
+
+use crate::nasl::utils::{error::FunctionErrorKind, Context};
+
+use super::{register_host_detail, set_kb_item, PACKAGE_LIST_NOTUS, RELEASE_NOTUS};
+
+/// Shell script printing the inventory in sections started by `@@<name>` lines
+///
+/// The script only uses POSIX sh, package managers that are not installed are skipped.
+pub const INVENTORY_SCRIPT: &str = r#"LC_ALL=C; export LC_ALL
+echo '@@os-release'; cat /etc/os-release 2>/dev/null || cat /usr/lib/os-release 2>/dev/null
+echo '@@uname'; uname -a
+echo '@@kernel'; uname -r
+echo '@@arch'; uname -m
+if command -v dpkg-query >/dev/null 2>&1; then
+  echo '@@dpkg'; dpkg-query -W -f '${db:Status-Abbrev}\t${Package}\t${Version}\t${Architecture}\n' 2>/dev/null
+fi
+if command -v rpm >/dev/null 2>&1; then
+  echo '@@rpm'; rpm -qa --qf '%{NAME}\t%{EPOCH}\t%{VERSION}\t%{RELEASE}\t%{ARCH}\n' 2>/dev/null
+fi
+if command -v apk >/dev/null 2>&1; then
+  echo '@@apk'; apk info -v 2>/dev/null
+fi
+echo '@@services'
+if command -v systemctl >/dev/null 2>&1; then
+  systemctl list-units --type=service --state=running --no-legend --plain 2>/dev/null | awk '{print $1}'
+fi
+"#;
+
+/// Distributions whose releases are only distinguished by the major version
+const ENTERPRISE_LINUX: &[&str] = &["rhel", "centos", "rocky", "almalinux", "ol"];
+
+/// Package manager the packages were listed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageFormat {
+    Dpkg,
+    Rpm,
+    Apk,
+}
+
+/// Installed package
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    /// Full version including epoch and release, e.g. 1:3.0.11-1~deb12u2
+    pub version: String,
+    pub arch: String,
+    /// Package as listed for the notus scanner
+    pub notus: String,
+}
+
+/// Inventory of a Linux or Unix host
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinuxInventory {
+    /// Fields of os-release, e.g. ID and VERSION_ID
+    pub os_release: BTreeMap<String, String>,
+    /// Output of uname -a
+    pub uname: String,
+    pub kernel: String,
+    pub arch: String,
+    pub package_format: Option<PackageFormat>,
+    pub packages: Vec<Package>,
+    /// Units of the running services, e.g. sshd.service
+    pub services: Vec<String>,
+}
+
+fn os_release_field(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')))
+        .unwrap_or(value);
+    Some((key.trim().to_string(), value.replace("\\\"", "\"")))
+}
+
+fn dpkg_package(line: &str) -> Option<Package> {
+    let mut fields = line.split('\t');
+    // only installed packages, other states are remnants of removed ones
+    if !fields.next()?.starts_with("ii") {
+        return None;
+    }
+    let (name, version, arch) = (fields.next()?, fields.next()?, fields.next().unwrap_or(""));
+    Some(Package {
+        name: name.to_string(),
+        version: version.to_string(),
+        arch: arch.to_string(),
+        notus: format!("{name}-{version}"),
+    })
+}
+
+fn rpm_package(line: &str) -> Option<Package> {
+    let mut fields = line.split('\t');
+    let (name, epoch, version, release, arch) = (
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+        fields.next().unwrap_or(""),
+    );
+    let version = match epoch {
+        "(none)" | "0" | "" => format!("{version}-{release}"),
+        epoch => format!("{epoch}:{version}-{release}"),
+    };
+    Some(Package {
+        name: name.to_string(),
+        notus: format!("{name}-{version}.{arch}"),
+        version,
+        arch: arch.to_string(),
+    })
+}
+
+fn apk_package(line: &str) -> Option<Package> {
+    // name-version-rN, the name itself may contain dashes
+    let mut parts = line.rsplitn(3, '-');
+    let (release, version, name) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(Package {
+        name: name.to_string(),
+        version: format!("{version}-{release}"),
+        arch: String::new(),
+        notus: line.to_string(),
+    })
+}
+
+impl LinuxInventory {
+    /// Parses the output of the inventory script, returns None when it contains no kernel.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut result = Self::default();
+        let mut section = "";
+        for line in output.lines() {
+            let line = line.trim_end_matches('\r');
+            if let Some(name) = line.strip_prefix("@@") {
+                section = name;
+                result.package_format = match section {
+                    "dpkg" => Some(PackageFormat::Dpkg),
+                    "rpm" => Some(PackageFormat::Rpm),
+                    "apk" => Some(PackageFormat::Apk),
+                    _ => result.package_format,
+                };
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match section {
+                "os-release" => result.os_release.extend(os_release_field(line)),
+                "uname" => result.uname = line.trim().to_string(),
+                "kernel" => result.kernel = line.trim().to_string(),
+                "arch" => result.arch = line.trim().to_string(),
+                "dpkg" => result.packages.extend(dpkg_package(line)),
+                "rpm" => result.packages.extend(rpm_package(line)),
+                "apk" => result.packages.extend(apk_package(line)),
+                "services" => result.services.push(line.trim().to_string()),
+                _ => {}
+            }
+        }
+        if result.kernel.is_empty() {
+            return None;
+        }
+        // on systems with rpm as secondary package manager, e.g. for alien, dpkg is preferred
+        if result.packages.is_empty() {
+            result.package_format = None;
+        }
+        Some(result)
+    }
+
+    /// Returns the release as used by the notus scanner, e.g. debian_12 or rhel_9.
+    ///
+    /// Returns None when os-release is missing.
+    pub fn release_notus(&self) -> Option<String> {
+        let id = self.os_release.get("ID")?;
+        let version = self.os_release.get("VERSION_ID")?;
+        let version = match ENTERPRISE_LINUX.contains(&id.as_str()) {
+            true => version.split('.').next().unwrap_or(version),
+            false => version,
+        };
+        Some(format!("{id}_{version}").to_lowercase())
+    }
+
+    /// Stores the inventory in the KB and registers the operating system as host detail.
+    pub fn store(&self, context: &Context) -> Result<(), FunctionErrorKind> {
+        set_kb_item(context, "Host/runs_unixoide", 1)?;
+        for (key, value) in [
+            ("ssh/login/uname", &self.uname),
+            ("ssh/login/kernel", &self.kernel),
+            ("ssh/login/arch", &self.arch),
+        ] {
+            if !value.is_empty() {
+                set_kb_item(context, key, value.as_str())?;
+            }
+        }
+        for (key, value) in &self.os_release {
+            set_kb_item(
+                context,
+                &format!("ssh/login/os_release/{key}"),
+                value.as_str(),
+            )?;
+        }
+        for package in &self.packages {
+            set_kb_item(
+                context,
+                &format!("ssh/login/package/{}", package.name),
+                package.version.as_str(),
+            )?;
+        }
+        for service in &self.services {
+            set_kb_item(context, "ssh/login/running_service", service.as_str())?;
+        }
+        if let Some(release) = self.release_notus() {
+            set_kb_item(context, RELEASE_NOTUS, release)?;
+        }
+        if self.package_format.is_some() {
+            let packages = self
+                .packages
+                .iter()
+                .map(|x| x.notus.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            set_kb_item(context, PACKAGE_LIST_NOTUS, packages)?;
+        }
+        if let Some(cpe) = self.os_release.get("CPE_NAME") {
+            register_host_detail(context, "OS", cpe)?;
+        }
+        if let Some(name) = self.os_release.get("PRETTY_NAME") {
+            register_host_detail(context, "best_os_txt", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinuxInventory, PackageFormat};
+
+    #[test]
+    fn parse() {
+        let output = "@@os-release
+PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"
+ID=debian
+VERSION_ID=\"12\"
+@@uname
+Linux host 6.1.0-18-amd64 #1 SMP PREEMPT_DYNAMIC Debian 6.1.76-1 (2024-02-01) x86_64 GNU/Linux
+@@kernel
+6.1.0-18-amd64
+@@arch
+x86_64
+@@dpkg
+ii \topenssl\t3.0.11-1~deb12u2\tamd64
+rc \told-package\t1.0\tamd64
+ii \tlibc6\t2.36-9+deb12u4\tamd64
+@@services
+cron.service
+ssh.service
+";
+        let inventory = LinuxInventory::parse(output).unwrap();
+        assert_eq!(
+            inventory.os_release["PRETTY_NAME"],
+            "Debian GNU/Linux 12 (bookworm)"
+        );
+        assert_eq!(inventory.kernel, "6.1.0-18-amd64");
+        assert_eq!(inventory.package_format, Some(PackageFormat::Dpkg));
+        assert_eq!(
+            inventory
+                .packages
+                .iter()
+                .map(|x| x.notus.as_str())
+                .collect::<Vec<_>>(),
+            ["openssl-3.0.11-1~deb12u2", "libc6-2.36-9+deb12u4"]
+        );
+        assert_eq!(inventory.services, ["cron.service", "ssh.service"]);
+        assert_eq!(inventory.release_notus().as_deref(), Some("debian_12"));
+
+        let output = "@@os-release
+ID=\"rocky\"
+VERSION_ID=\"9.3\"
+@@kernel
+5.14.0-362.8.1.el9_3.x86_64
+@@rpm
+openssl\t1\t3.0.7\t25.el9_3\tx86_64
+bash\t(none)\t5.1.8\t6.el9_1\tx86_64
+@@services
+";
+        let inventory = LinuxInventory::parse(output).unwrap();
+        assert_eq!(inventory.release_notus().as_deref(), Some("rocky_9"));
+        assert_eq!(inventory.packages[0].version, "1:3.0.7-25.el9_3");
+        assert_eq!(
+            inventory.packages[0].notus,
+            "openssl-1:3.0.7-25.el9_3.x86_64"
+        );
+        assert_eq!(inventory.packages[1].notus, "bash-5.1.8-6.el9_1.x86_64");
+
+        let output = "@@kernel\n6.6.14-0-lts\n@@apk\nmusl-utils-1.2.4_git20230717-r4\n";
+        let inventory = LinuxInventory::parse(output).unwrap();
+        assert_eq!(inventory.packages[0].name, "musl-utils");
+        assert_eq!(inventory.packages[0].version, "1.2.4_git20230717-r4");
+        assert_eq!(inventory.release_notus(), None);
+        assert_eq!(LinuxInventory::parse("@@os-release\nID=debian\n"), None);
+    }
+}
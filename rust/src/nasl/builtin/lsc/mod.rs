@@ -0,0 +1,43 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Gathers the inventory of authenticated hosts for local security checks.
+//!
+//! The gathered information is stored in the KB in the form product detections and the notus
+//! scanner expect, the detected operating system is registered as host detail.
+
+#[cfg(feature = "nasl-builtin-ssh")]
+pub(crate) mod linux;
+pub(crate) mod windows;
+
+use crate::nasl::utils::{error::FunctionErrorKind, Context};
+use crate::storage::{types::Primitive, Field};
+
+/// KB key of the release the notus scanner checks the packages against
+pub const RELEASE_NOTUS: &str = "ssh/login/release_notus";
+/// KB key of the packages checked by the notus scanner
+pub const PACKAGE_LIST_NOTUS: &str = "ssh/login/package_list_notus";
+
+fn set_kb_item(
+    context: &Context,
+    key: &str,
+    value: impl Into<Primitive>,
+) -> Result<(), FunctionErrorKind> {
+    context
+        .dispatcher()
+        .dispatch(context.key(), Field::KB((key, value.into()).into()))
+        .map_err(|e| e.into())
+}
+
+/// Registers a host detail the same way as register_host_detail of host_details.inc.
+fn register_host_detail(
+    context: &Context,
+    name: &str,
+    value: &str,
+) -> Result<(), FunctionErrorKind> {
+    let oid = context.key().value();
+    set_kb_item(context, "HostDetails", name)?;
+    set_kb_item(context, "HostDetails/NVT", oid.as_str())?;
+    set_kb_item(context, &format!("HostDetails/NVT/{oid}/{name}"), value)
+}
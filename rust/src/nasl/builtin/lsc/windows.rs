@@ -0,0 +1,206 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Inventory of Windows hosts: operating system build, installed updates and installed software.
+
+use crate::nasl::utils::{error::FunctionErrorKind, Context};
+
+use super::{register_host_detail, set_kb_item, PACKAGE_LIST_NOTUS, RELEASE_NOTUS};
+
+/// PowerShell script printing the inventory as key=value lines
+///
+/// Software is read from the uninstall keys of the registry, including the ones of 32 bit
+/// applications on 64 bit systems.
+pub const INVENTORY_SCRIPT: &str = r#"$ErrorActionPreference = 'SilentlyContinue'
+$cv = Get-ItemProperty 'HKLM:\SOFTWARE\Microsoft\Windows NT\CurrentVersion'
+$version = if ($null -ne $cv.CurrentMajorVersionNumber) { "$($cv.CurrentMajorVersionNumber).$($cv.CurrentMinorVersionNumber)" } else { $cv.CurrentVersion }
+"product_name=$($cv.ProductName)"
+"edition_id=$($cv.EditionID)"
+"display_version=$($cv.DisplayVersion)"
+"release_id=$($cv.ReleaseId)"
+"version=$version"
+"build=$($cv.CurrentBuildNumber)"
+"ubr=$($cv.UBR)"
+"installation_type=$($cv.InstallationType)"
+"arch=$env:PROCESSOR_ARCHITECTURE"
+Get-HotFix | ForEach-Object { "hotfix=$($_.HotFixID)" }
+'HKLM:\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\*', 'HKLM:\SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\*' |
+  ForEach-Object { Get-ItemProperty $_ } | Where-Object { $_.DisplayName } |
+  ForEach-Object { "software=$($_.DisplayName)`t$($_.DisplayVersion)`t$($_.Publisher)`t$($_.InstallLocation)" }
+"#;
+
+/// First build of Windows 11, which still reports itself as Windows 10 in the registry
+const WINDOWS_11_BUILD: u32 = 22000;
+
+/// Application listed in the uninstall keys of the registry
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Software {
+    pub name: String,
+    pub version: String,
+    pub publisher: String,
+    pub location: String,
+}
+
+/// Inventory of a Windows host
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowsInventory {
+    /// Name of the product, e.g. Windows 11 Pro
+    pub product_name: String,
+    pub edition_id: String,
+    /// Feature update, e.g. 23H2, empty before Windows 10 20H2
+    pub display_version: String,
+    /// Major and minor version, e.g. 10.0
+    pub version: String,
+    pub build: u32,
+    /// Update build revision
+    pub ubr: u32,
+    /// Client or Server
+    pub installation_type: String,
+    /// Architecture as reported by PROCESSOR_ARCHITECTURE, e.g. AMD64
+    pub arch: String,
+    /// IDs of the installed updates, e.g. KB5034441
+    pub hotfixes: Vec<String>,
+    pub software: Vec<Software>,
+}
+
+impl WindowsInventory {
+    /// Parses the output of the inventory script, returns None when it contains no build.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut result = Self::default();
+        let mut release_id = String::new();
+        for line in output.lines() {
+            let Some((key, value)) = line.trim_end_matches('\r').split_once('=') else {
+                continue;
+            };
+            let value = value.trim().to_string();
+            match key {
+                "product_name" => result.product_name = value,
+                "edition_id" => result.edition_id = value,
+                "display_version" => result.display_version = value,
+                "release_id" => release_id = value,
+                "version" => result.version = value,
+                "build" => result.build = value.parse().ok()?,
+                "ubr" => result.ubr = value.parse().unwrap_or_default(),
+                "installation_type" => result.installation_type = value,
+                "arch" => result.arch = value,
+                "hotfix" if !value.is_empty() && !result.hotfixes.contains(&value) => {
+                    result.hotfixes.push(value)
+                }
+                "software" => {
+                    let mut fields = value.split('\t').map(|x| x.trim().to_string());
+                    result.software.push(Software {
+                        name: fields.next().unwrap_or_default(),
+                        version: fields.next().unwrap_or_default(),
+                        publisher: fields.next().unwrap_or_default(),
+                        location: fields.next().unwrap_or_default(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        if result.build == 0 {
+            return None;
+        }
+        // releases before 20H2 only set the release id
+        if result.display_version.is_empty() {
+            result.display_version = release_id;
+        }
+        if result.build >= WINDOWS_11_BUILD && result.product_name.contains("Windows 10") {
+            result.product_name = result.product_name.replace("Windows 10", "Windows 11");
+        }
+        Some(result)
+    }
+
+    fn is_server(&self) -> bool {
+        self.installation_type.contains("Server") || self.product_name.contains("Server")
+    }
+
+    /// Returns the full version including build and update build revision, e.g. 10.0.22631.3447.
+    pub fn full_version(&self) -> String {
+        format!("{}.{}.{}", self.version, self.build, self.ubr)
+    }
+
+    /// Returns the product name without edition, e.g. windows_server_2012_r2.
+    fn family(&self) -> String {
+        self.product_name
+            .split_whitespace()
+            .filter(|x| *x != "Microsoft")
+            .take_while(|x| {
+                matches!(*x, "Windows" | "Server" | "R2") || x.chars().all(|c| c.is_ascii_digit())
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+            .to_lowercase()
+    }
+
+    /// Returns the product with the feature update for client versions, e.g. windows_11_23h2.
+    fn product(&self) -> String {
+        match self.is_server() || self.display_version.is_empty() {
+            true => self.family(),
+            false => format!("{}_{}", self.family(), self.display_version.to_lowercase()),
+        }
+    }
+
+    fn arch(&self) -> &str {
+        match self.arch.to_uppercase().as_str() {
+            "AMD64" | "X64" => "x64",
+            "ARM64" => "arm64",
+            _ => "x86",
+        }
+    }
+
+    /// Returns the CPE of the operating system, e.g. cpe:/o:microsoft:windows_11_23h2:10.0.22631.3447.
+    pub fn cpe(&self) -> String {
+        format!(
+            "cpe:/o:microsoft:{}:{}",
+            self.product(),
+            self.full_version()
+        )
+    }
+
+    /// Returns the release as used by the notus scanner, e.g. windows_11_23h2_x64.
+    pub fn release_notus(&self) -> String {
+        format!("{}_{}", self.product(), self.arch())
+    }
+
+    /// Stores the inventory in the KB and registers the operating system as host detail.
+    pub fn store(&self, context: &Context) -> Result<(), FunctionErrorKind> {
+        set_kb_item(context, "Host/runs_windows", 1)?;
+        for (key, value) in [
+            ("SMB/WindowsName", &self.product_name),
+            ("SMB/WindowsVersion", &self.version),
+            ("SMB/WindowsBuild", &self.build.to_string()),
+            ("SMB/Windows/UBR", &self.ubr.to_string()),
+            ("SMB/Windows/FullVersion", &self.full_version()),
+            ("SMB/Windows/DisplayVersion", &self.display_version),
+            ("SMB/Windows/EditionID", &self.edition_id),
+            ("SMB/Windows/InstallationType", &self.installation_type),
+            ("SMB/Windows/Arch", &self.arch().to_string()),
+        ] {
+            if !value.is_empty() {
+                set_kb_item(context, key, value.as_str())?;
+            }
+        }
+        for hotfix in &self.hotfixes {
+            set_kb_item(context, "SMB/Windows/Hotfix", hotfix.as_str())?;
+        }
+        for software in &self.software {
+            set_kb_item(context, "SMB/Windows/Software", software.name.as_str())?;
+            let prefix = format!("SMB/Windows/Software/{}", software.name);
+            for (key, value) in [
+                ("Version", &software.version),
+                ("Publisher", &software.publisher),
+                ("Location", &software.location),
+            ] {
+                if !value.is_empty() {
+                    set_kb_item(context, &format!("{prefix}/{key}"), value.as_str())?;
+                }
+            }
+        }
+        set_kb_item(context, RELEASE_NOTUS, self.release_notus())?;
+        set_kb_item(context, PACKAGE_LIST_NOTUS, self.full_version())?;
+        register_host_detail(context, "OS", &self.cpe())?;
+        register_host_detail(context, "best_os_txt", &self.product_name)
+    }
+}
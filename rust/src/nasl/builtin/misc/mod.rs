@@ -9,7 +9,6 @@ mod tests;
 
 use std::{
     collections::HashMap,
-    fs::File,
     io::{Read, Write},
     thread,
     time::{self, Duration, UNIX_EPOCH},
@@ -26,10 +25,10 @@ use flate2::{
 };
 
 #[inline]
-#[cfg(unix)]
+#[cfg(all(unix, feature = "nasl-builtin-raw-ip"))]
 /// Reads 8 bytes from /dev/urandom and parses it to an i64
 pub fn random_impl() -> Result<i64, FunctionErrorKind> {
-    let mut rng = File::open("/dev/urandom")?;
+    let mut rng = std::fs::File::open("/dev/urandom")?;
     let mut buffer = [0u8; 8];
     rng.read_exact(&mut buffer)
         .map(|_| i64::from_be_bytes(buffer))
@@ -37,9 +36,32 @@ pub fn random_impl() -> Result<i64, FunctionErrorKind> {
 }
 
 /// NASL function to get random number
+///
+/// The number is taken from the random source of the context, which is the random number
+/// generator of the operating system unless a deterministic one is injected.
 #[nasl_function]
-fn rand() -> Result<i64, FunctionErrorKind> {
-    random_impl()
+fn rand(context: &Context) -> i64 {
+    let mut buffer = [0u8; 8];
+    context.random().fill(&mut buffer);
+    i64::from_be_bytes(buffer)
+}
+
+/// Maximum number of bytes returned by rand_bytes at once
+const MAX_RAND_BYTES: usize = 1024 * 1024;
+
+/// Returns the given amount of cryptographically secure random bytes as data.
+///
+/// Takes the length as unnamed or named argument len. Meant for nonces, IVs and keys in
+/// protocol scripts, the bytes are taken from the random source of the context. Lengths above
+/// [MAX_RAND_BYTES] are rejected.
+#[nasl_function(maybe_named(len))]
+fn rand_bytes(context: &Context, len: usize) -> Result<Vec<u8>, FunctionErrorKind> {
+    if len > MAX_RAND_BYTES {
+        return Err(FunctionErrorKind::WrongArgument(format!(
+            "len must not exceed {MAX_RAND_BYTES}, got {len}"
+        )));
+    }
+    Ok(context.random().bytes(len))
 }
 
 /// NASL function to get host byte order
@@ -250,6 +272,7 @@ function_set! {
     sync_stateless,
     (
         rand,
+        rand_bytes,
         get_byte_order,
         dec2str,
         (nasl_typeof, "typeof"),
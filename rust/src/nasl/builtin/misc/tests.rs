@@ -7,6 +7,7 @@ mod tests {
     use chrono::Offset;
 
     use crate::nasl::test_prelude::*;
+    use crate::nasl::utils::RandomSource;
 
     use std::time::Instant;
 
@@ -16,6 +17,33 @@ mod tests {
         check_code_result_matches!("rand();", NaslValue::Number(_));
     }
 
+    #[test]
+    fn rand_bytes() {
+        let mut t = TestBuilder::default();
+        t.ok("strlen(hexstr(rand_bytes(16)));", 32);
+        t.ok("strlen(rand_bytes(len: 0));", 0);
+        t.ok("rand_bytes(8) == rand_bytes(8);", false);
+        t.ok("strlen(hexstr(rand_bytes(1048576)));", 2097152);
+        check_err_matches!(
+            t,
+            "rand_bytes(1048577);",
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+
+    #[test]
+    fn deterministic_random() {
+        let run = |seed| {
+            let mut t = TestBuilder::default()
+                .with_context(ContextFactory::default().random(RandomSource::seeded(seed)));
+            t.run("rand_bytes(16);");
+            t.run("rand();");
+            t.results()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+
     #[test]
     fn get_byte_order() {
         check_code_result_matches!("get_byte_order();", NaslValue::Boolean(_));
@@ -5,27 +5,42 @@
 #![doc = include_str!("README.md")]
 
 mod array;
+mod asn1;
+mod cert;
 mod cryptographic;
-mod description;
+pub(crate) mod description;
+mod file;
 mod host;
 mod http;
 mod isotime;
 mod knowledge_base;
+mod ldap;
+mod lsc;
 mod misc;
 mod network;
+mod postgres;
 #[cfg(feature = "nasl-builtin-raw-ip")]
 mod raw_ip;
+mod redis;
 mod regex;
 mod report_functions;
+mod smb;
+mod snmp;
 #[cfg(feature = "nasl-builtin-ssh")]
 mod ssh;
 mod string;
+mod types;
+mod winrm;
+mod wmi;
 
 #[cfg(test)]
 mod tests;
 
 use crate::nasl::syntax::{Loader, NoOpLoader};
-use crate::nasl::utils::{Context, Executor, NaslVarRegister, NaslVarRegisterBuilder, Register};
+use crate::nasl::utils::{
+    Context, Executor, NaslVarRegister, NaslVarRegisterBuilder, RandomSource, Register, TempDir,
+    Traffic,
+};
 use crate::storage::{ContextKey, DefaultDispatcher, Storage};
 
 /// Creates a new Executor and adds all the functions to it.
@@ -46,11 +61,29 @@ pub fn nasl_std_functions() -> Executor {
         .add_set(http::NaslHttp::default())
         .add_set(network::socket::NaslSockets::default())
         .add_set(network::network::Network)
+        .add_set(network::discovery::Discovery)
+        .add_set(network::mssql::Mssql)
+        .add_set(network::mysql::Mysql)
+        .add_set(network::ntp::Ntp)
+        .add_set(network::rdp::Rdp)
+        .add_set(network::vnc::Vnc)
         .add_set(regex::RegularExpressions)
         .add_set(cryptographic::Cryptographic)
         .add_set(description::Description)
         .add_set(isotime::NaslIsotime)
-        .add_set(cryptographic::rc4::CipherHandlers::default());
+        .add_set(cryptographic::rc4::CipherHandlers::default())
+        .add_set(cryptographic::hash::HashHandlers::default())
+        .add_set(cert::NaslCerts::default())
+        .add_set(asn1::NaslAsn1)
+        .add_set(types::Types)
+        .add_set(file::Files)
+        .add_set(winrm::WinRm::default())
+        .add_set(smb::Smb::default())
+        .add_set(wmi::Wmi::default())
+        .add_set(snmp::Snmp::default())
+        .add_set(ldap::Ldap::default())
+        .add_set(postgres::Postgres::default())
+        .add_set(redis::Redis::default());
 
     #[cfg(feature = "nasl-builtin-ssh")]
     executor.add_set(ssh::Ssh::default());
@@ -72,7 +105,7 @@ pub fn nasl_std_functions() -> Executor {
 ///
 /// This way the user can decide on compile if the functionality, and therefore the variables, are enabled or not.
 pub fn nasl_std_variables() -> NaslVarRegister {
-    let mut builder = NaslVarRegisterBuilder::new();
+    let mut builder = NaslVarRegisterBuilder::new().push_register(network::Encaps);
     builder = add_raw_ip_vars(builder);
     builder.build()
 }
@@ -99,6 +132,12 @@ pub struct ContextFactory<Loader, Storage> {
     pub loader: Loader,
     /// The functions available to the nasl script.
     pub functions: Executor,
+    /// The source of random bytes shared by the created contexts.
+    pub random: RandomSource,
+    /// The recording or replay of network traffic shared by the created contexts.
+    pub traffic: Option<Traffic>,
+    /// The temporary directory shared by the created contexts.
+    pub temp_dir: TempDir,
 }
 
 impl Default for ContextFactory<NoOpLoader, DefaultDispatcher> {
@@ -107,6 +146,9 @@ impl Default for ContextFactory<NoOpLoader, DefaultDispatcher> {
             loader: NoOpLoader::default(),
             functions: nasl_std_functions(),
             storage: DefaultDispatcher::default(),
+            random: RandomSource::default(),
+            traffic: None,
+            temp_dir: TempDir::default(),
         }
     }
 }
@@ -124,6 +166,9 @@ where
             storage,
             loader,
             functions: nasl_std_functions(),
+            random: RandomSource::default(),
+            traffic: None,
+            temp_dir: TempDir::default(),
         }
     }
 
@@ -133,6 +178,24 @@ where
         self
     }
 
+    /// Sets the source of random bytes, e.g. a seeded one for reproducible tests.
+    pub fn random(mut self, random: RandomSource) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// Records the network traffic of the scripts or replays a recording.
+    pub fn traffic(mut self, traffic: Traffic) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+
+    /// Sets the directory files written by the scripts are stored in.
+    pub fn temp_dir(mut self, temp_dir: TempDir) -> Self {
+        self.temp_dir = temp_dir;
+        self
+    }
+
     /// Creates a new Context with the shared loader, logger and function register
     pub fn build(&self, key: ContextKey) -> Context {
         let target = match &key {
@@ -148,6 +211,9 @@ where
             &self.loader,
             &self.functions,
         )
+        .with_random(self.random.clone())
+        .with_traffic(self.traffic.clone())
+        .with_temp_dir(self.temp_dir.clone())
     }
 }
 
@@ -0,0 +1,414 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Multicast name resolution for the discovery of devices in the local network.
+//!
+//! mDNS (RFC 6762) and LLMNR (RFC 4795) queries are sent to the scanned host or, with
+//! multicast set, to the group of the protocol so that every responder of the link answers.
+//! The queries are sent from an ephemeral port, therefore responders answer via unicast and
+//! the socket does not need to join the group.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use super::{
+    network_utils::{bind_local_socket, convert_timeout, target_ip},
+    udp::is_timeout,
+};
+use indexmap::IndexMap;
+
+use crate::function_set;
+use crate::nasl::builtin::host::{parse_record_type, record_to_nasl};
+use crate::nasl::prelude::*;
+use crate::nasl::utils::resolver::{
+    decode_multicast_response, encode_multicast_query, RecordData, RecordType, ResourceRecord,
+};
+
+const MDNS_PORT: u16 = 5353;
+const LLMNR_PORT: u16 = 5355;
+const MDNS_GROUP_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+const LLMNR_GROUP_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 252);
+const LLMNR_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 3);
+/// Name listing the service types of a responder (RFC 6763)
+const SERVICE_TYPES: &str = "_services._dns-sd._udp.local";
+/// Time to wait for answers when no timeout is given
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+/// mDNS messages may be as large as the jumbo frames of the link
+const MAX_MESSAGE: usize = 9000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Mdns,
+    Llmnr,
+}
+
+impl Protocol {
+    fn port(&self) -> u16 {
+        match self {
+            Self::Mdns => MDNS_PORT,
+            Self::Llmnr => LLMNR_PORT,
+        }
+    }
+
+    fn group(&self, ipv6: bool) -> IpAddr {
+        match (self, ipv6) {
+            (Self::Mdns, false) => MDNS_GROUP_V4.into(),
+            (Self::Mdns, true) => MDNS_GROUP_V6.into(),
+            (Self::Llmnr, false) => LLMNR_GROUP_V4.into(),
+            (Self::Llmnr, true) => LLMNR_GROUP_V6.into(),
+        }
+    }
+}
+
+/// A record together with the responder that sent it
+#[derive(Debug, Clone)]
+struct Answer {
+    source: IpAddr,
+    record: ResourceRecord,
+}
+
+/// Sends the query and returns the records of the responses.
+///
+/// A query to the target returns as soon as it answered, a multicast query collects the
+/// answers of all responders until the timeout expired.
+fn query(
+    context: &Context,
+    protocol: Protocol,
+    name: &str,
+    record_type: RecordType,
+    multicast: bool,
+    timeout: Duration,
+) -> Result<Vec<Answer>, FunctionErrorKind> {
+    let target = target_ip(context)?;
+    let destination = match multicast {
+        true => protocol.group(target.is_ipv6()),
+        false => target,
+    };
+    let destination = SocketAddr::new(destination, protocol.port());
+    let socket = bind_local_socket(&destination, context.source_binding())?;
+    let id = rand::random();
+    socket.send_to(&encode_multicast_query(id, name, record_type)?, destination)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut result = vec![];
+    let mut buf = vec![0; MAX_MESSAGE];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let (length, source) = match socket.recv_from(&mut buf) {
+            Ok(x) => x,
+            Err(e) if is_timeout(&e) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if !multicast && source.ip() != target {
+            continue;
+        }
+        match decode_multicast_response(&buf[..length]) {
+            // some mDNS responders do not echo the id of a unicast query
+            Ok((x, records)) if x == id || x == 0 => {
+                result.extend(records.into_iter().map(|record| Answer {
+                    source: source.ip(),
+                    record,
+                }));
+                if !multicast {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::debug!(%source, %e, "ignoring invalid response"),
+        }
+    }
+    Ok(result)
+}
+
+fn answers_to_nasl(answers: Vec<Answer>) -> Vec<NaslValue> {
+    answers
+        .into_iter()
+        .map(|answer| {
+            let mut result = record_to_nasl(answer.record);
+            if let NaslValue::Dict(x) = &mut result {
+                x.insert(
+                    "source".to_string(),
+                    NaslValue::String(answer.source.to_string()),
+                );
+            }
+            result
+        })
+        .collect()
+}
+
+/// NASL function to query the mDNS responder of the target or, with multicast, of all hosts
+/// of the local link
+///
+/// Each record is returned as array like the ones of dns_query with the address of the
+/// responder as additional key source.
+#[nasl_function(named(name, r#type, multicast, timeout))]
+fn mdns_query(
+    context: &Context,
+    name: &str,
+    r#type: Option<&str>,
+    multicast: Option<bool>,
+    timeout: Option<i64>,
+) -> Result<Vec<NaslValue>, FunctionErrorKind> {
+    let answers = query(
+        context,
+        Protocol::Mdns,
+        name,
+        parse_record_type(r#type)?,
+        multicast.unwrap_or(false),
+        convert_timeout(timeout).unwrap_or(DEFAULT_TIMEOUT),
+    )?;
+    Ok(answers_to_nasl(answers))
+}
+
+/// NASL function to query the LLMNR responder of the target or, with multicast, of all hosts
+/// of the local link
+///
+/// Each record is returned as array like the ones of dns_query with the address of the
+/// responder as additional key source.
+#[nasl_function(named(name, r#type, multicast, timeout))]
+fn llmnr_query(
+    context: &Context,
+    name: &str,
+    r#type: Option<&str>,
+    multicast: Option<bool>,
+    timeout: Option<i64>,
+) -> Result<Vec<NaslValue>, FunctionErrorKind> {
+    let answers = query(
+        context,
+        Protocol::Llmnr,
+        name,
+        parse_record_type(r#type)?,
+        multicast.unwrap_or(false),
+        convert_timeout(timeout).unwrap_or(DEFAULT_TIMEOUT),
+    )?;
+    Ok(answers_to_nasl(answers))
+}
+
+/// Service instance announced via DNS-SD
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ServiceInstance {
+    /// Service type, e.g. _ssh._tcp.local
+    service: String,
+    target: Option<String>,
+    port: Option<u16>,
+    text: Vec<String>,
+}
+
+/// Adds the instances pointed to by the service types and the details of their SRV and TXT
+/// records.
+fn collect_instances(
+    types: &BTreeSet<String>,
+    answers: &[Answer],
+    instances: &mut BTreeMap<(IpAddr, String), ServiceInstance>,
+) {
+    for answer in answers {
+        if let RecordData::Name(instance) = &answer.record.data {
+            if types.contains(&answer.record.name) {
+                instances
+                    .entry((answer.source, instance.clone()))
+                    .or_insert_with(|| ServiceInstance {
+                        service: answer.record.name.clone(),
+                        ..Default::default()
+                    });
+            }
+        }
+    }
+    for answer in answers {
+        let Some(instance) = instances.get_mut(&(answer.source, answer.record.name.clone())) else {
+            continue;
+        };
+        match &answer.record.data {
+            RecordData::Srv { port, target, .. } => {
+                instance.port = Some(*port);
+                instance.target = Some(target.clone());
+            }
+            RecordData::Txt(text) => instance.text = text.clone(),
+            _ => {}
+        }
+    }
+}
+
+/// NASL function to enumerate the services announced via DNS-SD by the target or, with
+/// multicast, by all hosts of the local link
+///
+/// Each service instance is returned as array with the keys source, service, instance, target,
+/// port and text.
+#[nasl_function(named(multicast, timeout))]
+fn mdns_services(
+    context: &Context,
+    multicast: Option<bool>,
+    timeout: Option<i64>,
+) -> Result<Vec<NaslValue>, FunctionErrorKind> {
+    let multicast = multicast.unwrap_or(false);
+    let timeout = convert_timeout(timeout).unwrap_or(DEFAULT_TIMEOUT);
+    let query = |name: &str, record_type| {
+        query(
+            context,
+            Protocol::Mdns,
+            name,
+            record_type,
+            multicast,
+            timeout,
+        )
+    };
+
+    let types: BTreeSet<String> = query(SERVICE_TYPES, RecordType::Ptr)?
+        .into_iter()
+        .filter_map(|x| match x.record.data {
+            RecordData::Name(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    let mut answers = vec![];
+    for service in &types {
+        answers.extend(query(service, RecordType::Ptr)?);
+    }
+    let mut instances = BTreeMap::new();
+    collect_instances(&types, &answers, &mut instances);
+    // responders usually add SRV and TXT records to the answer, otherwise they are asked
+    let missing: BTreeSet<String> = instances
+        .iter()
+        .filter(|(_, x)| x.port.is_none())
+        .map(|((_, name), _)| name.clone())
+        .collect();
+    for instance in missing {
+        answers.extend(query(&instance, RecordType::Srv)?);
+        answers.extend(query(&instance, RecordType::Txt)?);
+    }
+    collect_instances(&types, &answers, &mut instances);
+
+    Ok(instances
+        .into_iter()
+        .map(|((source, name), instance)| {
+            let mut result = IndexMap::new();
+            result.insert("source".to_string(), NaslValue::String(source.to_string()));
+            result.insert("service".to_string(), NaslValue::String(instance.service));
+            result.insert("instance".to_string(), NaslValue::String(name));
+            if let Some(target) = instance.target {
+                result.insert("target".to_string(), NaslValue::String(target));
+            }
+            if let Some(port) = instance.port {
+                result.insert("port".to_string(), NaslValue::Number(port as i64));
+            }
+            result.insert(
+                "text".to_string(),
+                NaslValue::Array(instance.text.into_iter().map(NaslValue::String).collect()),
+            );
+            NaslValue::Dict(result)
+        })
+        .collect())
+}
+
+pub struct Discovery;
+
+function_set! {
+    Discovery,
+    sync_stateless,
+    (
+        mdns_query,
+        mdns_services,
+        llmnr_query,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        net::IpAddr,
+    };
+
+    use super::{collect_instances, Answer, ServiceInstance};
+    use crate::nasl::utils::resolver::{RecordData, RecordType, ResourceRecord};
+
+    fn answer(source: &str, name: &str, data: RecordData) -> Answer {
+        let record_type = match data {
+            RecordData::Name(_) => RecordType::Ptr,
+            RecordData::Srv { .. } => RecordType::Srv,
+            _ => RecordType::Txt,
+        };
+        Answer {
+            source: source.parse().unwrap(),
+            record: ResourceRecord {
+                name: name.to_string(),
+                record_type,
+                ttl: 120,
+                data,
+            },
+        }
+    }
+
+    #[test]
+    fn instances() {
+        let types = BTreeSet::from(["_ssh._tcp.local".to_string()]);
+        let answers = vec![
+            answer(
+                "192.0.2.1",
+                "_ssh._tcp.local",
+                RecordData::Name("a._ssh._tcp.local".into()),
+            ),
+            answer(
+                "192.0.2.2",
+                "_ssh._tcp.local",
+                RecordData::Name("b._ssh._tcp.local".into()),
+            ),
+            answer(
+                "192.0.2.1",
+                "a._ssh._tcp.local",
+                RecordData::Srv {
+                    priority: 0,
+                    weight: 0,
+                    port: 22,
+                    target: "a.local".into(),
+                },
+            ),
+            // the SRV record of another responder is ignored
+            answer(
+                "192.0.2.1",
+                "b._ssh._tcp.local",
+                RecordData::Srv {
+                    priority: 0,
+                    weight: 0,
+                    port: 2222,
+                    target: "b.local".into(),
+                },
+            ),
+            answer(
+                "192.0.2.2",
+                "b._ssh._tcp.local",
+                RecordData::Txt(vec!["path=/".into()]),
+            ),
+        ];
+        let mut instances = BTreeMap::new();
+        collect_instances(&types, &answers, &mut instances);
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+        assert_eq!(
+            instances[&(a, "a._ssh._tcp.local".to_string())],
+            ServiceInstance {
+                service: "_ssh._tcp.local".into(),
+                target: Some("a.local".into()),
+                port: Some(22),
+                text: vec![],
+            }
+        );
+        assert_eq!(
+            instances[&(b, "b._ssh._tcp.local".to_string())],
+            ServiceInstance {
+                service: "_ssh._tcp.local".into(),
+                target: None,
+                port: None,
+                text: vec!["path=/".into()],
+            }
+        );
+    }
+}
@@ -5,16 +5,27 @@
 use std::{fmt::Display, net::IpAddr};
 
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::{Context, FunctionErrorKind};
+use crate::nasl::utils::{Context, FunctionErrorKind, NaslVarDefiner, NaslVars};
 use crate::storage::{Field, Retrieve};
 
+pub mod discovery;
+pub mod mssql;
+pub mod mysql;
 #[allow(clippy::module_inception)]
 pub mod network;
 pub mod network_utils;
+pub mod ntp;
+pub mod rdp;
+pub mod sctp;
 pub mod socket;
+pub mod ssh_kex;
+pub mod ssl;
 pub mod tcp;
+pub mod telnet;
 pub mod tls;
 pub mod udp;
+pub mod unix;
+pub mod vnc;
 
 // 512 Bytes are typically supported by network devices. The ip header maximum size is 60 and a UDP
 // header contains 8 bytes, which must be subtracted from the max size for UDP packages.
@@ -78,6 +89,30 @@ impl Display for OpenvasEncaps {
     }
 }
 
+/// Defines the ENCAPS_* constants selecting the transport of a socket.
+pub struct Encaps;
+
+impl NaslVarDefiner for Encaps {
+    fn nasl_var_define(&self) -> NaslVars<'_> {
+        [
+            ("ENCAPS_AUTO", OpenvasEncaps::Auto),
+            ("ENCAPS_IP", OpenvasEncaps::Ip),
+            ("ENCAPS_SSLv23", OpenvasEncaps::Ssl23),
+            ("ENCAPS_SSLv2", OpenvasEncaps::Ssl2),
+            ("ENCAPS_SSLv3", OpenvasEncaps::Ssl3),
+            ("ENCAPS_TLSv1", OpenvasEncaps::Tls1),
+            ("ENCAPS_TLSv11", OpenvasEncaps::Tls11),
+            ("ENCAPS_TLSv12", OpenvasEncaps::Tls12),
+            ("ENCAPS_TLSv13", OpenvasEncaps::Tls13),
+            ("ENCAPS_TLScustom", OpenvasEncaps::TlsCustom),
+            ("ENCAPS_MAX", OpenvasEncaps::Max),
+        ]
+        .into_iter()
+        .map(|(name, encaps)| (name, NaslValue::Number(encaps as i64)))
+        .collect()
+    }
+}
+
 pub fn get_retry(context: &Context) -> u8 {
     if let Ok(Some(val)) = get_kb_item(context, "timeout_retry") {
         match val {
@@ -0,0 +1,851 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Pre-login and login of the Tabular Data Stream protocol (MS-TDS) of Microsoft SQL Server.
+//!
+//! The client offers encryption of the login in the pre-login, the server decides whether no,
+//! only the login or the whole connection is encrypted. The TLS handshake is tunneled in
+//! pre-login packets, afterwards the TLS records are sent on the connection directly. When
+//! only the login is encrypted, the connection continues in plain text after the login packet.
+
+use std::{
+    io::{self, Read, Write},
+    mem,
+    net::IpAddr,
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+use openssl::ssl::{SslStream, SslVersion};
+
+use super::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    ssl::{self, SslOptions},
+    tcp::TcpConnection,
+};
+use crate::function_set;
+use crate::nasl::prelude::*;
+
+const MSSQL_PORT: u16 = 1433;
+
+const PACKET_HEADER_LENGTH: usize = 8;
+/// Size of the packets sent by the client, including the header
+const PACKET_SIZE: usize = 4096;
+/// Status of the last packet of a message
+const STATUS_EOM: u8 = 0x01;
+
+const TYPE_TABULAR_RESULT: u8 = 0x04;
+const TYPE_LOGIN7: u8 = 0x10;
+const TYPE_PRELOGIN: u8 = 0x12;
+
+const PRELOGIN_VERSION: u8 = 0x00;
+const PRELOGIN_ENCRYPTION: u8 = 0x01;
+const PRELOGIN_INSTOPT: u8 = 0x02;
+const PRELOGIN_THREADID: u8 = 0x03;
+const PRELOGIN_MARS: u8 = 0x04;
+const PRELOGIN_TERMINATOR: u8 = 0xff;
+/// Length of an option in the pre-login: token, offset and length of the data
+const PRELOGIN_OPTION_LENGTH: usize = 5;
+
+const ENCRYPT_OFF: u8 = 0x00;
+const ENCRYPT_ON: u8 = 0x01;
+const ENCRYPT_NOT_SUP: u8 = 0x02;
+const ENCRYPT_REQ: u8 = 0x03;
+
+const ENCRYPTIONS: [(u8, &str); 4] = [
+    (ENCRYPT_OFF, "off"),
+    (ENCRYPT_ON, "on"),
+    (ENCRYPT_NOT_SUP, "not_supported"),
+    (ENCRYPT_REQ, "required"),
+];
+
+/// TDS 7.4, sent little-endian in the login but big-endian in the acknowledgement
+const TDS_VERSION: u32 = 0x7400_0004;
+/// Length of the login before the variable fields, which are referenced by offset and length
+const LOGIN_FIXED_LENGTH: usize = 94;
+/// USE_DB_ON, INIT_DB_FATAL and SET_LANG_ON
+const OPTION_FLAGS1: u8 = 0xe0;
+/// INIT_LANG_FATAL and ODBC_ON
+const OPTION_FLAGS2: u8 = 0x03;
+const LCID_EN_US: u32 = 0x0409;
+/// Name of the application and the client library
+const CLIENT_NAME: &str = "OpenVAS";
+/// Longest username, password or database name in characters
+const MAX_LOGIN_FIELD: usize = 128;
+
+const TOKEN_ERROR: u8 = 0xaa;
+const TOKEN_INFO: u8 = 0xab;
+const TOKEN_LOGINACK: u8 = 0xad;
+const TOKEN_ENVCHANGE: u8 = 0xe3;
+const ENVCHANGE_DATABASE: u8 = 1;
+
+fn encryption_name(encryption: u8) -> &'static str {
+    ENCRYPTIONS
+        .iter()
+        .find(|(x, _)| *x == encryption)
+        .map_or("unknown", |(_, name)| name)
+}
+
+fn ucs2(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn from_ucs2(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Encodes the password as UCS-2 with the nibbles of each byte swapped and XORed with 0xa5.
+fn obfuscate(password: &str) -> Vec<u8> {
+    ucs2(password)
+        .into_iter()
+        .map(|x| x.rotate_left(4) ^ 0xa5)
+        .collect()
+}
+
+/// Splits the message into packets of the type.
+fn packets(kind: u8, message: &[u8]) -> Vec<u8> {
+    let chunks: Vec<&[u8]> = message.chunks(PACKET_SIZE - PACKET_HEADER_LENGTH).collect();
+    let mut packets = Vec::with_capacity(message.len() + chunks.len() * PACKET_HEADER_LENGTH);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let status = if i + 1 == chunks.len() { STATUS_EOM } else { 0 };
+        let length = (chunk.len() + PACKET_HEADER_LENGTH) as u16;
+        packets.extend([kind, status]);
+        packets.extend(length.to_be_bytes());
+        // the process ID on the server, the ID of the packet and the unused window
+        packets.extend([0, 0, (i + 1) as u8, 0]);
+        packets.extend_from_slice(chunk);
+    }
+    packets
+}
+
+/// Returns the type, the status and the length of the data of the packet.
+fn packet_header(header: &[u8; PACKET_HEADER_LENGTH]) -> io::Result<(u8, u8, usize)> {
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if length < PACKET_HEADER_LENGTH {
+        return Err(invalid_data("invalid TDS packet length"));
+    }
+    Ok((header[0], header[1], length - PACKET_HEADER_LENGTH))
+}
+
+fn prelogin_request(encryption: u8) -> Vec<u8> {
+    let options: [(u8, &[u8]); 5] = [
+        // version of the client and its subbuild
+        (PRELOGIN_VERSION, &[0; 6]),
+        (PRELOGIN_ENCRYPTION, &[encryption]),
+        // the default instance
+        (PRELOGIN_INSTOPT, &[0]),
+        (PRELOGIN_THREADID, &[0; 4]),
+        (PRELOGIN_MARS, &[0]),
+    ];
+    let start = options.len() * PRELOGIN_OPTION_LENGTH + 1;
+    let mut request = Vec::new();
+    let mut data = Vec::new();
+    for (token, value) in options {
+        request.push(token);
+        request.extend(((start + data.len()) as u16).to_be_bytes());
+        request.extend((value.len() as u16).to_be_bytes());
+        data.extend_from_slice(value);
+    }
+    request.push(PRELOGIN_TERMINATOR);
+    request.extend(data);
+    request
+}
+
+/// Pre-login response of the server
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Prelogin {
+    /// Major, minor, build and subbuild number of the server
+    version: Option<(u8, u8, u16, u16)>,
+    encryption: u8,
+    mars: Option<bool>,
+}
+
+fn parse_prelogin(data: &[u8]) -> io::Result<Prelogin> {
+    let mut version = None;
+    let mut encryption = None;
+    let mut mars = None;
+    let mut options = data;
+    loop {
+        match options {
+            [PRELOGIN_TERMINATOR, ..] => break,
+            [token, o1, o2, l1, l2, rest @ ..] => {
+                let offset = u16::from_be_bytes([*o1, *o2]) as usize;
+                let length = u16::from_be_bytes([*l1, *l2]) as usize;
+                let value = data
+                    .get(offset..offset + length)
+                    .ok_or_else(|| invalid_data("pre-login option out of bounds"))?;
+                match (*token, value) {
+                    (PRELOGIN_VERSION, [major, minor, b1, b2, s1, s2, ..]) => {
+                        let build = u16::from_be_bytes([*b1, *b2]);
+                        let subbuild = u16::from_be_bytes([*s1, *s2]);
+                        version = Some((*major, *minor, build, subbuild));
+                    }
+                    (PRELOGIN_ENCRYPTION, [x, ..]) => encryption = Some(*x),
+                    (PRELOGIN_MARS, [x, ..]) => mars = Some(*x != 0),
+                    _ => {}
+                }
+                options = rest;
+            }
+            _ => return Err(invalid_data("truncated pre-login")),
+        }
+    }
+    Ok(Prelogin {
+        version,
+        encryption: encryption.ok_or_else(|| invalid_data("pre-login without encryption"))?,
+        mars,
+    })
+}
+
+/// Encryption negotiated in the pre-login, when the client offers to encrypt the login
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encryption {
+    None,
+    Login,
+    Full,
+}
+
+impl Encryption {
+    fn negotiate(server: u8) -> io::Result<Self> {
+        match server {
+            ENCRYPT_NOT_SUP => Ok(Self::None),
+            ENCRYPT_OFF => Ok(Self::Login),
+            ENCRYPT_ON | ENCRYPT_REQ => Ok(Self::Full),
+            _ => Err(invalid_data("invalid encryption")),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Login => "login",
+            Self::Full => "full",
+        }
+    }
+}
+
+fn login_request(username: &str, password: &str, database: &str, server: &str) -> Vec<u8> {
+    let fields = [
+        // name of the client host
+        vec![],
+        ucs2(username),
+        obfuscate(password),
+        ucs2(CLIENT_NAME),
+        ucs2(server),
+        // extension
+        vec![],
+        ucs2(CLIENT_NAME),
+        // language
+        vec![],
+        ucs2(database),
+    ];
+    let mut offsets = Vec::new();
+    let mut data = Vec::new();
+    for field in fields {
+        offsets.extend(((LOGIN_FIXED_LENGTH + data.len()) as u16).to_le_bytes());
+        offsets.extend(((field.len() / 2) as u16).to_le_bytes());
+        data.extend(field);
+    }
+    // the MAC address of the client
+    offsets.extend([0; 6]);
+    // SSPI, the database file to attach and the new password are empty, as is the long SSPI
+    for _ in 0..3 {
+        offsets.extend(((LOGIN_FIXED_LENGTH + data.len()) as u16).to_le_bytes());
+        offsets.extend([0; 2]);
+    }
+    offsets.extend([0; 4]);
+    let mut login = Vec::with_capacity(LOGIN_FIXED_LENGTH + data.len());
+    login.extend(((LOGIN_FIXED_LENGTH + data.len()) as u32).to_le_bytes());
+    login.extend(TDS_VERSION.to_le_bytes());
+    login.extend((PACKET_SIZE as u32).to_le_bytes());
+    // version of the client program, its process ID and the connection ID
+    login.extend([0; 4]);
+    login.extend(std::process::id().to_le_bytes());
+    login.extend([0; 4]);
+    // type flags and option flags 3 are not set
+    login.extend([OPTION_FLAGS1, OPTION_FLAGS2, 0, 0]);
+    // time zone
+    login.extend([0; 4]);
+    login.extend(LCID_EN_US.to_le_bytes());
+    login.extend(offsets);
+    login.extend(data);
+    login
+}
+
+/// Response of the server to the login
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct LoginResponse {
+    /// TDS version, name and major, minor and build number of the server acknowledging the login
+    ack: Option<(u32, String, (u8, u8, u16))>,
+    /// Number, state and message of the first error
+    error: Option<(i32, u8, String)>,
+    database: Option<String>,
+}
+
+/// Splits the string of the length in characters from the data.
+fn split_ucs2(data: &[u8], length: usize) -> Option<(String, &[u8])> {
+    let (text, rest) = data.split_at_checked(length * 2)?;
+    Some((from_ucs2(text), rest))
+}
+
+fn parse_login_response(data: &[u8]) -> io::Result<LoginResponse> {
+    let truncated = || invalid_data("truncated login response");
+    let mut result = LoginResponse::default();
+    let mut tokens = data;
+    while let [token @ (TOKEN_ERROR | TOKEN_INFO | TOKEN_LOGINACK | TOKEN_ENVCHANGE), l1, l2, rest @ ..] =
+        tokens
+    {
+        let length = u16::from_le_bytes([*l1, *l2]) as usize;
+        let (value, rest) = rest.split_at_checked(length).ok_or_else(truncated)?;
+        match (*token, value) {
+            (TOKEN_ERROR, [n1, n2, n3, n4, state, _class, l1, l2, message @ ..]) => {
+                let length = u16::from_le_bytes([*l1, *l2]) as usize;
+                let (message, _) = split_ucs2(message, length).ok_or_else(truncated)?;
+                let number = i32::from_le_bytes([*n1, *n2, *n3, *n4]);
+                result.error.get_or_insert((number, *state, message));
+            }
+            (TOKEN_LOGINACK, [_interface, v1, v2, v3, v4, length, name @ ..]) => {
+                let (name, version) = split_ucs2(name, *length as usize).ok_or_else(truncated)?;
+                let [major, minor, b1, b2] = version else {
+                    return Err(truncated());
+                };
+                let tds_version = u32::from_be_bytes([*v1, *v2, *v3, *v4]);
+                let build = u16::from_be_bytes([*b1, *b2]);
+                result.ack = Some((tds_version, name, (*major, *minor, build)));
+            }
+            (TOKEN_ENVCHANGE, [ENVCHANGE_DATABASE, length, database @ ..]) => {
+                result.database = split_ucs2(database, *length as usize).map(|(x, _)| x);
+            }
+            _ => {}
+        }
+        tokens = rest;
+    }
+    // the done token and tokens not answering a login end the response
+    Ok(result)
+}
+
+/// Connection to the server, tunneling the TLS records in pre-login packets during the
+/// handshake
+struct Tunnel {
+    conn: TcpConnection,
+    timeout: Duration,
+    tunneling: bool,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Read for Tunnel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.tunneling {
+            return self.conn.read_with_timeout(buf, self.timeout);
+        }
+        self.flush()?;
+        while self.incoming.is_empty() {
+            let mut header = [0; PACKET_HEADER_LENGTH];
+            self.conn
+                .read_exact_with_timeout(&mut header, self.timeout)?;
+            let (_, _, length) = packet_header(&header)?;
+            self.incoming = vec![0; length];
+            self.conn
+                .read_exact_with_timeout(&mut self.incoming, self.timeout)?;
+        }
+        let n = buf.len().min(self.incoming.len());
+        buf[..n].copy_from_slice(&self.incoming[..n]);
+        self.incoming.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for Tunnel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.tunneling {
+            return self.conn.write(buf);
+        }
+        // the records of a flight are sent together when the TLS layer flushes
+        self.outgoing.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.outgoing.is_empty() {
+            let outgoing = mem::take(&mut self.outgoing);
+            self.conn.write_all(&packets(TYPE_PRELOGIN, &outgoing))?;
+        }
+        self.conn.flush()
+    }
+}
+
+/// Both variants are boxed, as the tunneled connection is large
+enum Stream {
+    Plain(Box<Tunnel>),
+    Tls(Box<SslStream<Tunnel>>),
+}
+
+struct Connection {
+    stream: Stream,
+    /// False after the login when only the login is encrypted
+    encrypted: bool,
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            Stream::Tls(tls) if self.encrypted => tls.read(buf),
+            Stream::Tls(tls) => tls.get_mut().read(buf),
+            Stream::Plain(tunnel) => tunnel.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            Stream::Tls(tls) if self.encrypted => tls.write(buf),
+            Stream::Tls(tls) => tls.get_mut().write(buf),
+            Stream::Plain(tunnel) => tunnel.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Stream::Tls(tls) if self.encrypted => tls.flush(),
+            Stream::Tls(tls) => tls.get_mut().flush(),
+            Stream::Plain(tunnel) => tunnel.flush(),
+        }
+    }
+}
+
+impl Connection {
+    fn open(context: &Context, addrs: &[IpAddr], port: u16, timeout: Duration) -> io::Result<Self> {
+        let conn = NaslSockets::connect_tcp(context, addrs, port, None, timeout, None)?;
+        let tunnel = Tunnel {
+            conn,
+            timeout,
+            tunneling: false,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        };
+        Ok(Self {
+            stream: Stream::Plain(Box::new(tunnel)),
+            encrypted: false,
+        })
+    }
+
+    fn send(&mut self, kind: u8, message: &[u8]) -> io::Result<()> {
+        self.write_all(&packets(kind, message))?;
+        self.flush()
+    }
+
+    /// Receives the packets of a message and returns its type and data.
+    fn receive(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0; PACKET_HEADER_LENGTH];
+            self.read_exact(&mut header)?;
+            let (kind, status, length) = packet_header(&header)?;
+            let start = message.len();
+            message.resize(start + length, 0);
+            self.read_exact(&mut message[start..])?;
+            if status & STATUS_EOM != 0 {
+                return Ok((kind, message));
+            }
+        }
+    }
+
+    fn prelogin(&mut self, encryption: u8) -> io::Result<Prelogin> {
+        self.send(TYPE_PRELOGIN, &prelogin_request(encryption))?;
+        match self.receive()? {
+            (TYPE_TABULAR_RESULT, response) => parse_prelogin(&response),
+            _ => Err(invalid_data("no TDS server")),
+        }
+    }
+
+    /// Performs the TLS handshake tunneled in pre-login packets.
+    ///
+    /// TLS 1.3 is only used with TDS 8, whose handshake precedes the pre-login. It is not
+    /// offered, as the session tickets sent after its handshake would not be tunneled.
+    fn start_tls(self) -> io::Result<Self> {
+        let Stream::Plain(mut tunnel) = self.stream else {
+            return Err(io::Error::other("TLS is already negotiated"));
+        };
+        tunnel.tunneling = true;
+        let options = SslOptions {
+            max_version: Some(SslVersion::TLS1_2),
+            ..Default::default()
+        };
+        let mut tls = ssl::connect(*tunnel, &options)?;
+        tls.get_mut().tunneling = false;
+        Ok(Self {
+            stream: Stream::Tls(Box::new(tls)),
+            encrypted: true,
+        })
+    }
+}
+
+fn login(
+    mut conn: Connection,
+    username: &str,
+    password: &str,
+    database: &str,
+    server: &str,
+) -> io::Result<(Encryption, LoginResponse)> {
+    let prelogin = conn.prelogin(ENCRYPT_OFF)?;
+    let encryption = Encryption::negotiate(prelogin.encryption)?;
+    if encryption != Encryption::None {
+        conn = conn.start_tls()?;
+    }
+    conn.send(
+        TYPE_LOGIN7,
+        &login_request(username, password, database, server),
+    )?;
+    conn.encrypted = encryption == Encryption::Full;
+    match conn.receive()? {
+        (TYPE_TABULAR_RESULT, response) => Ok((encryption, parse_login_response(&response)?)),
+        _ => Err(invalid_data("unexpected response to the login")),
+    }
+}
+
+/// NASL function to send a pre-login to the SQL server of the target
+///
+/// Returns an array with the version of the server and its encryption setting, NULL when the
+/// service does not answer the pre-login.
+#[nasl_function(named(port, timeout))]
+fn mssql_prelogin(
+    context: &Context,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let (addrs, port, timeout) = target_service(context, port, MSSQL_PORT, timeout)?;
+    let prelogin = match Connection::open(context, &addrs, port, timeout)
+        .and_then(|mut x| x.prelogin(ENCRYPT_OFF))
+    {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::debug!(%e, "TDS pre-login failed");
+            return Ok(NaslValue::Null);
+        }
+    };
+    let mut result = IndexMap::new();
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    if let Some((major, minor, build, subbuild)) = prelogin.version {
+        add(
+            "version",
+            NaslValue::String(format!("{major}.{minor}.{build}")),
+        );
+        add("major", NaslValue::Number(major as i64));
+        add("minor", NaslValue::Number(minor as i64));
+        add("build", NaslValue::Number(build as i64));
+        add("subbuild", NaslValue::Number(subbuild as i64));
+    }
+    add("encryption", NaslValue::Number(prelogin.encryption as i64));
+    add(
+        "encryption_name",
+        NaslValue::String(encryption_name(prelogin.encryption).to_string()),
+    );
+    if let Some(mars) = prelogin.mars {
+        add("mars", NaslValue::Boolean(mars));
+    }
+    Ok(NaslValue::Dict(result))
+}
+
+/// NASL function to log into the SQL server of the target with SQL Server authentication
+///
+/// The login is encrypted unless the server does not support encryption. Returns an array
+/// with the result of the login, NULL when the connection or the encryption fails.
+#[nasl_function(named(username, password, database, port, timeout))]
+fn mssql_login(
+    context: &Context,
+    username: &str,
+    password: Option<&str>,
+    database: Option<&str>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let password = password.unwrap_or_default();
+    let database = database.unwrap_or_default();
+    for (key, value) in [
+        ("username", username),
+        ("password", password),
+        ("database", database),
+    ] {
+        if value.encode_utf16().count() > MAX_LOGIN_FIELD {
+            return Err(FunctionErrorKind::wrong_argument(
+                key,
+                "at most 128 characters",
+                value,
+            ));
+        }
+    }
+    let (addrs, port, timeout) = target_service(context, port, MSSQL_PORT, timeout)?;
+    let server = context.target();
+    let (encryption, response) = match Connection::open(context, &addrs, port, timeout)
+        .and_then(|x| login(x, username, password, database, server))
+    {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::debug!(%e, "TDS login failed");
+            return Ok(NaslValue::Null);
+        }
+    };
+    let mut result = IndexMap::new();
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    add("authenticated", NaslValue::Boolean(response.ack.is_some()));
+    add(
+        "encryption",
+        NaslValue::String(encryption.name().to_string()),
+    );
+    if let Some((tds_version, product, (major, minor, build))) = response.ack {
+        add("tds_version", NaslValue::Number(tds_version as i64));
+        add("product", NaslValue::String(product));
+        add(
+            "product_version",
+            NaslValue::String(format!("{major}.{minor}.{build}")),
+        );
+    }
+    if let Some(database) = response.database {
+        add("database", NaslValue::String(database));
+    }
+    if let Some((number, state, message)) = response.error {
+        add("error_number", NaslValue::Number(number as i64));
+        add("error_state", NaslValue::Number(state as i64));
+        add("error", NaslValue::String(message));
+    }
+    Ok(NaslValue::Dict(result))
+}
+
+pub struct Mssql;
+
+function_set! {
+    Mssql,
+    sync_stateless,
+    (
+        mssql_prelogin,
+        mssql_login,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read, Write},
+        net::TcpStream,
+    };
+
+    use crate::nasl::builtin::network::network_utils::tests::tcp_server;
+    use crate::nasl::builtin::network::ssl::tests::acceptor;
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    use super::*;
+
+    /// Server side of the tunnel, sending each TLS record in its own pre-login packet
+    struct Framed {
+        tcp: TcpStream,
+        tunneling: bool,
+        incoming: Vec<u8>,
+    }
+
+    impl Read for Framed {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.tunneling {
+                return self.tcp.read(buf);
+            }
+            if self.incoming.is_empty() {
+                self.incoming = read_message(&mut self.tcp)?;
+            }
+            let n = buf.len().min(self.incoming.len());
+            buf[..n].copy_from_slice(&self.incoming[..n]);
+            self.incoming.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for Framed {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.tunneling {
+                return self.tcp.write(buf);
+            }
+            self.tcp.write_all(&packets(TYPE_PRELOGIN, buf))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.tcp.flush()
+        }
+    }
+
+    fn read_message(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0; PACKET_HEADER_LENGTH];
+            stream.read_exact(&mut header)?;
+            let (_, status, length) = packet_header(&header)?;
+            let start = message.len();
+            message.resize(start + length, 0);
+            stream.read_exact(&mut message[start..])?;
+            if status & STATUS_EOM != 0 {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn token(kind: u8, value: &[u8]) -> Vec<u8> {
+        let mut token = vec![kind];
+        token.extend((value.len() as u16).to_le_bytes());
+        token.extend(value);
+        token
+    }
+
+    /// Returns the response to the login, which succeeds for sa with the password secret.
+    fn login_response(login: &[u8]) -> Vec<u8> {
+        assert_eq!(u32_le(login) as usize, login.len());
+        let field = |i: usize| {
+            let at = 36 + i * 4;
+            let offset = u16::from_le_bytes([login[at], login[at + 1]]) as usize;
+            let length = u16::from_le_bytes([login[at + 2], login[at + 3]]) as usize * 2;
+            &login[offset..offset + length]
+        };
+        let username = from_ucs2(field(1));
+        let password: Vec<u8> = field(2).iter().map(|x| (x ^ 0xa5).rotate_left(4)).collect();
+        let mut response = Vec::new();
+        if username == "sa" && from_ucs2(&password) == "secret" {
+            let database = ucs2("master");
+            let mut value = vec![ENVCHANGE_DATABASE, 6];
+            value.extend(database);
+            value.push(0);
+            response.extend(token(TOKEN_ENVCHANGE, &value));
+            let name = ucs2("Microsoft SQL Server");
+            let mut value = vec![1];
+            value.extend(TDS_VERSION.to_be_bytes());
+            value.push(20);
+            value.extend(name);
+            value.extend([15, 0, 0x07, 0xd0]);
+            response.extend(token(TOKEN_LOGINACK, &value));
+        } else {
+            let message = format!("Login failed for user '{username}'.");
+            let mut value = 18456i32.to_le_bytes().to_vec();
+            value.extend([1, 14]);
+            value.extend((message.len() as u16).to_le_bytes());
+            value.extend(ucs2(&message));
+            // names of the server and the procedure and the line number
+            value.extend([0, 0, 1, 0, 0, 0]);
+            response.extend(token(TOKEN_ERROR, &value));
+        }
+        // the done token
+        response.extend([0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        response
+    }
+
+    fn u32_le(data: &[u8]) -> u32 {
+        u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+    }
+
+    fn serve(tcp: TcpStream, encryption: u8) -> io::Result<()> {
+        let mut framed = Framed {
+            tcp,
+            tunneling: false,
+            incoming: Vec::new(),
+        };
+        let request = parse_prelogin(&read_message(&mut framed)?)?;
+        assert_eq!(request.encryption, ENCRYPT_OFF);
+        let mut response = prelogin_request(encryption);
+        // the version is the first option, followed by MARS disabled
+        let start = 5 * PRELOGIN_OPTION_LENGTH + 1;
+        response[start..start + 6].copy_from_slice(&[15, 0, 0x07, 0xd0, 0, 5]);
+        framed.write_all(&packets(TYPE_TABULAR_RESULT, &response))?;
+        if encryption == ENCRYPT_NOT_SUP {
+            let response = login_response(&read_message(&mut framed)?);
+            return framed.write_all(&packets(TYPE_TABULAR_RESULT, &response));
+        }
+        framed.tunneling = true;
+        let mut tls = acceptor()
+            .build()
+            .accept(framed)
+            .map_err(|_| io::Error::other("TLS handshake failed"))?;
+        tls.get_mut().tunneling = false;
+        let response = packets(
+            TYPE_TABULAR_RESULT,
+            &login_response(&read_message(&mut tls)?),
+        );
+        match encryption {
+            // only the login is encrypted
+            ENCRYPT_OFF => tls.get_mut().write_all(&response),
+            _ => tls.write_all(&response),
+        }
+    }
+
+    /// Answers the pre-login with the encryption setting of the server and the login.
+    fn server(encryption: u8) -> u16 {
+        tcp_server(move |stream| {
+            let _ = serve(stream, encryption);
+        })
+    }
+
+    #[test]
+    fn requests() {
+        let prelogin = parse_prelogin(&prelogin_request(ENCRYPT_OFF)).unwrap();
+        assert_eq!(prelogin.version, Some((0, 0, 0, 0)));
+        assert_eq!(prelogin.mars, Some(false));
+        let login = login_request("sa", "", "", "127.0.0.1");
+        assert_eq!(
+            login.len(),
+            LOGIN_FIXED_LENGTH + ucs2("sa127.0.0.1").len() + 2 * ucs2(CLIENT_NAME).len()
+        );
+        assert_eq!(login[4..8], [0x04, 0, 0, 0x74]);
+        assert_eq!(obfuscate("a"), [0xb3, 0xa5]);
+        let split = packets(TYPE_LOGIN7, &[0; PACKET_SIZE]);
+        assert_eq!(split.len(), PACKET_SIZE + 2 * PACKET_HEADER_LENGTH);
+        assert_eq!(split[1], 0);
+        assert_eq!(split[PACKET_SIZE + 1], STATUS_EOM);
+    }
+
+    #[test]
+    fn prelogin() {
+        let port = server(ENCRYPT_NOT_SUP);
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.run(format!("r = mssql_prelogin(port: {port});"));
+        t.ok(r#"r["version"];"#, "15.0.2000");
+        t.ok(r#"r["subbuild"];"#, 5);
+        t.ok(r#"r["encryption_name"];"#, "not_supported");
+        t.ok(r#"r["mars"];"#, false);
+    }
+
+    #[test]
+    fn login() {
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        for (encryption, name) in [
+            (ENCRYPT_NOT_SUP, "none"),
+            (ENCRYPT_OFF, "login"),
+            (ENCRYPT_REQ, "full"),
+        ] {
+            let port = server(encryption);
+            t.run(format!(
+                r#"r = mssql_login(username: "sa", password: "secret", port: {port});"#
+            ));
+            t.ok(r#"r["authenticated"];"#, true);
+            t.ok(r#"r["encryption"];"#, name);
+            t.ok(r#"r["tds_version"];"#, TDS_VERSION as i64);
+            t.ok(r#"r["product"];"#, "Microsoft SQL Server");
+            t.ok(r#"r["product_version"];"#, "15.0.2000");
+            t.ok(r#"r["database"];"#, "master");
+            t.run(format!(
+                r#"r = mssql_login(username: "sa", password: "", port: {port});"#
+            ));
+            t.ok(r#"r["authenticated"];"#, false);
+            t.ok(r#"r["error_number"];"#, 18456);
+            t.ok(r#"r["error"];"#, "Login failed for user 'sa'.");
+        }
+        check_err_matches!(
+            t,
+            r#"mssql_login(username: crap(129));"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+}
@@ -0,0 +1,780 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Initial handshake and authentication of the MySQL client/server protocol.
+//!
+//! The server greets with its version and the scramble the password is hashed with. TLS is
+//! negotiated before the login when the server supports it. Otherwise a full authentication
+//! with caching_sha2_password encrypts the password with the RSA key of the server.
+
+use std::{
+    io::{self, Write},
+    net::IpAddr,
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+use rsa::{pkcs8::DecodePublicKey, Oaep, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    ssl::SslOptions,
+    tcp::TcpConnection,
+};
+use crate::function_set;
+use crate::nasl::prelude::*;
+
+const MYSQL_PORT: u16 = 3306;
+
+const PACKET_HEADER_LENGTH: usize = 4;
+/// Longest payload of a packet, longer ones continue in the next packet
+const MAX_PAYLOAD: usize = 0xff_ffff;
+/// Largest packet the client accepts
+const MAX_PACKET_SIZE: u32 = 1 << 24;
+const PROTOCOL_VERSION: u8 = 10;
+const CHARSET_UTF8: u8 = 33;
+/// Length of the client capabilities, the SSL request consists of them only
+const CAPABILITIES_LENGTH: usize = 32;
+
+const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_TRANSACTIONS: u32 = 0x0000_2000;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+
+const OK: u8 = 0x00;
+const AUTH_MORE_DATA: u8 = 0x01;
+const AUTH_SWITCH: u8 = 0xfe;
+const ERR: u8 = 0xff;
+
+const NATIVE_PASSWORD: &str = "mysql_native_password";
+const CACHING_SHA2_PASSWORD: &str = "caching_sha2_password";
+const REQUEST_PUBLIC_KEY: u8 = 0x02;
+const FAST_AUTH_SUCCESS: u8 = 0x03;
+const PERFORM_FULL_AUTH: u8 = 0x04;
+
+/// Reads the fields of a packet.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let (field, rest) = self
+            .0
+            .split_at_checked(length)
+            .ok_or_else(|| invalid_data("truncated packet"))?;
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let field = self.take(2)?;
+        Ok(u16::from_le_bytes([field[0], field[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let field = self.take(4)?;
+        Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+    }
+
+    /// Takes the string up to the NUL byte, or the rest when it lacks one.
+    fn string(&mut self) -> String {
+        let length = self.0.iter().position(|x| *x == 0);
+        let (field, rest) = self.0.split_at(length.unwrap_or(self.0.len()));
+        self.0 = rest.get(1..).unwrap_or_default();
+        String::from_utf8_lossy(field).into_owned()
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.0)
+    }
+}
+
+/// Error sent by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ServerError {
+    number: u16,
+    state: Option<String>,
+    message: String,
+}
+
+/// Parses the error packet following its header byte.
+fn parse_error(data: &[u8]) -> io::Result<ServerError> {
+    let mut fields = Fields(data);
+    let number = fields.u16()?;
+    // the state is missing when the server refuses the connection before the handshake
+    let state = match fields.0 {
+        [b'#', ..] => Some(String::from_utf8_lossy(&fields.take(6)?[1..]).into_owned()),
+        _ => None,
+    };
+    Ok(ServerError {
+        number,
+        state,
+        message: String::from_utf8_lossy(fields.rest()).into_owned(),
+    })
+}
+
+/// Initial handshake of the server
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Handshake {
+    server_version: String,
+    connection_id: u32,
+    capabilities: u32,
+    character_set: u8,
+    status: u16,
+    scramble: Vec<u8>,
+    /// Default authentication plugin, empty for servers before 5.5
+    auth_plugin: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Greeting {
+    Handshake(Handshake),
+    Refused(ServerError),
+}
+
+fn parse_greeting(data: &[u8]) -> io::Result<Greeting> {
+    let mut fields = Fields(data);
+    match fields.u8()? {
+        PROTOCOL_VERSION => {}
+        ERR => return Ok(Greeting::Refused(parse_error(fields.rest())?)),
+        _ => return Err(invalid_data("unsupported protocol version")),
+    }
+    let mut handshake = Handshake {
+        server_version: fields.string(),
+        connection_id: fields.u32()?,
+        scramble: fields.take(8)?.to_vec(),
+        ..Default::default()
+    };
+    fields.take(1)?;
+    handshake.capabilities = fields.u16()? as u32;
+    if fields.0.is_empty() {
+        return Ok(Greeting::Handshake(handshake));
+    }
+    handshake.character_set = fields.u8()?;
+    handshake.status = fields.u16()?;
+    handshake.capabilities |= (fields.u16()? as u32) << 16;
+    let scramble_length = fields.u8()? as usize;
+    fields.take(10)?;
+    if handshake.capabilities & CLIENT_SECURE_CONNECTION != 0 {
+        let scramble = fields.take(scramble_length.saturating_sub(8).max(13))?;
+        handshake
+            .scramble
+            .extend_from_slice(scramble.strip_suffix(&[0]).unwrap_or(scramble));
+    }
+    if handshake.capabilities & CLIENT_PLUGIN_AUTH != 0 {
+        handshake.auth_plugin = fields.string();
+    }
+    Ok(Greeting::Handshake(handshake))
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(x, y)| x ^ y).collect()
+}
+
+/// Returns SHA1(password) XOR SHA1(scramble + SHA1(SHA1(password))).
+fn native_password(password: &[u8], scramble: &[u8]) -> Vec<u8> {
+    let hash = Sha1::digest(password);
+    let mut hasher = Sha1::new();
+    hasher.update(scramble);
+    hasher.update(Sha1::digest(hash));
+    xor(&hash, &hasher.finalize())
+}
+
+/// Returns SHA256(password) XOR SHA256(SHA256(SHA256(password)) + scramble).
+fn caching_sha2_password(password: &[u8], scramble: &[u8]) -> Vec<u8> {
+    let hash = Sha256::digest(password);
+    let mut hasher = Sha256::new();
+    hasher.update(Sha256::digest(hash));
+    hasher.update(scramble);
+    xor(&hash, &hasher.finalize())
+}
+
+/// Returns the response to the scramble with the authentication plugin, an empty password is
+/// sent as empty response.
+fn auth_response(plugin: &str, password: &str, scramble: &[u8]) -> io::Result<Vec<u8>> {
+    let password = password.as_bytes();
+    match plugin {
+        NATIVE_PASSWORD | CACHING_SHA2_PASSWORD if password.is_empty() => Ok(Vec::new()),
+        NATIVE_PASSWORD => Ok(native_password(password, scramble)),
+        CACHING_SHA2_PASSWORD => Ok(caching_sha2_password(password, scramble)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported authentication plugin {plugin}"),
+        )),
+    }
+}
+
+/// Encrypts the NUL-terminated password XORed with the scramble with the PEM encoded RSA key.
+fn encrypt_password(password: &[u8], scramble: &[u8], key: &[u8]) -> io::Result<Vec<u8>> {
+    let key = std::str::from_utf8(key)
+        .ok()
+        .and_then(|x| RsaPublicKey::from_public_key_pem(x).ok())
+        .ok_or_else(|| invalid_data("invalid public key"))?;
+    let password: Vec<u8> = password
+        .iter()
+        .zip(scramble.iter().cycle())
+        .map(|(x, y)| x ^ y)
+        .collect();
+    key.encrypt(&mut rand::thread_rng(), Oaep::new::<Sha1>(), &password)
+        .map_err(io::Error::other)
+}
+
+/// Returns the capabilities, the maximum packet size and the character set of the client.
+fn client_capabilities(capabilities: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(CAPABILITIES_LENGTH);
+    data.extend(capabilities.to_le_bytes());
+    data.extend(MAX_PACKET_SIZE.to_le_bytes());
+    data.push(CHARSET_UTF8);
+    data.resize(CAPABILITIES_LENGTH, 0);
+    data
+}
+
+/// Result of the login
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Login {
+    /// The error refusing the login
+    error: Option<ServerError>,
+    auth_plugin: String,
+    ssl: bool,
+}
+
+struct Connection {
+    conn: TcpConnection,
+    timeout: Duration,
+    /// Sequence number of the next packet
+    sequence: u8,
+}
+
+impl Connection {
+    fn open(context: &Context, addrs: &[IpAddr], port: u16, timeout: Duration) -> io::Result<Self> {
+        let conn = NaslSockets::connect_tcp(context, addrs, port, None, timeout, None)?;
+        Ok(Self {
+            conn,
+            timeout,
+            sequence: 0,
+        })
+    }
+
+    fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut packet = (payload.len() as u32).to_le_bytes();
+        packet[3] = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.conn.write_all(&packet)?;
+        self.conn.write_all(payload)?;
+        self.conn.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        loop {
+            let mut header = [0; PACKET_HEADER_LENGTH];
+            self.conn
+                .read_exact_with_timeout(&mut header, self.timeout)?;
+            self.sequence = header[3].wrapping_add(1);
+            let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            let start = payload.len();
+            payload.resize(start + length, 0);
+            self.conn
+                .read_exact_with_timeout(&mut payload[start..], self.timeout)?;
+            if length < MAX_PAYLOAD {
+                return Ok(payload);
+            }
+        }
+    }
+
+    fn greeting(&mut self) -> io::Result<Greeting> {
+        let greeting = self.receive()?;
+        parse_greeting(&greeting)
+    }
+
+    fn login(
+        &mut self,
+        handshake: &Handshake,
+        username: &str,
+        password: &str,
+        database: Option<&str>,
+    ) -> io::Result<Login> {
+        if handshake.capabilities & CLIENT_PROTOCOL_41 == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "protocol before MySQL 4.1",
+            ));
+        }
+        let mut capabilities = CLIENT_LONG_PASSWORD
+            | CLIENT_PROTOCOL_41
+            | CLIENT_TRANSACTIONS
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_PLUGIN_AUTH;
+        if database.is_some() {
+            capabilities |= CLIENT_CONNECT_WITH_DB;
+        }
+        let ssl = handshake.capabilities & CLIENT_SSL != 0;
+        if ssl {
+            capabilities |= CLIENT_SSL;
+            self.send(&client_capabilities(capabilities))?;
+            self.conn
+                .negotiate_ssl(&SslOptions::default(), self.timeout)?;
+        }
+        // the plugin of servers before 5.5 and those not known to the client are replaced
+        // by the native password, the server switches to the plugin of the user if needed
+        let mut auth_plugin = match handshake.auth_plugin.as_str() {
+            x @ (NATIVE_PASSWORD | CACHING_SHA2_PASSWORD) => x.to_string(),
+            _ => NATIVE_PASSWORD.to_string(),
+        };
+        let mut scramble = handshake.scramble.clone();
+        let auth = auth_response(&auth_plugin, password, &scramble)?;
+        let mut response = client_capabilities(capabilities);
+        response.extend(username.as_bytes());
+        response.push(0);
+        response.push(auth.len() as u8);
+        response.extend(auth);
+        if let Some(database) = database {
+            response.extend(database.as_bytes());
+            response.push(0);
+        }
+        response.extend(auth_plugin.as_bytes());
+        response.push(0);
+        self.send(&response)?;
+        loop {
+            let packet = self.receive()?;
+            match packet.split_first() {
+                Some((&OK, _)) => {
+                    return Ok(Login {
+                        error: None,
+                        auth_plugin,
+                        ssl,
+                    })
+                }
+                Some((&ERR, error)) => {
+                    return Ok(Login {
+                        error: Some(parse_error(error)?),
+                        auth_plugin,
+                        ssl,
+                    })
+                }
+                Some((&AUTH_SWITCH, data)) => {
+                    let mut fields = Fields(data);
+                    auth_plugin = fields.string();
+                    let data = fields.rest();
+                    scramble = data.strip_suffix(&[0]).unwrap_or(data).to_vec();
+                    self.send(&auth_response(&auth_plugin, password, &scramble)?)?;
+                }
+                Some((&AUTH_MORE_DATA, [FAST_AUTH_SUCCESS])) => {}
+                Some((&AUTH_MORE_DATA, [PERFORM_FULL_AUTH])) => {
+                    let mut cleartext = password.as_bytes().to_vec();
+                    cleartext.push(0);
+                    if ssl {
+                        self.send(&cleartext)?;
+                    } else {
+                        self.send(&[REQUEST_PUBLIC_KEY])?;
+                        let key = self.receive()?;
+                        let key = key
+                            .strip_prefix(&[AUTH_MORE_DATA])
+                            .ok_or_else(|| invalid_data("no public key"))?;
+                        self.send(&encrypt_password(&cleartext, &scramble, key)?)?;
+                    }
+                }
+                _ => return Err(invalid_data("unexpected authentication packet")),
+            }
+        }
+    }
+}
+
+fn add_error(result: &mut IndexMap<String, NaslValue>, error: ServerError) {
+    result.insert(
+        "error_number".to_string(),
+        NaslValue::Number(error.number as i64),
+    );
+    if let Some(state) = error.state {
+        result.insert("error_state".to_string(), NaslValue::String(state));
+    }
+    result.insert("error".to_string(), NaslValue::String(error.message));
+}
+
+/// NASL function to read the initial handshake of the MySQL server of the target
+///
+/// Returns an array with the version and the capabilities of the server or the error it
+/// refused the connection with, NULL when the service does not speak the MySQL protocol.
+#[nasl_function(named(port, timeout))]
+fn mysql_handshake(
+    context: &Context,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let (addrs, port, timeout) = target_service(context, port, MYSQL_PORT, timeout)?;
+    let greeting =
+        match Connection::open(context, &addrs, port, timeout).and_then(|mut x| x.greeting()) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, "MySQL handshake failed");
+                return Ok(NaslValue::Null);
+            }
+        };
+    let mut result = IndexMap::new();
+    let handshake = match greeting {
+        Greeting::Handshake(x) => x,
+        Greeting::Refused(error) => {
+            add_error(&mut result, error);
+            return Ok(NaslValue::Dict(result));
+        }
+    };
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    add(
+        "protocol_version",
+        NaslValue::Number(PROTOCOL_VERSION as i64),
+    );
+    add(
+        "server_version",
+        NaslValue::String(handshake.server_version),
+    );
+    add(
+        "connection_id",
+        NaslValue::Number(handshake.connection_id as i64),
+    );
+    add(
+        "capabilities",
+        NaslValue::Number(handshake.capabilities as i64),
+    );
+    add(
+        "character_set",
+        NaslValue::Number(handshake.character_set as i64),
+    );
+    add("status", NaslValue::Number(handshake.status as i64));
+    add("auth_plugin", NaslValue::String(handshake.auth_plugin));
+    add(
+        "ssl",
+        NaslValue::Boolean(handshake.capabilities & CLIENT_SSL != 0),
+    );
+    Ok(NaslValue::Dict(result))
+}
+
+/// NASL function to log into the MySQL server of the target
+///
+/// Supports the authentication plugins mysql_native_password and caching_sha2_password.
+/// Returns an array with the result of the login, NULL when the connection fails or the
+/// server requires another plugin.
+#[nasl_function(named(username, password, database, port, timeout))]
+fn mysql_login(
+    context: &Context,
+    username: &str,
+    password: Option<&str>,
+    database: Option<&str>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let password = password.unwrap_or_default();
+    let (addrs, port, timeout) = target_service(context, port, MYSQL_PORT, timeout)?;
+    let mut result = IndexMap::new();
+    let login = Connection::open(context, &addrs, port, timeout).and_then(|mut conn| {
+        match conn.greeting()? {
+            Greeting::Handshake(handshake) => {
+                let login = conn.login(&handshake, username, password, database)?;
+                Ok((Some(handshake.server_version), login))
+            }
+            Greeting::Refused(error) => Ok((
+                None,
+                Login {
+                    error: Some(error),
+                    auth_plugin: String::new(),
+                    ssl: false,
+                },
+            )),
+        }
+    });
+    let (server_version, login) = match login {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::debug!(%e, "MySQL login failed");
+            return Ok(NaslValue::Null);
+        }
+    };
+    result.insert(
+        "authenticated".to_string(),
+        NaslValue::Boolean(login.error.is_none()),
+    );
+    if let Some(server_version) = server_version {
+        result.insert(
+            "server_version".to_string(),
+            NaslValue::String(server_version),
+        );
+        result.insert(
+            "auth_plugin".to_string(),
+            NaslValue::String(login.auth_plugin),
+        );
+        result.insert("ssl".to_string(), NaslValue::Boolean(login.ssl));
+    }
+    if let Some(error) = login.error {
+        add_error(&mut result, error);
+    }
+    Ok(NaslValue::Dict(result))
+}
+
+pub struct Mysql;
+
+function_set! {
+    Mysql,
+    sync_stateless,
+    (
+        mysql_handshake,
+        mysql_login,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use openssl::rsa::{Padding, Rsa};
+
+    use crate::nasl::builtin::network::network_utils::tests::tcp_server;
+    use crate::nasl::builtin::network::ssl::tests::acceptor;
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    use super::*;
+
+    const SCRAMBLE: &[u8; 20] = b"0123456789abcdefghij";
+    const PASSWORD: &str = "secret";
+
+    trait Stream: Read + Write {}
+
+    impl<S: Read + Write> Stream for S {}
+
+    fn read_packet(stream: &mut dyn Stream) -> io::Result<(u8, Vec<u8>)> {
+        let mut header = [0; PACKET_HEADER_LENGTH];
+        stream.read_exact(&mut header)?;
+        let mut payload =
+            vec![0; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+        stream.read_exact(&mut payload)?;
+        Ok((header[3], payload))
+    }
+
+    fn write_packet(stream: &mut dyn Stream, sequence: u8, payload: &[u8]) -> io::Result<()> {
+        let mut header = (payload.len() as u32).to_le_bytes();
+        header[3] = sequence;
+        stream.write_all(&header)?;
+        stream.write_all(payload)
+    }
+
+    fn greeting(ssl: bool) -> Vec<u8> {
+        let mut capabilities = CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH;
+        if ssl {
+            capabilities |= CLIENT_SSL;
+        }
+        let mut greeting = vec![PROTOCOL_VERSION];
+        greeting.extend(b"8.0.36\0");
+        greeting.extend(7u32.to_le_bytes());
+        greeting.extend(&SCRAMBLE[..8]);
+        greeting.push(0);
+        greeting.extend((capabilities as u16).to_le_bytes());
+        greeting.push(CHARSET_UTF8);
+        greeting.extend(2u16.to_le_bytes());
+        greeting.extend(((capabilities >> 16) as u16).to_le_bytes());
+        greeting.push(SCRAMBLE.len() as u8 + 1);
+        greeting.extend([0; 10]);
+        greeting.extend(&SCRAMBLE[8..]);
+        greeting.push(0);
+        greeting.extend(CACHING_SHA2_PASSWORD.as_bytes());
+        greeting.push(0);
+        greeting
+    }
+
+    /// Verifies the response to the scramble like the server does with the stored hash of the
+    /// password.
+    fn verify(plugin: &str, response: &[u8]) -> bool {
+        match plugin {
+            NATIVE_PASSWORD => {
+                let stored = Sha1::digest(Sha1::digest(PASSWORD));
+                let mut hasher = Sha1::new();
+                hasher.update(SCRAMBLE);
+                hasher.update(stored);
+                Sha1::digest(xor(response, &hasher.finalize())) == stored
+            }
+            _ => {
+                let stored = Sha256::digest(Sha256::digest(PASSWORD));
+                let mut hasher = Sha256::new();
+                hasher.update(stored);
+                hasher.update(SCRAMBLE);
+                Sha256::digest(xor(response, &hasher.finalize())) == stored
+            }
+        }
+    }
+
+    /// Answers like a server with the user root, whose password is hashed with the plugin.
+    /// caching_sha2_password performs a full authentication until the first successful login.
+    fn serve(tcp: TcpStream, ssl: bool, plugin: &str, cached: &mut bool) -> io::Result<()> {
+        let mut stream: Box<dyn Stream> = Box::new(tcp.try_clone()?);
+        write_packet(&mut *stream, 0, &greeting(ssl))?;
+        let (mut sequence, mut response) = read_packet(&mut *stream)?;
+        if response.len() == CAPABILITIES_LENGTH {
+            let tls = acceptor()
+                .build()
+                .accept(tcp)
+                .map_err(|_| io::Error::other("TLS handshake failed"))?;
+            stream = Box::new(tls);
+            (sequence, response) = read_packet(&mut *stream)?;
+        }
+        let mut fields = Fields(&response[CAPABILITIES_LENGTH..]);
+        let username = fields.string();
+        let length = fields.u8()? as usize;
+        let mut auth = fields.take(length)?.to_vec();
+        if fields.string() != plugin {
+            let mut switch = vec![AUTH_SWITCH];
+            switch.extend(plugin.as_bytes());
+            switch.push(0);
+            switch.extend(SCRAMBLE);
+            switch.push(0);
+            write_packet(&mut *stream, sequence + 1, &switch)?;
+            (sequence, auth) = read_packet(&mut *stream)?;
+        }
+        let valid = match plugin {
+            NATIVE_PASSWORD => verify(plugin, &auth),
+            _ if *cached && verify(plugin, &auth) => {
+                write_packet(
+                    &mut *stream,
+                    sequence + 1,
+                    &[AUTH_MORE_DATA, FAST_AUTH_SUCCESS],
+                )?;
+                sequence += 1;
+                true
+            }
+            _ => {
+                write_packet(
+                    &mut *stream,
+                    sequence + 1,
+                    &[AUTH_MORE_DATA, PERFORM_FULL_AUTH],
+                )?;
+                let (mut next, mut password) = read_packet(&mut *stream)?;
+                if password == [REQUEST_PUBLIC_KEY] {
+                    let key = Rsa::generate(2048).unwrap();
+                    let mut pem = vec![AUTH_MORE_DATA];
+                    pem.extend(key.public_key_to_pem().unwrap());
+                    write_packet(&mut *stream, next + 1, &pem)?;
+                    let encrypted;
+                    (next, encrypted) = read_packet(&mut *stream)?;
+                    let mut decrypted = vec![0; key.size() as usize];
+                    let length = key
+                        .private_decrypt(&encrypted, &mut decrypted, Padding::PKCS1_OAEP)
+                        .unwrap();
+                    password = xor(&decrypted[..length], &SCRAMBLE.repeat(2));
+                }
+                sequence = next;
+                password == b"secret\0"
+            }
+        };
+        if username == "root" && valid {
+            *cached = true;
+            write_packet(&mut *stream, sequence + 1, &[OK, 0, 0, 2, 0, 0, 0])
+        } else {
+            let mut error = vec![ERR];
+            error.extend(1045u16.to_le_bytes());
+            error.extend(b"#28000Access denied for user");
+            write_packet(&mut *stream, sequence + 1, &error)
+        }
+    }
+
+    fn server(ssl: bool, plugin: &'static str) -> u16 {
+        let mut cached = false;
+        tcp_server(move |stream| {
+            let _ = serve(stream, ssl, plugin, &mut cached);
+        })
+    }
+
+    /// Refuses the connection before the handshake.
+    fn refusing_server() -> u16 {
+        tcp_server(|mut stream| {
+            let mut error = vec![ERR];
+            error.extend(1130u16.to_le_bytes());
+            error.extend(b"Host is not allowed to connect");
+            let _ = write_packet(&mut stream, 0, &error);
+        })
+    }
+
+    #[test]
+    fn packets() {
+        let Greeting::Handshake(handshake) = parse_greeting(&greeting(false)).unwrap() else {
+            panic!("no handshake");
+        };
+        assert_eq!(handshake.scramble, SCRAMBLE);
+        assert_eq!(handshake.auth_plugin, CACHING_SHA2_PASSWORD);
+        assert_eq!(handshake.capabilities & CLIENT_SSL, 0);
+        assert_eq!(
+            parse_error(b"\x15\x04#28000Access denied").unwrap(),
+            ServerError {
+                number: 1045,
+                state: Some("28000".to_string()),
+                message: "Access denied".to_string(),
+            }
+        );
+        assert!(verify(
+            NATIVE_PASSWORD,
+            &native_password(PASSWORD.as_bytes(), SCRAMBLE)
+        ));
+        assert!(verify(
+            CACHING_SHA2_PASSWORD,
+            &caching_sha2_password(PASSWORD.as_bytes(), SCRAMBLE)
+        ));
+    }
+
+    #[test]
+    fn handshake() {
+        let port = server(true, NATIVE_PASSWORD);
+        let refusing = refusing_server();
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.run(format!("r = mysql_handshake(port: {port});"));
+        t.ok(r#"r["server_version"];"#, "8.0.36");
+        t.ok(r#"r["connection_id"];"#, 7);
+        t.ok(r#"r["auth_plugin"];"#, "caching_sha2_password");
+        t.ok(r#"r["ssl"];"#, true);
+        t.run(format!("r = mysql_handshake(port: {refusing});"));
+        t.ok(r#"r["error_number"];"#, 1130);
+        t.ok(r#"r["error"];"#, "Host is not allowed to connect");
+        t.run(format!(
+            r#"r = mysql_login(username: "root", port: {refusing});"#
+        ));
+        t.ok(r#"r["authenticated"];"#, false);
+    }
+
+    #[test]
+    fn login() {
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        for (ssl, plugin) in [
+            (false, CACHING_SHA2_PASSWORD),
+            (true, CACHING_SHA2_PASSWORD),
+            (false, NATIVE_PASSWORD),
+            (true, NATIVE_PASSWORD),
+        ] {
+            let port = server(ssl, plugin);
+            // the full authentication of caching_sha2_password is followed by the fast one
+            for _ in 0..2 {
+                t.run(format!(
+                    r#"r = mysql_login(username: "root", password: "secret", port: {port});"#
+                ));
+                t.ok(r#"r["authenticated"];"#, true);
+                t.ok(r#"r["auth_plugin"];"#, plugin);
+                t.ok(r#"r["ssl"];"#, ssl);
+            }
+            t.run(format!(
+                r#"r = mysql_login(username: "root", password: "wrong", port: {port});"#
+            ));
+            t.ok(r#"r["authenticated"];"#, false);
+            t.ok(r#"r["error_number"];"#, 1045);
+            t.ok(r#"r["error_state"];"#, "28000");
+        }
+    }
+}
@@ -6,7 +6,7 @@ use std::{net::IpAddr, process::Command};
 
 use super::mtu;
 use super::{
-    network_utils::{get_netmask_by_local_ip, get_source_ip, ipstr2ipaddr, islocalhost},
+    network_utils::{get_netmask_by_local_ip, get_source_ip, islocalhost, target_ip},
     verify_port, DEFAULT_PORT,
 };
 use crate::function_set;
@@ -23,11 +23,11 @@ fn get_host_ip(context: &Context) -> String {
 /// Get the IP address of the current (attacking) machine depending on which network device is used
 #[nasl_function]
 fn this_host(context: &Context) -> Result<String, FunctionErrorKind> {
-    let dst = ipstr2ipaddr(context.target())?;
+    let dst = target_ip(context)?;
 
     let port: u16 = DEFAULT_PORT;
 
-    get_source_ip(dst, port).map(|ip| ip.to_string())
+    get_source_ip(dst, port, context.source_binding()).map(|ip| ip.to_string())
 }
 
 /// Get the host name of the current (attacking) machine
@@ -43,22 +43,22 @@ fn this_host_name() -> String {
 /// get the maximum transition unit for the scanned host
 #[nasl_function]
 fn get_mtu(context: &Context) -> Result<i64, FunctionErrorKind> {
-    let target = ipstr2ipaddr(context.target())?;
+    let target = target_ip(context)?;
     Ok(mtu(target) as i64)
 }
 
 /// check if the currently scanned host is the localhost
 #[nasl_function]
 fn nasl_islocalhost(context: &Context) -> Result<bool, FunctionErrorKind> {
-    let host_ip = ipstr2ipaddr(context.target())?;
+    let host_ip = target_ip(context)?;
     Ok(islocalhost(host_ip))
 }
 
 /// Check if the target host is on the same network as the attacking host
 #[nasl_function]
 fn islocalnet(context: &Context) -> Result<bool, FunctionErrorKind> {
-    let dst = ipstr2ipaddr(context.target())?;
-    let src = get_source_ip(dst, DEFAULT_PORT)?;
+    let dst = target_ip(context)?;
+    let src = get_source_ip(dst, DEFAULT_PORT, context.source_binding())?;
     let netmask = match get_netmask_by_local_ip(src)? {
         Some(netmask) => netmask,
         None => return Ok(false),
@@ -6,12 +6,16 @@
 use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    ops::RangeInclusive,
     ptr,
     str::FromStr,
     time::Duration,
 };
 
-use crate::nasl::prelude::*;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{unix::target_socket_path, verify_port};
+use crate::nasl::{prelude::*, utils::SourceBinding};
 
 /// Convert a string in a IpAddr
 pub fn ipstr2ipaddr(ip_addr: &str) -> Result<IpAddr, FunctionErrorKind> {
@@ -24,6 +28,33 @@ pub fn ipstr2ipaddr(ip_addr: &str) -> Result<IpAddr, FunctionErrorKind> {
     }
 }
 
+/// Returns the IP address of the target.
+///
+/// A target given as UNIX socket is the local host.
+pub fn target_ip(context: &Context) -> Result<IpAddr, FunctionErrorKind> {
+    match target_socket_path(context.target()) {
+        Some(_) => Ok(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        None => ipstr2ipaddr(context.target()),
+    }
+}
+
+/// Returns the addresses of a host given as IP address or hostname.
+///
+/// A hostname may resolve to addresses of both families, which are ordered for connection
+/// attempts by the preferred address family. The returned addresses are never empty.
+pub fn resolve_host(context: &Context, host: &str) -> Result<Vec<IpAddr>, FunctionErrorKind> {
+    if let Ok(ip) = IpAddr::from_str(host) {
+        return Ok(vec![ip]);
+    }
+    if target_socket_path(host).is_some() {
+        return Ok(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+    }
+    let addrs = context.dns_cache().lookup_host(host).ok_or_else(|| {
+        FunctionErrorKind::Diagnostic(format!("unable to lookup hostname {host}"), None)
+    })?;
+    Ok(context.address_family().sort(addrs))
+}
+
 /// Convert timeout
 pub fn convert_timeout(timeout: Option<i64>) -> Option<Duration> {
     timeout
@@ -31,19 +62,97 @@ pub fn convert_timeout(timeout: Option<i64>) -> Option<Duration> {
         .map(|timeout| Duration::from_secs(timeout as u64))
 }
 
+/// Time to wait for the answers of a service when neither the script nor the scan configures
+/// a timeout
+const SERVICE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the addresses of the target, the port of the service on it and the time to wait
+/// for its answers.
+///
+/// Without a timeout given by the script the scan-wide network timeout is used.
+pub fn target_service(
+    context: &Context,
+    port: Option<i64>,
+    default_port: u16,
+    timeout: Option<i64>,
+) -> Result<(Vec<IpAddr>, u16, Duration), FunctionErrorKind> {
+    let addrs = resolve_host(context, context.target())?;
+    let port = port.map(verify_port).transpose()?.unwrap_or(default_port);
+    let timeout = convert_timeout(timeout)
+        .unwrap_or_else(|| context.network_timeout().timeout(&addrs, SERVICE_TIMEOUT));
+    Ok((addrs, port, timeout))
+}
+
+/// Returns the error for data of a peer violating its protocol.
+pub fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
 /// Bind a local UDP socket to a V4 or V6 address depending on the given destination address
-pub fn bind_local_socket(dst: &SocketAddr) -> io::Result<UdpSocket> {
-    match dst {
-        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0"),
-        SocketAddr::V6(_) => UdpSocket::bind("[::]:0"),
+///
+/// When a source binding is configured the socket is bound to its interface and address.
+pub fn bind_local_socket(dst: &SocketAddr, binding: &SourceBinding) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(*dst), Type::DGRAM, Some(Protocol::UDP))?;
+    binding.bind(&socket, &dst.ip())?;
+    if binding.address(&dst.ip()).is_none() {
+        let unspecified = match dst {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        socket.bind(&SocketAddr::new(unspecified, 0).into())?;
     }
+    Ok(socket.into())
+}
+
+/// Source ports tried in descending order when no source port is given, like rresvport(3) does
+pub const PRIVILEGED_PORTS: RangeInclusive<u16> = 512..=1023;
+
+/// Calls open with privileged source ports until it succeeds.
+///
+/// When a source port is given only that port is tried. Otherwise the ports of
+/// [PRIVILEGED_PORTS] are tried as long as open fails because the port is in use, other errors
+/// like a missing CAP_NET_BIND_SERVICE are returned immediately.
+pub fn with_privileged_port<T>(
+    sport: Option<u16>,
+    mut open: impl FnMut(u16) -> io::Result<T>,
+) -> io::Result<T> {
+    if let Some(port) = sport {
+        return open(port);
+    }
+    let mut last_error = io::Error::from(io::ErrorKind::AddrInUse);
+    for port in PRIVILEGED_PORTS.rev() {
+        match open(port) {
+            Ok(x) => return Ok(x),
+            // the port is bound or, for TCP, already connected to the same destination
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::AddrInUse | io::ErrorKind::AddrNotAvailable
+                ) =>
+            {
+                last_error = e
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error)
 }
 
 /// Return the source IP address given the destination IP address
-pub fn get_source_ip(dst: IpAddr, port: u16) -> Result<IpAddr, FunctionErrorKind> {
+///
+/// A configured source address takes precedence over the address of the route to the
+/// destination.
+pub fn get_source_ip(
+    dst: IpAddr,
+    port: u16,
+    binding: &SourceBinding,
+) -> Result<IpAddr, FunctionErrorKind> {
+    if let Some(ip) = binding.address(&dst) {
+        return Ok(ip);
+    }
     let socket = SocketAddr::new(dst, port);
     let sd = format!("{}:{}", dst, port);
-    let local_socket = bind_local_socket(&socket)?;
+    let local_socket = bind_local_socket(&socket, binding)?;
     local_socket
         .connect(sd)
         .ok()
@@ -136,3 +245,62 @@ pub fn get_netmask_by_local_ip(local_address: IpAddr) -> Result<Option<IpAddr>,
         None,
     ))
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io,
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::with_privileged_port;
+
+    /// Listens on a port of the local host and passes the accepted connections to serve in the
+    /// order they arrive. Returns the port.
+    ///
+    /// Servers keeping several connections open spawn a thread for each connection in serve.
+    pub(crate) fn tcp_server(mut serve: impl FnMut(TcpStream) + Send + 'static) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                serve(stream.unwrap());
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn privileged_port() {
+        let mut tried = vec![];
+        let port = with_privileged_port(None, |port| {
+            tried.push(port);
+            if port > 1020 {
+                Err(io::ErrorKind::AddrInUse.into())
+            } else {
+                Ok(port)
+            }
+        })
+        .unwrap();
+        assert_eq!(port, 1020);
+        assert_eq!(tried, vec![1023, 1022, 1021, 1020]);
+
+        let mut tried = 0;
+        let e = with_privileged_port(None, |_| -> io::Result<()> {
+            tried += 1;
+            Err(io::ErrorKind::PermissionDenied.into())
+        })
+        .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(tried, 1);
+
+        let e = with_privileged_port(None, |_| -> io::Result<()> {
+            Err(io::ErrorKind::AddrInUse.into())
+        })
+        .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AddrInUse);
+
+        assert_eq!(with_privileged_port(Some(2000), Ok).unwrap(), 2000);
+    }
+}
@@ -0,0 +1,557 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! NTP queries for the detection of time servers and their information disclosure.
+//!
+//! Client queries (mode 3, RFC 5905) return the header of the server answer. Control queries
+//! (mode 6, RFC 9327) return the reassembled data of all fragments, e.g. the system variables
+//! containing the version of ntpd, together with the number and size of the answer datagrams
+//! to determine the amplification factor.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    net::Ipv4Addr,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use indexmap::IndexMap;
+
+use super::{
+    network_utils::{convert_timeout, target_ip},
+    socket::NaslSockets,
+    udp::{is_timeout, DEFAULT_RETRIES, DEFAULT_TIMEOUT},
+    verify_port,
+};
+use crate::function_set;
+use crate::nasl::prelude::*;
+
+const NTP_PORT: u16 = 123;
+/// Seconds between the NTP era 0 (1900) and the Unix epoch
+const UNIX_OFFSET: i64 = 2208988800;
+/// Length of a mode 3 and mode 4 packet without extension fields
+const PACKET_LENGTH: usize = 48;
+/// Length of the header of a control message
+const CONTROL_HEADER_LENGTH: usize = 12;
+/// Largest datagram accepted from the server
+const MAX_MESSAGE: usize = 2048;
+
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const MODE_CONTROL: u8 = 6;
+
+const CONTROL_RESPONSE: u8 = 0x80;
+const CONTROL_ERROR: u8 = 0x40;
+const CONTROL_MORE: u8 = 0x20;
+
+/// Operation code reading the status of the associations
+const READSTAT: u8 = 1;
+/// Operation code reading variables
+const READVAR: u8 = 2;
+/// Operation code reading the variables of a reference clock
+const READCLOCK: u8 = 4;
+
+/// Converts a NTP timestamp into seconds since the Unix epoch, 0 when it is not set.
+fn unix_time(timestamp: &[u8]) -> i64 {
+    match u32::from_be_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]) {
+        0 => 0,
+        x => x as i64 - UNIX_OFFSET,
+    }
+}
+
+/// Converts a NTP short format of 16.16 seconds into microseconds.
+fn micros(short: &[u8]) -> i64 {
+    let x = u32::from_be_bytes([short[0], short[1], short[2], short[3]]);
+    ((x as u64 * 1_000_000) >> 16) as i64
+}
+
+/// Returns the reference id, a kiss code or clock source for stratum 0 and 1 and the address
+/// of the upstream server otherwise.
+fn reference_id(stratum: u8, id: &[u8]) -> String {
+    match stratum {
+        0 | 1 => id
+            .iter()
+            .take_while(|x| **x != 0)
+            .map(|x| *x as char)
+            .collect(),
+        _ => Ipv4Addr::new(id[0], id[1], id[2], id[3]).to_string(),
+    }
+}
+
+/// Returns the client request with the current time as transmit timestamp.
+fn client_request(version: u8) -> Vec<u8> {
+    let mut request = vec![0; PACKET_LENGTH];
+    request[0] = (version << 3) | MODE_CLIENT;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = (now.as_secs() as i64 + UNIX_OFFSET) as u32;
+    request[40..44].copy_from_slice(&seconds.to_be_bytes());
+    // the random fraction makes the origin timestamp of the answer hard to guess
+    request[44..48].copy_from_slice(&rand::random::<u32>().to_be_bytes());
+    request
+}
+
+/// Decodes the server answer to the request, None when it is no answer to it.
+fn decode_server(request: &[u8], data: &[u8]) -> Option<IndexMap<String, NaslValue>> {
+    if data.len() < PACKET_LENGTH
+        || data[0] & 0x07 != MODE_SERVER
+        || data[24..32] != request[40..48]
+    {
+        return None;
+    }
+    let stratum = data[1];
+    let mut result = IndexMap::new();
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    add("leap", NaslValue::Number((data[0] >> 6) as i64));
+    add("version", NaslValue::Number(((data[0] >> 3) & 0x07) as i64));
+    add("mode", NaslValue::Number(MODE_SERVER as i64));
+    add("stratum", NaslValue::Number(stratum as i64));
+    add("poll", NaslValue::Number(data[2] as i8 as i64));
+    add("precision", NaslValue::Number(data[3] as i8 as i64));
+    add("root_delay", NaslValue::Number(micros(&data[4..8])));
+    add("root_dispersion", NaslValue::Number(micros(&data[8..12])));
+    add(
+        "reference_id",
+        NaslValue::String(reference_id(stratum, &data[12..16])),
+    );
+    add(
+        "reference_time",
+        NaslValue::Number(unix_time(&data[16..24])),
+    );
+    add("receive_time", NaslValue::Number(unix_time(&data[32..40])));
+    add("transmit_time", NaslValue::Number(unix_time(&data[40..48])));
+    Some(result)
+}
+
+/// Returns the control request, padded to a multiple of 4 octets.
+fn control_request(
+    version: u8,
+    opcode: u8,
+    sequence: u16,
+    association: u16,
+    data: &[u8],
+) -> Vec<u8> {
+    let mut request = vec![(version << 3) | MODE_CONTROL, opcode];
+    request.extend(sequence.to_be_bytes());
+    // status and offset are always 0 in requests
+    request.extend([0, 0]);
+    request.extend(association.to_be_bytes());
+    request.extend([0, 0]);
+    request.extend((data.len() as u16).to_be_bytes());
+    request.extend(data);
+    request.resize(request.len().next_multiple_of(4), 0);
+    request
+}
+
+/// A control response, which may be split into several datagrams
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fragment {
+    version: u8,
+    error: bool,
+    more: bool,
+    status: u16,
+    association: u16,
+    offset: usize,
+    data: Vec<u8>,
+}
+
+/// Decodes the fragment of the response to the request, None when it is no answer to it.
+fn decode_fragment(opcode: u8, sequence: u16, data: &[u8]) -> Option<Fragment> {
+    if data.len() < CONTROL_HEADER_LENGTH
+        || data[0] & 0x07 != MODE_CONTROL
+        || data[1] & CONTROL_RESPONSE == 0
+        || data[1] & 0x1f != opcode
+        || u16::from_be_bytes([data[2], data[3]]) != sequence
+    {
+        return None;
+    }
+    let count = u16::from_be_bytes([data[10], data[11]]) as usize;
+    Some(Fragment {
+        version: (data[0] >> 3) & 0x07,
+        error: data[1] & CONTROL_ERROR != 0,
+        more: data[1] & CONTROL_MORE != 0,
+        status: u16::from_be_bytes([data[4], data[5]]),
+        association: u16::from_be_bytes([data[6], data[7]]),
+        offset: u16::from_be_bytes([data[8], data[9]]) as usize,
+        data: data
+            .get(CONTROL_HEADER_LENGTH..CONTROL_HEADER_LENGTH + count)?
+            .to_vec(),
+    })
+}
+
+/// Collects fragments by their offset until the last one is received and no data is missing.
+#[derive(Debug, Default)]
+struct Response {
+    fragments: BTreeMap<usize, Fragment>,
+    packets: usize,
+    bytes: usize,
+}
+
+impl Response {
+    fn add(&mut self, fragment: Fragment, length: usize) {
+        self.packets += 1;
+        self.bytes += length;
+        self.fragments.insert(fragment.offset, fragment);
+    }
+
+    fn is_complete(&self) -> bool {
+        let mut end = 0;
+        for (offset, fragment) in &self.fragments {
+            if *offset != end {
+                return false;
+            }
+            end += fragment.data.len();
+        }
+        self.fragments.values().last().is_some_and(|x| !x.more)
+    }
+
+    fn into_nasl(self, opcode: u8) -> Option<IndexMap<String, NaslValue>> {
+        let first = self.fragments.values().next()?.clone();
+        let data: Vec<u8> = self.fragments.into_values().flat_map(|x| x.data).collect();
+        let mut result = IndexMap::new();
+        let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+        add("version", NaslValue::Number(first.version as i64));
+        add("mode", NaslValue::Number(MODE_CONTROL as i64));
+        add("opcode", NaslValue::Number(opcode as i64));
+        add("status", NaslValue::Number(first.status as i64));
+        add("association", NaslValue::Number(first.association as i64));
+        // the error code is the high octet of the status
+        add(
+            "error",
+            NaslValue::Number(match first.error {
+                true => (first.status >> 8) as i64,
+                false => 0,
+            }),
+        );
+        add("packets", NaslValue::Number(self.packets as i64));
+        add("bytes", NaslValue::Number(self.bytes as i64));
+        match opcode {
+            READSTAT if !first.error => {
+                add("associations", associations(&data));
+            }
+            READVAR | READCLOCK if !first.error => {
+                add(
+                    "variables",
+                    NaslValue::Dict(variables(&String::from_utf8_lossy(&data))),
+                );
+            }
+            _ => {}
+        }
+        add("data", NaslValue::Data(data));
+        Some(result)
+    }
+}
+
+/// Parses the association ids and their status of a READSTAT response.
+fn associations(data: &[u8]) -> NaslValue {
+    NaslValue::Array(
+        data.chunks_exact(4)
+            .map(|x| {
+                let mut result = IndexMap::new();
+                result.insert(
+                    "association".to_string(),
+                    NaslValue::Number(u16::from_be_bytes([x[0], x[1]]) as i64),
+                );
+                result.insert(
+                    "status".to_string(),
+                    NaslValue::Number(u16::from_be_bytes([x[2], x[3]]) as i64),
+                );
+                NaslValue::Dict(result)
+            })
+            .collect(),
+    )
+}
+
+/// Parses the comma separated list of variables of a READVAR response.
+///
+/// Quoted values may contain commas, the quotes are removed.
+fn variables(text: &str) -> IndexMap<String, NaslValue> {
+    let mut items = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    items.push(current);
+    items
+        .iter()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let (name, value) = x.split_once('=').unwrap_or((x, ""));
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|x| x.strip_suffix('"'))
+                .unwrap_or(value);
+            (
+                name.trim().to_string(),
+                NaslValue::String(value.to_string()),
+            )
+        })
+        .collect()
+}
+
+/// Returns true when the error means that the server did not answer.
+fn is_unanswered(e: &io::Error) -> bool {
+    is_timeout(e) || e.kind() == io::ErrorKind::ConnectionRefused
+}
+
+/// NASL function to send a client (mode 3) or control (mode 6) query to the NTP server of
+/// the target
+///
+/// Returns an array of the header fields of the answer, for control queries also the
+/// reassembled data and the number and size of the answer datagrams. Returns NULL when the
+/// server did not answer.
+#[nasl_function(named(mode, opcode, association, data, version, port, timeout))]
+#[allow(clippy::too_many_arguments)]
+fn ntp_query(
+    context: &Context,
+    mode: Option<i64>,
+    opcode: Option<i64>,
+    association: Option<i64>,
+    data: Option<&str>,
+    version: Option<i64>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let mode = match mode.unwrap_or(MODE_CLIENT as i64) {
+        x @ 3 | x @ 6 => x as u8,
+        x => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "mode",
+                "3 or 6",
+                &x.to_string(),
+            ))
+        }
+    };
+    // ntpq sends control messages with version 2, which all versions of ntpd answer
+    let version = match version.unwrap_or(if mode == MODE_CLIENT { 4 } else { 2 }) {
+        x @ 1..=4 => x as u8,
+        x => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "version",
+                "1 to 4",
+                &x.to_string(),
+            ))
+        }
+    };
+    let opcode = match opcode.unwrap_or(READVAR as i64) {
+        x @ 0..=31 => x as u8,
+        x => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "opcode",
+                "0 to 31",
+                &x.to_string(),
+            ))
+        }
+    };
+    let association = match association.unwrap_or(0) {
+        x @ 0..=65535 => x as u16,
+        x => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "association",
+                "0 to 65535",
+                &x.to_string(),
+            ))
+        }
+    };
+    let port = port.map(verify_port).transpose()?.unwrap_or(NTP_PORT);
+    let timeout = convert_timeout(timeout).unwrap_or(DEFAULT_TIMEOUT);
+
+    let mut conn =
+        NaslSockets::connect_udp(context, target_ip(context)?, port, timeout, DEFAULT_RETRIES)?;
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0; MAX_MESSAGE];
+    let result = match mode {
+        MODE_CLIENT => {
+            let request = client_request(version);
+            conn.write_all(&request)?;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break None;
+                }
+                let length = match conn.read_with_retries(&mut buf, remaining, DEFAULT_RETRIES) {
+                    Ok(x) => x,
+                    Err(e) if is_unanswered(&e) => break None,
+                    Err(e) => return Err(e.into()),
+                };
+                if let Some(x) = decode_server(&request, &buf[..length]) {
+                    break Some(x);
+                }
+            }
+        }
+        _ => {
+            let sequence = rand::random::<u16>().max(1);
+            let request = control_request(
+                version,
+                opcode,
+                sequence,
+                association,
+                data.unwrap_or_default().as_bytes(),
+            );
+            conn.write_all(&request)?;
+            let mut response = Response::default();
+            while !response.is_complete() {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                // once the server answered, a retransmission would only duplicate the fragments
+                let retries = match response.packets {
+                    0 => DEFAULT_RETRIES,
+                    _ => 0,
+                };
+                let length = match conn.read_with_retries(&mut buf, remaining, retries) {
+                    Ok(x) => x,
+                    Err(e) if is_unanswered(&e) => break,
+                    Err(e) => return Err(e.into()),
+                };
+                if let Some(x) = decode_fragment(opcode, sequence, &buf[..length]) {
+                    response.add(x, length);
+                }
+            }
+            response.into_nasl(opcode)
+        }
+    };
+    Ok(result.map_or(NaslValue::Null, NaslValue::Dict))
+}
+
+pub struct Ntp;
+
+function_set! {
+    Ntp,
+    sync_stateless,
+    (
+        ntp_query,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, thread};
+
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    use super::{
+        control_request, decode_fragment, variables, Response, CONTROL_HEADER_LENGTH, CONTROL_MORE,
+        CONTROL_RESPONSE, MODE_SERVER, UNIX_OFFSET,
+    };
+
+    const SYSTEM: &str = r#"version="ntpd 4.2.8p15@1.3728-o", processor="x86_64", system="Linux/5.10.0", leap=00, stratum=2"#;
+
+    /// Returns the fragments of the control response containing the text.
+    fn fragments(request: &[u8], text: &[u8], size: usize) -> Vec<Vec<u8>> {
+        let chunks: Vec<&[u8]> = text.chunks(size).collect();
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut fragment = request[..CONTROL_HEADER_LENGTH].to_vec();
+                fragment[1] |= CONTROL_RESPONSE;
+                if i + 1 < chunks.len() {
+                    fragment[1] |= CONTROL_MORE;
+                }
+                fragment[8..10].copy_from_slice(&((i * size) as u16).to_be_bytes());
+                fragment[10..12].copy_from_slice(&(chunk.len() as u16).to_be_bytes());
+                fragment.extend_from_slice(chunk);
+                fragment
+            })
+            .collect()
+    }
+
+    fn server() -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buf = [0; 1500];
+            while let Ok((length, peer)) = socket.recv_from(&mut buf) {
+                let request = &buf[..length];
+                match request[0] & 0x07 {
+                    3 => {
+                        let mut answer = vec![0; 48];
+                        answer[0] = (4 << 3) | MODE_SERVER;
+                        answer[1] = 2;
+                        answer[3] = 0xe9;
+                        answer[12..16].copy_from_slice(&[192, 0, 2, 1]);
+                        answer[24..32].copy_from_slice(&request[40..48]);
+                        let time = (1_700_000_000 + UNIX_OFFSET) as u32;
+                        answer[40..44].copy_from_slice(&time.to_be_bytes());
+                        socket.send_to(&answer, peer).unwrap();
+                    }
+                    6 => {
+                        // the fragments arrive in reverse order
+                        for fragment in fragments(request, SYSTEM.as_bytes(), 40).iter().rev() {
+                            socket.send_to(fragment, peer).unwrap();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn query() {
+        let port = server();
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.run(format!("r = ntp_query(port: {port});"));
+        t.ok(r#"r["stratum"];"#, 2);
+        t.ok(r#"r["precision"];"#, -23);
+        t.ok(r#"r["reference_id"];"#, "192.0.2.1");
+        t.ok(r#"r["transmit_time"];"#, 1_700_000_000);
+        t.run(format!("c = ntp_query(mode: 6, port: {port});"));
+        t.ok(r#"c["variables"]["version"];"#, "ntpd 4.2.8p15@1.3728-o");
+        t.ok(r#"c["variables"]["leap"];"#, "00");
+        t.ok(r#"c["packets"];"#, 3);
+        t.ok(
+            r#"c["bytes"];"#,
+            (SYSTEM.len() + 3 * CONTROL_HEADER_LENGTH) as i64,
+        );
+        check_err_matches!(
+            t,
+            format!("ntp_query(port: {port}, version: 5);"),
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+
+    #[test]
+    fn parse_variables() {
+        let parsed = variables("a=1, b=\"x, y\",\r\nc=, d");
+        assert_eq!(parsed["a"], NaslValue::String("1".into()));
+        assert_eq!(parsed["b"], NaslValue::String("x, y".into()));
+        assert_eq!(parsed["c"], NaslValue::String("".into()));
+        assert_eq!(parsed["d"], NaslValue::String("".into()));
+    }
+
+    #[test]
+    fn reassemble() {
+        let request = control_request(2, 2, 7, 0, b"");
+        assert_eq!(request.len(), 12);
+        let mut response = Response::default();
+        let fragments = fragments(&request, b"0123456789", 4);
+        for i in 0..fragments.len() {
+            assert!(!response.is_complete());
+            // the first fragment is received last
+            let fragment = &fragments[(i + 1) % fragments.len()];
+            response.add(decode_fragment(2, 7, fragment).unwrap(), fragment.len());
+        }
+        assert!(response.is_complete());
+        assert_eq!(decode_fragment(2, 8, &fragments[0]), None);
+        let result = response.into_nasl(2).unwrap();
+        assert_eq!(result["data"], NaslValue::Data(b"0123456789".to_vec()));
+    }
+}
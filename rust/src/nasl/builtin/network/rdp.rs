@@ -0,0 +1,941 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Probes of the connection sequence of the Remote Desktop Protocol (MS-RDPBCGR 1.3.1.1).
+//!
+//! The security protocols are negotiated with the X.224 connection request. The MCS connection
+//! is only established with standard RDP security, as the enhanced security protocols wrap the
+//! MCS PDUs into TLS. It ends after the channels are joined, before the security exchange, so
+//! no credentials are needed.
+
+use std::{
+    io::{self, Write},
+    net::IpAddr,
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+
+use super::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    tcp::TcpConnection,
+};
+use crate::function_set;
+use crate::nasl::builtin::asn1::der::{self, Class};
+use crate::nasl::prelude::*;
+
+const RDP_PORT: u16 = 3389;
+
+const TPKT_VERSION: u8 = 3;
+const TPKT_HEADER_LENGTH: usize = 4;
+/// Length of the fixed part of a X.224 connection request or confirm
+const X224_CONNECTION_LENGTH: usize = 7;
+const X224_CONNECTION_REQUEST: u8 = 0xe0;
+const X224_CONNECTION_CONFIRM: u8 = 0xd0;
+/// Header of a X.224 data TPDU, the last one of its sequence
+const X224_DATA: [u8; 3] = [0x02, 0xf0, 0x80];
+
+const TYPE_RDP_NEG_REQ: u8 = 0x01;
+const TYPE_RDP_NEG_RSP: u8 = 0x02;
+const TYPE_RDP_NEG_FAILURE: u8 = 0x03;
+const NEGOTIATION_LENGTH: usize = 8;
+
+const PROTOCOL_RDP: u32 = 0x00;
+const PROTOCOL_SSL: u32 = 0x01;
+const PROTOCOL_HYBRID: u32 = 0x02;
+const PROTOCOL_HYBRID_EX: u32 = 0x08;
+
+/// Security protocols by their names, standard RDP security is the absence of the others
+const PROTOCOLS: [(u32, &str); 6] = [
+    (PROTOCOL_RDP, "rdp"),
+    (PROTOCOL_SSL, "ssl"),
+    (PROTOCOL_HYBRID, "hybrid"),
+    (0x04, "rdstls"),
+    (PROTOCOL_HYBRID_EX, "hybrid_ex"),
+    (0x10, "rdsaad"),
+];
+
+/// Failure codes of a RDP_NEG_FAILURE by their names
+const FAILURES: [(u32, &str); 6] = [
+    (1, "SSL_REQUIRED_BY_SERVER"),
+    (2, "SSL_NOT_ALLOWED_BY_SERVER"),
+    (3, "SSL_CERT_NOT_ON_SERVER"),
+    (4, "INCONSISTENT_FLAGS"),
+    (5, "HYBRID_REQUIRED_BY_SERVER"),
+    (6, "SSL_WITH_USER_AUTH_REQUIRED_BY_SERVER"),
+];
+
+/// Longest cookie keeping the connection request within the length indicator
+const MAX_COOKIE: usize = 128;
+/// Static virtual channels a client may request
+const MAX_CHANNELS: usize = 31;
+/// Longest name of a static virtual channel, without the terminating null
+const MAX_CHANNEL_NAME: usize = 7;
+/// Static virtual channels requested when the script names none, like the Microsoft client
+const DEFAULT_CHANNELS: [&str; 4] = ["rdpdr", "rdpsnd", "cliprdr", "drdynvc"];
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const SEQUENCE: u8 = 0x30;
+const CONNECT_INITIAL: u8 = 101;
+const CONNECT_RESPONSE: u64 = 102;
+
+/// Key of the T.124 object identifier preceding the GCC conference create PDUs
+const T124_OBJECT: [u8; 7] = [0x00, 0x05, 0x00, 0x14, 0x7c, 0x00, 0x01];
+/// Start of the PER encoded conference create request up to its user data
+const CONFERENCE_CREATE_REQUEST: [u8; 8] = [0x00, 0x08, 0x00, 0x10, 0x00, 0x01, 0xc0, 0x00];
+const H221_CLIENT_KEY: &[u8] = b"Duca";
+const H221_SERVER_KEY: &[u8] = b"McDn";
+
+const CS_CORE: u16 = 0xc001;
+const CS_SECURITY: u16 = 0xc002;
+const CS_NET: u16 = 0xc003;
+const SC_CORE: u16 = 0x0c01;
+const SC_SECURITY: u16 = 0x0c02;
+const SC_NET: u16 = 0x0c03;
+
+/// RDP 5.0 to 8.1
+const CLIENT_VERSION: u32 = 0x0008_0004;
+/// 40, 56 and 128 bit keys and FIPS
+const ENCRYPTION_METHODS: u32 = 0x01 | 0x02 | 0x08 | 0x10;
+const CHANNEL_OPTION_INITIALIZED: u32 = 0x8000_0000;
+
+/// Choices of the DomainMCSPDU, shifted into the first octet of their PER encoding
+const ERECT_DOMAIN_REQUEST: u8 = 1 << 2;
+const ATTACH_USER_REQUEST: u8 = 10 << 2;
+const ATTACH_USER_CONFIRM: u8 = 11 << 2;
+const CHANNEL_JOIN_REQUEST: u8 = 14 << 2;
+const CHANNEL_JOIN_CONFIRM: u8 = 15 << 2;
+/// User ids are encoded relative to the first channel id
+const MCS_BASE_CHANNEL_ID: u16 = 1001;
+
+fn u16_le(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data[1]])
+}
+
+fn u32_le(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn u16_be(data: &[u8]) -> u16 {
+    u16::from_be_bytes([data[0], data[1]])
+}
+
+fn protocol_name(protocol: u32) -> &'static str {
+    PROTOCOLS
+        .iter()
+        .find(|(x, _)| *x == protocol)
+        .map_or("unknown", |(_, name)| name)
+}
+
+fn failure_name(failure: u32) -> &'static str {
+    FAILURES
+        .iter()
+        .find(|(x, _)| *x == failure)
+        .map_or("unknown", |(_, name)| name)
+}
+
+/// Result of the negotiation of the security protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Negotiation {
+    /// The server does not negotiate, e.g. before Windows Server 2003, and uses standard RDP
+    /// security
+    Legacy,
+    Selected {
+        protocol: u32,
+        flags: u8,
+    },
+    Failure(u32),
+}
+
+fn tpkt(tpdu: &[u8]) -> Vec<u8> {
+    let mut packet = vec![TPKT_VERSION, 0];
+    packet.extend(((tpdu.len() + TPKT_HEADER_LENGTH) as u16).to_be_bytes());
+    packet.extend(tpdu);
+    packet
+}
+
+fn data_tpdu(pdu: &[u8]) -> Vec<u8> {
+    tpkt(&[&X224_DATA[..], pdu].concat())
+}
+
+/// Returns the X.224 connection request with the routing cookie and the requested protocols.
+fn connection_request(protocols: u32, cookie: Option<&str>) -> Vec<u8> {
+    let mut tpdu = vec![0, X224_CONNECTION_REQUEST, 0, 0, 0, 0, 0];
+    if let Some(cookie) = cookie {
+        tpdu.extend(format!("Cookie: mstshash={cookie}\r\n").as_bytes());
+    }
+    tpdu.extend([TYPE_RDP_NEG_REQ, 0, NEGOTIATION_LENGTH as u8, 0]);
+    tpdu.extend(protocols.to_le_bytes());
+    // the length indicator does not count itself
+    tpdu[0] = (tpdu.len() - 1) as u8;
+    tpkt(&tpdu)
+}
+
+/// Parses the X.224 connection confirm.
+fn connection_confirm(tpdu: &[u8]) -> io::Result<Negotiation> {
+    if tpdu.len() < X224_CONNECTION_LENGTH || tpdu[1] & 0xf0 != X224_CONNECTION_CONFIRM {
+        return Err(invalid_data("no X.224 connection confirm received"));
+    }
+    let Some(negotiation) =
+        tpdu.get(X224_CONNECTION_LENGTH..X224_CONNECTION_LENGTH + NEGOTIATION_LENGTH)
+    else {
+        return Ok(Negotiation::Legacy);
+    };
+    let value = u32_le(&negotiation[4..]);
+    match negotiation[0] {
+        TYPE_RDP_NEG_RSP => Ok(Negotiation::Selected {
+            protocol: value,
+            flags: negotiation[1],
+        }),
+        TYPE_RDP_NEG_FAILURE => Ok(Negotiation::Failure(value)),
+        _ => Err(invalid_data("unexpected negotiation data")),
+    }
+}
+
+/// Returns the length as PER length determinant, always in its two octet form.
+fn per_length(length: usize) -> [u8; 2] {
+    (length as u16 | 0x8000).to_be_bytes()
+}
+
+/// Decodes a PER length determinant and returns the length and the number of its octets.
+fn decode_per_length(data: &[u8]) -> io::Result<(usize, usize)> {
+    match data {
+        [first, ..] if first & 0x80 == 0 => Ok((*first as usize, 1)),
+        [first, second, ..] => Ok(((((first & 0x3f) as usize) << 8) | *second as usize, 2)),
+        _ => Err(invalid_data("truncated PER length")),
+    }
+}
+
+fn data_block(kind: u16, content: &[u8]) -> Vec<u8> {
+    let mut block = kind.to_le_bytes().to_vec();
+    block.extend(((content.len() + 4) as u16).to_le_bytes());
+    block.extend(content);
+    block
+}
+
+/// Returns the client core, security and network data.
+fn client_data(protocol: u32, channels: &[String]) -> Vec<u8> {
+    let mut core = CLIENT_VERSION.to_le_bytes().to_vec();
+    // desktop of 1024x768 with 8 bits per pixel and the secure access sequence
+    core.extend(1024u16.to_le_bytes());
+    core.extend(768u16.to_le_bytes());
+    core.extend(0xca01u16.to_le_bytes());
+    core.extend(0xaa03u16.to_le_bytes());
+    // US keyboard layout and client build
+    core.extend(0x0409u32.to_le_bytes());
+    core.extend(2600u32.to_le_bytes());
+    let mut name = [0; 32];
+    for (i, x) in "OPENVAS".encode_utf16().enumerate() {
+        name[i * 2..i * 2 + 2].copy_from_slice(&x.to_le_bytes());
+    }
+    core.extend(name);
+    // IBM enhanced keyboard with 12 function keys, no IME file
+    core.extend(4u32.to_le_bytes());
+    core.extend(0u32.to_le_bytes());
+    core.extend(12u32.to_le_bytes());
+    core.extend([0; 64]);
+    // post beta 2 color depth, product id and serial number
+    core.extend(0xca01u16.to_le_bytes());
+    core.extend(1u16.to_le_bytes());
+    core.extend(0u32.to_le_bytes());
+    // 24 bits per pixel, supporting 15 to 24 bits, and error info PDUs
+    core.extend(24u16.to_le_bytes());
+    core.extend(0x0007u16.to_le_bytes());
+    core.extend(0x0001u16.to_le_bytes());
+    // digital product id, connection type and padding
+    core.extend([0; 64]);
+    core.extend([0, 0]);
+    core.extend(protocol.to_le_bytes());
+    let mut security = ENCRYPTION_METHODS.to_le_bytes().to_vec();
+    security.extend(0u32.to_le_bytes());
+    let mut net = (channels.len() as u32).to_le_bytes().to_vec();
+    for channel in channels {
+        let mut name = [0; MAX_CHANNEL_NAME + 1];
+        name[..channel.len()].copy_from_slice(channel.as_bytes());
+        net.extend(name);
+        net.extend(CHANNEL_OPTION_INITIALIZED.to_le_bytes());
+    }
+    [
+        data_block(CS_CORE, &core),
+        data_block(CS_SECURITY, &security),
+        data_block(CS_NET, &net),
+    ]
+    .concat()
+}
+
+/// Returns the MCS domain parameters as BER encoded sequence.
+fn domain_parameters(values: [i64; 8]) -> Vec<u8> {
+    let content: Vec<u8> = values
+        .iter()
+        .flat_map(|x| der::tlv(INTEGER, &der::encode_integer(*x)))
+        .collect();
+    der::tlv(SEQUENCE, &content)
+}
+
+/// Returns the MCS connect initial PDU containing the GCC conference create request.
+fn connect_initial(protocol: u32, channels: &[String]) -> Vec<u8> {
+    let user_data = client_data(protocol, channels);
+    let mut request = CONFERENCE_CREATE_REQUEST.to_vec();
+    request.extend(H221_CLIENT_KEY);
+    request.extend(per_length(user_data.len()));
+    request.extend(user_data);
+    let mut conference = T124_OBJECT.to_vec();
+    conference.extend(per_length(request.len()));
+    conference.extend(request);
+
+    // calling and called domain selector and upward flag
+    let mut content = der::tlv(OCTET_STRING, &[1]);
+    content.extend(der::tlv(OCTET_STRING, &[1]));
+    content.extend(der::tlv(BOOLEAN, &[0xff]));
+    // target, minimum and maximum parameters
+    content.extend(domain_parameters([34, 2, 0, 1, 0, 1, 0xffff, 2]));
+    content.extend(domain_parameters([1, 1, 1, 1, 0, 1, 0x420, 2]));
+    content.extend(domain_parameters([
+        0xffff, 0xfc17, 0xffff, 1, 0, 1, 0xffff, 2,
+    ]));
+    content.extend(der::tlv(OCTET_STRING, &conference));
+    // the application tag 101 needs the high tag number form
+    let mut pdu = vec![0x7f];
+    pdu.extend(der::tlv(CONNECT_INITIAL, &content));
+    data_tpdu(&pdu)
+}
+
+/// Settings of the server from its data blocks
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ServerData {
+    version: u32,
+    encryption_method: u32,
+    encryption_level: u32,
+    io_channel: u16,
+    /// Channel ids of the requested static virtual channels in their order
+    channels: Vec<u16>,
+}
+
+fn server_data(mut data: &[u8]) -> io::Result<ServerData> {
+    let mut result = ServerData::default();
+    while data.len() >= 4 {
+        let length = u16_le(&data[2..]) as usize;
+        if !(4..=data.len()).contains(&length) {
+            return Err(invalid_data("invalid length of a server data block"));
+        }
+        let (block, rest) = data.split_at(length);
+        match u16_le(block) {
+            SC_CORE if length >= 8 => result.version = u32_le(&block[4..]),
+            SC_SECURITY if length >= 12 => {
+                result.encryption_method = u32_le(&block[4..]);
+                result.encryption_level = u32_le(&block[8..]);
+            }
+            SC_NET if length >= 8 => {
+                result.io_channel = u16_le(&block[4..]);
+                let count = u16_le(&block[6..]) as usize;
+                result.channels = block[8..].chunks_exact(2).take(count).map(u16_le).collect();
+            }
+            _ => {}
+        }
+        data = rest;
+    }
+    Ok(result)
+}
+
+/// Parses the MCS connect response and returns the data of the server from the GCC conference
+/// create response.
+fn connect_response(pdu: &[u8]) -> io::Result<ServerData> {
+    let elements = der::parse(pdu).map_err(|e| invalid_data(e.reason))?;
+    let response = elements
+        .first()
+        .filter(|x| x.class == Class::Application && x.tag == CONNECT_RESPONSE)
+        .ok_or_else(|| invalid_data("no MCS connect response received"))?;
+    let [result, _, _, user_data] = &response.children[..] else {
+        return Err(invalid_data("invalid MCS connect response"));
+    };
+    if result.content != [0] {
+        return Err(invalid_data("the MCS connection was refused"));
+    }
+    let conference = user_data.content;
+    let start = conference
+        .windows(H221_SERVER_KEY.len())
+        .position(|x| x == H221_SERVER_KEY)
+        .ok_or_else(|| invalid_data("no GCC conference create response"))?
+        + H221_SERVER_KEY.len();
+    let (length, octets) = decode_per_length(&conference[start..])?;
+    let data = conference
+        .get(start + octets..start + octets + length)
+        .ok_or_else(|| invalid_data("truncated server data"))?;
+    server_data(data)
+}
+
+fn erect_domain_request() -> Vec<u8> {
+    // sub height and sub interval of 0
+    data_tpdu(&[ERECT_DOMAIN_REQUEST, 1, 0, 1, 0])
+}
+
+fn attach_user_request() -> Vec<u8> {
+    data_tpdu(&[ATTACH_USER_REQUEST])
+}
+
+/// Parses the attach user confirm and returns the channel id of the user.
+fn attach_user_confirm(pdu: &[u8]) -> io::Result<u16> {
+    match pdu {
+        [choice, 0, initiator @ ..]
+            if choice & 0xfc == ATTACH_USER_CONFIRM && choice & 0x02 != 0 =>
+        {
+            let initiator = initiator
+                .get(..2)
+                .ok_or_else(|| invalid_data("truncated PDU"))?;
+            Ok(u16_be(initiator) + MCS_BASE_CHANNEL_ID)
+        }
+        [choice, ..] if choice & 0xfc == ATTACH_USER_CONFIRM => {
+            Err(invalid_data("the user was not attached"))
+        }
+        _ => Err(invalid_data("no attach user confirm received")),
+    }
+}
+
+fn channel_join_request(user: u16, channel: u16) -> Vec<u8> {
+    let mut pdu = vec![CHANNEL_JOIN_REQUEST];
+    pdu.extend((user - MCS_BASE_CHANNEL_ID).to_be_bytes());
+    pdu.extend(channel.to_be_bytes());
+    data_tpdu(&pdu)
+}
+
+/// Parses the channel join confirm and returns the requested channel and whether it was
+/// joined.
+fn channel_join_confirm(pdu: &[u8]) -> io::Result<(u16, bool)> {
+    if pdu.len() < 6 || pdu[0] & 0xfc != CHANNEL_JOIN_CONFIRM {
+        return Err(invalid_data("no channel join confirm received"));
+    }
+    Ok((u16_be(&pdu[4..]), pdu[1] == 0))
+}
+
+/// Result of the MCS connection
+#[derive(Debug)]
+struct Mcs {
+    server: ServerData,
+    user_channel: u16,
+    /// Whether the requested static virtual channels were joined, in their order
+    joined: Vec<bool>,
+}
+
+struct Connection {
+    conn: TcpConnection,
+    timeout: Duration,
+}
+
+impl Connection {
+    fn open(context: &Context, addrs: &[IpAddr], port: u16, timeout: Duration) -> io::Result<Self> {
+        let conn = NaslSockets::connect_tcp(context, addrs, port, None, timeout, None)?;
+        Ok(Self { conn, timeout })
+    }
+
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.conn.write_all(packet)?;
+        self.conn.flush()
+    }
+
+    /// Receives a TPKT and returns the X.224 TPDU it contains.
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let mut header = [0; TPKT_HEADER_LENGTH];
+        self.conn
+            .read_exact_with_timeout(&mut header, self.timeout)?;
+        let length = u16_be(&header[2..]) as usize;
+        if header[0] != TPKT_VERSION || length < TPKT_HEADER_LENGTH {
+            return Err(invalid_data("no TPKT received"));
+        }
+        let mut tpdu = vec![0; length - TPKT_HEADER_LENGTH];
+        self.conn.read_exact_with_timeout(&mut tpdu, self.timeout)?;
+        Ok(tpdu)
+    }
+
+    /// Receives a X.224 data TPDU and returns the MCS PDU it contains.
+    fn receive_data(&mut self) -> io::Result<Vec<u8>> {
+        let mut tpdu = self.receive()?;
+        if !tpdu.starts_with(&X224_DATA) {
+            return Err(invalid_data("no X.224 data received"));
+        }
+        Ok(tpdu.split_off(X224_DATA.len()))
+    }
+
+    fn negotiate(&mut self, protocols: u32, cookie: Option<&str>) -> io::Result<Negotiation> {
+        self.send(&connection_request(protocols, cookie))?;
+        connection_confirm(&self.receive()?)
+    }
+
+    /// Negotiates standard RDP security, refused by servers requiring network level
+    /// authentication.
+    fn standard_security(&mut self) -> io::Result<()> {
+        match self.negotiate(PROTOCOL_RDP, None)? {
+            Negotiation::Legacy
+            | Negotiation::Selected {
+                protocol: PROTOCOL_RDP,
+                ..
+            } => {}
+            Negotiation::Selected { .. } => {
+                return Err(invalid_data(
+                    "the server selected another security protocol",
+                ))
+            }
+            Negotiation::Failure(x) => {
+                return Err(invalid_data(&format!(
+                    "standard RDP security was refused with {}",
+                    failure_name(x)
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Connects the MCS domain after negotiating standard RDP security and joins the channels.
+    fn mcs_connect(&mut self, channels: &[String]) -> io::Result<Mcs> {
+        self.send(&connect_initial(PROTOCOL_RDP, channels))?;
+        let server = connect_response(&self.receive_data()?)?;
+        self.send(&erect_domain_request())?;
+        self.send(&attach_user_request())?;
+        let user_channel = attach_user_confirm(&self.receive_data()?)?;
+        let mut joined = vec![];
+        let ids = [user_channel, server.io_channel]
+            .into_iter()
+            .chain(server.channels.iter().copied());
+        for (i, id) in ids.enumerate() {
+            self.send(&channel_join_request(user_channel, id))?;
+            let (channel, success) = channel_join_confirm(&self.receive_data()?)?;
+            if channel != id {
+                return Err(invalid_data("the confirmed channel was not requested"));
+            }
+            match i {
+                0 | 1 if !success => {
+                    return Err(invalid_data("the user or I/O channel was not joined"))
+                }
+                0 | 1 => {}
+                _ => joined.push(success),
+            }
+        }
+        Ok(Mcs {
+            server,
+            user_channel,
+            joined,
+        })
+    }
+}
+
+/// Logs the error of a probe, the server is treated as not answering.
+fn unanswered(e: io::Error) -> NaslValue {
+    tracing::debug!(%e, "RDP probe failed");
+    NaslValue::Null
+}
+
+/// NASL function to send a X.224 connection request with the requested security protocols to
+/// the RDP service of the target
+///
+/// Returns an array with the selected protocol or the failure code of the server, NULL when
+/// the service did not confirm the connection.
+#[nasl_function(named(protocols, cookie, port, timeout))]
+fn rdp_negotiate(
+    context: &Context,
+    protocols: Option<i64>,
+    cookie: Option<&str>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let default = PROTOCOL_SSL | PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX;
+    let protocols = match protocols.unwrap_or(default as i64) {
+        x @ 0..=0xffff_ffff => x as u32,
+        x => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "protocols",
+                "0 to 0xffffffff",
+                &x.to_string(),
+            ))
+        }
+    };
+    if let Some(cookie) = cookie.filter(|x| x.len() > MAX_COOKIE || x.contains(['\r', '\n'])) {
+        return Err(FunctionErrorKind::wrong_argument(
+            "cookie",
+            "a single line of at most 128 characters",
+            cookie,
+        ));
+    }
+    let (addrs, port, timeout) = target_service(context, port, RDP_PORT, timeout)?;
+    let negotiation = match Connection::open(context, &addrs, port, timeout)
+        .and_then(|mut x| x.negotiate(protocols, cookie))
+    {
+        Ok(x) => x,
+        Err(e) => return Ok(unanswered(e)),
+    };
+    let mut result = IndexMap::new();
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    add(
+        "negotiated",
+        NaslValue::Boolean(negotiation != Negotiation::Legacy),
+    );
+    match negotiation {
+        Negotiation::Legacy => {
+            add("protocol", NaslValue::Number(PROTOCOL_RDP as i64));
+            add("protocol_name", NaslValue::String("rdp".to_string()));
+            add("flags", NaslValue::Number(0));
+        }
+        Negotiation::Selected { protocol, flags } => {
+            add("protocol", NaslValue::Number(protocol as i64));
+            add(
+                "protocol_name",
+                NaslValue::String(protocol_name(protocol).to_string()),
+            );
+            add("flags", NaslValue::Number(flags as i64));
+        }
+        Negotiation::Failure(failure) => {
+            add("failure", NaslValue::Number(failure as i64));
+            add(
+                "failure_name",
+                NaslValue::String(failure_name(failure).to_string()),
+            );
+        }
+    }
+    Ok(NaslValue::Dict(result))
+}
+
+/// NASL function to determine the security protocols the RDP service of the target accepts
+///
+/// Each protocol is requested alone on its own connection. Returns an array of the protocols
+/// by their names and whether network level authentication is required, NULL when the
+/// service did not confirm the first connection.
+#[nasl_function(named(port, timeout))]
+fn rdp_security_protocols(
+    context: &Context,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let (addrs, port, timeout) = target_service(context, port, RDP_PORT, timeout)?;
+    let mut result = IndexMap::new();
+    for (i, (protocol, name)) in PROTOCOLS.iter().enumerate() {
+        let negotiation = Connection::open(context, &addrs, port, timeout)
+            .and_then(|mut x| x.negotiate(*protocol, None));
+        let accepted = match negotiation {
+            Ok(Negotiation::Legacy) => *protocol == PROTOCOL_RDP,
+            Ok(Negotiation::Selected { protocol: x, .. }) => x == *protocol,
+            Ok(Negotiation::Failure(_)) => false,
+            Err(e) if i == 0 => return Ok(unanswered(e)),
+            // some servers close the connection instead of sending a failure
+            Err(_) => false,
+        };
+        result.insert(name.to_string(), NaslValue::Boolean(accepted));
+    }
+    let accepted = |name: &str| result[name] == NaslValue::Boolean(true);
+    let nla_required =
+        !accepted("rdp") && !accepted("ssl") && (accepted("hybrid") || accepted("hybrid_ex"));
+    result.insert("nla_required".to_string(), NaslValue::Boolean(nla_required));
+    Ok(NaslValue::Dict(result))
+}
+
+/// NASL function to connect the MCS domain of the RDP service of the target with standard RDP
+/// security and to join the static virtual channels
+///
+/// Without channels the channels of the Microsoft client are requested. Returns an array with
+/// the version and encryption settings of the server and the channels, NULL when the server
+/// refuses standard RDP security or the connection fails.
+#[nasl_function(named(channels, port, timeout))]
+fn rdp_mcs_connect(
+    context: &Context,
+    channels: Option<&NaslValue>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let (addrs, port, timeout) = target_service(context, port, RDP_PORT, timeout)?;
+    let mut conn = match Connection::open(context, &addrs, port, timeout)
+        .and_then(|mut x| x.standard_security().map(|_| x))
+    {
+        Ok(x) => x,
+        Err(e) => return Ok(unanswered(e)),
+    };
+    // servers requiring network level authentication return NULL regardless of the channels
+    let channels = match channels {
+        Some(x) => Vec::<String>::from_nasl_value(x)?,
+        None => DEFAULT_CHANNELS.iter().map(|x| x.to_string()).collect(),
+    };
+    if channels.len() > MAX_CHANNELS {
+        return Err(FunctionErrorKind::wrong_argument(
+            "channels",
+            "at most 31 channels",
+            &channels.len().to_string(),
+        ));
+    }
+    if let Some(name) = channels
+        .iter()
+        .find(|x| x.is_empty() || x.len() > MAX_CHANNEL_NAME || !x.is_ascii())
+    {
+        return Err(FunctionErrorKind::wrong_argument(
+            "channels",
+            "names of 1 to 7 ASCII characters",
+            name,
+        ));
+    }
+    let mcs = match conn.mcs_connect(&channels) {
+        Ok(x) => x,
+        Err(e) => return Ok(unanswered(e)),
+    };
+    let channels = channels
+        .iter()
+        .zip(mcs.server.channels.iter().zip(mcs.joined))
+        .map(|(name, (id, joined))| {
+            let mut channel = IndexMap::new();
+            channel.insert("name".to_string(), NaslValue::String(name.clone()));
+            channel.insert("id".to_string(), NaslValue::Number(*id as i64));
+            channel.insert("joined".to_string(), NaslValue::Boolean(joined));
+            NaslValue::Dict(channel)
+        })
+        .collect();
+    let mut result = IndexMap::new();
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    add("version", NaslValue::Number(mcs.server.version as i64));
+    add(
+        "encryption_method",
+        NaslValue::Number(mcs.server.encryption_method as i64),
+    );
+    add(
+        "encryption_level",
+        NaslValue::Number(mcs.server.encryption_level as i64),
+    );
+    add("user_channel", NaslValue::Number(mcs.user_channel as i64));
+    add(
+        "io_channel",
+        NaslValue::Number(mcs.server.io_channel as i64),
+    );
+    add("channels", NaslValue::Array(channels));
+    Ok(NaslValue::Dict(result))
+}
+
+pub struct Rdp;
+
+function_set! {
+    Rdp,
+    sync_stateless,
+    (
+        rdp_negotiate,
+        rdp_security_protocols,
+        rdp_mcs_connect,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use crate::nasl::builtin::network::network_utils::tests::tcp_server;
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    use super::*;
+
+    const IO_CHANNEL: u16 = 1003;
+    const USER_CHANNEL: u16 = 1007;
+
+    fn read_tpdu(stream: &mut TcpStream) -> Option<Vec<u8>> {
+        let mut header = [0; TPKT_HEADER_LENGTH];
+        stream.read_exact(&mut header).ok()?;
+        let mut tpdu = vec![0; u16_be(&header[2..]) as usize - TPKT_HEADER_LENGTH];
+        stream.read_exact(&mut tpdu).ok()?;
+        Some(tpdu)
+    }
+
+    /// Returns the negotiation data answering the requested protocols, with network level
+    /// authentication only HYBRID and HYBRID_EX are accepted.
+    fn negotiation(requested: u32, nla: bool) -> Vec<u8> {
+        let selected = [PROTOCOL_HYBRID_EX, PROTOCOL_HYBRID, PROTOCOL_SSL]
+            .into_iter()
+            .find(|x| requested & x != 0);
+        let (kind, value) = match selected {
+            Some(x) if !nla || x != PROTOCOL_SSL => (TYPE_RDP_NEG_RSP, x),
+            _ if nla => (TYPE_RDP_NEG_FAILURE, 5),
+            _ => (TYPE_RDP_NEG_RSP, PROTOCOL_RDP),
+        };
+        let mut data = vec![kind, 0x1f, NEGOTIATION_LENGTH as u8, 0];
+        data.extend(value.to_le_bytes());
+        data
+    }
+
+    /// Returns the names of the static virtual channels requested by the connect initial PDU.
+    fn requested_channels(pdu: &[u8]) -> Vec<String> {
+        let elements = der::parse(pdu).unwrap();
+        assert_eq!(elements[0].tag, CONNECT_INITIAL as u64);
+        let conference = elements[0].children[6].content;
+        let start = conference
+            .windows(4)
+            .position(|x| x == H221_CLIENT_KEY)
+            .unwrap()
+            + 6;
+        let mut data = &conference[start..];
+        let mut channels = vec![];
+        while !data.is_empty() {
+            let (block, rest) = data.split_at(u16_le(&data[2..]) as usize);
+            if u16_le(block) == CS_NET {
+                for definition in block[8..].chunks_exact(12) {
+                    let name = definition[..8].split(|x| *x == 0).next().unwrap();
+                    channels.push(String::from_utf8(name.to_vec()).unwrap());
+                }
+            }
+            data = rest;
+        }
+        channels
+    }
+
+    fn connect_response_pdu(channels: usize) -> Vec<u8> {
+        let mut blocks = data_block(SC_CORE, &0x0008_0004u32.to_le_bytes());
+        blocks.extend(data_block(
+            SC_SECURITY,
+            &[2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ));
+        let mut net = IO_CHANNEL.to_le_bytes().to_vec();
+        net.extend((channels as u16).to_le_bytes());
+        for i in 0..channels {
+            net.extend((IO_CHANNEL + 1 + i as u16).to_le_bytes());
+        }
+        blocks.extend(data_block(SC_NET, &net));
+        let mut conference = T124_OBJECT.to_vec();
+        let mut response = vec![0x14, 0x76, 0x0a, 0x01, 0x01, 0x00, 0x01, 0xc0, 0x00];
+        response.extend(H221_SERVER_KEY);
+        response.extend(per_length(blocks.len()));
+        response.extend(blocks);
+        conference.push(response.len() as u8);
+        conference.extend(response);
+        let mut content = der::tlv(0x0a, &[0]);
+        content.extend(der::tlv(INTEGER, &[0]));
+        content.extend(domain_parameters([34, 3, 0, 1, 0, 1, 0xfff8, 2]));
+        content.extend(der::tlv(OCTET_STRING, &conference));
+        let mut pdu = vec![0x7f];
+        pdu.extend(der::tlv(CONNECT_RESPONSE as u8, &content));
+        data_tpdu(&pdu)
+    }
+
+    /// Answers like a RDP service, with network level authentication standard RDP security is
+    /// refused. Joining the channel `rdpsnd` fails.
+    fn server(nla: bool) -> u16 {
+        tcp_server(move |mut stream| {
+            let mut channels = vec![];
+            while let Some(tpdu) = read_tpdu(&mut stream) {
+                let answer = match tpdu[1] {
+                    X224_CONNECTION_REQUEST => {
+                        let requested = u32_le(&tpdu[tpdu.len() - 4..]);
+                        let mut confirm = vec![14, X224_CONNECTION_CONFIRM, 0, 0, 0, 0, 0];
+                        confirm.extend(negotiation(requested, nla));
+                        tpkt(&confirm)
+                    }
+                    _ => {
+                        let pdu = &tpdu[X224_DATA.len()..];
+                        match pdu[0] {
+                            0x7f => {
+                                channels = requested_channels(pdu);
+                                connect_response_pdu(channels.len())
+                            }
+                            ERECT_DOMAIN_REQUEST => continue,
+                            ATTACH_USER_REQUEST => {
+                                let mut confirm = vec![ATTACH_USER_CONFIRM | 0x02, 0];
+                                confirm.extend((USER_CHANNEL - MCS_BASE_CHANNEL_ID).to_be_bytes());
+                                data_tpdu(&confirm)
+                            }
+                            CHANNEL_JOIN_REQUEST => {
+                                assert_eq!(u16_be(&pdu[1..]), USER_CHANNEL - 1001);
+                                let channel = u16_be(&pdu[3..]);
+                                let refused = channel > IO_CHANNEL
+                                    && channels
+                                        .get((channel - IO_CHANNEL - 1) as usize)
+                                        .is_some_and(|x| x == "rdpsnd");
+                                let mut confirm =
+                                    vec![CHANNEL_JOIN_CONFIRM | 0x02, refused as u8 * 14];
+                                confirm.extend(&pdu[1..5]);
+                                confirm.extend(channel.to_be_bytes());
+                                data_tpdu(&confirm)
+                            }
+                            x => panic!("unexpected MCS PDU {x}"),
+                        }
+                    }
+                };
+                stream.write_all(&answer).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn request() {
+        // the example of MS-RDPBCGR 4.1.1
+        let expected = "0300002c27e00000000000436f6f6b69653a206d737473686173683d656c746f6e730d0a0100080000000000";
+        assert_eq!(
+            hex::encode(connection_request(PROTOCOL_RDP, Some("eltons"))),
+            expected
+        );
+    }
+
+    #[test]
+    fn confirm() {
+        let confirm = [6, X224_CONNECTION_CONFIRM, 0, 0, 0x12, 0x34, 0];
+        assert_eq!(connection_confirm(&confirm).unwrap(), Negotiation::Legacy);
+        let confirm = [&confirm[..], &[2, 0x1f, 8, 0, 2, 0, 0, 0]].concat();
+        assert_eq!(
+            connection_confirm(&confirm).unwrap(),
+            Negotiation::Selected {
+                protocol: PROTOCOL_HYBRID,
+                flags: 0x1f
+            }
+        );
+        let confirm = [&confirm[..7], &[3, 0, 8, 0, 5, 0, 0, 0]].concat();
+        assert_eq!(
+            connection_confirm(&confirm).unwrap(),
+            Negotiation::Failure(5)
+        );
+        assert!(connection_confirm(b"HTTP/1.1 400").is_err());
+    }
+
+    #[test]
+    fn probes() {
+        let port = server(false);
+        let nla = server(true);
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.run(format!("r = rdp_negotiate(port: {port});"));
+        t.ok(r#"r["protocol_name"];"#, "hybrid_ex");
+        t.ok(r#"r["flags"];"#, 0x1f);
+        t.run(format!("r = rdp_negotiate(protocols: 0, port: {nla});"));
+        t.ok(r#"r["negotiated"];"#, true);
+        t.ok(r#"r["failure_name"];"#, "HYBRID_REQUIRED_BY_SERVER");
+
+        t.run(format!("p = rdp_security_protocols(port: {port});"));
+        t.ok(r#"p["rdp"];"#, true);
+        t.ok(r#"p["ssl"];"#, true);
+        t.ok(r#"p["rdstls"];"#, false);
+        t.ok(r#"p["nla_required"];"#, false);
+        t.run(format!("p = rdp_security_protocols(port: {nla});"));
+        t.ok(r#"p["rdp"];"#, false);
+        t.ok(r#"p["hybrid"];"#, true);
+        t.ok(r#"p["nla_required"];"#, true);
+
+        t.run(format!(
+            r#"m = rdp_mcs_connect(channels: make_list("rdpdr", "rdpsnd", "MS_T120"), port: {port});"#
+        ));
+        t.ok(r#"m["version"];"#, 0x0008_0004);
+        t.ok(r#"m["encryption_method"];"#, 2);
+        t.ok(r#"m["user_channel"];"#, USER_CHANNEL as i64);
+        t.ok(r#"m["io_channel"];"#, IO_CHANNEL as i64);
+        t.ok(r#"m["channels"][2]["name"];"#, "MS_T120");
+        t.ok(r#"m["channels"][2]["id"];"#, 1006);
+        t.ok(r#"m["channels"][0]["joined"];"#, true);
+        t.ok(r#"m["channels"][1]["joined"];"#, false);
+        t.run(format!("m = rdp_mcs_connect(port: {port});"));
+        t.ok(r#"m["channels"][0]["name"];"#, "rdpdr");
+        t.ok(r#"m["channels"][3]["name"];"#, "drdynvc");
+        t.ok(r#"m["channels"][3]["joined"];"#, true);
+        t.ok(format!("rdp_mcs_connect(port: {nla});"), NaslValue::Null);
+        t.ok(
+            format!(r#"rdp_mcs_connect(channels: "rdpdr", port: {nla});"#),
+            NaslValue::Null,
+        );
+        check_err_matches!(
+            t,
+            format!(r#"rdp_mcs_connect(channels: make_list("toolongname"), port: {port});"#),
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+}
@@ -0,0 +1,258 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! SCTP associations.
+//!
+//! Telecom protocols like Diameter or the SIGTRAN family run on SCTP instead of TCP. An
+//! association is opened with open_sock_sctp and can be used with send and recv like a TCP
+//! connection, except that message boundaries are kept. Each message is sent on the stream and
+//! with the payload protocol identifier given when opening the association, sctp_send and
+//! sctp_recv allow to set and read them per message.
+
+use std::{
+    io::{self, Read, Write},
+    mem::{self, size_of},
+    net::SocketAddr,
+    os::fd::AsRawFd,
+    ptr,
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::nasl::utils::SourceBinding;
+
+/// A message received on an association
+pub struct SctpMessage {
+    pub data: Vec<u8>,
+    pub stream: u16,
+    pub ppid: u32,
+}
+
+pub struct SctpConnection {
+    socket: Socket,
+    stream: u16,
+    ppid: u32,
+}
+
+/// Returns the length of the buffer required for ancillary data of type T.
+fn cmsg_space<T>() -> usize {
+    unsafe { libc::CMSG_SPACE(size_of::<T>() as u32) as usize }
+}
+
+/// Returns a message header for a single buffer with room for ancillary data of type T.
+///
+/// The control buffer is allocated aligned for the headers of the ancillary data.
+fn message_header<T>(iov: &mut libc::iovec, control: &mut Vec<u64>) -> libc::msghdr {
+    *control = vec![0; cmsg_space::<T>().div_ceil(size_of::<u64>())];
+    // msghdr contains private padding fields on some platforms
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = cmsg_space::<T>() as _;
+    msg
+}
+
+/// Stores the value as the only SCTP ancillary data of the message.
+fn set_ancillary<T>(msg: &mut libc::msghdr, kind: libc::c_int, value: T) {
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(msg);
+        (*cmsg).cmsg_level = libc::IPPROTO_SCTP;
+        (*cmsg).cmsg_type = kind;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<T>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast(), value);
+    }
+}
+
+/// Returns the SCTP ancillary data of the given kind contained in the message.
+fn ancillary<T>(msg: &libc::msghdr, kind: libc::c_int) -> Option<T> {
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::IPPROTO_SCTP && (*cmsg).cmsg_type == kind {
+                return Some(ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast()));
+            }
+            cmsg = libc::CMSG_NXTHDR(msg, cmsg);
+        }
+    }
+    None
+}
+
+impl Read for SctpConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.socket.read(buf)
+    }
+}
+
+impl Write for SctpConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send_message(buf, self.stream, self.ppid)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SctpConnection {
+    /// Opens an association to the given address.
+    ///
+    /// Messages written to the connection are sent on the stream with the payload protocol
+    /// identifier.
+    pub fn connect(
+        addr: &SocketAddr,
+        timeout: Duration,
+        binding: &SourceBinding,
+        stream: u16,
+        ppid: u32,
+    ) -> io::Result<Self> {
+        let socket = Socket::new(
+            Domain::for_address(*addr),
+            Type::STREAM,
+            Some(Protocol::SCTP),
+        )?;
+        binding.bind(&socket, &addr.ip())?;
+        socket.connect_timeout(&(*addr).into(), timeout)?;
+        socket.set_read_timeout(Some(timeout))?;
+        socket.set_write_timeout(Some(timeout))?;
+        let enable: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_SCTP,
+                libc::SCTP_RECVRCVINFO,
+                &enable as *const libc::c_int as *const libc::c_void,
+                size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            socket,
+            stream,
+            ppid,
+        })
+    }
+
+    /// Returns the stream messages written to the connection are sent on.
+    pub fn stream(&self) -> u16 {
+        self.stream
+    }
+
+    /// Returns the payload protocol identifier of messages written to the connection.
+    pub fn ppid(&self) -> u32 {
+        self.ppid
+    }
+
+    /// Sends a message on the stream with the payload protocol identifier.
+    pub fn send_message(&self, data: &[u8], stream: u16, ppid: u32) -> io::Result<usize> {
+        let mut iov = libc::iovec {
+            iov_base: data.as_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+        let mut control = vec![];
+        let mut msg = message_header::<libc::sctp_sndinfo>(&mut iov, &mut control);
+        set_ancillary(
+            &mut msg,
+            libc::SCTP_SNDINFO,
+            libc::sctp_sndinfo {
+                snd_sid: stream,
+                snd_flags: 0,
+                // transmitted as is, so it has to be in network byte order
+                snd_ppid: ppid.to_be(),
+                snd_context: 0,
+                snd_assoc_id: 0,
+            },
+        );
+        let sent = unsafe { libc::sendmsg(self.socket.as_raw_fd(), &msg, 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(sent as usize)
+    }
+
+    /// Receives a message of at most length bytes.
+    pub fn recv_message(&self, length: usize) -> io::Result<SctpMessage> {
+        let mut data = vec![0; length];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut control = vec![];
+        let mut msg = message_header::<libc::sctp_rcvinfo>(&mut iov, &mut control);
+        let received = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, 0) };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        data.truncate(received as usize);
+        let (stream, ppid) = ancillary::<libc::sctp_rcvinfo>(&msg, libc::SCTP_RCVINFO)
+            .map_or((0, 0), |info| (info.rcv_sid, u32::from_be(info.rcv_ppid)));
+        Ok(SctpMessage { data, stream, ppid })
+    }
+
+    /// Receives a message, waiting at most for the timeout.
+    pub fn recv_message_with_timeout(
+        &self,
+        length: usize,
+        timeout: Duration,
+    ) -> io::Result<SctpMessage> {
+        let old = self.socket.read_timeout()?;
+        self.socket.set_read_timeout(Some(timeout))?;
+        let ret = self.recv_message(length);
+        self.socket.set_read_timeout(old)?;
+        ret
+    }
+
+    pub fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let old = self.socket.read_timeout()?;
+        self.socket.set_read_timeout(Some(timeout))?;
+        let ret = self.read(buf);
+        self.socket.set_read_timeout(old)?;
+        ret
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket
+            .local_addr()?
+            .as_socket()
+            .ok_or_else(|| io::Error::other("no IP address"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ancillary, message_header, set_ancillary};
+
+    #[test]
+    fn ancillary_data() {
+        let mut data = [0u8; 4];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut control = vec![];
+        let mut msg = message_header::<libc::sctp_rcvinfo>(&mut iov, &mut control);
+        assert!(ancillary::<libc::sctp_rcvinfo>(&msg, libc::SCTP_RCVINFO).is_none());
+        set_ancillary(
+            &mut msg,
+            libc::SCTP_RCVINFO,
+            libc::sctp_rcvinfo {
+                rcv_sid: 3,
+                rcv_ssn: 0,
+                rcv_flags: 0,
+                rcv_ppid: 46u32.to_be(),
+                rcv_tsn: 0,
+                rcv_cumtsn: 0,
+                rcv_context: 0,
+                rcv_assoc_id: 0,
+            },
+        );
+        assert!(ancillary::<libc::sctp_sndinfo>(&msg, libc::SCTP_SNDINFO).is_none());
+        let info = ancillary::<libc::sctp_rcvinfo>(&msg, libc::SCTP_RCVINFO).unwrap();
+        assert_eq!(info.rcv_sid, 3);
+        assert_eq!(u32::from_be(info.rcv_ppid), 46);
+    }
+}
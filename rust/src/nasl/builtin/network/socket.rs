@@ -3,29 +3,51 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 use std::{
-    io::{BufRead, Read, Write},
-    net::IpAddr,
+    collections::HashMap,
+    io::{self, BufRead, Read, Write},
+    net::{IpAddr, SocketAddr},
+    path::Path,
     sync::RwLock,
     thread::sleep,
     time::{Duration, SystemTime},
 };
 
-use crate::function_set;
+use crate::internal_call_expr;
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::{error::FunctionErrorKind, Context};
-use dns_lookup::lookup_host;
+use crate::nasl::utils::{
+    error::FunctionErrorKind,
+    traffic::{ReplayedConnection, Transport},
+    Context, IntoFunctionSet, Proxy, StoredFunctionSet,
+};
+use crate::storage::{types::Primitive, Field, Kb};
+use indexmap::IndexMap;
 use nasl_function_proc_macro::nasl_function;
 use rustls::ClientConnection;
 
 use super::{
     get_kb_item, get_kb_item_str, get_retry,
-    network_utils::{convert_timeout, ipstr2ipaddr},
+    network_utils::{convert_timeout, resolve_host},
+    sctp::SctpConnection,
+    ssh_kex::{self, KexInit, Progress},
+    ssl::{parse_version, ClientCertificate, SslOptions},
     tcp::TcpConnection,
+    telnet::{self, Negotiation},
     tls::create_tls_client,
-    udp::UdpConnection,
+    udp::{self, UdpConnection},
+    unix::{target_socket_path, UnixConnection},
     verify_port, OpenvasEncaps,
 };
 
+/// Default timeout of connection attempts when no network timeout is configured for the scan
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of lines of a FTP reply
+const MAX_FTP_REPLY_LINES: usize = 1024;
+/// User of a FTP login without credentials
+const FTP_ANONYMOUS_USER: &str = "anonymous";
+/// Password of a FTP login without credentials
+const FTP_ANONYMOUS_PASSWORD: &str = "anonymous@example.com";
+
 pub struct Interval {
     interval: Duration,
     last_tick: SystemTime,
@@ -54,6 +76,9 @@ enum NaslSocket {
     // This way the size of the enum is reduced
     Tcp(Box<TcpConnection>),
     Udp(UdpConnection),
+    Unix(UnixConnection),
+    Sctp(SctpConnection),
+    Replay(ReplayedConnection),
     Closed,
 }
 
@@ -61,6 +86,8 @@ enum NaslSocket {
 struct Handles {
     handles: Vec<NaslSocket>,
     closed_fd: Vec<usize>,
+    /// Index of the recorded connection of each socket whose traffic is recorded
+    recorded: HashMap<usize, usize>,
 }
 
 #[derive(Default)]
@@ -84,9 +111,66 @@ impl NaslSockets {
         }
     }
 
+    /// Adds a socket connected to the port and starts recording its traffic when enabled.
+    fn add_connected(
+        &self,
+        context: &Context,
+        transport: Transport,
+        port: u16,
+        socket: NaslSocket,
+    ) -> usize {
+        let fd = self.add(socket);
+        if let Some(connection) = context.traffic().and_then(|x| x.opened(transport, port)) {
+            self.handles
+                .write()
+                .unwrap()
+                .recorded
+                .insert(fd, connection);
+        }
+        fd
+    }
+
+    /// Opens a socket replaying the next recorded connection to the port.
+    ///
+    /// Returns None when the traffic is not replayed and Some(None) when no recorded connection
+    /// is left.
+    fn replay(&self, context: &Context, transport: Transport, port: u16) -> Option<Option<usize>> {
+        let traffic = context.traffic().filter(|x| x.is_replay())?;
+        match traffic.next_connection(transport, port) {
+            Some(conn) => Some(Some(self.add(NaslSocket::Replay(conn)))),
+            None => {
+                tracing::debug!(?transport, port, "no recorded connection left to replay");
+                Some(None)
+            }
+        }
+    }
+
+    /// Records data sent or received on the socket, when its traffic is recorded.
+    fn record(&self, context: &Context, socket: usize, sent: bool, data: &[u8]) {
+        let Some(traffic) = context.traffic() else {
+            return;
+        };
+        let recorded = self.handles.read().unwrap().recorded.get(&socket).copied();
+        if let Some(connection) = recorded {
+            if sent {
+                traffic.sent(connection, data);
+            } else {
+                traffic.received(connection, data);
+            }
+        }
+    }
+
     /// Close a given file descriptor taken as an unnamed argument.
     #[nasl_function]
-    fn close(&self, socket_fd: usize) -> Result<NaslValue, FunctionErrorKind> {
+    fn close(&self, context: &Context, socket_fd: usize) -> Result<NaslValue, FunctionErrorKind> {
+        self.close_socket(context, socket_fd)
+    }
+
+    fn close_socket(
+        &self,
+        context: &Context,
+        socket_fd: usize,
+    ) -> Result<NaslValue, FunctionErrorKind> {
         let mut handles = self.handles.write().unwrap();
         match handles.handles.get_mut(socket_fd) {
             Some(NaslSocket::Closed) => {
@@ -96,8 +180,14 @@ impl NaslSockets {
                 ))
             }
             Some(socket) => {
+                if let (Some(stats), NaslSocket::Tcp(conn)) = (context.script_stats(), &socket) {
+                    if let Ok(retransmissions) = conn.retransmissions() {
+                        stats.retransmitted(retransmissions);
+                    }
+                }
                 *socket = NaslSocket::Closed;
                 handles.closed_fd.push(socket_fd);
+                handles.recorded.remove(&socket_fd);
             }
             None => {
                 return Err(FunctionErrorKind::Diagnostic(
@@ -127,6 +217,18 @@ impl NaslSockets {
     #[nasl_function(named(socket, data, option, len))]
     fn send(
         &self,
+        context: &Context,
+        socket: usize,
+        data: &[u8],
+        option: Option<i64>,
+        len: Option<usize>,
+    ) -> Result<usize, FunctionErrorKind> {
+        self.write(context, socket, data, option, len)
+    }
+
+    fn write(
+        &self,
+        context: &Context,
         socket: usize,
         data: &[u8],
         option: Option<i64>,
@@ -144,7 +246,7 @@ impl NaslSockets {
 
         let data = &data[0..len];
 
-        match self
+        let sent = match self
             .handles
             .write()
             .unwrap()
@@ -162,18 +264,63 @@ impl NaslSockets {
                     }
                 }
 
-                Ok(conn.write(data)?)
+                conn.write(data)?
             }
             NaslSocket::Udp(conn) => {
                 if let Some(flags) = option {
                     conn.set_flags(flags as i32);
                 }
-                Ok(conn.write(data)?)
+                conn.write(data)?
             }
-            NaslSocket::Closed => Err(FunctionErrorKind::WrongArgument(
-                "the given socket FD is already closed".to_string(),
-            )),
+            NaslSocket::Unix(conn) => conn.write(data)?,
+            NaslSocket::Sctp(conn) => conn.write(data)?,
+            // keeps the reason why the replay diverges from the recording
+            NaslSocket::Replay(conn) => conn
+                .write(data)
+                .map_err(|e| FunctionErrorKind::Diagnostic(e.to_string(), None))?,
+            NaslSocket::Closed => {
+                return Err(FunctionErrorKind::WrongArgument(
+                    "the given socket FD is already closed".to_string(),
+                ))
+            }
+        };
+        self.record(context, socket, true, &data[..sent]);
+        if let Some(stats) = context.script_stats() {
+            stats.sent(sent);
+        }
+        Ok(sent)
+    }
+
+    /// Sends data on a socket and reads the next packet captured afterwards.
+    ///
+    /// - socket, data, length, option: like for send, without socket or data nothing is sent
+    /// - interface: network interface name, by default NASL will try to find the best one
+    /// - pcap_filter: BPF filter, by default it listens to everything
+    /// - timeout: timeout in seconds, 5 by default
+    ///
+    /// The capture is started before the data is sent, so that an early answer is not missed.
+    /// Returns the captured IP datagram or NULL when nothing was captured within the timeout.
+    #[cfg(feature = "nasl-builtin-raw-ip")]
+    #[nasl_function(named(interface, pcap_filter, timeout, socket, data, length, option))]
+    fn send_capture(
+        &self,
+        context: &Context,
+        interface: Option<&str>,
+        pcap_filter: Option<&str>,
+        timeout: Option<u64>,
+        socket: Option<usize>,
+        data: Option<&[u8]>,
+        length: Option<usize>,
+        option: Option<i64>,
+    ) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+        use super::super::raw_ip::capture::{capture_device, PacketCapture, DEFAULT_TIMEOUT};
+
+        let device = capture_device(context, interface)?;
+        let mut capture = PacketCapture::open(device, pcap_filter.unwrap_or_default())?;
+        if let (Some(socket), Some(data)) = (socket, data) {
+            self.write(context, socket, data, option, length)?;
         }
+        capture.next_datagram(Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT)))
     }
 
     /// Receives data from a TCP or UDP socket. For a UDP socket, if it cannot read data, NASL will
@@ -182,21 +329,28 @@ impl NaslSockets {
     /// - socket which was returned by an open sock function
     /// - length the number of bytes that you want to read at most. recv may return before length bytes have been read: as soon as at least one byte has been received, the timeout is lowered to 1 second. If no data is received during that time, the function returns the already read data; otherwise, if the full initial timeout has not been reached, a 1 second timeout is re-armed and the script tries to receive more data from the socket. This special feature was implemented to get a good compromise between reliability and speed when openvas-scanner talks to unknown or complex protocols. Two other optional named integer arguments can twist this behavior:
     /// - min is the minimum number of data that must be read in case the “magic read function” is activated and the timeout is lowered. By default this is 0. It works together with length. More info https://lists.archive.carbon60.com/nessus/devel/13796
-    /// - timeout can be changed from the default.
-    #[nasl_function(named(socket, length, min, timeout))]
+    /// - timeout can be changed from the default. For a UDP socket it includes the time spent for
+    ///   retries.
+    /// - retries the number of times the last datagram is sent again on a UDP socket, by default
+    ///   the one given to open_sock_udp.
+    ///
+    /// NULL is returned when nothing was received from a UDP socket.
+    #[nasl_function(named(socket, length, min, timeout, retries))]
     fn recv(
         &self,
+        context: &Context,
         socket: usize,
         length: usize,
         min: Option<i64>,
         timeout: Option<i64>,
+        retries: Option<usize>,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let min = min
             .map(|min| if min < 0 { length } else { min as usize })
             .unwrap_or(length);
         let mut data = vec![0; length];
 
-        match self
+        let received = match self
             .handles
             .write()
             .unwrap()
@@ -213,31 +367,80 @@ impl NaslSockets {
                         None => conn.read(&mut data[pos..]),
                     }?;
                 }
-                Ok(NaslValue::Data(data[..pos].to_vec()))
+                pos
             }
             NaslSocket::Udp(conn) => {
-                let pos = match convert_timeout(timeout) {
-                    Some(timeout) => conn.read_with_timeout(&mut data, timeout),
-                    None => conn.read(&mut data),
-                }?;
-
-                Ok(NaslValue::Data(data[..pos].to_vec()))
+                let pos = conn.read_with_retries(
+                    &mut data,
+                    convert_timeout(timeout).unwrap_or(conn.timeout()),
+                    retries.unwrap_or(conn.retries()),
+                );
+                if let Some(stats) = context.script_stats() {
+                    stats.retransmitted(conn.take_retransmissions());
+                }
+                match pos {
+                    Err(e) if udp::is_timeout(&e) => return Ok(NaslValue::Null),
+                    pos => pos?,
+                }
             }
-            NaslSocket::Closed => Err(FunctionErrorKind::WrongArgument(
-                "the given socket FD is already closed".to_string(),
-            )),
+            NaslSocket::Unix(conn) => {
+                let mut pos = 0;
+                loop {
+                    let read = match convert_timeout(timeout) {
+                        Some(timeout) => conn.read_with_timeout(&mut data[pos..], timeout),
+                        None => conn.read(&mut data[pos..]),
+                    }?;
+                    pos += read;
+                    // a datagram is received at once
+                    if read == 0 || pos >= min || matches!(conn, UnixConnection::Datagram(_)) {
+                        break;
+                    }
+                }
+                pos
+            }
+            // a message is received at once
+            NaslSocket::Sctp(conn) => match convert_timeout(timeout) {
+                Some(timeout) => conn.read_with_timeout(&mut data, timeout),
+                None => conn.read(&mut data),
+            }?,
+            NaslSocket::Replay(conn) => {
+                let mut pos = 0;
+                loop {
+                    let read = conn.read(&mut data[pos..])?;
+                    pos += read;
+                    if read == 0 || pos >= min || conn.transport() == Transport::Udp {
+                        break;
+                    }
+                }
+                // like a lost datagram
+                if pos == 0 && conn.transport() == Transport::Udp {
+                    return Ok(NaslValue::Null);
+                }
+                pos
+            }
+            NaslSocket::Closed => {
+                return Err(FunctionErrorKind::WrongArgument(
+                    "the given socket FD is already closed".to_string(),
+                ))
+            }
+        };
+        self.record(context, socket, false, &data[..received]);
+        if let Some(stats) = context.script_stats() {
+            stats.received(received);
         }
+        Ok(NaslValue::Data(data[..received].to_vec()))
     }
 
     #[nasl_function(named(socket, length, timeout))]
     fn recv_line(
         &self,
+        context: &Context,
         socket: usize,
         #[allow(unused_variables)] length: usize,
         timeout: Option<i64>,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let mut data = String::new();
-        match self
+        let received = match self
             .handles
             .write()
             .unwrap()
@@ -246,21 +449,31 @@ impl NaslSockets {
             .ok_or(FunctionErrorKind::WrongArgument(format!(
                 "the given socket FD {socket} does not exist"
             )))? {
-            NaslSocket::Tcp(conn) => {
-                let pos = match convert_timeout(timeout) {
-                    Some(timeout) => conn.read_line_with_timeout(&mut data, timeout),
-                    None => conn.read_line(&mut data),
-                }?;
-                Ok(NaslValue::Data(data.as_bytes()[..pos].to_vec()))
-            }
-            NaslSocket::Udp(_) => Err(FunctionErrorKind::Diagnostic(
-                "This function is only available for TCP connections".to_string(),
-                None,
-            )),
-            NaslSocket::Closed => Err(FunctionErrorKind::WrongArgument(
-                "the given socket FD is already closed".to_string(),
-            )),
+            NaslSocket::Tcp(conn) => match convert_timeout(timeout) {
+                Some(timeout) => conn.read_line_with_timeout(&mut data, timeout),
+                None => conn.read_line(&mut data),
+            }?,
+            NaslSocket::Unix(conn) => conn.read_line(&mut data, convert_timeout(timeout))?,
+            NaslSocket::Replay(conn) if conn.transport() == Transport::Tcp => {
+                conn.read_line(&mut data)?
+            }
+            NaslSocket::Udp(_) | NaslSocket::Sctp(_) | NaslSocket::Replay(_) => {
+                return Err(FunctionErrorKind::Diagnostic(
+                    "This function is only available for TCP connections".to_string(),
+                    None,
+                ))
+            }
+            NaslSocket::Closed => {
+                return Err(FunctionErrorKind::WrongArgument(
+                    "the given socket FD is already closed".to_string(),
+                ))
+            }
+        };
+        self.record(context, socket, false, &data.as_bytes()[..received]);
+        if let Some(stats) = context.script_stats() {
+            stats.received(received);
         }
+        Ok(NaslValue::Data(data.as_bytes()[..received].to_vec()))
     }
 
     /// Open a KDC socket. This function takes no arguments, but it is mandatory that keys are set. The following keys are required:
@@ -271,16 +484,7 @@ impl NaslSockets {
     fn open_sock_kdc(&self, context: &Context) -> Result<NaslValue, FunctionErrorKind> {
         let hostname = get_kb_item_str(context, "Secret/kdc_hostname")?;
 
-        let ip = lookup_host(&hostname)
-            .map_err(|_| {
-                FunctionErrorKind::Diagnostic(format!("unable to lookup hostname {hostname}"), None)
-            })?
-            .into_iter()
-            .next()
-            .ok_or(FunctionErrorKind::Diagnostic(
-                format!("No IP found for hostname {hostname}"),
-                None,
-            ))?;
+        let addrs = resolve_host(context, &hostname)?;
 
         let port = get_kb_item(context, "Secret/kdc_port")?;
 
@@ -310,17 +514,17 @@ impl NaslSockets {
             .unwrap_or(false);
 
         let socket = if use_tcp {
-            let tcp = TcpConnection::connect(
-                ip,
-                port,
-                None,
-                Duration::from_secs(30),
-                None,
-                get_retry(context),
-            )?;
+            let tcp =
+                Self::connect_tcp(context, &addrs, port, None, Duration::from_secs(30), None)?;
             NaslSocket::Tcp(Box::new(tcp))
         } else {
-            let udp = UdpConnection::new(ip, port)?;
+            let udp = Self::connect_udp(
+                context,
+                addrs[0],
+                port,
+                udp::DEFAULT_TIMEOUT,
+                udp::DEFAULT_RETRIES,
+            )?;
             NaslSocket::Udp(udp)
         };
 
@@ -329,7 +533,70 @@ impl NaslSockets {
         Ok(NaslValue::Number(ret as i64))
     }
 
-    fn make_tls_client_connection(context: &Context, vhost: &str) -> Option<ClientConnection> {
+    /// Connects to the target, through the proxy when one is configured.
+    pub(crate) fn connect_tcp(
+        context: &Context,
+        addrs: &[IpAddr],
+        port: u16,
+        tls: Option<ClientConnection>,
+        timeout: Duration,
+        bufsz: Option<usize>,
+    ) -> io::Result<TcpConnection> {
+        match context.proxy() {
+            Some(proxy) => TcpConnection::connect_proxy(
+                proxy,
+                addrs,
+                port,
+                tls,
+                timeout,
+                bufsz,
+                context.source_binding(),
+            ),
+            None => TcpConnection::connect(
+                addrs,
+                port,
+                tls,
+                timeout,
+                bufsz,
+                get_retry(context),
+                context.source_binding(),
+            ),
+        }
+    }
+
+    /// Opens a UDP socket to the target, sending through the relay of the SOCKS5 proxy when one
+    /// is configured.
+    ///
+    /// Fails when the scan is routed through an HTTP proxy, which can only tunnel TCP.
+    pub(crate) fn connect_udp(
+        context: &Context,
+        addr: IpAddr,
+        port: u16,
+        timeout: Duration,
+        retries: usize,
+    ) -> io::Result<UdpConnection> {
+        match context.proxy() {
+            Some(Proxy::Socks5(proxy)) => UdpConnection::new_socks(
+                proxy,
+                addr,
+                port,
+                context.source_binding(),
+                timeout,
+                retries,
+            ),
+            Some(Proxy::Http(_)) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "UDP can not be tunneled through the HTTP proxy",
+            )),
+            None => UdpConnection::new(addr, port, context.source_binding(), timeout, retries),
+        }
+    }
+
+    fn make_tls_client_connection(
+        context: &Context,
+        vhost: &str,
+        alpn: &[String],
+    ) -> Option<ClientConnection> {
         Self::get_tls_conf(context).ok().and_then(|conf| {
             create_tls_client(
                 vhost,
@@ -337,29 +604,42 @@ impl NaslSockets {
                 &conf.key_path,
                 &conf.password,
                 &conf.cafile_path,
+                alpn,
             )
             .ok()
         })
     }
 
+    /// Returns the transport of the port detected by the service detection, IP when the port
+    /// was not detected.
+    fn port_transport(context: &Context, port: u16) -> i64 {
+        match get_kb_item(context, &format!("Transports/TCP/{port}")) {
+            Ok(Some(x)) => x.to_string().parse().unwrap_or(OpenvasEncaps::Ip as i64),
+            _ => OpenvasEncaps::Ip as i64,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn open_sock_tcp_vhost(
         context: &Context,
-        addr: IpAddr,
+        addrs: &[IpAddr],
         timeout: Duration,
         bufsz: Option<usize>,
         port: u16,
         vhost: &str,
+        alpn: &[String],
         transport: i64,
     ) -> Result<Option<NaslSocket>, FunctionErrorKind> {
-        if transport < 0 {
-            // TODO: Get port transport and open connection depending on it
-            todo!()
-        }
+        let transport = if transport < 0 {
+            Self::port_transport(context, port)
+        } else {
+            transport
+        };
         let tls = match OpenvasEncaps::from_i64(transport) {
             // Auto Detection
             Some(OpenvasEncaps::Auto) => {
                 // Try SSL/TLS first
-                Self::make_tls_client_connection(context, vhost)
+                Self::make_tls_client_connection(context, vhost, alpn)
             }
             // IP
             Some(OpenvasEncaps::Ip) => None,
@@ -373,7 +653,7 @@ impl NaslSockets {
             // TLS/SSL
             Some(tls_version) => match tls_version {
                 OpenvasEncaps::Tls12 | OpenvasEncaps::Tls13 => {
-                    Self::make_tls_client_connection(context, vhost)
+                    Self::make_tls_client_connection(context, vhost, alpn)
                 }
                 _ => {
                     return Err(FunctionErrorKind::Diagnostic(
@@ -383,22 +663,27 @@ impl NaslSockets {
                 }
             },
         };
-        Ok(
-            TcpConnection::connect(addr, port, tls, timeout, bufsz, get_retry(context))
-                .map(|tcp| NaslSocket::Tcp(Box::new(tcp)))
-                .ok(),
-        )
+        Ok(Self::connect_tcp(context, addrs, port, tls, timeout, bufsz)
+            .map(|tcp| {
+                if let (Ok(peer), Ok(rtt)) = (tcp.peer_addr(), tcp.rtt()) {
+                    context.network_timeout().observe(peer.ip(), rtt);
+                }
+                NaslSocket::Tcp(Box::new(tcp))
+            })
+            .ok())
     }
 
     /// Open a TCP socket to the target host.
     /// This function is used to create a TCP connection to the target host.  It requires the port
     /// number as its argument and has various optional named arguments to control encapsulation,
     /// timeout and buffering.
+    /// When the target is a UNIX socket, the port is ignored and the socket is connected to
+    /// without any encapsulation.
     /// It takes an unnamed integer argument (the port number) and four optional named arguments:
     /// - bufsz: An integer with the the size buffer size.  Note that by default, no buffering is
     ///   used.
     /// - timeout: An integer with the timeout value in seconds.  The default timeout is controlled
-    ///   by a global value.
+    ///   by the network timeout of the scan.
     /// - transport: One of the ENCAPS_* constants to force a specific encapsulation mode or force
     ///   trying of all modes (ENCAPS_AUTO). This is for example useful to select a specific TLS or
     ///   SSL version or use specific TLS connection setup priorities.  See *get_port_transport for
@@ -406,7 +691,11 @@ impl NaslSockets {
     /// - priority A string value with priorities for an TLS encapsulation. For the syntax of the
     ///   priority string see the GNUTLS manual. This argument is only used in ENCAPS_TLScustom
     ///   encapsulation.
-    #[nasl_function(named(timeout, transport, bufsz))]
+    /// - sni: A string with the server name sent in the TLS handshake instead of the virtual host.
+    /// - alpn: A list of protocols offered with ALPN in the TLS handshake, e.g. "h2" and
+    ///   "http/1.1". The negotiated one is returned by socket_get_ssl_alpn.
+    #[nasl_function(named(timeout, transport, bufsz, sni, alpn))]
+    #[allow(clippy::too_many_arguments)]
     fn open_sock_tcp(
         &self,
         context: &Context,
@@ -414,14 +703,45 @@ impl NaslSockets {
         timeout: Option<i64>,
         transport: Option<i64>,
         bufsz: Option<i64>,
+        sni: Option<&str>,
+        alpn: Option<Vec<String>>,
         // TODO: Extract information from custom priority string
         // priority: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.open_tcp(context, port, timeout, transport, bufsz, sni, alpn)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn open_tcp(
+        &self,
+        context: &Context,
+        port: i64,
+        timeout: Option<i64>,
+        transport: Option<i64>,
+        bufsz: Option<i64>,
+        sni: Option<&str>,
+        alpn: Option<Vec<String>>,
     ) -> Result<NaslValue, FunctionErrorKind> {
         // Get port
         let port = verify_port(port)?;
         let transport = transport.unwrap_or(-1);
 
-        let addr = ipstr2ipaddr(context.target())?;
+        let timeout = convert_timeout(timeout);
+        if let Some(path) = target_socket_path(context.target()) {
+            return Ok(self.open_unix(&path, false, timeout.unwrap_or(CONNECT_TIMEOUT)));
+        }
+
+        if let Some(fd) = self.replay(context, Transport::Tcp, port) {
+            return Ok(NaslValue::Fork(
+                fd.map(|fd| NaslValue::Number(fd as i64))
+                    .into_iter()
+                    .collect(),
+            ));
+        }
+
+        let addrs = resolve_host(context, context.target())?;
+        let timeout =
+            timeout.unwrap_or_else(|| context.network_timeout().timeout(&addrs, CONNECT_TIMEOUT));
 
         self.wait_before_next_probe();
 
@@ -429,23 +749,30 @@ impl NaslSockets {
             .filter(|bufsz| *bufsz >= 0)
             .map(|bufsz| bufsz as usize);
 
-        // TODO: set timeout to global recv timeout * 2 when available
-        let timeout = convert_timeout(timeout).unwrap_or(Duration::from_secs(10));
         // TODO: for every vhost
         let vhosts = vec!["localhost"];
         let sockets: Vec<Option<NaslSocket>> = vhosts
             .iter()
             .map(|vhost| {
-                Self::open_sock_tcp_vhost(context, addr, timeout, bufsz, port, vhost, transport)
+                Self::open_sock_tcp_vhost(
+                    context,
+                    &addrs,
+                    timeout,
+                    bufsz,
+                    port,
+                    sni.unwrap_or(vhost),
+                    alpn.as_deref().unwrap_or_default(),
+                    transport,
+                )
             })
             .collect::<Result<_, _>>()?;
 
         Ok(NaslValue::Fork(
             sockets
                 .into_iter()
-                .filter_map(|socket| socket)
+                .flatten()
                 .map(|socket| {
-                    let fd = self.add(socket);
+                    let fd = self.add_connected(context, Transport::Tcp, port, socket);
                     NaslValue::Number(fd as i64)
                 })
                 .collect(),
@@ -455,7 +782,7 @@ impl NaslSockets {
     fn get_tls_conf(context: &Context) -> Result<TlsConfig, FunctionErrorKind> {
         let cert_path = get_kb_item_str(context, "SSL/cert")?;
         let key_path = get_kb_item_str(context, "SSL/key")?;
-        let password = get_kb_item_str(context, "SSL/password")?;
+        let password = get_kb_item_str(context, "SSL/password").unwrap_or_default();
         let cafile_path = get_kb_item_str(context, "SSL/CA")?;
 
         Ok(TlsConfig {
@@ -466,87 +793,800 @@ impl NaslSockets {
         })
     }
 
+    /// Loads the client certificate set in the KB items SSL/cert, SSL/key and SSL/password.
+    ///
+    /// Returns None when no certificate is set.
+    fn client_certificate(
+        context: &Context,
+    ) -> Result<Option<ClientCertificate>, FunctionErrorKind> {
+        let Ok(cert_path) = get_kb_item_str(context, "SSL/cert") else {
+            return Ok(None);
+        };
+        let key_path = get_kb_item_str(context, "SSL/key")?;
+        let password = get_kb_item_str(context, "SSL/password").unwrap_or_default();
+        ClientCertificate::load(&cert_path, &key_path, &password)
+            .map(Some)
+            .map_err(|e| {
+                FunctionErrorKind::Diagnostic(
+                    format!("unable to load the client certificate {cert_path}: {e}"),
+                    None,
+                )
+            })
+    }
+
     /// Open a UDP socket to the target host
-    #[nasl_function]
-    fn open_sock_udp(&self, context: &Context, port: i64) -> Result<NaslValue, FunctionErrorKind> {
+    ///
+    /// Takes the port as unnamed argument and the optional named arguments:
+    /// - timeout: the time in seconds recv waits for an answer including all retries, by default
+    ///   the network timeout of the scan or 5 seconds.
+    /// - retries: the number of times recv sends the last datagram again when no answer is
+    ///   received, 4 by default.
+    ///
+    /// When the target is a UNIX socket, a datagram socket connected to it is opened instead.
+    /// NULL is returned when the scan is routed through an HTTP proxy, which can not relay UDP.
+    #[nasl_function(named(timeout, retries))]
+    fn open_sock_udp(
+        &self,
+        context: &Context,
+        port: i64,
+        timeout: Option<i64>,
+        retries: Option<usize>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
         let port = verify_port(port)?;
-        let addr = ipstr2ipaddr(context.target())?;
+        if let Some(path) = target_socket_path(context.target()) {
+            return Ok(self.open_unix(&path, true, Duration::from_secs(1)));
+        }
+        if let Some(fd) = self.replay(context, Transport::Udp, port) {
+            return Ok(fd.map_or(NaslValue::Null, |fd| NaslValue::Number(fd as i64)));
+        }
+        let addr = resolve_host(context, context.target())?[0];
+        let timeout = convert_timeout(timeout).unwrap_or_else(|| {
+            context
+                .network_timeout()
+                .timeout(&[addr], udp::DEFAULT_TIMEOUT)
+        });
 
-        let socket = NaslSocket::Udp(UdpConnection::new(addr, port)?);
-        let fd = self.add(socket);
+        let udp = match Self::connect_udp(
+            context,
+            addr,
+            port,
+            timeout,
+            retries.unwrap_or(udp::DEFAULT_RETRIES),
+        ) {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                tracing::warn!(%e);
+                return Ok(NaslValue::Null);
+            }
+            udp => udp?,
+        };
+        let socket = NaslSocket::Udp(udp);
+        let fd = self.add_connected(context, Transport::Udp, port, socket);
 
         Ok(NaslValue::Number(fd as i64))
     }
 
-    /// Get the source port of a open socket
-    #[nasl_function]
-    fn get_source_port(&self, socket: usize) -> Result<NaslValue, FunctionErrorKind> {
-        let handles = self.handles.read().unwrap();
-        let socket = handles
-            .handles
-            .get(socket)
-            .ok_or(FunctionErrorKind::WrongArgument(
-                "the given socket FD does not exist".to_string(),
-            ))?;
-        let port = match socket {
-            NaslSocket::Tcp(conn) => conn.local_addr()?.port(),
-            NaslSocket::Udp(conn) => conn.local_addr()?.port(),
-            NaslSocket::Closed => {
-                return Err(FunctionErrorKind::WrongArgument(
-                    "the given socket FD is already closed".to_string(),
-                ))
+    /// Opens a socket from a privileged source port and returns its file descriptor.
+    ///
+    /// Binding a port below 1024 requires CAP_NET_BIND_SERVICE. Without it the socket is opened
+    /// from an unprivileged port instead, so that services accepting those still can be checked.
+    /// NULL is returned when no address of the target can be connected to.
+    fn open_privileged(
+        &self,
+        context: &Context,
+        transport: Transport,
+        addrs: &[IpAddr],
+        port: u16,
+        sport: Option<u16>,
+        open: impl Fn(&SocketAddr, Option<u16>) -> io::Result<NaslSocket>,
+    ) -> NaslValue {
+        for addr in addrs {
+            let addr = SocketAddr::new(*addr, port);
+            let result = match open(&addr, sport) {
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    tracing::warn!(
+                        %addr,
+                        "missing CAP_NET_BIND_SERVICE, using an unprivileged source port"
+                    );
+                    open(&addr, Some(0))
+                }
+                result => result,
+            };
+            match result {
+                Ok(socket) => {
+                    let fd = self.add_connected(context, transport, port, socket);
+                    return NaslValue::Number(fd as i64);
+                }
+                Err(e) => tracing::debug!(%addr, ?sport, %e, "unable to open privileged socket"),
             }
+        }
+        NaslValue::Null
+    }
+
+    /// Open a TCP socket to the target host from a privileged source port.
+    ///
+    /// Takes the named arguments:
+    /// - dport: the destination port.
+    /// - sport: the source port, by default the first free port from 1023 down to 512.
+    /// - timeout: the connection timeout in seconds, by default the network timeout of the scan.
+    ///
+    /// Services like the r-commands only accept connections from ports below 1024. When the
+    /// scanner may not bind those, an unprivileged port is used instead. The addresses of the
+    /// target are tried in order, NULL is returned when the connection fails.
+    #[nasl_function(named(dport, sport, timeout))]
+    fn open_priv_sock_tcp(
+        &self,
+        context: &Context,
+        dport: i64,
+        sport: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let dport = verify_port(dport)?;
+        let sport = sport.map(verify_port).transpose()?;
+        let timeout = convert_timeout(timeout);
+        if let Some(path) = target_socket_path(context.target()) {
+            return Ok(self.open_unix(&path, false, timeout.unwrap_or(CONNECT_TIMEOUT)));
+        }
+        if let Some(fd) = self.replay(context, Transport::Tcp, dport) {
+            return Ok(fd.map_or(NaslValue::Null, |fd| NaslValue::Number(fd as i64)));
+        }
+        let addrs = resolve_host(context, context.target())?;
+        let timeout =
+            timeout.unwrap_or_else(|| context.network_timeout().timeout(&addrs, CONNECT_TIMEOUT));
+        self.wait_before_next_probe();
+        let open = |addr: &SocketAddr, sport| {
+            let tcp = match context.proxy() {
+                // the proxy chooses the source port
+                Some(_) => {
+                    Self::connect_tcp(context, &[addr.ip()], addr.port(), None, timeout, None)?
+                }
+                None => TcpConnection::connect_privileged(
+                    addr,
+                    sport,
+                    timeout,
+                    context.source_binding(),
+                )?,
+            };
+            Ok(NaslSocket::Tcp(Box::new(tcp)))
         };
-        Ok(NaslValue::Number(port as i64))
+        Ok(self.open_privileged(context, Transport::Tcp, &addrs, dport, sport, open))
     }
 
-    pub fn check_ftp_response(
-        mut conn: impl BufRead,
-        expected_code: &[usize],
-    ) -> Result<usize, FunctionErrorKind> {
-        let mut line = String::with_capacity(5);
-        conn.read_line(&mut line)?;
+    /// Open a UDP socket to the target host from a privileged source port.
+    ///
+    /// Takes the named arguments:
+    /// - dport: the destination port.
+    /// - sport: the source port, by default the first free port from 1023 down to 512.
+    ///
+    /// Services like NFS mountd may only answer requests from ports below 1024. When the scanner
+    /// may not bind those, an unprivileged port is used instead. recv waits for answers as on
+    /// sockets opened by open_sock_udp. NULL is returned when the socket can not be opened.
+    #[nasl_function(named(dport, sport))]
+    fn open_priv_sock_udp(
+        &self,
+        context: &Context,
+        dport: i64,
+        sport: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let dport = verify_port(dport)?;
+        let sport = sport.map(verify_port).transpose()?;
+        if let Some(path) = target_socket_path(context.target()) {
+            return Ok(self.open_unix(&path, true, Duration::from_secs(1)));
+        }
+        if let Some(fd) = self.replay(context, Transport::Udp, dport) {
+            return Ok(fd.map_or(NaslValue::Null, |fd| NaslValue::Number(fd as i64)));
+        }
+        let addrs = resolve_host(context, context.target())?;
+        let timeout = context
+            .network_timeout()
+            .timeout(&addrs, udp::DEFAULT_TIMEOUT);
+        let open = |addr: &SocketAddr, sport| {
+            let udp = match context.proxy() {
+                // the proxy chooses the source port
+                Some(_) => Self::connect_udp(
+                    context,
+                    addr.ip(),
+                    addr.port(),
+                    timeout,
+                    udp::DEFAULT_RETRIES,
+                )?,
+                None => UdpConnection::new_privileged(
+                    addr,
+                    sport,
+                    context.source_binding(),
+                    timeout,
+                    udp::DEFAULT_RETRIES,
+                )?,
+            };
+            Ok(NaslSocket::Udp(udp))
+        };
+        Ok(self.open_privileged(context, Transport::Udp, &addrs, dport, sport, open))
+    }
+
+    /// Connects to a UNIX socket and returns its file descriptor or NULL on failure.
+    fn open_unix(&self, path: &Path, datagram: bool, timeout: Duration) -> NaslValue {
+        match UnixConnection::connect(path, datagram, timeout) {
+            Ok(conn) => NaslValue::Number(self.add(NaslSocket::Unix(conn)) as i64),
+            Err(e) => {
+                tracing::debug!(path = %path.display(), %e, "unable to connect to UNIX socket");
+                NaslValue::Null
+            }
+        }
+    }
 
-        if line.len() < 5 {
+    /// Open a UNIX domain socket to the target.
+    ///
+    /// Takes the optional path of the socket as unnamed argument and the optional named arguments:
+    /// - datagram: when TRUE a datagram socket is opened instead of a stream socket.
+    /// - timeout: the read timeout in seconds, defaults to 10 seconds.
+    ///
+    /// Only the socket of a target given as UNIX socket URI can be opened, so scripts can not
+    /// reach the other sockets of the scanner host. A path naming another socket is rejected.
+    /// The returned socket can be used with send, recv, recv_line and close. NULL is returned when
+    /// the connection fails.
+    #[nasl_function(named(datagram, timeout))]
+    fn open_sock_unix(
+        &self,
+        context: &Context,
+        path: Option<&str>,
+        datagram: Option<bool>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let Some(target) = target_socket_path(context.target()) else {
             return Err(FunctionErrorKind::Diagnostic(
-                "could not read reply code".to_owned(),
+                format!("the target {} is not a UNIX socket", context.target()),
                 None,
             ));
+        };
+        if let Some(path) = path.filter(|x| Path::new(x) != target) {
+            return Err(FunctionErrorKind::WrongArgument(format!(
+                "{path} is not the socket of the target {}",
+                target.display()
+            )));
         }
+        let timeout = convert_timeout(timeout).unwrap_or(Duration::from_secs(10));
+        Ok(self.open_unix(&target, datagram.unwrap_or_default(), timeout))
+    }
 
-        let code: usize = line[0..3].parse().map_err(|err| {
-            FunctionErrorKind::Diagnostic(format!("could not parse reply code: {}", err), None)
-        })?;
-
-        // multiple line reply
-        // loop while the line does not begin with the code and a space
-        let expected = format!("{} ", &line[0..3]);
-        while line.len() < 5 || line[0..4] != expected {
-            line.clear();
-            conn.read_line(&mut line)?;
+    /// Open an SCTP association to the target host.
+    ///
+    /// Takes the port as unnamed argument and the optional named arguments:
+    /// - timeout: the connection and read timeout in seconds, by default the network timeout of
+    ///   the scan.
+    /// - stream: the stream messages are sent on by send, 0 by default.
+    /// - ppid: the payload protocol identifier of messages sent by send, 0 by default.
+    ///
+    /// The addresses of the target are tried in order. The returned socket can be used with send,
+    /// recv, sctp_send, sctp_recv and close. NULL is returned when the association fails
+    /// or the scan is routed through a proxy, which can not relay SCTP.
+    #[nasl_function(named(timeout, stream, ppid))]
+    fn open_sock_sctp(
+        &self,
+        context: &Context,
+        port: i64,
+        timeout: Option<i64>,
+        stream: Option<u16>,
+        ppid: Option<u32>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let port = verify_port(port)?;
+        if context.proxy().is_some() {
+            tracing::warn!("SCTP can not be routed through the proxy");
+            return Ok(NaslValue::Null);
         }
-
-        line = String::from(line.trim());
-
-        if expected_code.iter().any(|ec| code == *ec) {
-            Ok(code)
-        } else {
-            Err(FunctionErrorKind::Diagnostic(
-                format!("Expected code {:?}, got response: {}", expected_code, line),
-                None,
-            ))
+        let addrs = resolve_host(context, context.target())?;
+        let timeout = convert_timeout(timeout)
+            .unwrap_or_else(|| context.network_timeout().timeout(&addrs, CONNECT_TIMEOUT));
+        self.wait_before_next_probe();
+        for addr in addrs {
+            let addr = SocketAddr::new(addr, port);
+            match SctpConnection::connect(
+                &addr,
+                timeout,
+                context.source_binding(),
+                stream.unwrap_or_default(),
+                ppid.unwrap_or_default(),
+            ) {
+                Ok(conn) => return Ok(NaslValue::Number(self.add(NaslSocket::Sctp(conn)) as i64)),
+                Err(e) => tracing::debug!(%addr, %e, "unable to open SCTP association"),
+            }
         }
+        Ok(NaslValue::Null)
     }
 
-    /// *any* **ftp_log_in**(user: *string*, pass: *string*, socket: *int*);
-
+    /// Sends a message on an SCTP association.
+    ///
+    /// Takes the named arguments socket and data and optionally stream and ppid, which default to
+    /// the ones given to open_sock_sctp. Returns the number of sent bytes.
+    #[nasl_function(named(socket, data, stream, ppid))]
+    fn sctp_send(
+        &self,
+        context: &Context,
+        socket: usize,
+        data: &[u8],
+        stream: Option<u16>,
+        ppid: Option<u32>,
+    ) -> Result<usize, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        let conn = Self::sctp_connection(&mut handles, socket)?;
+        self.wait_before_next_probe();
+        let sent = conn.send_message(
+            data,
+            stream.unwrap_or(conn.stream()),
+            ppid.unwrap_or(conn.ppid()),
+        )?;
+        if let Some(stats) = context.script_stats() {
+            stats.sent(sent);
+        }
+        Ok(sent)
+    }
+
+    /// Receives a message from an SCTP association.
+    ///
+    /// Takes the named arguments socket, length and optionally timeout in seconds. Returns an
+    /// array containing the received data, the stream and the ppid of the message.
+    #[nasl_function(named(socket, length, timeout))]
+    fn sctp_recv(
+        &self,
+        context: &Context,
+        socket: usize,
+        length: usize,
+        timeout: Option<i64>,
+    ) -> Result<HashMap<String, NaslValue>, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        let conn = Self::sctp_connection(&mut handles, socket)?;
+        let message = match convert_timeout(timeout) {
+            Some(timeout) => conn.recv_message_with_timeout(length, timeout),
+            None => conn.recv_message(length),
+        }?;
+        if let Some(stats) = context.script_stats() {
+            stats.received(message.data.len());
+        }
+        Ok(HashMap::from([
+            ("data".to_string(), NaslValue::Data(message.data)),
+            (
+                "stream".to_string(),
+                NaslValue::Number(message.stream as i64),
+            ),
+            ("ppid".to_string(), NaslValue::Number(message.ppid as i64)),
+        ]))
+    }
+
+    fn sctp_connection(
+        handles: &mut Handles,
+        socket: usize,
+    ) -> Result<&mut SctpConnection, FunctionErrorKind> {
+        match handles.handles.get_mut(socket) {
+            Some(NaslSocket::Sctp(conn)) => Ok(conn),
+            Some(NaslSocket::Closed) => Err(FunctionErrorKind::WrongArgument(
+                "the given socket FD is already closed".to_string(),
+            )),
+            Some(_) => Err(FunctionErrorKind::Diagnostic(
+                "This function is only available for SCTP associations".to_string(),
+                None,
+            )),
+            None => Err(FunctionErrorKind::WrongArgument(format!(
+                "the given socket FD {socket} does not exist"
+            ))),
+        }
+    }
+
+    /// Negotiates SSL/TLS on an open TCP socket.
+    ///
+    /// Takes the named argument socket and the optional named arguments:
+    /// - transport: one of the ENCAPS_* constants selecting the offered protocol versions,
+    ///   ENCAPS_TLScustom by default, which offers all versions.
+    /// - min_version, max_version: the lowest and highest offered protocol version as SSLv3,
+    ///   TLSv1, TLSv1.1, TLSv1.2 or TLSv1.3, overriding the transport.
+    /// - ciphers: the offered ciphers as OpenSSL cipher string, e.g. "DES-CBC3-SHA". TLS 1.3
+    ///   cipher suites like "TLS_AES_128_GCM_SHA256" may be included, when none is given TLS 1.3
+    ///   is not offered.
+    /// - sni: the server name sent in the handshake, by default none is sent.
+    /// - alpn: a list of protocols offered with ALPN, the negotiated one is returned by
+    ///   socket_get_ssl_alpn.
+    ///
+    /// Outdated versions and ciphers are offered as well, the certificate of the server is not
+    /// verified. When the KB items SSL/cert and SSL/key name a client certificate and its key,
+    /// e.g. from the certificate credential of the scan, it is sent when the server asks for one.
+    ///
+    /// Returns the socket on success. When the handshake fails the socket is closed and NULL is
+    /// returned.
+    #[nasl_function(named(socket, transport, min_version, max_version, ciphers, sni, alpn))]
+    #[allow(clippy::too_many_arguments)]
+    fn socket_negotiate_ssl(
+        &self,
+        context: &Context,
+        socket: usize,
+        transport: Option<i64>,
+        min_version: Option<&str>,
+        max_version: Option<&str>,
+        ciphers: Option<&str>,
+        sni: Option<String>,
+        alpn: Option<Vec<String>>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let transport = transport.unwrap_or(OpenvasEncaps::TlsCustom as i64);
+        let mut options = OpenvasEncaps::from_i64(transport)
+            .and_then(|x| SslOptions::from_encaps(&x))
+            .ok_or_else(|| {
+                FunctionErrorKind::WrongArgument(format!("{transport} is not a SSL/TLS transport"))
+            })?;
+        let version = |version: Option<&str>| match version {
+            Some(x) => parse_version(x).map(Some).ok_or_else(|| {
+                FunctionErrorKind::WrongArgument(format!("unknown SSL/TLS version {x}"))
+            }),
+            None => Ok(None),
+        };
+        if let Some(x) = version(min_version)? {
+            options.min_version = Some(x);
+        }
+        if let Some(x) = version(max_version)? {
+            options.max_version = Some(x);
+        }
+        options.ciphers = ciphers.map(|x| x.to_string());
+        options.sni = sni;
+        options.client_certificate = Self::client_certificate(context)?;
+        options.alpn = alpn.unwrap_or_default();
+        if let Some(x) = options.alpn.iter().find(|x| x.is_empty() || x.len() > 255) {
+            return Err(FunctionErrorKind::WrongArgument(format!(
+                "invalid ALPN protocol {x:?}"
+            )));
+        }
+
+        let mut handles = self.handles.write().unwrap();
+        let conn = match handles.handles.get_mut(socket) {
+            Some(NaslSocket::Tcp(conn)) => conn,
+            // the plain text is recorded, so the replay continues unencrypted
+            Some(NaslSocket::Replay(_)) => return Ok(NaslValue::Number(socket as i64)),
+            Some(NaslSocket::Closed) | None => {
+                return Err(FunctionErrorKind::WrongArgument(format!(
+                    "the given socket FD {socket} does not exist"
+                )))
+            }
+            Some(_) => {
+                return Err(FunctionErrorKind::Diagnostic(
+                    "This function is only available for TCP connections".to_string(),
+                    None,
+                ))
+            }
+        };
+        let timeout = Self::ssl_timeout(context, conn);
+        self.wait_before_next_probe();
+        match conn.negotiate_ssl(&options, timeout) {
+            Ok(()) => {
+                if let Some(ssl) = conn.ssl() {
+                    tracing::debug!(
+                        socket,
+                        version = ssl.version_str(),
+                        cipher = ssl.current_cipher().map(|x| x.name()),
+                        alpn = ?ssl.selected_alpn_protocol().map(String::from_utf8_lossy),
+                        "negotiated SSL/TLS"
+                    );
+                }
+                Ok(NaslValue::Number(socket as i64))
+            }
+            Err(e) => {
+                tracing::debug!(socket, %e, "unable to negotiate SSL/TLS");
+                handles.handles[socket] = NaslSocket::Closed;
+                handles.closed_fd.push(socket);
+                handles.recorded.remove(&socket);
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    fn ssl_timeout(context: &Context, conn: &TcpConnection) -> Duration {
+        match conn.peer_addr() {
+            Ok(peer) => context
+                .network_timeout()
+                .timeout(&[peer.ip()], CONNECT_TIMEOUT),
+            Err(_) => CONNECT_TIMEOUT,
+        }
+    }
+
+    fn ssl_connection(
+        handles: &mut Handles,
+        socket: usize,
+    ) -> Result<Option<&mut TcpConnection>, FunctionErrorKind> {
+        match handles.handles.get_mut(socket) {
+            Some(NaslSocket::Tcp(conn)) => Ok(Some(conn)),
+            Some(NaslSocket::Closed) | None => Err(FunctionErrorKind::WrongArgument(format!(
+                "the given socket FD {socket} does not exist"
+            ))),
+            Some(_) => Ok(None),
+        }
+    }
+
+    /// Checks whether the server of a TLS socket supports secure renegotiation (RFC 5746).
+    ///
+    /// Takes the named argument socket. Returns 1 when the server indicated support in the
+    /// handshake and 0 otherwise. Returns NULL when SSL/TLS was not negotiated with
+    /// socket_negotiate_ssl.
+    #[nasl_function(named(socket))]
+    fn socket_check_ssl_safe_renegotiation(
+        &self,
+        socket: usize,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        Ok(Self::ssl_connection(&mut handles, socket)?
+            .and_then(|x| x.secure_renegotiation())
+            .map_or(NaslValue::Null, |x| NaslValue::Number(x as i64)))
+    }
+
+    /// Renegotiates SSL/TLS on a socket, initiated by the client.
+    ///
+    /// Takes the named argument socket. Returns 1 when the server completed the renegotiation
+    /// and -1 when it refused, ignored or aborted it, the socket is unusable afterwards in that
+    /// case. Together with socket_check_ssl_safe_renegotiation this detects servers allowing
+    /// insecure renegotiation (CVE-2009-3555). Returns NULL when SSL/TLS was not negotiated with
+    /// socket_negotiate_ssl or TLS 1.3 was negotiated, which has no renegotiation.
+    #[nasl_function(named(socket))]
+    fn socket_ssl_do_handshake(
+        &self,
+        context: &Context,
+        socket: usize,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        let Some(conn) = Self::ssl_connection(&mut handles, socket)? else {
+            return Ok(NaslValue::Null);
+        };
+        let timeout = Self::ssl_timeout(context, conn);
+        match conn.renegotiate(timeout) {
+            Ok(Some(true)) => Ok(NaslValue::Number(1)),
+            Ok(Some(false)) => Ok(NaslValue::Number(-1)),
+            Ok(None) => Ok(NaslValue::Null),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(NaslValue::Null),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Tries to resume the SSL/TLS session of a socket on a new connection to the server.
+    ///
+    /// Takes the named arguments socket and ticket. With ticket set to TRUE the session
+    /// ticket issued by the server is offered, otherwise only the session ID. Returns TRUE when
+    /// the server resumed the session and FALSE when it performed a full handshake, did not
+    /// issue a ticket or the connection failed. A TLS 1.3 server sends its tickets after the
+    /// handshake, so data has to be received on the socket before. Returns NULL when SSL/TLS
+    /// was not negotiated with socket_negotiate_ssl.
+    #[nasl_function(named(socket, ticket))]
+    fn socket_resume_ssl_session(
+        &self,
+        context: &Context,
+        socket: usize,
+        ticket: Option<bool>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        let Some(conn) = Self::ssl_connection(&mut handles, socket)? else {
+            return Ok(NaslValue::Null);
+        };
+        let timeout = Self::ssl_timeout(context, conn);
+        self.wait_before_next_probe();
+        match conn.resume_session(ticket.unwrap_or_default(), timeout) {
+            Ok(x) => Ok(x.map_or(NaslValue::Null, NaslValue::Boolean)),
+            Err(e) => {
+                tracing::debug!(socket, %e, "unable to resume SSL/TLS session");
+                Ok(NaslValue::Boolean(false))
+            }
+        }
+    }
+
+    /// Returns the protocol negotiated with ALPN on a TLS socket.
+    ///
+    /// Takes the named argument socket. Returns NULL when the socket is not a TLS socket or the
+    /// server did not select any of the offered protocols.
+    #[nasl_function(named(socket))]
+    fn socket_get_ssl_alpn(&self, socket: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        match handles.handles.get_mut(socket) {
+            Some(NaslSocket::Tcp(conn)) => Ok(conn.alpn_protocol()?.map_or(NaslValue::Null, |x| {
+                NaslValue::String(String::from_utf8_lossy(&x).into_owned())
+            })),
+            Some(NaslSocket::Closed) | None => Err(FunctionErrorKind::WrongArgument(format!(
+                "the given socket FD {socket} does not exist"
+            ))),
+            Some(_) => Ok(NaslValue::Null),
+        }
+    }
+
+    fn peer_certificates(&self, socket: usize) -> Result<Vec<Vec<u8>>, FunctionErrorKind> {
+        let mut handles = self.handles.write().unwrap();
+        match handles.handles.get_mut(socket) {
+            Some(NaslSocket::Tcp(conn)) => Ok(conn.peer_certificates()?),
+            Some(NaslSocket::Closed) | None => Err(FunctionErrorKind::WrongArgument(format!(
+                "the given socket FD {socket} does not exist"
+            ))),
+            Some(_) => Ok(vec![]),
+        }
+    }
+
+    /// Returns the certificate of the server of a TLS socket.
+    ///
+    /// Takes the named argument socket. Returns the DER encoded certificate, which can be opened
+    /// with cert_open, or NULL when the socket is not a TLS socket.
+    #[nasl_function(named(socket))]
+    fn socket_get_cert(&self, socket: usize) -> Result<NaslValue, FunctionErrorKind> {
+        Ok(self
+            .peer_certificates(socket)?
+            .into_iter()
+            .next()
+            .map_or(NaslValue::Null, NaslValue::Data))
+    }
+
+    /// Returns the certificate chain sent by the server of a TLS socket.
+    ///
+    /// Takes the named argument socket. Returns an array of the DER encoded certificates,
+    /// starting with the one of the server followed by the intermediate certificates in the
+    /// order the server sent them. NULL is returned when the socket is not a TLS socket.
+    #[nasl_function(named(socket))]
+    fn get_server_cert(&self, socket: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let certificates = self.peer_certificates(socket)?;
+        if certificates.is_empty() {
+            return Ok(NaslValue::Null);
+        }
+        Ok(NaslValue::Array(
+            certificates.into_iter().map(NaslValue::Data).collect(),
+        ))
+    }
+
+    /// Get the source port of a open socket
+    #[nasl_function]
+    fn get_source_port(&self, socket: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let handles = self.handles.read().unwrap();
+        let socket = handles
+            .handles
+            .get(socket)
+            .ok_or(FunctionErrorKind::WrongArgument(
+                "the given socket FD does not exist".to_string(),
+            ))?;
+        let port = match socket {
+            NaslSocket::Tcp(conn) => conn.local_addr()?.port(),
+            NaslSocket::Udp(conn) => conn.local_addr()?.port(),
+            NaslSocket::Sctp(conn) => conn.local_addr()?.port(),
+            NaslSocket::Replay(_) => {
+                return Err(FunctionErrorKind::Diagnostic(
+                    "replayed connections have no port".to_string(),
+                    None,
+                ))
+            }
+            NaslSocket::Unix(_) => {
+                return Err(FunctionErrorKind::Diagnostic(
+                    "UNIX sockets have no port".to_string(),
+                    None,
+                ))
+            }
+            NaslSocket::Closed => {
+                return Err(FunctionErrorKind::WrongArgument(
+                    "the given socket FD is already closed".to_string(),
+                ))
+            }
+        };
+        Ok(NaslValue::Number(port as i64))
+    }
+
+    /// Reads a possibly multi-line reply, returns the reply code and the last line. None when
+    /// the connection closes or the reply is malformed.
+    fn read_ftp_reply(conn: &mut impl BufRead) -> Option<(usize, String)> {
+        let mut line = String::new();
+        conn.read_line(&mut line).ok()?;
+        let code: usize = line.get(0..3)?.parse().ok()?;
+        if line.as_bytes().get(3) == Some(&b'-') {
+            // a multi-line reply ends with the line beginning with the code and a space
+            let last = format!("{} ", &line[0..3]);
+            let mut lines = 0;
+            while !line.starts_with(&last) {
+                lines += 1;
+                if lines > MAX_FTP_REPLY_LINES {
+                    return None;
+                }
+                line.clear();
+                if conn.read_line(&mut line).ok()? == 0 {
+                    return None;
+                }
+            }
+        }
+        Some((code, line.trim().to_string()))
+    }
+
+    fn ftp_command(
+        conn: &mut TcpConnection,
+        command: &str,
+    ) -> Result<Option<usize>, FunctionErrorKind> {
+        conn.write_all(format!("{command}\r\n").as_bytes())?;
+        Ok(Self::read_ftp_reply(conn).map(|(code, _)| code))
+    }
+
+    /// Runs the FTP exchange on the connection of the socket.
+    ///
+    /// The connection is taken out of the handles for the exchange, so the lock is not held
+    /// while waiting for the server.
+    fn ftp_connection<T>(
+        &self,
+        socket: usize,
+        f: impl FnOnce(&mut TcpConnection) -> Result<T, FunctionErrorKind>,
+    ) -> Result<T, FunctionErrorKind> {
+        let mut conn = {
+            let mut handles = self.handles.write().unwrap();
+            let handle = handles.handles.get_mut(socket).ok_or_else(|| {
+                FunctionErrorKind::WrongArgument(format!(
+                    "the given socket FD {socket} does not exist"
+                ))
+            })?;
+            match std::mem::replace(handle, NaslSocket::Closed) {
+                NaslSocket::Tcp(conn) => conn,
+                NaslSocket::Closed => {
+                    return Err(FunctionErrorKind::WrongArgument(
+                        "the given socket FD is already closed".to_string(),
+                    ))
+                }
+                other => {
+                    *handle = other;
+                    return Err(FunctionErrorKind::Diagnostic(
+                        "This function is only available for TCP connections".to_string(),
+                        None,
+                    ));
+                }
+            }
+        };
+        let result = f(&mut conn);
+        self.handles.write().unwrap().handles[socket] = NaslSocket::Tcp(conn);
+        result
+    }
+
+    /// *any* **ftp_log_in**(user: *string*, pass: *string*, socket: *int*);
+
     /// **ftp_log_in** takes three named arguments:
-    /// - user: is the user name (it has no default value like “anonymous” or “ftp”)
-    /// - pass: is the password (again, no default value like the user e-mail address)
+    /// - user: is the user name, “anonymous” by default
+    /// - pass: is the password, an e-mail address as conventional for anonymous logins by
+    ///   default
     /// - socket: an open socket.
+    ///
+    /// Returns FALSE when the server rejects the login or closes the connection.
     #[nasl_function(named(user, pass, socket))]
-    fn ftp_log_in(&self, user: &str, pass: &str, socket: usize) -> Result<bool, FunctionErrorKind> {
-        match self
+    fn ftp_log_in(
+        &self,
+        user: Option<&str>,
+        pass: Option<&str>,
+        socket: usize,
+    ) -> Result<bool, FunctionErrorKind> {
+        self.ftp_connection(socket, |conn| {
+            if Self::read_ftp_reply(conn).map(|(code, _)| code) != Some(220) {
+                return Ok(false);
+            }
+            let user = user.unwrap_or(FTP_ANONYMOUS_USER);
+            match Self::ftp_command(conn, &format!("USER {user}"))? {
+                Some(230) => Ok(true),
+                Some(331) => Ok(Self::ftp_command(
+                    conn,
+                    &format!("PASS {}", pass.unwrap_or(FTP_ANONYMOUS_PASSWORD)),
+                )? == Some(230)),
+                _ => Ok(false),
+            }
+        })
+    }
+
+    /// *any* **ftp_get_pasv_port**(socket: *int*);
+    ///
+    /// Sends the PASV command and returns the port of the reply, NULL when the server did not
+    /// enter the passive mode.
+    #[nasl_function(named(socket))]
+    fn ftp_get_pasv_port(&self, socket: usize) -> Result<NaslValue, FunctionErrorKind> {
+        self.ftp_connection(socket, |conn| {
+            conn.write_all(b"PASV\r\n")?;
+            Ok(match Self::read_ftp_reply(conn) {
+                Some((227, line)) => parse_pasv_port(&line)
+                    .map(|port| NaslValue::Number(port as i64))
+                    .unwrap_or(NaslValue::Null),
+                _ => NaslValue::Null,
+            })
+        })
+    }
+
+    /// Reads from a stream socket, nothing is returned when the read times out.
+    fn read_stream(
+        &self,
+        context: &Context,
+        socket: usize,
+        data: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, FunctionErrorKind> {
+        let received = match self
             .handles
             .write()
             .unwrap()
@@ -555,42 +1595,830 @@ impl NaslSockets {
             .ok_or(FunctionErrorKind::WrongArgument(format!(
                 "the given socket FD {socket} does not exist"
             )))? {
-            NaslSocket::Tcp(conn) => {
-                Self::check_ftp_response(&mut *conn, &[220])?;
-                let data = format!("USER {}\r\n", user);
-                conn.write_all(data.as_bytes())?;
-
-                let code = Self::check_ftp_response(&mut *conn, &[230, 331])?;
-                if code == 331 {
-                    let data = format!("PASS {}\r\n", pass);
-                    conn.write_all(data.as_bytes())?;
-                    Self::check_ftp_response(&mut *conn, &[230])?;
+            NaslSocket::Tcp(conn) => conn.read_with_timeout(data, timeout),
+            NaslSocket::Unix(conn) => conn.read_with_timeout(data, timeout),
+            NaslSocket::Replay(conn) if conn.transport() == Transport::Tcp => conn.read(data),
+            NaslSocket::Udp(_) | NaslSocket::Sctp(_) | NaslSocket::Replay(_) => {
+                return Err(FunctionErrorKind::Diagnostic(
+                    "This function is only available for TCP connections".to_string(),
+                    None,
+                ))
+            }
+            NaslSocket::Closed => {
+                return Err(FunctionErrorKind::WrongArgument(
+                    "the given socket FD is already closed".to_string(),
+                ))
+            }
+        };
+        let received = match received {
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                ) =>
+            {
+                0
+            }
+            received => received?,
+        };
+        self.record(context, socket, false, &data[..received]);
+        if let Some(stats) = context.script_stats() {
+            stats.received(received);
+        }
+        Ok(received)
+    }
+
+    /// Answers the telnet options of the server until it sends text, returns the text without the
+    /// commands. None when the server sends no text or too many options.
+    fn negotiate_telnet(
+        &self,
+        context: &Context,
+        socket: usize,
+    ) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+        let mut negotiation = Negotiation::default();
+        let mut banner = vec![];
+        let mut data = [0; 1024];
+        loop {
+            let received = self.read_stream(context, socket, &mut data, telnet::TIMEOUT)?;
+            if received == 0 {
+                break;
+            }
+            let (text, replies) = negotiation.feed(&data[..received]);
+            if negotiation.options > telnet::MAX_OPTIONS {
+                tracing::debug!(
+                    socket,
+                    "telnet server sent more than {} options",
+                    telnet::MAX_OPTIONS
+                );
+                return Ok(None);
+            }
+            if !replies.is_empty() {
+                self.write(context, socket, &replies, None, None)?;
+            }
+            banner.extend(text);
+            if !banner.is_empty() {
+                break;
+            }
+        }
+        Ok((!banner.is_empty()).then_some(banner))
+    }
+
+    /// *any* **telnet_init**(*int*);
+    ///
+    /// Performs the telnet option negotiation on the open socket and returns the data received
+    /// afterwards, usually the banner or the login prompt. All options offered by the server are
+    /// refused, except suppressing go ahead. Returns NULL when nothing but options was received.
+    #[nasl_function]
+    fn telnet_init(
+        &self,
+        context: &Context,
+        socket: usize,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        Ok(self
+            .negotiate_telnet(context, socket)?
+            .map(NaslValue::Data)
+            .unwrap_or(NaslValue::Null))
+    }
+
+    /// Opens a plain TCP connection to the port of the target, None when it is closed.
+    fn connect_to_port(
+        &self,
+        context: &Context,
+        port: i64,
+    ) -> Result<Option<usize>, FunctionErrorKind> {
+        Ok(
+            match self.open_tcp(context, port, None, None, None, None, None)? {
+                NaslValue::Number(x) => Some(x as usize),
+                NaslValue::Fork(x) => match x.first() {
+                    Some(NaslValue::Number(x)) => Some(*x as usize),
+                    _ => None,
+                },
+                _ => None,
+            },
+        )
+    }
+
+    /// *any* **get_telnet_banner**(port: *int*);
+    ///
+    /// Connects to the telnet service on the port, 23 by default, and returns its banner. Like the
+    /// function of the telnet_func.inc library the banner is stored in the KB item
+    /// `telnet/banner/<port>` and taken from there on later calls. Returns NULL when the port is
+    /// closed or the service sends no banner.
+    #[nasl_function(named(port))]
+    fn get_telnet_banner(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let port = port.unwrap_or(23);
+        let key = format!("telnet/banner/{port}");
+        if let Some(banner) = get_kb_item(context, &key)? {
+            return Ok(banner);
+        }
+        let Some(socket) = self.connect_to_port(context, port)? else {
+            return Ok(NaslValue::Null);
+        };
+        let banner = self.negotiate_telnet(context, socket);
+        self.close_socket(context, socket)?;
+        let Some(banner) = banner? else {
+            return Ok(NaslValue::Null);
+        };
+        context.dispatcher().dispatch_replace(
+            context.key(),
+            Field::KB(Kb {
+                key,
+                value: Primitive::Data(banner.clone()),
+                expire: None,
+            }),
+        )?;
+        Ok(NaslValue::Data(banner))
+    }
+
+    /// Identifies to the SSH server and reads its key exchange message, None when the service
+    /// does not speak SSH.
+    fn read_kexinit(
+        &self,
+        context: &Context,
+        socket: usize,
+    ) -> Result<Option<KexInit>, FunctionErrorKind> {
+        let mut received = vec![];
+        let mut data = [0; 4096];
+        let mut identified = false;
+        loop {
+            match ssh_kex::parse(&received) {
+                Ok(Progress::Complete(kex)) => return Ok(Some(kex)),
+                Ok(Progress::Identified) if !identified => {
+                    identified = true;
+                    self.write(context, socket, ssh_kex::IDENTIFICATION, None, None)?;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!(socket, error = e, "no SSH key exchange received");
+                    return Ok(None);
                 }
-                Ok(true)
             }
-            NaslSocket::Udp(_) => Err(FunctionErrorKind::Diagnostic(
-                "This function is only available for TCP connections".to_string(),
-                None,
-            )),
-            NaslSocket::Closed => Err(FunctionErrorKind::WrongArgument(
-                "the given socket FD is already closed".to_string(),
-            )),
+            let length = self.read_stream(context, socket, &mut data, ssh_kex::TIMEOUT)?;
+            if length == 0 {
+                return Ok(None);
+            }
+            received.extend_from_slice(&data[..length]);
         }
     }
+
+    /// *array* **ssh_get_server_algorithms**(port: *int*);
+    ///
+    /// Connects to the SSH server on the port, 22 by default, and returns the algorithms offered
+    /// in its key exchange message. The connection is closed before the key exchange, so neither
+    /// credentials nor the SSH library are needed. The array contains the identification of the
+    /// server, the name-lists of RFC 4253 like `kex_algorithms` as lists and
+    /// `first_kex_packet_follows`. Returns NULL when the port is closed or the service does not
+    /// speak SSH.
+    #[nasl_function(named(port))]
+    fn ssh_get_server_algorithms(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let Some(socket) = self.connect_to_port(context, port.unwrap_or(22))? else {
+            return Ok(NaslValue::Null);
+        };
+        let kex = self.read_kexinit(context, socket);
+        self.close_socket(context, socket)?;
+        let Some(kex) = kex? else {
+            return Ok(NaslValue::Null);
+        };
+        let mut result: IndexMap<String, NaslValue> = ssh_kex::NAME_LISTS
+            .iter()
+            .zip(kex.name_lists)
+            .map(|(name, list)| {
+                let list = list.into_iter().map(NaslValue::String).collect();
+                (name.to_string(), NaslValue::Array(list))
+            })
+            .collect();
+        result.insert(
+            "identification".to_string(),
+            NaslValue::String(kex.identification),
+        );
+        result.insert(
+            "first_kex_packet_follows".to_string(),
+            NaslValue::Boolean(kex.first_kex_packet_follows),
+        );
+        Ok(NaslValue::Dict(result))
+    }
+}
+
+/// Parses the port of a reply like `227 Entering Passive Mode (127,0,0,1,4,1)`. Some servers omit
+/// the parentheses, so the numbers are taken from the first digit after the reply code.
+fn parse_pasv_port(line: &str) -> Option<u16> {
+    let text = line.get(4..)?;
+    let text = match text.find('(') {
+        Some(x) => &text[x + 1..],
+        None => &text[text.find(|c: char| c.is_ascii_digit())?..],
+    };
+    let numbers = text
+        .split(',')
+        .take(6)
+        .map(|x| {
+            let digits: String = x.trim().chars().take_while(char::is_ascii_digit).collect();
+            digits.parse::<u8>().ok()
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match numbers.as_slice() {
+        [_, _, _, _, high, low] => Some(u16::from_be_bytes([*high, *low])),
+        _ => None,
+    }
+}
+
+// Not defined with function_set! as send_capture is only available with raw IP support
+impl IntoFunctionSet for NaslSockets {
+    type State = NaslSockets;
+
+    fn into_function_set(self) -> StoredFunctionSet<Self::State> {
+        let mut set = StoredFunctionSet::new(self);
+        internal_call_expr!(
+            sync_stateful,
+            set,
+            (NaslSockets::open_sock_kdc, "open_sock_kdc"),
+            (NaslSockets::open_sock_tcp, "open_sock_tcp"),
+            (NaslSockets::open_sock_udp, "open_sock_udp"),
+            (NaslSockets::open_sock_unix, "open_sock_unix"),
+            (NaslSockets::open_sock_sctp, "open_sock_sctp"),
+            (NaslSockets::open_priv_sock_tcp, "open_priv_sock_tcp"),
+            (NaslSockets::open_priv_sock_udp, "open_priv_sock_udp"),
+            (NaslSockets::close, "close"),
+            (NaslSockets::send, "send"),
+            (NaslSockets::recv, "recv"),
+            (NaslSockets::recv_line, "recv_line"),
+            (NaslSockets::get_source_port, "get_source_port"),
+            (NaslSockets::ftp_log_in, "ftp_log_in"),
+            (NaslSockets::ftp_get_pasv_port, "ftp_get_pasv_port"),
+            (NaslSockets::telnet_init, "telnet_init"),
+            (NaslSockets::get_telnet_banner, "get_telnet_banner"),
+            (
+                NaslSockets::ssh_get_server_algorithms,
+                "ssh_get_server_algorithms"
+            ),
+            (NaslSockets::sctp_send, "sctp_send"),
+            (NaslSockets::sctp_recv, "sctp_recv"),
+            (NaslSockets::socket_negotiate_ssl, "socket_negotiate_ssl"),
+            (NaslSockets::socket_get_ssl_alpn, "socket_get_ssl_alpn"),
+            (
+                NaslSockets::socket_check_ssl_safe_renegotiation,
+                "socket_check_ssl_safe_renegotiation"
+            ),
+            (
+                NaslSockets::socket_ssl_do_handshake,
+                "socket_ssl_do_handshake"
+            ),
+            (
+                NaslSockets::socket_resume_ssl_session,
+                "socket_resume_ssl_session"
+            ),
+            (NaslSockets::socket_get_cert, "socket_get_cert"),
+            (NaslSockets::get_server_cert, "get_server_cert"),
+        );
+        #[cfg(feature = "nasl-builtin-raw-ip")]
+        set.sync_stateful("send_capture", NaslSockets::send_capture);
+        set
+    }
 }
 
-function_set! {
-    NaslSockets,
-    sync_stateful,
-    (
-        (NaslSockets::open_sock_kdc, "open_sock_kdc"),
-        (NaslSockets::open_sock_tcp, "open_sock_tcp"),
-        (NaslSockets::open_sock_udp, "open_sock_udp"),
-        (NaslSockets::close, "close"),
-        (NaslSockets::send, "send"),
-        (NaslSockets::recv, "recv"),
-        (NaslSockets::recv_line, "recv_line"),
-        (NaslSockets::get_source_port, "get_source_port"),
-        (NaslSockets::ftp_log_in, "ftp_log_in"),
-    )
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, UdpSocket},
+        os::unix::net::UnixListener,
+        thread,
+    };
+
+    use futures::StreamExt;
+    use openssl::ssl::{select_next_proto, AlpnError, SslVerifyMode, SslVersion};
+
+    use crate::nasl::builtin::network::ssh_kex::tests::kexinit;
+    use crate::nasl::builtin::network::ssl::tests::{acceptor, echo_server, self_signed};
+    use crate::nasl::test_prelude::*;
+    use crate::nasl::utils::{
+        http_proxy::tests::http_proxy_server,
+        socks_proxy::tests::socks_server,
+        traffic::{Connection, Exchange, Payload, Recording, Transport},
+        HttpProxy, Proxy, SocksProxy, TempDir, Traffic,
+    };
+    use crate::storage::ContextKey;
+
+    fn connection(transport: Transport, port: u16, sent: &[u8], received: &[u8]) -> Connection {
+        Connection {
+            transport,
+            port,
+            exchanges: vec![
+                Exchange::Sent(Payload(sent.to_vec())),
+                Exchange::Received(Payload(received.to_vec())),
+            ],
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let recording = Recording {
+            connections: vec![
+                connection(Transport::Udp, 53, b"query", b"answer"),
+                connection(Transport::Tcp, 513, b"\0root\0", b"\0"),
+            ],
+        };
+        let mut t = TestBuilder::default()
+            .with_context(ContextFactory::default().traffic(Traffic::replay(recording)));
+        t.ok("u = open_sock_udp(53);", 0);
+        t.ok(r#"send(socket: u, data: 'query');"#, 5);
+        t.ok(
+            "recv(socket: u, length: 10);",
+            NaslValue::Data(b"answer".to_vec()),
+        );
+        t.ok("recv(socket: u, length: 10);", NaslValue::Null);
+        t.ok("open_sock_udp(53);", NaslValue::Null);
+        t.ok("s = open_priv_sock_tcp(dport: 513);", 1);
+        check_err_matches!(
+            t,
+            r#"send(socket: s, data: 'root');"#,
+            FunctionErrorKind::Diagnostic(_, _)
+        );
+        t.ok(r#"send(socket: s, data: raw_string(0, "root", 0));"#, 6);
+        t.ok("recv(socket: s, length: 10);", NaslValue::Data(vec![0]));
+    }
+
+    #[test]
+    fn negotiate_ssl() {
+        let mut acceptor = acceptor();
+        acceptor
+            .set_max_proto_version(Some(SslVersion::TLS1_2))
+            .unwrap();
+        let acceptor = acceptor.build();
+        let first = echo_server(acceptor.clone());
+        let second = echo_server(acceptor);
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.ok(format!("s = open_sock_tcp({first}, transport: 1);"), 0);
+        check_err_matches!(
+            t,
+            r#"socket_negotiate_ssl(socket: s, transport: 1);"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+        check_err_matches!(
+            t,
+            r#"socket_negotiate_ssl(socket: s, min_version: "TLSv1.4");"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+        // the server does not support TLS 1.3
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, min_version: "TLSv1.3");"#,
+            NaslValue::Null,
+        );
+        t.ok(format!("s = open_sock_tcp({second}, transport: 1);"), 0);
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, transport: 7, ciphers: "ECDHE-ECDSA-AES256-GCM-SHA384");"#,
+            0,
+        );
+        t.ok("send(socket: s, data: 'ping');", 4);
+        t.ok(
+            "recv(socket: s, length: 4);",
+            NaslValue::Data(b"ping".to_vec()),
+        );
+    }
+
+    #[test]
+    fn negotiate_alpn() {
+        let mut acceptor = acceptor();
+        acceptor.set_alpn_select_callback(|_, client| {
+            select_next_proto(b"\x02h2", client).ok_or(AlpnError::NOACK)
+        });
+        let acceptor = acceptor.build();
+        let first = echo_server(acceptor.clone());
+        let second = echo_server(acceptor);
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.ok(format!("s = open_sock_tcp({first}, transport: 1);"), 0);
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, sni: "localhost", alpn: make_list("h2", "http/1.1"));"#,
+            0,
+        );
+        t.ok("socket_get_ssl_alpn(socket: s);", "h2");
+        t.ok(format!("u = open_sock_tcp({second}, transport: 1);"), 1);
+        t.ok("socket_get_ssl_alpn(socket: u);", NaslValue::Null);
+        t.ok(
+            r#"socket_negotiate_ssl(socket: u, alpn: make_list("http/1.1"));"#,
+            1,
+        );
+        t.ok("socket_get_ssl_alpn(socket: u);", NaslValue::Null);
+        check_err_matches!(
+            t,
+            r#"socket_negotiate_ssl(socket: u, alpn: make_list(""));"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+    #[test]
+    fn server_certificates() {
+        let mut acceptor = acceptor();
+        let (intermediate, _) = self_signed("intermediate");
+        acceptor.add_extra_chain_cert(intermediate).unwrap();
+        let acceptor = acceptor.build();
+        let port = echo_server(acceptor.clone());
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 0);
+        t.ok("socket_get_cert(socket: s);", NaslValue::Null);
+        t.ok("get_server_cert(socket: s);", NaslValue::Null);
+        t.ok("socket_negotiate_ssl(socket: s);", 0);
+        t.run("cert = socket_get_cert(socket: s);");
+        t.ok(r#"cert_query(cert_open(cert), "subject");"#, "CN=localhost");
+        t.run("chain = get_server_cert(socket: s);");
+        t.ok("max_index(chain);", 2);
+        t.ok("chain[0] == cert;", true);
+        t.ok(
+            r#"cert_query(cert_open(chain[1]), "subject");"#,
+            "CN=intermediate",
+        );
+        check_err_matches!(
+            t,
+            "socket_get_cert(socket: 42);",
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+
+    #[test]
+    fn renegotiation() {
+        let mut builder = acceptor();
+        // SSL_OP_ALLOW_CLIENT_RENEGOTIATION, OpenSSL refuses renegotiations initiated by the
+        // client by default
+        builder.set_options(openssl::ssl::SslOptions::from_bits_retain(1 << 8));
+        let port = echo_server(builder.build());
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 0);
+        t.ok("socket_ssl_do_handshake(socket: s);", NaslValue::Null);
+        t.ok(
+            "socket_check_ssl_safe_renegotiation(socket: s);",
+            NaslValue::Null,
+        );
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, max_version: "TLSv1.2");"#,
+            0,
+        );
+        t.ok("socket_check_ssl_safe_renegotiation(socket: s);", 1);
+        t.ok("socket_ssl_do_handshake(socket: s);", 1);
+        t.ok("send(socket: s, data: 'ping');", 4);
+        t.ok(
+            "recv(socket: s, length: 4);",
+            NaslValue::Data(b"ping".to_vec()),
+        );
+
+        let mut acceptor = acceptor();
+        acceptor.set_options(openssl::ssl::SslOptions::NO_RENEGOTIATION);
+        let acceptor = acceptor.build();
+        let port = echo_server(acceptor.clone());
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 1);
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, max_version: "TLSv1.2");"#,
+            1,
+        );
+        t.ok("socket_ssl_do_handshake(socket: s);", -1);
+
+        let port = echo_server(acceptor);
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 2);
+        t.ok("socket_negotiate_ssl(socket: s);", 2);
+        // TLS 1.3 has no renegotiation
+        t.ok("socket_ssl_do_handshake(socket: s);", NaslValue::Null);
+        t.ok("send(socket: s, data: 'ping');", 4);
+        t.ok(
+            "recv(socket: s, length: 4);",
+            NaslValue::Data(b"ping".to_vec()),
+        );
+    }
+
+    /// Accepts TLS connections, each on its own thread, until the client closes them.
+    fn resumption_server(acceptor: openssl::ssl::SslAcceptor, connections: usize) -> u16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for tcp in listener.incoming().take(connections).flatten() {
+                let acceptor = acceptor.clone();
+                std::thread::spawn(move || {
+                    if let Ok(mut tls) = acceptor.accept(tcp) {
+                        let _ = std::io::Read::read(&mut tls, &mut [0; 64]);
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn ftp_server(replies: &'static [&'static str]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(tcp.try_clone().unwrap());
+            let mut replies = replies.iter();
+            tcp.write_all(replies.next().unwrap().as_bytes()).unwrap();
+            for reply in replies {
+                let mut command = String::new();
+                std::io::BufRead::read_line(&mut reader, &mut command).unwrap();
+                tcp.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn ftp() {
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        let port = ftp_server(&[
+            "220-Welcome\r\n220 ready\r\n",
+            "331 password required\r\n",
+            "230 logged in\r\n",
+            "227 Entering Passive Mode (127,0,0,1,4,1)\r\n",
+            "500 unknown command\r\n",
+        ]);
+        t.ok(format!("s = open_sock_tcp({port});"), 0);
+        t.ok(
+            r#"ftp_log_in(socket: s, user: "anonymous", pass: "a@b.c");"#,
+            true,
+        );
+        t.ok("ftp_get_pasv_port(socket: s);", 1025);
+        t.ok("ftp_get_pasv_port(socket: s);", NaslValue::Null);
+
+        let port = ftp_server(&["220 ready\r\n", "530 anonymous access denied\r\n"]);
+        t.ok(format!("s = open_sock_tcp({port});"), 1);
+        t.ok("ftp_log_in(socket: s);", false);
+    }
+
+    fn telnet_server(connections: usize) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for mut tcp in listener.incoming().take(connections).flatten() {
+                // DO TERMINAL-TYPE, WILL SUPPRESS-GO-AHEAD
+                tcp.write_all(&[255, 253, 24, 255, 251, 3]).unwrap();
+                let mut replies = [0; 6];
+                tcp.read_exact(&mut replies).unwrap();
+                assert_eq!(replies, [255, 252, 24, 255, 253, 3]);
+                tcp.write_all(b"router login: ").unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn telnet() {
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        let port = telnet_server(2);
+        t.ok(format!("s = open_sock_tcp({port});"), 0);
+        t.ok("telnet_init(s);", "router login: ".as_bytes());
+        t.ok(
+            format!("get_telnet_banner(port: {port});"),
+            "router login: ".as_bytes(),
+        );
+        t.ok(
+            format!(r#"get_kb_item("telnet/banner/{port}");"#),
+            "router login: ".as_bytes(),
+        );
+    }
+
+    #[test]
+    fn ssh_server_algorithms() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            tcp.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+            let mut identification = [0; 17];
+            tcp.read_exact(&mut identification).unwrap();
+            assert_eq!(&identification, b"SSH-2.0-OpenVAS\r\n");
+            tcp.write_all(&kexinit()).unwrap();
+        });
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.run(format!("a = ssh_get_server_algorithms(port: {port});"));
+        t.ok(r#"a["identification"];"#, "SSH-2.0-OpenSSH_9.6");
+        t.ok(
+            r#"a["kex_algorithms"][1];"#,
+            "diffie-hellman-group14-sha256",
+        );
+        t.ok(r#"max_index(a["languages_server_to_client"]);"#, 0);
+        t.ok(r#"a["first_kex_packet_follows"];"#, false);
+    }
+
+    #[test]
+    fn unix_socket_of_target() {
+        let path = std::env::temp_dir().join(format!("nasl-target-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"ping").unwrap();
+        });
+        let target = format!("unix://{}", path.display());
+        let mut t = TestBuilder::default().with_context_key(ContextKey::FileName(target));
+        t.run(format!(r#"s = open_sock_unix("{}");"#, path.display()));
+        t.ok(
+            "recv(socket: s, length: 4);",
+            NaslValue::Data(b"ping".to_vec()),
+        );
+        check_err_matches!(
+            t,
+            r#"open_sock_unix("/run/other.sock");"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+        drop(t);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        check_err_matches!(
+            t,
+            r#"open_sock_unix("/run/other.sock");"#,
+            FunctionErrorKind::Diagnostic(..)
+        );
+    }
+
+    #[test]
+    fn pasv_port() {
+        assert_eq!(
+            super::parse_pasv_port("227 Entering Passive Mode (10,0,0,1,195,80)."),
+            Some(50000)
+        );
+        assert_eq!(super::parse_pasv_port("227 =10,0,0,1,0,21"), Some(21));
+        assert_eq!(
+            super::parse_pasv_port("227 Entering Passive Mode (10,0,0,1)"),
+            None
+        );
+    }
+
+    #[test]
+    fn session_resumption() {
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        let port = resumption_server(acceptor().build(), 3);
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 0);
+        t.ok("socket_resume_ssl_session(socket: s);", NaslValue::Null);
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, max_version: "TLSv1.2");"#,
+            0,
+        );
+        t.ok("socket_resume_ssl_session(socket: s, ticket: TRUE);", true);
+        // sessions for which a ticket was issued are not cached by the server
+        t.ok("socket_resume_ssl_session(socket: s);", false);
+
+        let mut acceptor = acceptor();
+        acceptor.set_options(openssl::ssl::SslOptions::NO_TICKET);
+        let port = resumption_server(acceptor.build(), 2);
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 1);
+        t.ok(
+            r#"socket_negotiate_ssl(socket: s, max_version: "TLSv1.2");"#,
+            1,
+        );
+        t.ok("socket_resume_ssl_session(socket: s, ticket: TRUE);", false);
+        t.ok("socket_resume_ssl_session(socket: s);", true);
+    }
+
+    #[test]
+    fn negotiate_client_certificate() {
+        let (cert, key) = self_signed("client");
+        let files = TempDir::default();
+        files.write("client.crt", &cert.to_pem().unwrap()).unwrap();
+        files
+            .write("client.key", &key.private_key_to_pem_pkcs8().unwrap())
+            .unwrap();
+        let mut acceptor = acceptor();
+        acceptor.set_verify_callback(
+            SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+            |_, _| true,
+        );
+        let acceptor = acceptor.build();
+        let port = echo_server(acceptor.clone());
+        let second = echo_server(acceptor);
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        for (name, file) in [("SSL/cert", "client.crt"), ("SSL/key", "client.key")] {
+            let path = files.resolve(file).unwrap();
+            t.run(format!(
+                r#"set_kb_item(name: "{name}", value: "{}");"#,
+                path.display()
+            ));
+        }
+        t.ok(format!("s = open_sock_tcp({port}, transport: 1);"), 0);
+        t.ok("socket_negotiate_ssl(socket: s);", 0);
+        t.ok("send(socket: s, data: 'ping');", 4);
+        t.ok(
+            "recv(socket: s, length: 4);",
+            NaslValue::Data(b"ping".to_vec()),
+        );
+        t.run(r#"replace_kb_item(name: "SSL/key", value: "/nonexistent");"#);
+        t.ok(format!("u = open_sock_tcp({second}, transport: 1);"), 1);
+        check_err_matches!(
+            t,
+            "socket_negotiate_ssl(socket: u);",
+            FunctionErrorKind::Diagnostic(_, _)
+        );
+    }
+
+    #[test]
+    fn socks5_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            let mut buf = [0; 4];
+            tcp.read_exact(&mut buf).unwrap();
+            tcp.write_all(&buf).unwrap();
+        });
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let udp_port = udp.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buf = [0; 16];
+            let (size, origin) = udp.recv_from(&mut buf).unwrap();
+            udp.send_to(&buf[..size], origin).unwrap();
+        });
+        let server = socks_server(Some(("user", "secret")));
+        let proxy = SocksProxy::new(
+            "127.0.0.1",
+            server.port(),
+            Some(("user".to_string(), "secret".to_string())),
+        );
+        let t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        let context = t.context().with_proxy(Some(Proxy::Socks5(proxy)));
+        let code = format!(
+            r#"
+            t = open_sock_tcp({tcp_port}, transport: 1);
+            send(socket: t, data: 'ping');
+            recv(socket: t, length: 4);
+            u = open_sock_udp({udp_port});
+            send(socket: u, data: 'pong');
+            recv(socket: u, length: 4);
+            open_sock_sctp({tcp_port});
+            "#
+        );
+        let results: Vec<_> = futures::executor::block_on(
+            t.results_stream(&code, &context)
+                .map(|x| x.unwrap())
+                .collect(),
+        );
+        assert_eq!(
+            results,
+            [
+                NaslValue::Number(0),
+                NaslValue::Number(4),
+                NaslValue::Data(b"ping".to_vec()),
+                NaslValue::Number(1),
+                NaslValue::Number(4),
+                NaslValue::Data(b"pong".to_vec()),
+                NaslValue::Null,
+            ]
+        );
+    }
+
+    #[test]
+    fn http_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            tcp.write_all(b"220 ready\r\n").unwrap();
+            let mut buf = [0; 4];
+            tcp.read_exact(&mut buf).unwrap();
+            tcp.write_all(&buf).unwrap();
+        });
+        let server = http_proxy_server(None);
+        let proxy = HttpProxy::new("127.0.0.1", server.port(), None);
+        let t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        let context = t.context().with_proxy(Some(Proxy::Http(proxy)));
+        let code = format!(
+            r#"
+            t = open_sock_tcp({port}, transport: 1);
+            recv_line(socket: t, length: 64);
+            send(socket: t, data: 'ping');
+            recv(socket: t, length: 4);
+            open_sock_udp({port});
+            open_priv_sock_udp(dport: {port});
+            "#
+        );
+        let results: Vec<_> = futures::executor::block_on(
+            t.results_stream(&code, &context)
+                .map(|x| x.unwrap())
+                .collect(),
+        );
+        assert_eq!(
+            results,
+            [
+                NaslValue::Number(0),
+                NaslValue::Data(b"220 ready\r\n".to_vec()),
+                NaslValue::Number(4),
+                NaslValue::Data(b"ping".to_vec()),
+                NaslValue::Null,
+                NaslValue::Null,
+            ]
+        );
+    }
 }
@@ -0,0 +1,218 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Reads the algorithms offered by a SSH server in its first key exchange message (RFC 4253).
+//!
+//! Only the unencrypted start of the transport layer protocol is implemented, so the algorithms
+//! are available without a SSH library and without authenticating.
+
+use std::time::Duration;
+
+/// Identification string sent to the server
+pub const IDENTIFICATION: &[u8] = b"SSH-2.0-OpenVAS\r\n";
+
+/// Time to wait for the next data of the server
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum length of the lines the server may send before its identification
+pub const MAX_PREAMBLE: usize = 8192;
+
+/// Maximum length of a packet every implementation must be able to process
+const MAX_PACKET: usize = 35000;
+
+const SSH_MSG_KEXINIT: u8 = 20;
+
+/// Names of the name-lists of the key exchange message in their order
+pub const NAME_LISTS: [&str; 10] = [
+    "kex_algorithms",
+    "server_host_key_algorithms",
+    "encryption_algorithms_client_to_server",
+    "encryption_algorithms_server_to_client",
+    "mac_algorithms_client_to_server",
+    "mac_algorithms_server_to_client",
+    "compression_algorithms_client_to_server",
+    "compression_algorithms_server_to_client",
+    "languages_client_to_server",
+    "languages_server_to_client",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KexInit {
+    /// Identification of the server without the line ending, e.g. `SSH-2.0-OpenSSH_9.6`
+    pub identification: String,
+    /// The name-lists in the order of [NAME_LISTS]
+    pub name_lists: Vec<Vec<String>>,
+    pub first_kex_packet_follows: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// The identification of the server is incomplete
+    Connected,
+    /// The server identified itself, its key exchange message is incomplete
+    Identified,
+    Complete(KexInit),
+}
+
+/// Returns the identification and the length of the data up to the end of its line.
+pub fn identification(data: &[u8]) -> Result<Option<(String, usize)>, &'static str> {
+    let mut start = 0;
+    while let Some(end) = data[start..].iter().position(|x| *x == b'\n') {
+        let line = &data[start..start + end];
+        start += end + 1;
+        if line.starts_with(b"SSH-") {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            return Ok(Some((String::from_utf8_lossy(line).into_owned(), start)));
+        }
+    }
+    if data.len() > MAX_PREAMBLE {
+        return Err("no SSH identification received");
+    }
+    Ok(None)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < length {
+            return Err("truncated key exchange message");
+        }
+        let (result, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(result)
+    }
+
+    fn u32(&mut self) -> Result<usize, &'static str> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn name_list(&mut self) -> Result<Vec<String>, &'static str> {
+        let length = self.u32()?;
+        let names = String::from_utf8_lossy(self.take(length)?);
+        Ok(names
+            .split(',')
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_string())
+            .collect())
+    }
+}
+
+/// Parses the data received so far, errors when the server does not speak SSH or sends another
+/// message than the key exchange message.
+pub fn parse(data: &[u8]) -> Result<Progress, &'static str> {
+    let Some((identification, start)) = identification(data)? else {
+        return Ok(Progress::Connected);
+    };
+    if !identification.starts_with("SSH-2.0-") && !identification.starts_with("SSH-1.99-") {
+        return Err("unsupported SSH protocol version");
+    }
+    let mut reader = Reader(&data[start..]);
+    let Ok(length) = reader.u32() else {
+        return Ok(Progress::Identified);
+    };
+    if !(5..=MAX_PACKET).contains(&length) {
+        return Err("invalid packet length");
+    }
+    let Ok(packet) = reader.take(length) else {
+        return Ok(Progress::Identified);
+    };
+    let padding = packet[0] as usize;
+    if padding + 1 > packet.len() {
+        return Err("invalid padding length");
+    }
+    let mut payload = Reader(&packet[1..packet.len() - padding]);
+    if payload.take(1)? != [SSH_MSG_KEXINIT] {
+        return Err("unexpected message instead of the key exchange");
+    }
+    // cookie
+    payload.take(16)?;
+    let name_lists = NAME_LISTS
+        .iter()
+        .map(|_| payload.name_list())
+        .collect::<Result<_, _>>()?;
+    let first_kex_packet_follows = payload.take(1)?[0] != 0;
+    Ok(Progress::Complete(KexInit {
+        identification,
+        name_lists,
+        first_kex_packet_follows,
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn name_list(names: &str) -> Vec<u8> {
+        let mut result = (names.len() as u32).to_be_bytes().to_vec();
+        result.extend(names.as_bytes());
+        result
+    }
+
+    /// Key exchange message offering one algorithm of each kind, curve25519 and dh group 14 for
+    /// the key exchange
+    pub fn kexinit() -> Vec<u8> {
+        let mut payload = vec![SSH_MSG_KEXINIT];
+        payload.extend([0; 16]);
+        payload.extend(name_list("curve25519-sha256,diffie-hellman-group14-sha256"));
+        payload.extend(name_list("ssh-ed25519"));
+        for _ in 0..2 {
+            payload.extend(name_list("aes128-ctr"));
+        }
+        for _ in 0..2 {
+            payload.extend(name_list("hmac-sha2-256"));
+        }
+        for _ in 0..2 {
+            payload.extend(name_list("none"));
+        }
+        for _ in 0..2 {
+            payload.extend(name_list(""));
+        }
+        payload.extend([0, 0, 0, 0, 0]);
+        let padding = 8 - (payload.len() + 5) % 8 + 4;
+        let mut packet = ((payload.len() + padding + 1) as u32)
+            .to_be_bytes()
+            .to_vec();
+        packet.push(padding as u8);
+        packet.extend(payload);
+        packet.extend(vec![0; padding]);
+        packet
+    }
+
+    #[test]
+    fn complete() {
+        let mut data = b"Welcome\r\nSSH-2.0-OpenSSH_9.6\r\n".to_vec();
+        data.extend(kexinit());
+        let Progress::Complete(kex) = parse(&data).unwrap() else {
+            panic!("incomplete key exchange message");
+        };
+        assert_eq!(kex.identification, "SSH-2.0-OpenSSH_9.6");
+        assert_eq!(
+            kex.name_lists[0],
+            ["curve25519-sha256", "diffie-hellman-group14-sha256"]
+        );
+        assert_eq!(kex.name_lists[1], ["ssh-ed25519"]);
+        assert!(kex.name_lists[9].is_empty());
+        assert!(!kex.first_kex_packet_follows);
+    }
+
+    #[test]
+    fn incomplete() {
+        let mut data = b"SSH-2.0-OpenSSH_9.6\r\n".to_vec();
+        assert_eq!(parse(&data[..10]), Ok(Progress::Connected));
+        assert_eq!(parse(&data), Ok(Progress::Identified));
+        data.extend(kexinit());
+        assert_eq!(parse(&data[..data.len() - 1]), Ok(Progress::Identified));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse(b"SSH-1.5-Server\r\n").is_err());
+        assert!(parse(&[b'x'; MAX_PREAMBLE + 1]).is_err());
+        let mut data = b"SSH-2.0-x\r\n".to_vec();
+        data.extend([0, 0, 0, 12, 4, 21, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(parse(&data).is_err());
+    }
+}
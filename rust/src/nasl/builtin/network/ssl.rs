@@ -0,0 +1,482 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! TLS transport negotiated on an open socket by socket_negotiate_ssl.
+//!
+//! The TLS of open_sock_tcp only connects to services. Checks for SSL/TLS weaknesses like
+//! POODLE or SWEET32 instead have to offer exactly the protocol versions and ciphers they test
+//! for, including outdated ones. Therefore this transport is based on OpenSSL with the security
+//! level lowered to 0 and does not verify the certificate of the server. Legacy renegotiation
+//! is allowed as well, so that servers lacking secure renegotiation can be detected.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    ptr,
+    time::Duration,
+};
+
+use foreign_types::ForeignTypeRef;
+use libc::c_int;
+use openssl::error::ErrorStack;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{
+    HandshakeError, Ssl, SslContext, SslContextBuilder, SslMethod, SslOptions as OpensslOptions,
+    SslRef, SslStream, SslVerifyMode, SslVersion,
+};
+use openssl::x509::X509;
+
+use super::OpenvasEncaps;
+
+/// Protocol versions and ciphers offered in the handshake
+#[derive(Debug, Clone, Default)]
+pub struct SslOptions {
+    /// Lowest offered version, by default the lowest supported one
+    pub min_version: Option<SslVersion>,
+    /// Highest offered version, by default the highest supported one
+    pub max_version: Option<SslVersion>,
+    /// OpenSSL cipher names separated by colons, TLS 1.3 cipher suites start with TLS_
+    pub ciphers: Option<String>,
+    /// Server name sent in the SNI extension, by default none is sent
+    pub sni: Option<String>,
+    /// Protocols offered with ALPN in order of preference, by default none are offered
+    pub alpn: Vec<String>,
+    /// Certificate sent when the server asks for one
+    pub client_certificate: Option<ClientCertificate>,
+}
+
+/// Client certificate with its chain and private key
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    chain: Vec<X509>,
+    key: PKey<Private>,
+}
+
+impl ClientCertificate {
+    /// Parses the PEM encoded certificate, optionally followed by its chain, and key.
+    ///
+    /// An encrypted key is decrypted with the password.
+    pub fn from_pem(certificate: &[u8], key: &[u8], password: &str) -> io::Result<Self> {
+        let invalid = |e: openssl::error::ErrorStack| {
+            io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        };
+        let chain = X509::stack_from_pem(certificate).map_err(invalid)?;
+        if chain.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no certificate found",
+            ));
+        }
+        let key =
+            PKey::private_key_from_pem_passphrase(key, password.as_bytes()).map_err(invalid)?;
+        Ok(Self { chain, key })
+    }
+
+    /// Loads the PEM encoded certificate and key from files.
+    pub fn load(certificate: &str, key: &str, password: &str) -> io::Result<Self> {
+        Self::from_pem(&fs::read(certificate)?, &fs::read(key)?, password)
+    }
+}
+
+impl SslOptions {
+    /// Returns the versions offered for the encapsulation, like the C implementation does.
+    ///
+    /// Returns None when the encapsulation is not SSL/TLS.
+    pub fn from_encaps(encaps: &OpenvasEncaps) -> Option<Self> {
+        let (min_version, max_version) = match encaps {
+            OpenvasEncaps::Ip => return None,
+            OpenvasEncaps::Auto | OpenvasEncaps::TlsCustom => (None, None),
+            OpenvasEncaps::Ssl23 | OpenvasEncaps::Ssl2 | OpenvasEncaps::Max => {
+                (Some(SslVersion::SSL3), Some(SslVersion::TLS1))
+            }
+            OpenvasEncaps::Ssl3 => (Some(SslVersion::SSL3), Some(SslVersion::SSL3)),
+            OpenvasEncaps::Tls1 => (Some(SslVersion::TLS1), Some(SslVersion::TLS1)),
+            OpenvasEncaps::Tls11 => (Some(SslVersion::TLS1_1), Some(SslVersion::TLS1_1)),
+            OpenvasEncaps::Tls12 => (Some(SslVersion::TLS1_2), Some(SslVersion::TLS1_2)),
+            OpenvasEncaps::Tls13 => (Some(SslVersion::TLS1_3), Some(SslVersion::TLS1_3)),
+        };
+        Some(Self {
+            min_version,
+            max_version,
+            ciphers: None,
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses a protocol version given as SSLv3, TLSv1, TLSv1.0, TLSv1.1, TLSv1.2 or TLSv1.3.
+pub fn parse_version(version: &str) -> Option<SslVersion> {
+    match version {
+        "SSLv3" => Some(SslVersion::SSL3),
+        "TLSv1" | "TLSv1.0" => Some(SslVersion::TLS1),
+        "TLSv1.1" => Some(SslVersion::TLS1_1),
+        "TLSv1.2" => Some(SslVersion::TLS1_2),
+        "TLSv1.3" => Some(SslVersion::TLS1_3),
+        _ => None,
+    }
+}
+
+// not exposed by openssl-sys
+extern "C" {
+    fn SSL_renegotiate(ssl: *mut openssl_sys::SSL) -> c_int;
+    fn SSL_SESSION_has_ticket(session: *const openssl_sys::SSL_SESSION) -> c_int;
+}
+
+/// Control command behind the SSL_get_secure_renegotiation_support macro
+const SSL_CTRL_GET_RI_SUPPORT: c_int = 76;
+
+/// Encodes the protocols as length-prefixed list, as sent in the ALPN extension.
+fn alpn_wire_format(protocols: &[String]) -> io::Result<Vec<u8>> {
+    let mut wire = Vec::new();
+    for protocol in protocols {
+        let len = u8::try_from(protocol.len())
+            .ok()
+            .filter(|x| *x > 0)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid ALPN protocol {protocol:?}"),
+                )
+            })?;
+        wire.push(len);
+        wire.extend_from_slice(protocol.as_bytes());
+    }
+    Ok(wire)
+}
+
+fn context(options: &SslOptions) -> io::Result<SslContextBuilder> {
+    let mut builder = SslContext::builder(SslMethod::tls_client()).map_err(io::Error::other)?;
+    builder.set_verify(SslVerifyMode::NONE);
+    builder.set_security_level(0);
+    // connects to and renegotiates with servers lacking secure renegotiation
+    builder.set_options(
+        OpensslOptions::from_bits_retain(openssl_sys::SSL_OP_LEGACY_SERVER_CONNECT as _)
+            | OpensslOptions::ALLOW_UNSAFE_LEGACY_RENEGOTIATION,
+    );
+    let mut min_version = options.min_version;
+    match &options.ciphers {
+        Some(ciphers) => {
+            let (tls13, legacy): (Vec<&str>, Vec<&str>) = ciphers
+                .split(':')
+                .filter(|x| !x.is_empty())
+                .partition(|x| x.starts_with("TLS_"));
+            // an empty list disables TLS 1.3
+            builder
+                .set_ciphersuites(&tls13.join(":"))
+                .map_err(io::Error::other)?;
+            if legacy.is_empty() {
+                min_version = Some(SslVersion::TLS1_3);
+            } else {
+                builder
+                    .set_cipher_list(&format!("{}:@SECLEVEL=0", legacy.join(":")))
+                    .map_err(io::Error::other)?;
+            }
+        }
+        None => builder
+            .set_cipher_list("ALL:@SECLEVEL=0")
+            .map_err(io::Error::other)?,
+    }
+    if let Some(client) = &options.client_certificate {
+        builder
+            .set_certificate(&client.chain[0])
+            .map_err(io::Error::other)?;
+        for cert in &client.chain[1..] {
+            builder
+                .add_extra_chain_cert(cert.clone())
+                .map_err(io::Error::other)?;
+        }
+        builder
+            .set_private_key(&client.key)
+            .map_err(io::Error::other)?;
+        builder
+            .check_private_key()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    }
+    if !options.alpn.is_empty() {
+        builder
+            .set_alpn_protos(&alpn_wire_format(&options.alpn)?)
+            .map_err(io::Error::other)?;
+    }
+    builder
+        .set_min_proto_version(min_version)
+        .map_err(io::Error::other)?;
+    builder
+        .set_max_proto_version(options.max_version)
+        .map_err(io::Error::other)?;
+    Ok(builder)
+}
+
+fn handshake<S: Read + Write>(
+    context: &SslContext,
+    stream: S,
+    options: &SslOptions,
+    session: Option<&openssl::ssl::SslSessionRef>,
+) -> io::Result<SslStream<S>> {
+    let mut ssl = Ssl::new(context).map_err(io::Error::other)?;
+    if let Some(sni) = &options.sni {
+        ssl.set_hostname(sni).map_err(io::Error::other)?;
+    }
+    if let Some(session) = session {
+        // the session was established by a context with the same options
+        unsafe { ssl.set_session(session) }.map_err(io::Error::other)?;
+    }
+    ssl.connect(stream).map_err(|e| match e {
+        HandshakeError::Failure(e) | HandshakeError::WouldBlock(e) => {
+            match e.into_error().into_io_error() {
+                Ok(e) => e,
+                Err(e) => io::Error::other(e.to_string()),
+            }
+        }
+        HandshakeError::SetupFailure(e) => io::Error::other(e),
+    })
+}
+
+/// Performs the TLS handshake on the connected stream.
+///
+/// The handshake is bound by the read and write timeouts of the stream. Protocols tunneling
+/// the handshake, like TDS, pass a stream framing the TLS records.
+pub fn connect<S: Read + Write>(stream: S, options: &SslOptions) -> io::Result<SslStream<S>> {
+    handshake(&context(options)?.build(), stream, options, None)
+}
+
+/// Returns true when the server indicated support of secure renegotiation (RFC 5746).
+pub fn secure_renegotiation(ssl: &SslRef) -> bool {
+    unsafe { openssl_sys::SSL_ctrl(ssl.as_ptr(), SSL_CTRL_GET_RI_SUPPORT, 0, ptr::null_mut()) != 0 }
+}
+
+/// Starts a renegotiation on the connection and waits for the handshake to complete.
+///
+/// Returns false when the server refuses, ignores or aborts the renegotiation. The connection
+/// is unusable afterwards in that case. Fails for TLS 1.3, which has no renegotiation.
+pub fn renegotiate(stream: &mut SslStream<TcpStream>) -> io::Result<bool> {
+    if stream.ssl().version2() == Some(SslVersion::TLS1_3) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TLS 1.3 does not support renegotiation",
+        ));
+    }
+    if unsafe { SSL_renegotiate(stream.ssl().as_ptr()) } != 1 {
+        return Err(io::Error::other(ErrorStack::get()));
+    }
+    match stream.do_handshake() {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            tracing::debug!(%e, "renegotiation failed");
+            Ok(false)
+        }
+    }
+}
+
+/// Connects to the peer again offering the session of the connection and returns true when
+/// the server resumes it instead of performing a full handshake.
+///
+/// The new connection is opened by connect, e.g. through the proxy of the first one.
+/// With ticket the session ticket issued by the server is offered, false is returned when
+/// the server did not issue one. Otherwise only the session ID is offered. A TLS 1.3 server
+/// sends its tickets after the handshake, so data has to be received before.
+pub fn resume(
+    ssl: &SslRef,
+    connect: impl FnOnce() -> io::Result<TcpStream>,
+    options: &SslOptions,
+    ticket: bool,
+    timeout: Duration,
+) -> io::Result<bool> {
+    let Some(session) = ssl.session() else {
+        return Ok(false);
+    };
+    if ticket && unsafe { SSL_SESSION_has_ticket(session.as_ptr()) } == 0 {
+        return Ok(false);
+    }
+    let mut context = context(options)?;
+    if !ticket {
+        context.set_options(OpensslOptions::NO_TICKET);
+    }
+    let tcp = connect()?;
+    tcp.set_read_timeout(Some(timeout))?;
+    tcp.set_write_timeout(Some(timeout))?;
+    let mut stream = handshake(&context.build(), tcp, options, Some(session))?;
+    let resumed = stream.ssl().session_reused();
+    // the server may have closed the connection already
+    let _ = stream.shutdown();
+    Ok(resumed)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use openssl::{
+        asn1::Asn1Time,
+        ec::{EcGroup, EcKey},
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::{PKey, Private},
+        ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod, SslVerifyMode, SslVersion},
+        x509::{X509Name, X509},
+    };
+
+    use super::{alpn_wire_format, connect, parse_version, ClientCertificate, SslOptions};
+
+    /// Returns a self-signed certificate for the name and its key.
+    pub(crate) fn self_signed(name: &str) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut subject = X509Name::builder().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_issuer_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        (cert.build(), key)
+    }
+
+    /// Returns an acceptor with a self-signed certificate for localhost.
+    pub(crate) fn acceptor() -> SslAcceptorBuilder {
+        let (cert, key) = self_signed("localhost");
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor
+    }
+
+    /// Accepts a single TLS connection and echoes the first message.
+    pub(crate) fn echo_server(acceptor: SslAcceptor) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            if let Ok(mut tls) = acceptor.accept(tcp) {
+                let mut buf = [0; 64];
+                let n = tls.read(&mut buf).unwrap();
+                tls.write_all(&buf[..n]).unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn versions() {
+        let mut acceptor = acceptor();
+        acceptor
+            .set_max_proto_version(Some(SslVersion::TLS1_2))
+            .unwrap();
+        let port = echo_server(acceptor.build());
+        let tcp = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let options = SslOptions {
+            min_version: parse_version("TLSv1.3"),
+            ..Default::default()
+        };
+        assert!(connect(tcp, &options).is_err());
+
+        let port = echo_server(self::acceptor().build());
+        let tcp = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let options = SslOptions {
+            max_version: parse_version("TLSv1.2"),
+            ciphers: Some("ECDHE-ECDSA-AES128-GCM-SHA256".to_string()),
+            ..Default::default()
+        };
+        let mut tls = connect(tcp, &options).unwrap();
+        assert_eq!(tls.ssl().version_str(), "TLSv1.2");
+        assert_eq!(
+            tls.ssl().current_cipher().unwrap().name(),
+            "ECDHE-ECDSA-AES128-GCM-SHA256"
+        );
+        assert_eq!(tls.ssl().selected_alpn_protocol(), None);
+        tls.write_all(b"ping").unwrap();
+        let mut buf = [0; 4];
+        tls.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[test]
+    fn sni_and_alpn() {
+        let mut acceptor = acceptor();
+        acceptor.set_servername_callback(|ssl, _| {
+            match ssl.servername(openssl::ssl::NameType::HOST_NAME) {
+                Some("www.example.com") => Ok(()),
+                _ => Err(openssl::ssl::SniError::ALERT_FATAL),
+            }
+        });
+        acceptor.set_alpn_select_callback(|_, client| {
+            openssl::ssl::select_next_proto(b"\x02h2", client).ok_or(openssl::ssl::AlpnError::NOACK)
+        });
+        let acceptor = acceptor.build();
+
+        let port = echo_server(acceptor.clone());
+        let tcp = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        assert!(connect(tcp, &SslOptions::default()).is_err());
+
+        let port = echo_server(acceptor);
+        let tcp = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let options = SslOptions {
+            sni: Some("www.example.com".to_string()),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+            ..Default::default()
+        };
+        let tls = connect(tcp, &options).unwrap();
+        assert_eq!(tls.ssl().selected_alpn_protocol(), Some(&b"h2"[..]));
+        assert!(alpn_wire_format(&["".to_string()]).is_err());
+    }
+
+    #[test]
+    fn client_certificate() {
+        let (cert, key) = self_signed("client");
+        let mut acceptor = acceptor();
+        acceptor.set_verify_callback(
+            SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+            |_, _| true,
+        );
+        let acceptor = acceptor.build();
+
+        let port = echo_server(acceptor.clone());
+        let mut tls = connect(
+            TcpStream::connect(("127.0.0.1", port)).unwrap(),
+            &SslOptions::default(),
+        )
+        .unwrap();
+        // TLS 1.3 servers reject a missing certificate after the handshake
+        let mut buf = [0; 4];
+        assert!(tls
+            .write_all(b"ping")
+            .and_then(|_| tls.read_exact(&mut buf))
+            .is_err());
+
+        let port = echo_server(acceptor);
+        let client = ClientCertificate::from_pem(
+            &cert.to_pem().unwrap(),
+            &key.private_key_to_pem_pkcs8_passphrase(
+                openssl::symm::Cipher::aes_128_cbc(),
+                b"secret",
+            )
+            .unwrap(),
+            "secret",
+        )
+        .unwrap();
+        let options = SslOptions {
+            client_certificate: Some(client),
+            ..Default::default()
+        };
+        let mut tls = connect(TcpStream::connect(("127.0.0.1", port)).unwrap(), &options).unwrap();
+        tls.write_all(b"ping").unwrap();
+        let mut buf = [0; 4];
+        tls.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        assert!(
+            ClientCertificate::from_pem(b"", &key.private_key_to_pem_pkcs8().unwrap(), "").is_err()
+        );
+    }
+}
@@ -2,19 +2,42 @@ use std::{
     io::{self, BufRead, BufReader, Read, Write},
     net::{IpAddr, SocketAddr, TcpStream},
     os::fd::AsRawFd,
-    time::Duration,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
 };
 
+use openssl::ssl::SslStream;
 use rustls::{ClientConnection, Stream};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{
+    network_utils::with_privileged_port,
+    ssl::{self, SslOptions},
+};
+use crate::nasl::utils::{Proxy, SourceBinding};
+
+/// Delay between the start of two connection attempts as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
 
 struct TcpDataStream {
     tcp: TcpStream,
     tls: Option<ClientConnection>,
+    /// TLS negotiated later on the connection, running on a clone of tcp
+    ssl: Option<SslStream<TcpStream>>,
+    /// Options of the negotiated TLS, used to connect again for session resumption
+    ssl_options: Option<SslOptions>,
+    /// Proxy the connection is routed through and the target it is connected to
+    proxy: Option<(Proxy, SocketAddr)>,
+    /// Source binding of the connection, used to connect again for session resumption
+    binding: SourceBinding,
 }
 
 impl Read for TcpDataStream {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if let Some(tls) = &mut self.tls {
+        if let Some(ssl) = &mut self.ssl {
+            ssl.read(buf)
+        } else if let Some(tls) = &mut self.tls {
             let mut stream = Stream::new(tls, &mut self.tcp);
             stream.read(buf)
         } else {
@@ -47,7 +70,9 @@ impl BufRead for TcpConnection {
 impl Write for TcpConnection {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let stream = self.stream.get_mut();
-        let ret = if let Some(tls) = &mut stream.tls {
+        let ret = if let Some(ssl) = &mut stream.ssl {
+            ssl.write(buf)
+        } else if let Some(tls) = &mut stream.tls {
             let mut stream = Stream::new(tls, &mut stream.tcp);
             stream.write(buf)
         } else {
@@ -72,7 +97,9 @@ impl Write for TcpConnection {
 
     fn flush(&mut self) -> std::io::Result<()> {
         let stream = self.stream.get_mut();
-        if let Some(tls) = &mut stream.tls {
+        if let Some(ssl) = &mut stream.ssl {
+            ssl.flush()
+        } else if let Some(tls) = &mut stream.tls {
             let mut stream = Stream::new(tls, &mut stream.tcp);
             stream.flush()
         } else {
@@ -97,24 +124,228 @@ impl TcpConnection {
     }
 
     pub fn is_tls(&self) -> bool {
-        self.stream.get_ref().tls.is_some()
+        let stream = self.stream.get_ref();
+        stream.tls.is_some() || stream.ssl.is_some()
+    }
+
+    /// Returns the socket of the connection for protocols implemented by other libraries.
+    ///
+    /// Fails when TLS is negotiated or data was already read into the buffer.
+    pub fn into_tcp(self) -> io::Result<TcpStream> {
+        if self.is_tls() || !self.stream.buffer().is_empty() {
+            return Err(io::Error::other("the connection is already in use"));
+        }
+        Ok(self.stream.into_inner().tcp)
+    }
+
+    /// Negotiates TLS on the connection, waiting at most for the timeout.
+    pub fn negotiate_ssl(&mut self, options: &SslOptions, timeout: Duration) -> io::Result<()> {
+        if self.is_tls() {
+            return Err(io::Error::other(
+                "TLS is already negotiated on the connection",
+            ));
+        }
+        let stream = self.stream.get_mut();
+        let old = (stream.tcp.read_timeout()?, stream.tcp.write_timeout()?);
+        stream.tcp.set_read_timeout(Some(timeout))?;
+        stream.tcp.set_write_timeout(Some(timeout))?;
+        let ssl = ssl::connect(stream.tcp.try_clone()?, options);
+        stream.tcp.set_read_timeout(old.0)?;
+        stream.tcp.set_write_timeout(old.1)?;
+        stream.ssl = Some(ssl?);
+        stream.ssl_options = Some(options.clone());
+        Ok(())
+    }
+
+    /// Returns whether the server supports secure renegotiation, None when TLS was not
+    /// negotiated by [Self::negotiate_ssl].
+    pub fn secure_renegotiation(&self) -> Option<bool> {
+        self.ssl().map(ssl::secure_renegotiation)
+    }
+
+    /// Renegotiates the TLS negotiated by [Self::negotiate_ssl], waiting at most for the
+    /// timeout.
+    ///
+    /// Returns None when TLS was not negotiated by [Self::negotiate_ssl] and whether the server
+    /// completed the renegotiation otherwise.
+    pub fn renegotiate(&mut self, timeout: Duration) -> io::Result<Option<bool>> {
+        let stream = self.stream.get_mut();
+        let Some(ssl) = &mut stream.ssl else {
+            return Ok(None);
+        };
+        let old = (stream.tcp.read_timeout()?, stream.tcp.write_timeout()?);
+        stream.tcp.set_read_timeout(Some(timeout))?;
+        stream.tcp.set_write_timeout(Some(timeout))?;
+        let renegotiated = ssl::renegotiate(ssl);
+        stream.tcp.set_read_timeout(old.0)?;
+        stream.tcp.set_write_timeout(old.1)?;
+        renegotiated.map(Some)
+    }
+
+    /// Offers the session of the TLS negotiated by [Self::negotiate_ssl] on a new connection to
+    /// the peer and returns whether the server resumed it.
+    ///
+    /// Returns None when TLS was not negotiated by [Self::negotiate_ssl].
+    pub fn resume_session(&self, ticket: bool, timeout: Duration) -> io::Result<Option<bool>> {
+        let stream = self.stream.get_ref();
+        let (Some(ssl), Some(options)) = (&stream.ssl, &stream.ssl_options) else {
+            return Ok(None);
+        };
+        let connect = || match &stream.proxy {
+            Some((proxy, target)) => proxy.connect(target, timeout, &stream.binding),
+            None => Self::connect_bound(&stream.tcp.peer_addr()?, timeout, &stream.binding),
+        };
+        ssl::resume(ssl.ssl(), connect, options, ticket, timeout).map(Some)
+    }
+
+    /// Returns the TLS session negotiated by [Self::negotiate_ssl].
+    pub fn ssl(&self) -> Option<&openssl::ssl::SslRef> {
+        self.stream.get_ref().ssl.as_ref().map(|x| x.ssl())
+    }
+
+    /// Completes a pending handshake of TLS established when connecting.
+    fn complete_handshake(&mut self) -> io::Result<()> {
+        let stream = self.stream.get_mut();
+        if let Some(tls) = &mut stream.tls {
+            while tls.is_handshaking() {
+                tls.complete_io(&mut stream.tcp)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the protocol negotiated with ALPN, completing a pending TLS handshake first.
+    pub fn alpn_protocol(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.complete_handshake()?;
+        let stream = self.stream.get_ref();
+        if let Some(ssl) = &stream.ssl {
+            return Ok(ssl.ssl().selected_alpn_protocol().map(|x| x.to_vec()));
+        }
+        Ok(stream
+            .tls
+            .as_ref()
+            .and_then(|x| x.alpn_protocol())
+            .map(|x| x.to_vec()))
+    }
+
+    /// Returns the DER encoded certificates sent by the peer, starting with its own.
+    ///
+    /// A pending TLS handshake is completed first. Returns an empty list when TLS is not used.
+    pub fn peer_certificates(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        self.complete_handshake()?;
+        let stream = self.stream.get_ref();
+        if let Some(ssl) = &stream.ssl {
+            let ssl = ssl.ssl();
+            // on the client side the chain starts with the certificate of the server
+            let certificates: Result<Vec<_>, _> = match ssl.peer_cert_chain() {
+                Some(chain) => chain.iter().map(|x| x.to_der()).collect(),
+                None => ssl
+                    .peer_certificate()
+                    .into_iter()
+                    .map(|x| x.to_der())
+                    .collect(),
+            };
+            return certificates.map_err(io::Error::other);
+        }
+        Ok(stream
+            .tls
+            .as_ref()
+            .and_then(|x| x.peer_certificates())
+            .map(|x| x.iter().map(|x| x.to_vec()).collect())
+            .unwrap_or_default())
     }
 
     pub fn set_flags(&mut self, flags: i32) {
         self.flags = Some(flags);
     }
 
+    /// Connects to the given address using a socket bound to the source binding.
+    fn connect_bound(
+        addr: &SocketAddr,
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<TcpStream> {
+        let socket = Socket::new(
+            Domain::for_address(*addr),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        binding.bind(&socket, &addr.ip())?;
+        socket.connect_timeout(&(*addr).into(), timeout)?;
+        Ok(socket.into())
+    }
+
+    /// Races connection attempts to the given addresses as described in RFC 8305.
+    ///
+    /// The attempts are started in the given order, each [CONNECTION_ATTEMPT_DELAY] after the
+    /// previous one or immediately when the previous one failed. The first established
+    /// connection is returned, the others are dropped.
+    fn connect_racing(
+        addrs: &[SocketAddr],
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<TcpStream> {
+        if let [addr] = addrs {
+            return Self::connect_bound(addr, timeout, binding);
+        }
+        let deadline = Instant::now() + timeout;
+        let (tx, rx) = mpsc::channel();
+        let mut remaining = addrs.iter();
+        let mut pending = 0;
+        let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "no address given");
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            let left = deadline - now;
+            if let Some(addr) = remaining.next() {
+                let (tx, addr, binding) = (tx.clone(), *addr, binding.clone());
+                thread::spawn(move || {
+                    // the receiver is gone when another attempt already succeeded
+                    let _ = tx.send(Self::connect_bound(&addr, left, &binding));
+                });
+                pending += 1;
+            }
+            if pending == 0 {
+                return Err(last_error);
+            }
+            let wait = if remaining.len() > 0 {
+                CONNECTION_ATTEMPT_DELAY.min(left)
+            } else {
+                left
+            };
+            match rx.recv_timeout(wait) {
+                Ok(Ok(tcp)) => return Ok(tcp),
+                Ok(Err(e)) => {
+                    pending -= 1;
+                    last_error = e;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Connects to the first of the given addresses that answers.
+    ///
+    /// Multiple addresses, e.g. of a dual-stack host, are raced as described in RFC 8305 and
+    /// should therefore be ordered by [crate::nasl::utils::AddressFamily::sort].
     pub fn connect(
-        addr: IpAddr,
+        addrs: &[IpAddr],
         port: u16,
         tls: Option<ClientConnection>,
         timeout: Duration,
         bufsz: Option<usize>,
         retry: u8,
+        binding: &SourceBinding,
     ) -> io::Result<Self> {
+        let addrs = addrs
+            .iter()
+            .map(|addr| SocketAddr::new(*addr, port))
+            .collect::<Vec<_>>();
         let mut i = 0;
         let tcp = loop {
-            match TcpStream::connect_timeout(&(addr, port).into(), timeout) {
+            match Self::connect_racing(&addrs, timeout, binding) {
                 Ok(tcp) => break tcp,
                 Err(e) if e.kind() == io::ErrorKind::TimedOut => {
                     if i == retry - 1 {
@@ -125,13 +356,129 @@ impl TcpConnection {
                 Err(e) => return Err(e),
             }
         };
-        Ok(Self::new(TcpDataStream { tcp, tls }, bufsz))
+        Ok(Self::new(
+            TcpDataStream {
+                tcp,
+                tls,
+                ssl: None,
+                ssl_options: None,
+                proxy: None,
+                binding: binding.clone(),
+            },
+            bufsz,
+        ))
+    }
+
+    /// Connects to the address from a privileged source port.
+    ///
+    /// Without a source port the first free one of
+    /// [super::network_utils::PRIVILEGED_PORTS] is used.
+    pub fn connect_privileged(
+        addr: &SocketAddr,
+        sport: Option<u16>,
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<Self> {
+        let tcp = with_privileged_port(sport, |port| {
+            let socket = Socket::new(
+                Domain::for_address(*addr),
+                Type::STREAM,
+                Some(Protocol::TCP),
+            )?;
+            binding.bind_port(&socket, &addr.ip(), port)?;
+            socket.connect_timeout(&(*addr).into(), timeout)?;
+            Ok(socket.into())
+        })?;
+        Ok(Self::new(
+            TcpDataStream {
+                tcp,
+                tls: None,
+                ssl: None,
+                ssl_options: None,
+                proxy: None,
+                binding: binding.clone(),
+            },
+            None,
+        ))
+    }
+
+    /// Connects to the first of the given addresses the proxy reaches.
+    ///
+    /// The addresses are tried one after another, as the proxy reports a failed attempt only
+    /// after its own timeout.
+    pub fn connect_proxy(
+        proxy: &Proxy,
+        addrs: &[IpAddr],
+        port: u16,
+        mut tls: Option<ClientConnection>,
+        timeout: Duration,
+        bufsz: Option<usize>,
+        binding: &SourceBinding,
+    ) -> io::Result<Self> {
+        let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "no address given");
+        for addr in addrs {
+            let target = SocketAddr::new(*addr, port);
+            match proxy.connect(&target, timeout, binding) {
+                Ok(tcp) => {
+                    return Ok(Self::new(
+                        TcpDataStream {
+                            tcp,
+                            tls: tls.take(),
+                            ssl: None,
+                            ssl_options: None,
+                            proxy: Some((proxy.clone(), target)),
+                            binding: binding.clone(),
+                        },
+                        bufsz,
+                    ))
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.stream.get_ref().tcp.local_addr()
     }
 
+    /// Returns the address of the target, also when connected through a proxy.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        let stream = self.stream.get_ref();
+        match &stream.proxy {
+            Some((_, target)) => Ok(*target),
+            None => stream.tcp.peer_addr(),
+        }
+    }
+
+    fn tcp_info(&self) -> io::Result<libc::tcp_info> {
+        let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                self.stream.get_ref().tcp.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut libc::tcp_info as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(info)
+    }
+
+    /// Returns the number of segments the kernel retransmitted on this connection.
+    pub fn retransmissions(&self) -> io::Result<u64> {
+        Ok(self.tcp_info()?.tcpi_total_retrans as u64)
+    }
+
+    /// Returns the smoothed round trip time the kernel measured on this connection.
+    pub fn rtt(&self) -> io::Result<Duration> {
+        Ok(Duration::from_micros(self.tcp_info()?.tcpi_rtt as u64))
+    }
+
     pub fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
         let old = self.stream.get_ref().tcp.read_timeout()?;
         self.stream.get_ref().tcp.set_read_timeout(Some(timeout))?;
@@ -140,6 +487,15 @@ impl TcpConnection {
         ret
     }
 
+    /// Fills the buffer, starting with data already buffered by a previous line read.
+    pub fn read_exact_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<()> {
+        let old = self.stream.get_ref().tcp.read_timeout()?;
+        self.stream.get_ref().tcp.set_read_timeout(Some(timeout))?;
+        let ret = self.stream.read_exact(buf);
+        self.stream.get_ref().tcp.set_read_timeout(old)?;
+        ret
+    }
+
     pub fn read_line_with_timeout(
         &mut self,
         buf: &mut String,
@@ -152,3 +508,83 @@ impl TcpConnection {
         ret
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, TcpListener},
+        sync::mpsc,
+        thread,
+        time::Duration,
+    };
+
+    use openssl::ssl::SslVersion;
+
+    use crate::nasl::utils::SourceBinding;
+
+    use super::super::ssl::{tests::acceptor, SslOptions};
+    use super::TcpConnection;
+
+    #[test]
+    fn connect_to_answering_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // nothing listens on the port of the IPv6 loopback address
+        let addrs: Vec<IpAddr> = vec!["::1".parse().unwrap(), "127.0.0.1".parse().unwrap()];
+        let tcp = TcpConnection::connect(
+            &addrs,
+            port,
+            None,
+            Duration::from_secs(5),
+            None,
+            1,
+            &SourceBinding::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            tcp.stream.get_ref().tcp.peer_addr().unwrap(),
+            listener.local_addr().unwrap()
+        );
+    }
+
+    #[test]
+    fn resume_session_from_source_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut acceptor = acceptor();
+        acceptor.set_options(openssl::ssl::SslOptions::NO_TICKET);
+        let acceptor = acceptor.build();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for tcp in listener.incoming().take(2).flatten() {
+                tx.send(tcp.peer_addr().unwrap().ip()).unwrap();
+                let acceptor = acceptor.clone();
+                thread::spawn(move || {
+                    if let Ok(mut tls) = acceptor.accept(tcp) {
+                        let _ = std::io::Read::read(&mut tls, &mut [0; 64]);
+                    }
+                });
+            }
+        });
+        let source: IpAddr = "127.0.0.2".parse().unwrap();
+        let timeout = Duration::from_secs(5);
+        let mut tcp = TcpConnection::connect(
+            &["127.0.0.1".parse().unwrap()],
+            port,
+            None,
+            timeout,
+            None,
+            1,
+            &SourceBinding::new(None, vec![source]),
+        )
+        .unwrap();
+        let options = SslOptions {
+            max_version: Some(SslVersion::TLS1_2),
+            ..Default::default()
+        };
+        tcp.negotiate_ssl(&options, timeout).unwrap();
+        assert_eq!(tcp.resume_session(false, timeout).unwrap(), Some(true));
+        assert_eq!(rx.recv().unwrap(), source);
+        assert_eq!(rx.recv().unwrap(), source);
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Option negotiation of the telnet protocol (RFC 854, RFC 855).
+//!
+//! The client refuses every option except suppressing go ahead, so that the server falls back
+//! to a plain network virtual terminal and sends its banner.
+
+use std::time::Duration;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// Time to wait for the next options or the banner
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of options a server may send before it is considered broken
+pub const MAX_OPTIONS: usize = 100;
+
+/// Maximum length of an incomplete command kept until the next data arrives
+const MAX_PENDING: usize = 4096;
+
+#[derive(Debug, Default)]
+pub struct Negotiation {
+    /// Incomplete command at the end of the data fed last
+    pending: Vec<u8>,
+    /// Number of options received so far
+    pub options: usize,
+}
+
+impl Negotiation {
+    /// Removes the commands from the received data, returns the remaining text and the replies
+    /// to send to the server.
+    pub fn feed(&mut self, received: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(received);
+        let mut text = Vec::with_capacity(data.len());
+        let mut replies = vec![];
+        let mut i = 0;
+        while i < data.len() {
+            if data[i] != IAC {
+                text.push(data[i]);
+                i += 1;
+                continue;
+            }
+            let Some(command) = data.get(i + 1) else {
+                break;
+            };
+            let length = match *command {
+                // an escaped 255 data byte
+                IAC => {
+                    text.push(IAC);
+                    2
+                }
+                WILL | WONT | DO | DONT => {
+                    let Some(option) = data.get(i + 2) else {
+                        break;
+                    };
+                    self.options += 1;
+                    // refusals are not acknowledged as no option is ever enabled
+                    match *command {
+                        WILL if *option == SUPPRESS_GO_AHEAD => replies.extend([IAC, DO, *option]),
+                        WILL => replies.extend([IAC, DONT, *option]),
+                        DO => replies.extend([IAC, WONT, *option]),
+                        _ => {}
+                    }
+                    3
+                }
+                SB => {
+                    let Some(end) = data[i + 2..].windows(2).position(|x| x == [IAC, SE]) else {
+                        break;
+                    };
+                    self.options += 1;
+                    end + 4
+                }
+                // commands without an option like go ahead or no operation
+                _ => 2,
+            };
+            i += length;
+        }
+        if data.len() - i <= MAX_PENDING {
+            self.pending = data[i..].to_vec();
+        }
+        (text, replies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_options() {
+        let mut negotiation = Negotiation::default();
+        let (text, replies) =
+            negotiation.feed(&[IAC, DO, 24, IAC, WILL, 1, IAC, WILL, 3, IAC, WONT, 5, b'>']);
+        assert_eq!(text, b">");
+        assert_eq!(replies, [IAC, WONT, 24, IAC, DONT, 1, IAC, DO, 3]);
+        assert_eq!(negotiation.options, 4);
+    }
+
+    #[test]
+    fn split_commands() {
+        let mut negotiation = Negotiation::default();
+        assert_eq!(negotiation.feed(&[b'a', IAC]), (b"a".to_vec(), vec![]));
+        assert_eq!(negotiation.feed(&[DO]), (vec![], vec![]));
+        assert_eq!(
+            negotiation.feed(&[31, IAC, SB, 24, 1]),
+            (vec![], vec![IAC, WONT, 31])
+        );
+        assert_eq!(
+            negotiation.feed(&[IAC, SE, IAC, IAC, b'b']),
+            (vec![IAC, b'b'], vec![])
+        );
+        assert_eq!(negotiation.options, 2);
+    }
+}
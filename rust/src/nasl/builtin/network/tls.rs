@@ -66,8 +66,14 @@ pub fn create_tls_client(
     key_path: &str,
     password: &str,
     cafile_path: &str,
+    alpn: &[String],
 ) -> Result<ClientConnection, TLSError> {
-    let server = ServerName::try_from(hostname.to_owned()).unwrap();
+    let server = ServerName::try_from(hostname.to_owned()).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid server name {hostname}: {e}"),
+        )
+    })?;
 
     let mut root_store = RootCertStore::empty();
     let ca_file = fs::File::open(cafile_path)?;
@@ -97,9 +103,10 @@ pub fn create_tls_client(
             decrypted_key.as_bytes().to_owned(),
         ));
     }
-    let config = ClientConfig::builder()
+    let mut config = ClientConfig::builder()
         .with_root_certificates(root_store)
         .with_client_auth_cert(cert, key)
         .unwrap();
+    config.alpn_protocols = alpn.iter().map(|x| x.as_bytes().to_vec()).collect();
     ClientConnection::new(Arc::new(config), server).map_err(|e| e.into())
 }
@@ -1,43 +1,63 @@
 use std::{
     io::{self, Read, Write},
-    net::{IpAddr, SocketAddr, UdpSocket},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, UdpSocket},
     os::fd::AsRawFd,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use super::{mtu, network_utils::bind_local_socket};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{
+    mtu,
+    network_utils::{bind_local_socket, with_privileged_port},
+};
+use crate::nasl::utils::{
+    socks_proxy::{self, MAX_UDP_HEADER},
+    SocksProxy, SourceBinding,
+};
+
+/// UDP relay of a SOCKS5 proxy the datagrams are sent through
+struct Relay {
+    /// Control connection, the proxy stops relaying when it is closed
+    _control: TcpStream,
+    target: SocketAddr,
+}
 
 pub struct UdpConnection {
     socket: UdpSocket,
+    relay: Option<Relay>,
     buffer: Vec<u8>,
     flags: Option<i32>,
+    retransmissions: u64,
+    timeout: Duration,
+    retries: usize,
 }
 
-const NUM_TIMES_TO_RESEND: usize = 5;
+/// Default time to wait for an answer, including all retransmissions
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default number of times a datagram is sent again when no answer is received
+pub const DEFAULT_RETRIES: usize = 4;
+/// Lower bound of the time to wait for an answer to a single attempt
+const MIN_ATTEMPT_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Returns true when the error is caused by an expired read timeout.
+pub fn is_timeout(e: &io::Error) -> bool {
+    // Linux reports an expired read timeout as EAGAIN
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
 
 impl Read for UdpConnection {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        for i in 0..NUM_TIMES_TO_RESEND {
-            let result = self.socket.recv_from(buf);
-            match result {
-                Ok((size, origin)) => {
-                    if self.socket.peer_addr()? == origin {
-                        return Ok(size);
-                    }
-                }
-                Err(e) if e.kind() == io::ErrorKind::TimedOut && i != NUM_TIMES_TO_RESEND - 1 => {
-                    self.socket.send(&self.buffer)?;
-                }
-                Err(e) => return Err(e),
-            };
-        }
-        unreachable!()
+        self.read_with_retries(buf, self.timeout, self.retries)
     }
 }
 
 impl Write for UdpConnection {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let mtu = mtu(self.socket.peer_addr()?.ip());
+        let mtu = mtu(self.peer_addr()?.ip());
         if buf.len() > mtu {
             return Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -48,11 +68,15 @@ impl Write for UdpConnection {
                 ),
             ));
         }
+        let datagram = match &self.relay {
+            Some(relay) => socks_proxy::encapsulate(&relay.target, buf),
+            None => buf.to_vec(),
+        };
         let result = unsafe {
             libc::send(
                 self.socket.as_raw_fd(),
-                buf.as_ptr() as *const libc::c_void,
-                buf.len(),
+                datagram.as_ptr() as *const libc::c_void,
+                datagram.len(),
                 self.flags.unwrap_or_default(),
             )
         };
@@ -60,7 +84,9 @@ impl Write for UdpConnection {
         if result < 0 {
             return Err(io::Error::last_os_error());
         }
-        Ok(result as usize)
+        // kept to resend the datagram when no answer is received
+        self.buffer = datagram;
+        Ok((result as usize).min(buf.len()))
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -69,31 +95,231 @@ impl Write for UdpConnection {
 }
 
 impl UdpConnection {
-    pub fn new(addr: IpAddr, port: u16) -> io::Result<Self> {
+    /// Opens a socket connected to the port of the address.
+    ///
+    /// An answer is awaited for the timeout, which is split evenly between the first attempt and
+    /// the given number of retries. When an attempt gets no answer, the last datagram is sent
+    /// again.
+    pub fn new(
+        addr: IpAddr,
+        port: u16,
+        binding: &SourceBinding,
+        timeout: Duration,
+        retries: usize,
+    ) -> io::Result<Self> {
         let sock_addr = SocketAddr::new(addr, port);
-        let socket = bind_local_socket(&sock_addr)?;
+        let socket = bind_local_socket(&sock_addr, binding)?;
         socket.connect(sock_addr)?;
-        socket.set_read_timeout(Some(Duration::from_secs(1)))?;
-        Ok(Self {
+        Ok(Self::from_socket(socket, timeout, retries))
+    }
+
+    /// Opens a socket connected to the port of the address from a privileged source port.
+    ///
+    /// Without a source port the first free one of
+    /// [super::network_utils::PRIVILEGED_PORTS] is used.
+    pub fn new_privileged(
+        addr: &SocketAddr,
+        sport: Option<u16>,
+        binding: &SourceBinding,
+        timeout: Duration,
+        retries: usize,
+    ) -> io::Result<Self> {
+        let socket = with_privileged_port(sport, |port| {
+            let socket = Socket::new(Domain::for_address(*addr), Type::DGRAM, Some(Protocol::UDP))?;
+            binding.bind_port(&socket, &addr.ip(), port)?;
+            socket.connect(&(*addr).into())?;
+            Ok(socket.into())
+        })?;
+        Ok(Self::from_socket(socket, timeout, retries))
+    }
+
+    /// Opens a socket sending to the port of the address through the UDP relay of the proxy.
+    ///
+    /// The source port of the relayed datagrams is chosen by the proxy.
+    pub fn new_socks(
+        proxy: &SocksProxy,
+        addr: IpAddr,
+        port: u16,
+        binding: &SourceBinding,
+        timeout: Duration,
+        retries: usize,
+    ) -> io::Result<Self> {
+        let target = SocketAddr::new(addr, port);
+        let (control, relay) = proxy.associate(timeout, binding)?;
+        let socket = bind_local_socket(&relay, binding)?;
+        socket.connect(relay)?;
+        let mut connection = Self::from_socket(socket, timeout, retries);
+        connection.relay = Some(Relay {
+            _control: control,
+            target,
+        });
+        Ok(connection)
+    }
+
+    fn from_socket(socket: UdpSocket, timeout: Duration, retries: usize) -> Self {
+        Self {
             socket,
+            relay: None,
             buffer: vec![],
             flags: None,
-        })
+            retransmissions: 0,
+            timeout,
+            retries,
+        }
     }
 
     pub fn set_flags(&mut self, flags: i32) {
         self.flags = Some(flags);
     }
 
+    /// Returns the number of resent datagrams since the last call.
+    pub fn take_retransmissions(&mut self) -> u64 {
+        std::mem::take(&mut self.retransmissions)
+    }
+
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.socket.local_addr()
     }
 
-    pub fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+    /// Returns the address of the target, also when sending through a proxy.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match &self.relay {
+            Some(relay) => Ok(relay.target),
+            None => self.socket.peer_addr(),
+        }
+    }
+
+    /// Receives a datagram and returns its origin, unwrapping datagrams of the relay.
+    ///
+    /// Malformed datagrams of the relay get the unspecified address as origin.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        if self.relay.is_none() {
+            return self.socket.recv_from(buf);
+        }
+        let mut datagram = vec![0; buf.len() + MAX_UDP_HEADER];
+        let size = self.socket.recv(&mut datagram)?;
+        Ok(match socks_proxy::decapsulate(&datagram[..size]) {
+            Some((origin, data)) => {
+                let size = data.len().min(buf.len());
+                buf[..size].copy_from_slice(&data[..size]);
+                (size, origin)
+            }
+            None => (0, SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)),
+        })
+    }
+
+    /// Returns the default time to wait for an answer.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns the default number of retries.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Receives a datagram of the peer like the C implementation does.
+    ///
+    /// The timeout is split evenly between the first attempt and the retries. After each
+    /// attempt without answer the last sent datagram is sent again, as it or its answer may have
+    /// been lost. Datagrams of other hosts are dropped.
+    pub fn read_with_retries(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+        retries: usize,
+    ) -> io::Result<usize> {
+        let attempts = retries.saturating_add(1);
         let old = self.socket.read_timeout()?;
-        self.socket.set_read_timeout(Some(timeout))?;
-        let ret = self.read(buf);
+        self.socket.set_read_timeout(Some(
+            (timeout / u32::try_from(attempts).unwrap_or(u32::MAX)).max(MIN_ATTEMPT_TIMEOUT),
+        ))?;
+        let ret = self.receive(buf, attempts, Instant::now() + timeout);
         self.socket.set_read_timeout(old)?;
         ret
     }
+
+    fn receive(&mut self, buf: &mut [u8], attempts: usize, deadline: Instant) -> io::Result<usize> {
+        let peer = self.peer_addr()?;
+        let mut attempt = 1;
+        loop {
+            match self.recv_from(buf) {
+                Ok((size, origin)) if origin == peer => return Ok(size),
+                Ok(_) if Instant::now() < deadline => {}
+                Ok(_) => return Err(io::ErrorKind::TimedOut.into()),
+                Err(e) if is_timeout(&e) && attempt < attempts => {
+                    attempt += 1;
+                    if !self.buffer.is_empty() {
+                        self.socket.send(&self.buffer)?;
+                        self.retransmissions += 1;
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::UdpSocket,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::nasl::utils::SourceBinding;
+
+    use super::{is_timeout, UdpConnection};
+
+    #[test]
+    fn retransmission() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let answer = thread::spawn(move || {
+            let mut buf = [0u8; 16];
+            // the first datagram is lost
+            server.recv_from(&mut buf).unwrap();
+            let (len, origin) = server.recv_from(&mut buf).unwrap();
+            server.send_to(&buf[..len], origin).unwrap();
+        });
+        let mut conn = UdpConnection::new(
+            addr.ip(),
+            addr.port(),
+            &SourceBinding::default(),
+            Duration::from_secs(2),
+            4,
+        )
+        .unwrap();
+        conn.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 16];
+        let len = conn.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"ping");
+        assert_eq!(conn.take_retransmissions(), 1);
+        answer.join().unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut conn = UdpConnection::new(
+            addr.ip(),
+            addr.port(),
+            &SourceBinding::default(),
+            Duration::from_secs(5),
+            4,
+        )
+        .unwrap();
+        conn.write_all(b"ping").unwrap();
+        let start = Instant::now();
+        let mut buf = [0u8; 16];
+        let err = conn
+            .read_with_retries(&mut buf, Duration::from_millis(200), 1)
+            .unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(conn.take_retransmissions(), 1);
+    }
 }
@@ -0,0 +1,171 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Connections to UNIX domain sockets.
+//!
+//! When the scanner runs on the asset itself, services that are only exposed on a local UNIX
+//! socket can be scanned by using a target URI like `unix:///run/app.sock`. Such a target is
+//! the local host and the socket builtins connect to the socket instead of a port.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::{UnixDatagram, UnixStream},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const SCHEME: &str = "unix:";
+
+/// Returns the path of the socket when the target is given as UNIX socket URI.
+///
+/// Accepted are `unix:/path`, `unix:///path` and `unix://localhost/path`.
+pub fn target_socket_path(target: &str) -> Option<PathBuf> {
+    let rest = target.strip_prefix(SCHEME)?;
+    let path = match rest.strip_prefix("//") {
+        Some(authority_and_path) => {
+            let (authority, path) = authority_and_path.split_at(authority_and_path.find('/')?);
+            if !authority.is_empty() && authority != "localhost" {
+                return None;
+            }
+            path
+        }
+        None => rest,
+    };
+    Some(PathBuf::from(path)).filter(|x| x.is_absolute())
+}
+
+pub enum UnixConnection {
+    Stream(BufReader<UnixStream>),
+    Datagram(UnixDatagram),
+}
+
+impl Read for UnixConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Stream(stream) => stream.read(buf),
+            Self::Datagram(socket) => socket.recv(buf),
+        }
+    }
+}
+
+impl Write for UnixConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stream(stream) => stream.get_mut().write(buf),
+            Self::Datagram(socket) => socket.send(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stream(stream) => stream.get_mut().flush(),
+            Self::Datagram(_) => Ok(()),
+        }
+    }
+}
+
+impl UnixConnection {
+    /// Connects to the socket at the given path.
+    pub fn connect(path: &Path, datagram: bool, timeout: Duration) -> io::Result<Self> {
+        if datagram {
+            let socket = UnixDatagram::unbound()?;
+            socket.connect(path)?;
+            socket.set_read_timeout(Some(timeout))?;
+            Ok(Self::Datagram(socket))
+        } else {
+            let stream = UnixStream::connect(path)?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            Ok(Self::Stream(BufReader::new(stream)))
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<Option<Duration>> {
+        let (old, result) = match self {
+            Self::Stream(stream) => (
+                stream.get_ref().read_timeout()?,
+                stream.get_ref().set_read_timeout(timeout),
+            ),
+            Self::Datagram(socket) => (socket.read_timeout()?, socket.set_read_timeout(timeout)),
+        };
+        result.map(|_| old)
+    }
+
+    pub fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let old = self.set_read_timeout(Some(timeout))?;
+        let ret = self.read(buf);
+        self.set_read_timeout(old)?;
+        ret
+    }
+
+    /// Reads a line from a stream socket.
+    pub fn read_line(&mut self, buf: &mut String, timeout: Option<Duration>) -> io::Result<usize> {
+        let old = match timeout {
+            Some(timeout) => Some(self.set_read_timeout(Some(timeout))?),
+            None => None,
+        };
+        let ret = match self {
+            Self::Stream(stream) => stream.read_line(buf),
+            Self::Datagram(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "lines can only be read from stream sockets",
+            )),
+        };
+        if let Some(old) = old {
+            self.set_read_timeout(old)?;
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        os::unix::net::UnixListener,
+        path::PathBuf,
+        thread,
+        time::Duration,
+    };
+
+    use super::{target_socket_path, UnixConnection};
+
+    #[test]
+    fn target_uris() {
+        let path = Some(PathBuf::from("/run/app.sock"));
+        assert_eq!(target_socket_path("unix:/run/app.sock"), path);
+        assert_eq!(target_socket_path("unix:///run/app.sock"), path);
+        assert_eq!(target_socket_path("unix://localhost/run/app.sock"), path);
+        assert_eq!(target_socket_path("unix://example.com/run/app.sock"), None);
+        assert_eq!(target_socket_path("unix:app.sock"), None);
+        assert_eq!(target_socket_path("127.0.0.1"), None);
+    }
+
+    #[test]
+    fn stream() {
+        let path = std::env::temp_dir().join(format!("nasl-unix-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            reader
+                .get_mut()
+                .write_all(line.to_uppercase().as_bytes())
+                .unwrap();
+        });
+        let mut conn = UnixConnection::connect(&path, false, Duration::from_secs(5)).unwrap();
+        conn.write_all(b"ping\n").unwrap();
+        let mut line = String::new();
+        conn.read_line(&mut line, None).unwrap();
+        assert_eq!(line, "PING\n");
+        server.join().unwrap();
+        let mut rest = vec![];
+        conn.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Handshake of the Remote Framebuffer protocol (RFC 6143) used by VNC servers.
+//!
+//! The client answers the protocol version of the server with the highest version it supports
+//! and reads the offered security types. Only the security type None is ever selected, so the
+//! initialisation is only reached on servers without authentication.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+
+use super::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    tcp::TcpConnection,
+};
+use crate::function_set;
+use crate::nasl::prelude::*;
+
+const VNC_PORT: u16 = 5900;
+
+/// Length of the ProtocolVersion message, e.g. `RFB 003.008\n`
+const VERSION_LENGTH: usize = 12;
+/// Longest failure reason or desktop name read from the server
+const MAX_STRING: usize = 4096;
+
+const SECURITY_NONE: u8 = 1;
+
+/// Security types registered at the IANA by their names
+const SECURITY_TYPES: [(u8, &str); 13] = [
+    (SECURITY_NONE, "None"),
+    (2, "VNC Authentication"),
+    (5, "RA2"),
+    (6, "RA2ne"),
+    (16, "Tight"),
+    (17, "Ultra"),
+    (18, "TLS"),
+    (19, "VeNCrypt"),
+    (20, "GTK-VNC SASL"),
+    (21, "MD5 hash authentication"),
+    (22, "Colin Dean xvp"),
+    (30, "Apple Remote Desktop"),
+    (113, "MSLogonII"),
+];
+
+fn security_name(security: u8) -> &'static str {
+    SECURITY_TYPES
+        .iter()
+        .find(|(x, _)| *x == security)
+        .map_or("unknown", |(_, name)| name)
+}
+
+/// Parses the ProtocolVersion message of the server into the major and minor version.
+fn parse_version(message: &[u8]) -> Option<(u32, u32)> {
+    let version = message.strip_prefix(b"RFB ")?.strip_suffix(b"\n")?;
+    let (major, minor) = std::str::from_utf8(version).ok()?.split_once('.')?;
+    if major.len() != 3 || minor.len() != 3 {
+        return None;
+    }
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Returns the minor version of the protocol 3 answered to the version of the server.
+///
+/// Servers announcing an unknown version of 3 must be treated like 3.3, later major versions
+/// like the one of Apple Remote Desktop are compatible to 3.8.
+fn client_version((major, minor): (u32, u32)) -> u32 {
+    match (major, minor) {
+        (3, 8..) | (4.., _) => 8,
+        (3, 7) => 7,
+        _ => 3,
+    }
+}
+
+/// Result of the handshake
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Handshake {
+    server_version: String,
+    version: (u32, u32),
+    /// Minor version of the protocol 3 used by the client
+    protocol: u32,
+    security_types: Vec<u8>,
+    failure: Option<String>,
+    /// Width, height and name of the desktop from the initialisation
+    desktop: Option<(u16, u16, String)>,
+}
+
+struct Connection {
+    conn: TcpConnection,
+    timeout: Duration,
+}
+
+impl Connection {
+    fn read(&mut self, length: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; length];
+        self.conn.read_exact_with_timeout(&mut data, self.timeout)?;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.read(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let data = self.read(2)?;
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let data = self.read(4)?;
+        Ok(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    /// Reads a string preceded by its length, e.g. a failure reason.
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.u32()? as usize;
+        if length > MAX_STRING {
+            return Err(invalid_data("string too long"));
+        }
+        Ok(String::from_utf8_lossy(&self.read(length)?).into_owned())
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.conn.write_all(data)?;
+        self.conn.flush()
+    }
+
+    /// Selects the security type None and reads the ServerInit message after the ClientInit.
+    fn initialise(&mut self, protocol: u32) -> io::Result<Option<(u16, u16, String)>> {
+        if protocol >= 7 {
+            self.send(&[SECURITY_NONE])?;
+        }
+        // version 3.8 sends the result for None as well, failing with a reason
+        if protocol >= 8 && self.u32()? != 0 {
+            return Ok(None);
+        }
+        // the desktop is shared with other clients
+        self.send(&[1])?;
+        let width = self.u16()?;
+        let height = self.u16()?;
+        // the pixel format
+        self.read(16)?;
+        let name = self.string()?;
+        Ok(Some((width, height, name)))
+    }
+
+    fn handshake(&mut self, initialise: bool) -> io::Result<Handshake> {
+        let message = self.read(VERSION_LENGTH)?;
+        let version = parse_version(&message).ok_or_else(|| invalid_data("no RFB server"))?;
+        let protocol = client_version(version);
+        self.send(format!("RFB 003.{protocol:03}\n").as_bytes())?;
+        let mut result = Handshake {
+            server_version: String::from_utf8_lossy(&message).trim_end().to_string(),
+            version,
+            protocol,
+            ..Default::default()
+        };
+        // version 3.3 lets the server decide on the security type
+        result.security_types = match protocol {
+            3 => match self.u32()? {
+                0 => vec![],
+                x => vec![u8::try_from(x).map_err(|_| invalid_data("invalid security type"))?],
+            },
+            _ => {
+                let count = self.u8()? as usize;
+                self.read(count)?
+            }
+        };
+        if result.security_types.is_empty() {
+            result.failure = Some(self.string()?);
+        } else if initialise && result.security_types.contains(&SECURITY_NONE) {
+            result.desktop = self.initialise(protocol)?;
+        }
+        Ok(result)
+    }
+}
+
+/// NASL function to perform the RFB handshake with the VNC server of the target
+///
+/// Returns an array with the protocol version and the security types of the server, NULL when
+/// the service does not speak RFB.
+#[nasl_function(named(initialise, port, timeout))]
+fn vnc_handshake(
+    context: &Context,
+    initialise: Option<bool>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let (addrs, port, timeout) = target_service(context, port, VNC_PORT, timeout)?;
+    let handshake = NaslSockets::connect_tcp(context, &addrs, port, None, timeout, None)
+        .and_then(|conn| Connection { conn, timeout }.handshake(initialise.unwrap_or(false)));
+    let handshake = match handshake {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::debug!(%e, "RFB handshake failed");
+            return Ok(NaslValue::Null);
+        }
+    };
+    let (major, minor) = handshake.version;
+    let mut result = IndexMap::new();
+    let mut add = |key: &str, value: NaslValue| result.insert(key.to_string(), value);
+    add(
+        "server_version",
+        NaslValue::String(handshake.server_version),
+    );
+    add("version", NaslValue::String(format!("{major}.{minor}")));
+    add(
+        "protocol",
+        NaslValue::String(format!("3.{}", handshake.protocol)),
+    );
+    add(
+        "security_types",
+        NaslValue::Array(
+            handshake
+                .security_types
+                .iter()
+                .map(|x| NaslValue::Number(*x as i64))
+                .collect(),
+        ),
+    );
+    add(
+        "security_names",
+        NaslValue::Array(
+            handshake
+                .security_types
+                .iter()
+                .map(|x| NaslValue::String(security_name(*x).to_string()))
+                .collect(),
+        ),
+    );
+    add(
+        "no_auth",
+        NaslValue::Boolean(handshake.security_types.contains(&SECURITY_NONE)),
+    );
+    if let Some(failure) = handshake.failure {
+        add("failure", NaslValue::String(failure));
+    }
+    if let Some((width, height, name)) = handshake.desktop {
+        add("width", NaslValue::Number(width as i64));
+        add("height", NaslValue::Number(height as i64));
+        add("desktop_name", NaslValue::String(name));
+    }
+    Ok(NaslValue::Dict(result))
+}
+
+pub struct Vnc;
+
+function_set! {
+    Vnc,
+    sync_stateless,
+    (
+        vnc_handshake,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::nasl::builtin::network::network_utils::tests::tcp_server;
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    use super::{client_version, parse_version};
+
+    /// Answers like a VNC server with the version and the security types, an empty list of
+    /// types is a failure. The desktop is only initialised with the security type None.
+    fn server(version: &'static str, types: &'static [u8]) -> u16 {
+        tcp_server(move |mut stream| {
+            stream.write_all(version.as_bytes()).unwrap();
+            let mut answer = [0; 12];
+            if stream.read_exact(&mut answer).is_err() {
+                return;
+            }
+            let legacy = &answer == b"RFB 003.003\n";
+            match (legacy, types) {
+                (true, []) => stream.write_all(&[0; 4]).unwrap(),
+                (true, [x, ..]) => stream.write_all(&(*x as u32).to_be_bytes()).unwrap(),
+                (false, _) => {
+                    stream.write_all(&[types.len() as u8]).unwrap();
+                    stream.write_all(types).unwrap();
+                }
+            }
+            if types.is_empty() {
+                let reason = b"Too many authentication failures";
+                stream
+                    .write_all(&(reason.len() as u32).to_be_bytes())
+                    .unwrap();
+                stream.write_all(reason).unwrap();
+                return;
+            }
+            let mut selected = [0];
+            if !legacy && stream.read_exact(&mut selected).is_ok() && selected[0] == 1 {
+                stream.write_all(&[0; 4]).unwrap();
+                let mut shared = [0];
+                stream.read_exact(&mut shared).unwrap();
+                let mut init = vec![0x04, 0x00, 0x03, 0x00];
+                init.extend([32, 24, 0, 1, 0, 255, 0, 255, 0, 255, 16, 8, 0, 0, 0, 0]);
+                init.extend(7u32.to_be_bytes());
+                init.extend(b"desktop");
+                stream.write_all(&init).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(parse_version(b"RFB 003.008\n"), Some((3, 8)));
+        assert_eq!(parse_version(b"RFB 003.889\n"), Some((3, 889)));
+        assert_eq!(parse_version(b"RFB 3.8\n"), None);
+        assert_eq!(parse_version(b"SSH-2.0-x\r\n"), None);
+        assert_eq!(client_version((3, 889)), 8);
+        assert_eq!(client_version((4, 1)), 8);
+        assert_eq!(client_version((3, 7)), 7);
+        assert_eq!(client_version((3, 5)), 3);
+    }
+
+    #[test]
+    fn handshake() {
+        let open = server("RFB 003.008\n", &[2, 1]);
+        let legacy = server("RFB 003.003\n", &[2]);
+        let blocked = server("RFB 003.007\n", &[]);
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.run(format!("r = vnc_handshake(port: {open});"));
+        t.ok(r#"r["server_version"];"#, "RFB 003.008");
+        t.ok(r#"r["version"];"#, "3.8");
+        t.ok(r#"r["security_types"][1];"#, 1);
+        t.ok(r#"r["security_names"][0];"#, "VNC Authentication");
+        t.ok(r#"r["no_auth"];"#, true);
+        t.ok(r#"r["desktop_name"];"#, NaslValue::Null);
+        t.run(format!(
+            "r = vnc_handshake(initialise: TRUE, port: {open});"
+        ));
+        t.ok(r#"r["width"];"#, 1024);
+        t.ok(r#"r["desktop_name"];"#, "desktop");
+        t.run(format!(
+            "r = vnc_handshake(initialise: TRUE, port: {legacy});"
+        ));
+        t.ok(r#"r["protocol"];"#, "3.3");
+        t.ok(r#"r["security_names"][0];"#, "VNC Authentication");
+        t.ok(r#"r["no_auth"];"#, false);
+        t.run(format!("r = vnc_handshake(port: {blocked});"));
+        t.ok(r#"r["protocol"];"#, "3.7");
+        t.ok(r#"r["failure"];"#, "Too many authentication failures");
+    }
+}
@@ -0,0 +1,299 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Messages of the PostgreSQL frontend/backend protocol 3.0.
+
+use std::io;
+
+use crate::nasl::builtin::network::network_utils::invalid_data;
+
+/// Version 3.0 of the protocol
+const PROTOCOL_VERSION: u32 = 196608;
+/// Code of the SSLRequest in place of the protocol version
+const SSL_REQUEST_CODE: u32 = 80877103;
+
+const AUTH_OK: u32 = 0;
+const AUTH_CLEARTEXT_PASSWORD: u32 = 3;
+const AUTH_MD5_PASSWORD: u32 = 5;
+const AUTH_SASL: u32 = 10;
+const AUTH_SASL_CONTINUE: u32 = 11;
+const AUTH_SASL_FINAL: u32 = 12;
+
+const AUTHENTICATIONS: [(u32, &str); 10] = [
+    (AUTH_OK, "ok"),
+    (2, "kerberos_v5"),
+    (AUTH_CLEARTEXT_PASSWORD, "cleartext_password"),
+    (AUTH_MD5_PASSWORD, "md5_password"),
+    (7, "gss"),
+    (8, "gss_continue"),
+    (9, "sspi"),
+    (AUTH_SASL, "sasl"),
+    (AUTH_SASL_CONTINUE, "sasl_continue"),
+    (AUTH_SASL_FINAL, "sasl_final"),
+];
+
+pub fn authentication_name(code: u32) -> &'static str {
+    AUTHENTICATIONS
+        .iter()
+        .find(|(x, _)| *x == code)
+        .map_or("unknown", |(_, name)| name)
+}
+
+/// Returns the message of the type with its length, which includes itself.
+fn message(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend(((payload.len() + 4) as u32).to_be_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+fn push_string(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(value.as_bytes());
+    data.push(0);
+}
+
+pub fn ssl_request() -> Vec<u8> {
+    let mut request = 8u32.to_be_bytes().to_vec();
+    request.extend(SSL_REQUEST_CODE.to_be_bytes());
+    request
+}
+
+/// Returns the startup message, which lacks a type.
+pub fn startup(parameters: &[(&str, &str)]) -> Vec<u8> {
+    let mut payload = PROTOCOL_VERSION.to_be_bytes().to_vec();
+    for (name, value) in parameters {
+        push_string(&mut payload, name);
+        push_string(&mut payload, value);
+    }
+    payload.push(0);
+    let mut startup = ((payload.len() + 4) as u32).to_be_bytes().to_vec();
+    startup.extend(payload);
+    startup
+}
+
+/// Returns the password message, which carries the password in clear text or hashed.
+pub fn password(password: &str) -> Vec<u8> {
+    let mut payload = Vec::new();
+    push_string(&mut payload, password);
+    message(b'p', &payload)
+}
+
+pub fn sasl_initial_response(mechanism: &str, data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    push_string(&mut payload, mechanism);
+    payload.extend((data.len() as u32).to_be_bytes());
+    payload.extend_from_slice(data);
+    message(b'p', &payload)
+}
+
+pub fn sasl_response(data: &[u8]) -> Vec<u8> {
+    message(b'p', data)
+}
+
+pub fn query(query: &str) -> Vec<u8> {
+    let mut payload = Vec::new();
+    push_string(&mut payload, query);
+    message(b'Q', &payload)
+}
+
+pub fn terminate() -> Vec<u8> {
+    message(b'X', &[])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authentication {
+    Ok,
+    CleartextPassword,
+    Md5Password([u8; 4]),
+    /// The offered SASL mechanisms
+    Sasl(Vec<String>),
+    SaslContinue(Vec<u8>),
+    SaslFinal(Vec<u8>),
+    /// Methods not supported by the client, e.g. GSSAPI
+    Other(u32),
+}
+
+impl Authentication {
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::Ok => AUTH_OK,
+            Self::CleartextPassword => AUTH_CLEARTEXT_PASSWORD,
+            Self::Md5Password(_) => AUTH_MD5_PASSWORD,
+            Self::Sasl(_) => AUTH_SASL,
+            Self::SaslContinue(_) => AUTH_SASL_CONTINUE,
+            Self::SaslFinal(_) => AUTH_SASL_FINAL,
+            Self::Other(x) => *x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Authentication(Authentication),
+    ParameterStatus(String, String),
+    ReadyForQuery,
+    /// Names of the columns of the following rows
+    RowDescription(Vec<String>),
+    /// Values of the columns in text format, None for NULL
+    DataRow(Vec<Option<Vec<u8>>>),
+    /// Fields of an ErrorResponse by their type, e.g. S for the severity or M for the message
+    Error(Vec<(u8, String)>),
+    /// Messages not needed by the client, e.g. NoticeResponse or CommandComplete
+    Other(u8),
+}
+
+/// Reads the fields of a message.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let (field, rest) = self
+            .0
+            .split_at_checked(length)
+            .ok_or_else(|| invalid_data("truncated message"))?;
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn i16(&mut self) -> io::Result<i16> {
+        let field = self.take(2)?;
+        Ok(i16::from_be_bytes([field[0], field[1]]))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let field = self.take(4)?;
+        Ok(i32::from_be_bytes([field[0], field[1], field[2], field[3]]))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let length = self
+            .0
+            .iter()
+            .position(|x| *x == 0)
+            .ok_or_else(|| invalid_data("unterminated string"))?;
+        let field = self.take(length + 1)?;
+        Ok(String::from_utf8_lossy(&field[..length]).into_owned())
+    }
+}
+
+fn parse_authentication(mut fields: Fields) -> io::Result<Authentication> {
+    Ok(match fields.i32()? as u32 {
+        AUTH_OK => Authentication::Ok,
+        AUTH_CLEARTEXT_PASSWORD => Authentication::CleartextPassword,
+        AUTH_MD5_PASSWORD => {
+            let salt = fields.take(4)?;
+            Authentication::Md5Password([salt[0], salt[1], salt[2], salt[3]])
+        }
+        AUTH_SASL => {
+            let mut mechanisms = Vec::new();
+            loop {
+                match fields.string()? {
+                    x if x.is_empty() => break,
+                    x => mechanisms.push(x),
+                }
+            }
+            Authentication::Sasl(mechanisms)
+        }
+        AUTH_SASL_CONTINUE => Authentication::SaslContinue(fields.0.to_vec()),
+        AUTH_SASL_FINAL => Authentication::SaslFinal(fields.0.to_vec()),
+        x => Authentication::Other(x),
+    })
+}
+
+/// Parses the message of the type with the payload following its length.
+pub fn parse(kind: u8, payload: &[u8]) -> io::Result<Backend> {
+    let mut fields = Fields(payload);
+    Ok(match kind {
+        b'R' => Backend::Authentication(parse_authentication(fields)?),
+        b'S' => Backend::ParameterStatus(fields.string()?, fields.string()?),
+        b'Z' => Backend::ReadyForQuery,
+        b'T' => {
+            let count = fields.i16()?;
+            let mut columns = Vec::new();
+            for _ in 0..count {
+                columns.push(fields.string()?);
+                // table, column number, type, its size and modifier and the format
+                fields.take(18)?;
+            }
+            Backend::RowDescription(columns)
+        }
+        b'D' => {
+            let count = fields.i16()?;
+            let mut values = Vec::new();
+            for _ in 0..count {
+                values.push(match fields.i32()? {
+                    -1 => None,
+                    x => Some(fields.take(x as usize)?.to_vec()),
+                });
+            }
+            Backend::DataRow(values)
+        }
+        b'E' => {
+            let mut errors = Vec::new();
+            while let Some(&kind) = fields.0.first().filter(|x| **x != 0) {
+                fields.take(1)?;
+                errors.push((kind, fields.string()?));
+            }
+            Backend::Error(errors)
+        }
+        x => Backend::Other(x),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_frontend() {
+        assert_eq!(ssl_request(), [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f]);
+        assert_eq!(
+            startup(&[("user", "postgres")]),
+            b"\0\0\0\x17\0\x03\0\0user\0postgres\0\0"
+        );
+        assert_eq!(query("SELECT 1"), b"Q\0\0\0\x0dSELECT 1\0");
+        assert_eq!(
+            sasl_initial_response("SCRAM-SHA-256", b"n,,"),
+            b"p\0\0\0\x19SCRAM-SHA-256\0\0\0\0\x03n,,"
+        );
+        assert_eq!(terminate(), b"X\0\0\0\x04");
+    }
+
+    #[test]
+    fn parse_backend() {
+        assert_eq!(
+            parse(b'R', b"\0\0\0\x0aSCRAM-SHA-256-PLUS\0SCRAM-SHA-256\0\0").unwrap(),
+            Backend::Authentication(Authentication::Sasl(vec![
+                "SCRAM-SHA-256-PLUS".to_string(),
+                "SCRAM-SHA-256".to_string()
+            ]))
+        );
+        assert_eq!(
+            parse(b'R', b"\0\0\0\x05salt").unwrap(),
+            Backend::Authentication(Authentication::Md5Password(*b"salt"))
+        );
+        let mut description = b"\0\x02".to_vec();
+        for name in ["a", "b"] {
+            description.extend(name.as_bytes());
+            description.extend([0; 19]);
+        }
+        assert_eq!(
+            parse(b'T', &description).unwrap(),
+            Backend::RowDescription(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(
+            parse(b'D', b"\0\x02\0\0\0\x011\xff\xff\xff\xff").unwrap(),
+            Backend::DataRow(vec![Some(b"1".to_vec()), None])
+        );
+        assert_eq!(
+            parse(b'E', b"SFATAL\0C28P01\0Mpassword authentication failed\0\0").unwrap(),
+            Backend::Error(vec![
+                (b'S', "FATAL".to_string()),
+                (b'C', "28P01".to_string()),
+                (b'M', "password authentication failed".to_string()),
+            ])
+        );
+        assert!(parse(b'D', b"\0\x01\0\0\0\x05ab").is_err());
+    }
+}
@@ -0,0 +1,447 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to speak the frontend/backend protocol of PostgreSQL, e.g. to
+//! fingerprint the server version or to audit the configuration of a server with credentials.
+
+mod message;
+mod scram;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, io, net::IpAddr, sync::Mutex, time::Duration};
+
+use indexmap::IndexMap;
+use md5::{Digest, Md5};
+use message::{authentication_name, Authentication, Backend};
+use scram::{Scram, SCRAM_SHA_256};
+
+use crate::nasl::prelude::*;
+
+use super::network::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    ssl::SslOptions,
+    tcp::TcpConnection,
+};
+
+const POSTGRES_PORT: u16 = 5432;
+/// Largest message accepted from the server
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+const DEFAULT_USER: &str = "postgres";
+
+/// Names of the fields of an ErrorResponse returned to the script
+const ERROR_FIELDS: [(u8, &str); 8] = [
+    (b'S', "severity"),
+    (b'C', "code"),
+    (b'M', "message"),
+    (b'D', "detail"),
+    (b'H', "hint"),
+    (b'F', "file"),
+    (b'L', "line"),
+    (b'R', "routine"),
+];
+
+struct Connection {
+    conn: TcpConnection,
+    timeout: Duration,
+    ssl: bool,
+    /// Run-time parameters reported by the server, e.g. server_version
+    parameters: IndexMap<String, String>,
+}
+
+#[derive(Default)]
+struct Handles {
+    connections: HashMap<usize, Connection>,
+    last_id: usize,
+}
+
+/// Holds the PostgreSQL connections opened by a script.
+#[derive(Default)]
+pub struct Postgres {
+    handles: Mutex<Handles>,
+}
+
+fn io_error(e: io::Error) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(format!("PostgreSQL: {e}"), Some(NaslValue::Null))
+}
+
+fn error_message(fields: &[(u8, String)]) -> String {
+    fields
+        .iter()
+        .find(|(kind, _)| *kind == b'M')
+        .map_or_else(|| "unknown error".to_string(), |(_, x)| x.clone())
+}
+
+/// Returns the response to the MD5 challenge, which hashes the password salted with the user
+/// name before salting it with the salt of the server.
+fn md5_password(username: &str, password: &str, salt: &[u8]) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(password);
+    hasher.update(username);
+    let mut hasher = Md5::new_with_prefix(hex::encode(hasher.finalize()));
+    hasher.update(salt);
+    format!("md5{}", hex::encode(hasher.finalize()))
+}
+
+/// Returns the value as string when it is valid UTF-8, e.g. bytea values are returned as data.
+fn value_to_nasl(value: Option<Vec<u8>>) -> NaslValue {
+    match value.map(String::from_utf8) {
+        None => NaslValue::Null,
+        Some(Ok(x)) => NaslValue::String(x),
+        Some(Err(e)) => NaslValue::Data(e.into_bytes()),
+    }
+}
+
+impl Connection {
+    /// Connects to the server and negotiates TLS when the server supports it.
+    fn open(context: &Context, addrs: &[IpAddr], port: u16, timeout: Duration) -> io::Result<Self> {
+        let mut conn = NaslSockets::connect_tcp(context, addrs, port, None, timeout, None)?;
+        io::Write::write_all(&mut conn, &message::ssl_request())?;
+        let mut answer = [0];
+        conn.read_exact_with_timeout(&mut answer, timeout)?;
+        let ssl = match &answer {
+            b"S" => {
+                let options = SslOptions {
+                    sni: Some(context.target().to_string())
+                        .filter(|x| x.parse::<IpAddr>().is_err()),
+                    ..Default::default()
+                };
+                conn.negotiate_ssl(&options, timeout)?;
+                true
+            }
+            b"N" => false,
+            _ => return Err(invalid_data("unexpected answer to the SSL request")),
+        };
+        Ok(Self {
+            conn,
+            timeout,
+            ssl,
+            parameters: IndexMap::new(),
+        })
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        io::Write::write_all(&mut self.conn, message)?;
+        io::Write::flush(&mut self.conn)
+    }
+
+    fn receive(&mut self) -> io::Result<Backend> {
+        let mut header = [0; 5];
+        self.conn
+            .read_exact_with_timeout(&mut header, self.timeout)?;
+        let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if !(4..=MAX_MESSAGE).contains(&length) {
+            return Err(invalid_data("invalid message length"));
+        }
+        let mut payload = vec![0; length - 4];
+        self.conn
+            .read_exact_with_timeout(&mut payload, self.timeout)?;
+        message::parse(header[0], &payload)
+    }
+
+    /// Sends the startup message and returns the first answer of the server.
+    fn startup(&mut self, username: &str, database: Option<&str>) -> io::Result<Backend> {
+        let mut parameters = vec![("user", username)];
+        if let Some(database) = database {
+            parameters.push(("database", database));
+        }
+        self.send(&message::startup(&parameters))?;
+        self.receive()
+    }
+
+    /// Reads the parameters following a successful authentication until the server is ready.
+    fn wait_ready(&mut self) -> io::Result<()> {
+        loop {
+            match self.receive()? {
+                Backend::ParameterStatus(name, value) => {
+                    self.parameters.insert(name, value);
+                }
+                Backend::ReadyForQuery => return Ok(()),
+                Backend::Error(fields) => {
+                    return Err(io::Error::other(error_message(&fields)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Answers the authentication requests of the server starting with the first one.
+    fn authenticate(&mut self, first: Backend, username: &str, password: &str) -> io::Result<()> {
+        let mut sasl = None;
+        let mut answer = first;
+        loop {
+            match answer {
+                Backend::Authentication(Authentication::Ok) => return self.wait_ready(),
+                Backend::Authentication(Authentication::CleartextPassword) => {
+                    self.send(&message::password(password))?
+                }
+                Backend::Authentication(Authentication::Md5Password(salt)) => {
+                    self.send(&message::password(&md5_password(username, password, &salt)))?
+                }
+                Backend::Authentication(Authentication::Sasl(mechanisms))
+                    if mechanisms.iter().any(|x| x == SCRAM_SHA_256) =>
+                {
+                    // the server uses the user name of the startup message instead
+                    let client = Scram::new("", password, scram::nonce());
+                    self.send(&message::sasl_initial_response(
+                        SCRAM_SHA_256,
+                        &client.client_first(),
+                    ))?;
+                    sasl = Some(client);
+                }
+                Backend::Authentication(Authentication::SaslContinue(data)) => {
+                    let client = sasl
+                        .as_mut()
+                        .ok_or_else(|| invalid_data("unexpected SASL message"))?;
+                    self.send(&message::sasl_response(&client.client_final(&data)?))?;
+                }
+                Backend::Authentication(Authentication::SaslFinal(data)) => sasl
+                    .as_ref()
+                    .ok_or_else(|| invalid_data("unexpected SASL message"))?
+                    .verify(&data)?,
+                Backend::Authentication(x) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!(
+                            "unsupported authentication {}",
+                            authentication_name(x.code())
+                        ),
+                    ))
+                }
+                Backend::Error(fields) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        error_message(&fields),
+                    ))
+                }
+                _ => {}
+            }
+            answer = self.receive()?;
+        }
+    }
+
+    /// Runs the query and returns its rows or the message of the server when it fails.
+    fn query(&mut self, query: &str) -> io::Result<Result<Vec<NaslValue>, String>> {
+        self.send(&message::query(query))?;
+        let mut columns = vec![];
+        let mut rows = vec![];
+        let mut error = None;
+        loop {
+            match self.receive()? {
+                Backend::RowDescription(x) => columns = x,
+                Backend::DataRow(values) => rows.push(NaslValue::Dict(
+                    columns
+                        .iter()
+                        .cloned()
+                        .zip(values.into_iter().map(value_to_nasl))
+                        .collect(),
+                )),
+                Backend::ParameterStatus(name, value) => {
+                    self.parameters.insert(name, value);
+                }
+                Backend::Error(fields) => error = Some(error_message(&fields)),
+                Backend::ReadyForQuery => return Ok(error.map_or(Ok(rows), Err)),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Postgres {
+    /// Sends the startup message without credentials and returns the answer of the server,
+    /// which reveals the required authentication or, on errors, the source location of the
+    /// server code.
+    ///
+    /// - username: User of the startup message, "postgres" by default
+    /// - database: Database of the startup message, the one named like the user by default
+    /// - port: Port of the server, 5432 by default
+    /// - timeout: Time in seconds to wait for each answer, the network timeout of the scan by
+    ///   default
+    ///
+    /// Returns NULL when the connection fails.
+    #[nasl_function(named(username, database, port, timeout))]
+    fn postgres_startup(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        database: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (addrs, port, timeout) = target_service(context, port, POSTGRES_PORT, timeout)?;
+        let mut connection = match Connection::open(context, &addrs, port, timeout) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, port, "unable to connect to the PostgreSQL server");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut result = IndexMap::new();
+        result.insert("ssl".to_string(), NaslValue::Boolean(connection.ssl));
+        match connection
+            .startup(username.unwrap_or(DEFAULT_USER), database)
+            .map_err(io_error)?
+        {
+            Backend::Authentication(authentication) => {
+                let code = authentication.code();
+                result.insert("authentication".to_string(), NaslValue::Number(code as i64));
+                result.insert(
+                    "authentication_name".to_string(),
+                    NaslValue::String(authentication_name(code).to_string()),
+                );
+                match authentication {
+                    Authentication::Sasl(mechanisms) => {
+                        result.insert(
+                            "mechanisms".to_string(),
+                            NaslValue::Array(
+                                mechanisms.into_iter().map(NaslValue::String).collect(),
+                            ),
+                        );
+                    }
+                    Authentication::Ok => {
+                        connection.wait_ready().map_err(io_error)?;
+                        let parameters = connection
+                            .parameters
+                            .drain(..)
+                            .map(|(name, value)| (name, NaslValue::String(value)))
+                            .collect();
+                        result.insert("parameters".to_string(), NaslValue::Dict(parameters));
+                        connection.send(&message::terminate()).map_err(io_error)?;
+                    }
+                    _ => {}
+                }
+            }
+            Backend::Error(fields) => {
+                for (kind, value) in fields {
+                    if let Some((_, name)) = ERROR_FIELDS.iter().find(|(x, _)| *x == kind) {
+                        result.insert(name.to_string(), NaslValue::String(value));
+                    }
+                }
+            }
+            _ => return Err(io_error(invalid_data("unexpected message"))),
+        }
+        Ok(NaslValue::Dict(result))
+    }
+
+    /// Connects to the server and authenticates with the password, using TLS when the server
+    /// supports it. Trust, password, MD5 and SCRAM-SHA-256 authentication are supported.
+    ///
+    /// - username: User to authenticate, "postgres" by default
+    /// - password: Password of the user, empty by default
+    /// - database: Database to connect to, the one named like the user by default
+    /// - port: Port of the server, 5432 by default
+    /// - timeout: Time in seconds to wait for each answer, the network timeout of the scan by
+    ///   default
+    ///
+    /// Returns the handle of the connection or NULL when the connection or the authentication
+    /// fails.
+    #[nasl_function(named(username, password, database, port, timeout))]
+    fn postgres_connect(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        password: Option<&str>,
+        database: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (addrs, port, timeout) = target_service(context, port, POSTGRES_PORT, timeout)?;
+        let username = username.unwrap_or(DEFAULT_USER);
+        let connection = Connection::open(context, &addrs, port, timeout).and_then(|mut x| {
+            let first = x.startup(username, database)?;
+            x.authenticate(first, username, password.unwrap_or_default())?;
+            Ok(x)
+        });
+        let connection = match connection {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, port, "unable to log into the PostgreSQL server");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.connections.insert(id, connection);
+        Ok(NaslValue::Number(id as i64))
+    }
+
+    fn with_connection<T>(
+        &self,
+        handle: usize,
+        f: impl FnOnce(&mut Connection) -> T,
+    ) -> Result<T, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let connection = handles.connections.get_mut(&handle).ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(format!("Unknown PostgreSQL connection {handle}"), None)
+        })?;
+        Ok(f(connection))
+    }
+
+    /// Runs the query, which may consist of several statements, with the simple query protocol.
+    ///
+    /// Returns the rows of all statements as arrays indexed by the column names, with NULL for
+    /// SQL NULL values, or NULL when the query fails.
+    #[nasl_function(named(handle, query))]
+    fn postgres_query(&self, handle: usize, query: &str) -> Result<NaslValue, FunctionErrorKind> {
+        match self
+            .with_connection(handle, |x| x.query(query))?
+            .map_err(io_error)?
+        {
+            Ok(rows) => Ok(NaslValue::Array(rows)),
+            Err(e) => {
+                tracing::debug!(%e, "PostgreSQL query failed");
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Returns the run-time parameters reported by the server, e.g. server_version,
+    /// server_encoding or is_superuser.
+    #[nasl_function(named(handle))]
+    fn postgres_parameters(&self, handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        self.with_connection(handle, |x| {
+            NaslValue::Dict(
+                x.parameters
+                    .iter()
+                    .map(|(name, value)| (name.clone(), NaslValue::String(value.clone())))
+                    .collect(),
+            )
+        })
+    }
+
+    /// Terminates and closes the connection.
+    #[nasl_function(named(handle))]
+    fn postgres_close(&self, handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let mut connection = self
+            .handles
+            .lock()
+            .unwrap()
+            .connections
+            .remove(&handle)
+            .ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(
+                    format!("Unknown PostgreSQL connection {handle}"),
+                    None,
+                )
+            })?;
+        if let Err(e) = connection.send(&message::terminate()) {
+            tracing::debug!(%e, "unable to terminate the PostgreSQL connection");
+        }
+        Ok(NaslValue::Null)
+    }
+}
+
+function_set! {
+    Postgres,
+    sync_stateful,
+    (
+        (Postgres::postgres_startup, "postgres_startup"),
+        (Postgres::postgres_connect, "postgres_connect"),
+        (Postgres::postgres_query, "postgres_query"),
+        (Postgres::postgres_parameters, "postgres_parameters"),
+        (Postgres::postgres_close, "postgres_close"),
+    )
+}
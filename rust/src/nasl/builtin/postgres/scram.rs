@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Client side of the SCRAM-SHA-256 mechanism as described in RFC 5802 and RFC 7677,
+//! without channel binding.
+
+use std::io;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::nasl::builtin::network::network_utils::invalid_data;
+
+pub const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+/// Header of the client messages announcing that channel binding is not supported
+const GS2_HEADER: &str = "n,,";
+/// Largest iteration count accepted from the server, which could stall the scan otherwise
+const MAX_ITERATIONS: u32 = 1_000_000;
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Returns the value of the attribute, e.g. the nonce of "r=...".
+fn attribute(message: &str, name: char) -> Option<&str> {
+    message
+        .split(',')
+        .find_map(|x| x.strip_prefix(name).and_then(|x| x.strip_prefix('=')))
+}
+
+/// Returns a random nonce of printable characters.
+pub fn nonce() -> String {
+    let mut nonce = [0; 18];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    STANDARD.encode(nonce)
+}
+
+pub struct Scram {
+    password: String,
+    nonce: String,
+    client_first_bare: String,
+    server_signature: Option<Vec<u8>>,
+}
+
+impl Scram {
+    pub fn new(username: &str, password: &str, nonce: String) -> Self {
+        let username = username.replace('=', "=3D").replace(',', "=2C");
+        Self {
+            password: password.to_string(),
+            client_first_bare: format!("n={username},r={nonce}"),
+            nonce,
+            server_signature: None,
+        }
+    }
+
+    pub fn client_first(&self) -> Vec<u8> {
+        format!("{GS2_HEADER}{}", self.client_first_bare).into_bytes()
+    }
+
+    /// Returns the proof of the password for the salt and iteration count of the server.
+    pub fn client_final(&mut self, server_first: &[u8]) -> io::Result<Vec<u8>> {
+        let server_first =
+            std::str::from_utf8(server_first).map_err(|_| invalid_data("invalid SCRAM message"))?;
+        let nonce = attribute(server_first, 'r')
+            .filter(|x| x.len() > self.nonce.len() && x.starts_with(&self.nonce))
+            .ok_or_else(|| invalid_data("invalid SCRAM nonce"))?;
+        let salt = attribute(server_first, 's')
+            .and_then(|x| STANDARD.decode(x).ok())
+            .ok_or_else(|| invalid_data("invalid SCRAM salt"))?;
+        let iterations = attribute(server_first, 'i')
+            .and_then(|x| x.parse::<u32>().ok())
+            .filter(|x| (1..=MAX_ITERATIONS).contains(x))
+            .ok_or_else(|| invalid_data("invalid SCRAM iteration count"))?;
+
+        let mut salted = [0; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(self.password.as_bytes(), &salt, iterations, &mut salted);
+        let client_key = hmac(&salted, b"Client Key");
+        let stored_key = Sha256::digest(&client_key);
+        let without_proof = format!("c={},r={nonce}", STANDARD.encode(GS2_HEADER));
+        let auth_message = format!("{},{server_first},{without_proof}", self.client_first_bare);
+        let signature = hmac(&stored_key, auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(signature)
+            .map(|(a, b)| a ^ b)
+            .collect();
+        let server_key = hmac(&salted, b"Server Key");
+        self.server_signature = Some(hmac(&server_key, auth_message.as_bytes()));
+        Ok(format!("{without_proof},p={}", STANDARD.encode(proof)).into_bytes())
+    }
+
+    /// Verifies that the server knows the password as well.
+    pub fn verify(&self, server_final: &[u8]) -> io::Result<()> {
+        let server_final =
+            std::str::from_utf8(server_final).map_err(|_| invalid_data("invalid SCRAM message"))?;
+        if let Some(error) = attribute(server_final, 'e') {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("SCRAM authentication failed: {error}"),
+            ));
+        }
+        let signature = attribute(server_final, 'v').and_then(|x| STANDARD.decode(x).ok());
+        match (signature, &self.server_signature) {
+            (Some(x), Some(expected)) if x == *expected => Ok(()),
+            _ => Err(invalid_data("invalid SCRAM server signature")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example of RFC 7677
+    #[test]
+    fn exchange() {
+        let mut scram = Scram::new("user", "pencil", "rOprNGfwEbeRWgbNEkqO".to_string());
+        assert_eq!(scram.client_first(), b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+        let client_final = scram
+            .client_final(
+                b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                  s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(client_final).unwrap(),
+            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+             p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+        );
+        scram
+            .verify(b"v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+            .unwrap();
+        assert!(scram.verify(b"v=AAAA").is_err());
+        assert!(scram.verify(b"e=invalid-proof").is_err());
+    }
+
+    #[test]
+    fn invalid_server_first() {
+        let mut scram = Scram::new("", "pencil", "abc".to_string());
+        // the nonce of the server has to extend the one of the client
+        assert!(scram.client_final(b"r=xyz,s=AAAA,i=4096").is_err());
+        assert!(scram.client_final(b"r=abc,s=AAAA,i=4096").is_err());
+        assert!(scram.client_final(b"r=abcd,s=AAAA,i=0").is_err());
+        assert!(scram.client_final(b"r=abcd,s=AAAA,i=4096").is_ok());
+    }
+}
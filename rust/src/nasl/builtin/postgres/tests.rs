@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::nasl::builtin::network::network_utils::tests::tcp_server;
+use crate::nasl::builtin::network::ssl::tests::acceptor;
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+use super::md5_password;
+
+const PASSWORD: &str = "secret";
+const SALT: &[u8] = b"0123456789abcdef";
+const ITERATIONS: u32 = 4096;
+
+#[derive(Clone, Copy)]
+enum Method {
+    Trust,
+    Md5,
+    Scram,
+}
+
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+fn read_message(stream: &mut dyn Stream) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 5];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    let mut payload = vec![0; length - 4];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+fn write_message(stream: &mut dyn Stream, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let mut message = vec![kind];
+    message.extend(((payload.len() + 4) as u32).to_be_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+fn authentication(code: u32, data: &[u8]) -> Vec<u8> {
+    let mut payload = code.to_be_bytes().to_vec();
+    payload.extend_from_slice(data);
+    payload
+}
+
+fn error(severity: &str, code: &str, message: &str, routine: &str) -> Vec<u8> {
+    let mut payload = vec![];
+    for (kind, value) in [
+        (b'S', severity),
+        (b'C', code),
+        (b'M', message),
+        (b'F', "auth.c"),
+        (b'L', "326"),
+        (b'R', routine),
+    ] {
+        payload.push(kind);
+        payload.extend(value.as_bytes());
+        payload.push(0);
+    }
+    payload.push(0);
+    payload
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Verifies the proof of the client like the server does with the stored keys of the password
+/// and returns the signature of the server.
+fn verify_scram(client_first_bare: &str, server_first: &str, client_final: &str) -> Option<String> {
+    let mut salted = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(PASSWORD.as_bytes(), SALT, ITERATIONS, &mut salted);
+    let stored_key = Sha256::digest(hmac(&salted, b"Client Key"));
+    let (without_proof, proof) = client_final.split_once(",p=")?;
+    let auth_message = format!("{client_first_bare},{server_first},{without_proof}");
+    let signature = hmac(&stored_key, auth_message.as_bytes());
+    let client_key: Vec<u8> = STANDARD
+        .decode(proof)
+        .ok()?
+        .iter()
+        .zip(signature)
+        .map(|(a, b)| a ^ b)
+        .collect();
+    (Sha256::digest(client_key) == stored_key).then(|| {
+        let server_key = hmac(&salted, b"Server Key");
+        format!(
+            "v={}",
+            STANDARD.encode(hmac(&server_key, auth_message.as_bytes()))
+        )
+    })
+}
+
+/// Authenticates the user postgres with the method and returns whether the password is valid.
+fn authenticate(stream: &mut dyn Stream, method: Method) -> io::Result<bool> {
+    match method {
+        Method::Trust => Ok(true),
+        Method::Md5 => {
+            write_message(stream, b'R', &authentication(5, b"salt"))?;
+            let (_, password) = read_message(stream)?;
+            let expected = md5_password("postgres", PASSWORD, b"salt");
+            Ok(password.strip_suffix(&[0]) == Some(expected.as_bytes()))
+        }
+        Method::Scram => {
+            write_message(stream, b'R', &authentication(10, b"SCRAM-SHA-256\0\0"))?;
+            let (_, initial) = read_message(stream)?;
+            let initial = initial
+                .strip_prefix(b"SCRAM-SHA-256\0")
+                .expect("SCRAM-SHA-256 mechanism");
+            let client_first = String::from_utf8(initial[4..].to_vec()).unwrap();
+            let client_first_bare = client_first.strip_prefix("n,,").unwrap();
+            let nonce = client_first_bare.split_once(",r=").unwrap().1;
+            let server_first =
+                format!("r={nonce}server,s={},i={ITERATIONS}", STANDARD.encode(SALT));
+            write_message(stream, b'R', &authentication(11, server_first.as_bytes()))?;
+            let (_, client_final) = read_message(stream)?;
+            let client_final = String::from_utf8(client_final).unwrap();
+            match verify_scram(client_first_bare, &server_first, &client_final) {
+                Some(signature) => {
+                    write_message(stream, b'R', &authentication(12, signature.as_bytes()))?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+}
+
+fn answer_query(stream: &mut dyn Stream, query: &[u8]) -> io::Result<()> {
+    match query {
+        b"SELECT 1 AS a, NULL AS b\0" => {
+            let mut description = 2u16.to_be_bytes().to_vec();
+            for name in ["a", "b"] {
+                description.extend(name.as_bytes());
+                description.extend([0; 19]);
+            }
+            write_message(stream, b'T', &description)?;
+            write_message(stream, b'D', b"\0\x02\0\0\0\x011\xff\xff\xff\xff")?;
+            write_message(stream, b'C', b"SELECT 1\0")?;
+        }
+        _ => {
+            let error = error("ERROR", "42601", "syntax error", "scanner_yyerror");
+            write_message(stream, b'E', &error)?;
+        }
+    }
+    write_message(stream, b'Z', b"I")
+}
+
+/// Answers like a server with the user postgres, whose password is checked with the method,
+/// and a query selecting a number and NULL.
+fn serve(tcp: TcpStream, ssl: bool, method: Method) -> io::Result<()> {
+    let mut stream: Box<dyn Stream> = Box::new(tcp.try_clone()?);
+    let mut request = [0; 8];
+    stream.read_exact(&mut request)?;
+    assert_eq!(request, [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f]);
+    if ssl {
+        stream.write_all(b"S")?;
+        let tls = acceptor()
+            .build()
+            .accept(tcp)
+            .map_err(|_| io::Error::other("TLS handshake failed"))?;
+        stream = Box::new(tls);
+    } else {
+        stream.write_all(b"N")?;
+    }
+    let mut length = [0; 4];
+    stream.read_exact(&mut length)?;
+    let mut startup = vec![0; u32::from_be_bytes(length) as usize - 4];
+    stream.read_exact(&mut startup)?;
+    if !startup.starts_with(b"\0\x03\0\0user\0postgres\0") {
+        let error = error(
+            "FATAL",
+            "28000",
+            "role does not exist",
+            "InitializeSessionUserId",
+        );
+        return write_message(&mut *stream, b'E', &error);
+    }
+    if !authenticate(&mut *stream, method)? {
+        let error = error(
+            "FATAL",
+            "28P01",
+            "password authentication failed for user \"postgres\"",
+            "auth_failed",
+        );
+        return write_message(&mut *stream, b'E', &error);
+    }
+    write_message(&mut *stream, b'R', &authentication(0, b""))?;
+    write_message(&mut *stream, b'S', b"server_version\x0016.2\0")?;
+    write_message(&mut *stream, b'S', b"server_encoding\0UTF8\0")?;
+    write_message(&mut *stream, b'K', &[0; 8])?;
+    write_message(&mut *stream, b'Z', b"I")?;
+    loop {
+        match read_message(&mut *stream)? {
+            (b'Q', query) => answer_query(&mut *stream, &query)?,
+            (b'X', _) => return Ok(()),
+            (x, _) => panic!("unexpected message {x}"),
+        }
+    }
+}
+
+fn server(ssl: bool, method: Method) -> u16 {
+    tcp_server(move |stream| {
+        thread::spawn(move || serve(stream, ssl, method));
+    })
+}
+
+#[test]
+fn startup() {
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    let port = server(false, Method::Trust);
+    t.run(format!("r = postgres_startup(port: {port});"));
+    t.ok(r#"r["ssl"];"#, false);
+    t.ok(r#"r["authentication_name"];"#, "ok");
+    t.ok(r#"r["parameters"]["server_version"];"#, "16.2");
+    let port = server(true, Method::Scram);
+    t.run(format!("r = postgres_startup(port: {port});"));
+    t.ok(r#"r["ssl"];"#, true);
+    t.ok(r#"r["authentication"];"#, 10);
+    t.ok(r#"r["mechanisms"][0];"#, "SCRAM-SHA-256");
+    t.run(format!(
+        r#"r = postgres_startup(username: "nobody", port: {port});"#
+    ));
+    t.ok(r#"r["code"];"#, "28000");
+    t.ok(r#"r["message"];"#, "role does not exist");
+    t.ok(r#"r["routine"];"#, "InitializeSessionUserId");
+    t.ok(r#"r["line"];"#, "326");
+}
+
+#[test]
+fn connect_and_query() {
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    let port = server(true, Method::Scram);
+    t.ok(
+        format!(r#"h = postgres_connect(password: "secret", port: {port});"#),
+        1,
+    );
+    t.ok(
+        format!(r#"postgres_connect(password: "wrong", port: {port});"#),
+        NaslValue::Null,
+    );
+    t.run(r#"r = postgres_query(handle: h, query: "SELECT 1 AS a, NULL AS b");"#);
+    t.ok(r#"r[0]["a"];"#, "1");
+    t.ok(r#"r[0]["b"];"#, NaslValue::Null);
+    t.ok(
+        r#"postgres_query(handle: h, query: "SELEC");"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        r#"postgres_parameters(handle: h)["server_encoding"];"#,
+        "UTF8",
+    );
+    t.ok("postgres_close(handle: h);", NaslValue::Null);
+    check_err_matches!(
+        t,
+        "postgres_close(handle: h);",
+        FunctionErrorKind::Diagnostic(_, _)
+    );
+    let port = server(false, Method::Md5);
+    t.ok(
+        format!(r#"h = postgres_connect(password: "secret", port: {port});"#),
+        2,
+    );
+    t.ok(
+        r#"postgres_parameters(handle: h)["server_version"];"#,
+        "16.2",
+    );
+    t.ok(
+        format!(r#"postgres_connect(password: "wrong", port: {port});"#),
+        NaslValue::Null,
+    );
+}
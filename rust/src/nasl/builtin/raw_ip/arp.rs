@@ -0,0 +1,170 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions for ARP host discovery on the local network
+//!
+//! Unlike send_arp_request, which returns the source of the first captured ARP frame, the probe
+//! only accepts replies of the asked address and can be sent for any IPv4 address of a local
+//! network, not only the target.
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Instant,
+};
+
+use indexmap::IndexMap;
+use pnet::packet::{
+    arp::{ArpOperations, ArpPacket},
+    ethernet::{EtherTypes, EthernetPacket},
+    Packet,
+};
+
+use super::super::host::get_host_ip;
+use super::capture::{answer_timeout, PacketCapture};
+use super::frame_forgery::{forge_arp_frame, get_local_mac_address};
+use super::raw_ip_utils::{get_interface, get_source_ip, ipstr2ipaddr};
+use crate::nasl::prelude::*;
+
+/// Describes an ARP packet without link layer header.
+fn parse_arp(packet: &[u8]) -> Option<IndexMap<String, NaslValue>> {
+    let arp = ArpPacket::new(packet)?;
+    if arp.get_hw_addr_len() != 6 || arp.get_proto_addr_len() != 4 {
+        return None;
+    }
+    let string = |x: &dyn ToString| NaslValue::String(x.to_string());
+    Some(IndexMap::from([
+        (
+            "op".to_string(),
+            NaslValue::Number(arp.get_operation().0 as i64),
+        ),
+        ("sender_mac".to_string(), string(&arp.get_sender_hw_addr())),
+        (
+            "sender_ip".to_string(),
+            string(&arp.get_sender_proto_addr()),
+        ),
+        ("target_mac".to_string(), string(&arp.get_target_hw_addr())),
+        (
+            "target_ip".to_string(),
+            string(&arp.get_target_proto_addr()),
+        ),
+    ]))
+}
+
+/// Returns the MAC address of the sender when the packet is an ARP reply for the address.
+fn reply_mac(packet: &[u8], ip: Ipv4Addr) -> Option<String> {
+    let arp = ArpPacket::new(packet)?;
+    (arp.get_operation() == ArpOperations::Reply && arp.get_sender_proto_addr() == ip)
+        .then(|| arp.get_sender_hw_addr().to_string())
+}
+
+/// Sends an ARP who-has request for an IPv4 address of a local network.
+///
+/// - ip: the address to resolve, the target by default
+/// - interface: the interface to send the request on, by default the one routing to the address
+/// - timeout: time to wait for the reply in seconds, by default the network timeout of the scan
+///
+/// Returns the MAC address of the reply or NULL when nobody answered.
+#[nasl_function(named(ip, interface, timeout))]
+fn arp_probe(
+    context: &Context,
+    ip: Option<&str>,
+    interface: Option<&str>,
+    timeout: Option<u64>,
+) -> Result<Option<String>, FunctionErrorKind> {
+    let ip = match ip {
+        Some(x) => ipstr2ipaddr(x)?,
+        None => get_host_ip(context)?,
+    };
+    let IpAddr::V4(dst_ip) = ip else {
+        return Err(FunctionErrorKind::Diagnostic(
+            format!("ARP is not supported for the IPv6 address {ip}"),
+            None,
+        ));
+    };
+    let device = match interface {
+        Some(x) if !x.is_empty() => x.into(),
+        _ => get_interface(ip, context.source_binding())?,
+    };
+    let src_mac = get_local_mac_address(&device.name).ok_or_else(|| {
+        FunctionErrorKind::Dirty(format!("No MAC address of interface {}", device.name))
+    })?;
+    let src_ip = match get_source_ip(ip, 50000u16, context.source_binding())? {
+        IpAddr::V4(x) => x,
+        IpAddr::V6(x) => x
+            .to_ipv4_mapped()
+            .ok_or_else(|| FunctionErrorKind::Dirty(format!("No IPv4 source address for {ip}")))?,
+    };
+
+    let filter = format!("arp and src host {dst_ip}");
+    let mut capture = PacketCapture::open(device, &filter)?;
+    capture.send_frame(&forge_arp_frame(src_mac, src_ip, dst_ip))?;
+    let deadline = Instant::now() + answer_timeout(context, ip, timeout);
+    while let Some(packet) =
+        capture.next_datagram(deadline.saturating_duration_since(Instant::now()))?
+    {
+        if let Some(mac) = reply_mac(&packet, dst_ip) {
+            return Ok(Some(mac));
+        }
+    }
+    Ok(None)
+}
+
+/// Parses an ethernet frame containing an ARP packet, e.g. as returned by send_frame.
+///
+/// Returns an array with the operation as op, sender_mac, sender_ip, target_mac and target_ip
+/// or NULL for other frames.
+#[nasl_function]
+fn parse_arp_frame(frame: &[u8]) -> Option<NaslValue> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Arp {
+        return None;
+    }
+    parse_arp(ethernet.payload()).map(NaslValue::Dict)
+}
+
+pub struct Arp;
+
+function_set! {
+    Arp,
+    sync_stateless,
+    (
+        arp_probe,
+        parse_arp_frame,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use pnet_base::MacAddr;
+
+    use crate::nasl::syntax::NaslValue;
+
+    use super::super::frame_forgery::forge_arp_frame;
+    use super::{parse_arp, reply_mac};
+
+    #[test]
+    fn reply() {
+        let mac = MacAddr(0x52, 0x54, 0, 0x12, 0x34, 0x56);
+        let ip = Ipv4Addr::new(192, 0, 2, 2);
+        let mut frame = forge_arp_frame(mac, ip, Ipv4Addr::new(192, 0, 2, 1));
+        let request = &frame[14..];
+        assert_eq!(reply_mac(request, ip), None);
+        let parsed = parse_arp(request).unwrap();
+        assert_eq!(parsed["op"], NaslValue::Number(1));
+        assert_eq!(
+            parsed["target_ip"],
+            NaslValue::String("192.0.2.1".to_string())
+        );
+
+        // operation is a reply
+        frame[21] = 2;
+        assert_eq!(
+            reply_mac(&frame[14..], ip),
+            Some("52:54:00:12:34:56".to_string())
+        );
+        assert_eq!(reply_mac(&frame[14..], Ipv4Addr::new(192, 0, 2, 3)), None);
+    }
+}
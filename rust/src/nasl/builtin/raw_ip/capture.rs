@@ -0,0 +1,132 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Captures the answers to sent packets.
+//!
+//! The capture device is opened and its BPF filter is set before anything is sent, so that an
+//! early answer is not lost. Captured frames are returned without their link layer header, as
+//! scripts expect IP datagrams regardless of the interface the answer was received on.
+
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use pcap::{Active, Capture, Device, Linktype};
+
+use super::raw_ip_utils::get_interface;
+use crate::nasl::builtin::host::get_host_ip;
+use crate::nasl::prelude::*;
+
+/// Default time to wait for an answer in seconds
+pub const DEFAULT_TIMEOUT: u64 = 5;
+
+/// Time a single read on the capture device blocks, the overall timeout is checked in between
+const READ_TIMEOUT_MS: i32 = 100;
+
+/// An opened capture device with its filter set
+pub struct PacketCapture {
+    capture: Capture<Active>,
+    header_length: usize,
+}
+
+/// Returns the length of the link layer header preceding the network layer packet.
+fn link_header_length(linktype: Linktype) -> Option<usize> {
+    match linktype {
+        Linktype::RAW | Linktype::IPV4 | Linktype::IPV6 => Some(0),
+        Linktype::NULL | Linktype::LOOP => Some(4),
+        Linktype::ETHERNET => Some(14),
+        Linktype::LINUX_SLL => Some(16),
+        Linktype::LINUX_SLL2 => Some(20),
+        _ => None,
+    }
+}
+
+/// Returns the given interface or the interface packets to the target are sent from.
+pub fn capture_device(
+    context: &Context,
+    interface: Option<&str>,
+) -> Result<Device, FunctionErrorKind> {
+    match interface {
+        Some(x) if !x.is_empty() => Ok(Device::from(x)),
+        _ => get_interface(get_host_ip(context)?, context.source_binding()),
+    }
+}
+
+/// Returns the time to wait for the answer of the target to a sent packet.
+///
+/// A timeout given by the script in seconds takes precedence over the network timeout of the
+/// scan.
+pub fn answer_timeout(context: &Context, target: IpAddr, timeout: Option<u64>) -> Duration {
+    timeout.map(Duration::from_secs).unwrap_or_else(|| {
+        context
+            .network_timeout()
+            .timeout(&[target], Duration::from_secs(DEFAULT_TIMEOUT))
+    })
+}
+
+fn capture_error(e: pcap::Error) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(format!("capture: {e}"), Some(NaslValue::Null))
+}
+
+impl PacketCapture {
+    /// Opens the device in promiscuous mode and sets the filter, an empty filter captures
+    /// everything.
+    pub fn open(device: Device, filter: &str) -> Result<Self, FunctionErrorKind> {
+        let mut capture = Capture::from_device(device)
+            .and_then(|c| c.promisc(true).timeout(READ_TIMEOUT_MS).open())
+            .map_err(capture_error)?;
+        let linktype = capture.get_datalink();
+        let header_length = link_header_length(linktype).ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(
+                format!("capture: unsupported link type {:?}", linktype),
+                Some(NaslValue::Null),
+            )
+        })?;
+        capture.filter(filter, true).map_err(capture_error)?;
+        Ok(Self {
+            capture,
+            header_length,
+        })
+    }
+
+    /// Sends a link layer frame on the device.
+    pub fn send_frame(&mut self, frame: &[u8]) -> Result<(), FunctionErrorKind> {
+        self.capture.sendpacket(frame).map_err(capture_error)
+    }
+
+    /// Returns the next captured IP datagram or None when nothing matching the filter is
+    /// captured within the timeout.
+    pub fn next_datagram(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            match self.capture.next_packet() {
+                Ok(packet) if packet.data.len() > self.header_length => {
+                    return Ok(Some(packet.data[self.header_length..].to_vec()));
+                }
+                Ok(_) | Err(pcap::Error::TimeoutExpired) => {}
+                Err(e) => return Err(capture_error(e)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pcap::Linktype;
+
+    use super::link_header_length;
+
+    #[test]
+    fn header_length() {
+        assert_eq!(link_header_length(Linktype::ETHERNET), Some(14));
+        assert_eq!(link_header_length(Linktype::LINUX_SLL), Some(16));
+        assert_eq!(link_header_length(Linktype::RAW), Some(0));
+        assert_eq!(link_header_length(Linktype::IEEE802_11), None);
+    }
+}
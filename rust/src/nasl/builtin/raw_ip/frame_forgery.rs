@@ -13,7 +13,7 @@ use pcap::{Capture, Device};
 
 use super::super::host::get_host_ip;
 
-use super::raw_ip_utils::{get_interface_by_local_ip, get_source_ip, ipstr2ipaddr};
+use super::raw_ip_utils::{get_interface, get_interface_by_local_ip, get_source_ip, ipstr2ipaddr};
 
 use tracing::info;
 
@@ -238,7 +238,7 @@ impl From<ArpFrame> for Vec<u8> {
 }
 
 /// Forge a data link layer frame with an ARP request in the payload
-fn forge_arp_frame(eth_src: MacAddr, src_ip: Ipv4Addr, dst_ip: Ipv4Addr) -> Vec<u8> {
+pub fn forge_arp_frame(eth_src: MacAddr, src_ip: Ipv4Addr, dst_ip: Ipv4Addr) -> Vec<u8> {
     let mut frame = Frame::new();
     frame.set_srchaddr(eth_src);
     frame.set_dsthaddr(MacAddr::broadcast());
@@ -284,7 +284,7 @@ fn validate_mac_address(v: Option<&ContextType>) -> Result<MacAddr, FunctionErro
 }
 
 /// Return the MAC address, given the interface name
-fn get_local_mac_address(name: &str) -> Option<MacAddr> {
+pub fn get_local_mac_address(name: &str) -> Option<MacAddr> {
     match interfaces().into_iter().find(|x| x.name == *name) {
         Some(dev) => dev.mac,
         _ => None,
@@ -369,8 +369,8 @@ fn nasl_send_arp_request(
             "IPv6 does not support ARP protocol.",
         ));
     }
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
-    let iface = get_interface_by_local_ip(local_ip)?;
+    let local_ip = get_source_ip(target_ip, 50000u16, context.source_binding())?;
+    let iface = get_interface(target_ip, context.source_binding())?;
     let local_mac_address = match get_local_mac_address(&iface.name) {
         Some(x) => x,
         _ => {
@@ -519,8 +519,7 @@ fn nasl_send_frame(register: &Register, context: &Context) -> Result<NaslValue,
 
     let target_ip = get_host_ip(context)?;
 
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
-    let iface = get_interface_by_local_ip(local_ip)?;
+    let iface = get_interface(target_ip, context.source_binding())?;
 
     // send the frame and get a response if pcap_active enabled
     match send_frame(frame, &iface, pcap_active, filter, timeout)? {
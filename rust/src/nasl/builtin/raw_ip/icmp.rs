@@ -0,0 +1,379 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL convenience functions for ICMP and ICMPv6
+//!
+//! The messages are sent on an ICMP socket, the kernel adds the IP header. Answers are read
+//! from the capture device and returned as IP datagrams, so that they can be inspected with
+//! get_icmp_element or get_icmp_v6_element.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use indexmap::IndexMap;
+use pnet::packet::{
+    icmp::{self, IcmpPacket},
+    ip::IpNextHeaderProtocols,
+    ipv4::Ipv4Packet,
+    ipv6::Ipv6Packet,
+};
+use socket2::{Domain, Protocol, Socket};
+
+use super::super::host::get_host_ip;
+use super::capture::{answer_timeout, capture_device, PacketCapture};
+use crate::nasl::builtin::misc::random_impl;
+use crate::nasl::prelude::*;
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_UNREACHABLE: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMP_TIMESTAMP_REQUEST: u8 = 13;
+const ICMP_TIMESTAMP_REPLY: u8 = 14;
+const ICMP6_UNREACHABLE: u8 = 1;
+const ICMP6_TIME_EXCEEDED: u8 = 3;
+const ICMP6_ECHO_REQUEST: u8 = 128;
+const ICMP6_ECHO_REPLY: u8 = 129;
+/// Length of the ICMP header including identifier and sequence number
+const ICMP_HEADER_LENGTH: usize = 8;
+
+/// Encodes an ICMP or ICMPv6 message with identifier and sequence number.
+///
+/// The checksum of ICMPv6 messages covers a pseudo header and is overwritten by the kernel.
+fn encode_message(icmp_type: u8, id: u16, seq: u16, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![icmp_type, 0, 0, 0];
+    result.extend_from_slice(&id.to_be_bytes());
+    result.extend_from_slice(&seq.to_be_bytes());
+    result.extend_from_slice(data);
+    if let Some(packet) = IcmpPacket::new(&result) {
+        let chksum = icmp::checksum(&packet);
+        result[2..4].copy_from_slice(&chksum.to_be_bytes());
+    }
+    result
+}
+
+/// Returns the ICMP message of an IPv4 or IPv6 datagram together with its source.
+fn icmp_message(datagram: &[u8]) -> Option<(IpAddr, &[u8])> {
+    match datagram.first()? >> 4 {
+        4 => {
+            let ip = Ipv4Packet::new(datagram)?;
+            let offset = ip.get_header_length() as usize * 4;
+            (ip.get_next_level_protocol() == IpNextHeaderProtocols::Icmp)
+                .then(|| datagram.get(offset..))
+                .flatten()
+                .map(|x| (IpAddr::V4(ip.get_source()), x))
+        }
+        6 => {
+            let ip = Ipv6Packet::new(datagram)?;
+            (ip.get_next_header() == IpNextHeaderProtocols::Icmpv6)
+                .then(|| datagram.get(40..))
+                .flatten()
+                .map(|x| (IpAddr::V6(ip.get_source()), x))
+        }
+        _ => None,
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|x| u16::from_be_bytes([x[0], x[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+}
+
+/// Returns true when the datagram is a reply of the given type with identifier and sequence
+fn is_reply(datagram: &[u8], reply_type: u8, id: u16, seq: u16) -> bool {
+    icmp_message(datagram).is_some_and(|(_, message)| {
+        message.first() == Some(&reply_type)
+            && read_u16(message, 4) == Some(id)
+            && read_u16(message, 6) == Some(seq)
+    })
+}
+
+/// Describes an ICMP destination unreachable or time exceeded message
+fn parse_error(datagram: &[u8]) -> Option<IndexMap<String, NaslValue>> {
+    let (source, message) = icmp_message(datagram)?;
+    let icmp_type = *message.first()?;
+    let v6 = source.is_ipv6();
+    let is_error = if v6 {
+        matches!(icmp_type, ICMP6_UNREACHABLE | ICMP6_TIME_EXCEEDED)
+    } else {
+        matches!(icmp_type, ICMP_UNREACHABLE | ICMP_TIME_EXCEEDED)
+    };
+    if !is_error {
+        return None;
+    }
+    let inner = message.get(ICMP_HEADER_LENGTH..)?;
+    let (destination, protocol, transport) = if v6 {
+        let ip = Ipv6Packet::new(inner)?;
+        (
+            IpAddr::V6(ip.get_destination()),
+            ip.get_next_header(),
+            inner.get(40..),
+        )
+    } else {
+        let ip = Ipv4Packet::new(inner)?;
+        (
+            IpAddr::V4(ip.get_destination()),
+            ip.get_next_level_protocol(),
+            inner.get(ip.get_header_length() as usize * 4..),
+        )
+    };
+    let mut result = IndexMap::from([
+        ("type".to_string(), NaslValue::Number(icmp_type as i64)),
+        ("code".to_string(), NaslValue::Number(message[1] as i64)),
+        ("src".to_string(), NaslValue::String(source.to_string())),
+        (
+            "dst".to_string(),
+            NaslValue::String(destination.to_string()),
+        ),
+        ("protocol".to_string(), NaslValue::Number(protocol.0 as i64)),
+    ]);
+    if matches!(
+        protocol,
+        IpNextHeaderProtocols::Tcp | IpNextHeaderProtocols::Udp
+    ) {
+        let transport = transport.unwrap_or_default();
+        if let (Some(sport), Some(dport)) = (read_u16(transport, 0), read_u16(transport, 2)) {
+            result.insert("sport".to_string(), NaslValue::Number(sport as i64));
+            result.insert("dport".to_string(), NaslValue::Number(dport as i64));
+        }
+    }
+    Some(result)
+}
+
+/// Sends the ICMP message to the target and returns the first captured datagram accepted by
+/// the matcher.
+fn exchange(
+    context: &Context,
+    message: &[u8],
+    timeout: Option<u64>,
+    matches: impl Fn(&[u8]) -> bool,
+) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+    let target_ip = get_host_ip(context)?;
+    let (domain, protocol, unspecified, filter) = match target_ip {
+        IpAddr::V4(_) => (
+            Domain::IPV4,
+            Protocol::ICMPV4,
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            format!("icmp and src host {target_ip}"),
+        ),
+        IpAddr::V6(_) => (
+            Domain::IPV6,
+            Protocol::ICMPV6,
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            format!("icmp6 and src host {target_ip}"),
+        ),
+    };
+    let mut capture = PacketCapture::open(capture_device(context, None)?, &filter)?;
+    let soc = Socket::new_raw(domain, socket2::Type::RAW, Some(protocol)).map_err(|e| {
+        FunctionErrorKind::Dirty(format!("Not possible to create a raw socket: {e}"))
+    })?;
+    context
+        .source_binding()
+        .bind(&soc, &unspecified)
+        .map_err(|e| {
+            FunctionErrorKind::Dirty(format!("Not possible to bind the raw socket: {e}"))
+        })?;
+    let sockaddr = socket2::SockAddr::from(SocketAddr::new(target_ip, 0));
+    soc.send_to(message, &sockaddr)
+        .map_err(|e| FunctionErrorKind::Diagnostic(format!("icmp: {e}"), None))?;
+
+    let start = Instant::now();
+    let deadline = start + answer_timeout(context, target_ip, timeout);
+    while let Some(datagram) =
+        capture.next_datagram(deadline.saturating_duration_since(Instant::now()))?
+    {
+        if matches(&datagram) {
+            context
+                .network_timeout()
+                .observe(target_ip, start.elapsed());
+            return Ok(Some(datagram));
+        }
+    }
+    Ok(None)
+}
+
+fn random_u16() -> Result<u16, FunctionErrorKind> {
+    Ok(random_impl()? as u16)
+}
+
+/// Sends an ICMP echo request, or an ICMPv6 echo request for IPv6 targets, to the target.
+///
+/// - id: identifier, random by default
+/// - seq: sequence number, 0 by default
+/// - data: payload of the request
+/// - timeout: time to wait for the reply in seconds, by default the network timeout of the scan
+///
+/// Returns the IP datagram containing the echo reply or NULL when the target did not answer.
+#[nasl_function(named(id, seq, data, timeout))]
+fn icmp_echo(
+    context: &Context,
+    id: Option<u16>,
+    seq: Option<u16>,
+    data: Option<&[u8]>,
+    timeout: Option<u64>,
+) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+    let id = id.map(Ok).unwrap_or_else(random_u16)?;
+    let seq = seq.unwrap_or_default();
+    let (request, reply) = match get_host_ip(context)? {
+        IpAddr::V4(_) => (ICMP_ECHO_REQUEST, ICMP_ECHO_REPLY),
+        IpAddr::V6(_) => (ICMP6_ECHO_REQUEST, ICMP6_ECHO_REPLY),
+    };
+    let message = encode_message(request, id, seq, data.unwrap_or_default());
+    exchange(context, &message, timeout, |x| is_reply(x, reply, id, seq))
+}
+
+/// Returns the milliseconds since midnight UTC as used by ICMP timestamps.
+fn timestamp_now() -> u32 {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    (millis % 86_400_000) as u32
+}
+
+/// Sends an ICMP timestamp request to an IPv4 target.
+///
+/// - timeout: time to wait for the reply in seconds, by default the network timeout of the scan
+///
+/// Returns an array with the originate, receive and transmit timestamps of the reply in
+/// milliseconds since midnight UTC or NULL when the target did not answer.
+#[nasl_function(named(timeout))]
+fn icmp_timestamp(
+    context: &Context,
+    timeout: Option<u64>,
+) -> Result<Option<NaslValue>, FunctionErrorKind> {
+    if get_host_ip(context)?.is_ipv6() {
+        return Err(FunctionErrorKind::Diagnostic(
+            "ICMP timestamp requests are not supported for IPv6 targets".to_string(),
+            None,
+        ));
+    }
+    let (id, seq) = (random_u16()?, 0);
+    let message = encode_message(
+        ICMP_TIMESTAMP_REQUEST,
+        id,
+        seq,
+        &[timestamp_now().to_be_bytes(), [0; 4], [0; 4]].concat(),
+    );
+    let reply = exchange(context, &message, timeout, |x| {
+        is_reply(x, ICMP_TIMESTAMP_REPLY, id, seq)
+    })?;
+    Ok(reply.and_then(|x| {
+        let (_, message) = icmp_message(&x)?;
+        let result: IndexMap<String, NaslValue> = ["originate", "receive", "transmit"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                read_u32(message, ICMP_HEADER_LENGTH + i * 4)
+                    .map(|x| (name.to_string(), NaslValue::Number(x as i64)))
+            })
+            .collect::<Option<_>>()?;
+        Some(NaslValue::Dict(result))
+    }))
+}
+
+/// Parses an IP datagram containing an ICMP or ICMPv6 destination unreachable or time exceeded
+/// message.
+///
+/// Returns an array with the type and code of the message, the address of the reporting host
+/// as src, the destination and protocol of the original datagram as dst and protocol and, for
+/// TCP and UDP, its ports as sport and dport. Returns NULL for other datagrams.
+#[nasl_function]
+fn parse_icmp_error(packet: &[u8]) -> Option<NaslValue> {
+    parse_error(packet).map(NaslValue::Dict)
+}
+
+pub struct Icmp;
+
+function_set! {
+    Icmp,
+    sync_stateless,
+    (
+        icmp_echo,
+        icmp_timestamp,
+        parse_icmp_error,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use pnet::packet::{
+        icmp::{self, IcmpPacket},
+        ip::IpNextHeaderProtocols,
+        ipv4::MutableIpv4Packet,
+    };
+
+    use crate::nasl::syntax::NaslValue;
+
+    use super::*;
+
+    fn ipv4(source: Ipv4Addr, protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0u8; 20 + payload.len()];
+        let mut ip = MutableIpv4Packet::new(&mut buf).unwrap();
+        ip.set_version(4);
+        ip.set_header_length(5);
+        ip.set_total_length(20 + payload.len() as u16);
+        ip.set_next_level_protocol(pnet::packet::ip::IpNextHeaderProtocol(protocol));
+        ip.set_source(source);
+        ip.set_destination(Ipv4Addr::new(192, 0, 2, 1));
+        ip.set_payload(payload);
+        buf
+    }
+
+    #[test]
+    fn echo() {
+        let message = encode_message(ICMP_ECHO_REQUEST, 0x1234, 7, b"ping");
+        let packet = IcmpPacket::new(&message).unwrap();
+        assert_eq!(packet.get_checksum(), icmp::checksum(&packet));
+        let mut reply = message.clone();
+        reply[0] = ICMP_ECHO_REPLY;
+        let datagram = ipv4(
+            Ipv4Addr::new(192, 0, 2, 2),
+            IpNextHeaderProtocols::Icmp.0,
+            &reply,
+        );
+        assert!(is_reply(&datagram, ICMP_ECHO_REPLY, 0x1234, 7));
+        assert!(!is_reply(&datagram, ICMP_ECHO_REPLY, 0x1234, 8));
+    }
+
+    #[test]
+    fn unreachable() {
+        let udp = [0x9c, 0x40, 0, 53, 0, 8, 0, 0];
+        let original = ipv4(
+            Ipv4Addr::new(192, 0, 2, 1),
+            IpNextHeaderProtocols::Udp.0,
+            &udp,
+        );
+        let mut message = vec![ICMP_UNREACHABLE, 3, 0, 0, 0, 0, 0, 0];
+        message.extend_from_slice(&original);
+        let datagram = ipv4(
+            Ipv4Addr::new(192, 0, 2, 254),
+            IpNextHeaderProtocols::Icmp.0,
+            &message,
+        );
+        let result = parse_error(&datagram).unwrap();
+        assert_eq!(result["type"], NaslValue::Number(3));
+        assert_eq!(result["code"], NaslValue::Number(3));
+        assert_eq!(result["src"], NaslValue::String("192.0.2.254".to_string()));
+        assert_eq!(result["dst"], NaslValue::String("192.0.2.1".to_string()));
+        assert_eq!(result["protocol"], NaslValue::Number(17));
+        assert_eq!(result["sport"], NaslValue::Number(40000));
+        assert_eq!(result["dport"], NaslValue::Number(53));
+        let echo = ipv4(
+            Ipv4Addr::new(192, 0, 2, 2),
+            IpNextHeaderProtocols::Icmp.0,
+            &encode_message(ICMP_ECHO_REPLY, 1, 1, &[]),
+        );
+        assert_eq!(parse_error(&echo), None);
+    }
+}
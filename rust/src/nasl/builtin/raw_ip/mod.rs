@@ -2,12 +2,21 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+mod arp;
+pub mod capture;
 mod frame_forgery;
+mod icmp;
 mod packet_forgery;
+mod packet_forgery_v6;
 mod raw_ip_utils;
+mod tcp_probe;
 use crate::nasl::utils::{IntoFunctionSet, NaslVars, StoredFunctionSet};
+use arp::Arp;
 use frame_forgery::FrameForgery;
+use icmp::Icmp;
 use packet_forgery::PacketForgery;
+use packet_forgery_v6::PacketForgeryV6;
+use tcp_probe::TcpProbe;
 
 pub struct RawIp;
 
@@ -25,7 +34,11 @@ impl IntoFunctionSet for RawIp {
     fn into_function_set(self) -> StoredFunctionSet<Self::State> {
         let mut set = StoredFunctionSet::new(self);
         set.add_set(PacketForgery);
+        set.add_set(PacketForgeryV6);
+        set.add_set(TcpProbe);
+        set.add_set(Icmp);
         set.add_set(FrameForgery);
+        set.add_set(Arp);
         set
     }
 }
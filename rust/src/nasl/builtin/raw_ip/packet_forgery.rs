@@ -5,22 +5,24 @@
 //! Defines NASL packet forgery functions
 
 use std::{
-    net::{Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     str::FromStr,
+    time::Duration,
 };
 
-use super::raw_ip_utils::{get_interface_by_local_ip, get_source_ip, islocalhost};
+use super::capture::{answer_timeout, capture_device, PacketCapture, DEFAULT_TIMEOUT};
+use super::raw_ip_utils::{get_interface, get_source_ip, islocalhost};
 
 use super::super::host::get_host_ip;
+use super::super::network::get_kb_item;
 use crate::nasl::builtin::misc::random_impl;
 use crate::nasl::prelude::*;
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::NaslVars;
+use crate::nasl::utils::{NaslVars, SourceBinding};
 
 use pcap::Capture;
 use pnet::packet::{
     self,
-    ethernet::EthernetPacket,
     icmp::*,
     ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
     ipv4::{checksum, Ipv4Packet, MutableIpv4Packet},
@@ -43,9 +45,6 @@ macro_rules! custom_error {
     };
 }
 
-/// Default Timeout for received
-const DEFAULT_TIMEOUT: i32 = 5000;
-
 /// Define IPPROTO_RAW
 const IPPROTO_RAW: i32 = 255;
 /// Define IPPROTO_IP for dummy tcp . From rfc3542:
@@ -1908,24 +1907,53 @@ fn forge_igmp_packet(
     Ok(NaslValue::Data(ip_buf))
 }
 
-fn new_raw_socket() -> Result<Socket, FunctionErrorKind> {
-    match Socket::new_raw(
+/// Creates a raw socket bound to the configured interface.
+pub fn new_raw_socket(binding: &SourceBinding) -> Result<Socket, FunctionErrorKind> {
+    let soc = match Socket::new_raw(
         Domain::IPV4,
         socket2::Type::RAW,
         Some(Protocol::from(IPPROTO_RAW)),
     ) {
-        Ok(s) => Ok(s),
-        Err(e) => Err(FunctionErrorKind::Dirty(format!(
-            "Not possible to create a raw socket: {}",
+        Ok(s) => s,
+        Err(e) => {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "Not possible to create a raw socket: {}",
+                e
+            )))
+        }
+    };
+    if let Err(e) = binding.bind(&soc, &IpAddr::V4(Ipv4Addr::UNSPECIFIED)) {
+        return Err(FunctionErrorKind::Dirty(format!(
+            "Not possible to bind the raw socket: {}",
             e
-        ))),
+        )));
     }
+    Ok(soc)
+}
+
+/// Ports checked for being open, in order of preference, when no port is given to tcp_ping
+const COMMON_TCP_PORTS: [u16; 16] = [
+    80, 443, 22, 21, 23, 25, 53, 110, 111, 135, 139, 143, 445, 3389, 8080, 8443,
+];
+
+/// Returns a port found open by a previous port scan.
+///
+/// The knowledge base cannot be queried by pattern, therefore only the common ports are
+/// checked for the item Ports/tcp/<port>.
+fn get_host_open_port(configs: &Context) -> Option<u16> {
+    COMMON_TCP_PORTS.into_iter().find(|port| {
+        matches!(
+            get_kb_item(configs, &format!("Ports/tcp/{port}")),
+            Ok(Some(NaslValue::Number(1)))
+        )
+    })
 }
 
 /// This function tries to open a TCP connection and sees if anything comes back (SYN/ACK or RST).
 ///  
 /// Its argument is:
-/// - port: port for the ping
+/// - port: port for the ping, defaults to a port known to be open. Without known open port a
+///   list of common ports is tried.
 fn nasl_tcp_ping(register: &Register, configs: &Context) -> Result<NaslValue, FunctionErrorKind> {
     let rnd_tcp_port = || -> u16 { (random_impl().unwrap_or(0) % 65535 + 1024) as u16 };
 
@@ -1946,7 +1974,7 @@ fn nasl_tcp_ping(register: &Register, configs: &Context) -> Result<NaslValue, Fu
         }
     }
 
-    let soc = new_raw_socket()?;
+    let soc = new_raw_socket(configs.source_binding())?;
     if let Err(e) = soc.set_header_included(true) {
         return Err(FunctionErrorKind::Dirty(format!(
             "Not possible to create a raw socket: {}",
@@ -1956,12 +1984,12 @@ fn nasl_tcp_ping(register: &Register, configs: &Context) -> Result<NaslValue, Fu
 
     // Get the iface name, to set the capture device.
     let target_ip = get_host_ip(configs)?;
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
-    let iface = get_interface_by_local_ip(local_ip)?;
+    let local_ip = get_source_ip(target_ip, 50000u16, configs.source_binding())?;
+    let iface = get_interface(target_ip, configs.source_binding())?;
 
     let port = match register.named("port") {
         Some(ContextType::Value(NaslValue::Number(x))) => *x,
-        None => 0, //TODO: implement plug_get_host_open_port()
+        None => get_host_open_port(configs).unwrap_or_default() as i64,
         _ => {
             return Err(FunctionErrorKind::wrong_unnamed_argument(
                 "Number",
@@ -2017,7 +2045,6 @@ fn nasl_tcp_ping(register: &Register, configs: &Context) -> Result<NaslValue, Fu
     tcp.set_urgent_ptr(0);
 
     for (i, _) in sports.iter().enumerate() {
-        // TODO: the port is fixed since the function to get open ports is not implemented.
         let mut sport = rnd_tcp_port();
         let mut dport = port as u16;
         if port == 0 {
@@ -2065,7 +2092,8 @@ fn nasl_tcp_ping(register: &Register, configs: &Context) -> Result<NaslValue, Fu
 /// - length: default length of each every packet, if a packet does not fit, its actual size is taken instead
 /// - pcap_active: option to capture the answers, TRUE by default
 /// - pcap_filter: BPF filter used for the answers
-/// - pcap_timeout: time to wait for the answers in seconds, 5 by default
+/// - pcap_timeout: time to wait for the answers in seconds, by default the network timeout of
+///   the scan
 /// - allow_broadcast: default FALSE
 fn nasl_send_packet(
     register: &Register,
@@ -2094,8 +2122,8 @@ fn nasl_send_packet(
     };
 
     let timeout = match register.named("pcap_timeout") {
-        Some(ContextType::Value(NaslValue::Number(x))) => *x as i32 * 1000i32, // to milliseconds
-        None => DEFAULT_TIMEOUT,
+        Some(ContextType::Value(NaslValue::Number(x))) => Duration::from_secs(*x as u64),
+        None => answer_timeout(configs, get_host_ip(configs)?, None),
         _ => {
             return Err(FunctionErrorKind::wrong_unnamed_argument(
                 "Integer",
@@ -2120,7 +2148,7 @@ fn nasl_send_packet(
         return Ok(NaslValue::Null);
     }
 
-    let soc = new_raw_socket()?;
+    let soc = new_raw_socket(configs.source_binding())?;
 
     if let Err(e) = soc.set_header_included(true) {
         return Err(FunctionErrorKind::Dirty(format!(
@@ -2140,17 +2168,14 @@ fn nasl_send_packet(
         }
     };
 
-    // Get the iface name, to set the capture device.
     let target_ip = get_host_ip(configs)?;
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
-    let iface = get_interface_by_local_ip(local_ip)?;
-
-    let mut capture_dev = match Capture::from_device(iface) {
-        Ok(c) => match c.promisc(true).timeout(timeout).open() {
-            Ok(capture) => capture,
-            Err(e) => return custom_error!("send_packet: {}", e),
-        },
-        Err(e) => return custom_error!("send_packet: {}", e),
+    let mut capture = if use_pcap {
+        Some(PacketCapture::open(
+            capture_device(configs, None)?,
+            &filter,
+        )?)
+    } else {
+        None
     };
 
     for pkt in positional.iter() {
@@ -2207,103 +2232,33 @@ fn nasl_send_packet(
             }
         }
 
-        if use_pcap {
-            let p = match capture_dev.filter(&filter, true) {
-                Ok(_) => capture_dev.next_packet(),
-                Err(e) => Err(pcap::Error::PcapError(e.to_string())),
-            };
-
-            match p {
-                Ok(packet) => return Ok(NaslValue::Data(packet.data.to_vec())),
-                Err(_) => return Ok(NaslValue::Null),
-            };
+        if let Some(capture) = capture.as_mut() {
+            return Ok(capture
+                .next_datagram(timeout)?
+                .map(NaslValue::Data)
+                .unwrap_or(NaslValue::Null));
         }
     }
     Ok(NaslValue::Null)
 }
 
-/// This function is the same as send_capture().
-///  
-/// - interface: network interface name, by default NASL will try to find the best one
-/// - pcap_filter: BPF filter, by default it listens to everything
-/// - timeout: timeout in seconds, 5 by default
-fn nasl_pcap_next(register: &Register, configs: &Context) -> Result<NaslValue, FunctionErrorKind> {
-    nasl_send_capture(register, configs)
-}
-
 /// Read the next packet.
-///  
+///
 /// - interface: network interface name, by default NASL will try to find the best one
 /// - pcap_filter: BPF filter, by default it listens to everything
 /// - timeout: timeout in seconds, 5 by default
-fn nasl_send_capture(
-    register: &Register,
+///
+/// Returns the captured IP datagram or NULL when nothing was captured within the timeout.
+#[nasl_function(named(interface, pcap_filter, timeout))]
+fn pcap_next(
     configs: &Context,
-) -> Result<NaslValue, FunctionErrorKind> {
-    let interface = match register.named("interface") {
-        Some(ContextType::Value(NaslValue::String(x))) => x.to_string(),
-        None => String::new(),
-        _ => {
-            return Err(FunctionErrorKind::wrong_unnamed_argument(
-                "String",
-                "Invalid interface value",
-            ))
-        }
-    };
-
-    let filter = match register.named("pcap_filter") {
-        Some(ContextType::Value(NaslValue::String(x))) => x.to_string(),
-        None => String::new(),
-        _ => {
-            return Err(FunctionErrorKind::wrong_unnamed_argument(
-                "String",
-                "Invalid pcap_filter value",
-            ))
-        }
-    };
-
-    let timeout = match register.named("pcap_timeout") {
-        Some(ContextType::Value(NaslValue::Number(x))) => *x as i32 * 1000i32, // to milliseconds
-        None => DEFAULT_TIMEOUT,
-        _ => {
-            return Err(FunctionErrorKind::wrong_unnamed_argument(
-                "Integer",
-                "Invalid timeout value",
-            ))
-        }
-    };
-
-    // Get the iface name, to set the capture device.
-    let target_ip = get_host_ip(configs)?;
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
-    let mut iface = get_interface_by_local_ip(local_ip)?;
-    if !interface.is_empty() {
-        iface = pcap::Device::from(interface.as_str());
-    }
-
-    let mut capture_dev = match Capture::from_device(iface) {
-        Ok(c) => match c.promisc(true).timeout(timeout).open() {
-            Ok(capture) => capture,
-            Err(e) => return custom_error!("send_capture: {}", e),
-        },
-        Err(e) => return custom_error!("send_capture: {}", e),
-    };
-
-    let p = match capture_dev.filter(&filter, true) {
-        Ok(_) => capture_dev.next_packet(),
-        Err(e) => Err(pcap::Error::PcapError(e.to_string())),
-    };
-
-    match p {
-        Ok(packet) => {
-            // Remove all from lower layer
-            let frame = EthernetPacket::new(packet.data).ok_or_else(|| {
-                FunctionErrorKind::Dirty("No possible to create a packet from buffer".to_string())
-            })?;
-            return Ok(NaslValue::Data(frame.payload().to_vec()));
-        }
-        Err(_) => Ok(NaslValue::Null),
-    }
+    interface: Option<&str>,
+    pcap_filter: Option<&str>,
+    timeout: Option<u64>,
+) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+    let device = capture_device(configs, interface)?;
+    PacketCapture::open(device, pcap_filter.unwrap_or_default())?
+        .next_datagram(Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT)))
 }
 
 /// Returns a NaslVars with all predefined variables which must be expose to nasl script
@@ -2386,7 +2341,38 @@ function_set! {
         forge_igmp_packet,
         (nasl_tcp_ping, "tcp_ping"),
         (nasl_send_packet, "send_packet"),
-        (nasl_pcap_next, "pcap_next"),
-        (nasl_send_capture, "send_capture"),
+        pcap_next,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::nasl::test_prelude::*;
+    use crate::storage::{types::Primitive, ContextKey, Field, Kb};
+
+    use super::get_host_open_port;
+
+    #[test]
+    fn open_port_of_port_scan() {
+        let factory = ContextFactory::default();
+        let context = factory.build(ContextKey::default());
+        let set = |port: u16, value: i64| {
+            let kb = Kb {
+                key: format!("Ports/tcp/{port}"),
+                value: Primitive::Number(value),
+                expire: None,
+            };
+            context
+                .dispatcher()
+                .dispatch(context.key(), Field::KB(kb))
+                .unwrap();
+        };
+        assert_eq!(get_host_open_port(&context), None);
+        set(80, 0);
+        set(8080, 1);
+        assert_eq!(get_host_open_port(&context), Some(8080));
+        // common ports are checked in order of their likelihood to be open
+        set(443, 1);
+        assert_eq!(get_host_open_port(&context), Some(443));
+    }
+}
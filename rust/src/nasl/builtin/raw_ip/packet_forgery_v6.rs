@@ -0,0 +1,522 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL packet forgery functions for IPv6
+//!
+//! Extension headers are kept between the fixed header and the upper layer, so the payload of
+//! ICMPv6 messages is placed after the last extension header of the datagram.
+
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+use pnet::packet::{
+    icmpv6::{self, Icmpv6Packet, MutableIcmpv6Packet},
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv6::{Ipv6Packet, MutableIpv6Packet},
+    Packet,
+};
+use socket2::{Domain, Protocol, Socket};
+use tracing::debug;
+
+use super::super::host::get_host_ip;
+use super::packet_forgery::display_packet;
+use crate::nasl::prelude::*;
+use crate::nasl::utils::function::CheckedPositionals;
+
+/// Length of the fixed IPv6 header
+const IP6_HEADER_LENGTH: usize = 40;
+/// Length of the ICMPv6 header including identifier and sequence number
+const ICMP6_HEADER_LENGTH: usize = 8;
+/// Message types whose header differs from echo messages
+const ICMP6_ROUTER_ADVERTISEMENT: u8 = 134;
+const ICMP6_NEIGHBOR_SOLICITATION: u8 = 135;
+const ICMP6_NEIGHBOR_ADVERTISEMENT: u8 = 136;
+/// Length of the fragment extension header, which has no length field
+const FRAGMENT_HEADER_LENGTH: usize = 8;
+
+fn packet_error() -> FunctionErrorKind {
+    FunctionErrorKind::Dirty("No possible to create a packet from buffer".to_string())
+}
+
+fn parse_ipv6(key: &str, value: &str) -> Result<Ipv6Addr, FunctionErrorKind> {
+    value
+        .parse::<Ipv6Addr>()
+        .map_err(|e| FunctionErrorKind::WrongArgument(format!("Invalid {key}: {e}")))
+}
+
+fn is_extension_header(next_header: IpNextHeaderProtocol) -> bool {
+    matches!(
+        next_header,
+        IpNextHeaderProtocols::Hopopt
+            | IpNextHeaderProtocols::Ipv6Route
+            | IpNextHeaderProtocols::Ipv6Frag
+            | IpNextHeaderProtocols::Ipv6Opts
+    )
+}
+
+/// Returns the offset of the upper layer and the position of the next header field referring
+/// to it. A datagram without payload has no extension headers, regardless of its next header.
+fn upper_layer(buf: &[u8]) -> Result<(usize, usize), FunctionErrorKind> {
+    if buf.len() < IP6_HEADER_LENGTH {
+        return Err(packet_error());
+    }
+    let mut next_header_at = 6;
+    let mut offset = IP6_HEADER_LENGTH;
+    while offset < buf.len() && is_extension_header(IpNextHeaderProtocol(buf[next_header_at])) {
+        let length = match IpNextHeaderProtocol(buf[next_header_at]) {
+            IpNextHeaderProtocols::Ipv6Frag => FRAGMENT_HEADER_LENGTH,
+            _ => match buf.get(offset + 1) {
+                Some(x) => (*x as usize + 1) * 8,
+                None => return Err(packet_error()),
+            },
+        };
+        if offset + length > buf.len() {
+            return Err(packet_error());
+        }
+        next_header_at = offset;
+        offset += length;
+    }
+    Ok((offset, next_header_at))
+}
+
+/// Sets the payload length to the length of the datagram
+fn update_payload_length(buf: &mut [u8]) -> Result<(), FunctionErrorKind> {
+    let length = buf.len() - IP6_HEADER_LENGTH;
+    let mut pkt = MutableIpv6Packet::new(buf).ok_or_else(packet_error)?;
+    pkt.set_payload_length(length as u16);
+    Ok(())
+}
+
+/// Forge an IPv6 datagram. It takes following arguments:
+///
+/// - data: is the payload.
+/// - ip6_v: is the IP version. 6 by default.
+/// - ip6_tc: is the traffic class. 0 by default.
+/// - ip6_fl: is the flow label. 0 by default.
+/// - ip6_p: is the next header. 0 by default.
+/// - ip6_hlim: is the hop limit. 64 by default.
+/// - ip6_src: is the source address in ASCII.
+/// - ip6_dst: is the destination address in ASCII. By default the target IP address.
+///
+/// The payload length is computed. Returns the IPv6 datagram.
+#[nasl_function(named(data, ip6_v, ip6_tc, ip6_fl, ip6_p, ip6_hlim, ip6_src, ip6_dst))]
+fn forge_ip_v6_packet(
+    context: &Context,
+    data: Option<&NaslValue>,
+    ip6_v: Option<u8>,
+    ip6_tc: Option<u8>,
+    ip6_fl: Option<u32>,
+    ip6_p: Option<u8>,
+    ip6_hlim: Option<u8>,
+    ip6_src: Option<&str>,
+    ip6_dst: Option<&str>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let destination = match ip6_dst {
+        Some(x) => parse_ipv6("ip6_dst", x)?,
+        None => match get_host_ip(context)? {
+            IpAddr::V6(x) => x,
+            IpAddr::V4(x) => x.to_ipv6_mapped(),
+        },
+    };
+    let data = data.map(Vec::<u8>::from).unwrap_or_default();
+    let mut buf = vec![0u8; IP6_HEADER_LENGTH + data.len()];
+    let mut pkt = MutableIpv6Packet::new(&mut buf).ok_or_else(packet_error)?;
+    pkt.set_version(ip6_v.unwrap_or(6));
+    pkt.set_traffic_class(ip6_tc.unwrap_or(0));
+    pkt.set_flow_label(ip6_fl.unwrap_or(0) & 0xfffff);
+    pkt.set_payload_length(data.len() as u16);
+    pkt.set_next_header(IpNextHeaderProtocol(ip6_p.unwrap_or(0)));
+    pkt.set_hop_limit(ip6_hlim.unwrap_or(64));
+    if let Some(src) = ip6_src {
+        pkt.set_source(parse_ipv6("ip6_src", src)?);
+    }
+    pkt.set_destination(destination);
+    pkt.set_payload(&data);
+    Ok(NaslValue::Data(buf))
+}
+
+/// Get an element of an IPv6 datagram. Its arguments are:
+///
+/// - ip6: is the IPv6 datagram.
+/// - element: is the name of the field to get.
+///
+/// Valid elements are ip6_v, ip6_tc, ip6_fl, ip6_plen, ip6_nxt, ip6_hlim, ip6_src and ip6_dst.
+#[nasl_function(named(ip6, element))]
+fn get_ip_v6_element(ip6: &NaslValue, element: &str) -> Result<NaslValue, FunctionErrorKind> {
+    let buf = Vec::<u8>::from(ip6);
+    let pkt = Ipv6Packet::new(&buf).ok_or_else(packet_error)?;
+    Ok(match element {
+        "ip6_v" => NaslValue::Number(pkt.get_version() as i64),
+        "ip6_tc" => NaslValue::Number(pkt.get_traffic_class() as i64),
+        "ip6_fl" => NaslValue::Number(pkt.get_flow_label() as i64),
+        "ip6_plen" => NaslValue::Number(pkt.get_payload_length() as i64),
+        "ip6_nxt" => NaslValue::Number(pkt.get_next_header().0 as i64),
+        "ip6_hlim" => NaslValue::Number(pkt.get_hop_limit() as i64),
+        "ip6_src" => NaslValue::String(pkt.get_source().to_string()),
+        "ip6_dst" => NaslValue::String(pkt.get_destination().to_string()),
+        _ => {
+            return Err(FunctionErrorKind::WrongArgument(
+                "Invalid element".to_string(),
+            ))
+        }
+    })
+}
+
+/// Set elements of an IPv6 datagram. Takes the datagram as ip6 and the elements of
+/// forge_ip_v6_packet, except data and ip6_dst, as well as ip6_plen. Returns the modified
+/// datagram.
+#[nasl_function(named(ip6, ip6_v, ip6_tc, ip6_fl, ip6_plen, ip6_nxt, ip6_hlim, ip6_src))]
+fn set_ip_v6_elements(
+    ip6: &NaslValue,
+    ip6_v: Option<u8>,
+    ip6_tc: Option<u8>,
+    ip6_fl: Option<u32>,
+    ip6_plen: Option<u16>,
+    ip6_nxt: Option<u8>,
+    ip6_hlim: Option<u8>,
+    ip6_src: Option<&str>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let mut buf = Vec::<u8>::from(ip6);
+    let mut pkt = MutableIpv6Packet::new(&mut buf).ok_or_else(packet_error)?;
+    if let Some(x) = ip6_v {
+        pkt.set_version(x);
+    }
+    if let Some(x) = ip6_tc {
+        pkt.set_traffic_class(x);
+    }
+    if let Some(x) = ip6_fl {
+        pkt.set_flow_label(x & 0xfffff);
+    }
+    if let Some(x) = ip6_plen {
+        pkt.set_payload_length(x);
+    }
+    if let Some(x) = ip6_nxt {
+        pkt.set_next_header(IpNextHeaderProtocol(x));
+    }
+    if let Some(x) = ip6_hlim {
+        pkt.set_hop_limit(x);
+    }
+    if let Some(x) = ip6_src {
+        pkt.set_source(parse_ipv6("ip6_src", x)?);
+    }
+    Ok(NaslValue::Data(buf))
+}
+
+/// Insert an extension header directly after the fixed header of an IPv6 datagram.
+///
+/// - ip6: is the IPv6 datagram.
+/// - code: is the type of the extension header, e.g. 0 for hop-by-hop options, 43 for routing,
+///   44 for fragment and 60 for destination options.
+/// - length: is the length of the value, which is truncated or padded with zeros. By default
+///   the length of the value.
+/// - value: is the content of the extension header following the next header and length
+///   fields. It is padded with zeros, which are Pad1 options, to a multiple of 8 bytes.
+///
+/// The next header fields and the payload length are updated. Returns the modified datagram.
+#[nasl_function(named(ip6, code, length, value))]
+fn insert_ip_v6_options(
+    ip6: &NaslValue,
+    code: u8,
+    length: Option<usize>,
+    value: &NaslValue,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let buf = Vec::<u8>::from(ip6);
+    let pkt = Ipv6Packet::new(&buf).ok_or_else(packet_error)?;
+    let mut value = Vec::<u8>::from(value);
+    if let Some(length) = length {
+        value.resize(length, 0);
+    }
+    let code = IpNextHeaderProtocol(code);
+    if !is_extension_header(code) {
+        return Err(FunctionErrorKind::wrong_argument(
+            "code",
+            "an extension header (0, 43, 44 or 60)",
+            &code.0.to_string(),
+        ));
+    }
+    let length = if code == IpNextHeaderProtocols::Ipv6Frag {
+        FRAGMENT_HEADER_LENGTH
+    } else {
+        (2 + value.len()).div_ceil(8) * 8
+    };
+    if value.len() > length - 2 || length > 2048 {
+        return Err(FunctionErrorKind::wrong_argument(
+            "value",
+            "at most 6 bytes for a fragment header, otherwise at most 2046 bytes",
+            &format!("{} bytes", value.len()),
+        ));
+    }
+    let mut header = vec![0u8; length];
+    header[0] = pkt.get_next_header().0;
+    if code != IpNextHeaderProtocols::Ipv6Frag {
+        header[1] = (length / 8 - 1) as u8;
+    }
+    header[2..2 + value.len()].copy_from_slice(&value);
+
+    let mut new_buf = buf[..IP6_HEADER_LENGTH].to_vec();
+    new_buf.extend(header);
+    new_buf.extend(&buf[IP6_HEADER_LENGTH..]);
+    new_buf[6] = code.0;
+    update_payload_length(&mut new_buf)?;
+    Ok(NaslValue::Data(new_buf))
+}
+
+/// Receive a list of IPv6 datagrams and print them in a readable format in the screen.
+#[nasl_function]
+fn dump_ip_v6_packet(positional: CheckedPositionals<&NaslValue>) -> Result<(), FunctionErrorKind> {
+    if positional.is_empty() {
+        return Err(FunctionErrorKind::MissingPositionalArguments {
+            expected: 1,
+            got: 0,
+        });
+    }
+    for ip6 in positional.iter() {
+        let NaslValue::Data(data) = ip6 else {
+            return Err(FunctionErrorKind::WrongArgument(
+                "Invalid ip packet".to_string(),
+            ));
+        };
+        let pkt = Ipv6Packet::new(data).ok_or_else(packet_error)?;
+        println!("------");
+        println!("\tip6_v  : {}", pkt.get_version());
+        println!("\tip6_tc : {}", pkt.get_traffic_class());
+        println!("\tip6_fl : {}", pkt.get_flow_label());
+        println!("\tip6_plen: {}", pkt.get_payload_length());
+        println!("\tip6_nxt : {}", pkt.get_next_header());
+        println!("\tip6_hlim : {}", pkt.get_hop_limit());
+        println!("\tip6_src : {}", pkt.get_source());
+        println!("\tip6_dst : {}", pkt.get_destination());
+        display_packet(data);
+    }
+    Ok(())
+}
+
+/// Fills an IPv6 datagram with an ICMPv6 message. Its arguments are:
+///
+/// - ip6: is the IPv6 datagram to be filled.
+/// - data: is the payload.
+/// - icmp_type: is the message type, e.g. 128 for an echo request. 0 by default.
+/// - icmp_code: is the message code. 0 by default.
+/// - icmp_id: is the identifier of echo messages. 0 by default.
+/// - icmp_seq: is the sequence number of echo messages. 0 by default.
+/// - reachable_time: is the reachable time of router advertisements. 0 by default.
+/// - retransmit_timer: is the retransmission timer of router advertisements. 0 by default.
+/// - flags: are the flags of router and neighbor advertisements. 0 by default.
+/// - target: is the target address of neighbor solicitations and advertisements.
+/// - icmp_cksum: is the checksum. By default, the right value is computed.
+/// - update_ip_len: is a flag (TRUE by default). If set, the payload length of the datagram is
+///   recomputed.
+///
+/// The message replaces the upper layer after the extension headers, whose last next header
+/// field is set to ICMPv6. Returns the modified datagram.
+#[nasl_function(named(
+    ip6,
+    data,
+    icmp_type,
+    icmp_code,
+    icmp_id,
+    icmp_seq,
+    reachable_time,
+    retransmit_timer,
+    flags,
+    target,
+    icmp_cksum,
+    update_ip_len
+))]
+fn forge_icmp_v6_packet(
+    ip6: &NaslValue,
+    data: Option<&NaslValue>,
+    icmp_type: Option<u8>,
+    icmp_code: Option<u8>,
+    icmp_id: Option<u16>,
+    icmp_seq: Option<u16>,
+    reachable_time: Option<u32>,
+    retransmit_timer: Option<u32>,
+    flags: Option<u8>,
+    target: Option<&str>,
+    icmp_cksum: Option<u16>,
+    update_ip_len: Option<&NaslValue>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let mut buf = Vec::<u8>::from(ip6);
+    let (offset, next_header_at) = upper_layer(&buf)?;
+    let icmp_type = icmp_type.unwrap_or(0);
+    let target = target
+        .map(|x| parse_ipv6("target", x))
+        .transpose()?
+        .unwrap_or(Ipv6Addr::UNSPECIFIED);
+    let mut icmp = vec![0u8; ICMP6_HEADER_LENGTH];
+    icmp[0] = icmp_type;
+    icmp[1] = icmp_code.unwrap_or(0);
+    match icmp_type {
+        ICMP6_ROUTER_ADVERTISEMENT => {
+            icmp[5] = flags.unwrap_or(0);
+            icmp.extend(reachable_time.unwrap_or(0).to_be_bytes());
+            icmp.extend(retransmit_timer.unwrap_or(0).to_be_bytes());
+        }
+        ICMP6_NEIGHBOR_SOLICITATION | ICMP6_NEIGHBOR_ADVERTISEMENT => {
+            if icmp_type == ICMP6_NEIGHBOR_ADVERTISEMENT {
+                icmp[4] = flags.unwrap_or(0);
+            }
+            icmp.extend(target.octets());
+        }
+        _ => {
+            icmp[4..6].copy_from_slice(&icmp_id.unwrap_or(0).to_be_bytes());
+            icmp[6..8].copy_from_slice(&icmp_seq.unwrap_or(0).to_be_bytes());
+        }
+    }
+    icmp.extend(data.map(Vec::<u8>::from).unwrap_or_default());
+
+    buf.truncate(offset);
+    buf[next_header_at] = IpNextHeaderProtocols::Icmpv6.0;
+    let pkt = Ipv6Packet::new(&buf).ok_or_else(packet_error)?;
+    let (source, destination) = (pkt.get_source(), pkt.get_destination());
+    let mut icmp_pkt = MutableIcmpv6Packet::new(&mut icmp).ok_or_else(packet_error)?;
+    let checksum = match icmp_cksum {
+        Some(x) => x,
+        None => icmpv6::checksum(&icmp_pkt.to_immutable(), &source, &destination),
+    };
+    icmp_pkt.set_checksum(checksum);
+    buf.extend(icmp);
+    if update_ip_len.is_none_or(|x| bool::from(x.clone())) {
+        update_payload_length(&mut buf)?;
+    }
+    Ok(NaslValue::Data(buf))
+}
+
+/// Get an element of the ICMPv6 message of an IPv6 datagram. Its arguments are:
+///
+/// - icmp: is the IPv6 datagram containing the message.
+/// - element: is the name of the field to get.
+///
+/// Valid elements are icmp_type, icmp_code, icmp_cksum, icmp_id, icmp_seq and data. The data
+/// starts after the identifier and sequence number.
+#[nasl_function(named(icmp, element))]
+fn get_icmp_v6_element(icmp: &NaslValue, element: &str) -> Result<NaslValue, FunctionErrorKind> {
+    let buf = Vec::<u8>::from(icmp);
+    let (offset, _) = upper_layer(&buf)?;
+    let message = &buf[offset..];
+    let pkt = Icmpv6Packet::new(message).ok_or_else(packet_error)?;
+    let field = |at: usize| {
+        message
+            .get(at..at + 2)
+            .map(|x| NaslValue::Number(u16::from_be_bytes([x[0], x[1]]) as i64))
+            .unwrap_or(NaslValue::Null)
+    };
+    Ok(match element {
+        "icmp_type" => NaslValue::Number(pkt.get_icmpv6_type().0 as i64),
+        "icmp_code" => NaslValue::Number(pkt.get_icmpv6_code().0 as i64),
+        "icmp_cksum" | "icmp_chsum" => NaslValue::Number(pkt.get_checksum() as i64),
+        "icmp_id" => field(4),
+        "icmp_seq" => field(6),
+        "data" | "icmp_data" => NaslValue::Data(
+            message
+                .get(ICMP6_HEADER_LENGTH..)
+                .unwrap_or_default()
+                .to_vec(),
+        ),
+        _ => {
+            return Err(FunctionErrorKind::WrongArgument(
+                "Invalid element".to_string(),
+            ))
+        }
+    })
+}
+
+/// Receive a list of IPv6 datagrams containing ICMPv6 messages and print the messages in a
+/// readable format in the screen.
+#[nasl_function]
+fn dump_icmp_v6_packet(
+    positional: CheckedPositionals<&NaslValue>,
+) -> Result<(), FunctionErrorKind> {
+    if positional.is_empty() {
+        return Err(FunctionErrorKind::MissingPositionalArguments {
+            expected: 1,
+            got: 0,
+        });
+    }
+    for ip6 in positional.iter() {
+        let buf = Vec::<u8>::from(*ip6);
+        let (offset, _) = upper_layer(&buf)?;
+        let pkt = Icmpv6Packet::new(&buf[offset..]).ok_or_else(packet_error)?;
+        println!("------");
+        println!("\ticmp6_type : {}", pkt.get_icmpv6_type().0);
+        println!("\ticmp6_code : {}", pkt.get_icmpv6_code().0);
+        println!("\ticmp6_cksum : {}", pkt.get_checksum());
+        display_packet(pkt.payload());
+    }
+    Ok(())
+}
+
+/// Send a list of IPv6 datagrams, passed as unnamed arguments.
+///
+/// Datagrams to another destination than the target are rejected. Returns NULL, answers can be
+/// read with pcap_next.
+#[nasl_function]
+fn send_v6packet(
+    context: &Context,
+    positional: CheckedPositionals<&NaslValue>,
+) -> Result<(), FunctionErrorKind> {
+    if positional.is_empty() {
+        return Ok(());
+    }
+    let target_ip = get_host_ip(context)?;
+    let soc = Socket::new_raw(
+        Domain::IPV6,
+        socket2::Type::RAW,
+        Some(Protocol::from(libc::IPPROTO_RAW)),
+    )
+    .map_err(|e| FunctionErrorKind::Dirty(format!("Not possible to create a raw socket: {e}")))?;
+    context
+        .source_binding()
+        .bind(&soc, &IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+        .map_err(|e| {
+            FunctionErrorKind::Dirty(format!("Not possible to bind the raw socket: {e}"))
+        })?;
+    for packet in positional.iter() {
+        let NaslValue::Data(packet_raw) = packet else {
+            return Err(FunctionErrorKind::wrong_unnamed_argument(
+                "Data",
+                "Invalid packet",
+            ));
+        };
+        let pkt = Ipv6Packet::new(packet_raw).ok_or_else(packet_error)?;
+        let destination = pkt.get_destination();
+        if IpAddr::V6(destination) != target_ip
+            && destination.to_ipv4_mapped().map(IpAddr::V4) != Some(target_ip)
+        {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "send_v6packet: malicious or buggy script is trying to send packet to {} instead of designated target {}",
+                destination, target_ip
+            )));
+        }
+        let sockaddr = socket2::SockAddr::from(SocketAddr::new(IpAddr::V6(destination), 0));
+        let sent = soc
+            .send_to(packet_raw, &sockaddr)
+            .map_err(|e| FunctionErrorKind::Diagnostic(format!("send_v6packet: {e}"), None))?;
+        debug!("Sent {} bytes", sent);
+    }
+    Ok(())
+}
+
+pub struct PacketForgeryV6;
+
+function_set! {
+    PacketForgeryV6,
+    sync_stateless,
+    (
+        forge_ip_v6_packet,
+        (forge_ip_v6_packet, "forge_ipv6_packet"),
+        get_ip_v6_element,
+        (get_ip_v6_element, "get_ipv6_element"),
+        set_ip_v6_elements,
+        (set_ip_v6_elements, "set_ipv6_elements"),
+        insert_ip_v6_options,
+        (insert_ip_v6_options, "insert_ipv6_options"),
+        dump_ip_v6_packet,
+        (dump_ip_v6_packet, "dump_ipv6_packet"),
+        forge_icmp_v6_packet,
+        get_icmp_v6_element,
+        dump_icmp_v6_packet,
+        send_v6packet,
+    )
+}
@@ -8,8 +8,19 @@ use std::{
 };
 
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::FunctionErrorKind;
+use crate::nasl::utils::{FunctionErrorKind, SourceBinding};
 use pcap::{Address, Device};
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Get the interface packets to the destination are sent from
+///
+/// A configured interface takes precedence over the interface of the route to the destination.
+pub fn get_interface(dst: IpAddr, binding: &SourceBinding) -> Result<Device, FunctionErrorKind> {
+    match binding.interface() {
+        Some(name) => Ok(Device::from(name)),
+        None => get_interface_by_local_ip(get_source_ip(dst, 50000u16, binding)?),
+    }
+}
 
 /// Convert a string in a IpAddr
 pub fn ipstr2ipaddr(ip_addr: &str) -> Result<IpAddr, FunctionErrorKind> {
@@ -77,22 +88,41 @@ pub fn get_interface_by_local_ip(local_address: IpAddr) -> Result<Device, Functi
     }
 }
 
-pub fn bind_local_socket(dst: &SocketAddr) -> Result<UdpSocket, FunctionErrorKind> {
-    let fe = Err(FunctionErrorKind::Diagnostic(
-        "Error binding".to_string(),
-        None,
-    ));
-    match dst {
-        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0").or(fe),
-        SocketAddr::V6(_) => UdpSocket::bind(" 0:0:0:0:0:0:0:0:0").or(fe),
+pub fn bind_local_socket(
+    dst: &SocketAddr,
+    binding: &SourceBinding,
+) -> Result<UdpSocket, FunctionErrorKind> {
+    let fe = |e| FunctionErrorKind::Diagnostic(format!("Error binding: {e}"), None);
+    let socket =
+        Socket::new(Domain::for_address(*dst), Type::DGRAM, Some(Protocol::UDP)).map_err(fe)?;
+    binding.bind(&socket, &dst.ip()).map_err(fe)?;
+    if binding.address(&dst.ip()).is_none() {
+        let unspecified = match dst {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        socket
+            .bind(&SocketAddr::new(unspecified, 0).into())
+            .map_err(fe)?;
     }
+    Ok(socket.into())
 }
 
 /// Return the source IP address given the destination IP address
-pub fn get_source_ip(dst: IpAddr, port: u16) -> Result<IpAddr, FunctionErrorKind> {
+///
+/// A configured source address takes precedence over the address of the route to the
+/// destination.
+pub fn get_source_ip(
+    dst: IpAddr,
+    port: u16,
+    binding: &SourceBinding,
+) -> Result<IpAddr, FunctionErrorKind> {
+    if let Some(ip) = binding.address(&dst) {
+        return Ok(ip);
+    }
     let socket = SocketAddr::new(dst, port);
     let sd = format!("{}:{}", dst, port);
-    let local_socket = bind_local_socket(&socket)?;
+    let local_socket = bind_local_socket(&socket, binding)?;
     match local_socket.connect(sd) {
         Ok(_) => match local_socket.local_addr() {
             Ok(l_addr) => match IpAddr::from_str(&l_addr.ip().to_string()) {
@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions sending single TCP probes
+//!
+//! A probe is a TCP segment with the given flags sent without opening a connection. The flags
+//! of the answer of the target tell the state of the port, e.g. SYN/ACK for an open port on a
+//! SYN probe or RST for a closed port on a FIN probe, while no answer hints at a filtered port.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Instant,
+};
+
+use pnet::packet::{
+    ip::IpNextHeaderProtocols,
+    ipv4::{self, Ipv4Packet, MutableIpv4Packet},
+    tcp::{self, MutableTcpPacket, TcpFlags, TcpPacket},
+    Packet,
+};
+use tracing::debug;
+
+use super::super::host::get_host_ip;
+use super::capture::{answer_timeout, capture_device, PacketCapture};
+use super::raw_ip_utils::get_source_ip;
+use crate::nasl::builtin::misc::random_impl;
+use crate::nasl::prelude::*;
+
+/// Length of the IPv4 header without options
+const IP_HEADER_LENGTH: usize = 20;
+/// Length of the TCP header without options
+const TCP_HEADER_LENGTH: usize = 20;
+
+fn packet_error() -> FunctionErrorKind {
+    FunctionErrorKind::Dirty("No possible to create a packet from buffer".to_string())
+}
+
+/// Forges an IPv4 datagram containing a TCP segment with the given flags and valid checksums.
+fn forge_probe(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    sport: u16,
+    dport: u16,
+    flags: u16,
+    sequence: u32,
+) -> Result<Vec<u8>, FunctionErrorKind> {
+    let mut buf = vec![0u8; IP_HEADER_LENGTH + TCP_HEADER_LENGTH];
+    let mut tcp = MutableTcpPacket::new(&mut buf[IP_HEADER_LENGTH..]).ok_or_else(packet_error)?;
+    tcp.set_source(sport);
+    tcp.set_destination(dport);
+    tcp.set_sequence(sequence);
+    tcp.set_data_offset((TCP_HEADER_LENGTH / 4) as u8);
+    tcp.set_flags(flags);
+    tcp.set_window(1024);
+    let chksum = tcp::ipv4_checksum(&tcp.to_immutable(), &source, &destination);
+    tcp.set_checksum(chksum);
+
+    let mut ip = MutableIpv4Packet::new(&mut buf).ok_or_else(packet_error)?;
+    ip.set_version(4);
+    ip.set_header_length((IP_HEADER_LENGTH / 4) as u8);
+    ip.set_total_length((IP_HEADER_LENGTH + TCP_HEADER_LENGTH) as u16);
+    ip.set_identification(sequence as u16);
+    ip.set_ttl(64);
+    ip.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
+    ip.set_source(source);
+    ip.set_destination(destination);
+    let chksum = ipv4::checksum(&ip.to_immutable());
+    ip.set_checksum(chksum);
+    Ok(buf)
+}
+
+/// Returns the TCP flags of an IPv4 datagram answering the probe.
+fn response_flags(probe: &[u8], datagram: &[u8]) -> Option<u16> {
+    let probe = Ipv4Packet::new(probe)?;
+    let probe_tcp = TcpPacket::new(probe.payload())?;
+    let ip = Ipv4Packet::new(datagram)?;
+    if ip.get_next_level_protocol() != IpNextHeaderProtocols::Tcp
+        || ip.get_source() != probe.get_destination()
+    {
+        return None;
+    }
+    let tcp = TcpPacket::new(ip.payload())?;
+    (tcp.get_source() == probe_tcp.get_destination()
+        && tcp.get_destination() == probe_tcp.get_source())
+    .then(|| tcp.get_flags())
+}
+
+/// Sends a probe with the given flags to the port of the target and waits for the answer.
+fn probe(
+    context: &Context,
+    port: u16,
+    flags: u16,
+    timeout: Option<u64>,
+) -> Result<Option<u16>, FunctionErrorKind> {
+    let target_ip = get_host_ip(context)?;
+    let IpAddr::V4(destination) = target_ip else {
+        return Err(FunctionErrorKind::Diagnostic(
+            format!("TCP probes are not supported for the IPv6 target {target_ip}"),
+            None,
+        ));
+    };
+    let source = match get_source_ip(target_ip, port, context.source_binding())? {
+        IpAddr::V4(x) => x,
+        IpAddr::V6(x) => x.to_ipv4_mapped().ok_or_else(|| {
+            FunctionErrorKind::Dirty(format!("No IPv4 source address for {target_ip}"))
+        })?,
+    };
+    let sport = (random_impl()?.unsigned_abs() % 64511 + 1024) as u16;
+    let packet = forge_probe(
+        source,
+        destination,
+        sport,
+        port,
+        flags,
+        random_impl()? as u32,
+    )?;
+
+    let filter = format!("tcp and src host {target_ip} and src port {port} and dst port {sport}");
+    let mut capture = PacketCapture::open(capture_device(context, None)?, &filter)?;
+
+    let soc = super::packet_forgery::new_raw_socket(context.source_binding())?;
+    soc.set_header_included(true).map_err(|e| {
+        FunctionErrorKind::Dirty(format!("Not possible to create a raw socket: {e}"))
+    })?;
+    let sockaddr = socket2::SockAddr::from(SocketAddr::new(target_ip, 0));
+    let sent = soc
+        .send_to(&packet, &sockaddr)
+        .map_err(|e| FunctionErrorKind::Diagnostic(format!("tcp probe: {e}"), None))?;
+    debug!("Sent {} bytes", sent);
+
+    let start = Instant::now();
+    let deadline = start + answer_timeout(context, target_ip, timeout);
+    while let Some(datagram) =
+        capture.next_datagram(deadline.saturating_duration_since(Instant::now()))?
+    {
+        if let Some(flags) = response_flags(&packet, &datagram) {
+            context
+                .network_timeout()
+                .observe(target_ip, start.elapsed());
+            return Ok(Some(flags));
+        }
+    }
+    Ok(None)
+}
+
+/// Sends a TCP segment with the given flags to the port of the target.
+///
+/// Returns the flags of the answer or NULL when the target did not answer within timeout
+/// seconds, by default the network timeout of the scan.
+#[nasl_function(named(port, flags, timeout))]
+fn tcp_probe(
+    context: &Context,
+    port: u16,
+    flags: u16,
+    timeout: Option<u64>,
+) -> Result<Option<i64>, FunctionErrorKind> {
+    Ok(probe(context, port, flags, timeout)?.map(i64::from))
+}
+
+/// Sends a SYN to the port of the target without completing the handshake.
+///
+/// Returns the flags of the answer, SYN/ACK for an open and RST for a closed port, or NULL
+/// when the target did not answer within timeout seconds.
+#[nasl_function(named(port, timeout))]
+fn tcp_syn_probe(
+    context: &Context,
+    port: u16,
+    timeout: Option<u64>,
+) -> Result<Option<i64>, FunctionErrorKind> {
+    Ok(probe(context, port, TcpFlags::SYN, timeout)?.map(i64::from))
+}
+
+/// Sends a FIN to the port of the target.
+///
+/// Returns the flags of the answer, usually RST for a closed port, or NULL when the target did
+/// not answer within timeout seconds.
+#[nasl_function(named(port, timeout))]
+fn tcp_fin_probe(
+    context: &Context,
+    port: u16,
+    timeout: Option<u64>,
+) -> Result<Option<i64>, FunctionErrorKind> {
+    Ok(probe(context, port, TcpFlags::FIN, timeout)?.map(i64::from))
+}
+
+/// Sends a segment with FIN, PUSH and URG set to the port of the target.
+///
+/// Returns the flags of the answer, usually RST for a closed port, or NULL when the target did
+/// not answer within timeout seconds.
+#[nasl_function(named(port, timeout))]
+fn tcp_xmas_probe(
+    context: &Context,
+    port: u16,
+    timeout: Option<u64>,
+) -> Result<Option<i64>, FunctionErrorKind> {
+    let flags = TcpFlags::FIN | TcpFlags::PSH | TcpFlags::URG;
+    Ok(probe(context, port, flags, timeout)?.map(i64::from))
+}
+
+pub struct TcpProbe;
+
+function_set! {
+    TcpProbe,
+    sync_stateless,
+    (
+        tcp_probe,
+        tcp_syn_probe,
+        tcp_fin_probe,
+        tcp_xmas_probe,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use pnet::packet::{
+        ipv4::{self, Ipv4Packet},
+        tcp::{self, TcpFlags, TcpPacket},
+        Packet,
+    };
+
+    use super::{forge_probe, response_flags};
+
+    #[test]
+    fn probe_checksums() {
+        let source = Ipv4Addr::new(192, 0, 2, 1);
+        let destination = Ipv4Addr::new(192, 0, 2, 2);
+        let packet = forge_probe(source, destination, 40000, 80, TcpFlags::SYN, 1).unwrap();
+        let ip = Ipv4Packet::new(&packet).unwrap();
+        assert_eq!(ip.get_checksum(), ipv4::checksum(&ip));
+        let segment = TcpPacket::new(ip.payload()).unwrap();
+        assert_eq!(segment.get_flags(), TcpFlags::SYN);
+        assert_eq!(
+            segment.get_checksum(),
+            tcp::ipv4_checksum(&segment, &source, &destination)
+        );
+    }
+
+    #[test]
+    fn response() {
+        let source = Ipv4Addr::new(192, 0, 2, 1);
+        let destination = Ipv4Addr::new(192, 0, 2, 2);
+        let probe = forge_probe(source, destination, 40000, 80, TcpFlags::SYN, 1).unwrap();
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+        let answer = forge_probe(destination, source, 80, 40000, syn_ack, 2).unwrap();
+        assert_eq!(response_flags(&probe, &answer), Some(syn_ack));
+        let other = forge_probe(destination, source, 443, 40000, TcpFlags::RST, 2).unwrap();
+        assert_eq!(response_flags(&probe, &other), None);
+    }
+}
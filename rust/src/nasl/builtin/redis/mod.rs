@@ -0,0 +1,322 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to send commands to Redis servers via the Redis serialization
+//! protocol, e.g. to check whether a server is reachable without authentication or to
+//! fingerprint it by the output of INFO.
+
+mod resp;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, io, sync::Mutex, time::Duration};
+
+use indexmap::IndexMap;
+use resp::{Reply, ReplyReader};
+
+use crate::nasl::prelude::*;
+
+use super::network::{
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    ssl::SslOptions,
+    tcp::TcpConnection,
+};
+
+const REDIS_PORT: u16 = 6379;
+/// User authenticated by AUTH with the password only
+const DEFAULT_USER: &str = "default";
+
+struct Connection {
+    conn: TcpConnection,
+    timeout: Duration,
+    /// Error returned by the server for the last command
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct Handles {
+    connections: HashMap<usize, Connection>,
+    last_id: usize,
+}
+
+/// Holds the Redis connections opened by a script.
+#[derive(Default)]
+pub struct Redis {
+    handles: Mutex<Handles>,
+}
+
+fn io_error(e: io::Error) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(format!("Redis: {e}"), Some(NaslValue::Null))
+}
+
+/// Returns the string as string when it is valid UTF-8, e.g. dumped values are returned as
+/// data.
+fn bulk_to_nasl(value: Vec<u8>) -> NaslValue {
+    match String::from_utf8(value) {
+        Ok(x) => NaslValue::String(x),
+        Err(e) => NaslValue::Data(e.into_bytes()),
+    }
+}
+
+/// Returns the reply as NASL value, errors nested in arrays are returned as their message.
+fn reply_to_nasl(reply: Reply) -> NaslValue {
+    match reply {
+        Reply::Null => NaslValue::Null,
+        Reply::Status(x) | Reply::Error(x) | Reply::Number(x) => NaslValue::String(x),
+        Reply::Integer(x) => NaslValue::Number(x),
+        Reply::Bulk(x) => bulk_to_nasl(x),
+        Reply::Boolean(x) => NaslValue::Boolean(x),
+        Reply::Array(x) => NaslValue::Array(x.into_iter().map(reply_to_nasl).collect()),
+        Reply::Map(x) => NaslValue::Dict(
+            x.into_iter()
+                .map(|(key, value)| (reply_to_nasl(key).to_string(), reply_to_nasl(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Returns the argument of a command, numbers are sent in their decimal representation.
+fn argument(value: &NaslValue) -> Result<Vec<u8>, FunctionErrorKind> {
+    match value {
+        NaslValue::String(x) => Ok(x.as_bytes().to_vec()),
+        NaslValue::Data(x) => Ok(x.clone()),
+        NaslValue::Number(x) => Ok(x.to_string().into_bytes()),
+        x => Err(FunctionErrorKind::wrong_argument(
+            "command",
+            "string, data or int",
+            &x.to_string(),
+        )),
+    }
+}
+
+/// Returns the fields of the output of INFO, which lists them as name:value in sections
+/// starting with a comment.
+fn parse_info(info: &str) -> IndexMap<String, NaslValue> {
+    info.lines()
+        .filter(|x| !x.starts_with('#'))
+        .filter_map(|x| x.split_once(':'))
+        .map(|(name, value)| (name.to_string(), NaslValue::String(value.to_string())))
+        .collect()
+}
+
+impl ReplyReader for Connection {
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        self.conn.read_line_with_timeout(&mut line, self.timeout)?;
+        match line.strip_suffix("\r\n") {
+            Some(x) => Ok(x.to_string()),
+            None if line.is_empty() => Err(io::ErrorKind::UnexpectedEof.into()),
+            None => Err(invalid_data("missing CRLF")),
+        }
+    }
+
+    fn read_blob(&mut self, length: usize) -> io::Result<Vec<u8>> {
+        let mut blob = vec![0; length + 2];
+        self.conn.read_exact_with_timeout(&mut blob, self.timeout)?;
+        blob.truncate(length);
+        Ok(blob)
+    }
+}
+
+impl Connection {
+    /// Sends the command and returns its reply, remembering an error returned by the server.
+    fn command<T: AsRef<[u8]>>(&mut self, arguments: &[T]) -> io::Result<Option<Reply>> {
+        io::Write::write_all(&mut self.conn, &resp::command(arguments))?;
+        io::Write::flush(&mut self.conn)?;
+        match self.read_reply()? {
+            Reply::Error(e) => {
+                self.error = Some(e);
+                Ok(None)
+            }
+            reply => {
+                self.error = None;
+                Ok(Some(reply))
+            }
+        }
+    }
+}
+
+impl Redis {
+    fn with_connection<T>(
+        &self,
+        handle: usize,
+        f: impl FnOnce(&mut Connection) -> T,
+    ) -> Result<T, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let connection = handles.connections.get_mut(&handle).ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(format!("Unknown Redis connection {handle}"), None)
+        })?;
+        Ok(f(connection))
+    }
+
+    /// Connects to the Redis server of the target and returns the handle of the connection.
+    ///
+    /// - port: Port of the server, 6379 by default
+    /// - ssl: TRUE to negotiate TLS directly after connecting
+    /// - username, password: Credentials sent with AUTH, the user "default" by default
+    /// - protocol: 2 (default) or 3 to switch to RESP3 with HELLO
+    /// - timeout: Time in seconds to wait for each reply, the network timeout of the scan by
+    ///   default
+    ///
+    /// Returns NULL when the connection, the authentication or the protocol switch fails.
+    #[nasl_function(named(port, ssl, username, password, protocol, timeout))]
+    #[allow(clippy::too_many_arguments)]
+    fn redis_connect(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        ssl: Option<bool>,
+        username: Option<&str>,
+        password: Option<&str>,
+        protocol: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let resp3 = match protocol {
+            None | Some(2) => false,
+            Some(3) => true,
+            Some(x) => {
+                return Err(FunctionErrorKind::wrong_argument(
+                    "protocol",
+                    "2 or 3",
+                    &x.to_string(),
+                ))
+            }
+        };
+        let (addrs, port, timeout) = target_service(context, port, REDIS_PORT, timeout)?;
+        let mut arguments = vec![];
+        if resp3 {
+            arguments.extend(["HELLO", "3"]);
+            if let Some(password) = password {
+                arguments.extend(["AUTH", username.unwrap_or(DEFAULT_USER), password]);
+            }
+        } else if let Some(password) = password {
+            arguments.push("AUTH");
+            arguments.extend(username);
+            arguments.push(password);
+        }
+        let connection = NaslSockets::connect_tcp(context, &addrs, port, None, timeout, None)
+            .and_then(|mut conn| {
+                if ssl.unwrap_or_default() {
+                    let options = SslOptions {
+                        sni: Some(context.target().to_string())
+                            .filter(|x| x.parse::<std::net::IpAddr>().is_err()),
+                        ..Default::default()
+                    };
+                    conn.negotiate_ssl(&options, timeout)?;
+                }
+                let mut connection = Connection {
+                    conn,
+                    timeout,
+                    error: None,
+                };
+                if !arguments.is_empty() && connection.command(&arguments)?.is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        connection.error.unwrap_or_default(),
+                    ));
+                }
+                Ok(connection)
+            });
+        let connection = match connection {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, port, "unable to connect to the Redis server");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.connections.insert(id, connection);
+        Ok(NaslValue::Number(id as i64))
+    }
+
+    /// Sends the command given by the positional arguments and returns its reply.
+    ///
+    /// Simple and bulk strings are returned as strings, or as data when they are not valid
+    /// UTF-8, integers as ints, arrays and sets as lists and maps as arrays. Doubles and big
+    /// numbers are returned in their text representation.
+    ///
+    /// Returns NULL for a null reply or when the server returns an error, which is available
+    /// via redis_error then.
+    #[nasl_function(named(handle))]
+    fn redis_command(
+        &self,
+        handle: usize,
+        command: CheckedPositionals<&NaslValue>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        if command.is_empty() {
+            return Err(FunctionErrorKind::MissingPositionalArguments {
+                expected: 1,
+                got: 0,
+            });
+        }
+        let arguments = command
+            .into_iter()
+            .map(argument)
+            .collect::<Result<Vec<_>, _>>()?;
+        let reply = self
+            .with_connection(handle, |x| x.command(&arguments))?
+            .map_err(io_error)?;
+        Ok(reply.map_or(NaslValue::Null, reply_to_nasl))
+    }
+
+    /// Returns the error returned by the server for the last command, e.g.
+    /// "NOAUTH Authentication required.", or NULL when it succeeded.
+    #[nasl_function(named(handle))]
+    fn redis_error(&self, handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        self.with_connection(handle, |x| {
+            x.error.clone().map_or(NaslValue::Null, NaslValue::String)
+        })
+    }
+
+    /// Sends INFO and returns the reported fields, e.g. redis_version or os.
+    ///
+    /// - section: Section of the fields, e.g. "server", the default sections by default
+    ///
+    /// Returns NULL when the server returns an error.
+    #[nasl_function(named(handle, section))]
+    fn redis_info(
+        &self,
+        handle: usize,
+        section: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let mut arguments = vec!["INFO"];
+        arguments.extend(section);
+        let reply = self
+            .with_connection(handle, |x| x.command(&arguments))?
+            .map_err(io_error)?;
+        Ok(match reply {
+            Some(Reply::Bulk(x)) => NaslValue::Dict(parse_info(&String::from_utf8_lossy(&x))),
+            _ => NaslValue::Null,
+        })
+    }
+
+    /// Closes the connection.
+    #[nasl_function(named(handle))]
+    fn redis_close(&self, handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        self.handles
+            .lock()
+            .unwrap()
+            .connections
+            .remove(&handle)
+            .ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(format!("Unknown Redis connection {handle}"), None)
+            })?;
+        Ok(NaslValue::Null)
+    }
+}
+
+function_set! {
+    Redis,
+    sync_stateful,
+    (
+        (Redis::redis_connect, "redis_connect"),
+        (Redis::redis_command, "redis_command"),
+        (Redis::redis_error, "redis_error"),
+        (Redis::redis_info, "redis_info"),
+        (Redis::redis_close, "redis_close"),
+    )
+}
@@ -0,0 +1,228 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Commands and replies of the Redis serialization protocol in version 2 and 3 (RESP2, RESP3).
+
+use std::io;
+
+use crate::nasl::builtin::network::network_utils::invalid_data;
+
+/// Longest bulk string accepted from the server
+const MAX_BLOB: usize = 16 * 1024 * 1024;
+/// Deepest nesting of aggregate replies accepted from the server
+const MAX_DEPTH: usize = 32;
+
+/// Returns the command as array of bulk strings, the only form of requests besides inline
+/// commands.
+pub fn command<T: AsRef<[u8]>>(arguments: &[T]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", arguments.len()).into_bytes();
+    for argument in arguments {
+        let argument = argument.as_ref();
+        command.extend(format!("${}\r\n", argument.len()).as_bytes());
+        command.extend_from_slice(argument);
+        command.extend(b"\r\n");
+    }
+    command
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Null,
+    /// Simple strings, e.g. OK
+    Status(String),
+    /// Simple and bulk errors
+    Error(String),
+    Integer(i64),
+    /// Bulk strings and the content of verbatim strings
+    Bulk(Vec<u8>),
+    Boolean(bool),
+    /// Doubles and big numbers in their text representation
+    Number(String),
+    /// Arrays and sets
+    Array(Vec<Reply>),
+    Map(Vec<(Reply, Reply)>),
+}
+
+/// Reads the lines and blobs replies consist of, e.g. from the connection to the server.
+pub trait ReplyReader {
+    /// Returns the next line without its CRLF.
+    fn read_line(&mut self) -> io::Result<String>;
+
+    /// Returns the blob of the length followed by CRLF.
+    fn read_blob(&mut self, length: usize) -> io::Result<Vec<u8>>;
+
+    /// Returns the next reply, skipping out-of-band pushes and attributes.
+    fn read_reply(&mut self) -> io::Result<Reply>
+    where
+        Self: Sized,
+    {
+        read(self, 0, true)
+    }
+}
+
+fn length(value: &str) -> io::Result<Option<usize>> {
+    match value.parse::<i64>() {
+        Ok(-1) => Ok(None),
+        Ok(x) if x >= 0 => Ok(Some(x as usize)),
+        _ => Err(invalid_data("invalid length")),
+    }
+}
+
+fn read_elements<R: ReplyReader>(
+    reader: &mut R,
+    count: usize,
+    depth: usize,
+) -> io::Result<Vec<Reply>> {
+    (0..count).map(|_| read(reader, depth + 1, false)).collect()
+}
+
+fn read<R: ReplyReader>(reader: &mut R, depth: usize, top: bool) -> io::Result<Reply> {
+    if depth > MAX_DEPTH {
+        return Err(invalid_data("nested too deeply"));
+    }
+    let line = reader.read_line()?;
+    let Some(kind) = line.chars().next() else {
+        return Err(invalid_data("empty reply"));
+    };
+    let value = &line[kind.len_utf8()..];
+    Ok(match kind {
+        '+' => Reply::Status(value.to_string()),
+        '-' => Reply::Error(value.to_string()),
+        ':' => Reply::Integer(value.parse().map_err(|_| invalid_data("invalid integer"))?),
+        '_' => Reply::Null,
+        '#' => match value {
+            "t" => Reply::Boolean(true),
+            "f" => Reply::Boolean(false),
+            _ => return Err(invalid_data("invalid boolean")),
+        },
+        ',' | '(' => Reply::Number(value.to_string()),
+        '$' | '!' | '=' => match length(value)? {
+            None => Reply::Null,
+            Some(x) if x > MAX_BLOB => return Err(invalid_data("bulk string too large")),
+            Some(x) => {
+                let blob = reader.read_blob(x)?;
+                match kind {
+                    '!' => Reply::Error(String::from_utf8_lossy(&blob).into_owned()),
+                    // the content follows its format, e.g. txt:
+                    '=' => Reply::Bulk(blob.get(4..).unwrap_or_default().to_vec()),
+                    _ => Reply::Bulk(blob),
+                }
+            }
+        },
+        '*' | '~' => match length(value)? {
+            None => Reply::Null,
+            Some(x) => Reply::Array(read_elements(reader, x, depth)?),
+        },
+        '%' | '|' => {
+            let count = length(value)?.ok_or_else(|| invalid_data("invalid map length"))?;
+            let elements = read_elements(reader, count.saturating_mul(2), depth)?;
+            let mut elements = elements.into_iter();
+            let mut map = Vec::new();
+            while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+                map.push((key, value));
+            }
+            match kind {
+                // attributes describe the following reply
+                '|' => read(reader, depth, top)?,
+                _ => Reply::Map(map),
+            }
+        }
+        '>' if top => {
+            let count = length(value)?.ok_or_else(|| invalid_data("invalid push length"))?;
+            read_elements(reader, count, depth)?;
+            read(reader, depth, top)?
+        }
+        _ => return Err(invalid_data("unknown reply type")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read};
+
+    use super::*;
+
+    struct Replies<'a>(&'a [u8]);
+
+    impl ReplyReader for Replies<'_> {
+        fn read_line(&mut self) -> io::Result<String> {
+            let mut line = String::new();
+            self.0.read_line(&mut line)?;
+            line.strip_suffix("\r\n")
+                .map(|x| x.to_string())
+                .ok_or_else(|| invalid_data("missing CRLF"))
+        }
+
+        fn read_blob(&mut self, length: usize) -> io::Result<Vec<u8>> {
+            let mut blob = vec![0; length + 2];
+            self.0.read_exact(&mut blob)?;
+            blob.truncate(length);
+            Ok(blob)
+        }
+    }
+
+    fn parse(data: &[u8]) -> io::Result<Reply> {
+        Replies(data).read_reply()
+    }
+
+    #[test]
+    fn encode_command() {
+        assert_eq!(
+            command(&["CONFIG", "GET", "dir"]),
+            b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$3\r\ndir\r\n"
+        );
+        assert_eq!(command(&[b"\xff".as_slice()]), b"*1\r\n$1\r\n\xff\r\n");
+    }
+
+    #[test]
+    fn resp2() {
+        assert_eq!(parse(b"+OK\r\n").unwrap(), Reply::Status("OK".to_string()));
+        assert_eq!(
+            parse(b"-NOAUTH Authentication required.\r\n").unwrap(),
+            Reply::Error("NOAUTH Authentication required.".to_string())
+        );
+        assert_eq!(parse(b":-5\r\n").unwrap(), Reply::Integer(-5));
+        assert_eq!(parse(b"$-1\r\n").unwrap(), Reply::Null);
+        assert_eq!(
+            parse(b"*2\r\n$3\r\ndir\r\n$4\r\na\r\nb\r\n").unwrap(),
+            Reply::Array(vec![
+                Reply::Bulk(b"dir".to_vec()),
+                Reply::Bulk(b"a\r\nb".to_vec())
+            ])
+        );
+        assert!(parse(b"$5\r\nabc\r\n").is_err());
+        assert!(parse(b"?\r\n").is_err());
+        assert!(parse(&b"*1\r\n".repeat(MAX_DEPTH + 2)).is_err());
+    }
+
+    #[test]
+    fn resp3() {
+        assert_eq!(parse(b"_\r\n").unwrap(), Reply::Null);
+        assert_eq!(parse(b"#t\r\n").unwrap(), Reply::Boolean(true));
+        assert_eq!(
+            parse(b",1.5\r\n").unwrap(),
+            Reply::Number("1.5".to_string())
+        );
+        assert_eq!(
+            parse(b"=9\r\ntxt:hello\r\n").unwrap(),
+            Reply::Bulk(b"hello".to_vec())
+        );
+        assert_eq!(
+            parse(b"!5\r\nERR x\r\n").unwrap(),
+            Reply::Error("ERR x".to_string())
+        );
+        assert_eq!(
+            parse(b"%1\r\n+proto\r\n:3\r\n").unwrap(),
+            Reply::Map(vec![(
+                Reply::Status("proto".to_string()),
+                Reply::Integer(3)
+            )])
+        );
+        // a push and an attribute preceding the reply
+        assert_eq!(
+            parse(b">2\r\n+invalidate\r\n_\r\n|1\r\n+ttl\r\n:3\r\n~1\r\n:1\r\n").unwrap(),
+            Reply::Array(vec![Reply::Integer(1)])
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    thread,
+};
+
+use crate::nasl::builtin::network::network_utils::tests::tcp_server;
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+const INFO: &str = "# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\n\
+                    os:Linux 6.1.0 x86_64\r\n\r\n# Clients\r\nconnected_clients:1\r\n";
+
+/// Reads a command sent as array of bulk strings.
+fn read_command(reader: &mut impl BufRead) -> io::Result<Vec<Vec<u8>>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let count: usize = line
+        .strip_prefix('*')
+        .and_then(|x| x.trim_end().parse().ok())
+        .ok_or(io::ErrorKind::InvalidData)?;
+    let mut arguments = vec![];
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let length: usize = line
+            .strip_prefix('$')
+            .and_then(|x| x.trim_end().parse().ok())
+            .ok_or(io::ErrorKind::InvalidData)?;
+        let mut argument = vec![0; length + 2];
+        reader.read_exact(&mut argument)?;
+        argument.truncate(length);
+        arguments.push(argument);
+    }
+    Ok(arguments)
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut reply = format!("${}\r\n", value.len()).into_bytes();
+    reply.extend_from_slice(value);
+    reply.extend(b"\r\n");
+    reply
+}
+
+/// Answers like a server requiring the password "secret" of the user default, which
+/// supports RESP3 after HELLO.
+fn serve(stream: TcpStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut authenticated = false;
+    let mut resp3 = false;
+    loop {
+        let command = read_command(&mut reader)?;
+        let arguments: Vec<&[u8]> = command.iter().map(|x| x.as_slice()).collect();
+        let reply = match arguments.as_slice() {
+            [b"AUTH", b"secret"] | [b"AUTH", b"default", b"secret"] => {
+                authenticated = true;
+                b"+OK\r\n".to_vec()
+            }
+            [b"AUTH", ..] => {
+                b"-WRONGPASS invalid username-password pair or user is disabled.\r\n".to_vec()
+            }
+            [b"HELLO", b"3", rest @ ..] => {
+                if rest == [b"AUTH".as_slice(), b"default", b"secret"] {
+                    authenticated = true;
+                }
+                if authenticated {
+                    resp3 = true;
+                    b"%2\r\n$6\r\nserver\r\n$5\r\nredis\r\n$5\r\nproto\r\n:3\r\n".to_vec()
+                } else {
+                    b"-NOAUTH HELLO must be called with the client already authenticated\r\n"
+                        .to_vec()
+                }
+            }
+            _ if !authenticated => b"-NOAUTH Authentication required.\r\n".to_vec(),
+            [b"INFO", ..] if resp3 => format!("={}\r\ntxt:{INFO}\r\n", INFO.len() + 4).into_bytes(),
+            [b"INFO", ..] => bulk(INFO.as_bytes()),
+            [b"CONFIG", b"GET", b"dir"] if resp3 => {
+                b"%1\r\n$3\r\ndir\r\n$14\r\n/var/lib/redis\r\n".to_vec()
+            }
+            [b"CONFIG", b"GET", b"dir"] => b"*2\r\n$3\r\ndir\r\n$14\r\n/var/lib/redis\r\n".to_vec(),
+            [b"GET", _] if resp3 => b"_\r\n".to_vec(),
+            [b"GET", _] => b"$-1\r\n".to_vec(),
+            [b"INCRBY", b"counter", x] => {
+                format!(":{}\r\n", String::from_utf8_lossy(x)).into_bytes()
+            }
+            [b"ECHO", x] => bulk(x),
+            _ => b"-ERR unknown command\r\n".to_vec(),
+        };
+        writer.write_all(&reply)?;
+    }
+}
+
+fn server() -> u16 {
+    tcp_server(|stream| {
+        thread::spawn(move || serve(stream));
+    })
+}
+
+#[test]
+fn resp2() {
+    let port = server();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(format!("h = redis_connect(port: {port});"), 1);
+    t.ok(r#"redis_command(handle: h, "INFO");"#, NaslValue::Null);
+    t.ok("redis_error(handle: h);", "NOAUTH Authentication required.");
+    t.ok(r#"redis_command(handle: h, "AUTH", "secret");"#, "OK");
+    t.ok("redis_error(handle: h);", NaslValue::Null);
+    t.ok(
+        r#"redis_command(handle: h, "CONFIG", "GET", "dir")[1];"#,
+        "/var/lib/redis",
+    );
+    t.ok(
+        r#"redis_command(handle: h, "GET", "missing");"#,
+        NaslValue::Null,
+    );
+    t.ok("redis_error(handle: h);", NaslValue::Null);
+    t.ok(r#"redis_command(handle: h, "INCRBY", "counter", 5);"#, 5);
+    t.ok(
+        r#"redis_command(handle: h, "ECHO", raw_string(0xff, 0x00));"#,
+        vec![0xffu8, 0],
+    );
+    t.ok(r#"redis_info(handle: h)["redis_version"];"#, "7.2.4");
+    check_err_matches!(
+        t,
+        "redis_command(handle: h);",
+        FunctionErrorKind::MissingPositionalArguments { .. }
+    );
+    check_err_matches!(
+        t,
+        r#"redis_command(handle: h, "GET", make_list(1));"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+    t.ok("redis_close(handle: h);", NaslValue::Null);
+    t.ok(
+        format!(r#"redis_connect(port: {port}, password: "wrong");"#),
+        NaslValue::Null,
+    );
+    t.ok(
+        format!(r#"h = redis_connect(port: {port}, username: "default", password: "secret");"#),
+        2,
+    );
+    t.ok(
+        r#"redis_info(handle: h, section: "clients")["connected_clients"];"#,
+        "1",
+    );
+}
+
+#[test]
+fn resp3() {
+    let port = server();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!("redis_connect(port: {port}, protocol: 3);"),
+        NaslValue::Null,
+    );
+    t.ok(
+        format!(r#"h = redis_connect(port: {port}, password: "secret", protocol: 3);"#),
+        1,
+    );
+    t.ok(
+        r#"redis_command(handle: h, "CONFIG", "GET", "dir")["dir"];"#,
+        "/var/lib/redis",
+    );
+    t.ok(
+        r#"redis_command(handle: h, "GET", "missing");"#,
+        NaslValue::Null,
+    );
+    t.ok(r#"redis_info(handle: h)["os"];"#, "Linux 6.1.0 x86_64");
+    check_err_matches!(
+        t,
+        format!("redis_connect(port: {port}, protocol: 4);"),
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
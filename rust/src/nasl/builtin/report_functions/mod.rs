@@ -56,6 +56,10 @@ impl Reporting {
             protocol: Some(protocol),
             message: data,
             detail: None,
+            stats: context.script_stats().map(|x| x.snapshot()),
+            taint_flows: context.taint().map(|x| x.flows()).filter(|x| !x.is_empty()),
+            labels: None,
+            severity: None,
         };
         context
             .dispatcher()
@@ -4,9 +4,12 @@
 
 #[cfg(test)]
 mod tests {
+    use futures::StreamExt;
+
     use crate::{
         models::{self, Protocol, ResultType},
-        nasl::test_prelude::*,
+        nasl::{interpreter::CodeInterpreter, test_prelude::*},
+        storage::ContextKey,
     };
 
     fn verify(function: &str, result_type: ResultType) {
@@ -45,6 +48,10 @@ mod tests {
             protocol: Some(protocol),
             message: Some(format!("test{id}")),
             detail: None,
+            stats: None,
+            taint_flows: None,
+            labels: None,
+            severity: None,
         };
 
         let udp = get_result(0);
@@ -75,4 +82,27 @@ mod tests {
     fn error_message() {
         verify("error_message", ResultType::Error)
     }
+
+    #[tokio::test]
+    async fn script_stats() {
+        let factory = ContextFactory::default();
+        let context = factory.build(ContextKey::default()).with_script_stats(true);
+        let results: Vec<_> = CodeInterpreter::new(
+            "log_message(data: \"test\");",
+            Register::default(),
+            &context,
+        )
+        .stream()
+        .collect()
+        .await;
+        assert!(results.iter().all(|x| x.is_ok()));
+        let result = context
+            .retriever()
+            .result(context.key(), 0)
+            .unwrap()
+            .unwrap();
+        let stats = result.stats.expect("expected script statistics");
+        assert_eq!(stats.bytes_sent, 0);
+        assert_eq!(stats.retransmissions, 0);
+    }
 }
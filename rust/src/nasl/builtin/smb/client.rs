@@ -0,0 +1,705 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! SMB2 and SMB3 client over a direct TCP connection.
+//!
+//! Sessions are authenticated with NTLMv2 and all requests after the authentication are signed.
+//! Signatures of the server are not verified. Sessions and shares that require encryption are
+//! encrypted with AES-128-CCM or AES-128-GCM when the dialect supports it, otherwise they can not
+//! be accessed.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use aes::Aes128;
+use aes_gcm::{aead::AeadInPlace, Aes128Gcm, KeyInit};
+use ccm::{
+    consts::{U11, U16},
+    Ccm,
+};
+use cmac::Cmac;
+use digest::Digest;
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+
+use crate::nasl::builtin::{network::tcp::TcpConnection, winrm::ntlm};
+
+use super::{
+    message::{
+        self, Create, File, Header, Negotiated, Response, Smb1Negotiated, SmbError, AES_128_CCM,
+        DIALECTS, DIALECT_3_1_1, FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_NON_DIRECTORY_FILE,
+        FILE_OPEN, FILE_OPEN_IF, FILE_OVERWRITE_IF, FLAGS_ASYNC_COMMAND, FLAGS_SIGNED,
+        FSCTL_PIPE_TRANSCEIVE, HEADER_LEN, SESSION_FLAG_ENCRYPT_DATA, SESSION_FLAG_IS_GUEST,
+        SESSION_FLAG_IS_NULL, STATUS_BUFFER_OVERFLOW, STATUS_END_OF_FILE,
+        STATUS_MORE_PROCESSING_REQUIRED, STATUS_PENDING, TRANSFORM_HEADER_LEN,
+        TRANSFORM_PROTOCOL_ID,
+    },
+    spnego,
+};
+
+/// Largest message accepted from the server
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+/// Largest read or write, larger ones would need multiple credits
+const MAX_CHUNK: u32 = 65536;
+/// Credits requested with every request
+const CREDITS: u16 = 32;
+/// Largest file read at once, unless a length is given
+pub const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+const SHARE_FLAG_ENCRYPT_DATA: u32 = 0x0000_8000;
+
+type Aes128Ccm = Ccm<Aes128, U16, U11>;
+
+/// Credentials of the session, an empty username authenticates anonymously
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    pub domain: String,
+}
+
+enum Signing {
+    HmacSha256([u8; 16]),
+    AesCmac([u8; 16]),
+}
+
+/// Derives a key with the counter mode KDF of SP800-108 as used by SMB 3.
+fn kdf(key: &[u8; 16], label: &[u8], context: &[u8]) -> [u8; 16] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&1u32.to_be_bytes());
+    mac.update(label);
+    mac.update(&[0]);
+    mac.update(context);
+    mac.update(&128u32.to_be_bytes());
+    mac.finalize().into_bytes()[..16].try_into().unwrap()
+}
+
+impl Signing {
+    fn new(dialect: u16, session_key: &[u8; 16], preauth: &[u8]) -> Self {
+        match dialect {
+            0x0202 | 0x0210 => Self::HmacSha256(*session_key),
+            DIALECT_3_1_1 => Self::AesCmac(kdf(session_key, b"SMBSigningKey\0", preauth)),
+            _ => Self::AesCmac(kdf(session_key, b"SMB2AESCMAC\0", b"SmbSign\0")),
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> [u8; 16] {
+        match self {
+            Self::HmacSha256(key) => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+                    .expect("HMAC accepts any key length");
+                mac.update(message);
+                mac.finalize().into_bytes()[..16].try_into().unwrap()
+            }
+            Self::AesCmac(key) => {
+                let mut mac = <Cmac<Aes128> as Mac>::new_from_slice(key)
+                    .expect("the key has the length of AES-128");
+                mac.update(message);
+                mac.finalize().into_bytes().into()
+            }
+        }
+    }
+}
+
+/// Keys of the encryption of a session
+struct Encryption {
+    cipher: u16,
+    /// Key of the messages to the server
+    encryption_key: [u8; 16],
+    /// Key of the messages from the server
+    decryption_key: [u8; 16],
+}
+
+impl Encryption {
+    fn new(dialect: u16, cipher: u16, session_key: &[u8; 16], preauth: &[u8]) -> Self {
+        let (encryption_key, decryption_key) = match dialect {
+            DIALECT_3_1_1 => (
+                kdf(session_key, b"SMBC2SCipherKey\0", preauth),
+                kdf(session_key, b"SMBS2CCipherKey\0", preauth),
+            ),
+            _ => (
+                kdf(session_key, b"SMB2AESCCM\0", b"ServerIn \0"),
+                kdf(session_key, b"SMB2AESCCM\0", b"ServerOut\0"),
+            ),
+        };
+        Self {
+            cipher,
+            encryption_key,
+            decryption_key,
+        }
+    }
+
+    /// Returns the length of the nonce of the cipher, the rest of the nonce field is zero.
+    fn nonce_len(&self) -> usize {
+        match self.cipher {
+            AES_128_CCM => 11,
+            _ => 12,
+        }
+    }
+
+    /// Returns the message encrypted into a transform message of the session.
+    fn encrypt(&self, message: &[u8], session_id: u64) -> Result<Vec<u8>, SmbError> {
+        let mut nonce: [u8; 16] = random()?;
+        nonce[self.nonce_len()..].fill(0);
+        let mut transform = message::transform_header(&nonce, message.len(), session_id);
+        let mut data = message.to_vec();
+        let nonce = &nonce[..self.nonce_len()];
+        let aad = &transform[20..TRANSFORM_HEADER_LEN];
+        let key = &self.encryption_key.into();
+        let signature = match self.cipher {
+            AES_128_CCM => {
+                Aes128Ccm::new(key).encrypt_in_place_detached(nonce.into(), aad, &mut data)
+            }
+            _ => Aes128Gcm::new(key).encrypt_in_place_detached(nonce.into(), aad, &mut data),
+        }
+        .map_err(|_| SmbError::Protocol("the message could not be encrypted"))?;
+        transform[4..20].copy_from_slice(&signature);
+        transform.extend(data);
+        Ok(transform)
+    }
+
+    /// Returns the message decrypted from a transform message.
+    fn decrypt(&self, transform: &[u8]) -> Result<Vec<u8>, SmbError> {
+        let transform = message::Transform::decode(transform)?;
+        let mut data = transform.encrypted.to_vec();
+        let nonce = &transform.nonce[..self.nonce_len()];
+        let aad = transform.associated_data;
+        let key = &self.decryption_key.into();
+        let signature = transform.signature.into();
+        match self.cipher {
+            AES_128_CCM => Aes128Ccm::new(key).decrypt_in_place_detached(
+                nonce.into(),
+                aad,
+                &mut data,
+                signature,
+            ),
+            _ => Aes128Gcm::new(key).decrypt_in_place_detached(
+                nonce.into(),
+                aad,
+                &mut data,
+                signature,
+            ),
+        }
+        .map_err(|_| SmbError::Protocol("the message could not be decrypted"))?;
+        Ok(data)
+    }
+}
+
+fn preauth_hash(previous: &[u8; 64], message: &[u8]) -> [u8; 64] {
+    let mut hash = [0; 64];
+    hash.copy_from_slice(
+        &Sha512::new()
+            .chain_update(previous)
+            .chain_update(message)
+            .finalize(),
+    );
+    hash
+}
+
+pub fn random<const N: usize>() -> io::Result<[u8; N]> {
+    let mut result = [0; N];
+    openssl::rand::rand_bytes(&mut result).map_err(io::Error::other)?;
+    Ok(result)
+}
+
+/// Sends the message with the header of the direct TCP transport.
+fn send_message(conn: &mut TcpConnection, message: &[u8]) -> io::Result<()> {
+    let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    conn.write_all(&frame)?;
+    conn.flush()
+}
+
+fn receive_message(conn: &mut TcpConnection, timeout: Duration) -> Result<Vec<u8>, SmbError> {
+    loop {
+        let mut header = [0; 4];
+        conn.read_exact_with_timeout(&mut header, timeout)?;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        match header[0] {
+            // NetBIOS session keep alive
+            0x85 if length == 0 => continue,
+            0 if length <= MAX_MESSAGE => {}
+            _ => return Err(SmbError::Protocol("invalid transport header")),
+        }
+        let mut message = vec![0; length];
+        conn.read_exact_with_timeout(&mut message, timeout)?;
+        return Ok(message);
+    }
+}
+
+/// Negotiates the SMB1 dialect NT LM 0.12 only, returns None when the server refuses it.
+pub fn negotiate_smb1(
+    conn: &mut TcpConnection,
+    timeout: Duration,
+) -> Result<Option<Smb1Negotiated>, SmbError> {
+    send_message(conn, &message::smb1_negotiate())?;
+    match receive_message(conn, timeout) {
+        Ok(response) => message::smb1_negotiate_response(&response),
+        // servers without SMB1 close the connection
+        Err(SmbError::Io(e))
+            if matches!(
+                e.kind(),
+                io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(SmbError::Protocol(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Converts a path to the form relative to the share, e.g. `/Windows/win.ini` to
+/// `Windows\win.ini`.
+pub fn share_path(path: &str) -> String {
+    path.replace('/', "\\").trim_start_matches('\\').to_string()
+}
+
+pub struct SmbClient {
+    conn: TcpConnection,
+    timeout: Duration,
+    message_id: u64,
+    session_id: u64,
+    tree_id: u32,
+    pub negotiated: Negotiated,
+    signing: Option<Signing>,
+    /// Keys of the encryption, None when the dialect or the session does not support it
+    encryption: Option<Encryption>,
+    /// All requests are encrypted since the session or a share requires it
+    encrypted: bool,
+    /// Hash of the negotiation and the session setup, only used by SMB 3.1.1
+    preauth: Option<[u8; 64]>,
+    /// The server authenticated the session as guest or anonymous user
+    pub guest: bool,
+}
+
+impl SmbClient {
+    /// Negotiates the highest dialect supported by both sides.
+    pub fn negotiate(mut conn: TcpConnection, timeout: Duration) -> Result<Self, SmbError> {
+        let header = Header {
+            command: message::NEGOTIATE,
+            credits: CREDITS,
+            ..Default::default()
+        };
+        let mut request = header.encode();
+        request.extend(message::negotiate(&random()?, &random()?));
+        send_message(&mut conn, &request)?;
+        let response = Response::decode(receive_message(&mut conn, timeout)?)?;
+        response.check(&[])?;
+        let negotiated = Negotiated::decode(&response)?;
+        if !DIALECTS.contains(&negotiated.dialect) {
+            return Err(SmbError::Protocol(
+                "unsupported dialect selected by the server",
+            ));
+        }
+        let preauth = (negotiated.dialect == DIALECT_3_1_1)
+            .then(|| preauth_hash(&preauth_hash(&[0; 64], &request), &response.data));
+        Ok(Self {
+            conn,
+            timeout,
+            message_id: 1,
+            session_id: 0,
+            tree_id: 0,
+            negotiated,
+            signing: None,
+            encryption: None,
+            encrypted: false,
+            preauth,
+            guest: false,
+        })
+    }
+
+    /// Sends a request and returns its message id and the sent message.
+    fn send(&mut self, command: u16, body: &[u8]) -> Result<(u64, Vec<u8>), SmbError> {
+        let header = Header {
+            // SMB 2.0.2 does not support multiple credits per request
+            credit_charge: (self.negotiated.dialect != 0x0202) as u16,
+            command,
+            credits: CREDITS,
+            // encrypted messages are authenticated by their encryption instead of signatures
+            flags: if self.signing.is_some() && !self.encrypted {
+                FLAGS_SIGNED
+            } else {
+                0
+            },
+            message_id: self.message_id,
+            tree_id: self.tree_id,
+            session_id: self.session_id,
+            ..Default::default()
+        };
+        self.message_id += 1;
+        let mut message = header.encode();
+        message.extend_from_slice(body);
+        match (&self.encryption, &self.signing) {
+            (Some(encryption), _) if self.encrypted => {
+                let transform = encryption.encrypt(&message, self.session_id)?;
+                send_message(&mut self.conn, &transform)?;
+            }
+            (_, signing) => {
+                if let Some(signing) = signing {
+                    let signature = signing.sign(&message);
+                    message[48..HEADER_LEN].copy_from_slice(&signature);
+                }
+                send_message(&mut self.conn, &message)?;
+            }
+        }
+        Ok((header.message_id, message))
+    }
+
+    /// Returns the final response to the request, skipping interim and unrelated responses.
+    fn receive(&mut self, message_id: u64) -> Result<Response, SmbError> {
+        loop {
+            let mut message = receive_message(&mut self.conn, self.timeout)?;
+            if message.starts_with(TRANSFORM_PROTOCOL_ID) {
+                message = self
+                    .encryption
+                    .as_ref()
+                    .ok_or(SmbError::Protocol("unexpected encrypted message"))?
+                    .decrypt(&message)?;
+            }
+            let response = Response::decode(message)?;
+            let header = &response.header;
+            if header.message_id != message_id
+                || (header.status == STATUS_PENDING && header.flags & FLAGS_ASYNC_COMMAND != 0)
+            {
+                continue;
+            }
+            return Ok(response);
+        }
+    }
+
+    fn exchange(&mut self, command: u16, body: &[u8]) -> Result<Response, SmbError> {
+        let (message_id, _) = self.send(command, body)?;
+        self.receive(message_id)
+    }
+
+    /// Authenticates the session with NTLM.
+    pub fn session_setup(&mut self, credentials: &Credentials) -> Result<(), SmbError> {
+        let mut preauth = self.preauth;
+        let update = |preauth: &mut Option<[u8; 64]>, message: &[u8]| {
+            if let Some(hash) = preauth {
+                *hash = preauth_hash(hash, message);
+            }
+        };
+        let token = spnego::init(&ntlm::negotiate());
+        let (message_id, request) =
+            self.send(message::SESSION_SETUP, &message::session_setup(&token))?;
+        let response = self.receive(message_id)?;
+        if response.header.status != STATUS_MORE_PROCESSING_REQUIRED {
+            response.check(&[])?;
+            return Err(SmbError::Protocol(
+                "authentication completed without a challenge",
+            ));
+        }
+        update(&mut preauth, &request);
+        update(&mut preauth, &response.data);
+        self.session_id = response.header.session_id;
+        let (_, token) = message::session_setup_response(&response)?;
+        let challenge = spnego::ntlm_message(token)
+            .and_then(ntlm::Challenge::parse)
+            .ok_or(SmbError::Protocol("no NTLM challenge received"))?;
+        let (token, session_key) = if credentials.username.is_empty() {
+            (ntlm::authenticate_anonymous(&challenge), None)
+        } else {
+            let (token, key) = ntlm::authenticate(
+                &challenge,
+                &credentials.username,
+                &credentials.password,
+                &credentials.domain,
+                &random()?,
+            );
+            (token, Some(key))
+        };
+        let (message_id, request) = self.send(
+            message::SESSION_SETUP,
+            &message::session_setup(&spnego::response(&token)),
+        )?;
+        update(&mut preauth, &request);
+        let response = self.receive(message_id)?;
+        response.check(&[])?;
+        let (flags, _) = message::session_setup_response(&response)?;
+        self.guest = flags & (SESSION_FLAG_IS_GUEST | SESSION_FLAG_IS_NULL) != 0;
+        if let (Some(key), false) = (session_key, self.guest) {
+            let preauth = preauth.unwrap_or([0; 64]);
+            let dialect = self.negotiated.dialect;
+            self.signing = Some(Signing::new(dialect, &key, &preauth));
+            self.encryption = self
+                .negotiated
+                .cipher
+                .map(|cipher| Encryption::new(dialect, cipher, &key, &preauth));
+        }
+        if flags & SESSION_FLAG_ENCRYPT_DATA != 0 {
+            self.require_encryption("the server requires encryption")?;
+        }
+        Ok(())
+    }
+
+    /// Encrypts all following requests, fails with the message when the session has no keys.
+    fn require_encryption(&mut self, message: &'static str) -> Result<(), SmbError> {
+        if self.encryption.is_none() {
+            return Err(SmbError::Protocol(message));
+        }
+        self.encrypted = true;
+        Ok(())
+    }
+
+    /// Connects to the share of the server, e.g. `C$` or `IPC$`.
+    pub fn tree_connect(&mut self, server: &str, share: &str) -> Result<(), SmbError> {
+        let path = format!("\\\\{server}\\{share}");
+        let response = self.exchange(message::TREE_CONNECT, &message::tree_connect(&path))?;
+        response.check(&[])?;
+        let flags = message::u32_at(&response.data, HEADER_LEN + 4)?;
+        self.tree_id = response.header.tree_id;
+        if flags & SHARE_FLAG_ENCRYPT_DATA != 0 {
+            self.require_encryption("the share requires encryption")?;
+        }
+        Ok(())
+    }
+
+    pub fn open(&mut self, path: &str, create: Create) -> Result<File, SmbError> {
+        let response = self.exchange(message::CREATE, &create.encode(&share_path(path)))?;
+        response.check(&[])?;
+        File::decode(&response)
+    }
+
+    /// Reads at most the length at the offset, returns no data at the end of the file.
+    ///
+    /// Named pipes return the next part of a message longer than the length.
+    pub fn read(&mut self, file: &File, offset: u64, length: u32) -> Result<Vec<u8>, SmbError> {
+        let length = length.min(self.negotiated.max_read_size).min(MAX_CHUNK);
+        let response = self.exchange(message::READ, &message::read(&file.id, offset, length))?;
+        if response.header.status == STATUS_END_OF_FILE {
+            return Ok(vec![]);
+        }
+        response.check(&[STATUS_BUFFER_OVERFLOW])?;
+        Ok(message::read_response(&response)?.to_vec())
+    }
+
+    /// Writes as much of the data as one request allows and returns the number of bytes written.
+    pub fn write(&mut self, file: &File, offset: u64, data: &[u8]) -> Result<usize, SmbError> {
+        let length = (self.negotiated.max_write_size.min(MAX_CHUNK) as usize).min(data.len());
+        let response = self.exchange(
+            message::WRITE,
+            &message::write(&file.id, offset, &data[..length]),
+        )?;
+        response.check(&[])?;
+        Ok(message::write_response(&response)? as usize)
+    }
+
+    pub fn close(&mut self, file: &File) -> Result<(), SmbError> {
+        self.exchange(message::CLOSE, &message::close(&file.id))?
+            .check(&[])
+    }
+
+    /// Writes the data into the named pipe and returns the answer of the server, the rest of a
+    /// message longer than the maximum is returned by the following reads.
+    pub fn transceive(
+        &mut self,
+        pipe: &File,
+        data: &[u8],
+        max_output: u32,
+    ) -> Result<Vec<u8>, SmbError> {
+        let request = message::ioctl(&pipe.id, FSCTL_PIPE_TRANSCEIVE, data, max_output);
+        let response = self.exchange(message::IOCTL, &request)?;
+        response.check(&[STATUS_BUFFER_OVERFLOW])?;
+        Ok(message::ioctl_response(&response)?.to_vec())
+    }
+
+    /// Runs the function with the opened file and closes it afterwards.
+    pub fn with_file<T>(
+        &mut self,
+        path: &str,
+        create: Create,
+        f: impl FnOnce(&mut Self, &File) -> Result<T, SmbError>,
+    ) -> Result<T, SmbError> {
+        let file = self.open(path, create)?;
+        let result = f(self, &file);
+        let closed = self.close(&file);
+        let result = result?;
+        closed.map(|_| result)
+    }
+
+    /// Reads the file from the offset up to the length or to its end.
+    pub fn read_file(
+        &mut self,
+        path: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Vec<u8>, SmbError> {
+        let create = Create {
+            desired_access: FILE_GENERIC_READ,
+            disposition: FILE_OPEN,
+            options: FILE_NON_DIRECTORY_FILE,
+        };
+        self.with_file(path, create, |client, file| {
+            let length = match length {
+                Some(x) => x,
+                None => file.size.saturating_sub(offset),
+            };
+            if length > MAX_FILE_SIZE {
+                return Err(SmbError::Protocol(
+                    "the file is larger than 16 MiB, read it in parts",
+                ));
+            }
+            let mut data = vec![];
+            while (data.len() as u64) < length {
+                let remaining = (length - data.len() as u64).min(MAX_CHUNK as u64) as u32;
+                let chunk = client.read(file, offset + data.len() as u64, remaining)?;
+                if chunk.is_empty() {
+                    break;
+                }
+                data.extend(chunk);
+            }
+            Ok(data)
+        })
+    }
+
+    /// Writes the data at the offset, without an offset the file is replaced.
+    pub fn write_file(
+        &mut self,
+        path: &str,
+        offset: Option<u64>,
+        data: &[u8],
+    ) -> Result<usize, SmbError> {
+        let create = Create {
+            desired_access: FILE_GENERIC_WRITE,
+            disposition: if offset.is_some() {
+                FILE_OPEN_IF
+            } else {
+                FILE_OVERWRITE_IF
+            },
+            options: FILE_NON_DIRECTORY_FILE,
+        };
+        let offset = offset.unwrap_or_default();
+        self.with_file(path, create, |client, file| {
+            let mut written = 0;
+            while written < data.len() {
+                match client.write(file, offset + written as u64, &data[written..])? {
+                    0 => break,
+                    n => written += n,
+                }
+            }
+            Ok(written)
+        })
+    }
+
+    /// Disconnects from the share and logs off, the server closes the connection afterwards.
+    pub fn logoff(&mut self) -> Result<(), SmbError> {
+        if self.tree_id != 0 {
+            self.exchange(message::TREE_DISCONNECT, &message::empty())?
+                .check(&[])?;
+            self.tree_id = 0;
+        }
+        self.exchange(message::LOGOFF, &message::empty())?
+            .check(&[])
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Returns the signature of the message, computed like the server does.
+    pub fn signature(
+        dialect: u16,
+        session_key: &[u8; 16],
+        preauth: &[u8],
+        message: &[u8],
+    ) -> [u8; 16] {
+        let mut message = message.to_vec();
+        message[48..HEADER_LEN].fill(0);
+        Signing::new(dialect, session_key, preauth).sign(&message)
+    }
+
+    /// Returns the encryption of the server side of a session, its keys are swapped.
+    fn server_encryption(
+        dialect: u16,
+        cipher: u16,
+        session_key: &[u8; 16],
+        preauth: &[u8],
+    ) -> Encryption {
+        let mut encryption = Encryption::new(dialect, cipher, session_key, preauth);
+        std::mem::swap(
+            &mut encryption.encryption_key,
+            &mut encryption.decryption_key,
+        );
+        encryption
+    }
+
+    /// Decrypts a transform message of the client like the server does.
+    pub fn server_decrypt(
+        dialect: u16,
+        cipher: u16,
+        session_key: &[u8; 16],
+        preauth: &[u8],
+        transform: &[u8],
+    ) -> Vec<u8> {
+        server_encryption(dialect, cipher, session_key, preauth)
+            .decrypt(transform)
+            .unwrap()
+    }
+
+    /// Encrypts a message to the client like the server does.
+    pub fn server_encrypt(
+        dialect: u16,
+        cipher: u16,
+        session_key: &[u8; 16],
+        preauth: &[u8],
+        message: &[u8],
+        session_id: u64,
+    ) -> Vec<u8> {
+        server_encryption(dialect, cipher, session_key, preauth)
+            .encrypt(message, session_id)
+            .unwrap()
+    }
+
+    /// Returns the next preauth integrity hash of SMB 3.1.1.
+    pub fn preauth(previous: &[u8; 64], message: &[u8]) -> [u8; 64] {
+        preauth_hash(previous, message)
+    }
+
+    #[test]
+    fn signing_keys() {
+        // MS-SMB2 4.2: session key and signing key of a SMB 3.0 session
+        let key = hex::decode("7cd451825d0450d235424e44ba6e78cc").unwrap();
+        assert_eq!(
+            hex::encode(kdf(
+                &key.try_into().unwrap(),
+                b"SMB2AESCMAC\0",
+                b"SmbSign\0"
+            )),
+            "0b7e9c5cac36c0f6ea9ab275298cedce"
+        );
+    }
+
+    #[test]
+    fn encryption() {
+        let key = [7; 16];
+        for (dialect, cipher) in [(0x0300, AES_128_CCM), (DIALECT_3_1_1, message::AES_128_GCM)] {
+            let client = Encryption::new(dialect, cipher, &key, &[3; 64]);
+            let transform = client.encrypt(b"message", 5).unwrap();
+            assert_eq!(&transform[..4], TRANSFORM_PROTOCOL_ID);
+            assert_eq!(&transform[44..52], 5u64.to_le_bytes());
+            assert_ne!(&transform[TRANSFORM_HEADER_LEN..], b"message");
+            let message = server_decrypt(dialect, cipher, &key, &[3; 64], &transform);
+            assert_eq!(message, b"message");
+            let mut tampered = transform.clone();
+            tampered[TRANSFORM_HEADER_LEN] ^= 1;
+            assert!(server_encryption(dialect, cipher, &key, &[3; 64])
+                .decrypt(&tampered)
+                .is_err());
+            let response = server_encrypt(dialect, cipher, &key, &[3; 64], b"response", 5);
+            assert_eq!(client.decrypt(&response).unwrap(), b"response");
+        }
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(share_path("/Windows/win.ini"), "Windows\\win.ini");
+        assert_eq!(share_path("\\\\a\\b"), "a\\b");
+    }
+}
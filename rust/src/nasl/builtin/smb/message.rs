@@ -0,0 +1,743 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding and decoding of the SMB2 messages (MS-SMB2) and of the SMB1 negotiation (MS-CIFS).
+
+use std::{fmt::Display, io};
+
+/// Length of the SMB2 header, the offsets within the messages are relative to its start
+pub const HEADER_LEN: usize = 64;
+const PROTOCOL_ID: &[u8; 4] = b"\xfeSMB";
+/// Protocol identifier of the TRANSFORM_HEADER of encrypted messages
+pub const TRANSFORM_PROTOCOL_ID: &[u8; 4] = b"\xfdSMB";
+/// Length of the TRANSFORM_HEADER, the encrypted message follows it
+pub const TRANSFORM_HEADER_LEN: usize = 52;
+const SMB1_PROTOCOL_ID: &[u8; 4] = b"\xffSMB";
+
+pub const NEGOTIATE: u16 = 0x0000;
+pub const SESSION_SETUP: u16 = 0x0001;
+pub const LOGOFF: u16 = 0x0002;
+pub const TREE_CONNECT: u16 = 0x0003;
+pub const TREE_DISCONNECT: u16 = 0x0004;
+pub const CREATE: u16 = 0x0005;
+pub const CLOSE: u16 = 0x0006;
+pub const READ: u16 = 0x0008;
+pub const WRITE: u16 = 0x0009;
+pub const IOCTL: u16 = 0x000b;
+
+pub const FLAGS_SERVER_TO_REDIR: u32 = 0x0000_0001;
+pub const FLAGS_ASYNC_COMMAND: u32 = 0x0000_0002;
+pub const FLAGS_SIGNED: u32 = 0x0000_0008;
+
+pub const GLOBAL_CAP_ENCRYPTION: u32 = 0x0000_0040;
+
+pub const SIGNING_ENABLED: u16 = 0x0001;
+pub const SIGNING_REQUIRED: u16 = 0x0002;
+
+pub const SESSION_FLAG_IS_GUEST: u16 = 0x0001;
+pub const SESSION_FLAG_IS_NULL: u16 = 0x0002;
+pub const SESSION_FLAG_ENCRYPT_DATA: u16 = 0x0004;
+
+pub const STATUS_SUCCESS: u32 = 0x0000_0000;
+pub const STATUS_PENDING: u32 = 0x0000_0103;
+pub const STATUS_BUFFER_OVERFLOW: u32 = 0x8000_0005;
+pub const STATUS_END_OF_FILE: u32 = 0xc000_0011;
+pub const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+/// Dialects offered to the server, from the oldest to the newest
+pub const DIALECTS: [u16; 5] = [0x0202, 0x0210, 0x0300, 0x0302, 0x0311];
+pub const DIALECT_3_1_1: u16 = 0x0311;
+/// Dialect of the SMB1 protocol offered in the SMB1 negotiation
+pub const SMB1_DIALECT: &str = "NT LM 0.12";
+
+const PREAUTH_INTEGRITY_CAPABILITIES: u16 = 0x0001;
+const SHA_512: u16 = 0x0001;
+pub const ENCRYPTION_CAPABILITIES: u16 = 0x0002;
+pub const AES_128_CCM: u16 = 0x0001;
+pub const AES_128_GCM: u16 = 0x0002;
+/// Ciphers offered to servers of SMB 3.1.1, in the order of preference
+const CIPHERS: [u16; 2] = [AES_128_GCM, AES_128_CCM];
+/// Flags of the TRANSFORM_HEADER, the message is encrypted
+const TRANSFORM_ENCRYPTED: u16 = 0x0001;
+
+/// Seconds between 1601-01-01, the epoch of FILETIME, and the UNIX epoch
+const FILETIME_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+/// Returns the name of a NTSTATUS code returned by SMB servers.
+pub fn status_name(status: u32) -> Option<&'static str> {
+    Some(match status {
+        STATUS_SUCCESS => "STATUS_SUCCESS",
+        STATUS_PENDING => "STATUS_PENDING",
+        STATUS_BUFFER_OVERFLOW => "STATUS_BUFFER_OVERFLOW",
+        STATUS_END_OF_FILE => "STATUS_END_OF_FILE",
+        STATUS_MORE_PROCESSING_REQUIRED => "STATUS_MORE_PROCESSING_REQUIRED",
+        0xc000_000d => "STATUS_INVALID_PARAMETER",
+        0xc000_000f => "STATUS_NO_SUCH_FILE",
+        0xc000_0022 => "STATUS_ACCESS_DENIED",
+        0xc000_0023 => "STATUS_BUFFER_TOO_SMALL",
+        0xc000_0033 => "STATUS_OBJECT_NAME_INVALID",
+        0xc000_0034 => "STATUS_OBJECT_NAME_NOT_FOUND",
+        0xc000_003a => "STATUS_OBJECT_PATH_NOT_FOUND",
+        0xc000_0043 => "STATUS_SHARING_VIOLATION",
+        0xc000_006d => "STATUS_LOGON_FAILURE",
+        0xc000_006e => "STATUS_ACCOUNT_RESTRICTION",
+        0xc000_006f => "STATUS_INVALID_LOGON_HOURS",
+        0xc000_0071 => "STATUS_PASSWORD_EXPIRED",
+        0xc000_0072 => "STATUS_ACCOUNT_DISABLED",
+        0xc000_00ba => "STATUS_FILE_IS_A_DIRECTORY",
+        0xc000_00bb => "STATUS_NOT_SUPPORTED",
+        0xc000_00b0 => "STATUS_PIPE_DISCONNECTED",
+        0xc000_00cc => "STATUS_BAD_NETWORK_NAME",
+        0xc000_0193 => "STATUS_ACCOUNT_EXPIRED",
+        0xc000_0203 => "STATUS_USER_SESSION_DELETED",
+        0xc000_0224 => "STATUS_PASSWORD_MUST_CHANGE",
+        0xc000_0234 => "STATUS_ACCOUNT_LOCKED_OUT",
+        _ => return None,
+    })
+}
+
+/// Returns the dialect revision in the dotted notation, e.g. 3.1.1.
+pub fn dialect_name(dialect: u16) -> String {
+    match dialect {
+        0x0202 => "2.0.2".to_string(),
+        0x0210 => "2.1".to_string(),
+        0x0300 => "3.0".to_string(),
+        0x0302 => "3.0.2".to_string(),
+        0x0311 => "3.1.1".to_string(),
+        x => format!("0x{x:04x}"),
+    }
+}
+
+/// Converts a FILETIME into seconds since the UNIX epoch.
+pub fn unix_time(filetime: u64) -> i64 {
+    (filetime / 10_000_000) as i64 - FILETIME_EPOCH_OFFSET as i64
+}
+
+pub fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+#[derive(Debug)]
+pub enum SmbError {
+    Io(io::Error),
+    /// The server answered with an error status
+    Status(u32),
+    /// The server sent something unexpected
+    Protocol(&'static str),
+    /// The server answered a DCE/RPC call with a fault or rejected the binding
+    Rpc(u32),
+    /// The remote procedure returned a Win32 error code
+    Win32(u32),
+    /// The method of a DCOM interface failed
+    HResult(u32),
+}
+
+impl Display for SmbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Status(status) => match status_name(*status) {
+                Some(name) => write!(f, "{name} (0x{status:08x})"),
+                None => write!(f, "status 0x{status:08x}"),
+            },
+            Self::Protocol(reason) => write!(f, "{reason}"),
+            Self::Rpc(status) => write!(f, "DCE/RPC fault 0x{status:08x}"),
+            Self::Win32(code) => write!(f, "Win32 error {code}"),
+            Self::HResult(result) => write!(f, "HRESULT 0x{result:08x}"),
+        }
+    }
+}
+
+impl From<io::Error> for SmbError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+fn truncated() -> SmbError {
+    SmbError::Protocol("truncated message")
+}
+
+/// Returns the bytes at the offset of the message.
+pub fn bytes_at(data: &[u8], offset: usize, length: usize) -> Result<&[u8], SmbError> {
+    offset
+        .checked_add(length)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(truncated)
+}
+
+pub fn u16_at(data: &[u8], offset: usize) -> Result<u16, SmbError> {
+    Ok(u16::from_le_bytes(
+        bytes_at(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+pub fn u32_at(data: &[u8], offset: usize) -> Result<u32, SmbError> {
+    Ok(u32::from_le_bytes(
+        bytes_at(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+pub fn u64_at(data: &[u8], offset: usize) -> Result<u64, SmbError> {
+    Ok(u64::from_le_bytes(
+        bytes_at(data, offset, 8)?.try_into().unwrap(),
+    ))
+}
+
+/// Fields of the SMB2 header used by the client
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    pub credit_charge: u16,
+    pub status: u32,
+    pub command: u16,
+    pub credits: u16,
+    pub flags: u32,
+    pub message_id: u64,
+    pub tree_id: u32,
+    pub session_id: u64,
+}
+
+impl Header {
+    /// Encodes the header with an empty signature.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut header = PROTOCOL_ID.to_vec();
+        header.extend_from_slice(&(HEADER_LEN as u16).to_le_bytes());
+        header.extend_from_slice(&self.credit_charge.to_le_bytes());
+        header.extend_from_slice(&self.status.to_le_bytes());
+        header.extend_from_slice(&self.command.to_le_bytes());
+        header.extend_from_slice(&self.credits.to_le_bytes());
+        header.extend_from_slice(&self.flags.to_le_bytes());
+        // next command
+        header.extend_from_slice(&[0; 4]);
+        header.extend_from_slice(&self.message_id.to_le_bytes());
+        // process id
+        header.extend_from_slice(&[0; 4]);
+        header.extend_from_slice(&self.tree_id.to_le_bytes());
+        header.extend_from_slice(&self.session_id.to_le_bytes());
+        header.extend_from_slice(&[0; 16]);
+        header
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, SmbError> {
+        if bytes_at(data, 0, 4)? != PROTOCOL_ID {
+            return Err(SmbError::Protocol("no SMB2 message"));
+        }
+        if data.len() < HEADER_LEN {
+            return Err(truncated());
+        }
+        let flags = u32_at(data, 16)?;
+        Ok(Self {
+            credit_charge: u16_at(data, 6)?,
+            status: u32_at(data, 8)?,
+            command: u16_at(data, 12)?,
+            credits: u16_at(data, 14)?,
+            flags,
+            message_id: u64_at(data, 24)?,
+            // asynchronous responses carry an async id instead of the tree id
+            tree_id: if flags & FLAGS_ASYNC_COMMAND == 0 {
+                u32_at(data, 36)?
+            } else {
+                0
+            },
+            session_id: u64_at(data, 40)?,
+        })
+    }
+}
+
+/// A received SMB2 message
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub header: Header,
+    /// The whole message including the header
+    pub data: Vec<u8>,
+}
+
+impl Response {
+    pub fn decode(data: Vec<u8>) -> Result<Self, SmbError> {
+        let header = Header::decode(&data)?;
+        if header.flags & FLAGS_SERVER_TO_REDIR == 0 {
+            return Err(SmbError::Protocol(
+                "received a request instead of a response",
+            ));
+        }
+        Ok(Self { header, data })
+    }
+
+    /// Returns an error unless the status is success or one of the expected ones.
+    pub fn check(&self, expected: &[u32]) -> Result<(), SmbError> {
+        match self.header.status {
+            STATUS_SUCCESS => Ok(()),
+            x if expected.contains(&x) => Ok(()),
+            x => Err(SmbError::Status(x)),
+        }
+    }
+
+    /// Returns the buffer given by the 16 bit offset and length at the position of the body.
+    pub fn buffer(&self, position: usize) -> Result<&[u8], SmbError> {
+        let offset = u16_at(&self.data, HEADER_LEN + position)? as usize;
+        let length = u16_at(&self.data, HEADER_LEN + position + 2)? as usize;
+        if length == 0 {
+            return Ok(&[]);
+        }
+        bytes_at(&self.data, offset, length)
+    }
+}
+
+/// Result of the negotiation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub dialect: u16,
+    pub security_mode: u16,
+    pub server_guid: [u8; 16],
+    pub capabilities: u32,
+    pub max_read_size: u32,
+    pub max_write_size: u32,
+    /// Time of the server as FILETIME
+    pub system_time: u64,
+    /// Cipher of the encryption of messages, None when the server does not support encryption
+    pub cipher: Option<u16>,
+}
+
+/// Appends the negotiate context to the message, aligned to 8 octets.
+fn negotiate_context(message: &mut Vec<u8>, context_type: u16, data: &[u8]) {
+    message.resize(message.len().next_multiple_of(8), 0);
+    message.extend_from_slice(&context_type.to_le_bytes());
+    message.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    message.extend_from_slice(&[0; 4]);
+    message.extend_from_slice(data);
+}
+
+/// Returns the types and the data of the negotiate contexts starting at the offset of the
+/// message.
+pub fn negotiate_contexts(
+    message: &[u8],
+    offset: usize,
+    count: usize,
+) -> Result<Vec<(u16, &[u8])>, SmbError> {
+    let mut position = offset;
+    let mut contexts = vec![];
+    for _ in 0..count {
+        position = position.next_multiple_of(8);
+        let length = u16_at(message, position + 2)? as usize;
+        contexts.push((
+            u16_at(message, position)?,
+            bytes_at(message, position + 8, length)?,
+        ));
+        position += 8 + length;
+    }
+    Ok(contexts)
+}
+
+/// Returns the body of a NEGOTIATE request offering all dialects and encryption.
+///
+/// The preauth integrity context required by SMB 3.1.1 uses the salt.
+pub fn negotiate(client_guid: &[u8; 16], salt: &[u8; 32]) -> Vec<u8> {
+    let fixed = 36;
+    let dialects_end = HEADER_LEN + fixed + DIALECTS.len() * 2;
+    let context_offset = dialects_end.next_multiple_of(8);
+    let mut body = 36u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&(DIALECTS.len() as u16).to_le_bytes());
+    body.extend_from_slice(&SIGNING_ENABLED.to_le_bytes());
+    body.extend_from_slice(&[0; 2]);
+    body.extend_from_slice(&GLOBAL_CAP_ENCRYPTION.to_le_bytes());
+    body.extend_from_slice(client_guid);
+    body.extend_from_slice(&(context_offset as u32).to_le_bytes());
+    body.extend_from_slice(&2u16.to_le_bytes());
+    body.extend_from_slice(&[0; 2]);
+    for dialect in DIALECTS {
+        body.extend_from_slice(&dialect.to_le_bytes());
+    }
+    // the contexts are aligned relative to the start of the message
+    let mut message = vec![0; HEADER_LEN];
+    message.extend(body);
+    let mut data = 1u16.to_le_bytes().to_vec();
+    data.extend_from_slice(&(salt.len() as u16).to_le_bytes());
+    data.extend_from_slice(&SHA_512.to_le_bytes());
+    data.extend_from_slice(salt);
+    negotiate_context(&mut message, PREAUTH_INTEGRITY_CAPABILITIES, &data);
+    let mut data = (CIPHERS.len() as u16).to_le_bytes().to_vec();
+    for cipher in CIPHERS {
+        data.extend_from_slice(&cipher.to_le_bytes());
+    }
+    negotiate_context(&mut message, ENCRYPTION_CAPABILITIES, &data);
+    message.split_off(HEADER_LEN)
+}
+
+impl Negotiated {
+    pub fn decode(response: &Response) -> Result<Self, SmbError> {
+        let data = &response.data;
+        let body = HEADER_LEN;
+        let dialect = u16_at(data, body + 4)?;
+        let capabilities = u32_at(data, body + 24)?;
+        let cipher = match dialect {
+            DIALECT_3_1_1 => {
+                let offset = u32_at(data, body + 60)? as usize;
+                let count = u16_at(data, body + 6)? as usize;
+                negotiate_contexts(data, offset, count)?
+                    .into_iter()
+                    .find(|(x, _)| *x == ENCRYPTION_CAPABILITIES)
+                    .map(|(_, x)| u16_at(x, 2))
+                    .transpose()?
+                    .filter(|x| CIPHERS.contains(x))
+            }
+            0x0300 | 0x0302 if capabilities & GLOBAL_CAP_ENCRYPTION != 0 => Some(AES_128_CCM),
+            _ => None,
+        };
+        Ok(Self {
+            security_mode: u16_at(data, body + 2)?,
+            dialect,
+            server_guid: bytes_at(data, body + 8, 16)?.try_into().unwrap(),
+            capabilities,
+            max_read_size: u32_at(data, body + 32)?,
+            max_write_size: u32_at(data, body + 36)?,
+            system_time: u64_at(data, body + 40)?,
+            cipher,
+        })
+    }
+}
+
+/// Returns the TRANSFORM_HEADER of a message encrypted with the nonce, with an empty signature.
+pub fn transform_header(nonce: &[u8; 16], message_size: usize, session_id: u64) -> Vec<u8> {
+    let mut header = TRANSFORM_PROTOCOL_ID.to_vec();
+    header.extend_from_slice(&[0; 16]);
+    header.extend_from_slice(nonce);
+    header.extend_from_slice(&(message_size as u32).to_le_bytes());
+    header.extend_from_slice(&[0; 2]);
+    header.extend_from_slice(&TRANSFORM_ENCRYPTED.to_le_bytes());
+    header.extend_from_slice(&session_id.to_le_bytes());
+    header
+}
+
+/// Fields of a received TRANSFORM_HEADER
+pub struct Transform<'a> {
+    pub signature: &'a [u8],
+    pub nonce: &'a [u8],
+    /// The part of the header authenticated with the message
+    pub associated_data: &'a [u8],
+    pub encrypted: &'a [u8],
+}
+
+impl<'a> Transform<'a> {
+    pub fn decode(data: &'a [u8]) -> Result<Self, SmbError> {
+        if bytes_at(data, 0, 4)? != TRANSFORM_PROTOCOL_ID {
+            return Err(SmbError::Protocol("no encrypted SMB2 message"));
+        }
+        if u16_at(data, 42)? != TRANSFORM_ENCRYPTED {
+            return Err(SmbError::Protocol("unsupported SMB2 transform"));
+        }
+        let size = u32_at(data, 36)? as usize;
+        Ok(Self {
+            signature: bytes_at(data, 4, 16)?,
+            nonce: bytes_at(data, 20, 16)?,
+            associated_data: bytes_at(data, 20, 32)?,
+            encrypted: bytes_at(data, TRANSFORM_HEADER_LEN, size)?,
+        })
+    }
+}
+
+/// Returns the body of a SESSION_SETUP request.
+pub fn session_setup(token: &[u8]) -> Vec<u8> {
+    let mut body = 25u16.to_le_bytes().to_vec();
+    // flags
+    body.push(0);
+    body.push(SIGNING_ENABLED as u8);
+    // capabilities and channel
+    body.extend_from_slice(&[0; 8]);
+    body.extend_from_slice(&((HEADER_LEN + 24) as u16).to_le_bytes());
+    body.extend_from_slice(&(token.len() as u16).to_le_bytes());
+    // previous session id
+    body.extend_from_slice(&[0; 8]);
+    body.extend_from_slice(token);
+    body
+}
+
+/// Returns the session flags and the security buffer of a SESSION_SETUP response.
+pub fn session_setup_response(response: &Response) -> Result<(u16, &[u8]), SmbError> {
+    Ok((u16_at(&response.data, HEADER_LEN + 2)?, response.buffer(4)?))
+}
+
+/// Returns the body of a TREE_CONNECT request for the UNC path of the share.
+pub fn tree_connect(path: &str) -> Vec<u8> {
+    let path = utf16le(path);
+    let mut body = 9u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&[0; 2]);
+    body.extend_from_slice(&((HEADER_LEN + 8) as u16).to_le_bytes());
+    body.extend_from_slice(&(path.len() as u16).to_le_bytes());
+    body.extend_from_slice(&path);
+    body
+}
+
+/// Returns the body of requests consisting of the structure size and a reserved field only,
+/// i.e. LOGOFF and TREE_DISCONNECT.
+pub fn empty() -> Vec<u8> {
+    vec![4, 0, 0, 0]
+}
+
+/// Parameters of a CREATE request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Create {
+    pub desired_access: u32,
+    pub disposition: u32,
+    pub options: u32,
+}
+
+pub const FILE_GENERIC_READ: u32 = 0x0012_0089;
+pub const FILE_GENERIC_WRITE: u32 = 0x0012_0116;
+const FILE_SHARE_ALL: u32 = 0x0000_0007;
+pub const FILE_OPEN: u32 = 0x0000_0001;
+pub const FILE_OPEN_IF: u32 = 0x0000_0003;
+pub const FILE_OVERWRITE_IF: u32 = 0x0000_0005;
+pub const FILE_NON_DIRECTORY_FILE: u32 = 0x0000_0040;
+const IMPERSONATION: u32 = 2;
+
+impl Create {
+    /// Returns the body of the request for the path relative to the share.
+    pub fn encode(&self, path: &str) -> Vec<u8> {
+        let name = utf16le(path);
+        let mut body = 57u16.to_le_bytes().to_vec();
+        // security flags and oplock level
+        body.extend_from_slice(&[0; 2]);
+        body.extend_from_slice(&IMPERSONATION.to_le_bytes());
+        // create flags and reserved
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(&self.desired_access.to_le_bytes());
+        // file attributes
+        body.extend_from_slice(&[0; 4]);
+        body.extend_from_slice(&FILE_SHARE_ALL.to_le_bytes());
+        body.extend_from_slice(&self.disposition.to_le_bytes());
+        body.extend_from_slice(&self.options.to_le_bytes());
+        body.extend_from_slice(&((HEADER_LEN + 56) as u16).to_le_bytes());
+        body.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // create contexts
+        body.extend_from_slice(&[0; 8]);
+        body.extend_from_slice(&name);
+        // the buffer must not be empty
+        if name.is_empty() {
+            body.push(0);
+        }
+        body
+    }
+}
+
+/// An opened file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct File {
+    pub id: [u8; 16],
+    pub size: u64,
+}
+
+impl File {
+    pub fn decode(response: &Response) -> Result<Self, SmbError> {
+        Ok(Self {
+            size: u64_at(&response.data, HEADER_LEN + 48)?,
+            id: bytes_at(&response.data, HEADER_LEN + 64, 16)?
+                .try_into()
+                .unwrap(),
+        })
+    }
+}
+
+/// Returns the body of a READ request.
+pub fn read(file: &[u8; 16], offset: u64, length: u32) -> Vec<u8> {
+    let mut body = 49u16.to_le_bytes().to_vec();
+    // padding, the data follows the fixed part of the response
+    body.push(0x50);
+    body.push(0);
+    body.extend_from_slice(&length.to_le_bytes());
+    body.extend_from_slice(&offset.to_le_bytes());
+    body.extend_from_slice(file);
+    // minimum count, channel, remaining bytes, channel info and the buffer
+    body.extend_from_slice(&[0; 17]);
+    body
+}
+
+/// Returns the data of a READ response.
+pub fn read_response(response: &Response) -> Result<&[u8], SmbError> {
+    let data = &response.data;
+    let offset = data.get(HEADER_LEN + 2).copied().ok_or_else(truncated)? as usize;
+    let length = u32_at(data, HEADER_LEN + 4)? as usize;
+    if length == 0 {
+        return Ok(&[]);
+    }
+    bytes_at(data, offset, length)
+}
+
+/// Returns the body of a WRITE request.
+pub fn write(file: &[u8; 16], offset: u64, data: &[u8]) -> Vec<u8> {
+    let mut body = 49u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&((HEADER_LEN + 48) as u16).to_le_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    body.extend_from_slice(&offset.to_le_bytes());
+    body.extend_from_slice(file);
+    // channel, remaining bytes, channel info and flags
+    body.extend_from_slice(&[0; 16]);
+    body.extend_from_slice(data);
+    body
+}
+
+/// Returns the number of bytes written of a WRITE response.
+pub fn write_response(response: &Response) -> Result<u32, SmbError> {
+    u32_at(&response.data, HEADER_LEN + 4)
+}
+
+/// Returns the body of a CLOSE request.
+pub fn close(file: &[u8; 16]) -> Vec<u8> {
+    let mut body = 24u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&[0; 6]);
+    body.extend_from_slice(file);
+    body
+}
+
+pub const FSCTL_PIPE_TRANSCEIVE: u32 = 0x0011_c017;
+const IOCTL_IS_FSCTL: u32 = 0x0000_0001;
+
+/// Returns the body of an IOCTL request for the file system control code.
+pub fn ioctl(file: &[u8; 16], ctl_code: u32, input: &[u8], max_output: u32) -> Vec<u8> {
+    let input_offset = (HEADER_LEN + 56) as u32;
+    let mut body = 57u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&[0; 2]);
+    body.extend_from_slice(&ctl_code.to_le_bytes());
+    body.extend_from_slice(file);
+    body.extend_from_slice(&input_offset.to_le_bytes());
+    body.extend_from_slice(&(input.len() as u32).to_le_bytes());
+    // max input response, output offset and output count
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(&input_offset.to_le_bytes());
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(&max_output.to_le_bytes());
+    body.extend_from_slice(&IOCTL_IS_FSCTL.to_le_bytes());
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(input);
+    body
+}
+
+/// Returns the output of an IOCTL response.
+pub fn ioctl_response(response: &Response) -> Result<&[u8], SmbError> {
+    let offset = u32_at(&response.data, HEADER_LEN + 32)? as usize;
+    let length = u32_at(&response.data, HEADER_LEN + 36)? as usize;
+    if length == 0 {
+        return Ok(&[]);
+    }
+    bytes_at(&response.data, offset, length)
+}
+
+/// Result of the SMB1 negotiation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Smb1Negotiated {
+    pub security_mode: u8,
+    pub capabilities: u32,
+    pub max_buffer_size: u32,
+    /// Time of the server as FILETIME
+    pub system_time: u64,
+}
+
+pub const SMB1_SIGNING_ENABLED: u8 = 0x04;
+pub const SMB1_SIGNING_REQUIRED: u8 = 0x08;
+
+/// Returns a SMB_COM_NEGOTIATE request offering the NT LM 0.12 dialect only.
+pub fn smb1_negotiate() -> Vec<u8> {
+    let mut message = SMB1_PROTOCOL_ID.to_vec();
+    // command
+    message.push(0x72);
+    // status
+    message.extend_from_slice(&[0; 4]);
+    // flags: canonicalized paths, case insensitive
+    message.push(0x18);
+    // flags2: unicode, NT status codes, extended security, long names
+    message.extend_from_slice(&0xc801u16.to_le_bytes());
+    // pid high, security features, reserved, tid, pid, uid and mid
+    message.extend_from_slice(&[0; 20]);
+    // no parameter words
+    message.push(0);
+    let mut dialect = vec![0x02];
+    dialect.extend_from_slice(SMB1_DIALECT.as_bytes());
+    dialect.push(0);
+    message.extend_from_slice(&(dialect.len() as u16).to_le_bytes());
+    message.extend_from_slice(&dialect);
+    message
+}
+
+/// Parses the response to [smb1_negotiate], returns None when the server rejected the dialect.
+pub fn smb1_negotiate_response(data: &[u8]) -> Result<Option<Smb1Negotiated>, SmbError> {
+    if bytes_at(data, 0, 4)? != SMB1_PROTOCOL_ID || bytes_at(data, 4, 1)? != [0x72] {
+        return Err(SmbError::Protocol("no SMB1 negotiate response"));
+    }
+    match u32_at(data, 5)? {
+        STATUS_SUCCESS => {}
+        x => return Err(SmbError::Status(x)),
+    }
+    let words = 33;
+    let word_count = bytes_at(data, 32, 1)?[0];
+    let dialect_index = u16_at(data, words)?;
+    if dialect_index == 0xffff {
+        return Ok(None);
+    }
+    if word_count != 17 {
+        return Err(SmbError::Protocol("unsupported SMB1 dialect"));
+    }
+    Ok(Some(Smb1Negotiated {
+        security_mode: bytes_at(data, words + 2, 1)?[0],
+        max_buffer_size: u32_at(data, words + 7)?,
+        capabilities: u32_at(data, words + 19)?,
+        system_time: u64_at(data, words + 23)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let header = Header {
+            credit_charge: 1,
+            status: STATUS_MORE_PROCESSING_REQUIRED,
+            command: SESSION_SETUP,
+            credits: 32,
+            flags: FLAGS_SERVER_TO_REDIR,
+            message_id: 2,
+            tree_id: 5,
+            session_id: 0x1122,
+        };
+        let data = header.encode();
+        assert_eq!(data.len(), HEADER_LEN);
+        assert_eq!(Header::decode(&data).unwrap(), header);
+        assert!(Header::decode(&data[..40]).is_err());
+        assert!(Header::decode(b"\xffSMB").is_err());
+    }
+
+    #[test]
+    fn negotiate_request() {
+        let body = negotiate(&[1; 16], &[2; 32]);
+        assert_eq!(u16_at(&body, 2).unwrap(), DIALECTS.len() as u16);
+        let offset = u32_at(&body, 28).unwrap() as usize - HEADER_LEN;
+        assert_eq!((offset + HEADER_LEN) % 8, 0);
+        assert_eq!(
+            u16_at(&body, offset).unwrap(),
+            PREAUTH_INTEGRITY_CAPABILITIES
+        );
+        assert_eq!(&body[offset + 14..offset + 46], [2; 32]);
+        let mut message = vec![0; HEADER_LEN];
+        message.extend(&body);
+        let contexts = negotiate_contexts(&message, offset + HEADER_LEN, 2).unwrap();
+        assert_eq!(
+            contexts[1],
+            (ENCRYPTION_CAPABILITIES, &[2, 0, 2, 0, 1, 0][..])
+        );
+    }
+
+    #[test]
+    fn status() {
+        assert_eq!(
+            SmbError::Status(0xc000006d).to_string(),
+            "STATUS_LOGON_FAILURE (0xc000006d)"
+        );
+        assert_eq!(
+            SmbError::Status(0xc0001234).to_string(),
+            "status 0xc0001234"
+        );
+        assert_eq!(dialect_name(0x0311), "3.1.1");
+        assert_eq!(unix_time(133_000_000_000_000_000), 1_655_526_400);
+    }
+}
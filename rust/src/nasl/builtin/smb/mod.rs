@@ -0,0 +1,478 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to read and write files on Windows shares via SMB 2 and SMB 3, to
+//! detect the dialects, including SMB1, supported by a server and to call the SAMR, LSARPC and
+//! SVCCTL interfaces via DCE/RPC over the named pipes of the IPC$ share.
+
+pub(super) mod client;
+pub(super) mod message;
+pub(super) mod rpc;
+mod spnego;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use client::{Credentials, SmbClient};
+use indexmap::IndexMap;
+use message::{
+    dialect_name, unix_time, SmbError, SIGNING_ENABLED, SIGNING_REQUIRED, SMB1_DIALECT,
+    SMB1_SIGNING_ENABLED, SMB1_SIGNING_REQUIRED,
+};
+use rpc::{lsarpc, ndr::Sid, samr, svcctl};
+
+use crate::nasl::prelude::*;
+
+use super::{
+    network::{network_utils::resolve_host, socket::NaslSockets, tcp::TcpConnection, verify_port},
+    winrm::credential,
+};
+
+const SMB_PORT: u16 = 445;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct Handles {
+    clients: HashMap<usize, SmbClient>,
+    last_id: usize,
+}
+
+/// Holds the SMB connections opened by a script.
+#[derive(Default)]
+pub struct Smb {
+    handles: Mutex<Handles>,
+}
+
+fn connect(
+    context: &Context,
+    port: Option<i64>,
+    timeout: Duration,
+) -> Result<Result<TcpConnection, SmbError>, FunctionErrorKind> {
+    let port = port.map_or(Ok(SMB_PORT), verify_port)?;
+    let addrs = resolve_host(context, context.target())?;
+    Ok(NaslSockets::connect_tcp(context, &addrs, port, None, timeout, None).map_err(SmbError::Io))
+}
+
+/// Returns the value or NULL when the call failed, the error is only logged.
+fn or_null(result: Result<NaslValue, SmbError>, action: &str) -> NaslValue {
+    result.unwrap_or_else(|e| {
+        tracing::debug!(%e, "unable to {action}");
+        NaslValue::Null
+    })
+}
+
+fn sid_value(sid: Option<&Sid>) -> NaslValue {
+    sid.map_or(NaslValue::Null, |x| NaslValue::String(x.to_string()))
+}
+
+fn timeout(timeout: Option<i64>) -> Duration {
+    timeout
+        .filter(|x| *x > 0)
+        .map_or(DEFAULT_TIMEOUT, |x| Duration::from_secs(x as u64))
+}
+
+impl Smb {
+    fn with_client<T>(
+        &self,
+        handle: usize,
+        f: impl FnOnce(&mut SmbClient) -> T,
+    ) -> Result<T, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let client = handles.clients.get_mut(&handle).ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(format!("Unknown SMB handle {handle}"), None)
+        })?;
+        Ok(f(client))
+    }
+
+    /// Returns the version of the SMB implementation.
+    #[nasl_function]
+    fn smb_versioninfo(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    /// Negotiates the dialect with the SMB service of the target and returns what the server
+    /// announced, without authenticating.
+    ///
+    /// - port: Port of the service, 445 by default
+    /// - smb1: TRUE to offer the SMB1 dialect NT LM 0.12 only, e.g. to check whether SMB1 is
+    ///   enabled
+    /// - timeout: Time in seconds to wait for the response
+    ///
+    /// Returns NULL when the service is not reachable or refuses all offered dialects.
+    #[nasl_function(named(port, smb1, timeout))]
+    fn smb_negotiate(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        smb1: Option<bool>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let timeout = self::timeout(timeout);
+        let conn = connect(context, port, timeout)?;
+        let mut dict = IndexMap::new();
+        let mut insert = |key: &str, value: NaslValue| dict.insert(key.to_string(), value);
+        if smb1.unwrap_or_default() {
+            let negotiated = match conn.and_then(|mut x| client::negotiate_smb1(&mut x, timeout)) {
+                Ok(Some(x)) => x,
+                Ok(None) => return Ok(NaslValue::Null),
+                Err(e) => {
+                    tracing::debug!(%e, "unable to negotiate SMB1");
+                    return Ok(NaslValue::Null);
+                }
+            };
+            let mode = negotiated.security_mode;
+            insert("dialect", NaslValue::String(SMB1_DIALECT.to_string()));
+            insert(
+                "signing_enabled",
+                NaslValue::Boolean(mode & SMB1_SIGNING_ENABLED != 0),
+            );
+            insert(
+                "signing_required",
+                NaslValue::Boolean(mode & SMB1_SIGNING_REQUIRED != 0),
+            );
+            insert(
+                "capabilities",
+                NaslValue::Number(negotiated.capabilities as i64),
+            );
+            insert(
+                "max_buffer_size",
+                NaslValue::Number(negotiated.max_buffer_size as i64),
+            );
+            insert(
+                "system_time",
+                NaslValue::Number(unix_time(negotiated.system_time)),
+            );
+        } else {
+            let negotiated = match conn.and_then(|x| SmbClient::negotiate(x, timeout)) {
+                Ok(x) => x.negotiated,
+                Err(e) => {
+                    tracing::debug!(%e, "unable to negotiate SMB2");
+                    return Ok(NaslValue::Null);
+                }
+            };
+            let mode = negotiated.security_mode;
+            insert(
+                "dialect",
+                NaslValue::String(dialect_name(negotiated.dialect)),
+            );
+            insert(
+                "signing_enabled",
+                NaslValue::Boolean(mode & SIGNING_ENABLED != 0),
+            );
+            insert(
+                "signing_required",
+                NaslValue::Boolean(mode & SIGNING_REQUIRED != 0),
+            );
+            insert(
+                "capabilities",
+                NaslValue::Number(negotiated.capabilities as i64),
+            );
+            insert(
+                "max_read_size",
+                NaslValue::Number(negotiated.max_read_size as i64),
+            );
+            insert(
+                "max_write_size",
+                NaslValue::Number(negotiated.max_write_size as i64),
+            );
+            insert(
+                "system_time",
+                NaslValue::Number(unix_time(negotiated.system_time)),
+            );
+            insert(
+                "server_guid",
+                NaslValue::String(hex::encode(negotiated.server_guid)),
+            );
+        }
+        Ok(NaslValue::Dict(dict))
+    }
+
+    /// Authenticates with NTLM and connects to a share of the target, returns the handle of the
+    /// connection.
+    ///
+    /// The credentials default to the SMB credentials of the target (KB items SMB/login,
+    /// SMB/password and SMB/domain). The domain may also be given as part of the username,
+    /// e.g. `DOMAIN\user`, and an empty username authenticates anonymously.
+    ///
+    /// - share: Name of the share, e.g. `C$` or `IPC$`
+    /// - port: Port of the service, 445 by default
+    /// - timeout: Time in seconds to wait for each response, 30 by default
+    ///
+    /// Returns NULL when the connection, the authentication or the access to the share fails.
+    #[nasl_function(named(username, password, share, domain, port, timeout))]
+    #[allow(clippy::too_many_arguments)]
+    fn smb_connect(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        password: Option<&str>,
+        share: &str,
+        domain: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let mut credentials = Credentials {
+            username: credential(context, username, "SMB/login")?,
+            password: credential(context, password, "SMB/password")?,
+            domain: credential(context, domain, "SMB/domain")?,
+        };
+        if credentials.domain.is_empty() {
+            if let Some((domain, user)) = credentials.username.split_once(['\\', '/']) {
+                credentials = Credentials {
+                    domain: domain.to_string(),
+                    username: user.to_string(),
+                    password: credentials.password,
+                };
+            }
+        }
+        let timeout = self::timeout(timeout);
+        let client = connect(context, port, timeout)?.and_then(|conn| {
+            let mut client = SmbClient::negotiate(conn, timeout)?;
+            client.session_setup(&credentials)?;
+            client.tree_connect(context.target(), share)?;
+            Ok(client)
+        });
+        let client = match client {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, share, "unable to connect to the SMB share");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.clients.insert(id, client);
+        Ok(NaslValue::Number(id as i64))
+    }
+
+    /// Reads a file of the share, the path is relative to the share.
+    ///
+    /// - offset: Position to start reading at, 0 by default
+    /// - length: Number of bytes to read, up to the end of the file by default
+    ///
+    /// Returns NULL when the file can not be read.
+    #[nasl_function(named(smb_handle, filename, offset, length))]
+    fn smb_file_read(
+        &self,
+        smb_handle: usize,
+        filename: &str,
+        offset: Option<u64>,
+        length: Option<u64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let data = self.with_client(smb_handle, |client| {
+            client.read_file(filename, offset.unwrap_or_default(), length)
+        })?;
+        match data {
+            Ok(x) => Ok(NaslValue::Data(x)),
+            Err(e) => {
+                tracing::debug!(%e, filename, "unable to read the file via SMB");
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Writes data into a file of the share, the path is relative to the share.
+    ///
+    /// Without an offset the file is created or replaced, with an offset the data is written at
+    /// that position and the file is created when it does not exist.
+    ///
+    /// Returns the number of bytes written or NULL when the file can not be written.
+    #[nasl_function(named(smb_handle, filename, data, offset))]
+    fn smb_file_write(
+        &self,
+        smb_handle: usize,
+        filename: &str,
+        data: &NaslValue,
+        offset: Option<u64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let data: Vec<u8> = data.into();
+        let written = self.with_client(smb_handle, |client| {
+            client.write_file(filename, offset, &data)
+        })?;
+        match written {
+            Ok(x) => Ok(NaslValue::Number(x as i64)),
+            Err(e) => {
+                tracing::debug!(%e, filename, "unable to write the file via SMB");
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Enumerates the users of the account domain via SAMR, the connection has to be to the IPC$
+    /// share.
+    ///
+    /// Returns an array of arrays with the keys name, rid and sid or NULL when the users can not
+    /// be enumerated.
+    #[nasl_function(named(smb_handle))]
+    fn samr_enum_users(
+        &self,
+        context: &Context,
+        smb_handle: usize,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let server = format!("\\\\{}", context.target());
+        let result = self.with_client(smb_handle, |client| {
+            rpc::with_pipe(client, samr::PIPE, &samr::INTERFACE, |rpc| {
+                samr::users(rpc, &server)
+            })
+        })?;
+        Ok(or_null(
+            result.map(|(_, sid, users)| {
+                let users = users
+                    .into_iter()
+                    .map(|x| {
+                        NaslValue::Dict(IndexMap::from([
+                            ("name".to_string(), NaslValue::String(x.name)),
+                            ("rid".to_string(), NaslValue::Number(x.rid as i64)),
+                            ("sid".to_string(), sid_value(Some(&sid.with_rid(x.rid)))),
+                        ]))
+                    })
+                    .collect();
+                NaslValue::Array(users)
+            }),
+            "enumerate the users via SAMR",
+        ))
+    }
+
+    /// Queries the name and the SID of the account domain, or with primary set to TRUE of the
+    /// domain the host is a member of, via LSARPC. The connection has to be to the IPC$ share.
+    ///
+    /// Returns an array with the keys name and sid, the SID is NULL for hosts being a member of a
+    /// workgroup. Returns NULL when the domain can not be queried.
+    #[nasl_function(named(smb_handle, primary))]
+    fn lsa_query_domain(
+        &self,
+        context: &Context,
+        smb_handle: usize,
+        primary: Option<bool>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let server = format!("\\\\{}", context.target());
+        let result = self.with_client(smb_handle, |client| {
+            rpc::with_pipe(client, lsarpc::PIPE, &lsarpc::INTERFACE, |rpc| {
+                lsarpc::domain(rpc, &server, primary.unwrap_or_default())
+            })
+        })?;
+        Ok(or_null(
+            result.map(|(name, sid)| {
+                NaslValue::Dict(IndexMap::from([
+                    ("name".to_string(), NaslValue::String(name)),
+                    ("sid".to_string(), sid_value(sid.as_ref())),
+                ]))
+            }),
+            "query the domain via LSARPC",
+        ))
+    }
+
+    /// Queries the state and the configuration of a service via SVCCTL, the connection has to be
+    /// to the IPC$ share.
+    ///
+    /// - service: Name of the service, e.g. `RemoteRegistry`
+    ///
+    /// Returns an array with the keys state, e.g. RUNNING, start_type, e.g. AUTO_START,
+    /// service_type, controls_accepted, exit_code, service_exit_code, display_name, binary_path
+    /// and start_name. Returns NULL when the service does not exist or can not be queried.
+    #[nasl_function(named(smb_handle, service))]
+    fn svcctl_query_service(
+        &self,
+        context: &Context,
+        smb_handle: usize,
+        service: &str,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let server = format!("\\\\{}", context.target());
+        let result = self.with_client(smb_handle, |client| {
+            rpc::with_pipe(client, svcctl::PIPE, &svcctl::INTERFACE, |rpc| {
+                svcctl::query_service(rpc, &server, service)
+            })
+        })?;
+        let name = |name: Option<&str>, value: u32| {
+            name.map_or(NaslValue::Number(value as i64), |x| {
+                NaslValue::String(x.to_string())
+            })
+        };
+        Ok(or_null(
+            result.map(|(status, config)| {
+                NaslValue::Dict(IndexMap::from([
+                    (
+                        "state".to_string(),
+                        name(
+                            svcctl::state_name(status.current_state),
+                            status.current_state,
+                        ),
+                    ),
+                    (
+                        "start_type".to_string(),
+                        name(
+                            svcctl::start_type_name(config.start_type),
+                            config.start_type,
+                        ),
+                    ),
+                    (
+                        "service_type".to_string(),
+                        NaslValue::Number(status.service_type as i64),
+                    ),
+                    (
+                        "controls_accepted".to_string(),
+                        NaslValue::Number(status.controls_accepted as i64),
+                    ),
+                    (
+                        "exit_code".to_string(),
+                        NaslValue::Number(status.win32_exit_code as i64),
+                    ),
+                    (
+                        "service_exit_code".to_string(),
+                        NaslValue::Number(status.service_specific_exit_code as i64),
+                    ),
+                    (
+                        "display_name".to_string(),
+                        NaslValue::String(config.display_name),
+                    ),
+                    (
+                        "binary_path".to_string(),
+                        NaslValue::String(config.binary_path),
+                    ),
+                    (
+                        "start_name".to_string(),
+                        NaslValue::String(config.start_name),
+                    ),
+                ]))
+            }),
+            "query the service via SVCCTL",
+        ))
+    }
+
+    /// Disconnects from the share and closes the connection.
+    #[nasl_function(named(smb_handle))]
+    fn smb_close(&self, smb_handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let mut client = self
+            .handles
+            .lock()
+            .unwrap()
+            .clients
+            .remove(&smb_handle)
+            .ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(format!("Unknown SMB handle {smb_handle}"), None)
+            })?;
+        if let Err(e) = client.logoff() {
+            tracing::debug!(%e, "unable to log off from the SMB service");
+        }
+        Ok(NaslValue::Boolean(true))
+    }
+}
+
+function_set! {
+    Smb,
+    sync_stateful,
+    (
+        (Smb::smb_versioninfo, "smb_versioninfo"),
+        (Smb::smb_negotiate, "smb_negotiate"),
+        (Smb::smb_connect, "smb_connect"),
+        (Smb::smb_file_read, "smb_file_read"),
+        (Smb::smb_file_write, "smb_file_write"),
+        (Smb::smb_close, "smb_close"),
+        (Smb::samr_enum_users, "samr_enum_users"),
+        (Smb::lsa_query_domain, "lsa_query_domain"),
+        (Smb::svcctl_query_service, "svcctl_query_service"),
+    )
+}
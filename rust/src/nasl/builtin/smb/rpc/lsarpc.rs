@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Local Security Authority (Domain Policy) Remote Protocol (MS-LSAD), used to query the
+//! domains of a host.
+
+use super::{
+    ndr::{Handle, Reader, Sid, Writer},
+    RpcClient, SyntaxId,
+};
+use crate::nasl::builtin::smb::message::SmbError;
+
+pub const PIPE: &str = "lsarpc";
+/// 12345778-1234-abcd-ef00-0123456789ab version 0.0
+pub const INTERFACE: SyntaxId = SyntaxId {
+    uuid: [
+        0x78, 0x57, 0x34, 0x12, 0x34, 0x12, 0xcd, 0xab, 0xef, 0x00, 0x01, 0x23, 0x45, 0x67, 0x89,
+        0xab,
+    ],
+    version: 0,
+};
+
+pub const CLOSE: u16 = 0;
+pub const QUERY_INFORMATION_POLICY: u16 = 7;
+pub const OPEN_POLICY2: u16 = 44;
+
+/// Domain the host is a member of, the name of the workgroup without SID for standalone hosts
+pub const POLICY_PRIMARY_DOMAIN_INFORMATION: u16 = 3;
+/// Domain of the local accounts of the host
+pub const POLICY_ACCOUNT_DOMAIN_INFORMATION: u16 = 5;
+
+const POLICY_VIEW_LOCAL_INFORMATION: u32 = 0x0000_0001;
+/// Length of the LSAPR_OBJECT_ATTRIBUTES
+const OBJECT_ATTRIBUTES_LEN: u32 = 24;
+
+pub fn open_policy(rpc: &mut RpcClient, server: &str) -> Result<Handle, SmbError> {
+    let mut stub = Writer::new();
+    stub.unique_string(Some(server))
+        .u32(OBJECT_ATTRIBUTES_LEN)
+        // root directory, object name, attributes, security descriptor and quality of service
+        .pointer(false)
+        .pointer(false)
+        .u32(0)
+        .pointer(false)
+        .pointer(false)
+        .u32(POLICY_VIEW_LOCAL_INFORMATION);
+    let response = rpc.call(OPEN_POLICY2, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    let handle = reader.handle()?;
+    reader.status()?;
+    Ok(handle)
+}
+
+pub fn close(rpc: &mut RpcClient, handle: &Handle) -> Result<(), SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(handle);
+    let response = rpc.call(CLOSE, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    reader.handle()?;
+    reader.status().map(|_| ())
+}
+
+/// Returns the name and the SID of the domain of the information class.
+pub fn query_domain(
+    rpc: &mut RpcClient,
+    policy: &Handle,
+    class: u16,
+) -> Result<(String, Option<Sid>), SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(policy).u16(class);
+    let response = rpc.call(QUERY_INFORMATION_POLICY, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    if !reader.pointer()? {
+        reader.status()?;
+        return Err(SmbError::Protocol("no policy information returned"));
+    }
+    if reader.u16()? != class {
+        return Err(SmbError::Protocol(
+            "policy information of another class returned",
+        ));
+    }
+    // the union arm is aligned like its largest member
+    reader.align(4);
+    let name = reader.unicode_string()?;
+    let sid = reader.pointer()?;
+    let name = if name {
+        reader.string()?
+    } else {
+        String::new()
+    };
+    let sid = if sid { Some(reader.sid()?) } else { None };
+    reader.status()?;
+    Ok((name, sid))
+}
+
+/// Returns the name and the SID of the primary or of the account domain of the server.
+pub fn domain(
+    rpc: &mut RpcClient,
+    server: &str,
+    primary: bool,
+) -> Result<(String, Option<Sid>), SmbError> {
+    let class = if primary {
+        POLICY_PRIMARY_DOMAIN_INFORMATION
+    } else {
+        POLICY_ACCOUNT_DOMAIN_INFORMATION
+    };
+    let policy = open_policy(rpc, server)?;
+    let domain = query_domain(rpc, &policy, class)?;
+    close(rpc, &policy)?;
+    Ok(domain)
+}
@@ -0,0 +1,581 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Connection oriented DCE/RPC (C706 chapter 12, MS-RPCE) over SMB named pipes and TCP.
+//!
+//! The calls over named pipes are not authenticated on the RPC level, the pipes are protected by
+//! the SMB session they are opened with. Over TCP the client authenticates with NTLM and the
+//! PDUs are signed or sealed.
+
+pub mod lsarpc;
+pub mod ndr;
+pub mod samr;
+pub mod svcctl;
+#[cfg(test)]
+pub(crate) mod tests;
+
+use std::{io::Write, time::Duration};
+
+use super::{
+    client::{random, Credentials, SmbClient},
+    message::{
+        bytes_at, u16_at, u32_at, Create, File, SmbError, FILE_GENERIC_READ, FILE_GENERIC_WRITE,
+        FILE_OPEN,
+    },
+};
+use crate::nasl::builtin::{network::tcp::TcpConnection, winrm::ntlm};
+use ndr::Uuid;
+
+/// Largest fragment sent and received
+const MAX_FRAGMENT: u16 = 4280;
+const HEADER_LEN: usize = 16;
+const REQUEST_HEADER_LEN: usize = 24;
+/// Length of the security trailer preceding the credentials of the authentication verifier
+const TRAILER_LEN: usize = 8;
+const SIGNATURE_LEN: usize = 16;
+/// The stub data of authenticated PDUs is padded to a multiple of this length
+const STUB_ALIGNMENT: usize = 16;
+
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 2;
+const FAULT: u8 = 3;
+const BIND: u8 = 11;
+const BIND_ACK: u8 = 12;
+const BIND_NAK: u8 = 13;
+const AUTH3: u8 = 16;
+
+const FIRST_FRAGMENT: u8 = 0x01;
+const LAST_FRAGMENT: u8 = 0x02;
+/// Set in BIND PDUs to sign the headers of the PDUs as well, which NTLM always does
+const SUPPORT_HEADER_SIGN: u8 = 0x04;
+/// Set in REQUEST PDUs followed by the UUID of an object
+const OBJECT_UUID: u8 = 0x80;
+
+/// RPC_C_AUTHN_WINNT, NTLM
+const AUTH_TYPE_NTLM: u8 = 10;
+
+/// Little endian integers, ASCII characters and IEEE floating point numbers
+const DATA_REPRESENTATION: [u8; 4] = [0x10, 0, 0, 0];
+
+/// Transfer syntax NDR 2.0, 8a885d04-1ceb-11c9-9fe8-08002b104860
+const NDR: SyntaxId = SyntaxId {
+    uuid: [
+        0x04, 0x5d, 0x88, 0x8a, 0xeb, 0x1c, 0xc9, 0x11, 0x9f, 0xe8, 0x08, 0x00, 0x2b, 0x10, 0x48,
+        0x60,
+    ],
+    version: 2,
+};
+
+/// Identifies an interface by its UUID, in the little endian encoding of the wire, and its
+/// major and minor version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxId {
+    pub uuid: [u8; 16],
+    pub version: u32,
+}
+
+impl SyntaxId {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = self.uuid.to_vec();
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data
+    }
+}
+
+/// Level of the authentication of the PDUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthLevel {
+    /// The PDUs are signed
+    Integrity = 5,
+    /// The PDUs are signed and their stub data is encrypted
+    Privacy = 6,
+}
+
+/// Returns a header of a PDU without the fragment length, which is set by [finish].
+fn header(ptype: u8, call_id: u32) -> Vec<u8> {
+    let mut pdu = vec![5, 0, ptype, FIRST_FRAGMENT | LAST_FRAGMENT];
+    pdu.extend_from_slice(&DATA_REPRESENTATION);
+    // fragment and authentication length
+    pdu.extend_from_slice(&[0; 4]);
+    pdu.extend_from_slice(&call_id.to_le_bytes());
+    pdu
+}
+
+fn finish(mut pdu: Vec<u8>) -> Vec<u8> {
+    let length = pdu.len() as u16;
+    pdu[8..10].copy_from_slice(&length.to_le_bytes());
+    pdu
+}
+
+/// Appends the authentication verifier with the credentials, e.g. a NTLM message, to the PDU
+/// and sets the fragment and the authentication length.
+fn authenticate(mut pdu: Vec<u8>, level: AuthLevel, pad: usize, credentials: &[u8]) -> Vec<u8> {
+    pdu.extend_from_slice(&[AUTH_TYPE_NTLM, level as u8, pad as u8, 0]);
+    // authentication context
+    pdu.extend_from_slice(&0u32.to_le_bytes());
+    pdu.extend_from_slice(credentials);
+    pdu[10..12].copy_from_slice(&(credentials.len() as u16).to_le_bytes());
+    finish(pdu)
+}
+
+/// Returns the BIND PDU for the interfaces, each one is offered in a presentation context with
+/// its index as identifier.
+pub fn bind(call_id: u32, interfaces: &[SyntaxId]) -> Vec<u8> {
+    let mut pdu = header(BIND, call_id);
+    pdu.extend_from_slice(&MAX_FRAGMENT.to_le_bytes());
+    pdu.extend_from_slice(&MAX_FRAGMENT.to_le_bytes());
+    // association group
+    pdu.extend_from_slice(&[0; 4]);
+    pdu.extend_from_slice(&[interfaces.len() as u8, 0, 0, 0]);
+    for (i, interface) in interfaces.iter().enumerate() {
+        // one transfer syntax
+        pdu.extend_from_slice(&(i as u16).to_le_bytes());
+        pdu.extend_from_slice(&[1, 0]);
+        pdu.extend(interface.encode());
+        pdu.extend(NDR.encode());
+    }
+    finish(pdu)
+}
+
+/// Returns the REQUEST PDUs of a call, with at most the given length of stub data each.
+///
+/// The operation is called on the interface of the presentation context, for DCOM the
+/// interface of an object identified by its UUID.
+pub fn request(
+    call_id: u32,
+    context: u16,
+    object: Option<&Uuid>,
+    opnum: u16,
+    stub: &[u8],
+    chunk: usize,
+) -> Vec<Vec<u8>> {
+    let count = stub.len().div_ceil(chunk).max(1);
+    (0..count)
+        .map(|i| {
+            let mut pdu = header(REQUEST, call_id);
+            pdu[3] = match (i == 0, i == count - 1) {
+                (true, true) => FIRST_FRAGMENT | LAST_FRAGMENT,
+                (true, false) => FIRST_FRAGMENT,
+                (false, true) => LAST_FRAGMENT,
+                (false, false) => 0,
+            };
+            // allocation hint
+            pdu.extend_from_slice(&(stub.len() as u32).to_le_bytes());
+            pdu.extend_from_slice(&context.to_le_bytes());
+            pdu.extend_from_slice(&opnum.to_le_bytes());
+            if let Some(object) = object {
+                pdu[3] |= OBJECT_UUID;
+                pdu.extend_from_slice(object);
+            }
+            pdu.extend_from_slice(
+                &stub[(i * chunk).min(stub.len())..((i + 1) * chunk).min(stub.len())],
+            );
+            finish(pdu)
+        })
+        .collect()
+}
+
+/// A received PDU
+#[derive(Debug)]
+pub struct Pdu {
+    pub ptype: u8,
+    pub flags: u8,
+    pub call_id: u32,
+    /// The PDU without the authentication verifier
+    pub data: Vec<u8>,
+    /// The security trailer followed by the credentials, empty without authentication
+    pub verifier: Vec<u8>,
+}
+
+impl Pdu {
+    fn decode(mut data: Vec<u8>) -> Result<Self, SmbError> {
+        let auth_length = u16_at(&data, 10)? as usize;
+        if data[0] != 5 || data[4] & 0xf0 != 0x10 {
+            return Err(SmbError::Protocol(
+                "unsupported DCE/RPC version or data representation",
+            ));
+        }
+        let end = match auth_length {
+            0 => data.len(),
+            // the verifier starts with an 8 byte trailer
+            x => data
+                .len()
+                .checked_sub(x + TRAILER_LEN)
+                .filter(|x| *x >= HEADER_LEN)
+                .ok_or(SmbError::Protocol("invalid DCE/RPC authentication length"))?,
+        };
+        let verifier = data.split_off(end);
+        Ok(Self {
+            ptype: data[2],
+            flags: data[3],
+            call_id: u32_at(&data, 12)?,
+            data,
+            verifier,
+        })
+    }
+
+    /// Returns the stub data of a RESPONSE PDU or the error of a FAULT PDU.
+    fn stub(&self) -> Result<&[u8], SmbError> {
+        match self.ptype {
+            RESPONSE => Ok(self.data.get(REQUEST_HEADER_LEN..).unwrap_or_default()),
+            FAULT => Err(SmbError::Rpc(u32_at(&self.data, REQUEST_HEADER_LEN)?)),
+            _ => Err(SmbError::Protocol("unexpected DCE/RPC PDU")),
+        }
+    }
+}
+
+/// Checks that the BIND_ACK PDU accepts the given number of presentation contexts and returns
+/// the largest fragment accepted by the server.
+fn bind_ack(pdu: &Pdu, contexts: usize) -> Result<u16, SmbError> {
+    match pdu.ptype {
+        BIND_ACK => {}
+        BIND_NAK => return Err(SmbError::Rpc(u16_at(&pdu.data, HEADER_LEN)? as u32)),
+        FAULT => return Err(SmbError::Rpc(u32_at(&pdu.data, REQUEST_HEADER_LEN)?)),
+        _ => return Err(SmbError::Protocol("unexpected DCE/RPC PDU")),
+    }
+    let max_receive = u16_at(&pdu.data, HEADER_LEN + 2)?;
+    // the secondary address is followed by the result list, aligned to 4 octets
+    let address_length = u16_at(&pdu.data, HEADER_LEN + 8)? as usize;
+    let results = (HEADER_LEN + 10 + address_length).next_multiple_of(4);
+    if (bytes_at(&pdu.data, results, 1)?[0] as usize) < contexts {
+        return Err(SmbError::Protocol(
+            "missing DCE/RPC presentation context result",
+        ));
+    }
+    for i in 0..contexts {
+        // result and reason followed by the transfer syntax
+        let result = results + 4 + i * 24;
+        if u16_at(&pdu.data, result)? != 0 {
+            return Err(SmbError::Rpc(u16_at(&pdu.data, result + 2)? as u32));
+        }
+    }
+    Ok(max_receive)
+}
+
+/// Connection the PDUs are sent over
+pub trait Transport {
+    fn write(&mut self, data: &[u8]) -> Result<(), SmbError>;
+
+    /// Returns the next part of the received data.
+    fn read(&mut self) -> Result<Vec<u8>, SmbError>;
+
+    /// Writes the data and returns the first part of the answer.
+    fn transact(&mut self, data: &[u8]) -> Result<Vec<u8>, SmbError> {
+        self.write(data)?;
+        self.read()
+    }
+}
+
+/// An opened named pipe of the IPC$ share
+pub struct Pipe<'a> {
+    client: &'a mut SmbClient,
+    file: &'a File,
+}
+
+impl Transport for Pipe<'_> {
+    fn write(&mut self, data: &[u8]) -> Result<(), SmbError> {
+        let mut written = 0;
+        while written < data.len() {
+            match self.client.write(self.file, 0, &data[written..])? {
+                0 => return Err(SmbError::Protocol("unable to write into the pipe")),
+                n => written += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<Vec<u8>, SmbError> {
+        match self.client.read(self.file, 0, MAX_FRAGMENT as u32)? {
+            x if x.is_empty() => Err(SmbError::Protocol("the pipe was closed")),
+            x => Ok(x),
+        }
+    }
+
+    fn transact(&mut self, data: &[u8]) -> Result<Vec<u8>, SmbError> {
+        self.client.transceive(self.file, data, MAX_FRAGMENT as u32)
+    }
+}
+
+/// Connection of the ncacn_ip_tcp protocol sequence
+pub struct Tcp {
+    conn: TcpConnection,
+    timeout: Duration,
+}
+
+impl Tcp {
+    pub fn new(conn: TcpConnection, timeout: Duration) -> Self {
+        Self { conn, timeout }
+    }
+}
+
+impl Transport for Tcp {
+    fn write(&mut self, data: &[u8]) -> Result<(), SmbError> {
+        self.conn.write_all(data)?;
+        Ok(self.conn.flush()?)
+    }
+
+    fn read(&mut self) -> Result<Vec<u8>, SmbError> {
+        let mut data = vec![0; MAX_FRAGMENT as usize];
+        match self.conn.read_with_timeout(&mut data, self.timeout)? {
+            0 => Err(SmbError::Protocol("the connection was closed")),
+            n => {
+                data.truncate(n);
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// NTLM session of an authenticated client
+struct Security {
+    level: AuthLevel,
+    session: ntlm::Session,
+}
+
+/// Returns the length of the header of a REQUEST or RESPONSE PDU, including the object UUID.
+fn body_offset(pdu: &[u8]) -> usize {
+    match pdu[3] & OBJECT_UUID {
+        0 => REQUEST_HEADER_LEN,
+        _ => REQUEST_HEADER_LEN + 16,
+    }
+}
+
+impl Security {
+    /// Adds the authentication verifier to a PDU to send, the stub data is sealed with privacy.
+    fn protect(&mut self, mut pdu: Vec<u8>) -> Vec<u8> {
+        let body = body_offset(&pdu);
+        let pad = (pdu.len() - body).next_multiple_of(STUB_ALIGNMENT) - (pdu.len() - body);
+        pdu.resize(pdu.len() + pad, 0);
+        let mut pdu = authenticate(pdu, self.level, pad, &[0; SIGNATURE_LEN]);
+        let signed = pdu.len() - SIGNATURE_LEN;
+        let message = pdu[..signed].to_vec();
+        if self.level == AuthLevel::Privacy {
+            self.session.seal(&mut pdu[body..signed - TRAILER_LEN]);
+        }
+        let signature = self.session.sign(&message);
+        pdu[signed..].copy_from_slice(&signature);
+        pdu
+    }
+
+    /// Verifies the signature of a received PDU, decrypts the stub data with privacy and removes
+    /// the padding.
+    fn verify(&mut self, pdu: &mut Pdu) -> Result<(), SmbError> {
+        let body = body_offset(&pdu.data);
+        if pdu.verifier.len() != TRAILER_LEN + SIGNATURE_LEN
+            || pdu.verifier[..2] != [AUTH_TYPE_NTLM, self.level as u8]
+            || pdu.data.len() < body
+        {
+            return Err(SmbError::Protocol("unexpected DCE/RPC authentication"));
+        }
+        if self.level == AuthLevel::Privacy {
+            self.session.unseal(&mut pdu.data[body..]);
+        }
+        let (trailer, signature) = pdu.verifier.split_at(TRAILER_LEN);
+        if !self
+            .session
+            .verify(&[&pdu.data[..], trailer].concat(), signature)
+        {
+            return Err(SmbError::Protocol("invalid DCE/RPC signature"));
+        }
+        let length = pdu
+            .data
+            .len()
+            .checked_sub(trailer[2] as usize)
+            .filter(|x| *x >= body)
+            .ok_or(SmbError::Protocol("invalid DCE/RPC padding"))?;
+        pdu.data.truncate(length);
+        Ok(())
+    }
+}
+
+/// DCE/RPC client bound to one or more interfaces
+pub struct RpcClient<'a> {
+    transport: Box<dyn Transport + Send + 'a>,
+    interfaces: Vec<SyntaxId>,
+    security: Option<Security>,
+    call_id: u32,
+    max_fragment: usize,
+    /// Received data not yet returned as PDU
+    buffer: Vec<u8>,
+}
+
+impl<'a> RpcClient<'a> {
+    fn new(transport: impl Transport + Send + 'a, interfaces: &[SyntaxId]) -> Self {
+        Self {
+            transport: Box::new(transport),
+            interfaces: interfaces.to_vec(),
+            security: None,
+            call_id: 1,
+            max_fragment: MAX_FRAGMENT as usize,
+            buffer: vec![],
+        }
+    }
+
+    fn bind_ack(&mut self) -> Result<Pdu, SmbError> {
+        let pdu = self.receive()?;
+        let max_receive = bind_ack(&pdu, self.interfaces.len())? as usize;
+        self.max_fragment = max_receive.clamp(
+            REQUEST_HEADER_LEN + 16 + TRAILER_LEN + SIGNATURE_LEN + STUB_ALIGNMENT,
+            MAX_FRAGMENT as usize,
+        );
+        Ok(pdu)
+    }
+
+    /// Binds to the interfaces without authentication.
+    pub fn bind(
+        transport: impl Transport + Send + 'a,
+        interfaces: &[SyntaxId],
+    ) -> Result<Self, SmbError> {
+        let mut client = Self::new(transport, interfaces);
+        client.buffer = client
+            .transport
+            .transact(&bind(client.call_id, interfaces))?;
+        client.bind_ack()?;
+        Ok(client)
+    }
+
+    /// Binds to the interfaces authenticating with NTLM, the following PDUs are signed or
+    /// sealed depending on the level.
+    pub fn bind_authenticated(
+        transport: impl Transport + Send + 'a,
+        interfaces: &[SyntaxId],
+        credentials: &Credentials,
+        level: AuthLevel,
+    ) -> Result<Self, SmbError> {
+        let mut client = Self::new(transport, interfaces);
+        let mut pdu = bind(client.call_id, interfaces);
+        pdu[3] |= SUPPORT_HEADER_SIGN;
+        let pdu = authenticate(pdu, level, 0, &ntlm::negotiate_session_security());
+        client.buffer = client.transport.transact(&pdu)?;
+        let ack = client.bind_ack()?;
+        let challenge = ack
+            .verifier
+            .get(TRAILER_LEN..)
+            .and_then(ntlm::Challenge::parse)
+            .ok_or(SmbError::Protocol("no NTLM challenge in the BIND_ACK"))?;
+        let (message, key) = ntlm::authenticate(
+            &challenge,
+            &credentials.username,
+            &credentials.password,
+            &credentials.domain,
+            &random()?,
+        );
+        let session = ntlm::Session::new(&challenge, &key)
+            .ok_or(SmbError::Protocol("NTLM session security not negotiated"))?;
+        // AUTH3 has no answer, its body is a padding of 4 octets
+        let mut pdu = header(AUTH3, client.call_id);
+        pdu.extend_from_slice(&[0; 4]);
+        client
+            .transport
+            .write(&authenticate(pdu, level, 0, &message))?;
+        client.security = Some(Security { level, session });
+        Ok(client)
+    }
+
+    /// Returns the next PDU, reading until it is complete.
+    fn receive(&mut self) -> Result<Pdu, SmbError> {
+        loop {
+            if self.buffer.len() >= HEADER_LEN {
+                let length = u16_at(&self.buffer, 8)? as usize;
+                if length < HEADER_LEN {
+                    return Err(SmbError::Protocol("invalid DCE/RPC fragment length"));
+                }
+                if self.buffer.len() >= length {
+                    let rest = self.buffer.split_off(length);
+                    let data = std::mem::replace(&mut self.buffer, rest);
+                    let mut pdu = Pdu::decode(data)?;
+                    if let (Some(security), RESPONSE) = (&mut self.security, pdu.ptype) {
+                        security.verify(&mut pdu)?;
+                    }
+                    return Ok(pdu);
+                }
+            }
+            let data = self.transport.read()?;
+            self.buffer.extend(data);
+        }
+    }
+
+    fn invoke(
+        &mut self,
+        context: u16,
+        object: Option<&Uuid>,
+        opnum: u16,
+        stub: &[u8],
+    ) -> Result<Vec<u8>, SmbError> {
+        self.call_id += 1;
+        let header_len = match object {
+            Some(_) => REQUEST_HEADER_LEN + 16,
+            None => REQUEST_HEADER_LEN,
+        };
+        let chunk = match self.security {
+            Some(_) => {
+                (self.max_fragment - header_len - TRAILER_LEN - SIGNATURE_LEN) / STUB_ALIGNMENT
+                    * STUB_ALIGNMENT
+            }
+            None => self.max_fragment - header_len,
+        };
+        let mut fragments = request(self.call_id, context, object, opnum, stub, chunk);
+        if let Some(security) = &mut self.security {
+            fragments = fragments.into_iter().map(|x| security.protect(x)).collect();
+        }
+        let last = fragments.pop().unwrap_or_default();
+        for fragment in fragments {
+            self.transport.write(&fragment)?;
+        }
+        let data = self.transport.transact(&last)?;
+        self.buffer.extend(data);
+        let mut response = vec![];
+        loop {
+            let pdu = self.receive()?;
+            if pdu.call_id != self.call_id {
+                return Err(SmbError::Protocol("DCE/RPC response to another call"));
+            }
+            response.extend_from_slice(pdu.stub()?);
+            if pdu.flags & LAST_FRAGMENT != 0 {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// Calls the operation of the first interface and returns the stub data of the response.
+    pub fn call(&mut self, opnum: u16, stub: &[u8]) -> Result<Vec<u8>, SmbError> {
+        self.invoke(0, None, opnum, stub)
+    }
+
+    /// Calls the operation of an interface implemented by the object, e.g. a DCOM interface
+    /// identified by its IPID, and returns the stub data of the response.
+    pub fn call_object(
+        &mut self,
+        interface: &SyntaxId,
+        object: &Uuid,
+        opnum: u16,
+        stub: &[u8],
+    ) -> Result<Vec<u8>, SmbError> {
+        let context = self
+            .interfaces
+            .iter()
+            .position(|x| x == interface)
+            .ok_or(SmbError::Protocol("DCE/RPC interface not bound"))?;
+        self.invoke(context as u16, Some(object), opnum, stub)
+    }
+}
+
+/// Opens the named pipe, e.g. `samr`, of the IPC$ share, binds to the interface and runs the
+/// function with the bound client.
+pub fn with_pipe<T>(
+    client: &mut SmbClient,
+    name: &str,
+    interface: &SyntaxId,
+    f: impl FnOnce(&mut RpcClient<'_>) -> Result<T, SmbError>,
+) -> Result<T, SmbError> {
+    let create = Create {
+        desired_access: FILE_GENERIC_READ | FILE_GENERIC_WRITE,
+        disposition: FILE_OPEN,
+        options: 0,
+    };
+    client.with_file(name, create, |client, file| {
+        let mut rpc = RpcClient::bind(Pipe { client, file }, &[*interface])?;
+        f(&mut rpc)
+    })
+}
@@ -0,0 +1,320 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding and decoding of the Network Data Representation (NDR 2.0, C706 chapter 14) of the
+//! parameters of remote procedure calls, in little endian byte order.
+//!
+//! Primitives are aligned to their size. Pointers are unique pointers, their referents follow
+//! the structure containing them.
+
+use std::fmt::Display;
+
+use crate::nasl::builtin::smb::message::{utf16le, SmbError};
+
+/// Context handle returned by the server, e.g. of an opened policy
+pub type Handle = [u8; 20];
+
+/// UUID or GUID in the little endian encoding of the wire
+pub type Uuid = [u8; 16];
+
+/// Security identifier, e.g. S-1-5-21-1004336348-1177238915-682003330
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sid {
+    pub revision: u8,
+    pub authority: u64,
+    pub sub_authorities: Vec<u32>,
+}
+
+impl Sid {
+    /// Returns the SID of an account of the domain with the relative identifier.
+    pub fn with_rid(&self, rid: u32) -> Self {
+        let mut sid = self.clone();
+        sid.sub_authorities.push(rid);
+        sid
+    }
+}
+
+impl Display for Sid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "S-{}-{}", self.revision, self.authority)?;
+        for x in &self.sub_authorities {
+            write!(f, "-{x}")?;
+        }
+        Ok(())
+    }
+}
+
+fn truncated() -> SmbError {
+    SmbError::Protocol("truncated NDR data")
+}
+
+#[derive(Debug, Default)]
+pub struct Writer {
+    data: Vec<u8>,
+    referent: u32,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn align(&mut self, alignment: usize) {
+        let len = self.data.len().next_multiple_of(alignment);
+        self.data.resize(len, 0);
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.data.push(value);
+        self
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.align(2);
+        self.data.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.align(4);
+        self.data.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn handle(&mut self, handle: &Handle) -> &mut Self {
+        self.align(4);
+        self.data.extend_from_slice(handle);
+        self
+    }
+
+    pub fn uuid(&mut self, uuid: &Uuid) -> &mut Self {
+        self.align(4);
+        self.data.extend_from_slice(uuid);
+        self
+    }
+
+    /// Writes the bytes without alignment, e.g. the elements of a byte array.
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    /// Writes the referent id of a unique pointer or 0 for a null pointer.
+    pub fn pointer(&mut self, present: bool) -> &mut Self {
+        if present {
+            self.referent += 4;
+            self.u32(0x0002_0000 + self.referent)
+        } else {
+            self.u32(0)
+        }
+    }
+
+    /// Writes a conformant and varying array of UTF-16 characters.
+    pub fn characters(&mut self, characters: &[u8]) -> &mut Self {
+        let count = (characters.len() / 2) as u32;
+        self.u32(count).u32(0).u32(count);
+        self.data.extend_from_slice(characters);
+        self
+    }
+
+    /// Writes a null terminated string, i.e. a `[string] wchar_t*`.
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        let mut characters = utf16le(value);
+        characters.extend_from_slice(&[0, 0]);
+        self.characters(&characters)
+    }
+
+    /// Writes a unique pointer to a null terminated string.
+    pub fn unique_string(&mut self, value: Option<&str>) -> &mut Self {
+        self.pointer(value.is_some());
+        if let Some(value) = value {
+            self.string(value);
+        }
+        self
+    }
+
+    /// Writes a RPC_UNICODE_STRING followed by its characters, as done for parameters.
+    pub fn unicode_string(&mut self, value: &str) -> &mut Self {
+        let characters = utf16le(value);
+        let len = characters.len() as u16;
+        self.u16(len).u16(len).pointer(true);
+        self.characters(&characters)
+    }
+
+    /// Writes a RPC_SID with its conformance.
+    pub fn sid(&mut self, sid: &Sid) -> &mut Self {
+        self.u32(sid.sub_authorities.len() as u32)
+            .u8(sid.revision)
+            .u8(sid.sub_authorities.len() as u8);
+        self.data
+            .extend_from_slice(&sid.authority.to_be_bytes()[2..]);
+        for x in &sid.sub_authorities {
+            self.u32(*x);
+        }
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn align(&mut self, alignment: usize) {
+        self.position = self.position.next_multiple_of(alignment);
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Result<&'a [u8], SmbError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SmbError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, SmbError> {
+        self.align(2);
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SmbError> {
+        self.align(4);
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SmbError> {
+        self.align(8);
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn handle(&mut self) -> Result<Handle, SmbError> {
+        self.align(4);
+        Ok(self.bytes(20)?.try_into().unwrap())
+    }
+
+    pub fn uuid(&mut self) -> Result<Uuid, SmbError> {
+        self.align(4);
+        Ok(self.bytes(16)?.try_into().unwrap())
+    }
+
+    /// Reads a unique pointer, returns whether its referent follows.
+    pub fn pointer(&mut self) -> Result<bool, SmbError> {
+        Ok(self.u32()? != 0)
+    }
+
+    /// Reads a conformant and varying array of UTF-16 characters, a terminating null character
+    /// is removed.
+    pub fn string(&mut self) -> Result<String, SmbError> {
+        let _max_count = self.u32()?;
+        let _offset = self.u32()?;
+        let count = self.u32()? as usize;
+        let units: Vec<u16> = self
+            .bytes(count * 2)?
+            .chunks_exact(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+            .collect();
+        let units = units.strip_suffix(&[0]).unwrap_or(&units);
+        Ok(String::from_utf16_lossy(units))
+    }
+
+    /// Reads the fixed part of a RPC_UNICODE_STRING, returns whether the characters follow.
+    pub fn unicode_string(&mut self) -> Result<bool, SmbError> {
+        let _length = self.u16()?;
+        let _maximum_length = self.u16()?;
+        self.pointer()
+    }
+
+    /// Reads a RPC_SID with its conformance.
+    pub fn sid(&mut self) -> Result<Sid, SmbError> {
+        let _count = self.u32()?;
+        let revision = self.u8()?;
+        let count = self.u8()?;
+        let mut authority = [0; 8];
+        authority[2..].copy_from_slice(self.bytes(6)?);
+        let sub_authorities = (0..count).map(|_| self.u32()).collect::<Result<_, _>>()?;
+        Ok(Sid {
+            revision,
+            authority: u64::from_be_bytes(authority),
+            sub_authorities,
+        })
+    }
+
+    /// Reads the NTSTATUS returned by a procedure, warnings and informational codes like
+    /// STATUS_MORE_ENTRIES are returned as well.
+    pub fn status(&mut self) -> Result<u32, SmbError> {
+        match self.u32()? {
+            x if x >> 30 == 3 => Err(SmbError::Status(x)),
+            x => Ok(x),
+        }
+    }
+
+    /// Reads the Win32 error code returned by a procedure.
+    pub fn win32_error(&mut self) -> Result<(), SmbError> {
+        match self.u32()? {
+            0 => Ok(()),
+            x => Err(SmbError::Win32(x)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode() {
+        let sid = Sid {
+            revision: 1,
+            authority: 5,
+            sub_authorities: vec![21, 1004336348, 1177238915, 682003330],
+        };
+        assert_eq!(sid.to_string(), "S-1-5-21-1004336348-1177238915-682003330");
+        assert_eq!(
+            sid.with_rid(500).to_string(),
+            "S-1-5-21-1004336348-1177238915-682003330-500"
+        );
+        let mut writer = Writer::new();
+        writer
+            .u8(1)
+            .unique_string(Some("ab"))
+            .unique_string(None)
+            .unicode_string("c")
+            .sid(&sid)
+            .u32(0xc000_0022);
+        let data = writer.finish();
+        assert_eq!(
+            &data[..28],
+            b"\x01\0\0\0\x04\0\x02\0\x03\0\0\0\0\0\0\0\x03\0\0\0a\0b\0\0\0\0\0"
+        );
+
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.u8().unwrap(), 1);
+        assert!(reader.pointer().unwrap());
+        assert_eq!(reader.string().unwrap(), "ab");
+        assert!(!reader.pointer().unwrap());
+        assert!(reader.unicode_string().unwrap());
+        assert_eq!(reader.string().unwrap(), "c");
+        assert_eq!(reader.sid().unwrap(), sid);
+        assert!(matches!(
+            reader.status(),
+            Err(SmbError::Status(0xc000_0022))
+        ));
+        assert!(reader.u8().is_err());
+    }
+}
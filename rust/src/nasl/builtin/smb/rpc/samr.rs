@@ -0,0 +1,157 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Security Account Manager Remote Protocol (MS-SAMR), used to enumerate the local users.
+
+use super::{
+    ndr::{Handle, Reader, Sid, Writer},
+    RpcClient, SyntaxId,
+};
+use crate::nasl::builtin::smb::message::SmbError;
+
+pub const PIPE: &str = "samr";
+/// 12345778-1234-abcd-ef00-0123456789ac version 1.0
+pub const INTERFACE: SyntaxId = SyntaxId {
+    uuid: [
+        0x78, 0x57, 0x34, 0x12, 0x34, 0x12, 0xcd, 0xab, 0xef, 0x00, 0x01, 0x23, 0x45, 0x67, 0x89,
+        0xac,
+    ],
+    version: 1,
+};
+
+pub const CLOSE_HANDLE: u16 = 1;
+pub const LOOKUP_DOMAIN: u16 = 5;
+pub const ENUMERATE_DOMAINS: u16 = 6;
+pub const OPEN_DOMAIN: u16 = 7;
+pub const ENUMERATE_USERS: u16 = 13;
+pub const CONNECT2: u16 = 57;
+
+const MAXIMUM_ALLOWED: u32 = 0x0200_0000;
+pub const STATUS_MORE_ENTRIES: u32 = 0x0000_0105;
+const PREFERRED_MAXIMUM_LENGTH: u32 = 0x0001_0000;
+/// Name of the domain containing the builtin groups, the other one is the account domain
+const BUILTIN_DOMAIN: &str = "Builtin";
+
+/// An entry of an enumeration, i.e. a domain or a user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub rid: u32,
+    pub name: String,
+}
+
+pub fn connect(rpc: &mut RpcClient, server: &str) -> Result<Handle, SmbError> {
+    let mut stub = Writer::new();
+    stub.unique_string(Some(server)).u32(MAXIMUM_ALLOWED);
+    let response = rpc.call(CONNECT2, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    let handle = reader.handle()?;
+    reader.status()?;
+    Ok(handle)
+}
+
+pub fn close(rpc: &mut RpcClient, handle: &Handle) -> Result<(), SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(handle);
+    let response = rpc.call(CLOSE_HANDLE, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    reader.handle()?;
+    reader.status().map(|_| ())
+}
+
+/// Calls one of the enumeration operations until all entries are returned.
+fn enumerate(
+    rpc: &mut RpcClient,
+    opnum: u16,
+    handle: &Handle,
+    user_account_control: Option<u32>,
+) -> Result<Vec<Account>, SmbError> {
+    let mut accounts = vec![];
+    let mut context = 0;
+    loop {
+        let mut stub = Writer::new();
+        stub.handle(handle).u32(context);
+        if let Some(x) = user_account_control {
+            stub.u32(x);
+        }
+        stub.u32(PREFERRED_MAXIMUM_LENGTH);
+        let response = rpc.call(opnum, &stub.finish())?;
+        let mut reader = Reader::new(&response);
+        context = reader.u32()?;
+        let mut entries = vec![];
+        if reader.pointer()? {
+            let _entries_read = reader.u32()?;
+            if reader.pointer()? {
+                let count = reader.u32()?;
+                for _ in 0..count {
+                    let rid = reader.u32()?;
+                    entries.push((rid, reader.unicode_string()?));
+                }
+            }
+        }
+        let returned = entries.len();
+        for (rid, name) in entries {
+            let name = if name {
+                reader.string()?
+            } else {
+                String::new()
+            };
+            accounts.push(Account { rid, name });
+        }
+        let _count_returned = reader.u32()?;
+        if reader.status()? != STATUS_MORE_ENTRIES || returned == 0 {
+            return Ok(accounts);
+        }
+    }
+}
+
+pub fn enumerate_domains(rpc: &mut RpcClient, server: &Handle) -> Result<Vec<String>, SmbError> {
+    let domains = enumerate(rpc, ENUMERATE_DOMAINS, server, None)?;
+    Ok(domains.into_iter().map(|x| x.name).collect())
+}
+
+pub fn lookup_domain(rpc: &mut RpcClient, server: &Handle, name: &str) -> Result<Sid, SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(server).unicode_string(name);
+    let response = rpc.call(LOOKUP_DOMAIN, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    if !reader.pointer()? {
+        return Err(SmbError::Protocol("no domain SID returned"));
+    }
+    let sid = reader.sid()?;
+    reader.status()?;
+    Ok(sid)
+}
+
+pub fn open_domain(rpc: &mut RpcClient, server: &Handle, sid: &Sid) -> Result<Handle, SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(server).u32(MAXIMUM_ALLOWED).sid(sid);
+    let response = rpc.call(OPEN_DOMAIN, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    let handle = reader.handle()?;
+    reader.status()?;
+    Ok(handle)
+}
+
+/// Returns all users of the domain, including disabled ones.
+pub fn enumerate_users(rpc: &mut RpcClient, domain: &Handle) -> Result<Vec<Account>, SmbError> {
+    enumerate(rpc, ENUMERATE_USERS, domain, Some(0))
+}
+
+/// Returns the name and the SID of the account domain of the server and its users.
+pub fn users(
+    rpc: &mut RpcClient,
+    server_name: &str,
+) -> Result<(String, Sid, Vec<Account>), SmbError> {
+    let server = connect(rpc, server_name)?;
+    let name = enumerate_domains(rpc, &server)?
+        .into_iter()
+        .find(|x| !x.eq_ignore_ascii_case(BUILTIN_DOMAIN))
+        .ok_or(SmbError::Protocol("no account domain found"))?;
+    let sid = lookup_domain(rpc, &server, &name)?;
+    let domain = open_domain(rpc, &server, &sid)?;
+    let users = enumerate_users(rpc, &domain)?;
+    close(rpc, &domain)?;
+    close(rpc, &server)?;
+    Ok((name, sid, users))
+}
@@ -0,0 +1,174 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Service Control Manager Remote Protocol (MS-SCMR), used to query the state and the
+//! configuration of services.
+
+use super::{
+    ndr::{Handle, Reader, Writer},
+    RpcClient, SyntaxId,
+};
+use crate::nasl::builtin::smb::message::SmbError;
+
+pub const PIPE: &str = "svcctl";
+/// 367abb81-9844-35f1-ad32-98f038001003 version 2.0
+pub const INTERFACE: SyntaxId = SyntaxId {
+    uuid: [
+        0x81, 0xbb, 0x7a, 0x36, 0x44, 0x98, 0xf1, 0x35, 0xad, 0x32, 0x98, 0xf0, 0x38, 0x00, 0x10,
+        0x03,
+    ],
+    version: 2,
+};
+
+pub const CLOSE_SERVICE_HANDLE: u16 = 0;
+pub const QUERY_SERVICE_STATUS: u16 = 6;
+pub const OPEN_SC_MANAGER: u16 = 15;
+pub const OPEN_SERVICE: u16 = 16;
+pub const QUERY_SERVICE_CONFIG: u16 = 17;
+
+const SC_MANAGER_CONNECT: u32 = 0x0000_0001;
+const SERVICE_QUERY_CONFIG: u32 = 0x0000_0001;
+const SERVICE_QUERY_STATUS: u32 = 0x0000_0004;
+/// Largest buffer for the configuration allowed by the protocol
+const MAX_CONFIG_LEN: u32 = 8 * 1024;
+
+/// SERVICE_STATUS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub service_type: u32,
+    pub current_state: u32,
+    pub controls_accepted: u32,
+    pub win32_exit_code: u32,
+    pub service_specific_exit_code: u32,
+}
+
+/// QUERY_SERVICE_CONFIGW without the load order group, the tag and the dependencies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub start_type: u32,
+    pub binary_path: String,
+    pub start_name: String,
+    pub display_name: String,
+}
+
+/// Returns the name of the SERVICE_STATUS current state, e.g. RUNNING.
+pub fn state_name(state: u32) -> Option<&'static str> {
+    Some(match state {
+        1 => "STOPPED",
+        2 => "START_PENDING",
+        3 => "STOP_PENDING",
+        4 => "RUNNING",
+        5 => "CONTINUE_PENDING",
+        6 => "PAUSE_PENDING",
+        7 => "PAUSED",
+        _ => return None,
+    })
+}
+
+/// Returns the name of the start type, e.g. AUTO_START.
+pub fn start_type_name(start_type: u32) -> Option<&'static str> {
+    Some(match start_type {
+        0 => "BOOT_START",
+        1 => "SYSTEM_START",
+        2 => "AUTO_START",
+        3 => "DEMAND_START",
+        4 => "DISABLED",
+        _ => return None,
+    })
+}
+
+fn handle_response(response: &[u8]) -> Result<Handle, SmbError> {
+    let mut reader = Reader::new(response);
+    let handle = reader.handle()?;
+    reader.win32_error()?;
+    Ok(handle)
+}
+
+pub fn open_sc_manager(rpc: &mut RpcClient, server: &str) -> Result<Handle, SmbError> {
+    let mut stub = Writer::new();
+    stub.unique_string(Some(server))
+        .unique_string(None)
+        .u32(SC_MANAGER_CONNECT);
+    handle_response(&rpc.call(OPEN_SC_MANAGER, &stub.finish())?)
+}
+
+pub fn open_service(rpc: &mut RpcClient, manager: &Handle, name: &str) -> Result<Handle, SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(manager)
+        .string(name)
+        .u32(SERVICE_QUERY_CONFIG | SERVICE_QUERY_STATUS);
+    handle_response(&rpc.call(OPEN_SERVICE, &stub.finish())?)
+}
+
+pub fn close(rpc: &mut RpcClient, handle: &Handle) -> Result<(), SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(handle);
+    handle_response(&rpc.call(CLOSE_SERVICE_HANDLE, &stub.finish())?).map(|_| ())
+}
+
+pub fn query_status(rpc: &mut RpcClient, service: &Handle) -> Result<Status, SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(service);
+    let response = rpc.call(QUERY_SERVICE_STATUS, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    let status = Status {
+        service_type: reader.u32()?,
+        current_state: reader.u32()?,
+        controls_accepted: reader.u32()?,
+        win32_exit_code: reader.u32()?,
+        service_specific_exit_code: reader.u32()?,
+    };
+    // check point and wait hint
+    reader.u32()?;
+    reader.u32()?;
+    reader.win32_error()?;
+    Ok(status)
+}
+
+pub fn query_config(rpc: &mut RpcClient, service: &Handle) -> Result<Config, SmbError> {
+    let mut stub = Writer::new();
+    stub.handle(service).u32(MAX_CONFIG_LEN);
+    let response = rpc.call(QUERY_SERVICE_CONFIG, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    let _service_type = reader.u32()?;
+    let start_type = reader.u32()?;
+    let _error_control = reader.u32()?;
+    let binary_path = reader.pointer()?;
+    let load_order_group = reader.pointer()?;
+    let _tag_id = reader.u32()?;
+    let dependencies = reader.pointer()?;
+    let start_name = reader.pointer()?;
+    let display_name = reader.pointer()?;
+    let mut string = |present: bool| match present {
+        true => reader.string(),
+        false => Ok(String::new()),
+    };
+    let binary_path = string(binary_path)?;
+    string(load_order_group)?;
+    string(dependencies)?;
+    let config = Config {
+        start_type,
+        binary_path,
+        start_name: string(start_name)?,
+        display_name: string(display_name)?,
+    };
+    let _bytes_needed = reader.u32()?;
+    reader.win32_error()?;
+    Ok(config)
+}
+
+/// Returns the status and the configuration of the service.
+pub fn query_service(
+    rpc: &mut RpcClient,
+    server: &str,
+    name: &str,
+) -> Result<(Status, Config), SmbError> {
+    let manager = open_sc_manager(rpc, server)?;
+    let service = open_service(rpc, &manager, name)?;
+    let status = query_status(rpc, &service)?;
+    let config = query_config(rpc, &service)?;
+    close(rpc, &service)?;
+    close(rpc, &manager)?;
+    Ok((status, config))
+}
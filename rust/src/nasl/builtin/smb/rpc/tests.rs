@@ -0,0 +1,443 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use super::ndr::{Handle, Reader, Sid, Uuid, Writer};
+use super::{
+    authenticate, bind_ack, body_offset, finish, header, lsarpc, request, samr, svcctl, AuthLevel,
+    Pdu, Security, SyntaxId, AUTH3, AUTH_TYPE_NTLM, BIND, BIND_ACK, FAULT, FIRST_FRAGMENT,
+    LAST_FRAGMENT, NDR, REQUEST, RESPONSE, TRAILER_LEN,
+};
+use crate::nasl::builtin::smb::message::{utf16le, SmbError, DIALECT_3_1_1};
+use crate::nasl::builtin::smb::tests::smb_server;
+use crate::nasl::builtin::winrm::ntlm::tests::{
+    challenge_message, server_session, server_session_key, verify,
+};
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+const SERVER: Handle = [1; 20];
+const DOMAIN: Handle = [2; 20];
+const POLICY: Handle = [3; 20];
+const MANAGER: Handle = [4; 20];
+const CLOSED: Handle = [0; 20];
+
+/// Largest stub data of a response fragment, so that the responses are fragmented
+const STUB_CHUNK: usize = 64;
+const NCA_S_OP_RNG_ERROR: u32 = 0x1c01_0002;
+const NCA_S_FAULT_ACCESS_DENIED: u32 = 0x0000_0005;
+const STATUS_NO_SUCH_DOMAIN: u32 = 0xc000_00df;
+const ERROR_SERVICE_DOES_NOT_EXIST: u32 = 1060;
+
+/// Name, state, start type, display name and binary path of the services
+const SERVICES: [(&str, u32, u32, &str, &str); 2] = [
+    (
+        "RemoteRegistry",
+        1,
+        4,
+        "Remote Registry",
+        "C:\\Windows\\system32\\svchost.exe -k localService",
+    ),
+    (
+        "wuauserv",
+        4,
+        2,
+        "Windows Update",
+        "C:\\Windows\\system32\\svchost.exe -k netsvcs",
+    ),
+];
+
+fn domain_sid() -> Sid {
+    Sid {
+        revision: 1,
+        authority: 5,
+        sub_authorities: vec![21, 1, 2, 3],
+    }
+}
+
+fn interface(pipe: &str) -> SyntaxId {
+    match pipe {
+        samr::PIPE => samr::INTERFACE,
+        lsarpc::PIPE => lsarpc::INTERFACE,
+        svcctl::PIPE => svcctl::INTERFACE,
+        x => panic!("unexpected pipe {x}"),
+    }
+}
+
+/// Returns a BIND_ACK PDU with the secondary address, e.g. the pipe, and the results of the
+/// presentation contexts.
+fn bind_ack_pdu(call_id: u32, address: &str, accepted: &[bool]) -> Vec<u8> {
+    let mut pdu = header(BIND_ACK, call_id);
+    pdu.extend_from_slice(&4280u16.to_le_bytes());
+    pdu.extend_from_slice(&4280u16.to_le_bytes());
+    pdu.extend_from_slice(&0x1234u32.to_le_bytes());
+    let address = format!("{address}\0");
+    pdu.extend_from_slice(&(address.len() as u16).to_le_bytes());
+    pdu.extend_from_slice(address.as_bytes());
+    pdu.resize(pdu.len().next_multiple_of(4), 0);
+    pdu.extend_from_slice(&[accepted.len() as u8, 0, 0, 0]);
+    for accepted in accepted {
+        // acceptance or provider rejection because of the abstract syntax
+        let (result, reason) = if *accepted { (0u16, 0u16) } else { (2, 1) };
+        pdu.extend_from_slice(&result.to_le_bytes());
+        pdu.extend_from_slice(&reason.to_le_bytes());
+        pdu.extend(NDR.encode());
+    }
+    finish(pdu)
+}
+
+fn response_pdus(call_id: u32, stub: &[u8]) -> Vec<Vec<u8>> {
+    let chunks: Vec<&[u8]> = stub.chunks(STUB_CHUNK).collect();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut pdu = header(RESPONSE, call_id);
+            pdu[3] = 0;
+            if i == 0 {
+                pdu[3] |= FIRST_FRAGMENT;
+            }
+            if i == chunks.len() - 1 {
+                pdu[3] |= LAST_FRAGMENT;
+            }
+            pdu.extend_from_slice(&(stub.len() as u32).to_le_bytes());
+            // presentation context, cancel count and reserved
+            pdu.extend_from_slice(&[0; 4]);
+            pdu.extend_from_slice(chunk);
+            finish(pdu)
+        })
+        .collect()
+}
+
+fn fault_pdu(call_id: u32, status: u32) -> Vec<u8> {
+    let mut pdu = header(FAULT, call_id);
+    pdu.extend_from_slice(&[0; 8]);
+    pdu.extend_from_slice(&status.to_le_bytes());
+    pdu.extend_from_slice(&[0; 4]);
+    finish(pdu)
+}
+
+/// Writes a SAMPR_ENUMERATION_BUFFER with the following count and status.
+fn enumeration(stub: &mut Writer, context: u32, entries: &[(u32, &str)], status: u32) {
+    let count = entries.len() as u32;
+    stub.u32(context)
+        .pointer(true)
+        .u32(count)
+        .pointer(true)
+        .u32(count);
+    for (rid, name) in entries {
+        let len = name.len() as u16 * 2;
+        stub.u32(*rid).u16(len).u16(len).pointer(true);
+    }
+    for (_, name) in entries {
+        stub.characters(&utf16le(name));
+    }
+    stub.u32(count).u32(status);
+}
+
+/// Returns the stub data of the response to the operation or the status of a fault.
+fn call(pipe: &str, opnum: u16, stub: &[u8]) -> Result<Vec<u8>, u32> {
+    let mut request = Reader::new(stub);
+    let mut response = Writer::new();
+    match (pipe, opnum) {
+        (samr::PIPE, samr::CONNECT2) => {
+            assert!(request.pointer().unwrap());
+            assert_eq!(request.string().unwrap(), "\\\\127.0.0.1");
+            response.handle(&SERVER).u32(0);
+        }
+        (samr::PIPE, samr::ENUMERATE_DOMAINS) => {
+            assert_eq!(request.handle().unwrap(), SERVER);
+            enumeration(&mut response, 0, &[(0, "Builtin"), (1, "WIN-SERVER")], 0);
+        }
+        (samr::PIPE, samr::LOOKUP_DOMAIN) => {
+            assert_eq!(request.handle().unwrap(), SERVER);
+            assert!(request.unicode_string().unwrap());
+            if request.string().unwrap() == "WIN-SERVER" {
+                response.pointer(true).sid(&domain_sid()).u32(0);
+            } else {
+                response.pointer(false).u32(STATUS_NO_SUCH_DOMAIN);
+            }
+        }
+        (samr::PIPE, samr::OPEN_DOMAIN) => {
+            assert_eq!(request.handle().unwrap(), SERVER);
+            request.u32().unwrap();
+            assert_eq!(request.sid().unwrap(), domain_sid());
+            response.handle(&DOMAIN).u32(0);
+        }
+        (samr::PIPE, samr::ENUMERATE_USERS) => {
+            assert_eq!(request.handle().unwrap(), DOMAIN);
+            let users = [(500, "Administrator"), (501, "Guest"), (1001, "scanner")];
+            match request.u32().unwrap() {
+                0 => enumeration(&mut response, 2, &users[..2], samr::STATUS_MORE_ENTRIES),
+                2 => enumeration(&mut response, 3, &users[2..], 0),
+                x => panic!("unexpected enumeration context {x}"),
+            }
+        }
+        (samr::PIPE, samr::CLOSE_HANDLE) | (lsarpc::PIPE, lsarpc::CLOSE) => {
+            assert!([SERVER, DOMAIN, POLICY].contains(&request.handle().unwrap()));
+            response.handle(&CLOSED).u32(0);
+        }
+        (lsarpc::PIPE, lsarpc::OPEN_POLICY2) => {
+            assert!(request.pointer().unwrap());
+            assert_eq!(request.string().unwrap(), "\\\\127.0.0.1");
+            assert_eq!(request.u32().unwrap(), 24);
+            response.handle(&POLICY).u32(0);
+        }
+        (lsarpc::PIPE, lsarpc::QUERY_INFORMATION_POLICY) => {
+            assert_eq!(request.handle().unwrap(), POLICY);
+            let class = request.u16().unwrap();
+            let (name, sid) = match class {
+                lsarpc::POLICY_ACCOUNT_DOMAIN_INFORMATION => ("WIN-SERVER", Some(domain_sid())),
+                lsarpc::POLICY_PRIMARY_DOMAIN_INFORMATION => ("WORKGROUP", None),
+                x => panic!("unexpected information class {x}"),
+            };
+            let len = name.len() as u16 * 2;
+            response.pointer(true).u16(class).align(4);
+            response
+                .u16(len)
+                .u16(len)
+                .pointer(true)
+                .pointer(sid.is_some())
+                .characters(&utf16le(name));
+            if let Some(sid) = sid {
+                response.sid(&sid);
+            }
+            response.u32(0);
+        }
+        (svcctl::PIPE, svcctl::OPEN_SC_MANAGER) => {
+            assert!(request.pointer().unwrap());
+            assert_eq!(request.string().unwrap(), "\\\\127.0.0.1");
+            assert!(!request.pointer().unwrap());
+            response.handle(&MANAGER).u32(0);
+        }
+        (svcctl::PIPE, svcctl::OPEN_SERVICE) => {
+            assert_eq!(request.handle().unwrap(), MANAGER);
+            let name = request.string().unwrap();
+            match SERVICES.iter().position(|x| x.0 == name) {
+                Some(i) => response.handle(&[10 + i as u8; 20]).u32(0),
+                None => response.handle(&CLOSED).u32(ERROR_SERVICE_DOES_NOT_EXIST),
+            };
+        }
+        (svcctl::PIPE, svcctl::QUERY_SERVICE_STATUS) => {
+            let (_, state, ..) = SERVICES[request.handle().unwrap()[0] as usize - 10];
+            // own process, accepting stop if running
+            response
+                .u32(0x10)
+                .u32(state)
+                .u32(if state == 4 { 1 } else { 0 })
+                .u32(if state == 1 { 1077 } else { 0 })
+                .u32(0)
+                .u32(0)
+                .u32(0)
+                .u32(0);
+        }
+        (svcctl::PIPE, svcctl::QUERY_SERVICE_CONFIG) => {
+            let (_, _, start_type, display_name, path) =
+                SERVICES[request.handle().unwrap()[0] as usize - 10];
+            assert_eq!(request.u32().unwrap(), 8192);
+            response
+                .u32(0x10)
+                .u32(start_type)
+                .u32(1)
+                .pointer(true)
+                .pointer(true)
+                .u32(0)
+                .pointer(true)
+                .pointer(true)
+                .pointer(true)
+                .string(path)
+                .string("")
+                .string("RpcSs")
+                .string("NT AUTHORITY\\LocalService")
+                .string(display_name)
+                .u32(512)
+                .u32(0);
+        }
+        (svcctl::PIPE, svcctl::CLOSE_SERVICE_HANDLE) => {
+            request.handle().unwrap();
+            response.handle(&CLOSED).u32(0);
+        }
+        _ => return Err(NCA_S_OP_RNG_ERROR),
+    }
+    Ok(response.finish())
+}
+
+/// Returns the PDUs answering the PDU sent into the pipe.
+pub fn answer(pipe: &str, pdu: &[u8]) -> Vec<Vec<u8>> {
+    let call_id = u32::from_le_bytes(pdu[12..16].try_into().unwrap());
+    match pdu[2] {
+        BIND => {
+            let accepted = pdu[32..52] == interface(pipe).encode();
+            vec![bind_ack_pdu(
+                call_id,
+                &format!("\\PIPE\\{pipe}"),
+                &[accepted],
+            )]
+        }
+        REQUEST => {
+            assert_eq!(pdu[3], FIRST_FRAGMENT | LAST_FRAGMENT);
+            let opnum = u16::from_le_bytes([pdu[22], pdu[23]]);
+            match call(pipe, opnum, &pdu[24..]) {
+                Ok(stub) => response_pdus(call_id, &stub),
+                Err(status) => vec![fault_pdu(call_id, status)],
+            }
+        }
+        x => panic!("unexpected PDU type {x}"),
+    }
+}
+
+/// Server side of an association over TCP authenticated with NTLM, the user "scanner" has the
+/// password "secret"
+#[derive(Default)]
+pub struct AuthenticatedServer {
+    level: Option<AuthLevel>,
+    security: Option<Security>,
+}
+
+impl AuthenticatedServer {
+    /// Returns the PDUs answering the PDU, the calls are answered by the function from the
+    /// presentation context, the object, the operation and the stub data of the request.
+    pub fn answer(
+        &mut self,
+        pdu: Vec<u8>,
+        call: impl FnOnce(u16, Option<Uuid>, u16, &[u8]) -> Result<Vec<u8>, u32>,
+    ) -> Vec<Vec<u8>> {
+        let mut pdu = Pdu::decode(pdu).unwrap();
+        let call_id = pdu.call_id;
+        match pdu.ptype {
+            BIND => {
+                assert_eq!(pdu.verifier[0], AUTH_TYPE_NTLM);
+                assert_eq!(&pdu.verifier[TRAILER_LEN..TRAILER_LEN + 8], b"NTLMSSP\0");
+                let level = match pdu.verifier[1] {
+                    5 => AuthLevel::Integrity,
+                    6 => AuthLevel::Privacy,
+                    x => panic!("unexpected authentication level {x}"),
+                };
+                self.level = Some(level);
+                let contexts = vec![true; pdu.data[24] as usize];
+                let ack = bind_ack_pdu(call_id, "135", &contexts);
+                vec![authenticate(ack, level, 0, &challenge_message())]
+            }
+            AUTH3 => {
+                let message = &pdu.verifier[TRAILER_LEN..];
+                let valid = verify(message, &challenge_message(), "secret")
+                    .is_some_and(|(user, valid)| user == "scanner" && valid);
+                if valid {
+                    let key = server_session_key(message, "secret").unwrap();
+                    self.security = Some(Security {
+                        level: self.level.unwrap(),
+                        session: server_session(message, &key),
+                    });
+                }
+                vec![]
+            }
+            REQUEST => {
+                let Some(security) = &mut self.security else {
+                    return vec![fault_pdu(call_id, NCA_S_FAULT_ACCESS_DENIED)];
+                };
+                security.verify(&mut pdu).unwrap();
+                let fragment = FIRST_FRAGMENT | LAST_FRAGMENT;
+                assert_eq!(pdu.flags & fragment, fragment);
+                let context = u16::from_le_bytes([pdu.data[20], pdu.data[21]]);
+                let opnum = u16::from_le_bytes([pdu.data[22], pdu.data[23]]);
+                let body = body_offset(&pdu.data);
+                let object = (body > 24).then(|| pdu.data[24..40].try_into().unwrap());
+                match call(context, object, opnum, &pdu.data[body..]) {
+                    Ok(stub) => response_pdus(call_id, &stub)
+                        .into_iter()
+                        .map(|x| security.protect(x))
+                        .collect(),
+                    Err(status) => vec![fault_pdu(call_id, status)],
+                }
+            }
+            x => panic!("unexpected PDU type {x}"),
+        }
+    }
+}
+
+#[test]
+fn pdus() {
+    let fragments = request(3, 0, None, 7, &[0xaa; 100], 40);
+    assert_eq!(fragments.len(), 3);
+    assert_eq!(
+        fragments.iter().map(|x| x[3]).collect::<Vec<_>>(),
+        [FIRST_FRAGMENT, 0, LAST_FRAGMENT]
+    );
+    assert!(fragments.iter().all(|x| x.len() <= 64));
+    assert_eq!(fragments[2].len(), 24 + 20);
+    assert_eq!(&fragments[0][8..10], &64u16.to_le_bytes());
+    assert_eq!(request(3, 0, None, 7, &[], 40).len(), 1);
+    let object = [0xbb; 16];
+    let fragment = &request(3, 1, Some(&object), 7, &[0xaa; 4], 40)[0];
+    assert_eq!(fragment[3], FIRST_FRAGMENT | LAST_FRAGMENT | 0x80);
+    assert_eq!(&fragment[20..24], &[1, 0, 7, 0]);
+    assert_eq!(&fragment[24..40], &object);
+
+    let ack = |accepted| Pdu::decode(bind_ack_pdu(1, "\\PIPE\\samr", &[accepted])).unwrap();
+    assert_eq!(bind_ack(&ack(true), 1).unwrap(), 4280);
+    assert!(matches!(bind_ack(&ack(false), 1), Err(SmbError::Rpc(1))));
+    assert!(matches!(
+        bind_ack(&ack(true), 2),
+        Err(SmbError::Protocol(_))
+    ));
+    let fault = Pdu::decode(fault_pdu(2, NCA_S_OP_RNG_ERROR)).unwrap();
+    assert!(matches!(
+        fault.stub(),
+        Err(SmbError::Rpc(NCA_S_OP_RNG_ERROR))
+    ));
+}
+
+#[test]
+fn rpc_over_named_pipes() {
+    let port = smb_server(DIALECT_3_1_1, false);
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(
+            r#"h = smb_connect(port: {port}, username: "scanner", password: "secret", share: "IPC$");"#
+        ),
+        1,
+    );
+    t.run("u = samr_enum_users(smb_handle: h);");
+    t.ok("max_index(u);", 3);
+    t.ok(r#"u[0]["name"];"#, "Administrator");
+    t.ok(r#"u[1]["rid"];"#, 501);
+    t.ok(r#"u[2]["name"];"#, "scanner");
+    t.ok(r#"u[2]["sid"];"#, "S-1-5-21-1-2-3-1001");
+
+    t.run("d = lsa_query_domain(smb_handle: h);");
+    t.ok(r#"d["name"];"#, "WIN-SERVER");
+    t.ok(r#"d["sid"];"#, "S-1-5-21-1-2-3");
+    t.run("d = lsa_query_domain(smb_handle: h, primary: TRUE);");
+    t.ok(r#"d["name"];"#, "WORKGROUP");
+    t.ok(r#"d["sid"];"#, NaslValue::Null);
+
+    t.run(r#"s = svcctl_query_service(smb_handle: h, service: "RemoteRegistry");"#);
+    t.ok(r#"s["state"];"#, "STOPPED");
+    t.ok(r#"s["start_type"];"#, "DISABLED");
+    t.ok(r#"s["exit_code"];"#, 1077);
+    t.ok(r#"s["display_name"];"#, "Remote Registry");
+    t.ok(
+        r#"s["binary_path"];"#,
+        "C:\\Windows\\system32\\svchost.exe -k localService",
+    );
+    t.ok(r#"s["start_name"];"#, "NT AUTHORITY\\LocalService");
+    t.run(r#"s = svcctl_query_service(smb_handle: h, service: "wuauserv");"#);
+    t.ok(r#"s["state"];"#, "RUNNING");
+    t.ok(r#"s["start_type"];"#, "AUTO_START");
+    t.ok(r#"s["controls_accepted"];"#, 1);
+    t.ok(
+        r#"svcctl_query_service(smb_handle: h, service: "missing");"#,
+        NaslValue::Null,
+    );
+    t.ok("smb_close(smb_handle: h);", true);
+
+    // the pipes are only available on the IPC$ share
+    t.ok(
+        format!(
+            r#"h = smb_connect(port: {port}, username: "scanner", password: "secret", share: "C$");"#
+        ),
+        2,
+    );
+    t.ok("samr_enum_users(smb_handle: h);", NaslValue::Null);
+}
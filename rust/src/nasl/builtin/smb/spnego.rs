@@ -0,0 +1,59 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Wraps NTLM messages into the SPNEGO tokens (RFC 4178) expected by SMB servers.
+
+use crate::nasl::builtin::asn1::der::{self, tlv, Class, Element};
+
+/// 1.3.6.1.5.5.2
+const SPNEGO: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+/// 1.3.6.1.4.1.311.2.2.10
+const NTLMSSP: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a];
+const NTLM_SIGNATURE: &[u8] = b"NTLMSSP\0";
+
+/// Returns the initial token offering NTLM with its first message.
+pub fn init(token: &[u8]) -> Vec<u8> {
+    let mech_types = tlv(0xa0, &tlv(0x30, &tlv(0x06, NTLMSSP)));
+    let mech_token = tlv(0xa2, &tlv(0x04, token));
+    let neg_token_init = tlv(0xa0, &tlv(0x30, &[mech_types, mech_token].concat()));
+    tlv(0x60, &[tlv(0x06, SPNEGO), neg_token_init].concat())
+}
+
+/// Returns the token continuing the negotiation with the next NTLM message.
+pub fn response(token: &[u8]) -> Vec<u8> {
+    tlv(0xa1, &tlv(0x30, &tlv(0xa2, &tlv(0x04, token))))
+}
+
+fn find_ntlm<'a>(elements: &[Element<'a>]) -> Option<&'a [u8]> {
+    elements.iter().find_map(|x| {
+        if x.class == Class::Universal && x.tag == 4 && x.content.starts_with(NTLM_SIGNATURE) {
+            Some(x.content)
+        } else {
+            find_ntlm(&x.children)
+        }
+    })
+}
+
+/// Returns the NTLM message within a token, servers may also send it without SPNEGO.
+pub fn ntlm_message(token: &[u8]) -> Option<&[u8]> {
+    if token.starts_with(NTLM_SIGNATURE) {
+        return Some(token);
+    }
+    find_ntlm(&der::parse(token).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let message = b"NTLMSSP\0\x01\0\0\0";
+        assert_eq!(ntlm_message(&init(message)), Some(&message[..]));
+        assert_eq!(ntlm_message(&response(message)), Some(&message[..]));
+        assert_eq!(ntlm_message(message), Some(&message[..]));
+        assert_eq!(ntlm_message(&response(b"other")), None);
+        assert_eq!(ntlm_message(b"\x30\x80"), None);
+    }
+}
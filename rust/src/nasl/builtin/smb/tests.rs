@@ -0,0 +1,612 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use super::client::tests::{preauth, server_decrypt, server_encrypt, signature};
+use super::message::*;
+use super::rpc::tests::answer;
+use super::spnego;
+use crate::nasl::builtin::winrm::ntlm::tests::{challenge_message, server_session_key, verify};
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+const STATUS_OBJECT_NAME_NOT_FOUND: u32 = 0xc000_0034;
+const STATUS_LOGON_FAILURE: u32 = 0xc000_006d;
+const STATUS_BAD_NETWORK_NAME: u32 = 0xc000_00cc;
+
+const SESSION_ID: u64 = 0x4455;
+const TREE_ID: u32 = 7;
+const IPC_TREE_ID: u32 = 8;
+const SHARE_FLAG_ENCRYPT_DATA: u32 = 0x0000_8000;
+/// Largest part of a pipe message returned at once
+const PIPE_CHUNK: usize = 100;
+
+fn read_message(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).ok()?;
+    let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+    let mut message = vec![0; length];
+    stream.read_exact(&mut message).ok()?;
+    Some(message)
+}
+
+fn write_message(stream: &mut TcpStream, message: &[u8]) {
+    let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    stream.write_all(&frame).unwrap();
+}
+
+fn text(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    String::from_utf16(&units).unwrap()
+}
+
+/// Returns the bytes of the request referenced by the 16 bit offset and length at the position.
+fn field(request: &[u8], position: usize, length: usize) -> &[u8] {
+    let offset = u16_at(request, HEADER_LEN + position).unwrap() as usize;
+    &request[offset..offset + length]
+}
+
+fn smb1_response() -> Vec<u8> {
+    let mut response = b"\xffSMB\x72".to_vec();
+    response.extend_from_slice(&[0; 4]);
+    response.push(0x98);
+    response.extend_from_slice(&0xc801u16.to_le_bytes());
+    response.extend_from_slice(&[0; 20]);
+    response.push(17);
+    // dialect index and security mode: user level, encrypted passwords, signing enabled
+    response.extend_from_slice(&0u16.to_le_bytes());
+    response.push(0x07);
+    // max mpx count and number of virtual circuits
+    response.extend_from_slice(&[50, 0, 1, 0]);
+    response.extend_from_slice(&16644u32.to_le_bytes());
+    response.extend_from_slice(&65536u32.to_le_bytes());
+    response.extend_from_slice(&0u32.to_le_bytes());
+    response.extend_from_slice(&0x8000_e3fcu32.to_le_bytes());
+    response.extend_from_slice(&132_000_000_000_000_000u64.to_le_bytes());
+    // time zone, challenge length and byte count
+    response.extend_from_slice(&[0, 0, 0, 0, 0]);
+    response
+}
+
+/// Returns the cipher selected from the NEGOTIATE request, GCM is preferred by SMB 3.1.1.
+fn select_cipher(dialect: u16, request: &[u8]) -> Option<u16> {
+    match dialect {
+        DIALECT_3_1_1 => {
+            let offset = u32_at(request, HEADER_LEN + 28).unwrap() as usize;
+            let count = u16_at(request, HEADER_LEN + 32).unwrap() as usize;
+            let contexts = negotiate_contexts(request, offset, count).unwrap();
+            let (_, ciphers) = contexts
+                .into_iter()
+                .find(|(x, _)| *x == ENCRYPTION_CAPABILITIES)?;
+            let ciphers: Vec<u16> = ciphers[2..]
+                .chunks_exact(2)
+                .map(|x| u16::from_le_bytes([x[0], x[1]]))
+                .collect();
+            [AES_128_GCM, AES_128_CCM]
+                .into_iter()
+                .find(|x| ciphers.contains(x))
+        }
+        0x0300 | 0x0302 => {
+            let capabilities = u32_at(request, HEADER_LEN + 8).unwrap();
+            (capabilities & GLOBAL_CAP_ENCRYPTION != 0).then_some(AES_128_CCM)
+        }
+        _ => None,
+    }
+}
+
+fn negotiate_response(dialect: u16, cipher: Option<u16>) -> Vec<u8> {
+    let mut body = 65u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&(SIGNING_ENABLED | SIGNING_REQUIRED).to_le_bytes());
+    body.extend_from_slice(&dialect.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body.extend_from_slice(&[0x11; 16]);
+    // capabilities, max transact, read and write size
+    let capabilities = match cipher {
+        Some(_) if dialect != DIALECT_3_1_1 => GLOBAL_CAP_ENCRYPTION,
+        _ => 0,
+    };
+    body.extend_from_slice(&capabilities.to_le_bytes());
+    body.extend_from_slice(&65536u32.to_le_bytes());
+    body.extend_from_slice(&65536u32.to_le_bytes());
+    body.extend_from_slice(&65536u32.to_le_bytes());
+    body.extend_from_slice(&132_000_000_000_000_000u64.to_le_bytes());
+    body.extend_from_slice(&0u64.to_le_bytes());
+    // empty security buffer and the offset of the negotiate contexts
+    body.extend_from_slice(&128u16.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body.extend_from_slice(&0u32.to_le_bytes());
+    if let (Some(cipher), DIALECT_3_1_1) = (cipher, dialect) {
+        body[6..8].copy_from_slice(&1u16.to_le_bytes());
+        let offset = (HEADER_LEN + body.len()) as u32;
+        body[60..64].copy_from_slice(&offset.to_le_bytes());
+        body.extend_from_slice(&ENCRYPTION_CAPABILITIES.to_le_bytes());
+        body.extend_from_slice(&4u16.to_le_bytes());
+        body.extend_from_slice(&[0; 4]);
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&cipher.to_le_bytes());
+    }
+    body
+}
+
+fn session_setup_response(flags: u16, token: &[u8]) -> Vec<u8> {
+    let mut body = 9u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&flags.to_le_bytes());
+    body.extend_from_slice(&72u16.to_le_bytes());
+    body.extend_from_slice(&(token.len() as u16).to_le_bytes());
+    body.extend_from_slice(token);
+    body
+}
+
+fn read_response(data: &[u8]) -> Vec<u8> {
+    let mut body = 17u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&80u16.to_le_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    body.extend_from_slice(&[0; 8]);
+    body.extend_from_slice(data);
+    body
+}
+
+/// Returns the next part of the first message of the pipe.
+fn pipe_read(messages: &mut VecDeque<Vec<u8>>, length: usize) -> (u32, Vec<u8>) {
+    let mut message = messages.pop_front().expect("read from an empty pipe");
+    if message.len() <= length {
+        return (STATUS_SUCCESS, message);
+    }
+    messages.push_front(message.split_off(length));
+    (STATUS_BUFFER_OVERFLOW, message)
+}
+
+fn error_response() -> Vec<u8> {
+    let mut body = 9u16.to_le_bytes().to_vec();
+    body.extend_from_slice(&[0; 7]);
+    body
+}
+
+/// Answers like a Windows server with the share C$ for the user "scanner" with the password
+/// "secret" and for anonymous users. Read responses contain at most 1000 bytes.
+///
+/// The pipes of IPC$ are answered by [answer], in parts of at most [PIPE_CHUNK] bytes. The share
+/// Secure$ has the files of C$ and requires encryption.
+struct Server {
+    dialect: u16,
+    smb1: bool,
+    /// Sessions of "scanner" require encryption
+    encrypt: bool,
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl Server {
+    fn serve(&mut self, mut stream: TcpStream) {
+        let mut hash = [0; 64];
+        let mut signing_key = None;
+        let mut cipher = None;
+        // all requests have to be encrypted
+        let mut encrypting = false;
+        let mut opened: HashMap<[u8; 16], String> = HashMap::new();
+        let mut pipes: HashMap<[u8; 16], (String, VecDeque<Vec<u8>>)> = HashMap::new();
+        let challenge = challenge_message();
+        while let Some(mut request) = read_message(&mut stream) {
+            if request.starts_with(b"\xffSMB") {
+                if !self.smb1 {
+                    return;
+                }
+                write_message(&mut stream, &smb1_response());
+                continue;
+            }
+            let encrypted = request.starts_with(TRANSFORM_PROTOCOL_ID);
+            assert_eq!(encrypted, encrypting);
+            if encrypted {
+                let key = signing_key.as_ref().unwrap();
+                request = server_decrypt(self.dialect, cipher.unwrap(), key, &hash, &request);
+            }
+            let header = Header::decode(&request).unwrap();
+            if let (Some(key), false) = (&signing_key, encrypted) {
+                assert_ne!(header.flags & FLAGS_SIGNED, 0);
+                assert_eq!(
+                    request[48..HEADER_LEN],
+                    signature(self.dialect, key, &hash, &request)
+                );
+            }
+            let mut tree_id = header.tree_id;
+            let (status, body) = match header.command {
+                NEGOTIATE => {
+                    cipher = select_cipher(self.dialect, &request);
+                    (STATUS_SUCCESS, negotiate_response(self.dialect, cipher))
+                }
+                SESSION_SETUP => {
+                    let token = field(
+                        &request,
+                        12,
+                        u16_at(&request, HEADER_LEN + 14).unwrap() as usize,
+                    );
+                    let message = spnego::ntlm_message(token).unwrap();
+                    match message[8] {
+                        1 => (
+                            STATUS_MORE_PROCESSING_REQUIRED,
+                            session_setup_response(0, &spnego::response(&challenge)),
+                        ),
+                        // anonymous users send no NTLMv2 response
+                        _ => match verify(message, &challenge, "secret") {
+                            None => (
+                                STATUS_SUCCESS,
+                                session_setup_response(SESSION_FLAG_IS_NULL, &[]),
+                            ),
+                            Some((user, true)) if user == "scanner" => {
+                                signing_key = server_session_key(message, "secret");
+                                let flags = if self.encrypt {
+                                    SESSION_FLAG_ENCRYPT_DATA
+                                } else {
+                                    0
+                                };
+                                (STATUS_SUCCESS, session_setup_response(flags, &[]))
+                            }
+                            Some(_) => (STATUS_LOGON_FAILURE, error_response()),
+                        },
+                    }
+                }
+                TREE_CONNECT => {
+                    let length = u16_at(&request, HEADER_LEN + 6).unwrap() as usize;
+                    let path = text(field(&request, 4, length));
+                    let share = match path.as_str() {
+                        "\\\\127.0.0.1\\C$" => Some((TREE_ID, 1, 0)),
+                        "\\\\127.0.0.1\\Secure$" => Some((TREE_ID, 1, SHARE_FLAG_ENCRYPT_DATA)),
+                        "\\\\127.0.0.1\\IPC$" => Some((IPC_TREE_ID, 2, 0)),
+                        _ => None,
+                    };
+                    if let Some((id, share_type, flags)) = share {
+                        tree_id = id;
+                        let mut body = 16u16.to_le_bytes().to_vec();
+                        body.extend_from_slice(&[share_type, 0]);
+                        body.extend_from_slice(&flags.to_le_bytes());
+                        body.extend_from_slice(&[0; 4]);
+                        body.extend_from_slice(&0x001f_01ffu32.to_le_bytes());
+                        (STATUS_SUCCESS, body)
+                    } else {
+                        (STATUS_BAD_NETWORK_NAME, error_response())
+                    }
+                }
+                CREATE => {
+                    let length = u16_at(&request, HEADER_LEN + 46).unwrap() as usize;
+                    let name = text(field(&request, 44, length));
+                    let disposition = u32_at(&request, HEADER_LEN + 36).unwrap();
+                    let id = [(opened.len() + pipes.len()) as u8 + 1; 16];
+                    let mut body = 89u16.to_le_bytes().to_vec();
+                    body.extend_from_slice(&[0; 62]);
+                    body.extend_from_slice(&id);
+                    body.extend_from_slice(&[0; 9]);
+                    if header.tree_id == IPC_TREE_ID {
+                        if ["samr", "lsarpc", "svcctl"].contains(&name.as_str()) {
+                            pipes.insert(id, (name, VecDeque::new()));
+                            (STATUS_SUCCESS, body)
+                        } else {
+                            (STATUS_OBJECT_NAME_NOT_FOUND, error_response())
+                        }
+                    } else if disposition == FILE_OPEN && !self.files.contains_key(&name) {
+                        (STATUS_OBJECT_NAME_NOT_FOUND, error_response())
+                    } else {
+                        let file = self.files.entry(name.clone()).or_default();
+                        if disposition == FILE_OVERWRITE_IF {
+                            file.clear();
+                        }
+                        body[48..56].copy_from_slice(&(file.len() as u64).to_le_bytes());
+                        opened.insert(id, name);
+                        (STATUS_SUCCESS, body)
+                    }
+                }
+                READ => {
+                    let length = u32_at(&request, HEADER_LEN + 4).unwrap() as usize;
+                    let offset = u64_at(&request, HEADER_LEN + 8).unwrap() as usize;
+                    let id = bytes_at(&request, HEADER_LEN + 16, 16).unwrap();
+                    if let Some((_, messages)) = pipes.get_mut(id) {
+                        let (status, data) = pipe_read(messages, length.min(PIPE_CHUNK));
+                        (status, read_response(&data))
+                    } else {
+                        let data = &self.files[&opened[id]];
+                        if offset >= data.len() {
+                            (STATUS_END_OF_FILE, error_response())
+                        } else {
+                            let end = data.len().min(offset + length.min(1000));
+                            (STATUS_SUCCESS, read_response(&data[offset..end]))
+                        }
+                    }
+                }
+                IOCTL => {
+                    assert_eq!(
+                        u32_at(&request, HEADER_LEN + 4).unwrap(),
+                        FSCTL_PIPE_TRANSCEIVE
+                    );
+                    let id = bytes_at(&request, HEADER_LEN + 8, 16).unwrap();
+                    let offset = u32_at(&request, HEADER_LEN + 24).unwrap() as usize;
+                    let length = u32_at(&request, HEADER_LEN + 28).unwrap() as usize;
+                    let max_output = u32_at(&request, HEADER_LEN + 44).unwrap() as usize;
+                    let (name, messages) = pipes.get_mut(id).unwrap();
+                    messages.extend(answer(name, &request[offset..offset + length]));
+                    let (status, data) = pipe_read(messages, max_output.min(PIPE_CHUNK));
+                    let mut body = 49u16.to_le_bytes().to_vec();
+                    body.extend_from_slice(&[0; 2]);
+                    body.extend_from_slice(&FSCTL_PIPE_TRANSCEIVE.to_le_bytes());
+                    body.extend_from_slice(id);
+                    body.extend_from_slice(&[0; 8]);
+                    body.extend_from_slice(&((HEADER_LEN + 48) as u32).to_le_bytes());
+                    body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    body.extend_from_slice(&[0; 8]);
+                    body.extend_from_slice(&data);
+                    (status, body)
+                }
+                WRITE => {
+                    let length = u32_at(&request, HEADER_LEN + 4).unwrap() as usize;
+                    let offset = u64_at(&request, HEADER_LEN + 8).unwrap() as usize;
+                    let id = bytes_at(&request, HEADER_LEN + 16, 16).unwrap();
+                    let data = field(&request, 2, length);
+                    let file = self.files.get_mut(&opened[id]).unwrap();
+                    if file.len() < offset + length {
+                        file.resize(offset + length, 0);
+                    }
+                    file[offset..offset + length].copy_from_slice(data);
+                    let mut body = 17u16.to_le_bytes().to_vec();
+                    body.extend_from_slice(&[0; 2]);
+                    body.extend_from_slice(&(length as u32).to_le_bytes());
+                    body.extend_from_slice(&[0; 8]);
+                    (STATUS_SUCCESS, body)
+                }
+                CLOSE => {
+                    let mut body = 60u16.to_le_bytes().to_vec();
+                    body.extend_from_slice(&[0; 58]);
+                    (STATUS_SUCCESS, body)
+                }
+                TREE_DISCONNECT | LOGOFF => (STATUS_SUCCESS, vec![4, 0, 0, 0]),
+                x => panic!("unexpected command {x}"),
+            };
+            let mut response = Header {
+                credit_charge: 1,
+                status,
+                command: header.command,
+                credits: 32,
+                flags: FLAGS_SERVER_TO_REDIR,
+                message_id: header.message_id,
+                tree_id,
+                session_id: SESSION_ID,
+            }
+            .encode();
+            response.extend_from_slice(&body);
+            if self.dialect == DIALECT_3_1_1 {
+                match header.command {
+                    NEGOTIATE => hash = preauth(&preauth(&hash, &request), &response),
+                    SESSION_SETUP => {
+                        hash = preauth(&hash, &request);
+                        if status == STATUS_MORE_PROCESSING_REQUIRED {
+                            hash = preauth(&hash, &response);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if encrypted {
+                let key = signing_key.as_ref().unwrap();
+                response = server_encrypt(
+                    self.dialect,
+                    cipher.unwrap(),
+                    key,
+                    &hash,
+                    &response,
+                    SESSION_ID,
+                );
+            }
+            write_message(&mut stream, &response);
+            // the responses enabling the encryption are not encrypted themselves
+            let session_flags = match header.command {
+                SESSION_SETUP if status == STATUS_SUCCESS => u16_at(&body, 2).unwrap(),
+                _ => 0,
+            };
+            let share_flags = match header.command {
+                TREE_CONNECT if status == STATUS_SUCCESS => u32_at(&body, 4).unwrap(),
+                _ => 0,
+            };
+            encrypting |= session_flags & SESSION_FLAG_ENCRYPT_DATA != 0
+                || share_flags & SHARE_FLAG_ENCRYPT_DATA != 0;
+        }
+    }
+}
+
+pub fn smb_server(dialect: u16, smb1: bool) -> u16 {
+    spawn(Server {
+        dialect,
+        smb1,
+        encrypt: false,
+        files: HashMap::new(),
+    })
+}
+
+/// Returns the port of a server whose sessions require encryption.
+fn encrypting_smb_server(dialect: u16) -> u16 {
+    spawn(Server {
+        dialect,
+        smb1: false,
+        encrypt: true,
+        files: HashMap::new(),
+    })
+}
+
+fn spawn(mut server: Server) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    server.files.insert(
+        "Windows\\win.ini".to_string(),
+        b"; for 16-bit app support\r\n".repeat(100),
+    );
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            server.serve(stream.unwrap());
+        }
+    });
+    port
+}
+
+#[test]
+fn negotiate() {
+    let port = smb_server(DIALECT_3_1_1, false);
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.run(format!("n = smb_negotiate(port: {port});"));
+    t.ok(r#"n["dialect"];"#, "3.1.1");
+    t.ok(r#"n["signing_required"];"#, true);
+    t.ok(r#"n["max_read_size"];"#, 65536);
+    t.ok(r#"n["server_guid"];"#, "11".repeat(16));
+    t.ok(r#"n["system_time"];"#, 1_555_526_400);
+    t.ok(
+        format!("smb_negotiate(port: {port}, smb1: TRUE);"),
+        NaslValue::Null,
+    );
+
+    let port = smb_server(0x0210, true);
+    t.run(format!("n = smb_negotiate(port: {port});"));
+    t.ok(r#"n["dialect"];"#, "2.1");
+    t.run(format!("n = smb_negotiate(port: {port}, smb1: TRUE);"));
+    t.ok(r#"n["dialect"];"#, "NT LM 0.12");
+    t.ok(r#"n["signing_enabled"];"#, true);
+    t.ok(r#"n["signing_required"];"#, false);
+    t.ok(r#"n["max_buffer_size"];"#, 16644);
+}
+
+#[test]
+fn read_and_write_files() {
+    let port = smb_server(DIALECT_3_1_1, false);
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(
+            r#"h = smb_connect(port: {port}, username: "EXAMPLE\scanner", password: "secret", share: "C$");"#
+        ),
+        1,
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "Windows/win.ini");"#,
+        b"; for 16-bit app support\r\n".repeat(100),
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "\Windows\win.ini", offset: 2, length: 3);"#,
+        b"for".to_vec(),
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "missing.txt");"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        r#"smb_file_write(smb_handle: h, filename: "test.txt", data: "hello");"#,
+        5,
+    );
+    t.ok(
+        r#"smb_file_write(smb_handle: h, filename: "test.txt", data: "p!", offset: 3);"#,
+        2,
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "test.txt");"#,
+        b"help!".to_vec(),
+    );
+    t.ok("smb_close(smb_handle: h);", true);
+    check_err_matches!(
+        t,
+        "smb_close(smb_handle: h);",
+        FunctionErrorKind::Diagnostic(_, _)
+    );
+}
+
+#[test]
+fn sign_with_hmac_sha256() {
+    let port = smb_server(0x0210, false);
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(
+            r#"h = smb_connect(port: {port}, username: "scanner", password: "secret", domain: "EXAMPLE", share: "C$");"#
+        ),
+        1,
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "Windows\win.ini", length: 5);"#,
+        b"; for".to_vec(),
+    );
+}
+
+#[test]
+fn failed_connections() {
+    let port = smb_server(DIALECT_3_1_1, false);
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(
+            r#"smb_connect(port: {port}, username: "scanner", password: "wrong", share: "C$");"#
+        ),
+        NaslValue::Null,
+    );
+    t.ok(
+        format!(
+            r#"smb_connect(port: {port}, username: "scanner", password: "secret", share: "D$");"#
+        ),
+        NaslValue::Null,
+    );
+    t.ok(
+        format!(r#"smb_connect(port: {port}, username: "", password: "", share: "C$");"#),
+        1,
+    );
+}
+
+#[test]
+fn encryption() {
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    // the session of SMB 3.1.1 is encrypted with AES-128-GCM
+    let port = encrypting_smb_server(DIALECT_3_1_1);
+    t.ok(
+        format!(
+            r#"h = smb_connect(port: {port}, username: "scanner", password: "secret", share: "C$");"#
+        ),
+        1,
+    );
+    t.ok(
+        r#"smb_file_write(smb_handle: h, filename: "test.txt", data: "hello");"#,
+        5,
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "test.txt");"#,
+        b"hello".to_vec(),
+    );
+    t.ok("smb_close(smb_handle: h);", true);
+    // the share is encrypted with AES-128-CCM by SMB 3.0
+    let port = smb_server(0x0300, false);
+    t.ok(
+        format!(
+            r#"h = smb_connect(port: {port}, username: "scanner", password: "secret", share: "Secure$");"#
+        ),
+        2,
+    );
+    t.ok(
+        r#"smb_file_read(smb_handle: h, filename: "Windows\win.ini", length: 5);"#,
+        b"; for".to_vec(),
+    );
+    t.ok("smb_close(smb_handle: h);", true);
+    // SMB 2.1 and anonymous sessions have no encryption keys
+    let port = smb_server(0x0210, false);
+    t.ok(
+        format!(
+            r#"smb_connect(port: {port}, username: "scanner", password: "secret", share: "Secure$");"#
+        ),
+        NaslValue::Null,
+    );
+    let port = smb_server(DIALECT_3_1_1, false);
+    t.ok(
+        format!(r#"smb_connect(port: {port}, username: "", password: "", share: "Secure$");"#),
+        NaslValue::Null,
+    );
+}
@@ -0,0 +1,381 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding and decoding of SNMPv1 and SNMPv2c messages (RFC 1157, RFC 3416) and of the PDUs
+//! shared with SNMPv3.
+
+use std::fmt::Display;
+
+use super::super::asn1::der::{self, encode_integer, tlv, Class, Element};
+
+pub const INTEGER: u8 = 0x02;
+pub const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+
+/// Version field of the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1 = 0,
+    V2c = 1,
+}
+
+/// Type of the PDU, the context specific tag of its sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PduType {
+    Get = 0,
+    GetNext = 1,
+    Response = 2,
+    Report = 8,
+}
+
+/// Value of a variable binding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    Oid(String),
+    IpAddress([u8; 4]),
+    Counter32(u64),
+    Gauge32(u64),
+    TimeTicks(u64),
+    Opaque(Vec<u8>),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl Value {
+    /// Returns the reason when the value is an exception of SNMPv2c instead of a value.
+    pub fn exception(&self) -> Option<&'static str> {
+        match self {
+            Self::NoSuchObject => Some("No Such Object available on this agent at this OID"),
+            Self::NoSuchInstance => Some("No Such Instance currently exists at this OID"),
+            Self::EndOfMibView => Some("No more variables left in this MIB View"),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Integer(x) => tlv(INTEGER, &encode_integer(*x)),
+            Self::OctetString(x) => tlv(OCTET_STRING, x),
+            Self::Oid(x) => tlv(OBJECT_IDENTIFIER, &encode_oid(x).unwrap_or_default()),
+            Self::IpAddress(x) => tlv(0x40, x),
+            Self::Counter32(x) => tlv(0x41, &encode_integer(*x as i64)),
+            Self::Gauge32(x) => tlv(0x42, &encode_integer(*x as i64)),
+            Self::TimeTicks(x) => tlv(0x43, &encode_integer(*x as i64)),
+            Self::Opaque(x) => tlv(0x44, x),
+            Self::Counter64(x) => tlv(0x46, &encode_unsigned(*x)),
+            Self::Null => tlv(NULL, &[]),
+            Self::NoSuchObject => tlv(0x80, &[]),
+            Self::NoSuchInstance => tlv(0x81, &[]),
+            Self::EndOfMibView => tlv(0x82, &[]),
+        }
+    }
+
+    fn decode(element: &Element) -> Option<Self> {
+        let content = element.content;
+        Some(match (element.class, element.tag) {
+            (Class::Universal, 2) => Self::Integer(der::integer(content)?),
+            (Class::Universal, 4) => Self::OctetString(content.to_vec()),
+            (Class::Universal, 5) => Self::Null,
+            (Class::Universal, 6) => Self::Oid(der::oid(content)?),
+            (Class::Application, 0) => Self::IpAddress(content.try_into().ok()?),
+            (Class::Application, 1) => Self::Counter32(unsigned(content)?),
+            (Class::Application, 2) => Self::Gauge32(unsigned(content)?),
+            (Class::Application, 3) => Self::TimeTicks(unsigned(content)?),
+            (Class::Application, 4) => Self::Opaque(content.to_vec()),
+            (Class::Application, 6) => Self::Counter64(unsigned(content)?),
+            (Class::Context, 0) => Self::NoSuchObject,
+            (Class::Context, 1) => Self::NoSuchInstance,
+            (Class::Context, 2) => Self::EndOfMibView,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for Value {
+    /// Formats the value like the text returned to NASL scripts, binary strings are formatted
+    /// as hex bytes separated by spaces.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(x) => write!(f, "{x}"),
+            Self::OctetString(x) | Self::Opaque(x) => match std::str::from_utf8(x) {
+                Ok(s) if !s.chars().any(|c| c.is_control() && !c.is_whitespace()) => {
+                    write!(f, "{s}")
+                }
+                _ => write!(
+                    f,
+                    "{}",
+                    x.iter()
+                        .map(|b| format!("{b:02X}"))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+            },
+            Self::Null => Ok(()),
+            Self::Oid(x) => write!(f, "{x}"),
+            Self::IpAddress(x) => write!(f, "{}.{}.{}.{}", x[0], x[1], x[2], x[3]),
+            Self::Counter32(x) | Self::Gauge32(x) | Self::TimeTicks(x) | Self::Counter64(x) => {
+                write!(f, "{x}")
+            }
+            Self::NoSuchObject | Self::NoSuchInstance | Self::EndOfMibView => {
+                write!(f, "{}", self.exception().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// A variable and its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarBind {
+    pub oid: String,
+    pub value: Value,
+}
+
+/// The request or response of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pdu {
+    pub pdu_type: PduType,
+    pub request_id: i32,
+    pub error_status: i64,
+    pub error_index: i64,
+    pub bindings: Vec<VarBind>,
+}
+
+/// A community based SNMP message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub version: Version,
+    pub community: Vec<u8>,
+    pub pdu: Pdu,
+}
+
+/// Returns the name of the error status of a response.
+pub fn error_status_name(status: i64) -> &'static str {
+    match status {
+        0 => "noError",
+        1 => "tooBig",
+        2 => "noSuchName",
+        3 => "badValue",
+        4 => "readOnly",
+        5 => "genErr",
+        6 => "noAccess",
+        7 => "wrongType",
+        8 => "wrongLength",
+        9 => "wrongEncoding",
+        10 => "wrongValue",
+        11 => "noCreation",
+        12 => "inconsistentValue",
+        13 => "resourceUnavailable",
+        14 => "commitFailed",
+        15 => "undoFailed",
+        16 => "authorizationError",
+        17 => "notWritable",
+        18 => "inconsistentName",
+        _ => "unknown error",
+    }
+}
+
+fn encode_unsigned(value: u64) -> Vec<u8> {
+    let mut result = vec![0];
+    result.extend_from_slice(&value.to_be_bytes());
+    let skip = result
+        .windows(2)
+        .take_while(|x| x[0] == 0 && x[1] & 0x80 == 0)
+        .count();
+    result[skip..].to_vec()
+}
+
+/// Decodes the content of an unsigned integer, which may have a leading zero octet.
+fn unsigned(content: &[u8]) -> Option<u64> {
+    let content = match content {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        x => x,
+    };
+    if content.is_empty() || content.len() > 8 {
+        return None;
+    }
+    Some(content.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+}
+
+/// Encodes an object identifier in dotted notation, a leading dot is ignored.
+pub fn encode_oid(oid: &str) -> Option<Vec<u8>> {
+    let arcs = oid
+        .strip_prefix('.')
+        .unwrap_or(oid)
+        .split('.')
+        .map(|x| x.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if arcs.len() < 2 || arcs[0] > 2 || (arcs[0] < 2 && arcs[1] >= 40) {
+        return None;
+    }
+    let mut result = vec![];
+    let first = arcs[0].checked_mul(40)?.checked_add(arcs[1])?;
+    for arc in std::iter::once(first).chain(arcs[2..].iter().copied()) {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            bytes.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        result.extend(bytes.iter().rev());
+    }
+    Some(result)
+}
+
+/// Returns the normalized dotted notation of the object identifier, None when it is invalid.
+pub fn normalize_oid(oid: &str) -> Option<String> {
+    der::oid(&encode_oid(oid)?)
+}
+
+impl Pdu {
+    /// Creates a request for the value of the object identifier.
+    pub fn request(pdu_type: PduType, request_id: i32, oid: &str) -> Self {
+        Self {
+            pdu_type,
+            request_id,
+            error_status: 0,
+            error_index: 0,
+            bindings: vec![VarBind {
+                oid: oid.to_string(),
+                value: Value::Null,
+            }],
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let bindings: Vec<u8> = self
+            .bindings
+            .iter()
+            .flat_map(|x| {
+                let mut binding = tlv(OBJECT_IDENTIFIER, &encode_oid(&x.oid).unwrap_or_default());
+                binding.extend(x.value.encode());
+                tlv(SEQUENCE, &binding)
+            })
+            .collect();
+        let mut pdu = tlv(INTEGER, &encode_integer(self.request_id as i64));
+        pdu.extend(tlv(INTEGER, &encode_integer(self.error_status)));
+        pdu.extend(tlv(INTEGER, &encode_integer(self.error_index)));
+        pdu.extend(tlv(SEQUENCE, &bindings));
+        tlv(0xa0 | self.pdu_type as u8, &pdu)
+    }
+
+    /// Decodes the PDU element, None when it is malformed or not a known PDU.
+    pub fn decode(pdu: &Element) -> Option<Self> {
+        let pdu_type = match (pdu.class, pdu.tag) {
+            (Class::Context, 0) => PduType::Get,
+            (Class::Context, 1) => PduType::GetNext,
+            (Class::Context, 2) => PduType::Response,
+            (Class::Context, 8) => PduType::Report,
+            _ => return None,
+        };
+        let [request_id, error_status, error_index, bindings] = pdu.children.as_slice() else {
+            return None;
+        };
+        let bindings = bindings
+            .children
+            .iter()
+            .map(|binding| match binding.children.as_slice() {
+                [oid, value] => Some(VarBind {
+                    oid: der::oid(oid.content)?,
+                    value: Value::decode(value)?,
+                }),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            pdu_type,
+            request_id: i32::try_from(der::integer(request_id.content)?).ok()?,
+            error_status: der::integer(error_status.content)?,
+            error_index: der::integer(error_index.content)?,
+            bindings,
+        })
+    }
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = tlv(INTEGER, &encode_integer(self.version as i64));
+        message.extend(tlv(OCTET_STRING, &self.community));
+        message.extend(self.pdu.encode());
+        tlv(SEQUENCE, &message)
+    }
+
+    /// Decodes a message, None when it is malformed or not a known PDU.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let elements = der::parse(data).ok()?;
+        let [message] = elements.as_slice() else {
+            return None;
+        };
+        let [version, community, pdu] = message.children.as_slice() else {
+            return None;
+        };
+        let version = match der::integer(version.content)? {
+            0 => Version::V1,
+            1 => Version::V2c,
+            _ => return None,
+        };
+        Some(Self {
+            version,
+            community: community.content.to_vec(),
+            pdu: Pdu::decode(pdu)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_request() {
+        let request = Message {
+            version: Version::V2c,
+            community: b"public".to_vec(),
+            pdu: Pdu::request(PduType::Get, 1, ".1.3.6.1.2.1.1.1.0"),
+        };
+        let expected = [
+            0x30, 0x26, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa0,
+            0x19, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x0e, 0x30, 0x0c,
+            0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00, 0x05, 0x00,
+        ];
+        assert_eq!(request.encode(), expected);
+        let mut decoded = Message::decode(&expected).unwrap();
+        assert_eq!(decoded.pdu.bindings[0].oid, "1.3.6.1.2.1.1.1.0");
+        decoded.pdu.bindings[0].oid = ".1.3.6.1.2.1.1.1.0".to_string();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn values() {
+        let values = [
+            Value::Integer(-129),
+            Value::OctetString(b"Linux".to_vec()),
+            Value::Oid("1.3.6.1.4.1.8072.3.2.10".to_string()),
+            Value::IpAddress([192, 0, 2, 1]),
+            Value::Counter32(4294967295),
+            Value::TimeTicks(128),
+            Value::Counter64(u64::MAX),
+            Value::EndOfMibView,
+        ];
+        for value in values {
+            let encoded = value.encode();
+            let elements = der::parse(&encoded).unwrap();
+            assert_eq!(Value::decode(&elements[0]), Some(value));
+        }
+        assert_eq!(
+            Value::OctetString(vec![0, 0x1a, 0xff]).to_string(),
+            "00 1A FF"
+        );
+        assert_eq!(Value::IpAddress([192, 0, 2, 1]).to_string(), "192.0.2.1");
+        assert_eq!(normalize_oid(".1.3.6.1").as_deref(), Some("1.3.6.1"));
+        assert_eq!(normalize_oid("1.3.x"), None);
+        assert_eq!(encode_oid("2.999"), Some(vec![0x88, 0x37]));
+    }
+}
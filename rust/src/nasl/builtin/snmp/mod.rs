@@ -0,0 +1,691 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to query SNMP agents with the community based versions 1 and 2c and
+//! with SNMPv3 and the user-based security model.
+
+mod message;
+#[cfg(test)]
+mod tests;
+mod v3;
+
+use std::{
+    io::{self, Read, Write},
+    sync::Mutex,
+    time::Instant,
+};
+
+use indexmap::IndexMap;
+use message::{error_status_name, normalize_oid, Message, Pdu, PduType, VarBind, Version};
+use v3::{AuthProtocol, PrivProtocol, Session, User, V3Message, MIN_PASSWORD_LENGTH};
+
+use crate::nasl::prelude::*;
+
+use super::network::{
+    network_utils::target_ip,
+    socket::NaslSockets,
+    udp::{DEFAULT_RETRIES, DEFAULT_TIMEOUT},
+    verify_port,
+};
+
+const DEFAULT_PORT: u16 = 161;
+/// Largest message accepted from an agent
+const MAX_MESSAGE: usize = 65535;
+/// Maximum number of variables returned by a walk
+const MAX_WALK: usize = 10000;
+
+/// Return code of a failed request
+const ERROR: i64 = -1;
+/// Return code of invalid arguments
+const INVALID_ARGUMENT: i64 = -2;
+
+/// Transport of the messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Udp,
+    Tcp,
+}
+
+impl Protocol {
+    fn parse(value: Option<&str>) -> Option<Self> {
+        match value.map(|x| x.to_lowercase()).as_deref() {
+            None | Some("udp") | Some("udp6") => Some(Self::Udp),
+            Some("tcp") | Some("tcp6") => Some(Self::Tcp),
+            _ => None,
+        }
+    }
+}
+
+/// Credentials of the requests
+enum Security<'a> {
+    Community(Version, &'a str),
+    /// The SNMPv3 user and its session, once the engine of the agent is discovered
+    User(User<'a>, Option<Session>),
+}
+
+/// Returns the SNMPv3 user of the arguments or the reason why they are invalid.
+fn user<'a>(
+    username: Option<&'a str>,
+    authpass: Option<&'a str>,
+    authproto: Option<&str>,
+    privpass: Option<&'a str>,
+    privproto: Option<&str>,
+) -> Result<User<'a>, &'static str> {
+    let (Some(name), Some(auth_password), Some(auth)) = (username, authpass, authproto) else {
+        return Err("Missing function argument");
+    };
+    let auth = AuthProtocol::parse(auth).ok_or("authproto should be md5 or sha1")?;
+    let privacy = match (privproto, privpass) {
+        (Some(protocol), Some(password)) => Some((
+            PrivProtocol::parse(protocol).ok_or("privproto should be des or aes")?,
+            password,
+        )),
+        (None, None) => None,
+        _ => return Err("Missing privproto or privpass"),
+    };
+    let short = |x: &str| x.len() < MIN_PASSWORD_LENGTH;
+    if short(auth_password) || privacy.is_some_and(|(_, x)| short(x)) {
+        return Err("Passwords should have at least 8 characters");
+    }
+    Ok(User {
+        name,
+        auth,
+        auth_password,
+        privacy,
+    })
+}
+
+/// Address of the agent
+struct Transport {
+    protocol: Protocol,
+    port: u16,
+}
+
+/// Agent and credentials of a request
+struct Agent<'a> {
+    transport: Transport,
+    security: Security<'a>,
+}
+
+/// Returns the array of the return code and the reason of a failure.
+fn error_array(code: i64, reason: &str) -> NaslValue {
+    NaslValue::Array(vec![
+        NaslValue::Number(code),
+        NaslValue::String(reason.to_string()),
+    ])
+}
+
+fn result_array(binding: &VarBind) -> NaslValue {
+    NaslValue::Array(vec![
+        NaslValue::Number(0),
+        NaslValue::String(binding.oid.clone()),
+        NaslValue::String(binding.value.to_string()),
+    ])
+}
+
+/// Reads a message of a TCP stream, which is not framed besides its BER length.
+fn read_message(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut header = vec![0; 2];
+    stream.read_exact(&mut header)?;
+    let length = match header[1] {
+        x if x < 0x80 => x as usize,
+        x => {
+            let mut octets = vec![0; (x & 0x7f) as usize];
+            if octets.is_empty() || octets.len() > 4 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid message length",
+                ));
+            }
+            stream.read_exact(&mut octets)?;
+            header.extend_from_slice(&octets);
+            octets.iter().fold(0, |acc, b| (acc << 8) | *b as usize)
+        }
+    };
+    if length > MAX_MESSAGE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+    let mut content = vec![0; length];
+    stream.read_exact(&mut content)?;
+    header.extend(content);
+    Ok(header)
+}
+
+impl Transport {
+    /// Sends the request to the target and returns the first response accepted by `matches`.
+    fn exchange<T>(
+        &self,
+        context: &Context,
+        request: &[u8],
+        matches: impl Fn(&[u8]) -> Option<T>,
+    ) -> io::Result<T> {
+        let addr = target_ip(context).map_err(io::Error::other)?;
+        match self.protocol {
+            Protocol::Udp => {
+                let mut conn = NaslSockets::connect_udp(
+                    context,
+                    addr,
+                    self.port,
+                    DEFAULT_TIMEOUT,
+                    DEFAULT_RETRIES,
+                )?;
+                conn.write_all(request)?;
+                let deadline = Instant::now() + DEFAULT_TIMEOUT;
+                let mut buf = vec![0; MAX_MESSAGE];
+                // responses to earlier requests are skipped
+                while Instant::now() < deadline {
+                    let length = conn.read(&mut buf)?;
+                    if let Some(response) = matches(&buf[..length]) {
+                        return Ok(response);
+                    }
+                }
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            Protocol::Tcp => {
+                let mut conn = NaslSockets::connect_tcp(
+                    context,
+                    &[addr],
+                    self.port,
+                    None,
+                    DEFAULT_TIMEOUT,
+                    None,
+                )?;
+                conn.write_all(request)?;
+                matches(&read_message(&mut conn)?)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid response"))
+            }
+        }
+    }
+
+    /// Sends the PDU with a community and returns the response.
+    fn exchange_community(
+        &self,
+        context: &Context,
+        version: Version,
+        community: &str,
+        pdu: Pdu,
+    ) -> io::Result<Pdu> {
+        let request = Message {
+            version,
+            community: community.as_bytes().to_vec(),
+            pdu,
+        };
+        let request_id = request.pdu.request_id;
+        self.exchange(context, &request.encode(), |data| {
+            Message::decode(data)
+                .map(|x| x.pdu)
+                .filter(|x| x.pdu_type == PduType::Response && x.request_id == request_id)
+        })
+    }
+
+    /// Sends the PDU as SNMPv3 user and returns the response.
+    ///
+    /// The engine of the agent is discovered by the first request. When the agent reports an
+    /// outdated engine time, the request is repeated once with the reported time.
+    fn exchange_user(
+        &self,
+        context: &Context,
+        user: &User,
+        session: &mut Option<Session>,
+        pdu: Pdu,
+    ) -> Result<Pdu, String> {
+        let session = match session {
+            Some(x) => x,
+            None => session.insert(self.discover(context, user)?),
+        };
+        for _ in 0..2 {
+            let id = message_id();
+            let data = self
+                .exchange(context, &session.seal(id, pdu.clone()), |data| {
+                    V3Message::decode(data)
+                        .filter(|(x, _)| x.id == id)
+                        .map(|_| data.to_vec())
+                })
+                .map_err(reason)?;
+            let (engine, response) = session.open(&data)?;
+            if response.pdu_type != PduType::Report {
+                return Ok(response);
+            }
+            if !v3::is_not_in_time_window(&response) {
+                return Err(v3::report_reason(&response).to_string());
+            }
+            session.synchronize(&engine);
+        }
+        Err("Not in time window".to_string())
+    }
+
+    /// Discovers the engine of the agent and returns the session of the user with it.
+    fn discover(&self, context: &Context, user: &User) -> Result<Session, String> {
+        let id = message_id();
+        let (report, _) = self
+            .exchange(context, &V3Message::discovery(id).encode(), |data| {
+                V3Message::decode(data).filter(|(x, _)| x.id == id)
+            })
+            .map_err(reason)?;
+        if report.security.engine.id.is_empty() {
+            return Err("No engine ID discovered".to_string());
+        }
+        Ok(Session::new(user, report.security.engine))
+    }
+}
+
+impl Agent<'_> {
+    /// Requests the variable and returns it or the reason of the failure.
+    fn request(
+        &mut self,
+        context: &Context,
+        pdu_type: PduType,
+        oid: &str,
+    ) -> Result<VarBind, String> {
+        let pdu = Pdu::request(pdu_type, message_id(), oid);
+        let response = match &mut self.security {
+            Security::Community(version, community) => self
+                .transport
+                .exchange_community(context, *version, community, pdu)
+                .map_err(reason)?,
+            Security::User(user, session) => {
+                self.transport.exchange_user(context, user, session, pdu)?
+            }
+        };
+        if response.error_status != 0 {
+            return Err(error_status_name(response.error_status).to_string());
+        }
+        let binding = response
+            .bindings
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Empty response".to_string())?;
+        match binding.value.exception() {
+            Some(reason) => Err(reason.to_string()),
+            None => Ok(binding),
+        }
+    }
+}
+
+/// Returns a random positive id of a request or message.
+fn message_id() -> i32 {
+    rand::random::<i32>() & i32::MAX
+}
+
+/// Returns the reason of a failed exchange.
+fn reason(e: io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => "Timeout".to_string(),
+        _ => e.to_string(),
+    }
+}
+
+/// Holds the OID returned by the last getnext request, the start of the next one.
+#[derive(Default)]
+pub struct Snmp {
+    next_oid: Mutex<Option<String>>,
+}
+
+impl Snmp {
+    fn get(
+        &self,
+        context: &Context,
+        pdu_type: PduType,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        security: Result<Security, &str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let oid = match (oid, pdu_type) {
+            (Some(x), _) => Some(x.to_string()),
+            (None, PduType::GetNext) => self.next_oid.lock().unwrap().clone(),
+            (None, _) => None,
+        };
+        let (security, oid) = match (security, oid) {
+            (Ok(security), Some(oid)) => (security, oid),
+            (Err(reason), _) => return Ok(error_array(INVALID_ARGUMENT, reason)),
+            (_, None) => {
+                return Ok(error_array(INVALID_ARGUMENT, "Missing function argument"));
+            }
+        };
+        let Some(oid) = normalize_oid(&oid) else {
+            return Ok(error_array(INVALID_ARGUMENT, "Invalid OID value"));
+        };
+        let mut agent = match agent(port, protocol, security) {
+            Ok(x) => x,
+            Err(e) => return Ok(e),
+        };
+        match agent.request(context, pdu_type, &oid) {
+            Ok(binding) => {
+                if pdu_type == PduType::GetNext {
+                    *self.next_oid.lock().unwrap() = Some(binding.oid.clone());
+                }
+                Ok(result_array(&binding))
+            }
+            Err(reason) => Ok(error_array(ERROR, &reason)),
+        }
+    }
+
+    /// Get the value of a variable via SNMPv1.
+    ///
+    /// - port: Port of the agent, 161 by default
+    /// - protocol: "udp" (default) or "tcp"
+    /// - community: Community string
+    /// - oid: Object identifier of the variable
+    ///
+    /// Returns an array of 0, the OID and the value, on failure an array of the return code and
+    /// the reason: -1 when the request failed and -2 on invalid arguments.
+    #[nasl_function(named(port, protocol, community, oid))]
+    fn snmpv1_get(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        community: Option<&str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.get(
+            context,
+            PduType::Get,
+            port,
+            protocol,
+            community_security(Version::V1, community),
+            oid,
+        )
+    }
+
+    /// Get the variable following the OID via SNMPv1.
+    ///
+    /// Takes the same arguments as snmpv1_get. Without oid the request starts at the OID
+    /// returned by the last getnext request, so consecutive calls walk through the MIB.
+    #[nasl_function(named(port, protocol, community, oid))]
+    fn snmpv1_getnext(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        community: Option<&str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.get(
+            context,
+            PduType::GetNext,
+            port,
+            protocol,
+            community_security(Version::V1, community),
+            oid,
+        )
+    }
+
+    /// Get the value of a variable via SNMPv2c.
+    ///
+    /// Takes the same arguments and returns the same values as snmpv1_get.
+    #[nasl_function(named(port, protocol, community, oid))]
+    fn snmpv2c_get(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        community: Option<&str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.get(
+            context,
+            PduType::Get,
+            port,
+            protocol,
+            community_security(Version::V2c, community),
+            oid,
+        )
+    }
+
+    /// Get the variable following the OID via SNMPv2c.
+    ///
+    /// Takes the same arguments and returns the same values as snmpv1_getnext.
+    #[nasl_function(named(port, protocol, community, oid))]
+    fn snmpv2c_getnext(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        community: Option<&str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.get(
+            context,
+            PduType::GetNext,
+            port,
+            protocol,
+            community_security(Version::V2c, community),
+            oid,
+        )
+    }
+
+    /// Get all variables below the OID via SNMPv1.
+    ///
+    /// - port: Port of the agent, 161 by default
+    /// - protocol: "udp" (default) or "tcp"
+    /// - community: Community string
+    /// - oid: Object identifier of the subtree
+    ///
+    /// Returns an array of the values indexed by their OIDs in the order of the MIB, empty when
+    /// the agent did not answer.
+    #[nasl_function(named(port, protocol, community, oid))]
+    fn snmpv1_walk(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        community: &str,
+        oid: &str,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        walk(
+            context,
+            port,
+            protocol,
+            Security::Community(Version::V1, community),
+            oid,
+        )
+    }
+
+    /// Get all variables below the OID via SNMPv2c.
+    ///
+    /// Takes the same arguments and returns the same values as snmpv1_walk.
+    #[nasl_function(named(port, protocol, community, oid))]
+    fn snmpv2c_walk(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        community: &str,
+        oid: &str,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        walk(
+            context,
+            port,
+            protocol,
+            Security::Community(Version::V2c, community),
+            oid,
+        )
+    }
+
+    /// Get the value of a variable via SNMPv3.
+    ///
+    /// - port: Port of the agent, 161 by default
+    /// - protocol: "udp" (default) or "tcp"
+    /// - username: Name of the user
+    /// - authpass: Password of the authentication, at least 8 characters
+    /// - authproto: "md5" or "sha1"
+    /// - privpass: Optional password of the privacy, at least 8 characters
+    /// - privproto: Optional "des" or "aes", required together with privpass
+    /// - oid: Object identifier of the variable
+    ///
+    /// Returns the same values as snmpv1_get.
+    #[nasl_function(named(
+        port, protocol, username, authpass, authproto, privpass, privproto, oid
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn snmpv3_get(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        username: Option<&str>,
+        authpass: Option<&str>,
+        authproto: Option<&str>,
+        privpass: Option<&str>,
+        privproto: Option<&str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let user = user(username, authpass, authproto, privpass, privproto);
+        self.get(
+            context,
+            PduType::Get,
+            port,
+            protocol,
+            user.map(|x| Security::User(x, None)),
+            oid,
+        )
+    }
+
+    /// Get the variable following the OID via SNMPv3.
+    ///
+    /// Takes the same arguments as snmpv3_get and returns the same values as snmpv1_getnext.
+    #[nasl_function(named(
+        port, protocol, username, authpass, authproto, privpass, privproto, oid
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn snmpv3_getnext(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        username: Option<&str>,
+        authpass: Option<&str>,
+        authproto: Option<&str>,
+        privpass: Option<&str>,
+        privproto: Option<&str>,
+        oid: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let user = user(username, authpass, authproto, privpass, privproto);
+        self.get(
+            context,
+            PduType::GetNext,
+            port,
+            protocol,
+            user.map(|x| Security::User(x, None)),
+            oid,
+        )
+    }
+
+    /// Get all variables below the OID via SNMPv3.
+    ///
+    /// Takes the same arguments as snmpv3_get and returns the same values as snmpv1_walk. The
+    /// engine of the agent is discovered once for the whole walk.
+    #[nasl_function(named(
+        port, protocol, username, authpass, authproto, privpass, privproto, oid
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn snmpv3_walk(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        protocol: Option<&str>,
+        username: &str,
+        authpass: &str,
+        authproto: &str,
+        privpass: Option<&str>,
+        privproto: Option<&str>,
+        oid: &str,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let user = user(
+            Some(username),
+            Some(authpass),
+            Some(authproto),
+            privpass,
+            privproto,
+        )
+        .map_err(|e| FunctionErrorKind::WrongArgument(e.to_string()))?;
+        walk(context, port, protocol, Security::User(user, None), oid)
+    }
+}
+
+/// Returns the community security or the reason why it is missing.
+fn community_security(version: Version, community: Option<&str>) -> Result<Security, &str> {
+    community
+        .map(|x| Security::Community(version, x))
+        .ok_or("Missing function argument")
+}
+
+/// Returns the agent of the arguments or the error array of the invalid one.
+fn agent<'a>(
+    port: Option<i64>,
+    protocol: Option<&str>,
+    security: Security<'a>,
+) -> Result<Agent<'a>, NaslValue> {
+    let port = match port {
+        Some(x) => {
+            verify_port(x).map_err(|_| error_array(INVALID_ARGUMENT, "Invalid port value"))?
+        }
+        None => DEFAULT_PORT,
+    };
+    let protocol = Protocol::parse(protocol)
+        .ok_or_else(|| error_array(INVALID_ARGUMENT, "Invalid protocol value"))?;
+    Ok(Agent {
+        transport: Transport { protocol, port },
+        security,
+    })
+}
+
+/// Returns the variables below the OID, requested one by one with getnext.
+fn walk(
+    context: &Context,
+    port: Option<i64>,
+    protocol: Option<&str>,
+    security: Security,
+    oid: &str,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let Some(root) = normalize_oid(oid) else {
+        return Err(FunctionErrorKind::wrong_argument("oid", "OID", oid));
+    };
+    let mut agent = match agent(port, protocol, security) {
+        Ok(x) => x,
+        Err(_) => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "port or protocol",
+                "valid port and udp or tcp",
+                &format!("{port:?}, {protocol:?}"),
+            ))
+        }
+    };
+    let prefix = format!("{root}.");
+    let mut result = IndexMap::new();
+    let mut next = root.clone();
+    while result.len() < MAX_WALK {
+        // SNMPv1 agents signal the end of the MIB with noSuchName
+        let Ok(binding) = agent.request(context, PduType::GetNext, &next) else {
+            break;
+        };
+        if !binding.oid.starts_with(&prefix) || result.contains_key(&binding.oid) {
+            break;
+        }
+        next = binding.oid.clone();
+        result.insert(binding.oid, NaslValue::String(binding.value.to_string()));
+    }
+    Ok(NaslValue::Dict(result))
+}
+
+function_set! {
+    Snmp,
+    sync_stateful,
+    (
+        (Snmp::snmpv1_get, "snmpv1_get"),
+        (Snmp::snmpv1_getnext, "snmpv1_getnext"),
+        (Snmp::snmpv2c_get, "snmpv2c_get"),
+        (Snmp::snmpv2c_getnext, "snmpv2c_getnext"),
+        (Snmp::snmpv1_walk, "snmpv1_walk"),
+        (Snmp::snmpv2c_walk, "snmpv2c_walk"),
+        (Snmp::snmpv3_get, "snmpv3_get"),
+        (Snmp::snmpv3_getnext, "snmpv3_getnext"),
+        (Snmp::snmpv3_walk, "snmpv3_walk"),
+    )
+}
@@ -0,0 +1,306 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    io::Write,
+    net::{TcpListener, UdpSocket},
+    thread,
+};
+
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+use super::message::{encode_oid, Message, Pdu, PduType, Value, VarBind, Version};
+use super::read_message;
+use super::v3::{
+    AuthProtocol, Engine, PrivProtocol, ScopedData, ScopedPdu, SecurityParameters, Session, User,
+    V3Message,
+};
+
+/// Variables of the fake agent in the order of the MIB
+fn mib() -> Vec<(&'static str, Value)> {
+    vec![
+        (
+            "1.3.6.1.2.1.1.1.0",
+            Value::OctetString(b"Linux agent".to_vec()),
+        ),
+        (
+            "1.3.6.1.2.1.1.2.0",
+            Value::Oid("1.3.6.1.4.1.8072.3.2.10".to_string()),
+        ),
+        ("1.3.6.1.2.1.1.3.0", Value::TimeTicks(4200)),
+        ("1.3.6.1.2.1.2.1.0", Value::Integer(2)),
+    ]
+}
+
+/// Returns the response of an agent with the variables of [mib].
+///
+/// SNMPv1 agents answer with noSuchName for a missing variable, later versions with an
+/// exception.
+fn respond(request: &Pdu, v1: bool) -> Option<Pdu> {
+    let oid = encode_oid(&request.bindings.first()?.oid)?;
+    let mib = mib();
+    // the encoding of object identifiers preserves their order
+    let found = match request.pdu_type {
+        PduType::Get => mib.into_iter().find(|(x, _)| encode_oid(x).unwrap() == oid),
+        _ => mib.into_iter().find(|(x, _)| encode_oid(x).unwrap() > oid),
+    };
+    let mut response = Pdu {
+        pdu_type: PduType::Response,
+        ..request.clone()
+    };
+    match (found, v1) {
+        (Some((oid, value)), _) => {
+            response.bindings = vec![VarBind {
+                oid: oid.to_string(),
+                value,
+            }]
+        }
+        (None, true) => {
+            response.error_status = 2;
+            response.error_index = 1;
+        }
+        (None, false) => {
+            response.bindings[0].value = match request.pdu_type {
+                PduType::Get => Value::NoSuchObject,
+                _ => Value::EndOfMibView,
+            }
+        }
+    }
+    Some(response)
+}
+
+/// The engine of the fake agent
+fn engine() -> Engine {
+    Engine {
+        id: b"\x80\x00\x1f\x88\x04agent".to_vec(),
+        boots: 2,
+        time: 3600,
+    }
+}
+
+/// The only SNMPv3 user known to the fake agent
+fn v3_user() -> User<'static> {
+    User {
+        name: "admin",
+        auth: AuthProtocol::Sha1,
+        auth_password: "authpassword",
+        privacy: Some((PrivProtocol::Aes, "privpassword")),
+    }
+}
+
+/// Returns an unauthenticated report of the agent with the counter of the OID.
+fn report(id: i32, oid: &str) -> Vec<u8> {
+    V3Message {
+        id,
+        flags: 0,
+        security: SecurityParameters {
+            engine: engine(),
+            ..Default::default()
+        },
+        data: ScopedData::Plain(ScopedPdu {
+            context_engine_id: engine().id,
+            context_name: vec![],
+            pdu: Pdu {
+                pdu_type: PduType::Report,
+                request_id: id,
+                error_status: 0,
+                error_index: 0,
+                bindings: vec![VarBind {
+                    oid: oid.to_string(),
+                    value: Value::Counter32(1),
+                }],
+            },
+        }),
+    }
+    .encode()
+}
+
+/// Answers a request for the community "public" or of the user of [v3_user].
+fn answer(data: &[u8]) -> Option<Vec<u8>> {
+    if let Some((request, _)) = V3Message::decode(data) {
+        if request.security.engine.id.is_empty() {
+            // usmStatsUnknownEngineIDs
+            return Some(report(request.id, "1.3.6.1.6.3.15.1.1.4.0"));
+        }
+        let session = Session::new(&v3_user(), engine());
+        return match session.open(data) {
+            Ok((_, pdu)) => Some(session.seal(request.id, respond(&pdu, false)?)),
+            // usmStatsWrongDigests
+            Err(_) => Some(report(request.id, "1.3.6.1.6.3.15.1.1.5.0")),
+        };
+    }
+    let request = Message::decode(data)?;
+    if request.community != b"public" {
+        return None;
+    }
+    let response = Message {
+        pdu: respond(&request.pdu, request.version == Version::V1)?,
+        ..request
+    };
+    Some(response.encode())
+}
+
+fn udp_agent() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut buf = [0; 1500];
+        while let Ok((length, peer)) = socket.recv_from(&mut buf) {
+            if let Some(response) = answer(&buf[..length]) {
+                socket.send_to(&response, peer).unwrap();
+            }
+        }
+    });
+    port
+}
+
+fn tcp_agent() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            while let Ok(request) = read_message(&mut stream) {
+                if let Some(response) = answer(&request) {
+                    stream.write_all(&response).unwrap();
+                }
+            }
+        }
+    });
+    port
+}
+
+#[test]
+fn get() {
+    let port = udp_agent();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(r#"snmpv2c_get(port: {port}, community: "public", oid: ".1.3.6.1.2.1.1.1.0");"#),
+        NaslValue::Array(vec![
+            0.into(),
+            "1.3.6.1.2.1.1.1.0".into(),
+            "Linux agent".into(),
+        ]),
+    );
+    t.ok(
+        format!(r#"snmpv1_get(port: {port}, community: "public", oid: "1.3.6.1.2.1.1.3.0");"#),
+        NaslValue::Array(vec![0.into(), "1.3.6.1.2.1.1.3.0".into(), "4200".into()]),
+    );
+    t.ok(
+        format!(r#"snmpv2c_get(port: {port}, community: "public", oid: "1.3.6.1.2.1.1.9.0");"#),
+        NaslValue::Array(vec![
+            (-1).into(),
+            "No Such Object available on this agent at this OID".into(),
+        ]),
+    );
+    t.ok(
+        format!(r#"snmpv1_get(port: {port}, community: "public", oid: "1.3.6.1.2.1.1.9.0");"#),
+        NaslValue::Array(vec![(-1).into(), "noSuchName".into()]),
+    );
+    t.ok(
+        format!(r#"snmpv1_get(port: {port}, oid: "1.3.6.1.2.1.1.1.0");"#),
+        NaslValue::Array(vec![(-2).into(), "Missing function argument".into()]),
+    );
+    t.ok(
+        format!(
+            r#"snmpv1_get(port: {port}, protocol: "sctp", community: "public", oid: "1.3.6.1.2.1.1.1.0");"#
+        ),
+        NaslValue::Array(vec![(-2).into(), "Invalid protocol value".into()]),
+    );
+}
+
+#[test]
+fn getnext() {
+    let port = udp_agent();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(r#"snmpv1_getnext(port: {port}, community: "public", oid: "1.3.6.1.2.1.1");"#),
+        NaslValue::Array(vec![
+            0.into(),
+            "1.3.6.1.2.1.1.1.0".into(),
+            "Linux agent".into(),
+        ]),
+    );
+    // continues at the last returned OID
+    t.ok(
+        format!(r#"snmpv1_getnext(port: {port}, community: "public");"#),
+        NaslValue::Array(vec![
+            0.into(),
+            "1.3.6.1.2.1.1.2.0".into(),
+            "1.3.6.1.4.1.8072.3.2.10".into(),
+        ]),
+    );
+    t.ok(
+        format!(r#"snmpv2c_getnext(port: {port}, community: "public", oid: "1.3.6.1.2.1.2.1.0");"#),
+        NaslValue::Array(vec![
+            (-1).into(),
+            "No more variables left in this MIB View".into(),
+        ]),
+    );
+}
+
+#[test]
+fn walk() {
+    let udp = udp_agent();
+    let tcp = tcp_agent();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.run(format!(
+        r#"a = snmpv1_walk(port: {udp}, community: "public", oid: "1.3.6.1.2.1.1");"#
+    ));
+    t.ok("max_index(a);", 3);
+    t.ok(r#"a["1.3.6.1.2.1.1.3.0"];"#, "4200");
+    t.run(format!(
+        r#"b = snmpv2c_walk(port: {tcp}, protocol: "tcp", community: "public", oid: "1.3.6.1.2.1");"#
+    ));
+    t.ok("max_index(b);", 4);
+    t.ok(r#"b["1.3.6.1.2.1.2.1.0"];"#, "2");
+}
+
+#[test]
+fn v3() {
+    let port = udp_agent();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    let user = r#"username: "admin", authpass: "authpassword", authproto: "sha1""#;
+    let privacy = r#"privpass: "privpassword", privproto: "aes""#;
+    t.ok(
+        format!(r#"snmpv3_get(port: {port}, {user}, {privacy}, oid: "1.3.6.1.2.1.1.1.0");"#),
+        NaslValue::Array(vec![
+            0.into(),
+            "1.3.6.1.2.1.1.1.0".into(),
+            "Linux agent".into(),
+        ]),
+    );
+    t.ok(
+        format!(r#"snmpv3_getnext(port: {port}, {user}, {privacy}, oid: "1.3.6.1.2.1.1.3.0");"#),
+        NaslValue::Array(vec![0.into(), "1.3.6.1.2.1.2.1.0".into(), "2".into()]),
+    );
+    t.run(format!(
+        r#"a = snmpv3_walk(port: {port}, {user}, {privacy}, oid: "1.3.6.1.2.1.1");"#
+    ));
+    t.ok("max_index(a);", 3);
+    t.ok(
+        format!(
+            r#"snmpv3_get(port: {port}, username: "admin", authpass: "wrongpassword", authproto: "sha1", {privacy}, oid: "1.3.6.1.2.1.1.1.0");"#
+        ),
+        NaslValue::Array(vec![
+            (-1).into(),
+            "Authentication failure (wrong digest)".into(),
+        ]),
+    );
+    t.ok(
+        format!(r#"snmpv3_get(port: {port}, {user}, privproto: "aes", oid: "1.3.6.1.2.1.1.1.0");"#),
+        NaslValue::Array(vec![(-2).into(), "Missing privproto or privpass".into()]),
+    );
+    t.ok(
+        format!(
+            r#"snmpv3_get(port: {port}, username: "admin", authpass: "authpassword", authproto: "sha256", oid: "1.3.6.1.2.1.1.1.0");"#
+        ),
+        NaslValue::Array(vec![(-2).into(), "authproto should be md5 or sha1".into()]),
+    );
+}
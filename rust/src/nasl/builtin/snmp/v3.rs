@@ -0,0 +1,577 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Encoding and decoding of SNMPv3 messages with the user-based security model (RFC 3412,
+//! RFC 3414) and AES privacy (RFC 3826).
+
+use std::time::Instant;
+
+use aes::{
+    cipher::{
+        block_padding::{NoPadding, ZeroPadding},
+        BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit,
+    },
+    Aes128,
+};
+use cbc::{Decryptor, Encryptor};
+use des::Des;
+use digest::Digest;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+
+use super::super::asn1::der::{self, encode_integer, tlv, Element};
+use super::message::{Pdu, PduType, INTEGER, OCTET_STRING, SEQUENCE};
+use super::read_message;
+
+const VERSION: i64 = 3;
+/// Security model of the user-based security model
+const USM: i64 = 3;
+/// Largest message the scanner accepts
+const MAX_SIZE: i64 = 65507;
+/// Length of the truncated HMAC of HMAC-MD5-96 and HMAC-SHA-96
+const MAC_LENGTH: usize = 12;
+/// Number of password octets hashed into the key
+const PASSWORD_TO_KEY_LENGTH: usize = 1048576;
+/// Shortest password accepted by agents
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+const FLAG_AUTH: u8 = 0x01;
+const FLAG_PRIV: u8 = 0x02;
+const FLAG_REPORTABLE: u8 = 0x04;
+
+/// OID of the usmStatsNotInTimeWindows counter reported on an outdated engine time
+const NOT_IN_TIME_WINDOWS: &str = "1.3.6.1.6.3.15.1.1.2.0";
+
+/// Returns the reason of a report PDU of the agent.
+pub fn report_reason(pdu: &Pdu) -> &'static str {
+    match pdu.bindings.first().map(|x| x.oid.as_str()) {
+        Some("1.3.6.1.6.3.15.1.1.1.0") => "Unsupported security level",
+        Some(NOT_IN_TIME_WINDOWS) => "Not in time window",
+        Some("1.3.6.1.6.3.15.1.1.3.0") => "Unknown user name",
+        Some("1.3.6.1.6.3.15.1.1.4.0") => "Unknown engine ID",
+        Some("1.3.6.1.6.3.15.1.1.5.0") => "Authentication failure (wrong digest)",
+        Some("1.3.6.1.6.3.15.1.1.6.0") => "Decryption error",
+        _ => "Unknown report",
+    }
+}
+
+/// Returns true when the report asks to repeat the request with the engine time of the report.
+pub fn is_not_in_time_window(pdu: &Pdu) -> bool {
+    pdu.bindings
+        .first()
+        .is_some_and(|x| x.oid == NOT_IN_TIME_WINDOWS)
+}
+
+/// Hash function of the authentication
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthProtocol {
+    Md5,
+    Sha1,
+}
+
+impl AuthProtocol {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "md5" => Some(Self::Md5),
+            "sha1" | "sha" => Some(Self::Sha1),
+            _ => None,
+        }
+    }
+
+    /// Returns the key of the password localized to the engine as described in RFC 3414 A.2.
+    pub fn localize(&self, password: &[u8], engine_id: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Md5 => localize::<Md5>(password, engine_id),
+            Self::Sha1 => localize::<Sha1>(password, engine_id),
+        }
+    }
+
+    /// Returns the truncated HMAC of the message.
+    fn mac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut result = match self {
+            Self::Md5 => hmac::<Hmac<Md5>>(key, data),
+            Self::Sha1 => hmac::<Hmac<Sha1>>(key, data),
+        };
+        result.truncate(MAC_LENGTH);
+        result
+    }
+}
+
+fn localize<D: Digest>(password: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    let mut buf = [0; 64];
+    for block in 0..PASSWORD_TO_KEY_LENGTH / buf.len() {
+        for (i, x) in buf.iter_mut().enumerate() {
+            *x = password[(block * 64 + i) % password.len()];
+        }
+        hasher.update(buf);
+    }
+    let key = hasher.finalize();
+    let mut hasher = D::new();
+    hasher.update(&key);
+    hasher.update(engine_id);
+    hasher.update(&key);
+    hasher.finalize().to_vec()
+}
+
+fn hmac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Cipher of the privacy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivProtocol {
+    /// CBC-DES as described in RFC 3414 8.1
+    Des,
+    /// CFB128-AES-128 as described in RFC 3826
+    Aes,
+}
+
+impl PrivProtocol {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "des" => Some(Self::Des),
+            "aes" | "aes128" => Some(Self::Aes),
+            _ => None,
+        }
+    }
+
+    /// Returns the initialization vector of the privacy parameters.
+    fn iv(&self, key: &[u8], engine: &Engine, params: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Des if params.len() == 8 => Some(
+                key.get(8..16)?
+                    .iter()
+                    .zip(params)
+                    .map(|(a, b)| a ^ b)
+                    .collect(),
+            ),
+            Self::Aes if params.len() == 8 => {
+                let mut iv = (engine.boots as u32).to_be_bytes().to_vec();
+                iv.extend((engine.time as u32).to_be_bytes());
+                iv.extend(params);
+                Some(iv)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the encrypted data and the privacy parameters.
+    fn encrypt(&self, key: &[u8], engine: &Engine, data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let params = match self {
+            // the salt starts with the boots of the engine to be unique across reboots
+            Self::Des => {
+                let mut salt = (engine.boots as u32).to_be_bytes().to_vec();
+                salt.extend(rand::random::<u32>().to_be_bytes());
+                salt
+            }
+            Self::Aes => rand::random::<u64>().to_be_bytes().to_vec(),
+        };
+        let iv = self
+            .iv(key, engine, &params)
+            .expect("valid privacy parameters");
+        let encrypted = match self {
+            Self::Des => Encryptor::<Des>::new_from_slices(&key[..8], &iv)
+                .expect("valid key length")
+                .encrypt_padded_vec_mut::<ZeroPadding>(data),
+            Self::Aes => cfb128(&key[..16], &iv, data, true),
+        };
+        (encrypted, params)
+    }
+
+    fn decrypt(&self, key: &[u8], engine: &Engine, params: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+        let iv = self.iv(key, engine, params)?;
+        match self {
+            Self::Des => Decryptor::<Des>::new_from_slices(&key[..8], &iv)
+                .ok()?
+                .decrypt_padded_vec_mut::<NoPadding>(data)
+                .ok(),
+            Self::Aes => Some(cfb128(&key[..16], &iv, data, false)),
+        }
+    }
+}
+
+/// En- or decrypts the data with AES-128 in the CFB mode with 128 bit segments.
+fn cfb128(key: &[u8], iv: &[u8], data: &[u8], encrypt: bool) -> Vec<u8> {
+    let cipher = Aes128::new_from_slice(key).expect("valid key length");
+    let mut feedback = aes::Block::clone_from_slice(iv);
+    let mut result = Vec::with_capacity(data.len());
+    for chunk in data.chunks(16) {
+        let mut stream = feedback;
+        cipher.encrypt_block(&mut stream);
+        let output: Vec<u8> = chunk.iter().zip(stream).map(|(a, b)| a ^ b).collect();
+        let ciphertext = if encrypt { &output[..] } else { chunk };
+        // only a full block is fed back, a shorter one is the last
+        feedback[..ciphertext.len()].copy_from_slice(ciphertext);
+        result.extend(output);
+    }
+    result
+}
+
+/// The authoritative engine of a message, which is the agent for requests to it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Engine {
+    pub id: Vec<u8>,
+    pub boots: i64,
+    pub time: i64,
+}
+
+/// The security parameters of the user-based security model
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityParameters {
+    pub engine: Engine,
+    pub user: Vec<u8>,
+    pub auth: Vec<u8>,
+    pub privacy: Vec<u8>,
+}
+
+impl SecurityParameters {
+    fn encode(&self) -> Vec<u8> {
+        let mut result = tlv(OCTET_STRING, &self.engine.id);
+        result.extend(tlv(INTEGER, &encode_integer(self.engine.boots)));
+        result.extend(tlv(INTEGER, &encode_integer(self.engine.time)));
+        result.extend(tlv(OCTET_STRING, &self.user));
+        result.extend(tlv(OCTET_STRING, &self.auth));
+        result.extend(tlv(OCTET_STRING, &self.privacy));
+        tlv(SEQUENCE, &result)
+    }
+}
+
+/// The PDU and its context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedPdu {
+    pub context_engine_id: Vec<u8>,
+    pub context_name: Vec<u8>,
+    pub pdu: Pdu,
+}
+
+impl ScopedPdu {
+    fn encode(&self) -> Vec<u8> {
+        let mut result = tlv(OCTET_STRING, &self.context_engine_id);
+        result.extend(tlv(OCTET_STRING, &self.context_name));
+        result.extend(self.pdu.encode());
+        tlv(SEQUENCE, &result)
+    }
+
+    fn decode(element: &Element) -> Option<Self> {
+        let [context_engine_id, context_name, pdu] = element.children.as_slice() else {
+            return None;
+        };
+        Some(Self {
+            context_engine_id: context_engine_id.content.to_vec(),
+            context_name: context_name.content.to_vec(),
+            pdu: Pdu::decode(pdu)?,
+        })
+    }
+}
+
+/// The scoped PDU of a message, encrypted when the message uses privacy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopedData {
+    Plain(ScopedPdu),
+    Encrypted(Vec<u8>),
+}
+
+/// A SNMPv3 message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V3Message {
+    pub id: i32,
+    pub flags: u8,
+    pub security: SecurityParameters,
+    pub data: ScopedData,
+}
+
+impl V3Message {
+    /// Creates the unauthenticated request discovering the engine of the agent.
+    ///
+    /// The agent answers with a report containing its engine id, boots and time.
+    pub fn discovery(id: i32) -> Self {
+        Self {
+            id,
+            flags: FLAG_REPORTABLE,
+            security: SecurityParameters::default(),
+            data: ScopedData::Plain(ScopedPdu {
+                context_engine_id: vec![],
+                context_name: vec![],
+                pdu: Pdu {
+                    pdu_type: PduType::Get,
+                    request_id: id,
+                    error_status: 0,
+                    error_index: 0,
+                    bindings: vec![],
+                },
+            }),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut global = tlv(INTEGER, &encode_integer(self.id as i64));
+        global.extend(tlv(INTEGER, &encode_integer(MAX_SIZE)));
+        global.extend(tlv(OCTET_STRING, &[self.flags]));
+        global.extend(tlv(INTEGER, &encode_integer(USM)));
+        let mut message = tlv(INTEGER, &encode_integer(VERSION));
+        message.extend(tlv(SEQUENCE, &global));
+        message.extend(tlv(OCTET_STRING, &self.security.encode()));
+        message.extend(match &self.data {
+            ScopedData::Plain(x) => x.encode(),
+            ScopedData::Encrypted(x) => tlv(OCTET_STRING, x),
+        });
+        tlv(SEQUENCE, &message)
+    }
+
+    /// Decodes a message and returns it with the position of the authentication parameters
+    /// within the data, None when it is malformed or uses another security model.
+    pub fn decode(data: &[u8]) -> Option<(Self, std::ops::Range<usize>)> {
+        let elements = der::parse(data).ok()?;
+        let [message] = elements.as_slice() else {
+            return None;
+        };
+        let [version, global, security, scoped] = message.children.as_slice() else {
+            return None;
+        };
+        let [id, _, flags, model] = global.children.as_slice() else {
+            return None;
+        };
+        if der::integer(version.content)? != VERSION || der::integer(model.content)? != USM {
+            return None;
+        }
+        let [flags] = flags.content else {
+            return None;
+        };
+        let parameters = der::parse(security.content).ok()?;
+        let [parameters] = parameters.as_slice() else {
+            return None;
+        };
+        let [engine_id, boots, time, user, auth, privacy] = parameters.children.as_slice() else {
+            return None;
+        };
+        let start = security.offset + security.header_length + auth.offset + auth.header_length;
+        let data = match flags & FLAG_PRIV {
+            0 => ScopedData::Plain(ScopedPdu::decode(scoped)?),
+            _ => ScopedData::Encrypted(scoped.content.to_vec()),
+        };
+        Some((
+            Self {
+                id: i32::try_from(der::integer(id.content)?).ok()?,
+                flags: *flags,
+                security: SecurityParameters {
+                    engine: Engine {
+                        id: engine_id.content.to_vec(),
+                        boots: der::integer(boots.content)?,
+                        time: der::integer(time.content)?,
+                    },
+                    user: user.content.to_vec(),
+                    auth: auth.content.to_vec(),
+                    privacy: privacy.content.to_vec(),
+                },
+                data,
+            },
+            start..start + auth.content.len(),
+        ))
+    }
+}
+
+/// Credentials of a SNMPv3 user
+#[derive(Debug, Clone, Copy)]
+pub struct User<'a> {
+    pub name: &'a str,
+    pub auth: AuthProtocol,
+    pub auth_password: &'a str,
+    pub privacy: Option<(PrivProtocol, &'a str)>,
+}
+
+/// The keys of a user localized to the engine of an agent
+pub struct Session {
+    name: Vec<u8>,
+    auth: AuthProtocol,
+    auth_key: Vec<u8>,
+    privacy: Option<(PrivProtocol, Vec<u8>)>,
+    engine: Engine,
+    /// When the engine time was received
+    synchronized: Instant,
+}
+
+impl Session {
+    /// Creates the session of the user with the discovered engine.
+    pub fn new(user: &User, engine: Engine) -> Self {
+        Self {
+            name: user.name.as_bytes().to_vec(),
+            auth: user.auth,
+            auth_key: user
+                .auth
+                .localize(user.auth_password.as_bytes(), &engine.id),
+            privacy: user.privacy.map(|(protocol, password)| {
+                (
+                    protocol,
+                    user.auth.localize(password.as_bytes(), &engine.id),
+                )
+            }),
+            engine,
+            synchronized: Instant::now(),
+        }
+    }
+
+    /// Sets the boots and time of the engine, e.g. of a report of an outdated time.
+    pub fn synchronize(&mut self, engine: &Engine) {
+        self.engine.boots = engine.boots;
+        self.engine.time = engine.time;
+        self.synchronized = Instant::now();
+    }
+
+    /// Returns the engine with its estimated current time.
+    fn engine(&self) -> Engine {
+        Engine {
+            time: self.engine.time + self.synchronized.elapsed().as_secs() as i64,
+            ..self.engine.clone()
+        }
+    }
+
+    /// Returns the authenticated and, when configured, encrypted message of the PDU.
+    pub fn seal(&self, id: i32, pdu: Pdu) -> Vec<u8> {
+        let engine = self.engine();
+        let scoped = ScopedPdu {
+            context_engine_id: engine.id.clone(),
+            context_name: vec![],
+            pdu,
+        };
+        let mut flags = FLAG_AUTH;
+        if matches!(scoped.pdu.pdu_type, PduType::Get | PduType::GetNext) {
+            flags |= FLAG_REPORTABLE;
+        }
+        let (data, privacy) = match &self.privacy {
+            Some((protocol, key)) => {
+                flags |= FLAG_PRIV;
+                let (encrypted, params) = protocol.encrypt(key, &engine, &scoped.encode());
+                (ScopedData::Encrypted(encrypted), params)
+            }
+            None => (ScopedData::Plain(scoped), vec![]),
+        };
+        let mut message = V3Message {
+            id,
+            flags,
+            security: SecurityParameters {
+                engine,
+                user: self.name.clone(),
+                auth: vec![0; MAC_LENGTH],
+                privacy,
+            },
+            data,
+        };
+        // the HMAC is calculated over the message with zeroed authentication parameters
+        let mac = self.auth.mac(&self.auth_key, &message.encode());
+        message.security.auth = mac;
+        message.encode()
+    }
+
+    /// Verifies and decrypts the message and returns its PDU.
+    ///
+    /// Unauthenticated messages are only accepted when they contain a report.
+    pub fn open(&self, data: &[u8]) -> Result<(Engine, Pdu), &'static str> {
+        let (message, auth) = V3Message::decode(data).ok_or("Invalid response")?;
+        let engine = message.security.engine.clone();
+        if message.flags & FLAG_AUTH != 0 {
+            if message.security.engine.id != self.engine.id || message.security.user != self.name {
+                return Err("Invalid response");
+            }
+            let mut zeroed = data.to_vec();
+            zeroed[auth].fill(0);
+            if self.auth.mac(&self.auth_key, &zeroed) != message.security.auth {
+                return Err("Authentication failure (wrong digest)");
+            }
+        }
+        let scoped = match (message.data, &self.privacy) {
+            (ScopedData::Plain(x), _) => x,
+            (ScopedData::Encrypted(x), Some((protocol, key))) => {
+                let decrypted = protocol
+                    .decrypt(key, &engine, &message.security.privacy, &x)
+                    .ok_or("Decryption error")?;
+                // the padding of DES follows the scoped PDU
+                let decrypted =
+                    read_message(&mut decrypted.as_slice()).map_err(|_| "Decryption error")?;
+                let elements = der::parse(&decrypted).map_err(|_| "Decryption error")?;
+                elements
+                    .first()
+                    .and_then(ScopedPdu::decode)
+                    .ok_or("Decryption error")?
+            }
+            (ScopedData::Encrypted(_), None) => return Err("Invalid response"),
+        };
+        if message.flags & FLAG_AUTH == 0 && scoped.pdu.pdu_type != PduType::Report {
+            return Err("Unauthenticated response");
+        }
+        Ok((engine, scoped.pdu))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::message::{Value, VarBind};
+    use super::*;
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|x| format!("{x:02x}")).collect()
+    }
+
+    #[test]
+    fn localize_keys() {
+        // RFC 3414 A.3
+        let engine_id = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        assert_eq!(
+            hex(&AuthProtocol::Md5.localize(b"maplesyrup", &engine_id)),
+            "526f5eed9fcce26f8964c2930787d82b"
+        );
+        assert_eq!(
+            hex(&AuthProtocol::Sha1.localize(b"maplesyrup", &engine_id)),
+            "6695febc9288e36282235fc7151f128497b38f3f"
+        );
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let engine = Engine {
+            id: b"\x80\x00\x1f\x88\x04agent".to_vec(),
+            boots: 3,
+            time: 1200,
+        };
+        let pdu = Pdu {
+            pdu_type: PduType::Response,
+            request_id: 7,
+            error_status: 0,
+            error_index: 0,
+            bindings: vec![VarBind {
+                oid: "1.3.6.1.2.1.1.1.0".to_string(),
+                value: Value::OctetString(b"Linux agent".to_vec()),
+            }],
+        };
+        for privacy in [None, Some(PrivProtocol::Des), Some(PrivProtocol::Aes)] {
+            let user = User {
+                name: "admin",
+                auth: AuthProtocol::Sha1,
+                auth_password: "authpassword",
+                privacy: privacy.map(|x| (x, "privpassword")),
+            };
+            let session = Session::new(&user, engine.clone());
+            let mut sealed = session.seal(1, pdu.clone());
+            let (received, opened) = session.open(&sealed).unwrap();
+            assert_eq!(received.id, engine.id);
+            assert_eq!(opened, pdu);
+            let last = sealed.len() - 1;
+            sealed[last] ^= 1;
+            assert!(session.open(&sealed).is_err());
+        }
+    }
+
+    #[test]
+    fn cfb128_partial_block() {
+        let key = [1; 16];
+        let iv = [2; 16];
+        let data = b"a scoped pdu longer than one block";
+        let encrypted = cfb128(&key, &iv, data, true);
+        assert_eq!(encrypted.len(), data.len());
+        assert_eq!(cfb128(&key, &iv, &encrypted, false), data);
+    }
+}
@@ -0,0 +1,322 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Runs commands with elevated privileges via sudo, su or doas.
+//!
+//! The command is run in a pty, so the password prompt of the elevation tool can be answered.
+//! The output of the command is separated from prompts and messages of the tool by a marker
+//! the wrapped command prints first. When the marker is missing the tool rejected the elevation
+//! and the reason is determined from its messages.
+//!
+//! Running the command is left to the SSH backends, this module builds the command and
+//! interprets its output.
+
+use std::{fmt::Display, str::FromStr};
+
+/// Printed by the wrapped command before its output
+const MARKER: &str = "__OPENVAS_ELEVATED__";
+/// Prompt of sudo, so it is not mistaken for a prompt of the command
+const SUDO_PROMPT: &str = "openvas-sudo-password:";
+
+/// Tool used to elevate the privileges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElevationMethod {
+    Sudo,
+    Su,
+    Doas,
+}
+
+impl FromStr for ElevationMethod {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sudo" => Ok(Self::Sudo),
+            "su" => Ok(Self::Su),
+            "doas" => Ok(Self::Doas),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for ElevationMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sudo => write!(f, "sudo"),
+            Self::Su => write!(f, "su"),
+            Self::Doas => write!(f, "doas"),
+        }
+    }
+}
+
+/// Why the privileges could not be elevated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElevationError {
+    /// The tool is not installed
+    NotAvailable,
+    /// The user is not allowed to elevate, e.g. not in the sudoers file
+    NotPermitted,
+    /// The password was rejected
+    WrongPassword,
+    /// A password was requested but none is configured
+    PasswordRequired,
+    /// Neither the output nor a prompt arrived in time
+    Timeout,
+}
+
+impl ElevationError {
+    /// Returns the identifier of the error returned to NASL scripts, e.g. wrong_password.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotAvailable => "not_available",
+            Self::NotPermitted => "not_permitted",
+            Self::WrongPassword => "wrong_password",
+            Self::PasswordRequired => "password_required",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+impl Display for ElevationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAvailable => write!(f, "the elevation tool is not installed"),
+            Self::NotPermitted => write!(f, "the user is not allowed to elevate privileges"),
+            Self::WrongPassword => write!(f, "the elevation password was rejected"),
+            Self::PasswordRequired => write!(f, "a password is required to elevate privileges"),
+            Self::Timeout => write!(f, "the elevation timed out"),
+        }
+    }
+}
+
+/// Privilege elevation configured for an SSH session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Elevation {
+    pub method: ElevationMethod,
+    /// User to run the commands as, root by default
+    pub user: Option<String>,
+    /// Password answered to the prompt of the tool
+    pub password: Option<String>,
+}
+
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+impl Elevation {
+    /// Returns the command running cmd with elevated privileges.
+    pub fn command(&self, cmd: &str) -> String {
+        let script = quote(&format!("echo {MARKER}; {cmd}"));
+        let user = self.user.as_deref();
+        match self.method {
+            ElevationMethod::Sudo => match user {
+                Some(user) => format!(
+                    "LC_ALL=C sudo -p {SUDO_PROMPT} -u {} sh -c {script}",
+                    quote(user)
+                ),
+                None => format!("LC_ALL=C sudo -p {SUDO_PROMPT} sh -c {script}"),
+            },
+            ElevationMethod::Su => {
+                format!("LC_ALL=C su {} -c {script}", quote(user.unwrap_or("root")))
+            }
+            ElevationMethod::Doas => match user {
+                Some(user) => format!("LC_ALL=C doas -u {} sh -c {script}", quote(user)),
+                None => format!("LC_ALL=C doas sh -c {script}"),
+            },
+        }
+    }
+}
+
+/// Returns true when the output ends with a password prompt.
+fn is_prompt(output: &str) -> bool {
+    let last = output.rsplit('\n').next().unwrap_or_default().trim_end();
+    last.ends_with(SUDO_PROMPT) || last.to_lowercase().ends_with("password:")
+}
+
+/// Returns the output of the command following the marker, None while it was not printed.
+fn after_marker(output: &str) -> Option<&str> {
+    let (_, rest) = output.split_once(MARKER)?;
+    Some(
+        rest.strip_prefix("\r\n")
+            .or(rest.strip_prefix('\n'))
+            .unwrap_or(rest),
+    )
+}
+
+/// Determines why the elevation failed from the messages of the tool.
+fn classify(output: &str, prompts: usize) -> ElevationError {
+    let output = output.to_lowercase();
+    let contains = |xs: &[&str]| xs.iter().any(|x| output.contains(x));
+    if contains(&[
+        "incorrect password",
+        "sorry, try again",
+        "authentication failure",
+        "authentication failed",
+    ]) || prompts > 1
+    {
+        ElevationError::WrongPassword
+    } else if contains(&[
+        "not in the sudoers",
+        "not allowed to",
+        "operation not permitted",
+        "permission denied",
+        "must be run from a terminal",
+    ]) {
+        ElevationError::NotPermitted
+    } else if contains(&["not found", "no such file"]) {
+        ElevationError::NotAvailable
+    } else if contains(&["password is required"]) || prompts > 0 {
+        ElevationError::PasswordRequired
+    } else {
+        ElevationError::NotPermitted
+    }
+}
+
+/// What to do next with the channel of an elevated command
+#[derive(Debug, PartialEq, Eq)]
+pub enum Step<'a> {
+    /// Wait for more output
+    Read,
+    /// Write the password followed by a newline
+    Answer(&'a str),
+    /// The output of the command or why the elevation failed
+    Done(Result<String, ElevationError>),
+}
+
+/// Collects the output of an elevated command and answers the password prompt once, a second
+/// prompt means the password was rejected.
+#[derive(Debug, Default)]
+pub struct ElevatedOutput {
+    output: Vec<u8>,
+    prompts: usize,
+}
+
+impl ElevatedOutput {
+    /// Adds the data read from the channel, eof is set when the channel has no more data.
+    pub fn step<'a>(&mut self, data: &[u8], eof: bool, password: Option<&'a str>) -> Step<'a> {
+        self.output.extend_from_slice(data);
+        let text = String::from_utf8_lossy(&self.output).into_owned();
+        if text.contains(MARKER) {
+            return match eof {
+                true => Step::Done(Ok(after_marker(&text).unwrap_or_default().to_string())),
+                false => Step::Read,
+            };
+        }
+        if eof {
+            return Step::Done(Err(classify(&text, self.prompts)));
+        }
+        if !is_prompt(&text) {
+            return Step::Read;
+        }
+        self.prompts += 1;
+        match password {
+            Some(password) if self.prompts == 1 => {
+                // only prompts following the answer are taken into account
+                self.output.clear();
+                Step::Answer(password)
+            }
+            _ => Step::Done(Err(classify(&text, self.prompts))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command() {
+        let elevation = Elevation {
+            method: ElevationMethod::Sudo,
+            user: None,
+            password: None,
+        };
+        assert_eq!(
+            elevation.command("cat '/etc/shadow'"),
+            format!(
+                r#"LC_ALL=C sudo -p {SUDO_PROMPT} sh -c 'echo {MARKER}; cat '\''/etc/shadow'\'''"#
+            )
+        );
+        let elevation = Elevation {
+            method: ElevationMethod::Su,
+            user: Some("admin".into()),
+            password: None,
+        };
+        assert_eq!(
+            elevation.command("id"),
+            format!("LC_ALL=C su 'admin' -c 'echo {MARKER}; id'")
+        );
+    }
+
+    #[test]
+    fn output() {
+        assert!(is_prompt("doas (scan@host) password: "));
+        assert!(is_prompt(&format!("\r\n{SUDO_PROMPT}")));
+        assert!(!is_prompt("Password: 1234\r\n"));
+        assert_eq!(
+            after_marker(&format!("\r\n{MARKER}\r\nroot\r\n")),
+            Some("root\r\n")
+        );
+        assert_eq!(after_marker("Sorry, try again."), None);
+    }
+
+    #[test]
+    fn answer_prompt_once() {
+        let mut output = ElevatedOutput::default();
+        assert_eq!(output.step(b"[sudo] ", false, Some("secret")), Step::Read);
+        assert_eq!(
+            output.step(SUDO_PROMPT.as_bytes(), false, Some("secret")),
+            Step::Answer("secret")
+        );
+        assert_eq!(
+            output.step(format!("\r\n{MARKER}\r\nroot").as_bytes(), false, None),
+            Step::Read
+        );
+        assert_eq!(
+            output.step(b"\r\n", true, None),
+            Step::Done(Ok("root\r\n".to_string()))
+        );
+
+        let mut output = ElevatedOutput::default();
+        output.step(SUDO_PROMPT.as_bytes(), false, Some("wrong"));
+        assert_eq!(
+            output.step(
+                format!("Sorry, try again.\r\n{SUDO_PROMPT}").as_bytes(),
+                false,
+                Some("wrong")
+            ),
+            Step::Done(Err(ElevationError::WrongPassword))
+        );
+        assert_eq!(
+            ElevatedOutput::default().step(b"Password: ", false, None),
+            Step::Done(Err(ElevationError::PasswordRequired))
+        );
+    }
+
+    #[test]
+    fn classify_failures() {
+        assert_eq!(
+            classify("Sorry, try again.\r\n", 1),
+            ElevationError::WrongPassword
+        );
+        assert_eq!(
+            classify("su: Authentication failure\r\n", 1),
+            ElevationError::WrongPassword
+        );
+        assert_eq!(classify("", 2), ElevationError::WrongPassword);
+        assert_eq!(
+            classify("scan is not in the sudoers file.\r\n", 1),
+            ElevationError::NotPermitted
+        );
+        assert_eq!(
+            classify("doas: Operation not permitted\r\n", 0),
+            ElevationError::NotPermitted
+        );
+        assert_eq!(
+            classify("sh: 1: doas: not found\r\n", 0),
+            ElevationError::NotAvailable
+        );
+        assert_eq!(classify("Password: ", 1), ElevationError::PasswordRequired);
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Runs commands with elevated privileges on a libssh channel.
+
+use std::{
+    fmt::Display,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use libssh_rs::Channel;
+
+use crate::nasl::builtin::ssh::elevation::{ElevatedOutput, Elevation, ElevationError, Step};
+use crate::nasl::{syntax::NaslValue, utils::error::FunctionErrorKind};
+
+use super::sessions::SshSession;
+
+/// Interval in which the output is checked for a password prompt
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+fn write_password(channel: &Channel, password: &str) -> std::io::Result<()> {
+    let mut stdin = channel.stdin();
+    stdin.write_all(password.as_bytes())?;
+    stdin.write_all(b"\n")?;
+    stdin.flush()
+}
+
+/// Runs the command with elevated privileges and collects its output.
+///
+/// Returns the classified error when the privileges could not be elevated.
+pub fn exec_ssh_cmd_elevated(
+    session: &SshSession,
+    elevation: &Elevation,
+    cmd: &str,
+    timeout: Duration,
+) -> Result<Result<String, ElevationError>, FunctionErrorKind> {
+    let error = |e: &dyn Display| {
+        FunctionErrorKind::Diagnostic(
+            format!(
+                "Channel failed to exec elevated command for session ID {}: {}",
+                session.session_id, e
+            ),
+            Some(NaslValue::Null),
+        )
+    };
+    let channel = session.session.new_channel().map_err(|e| error(&e))?;
+    channel.open_session().map_err(|e| error(&e))?;
+    // the tools only read the password from a terminal
+    channel
+        .request_pty("xterm", 80, 24)
+        .map_err(|e| error(&e))?;
+    channel
+        .request_exec(&elevation.command(cmd))
+        .map_err(|e| error(&e))?;
+
+    let start = Instant::now();
+    let mut output = ElevatedOutput::default();
+    let mut buf: [u8; 4096] = [0; 4096];
+    let result = loop {
+        if start.elapsed() > timeout {
+            break Err(ElevationError::Timeout);
+        }
+        let n = match channel.read_timeout(&mut buf, false, Some(POLL_INTERVAL)) {
+            Ok(n) => n,
+            Err(libssh_rs::Error::TryAgain) => 0,
+            Err(e) => return Err(error(&e)),
+        };
+        let eof = n == 0 && channel.is_eof();
+        match output.step(&buf[..n], eof, elevation.password.as_deref()) {
+            Step::Read => {}
+            Step::Answer(password) => write_password(&channel, password).map_err(|e| error(&e))?,
+            Step::Done(result) => break result,
+        }
+    };
+    let _ = channel.close();
+    Ok(result)
+}
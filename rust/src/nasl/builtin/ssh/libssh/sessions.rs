@@ -6,6 +6,8 @@
 
 use libssh_rs::{AuthMethods, Channel, Session};
 
+use crate::nasl::builtin::ssh::elevation::{Elevation, ElevationError};
+
 /// Structure to hold an SSH Session
 pub struct SshSession {
     /// Session ID
@@ -20,6 +22,10 @@ pub struct SshSession {
     pub user_set: bool,
     /// Channel
     pub channel: Option<Channel>,
+    /// Privilege elevation of commands
+    pub elevation: Option<Elevation>,
+    /// Why the last elevation failed
+    pub elevation_error: Option<ElevationError>,
 }
 
 impl Default for SshSession {
@@ -32,6 +38,8 @@ impl Default for SshSession {
                 authmethods_valid: false,
                 user_set: false,
                 channel: None,
+                elevation: None,
+                elevation_error: None,
             }
         }
     }
@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Reads files and directories of the target via SFTP or SCP on a libssh session.
+
+use std::{
+    io::{Read, Write},
+    time::UNIX_EPOCH,
+};
+
+use libssh_rs::{Channel, FileType, Metadata, Session};
+
+use crate::nasl::builtin::ssh::{
+    elevation::quote,
+    transfer::{Entry, ScpSink, ScpStep, TransferError, SCP_TIMEOUT},
+};
+use crate::nasl::syntax::NaslValue;
+
+impl From<libssh_rs::Error> for TransferError {
+    fn from(value: libssh_rs::Error) -> Self {
+        Self::Ssh(value.to_string())
+    }
+}
+
+/// Reads a file via SFTP.
+pub fn sftp_read_file(
+    session: &Session,
+    path: &str,
+    max_size: u64,
+) -> Result<Vec<u8>, TransferError> {
+    let sftp = session.sftp()?;
+    let file = sftp.open(path, libc::O_RDONLY, 0)?;
+    let mut data = vec![];
+    file.take(max_size + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > max_size {
+        return Err(TransferError::TooLarge(max_size));
+    }
+    Ok(data)
+}
+
+fn entry(metadata: &Metadata) -> Entry {
+    Entry {
+        name: metadata.name().unwrap_or_default().to_string(),
+        kind: match metadata.file_type() {
+            Some(FileType::Regular) => "file",
+            Some(FileType::Directory) => "directory",
+            Some(FileType::Symlink) => "symlink",
+            Some(FileType::Special) => "special",
+            _ => "unknown",
+        },
+        size: metadata.len(),
+        permissions: metadata.permissions(),
+        owner: metadata.owner().map(str::to_string),
+        group: metadata.group().map(str::to_string),
+        mtime: metadata
+            .modified()
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .map(|x| x.as_secs()),
+    }
+}
+
+/// Lists a directory via SFTP, without the `.` and `..` entries.
+pub fn sftp_list_dir(session: &Session, path: &str) -> Result<Vec<NaslValue>, TransferError> {
+    let sftp = session.sftp()?;
+    Ok(sftp
+        .read_dir(path)?
+        .iter()
+        .filter(|x| !matches!(x.name(), Some(".") | Some("..")))
+        .map(|x| entry(x).into())
+        .collect())
+}
+
+fn ack(channel: &Channel) -> Result<(), TransferError> {
+    let mut stdin = channel.stdin();
+    stdin.write_all(&[0])?;
+    stdin.flush()?;
+    Ok(())
+}
+
+fn receive(channel: &Channel, max_size: u64) -> Result<Vec<u8>, TransferError> {
+    let mut sink = ScpSink::new(max_size);
+    let mut buf = [0; 16384];
+    ack(channel)?;
+    loop {
+        match sink.step()? {
+            ScpStep::Read => match channel.read_timeout(&mut buf, false, Some(SCP_TIMEOUT)) {
+                Ok(0) => return Err(TransferError::Protocol("connection closed".to_string())),
+                Ok(n) => sink.push(&buf[..n]),
+                Err(libssh_rs::Error::TryAgain) => {}
+                Err(e) => return Err(e.into()),
+            },
+            ScpStep::Ack => ack(channel)?,
+            ScpStep::Done(data) => {
+                ack(channel)?;
+                return Ok(data);
+            }
+        }
+    }
+}
+
+/// Downloads a file via SCP.
+pub fn scp_download(
+    session: &Session,
+    path: &str,
+    max_size: u64,
+) -> Result<Vec<u8>, TransferError> {
+    let channel = session.new_channel()?;
+    channel.open_session()?;
+    channel.request_exec(&format!("scp -f {}", quote(path)))?;
+    let result = receive(&channel, max_size);
+    let _ = channel.close();
+    result
+}
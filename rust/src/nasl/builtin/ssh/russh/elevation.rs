@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Runs commands with elevated privileges on a russh channel.
+
+use std::time::Duration;
+
+use russh::ChannelMsg;
+use tokio::time::{timeout_at, Instant};
+
+use crate::nasl::builtin::ssh::elevation::{ElevatedOutput, Elevation, ElevationError, Step};
+use crate::nasl::{syntax::NaslValue, utils::error::FunctionErrorKind};
+
+use super::session::SshSession;
+
+/// Runs the command with elevated privileges and collects its output.
+///
+/// Returns the classified error when the privileges could not be elevated.
+pub async fn exec_ssh_cmd_elevated(
+    session: &SshSession,
+    elevation: &Elevation,
+    cmd: &str,
+    timeout: Duration,
+) -> Result<Result<String, ElevationError>, FunctionErrorKind> {
+    let error = |e: russh::Error| {
+        FunctionErrorKind::Diagnostic(
+            format!(
+                "Channel failed to exec elevated command for session ID {}: {}",
+                session.session_id, e
+            ),
+            Some(NaslValue::Null),
+        )
+    };
+    let mut channel = session.handle.channel_open_session().await.map_err(error)?;
+    // the tools only read the password from a terminal
+    channel
+        .request_pty(false, "xterm", 80, 24, 0, 0, &[])
+        .await
+        .map_err(error)?;
+    channel
+        .exec(true, elevation.command(cmd))
+        .await
+        .map_err(error)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut output = ElevatedOutput::default();
+    let result = loop {
+        let (data, eof) = match timeout_at(deadline, channel.wait()).await {
+            Ok(Some(ChannelMsg::Data { data })) => (data.to_vec(), false),
+            Ok(Some(ChannelMsg::Eof | ChannelMsg::Close)) | Ok(None) => (vec![], true),
+            Ok(Some(_)) => continue,
+            Err(_) => break Err(ElevationError::Timeout),
+        };
+        match output.step(&data, eof, elevation.password.as_deref()) {
+            Step::Read => {}
+            Step::Answer(password) => channel
+                .data(format!("{password}\n").as_bytes())
+                .await
+                .map_err(error)?,
+            Step::Done(result) => break result,
+        }
+    };
+    let _ = channel.close().await;
+    Ok(result)
+}
@@ -0,0 +1,1566 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL ssh and sftp functions on russh
+//!
+//! The connection is opened like the other connections of a script, so proxies and the source
+//! binding of the scan apply. Host keys are accepted without verification and recorded for
+//! ssh_get_host_key.
+mod elevation;
+mod session;
+#[cfg(test)]
+mod tests;
+mod transfer;
+
+use std::{
+    fmt::Display,
+    io,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use md5::{Digest, Md5};
+use russh::{
+    cipher::{self, ALL_CIPHERS},
+    client::{self, AuthResult, KeyboardInteractiveAuthResponse},
+    keys::{
+        agent::client::AgentClient, decode_secret_key, Algorithm, PrivateKeyWithHashAlg, PublicKey,
+    },
+    Channel, ChannelMsg, Disconnect, MethodKind, Preferred,
+};
+use tokio::{net::TcpStream, sync::Mutex};
+use tracing::{debug, info};
+
+use crate::nasl::builtin::lsc::linux::{LinuxInventory, INVENTORY_SCRIPT};
+use crate::nasl::builtin::network::{
+    get_kb_item,
+    network_utils::{invalid_data, target_service},
+    socket::NaslSockets,
+    ssh_kex::{identification, MAX_PREAMBLE},
+    tcp::TcpConnection,
+};
+use crate::nasl::prelude::*;
+
+use super::elevation::{Elevation, ElevationMethod};
+use super::transfer::TransferError;
+use super::{
+    elevation_failed, kb_string, next_session_id, session_id_arg, transfer_args, ELEVATED_TIMEOUT,
+    GATHER_TIMEOUT,
+};
+use elevation::exec_ssh_cmd_elevated;
+use session::{method_names, Client, ServerInfo, SshSession};
+
+const SSH_PORT: u16 = 22;
+/// Time to wait for the next output of a command run by ssh_request_exec
+const EXEC_TIMEOUT: Duration = Duration::from_secs(15);
+/// Interval in which the received data is checked for the identification of the server
+const PEEK_INTERVAL: Duration = Duration::from_millis(10);
+/// Maximum number of prompt rounds of a keyboard-interactive authentication
+const MAX_PROMPT_ROUNDS: usize = 8;
+
+/// Returns the identification the server sends first, without consuming it.
+async fn server_identification(tcp: &TcpStream, timeout: Duration) -> io::Result<String> {
+    let mut buf = vec![0; MAX_PREAMBLE + 256];
+    let read = async {
+        let mut received = 0;
+        loop {
+            let n = tcp.peek(&mut buf).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            match identification(&buf[..n]).map_err(invalid_data)? {
+                Some((identification, _)) => return Ok(identification),
+                // peeking returns the same data until more arrives
+                None if n == received => tokio::time::sleep(PEEK_INTERVAL).await,
+                None => received = n,
+            }
+        }
+    };
+    tokio::time::timeout(timeout, read)
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+/// Returns the algorithms offered to the server, the given lists replace the defaults.
+///
+/// The ciphers are negotiated from one list for both directions, so the ciphers of both lists
+/// are offered.
+fn preferred(
+    key_types: &str,
+    csciphers: &str,
+    scciphers: &str,
+) -> Result<Preferred, FunctionErrorKind> {
+    let unsupported = |what: &str, list: &str| {
+        FunctionErrorKind::Diagnostic(
+            format!("Failed to set SSH {what} '{list}': no supported algorithm"),
+            Some(NaslValue::Number(0)),
+        )
+    };
+    let mut preferred = Preferred::default();
+    if !key_types.is_empty() {
+        let keys = key_types
+            .split(',')
+            .filter_map(|x| x.trim().parse::<Algorithm>().ok())
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Err(unsupported("key type", key_types));
+        }
+        preferred.key = keys.into();
+    }
+    let ciphers = format!("{csciphers},{scciphers}");
+    let ciphers = ciphers
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    if !ciphers.is_empty() {
+        let supported = ciphers
+            .iter()
+            .filter_map(|x| {
+                ALL_CIPHERS.iter().find(|c| {
+                    let name: &str = c.as_ref();
+                    name == *x
+                })
+            })
+            .map(|c| **c)
+            .collect::<Vec<cipher::Name>>();
+        if supported.is_empty() {
+            return Err(unsupported("ciphers", &ciphers.join(",")));
+        }
+        preferred.cipher = supported.into();
+    }
+    Ok(preferred)
+}
+
+/// Returns the MD5 fingerprint of the key, e.g. `aa:bb:...`.
+fn md5_fingerprint(key: &PublicKey) -> Option<String> {
+    let blob = key.to_bytes().ok()?;
+    Some(
+        Md5::digest(blob)
+            .iter()
+            .map(|x| format!("{x:02x}"))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+fn find_session(
+    sessions: &mut [SshSession],
+    session_id: i32,
+) -> Result<&mut SshSession, FunctionErrorKind> {
+    sessions
+        .iter_mut()
+        .find(|s| s.session_id == session_id)
+        .ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(
+                format!("Session ID {} not found", session_id),
+                Some(NaslValue::Null),
+            )
+        })
+}
+
+fn channel_error(session_id: i32, e: &dyn Display) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(
+        format!("Channel failed for session ID {}: {}", session_id, e),
+        Some(NaslValue::Null),
+    )
+}
+
+async fn open_channel(session: &SshSession) -> Result<Channel<client::Msg>, FunctionErrorKind> {
+    session
+        .handle
+        .channel_open_session()
+        .await
+        .map_err(|e| channel_error(session.session_id, &e))
+}
+
+/// Reads the output of the channel until it ends or no data arrives within the timeout. The
+/// data is passed with a flag whether it was written to stderr.
+///
+/// Returns false when the timeout elapsed. A zero timeout reads the data already received.
+async fn read_channel(
+    channel: &mut Channel<client::Msg>,
+    timeout: Duration,
+    mut f: impl FnMut(&[u8], bool),
+) -> bool {
+    loop {
+        match tokio::time::timeout(timeout, channel.wait()).await {
+            Ok(Some(ChannelMsg::Data { data })) => f(&data, false),
+            Ok(Some(ChannelMsg::ExtendedData { data, ext: 1 })) => f(&data, true),
+            Ok(Some(ChannelMsg::Eof | ChannelMsg::Close)) | Ok(None) => return true,
+            Ok(Some(_)) => {}
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Runs the command without a pty and collects its complete stdout, so it can be parsed.
+async fn exec_ssh_cmd_output(
+    session: &SshSession,
+    cmd: &str,
+    timeout: Duration,
+) -> Result<String, FunctionErrorKind> {
+    let mut channel = open_channel(session).await?;
+    channel
+        .exec(true, cmd)
+        .await
+        .map_err(|e| channel_error(session.session_id, &e))?;
+    let mut output = vec![];
+    let complete = read_channel(&mut channel, timeout, |data, stderr| {
+        if !stderr {
+            output.extend_from_slice(data)
+        }
+    })
+    .await;
+    let _ = channel.close().await;
+    if !complete {
+        return Err(channel_error(session.session_id, &"the command timed out"));
+    }
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Requests the authentication methods with the none method, once per session.
+async fn get_authmethods(session: &mut SshSession) -> Result<Vec<MethodKind>, FunctionErrorKind> {
+    if let Some(methods) = &session.authmethods {
+        return Ok(methods.clone());
+    }
+    let user = session.user.clone().unwrap_or_default();
+    let methods = match session.handle.authenticate_none(user).await {
+        Ok(AuthResult::Success) => {
+            info!("SSH authentication succeeded using the none method - should not happen; very old server?");
+            session.authenticated = true;
+            vec![MethodKind::None]
+        }
+        Ok(AuthResult::Failure {
+            remaining_methods, ..
+        }) => remaining_methods.iter().cloned().collect(),
+        Err(e) => {
+            return Err(FunctionErrorKind::Diagnostic(
+                format!(
+                    "Invalid SSH session for SessionID {}: {}",
+                    session.session_id, e
+                ),
+                Some(NaslValue::Null),
+            ))
+        }
+    };
+    session.authmethods = Some(methods.clone());
+    Ok(methods)
+}
+
+/// Answers the non-echo prompts of a keyboard-interactive authentication with the password.
+async fn keyboard_interactive(
+    handle: &mut client::Handle<Client>,
+    user: &str,
+    password: &str,
+) -> Result<bool, russh::Error> {
+    let mut response = handle
+        .authenticate_keyboard_interactive_start(user, None::<String>)
+        .await?;
+    for _ in 0..MAX_PROMPT_ROUNDS {
+        match response {
+            KeyboardInteractiveAuthResponse::Success => return Ok(true),
+            KeyboardInteractiveAuthResponse::Failure { .. } => return Ok(false),
+            KeyboardInteractiveAuthResponse::InfoRequest {
+                name,
+                instructions,
+                prompts,
+            } => {
+                debug!(name, instructions, "SSH keyboard-interactive");
+                let answers = prompts
+                    .iter()
+                    .map(|p| match p.echo {
+                        true => String::new(),
+                        false => password.to_string(),
+                    })
+                    .collect();
+                response = handle
+                    .authenticate_keyboard_interactive_respond(answers)
+                    .await?;
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Tries the keys of the SSH agent listening on the socket, SSH_AUTH_SOCK when it is empty.
+async fn agent_auth(handle: &mut client::Handle<Client>, user: &str, socket: &str) -> bool {
+    let agent = match socket {
+        "" => AgentClient::connect_env().await,
+        socket => AgentClient::connect_uds(socket).await,
+    };
+    let mut agent = match agent {
+        Ok(agent) => agent,
+        Err(e) => {
+            debug!(%e, "Failed to connect to the SSH agent");
+            return false;
+        }
+    };
+    let keys = agent.request_identities().await.unwrap_or_default();
+    let hash = handle
+        .best_supported_rsa_hash()
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+    for key in keys {
+        match handle
+            .authenticate_publickey_with(user, key, hash, &mut agent)
+            .await
+        {
+            Ok(result) if result.success() => return true,
+            Ok(_) => {}
+            Err(e) => debug!(%e, "SSH agent authentication failed."),
+        }
+    }
+    false
+}
+
+#[derive(Default)]
+pub struct Ssh {
+    sessions: Arc<Mutex<Vec<SshSession>>>,
+}
+
+impl Ssh {
+    /// Connect to the target host via TCP and setup an ssh
+    ///        connection.
+    ///
+    /// The port is taken from the named parameter "port", the KB item Services/ssh or defaults
+    /// to 22. A socket given in the named parameter "socket" is not reused, a new connection is
+    /// opened.
+    ///
+    /// On success an ssh session to the host has been established; the
+    /// caller may then run an authentication function.  If the connection
+    /// is no longer needed, ssh_disconnect may be used to disconnect and
+    /// close the socket.
+    ///
+    /// nasl named params:
+    ///
+    /// - socket Ignored, a new connection is opened.
+    ///
+    /// - port A non-standard port to connect to.
+    ///
+    /// - keytype List of the preferred server host key types. Example:
+    ///   "ssh-rsa,ssh-dss"
+    ///
+    /// - csciphers SSH client-to-server ciphers.
+    ///
+    /// - scciphers SSH server-to-client ciphers.
+    ///
+    /// - timeout Set a timeout for the connection in seconds, the network timeout of the scan by
+    ///   default.
+    ///
+    /// nasl return An integer to identify the ssh session. Zero on error.
+    async fn nasl_ssh_connect<'a>(
+        &self,
+        register: &Register,
+        ctx: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let named_string = |name| match register.named(name) {
+            Some(ContextType::Value(NaslValue::String(x))) => x.as_str(),
+            _ => "",
+        };
+        if let Some(ContextType::Value(NaslValue::Number(sock))) = register.named("socket") {
+            if *sock > 0 {
+                debug!(
+                    sock,
+                    "The socket is not reused for SSH, opening a new connection"
+                );
+            }
+        }
+        let port = match register.named("port") {
+            Some(ContextType::Value(NaslValue::Number(x))) if *x > 0 => Some(*x),
+            _ => match get_kb_item(ctx, "Services/ssh")? {
+                Some(NaslValue::Number(x)) => Some(x),
+                _ => None,
+            },
+        };
+        let timeout = match register.named("timeout") {
+            Some(ContextType::Value(NaslValue::Number(x))) => Some(*x),
+            _ => None,
+        };
+        let preferred = preferred(
+            named_string("keytype"),
+            named_string("csciphers"),
+            named_string("scciphers"),
+        )?;
+        let (addrs, port, timeout) = target_service(ctx, port, SSH_PORT, timeout)?;
+        let failed = |e: &dyn Display| {
+            FunctionErrorKind::Diagnostic(
+                format!(
+                    "Failed to connect to SSH server '{}' (port {}): {}",
+                    ctx.target(),
+                    port,
+                    e
+                ),
+                Some(NaslValue::Number(0)),
+            )
+        };
+
+        debug!(
+            "Connecting to SSH server '{}' (port {})",
+            ctx.target(),
+            port
+        );
+        let tcp = NaslSockets::connect_tcp(ctx, &addrs, port, None, timeout, None)
+            .and_then(TcpConnection::into_tcp)
+            .and_then(|tcp| {
+                tcp.set_nonblocking(true)?;
+                TcpStream::from_std(tcp)
+            })
+            .map_err(|e| failed(&e))?;
+        let server_banner = server_identification(&tcp, timeout)
+            .await
+            .map_err(|e| failed(&e))?;
+        let config = client::Config {
+            preferred,
+            ..Default::default()
+        };
+        let info = Arc::new(StdMutex::new(ServerInfo::default()));
+        let client = Client { info: info.clone() };
+        let handle = match tokio::time::timeout(
+            timeout,
+            client::connect_stream(Arc::new(config), tcp, client),
+        )
+        .await
+        {
+            Ok(Ok(handle)) => handle,
+            Ok(Err(e)) => return Err(failed(&e)),
+            Err(_) => return Err(failed(&"the key exchange timed out")),
+        };
+
+        let mut sessions = self.sessions.lock().await;
+        let session_id = next_session_id(sessions.iter().map(|x| x.session_id));
+        sessions.push(SshSession {
+            session_id,
+            handle,
+            info,
+            server_banner,
+            user: None,
+            authmethods: None,
+            authenticated: false,
+            prompts: None,
+            channel: None,
+            elevation: None,
+            elevation_error: None,
+        });
+        Ok(NaslValue::Number(session_id as i64))
+    }
+
+    /// Disconnect an ssh connection
+    ///
+    /// This function takes the ssh session id (as returned by ssh_connect)
+    /// as its only unnamed argument.  Passing 0 as session id is
+    /// explicitly allowed and does nothing.  If there are any open
+    /// channels they are closed as well and their ids will be marked as
+    /// invalid.
+    ///
+    /// nasl params
+    /// - An SSH session id.  A value of 0 is allowed and acts as a NOP.
+    async fn nasl_ssh_disconnect<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        if session_id == 0 {
+            return Ok(NaslValue::Null);
+        }
+        let mut sessions = self.sessions.lock().await;
+        let Some(i) = sessions.iter().position(|s| s.session_id == session_id) else {
+            return Err(FunctionErrorKind::Diagnostic(
+                format!("Session ID {} not found", session_id),
+                Some(NaslValue::Null),
+            ));
+        };
+        let session = sessions.remove(i);
+        if let Some(channel) = session.channel {
+            let _ = channel.close().await;
+        }
+        let _ = session
+            .handle
+            .disconnect(Disconnect::ByApplication, "", "")
+            .await;
+        Ok(NaslValue::Null)
+    }
+
+    /// Given a socket, return the corresponding session id.
+    /// nasl params
+    /// - A NASL socket value
+    ///
+    /// return An integer with the corresponding ssh session id or 0 if
+    ///          no session id is known for the given socket.
+    async fn nasl_ssh_session_id_from_sock<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        match register.positional().first() {
+            // sessions never use NASL sockets
+            Some(NaslValue::Number(_)) => Ok(NaslValue::Number(0)),
+            Some(_) => Err(FunctionErrorKind::WrongArgument(
+                ("Invalid socket FD").to_string(),
+            )),
+            None => Err(FunctionErrorKind::MissingPositionalArguments {
+                expected: 0,
+                got: 1,
+            }),
+        }
+    }
+
+    /// Given a session id, return the corresponding socket
+    ///
+    /// The connections of the sessions are not NASL sockets.
+    ///
+    /// nasl params
+    /// - An SSH session id.
+    ///
+    /// return -1 as there is no socket.
+    async fn nasl_ssh_get_sock<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        session_id_arg(register)?;
+        Ok(NaslValue::Number(-1))
+    }
+
+    /// Set the login name for the authentication.
+    ///
+    /// This is an optional function and usually not required.  However,
+    /// if you want to get the banner before starting the authentication,
+    /// you need to tell the user because getting the banner uses an
+    /// authentication function.
+    ///
+    /// Given that many servers don't allow changing the login for an established connection,
+    /// the "login" parameter is silently ignored on all further calls.
+    ///
+    /// nasl params
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    /// - login: A string with the login name.
+    async fn nasl_ssh_set_login<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let login = match register.named("login") {
+            Some(ContextType::Value(NaslValue::String(x))) => x.to_owned(),
+            _ => return Err(FunctionErrorKind::missing_argument("login")),
+        };
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        if session.user.is_none() {
+            session.user = Some(login);
+        }
+        Ok(NaslValue::Null)
+    }
+
+    /// Configures the privilege elevation of commands run with elevate set.
+    ///
+    /// nasl params
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    /// - method: sudo, su or doas.
+    /// - password: The password answered to the prompt of the tool (optional).
+    /// - user: The user to run the commands as, root by default (optional).
+    async fn nasl_ssh_set_elevation<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let get_named_val = |name| match register.named(name) {
+            Some(ContextType::Value(NaslValue::String(x))) => Ok(Some(x.as_str())),
+            None => Ok(None),
+            _ => Err(FunctionErrorKind::WrongArgument(format!(
+                "Invalid value for {}",
+                name
+            ))),
+        };
+        let method = get_named_val("method")?
+            .ok_or_else(|| FunctionErrorKind::missing_argument("method"))?;
+        let method = method
+            .parse::<ElevationMethod>()
+            .map_err(|_| FunctionErrorKind::wrong_argument("method", "sudo, su or doas", method))?;
+        let elevation = Elevation {
+            method,
+            user: get_named_val("user")?
+                .filter(|x| !x.is_empty())
+                .map(str::to_string),
+            password: get_named_val("password")?
+                .filter(|x| !x.is_empty())
+                .map(str::to_string),
+        };
+
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        session.elevation = Some(elevation);
+        session.elevation_error = None;
+        Ok(NaslValue::Null)
+    }
+
+    /// Returns why the last privilege elevation of the session failed.
+    ///
+    /// nasl params
+    /// - An SSH session id.
+    ///
+    /// return not_available, not_permitted, wrong_password, password_required or timeout, NULL
+    /// when the last elevation succeeded.
+    async fn nasl_ssh_get_elevation_error<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        Ok(session
+            .elevation_error
+            .map(|x| NaslValue::String(x.as_str().to_string()))
+            .unwrap_or(NaslValue::Null))
+    }
+
+    /// Authenticate a user on an ssh connection
+    ///
+    /// The function expects the session id as its first unnamed argument.
+    /// The first time this function is called for a session id, the named
+    /// argument "login" is also expected; it defaults the KB entry
+    /// "Secret/SSH/login".  It should contain the user name to login.
+    /// Given that many servers don't allow changing the login for an
+    /// established connection, the "login" parameter is silently ignored
+    /// on all further calls.
+    ///
+    /// To perform a password based authentication, the named argument
+    /// "password" must contain a password.
+    ///
+    /// To perform a public key based authentication, the named argument
+    /// "privatekey" must contain a private key in ssh native or in PKCS#8 format.
+    ///
+    /// If both, "password" and "privatekey" are given as named arguments
+    /// only "password" is used.  If neither are given the values are taken
+    /// from the KB ("Secret/SSH/password" and "Secret/SSH/privatekey") and
+    /// tried in the order {password, privatekey}.  Note well, that if one
+    /// of the named arguments are given, only those are used and the KB is
+    /// not consulted.
+    ///
+    /// If the private key is protected, its passphrase is taken from the
+    /// named argument "passphrase" or, if not given, taken from the KB
+    /// ("Secret/SSH/passphrase").
+    ///
+    /// The keys of a SSH agent are tried last when the named argument
+    /// "agent" or the KB item "Secret/SSH/agent" contains the path of the
+    /// agent socket.  An empty "agent" uses the socket in SSH_AUTH_SOCK.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - login: A string with the login name.
+    ///
+    /// - password: A string with the password.
+    ///
+    /// - privatekey: A private key in ssh native or in pkcs#8 format.  This parameter is
+    ///   ignored if password is given.
+    ///
+    /// - passphrase: A string with the passphrase used to unprotect privatekey.
+    ///
+    /// - agent: A string with the path of the SSH agent socket.
+    ///
+    /// return An integer as status value; 0 indicates success and -1 that
+    /// all methods failed.
+    async fn nasl_ssh_userauth<'a>(
+        &self,
+        register: &Register,
+        ctx: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let get_named_val = |name| match register.named(name) {
+            Some(ContextType::Value(NaslValue::String(x))) => Ok(Some(x.to_string())),
+            None => Ok(None),
+            _ => Err(FunctionErrorKind::WrongArgument(format!(
+                "Invalid value for {}",
+                name
+            ))),
+        };
+
+        let login = match get_named_val("login")? {
+            Some(x) => Some(x),
+            None => kb_string(ctx, "Secret/SSH/login")?,
+        };
+        let mut password = get_named_val("password")?;
+        let mut privatekey = get_named_val("privatekey")?;
+        let mut passphrase = get_named_val("passphrase")?;
+        let mut agent = get_named_val("agent")?;
+        if password.is_none() && privatekey.is_none() && passphrase.is_none() && agent.is_none() {
+            password = kb_string(ctx, "Secret/SSH/password")?;
+            privatekey = kb_string(ctx, "Secret/SSH/privatekey")?;
+            passphrase = kb_string(ctx, "Secret/SSH/passphrase")?;
+            agent = kb_string(ctx, "Secret/SSH/agent")?;
+        }
+        if password.is_none() && privatekey.is_none() && agent.is_none() {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "No SSH credentials given for SessionID {}",
+                session_id
+            )));
+        }
+
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        if session.user.is_none() {
+            session.user = login;
+        }
+        let Some(user) = session.user.clone() else {
+            return Err(FunctionErrorKind::Diagnostic(
+                format!("No SSH login given for SessionID {}", session_id),
+                Some(NaslValue::Number(-1)),
+            ));
+        };
+
+        let methods = get_authmethods(session).await?;
+        debug!("Available methods:\n{:?}", methods);
+        if session.authenticated {
+            return Ok(NaslValue::Number(0));
+        }
+        let auth_error = |e: russh::Error| {
+            FunctionErrorKind::Dirty(format!(
+                "Failed setting user authentication for SessionID {}: {}",
+                session_id, e
+            ))
+        };
+
+        /* Check whether a password has been given.  If so, try to
+        authenticate using that password.  Note that the OpenSSH client
+        uses a different order it first tries the public key and then the
+        password.  However, the old NASL SSH protocol implementation tries
+        the password before the public key authentication.  Because we
+        want to be compatible, we do it in that order. */
+        if let Some(password) = &password {
+            if methods.contains(&MethodKind::Password) {
+                let result = session
+                    .handle
+                    .authenticate_password(user.as_str(), password.as_str())
+                    .await
+                    .map_err(auth_error)?;
+                if result.success() {
+                    session.authenticated = true;
+                    return Ok(NaslValue::Number(0));
+                }
+                debug!(session_id, "SSH password authentication failed.");
+            }
+            if methods.contains(&MethodKind::KeyboardInteractive) {
+                if keyboard_interactive(&mut session.handle, &user, password)
+                    .await
+                    .map_err(auth_error)?
+                {
+                    session.authenticated = true;
+                    return Ok(NaslValue::Number(0));
+                }
+                debug!(
+                    session_id,
+                    "SSH keyboard-interactive authentication failed."
+                );
+            }
+        }
+
+        // If we have a private key, try public key authentication.
+        if let Some(privatekey) = &privatekey {
+            if methods.contains(&MethodKind::PublicKey) {
+                match decode_secret_key(privatekey, passphrase.as_deref()) {
+                    Ok(key) => {
+                        let hash = session
+                            .handle
+                            .best_supported_rsa_hash()
+                            .await
+                            .ok()
+                            .flatten()
+                            .flatten();
+                        let key = PrivateKeyWithHashAlg::new(Arc::new(key), hash);
+                        match session
+                            .handle
+                            .authenticate_publickey(user.as_str(), key)
+                            .await
+                        {
+                            Ok(result) if result.success() => {
+                                session.authenticated = true;
+                                return Ok(NaslValue::Number(0));
+                            }
+                            _ => {
+                                debug!(session_id, "SSH public key authentication failed.");
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        debug!(
+                            session_id,
+                            "SSH public key authentication failed: Error converting provided key"
+                        );
+                    }
+                }
+            }
+        }
+
+        // Try the keys of the agent, an empty socket path uses SSH_AUTH_SOCK.
+        if let Some(agent) = &agent {
+            if methods.contains(&MethodKind::PublicKey)
+                && agent_auth(&mut session.handle, &user, agent).await
+            {
+                session.authenticated = true;
+                return Ok(NaslValue::Number(0));
+            }
+        }
+        Ok(NaslValue::Number(-1))
+    }
+
+    /// Run a command via ssh.
+    ///
+    /// The function opens a channel to the remote end and ask it to
+    /// execute a command.  The output of the command is then returned as a
+    /// data block.  The first unnamed argument is the session id. The
+    /// command itself is expected as string in the named argument "cmd".
+    ///
+    /// Regarding the handling of the stderr and stdout stream, this
+    /// function may be used in different modes.
+    ///
+    /// If either the named arguments @a stdout or @a stderr are given and
+    /// that one is set to 1, only the output of the specified stream is
+    /// returned.
+    ///
+    /// If stdout and stderr are both given and set to 1, the output
+    /// of both is returned interleaved.
+    ///
+    /// If stdout and stderr are both given but set to 0, a special
+    /// backward compatibility mode is used: First all output to stderr is
+    /// collected up until any output to stdout is received.  Then all
+    /// output to stdout is returned while ignoring all further stderr
+    /// output; at EOF the initial collected data from stderr is returned.
+    ///
+    /// If the named parameters @a stdout and @a stderr are not given, the
+    /// function acts exactly as if only @a stdout has been set to 1.
+    ///
+    /// With the named argument elevate set the command is run with the privilege elevation
+    /// configured by ssh_set_elevation and only its output is returned.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - cmd: A string with the command to execute.
+    ///
+    /// - stdout: An integer with value 0 or 1; see above for a full
+    ///   description.
+    ///
+    /// - stderr: An integer with value 0 or 1; see above for a full
+    ///   description.
+    ///
+    /// - elevate: Run the command with elevated privileges.
+    ///
+    /// return A data block on success or NULL on error.
+    async fn nasl_ssh_request_exec<'a>(
+        &self,
+        register: &Register,
+        ctx: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let cmd = match register.named("cmd") {
+            Some(ContextType::Value(NaslValue::String(x))) => x,
+            _ => return Err(FunctionErrorKind::missing_argument("No command passed")),
+        };
+        let flag = |name| match register.named(name) {
+            Some(ContextType::Value(NaslValue::Number(x))) => Some(*x != 0),
+            _ => None,
+        };
+        let elevate = match register.named("elevate") {
+            Some(ContextType::Value(x)) => bool::from(x.clone()),
+            _ => false,
+        };
+        let (to_stdout, to_stderr, compat_mode) = match (flag("stdout"), flag("stderr")) {
+            (None, None) => (true, false, false),
+            (Some(false), Some(false)) => (true, false, true),
+            (stdout, stderr) => (
+                stdout.unwrap_or_default(),
+                stderr.unwrap_or_default(),
+                false,
+            ),
+        };
+
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        if cmd.is_empty() {
+            return Ok(NaslValue::Null);
+        }
+        if elevate {
+            let elevation = session.elevation.clone().ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(
+                    format!("No elevation set for session ID {}", session_id),
+                    Some(NaslValue::Null),
+                )
+            })?;
+            return match exec_ssh_cmd_elevated(session, &elevation, cmd, ELEVATED_TIMEOUT).await? {
+                Ok(output) => {
+                    session.elevation_error = None;
+                    Ok(NaslValue::String(output))
+                }
+                Err(e) => {
+                    session.elevation_error = Some(e);
+                    elevation_failed(ctx, session_id, Some(&elevation), e)?;
+                    Ok(NaslValue::Null)
+                }
+            };
+        }
+
+        let mut channel = open_channel(session).await?;
+        if let Err(e) = channel.request_pty(false, "xterm", 80, 24, 0, 0, &[]).await {
+            debug!(session_id, error=%e, "Channel failed to request pty for session ID");
+        }
+        channel
+            .exec(true, cmd.as_str())
+            .await
+            .map_err(|e| channel_error(session_id, &e))?;
+        let mut response = vec![];
+        let mut compat_buf = vec![];
+        let mut stdout_seen = false;
+        read_channel(&mut channel, EXEC_TIMEOUT, |data, stderr| match stderr {
+            false => {
+                stdout_seen = true;
+                if to_stdout {
+                    response.extend_from_slice(data);
+                }
+            }
+            true if compat_mode => {
+                if !stdout_seen {
+                    compat_buf.extend_from_slice(data);
+                }
+            }
+            true => {
+                if to_stderr {
+                    response.extend_from_slice(data);
+                }
+            }
+        })
+        .await;
+        let _ = channel.close().await;
+        response.extend(compat_buf);
+        Ok(NaslValue::String(
+            String::from_utf8_lossy(&response).into_owned(),
+        ))
+    }
+
+    /// Collects the release, installed packages, kernel and running services of a Linux or
+    /// Unix target in a single command and stores them in the KB.
+    ///
+    /// Besides the ssh/login KB items consumed by local security checks the release and package
+    /// list checked by the notus scanner are set and the operating system is registered as host
+    /// detail.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// return TRUE on success or NULL when the inventory could not be collected.
+    async fn nasl_ssh_gather_package_list<'a>(
+        &self,
+        register: &Register,
+        ctx: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        // some files like the package database of a few distributions are only
+        // readable by root, without elevation the inventory may be incomplete
+        let elevated = match session.elevation.clone() {
+            Some(elevation) => {
+                match exec_ssh_cmd_elevated(session, &elevation, INVENTORY_SCRIPT, GATHER_TIMEOUT)
+                    .await?
+                {
+                    Ok(output) => {
+                        session.elevation_error = None;
+                        Some(output)
+                    }
+                    Err(e) => {
+                        session.elevation_error = Some(e);
+                        elevation_failed(ctx, session_id, Some(&elevation), e)?;
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let output = match elevated {
+            Some(output) => output,
+            None => exec_ssh_cmd_output(session, INVENTORY_SCRIPT, GATHER_TIMEOUT).await?,
+        };
+        match LinuxInventory::parse(&output) {
+            Some(inventory) => {
+                inventory.store(ctx)?;
+                Ok(NaslValue::Boolean(true))
+            }
+            None => {
+                debug!(session_id, "unable to collect the inventory");
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Request an ssh shell.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - pty: To enable/disable the interactive shell. Default is 1 (interactive).
+    ///
+    /// @naslret An int on success or NULL on error.
+    async fn nasl_ssh_shell_open<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let pty = match register.named("pty") {
+            Some(ContextType::Value(x)) => bool::from(x.clone()),
+            _ => true,
+        };
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let channel = open_channel(session).await?;
+        if pty {
+            channel
+                .request_pty(false, "xterm", 80, 24, 0, 0, &[])
+                .await
+                .map_err(|e| channel_error(session_id, &e))?;
+        }
+        channel
+            .request_shell(true)
+            .await
+            .map_err(|e| channel_error(session_id, &e))?;
+        session.channel = Some(channel);
+        Ok(NaslValue::Number(session_id as i64))
+    }
+
+    /// Read the output of an ssh shell.
+    /// nasl params
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    /// - timeout: Enable the blocking ssh read until it gives the timeout or there is no
+    ///   bytes left to read.
+    ///
+    /// return A string on success or NULL on error.
+    async fn nasl_ssh_shell_read<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let timeout = match register.named("timeout") {
+            Some(ContextType::Value(NaslValue::Number(x))) if *x > 0 => {
+                Duration::from_secs(*x as u64)
+            }
+            _ => Duration::ZERO,
+        };
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let Some(channel) = &mut session.channel else {
+            return Ok(NaslValue::Null);
+        };
+        let mut response = vec![];
+        let eof = read_channel(channel, timeout, |data, _| response.extend_from_slice(data)).await;
+        if eof && response.is_empty() {
+            return Ok(NaslValue::Null);
+        }
+        Ok(NaslValue::String(
+            String::from_utf8_lossy(&response).into_owned(),
+        ))
+    }
+
+    /// Write string to ssh shell.
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - cmd: A string to write to shell.
+    ///
+    /// return An integer: 0 on success, -1 on failure.
+    async fn nasl_ssh_shell_write<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let cmd = match register.named("cmd") {
+            Some(ContextType::Value(NaslValue::String(x))) => x.as_bytes().to_vec(),
+            Some(ContextType::Value(NaslValue::Data(x))) => x.clone(),
+            _ => return Err(FunctionErrorKind::missing_argument("cmd")),
+        };
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let Some(channel) = &session.channel else {
+            return Ok(NaslValue::Null);
+        };
+        match channel.data(cmd.as_slice()).await {
+            Ok(_) => Ok(NaslValue::Number(0)),
+            Err(_) => Ok(NaslValue::Number(-1)),
+        }
+    }
+
+    /// Close an ssh shell.
+    ///
+    /// nasl params
+    /// - An SSH session id.
+    async fn nasl_ssh_shell_close<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        if let Some(channel) = session.channel.take() {
+            let _ = channel.close().await;
+        }
+        Ok(NaslValue::Null)
+    }
+
+    /// Authenticate a user on an ssh connection
+    ///
+    /// The function starts the authentication process and pauses it when
+    /// it finds the first non-echo prompt. The function expects the session
+    /// id as its first unnamed argument.
+    /// The first time this function is called for a session id, the named
+    /// argument "login" is also expected.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - login: A string with the login name.
+    ///
+    /// return A data block with the prompt on success or NULL on error.
+    async fn nasl_ssh_login_interactive<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let login = match register.named("login") {
+            Some(ContextType::Value(NaslValue::String(x))) => x.to_owned(),
+            _ => return Err(FunctionErrorKind::missing_argument("login")),
+        };
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let user = session.user.get_or_insert(login).clone();
+        let methods = get_authmethods(session).await?;
+        debug!("Available methods:\n{:?}", methods);
+        if !methods.contains(&MethodKind::KeyboardInteractive) {
+            return Ok(NaslValue::Null);
+        }
+        let response = session
+            .handle
+            .authenticate_keyboard_interactive_start(user, None::<String>)
+            .await
+            .map_err(|e| {
+                FunctionErrorKind::Dirty(format!(
+                    "Failed setting user authentication for SessionID {}: {}",
+                    session_id, e
+                ))
+            })?;
+        match response {
+            KeyboardInteractiveAuthResponse::InfoRequest {
+                name,
+                instructions,
+                prompts,
+            } => {
+                debug!(name, instructions, "SSH keyboard-interactive");
+                let prompt = prompts
+                    .iter()
+                    .rfind(|p| !p.echo)
+                    .map(|p| p.prompt.clone())
+                    .unwrap_or_default();
+                session.prompts = Some(prompts.iter().map(|p| p.echo).collect());
+                Ok(NaslValue::String(prompt))
+            }
+            _ => {
+                debug!(
+                    "SSH keyboard-interactive authentication failed for session {}",
+                    session_id
+                );
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Authenticate a user on an ssh connection
+    ///
+    /// The function finishes the authentication process started by
+    /// ssh_login_interactive. The function expects the session id as its first
+    /// unnamed argument.
+    ///
+    /// To finish the password, the named argument "pass" must contain
+    /// a password.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - pass: A string with the password.
+    ///
+    /// return An integer as status value; 0 indicates success.
+    async fn nasl_ssh_login_interactive_pass<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let password = match register.named("pass") {
+            Some(ContextType::Value(NaslValue::String(x))) => x,
+            _ => return Err(FunctionErrorKind::missing_argument("pass")),
+        };
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let Some(prompts) = session.prompts.take() else {
+            return Err(FunctionErrorKind::Diagnostic(
+                format!(
+                    "No keyboard-interactive authentication started for SessionID {}",
+                    session_id
+                ),
+                Some(NaslValue::Number(-1)),
+            ));
+        };
+        let mut answers: Vec<String> = prompts
+            .iter()
+            .map(|echo| match echo {
+                true => String::new(),
+                false => password.to_string(),
+            })
+            .collect();
+        // once answered, servers may send further requests without prompts to finish
+        for _ in 0..MAX_PROMPT_ROUNDS {
+            match session
+                .handle
+                .authenticate_keyboard_interactive_respond(answers)
+                .await
+            {
+                Ok(KeyboardInteractiveAuthResponse::Success) => {
+                    session.authenticated = true;
+                    return Ok(NaslValue::Number(0));
+                }
+                Ok(KeyboardInteractiveAuthResponse::InfoRequest { prompts, .. })
+                    if prompts.is_empty() =>
+                {
+                    answers = vec![];
+                }
+                _ => break,
+            }
+        }
+        Ok(NaslValue::Number(-1))
+    }
+
+    /// Get the issue banner
+    ///
+    /// The function returns a string with the issue banner.  This is
+    /// usually displayed before authentication.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// return A data block on success or NULL on error.
+    async fn nasl_ssh_get_issue_banner<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        // the banner is sent in reply to the first authentication request
+        if session.user.is_none() {
+            return Ok(NaslValue::Null);
+        }
+        get_authmethods(session).await?;
+        let banner = session.info.lock().unwrap().issue_banner.clone();
+        Ok(banner.map(NaslValue::String).unwrap_or(NaslValue::Null))
+    }
+
+    /// Get the server banner
+    ///
+    /// The function returns a string with the server banner.  This is
+    /// usually the first data sent by the server.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// return A data block on success or NULL on error.
+    async fn nasl_ssh_get_server_banner<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        Ok(NaslValue::String(session.server_banner.clone()))
+    }
+
+    /// Get the list of authmethods
+    ///
+    /// The function returns a string with comma separated authentication
+    /// methods.  This is basically the same as returned by
+    /// SSH_MSG_USERAUTH_FAILURE protocol element; however, it has been
+    /// screened and put into a definitive order.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// return A string on success or NULL on error.
+    async fn nasl_ssh_get_auth_methods<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        if session.user.is_none() {
+            return Ok(NaslValue::Null);
+        }
+        let methods = method_names(&get_authmethods(session).await?);
+        if methods.is_empty() {
+            return Ok(NaslValue::Null);
+        }
+        Ok(NaslValue::String(methods.join(",")))
+    }
+
+    /// Get the host key
+    ///
+    /// The function returns a string with the MD5 host key.
+    ///
+    /// @nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// @naslret A data block on success or NULL on error.
+    async fn nasl_ssh_get_host_key<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let info = session.info.lock().unwrap();
+        match info.host_key.as_ref() {
+            Some(key) => Ok(md5_fingerprint(key)
+                .map(NaslValue::String)
+                .unwrap_or(NaslValue::Null)),
+            None => Err(FunctionErrorKind::Diagnostic(
+                "Not possible to get the public key".to_string(),
+                Some(NaslValue::Null),
+            )),
+        }
+    }
+
+    /// Check if the SFTP subsystem is enabled on the remote SSH server.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// return An integer: 0 on success, greater than 0 when the SFTP subsystem could not be
+    /// started. NULL indicates a failure during session id verification.
+    async fn nasl_sftp_enabled_check<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        match transfer::sftp(session).await {
+            Ok(_) => Ok(NaslValue::Number(0)),
+            Err(e) => {
+                debug!("SFTP enabled check error: {}", e);
+                Ok(NaslValue::Number(1))
+            }
+        }
+    }
+
+    /// Execute the NETCONF subsystem on the the ssh channel
+    ///
+    /// The channel is read and written with ssh_shell_read and ssh_shell_write.
+    ///
+    /// nasl params
+    /// - An SSH session id.
+    ///
+    /// return Session ID on success, NULL on failure.
+    async fn nasl_ssh_execute_netconf_subsystem<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let session_id = session_id_arg(register)?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        let channel = open_channel(session).await?;
+        channel
+            .request_subsystem(true, "netconf")
+            .await
+            .map_err(|e| channel_error(session_id, &e))?;
+        session.channel = Some(channel);
+        Ok(NaslValue::Number(session_id as i64))
+    }
+
+    /// Read a file of the remote host via SFTP.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - file: The path of the file.
+    /// - max_size: The maximum size of the file in bytes, 16 MiB by default.
+    ///
+    /// return The content of the file or NULL when it could not be read.
+    async fn nasl_sftp_read_file<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (session_id, path, max_size) = transfer_args(register, "file")?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        transfer_result(
+            session_id,
+            path,
+            transfer::sftp_read_file(session, path, max_size)
+                .await
+                .map(NaslValue::Data),
+        )
+    }
+
+    /// List a directory of the remote host via SFTP.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - dir: The path of the directory.
+    ///
+    /// return A list with an array for each entry or NULL when the directory could not be
+    /// read. The arrays contain the name and type of the entry, as well as the size,
+    /// permissions, owner, group and mtime if the server sent them.
+    async fn nasl_sftp_list_dir<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (session_id, path, _) = transfer_args(register, "dir")?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        transfer_result(
+            session_id,
+            path,
+            transfer::sftp_list_dir(session, path)
+                .await
+                .map(NaslValue::Array),
+        )
+    }
+
+    /// Download a file of the remote host via SCP, for hosts without the SFTP subsystem.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - file: The path of the file.
+    /// - max_size: The maximum size of the file in bytes, 16 MiB by default.
+    ///
+    /// return The content of the file or NULL when it could not be downloaded.
+    async fn nasl_scp_download<'a>(
+        &self,
+        register: &Register,
+        _: &Context<'a>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (session_id, path, max_size) = transfer_args(register, "file")?;
+        let mut sessions = self.sessions.lock().await;
+        let session = find_session(&mut sessions, session_id)?;
+        transfer_result(
+            session_id,
+            path,
+            transfer::scp_download(session, path, max_size)
+                .await
+                .map(NaslValue::Data),
+        )
+    }
+}
+
+/// Returns NULL when the file transfer failed.
+fn transfer_result(
+    session_id: i32,
+    path: &str,
+    result: Result<NaslValue, TransferError>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    match result {
+        Ok(x) => Ok(x),
+        Err(e) => {
+            debug!(session_id, path, %e, "file transfer failed");
+            Ok(NaslValue::Null)
+        }
+    }
+}
+
+function_set! {
+    Ssh,
+    async_stateful,
+    (
+        (Ssh::nasl_ssh_connect, "ssh_connect"),
+        (Ssh::nasl_ssh_disconnect, "ssh_disconnect"),
+        (Ssh::nasl_ssh_session_id_from_sock, "ssh_session_id_from_sock"),
+        (Ssh::nasl_ssh_get_sock, "ssh_get_sock"),
+        (Ssh::nasl_ssh_set_login, "ssh_set_login"),
+        (Ssh::nasl_ssh_userauth, "ssh_userauth"),
+        (Ssh::nasl_ssh_request_exec, "ssh_request_exec"),
+        (Ssh::nasl_ssh_gather_package_list, "ssh_gather_package_list"),
+        (Ssh::nasl_ssh_set_elevation, "ssh_set_elevation"),
+        (Ssh::nasl_ssh_get_elevation_error, "ssh_get_elevation_error"),
+        (Ssh::nasl_ssh_shell_open, "ssh_shell_open"),
+        (Ssh::nasl_ssh_shell_read, "ssh_shell_read"),
+        (Ssh::nasl_ssh_shell_write, "ssh_shell_write"),
+        (Ssh::nasl_ssh_shell_close, "ssh_shell_close"),
+        (Ssh::nasl_ssh_login_interactive, "ssh_login_interactive"),
+        (Ssh::nasl_ssh_login_interactive_pass, "ssh_login_interactive_pass"),
+        (Ssh::nasl_ssh_get_issue_banner, "ssh_get_issue_banner"),
+        (Ssh::nasl_ssh_get_server_banner, "ssh_get_server_banner"),
+        (Ssh::nasl_ssh_get_auth_methods, "ssh_get_auth_methods"),
+        (Ssh::nasl_ssh_get_host_key, "ssh_get_host_key"),
+        (Ssh::nasl_sftp_enabled_check, "sftp_enabled_check"),
+        (Ssh::nasl_sftp_read_file, "sftp_read_file"),
+        (Ssh::nasl_sftp_list_dir, "sftp_list_dir"),
+        (Ssh::nasl_scp_download, "scp_download"),
+        (Ssh::nasl_ssh_execute_netconf_subsystem, "ssh_execute_netconf_subsystem"),
+    )
+}
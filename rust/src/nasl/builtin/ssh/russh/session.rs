@@ -0,0 +1,92 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines functions and structures for handling sessions
+
+use std::sync::{Arc, Mutex};
+
+use russh::{client, keys::PublicKey, Channel, MethodKind};
+
+use crate::nasl::builtin::ssh::elevation::{Elevation, ElevationError};
+
+/// Authentication methods in the order they are reported to scripts
+const METHODS: [(MethodKind, &str); 5] = [
+    (MethodKind::None, "none"),
+    (MethodKind::Password, "password"),
+    (MethodKind::PublicKey, "publickey"),
+    (MethodKind::HostBased, "hostbased"),
+    (MethodKind::KeyboardInteractive, "keyboard-interactive"),
+];
+
+/// Returns the names of the authentication methods.
+pub fn method_names(methods: &[MethodKind]) -> Vec<&'static str> {
+    METHODS
+        .iter()
+        .filter(|(kind, _)| methods.contains(kind))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// What the server sent outside of the channels
+#[derive(Debug, Default)]
+pub struct ServerInfo {
+    /// Host key of the key exchange
+    pub host_key: Option<PublicKey>,
+    /// Banner sent during the authentication
+    pub issue_banner: Option<String>,
+}
+
+/// Handler of the client side of a session
+pub struct Client {
+    pub info: Arc<Mutex<ServerInfo>>,
+}
+
+impl client::Handler for Client {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        // the hosts of a scan are not known in advance, so any key is accepted and recorded
+        self.info.lock().unwrap().host_key = Some(server_public_key.clone());
+        Ok(true)
+    }
+
+    async fn auth_banner(
+        &mut self,
+        banner: &str,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        self.info.lock().unwrap().issue_banner = Some(banner.to_string());
+        Ok(())
+    }
+}
+
+/// Structure to hold an SSH Session
+pub struct SshSession {
+    /// Session ID
+    pub session_id: i32,
+    /// Connection to the server
+    pub handle: client::Handle<Client>,
+    /// Host key and issue banner
+    pub info: Arc<Mutex<ServerInfo>>,
+    /// Identification of the server, e.g. SSH-2.0-OpenSSH_9.6
+    pub server_banner: String,
+    /// Login name used for the authentication
+    pub user: Option<String>,
+    /// Authentication methods offered by the server, None until they were requested
+    pub authmethods: Option<Vec<MethodKind>>,
+    /// Set once an authentication succeeded
+    pub authenticated: bool,
+    /// Echo flags of the prompts of a keyboard-interactive authentication started by
+    /// ssh_login_interactive
+    pub prompts: Option<Vec<bool>>,
+    /// Channel of the shell or the NETCONF subsystem
+    pub channel: Option<Channel<client::Msg>>,
+    /// Privilege elevation of commands
+    pub elevation: Option<Elevation>,
+    /// Why the last elevation failed
+    pub elevation_error: Option<ElevationError>,
+}
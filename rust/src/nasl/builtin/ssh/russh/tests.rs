@@ -0,0 +1,386 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use rand::rngs::OsRng;
+use russh::{
+    keys::{ssh_key::LineEnding, Algorithm, PrivateKey, PublicKey},
+    server::{self, Auth, Msg, Response, Session},
+    Channel, ChannelId, CryptoVec, MethodKind, MethodSet, SshId,
+};
+use tokio::{net::TcpListener, runtime::Runtime};
+
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+const SERVER_ID: &str = "SSH-2.0-OpenSSH_9.6";
+const BANNER: &str = "Authorized access only\n";
+const USER: &str = "admin";
+const PASSWORD: &str = "secret";
+/// User that is authenticated with the none method
+const GUEST: &str = "guest";
+
+/// Handler of a connection, accepts the credentials of USER and answers commands and shell
+/// input.
+struct Handler {
+    client_key: PublicKey,
+}
+
+impl server::Handler for Handler {
+    type Error = russh::Error;
+
+    async fn authentication_banner(&mut self) -> Result<Option<String>, Self::Error> {
+        Ok(Some(BANNER.to_string()))
+    }
+
+    async fn auth_none(&mut self, user: &str) -> Result<Auth, Self::Error> {
+        Ok(match user {
+            GUEST => Auth::Accept,
+            _ => Auth::reject(),
+        })
+    }
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        Ok(match (user, password) {
+            (USER, PASSWORD) => Auth::Accept,
+            _ => Auth::reject(),
+        })
+    }
+
+    async fn auth_publickey(&mut self, user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        Ok(match user == USER && key == &self.client_key {
+            true => Auth::Accept,
+            false => Auth::reject(),
+        })
+    }
+
+    async fn auth_keyboard_interactive<'a>(
+        &'a mut self,
+        user: &str,
+        _: &str,
+        response: Option<Response<'a>>,
+    ) -> Result<Auth, Self::Error> {
+        Ok(match response {
+            None => Auth::Partial {
+                name: "".into(),
+                instructions: "".into(),
+                prompts: vec![
+                    ("Verification code: ".into(), true),
+                    ("Password: ".into(), false),
+                ]
+                .into(),
+            },
+            Some(mut response) => {
+                let (code, password) = (response.next(), response.next());
+                match code.is_some() && password.is_some_and(|x| &x[..] == PASSWORD.as_bytes()) {
+                    true if user == USER => Auth::Accept,
+                    _ => Auth::reject(),
+                }
+            }
+        })
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        _: Channel<Msg>,
+        _: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel)?;
+        let output = match data {
+            b"id" => "uid=0(root) gid=0(root)\n",
+            _ => "",
+        };
+        session.extended_data(channel, 1, CryptoVec::from_slice(b"warning\n"))?;
+        session.data(channel, CryptoVec::from_slice(output.as_bytes()))?;
+        session.exit_status_request(channel, 0)?;
+        session.eof(channel)?;
+        session.close(channel)
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel)?;
+        session.data(channel, CryptoVec::from_slice(b"$ "))
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.data(channel, CryptoVec::from_slice(data))
+    }
+}
+
+/// SSH server running in the background on its own runtime.
+struct Server {
+    runtime: Runtime,
+    port: u16,
+    client_key: PrivateKey,
+    /// Number of connections that ended
+    closed: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// Starts a server offering the authentication methods.
+    fn start(methods: &[MethodKind]) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap();
+        let client_key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let config = Arc::new(server::Config {
+            server_id: SshId::Standard(SERVER_ID.to_string()),
+            methods: MethodSet::from(methods),
+            auth_rejection_time: Duration::ZERO,
+            auth_rejection_time_initial: Some(Duration::ZERO),
+            keys: vec![PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap()],
+            ..Default::default()
+        });
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let closed = Arc::new(AtomicUsize::new(0));
+        let public_key = client_key.public_key().clone();
+        let counter = closed.clone();
+        runtime.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = Handler {
+                    client_key: public_key.clone(),
+                };
+                let (config, closed) = (config.clone(), counter.clone());
+                tokio::spawn(async move {
+                    if let Ok(session) = server::run_stream(config, stream, handler).await {
+                        let _ = session.await;
+                    }
+                    closed.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+        Self {
+            runtime,
+            port,
+            client_key,
+            closed,
+        }
+    }
+
+    /// Returns a test builder scanning the server, its NASL variable `port` is set.
+    fn test_builder(&self) -> DefaultTestBuilder {
+        let mut t =
+            TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+        t.set_variable("port", NaslValue::Number(self.port as i64));
+        t
+    }
+}
+
+#[test]
+fn connect() {
+    let server = Server::start(&[MethodKind::Password, MethodKind::PublicKey]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    t.ok("s = ssh_connect(port: port);", 9000);
+    t.ok("ssh_get_server_banner(s);", SERVER_ID);
+    t.check(
+        "ssh_get_host_key(s);",
+        |x| matches!(x, Ok(NaslValue::String(x)) if x.split(':').count() == 16),
+        Some("MD5 fingerprint"),
+    );
+    // the methods and the banner are requested with the login
+    t.ok("ssh_get_auth_methods(s);", NaslValue::Null);
+    t.ok(
+        format!("ssh_set_login(s, login: \"{USER}\");"),
+        NaslValue::Null,
+    );
+    t.ok("ssh_get_auth_methods(s);", "password,publickey");
+    t.ok("ssh_get_issue_banner(s);", BANNER);
+    t.ok("ssh_connect(port: port);", 9001);
+}
+
+#[test]
+fn password() {
+    let server = Server::start(&[MethodKind::Password]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    t.run("s = ssh_connect(port: port);");
+    t.ok(
+        format!("ssh_userauth(s, login: \"{USER}\", password: \"wrong\");"),
+        -1,
+    );
+    // the server does not offer a method again once it failed
+    t.run("s = ssh_connect(port: port);");
+    t.ok(
+        format!("ssh_userauth(s, login: \"{USER}\", password: \"{PASSWORD}\");"),
+        0,
+    );
+    // credentials are taken from the KB when none are given
+    t.run("s = ssh_connect(port: port);");
+    t.run(format!(
+        "set_kb_item(name: \"Secret/SSH/login\", value: \"{USER}\");"
+    ));
+    t.run(format!(
+        "set_kb_item(name: \"Secret/SSH/password\", value: \"{PASSWORD}\");"
+    ));
+    t.ok("ssh_userauth(s);", 0);
+}
+
+#[test]
+fn public_key() {
+    let server = Server::start(&[MethodKind::PublicKey]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    let key = server.client_key.to_openssh(LineEnding::LF).unwrap();
+    t.set_variable("key", NaslValue::String(key.to_string()));
+    let other = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+    let other = other.to_openssh(LineEnding::LF).unwrap();
+    t.set_variable("other", NaslValue::String(other.to_string()));
+    t.run("s = ssh_connect(port: port);");
+    t.ok(
+        format!("ssh_userauth(s, login: \"{USER}\", privatekey: other);"),
+        -1,
+    );
+    t.ok(
+        format!("ssh_userauth(s, login: \"{USER}\", privatekey: \"invalid\");"),
+        -1,
+    );
+    t.ok(
+        format!("ssh_userauth(s, login: \"{USER}\", privatekey: key);"),
+        0,
+    );
+}
+
+#[test]
+fn keyboard_interactive() {
+    let server = Server::start(&[MethodKind::KeyboardInteractive]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    // ssh_userauth answers the non-echo prompts with the password
+    t.run("s = ssh_connect(port: port);");
+    t.ok(
+        format!("ssh_userauth(s, login: \"{USER}\", password: \"{PASSWORD}\");"),
+        0,
+    );
+    t.run("s = ssh_connect(port: port);");
+    t.ok(
+        format!("ssh_login_interactive(s, login: \"{USER}\");"),
+        "Password: ",
+    );
+    t.ok(
+        format!("ssh_login_interactive_pass(s, pass: \"{PASSWORD}\");"),
+        0,
+    );
+    t.run("s = ssh_connect(port: port);");
+    t.run(format!("ssh_login_interactive(s, login: \"{USER}\");"));
+    t.ok("ssh_login_interactive_pass(s, pass: \"wrong\");", -1);
+}
+
+#[test]
+fn none() {
+    let server = Server::start(&[MethodKind::Password]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    t.run("s = ssh_connect(port: port);");
+    t.ok(
+        format!("ssh_userauth(s, login: \"{GUEST}\", password: \"\");"),
+        0,
+    );
+    t.ok("ssh_get_auth_methods(s);", "none");
+}
+
+#[test]
+fn exec() {
+    let server = Server::start(&[MethodKind::Password]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    t.run("s = ssh_connect(port: port);");
+    t.run(format!(
+        "ssh_userauth(s, login: \"{USER}\", password: \"{PASSWORD}\");"
+    ));
+    t.ok(
+        "ssh_request_exec(s, cmd: \"id\");",
+        "uid=0(root) gid=0(root)\n",
+    );
+    t.ok(
+        "ssh_request_exec(s, cmd: \"id\", stdout: 0, stderr: 1);",
+        "warning\n",
+    );
+    t.ok(
+        "ssh_request_exec(s, cmd: \"id\", stdout: 1, stderr: 1);",
+        "warning\nuid=0(root) gid=0(root)\n",
+    );
+    // stderr received before stdout is appended
+    t.ok(
+        "ssh_request_exec(s, cmd: \"id\", stdout: 0, stderr: 0);",
+        "uid=0(root) gid=0(root)\nwarning\n",
+    );
+    t.ok("ssh_request_exec(s, cmd: \"\");", NaslValue::Null);
+}
+
+#[test]
+fn shell() {
+    let server = Server::start(&[MethodKind::Password]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    t.run("s = ssh_connect(port: port);");
+    t.run(format!(
+        "ssh_userauth(s, login: \"{USER}\", password: \"{PASSWORD}\");"
+    ));
+    t.ok("ssh_shell_read(s);", NaslValue::Null);
+    t.ok("ssh_shell_open(s);", 9000);
+    t.ok("ssh_shell_read(s, timeout: 1);", "$ ");
+    t.ok("ssh_shell_write(s, cmd: \"id\n\");", 0);
+    t.ok("ssh_shell_read(s, timeout: 1);", "id\n");
+    t.ok("ssh_shell_close(s);", NaslValue::Null);
+    t.ok("ssh_shell_write(s, cmd: \"id\n\");", NaslValue::Null);
+}
+
+#[test]
+fn disconnect() {
+    let server = Server::start(&[MethodKind::Password]);
+    let _runtime = server.runtime.enter();
+    let mut t = server.test_builder();
+    let closed = server.closed.clone();
+    t.run("s = ssh_connect(port: port);");
+    t.ok("ssh_disconnect(0);", NaslValue::Null);
+    t.check(
+        "ssh_disconnect(s);",
+        move |x| {
+            // the server ends the connection once it received the disconnect message
+            let start = Instant::now();
+            while closed.load(Ordering::SeqCst) == 0 && start.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            matches!(x, Ok(NaslValue::Null)) && closed.load(Ordering::SeqCst) == 1
+        },
+        Some("closed connection"),
+    );
+    check_err_matches!(
+        t,
+        "ssh_get_server_banner(s);",
+        FunctionErrorKind::Diagnostic(_, _),
+    );
+    check_err_matches!(t, "ssh_disconnect(s);", FunctionErrorKind::Diagnostic(_, _));
+    // the session ID is handed out again
+    t.ok("ssh_connect(port: port);", 9000);
+}
@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Reads files and directories of the target via SFTP or SCP on a russh session.
+
+use russh::{client, Channel, ChannelMsg};
+use russh_sftp::{
+    client::{error::Error as SftpError, fs::Metadata, SftpSession},
+    protocol::FileType,
+};
+use tokio::io::AsyncReadExt;
+
+use crate::nasl::builtin::ssh::{
+    elevation::quote,
+    transfer::{Entry, ScpSink, ScpStep, TransferError, SCP_TIMEOUT},
+};
+use crate::nasl::syntax::NaslValue;
+
+use super::session::SshSession;
+
+impl From<russh::Error> for TransferError {
+    fn from(value: russh::Error) -> Self {
+        Self::Ssh(value.to_string())
+    }
+}
+
+impl From<SftpError> for TransferError {
+    fn from(value: SftpError) -> Self {
+        Self::Ssh(value.to_string())
+    }
+}
+
+/// Opens the SFTP subsystem on a new channel.
+pub async fn sftp(session: &SshSession) -> Result<SftpSession, TransferError> {
+    let channel = session.handle.channel_open_session().await?;
+    channel.request_subsystem(true, "sftp").await?;
+    Ok(SftpSession::new(channel.into_stream()).await?)
+}
+
+/// Reads a file via SFTP.
+pub async fn sftp_read_file(
+    session: &SshSession,
+    path: &str,
+    max_size: u64,
+) -> Result<Vec<u8>, TransferError> {
+    let sftp = sftp(session).await?;
+    let file = sftp.open(path).await?;
+    let mut data = vec![];
+    file.take(max_size + 1).read_to_end(&mut data).await?;
+    if data.len() as u64 > max_size {
+        return Err(TransferError::TooLarge(max_size));
+    }
+    Ok(data)
+}
+
+fn entry(name: String, metadata: &Metadata) -> Entry {
+    Entry {
+        name,
+        kind: match metadata.permissions.map(|_| metadata.file_type()) {
+            Some(FileType::File) => "file",
+            Some(FileType::Dir) => "directory",
+            Some(FileType::Symlink) => "symlink",
+            Some(FileType::Other) => "special",
+            None => "unknown",
+        },
+        size: metadata.size,
+        permissions: metadata.permissions,
+        owner: metadata.user.clone(),
+        group: metadata.group.clone(),
+        mtime: metadata.mtime.map(u64::from),
+    }
+}
+
+/// Lists a directory via SFTP, without the `.` and `..` entries.
+pub async fn sftp_list_dir(
+    session: &SshSession,
+    path: &str,
+) -> Result<Vec<NaslValue>, TransferError> {
+    let sftp = sftp(session).await?;
+    Ok(sftp
+        .read_dir(path)
+        .await?
+        .map(|x| entry(x.file_name(), &x.metadata()).into())
+        .collect())
+}
+
+async fn ack(channel: &Channel<client::Msg>) -> Result<(), TransferError> {
+    Ok(channel.data(&[0][..]).await?)
+}
+
+async fn receive(
+    channel: &mut Channel<client::Msg>,
+    max_size: u64,
+) -> Result<Vec<u8>, TransferError> {
+    let mut sink = ScpSink::new(max_size);
+    ack(channel).await?;
+    loop {
+        match sink.step()? {
+            ScpStep::Read => match tokio::time::timeout(SCP_TIMEOUT, channel.wait()).await {
+                Ok(Some(ChannelMsg::Data { data })) => sink.push(&data),
+                Ok(Some(ChannelMsg::Eof | ChannelMsg::Close)) | Ok(None) => {
+                    return Err(TransferError::Protocol("connection closed".to_string()))
+                }
+                Ok(Some(_)) => {}
+                Err(_) => return Err(TransferError::Protocol("timed out".to_string())),
+            },
+            ScpStep::Ack => ack(channel).await?,
+            ScpStep::Done(data) => {
+                ack(channel).await?;
+                return Ok(data);
+            }
+        }
+    }
+}
+
+/// Downloads a file via SCP.
+pub async fn scp_download(
+    session: &SshSession,
+    path: &str,
+    max_size: u64,
+) -> Result<Vec<u8>, TransferError> {
+    let mut channel = session.handle.channel_open_session().await?;
+    channel
+        .exec(true, format!("scp -f {}", quote(path)))
+        .await?;
+    let result = receive(&mut channel, max_size).await;
+    let _ = channel.close().await;
+    result
+}
@@ -0,0 +1,256 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Reads files and directories of the target via SFTP or SCP.
+//!
+//! SCP is implemented as the sink side of the protocol talking to `scp -f` on the target, so
+//! files can be downloaded from hosts without the SFTP subsystem. The SSH backends move the
+//! data between the channel and [ScpSink], which implements the protocol.
+
+use std::{fmt::Display, io, time::Duration};
+
+use indexmap::IndexMap;
+
+use crate::nasl::syntax::NaslValue;
+
+/// Maximum size of a downloaded file, unless another one is given
+pub const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+/// Time to wait for the next data of a SCP download
+pub const SCP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum length of a SCP control message
+const MAX_CONTROL: usize = 4096;
+
+#[derive(Debug)]
+pub enum TransferError {
+    /// Error of the SSH backend
+    Ssh(String),
+    Io(io::Error),
+    /// The file is larger than the given maximum size
+    TooLarge(u64),
+    /// The remote scp reported an error, e.g. that the file does not exist
+    Remote(String),
+    /// The remote scp sent something unexpected
+    Protocol(String),
+}
+
+impl Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ssh(e) => write!(f, "{e}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::TooLarge(max) => write!(f, "the file is larger than {max} bytes"),
+            Self::Remote(message) => write!(f, "scp failed: {message}"),
+            Self::Protocol(message) => write!(f, "unexpected scp message: {message}"),
+        }
+    }
+}
+
+impl From<io::Error> for TransferError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Entry of a directory listing, attributes the server did not send are None
+pub struct Entry {
+    pub name: String,
+    /// file, directory, symlink, special or unknown
+    pub kind: &'static str,
+    pub size: Option<u64>,
+    pub permissions: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Seconds since the epoch
+    pub mtime: Option<u64>,
+}
+
+impl From<Entry> for NaslValue {
+    /// Describes the entry, missing attributes are omitted.
+    fn from(value: Entry) -> Self {
+        let mut entry = IndexMap::new();
+        let mut insert = |key: &str, value: NaslValue| entry.insert(key.to_string(), value);
+        insert("name", NaslValue::String(value.name));
+        insert("type", NaslValue::String(value.kind.to_string()));
+        if let Some(x) = value.size {
+            insert("size", NaslValue::Number(x as i64));
+        }
+        if let Some(x) = value.permissions {
+            insert("permissions", NaslValue::Number(x as i64 & 0o7777));
+        }
+        if let Some(x) = value.owner {
+            insert("owner", NaslValue::String(x));
+        }
+        if let Some(x) = value.group {
+            insert("group", NaslValue::String(x));
+        }
+        if let Some(x) = value.mtime {
+            insert("mtime", NaslValue::Number(x as i64));
+        }
+        NaslValue::Dict(entry)
+    }
+}
+
+/// Control message of the SCP protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Control {
+    /// `C<mode> <size> <name>`, the file of the size follows
+    File(u64),
+    /// `T<mtime> 0 <atime> 0`, the times of the next file
+    Times,
+    /// `D<mode> 0 <name>` or `E`, the start or end of a directory
+    Directory,
+    /// Status byte 1 or 2 followed by a message
+    Error(String),
+}
+
+fn parse_control(line: &[u8]) -> Result<Control, TransferError> {
+    let protocol = || TransferError::Protocol(String::from_utf8_lossy(line).into_owned());
+    let (&kind, text) = line.split_first().ok_or_else(protocol)?;
+    let text = String::from_utf8_lossy(text);
+    match kind {
+        b'C' => {
+            let mut fields = text.splitn(3, ' ');
+            let (Some(_mode), Some(size), Some(_name)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(protocol());
+            };
+            Ok(Control::File(size.parse().map_err(|_| protocol())?))
+        }
+        b'T' => Ok(Control::Times),
+        b'D' | b'E' => Ok(Control::Directory),
+        1 | 2 => Ok(Control::Error(text.trim().to_string())),
+        _ => Err(protocol()),
+    }
+}
+
+/// What to do next with the channel of a SCP download
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScpStep {
+    /// Pass more output of the remote scp
+    Read,
+    /// Acknowledge the last message by writing a zero byte
+    Ack,
+    /// Acknowledge the file, the download is complete
+    Done(Vec<u8>),
+}
+
+/// Sink side of a SCP download, the remote scp is acknowledged once before the first step.
+pub struct ScpSink {
+    buffer: Vec<u8>,
+    max_size: u64,
+    /// Size of the file once its control message arrived
+    size: Option<usize>,
+}
+
+impl ScpSink {
+    pub fn new(max_size: u64) -> Self {
+        Self {
+            buffer: vec![],
+            max_size,
+            size: None,
+        }
+    }
+
+    /// Adds output of the remote scp.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn line(&mut self, start: usize) -> Result<Option<Vec<u8>>, TransferError> {
+        match self.buffer[start..].iter().position(|x| *x == b'\n') {
+            Some(end) => {
+                let mut line: Vec<u8> = self.buffer.drain(..=start + end).skip(start).collect();
+                line.pop();
+                Ok(Some(line))
+            }
+            None if self.buffer.len() - start > MAX_CONTROL => Err(TransferError::Protocol(
+                "control message too long".to_string(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Processes the output passed so far.
+    pub fn step(&mut self) -> Result<ScpStep, TransferError> {
+        let Some(size) = self.size else {
+            let Some(line) = self.line(0)? else {
+                return Ok(ScpStep::Read);
+            };
+            return match parse_control(&line)? {
+                Control::File(size) if size > self.max_size => {
+                    Err(TransferError::TooLarge(self.max_size))
+                }
+                Control::File(size) => {
+                    self.size = Some(size as usize);
+                    Ok(ScpStep::Ack)
+                }
+                Control::Times => Ok(ScpStep::Ack),
+                Control::Directory => {
+                    Err(TransferError::Remote("the path is a directory".to_string()))
+                }
+                Control::Error(message) => Err(TransferError::Remote(message)),
+            };
+        };
+        // the file is followed by the status byte of its transfer
+        match self.buffer.get(size) {
+            None => Ok(ScpStep::Read),
+            Some(0) => {
+                let data = self.buffer.drain(..size).collect();
+                Ok(ScpStep::Done(data))
+            }
+            Some(_) => match self.line(size + 1)? {
+                Some(message) => Err(TransferError::Remote(
+                    String::from_utf8_lossy(&message).trim().to_string(),
+                )),
+                None => Ok(ScpStep::Read),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_messages() {
+        assert_eq!(
+            parse_control(b"C0644 1234 os release").unwrap(),
+            Control::File(1234)
+        );
+        assert_eq!(
+            parse_control(b"T1700000000 0 1700000000 0").unwrap(),
+            Control::Times
+        );
+        assert_eq!(
+            parse_control(b"\x01scp: /etc/shadow: Permission denied").unwrap(),
+            Control::Error("scp: /etc/shadow: Permission denied".to_string())
+        );
+        assert!(parse_control(b"C0644 x name").is_err());
+        assert!(parse_control(b"hello").is_err());
+    }
+
+    #[test]
+    fn download() {
+        let mut sink = ScpSink::new(MAX_FILE_SIZE);
+        assert_eq!(sink.step().unwrap(), ScpStep::Read);
+        sink.push(b"T1700000000 0 1700000000 0\nC0644 5 os-rel");
+        assert_eq!(sink.step().unwrap(), ScpStep::Ack);
+        assert_eq!(sink.step().unwrap(), ScpStep::Read);
+        sink.push(b"ease\nID=d");
+        assert_eq!(sink.step().unwrap(), ScpStep::Ack);
+        assert_eq!(sink.step().unwrap(), ScpStep::Read);
+        sink.push(b"e\0");
+        assert_eq!(sink.step().unwrap(), ScpStep::Done(b"ID=de".to_vec()));
+
+        let mut sink = ScpSink::new(4);
+        sink.push(b"C0644 5 os-release\n");
+        assert!(matches!(sink.step(), Err(TransferError::TooLarge(4))));
+
+        let mut sink = ScpSink::new(MAX_FILE_SIZE);
+        sink.push(b"\x01scp: /etc/shadow: Permission denied\n");
+        assert!(matches!(sink.step(), Err(TransferError::Remote(_))));
+    }
+}
@@ -19,6 +19,8 @@ use std::num::ParseIntError;
 use crate::function_set;
 use crate::nasl::syntax::NaslValue;
 
+use super::types::parse_int;
+
 /// `Some(string)` if constructed from either a `NaslValue::String`
 /// or `NaslValue::Data`.
 struct StringOrData(String);
@@ -320,26 +322,21 @@ fn ord(s: NaslValue) -> Option<u8> {
 /// NASL function to convert a string to an integer.  This function
 /// tries to convert any given parameter into an integer. If the
 /// conversion is not possible or no argument was given, a 0 is
-/// returned instead. If a string contains any non-numerical
-/// characters, it only converts, if the string starts with a
-/// numerical character and end at the first appearance of any
-/// non-numerical character. The TRUE value converts to 1, FALSE to 0.
+/// returned instead. Like atoi of the C implementation, leading
+/// whitespace is skipped and a string containing non-numerical
+/// characters is converted up to the first of them. The TRUE value
+/// converts to 1, FALSE to 0.
 #[nasl_function]
 fn int(s: &NaslValue) -> i64 {
     match s {
-        NaslValue::String(s) => str_to_int(s),
-        NaslValue::Data(data) => str_to_int(&bytes_to_str(data)),
+        NaslValue::String(s) => parse_int(s, 10).unwrap_or(0),
+        NaslValue::Data(data) => parse_int(&bytes_to_str(data), 10).unwrap_or(0),
         NaslValue::Number(num) => *num,
         NaslValue::Boolean(b) => *b as i64,
         _ => 0,
     }
 }
 
-fn str_to_int(s: &str) -> i64 {
-    s.parse::<i64>()
-        .unwrap_or_else(|_| s[..s.len() - 1].parse::<i64>().unwrap_or(0))
-}
-
 /// NASL function that replaces a substring in one string with another string.
 /// 1st positional argument: string in which the replacement takes place.
 /// 2nd positional argument: string to replace the substring in the 1st argument with
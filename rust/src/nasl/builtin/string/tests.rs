@@ -179,7 +179,9 @@ mod tests {
         check_code_result(r#"int("123");"#, 123);
         check_code_result(r#"int(123);"#, 123);
         check_code_result(r#"int("123x");"#, 123);
-        check_code_result(r#"int("123xx");"#, 0);
+        check_code_result(r#"int("123xx");"#, 123);
+        check_code_result(r#"int(" -12");"#, -12);
+        check_code_result(r#"int("");"#, 0);
         check_code_result(r#"int(TRUE);"#, 1);
     }
 
@@ -0,0 +1,139 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions for explicit type conversion and introspection.
+//!
+//! Operators coerce their operands implicitly, e.g. any string is 1 when used as a number. The
+//! functions of this module convert values by fixed rules instead:
+//! - to_int parses strings and data like `strtol` of the C implementation. Booleans are 0 or 1,
+//!   everything that is not a number is NULL instead of 0.
+//! - to_data converts strings and numbers to their bytes in their textual representation and
+//!   refuses arrays instead of silently dropping them.
+//! - is_null and typeof_ex distinguish all types, while typeof reports booleans as int and
+//!   lists and dicts both as array.
+
+#[cfg(test)]
+mod tests;
+
+use crate::nasl::prelude::*;
+
+/// Returns the name of the type of a value as reported by typeof_ex.
+fn type_name(value: &NaslValue) -> &'static str {
+    match value {
+        NaslValue::Null => "null",
+        NaslValue::Boolean(_) => "bool",
+        NaslValue::Number(_) => "int",
+        NaslValue::String(_) => "string",
+        NaslValue::Data(_) => "data",
+        NaslValue::Array(_) => "list",
+        NaslValue::Dict(_) => "dict",
+        _ => "unknown",
+    }
+}
+
+/// Parses the integer at the start of the text like `strtol`.
+///
+/// Leading whitespace and a sign are skipped. With base 16 an optional 0x prefix is skipped,
+/// with base 0 the base is taken from the prefix: 0x for hexadecimal, 0 for octal and decimal
+/// otherwise. Parsing stops at the first character that is not a digit of the base.
+///
+/// Returns None when there is no digit or the number does not fit into 64 bits. The base must
+/// be 0 or between 2 and 36.
+pub(crate) fn parse_int(text: &str, base: u32) -> Option<i64> {
+    let text = text.trim_start();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_hexdigit()));
+    let (base, text) = match (base, hex) {
+        (0 | 16, Some(rest)) => (16, rest),
+        (0, None) if text.starts_with('0') => (8, text),
+        (0, None) => (10, text),
+        (base, _) => (base, text),
+    };
+    let digits = text.find(|c: char| !c.is_digit(base)).unwrap_or(text.len());
+    let magnitude = u64::from_str_radix(&text[..digits], base).ok()?;
+    if negative {
+        0i64.checked_sub_unsigned(magnitude)
+    } else {
+        i64::try_from(magnitude).ok()
+    }
+}
+
+/// Returns the type of the given unnamed argument.
+///
+/// Unlike typeof, the types are distinguished exactly: "null", "bool", "int", "string", "data",
+/// "list" or "dict".
+#[nasl_function]
+fn typeof_ex(value: Option<&NaslValue>) -> &'static str {
+    value.map_or("null", type_name)
+}
+
+/// Converts the given unnamed argument to an integer.
+///
+/// Strings and data are parsed like `strtol` in the given base, 10 by default. Base 0 detects
+/// the base from the prefix. Numbers are returned as they are, TRUE is 1 and FALSE 0. Returns
+/// NULL when the value does not start with a number or is of another type.
+#[nasl_function(named(base))]
+fn to_int(value: &NaslValue, base: Option<u32>) -> Result<Option<i64>, FunctionErrorKind> {
+    let base = base.unwrap_or(10);
+    if base == 1 || base > 36 {
+        return Err(FunctionErrorKind::wrong_argument(
+            "base",
+            "0 or between 2 and 36",
+            &base.to_string(),
+        ));
+    }
+    Ok(match value {
+        NaslValue::Number(x) => Some(*x),
+        NaslValue::Boolean(x) => Some(*x as i64),
+        NaslValue::String(x) => parse_int(x, base),
+        NaslValue::Data(x) => parse_int(&String::from_utf8_lossy(x), base),
+        _ => None,
+    })
+}
+
+/// Converts the given unnamed argument to data.
+///
+/// Strings are converted to their bytes, numbers to their decimal representation, TRUE to "1"
+/// and FALSE to "0". NULL stays NULL. Returns an error for lists and dicts.
+#[nasl_function]
+fn to_data(value: &NaslValue) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+    match value {
+        NaslValue::Data(x) => Ok(Some(x.clone())),
+        NaslValue::String(x) => Ok(Some(x.as_bytes().to_vec())),
+        NaslValue::Number(x) => Ok(Some(x.to_string().into_bytes())),
+        NaslValue::Boolean(x) => Ok(Some(vec![if *x { b'1' } else { b'0' }])),
+        NaslValue::Null => Ok(None),
+        x => Err(FunctionErrorKind::wrong_unnamed_argument(
+            "a string, data or a number",
+            &format!("a {} was given", type_name(x)),
+        )),
+    }
+}
+
+/// Returns TRUE when the given unnamed argument is NULL or missing.
+///
+/// Only NULL is null: 0, FALSE, an empty string and an empty array are not.
+#[nasl_function]
+fn is_null(value: Option<&NaslValue>) -> bool {
+    matches!(value, None | Some(NaslValue::Null))
+}
+
+pub struct Types;
+
+function_set! {
+    Types,
+    sync_stateless,
+    (
+        typeof_ex,
+        to_int,
+        to_data,
+        is_null,
+    )
+}
@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::nasl::builtin::types::parse_int;
+use crate::nasl::test_prelude::*;
+
+#[test]
+fn typeof_ex() {
+    let mut t = TestBuilder::default();
+    t.ok("typeof_ex(NULL);", "null");
+    t.ok("typeof_ex();", "null");
+    t.ok("typeof_ex(TRUE);", "bool");
+    t.ok("typeof_ex(1);", "int");
+    t.ok(r#"typeof_ex("a");"#, "string");
+    t.ok("typeof_ex('a');", "data");
+    t.ok("typeof_ex(make_list(1));", "list");
+    t.ok("typeof_ex(make_array(1, 2));", "dict");
+}
+
+#[test]
+fn parse() {
+    assert_eq!(parse_int("42", 10), Some(42));
+    assert_eq!(parse_int("  -42abc", 10), Some(-42));
+    assert_eq!(parse_int("+7", 10), Some(7));
+    assert_eq!(parse_int("0x1F", 16), Some(31));
+    assert_eq!(parse_int("1f", 16), Some(31));
+    assert_eq!(parse_int("0x1F", 0), Some(31));
+    assert_eq!(parse_int("017", 0), Some(15));
+    assert_eq!(parse_int("08", 0), Some(0));
+    assert_eq!(parse_int("0x", 16), Some(0));
+    assert_eq!(parse_int("101", 2), Some(5));
+    assert_eq!(parse_int("-9223372036854775808", 10), Some(i64::MIN));
+    assert_eq!(parse_int("9223372036854775808", 10), None);
+    assert_eq!(parse_int("", 10), None);
+    assert_eq!(parse_int("abc", 10), None);
+    assert_eq!(parse_int("-", 10), None);
+}
+
+#[test]
+fn to_int() {
+    let mut t = TestBuilder::default();
+    t.ok(r#"to_int("123xx");"#, 123);
+    t.ok(r#"to_int("ff", base: 16);"#, 255);
+    t.ok(r#"to_int('0x10', base: 0);"#, 16);
+    t.ok("to_int(TRUE);", 1);
+    t.ok("to_int(-3);", -3);
+    t.ok(r#"to_int("abc");"#, NaslValue::Null);
+    t.ok("to_int(NULL);", NaslValue::Null);
+    t.ok("to_int(make_list(1));", NaslValue::Null);
+    check_err_matches!(
+        t,
+        r#"to_int("1", base: 37);"#,
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn to_data() {
+    let mut t = TestBuilder::default();
+    t.ok(r#"to_data("abc");"#, "abc".as_bytes().to_vec());
+    t.ok("to_data(-12);", "-12".as_bytes().to_vec());
+    t.ok("to_data(FALSE);", "0".as_bytes().to_vec());
+    t.ok("to_data(NULL);", NaslValue::Null);
+    check_err_matches!(
+        t,
+        "to_data(make_list(1));",
+        FunctionErrorKind::WrongArgument(_)
+    );
+}
+
+#[test]
+fn is_null() {
+    let mut t = TestBuilder::default();
+    t.ok("is_null(NULL);", true);
+    t.ok("is_null();", true);
+    t.ok("is_null(undefined_variable);", true);
+    t.ok("is_null(0);", false);
+    t.ok(r#"is_null("");"#, false);
+    t.ok("is_null(make_list());", false);
+}
@@ -0,0 +1,454 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Remote shell client of the Windows Remote Management service (WS-Management, MS-WSMV).
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::nasl::builtin::network::tcp::TcpConnection;
+
+use super::ntlm;
+
+const RESOURCE_URI: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/cmd";
+const ACTION_CREATE: &str = "http://schemas.xmlsoap.org/ws/2004/09/transfer/Create";
+const ACTION_DELETE: &str = "http://schemas.xmlsoap.org/ws/2004/09/transfer/Delete";
+const ACTION_COMMAND: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Command";
+const ACTION_RECEIVE: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Receive";
+const ACTION_SIGNAL: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Signal";
+const SIGNAL_TERMINATE: &str =
+    "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/signal/terminate";
+const STATE_DONE: &str =
+    "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/CommandState/Done";
+
+/// Time the service waits for output before answering a receive request without any
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Authentication scheme used for the HTTP requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    /// Username and password sent with each request, only allowed for local accounts
+    Basic,
+    /// NTLMv2 authentication of the connection
+    Ntlm,
+}
+
+/// Credentials of the user the shell runs as
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// Domain of the user, empty for local accounts
+    pub domain: String,
+}
+
+/// Output of a finished command
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Output {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: Option<i64>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Response {
+    fn header<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.headers
+            .iter()
+            .filter(move |(x, _)| x.eq_ignore_ascii_case(name))
+            .map(|(_, x)| x.as_str())
+    }
+}
+
+/// Returns the attributes and the text of all elements with the local name.
+fn elements(xml: &str, name: &str) -> Vec<(Vec<(String, String)>, String)> {
+    let attributes = |e: &BytesStart| {
+        e.attributes()
+            .flatten()
+            .map(|x| {
+                (
+                    String::from_utf8_lossy(x.key.local_name().as_ref()).into_owned(),
+                    x.unescape_value()
+                        .map(|x| x.into_owned())
+                        .unwrap_or_default(),
+                )
+            })
+            .collect()
+    };
+    let mut reader = Reader::from_str(xml);
+    let mut result = vec![];
+    let mut current: Option<(Vec<(String, String)>, String)> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) if e.local_name().as_ref() == name.as_bytes() => {
+                current = Some((attributes(&e), String::new()));
+            }
+            Ok(Event::Empty(e)) if e.local_name().as_ref() == name.as_bytes() => {
+                result.push((attributes(&e), String::new()));
+            }
+            Ok(Event::Text(e)) => {
+                if let Some((_, text)) = &mut current {
+                    text.push_str(&e.unescape().unwrap_or_default());
+                }
+            }
+            Ok(Event::End(e)) if e.local_name().as_ref() == name.as_bytes() => {
+                result.extend(current.take());
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    result
+}
+
+fn element(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name).into_iter().next().map(|(_, x)| x)
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(x, _)| x == name)
+        .map(|(_, x)| x.as_str())
+}
+
+/// Converts a SOAP fault into an error, a timed out receive request into TimedOut.
+fn fault(response: &Response) -> io::Error {
+    let body = &response.body;
+    if body.contains("w:TimedOut") || body.contains("2150858793") {
+        return io::ErrorKind::TimedOut.into();
+    }
+    let message = element(body, "Message")
+        .or_else(|| element(body, "Text"))
+        .map(|x| x.trim().to_string())
+        .unwrap_or_else(|| format!("HTTP status {}", response.status));
+    io::Error::other(format!("WinRM fault: {message}"))
+}
+
+/// Shell on a Windows host opened via WinRM
+pub struct WinRmClient {
+    conn: TcpConnection,
+    url: String,
+    host: String,
+    credentials: Credentials,
+    auth: Auth,
+    authenticated: bool,
+    timeout: Duration,
+    shell_id: String,
+}
+
+impl WinRmClient {
+    /// Opens a shell on the connected WinRM service, negotiating TLS first when https is set.
+    ///
+    /// Fails with PermissionDenied when the service rejects the credentials.
+    pub fn open(
+        mut conn: TcpConnection,
+        host: &str,
+        https: bool,
+        credentials: Credentials,
+        auth: Auth,
+        timeout: Duration,
+    ) -> io::Result<Self> {
+        let port = conn.peer_addr()?.port();
+        if https {
+            let options = crate::nasl::builtin::network::ssl::SslOptions {
+                sni: Some(host.to_string()).filter(|x| x.parse::<std::net::IpAddr>().is_err()),
+                ..Default::default()
+            };
+            conn.negotiate_ssl(&options, timeout)?;
+        }
+        let scheme = if https { "https" } else { "http" };
+        let host = match host.contains(':') {
+            true => format!("[{host}]:{port}"),
+            false => format!("{host}:{port}"),
+        };
+        let mut client = Self {
+            conn,
+            url: format!("{scheme}://{host}/wsman"),
+            host,
+            credentials,
+            auth,
+            authenticated: false,
+            timeout,
+            shell_id: String::new(),
+        };
+        let options = r#"<w:OptionSet><w:Option Name="WINRS_NOPROFILE">TRUE</w:Option><w:Option Name="WINRS_CODEPAGE">65001</w:Option></w:OptionSet>"#;
+        let body = "<rsp:Shell><rsp:InputStreams>stdin</rsp:InputStreams><rsp:OutputStreams>stdout stderr</rsp:OutputStreams></rsp:Shell>";
+        let response = client.invoke(ACTION_CREATE, options, body, timeout)?;
+        client.shell_id = element(&response, "ShellId")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing shell id"))?;
+        Ok(client)
+    }
+
+    fn read_response(&mut self) -> io::Result<Response> {
+        let timeout = self.timeout + RECEIVE_TIMEOUT;
+        let mut line = String::new();
+        self.conn.read_line_with_timeout(&mut line, timeout)?;
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response"))?;
+        let mut headers = vec![];
+        loop {
+            line.clear();
+            if self.conn.read_line_with_timeout(&mut line, timeout)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            match line.trim_end().split_once(':') {
+                Some((name, value)) => headers.push((name.to_string(), value.trim().to_string())),
+                None => break,
+            }
+        }
+        let mut response = Response {
+            status,
+            headers,
+            body: String::new(),
+        };
+        let mut body = vec![];
+        if response
+            .header("Transfer-Encoding")
+            .any(|x| x.eq_ignore_ascii_case("chunked"))
+        {
+            loop {
+                line.clear();
+                self.conn.read_line_with_timeout(&mut line, timeout)?;
+                let size =
+                    usize::from_str_radix(line.trim().split(';').next().unwrap_or(""), 16)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk"))?;
+                let start = body.len();
+                body.resize(start + size + 2, 0);
+                self.conn
+                    .read_exact_with_timeout(&mut body[start..], timeout)?;
+                body.truncate(start + size);
+                if size == 0 {
+                    break;
+                }
+            }
+        } else {
+            let length = response
+                .header("Content-Length")
+                .next()
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(0);
+            body.resize(length, 0);
+            self.conn.read_exact_with_timeout(&mut body, timeout)?;
+        }
+        response.body = String::from_utf8_lossy(&body).into_owned();
+        Ok(response)
+    }
+
+    fn send(&mut self, authorization: Option<&str>, body: &str) -> io::Result<Response> {
+        let mut request = format!(
+            "POST /wsman HTTP/1.1\r\nHost: {}\r\nUser-Agent: openvas\r\nContent-Type: application/soap+xml;charset=UTF-8\r\nContent-Length: {}\r\n",
+            self.host,
+            body.len()
+        );
+        if let Some(authorization) = authorization {
+            request.push_str(&format!("Authorization: {authorization}\r\n"));
+        }
+        request.push_str("\r\n");
+        request.push_str(body);
+        self.conn.write_all(request.as_bytes())?;
+        self.conn.flush()?;
+        self.read_response()
+    }
+
+    fn rejected() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "WinRM service rejected the credentials",
+        )
+    }
+
+    /// Authenticates the connection with NTLM while sending the first request.
+    fn authenticate_ntlm(&mut self, body: &str) -> io::Result<Response> {
+        let negotiate = format!("Negotiate {}", STANDARD.encode(ntlm::negotiate()));
+        let response = self.send(Some(&negotiate), "")?;
+        let challenge = response
+            .header("WWW-Authenticate")
+            .find_map(|x| {
+                let (scheme, token) = x.split_once(' ')?;
+                matches!(scheme, "Negotiate" | "NTLM")
+                    .then(|| STANDARD.decode(token.trim()).ok())
+                    .flatten()
+            })
+            .and_then(|x| ntlm::Challenge::parse(&x))
+            .ok_or_else(|| match response.status {
+                401 => Self::rejected(),
+                status => io::Error::other(format!("unexpected HTTP status {status}")),
+            })?;
+        let mut client_challenge = [0; 8];
+        openssl::rand::rand_bytes(&mut client_challenge).map_err(io::Error::other)?;
+        let credentials = &self.credentials;
+        let (message, _) = ntlm::authenticate(
+            &challenge,
+            &credentials.username,
+            &credentials.password,
+            &credentials.domain,
+            &client_challenge,
+        );
+        let authenticate = format!("Negotiate {}", STANDARD.encode(message));
+        let response = self.send(Some(&authenticate), body)?;
+        if response.status != 401 {
+            self.authenticated = true;
+        }
+        Ok(response)
+    }
+
+    fn post(&mut self, body: &str) -> io::Result<Response> {
+        let response = match self.auth {
+            Auth::Basic => {
+                let basic = format!(
+                    "Basic {}",
+                    STANDARD.encode(format!(
+                        "{}:{}",
+                        self.credentials.username, self.credentials.password
+                    ))
+                );
+                self.send(Some(&basic), body)?
+            }
+            Auth::Ntlm if self.authenticated => self.send(None, body)?,
+            Auth::Ntlm => self.authenticate_ntlm(body)?,
+        };
+        match response.status {
+            200 => Ok(response),
+            401 => Err(Self::rejected()),
+            _ => Err(fault(&response)),
+        }
+    }
+
+    /// Sends a request concerning the shell and returns the body of the response.
+    fn invoke(
+        &mut self,
+        action: &str,
+        options: &str,
+        body: &str,
+        timeout: Duration,
+    ) -> io::Result<String> {
+        let selector = match self.shell_id.is_empty() {
+            true => String::new(),
+            false => format!(
+                r#"<w:SelectorSet><w:Selector Name="ShellId">{}</w:Selector></w:SelectorSet>"#,
+                escape(&self.shell_id)
+            ),
+        };
+        let envelope = format!(
+            concat!(
+                r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:w="http://schemas.dmtf.org/wbem/wsman/1/wsman.xsd" xmlns:rsp="http://schemas.microsoft.com/wbem/wsman/1/windows/shell">"#,
+                r#"<s:Header><a:To>{}</a:To>"#,
+                r#"<a:ReplyTo><a:Address s:mustUnderstand="true">http://schemas.xmlsoap.org/ws/2004/08/addressing/role/anonymous</a:Address></a:ReplyTo>"#,
+                r#"<w:ResourceURI s:mustUnderstand="true">{}</w:ResourceURI>"#,
+                r#"<a:Action s:mustUnderstand="true">{}</a:Action>"#,
+                r#"<w:MaxEnvelopeSize s:mustUnderstand="true">512000</w:MaxEnvelopeSize>"#,
+                r#"<a:MessageID>uuid:{}</a:MessageID>"#,
+                r#"<w:Locale xml:lang="en-US" s:mustUnderstand="false"/>"#,
+                r#"<w:OperationTimeout>PT{}S</w:OperationTimeout>{}{}</s:Header>"#,
+                r#"<s:Body>{}</s:Body></s:Envelope>"#
+            ),
+            escape(&self.url),
+            RESOURCE_URI,
+            action,
+            uuid::Uuid::new_v4(),
+            timeout.as_secs().max(1),
+            selector,
+            options,
+            body
+        );
+        self.post(&envelope).map(|x| x.body)
+    }
+
+    /// Runs a command line in the shell and waits at most for the timeout for it to finish.
+    ///
+    /// Output received before the timeout expires is returned without exit code.
+    pub fn run(&mut self, command: &str, timeout: Duration) -> io::Result<Output> {
+        let options = r#"<w:OptionSet><w:Option Name="WINRS_CONSOLEMODE_STDIN">TRUE</w:Option><w:Option Name="WINRS_SKIP_CMD_SHELL">FALSE</w:Option></w:OptionSet>"#;
+        let body = format!(
+            "<rsp:CommandLine><rsp:Command>{}</rsp:Command></rsp:CommandLine>",
+            escape(command)
+        );
+        let response = self.invoke(ACTION_COMMAND, options, &body, self.timeout)?;
+        let command_id = element(&response, "CommandId")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing command id"))?;
+        let deadline = Instant::now() + timeout;
+        let mut output = Output::default();
+        let body = format!(
+            r#"<rsp:Receive><rsp:DesiredStream CommandId="{}">stdout stderr</rsp:DesiredStream></rsp:Receive>"#,
+            escape(&command_id)
+        );
+        let mut done = false;
+        while !done && Instant::now() < deadline {
+            let wait = deadline
+                .saturating_duration_since(Instant::now())
+                .min(RECEIVE_TIMEOUT);
+            let response = match self.invoke(ACTION_RECEIVE, "", &body, wait) {
+                Ok(x) => x,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => return Err(e),
+            };
+            for (attributes, data) in elements(&response, "Stream") {
+                let data = STANDARD.decode(data.trim()).unwrap_or_default();
+                match attribute(&attributes, "Name") {
+                    Some("stdout") => output.stdout.extend(data),
+                    Some("stderr") => output.stderr.extend(data),
+                    _ => {}
+                }
+            }
+            for (attributes, _) in elements(&response, "CommandState") {
+                if attribute(&attributes, "State") == Some(STATE_DONE) {
+                    done = true;
+                    output.exit_code =
+                        element(&response, "ExitCode").and_then(|x| x.trim().parse().ok());
+                }
+            }
+        }
+        let body = format!(
+            r#"<rsp:Signal CommandId="{}"><rsp:Code>{}</rsp:Code></rsp:Signal>"#,
+            escape(&command_id),
+            SIGNAL_TERMINATE
+        );
+        if let Err(e) = self.invoke(ACTION_SIGNAL, "", &body, self.timeout) {
+            tracing::debug!(%e, "unable to terminate WinRM command");
+        }
+        Ok(output)
+    }
+}
+
+impl Drop for WinRmClient {
+    fn drop(&mut self) {
+        if self.shell_id.is_empty() {
+            return;
+        }
+        // the number of shells per user is limited, so they are deleted instead of expiring
+        if let Err(e) = self.invoke(ACTION_DELETE, "", "", self.timeout) {
+            tracing::debug!(%e, "unable to delete WinRM shell");
+        }
+    }
+}
+
+/// Returns the command line running the PowerShell script.
+pub fn powershell(script: &str) -> String {
+    let encoded: Vec<u8> = script
+        .encode_utf16()
+        .flat_map(|x| x.to_le_bytes())
+        .collect();
+    format!(
+        "powershell -NoProfile -NonInteractive -EncodedCommand {}",
+        STANDARD.encode(encoded)
+    )
+}
@@ -0,0 +1,229 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to run commands on Windows hosts via Windows Remote Management.
+
+mod client;
+pub(super) mod ntlm;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, io, sync::Mutex, time::Duration};
+
+use client::{Auth, Credentials, WinRmClient};
+
+use crate::nasl::prelude::*;
+
+use super::{
+    lsc::windows::{WindowsInventory, INVENTORY_SCRIPT},
+    network::{get_kb_item, network_utils::resolve_host, socket::NaslSockets, verify_port},
+};
+
+const HTTP_PORT: u16 = 5985;
+const HTTPS_PORT: u16 = 5986;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time the inventory script may take, collecting the installed updates is slow
+const GATHER_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Default)]
+struct Handles {
+    sessions: HashMap<usize, WinRmClient>,
+    last_id: usize,
+}
+
+/// Holds the WinRM shells opened by a script.
+#[derive(Default)]
+pub struct WinRm {
+    handles: Mutex<Handles>,
+}
+
+/// Returns the named argument or the KB item set from the SMB credentials of the target.
+pub(super) fn credential(
+    context: &Context,
+    value: Option<&str>,
+    key: &str,
+) -> Result<String, FunctionErrorKind> {
+    match value {
+        Some(x) => Ok(x.to_string()),
+        None => Ok(get_kb_item(context, key)?
+            .map(|x| x.to_string())
+            .unwrap_or_default()),
+    }
+}
+
+fn io_error(e: io::Error) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(format!("WinRM: {e}"), Some(NaslValue::Null))
+}
+
+impl WinRm {
+    fn run(
+        &self,
+        session: usize,
+        command: &str,
+        timeout: Duration,
+    ) -> Result<client::Output, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let client = handles.sessions.get_mut(&session).ok_or_else(|| {
+            FunctionErrorKind::Diagnostic(format!("Unknown WinRM session {session}"), None)
+        })?;
+        client.run(command, timeout).map_err(io_error)
+    }
+
+    /// Opens a remote shell on the target via WinRM and returns the id of the session.
+    ///
+    /// The credentials default to the SMB credentials of the target (KB items SMB/login,
+    /// SMB/password and SMB/domain).
+    ///
+    /// - port: Port of the WinRM service, 5985 for HTTP and 5986 for HTTPS by default
+    /// - login, password, domain: Credentials of the user, the domain is empty for local accounts
+    /// - https: TRUE to connect via HTTPS
+    /// - auth: "ntlm" (default) or "basic", which only works for local accounts
+    /// - timeout: Timeout of each request in seconds
+    ///
+    /// Returns NULL when the connection fails or the credentials are rejected.
+    #[nasl_function(named(port, login, password, domain, https, auth, timeout))]
+    #[allow(clippy::too_many_arguments)]
+    fn winrm_connect(
+        &self,
+        context: &Context,
+        port: Option<i64>,
+        login: Option<&str>,
+        password: Option<&str>,
+        domain: Option<&str>,
+        https: Option<bool>,
+        auth: Option<&str>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let https = https.unwrap_or_default();
+        let port = match port {
+            Some(port) => verify_port(port)?,
+            None if https => HTTPS_PORT,
+            None => HTTP_PORT,
+        };
+        let auth = match auth.map(|x| x.to_lowercase()).as_deref() {
+            None | Some("ntlm") => Auth::Ntlm,
+            Some("basic") => Auth::Basic,
+            Some(x) => {
+                return Err(FunctionErrorKind::wrong_argument(
+                    "auth",
+                    "ntlm or basic",
+                    x,
+                ))
+            }
+        };
+        let credentials = Credentials {
+            username: credential(context, login, "SMB/login")?,
+            password: credential(context, password, "SMB/password")?,
+            domain: credential(context, domain, "SMB/domain")?,
+        };
+        if credentials.username.is_empty() {
+            return Err(FunctionErrorKind::MissingArguments(vec![
+                "login".to_string()
+            ]));
+        }
+        let timeout = timeout
+            .filter(|x| *x > 0)
+            .map_or(DEFAULT_TIMEOUT, |x| Duration::from_secs(x as u64));
+        let addrs = resolve_host(context, context.target())?;
+        let client =
+            NaslSockets::connect_tcp(context, &addrs, port, None, timeout, None).and_then(|conn| {
+                WinRmClient::open(conn, context.target(), https, credentials, auth, timeout)
+            });
+        let client = match client {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, port, "unable to open WinRM shell");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.sessions.insert(id, client);
+        Ok(NaslValue::Number(id as i64))
+    }
+
+    /// Runs a command in the shell of the session and returns what it printed to stdout.
+    ///
+    /// - cmd: Command line run by cmd.exe, or a script when powershell is set
+    /// - powershell: TRUE to run cmd as PowerShell script
+    /// - timeout: Time in seconds the command may take, output received until then is returned
+    #[nasl_function(named(session, cmd, powershell, timeout))]
+    fn winrm_exec(
+        &self,
+        session: usize,
+        cmd: &str,
+        powershell: Option<bool>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let command = match powershell.unwrap_or_default() {
+            true => client::powershell(cmd),
+            false => cmd.to_string(),
+        };
+        let timeout = timeout
+            .filter(|x| *x > 0)
+            .map_or(DEFAULT_TIMEOUT, |x| Duration::from_secs(x as u64));
+        let output = self.run(session, &command, timeout)?;
+        Ok(NaslValue::String(
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        ))
+    }
+
+    /// Collects the operating system build, installed updates and installed software of the
+    /// target and stores them in the KB.
+    ///
+    /// Besides the SMB/Windows KB items consumed by product detections the release and version
+    /// checked by the notus scanner are set and the operating system is registered as host
+    /// detail. Returns TRUE on success and NULL when the inventory could not be collected.
+    #[nasl_function(named(session))]
+    fn winrm_gather_package_list(
+        &self,
+        context: &Context,
+        session: usize,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let output = self.run(
+            session,
+            &client::powershell(INVENTORY_SCRIPT),
+            GATHER_TIMEOUT,
+        )?;
+        match WindowsInventory::parse(&String::from_utf8_lossy(&output.stdout)) {
+            Some(inventory) => {
+                inventory.store(context)?;
+                Ok(NaslValue::Boolean(true))
+            }
+            None => {
+                tracing::debug!(
+                    stderr = %String::from_utf8_lossy(&output.stderr),
+                    "unable to collect the Windows inventory"
+                );
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Closes the shell of the session.
+    #[nasl_function(named(session))]
+    fn winrm_close(&self, session: usize) -> Result<NaslValue, FunctionErrorKind> {
+        self.handles
+            .lock()
+            .unwrap()
+            .sessions
+            .remove(&session)
+            .map(|_| NaslValue::Null)
+            .ok_or_else(|| {
+                FunctionErrorKind::Diagnostic(format!("Unknown WinRM session {session}"), None)
+            })
+    }
+}
+
+function_set! {
+    WinRm,
+    sync_stateful,
+    (
+        (WinRm::winrm_connect, "winrm_connect"),
+        (WinRm::winrm_exec, "winrm_exec"),
+        (WinRm::winrm_gather_package_list, "winrm_gather_package_list"),
+        (WinRm::winrm_close, "winrm_close"),
+    )
+}
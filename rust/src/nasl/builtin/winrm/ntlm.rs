@@ -0,0 +1,451 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! NTLMv2 authentication as described in MS-NLMP.
+//!
+//! WinRM only uses the authentication, its messages are neither signed nor sealed. Over HTTP the
+//! WinRM service therefore must allow unencrypted traffic, HTTPS works without changes. The
+//! messages of other protocols, e.g. DCE/RPC, can be signed and sealed with a [Session].
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use digest::Digest;
+use hmac::{Hmac, Mac};
+use md4::Md4;
+use md5::Md5;
+use rc4::{consts::U16, KeyInit, Rc4, StreamCipher};
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+const NEGOTIATE_MESSAGE: u32 = 1;
+const CHALLENGE_MESSAGE: u32 = 2;
+const AUTHENTICATE_MESSAGE: u32 = 3;
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_SIGN: u32 = 0x0000_0010;
+const NEGOTIATE_SEAL: u32 = 0x0000_0020;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ANONYMOUS: u32 = 0x0000_0800;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_KEY_EXCH: u32 = 0x4000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+const FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+/// Flags requested in addition to sign and seal the messages of the session
+const SESSION_SECURITY: u32 = NEGOTIATE_SIGN | NEGOTIATE_SEAL;
+
+const CLIENT_SIGNING: &[u8] = b"session key to client-to-server signing key magic constant\0";
+const SERVER_SIGNING: &[u8] = b"session key to server-to-client signing key magic constant\0";
+const CLIENT_SEALING: &[u8] = b"session key to client-to-server sealing key magic constant\0";
+const SERVER_SEALING: &[u8] = b"session key to server-to-client sealing key magic constant\0";
+
+/// AV pair of the target info containing the time of the server
+const MSV_AV_TIMESTAMP: u16 = 7;
+
+/// Seconds between 1601-01-01, the epoch of FILETIME, and the UNIX epoch
+const FILETIME_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+/// Challenge sent by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    flags: u32,
+    server_challenge: [u8; 8],
+    target_info: Vec<u8>,
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn hmac_md5(key: &[u8], data: &[&[u8]]) -> [u8; 16] {
+    let mut mac = <Hmac<Md5> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for x in data {
+        mac.update(x);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Returns the NTLMv2 hash of the credentials.
+pub fn ntowf_v2(user: &str, password: &str, domain: &str) -> [u8; 16] {
+    let hash = Md4::digest(utf16le(password));
+    hmac_md5(&hash, &[&utf16le(&(user.to_uppercase() + domain))])
+}
+
+/// Returns the NT and LM challenge responses.
+pub fn responses(
+    hash: &[u8; 16],
+    challenge: &Challenge,
+    client_challenge: &[u8; 8],
+    timestamp: u64,
+) -> (Vec<u8>, Vec<u8>) {
+    let mut temp = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    temp.extend_from_slice(&timestamp.to_le_bytes());
+    temp.extend_from_slice(client_challenge);
+    temp.extend_from_slice(&[0; 4]);
+    temp.extend_from_slice(&challenge.target_info);
+    temp.extend_from_slice(&[0; 4]);
+    let proof = hmac_md5(hash, &[&challenge.server_challenge, &temp]);
+    let nt = [&proof[..], &temp].concat();
+    // with a timestamp of the server the LMv2 response is omitted
+    let lm = if challenge.timestamp().is_some() {
+        vec![0; 24]
+    } else {
+        let lm = hmac_md5(hash, &[&challenge.server_challenge, client_challenge]);
+        [&lm[..], client_challenge].concat()
+    };
+    (nt, lm)
+}
+
+impl Challenge {
+    /// Parses a CHALLENGE_MESSAGE.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 32 || &data[..8] != SIGNATURE || u32_at(data, 8)? != CHALLENGE_MESSAGE {
+            return None;
+        }
+        let flags = u32_at(data, 20)?;
+        let server_challenge = data[24..32].try_into().ok()?;
+        let target_info = if flags & NEGOTIATE_TARGET_INFO != 0 {
+            let len = u16::from_le_bytes(data.get(40..42)?.try_into().ok()?) as usize;
+            let offset = u32_at(data, 44)? as usize;
+            data.get(offset..offset.checked_add(len)?)?.to_vec()
+        } else {
+            vec![]
+        };
+        Some(Self {
+            flags,
+            server_challenge,
+            target_info,
+        })
+    }
+
+    /// Returns the time of the server as FILETIME when it is part of the target info.
+    fn timestamp(&self) -> Option<u64> {
+        let mut rest = &self.target_info[..];
+        while rest.len() >= 4 {
+            let id = u16::from_le_bytes([rest[0], rest[1]]);
+            let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+            let value = rest.get(4..4 + len)?;
+            match id {
+                0 => return None,
+                MSV_AV_TIMESTAMP => return Some(u64::from_le_bytes(value.try_into().ok()?)),
+                _ => rest = &rest[4 + len..],
+            }
+        }
+        None
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Returns the NEGOTIATE_MESSAGE starting the authentication.
+pub fn negotiate() -> Vec<u8> {
+    encode_negotiate(FLAGS)
+}
+
+/// Returns the NEGOTIATE_MESSAGE starting an authentication, which requests to sign and seal
+/// the messages of the session as well.
+pub fn negotiate_session_security() -> Vec<u8> {
+    encode_negotiate(FLAGS | SESSION_SECURITY)
+}
+
+fn encode_negotiate(flags: u32) -> Vec<u8> {
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&NEGOTIATE_MESSAGE.to_le_bytes());
+    message.extend_from_slice(&flags.to_le_bytes());
+    // empty domain and workstation fields
+    message.extend_from_slice(&[0; 16]);
+    message
+}
+
+/// Returns the session key of a NTLMv2 response, which is also the exported session key as the
+/// key exchange is not negotiated.
+pub fn session_key(hash: &[u8; 16], nt_response: &[u8]) -> [u8; 16] {
+    hmac_md5(hash, &[&nt_response[..16.min(nt_response.len())]])
+}
+
+/// Returns the AUTHENTICATE_MESSAGE answering the challenge of the server and the session key.
+///
+/// The domain may be empty for local accounts.
+pub fn authenticate(
+    challenge: &Challenge,
+    user: &str,
+    password: &str,
+    domain: &str,
+    client_challenge: &[u8; 8],
+) -> (Vec<u8>, [u8; 16]) {
+    let timestamp = challenge.timestamp().unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        (now.as_secs() + FILETIME_EPOCH_OFFSET) * 10_000_000 + now.subsec_nanos() as u64 / 100
+    });
+    let hash = ntowf_v2(user, password, domain);
+    let (nt, lm) = responses(&hash, challenge, client_challenge, timestamp);
+    let key = session_key(&hash, &nt);
+    let fields = [lm, nt, utf16le(domain), utf16le(user)];
+    let flags = challenge.flags & (FLAGS | SESSION_SECURITY);
+    (encode_authenticate(&fields, flags), key)
+}
+
+/// Returns the AUTHENTICATE_MESSAGE of an anonymous authentication, which has no session key.
+pub fn authenticate_anonymous(challenge: &Challenge) -> Vec<u8> {
+    let fields = [vec![0], vec![], vec![], vec![]];
+    encode_authenticate(&fields, (challenge.flags & FLAGS) | NEGOTIATE_ANONYMOUS)
+}
+
+/// Encodes the message from the LM and NT responses, the domain and the user.
+fn encode_authenticate(fields: &[Vec<u8>; 4], flags: u32) -> Vec<u8> {
+    // workstation and encrypted random session key
+    let fields: Vec<&[u8]> = fields
+        .iter()
+        .map(Vec::as_slice)
+        .chain([&[][..], &[][..]])
+        .collect();
+    let header_len = 64;
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&AUTHENTICATE_MESSAGE.to_le_bytes());
+    let mut offset = header_len;
+    for field in &fields {
+        let len = field.len() as u16;
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    message.extend_from_slice(&flags.to_le_bytes());
+    for field in fields {
+        message.extend_from_slice(field);
+    }
+    message
+}
+
+/// Keys and sequence number of one direction of a session
+struct Direction {
+    signing_key: [u8; 16],
+    sealing: Rc4<U16>,
+    sequence: u32,
+}
+
+impl Direction {
+    fn new(key: &[u8; 16], sealing_key: &[u8], signing: &[u8], sealing: &[u8]) -> Self {
+        let signing_key = Md5::digest([&key[..], signing].concat()).into();
+        let sealing_key = Md5::digest([sealing_key, sealing].concat());
+        Self {
+            signing_key,
+            sealing: Rc4::new(&sealing_key),
+            sequence: 0,
+        }
+    }
+
+    fn signature(&mut self, message: &[u8], key_exchange: bool) -> [u8; 16] {
+        let sequence = self.sequence.to_le_bytes();
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut checksum = hmac_md5(&self.signing_key, &[&sequence, message]);
+        if key_exchange {
+            self.sealing.apply_keystream(&mut checksum[..8]);
+        }
+        let mut signature = [0; 16];
+        // version
+        signature[0] = 1;
+        signature[4..12].copy_from_slice(&checksum[..8]);
+        signature[12..].copy_from_slice(&sequence);
+        signature
+    }
+}
+
+/// Signs and seals the messages of an authenticated session with extended session security as
+/// described in MS-NLMP section 3.4.
+///
+/// The connection oriented variant is implemented, the state of the keys and the sequence
+/// numbers are kept over the messages of the session.
+pub struct Session {
+    key_exchange: bool,
+    sending: Direction,
+    receiving: Direction,
+}
+
+impl Session {
+    /// Returns the session of the client from the flags negotiated by the challenge and the
+    /// exported session key, None when extended session security was not negotiated.
+    pub fn new(challenge: &Challenge, key: &[u8; 16]) -> Option<Self> {
+        Self::with_flags(challenge.flags & (FLAGS | SESSION_SECURITY), key, true)
+    }
+
+    fn with_flags(flags: u32, key: &[u8; 16], client: bool) -> Option<Self> {
+        if flags & NEGOTIATE_EXTENDED_SESSIONSECURITY == 0 {
+            return None;
+        }
+        let sealing_key = if flags & NEGOTIATE_128 != 0 {
+            &key[..]
+        } else if flags & NEGOTIATE_56 != 0 {
+            &key[..7]
+        } else {
+            &key[..5]
+        };
+        let to_server = Direction::new(key, sealing_key, CLIENT_SIGNING, CLIENT_SEALING);
+        let to_client = Direction::new(key, sealing_key, SERVER_SIGNING, SERVER_SEALING);
+        let (sending, receiving) = if client {
+            (to_server, to_client)
+        } else {
+            (to_client, to_server)
+        };
+        Some(Self {
+            key_exchange: flags & NEGOTIATE_KEY_EXCH != 0,
+            sending,
+            receiving,
+        })
+    }
+
+    /// Encrypts the data of a message to send, its signature has to be created afterwards.
+    pub fn seal(&mut self, data: &mut [u8]) {
+        self.sending.sealing.apply_keystream(data);
+    }
+
+    /// Returns the signature of the message to send.
+    pub fn sign(&mut self, message: &[u8]) -> [u8; 16] {
+        self.sending.signature(message, self.key_exchange)
+    }
+
+    /// Decrypts the data of a received message, before its signature is verified.
+    pub fn unseal(&mut self, data: &mut [u8]) {
+        self.receiving.sealing.apply_keystream(data);
+    }
+
+    /// Returns whether the signature of the received message is valid.
+    pub fn verify(&mut self, message: &[u8], signature: &[u8]) -> bool {
+        self.receiving.signature(message, self.key_exchange) == signature
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{
+        authenticate, negotiate, ntowf_v2, responses, session_key, u32_at, Challenge, Session,
+    };
+
+    /// Returns a CHALLENGE_MESSAGE with the example values of MS-NLMP section 4.2.1.
+    pub fn challenge_message() -> Vec<u8> {
+        let target_info =
+            hex::decode("02000c0044006f006d00610069006e0001000c0053006500720076006500720000000000")
+                .unwrap();
+        let mut message = b"NTLMSSP\0".to_vec();
+        message.extend_from_slice(&2u32.to_le_bytes());
+        // target name
+        message.extend_from_slice(&[0, 0, 0, 0, 48, 0, 0, 0]);
+        message.extend_from_slice(&0xe28a8233u32.to_le_bytes());
+        message.extend_from_slice(&hex::decode("0123456789abcdef").unwrap());
+        message.extend_from_slice(&[0; 8]);
+        let len = target_info.len() as u16;
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&48u32.to_le_bytes());
+        message.extend_from_slice(&target_info);
+        message
+    }
+
+    /// Returns the session key derived by the server from an AUTHENTICATE_MESSAGE.
+    pub fn server_session_key(message: &[u8], password: &str) -> Option<[u8; 16]> {
+        let (user, domain, nt) = parse(message)?;
+        Some(session_key(&ntowf_v2(&user, password, &domain), nt))
+    }
+
+    /// Returns the session of the server with the flags of the AUTHENTICATE_MESSAGE and the
+    /// session key.
+    pub fn server_session(message: &[u8], key: &[u8; 16]) -> Session {
+        Session::with_flags(u32_at(message, 60).unwrap(), key, false).unwrap()
+    }
+
+    /// Returns the user, the domain and the NT response of an AUTHENTICATE_MESSAGE.
+    fn parse(message: &[u8]) -> Option<(String, String, &[u8])> {
+        let field = |i: usize| {
+            let len = u16::from_le_bytes([message[12 + i * 8], message[13 + i * 8]]) as usize;
+            let offset = u32_at(message, 16 + i * 8)? as usize;
+            message.get(offset..offset + len)
+        };
+        let text = |x: &[u8]| {
+            String::from_utf16(
+                &x.chunks(2)
+                    .map(|x| u16::from_le_bytes([x[0], x[1]]))
+                    .collect::<Vec<_>>(),
+            )
+            .ok()
+        };
+        Some((text(field(3)?)?, text(field(2)?)?, field(1)?))
+    }
+
+    /// Returns the user name and whether the NTLMv2 response of an AUTHENTICATE_MESSAGE is valid
+    /// for the password.
+    pub fn verify(message: &[u8], challenge: &[u8], password: &str) -> Option<(String, bool)> {
+        let (user, domain, nt) = parse(message)?;
+        let challenge = Challenge::parse(challenge)?;
+        let hash = ntowf_v2(&user, password, &domain);
+        let client_challenge = nt.get(32..40)?.try_into().ok()?;
+        let timestamp = u64::from_le_bytes(nt.get(24..32)?.try_into().ok()?);
+        let (expected, _) = responses(&hash, &challenge, client_challenge, timestamp);
+        Some((user, expected == nt))
+    }
+
+    #[test]
+    fn ntlmv2() {
+        let challenge = Challenge::parse(&challenge_message()).unwrap();
+        let hash = ntowf_v2("User", "Password", "Domain");
+        assert_eq!(hex::encode(hash), "0c868a403bfd7a93a3001ef22ef02e3f");
+        let (nt, lm) = responses(&hash, &challenge, &[0xaa; 8], 0);
+        assert_eq!(hex::encode(&nt[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+        assert_eq!(
+            hex::encode(lm),
+            "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa"
+        );
+
+        assert_eq!(&negotiate()[..12], b"NTLMSSP\0\x01\0\0\0");
+        let (message, key) = authenticate(&challenge, "User", "Password", "Domain", &[0xaa; 8]);
+        assert_eq!(key, session_key(&hash, &message[88..104]));
+        assert_eq!(
+            verify(&message, &challenge_message(), "Password"),
+            Some(("User".to_string(), true))
+        );
+        assert_eq!(
+            verify(&message, &challenge_message(), "wrong"),
+            Some(("User".to_string(), false))
+        );
+        assert_eq!(Challenge::parse(&message), None);
+    }
+
+    #[test]
+    fn session_security() {
+        // the example of MS-NLMP section 4.2.4.4, which uses a key exchange
+        let challenge = Challenge::parse(&challenge_message()).unwrap();
+        let mut client = Session::with_flags(challenge.flags, &[0x55; 16], true).unwrap();
+        let mut server = Session::with_flags(challenge.flags, &[0x55; 16], false).unwrap();
+        let plaintext = super::utf16le("Plaintext");
+        let mut data = plaintext.clone();
+        client.seal(&mut data);
+        let signature = client.sign(&plaintext);
+        assert_eq!(hex::encode(&data), "54e50165bf1936dc996020c1811b0f06fb5f");
+        assert_eq!(hex::encode(signature), "010000007fb38ec5c55d497600000000");
+        server.unseal(&mut data);
+        assert_eq!(data, plaintext);
+        assert!(server.verify(&data, &signature));
+        // the sequence number was incremented
+        assert!(!server.verify(&data, &signature));
+
+        let mut session = Session::new(&challenge, &[0x55; 16]).unwrap();
+        assert!(!session.key_exchange);
+        assert_eq!(session.sign(b"")[12..], [0, 0, 0, 0]);
+        assert_eq!(session.sign(b"")[12..], [1, 0, 0, 0]);
+    }
+}
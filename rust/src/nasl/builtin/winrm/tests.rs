@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::nasl::builtin::lsc::windows::WindowsInventory;
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+use super::ntlm;
+
+const INVENTORY: &str = "product_name=Windows 10 Pro\r
+edition_id=Professional\r
+display_version=23H2\r
+release_id=2009\r
+version=10.0\r
+build=22631\r
+ubr=3447\r
+installation_type=Client\r
+arch=AMD64\r
+hotfix=KB5034441\r
+hotfix=KB5036893\r
+software=7-Zip 23.01 (x64)\t23.01\tIgor Pavlov\tC:\\Program Files\\7-Zip\\\r
+software=Mozilla Firefox (x64 en-US)\t124.0.2\tMozilla\t\r
+";
+
+/// Answers the requests of a single connection like the WinRM service, with NTLM or Basic
+/// authentication of the user "User" with the password "Password".
+fn winrm_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut authenticated = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                let mut authorization = String::new();
+                let mut length = 0;
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some(("Authorization", x)) => authorization = x.to_string(),
+                        Some(("Content-Length", x)) => length = x.parse().unwrap(),
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let (scheme, token) = authorization.split_once(' ').unwrap_or_default();
+                let token = STANDARD.decode(token).unwrap_or_default();
+                let response = match scheme {
+                    "Basic" if token == b"User:Password" => None,
+                    "Negotiate" if body.is_empty() => {
+                        let challenge = STANDARD.encode(ntlm::tests::challenge_message());
+                        Some(format!("HTTP/1.1 401 \r\nWWW-Authenticate: Negotiate {challenge}\r\nContent-Length: 0\r\n\r\n"))
+                    }
+                    "Negotiate"
+                        if ntlm::tests::verify(
+                            &token,
+                            &ntlm::tests::challenge_message(),
+                            "Password",
+                        )
+                        .is_some_and(|(user, valid)| user == "User" && valid) =>
+                    {
+                        authenticated = true;
+                        None
+                    }
+                    "" if authenticated => None,
+                    _ => Some("HTTP/1.1 401 \r\nContent-Length: 0\r\n\r\n".to_string()),
+                };
+                let response = response.unwrap_or_else(|| {
+                    let answer = if body.contains("transfer/Create") {
+                        "<rsp:Shell><rsp:ShellId>shell-1</rsp:ShellId></rsp:Shell>".to_string()
+                    } else if body.contains("shell/Command") {
+                        "<rsp:CommandResponse><rsp:CommandId>cmd-1</rsp:CommandId></rsp:CommandResponse>".to_string()
+                    } else if body.contains("shell/Receive") {
+                        let stdout = if body.contains("cmd-1") {
+                            INVENTORY
+                        } else {
+                            ""
+                        };
+                        format!(
+                            r#"<rsp:ReceiveResponse><rsp:Stream Name="stdout" CommandId="cmd-1">{}</rsp:Stream><rsp:Stream Name="stdout" CommandId="cmd-1" End="true"></rsp:Stream><rsp:CommandState CommandId="cmd-1" State="http://schemas.microsoft.com/wbem/wsman/1/windows/shell/CommandState/Done"><rsp:ExitCode>0</rsp:ExitCode></rsp:CommandState></rsp:ReceiveResponse>"#,
+                            STANDARD.encode(stdout)
+                        )
+                    } else {
+                        String::new()
+                    };
+                    let envelope = format!(
+                        r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:rsp="http://schemas.microsoft.com/wbem/wsman/1/windows/shell"><s:Header/><s:Body>{answer}</s:Body></s:Envelope>"#
+                    );
+                    // split into chunks as the WinRM service does
+                    let (first, second) = envelope.split_at(envelope.len() / 2);
+                    format!(
+                        "HTTP/1.1 200 \r\nContent-Type: application/soap+xml;charset=UTF-8\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{first}\r\n{:x}\r\n{second}\r\n0\r\n\r\n",
+                        first.len(),
+                        second.len()
+                    )
+                });
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        }
+    });
+    port
+}
+
+#[test]
+fn parse_inventory() {
+    let inventory = WindowsInventory::parse(INVENTORY).unwrap();
+    assert_eq!(inventory.product_name, "Windows 11 Pro");
+    assert_eq!(inventory.full_version(), "10.0.22631.3447");
+    assert_eq!(inventory.hotfixes, ["KB5034441", "KB5036893"]);
+    assert_eq!(inventory.software.len(), 2);
+    assert_eq!(inventory.software[0].location, "C:\\Program Files\\7-Zip\\");
+    assert_eq!(inventory.software[1].publisher, "Mozilla");
+    assert_eq!(inventory.release_notus(), "windows_11_23h2_x64");
+    assert_eq!(
+        inventory.cpe(),
+        "cpe:/o:microsoft:windows_11_23h2:10.0.22631.3447"
+    );
+
+    let server = WindowsInventory::parse(
+        "product_name=Windows Server 2012 R2 Standard\nversion=6.3\nbuild=9600\nrelease_id=\ninstallation_type=Server\narch=AMD64\n",
+    )
+    .unwrap();
+    assert_eq!(server.release_notus(), "windows_server_2012_r2_x64");
+    assert_eq!(
+        server.cpe(),
+        "cpe:/o:microsoft:windows_server_2012_r2:6.3.9600.0"
+    );
+    assert_eq!(WindowsInventory::parse("product_name=Windows\n"), None);
+}
+
+#[test]
+fn winrm() {
+    let port = winrm_server();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(r#"winrm_connect(port: {port}, login: "User", password: "wrong");"#),
+        NaslValue::Null,
+    );
+    t.run(r#"set_kb_item(name: "SMB/login", value: "User");"#);
+    t.run(r#"set_kb_item(name: "SMB/password", value: "Password");"#);
+    t.run(r#"set_kb_item(name: "SMB/domain", value: "Domain");"#);
+    t.ok(format!("s = winrm_connect(port: {port});"), 1);
+    t.ok(
+        r#"winrm_exec(session: s, cmd: "ver");"#,
+        NaslValue::String(INVENTORY.to_string()),
+    );
+    t.ok("winrm_gather_package_list(session: s);", true);
+    t.ok("winrm_close(session: s);", NaslValue::Null);
+    check_err_matches!(
+        t,
+        r#"winrm_exec(session: s, cmd: "ver");"#,
+        FunctionErrorKind::Diagnostic(_, _)
+    );
+    t.ok(
+        format!(
+            r#"winrm_connect(port: {port}, auth: "basic", login: "User", password: "Password");"#
+        ),
+        2,
+    );
+
+    t.ok(
+        r#"get_kb_item("SMB/WindowsName");"#,
+        NaslValue::String("Windows 11 Pro".to_string()),
+    );
+    t.ok(r#"get_kb_item("SMB/WindowsBuild");"#, "22631");
+    t.ok(
+        r#"get_kb_item("SMB/Windows/Software/Mozilla Firefox (x64 en-US)/Version");"#,
+        "124.0.2",
+    );
+    t.ok(
+        r#"get_kb_item("ssh/login/release_notus");"#,
+        "windows_11_23h2_x64",
+    );
+    t.ok(
+        r#"get_kb_item("ssh/login/package_list_notus");"#,
+        "10.0.22631.3447",
+    );
+    t.ok(
+        r#"get_kb_item("HostDetails/NVT/" + get_kb_item("HostDetails/NVT") + "/OS");"#,
+        "cpe:/o:microsoft:windows_11_23h2:10.0.22631.3447",
+    );
+}
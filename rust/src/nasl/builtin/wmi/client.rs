@@ -0,0 +1,293 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Client of the WMI Remote Protocol (MS-WMI), querying the instances of a namespace via the
+//! DCOM interfaces IWbemLevel1Login, IWbemServices and IEnumWbemClassObject.
+
+use std::time::Duration;
+
+use super::{
+    dcom::{self, ObjRef},
+    object::{self, Instance, Parameter},
+};
+use crate::nasl::builtin::{
+    network::tcp::TcpConnection,
+    smb::{
+        client::Credentials,
+        message::{utf16le, SmbError},
+        rpc::{
+            ndr::{Reader, Uuid, Writer},
+            AuthLevel, RpcClient, SyntaxId, Tcp,
+        },
+    },
+};
+
+/// CLSID_WbemLevel1Login, 8bc3f05e-d86b-11d0-a075-00c04fb68820
+pub const CLSID_WBEM_LEVEL1_LOGIN: Uuid = [
+    0x5e, 0xf0, 0xc3, 0x8b, 0x6b, 0xd8, 0xd0, 0x11, 0xa0, 0x75, 0x00, 0xc0, 0x4f, 0xb6, 0x88, 0x20,
+];
+/// CLSID_WbemClassObject, 4590f812-1d3a-11d0-891f-00aa004b2e24, the unmarshaler of the objects
+pub const CLSID_WBEM_CLASS_OBJECT: Uuid = [
+    0x12, 0xf8, 0x90, 0x45, 0x3a, 0x1d, 0xd0, 0x11, 0x89, 0x1f, 0x00, 0xaa, 0x00, 0x4b, 0x2e, 0x24,
+];
+
+/// IID_IWbemClassObject, dc12a681-737f-11cf-884d-00aa004b2e24
+pub const IID_IWBEM_CLASS_OBJECT: Uuid = [
+    0x81, 0xa6, 0x12, 0xdc, 0x7f, 0x73, 0xcf, 0x11, 0x88, 0x4d, 0x00, 0xaa, 0x00, 0x4b, 0x2e, 0x24,
+];
+
+/// f309ad18-d86a-11d0-a075-00c04fb68820 version 0.0
+pub const IWBEM_LEVEL1_LOGIN: SyntaxId = SyntaxId {
+    uuid: [
+        0x18, 0xad, 0x09, 0xf3, 0x6a, 0xd8, 0xd0, 0x11, 0xa0, 0x75, 0x00, 0xc0, 0x4f, 0xb6, 0x88,
+        0x20,
+    ],
+    version: 0,
+};
+/// 9556dc99-828c-11cf-a37e-00aa003240c7 version 0.0
+pub const IWBEM_SERVICES: SyntaxId = SyntaxId {
+    uuid: [
+        0x99, 0xdc, 0x56, 0x95, 0x8c, 0x82, 0xcf, 0x11, 0xa3, 0x7e, 0x00, 0xaa, 0x00, 0x32, 0x40,
+        0xc7,
+    ],
+    version: 0,
+};
+/// 027947e1-d731-11ce-a357-000000000001 version 0.0
+pub const IENUM_WBEM_CLASS_OBJECT: SyntaxId = SyntaxId {
+    uuid: [
+        0xe1, 0x47, 0x79, 0x02, 0x31, 0xd7, 0xce, 0x11, 0xa3, 0x57, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01,
+    ],
+    version: 0,
+};
+
+pub const NTLM_LOGIN: u16 = 6;
+pub const EXEC_QUERY: u16 = 20;
+pub const NEXT: u16 = 4;
+pub const EXEC_METHOD: u16 = 24;
+
+/// Class of the input parameters of a method
+const PARAMETERS: &str = "__PARAMETERS";
+
+/// WBEM_FLAG_RETURN_IMMEDIATELY and WBEM_FLAG_FORWARD_ONLY
+const QUERY_FLAGS: u32 = 0x10 | 0x20;
+/// WBEM_INFINITE
+const INFINITE: u32 = 0xffff_ffff;
+/// Number of objects requested at once
+const BATCH: u32 = 16;
+/// WBEM_S_NO_ERROR, further objects may follow
+const MORE_OBJECTS: u32 = 0;
+
+/// Writes a BSTR, marshaled as unique pointer to a FLAGGED_WORD_BLOB.
+fn bstr(stub: &mut Writer, value: &str) {
+    let characters = utf16le(value);
+    let count = (characters.len() / 2) as u32;
+    stub.pointer(true)
+        .u32(count)
+        .u32(characters.len() as u32)
+        .u32(count)
+        .bytes(&characters);
+}
+
+/// Returns the network resource of the namespace as expected by NTLMLogin, e.g.
+/// `//./root/cimv2` for `root\cimv2`.
+fn network_resource(namespace: &str) -> String {
+    let namespace = namespace.replace('\\', "/");
+    match namespace.starts_with("//") {
+        true => namespace,
+        false => format!("//./{}", namespace.trim_start_matches('/')),
+    }
+}
+
+/// Logs into the namespace with the IWbemLevel1Login interface and returns the IWbemServices
+/// interface of the namespace.
+fn login(rpc: &mut RpcClient, login: &ObjRef, namespace: &str) -> Result<ObjRef, SmbError> {
+    let mut stub = Writer::new();
+    dcom::orpc_this(&mut stub);
+    stub.unique_string(Some(&network_resource(namespace)))
+        // preferred locale, flags and context
+        .unique_string(None)
+        .u32(0)
+        .pointer(false);
+    let response = rpc.call_object(&IWBEM_LEVEL1_LOGIN, &login.ipid, NTLM_LOGIN, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    dcom::orpc_that(&mut reader)?;
+    dcom::returned_interface(&mut reader)
+}
+
+/// Decodes the CIM object of the OBJREF.
+fn instance(objref: &[u8]) -> Result<Instance, SmbError> {
+    let (clsid, data) = dcom::custom(objref)?;
+    if clsid != CLSID_WBEM_CLASS_OBJECT {
+        return Err(SmbError::Protocol("unexpected marshaling of a CIM object"));
+    }
+    object::decode(data)
+}
+
+/// Releases the reference to the interface, a failure is only logged.
+fn release(rpc: &mut RpcClient, rem_unknown: &Uuid, object: &ObjRef) {
+    if let Err(e) = dcom::release(rpc, rem_unknown, &[object]) {
+        tracing::debug!(%e, "unable to release the DCOM object");
+    }
+}
+
+/// Connection to the IWbemServices interface of a namespace
+pub struct WmiClient {
+    rpc: RpcClient<'static>,
+    /// IPID of the IRemUnknown interface of the object exporter
+    rem_unknown: Uuid,
+    services: ObjRef,
+}
+
+impl WmiClient {
+    /// Activates the WMI service via the endpoint mapper at the port and logs into the
+    /// namespace, e.g. `root\cimv2`. The connections are opened by the given function.
+    pub fn connect(
+        mut connect: impl FnMut(u16) -> Result<TcpConnection, SmbError>,
+        port: u16,
+        credentials: &Credentials,
+        level: AuthLevel,
+        namespace: &str,
+        timeout: Duration,
+    ) -> Result<Self, SmbError> {
+        let activation = {
+            let mut rpc = RpcClient::bind_authenticated(
+                Tcp::new(connect(port)?, timeout),
+                &[dcom::IACTIVATION],
+                credentials,
+                level,
+            )?;
+            dcom::activate(&mut rpc, &CLSID_WBEM_LEVEL1_LOGIN, &IWBEM_LEVEL1_LOGIN.uuid)?
+        };
+        let port = dcom::tcp_port(&activation.bindings)
+            .ok_or(SmbError::Protocol("no TCP binding of the WMI service"))?;
+        let mut rpc = RpcClient::bind_authenticated(
+            Tcp::new(connect(port)?, timeout),
+            &[
+                IWBEM_LEVEL1_LOGIN,
+                IWBEM_SERVICES,
+                IENUM_WBEM_CLASS_OBJECT,
+                dcom::IREMUNKNOWN,
+            ],
+            credentials,
+            level,
+        )?;
+        let login = activation.object;
+        let services = self::login(&mut rpc, &login, namespace);
+        // the login object is not needed after the login
+        release(&mut rpc, &activation.rem_unknown, &login);
+        Ok(Self {
+            rpc,
+            rem_unknown: activation.rem_unknown,
+            services: services?,
+        })
+    }
+
+    /// Returns the next objects of the enumerator and whether more objects may follow.
+    fn next(&mut self, enumerator: &ObjRef) -> Result<(Vec<Instance>, bool), SmbError> {
+        let mut stub = Writer::new();
+        dcom::orpc_this(&mut stub);
+        stub.u32(INFINITE).u32(BATCH);
+        let response = self.rpc.call_object(
+            &IENUM_WBEM_CLASS_OBJECT,
+            &enumerator.ipid,
+            NEXT,
+            &stub.finish(),
+        )?;
+        let mut reader = Reader::new(&response);
+        dcom::orpc_that(&mut reader)?;
+        // conformant and varying array of unique pointers
+        let _max_count = reader.u32()?;
+        let _offset = reader.u32()?;
+        let count = reader.u32()?;
+        let pointers = (0..count)
+            .map(|_| reader.pointer())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut objects = vec![];
+        for _ in pointers.into_iter().filter(|x| *x) {
+            objects.push(instance(&dcom::interface_pointer(&mut reader)?)?);
+        }
+        let _returned = reader.u32()?;
+        let more = dcom::hresult(&mut reader)? == MORE_OBJECTS;
+        Ok((objects, more))
+    }
+
+    /// Runs the WQL query and returns the instances.
+    pub fn query(&mut self, query: &str) -> Result<Vec<Instance>, SmbError> {
+        let mut stub = Writer::new();
+        dcom::orpc_this(&mut stub);
+        bstr(&mut stub, "WQL");
+        bstr(&mut stub, query);
+        // flags and context
+        stub.u32(QUERY_FLAGS).pointer(false);
+        let response = self.rpc.call_object(
+            &IWBEM_SERVICES,
+            &self.services.ipid,
+            EXEC_QUERY,
+            &stub.finish(),
+        )?;
+        let mut reader = Reader::new(&response);
+        dcom::orpc_that(&mut reader)?;
+        let enumerator = dcom::returned_interface(&mut reader)?;
+        let mut instances = vec![];
+        let result = loop {
+            match self.next(&enumerator) {
+                Ok((objects, more)) => {
+                    instances.extend(objects);
+                    if !more {
+                        break Ok(instances);
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        release(&mut self.rpc, &self.rem_unknown, &enumerator);
+        result
+    }
+
+    /// Runs the static method of the class, e.g. `GetStringValue` of `StdRegProv`, and returns
+    /// its output parameters.
+    pub fn exec_method(
+        &mut self,
+        class: &str,
+        method: &str,
+        parameters: &[(&str, Parameter)],
+    ) -> Result<Instance, SmbError> {
+        let objref = dcom::custom_objref(
+            &IID_IWBEM_CLASS_OBJECT,
+            &CLSID_WBEM_CLASS_OBJECT,
+            &object::encode(PARAMETERS, parameters),
+        );
+        let mut stub = Writer::new();
+        dcom::orpc_this(&mut stub);
+        bstr(&mut stub, class);
+        bstr(&mut stub, method);
+        // flags and context
+        stub.u32(0).pointer(false);
+        dcom::write_interface_pointer(&mut stub, &objref);
+        // a pointer to the returned output parameters and no call result
+        stub.pointer(true).pointer(false).pointer(false);
+        let response = self.rpc.call_object(
+            &IWBEM_SERVICES,
+            &self.services.ipid,
+            EXEC_METHOD,
+            &stub.finish(),
+        )?;
+        let mut reader = Reader::new(&response);
+        dcom::orpc_that(&mut reader)?;
+        let objref = match reader.pointer()? && reader.pointer()? {
+            true => Some(dcom::interface_pointer(&mut reader)?),
+            false => None,
+        };
+        let _call_result = reader.pointer()?;
+        dcom::hresult(&mut reader)?;
+        instance(&objref.ok_or(SmbError::Protocol("no output parameters returned"))?)
+    }
+
+    /// Releases the IWbemServices interface, the connection is closed when the client is
+    /// dropped.
+    pub fn close(mut self) {
+        release(&mut self.rpc, &self.rem_unknown, &self.services);
+    }
+}
@@ -0,0 +1,292 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Distributed Component Object Model Remote Protocol (MS-DCOM): the activation of objects via
+//! IActivation, the references to their interfaces and the ORPC extensions of the calls.
+
+use crate::nasl::builtin::smb::{
+    message::SmbError,
+    rpc::{
+        ndr::{Reader, Uuid, Writer},
+        RpcClient, SyntaxId,
+    },
+};
+
+/// IActivation, 4d9f4ab8-7d1c-11cf-861e-0020af6e7c57 version 0.0
+pub const IACTIVATION: SyntaxId = SyntaxId {
+    uuid: [
+        0xb8, 0x4a, 0x9f, 0x4d, 0x1c, 0x7d, 0xcf, 0x11, 0x86, 0x1e, 0x00, 0x20, 0xaf, 0x6e, 0x7c,
+        0x57,
+    ],
+    version: 0,
+};
+
+/// IRemUnknown, 00000131-0000-0000-c000-000000000046 version 0.0
+pub const IREMUNKNOWN: SyntaxId = SyntaxId {
+    uuid: [
+        0x31, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x46,
+    ],
+    version: 0,
+};
+
+pub const REMOTE_ACTIVATION: u16 = 0;
+pub const REM_RELEASE: u16 = 5;
+
+/// Version 5.7 of the protocol
+const COM_VERSION: [u16; 2] = [5, 7];
+/// RPC_C_IMP_LEVEL_IDENTIFY, the server may identify but not impersonate the client
+const IMPERSONATION_LEVEL: u32 = 2;
+/// Tower identifier of the ncacn_ip_tcp protocol sequence
+pub const NCACN_IP_TCP: u16 = 0x07;
+
+/// "MEOW"
+const OBJREF_SIGNATURE: u32 = 0x574f_454d;
+const OBJREF_STANDARD: u32 = 0x01;
+const OBJREF_CUSTOM: u32 = 0x04;
+
+/// Reference to an interface of a remote object, the STDOBJREF of an OBJREF_STANDARD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjRef {
+    pub public_refs: u32,
+    /// Identifies the object exporter
+    pub oxid: u64,
+    pub oid: u64,
+    /// Identifies the interface of the object, the object UUID of the calls
+    pub ipid: Uuid,
+}
+
+/// Result of an activation
+#[derive(Debug)]
+pub struct Activation {
+    /// String bindings of the object exporter with their tower identifier
+    pub bindings: Vec<(u16, String)>,
+    /// IPID of the IRemUnknown interface of the object exporter
+    pub rem_unknown: Uuid,
+    /// The requested interface of the object
+    pub object: ObjRef,
+}
+
+/// Writes the ORPCTHIS starting the stub data of a request.
+pub fn orpc_this(stub: &mut Writer) {
+    let causality = uuid::Uuid::new_v4().to_bytes_le();
+    stub.u16(COM_VERSION[0])
+        .u16(COM_VERSION[1])
+        // flags and reserved
+        .u32(0)
+        .u32(0)
+        .uuid(&causality)
+        // extensions
+        .pointer(false);
+}
+
+/// Reads the ORPCTHAT starting the stub data of a response, its extensions are skipped.
+pub fn orpc_that(reader: &mut Reader) -> Result<(), SmbError> {
+    let _flags = reader.u32()?;
+    if reader.pointer()? {
+        let _size = reader.u32()?;
+        let _reserved = reader.u32()?;
+        if reader.pointer()? {
+            let count = reader.u32()?;
+            let extents = (0..count)
+                .map(|_| reader.pointer())
+                .collect::<Result<Vec<_>, _>>()?;
+            for _ in extents.into_iter().filter(|x| *x) {
+                // conformance, identifier, size and data padded to 8 octets
+                let length = reader.u32()? as usize;
+                reader.uuid()?;
+                reader.u32()?;
+                reader.bytes(length)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the HRESULT returned by a method, success codes like S_FALSE are returned as well.
+pub fn hresult(reader: &mut Reader) -> Result<u32, SmbError> {
+    match reader.u32()? {
+        x if x & 0x8000_0000 != 0 => Err(SmbError::HResult(x)),
+        x => Ok(x),
+    }
+}
+
+/// Reads a MInterfacePointer and returns the OBJREF it contains.
+pub fn interface_pointer(reader: &mut Reader) -> Result<Vec<u8>, SmbError> {
+    let _count = reader.u32()?;
+    let length = reader.u32()? as usize;
+    Ok(reader.bytes(length)?.to_vec())
+}
+
+/// Writes an unique pointer to a MInterfacePointer containing the OBJREF.
+pub fn write_interface_pointer(stub: &mut Writer, objref: &[u8]) {
+    stub.pointer(true)
+        .u32(objref.len() as u32)
+        .u32(objref.len() as u32)
+        .bytes(objref);
+}
+
+/// Reads an unique pointer to a MInterfacePointer followed by the HRESULT of the method and
+/// returns the reference to the interface.
+pub fn returned_interface(reader: &mut Reader) -> Result<ObjRef, SmbError> {
+    let objref = match reader.pointer()? {
+        true => Some(interface_pointer(reader)?),
+        false => None,
+    };
+    hresult(reader)?;
+    standard(&objref.ok_or(SmbError::Protocol("no interface returned"))?)
+}
+
+/// Returns the interface identifier and the reader of the data following the header of an
+/// OBJREF of the given type.
+fn objref(data: &[u8], kind: u32) -> Result<(Uuid, Reader<'_>), SmbError> {
+    let mut reader = Reader::new(data);
+    if reader.u32()? != OBJREF_SIGNATURE || reader.u32()? != kind {
+        return Err(SmbError::Protocol("unsupported OBJREF"));
+    }
+    let iid = reader.uuid()?;
+    Ok((iid, reader))
+}
+
+/// Parses an OBJREF_STANDARD.
+pub fn standard(data: &[u8]) -> Result<ObjRef, SmbError> {
+    let (_, mut reader) = objref(data, OBJREF_STANDARD)?;
+    let _flags = reader.u32()?;
+    Ok(ObjRef {
+        public_refs: reader.u32()?,
+        oxid: reader.u64()?,
+        oid: reader.u64()?,
+        ipid: reader.uuid()?,
+    })
+}
+
+/// Parses an OBJREF_CUSTOM and returns the class identifier of the unmarshaler and the data of
+/// the object.
+pub fn custom(data: &[u8]) -> Result<(Uuid, &[u8]), SmbError> {
+    let (_, mut reader) = objref(data, OBJREF_CUSTOM)?;
+    let clsid = reader.uuid()?;
+    // extension length and size of the data
+    reader.u32()?;
+    reader.u32()?;
+    Ok((clsid, &data[48..]))
+}
+
+/// Returns an OBJREF_CUSTOM of the interface with the class identifier of the unmarshaler and
+/// the data of the object.
+pub fn custom_objref(iid: &Uuid, clsid: &Uuid, data: &[u8]) -> Vec<u8> {
+    let mut objref = OBJREF_SIGNATURE.to_le_bytes().to_vec();
+    objref.extend(OBJREF_CUSTOM.to_le_bytes());
+    objref.extend(iid);
+    objref.extend(clsid);
+    // no extension
+    objref.extend(0u32.to_le_bytes());
+    objref.extend((data.len() as u32).to_le_bytes());
+    objref.extend(data);
+    objref
+}
+
+/// Reads a DUALSTRINGARRAY and returns its string bindings, the security bindings are ignored.
+fn string_bindings(reader: &mut Reader) -> Result<Vec<(u16, String)>, SmbError> {
+    let _count = reader.u32()?;
+    let entries = reader.u16()? as usize;
+    let security_offset = reader.u16()? as usize;
+    let array = (0..entries)
+        .map(|_| reader.u16())
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut bindings = vec![];
+    // each binding is a tower identifier followed by a null terminated address, the list ends
+    // with an empty binding
+    let mut rest = &array[..security_offset.min(entries)];
+    while let Some((&tower, address)) = rest.split_first() {
+        if tower == 0 {
+            break;
+        }
+        let end = address
+            .iter()
+            .position(|x| *x == 0)
+            .ok_or(SmbError::Protocol("unterminated string binding"))?;
+        bindings.push((tower, String::from_utf16_lossy(&address[..end])));
+        rest = &address[end + 1..];
+    }
+    Ok(bindings)
+}
+
+/// Returns the port of the first ncacn_ip_tcp binding, e.g. of `host[49668]`.
+pub fn tcp_port(bindings: &[(u16, String)]) -> Option<u16> {
+    bindings
+        .iter()
+        .filter(|(tower, _)| *tower == NCACN_IP_TCP)
+        .find_map(|(_, address)| {
+            let (_, port) = address.split_once('[')?;
+            port.strip_suffix(']')?.parse().ok()
+        })
+}
+
+/// Creates an object of the class and returns the reference to the requested interface.
+pub fn activate(rpc: &mut RpcClient, clsid: &Uuid, iid: &Uuid) -> Result<Activation, SmbError> {
+    let mut stub = Writer::new();
+    orpc_this(&mut stub);
+    stub.uuid(clsid)
+        // object name and storage
+        .pointer(false)
+        .pointer(false)
+        .u32(IMPERSONATION_LEVEL)
+        // mode
+        .u32(0)
+        // one interface
+        .u32(1)
+        .pointer(true)
+        .u32(1)
+        .uuid(iid)
+        // one protocol sequence
+        .u16(1)
+        .u32(1)
+        .u16(NCACN_IP_TCP);
+    let response = rpc.call(REMOTE_ACTIVATION, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    orpc_that(&mut reader)?;
+    let _oxid = reader.u64()?;
+    let bindings = match reader.pointer()? {
+        true => string_bindings(&mut reader)?,
+        false => vec![],
+    };
+    let rem_unknown = reader.uuid()?;
+    let _authentication_hint = reader.u32()?;
+    let _server_version = (reader.u16()?, reader.u16()?);
+    hresult(&mut reader)?;
+    let _count = reader.u32()?;
+    let objref = match reader.pointer()? {
+        true => Some(interface_pointer(&mut reader)?),
+        false => None,
+    };
+    // the result of the interface and of the method
+    let _count = reader.u32()?;
+    hresult(&mut reader)?;
+    hresult(&mut reader)?;
+    let object = standard(&objref.ok_or(SmbError::Protocol("no interface returned"))?)?;
+    Ok(Activation {
+        bindings,
+        rem_unknown,
+        object,
+    })
+}
+
+/// Releases the references to the interfaces, so that the server can free the objects.
+pub fn release(
+    rpc: &mut RpcClient,
+    rem_unknown: &Uuid,
+    objects: &[&ObjRef],
+) -> Result<(), SmbError> {
+    let mut stub = Writer::new();
+    orpc_this(&mut stub);
+    stub.u16(objects.len() as u16).u32(objects.len() as u32);
+    for object in objects {
+        // public and private references
+        stub.uuid(&object.ipid).u32(object.public_refs).u32(0);
+    }
+    let response = rpc.call_object(&IREMUNKNOWN, rem_unknown, REM_RELEASE, &stub.finish())?;
+    let mut reader = Reader::new(&response);
+    orpc_that(&mut reader)?;
+    hresult(&mut reader).map(|_| ())
+}
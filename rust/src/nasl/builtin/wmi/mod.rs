@@ -0,0 +1,611 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to run WQL queries on Windows hosts and to access their registry via
+//! the WMI service, using DCOM over DCE/RPC on TCP authenticated with NTLM.
+
+mod client;
+mod dcom;
+mod object;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use client::WmiClient;
+use object::{Instance, Parameter, Value};
+
+use crate::nasl::prelude::*;
+
+use super::{
+    network::{network_utils::resolve_host, socket::NaslSockets, verify_port},
+    smb::{client::Credentials, message::SmbError, rpc::AuthLevel},
+};
+
+/// Port of the endpoint mapper activating the WMI service
+const ENDPOINT_MAPPER_PORT: u16 = 135;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_NAMESPACE: &str = "root\\cimv2";
+const RSOP_NAMESPACE: &str = "root\\rsop\\computer";
+const REGISTRY_NAMESPACE: &str = "root\\default";
+/// Class of the registry provider
+const REGISTRY_PROVIDER: &str = "StdRegProv";
+/// Hive of the registry functions unless another one is given
+const HKEY_LOCAL_MACHINE: u32 = 0x8000_0002;
+
+#[derive(Default)]
+struct Handles {
+    clients: HashMap<usize, WmiClient>,
+    last_id: usize,
+}
+
+/// Holds the WMI connections opened by a script.
+#[derive(Default)]
+pub struct Wmi {
+    handles: Mutex<Handles>,
+}
+
+fn timeout(timeout: Option<i64>) -> Duration {
+    timeout
+        .filter(|x| *x > 0)
+        .map_or(DEFAULT_TIMEOUT, |x| Duration::from_secs(x as u64))
+}
+
+/// Returns the authentication level of the options, e.g. `[sign]`, the PDUs are signed unless
+/// sealing is requested.
+fn auth_level(options: Option<&str>) -> AuthLevel {
+    let sealed = options.is_some_and(|x| {
+        x.trim_matches(['[', ']'])
+            .split(',')
+            .any(|x| x.trim() == "seal")
+    });
+    match sealed {
+        true => AuthLevel::Privacy,
+        false => AuthLevel::Integrity,
+    }
+}
+
+fn unknown_handle(wmi_handle: usize) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(format!("Unknown WMI handle {wmi_handle}"), None)
+}
+
+/// Returns the number of a value given as number or as text, e.g. `"4096"`.
+fn number(value: &NaslValue) -> Result<u64, FunctionErrorKind> {
+    let number = match value {
+        NaslValue::Number(x) => u64::try_from(*x).ok(),
+        x => x.to_string().trim().parse().ok(),
+    };
+    number.ok_or_else(|| {
+        FunctionErrorKind::WrongArgument(format!("Expected a positive number, found '{value}'."))
+    })
+}
+
+/// Returns the elements of an array value, NULL is an empty array.
+fn array(value: Option<&Value>) -> Option<Vec<String>> {
+    match value? {
+        Value::Array(x) => Some(x.clone()),
+        Value::Null(_) => Some(vec![]),
+        Value::Scalar(_) => None,
+    }
+}
+
+fn scalar(value: Option<&Value>) -> Option<&str> {
+    match value? {
+        Value::Scalar(x) => Some(x),
+        _ => None,
+    }
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+    scalar(value).map(|x| x.to_string())
+}
+
+fn integer(value: Option<&Value>) -> Option<u64> {
+    scalar(value)?.parse().ok()
+}
+
+/// Returns the names returned by EnumKey or EnumValues.
+fn names(result: Option<Instance>) -> Option<Vec<String>> {
+    array(result?.get("sNames"))
+}
+
+impl Wmi {
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        password: Option<&str>,
+        namespace: &str,
+        options: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (Some(username), Some(password)) = (username, password) else {
+            return Ok(NaslValue::Null);
+        };
+        if username.is_empty() || password.is_empty() {
+            return Ok(NaslValue::Null);
+        }
+        let (domain, username) = username.split_once(['\\', '/']).unwrap_or(("", username));
+        let credentials = Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+            domain: domain.to_string(),
+        };
+        let port = port.map_or(Ok(ENDPOINT_MAPPER_PORT), verify_port)?;
+        let timeout = self::timeout(timeout);
+        let addrs = resolve_host(context, context.target())?;
+        let connect = |port| {
+            NaslSockets::connect_tcp(context, &addrs, port, None, timeout, None)
+                .map_err(SmbError::Io)
+        };
+        let client = match WmiClient::connect(
+            connect,
+            port,
+            &credentials,
+            auth_level(options),
+            namespace,
+            timeout,
+        ) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(%e, namespace, "unable to connect to the WMI service");
+                return Ok(NaslValue::Null);
+            }
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.last_id += 1;
+        let id = handles.last_id;
+        handles.clients.insert(id, client);
+        Ok(NaslValue::Number(id as i64))
+    }
+
+    /// Runs the method of the registry provider on the key of the hive and returns the output
+    /// parameters. Returns None when the call fails or the method returns an error, e.g. when
+    /// the key does not exist.
+    fn registry(
+        &self,
+        wmi_handle: usize,
+        method: &str,
+        hive: Option<u32>,
+        key: &str,
+        parameters: &[(&str, Parameter)],
+    ) -> Result<Option<Instance>, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let client = handles
+            .clients
+            .get_mut(&wmi_handle)
+            .ok_or_else(|| unknown_handle(wmi_handle))?;
+        let mut all = vec![
+            (
+                "hDefKey",
+                Parameter::Uint32(hive.unwrap_or(HKEY_LOCAL_MACHINE)),
+            ),
+            ("sSubKeyName", Parameter::String(key)),
+        ];
+        all.extend_from_slice(parameters);
+        match client.exec_method(REGISTRY_PROVIDER, method, &all) {
+            Ok(x) if scalar(x.get("ReturnValue")) == Some("0") => Ok(Some(x)),
+            Ok(x) => {
+                let result = x.get("ReturnValue").map(|x| x.to_string());
+                tracing::debug!(method, key, result, "the registry method failed");
+                Ok(None)
+            }
+            Err(e) => {
+                tracing::debug!(%e, method, key, "unable to run the registry method");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads the named value of the key with the method and returns the output parameter
+    /// holding it, `sValue` or `uValue` depending on the type.
+    fn get_value(
+        &self,
+        wmi_handle: usize,
+        method: &str,
+        output: &str,
+        hive: Option<u32>,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<Value>, FunctionErrorKind> {
+        let parameters = [("sValueName", Parameter::String(name))];
+        let result = self.registry(wmi_handle, method, hive, key, &parameters)?;
+        Ok(result.and_then(|x| x.get(output).cloned()))
+    }
+
+    /// Writes the named value of the key with the method, returns TRUE on success.
+    fn set_value(
+        &self,
+        wmi_handle: usize,
+        method: &str,
+        hive: Option<u32>,
+        key: &str,
+        name: &str,
+        value: (&str, Parameter),
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        let parameters = [("sValueName", Parameter::String(name)), value];
+        let result = self.registry(wmi_handle, method, hive, key, &parameters)?;
+        Ok(result.map(|_| true))
+    }
+
+    /// Returns the version of the WMI implementation.
+    #[nasl_function]
+    fn wmi_versioninfo(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    /// Connects to the WMI service of the target and logs into the namespace, returns the handle
+    /// of the connection.
+    ///
+    /// The domain may be given as part of the username, e.g. `DOMAIN\user`.
+    ///
+    /// - ns: Namespace, `root\cimv2` by default
+    /// - options: Options of the connection, e.g. `[sign]`. With `seal` the calls are encrypted,
+    ///   otherwise they are signed.
+    /// - port: Port of the endpoint mapper, 135 by default
+    /// - timeout: Time in seconds to wait for each response, 30 by default
+    ///
+    /// Returns NULL when the username or the password is missing or when the connection, the
+    /// authentication or the login fails.
+    #[nasl_function(named(username, password, ns, options, port, timeout))]
+    #[allow(clippy::too_many_arguments)]
+    fn wmi_connect(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        password: Option<&str>,
+        ns: Option<&str>,
+        options: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let namespace = ns.unwrap_or(DEFAULT_NAMESPACE);
+        self.connect(
+            context, username, password, namespace, options, port, timeout,
+        )
+    }
+
+    /// Connects to the WMI service of the target like wmi_connect, logging into the namespace
+    /// `root\rsop\computer` of the Resultant Set of Policy.
+    #[nasl_function(named(username, password, options, port, timeout))]
+    fn wmi_connect_rsop(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        password: Option<&str>,
+        options: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.connect(
+            context,
+            username,
+            password,
+            RSOP_NAMESPACE,
+            options,
+            port,
+            timeout,
+        )
+    }
+
+    /// Runs a WQL query, e.g. `SELECT Caption FROM Win32_OperatingSystem`.
+    ///
+    /// Returns the instances as text like the wmic client: a line with the names of the
+    /// properties separated by `|`, followed by a line with the values of each instance.
+    /// Returns NULL when the query fails or has no results.
+    #[nasl_function(named(wmi_handle, query))]
+    fn wmi_query(&self, wmi_handle: usize, query: &str) -> Result<NaslValue, FunctionErrorKind> {
+        let mut handles = self.handles.lock().unwrap();
+        let client = handles
+            .clients
+            .get_mut(&wmi_handle)
+            .ok_or_else(|| unknown_handle(wmi_handle))?;
+        match client.query(query) {
+            Ok(x) if x.is_empty() => Ok(NaslValue::Null),
+            Ok(x) => Ok(NaslValue::String(object::format(&x))),
+            Err(e) => {
+                tracing::debug!(%e, query, "unable to run the WQL query");
+                Ok(NaslValue::Null)
+            }
+        }
+    }
+
+    /// Releases the namespace and closes the connection.
+    #[nasl_function(named(wmi_handle))]
+    fn wmi_close(&self, wmi_handle: usize) -> Result<NaslValue, FunctionErrorKind> {
+        let client = self
+            .handles
+            .lock()
+            .unwrap()
+            .clients
+            .remove(&wmi_handle)
+            .ok_or_else(|| unknown_handle(wmi_handle))?;
+        client.close();
+        Ok(NaslValue::Boolean(true))
+    }
+
+    /// Connects to the WMI service of the target like wmi_connect, logging into the namespace
+    /// `root\default` of the registry provider StdRegProv used by the wmi_reg functions.
+    #[nasl_function(named(username, password, options, port, timeout))]
+    fn wmi_connect_reg(
+        &self,
+        context: &Context,
+        username: Option<&str>,
+        password: Option<&str>,
+        options: Option<&str>,
+        port: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        self.connect(
+            context,
+            username,
+            password,
+            REGISTRY_NAMESPACE,
+            options,
+            port,
+            timeout,
+        )
+    }
+
+    /// Returns the names of the subkeys of the key as array.
+    ///
+    /// The registry functions take the handle of wmi_connect_reg, the key without the hive,
+    /// e.g. `SOFTWARE\Microsoft\Windows NT\CurrentVersion`, and optionally the hive, e.g.
+    /// 0x80000001 for HKEY_CURRENT_USER. The hive is HKEY_LOCAL_MACHINE by default. They
+    /// return NULL when the key or the value does not exist or the call fails.
+    #[nasl_function(named(wmi_handle, hive, key))]
+    fn wmi_reg_enum_key(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+    ) -> Result<Option<Vec<String>>, FunctionErrorKind> {
+        let result = self.registry(wmi_handle, "EnumKey", hive, key, &[])?;
+        Ok(names(result))
+    }
+
+    /// Returns the names of the values of the key as array.
+    #[nasl_function(named(wmi_handle, hive, key))]
+    fn wmi_reg_enum_value(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+    ) -> Result<Option<Vec<String>>, FunctionErrorKind> {
+        let result = self.registry(wmi_handle, "EnumValues", hive, key, &[])?;
+        Ok(names(result))
+    }
+
+    /// Returns the string value REG_SZ of the key named by key_name.
+    #[nasl_function(named(wmi_handle, hive, key, key_name))]
+    fn wmi_reg_get_sz(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        key_name: &str,
+    ) -> Result<Option<String>, FunctionErrorKind> {
+        let value = self.get_value(wmi_handle, "GetStringValue", "sValue", hive, key, key_name)?;
+        Ok(string(value.as_ref()))
+    }
+
+    /// Returns the expandable string value REG_EXPAND_SZ without expanding the environment
+    /// variables, e.g. `%SystemRoot%\system32`.
+    #[nasl_function(named(wmi_handle, hive, key, val_name))]
+    fn wmi_reg_get_ex_string_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+    ) -> Result<Option<String>, FunctionErrorKind> {
+        let value = self.get_value(
+            wmi_handle,
+            "GetExpandedStringValue",
+            "sValue",
+            hive,
+            key,
+            val_name,
+        )?;
+        Ok(string(value.as_ref()))
+    }
+
+    /// Returns the strings of the value REG_MULTI_SZ as array.
+    #[nasl_function(named(wmi_handle, hive, key, val_name))]
+    fn wmi_reg_get_mul_string_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+    ) -> Result<Option<Vec<String>>, FunctionErrorKind> {
+        let value = self.get_value(
+            wmi_handle,
+            "GetMultiStringValue",
+            "sValue",
+            hive,
+            key,
+            val_name,
+        )?;
+        Ok(array(value.as_ref()))
+    }
+
+    /// Returns the number of the value REG_DWORD.
+    #[nasl_function(named(wmi_handle, hive, key, val_name))]
+    fn wmi_reg_get_dword_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+    ) -> Result<Option<u64>, FunctionErrorKind> {
+        let value = self.get_value(wmi_handle, "GetDWORDValue", "uValue", hive, key, val_name)?;
+        Ok(integer(value.as_ref()))
+    }
+
+    /// Returns the number of the value REG_QWORD.
+    #[nasl_function(named(wmi_handle, hive, key, val_name))]
+    fn wmi_reg_get_qword_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+    ) -> Result<Option<u64>, FunctionErrorKind> {
+        let value = self.get_value(wmi_handle, "GetQWORDValue", "uValue", hive, key, val_name)?;
+        Ok(integer(value.as_ref()))
+    }
+
+    /// Returns the data of the value REG_BINARY.
+    #[nasl_function(named(wmi_handle, hive, key, val_name))]
+    fn wmi_reg_get_bin_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+    ) -> Result<Option<Vec<u8>>, FunctionErrorKind> {
+        let value = self.get_value(wmi_handle, "GetBinaryValue", "uValue", hive, key, val_name)?;
+        Ok(array(value.as_ref()).and_then(|x| x.iter().map(|x| x.parse().ok()).collect()))
+    }
+
+    /// Sets the value REG_DWORD named by val_name to val, returns TRUE on success.
+    ///
+    /// The value is created if it does not exist. The number may be given as text.
+    #[nasl_function(named(wmi_handle, hive, key, val_name, val))]
+    fn wmi_reg_set_dword_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+        val: &NaslValue,
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        let val = u32::try_from(number(val)?).map_err(|_| {
+            FunctionErrorKind::WrongArgument(format!("The DWORD {val} is too large."))
+        })?;
+        self.set_value(
+            wmi_handle,
+            "SetDWORDValue",
+            hive,
+            key,
+            val_name,
+            ("uValue", Parameter::Uint32(val)),
+        )
+    }
+
+    /// Sets the value REG_QWORD named by val_name to val, returns TRUE on success.
+    #[nasl_function(named(wmi_handle, hive, key, val_name, val))]
+    fn wmi_reg_set_qword_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+        val: &NaslValue,
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        self.set_value(
+            wmi_handle,
+            "SetQWORDValue",
+            hive,
+            key,
+            val_name,
+            ("uValue", Parameter::Uint64(number(val)?)),
+        )
+    }
+
+    /// Sets the value REG_SZ named by val_name to val, returns TRUE on success.
+    #[nasl_function(named(wmi_handle, hive, key, val_name, val))]
+    fn wmi_reg_set_string_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+        val: &str,
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        self.set_value(
+            wmi_handle,
+            "SetStringValue",
+            hive,
+            key,
+            val_name,
+            ("sValue", Parameter::String(val)),
+        )
+    }
+
+    /// Sets the value REG_EXPAND_SZ named by val_name to val, returns TRUE on success.
+    #[nasl_function(named(wmi_handle, hive, key, val_name, val))]
+    fn wmi_reg_set_ex_string_val(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+        val_name: &str,
+        val: &str,
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        self.set_value(
+            wmi_handle,
+            "SetExpandedStringValue",
+            hive,
+            key,
+            val_name,
+            ("sValue", Parameter::String(val)),
+        )
+    }
+
+    /// Creates the key including missing parent keys, returns TRUE on success.
+    #[nasl_function(named(wmi_handle, hive, key))]
+    fn wmi_reg_create_key(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        let result = self.registry(wmi_handle, "CreateKey", hive, key, &[])?;
+        Ok(result.map(|_| true))
+    }
+
+    /// Deletes the key, which must not have subkeys, returns TRUE on success.
+    #[nasl_function(named(wmi_handle, hive, key))]
+    fn wmi_reg_delete_key(
+        &self,
+        wmi_handle: usize,
+        hive: Option<u32>,
+        key: &str,
+    ) -> Result<Option<bool>, FunctionErrorKind> {
+        let result = self.registry(wmi_handle, "DeleteKey", hive, key, &[])?;
+        Ok(result.map(|_| true))
+    }
+}
+
+function_set! {
+    Wmi,
+    sync_stateful,
+    (
+        (Wmi::wmi_versioninfo, "wmi_versioninfo"),
+        (Wmi::wmi_connect, "wmi_connect"),
+        (Wmi::wmi_connect_rsop, "wmi_connect_rsop"),
+        (Wmi::wmi_query, "wmi_query"),
+        (Wmi::wmi_query, "wmi_query_rsop"),
+        (Wmi::wmi_close, "wmi_close"),
+        (Wmi::wmi_connect_reg, "wmi_connect_reg"),
+        (Wmi::wmi_reg_enum_key, "wmi_reg_enum_key"),
+        (Wmi::wmi_reg_enum_value, "wmi_reg_enum_value"),
+        (Wmi::wmi_reg_get_sz, "wmi_reg_get_sz"),
+        (Wmi::wmi_reg_get_ex_string_val, "wmi_reg_get_ex_string_val"),
+        (Wmi::wmi_reg_get_mul_string_val, "wmi_reg_get_mul_string_val"),
+        (Wmi::wmi_reg_get_dword_val, "wmi_reg_get_dword_val"),
+        (Wmi::wmi_reg_get_qword_val, "wmi_reg_get_qword_val"),
+        (Wmi::wmi_reg_get_bin_val, "wmi_reg_get_bin_val"),
+        (Wmi::wmi_reg_set_dword_val, "wmi_reg_set_dword_val"),
+        (Wmi::wmi_reg_set_qword_val, "wmi_reg_set_qword_val"),
+        (Wmi::wmi_reg_set_string_val, "wmi_reg_set_string_val"),
+        (Wmi::wmi_reg_set_ex_string_val, "wmi_reg_set_ex_string_val"),
+        (Wmi::wmi_reg_create_key, "wmi_reg_create_key"),
+        (Wmi::wmi_reg_delete_key, "wmi_reg_delete_key"),
+    )
+}
@@ -0,0 +1,494 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Decoding of the CIM objects returned by queries and methods (MS-WMIO) into the text of the
+//! values of their properties, and encoding of the input parameters of methods.
+//!
+//! The objects are encoded as an instance with its complete class, without alignment. Strings
+//! and arrays are stored in the heap of the class or of the instance.
+
+use std::fmt::Display;
+
+use crate::nasl::builtin::smb::message::{bytes_at, u16_at, u32_at, u64_at, utf16le, SmbError};
+
+const ENCODING_UNIT_SIGNATURE: u32 = 0x1234_5678;
+const OBJECT_INSTANCE: u8 = 0x02;
+/// The object starts with the names of the server and of the namespace
+const OBJECT_DECORATED: u8 = 0x04;
+
+/// Flags of a property in the NdTable
+const VALUE_NULL: u8 = 0x01;
+const VALUE_DEFAULT: u8 = 0x02;
+
+/// Set in the type of properties inherited from a parent class
+const INHERITED: u32 = 0x4000;
+
+/// Set in the type of properties being arrays of the type
+pub const CIM_ARRAY: u32 = 0x2000;
+pub const CIM_SINT16: u32 = 2;
+pub const CIM_SINT32: u32 = 3;
+pub const CIM_REAL32: u32 = 4;
+pub const CIM_REAL64: u32 = 5;
+pub const CIM_STRING: u32 = 8;
+pub const CIM_BOOLEAN: u32 = 11;
+pub const CIM_OBJECT: u32 = 13;
+pub const CIM_SINT8: u32 = 16;
+pub const CIM_UINT8: u32 = 17;
+pub const CIM_UINT16: u32 = 18;
+pub const CIM_UINT32: u32 = 19;
+pub const CIM_SINT64: u32 = 20;
+pub const CIM_UINT64: u32 = 21;
+pub const CIM_DATETIME: u32 = 101;
+pub const CIM_REFERENCE: u32 = 102;
+pub const CIM_CHAR16: u32 = 103;
+
+/// Strings referenced by heap references with the most significant bit set
+const DICTIONARY: [&str; 11] = [
+    "\"", "key", "NADA", "read", "write", "volatile", "provider", "dynamic", "cimwin32", "DWORD",
+    "CIMTYPE",
+];
+
+/// Value of a property as text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// NULL, with its text depending on the type, e.g. `(null)` for strings
+    Null(&'static str),
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+impl Display for Value {
+    /// Formats the value like the wmic client, arrays are enclosed in parentheses with their
+    /// elements separated by commas.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null(x) => write!(f, "{x}"),
+            Value::Scalar(x) => write!(f, "{x}"),
+            Value::Array(x) => write!(f, "({})", x.join(",")),
+        }
+    }
+}
+
+/// An instance with the values of its properties
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    pub class: String,
+    /// Names and values of the properties, ordered by name
+    pub properties: Vec<(String, Value)>,
+}
+
+impl Instance {
+    /// Returns the value of the property with the name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.properties
+            .iter()
+            .find(|(x, _)| x == name)
+            .map(|(_, x)| x)
+    }
+}
+
+/// Value of an input parameter of a method
+#[derive(Debug, Clone, Copy)]
+pub enum Parameter<'a> {
+    Uint32(u32),
+    Uint64(u64),
+    String(&'a str),
+}
+
+impl Parameter<'_> {
+    fn cim_type(&self) -> u32 {
+        match self {
+            Parameter::Uint32(_) => CIM_UINT32,
+            Parameter::Uint64(_) => CIM_UINT64,
+            Parameter::String(_) => CIM_STRING,
+        }
+    }
+}
+
+fn truncated() -> SmbError {
+    SmbError::Protocol("truncated CIM object")
+}
+
+/// Returns the size of a value of the type in the value table or in an array.
+pub fn size(cim_type: u32) -> usize {
+    if cim_type & CIM_ARRAY != 0 {
+        return 4;
+    }
+    match cim_type {
+        CIM_SINT8 | CIM_UINT8 => 1,
+        CIM_SINT16 | CIM_UINT16 | CIM_BOOLEAN | CIM_CHAR16 => 2,
+        CIM_SINT64 | CIM_UINT64 | CIM_REAL64 => 8,
+        _ => 4,
+    }
+}
+
+/// Returns the text of a NULL value of the type, as printed by the wmic client.
+fn null(cim_type: u32) -> &'static str {
+    if cim_type & CIM_ARRAY != 0 {
+        return "NULL";
+    }
+    match cim_type {
+        CIM_STRING | CIM_DATETIME | CIM_REFERENCE | CIM_OBJECT => "(null)",
+        CIM_BOOLEAN => "False",
+        _ => "0",
+    }
+}
+
+/// Returns the EncodedString at the offset and its length.
+fn encoded_string(data: &[u8], offset: usize) -> Result<(String, usize), SmbError> {
+    let characters = data.get(offset + 1..).ok_or_else(truncated)?;
+    match data[offset] {
+        // compressed, each character is a Latin-1 octet
+        0 => {
+            let end = characters
+                .iter()
+                .position(|x| *x == 0)
+                .ok_or_else(truncated)?;
+            Ok((
+                characters[..end].iter().map(|x| *x as char).collect(),
+                end + 2,
+            ))
+        }
+        1 => {
+            let units: Vec<u16> = characters
+                .chunks_exact(2)
+                .map(|x| u16::from_le_bytes([x[0], x[1]]))
+                .take_while(|x| *x != 0)
+                .collect();
+            if characters.len() < units.len() * 2 + 2 {
+                return Err(truncated());
+            }
+            Ok((String::from_utf16_lossy(&units), units.len() * 2 + 3))
+        }
+        _ => Err(SmbError::Protocol("invalid encoding of a CIM string")),
+    }
+}
+
+/// Heap of a class or of an instance, referenced by offsets
+struct Heap<'a>(&'a [u8]);
+
+impl<'a> Heap<'a> {
+    /// Reads the heap starting with its length at the offset.
+    fn at(data: &'a [u8], offset: usize) -> Result<Self, SmbError> {
+        // the most significant bit of the length is always set
+        let length = u32_at(data, offset)? & 0x7fff_ffff;
+        Ok(Self(bytes_at(data, offset + 4, length as usize)?))
+    }
+
+    fn string(&self, reference: u32) -> Result<String, SmbError> {
+        if reference & 0x8000_0000 != 0 {
+            return DICTIONARY
+                .get((reference & 0x7fff_ffff) as usize)
+                .map(|x| x.to_string())
+                .ok_or(SmbError::Protocol("unknown CIM dictionary reference"));
+        }
+        encoded_string(self.0, reference as usize).map(|(x, _)| x)
+    }
+}
+
+struct Property {
+    name: String,
+    cim_type: u32,
+    /// Position of the property in the NdTable
+    order: u16,
+    /// Position of the value in the value table
+    offset: usize,
+}
+
+struct Class<'a> {
+    name: String,
+    /// The properties, including the inherited ones, ordered by name
+    properties: Vec<Property>,
+    /// NdTable followed by the value table with the default values
+    defaults: &'a [u8],
+    heap: Heap<'a>,
+}
+
+/// Returns the NdTable flags of the property with the declaration order.
+fn value_flags(nd_table: &[u8], order: u16) -> u8 {
+    let order = order as usize;
+    nd_table
+        .get(order / 4)
+        .map_or(0, |x| (x >> (order % 4 * 2)) & 0x03)
+}
+
+/// Decodes a ClassPart and returns it with its length.
+fn class_part(data: &[u8]) -> Result<(Class<'_>, usize), SmbError> {
+    let length = u32_at(data, 0)? as usize;
+    let data = bytes_at(data, 0, length)?;
+    let name = u32_at(data, 5)?;
+    let defaults_len = u32_at(data, 9)? as usize;
+    // the derivation list and the qualifier set start with their length
+    let mut position = 13;
+    position += u32_at(data, position)? as usize;
+    position += u32_at(data, position)? as usize;
+    let count = u32_at(data, position)? as usize;
+    let lookup = position + 4;
+    position = lookup + count * 8;
+    let defaults = bytes_at(data, position, defaults_len)?;
+    let heap = Heap::at(data, position + defaults_len)?;
+    let properties = (0..count)
+        .map(|i| {
+            let name = heap.string(u32_at(data, lookup + i * 8)?)?;
+            let info = u32_at(data, lookup + i * 8 + 4)? as usize;
+            Ok(Property {
+                name,
+                cim_type: u32_at(heap.0, info)? & !INHERITED,
+                order: u16_at(heap.0, info + 4)?,
+                offset: u32_at(heap.0, info + 6)? as usize,
+            })
+        })
+        .collect::<Result<_, SmbError>>()?;
+    let class = Class {
+        name: heap.string(name)?,
+        properties,
+        defaults,
+        heap,
+    };
+    Ok((class, length))
+}
+
+fn scalar(cim_type: u32, data: &[u8], offset: usize, heap: &Heap) -> Result<String, SmbError> {
+    Ok(match cim_type {
+        CIM_SINT8 => (bytes_at(data, offset, 1)?[0] as i8).to_string(),
+        CIM_UINT8 => bytes_at(data, offset, 1)?[0].to_string(),
+        CIM_SINT16 => (u16_at(data, offset)? as i16).to_string(),
+        CIM_UINT16 => u16_at(data, offset)?.to_string(),
+        CIM_SINT32 => (u32_at(data, offset)? as i32).to_string(),
+        CIM_UINT32 => u32_at(data, offset)?.to_string(),
+        CIM_SINT64 => (u64_at(data, offset)? as i64).to_string(),
+        CIM_UINT64 => u64_at(data, offset)?.to_string(),
+        CIM_REAL32 => format!("{:.6}", f32::from_bits(u32_at(data, offset)?)),
+        CIM_REAL64 => format!("{:.6}", f64::from_bits(u64_at(data, offset)?)),
+        CIM_BOOLEAN => match u16_at(data, offset)? {
+            0 => "False".to_string(),
+            _ => "True".to_string(),
+        },
+        CIM_CHAR16 => char::from_u32(u16_at(data, offset)? as u32)
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+            .to_string(),
+        CIM_STRING | CIM_DATETIME | CIM_REFERENCE => heap.string(u32_at(data, offset)?)?,
+        _ => "Unsupported".to_string(),
+    })
+}
+
+/// Returns the value at the offset of the value table.
+fn value(cim_type: u32, values: &[u8], offset: usize, heap: &Heap) -> Result<Value, SmbError> {
+    if cim_type & CIM_ARRAY == 0 {
+        return scalar(cim_type, values, offset, heap).map(Value::Scalar);
+    }
+    let element = cim_type & !CIM_ARRAY;
+    let array = u32_at(values, offset)? as usize;
+    let count = u32_at(heap.0, array)? as usize;
+    let elements = (0..count)
+        .map(|i| scalar(element, heap.0, array + 4 + i * size(element), heap))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::Array(elements))
+}
+
+/// Decodes the ENCODING_UNIT of an instance.
+pub fn decode(data: &[u8]) -> Result<Instance, SmbError> {
+    if u32_at(data, 0)? != ENCODING_UNIT_SIGNATURE {
+        return Err(SmbError::Protocol("invalid signature of a CIM object"));
+    }
+    let flags = bytes_at(data, 8, 1)?[0];
+    let mut position = 9;
+    if flags & OBJECT_DECORATED != 0 {
+        // server and namespace
+        position += encoded_string(data, position)?.1;
+        position += encoded_string(data, position)?.1;
+    }
+    if flags & OBJECT_INSTANCE == 0 {
+        return Err(SmbError::Protocol("the CIM object is not an instance"));
+    }
+    let (class, length) = class_part(data.get(position..).ok_or_else(truncated)?)?;
+    // the methods part starts with its length
+    position += length;
+    position += u32_at(data, position)? as usize;
+    let instance = data.get(position..).ok_or_else(truncated)?;
+
+    // length, flags and class name
+    let count = class.properties.len();
+    let nd_len = (count * 2).div_ceil(8);
+    let nd_table = bytes_at(instance, 9, nd_len)?;
+    let values_len = class
+        .defaults
+        .len()
+        .checked_sub(nd_len)
+        .ok_or_else(truncated)?;
+    let values = bytes_at(instance, 9 + nd_len, values_len)?;
+    // the qualifier set starts with its length and is followed by the qualifiers of the
+    // properties when the flag is 2
+    let mut position = 9 + nd_len + values_len;
+    position += u32_at(instance, position)? as usize;
+    match bytes_at(instance, position, 1)?[0] {
+        1 => position += 1,
+        2 => {
+            position += 1;
+            for _ in 0..count {
+                position += u32_at(instance, position)? as usize;
+            }
+        }
+        _ => return Err(SmbError::Protocol("invalid CIM property qualifiers")),
+    }
+    let heap = Heap::at(instance, position)?;
+
+    let (defaults_nd_table, defaults) = class.defaults.split_at(nd_len);
+    let properties = class
+        .properties
+        .iter()
+        .map(|property| {
+            let flags = value_flags(nd_table, property.order);
+            let (flags, values, heap) = if flags & VALUE_DEFAULT != 0 {
+                let flags = value_flags(defaults_nd_table, property.order);
+                (flags, defaults, &class.heap)
+            } else {
+                (flags, values, &heap)
+            };
+            let value = if flags & VALUE_NULL != 0 {
+                Value::Null(null(property.cim_type))
+            } else {
+                value(property.cim_type, values, property.offset, heap)?
+            };
+            Ok((property.name.clone(), value))
+        })
+        .collect::<Result<_, SmbError>>()?;
+    Ok(Instance {
+        class: class.name,
+        properties,
+    })
+}
+
+/// Formats the instances like the wmic client: a line with the names of the properties
+/// separated by `|`, followed by a line with the values of each instance.
+pub fn format(instances: &[Instance]) -> String {
+    let mut text = String::new();
+    let Some(first) = instances.first() else {
+        return text;
+    };
+    let names: Vec<&str> = first.properties.iter().map(|(x, _)| x.as_str()).collect();
+    text.push_str(&names.join("|"));
+    text.push('\n');
+    for instance in instances {
+        let values: Vec<String> = instance
+            .properties
+            .iter()
+            .map(|(_, x)| x.to_string())
+            .collect();
+        text.push_str(&values.join("|"));
+        text.push('\n');
+    }
+    text
+}
+
+/// Returns an EncodedString, compressed if all characters are Latin-1 characters.
+fn encode_string(value: &str) -> Vec<u8> {
+    let mut data = vec![];
+    if value.chars().all(|x| (x as u32) < 0x100) {
+        data.push(0);
+        data.extend(value.chars().map(|x| x as u8));
+        data.push(0);
+    } else {
+        data.push(1);
+        data.extend(utf16le(value));
+        data.extend_from_slice(&[0, 0]);
+    }
+    data
+}
+
+/// Heap of a class or of an instance being encoded
+#[derive(Default)]
+struct HeapWriter(Vec<u8>);
+
+impl HeapWriter {
+    /// Appends the data and returns its reference.
+    fn push(&mut self, data: &[u8]) -> u32 {
+        let reference = self.0.len() as u32;
+        self.0.extend_from_slice(data);
+        reference
+    }
+
+    fn string(&mut self, value: &str) -> u32 {
+        self.push(&encode_string(value))
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut data = (self.0.len() as u32 | 0x8000_0000).to_le_bytes().to_vec();
+        data.extend(self.0);
+        data
+    }
+}
+
+/// Stores the length of the part in its first four octets.
+fn with_length(mut part: Vec<u8>) -> Vec<u8> {
+    let length = part.len() as u32;
+    part[..4].copy_from_slice(&length.to_le_bytes());
+    part
+}
+
+/// Encodes the input parameters of a method as the ENCODING_UNIT of an instance of the class,
+/// e.g. `__PARAMETERS`. The class is encoded as well, its properties are the parameters in the
+/// given order without default values.
+pub fn encode(class: &str, parameters: &[(&str, Parameter)]) -> Vec<u8> {
+    let nd_len = (parameters.len() * 2).div_ceil(8);
+    let mut class_heap = HeapWriter::default();
+    let class_name = class_heap.string(class);
+    let mut instance_heap = HeapWriter::default();
+    let instance_name = instance_heap.string(class);
+    let mut defaults = vec![0; nd_len];
+    let mut values = vec![0; nd_len];
+    let mut lookup = vec![];
+    for (order, (name, parameter)) in parameters.iter().enumerate() {
+        let cim_type = parameter.cim_type();
+        let mut info = cim_type.to_le_bytes().to_vec();
+        info.extend((order as u16).to_le_bytes());
+        info.extend(((values.len() - nd_len) as u32).to_le_bytes());
+        // class of origin and an empty qualifier set
+        info.extend(class_name.to_le_bytes());
+        info.extend(4u32.to_le_bytes());
+        lookup.push((*name, class_heap.string(name), class_heap.push(&info)));
+        defaults[order / 4] |= VALUE_NULL << (order % 4 * 2);
+        defaults.resize(defaults.len() + size(cim_type), 0);
+        match parameter {
+            Parameter::Uint32(x) => values.extend(x.to_le_bytes()),
+            Parameter::Uint64(x) => values.extend(x.to_le_bytes()),
+            Parameter::String(x) => values.extend(instance_heap.string(x).to_le_bytes()),
+        }
+    }
+    lookup.sort();
+
+    let mut class_part = vec![0; 5];
+    class_part.extend(class_name.to_le_bytes());
+    class_part.extend((defaults.len() as u32).to_le_bytes());
+    // empty derivation list and qualifier set
+    class_part.extend(4u32.to_le_bytes());
+    class_part.extend(4u32.to_le_bytes());
+    class_part.extend((lookup.len() as u32).to_le_bytes());
+    for (_, name, info) in lookup {
+        class_part.extend(name.to_le_bytes());
+        class_part.extend(info.to_le_bytes());
+    }
+    class_part.extend(defaults);
+    class_part.extend(class_heap.finish());
+
+    let mut instance_part = vec![0; 5];
+    instance_part.extend(instance_name.to_le_bytes());
+    instance_part.extend(values);
+    // empty qualifier set and no qualifiers of the properties
+    instance_part.extend(4u32.to_le_bytes());
+    instance_part.push(1);
+    instance_part.extend(instance_heap.finish());
+
+    let mut block = vec![OBJECT_INSTANCE];
+    block.extend(with_length(class_part));
+    // methods part without methods
+    block.extend(12u32.to_le_bytes());
+    block.extend([0; 4]);
+    block.extend(0x8000_0000u32.to_le_bytes());
+    block.extend(with_length(instance_part));
+    let mut unit = ENCODING_UNIT_SIGNATURE.to_le_bytes().to_vec();
+    unit.extend((block.len() as u32).to_le_bytes());
+    unit.extend(block);
+    unit
+}
@@ -0,0 +1,773 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+use super::client::{
+    CLSID_WBEM_CLASS_OBJECT, CLSID_WBEM_LEVEL1_LOGIN, EXEC_METHOD, EXEC_QUERY, IWBEM_LEVEL1_LOGIN,
+    NEXT, NTLM_LOGIN,
+};
+use super::dcom::{self, REMOTE_ACTIVATION, REM_RELEASE};
+use super::object::{self, *};
+use crate::nasl::builtin::smb::message::utf16le;
+use crate::nasl::builtin::smb::rpc::ndr::{Reader, Uuid, Writer};
+use crate::nasl::builtin::smb::rpc::tests::AuthenticatedServer;
+use crate::nasl::test_prelude::*;
+use crate::storage::ContextKey;
+
+const OXID: u64 = 0x1122_3344_5566_7788;
+const REM_UNKNOWN: Uuid = [0xa1; 16];
+const LOGIN: Uuid = [0xa2; 16];
+const SERVICES: Uuid = [0xa3; 16];
+const ENUMERATOR: Uuid = [0xa4; 16];
+const EMPTY_ENUMERATOR: Uuid = [0xa5; 16];
+
+const NCA_S_OP_RNG_ERROR: u32 = 0x1c01_0002;
+const WBEM_S_FALSE: u32 = 0x0000_0001;
+const WBEM_E_INVALID_NAMESPACE: u32 = 0x8004_100e;
+const WBEM_E_INVALID_CLASS: u32 = 0x8004_1010;
+/// ERROR_FILE_NOT_FOUND returned by the registry provider
+const NOT_FOUND: u64 = 2;
+
+const OPERATING_SYSTEM: &str = "Win32_OperatingSystem";
+const CURRENT_VERSION: &str = "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion";
+
+/// Set in the type of properties inherited from a parent class
+const INHERITED: u32 = 0x4000;
+const NULL: u8 = 0x01;
+const DEFAULT: u8 = 0x02;
+
+/// Value of a property
+enum Value {
+    Number(u64),
+    Numbers(&'static [u64]),
+    Text(&'static str),
+    Texts(&'static [&'static str]),
+}
+
+/// Name, type, default value of the class and value of the instance of a property
+type Property = (&'static str, u32, Option<Value>, Option<Value>);
+
+/// Returns the properties of an instance of Win32_OperatingSystem in the order of their
+/// declaration.
+fn properties(caption: &'static str) -> Vec<Property> {
+    vec![
+        (
+            "Caption",
+            CIM_STRING | INHERITED,
+            None,
+            Some(Value::Text(caption)),
+        ),
+        ("BuildNumber", CIM_STRING, None, Some(Value::Text("17763"))),
+        ("OSType", CIM_UINT16, None, Some(Value::Number(18))),
+        (
+            "FreePhysicalMemory",
+            CIM_UINT64,
+            None,
+            Some(Value::Number(6_000_000_000)),
+        ),
+        ("Primary", CIM_BOOLEAN, None, Some(Value::Number(1))),
+        (
+            "InstallDate",
+            CIM_DATETIME,
+            None,
+            Some(Value::Text("20240115093000.000000+060")),
+        ),
+        (
+            "MUILanguages",
+            CIM_STRING | CIM_ARRAY,
+            None,
+            Some(Value::Texts(&["en-US", "de-DE"])),
+        ),
+        ("OSLanguage", CIM_UINT32, Some(Value::Number(1033)), None),
+        (
+            "CurrentTimeZone",
+            CIM_SINT16,
+            None,
+            Some(Value::Number(-60i16 as u16 as u64)),
+        ),
+        (
+            "Organization",
+            CIM_STRING,
+            None,
+            Some(Value::Text("Contoso – IT")),
+        ),
+        (
+            "RegisteredUser",
+            CIM_STRING,
+            None,
+            Some(Value::Text("Jürgen")),
+        ),
+        ("Description", CIM_STRING, None, None),
+    ]
+}
+
+/// Returns an EncodedString, compressed if all characters are Latin-1 characters.
+fn encoded_string(value: &str) -> Vec<u8> {
+    if value.chars().all(|x| (x as u32) < 0x100) {
+        let mut data = vec![0];
+        data.extend(value.chars().map(|x| x as u8));
+        data.push(0);
+        data
+    } else {
+        let mut data = vec![1];
+        data.extend(utf16le(value));
+        data.extend_from_slice(&[0, 0]);
+        data
+    }
+}
+
+#[derive(Default)]
+struct Heap(Vec<u8>);
+
+impl Heap {
+    fn push(&mut self, data: &[u8]) -> u32 {
+        let reference = self.0.len() as u32;
+        self.0.extend_from_slice(data);
+        reference
+    }
+
+    fn string(&mut self, value: &str) -> u32 {
+        self.push(&encoded_string(value))
+    }
+
+    fn encode(self) -> Vec<u8> {
+        let mut data = (self.0.len() as u32 | 0x8000_0000).to_le_bytes().to_vec();
+        data.extend(self.0);
+        data
+    }
+}
+
+/// Returns the NdTable followed by the value table of the default values of the class or of the
+/// values of the instance, strings and arrays are added to the heap.
+fn values(properties: &[Property], instance: bool, heap: &mut Heap) -> Vec<u8> {
+    let mut nd_table = vec![0; (properties.len() * 2).div_ceil(8)];
+    let mut table = vec![];
+    for (i, (_, cim_type, default, value)) in properties.iter().enumerate() {
+        let value = match (instance, default, value) {
+            (false, Some(x), _) | (true, _, Some(x)) => Ok(x),
+            (true, Some(_), None) => Err(DEFAULT),
+            _ => Err(NULL),
+        };
+        let encoded = match value {
+            Ok(Value::Number(x)) => x.to_le_bytes()[..size(*cim_type)].to_vec(),
+            Ok(Value::Text(x)) => heap.string(x).to_le_bytes().to_vec(),
+            Ok(Value::Numbers(x)) => {
+                let element = size(cim_type & !CIM_ARRAY);
+                let mut array = (x.len() as u32).to_le_bytes().to_vec();
+                for x in x.iter() {
+                    array.extend(&x.to_le_bytes()[..element]);
+                }
+                heap.push(&array).to_le_bytes().to_vec()
+            }
+            Ok(Value::Texts(x)) => {
+                let mut array = (x.len() as u32).to_le_bytes().to_vec();
+                for x in x.iter() {
+                    array.extend(heap.string(x).to_le_bytes());
+                }
+                heap.push(&array).to_le_bytes().to_vec()
+            }
+            Err(flags) => {
+                nd_table[i / 4] |= flags << (i % 4 * 2);
+                vec![0; size(*cim_type)]
+            }
+        };
+        table.extend(encoded);
+    }
+    [nd_table, table].concat()
+}
+
+fn class_part(class: &str, properties: &[Property]) -> Vec<u8> {
+    let mut heap = Heap::default();
+    let name = heap.string(class);
+    let defaults = values(properties, false, &mut heap);
+    let mut lookup = vec![];
+    let mut offset = 0u32;
+    for (i, (property, cim_type, ..)) in properties.iter().enumerate() {
+        let mut info = cim_type.to_le_bytes().to_vec();
+        info.extend((i as u16).to_le_bytes());
+        info.extend(offset.to_le_bytes());
+        // class of origin and an empty qualifier set
+        info.extend(name.to_le_bytes());
+        info.extend(4u32.to_le_bytes());
+        offset += size(*cim_type) as u32;
+        lookup.push((*property, heap.string(property), heap.push(&info)));
+    }
+    lookup.sort();
+    let mut part = vec![0; 5];
+    part.extend(name.to_le_bytes());
+    part.extend((defaults.len() as u32).to_le_bytes());
+    // empty derivation list and qualifier set
+    part.extend(4u32.to_le_bytes());
+    part.extend(4u32.to_le_bytes());
+    part.extend((lookup.len() as u32).to_le_bytes());
+    for (_, name, info) in lookup {
+        part.extend(name.to_le_bytes());
+        part.extend(info.to_le_bytes());
+    }
+    part.extend(defaults);
+    part.extend(heap.encode());
+    let length = part.len() as u32;
+    part[..4].copy_from_slice(&length.to_le_bytes());
+    part
+}
+
+fn instance_part(class: &str, properties: &[Property]) -> Vec<u8> {
+    let mut heap = Heap::default();
+    let name = heap.string(class);
+    let mut part = vec![0; 5];
+    part.extend(name.to_le_bytes());
+    part.extend(values(properties, true, &mut heap));
+    // empty qualifier set and no qualifiers of the properties
+    part.extend(4u32.to_le_bytes());
+    part.push(1);
+    part.extend(heap.encode());
+    let length = part.len() as u32;
+    part[..4].copy_from_slice(&length.to_le_bytes());
+    part
+}
+
+/// Returns the ENCODING_UNIT of a decorated instance of the class with its class.
+fn encode_instance(class: &str, properties: &[Property]) -> Vec<u8> {
+    let mut block = vec![0x02 | 0x04];
+    block.extend(encoded_string("WIN-SERVER"));
+    block.extend(encoded_string("ROOT\\cimv2"));
+    block.extend(class_part(class, properties));
+    // methods part without methods
+    block.extend(12u32.to_le_bytes());
+    block.extend([0; 4]);
+    block.extend(0x8000_0000u32.to_le_bytes());
+    block.extend(instance_part(class, properties));
+    let mut unit = 0x1234_5678u32.to_le_bytes().to_vec();
+    unit.extend((block.len() as u32).to_le_bytes());
+    unit.extend(block);
+    unit
+}
+
+fn objref(kind: u32) -> Vec<u8> {
+    let mut objref = b"MEOW".to_vec();
+    objref.extend(kind.to_le_bytes());
+    // the interface identifier is not checked
+    objref.extend([0; 16]);
+    objref
+}
+
+fn standard_objref(ipid: &Uuid) -> Vec<u8> {
+    let mut objref = objref(1);
+    // flags and public references
+    objref.extend(0u32.to_le_bytes());
+    objref.extend(5u32.to_le_bytes());
+    objref.extend(OXID.to_le_bytes());
+    objref.extend(0x99u64.to_le_bytes());
+    objref.extend(ipid);
+    // empty resolver address
+    objref.extend([0; 4]);
+    objref
+}
+
+fn custom_objref(unit: &[u8]) -> Vec<u8> {
+    let mut objref = objref(4);
+    objref.extend(CLSID_WBEM_CLASS_OBJECT);
+    objref.extend(0u32.to_le_bytes());
+    objref.extend((unit.len() as u32).to_le_bytes());
+    objref.extend(unit);
+    objref
+}
+
+fn interface_pointer(stub: &mut Writer, objref: &[u8]) {
+    stub.u32(objref.len() as u32)
+        .u32(objref.len() as u32)
+        .bytes(objref);
+}
+
+/// Writes the ORPCTHAT followed by the unique pointer to the interface and the HRESULT.
+fn returned_interface(stub: &mut Writer, ipid: Result<&Uuid, u32>) {
+    stub.u32(0).pointer(false);
+    match ipid {
+        Ok(ipid) => {
+            stub.pointer(true);
+            interface_pointer(stub, &standard_objref(ipid));
+            stub.u32(0);
+        }
+        Err(hresult) => {
+            stub.pointer(false).u32(hresult);
+        }
+    };
+}
+
+fn orpc_this(request: &mut Reader) {
+    assert_eq!((request.u16().unwrap(), request.u16().unwrap()), (5, 7));
+    request.u32().unwrap();
+    request.u32().unwrap();
+    request.uuid().unwrap();
+    assert!(!request.pointer().unwrap());
+}
+
+fn bstr(request: &mut Reader) -> String {
+    assert!(request.pointer().unwrap());
+    request.u32().unwrap();
+    let length = request.u32().unwrap() as usize;
+    request.u32().unwrap();
+    let units: Vec<u16> = request
+        .bytes(length)
+        .unwrap()
+        .chunks(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    String::from_utf16(&units).unwrap()
+}
+
+/// State of the WMI service shared by the connections
+#[derive(Default)]
+struct Service {
+    /// Number of objects returned by the enumerator
+    returned: usize,
+    /// IPIDs of the released interfaces
+    released: Vec<Uuid>,
+    /// Methods changing the registry with the key, the value name and the value
+    changes: Vec<String>,
+}
+
+/// Returns the output parameters of the method of StdRegProv on a registry with the key
+/// HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows NT\CurrentVersion and the empty key
+/// HKEY_LOCAL_MACHINE\SOFTWARE\Test, whose values may be changed.
+fn registry(service: &mut Service, method: &str, input: &Instance) -> Vec<Property> {
+    let parameter = |name: &str| input.get(name).map(|x| x.to_string()).unwrap_or_default();
+    let result = |value| ("ReturnValue", CIM_UINT32, None, Some(Value::Number(value)));
+    if parameter("hDefKey") != "2147483650" {
+        return vec![result(NOT_FOUND)];
+    }
+    let key = parameter("sSubKeyName");
+    let name = parameter("sValueName");
+    let output: (_, _, Option<Value>) = match (method, key.as_str(), name.as_str()) {
+        ("EnumKey", "SOFTWARE\\Microsoft", _) => (
+            "sNames",
+            CIM_STRING | CIM_ARRAY,
+            Some(Value::Texts(&["Windows", "Windows NT"])),
+        ),
+        ("EnumKey" | "EnumValues", "SOFTWARE\\Test", _) => ("sNames", CIM_STRING | CIM_ARRAY, None),
+        ("EnumValues", CURRENT_VERSION, _) => (
+            "sNames",
+            CIM_STRING | CIM_ARRAY,
+            Some(Value::Texts(&["ProductName", "SystemRoot"])),
+        ),
+        ("GetStringValue", CURRENT_VERSION, "ProductName") => (
+            "sValue",
+            CIM_STRING,
+            Some(Value::Text("Windows Server 2019 Standard")),
+        ),
+        ("GetExpandedStringValue", CURRENT_VERSION, "SystemRoot") => (
+            "sValue",
+            CIM_STRING,
+            Some(Value::Text("%SystemDrive%\\Windows")),
+        ),
+        ("GetMultiStringValue", CURRENT_VERSION, "Languages") => (
+            "sValue",
+            CIM_STRING | CIM_ARRAY,
+            Some(Value::Texts(&["en-US", "de-DE"])),
+        ),
+        ("GetDWORDValue", CURRENT_VERSION, "CurrentMajorVersionNumber") => {
+            ("uValue", CIM_UINT32, Some(Value::Number(10)))
+        }
+        ("GetQWORDValue", CURRENT_VERSION, "InstallTime") => (
+            "uValue",
+            CIM_UINT64,
+            Some(Value::Number(133_497_000_000_000_000)),
+        ),
+        ("GetBinaryValue", CURRENT_VERSION, "DigitalProductId") => (
+            "uValue",
+            CIM_UINT8 | CIM_ARRAY,
+            Some(Value::Numbers(&[0xa4, 0, 3])),
+        ),
+        (
+            "SetDWORDValue"
+            | "SetQWORDValue"
+            | "SetStringValue"
+            | "SetExpandedStringValue"
+            | "CreateKey"
+            | "DeleteKey",
+            "SOFTWARE\\Test",
+            _,
+        ) => {
+            let value = parameter("uValue") + &parameter("sValue");
+            service.changes.push(format!("{method} {name}={value}"));
+            return vec![result(0)];
+        }
+        _ => return vec![result(NOT_FOUND)],
+    };
+    let (name, cim_type, value) = output;
+    vec![result(0), (name, cim_type, None, value)]
+}
+
+/// Returns the stub data of the response to the call or the status of a fault.
+fn call(
+    service: &mut Service,
+    port: u16,
+    object: Option<Uuid>,
+    opnum: u16,
+    stub: &[u8],
+) -> Result<Vec<u8>, u32> {
+    let mut request = Reader::new(stub);
+    let mut response = Writer::new();
+    orpc_this(&mut request);
+    match (object, opnum) {
+        (None, REMOTE_ACTIVATION) => {
+            assert_eq!(request.uuid().unwrap(), CLSID_WBEM_LEVEL1_LOGIN);
+            assert!(!request.pointer().unwrap());
+            assert!(!request.pointer().unwrap());
+            request.u32().unwrap();
+            request.u32().unwrap();
+            assert_eq!(request.u32().unwrap(), 1);
+            assert!(request.pointer().unwrap());
+            request.u32().unwrap();
+            assert_eq!(request.uuid().unwrap(), IWBEM_LEVEL1_LOGIN.uuid);
+            // the string binding of the service followed by a security binding
+            let mut bindings = vec![7];
+            bindings.extend(format!("127.0.0.1[{port}]").encode_utf16());
+            bindings.extend([0, 0]);
+            let security = bindings.len() as u16;
+            bindings.extend([10, 0xffff, 0, 0]);
+            response
+                .u32(0)
+                .pointer(false)
+                .bytes(&OXID.to_le_bytes())
+                .pointer(true)
+                .u32(bindings.len() as u32)
+                .u16(bindings.len() as u16)
+                .u16(security);
+            for x in bindings {
+                response.u16(x);
+            }
+            response
+                .uuid(&REM_UNKNOWN)
+                .u32(2)
+                .u16(5)
+                .u16(7)
+                .u32(0)
+                .u32(1)
+                .pointer(true);
+            interface_pointer(&mut response, &standard_objref(&LOGIN));
+            response.u32(1).u32(0).u32(0);
+        }
+        (Some(LOGIN), NTLM_LOGIN) => {
+            assert!(request.pointer().unwrap());
+            let namespace = request.string().unwrap();
+            let services = match namespace.as_str() {
+                "//./root/cimv2" | "//./root/rsop/computer" | "//./root/default" => Ok(&SERVICES),
+                _ => Err(WBEM_E_INVALID_NAMESPACE),
+            };
+            returned_interface(&mut response, services);
+        }
+        (Some(SERVICES), EXEC_QUERY) => {
+            assert_eq!(bstr(&mut request), "WQL");
+            let query = bstr(&mut request).to_lowercase();
+            assert_eq!(request.u32().unwrap(), 0x30);
+            let enumerator = match query.as_str() {
+                "select * from win32_operatingsystem" => Ok(&ENUMERATOR),
+                "select * from win32_quickfixengineering" => Ok(&EMPTY_ENUMERATOR),
+                _ => Err(WBEM_E_INVALID_CLASS),
+            };
+            service.returned = 0;
+            returned_interface(&mut response, enumerator);
+        }
+        (Some(enumerator @ (ENUMERATOR | EMPTY_ENUMERATOR)), NEXT) => {
+            assert_eq!(request.u32().unwrap(), 0xffff_ffff);
+            let count = request.u32().unwrap();
+            // one object at a time, S_FALSE with the last one
+            let (objects, hresult) = match (enumerator, service.returned) {
+                (ENUMERATOR, 0) => (vec!["Microsoft Windows Server 2019 Standard"], 0),
+                (ENUMERATOR, 1) => (
+                    vec!["Microsoft Windows Server 2019 Datacenter"],
+                    WBEM_S_FALSE,
+                ),
+                _ => (vec![], WBEM_S_FALSE),
+            };
+            service.returned += objects.len();
+            response
+                .u32(0)
+                .pointer(false)
+                .u32(count)
+                .u32(0)
+                .u32(objects.len() as u32);
+            for _ in &objects {
+                response.pointer(true);
+            }
+            for caption in &objects {
+                let unit = encode_instance(OPERATING_SYSTEM, &properties(caption));
+                interface_pointer(&mut response, &custom_objref(&unit));
+            }
+            response.u32(objects.len() as u32).u32(hresult);
+        }
+        (Some(SERVICES), EXEC_METHOD) => {
+            assert_eq!(bstr(&mut request), "StdRegProv");
+            let method = bstr(&mut request);
+            assert_eq!(request.u32().unwrap(), 0);
+            assert!(!request.pointer().unwrap());
+            assert!(request.pointer().unwrap());
+            request.u32().unwrap();
+            let length = request.u32().unwrap() as usize;
+            let objref = request.bytes(length).unwrap().to_vec();
+            let (clsid, data) = dcom::custom(&objref).unwrap();
+            assert_eq!(clsid, CLSID_WBEM_CLASS_OBJECT);
+            let input = object::decode(data).unwrap();
+            assert_eq!(input.class, "__PARAMETERS");
+            // the output parameters are returned, there is no call result
+            assert!(request.pointer().unwrap());
+            assert!(!request.pointer().unwrap());
+            assert!(!request.pointer().unwrap());
+            let output = registry(service, &method, &input);
+            response.u32(0).pointer(false).pointer(true).pointer(true);
+            let unit = encode_instance("__PARAMETERS", &output);
+            interface_pointer(&mut response, &custom_objref(&unit));
+            response.pointer(false).u32(0);
+        }
+        (Some(REM_UNKNOWN), REM_RELEASE) => {
+            let count = request.u16().unwrap();
+            request.u32().unwrap();
+            for _ in 0..count {
+                service.released.push(request.uuid().unwrap());
+                assert_eq!(request.u32().unwrap(), 5);
+                request.u32().unwrap();
+            }
+            response.u32(0).pointer(false).u32(0);
+        }
+        _ => return Err(NCA_S_OP_RNG_ERROR),
+    }
+    Ok(response.finish())
+}
+
+fn read_pdu(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut pdu = vec![0; 16];
+    stream.read_exact(&mut pdu).ok()?;
+    let length = u16::from_le_bytes([pdu[8], pdu[9]]) as usize;
+    pdu.resize(length, 0);
+    stream.read_exact(&mut pdu[16..]).ok()?;
+    Some(pdu)
+}
+
+/// Answers like the endpoint mapper and the WMI service of a host with the user "scanner" and
+/// the password "secret", both on the same port.
+fn wmi_server() -> (u16, Arc<Mutex<Service>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let service = Arc::new(Mutex::new(Service::default()));
+    let shared = service.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut server = AuthenticatedServer::default();
+            while let Some(pdu) = read_pdu(&mut stream) {
+                let pdus = server.answer(pdu, |_, object, opnum, stub| {
+                    call(
+                        &mut shared.lock().unwrap_or_else(PoisonError::into_inner),
+                        port,
+                        object,
+                        opnum,
+                        stub,
+                    )
+                });
+                for pdu in pdus {
+                    stream.write_all(&pdu).unwrap();
+                }
+            }
+        }
+    });
+    (port, service)
+}
+
+#[test]
+fn decode_instance() {
+    let unit = encode_instance(OPERATING_SYSTEM, &properties("Microsoft Windows 10 Pro"));
+    let instance = object::decode(&unit).unwrap();
+    assert_eq!(instance.class, "Win32_OperatingSystem");
+    let value = |name: &str| instance.get(name).map(|x| x.to_string());
+    assert_eq!(value("Caption").unwrap(), "Microsoft Windows 10 Pro");
+    assert_eq!(value("OSLanguage").unwrap(), "1033");
+    assert_eq!(value("CurrentTimeZone").unwrap(), "-60");
+    assert_eq!(value("Organization").unwrap(), "Contoso – IT");
+    assert_eq!(value("RegisteredUser").unwrap(), "Jürgen");
+    assert_eq!(value("Description").unwrap(), "(null)");
+    assert_eq!(value("MUILanguages").unwrap(), "(en-US,de-DE)");
+    assert!(object::decode(&unit[..unit.len() - 1]).is_err());
+    assert_eq!(object::format(&[]), "");
+}
+
+#[test]
+fn wmi_query() {
+    let (port, service) = wmi_server();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(
+            r#"h = wmi_connect(username: "WORKGROUP\scanner", password: "secret", port: {port});"#
+        ),
+        1,
+    );
+    let header = "BuildNumber|Caption|CurrentTimeZone|Description|FreePhysicalMemory|InstallDate|MUILanguages|OSLanguage|OSType|Organization|Primary|RegisteredUser\n";
+    let row = |edition: &str| {
+        format!("17763|Microsoft Windows Server 2019 {edition}|-60|(null)|6000000000|20240115093000.000000+060|(en-US,de-DE)|1033|18|Contoso – IT|True|Jürgen\n")
+    };
+    let result = format!("{header}{}{}", row("Standard"), row("Datacenter"));
+    t.ok(
+        r#"wmi_query(wmi_handle: h, query: "SELECT * FROM Win32_OperatingSystem");"#,
+        result.clone(),
+    );
+    t.ok(
+        r#"wmi_query(wmi_handle: h, query: "SELECT * FROM Win32_QuickFixEngineering");"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        r#"wmi_query(wmi_handle: h, query: "SELECT * FROM Win32_Missing");"#,
+        NaslValue::Null,
+    );
+    t.ok("wmi_close(wmi_handle: h);", true);
+
+    // the calls are encrypted with seal
+    t.ok(
+        format!(
+            r#"h = wmi_connect_rsop(username: "scanner", password: "secret", options: "[seal]", port: {port});"#
+        ),
+        2,
+    );
+    t.ok(
+        r#"wmi_query_rsop(wmi_handle: h, query: "SELECT * FROM Win32_OperatingSystem");"#,
+        result,
+    );
+    t.ok("wmi_close(wmi_handle: h);", true);
+
+    t.ok(
+        format!(
+            r#"wmi_connect(username: "scanner", password: "secret", ns: "root\missing", port: {port});"#
+        ),
+        NaslValue::Null,
+    );
+    t.ok(
+        format!(r#"wmi_connect(username: "scanner", password: "wrong", port: {port});"#),
+        NaslValue::Null,
+    );
+    t.ok(
+        format!(r#"wmi_connect(username: "scanner", password: "", port: {port});"#),
+        NaslValue::Null,
+    );
+    // the lines only run when the builder is dropped
+    drop(t);
+    assert_eq!(
+        service.lock().unwrap().released,
+        [
+            LOGIN,
+            ENUMERATOR,
+            EMPTY_ENUMERATOR,
+            SERVICES,
+            LOGIN,
+            ENUMERATOR,
+            SERVICES,
+            LOGIN
+        ]
+    );
+}
+
+#[test]
+fn wmi_registry() {
+    let (port, service) = wmi_server();
+    let mut t =
+        TestBuilder::default().with_context_key(ContextKey::FileName("127.0.0.1".to_string()));
+    t.ok(
+        format!(r#"h = wmi_connect_reg(username: "scanner", password: "secret", port: {port});"#),
+        1,
+    );
+    t.ok(
+        r#"key = "SOFTWARE\Microsoft\Windows NT\CurrentVersion";"#,
+        CURRENT_VERSION,
+    );
+    t.ok(
+        r#"wmi_reg_enum_key(wmi_handle: h, key: "SOFTWARE\Microsoft");"#,
+        vec!["Windows", "Windows NT"],
+    );
+    t.ok(
+        r#"wmi_reg_enum_key(wmi_handle: h, key: "SOFTWARE\Test");"#,
+        Vec::<String>::new(),
+    );
+    t.ok(
+        r#"wmi_reg_enum_key(wmi_handle: h, hive: 0x80000001, key: "SOFTWARE\Microsoft");"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        "wmi_reg_enum_value(wmi_handle: h, key: key);",
+        vec!["ProductName", "SystemRoot"],
+    );
+    t.ok(
+        r#"wmi_reg_get_sz(wmi_handle: h, key: key, key_name: "ProductName");"#,
+        "Windows Server 2019 Standard",
+    );
+    t.ok(
+        r#"wmi_reg_get_sz(wmi_handle: h, key: key, key_name: "Missing");"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        r#"wmi_reg_get_ex_string_val(wmi_handle: h, key: key, val_name: "SystemRoot");"#,
+        "%SystemDrive%\\Windows",
+    );
+    t.ok(
+        r#"wmi_reg_get_mul_string_val(wmi_handle: h, key: key, val_name: "Languages");"#,
+        vec!["en-US", "de-DE"],
+    );
+    t.ok(
+        r#"wmi_reg_get_dword_val(wmi_handle: h, key: key, val_name: "CurrentMajorVersionNumber");"#,
+        10,
+    );
+    t.ok(
+        r#"wmi_reg_get_qword_val(wmi_handle: h, key: key, val_name: "InstallTime");"#,
+        133_497_000_000_000_000i64,
+    );
+    t.ok(
+        r#"wmi_reg_get_bin_val(wmi_handle: h, key: key, val_name: "DigitalProductId");"#,
+        vec![0xa4u8, 0, 3],
+    );
+    t.ok(
+        r#"wmi_reg_create_key(wmi_handle: h, key: "SOFTWARE\Test");"#,
+        true,
+    );
+    t.ok(
+        r#"wmi_reg_set_dword_val(wmi_handle: h, key: "SOFTWARE\Test", val_name: "Count", val: "4096");"#,
+        true,
+    );
+    t.ok(
+        r#"wmi_reg_set_qword_val(wmi_handle: h, key: "SOFTWARE\Test", val_name: "Size", val: 8589934592);"#,
+        true,
+    );
+    t.ok(
+        r#"wmi_reg_set_string_val(wmi_handle: h, key: "SOFTWARE\Test", val_name: "Name", val: "test");"#,
+        true,
+    );
+    t.ok(
+        r#"wmi_reg_set_ex_string_val(wmi_handle: h, key: "SOFTWARE\Test", val_name: "Path", val: "%TEMP%");"#,
+        true,
+    );
+    t.ok(
+        r#"wmi_reg_set_string_val(wmi_handle: h, key: "SOFTWARE\Missing", val_name: "Name", val: "test");"#,
+        NaslValue::Null,
+    );
+    t.ok(
+        r#"wmi_reg_delete_key(wmi_handle: h, key: "SOFTWARE\Test");"#,
+        true,
+    );
+    t.ok("wmi_close(wmi_handle: h);", true);
+    drop(t);
+    assert_eq!(
+        service.lock().unwrap().changes,
+        [
+            "CreateKey =",
+            "SetDWORDValue Count=4096",
+            "SetQWORDValue Size=8589934592",
+            "SetStringValue Name=test",
+            "SetExpandedStringValue Path=%TEMP%",
+            "DeleteKey =",
+        ]
+    );
+}
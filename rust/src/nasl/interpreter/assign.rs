@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use crate::nasl::syntax::{AssignOrder, Statement, TokenCategory};
 
@@ -26,7 +26,7 @@ fn prepare_array(idx: &NaslValue, left: NaslValue) -> (usize, Vec<NaslValue>) {
     (idx, arr)
 }
 
-fn prepare_dict(left: NaslValue) -> HashMap<String, NaslValue> {
+fn prepare_dict(left: NaslValue) -> IndexMap<String, NaslValue> {
     match left {
         NaslValue::Array(x) => x
             .into_iter()
@@ -34,8 +34,43 @@ fn prepare_dict(left: NaslValue) -> HashMap<String, NaslValue> {
             .map(|(i, v)| (i.to_string(), v))
             .collect(),
         NaslValue::Dict(x) => x,
-        NaslValue::Null => HashMap::new(),
-        x => HashMap::from([("0".to_string(), x)]),
+        NaslValue::Null => IndexMap::new(),
+        x => IndexMap::from([("0".to_string(), x)]),
+    }
+}
+
+/// Applies the result to the element at the path of lookups in the value, missing elements are
+/// created. Returns the updated value, the original element and the new one.
+fn update(
+    value: NaslValue,
+    path: &[NaslValue],
+    result: &impl Fn(&NaslValue) -> NaslValue,
+) -> (NaslValue, NaslValue, NaslValue) {
+    let Some((idx, path)) = path.split_first() else {
+        let new = result(&value);
+        return (new.clone(), value, new);
+    };
+    let key = match idx {
+        NaslValue::String(idx) => Some(idx.clone()),
+        NaslValue::Data(idx) => Some(idx.iter().map(|&x| x as char).collect()),
+        _ if matches!(value, NaslValue::Dict(_)) => Some(idx.to_string()),
+        _ => None,
+    };
+    match key {
+        Some(key) => {
+            let mut dict = prepare_dict(value);
+            let element = dict.get(&key).cloned().unwrap_or(NaslValue::Null);
+            let (element, original, new) = update(element, path, result);
+            dict.insert(key, element);
+            (NaslValue::Dict(dict), original, new)
+        }
+        None => {
+            let (idx, mut arr) = prepare_array(idx, value);
+            let element = std::mem::take(&mut arr[idx]);
+            let (element, original, new) = update(element, path, result);
+            arr[idx] = element;
+            (NaslValue::Array(arr), original, new)
+        }
     }
 }
 
@@ -55,123 +90,73 @@ impl<'a> Interpreter<'a> {
             (idx, ContextType::Value(val)) => Ok((idx, val.clone())),
         }
     }
-    #[allow(clippy::too_many_arguments)]
-    fn handle_dict(
-        &mut self,
-        ridx: usize,
-        key: &str,
-        idx: String,
-        left: NaslValue,
-        right: &NaslValue,
-        return_original: &AssignOrder,
-        result: impl Fn(&NaslValue, &NaslValue) -> NaslValue,
-    ) -> NaslValue {
-        let mut dict = prepare_dict(left);
-        match return_original {
-            AssignOrder::ReturnAssign => {
-                let original = dict.get(&idx).unwrap_or(&NaslValue::Null).clone();
-                let result = result(&original, right);
-                dict.insert(idx, result);
-                self.save(ridx, key, NaslValue::Dict(dict));
-                original
-            }
-            AssignOrder::AssignReturn => {
-                let original = dict.get(&idx).unwrap_or(&NaslValue::Null);
-                let result = result(original, right);
-                dict.insert(idx, result.clone());
-                self.save(ridx, key, NaslValue::Dict(dict));
-                result
-            }
-        }
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    fn handle_array(
-        &mut self,
-        ridx: usize,
-        key: &str,
-        idx: &NaslValue,
-        left: NaslValue,
-        right: &NaslValue,
-        return_original: &AssignOrder,
-        result: impl Fn(&NaslValue, &NaslValue) -> NaslValue,
-    ) -> NaslValue {
-        let (idx, mut arr) = prepare_array(idx, left);
-        match return_original {
-            AssignOrder::ReturnAssign => {
-                let orig = arr[idx].clone();
-                let result = result(&orig, right);
-                arr[idx] = result;
-                self.save(ridx, key, NaslValue::Array(arr));
-                orig
-            }
-            AssignOrder::AssignReturn => {
-                let result = result(&arr[idx], right);
-                arr[idx] = result.clone();
-                self.save(ridx, key, NaslValue::Array(arr));
-                result
-            }
-        }
-    }
 
     fn store_return(
         &mut self,
         key: &str,
-        lookup: Option<NaslValue>,
+        lookups: &[NaslValue],
         right: &NaslValue,
         result: impl Fn(&NaslValue, &NaslValue) -> NaslValue,
     ) -> InterpretResult {
-        self.dynamic_return(key, &AssignOrder::AssignReturn, lookup, right, result)
+        self.dynamic_return(key, &AssignOrder::AssignReturn, lookups, right, result)
     }
 
     fn dynamic_return(
         &mut self,
         key: &str,
         order: &AssignOrder,
-        lookup: Option<NaslValue>,
+        lookups: &[NaslValue],
         right: &NaslValue,
         result: impl Fn(&NaslValue, &NaslValue) -> NaslValue,
     ) -> InterpretResult {
         let (ridx, left) = self.named_value(key)?;
-        let result = match lookup {
-            None => {
-                let result = result(&left, right);
-                self.save(ridx, key, result.clone());
-                match order {
-                    AssignOrder::AssignReturn => result,
-                    AssignOrder::ReturnAssign => left,
-                }
-            }
-            Some(idx) => match idx {
-                NaslValue::String(idx) => {
-                    self.handle_dict(ridx, key, idx, left, right, order, result)
-                }
-                NaslValue::Data(idx) => {
-                    let idx = idx.into_iter().map(|x| x as char).collect();
-                    self.handle_dict(ridx, key, idx, left, right, order, result)
-                }
-                _ => match left {
-                    NaslValue::Dict(_) => {
-                        self.handle_dict(ridx, key, idx.to_string(), left, right, order, result)
-                    }
-                    _ => self.handle_array(ridx, key, &idx, left, right, order, result),
-                },
-            },
-        };
-        Ok(result)
+        let (value, original, new) = update(left, lookups, &|x| result(x, right));
+        self.save(ridx, key, value);
+        Ok(match order {
+            AssignOrder::AssignReturn => new,
+            AssignOrder::ReturnAssign => original,
+        })
     }
+
     fn without_right(
         &mut self,
         order: &AssignOrder,
         key: &str,
-        lookup: Option<NaslValue>,
+        lookups: &[NaslValue],
         result: impl Fn(&NaslValue, &NaslValue) -> NaslValue,
     ) -> InterpretResult {
-        self.dynamic_return(key, order, lookup, &NaslValue::Null, result)
+        self.dynamic_return(key, order, lookups, &NaslValue::Null, result)
+    }
+
+    /// Returns the variable and the evaluated lookups of the left side of an assignment, e.g.
+    /// `a` and `["x", "y"]` for `a["x"]["y"]`.
+    async fn assignable(
+        &mut self,
+        left: &Statement,
+    ) -> Result<(String, Vec<NaslValue>), InterpretError> {
+        match left.kind() {
+            Variable | Array(None) => Ok((Self::identifier(left.as_token())?, vec![])),
+            Array(Some(stmt)) => Ok((
+                Self::identifier(left.as_token())?,
+                vec![self.resolve(stmt).await?],
+            )),
+            Index(base, stmt) => {
+                let (key, mut lookups) = Box::pin(self.assignable(base)).await?;
+                lookups.push(self.resolve(stmt).await?);
+                Ok((key, lookups))
+            }
+            _ => Err(InterpretError::unsupported(left, "Array or Variable")),
+        }
     }
 }
 
 impl<'a> Interpreter<'a> {
+    /// Assigns an already evaluated value to a variable, like `key = value`.
+    pub(crate) fn assign_value(&mut self, key: &str, value: NaslValue) {
+        let idx = self.register().index_named(key).map_or(0, |(idx, _)| idx);
+        self.save(idx, key, value);
+    }
+
     /// Assign a right value to a left value. Return either the
     /// previous or the new value, based on the order.
     pub async fn assign(
@@ -181,55 +166,51 @@ impl<'a> Interpreter<'a> {
         left: &Statement,
         right: &Statement,
     ) -> InterpretResult {
-        let (key, lookup) = {
-            match left.kind() {
-                Variable => (Self::identifier(left.as_token())?, None),
-                Array(Some(stmt)) => (
-                    Self::identifier(left.as_token())?,
-                    Some(self.resolve(stmt).await?),
-                ),
-                Array(None) => (Self::identifier(left.as_token())?, None),
-                _ => return Err(InterpretError::unsupported(left, "Array or Variable")),
-            }
-        };
+        let (key, lookups) = self.assignable(left).await?;
         let val = self.resolve(right).await?;
         match category {
-            TokenCategory::Equal => self.store_return(&key, lookup, &val, |_, right| right.clone()),
-            TokenCategory::PlusEqual => self.store_return(&key, lookup, &val, |left, right| {
+            TokenCategory::Equal => {
+                self.store_return(&key, &lookups, &val, |_, right| right.clone())
+            }
+            TokenCategory::PlusEqual => self.store_return(&key, &lookups, &val, |left, right| {
                 NaslValue::Number(i64::from(left) + i64::from(right))
             }),
-            TokenCategory::MinusEqual => self.store_return(&key, lookup, &val, |left, right| {
+            TokenCategory::MinusEqual => self.store_return(&key, &lookups, &val, |left, right| {
                 NaslValue::Number(i64::from(left) - i64::from(right))
             }),
-            TokenCategory::SlashEqual => self.store_return(&key, lookup, &val, |left, right| {
+            TokenCategory::SlashEqual => self.store_return(&key, &lookups, &val, |left, right| {
                 NaslValue::Number(i64::from(left) / i64::from(right))
             }),
-            TokenCategory::StarEqual => self.store_return(&key, lookup, &val, |left, right| {
+            TokenCategory::StarEqual => self.store_return(&key, &lookups, &val, |left, right| {
                 NaslValue::Number(i64::from(left) * i64::from(right))
             }),
             TokenCategory::GreaterGreaterEqual => {
-                self.store_return(&key, lookup, &val, |left, right| {
+                self.store_return(&key, &lookups, &val, |left, right| {
                     NaslValue::Number(i64::from(left) >> i64::from(right))
                 })
             }
-            TokenCategory::LessLessEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) << i64::from(right))
-            }),
+            TokenCategory::LessLessEqual => {
+                self.store_return(&key, &lookups, &val, |left, right| {
+                    NaslValue::Number(i64::from(left) << i64::from(right))
+                })
+            }
             TokenCategory::GreaterGreaterGreaterEqual => {
-                self.store_return(&key, lookup, &val, |left, right| {
+                self.store_return(&key, &lookups, &val, |left, right| {
                     // get rid of minus sign
                     let left = i64::from(left) as u32;
                     let right = i64::from(right) as u32;
                     NaslValue::Number((left << right) as i64)
                 })
             }
-            TokenCategory::PercentEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) % i64::from(right))
-            }),
-            TokenCategory::PlusPlus => self.without_right(order, &key, lookup, |left, _| {
+            TokenCategory::PercentEqual => {
+                self.store_return(&key, &lookups, &val, |left, right| {
+                    NaslValue::Number(i64::from(left) % i64::from(right))
+                })
+            }
+            TokenCategory::PlusPlus => self.without_right(order, &key, &lookups, |left, _| {
                 NaslValue::Number(i64::from(left) + 1)
             }),
-            TokenCategory::MinusMinus => self.without_right(order, &key, lookup, |left, _| {
+            TokenCategory::MinusMinus => self.without_right(order, &key, &lookups, |left, _| {
                 NaslValue::Number(i64::from(left) - 1)
             }),
 
@@ -241,7 +222,7 @@ impl<'a> Interpreter<'a> {
 #[cfg(test)]
 mod tests {
     use crate::nasl::test_prelude::*;
-    use std::collections::HashMap;
+    use indexmap::IndexMap;
 
     #[test]
     fn variables() {
@@ -289,11 +270,56 @@ mod tests {
         t.ok("a['hi'] = 12;", 12);
         t.ok(
             "a;",
-            NaslValue::Dict(HashMap::from([("hi".to_string(), 12.into())])),
+            NaslValue::Dict(IndexMap::from([("hi".to_string(), 12.into())])),
         );
         t.ok("a['hi'];", 12);
     }
 
+    #[test]
+    fn chained_lookups() {
+        let mut t = TestBuilder::default();
+        t.ok("a['x']['y'] = 1;", 1);
+        t.ok("a['x']['z'] += 2;", 2);
+        t.ok("a['x']['y']++;", 1);
+        t.ok("a['x']['y'];", 2);
+        t.ok("a['x']['missing']['deeper'];", NaslValue::Null);
+        t.ok("l[1][0] = 'v';", NaslValue::Data("v".into()));
+        t.ok("l[1][0];", NaslValue::Data("v".into()));
+        t.ok(
+            "l;",
+            NaslValue::Array(vec![
+                NaslValue::Null,
+                NaslValue::Array(vec![NaslValue::Data("v".into())]),
+            ]),
+        );
+        t.run(
+            "u = [make_array('name', 'Administrator'), make_array('name', 'Guest', 'rid', 501)];",
+        );
+        t.ok("u[1]['rid'];", 501);
+        t.ok("u[0]['name'];", NaslValue::Data("Administrator".into()));
+        t.ok("make_array('x', make_array('y', 3))['x']['y'];", 3);
+    }
+
+    #[test]
+    fn structs() {
+        let mut t = TestBuilder::default();
+        t.ok(
+            "a = {name: 'ssh', port: 20 + 2};",
+            NaslValue::Dict(IndexMap::from([
+                ("name".to_string(), NaslValue::Data("ssh".into())),
+                ("port".to_string(), 22.into()),
+            ])),
+        );
+        t.ok("a.port;", 22);
+        t.ok("a.port++;", 22);
+        t.ok("a['port'];", 23);
+        t.ok("a.banner = 'SSH-2.0';", NaslValue::Data("SSH-2.0".into()));
+        t.run("b = {inner: a, nested: {x: 1}};");
+        t.ok("b.inner.banner;", NaslValue::Data("SSH-2.0".into()));
+        t.ok("b['nested'].x;", 1);
+        t.ok("a.missing;", NaslValue::Null);
+    }
+
     #[test]
     fn array_creation() {
         check_code_result("a = [1, 2, 3];", vec![1, 2, 3]);
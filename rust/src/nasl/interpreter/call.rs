@@ -40,8 +40,15 @@ impl<'a> Interpreter<'a> {
             ContextType::Value(NaslValue::Array(position)),
         );
         self.register_mut().create_root_child(named);
+        if self.ctxconfigs.nasl_fn_defined(name) {
+            if let Err(e) = self.hooks.before_builtin(name, self.register()) {
+                self.register_mut().drop_last();
+                return Err(*e);
+            }
+        }
         let result = match self.ctxconfigs.nasl_fn_execute(name, self.register()).await {
-            Some(r) => {
+            Some(mut r) => {
+                self.hooks.after_builtin(name, &mut r);
                 if let Ok(NaslValue::Fork(mut x)) = r {
                     Ok(if let Some(r) = x.pop() {
                         // this is a proposal for the case that the caller is immediately executing
@@ -1,10 +1,13 @@
 //! Contains implementations of Interpreter that handle the simulation of forking methods for the
 //! caller.
 
+use std::sync::Arc;
+
 use futures::{stream, Stream};
 
 use crate::nasl::syntax::{Lexer, Statement, Tokenizer};
 
+use crate::nasl::interpreter::hooks::InterpreterHook;
 use crate::nasl::interpreter::interpreter::{InterpretResult, Interpreter};
 use crate::nasl::prelude::*;
 
@@ -78,6 +81,11 @@ impl<'a, 'b> CodeInterpreter<'a, 'b> {
         futures::executor::block_on(async { self.stream().collect::<Vec<_>>().await.into_iter() })
     }
 
+    /// Registers a hook that is called around the execution of statements and builtin functions
+    pub fn add_hook(&mut self, hook: Arc<dyn InterpreterHook>) {
+        self.interpreter.add_hook(hook);
+    }
+
     /// Returns the Register of the underlying Interpreter
     pub fn register(&self) -> &Register {
         self.interpreter.register()
@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::io;
+use std::{io, time::Duration};
 
 use crate::nasl::syntax::LoadError;
 use crate::nasl::syntax::{Statement, SyntaxError, TokenCategory};
@@ -90,6 +90,12 @@ pub enum InterpretErrorKind {
     /// An error occurred while calling a built-in function.
     #[error("{0}")]
     FunctionCallError(FunctionError),
+    /// The script exceeded its maximum lifetime.
+    #[error("Script timed out after {} seconds", .0.as_secs())]
+    Timeout(Duration),
+    /// The script exceeded a resource limit.
+    #[error("Script exceeded its resource limits: {0}")]
+    ResourceLimit(String),
 }
 
 impl InterpretError {
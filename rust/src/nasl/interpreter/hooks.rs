@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Hooks around the execution of statements and builtin function calls.
+//!
+//! Hooks allow external tools to observe and influence the interpretation of a script without
+//! changing the interpreter itself, e.g. to trace, measure coverage, enforce policies or to
+//! inject mutations. They are registered on an [Interpreter](super::Interpreter) or a
+//! [CodeInterpreter](super::CodeInterpreter) and are inherited by included scripts.
+
+use std::sync::Arc;
+
+use crate::nasl::syntax::{NaslValue, Statement};
+use crate::nasl::utils::{error::FunctionErrorKind, Register};
+
+use super::{interpreter::InterpretResult, InterpretError};
+
+/// Is called before and after statements and builtin function calls are executed.
+///
+/// All methods have a default implementation doing nothing, so that an implementation only
+/// needs to override the methods it is interested in.
+pub trait InterpreterHook: Send + Sync {
+    /// Is called before a statement is executed.
+    ///
+    /// Returning an error aborts the execution of the statement with that error. It is boxed as
+    /// it is returned on the path of every statement.
+    fn before_statement(&self, _statement: &Statement) -> Result<(), Box<InterpretError>> {
+        Ok(())
+    }
+
+    /// Is called after a statement is executed and may change its result.
+    fn after_statement(&self, _statement: &Statement, _result: &mut InterpretResult) {}
+
+    /// Is called before a builtin function is executed with the register containing its
+    /// arguments.
+    ///
+    /// Returning an error aborts the call with that error.
+    fn before_builtin(&self, _name: &str, _register: &Register) -> Result<(), Box<InterpretError>> {
+        Ok(())
+    }
+
+    /// Is called after a builtin function is executed and may change its result.
+    fn after_builtin(&self, _name: &str, _result: &mut Result<NaslValue, FunctionErrorKind>) {}
+}
+
+/// The hooks registered on an interpreter, executed in order of registration.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Vec<Arc<dyn InterpreterHook>>);
+
+impl Hooks {
+    pub fn add(&mut self, hook: Arc<dyn InterpreterHook>) {
+        self.0.push(hook);
+    }
+
+    pub fn before_statement(&self, statement: &Statement) -> Result<(), Box<InterpretError>> {
+        self.0
+            .iter()
+            .try_for_each(|x| x.before_statement(statement))
+    }
+
+    pub fn after_statement(&self, statement: &Statement, result: &mut InterpretResult) {
+        for hook in &self.0 {
+            hook.after_statement(statement, result);
+        }
+    }
+
+    pub fn before_builtin(
+        &self,
+        name: &str,
+        register: &Register,
+    ) -> Result<(), Box<InterpretError>> {
+        self.0
+            .iter()
+            .try_for_each(|x| x.before_builtin(name, register))
+    }
+
+    pub fn after_builtin(&self, name: &str, result: &mut Result<NaslValue, FunctionErrorKind>) {
+        for hook in &self.0 {
+            hook.after_builtin(name, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::StreamExt;
+
+    use crate::nasl::interpreter::{
+        interpreter::InterpretResult, CodeInterpreter, FunctionError, InterpretError,
+        InterpretErrorKind,
+    };
+    use crate::nasl::prelude::*;
+    use crate::nasl::syntax::Statement;
+    use crate::storage::ContextKey;
+
+    use super::InterpreterHook;
+
+    #[derive(Default)]
+    struct Recorder {
+        statements: Mutex<usize>,
+        builtins: Mutex<Vec<String>>,
+    }
+
+    impl InterpreterHook for Recorder {
+        fn after_statement(&self, _: &Statement, _: &mut InterpretResult) {
+            *self.statements.lock().unwrap() += 1;
+        }
+
+        fn before_builtin(&self, name: &str, _: &Register) -> Result<(), Box<InterpretError>> {
+            self.builtins.lock().unwrap().push(name.to_string());
+            Ok(())
+        }
+    }
+
+    /// Denies calls to a builtin and replaces the results of another one
+    struct Policy;
+
+    impl InterpreterHook for Policy {
+        fn before_builtin(&self, name: &str, _: &Register) -> Result<(), Box<InterpretError>> {
+            match name {
+                "sleep" => Err(InterpretError::new(
+                    InterpretErrorKind::FunctionCallError(FunctionError::new(
+                        name,
+                        FunctionErrorKind::Diagnostic("denied".to_string(), None),
+                    )),
+                    None,
+                )
+                .into()),
+                _ => Ok(()),
+            }
+        }
+
+        fn after_builtin(&self, name: &str, result: &mut Result<NaslValue, FunctionErrorKind>) {
+            if name == "strlen" {
+                *result = Ok(NaslValue::Number(42));
+            }
+        }
+    }
+
+    async fn run(code: &str, hooks: Vec<Arc<dyn InterpreterHook>>) -> Vec<InterpretResult> {
+        let factory = ContextFactory::default();
+        let context = factory.build(ContextKey::default());
+        let mut interpreter = CodeInterpreter::new(code, Register::default(), &context);
+        for hook in hooks {
+            interpreter.add_hook(hook);
+        }
+        interpreter.stream().collect().await
+    }
+
+    #[tokio::test]
+    async fn observe() {
+        let recorder = Arc::new(Recorder::default());
+        let results = run(
+            "a = 1; if (a) { b = strlen('abc'); } function f() { return 1; } f();",
+            vec![recorder.clone()],
+        )
+        .await;
+        assert!(results.iter().all(|x| x.is_ok()));
+        assert!(*recorder.statements.lock().unwrap() > 4);
+        // user defined functions are not builtins
+        assert_eq!(*recorder.builtins.lock().unwrap(), vec!["strlen"]);
+    }
+
+    #[tokio::test]
+    async fn enforce_and_mutate() {
+        let results = run("strlen('abc'); sleep(1);", vec![Arc::new(Policy)]).await;
+        assert_eq!(results[0], Ok(NaslValue::Number(42)));
+        assert!(matches!(
+            results[1].as_ref().unwrap_err().kind,
+            InterpretErrorKind::FunctionCallError(_)
+        ));
+    }
+}
@@ -2,11 +2,68 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+//! Detects the include-level constants cached by [crate::nasl::utils::IncludeCache].
+
+use crate::nasl::syntax::{Statement, StatementKind, TokenCategory};
+
+use super::Interpreter;
+
+/// Builtins without side effects whose result only depends on their arguments
+const PURE_FUNCTIONS: &[&str] = &[
+    "crap",
+    "hex",
+    "hexstr",
+    "int",
+    "make_array",
+    "make_list",
+    "ord",
+    "raw_string",
+    "strcat",
+    "string",
+    "strlen",
+    "substr",
+    "tolower",
+    "toupper",
+];
+
+/// Returns true when the expression evaluates to the same value in every script.
+fn is_constant(statement: &Statement) -> bool {
+    match statement.kind() {
+        StatementKind::Primitive => true,
+        StatementKind::NamedParameter(x) => is_constant(x),
+        StatementKind::Parameter(x) | StatementKind::Struct(x) | StatementKind::Operator(_, x) => {
+            x.iter().all(is_constant)
+        }
+        StatementKind::Call(arguments) => {
+            Interpreter::identifier(statement.as_token())
+                .is_ok_and(|name| PURE_FUNCTIONS.contains(&name.as_str()))
+                && is_constant(arguments)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the name of the variable when the statement assigns a constant expression to it,
+/// e.g. `known_versions = make_list("1.0", "1.1");`.
+pub(crate) fn constant_assignment(statement: &Statement) -> Option<String> {
+    match statement.kind() {
+        StatementKind::Assign(TokenCategory::Equal, _, left, right)
+            if matches!(left.kind(), StatementKind::Variable) && is_constant(right) =>
+        {
+            Interpreter::identifier(left.as_token()).ok()
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{collections::HashMap, string::String};
 
+    use indexmap::IndexMap;
+
     use crate::nasl::interpreter::CodeInterpreter;
+    use crate::nasl::utils::include_cache::include_hash;
     use crate::nasl::{syntax::LoadError, Loader};
 
     use crate::nasl::{nasl_std_functions, prelude::*};
@@ -50,6 +107,9 @@ mod tests {
             loader,
             functions: nasl_std_functions(),
             storage: DefaultDispatcher::default(),
+            random: Default::default(),
+            traffic: None,
+            temp_dir: Default::default(),
         };
         let ctx = context.build(Default::default());
         let mut interpreter = CodeInterpreter::new(code, register, &ctx);
@@ -60,10 +120,50 @@ mod tests {
         assert_eq!(interpreter.next_statement().await, Some(Ok(12.into())));
         assert_eq!(
             interpreter.next_statement().await,
-            Some(Ok(NaslValue::Dict(HashMap::from([(
+            Some(Ok(NaslValue::Dict(IndexMap::from([(
                 "hello".to_owned(),
                 NaslValue::Data("world".as_bytes().into())
             )]))))
         );
     }
+
+    #[tokio::test]
+    async fn cached_constants() {
+        let example = r#"
+        versions = make_list("1.0", "1.1");
+        port = get_kb_item("Ports/tcp/80");
+        "#
+        .to_string();
+        let hash = include_hash(&example);
+        let plugins = HashMap::from([("example.inc".to_string(), example)]);
+        let context = ContextFactory {
+            loader: FakeInclude { plugins },
+            functions: nasl_std_functions(),
+            storage: DefaultDispatcher::default(),
+            random: Default::default(),
+            traffic: None,
+            temp_dir: Default::default(),
+        };
+        let ctx = context.build(Default::default());
+        let code = r#"
+        include("example.inc");
+        versions;
+        "#;
+        let run = || async {
+            let mut interpreter = CodeInterpreter::new(code, Register::default(), &ctx);
+            interpreter.next_statement().await;
+            interpreter.next_statement().await
+        };
+        let expected = NaslValue::Array(vec!["1.0".into(), "1.1".into()]);
+        assert_eq!(run().await, Some(Ok(expected)));
+        // only the constant is cached
+        assert_eq!(ctx.include_cache().len(), 1);
+        assert_eq!(
+            ctx.include_cache().get(&hash, 0),
+            Some(NaslValue::Array(vec!["1.0".into(), "1.1".into()]))
+        );
+
+        ctx.include_cache().insert(hash, 0, "cached".into());
+        assert_eq!(run().await, Some(Ok("cached".into())));
+    }
 }
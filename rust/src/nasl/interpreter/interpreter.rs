@@ -2,20 +2,23 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::{collections::HashMap, io};
+use std::{collections::HashMap, io, sync::Arc};
+
+use indexmap::IndexMap;
 
 use crate::nasl::syntax::{
-    IdentifierType, LoadError, NaslValue, Statement, StatementKind::*, SyntaxError, Token,
-    TokenCategory,
+    IdentifierType, LoadError, NaslValue, Statement, StatementKind::*, Token, TokenCategory,
 };
 use crate::storage::StorageError;
 
 use crate::nasl::interpreter::{
     declare::{DeclareFunctionExtension, DeclareVariableExtension},
+    hooks::{Hooks, InterpreterHook},
+    include::constant_assignment,
     InterpretError, InterpretErrorKind,
 };
 
-use crate::nasl::utils::{Context, ContextType, Register};
+use crate::nasl::utils::{include_cache::include_hash, Context, ContextType, Register};
 
 /// Is used to identify the depth of the current statement
 ///
@@ -65,6 +68,7 @@ pub struct Interpreter<'a> {
     pub(crate) run_specific: Vec<RunSpecific>,
     pub(crate) ctxconfigs: &'a Context<'a>,
     pub(crate) index: usize,
+    pub(crate) hooks: Hooks,
 }
 
 /// Interpreter always returns a NaslValue or an InterpretError
@@ -80,13 +84,23 @@ impl<'a> Interpreter<'a> {
             position: Position::new(0),
             skip_until_return: None,
         };
+        let mut hooks = Hooks::default();
+        if let Some(taint) = ctxconfigs.taint() {
+            hooks.add(taint.clone());
+        }
         Interpreter {
             run_specific: vec![root_run],
             ctxconfigs,
             index: 0,
+            hooks,
         }
     }
 
+    /// Registers a hook that is called around the execution of statements and builtin functions
+    pub fn add_hook(&mut self, hook: Arc<dyn InterpreterHook>) {
+        self.hooks.add(hook);
+    }
+
     pub(crate) fn identifier(token: &Token) -> Result<String, InterpretError> {
         match token.category() {
             TokenCategory::Identifier(IdentifierType::Undefined(x)) => Ok(x.to_owned()),
@@ -114,18 +128,6 @@ impl<'a> Interpreter<'a> {
         Some(self)
     }
 
-    async fn execute_statements<'b>(
-        &self,
-        key: &str,
-        inter: &mut Interpreter<'b>,
-        stmt: Result<Statement, SyntaxError>,
-    ) -> InterpretResult {
-        match stmt {
-            Ok(stmt) => inter.resolve(&stmt).await,
-            Err(err) => Err(InterpretError::include_syntax_error(key, err)),
-        }
-    }
-
     /// Includes a script into to the current runtime by executing it and share the register as
     /// well as DB of the current runtime.
     ///
@@ -138,10 +140,27 @@ impl<'a> Interpreter<'a> {
         match self.resolve(name).await? {
             NaslValue::String(key) => {
                 let code = self.ctxconfigs.loader().load(&key)?;
+                let hash = include_hash(&code);
+                let cache = self.ctxconfigs.include_cache();
 
                 let mut inter = Interpreter::new(self.register().clone(), self.ctxconfigs);
-                for stmt in crate::nasl::syntax::parse(&code) {
-                    self.execute_statements(&key, &mut inter, stmt).await?;
+                inter.hooks = self.hooks.clone();
+                for (index, stmt) in crate::nasl::syntax::parse(&code).enumerate() {
+                    let stmt = stmt.map_err(|e| InterpretError::include_syntax_error(&key, e))?;
+                    let Some(name) = constant_assignment(&stmt) else {
+                        inter.resolve(&stmt).await?;
+                        continue;
+                    };
+                    // constants are evaluated once per scan and shared by all including scripts
+                    match cache.get(&hash, index) {
+                        Some(value) => inter.assign_value(&name, value),
+                        None => {
+                            inter.resolve(&stmt).await?;
+                            if let Some(ContextType::Value(value)) = inter.register().named(&name) {
+                                cache.insert(hash, index, value.clone());
+                            }
+                        }
+                    }
                 }
                 self.set_register(inter.register().clone());
                 Ok(NaslValue::Null)
@@ -229,10 +248,16 @@ impl<'a> Interpreter<'a> {
             }
         }
 
-        let results = {
+        if let Err(e) = self.hooks.before_statement(statement) {
+            self.position_mut().down();
+            return Err(*e);
+        }
+
+        let mut results = {
             match statement.kind() {
                 Include(inc) => Box::pin(self.include(inc)).await,
                 Array(position) => self.resolve_array(statement, position.clone()).await,
+                Index(base, position) => self.resolve_index(base, position).await,
                 Exit(stmt) => self.resolve_exit(stmt).await,
                 Return(stmt) => self.resolve_return(stmt).await,
                 NamedParameter(..) => {
@@ -256,6 +281,7 @@ impl<'a> Interpreter<'a> {
                 }
                 Declare(stmts) => self.declare_variable(statement.as_token(), stmts),
                 Parameter(x) => self.resolve_parameter(x).await,
+                Struct(x) => self.resolve_struct(x).await,
                 Assign(cat, order, left, right) => {
                     Box::pin(self.assign(cat, order, left, right)).await
                 }
@@ -279,6 +305,7 @@ impl<'a> Interpreter<'a> {
                 }
             })
         };
+        self.hooks.after_statement(statement, &mut results);
         self.position_mut().down();
         results
     }
@@ -316,6 +343,26 @@ impl<'a> Interpreter<'a> {
         }
     }
 
+    async fn resolve_index(
+        &mut self,
+        base: &Statement,
+        position: &Statement,
+    ) -> Result<NaslValue, InterpretError> {
+        match Box::pin(self.resolve(base)).await? {
+            NaslValue::Array(x) => {
+                let position = Box::pin(self.resolve(position)).await?;
+                let position = i64::from(&position) as usize;
+                Ok(x.get(position).cloned().unwrap_or(NaslValue::Null))
+            }
+            NaslValue::Dict(x) => {
+                let position = Box::pin(self.resolve(position)).await?.to_string();
+                Ok(x.get(&position).cloned().unwrap_or(NaslValue::Null))
+            }
+            NaslValue::Null => Ok(NaslValue::Null),
+            _ => Err(InterpretError::unsupported(position, "array")),
+        }
+    }
+
     /// Returns used register
     pub fn register(&self) -> &Register {
         &self.run_specific[self.index].register
@@ -385,6 +432,18 @@ impl<'a> Interpreter<'a> {
         Ok(NaslValue::Array(result))
     }
 
+    async fn resolve_struct(&mut self, fields: &[Statement]) -> Result<NaslValue, InterpretError> {
+        let mut result = IndexMap::new();
+        for field in fields {
+            if let NamedParameter(value) = field.kind() {
+                let name = Self::identifier(field.as_token())?;
+                let value = Box::pin(self.resolve(value)).await?;
+                result.insert(name, value);
+            }
+        }
+        Ok(NaslValue::Dict(result))
+    }
+
     async fn resolve_if(
         &mut self,
         condition: &Statement,
@@ -10,6 +10,7 @@ mod assign;
 mod call;
 mod code_interpreter;
 mod declare;
+mod hooks;
 mod include;
 #[allow(clippy::module_inception)]
 mod interpreter;
@@ -23,4 +24,5 @@ pub use code_interpreter::*;
 pub use error::FunctionError;
 pub use error::InterpretError;
 pub use error::InterpretErrorKind;
+pub use hooks::InterpreterHook;
 pub use interpreter::Interpreter;
@@ -1,4 +1,4 @@
-mod builtin;
+pub(crate) mod builtin;
 pub mod interpreter;
 pub mod syntax;
 pub mod utils;
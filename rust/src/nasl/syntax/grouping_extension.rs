@@ -9,7 +9,7 @@ use super::{
     AssignOrder, Statement, StatementKind,
 };
 
-use crate::{unclosed_token, unexpected_token};
+use crate::{unclosed_token, unexpected_statement, unexpected_token};
 
 pub(crate) trait Grouping {
     /// Parses (...)
@@ -81,6 +81,23 @@ impl<'a> Grouping for Lexer<'a> {
         }
         match token.category() {
             Category::LeftParen => self.parse_paren(token).map(as_con),
+            // a block cannot start with a named parameter, therefore it is a struct literal
+            Category::LeftCurlyBracket if self.peek_field() => {
+                let (end, fields) = self.parse_comma_group(Category::RightCurlyBracket)?;
+                if let Some(field) = fields
+                    .iter()
+                    .find(|x| !matches!(x.kind(), StatementKind::NamedParameter(_)))
+                {
+                    return Err(unexpected_statement!(field.clone()));
+                }
+                match end {
+                    End::Done(end) => Ok((
+                        End::Continue,
+                        Statement::with_start_end_token(token, end, StatementKind::Struct(fields)),
+                    )),
+                    End::Continue => Err(unclosed_token!(token)),
+                }
+            }
             Category::LeftCurlyBracket => self.parse_block(token).map(as_done),
             Category::LeftBrace => {
                 let (end, right) = self.parse_comma_group(Category::RightBrace)?;
@@ -126,4 +143,20 @@ mod test {
         );
         assert!(matches!(stmt, Block(..)));
     }
+
+    #[test]
+    fn structs() {
+        let stmt = result("a = {name: \"ssh\", port: 22};");
+        match stmt {
+            Assign(_, _, _, right) => match right.kind() {
+                Struct(fields) => {
+                    assert_eq!(fields.len(), 2);
+                    assert!(matches!(fields[0].kind(), NamedParameter(..)));
+                }
+                kind => panic!("expected Struct, but got: {kind:?}"),
+            },
+            kind => panic!("expected Assign, but got: {kind:?}"),
+        }
+        assert!(parse("a = {name: 1, 2};").next().unwrap().is_err());
+    }
 }
@@ -9,7 +9,7 @@ use super::{
     error::SyntaxError,
     operation::Operation,
     prefix_extension::Prefix,
-    token::{Category, Token, Tokenizer},
+    token::{Category, IdentifierType, Token, Tokenizer},
     AssignOrder, Statement, StatementKind,
 };
 
@@ -118,6 +118,21 @@ impl<'a> Lexer<'a> {
         None
     }
 
+    /// Returns true when the next tokens are a field of a struct literal, e.g. `name:`.
+    pub(crate) fn peek_field(&self) -> bool {
+        let mut tokens = self
+            .tokenizer
+            .clone()
+            .filter(|token| token.category() != &Category::Comment);
+        matches!(
+            tokens.next().map(|x| x.category),
+            Some(Category::Identifier(IdentifierType::Undefined(_)))
+        ) && matches!(
+            tokens.next().map(|x| x.category),
+            Some(Category::DoublePoint)
+        )
+    }
+
     pub(crate) fn parse_comma_group(
         &mut self,
         category: Category,
@@ -200,12 +215,14 @@ impl<'a> Lexer<'a> {
                         Box::new(rhs),
                     ))
                 }
-                StatementKind::Array(..) => build_stmt(StatementKind::Assign(
-                    category,
-                    AssignOrder::AssignReturn,
-                    Box::new(lhs),
-                    Box::new(rhs),
-                )),
+                StatementKind::Array(..) | StatementKind::Index(..) => {
+                    build_stmt(StatementKind::Assign(
+                        category,
+                        AssignOrder::AssignReturn,
+                        Box::new(lhs),
+                        Box::new(rhs),
+                    ))
+                }
 
                 _ => build_stmt(StatementKind::Operator(
                     token.category().clone(),
@@ -315,7 +332,9 @@ impl<'a> Lexer<'a> {
                 Operation::Assign(c) if matches!(c, Category::PlusPlus | Category::MinusMinus) => {
                     let token = self.token().expect("expected token");
                     match left.kind() {
-                        StatementKind::Variable | StatementKind::Array(..) => {
+                        StatementKind::Variable
+                        | StatementKind::Array(..)
+                        | StatementKind::Index(..) => {
                             left = Statement::with_start_end_token(
                                 left.end().clone(),
                                 token,
@@ -50,6 +50,13 @@ pub trait Loader: Sync + Send {
     fn load(&self, key: &str) -> Result<String, LoadError>;
     /// Return the root plugins folder
     fn root_path(&self) -> Result<String, LoadError>;
+    /// Opens the given key for reading its bytes piece by piece
+    ///
+    /// By default the whole content is loaded at once, loaders of files read them lazily.
+    fn open(&self, key: &str) -> Result<Box<dyn io::Read + Send>, LoadError> {
+        let bytes: Vec<u8> = self.load(key)?.chars().map(|c| c as u8).collect();
+        Ok(Box::new(io::Cursor::new(bytes)))
+    }
 }
 
 /// Returns given key as BufReader
@@ -154,6 +161,10 @@ impl Loader for FSPluginLoader {
         let path = self.root.to_str().unwrap_or_default().to_string();
         Ok(path)
     }
+
+    fn open(&self, key: &str) -> Result<Box<dyn io::Read + Send>, LoadError> {
+        Ok(Box::new(self.as_bufreader(key)?))
+    }
 }
 
 impl<S> Loader for S
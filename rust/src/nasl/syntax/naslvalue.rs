@@ -4,6 +4,8 @@
 
 use std::{cmp::Ordering, collections::HashMap, fmt::Display};
 
+use indexmap::IndexMap;
+
 use crate::storage::types::Primitive;
 
 use super::{IdentifierType, Token, TokenCategory, ACT};
@@ -19,8 +21,8 @@ pub enum NaslValue {
     Number(i64),
     /// Array value
     Array(Vec<NaslValue>),
-    /// Array value
-    Dict(HashMap<String, NaslValue>),
+    /// Array value with string keys, iterated in insertion order
+    Dict(IndexMap<String, NaslValue>),
     /// Boolean value
     Boolean(bool),
     /// Attack category keyword
@@ -178,9 +180,15 @@ impl From<usize> for NaslValue {
     }
 }
 
+impl From<IndexMap<String, NaslValue>> for NaslValue {
+    fn from(x: IndexMap<String, NaslValue>) -> Self {
+        NaslValue::Dict(x)
+    }
+}
+
 impl From<HashMap<String, NaslValue>> for NaslValue {
     fn from(x: HashMap<String, NaslValue>) -> Self {
-        NaslValue::Dict(x)
+        NaslValue::Dict(x.into_iter().collect())
     }
 }
 
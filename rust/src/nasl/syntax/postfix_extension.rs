@@ -34,22 +34,26 @@ impl<'a> Postfix for Lexer<'a> {
         lhs: Statement,
     ) -> Option<Result<(End, Statement), SyntaxError>> {
         match op {
-            Operation::Assign(c) if matches!(c, Category::PlusPlus | Category::MinusMinus) => match lhs.kind() {
-                StatementKind::Variable | StatementKind::Array(..) => Some(Ok((
-                    End::Continue,
-                    Statement::with_start_end_token(
-                        lhs.end().clone(),
-                        token,
-                        StatementKind::Assign(
-                            c,
-                            AssignOrder::ReturnAssign,
-                            Box::new(lhs),
-                            Box::new(Statement::without_token(StatementKind::NoOp)),
+            Operation::Assign(c) if matches!(c, Category::PlusPlus | Category::MinusMinus) => {
+                match lhs.kind() {
+                    StatementKind::Variable
+                    | StatementKind::Array(..)
+                    | StatementKind::Index(..) => Some(Ok((
+                        End::Continue,
+                        Statement::with_start_end_token(
+                            lhs.end().clone(),
+                            token,
+                            StatementKind::Assign(
+                                c,
+                                AssignOrder::ReturnAssign,
+                                Box::new(lhs),
+                                Box::new(Statement::without_token(StatementKind::NoOp)),
+                            ),
                         ),
-                    ),
-                ))),
-                _ => Some(Err(unexpected_token!(token))),
-            },
+                    ))),
+                    _ => Some(Err(unexpected_token!(token))),
+                }
+            }
             _ => None,
         }
     }
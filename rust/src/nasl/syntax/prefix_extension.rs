@@ -43,44 +43,87 @@ impl<'a> Lexer<'a> {
             return Err(unexpected_token!(token));
         }
         use End::*;
-        let (kind, end) = {
-            if let Some(nt) = self.peek() {
-                match nt.category() {
-                    Category::LeftParen => {
-                        self.token();
-                        let (end, params) = self.parse_comma_group(Category::RightParen)?;
-                        match end {
-                            Done(end) => {
-                                let params = Statement::with_start_end_token(
-                                    nt,
-                                    end.clone(),
-                                    StatementKind::Parameter(params),
-                                );
-                                Ok((StatementKind::Call(Box::new(params)), end))
-                            }
-                            Continue => Err(unclosed_token!(nt)),
-                        }
+        let mut stmt = match self.peek() {
+            Some(nt) if nt.category() == &Category::LeftParen => {
+                self.token();
+                let (end, params) = self.parse_comma_group(Category::RightParen)?;
+                match end {
+                    Done(end) => {
+                        let params = Statement::with_start_end_token(
+                            nt,
+                            end.clone(),
+                            StatementKind::Parameter(params),
+                        );
+                        let kind = StatementKind::Call(Box::new(params));
+                        Statement::with_start_end_token(token.clone(), end, kind)
                     }
-                    Category::LeftBrace => {
-                        self.token();
-                        let (end, lookup) = self.statement(0, &|c| c == &Category::RightBrace)?;
-                        let lookup = lookup.as_returnable_or_err()?;
-                        match end {
-                            Done(end) => Ok((StatementKind::Array(Some(Box::new(lookup))), end)),
-                            Continue => Err(unclosed_token!(token.clone())),
-                        }
-                    }
-                    _ => Ok((StatementKind::Variable, token.clone())),
+                    Continue => return Err(unclosed_token!(nt)),
                 }
-            } else {
-                Ok((StatementKind::Variable, token.clone()))
             }
-        }?;
-        let stmt = Statement::with_start_end_token(token, end, kind);
-
+            _ => match self.parse_lookup(&token)? {
+                Some((lookup, end)) => {
+                    let kind = StatementKind::Array(Some(lookup));
+                    Statement::with_start_end_token(token.clone(), end, kind)
+                }
+                None => {
+                    let stmt = Statement::with_start_end_token(
+                        token.clone(),
+                        token,
+                        StatementKind::Variable,
+                    );
+                    return Ok((Continue, stmt));
+                }
+            },
+        };
+        // chained lookups like x["a"]["b"] or f()["a"] index the result of the previous one
+        while let Some((lookup, end)) = self.parse_lookup(&token)? {
+            let kind = StatementKind::Index(Box::new(stmt), lookup);
+            stmt = Statement::with_start_end_token(token.clone(), end, kind);
+        }
         Ok((Continue, stmt))
     }
 
+    /// Parses the lookup `[...]` or `.field` following the variable, None for other tokens.
+    fn parse_lookup(
+        &mut self,
+        variable: &Token,
+    ) -> Result<Option<(Box<Statement>, Token)>, SyntaxError> {
+        let Some(nt) = self.peek() else {
+            return Ok(None);
+        };
+        match nt.category() {
+            Category::LeftBrace => {
+                self.token();
+                let (end, lookup) = self.statement(0, &|c| c == &Category::RightBrace)?;
+                let lookup = lookup.as_returnable_or_err()?;
+                match end {
+                    End::Done(end) => Ok(Some((Box::new(lookup), end))),
+                    End::Continue => Err(unclosed_token!(variable.clone())),
+                }
+            }
+            // x.field is a shorthand for x["field"]
+            Category::Dot => {
+                self.token();
+                let field = self
+                    .token()
+                    .ok_or_else(|| unexpected_end!("parsing member access"))?;
+                let name = match field.category() {
+                    Category::Identifier(x) => x.to_string(),
+                    _ => return Err(unexpected_token!(field)),
+                };
+                let lookup = Statement::with_start_token(
+                    Token {
+                        category: Category::String(name),
+                        ..field.clone()
+                    },
+                    StatementKind::Primitive,
+                );
+                Ok(Some((Box::new(lookup), field)))
+            }
+            _ => Ok(None),
+        }
+    }
+
     /// Parses Operations that have an prefix (e.g. -1)
     fn parse_prefix_assign_operator(
         &mut self,
@@ -93,7 +136,7 @@ impl<'a> Lexer<'a> {
         let (_, stmt) = self.parse_variable(next)?;
         if !matches!(
             stmt.kind(),
-            StatementKind::Variable | StatementKind::Array(..)
+            StatementKind::Variable | StatementKind::Array(..) | StatementKind::Index(..)
         ) {
             return Err(unexpected_token!(token));
         }
@@ -29,6 +29,8 @@ pub enum StatementKind {
     Variable,
     /// Is a array variable, it contains the lookup token as well as an optional lookup statement
     Array(Option<Box<Statement>>),
+    /// Is a lookup in the result of an array lookup, e.g. the `["b"]` of `x["a"]["b"]`
+    Index(Box<Statement>, Box<Statement>),
     /// Is a call of a function
     Call(Box<Statement>),
     /// Special exit call
@@ -47,6 +49,8 @@ pub enum StatementKind {
     Parameter(Vec<Statement>),
     /// Named parameter on a function
     NamedParameter(Box<Statement>),
+    /// Struct literal, e.g. `{name: "a", port: 22}`, contains the fields as NamedParameter
+    Struct(Vec<Statement>),
     /// Assignment to a variable
     Assign(TokenCategory, AssignOrder, Box<Statement>, Box<Statement>),
     /// An Operator (e.g. +, -, *)
@@ -139,6 +143,7 @@ impl Statement {
             StatementKind::Block(x)
             | StatementKind::Operator(_, x)
             | StatementKind::Parameter(x)
+            | StatementKind::Struct(x)
             | StatementKind::Declare(x) => {
                 for stmt in x {
                     results.extend(stmt.as_tokens());
@@ -146,6 +151,7 @@ impl Statement {
             }
             StatementKind::While(x, y)
             | StatementKind::Repeat(x, y)
+            | StatementKind::Index(x, y)
             | StatementKind::Assign(_, _, x, y) => {
                 results.extend(x.as_tokens());
                 results.extend(y.as_tokens());
@@ -202,6 +208,7 @@ impl Statement {
             | StatementKind::Return(_)
             | StatementKind::Include(_)
             | StatementKind::Array(_)
+            | StatementKind::Index(..)
             | StatementKind::Primitive
             | StatementKind::AttackCategory
             | StatementKind::Variable
@@ -216,6 +223,7 @@ impl Statement {
             StatementKind::Block(x)
             | StatementKind::Operator(_, x)
             | StatementKind::Parameter(x)
+            | StatementKind::Struct(x)
             | StatementKind::Declare(x) => x,
         }
     }
@@ -294,6 +302,7 @@ impl Statement {
                 StatementKind::Block(x)
                 | StatementKind::Operator(_, x)
                 | StatementKind::Parameter(x)
+                | StatementKind::Struct(x)
                 | StatementKind::Declare(x) => {
                     for stmt in x {
                         results.extend(Self::find(stmt, wanted));
@@ -301,6 +310,7 @@ impl Statement {
                 }
                 StatementKind::While(x, y)
                 | StatementKind::Repeat(x, y)
+                | StatementKind::Index(x, y)
                 | StatementKind::Assign(_, _, x, y) => {
                     results.extend(Self::find(x, wanted));
                     results.extend(Self::find(y, wanted));
@@ -401,6 +411,7 @@ impl std::fmt::Display for Statement {
                 }
                 None => write!(f, "{}", x.category()),
             },
+            StatementKind::Index(base, lookup) => write!(f, "{base}[{lookup}]"),
             StatementKind::Call(args) => {
                 write!(f, "{}{};", x.category(), args)
             }
@@ -412,6 +423,7 @@ impl std::fmt::Display for Statement {
             }
             StatementKind::Parameter(x) => write!(f, "({})", as_str_list(x),),
             StatementKind::NamedParameter(s) => write!(f, "{}: {s}", x.category()),
+            StatementKind::Struct(x) => write!(f, "{{{}}}", as_str_list(x)),
             StatementKind::Assign(c, o, l, r) => match (o, r.kind().clone()) {
                 (AssignOrder::AssignReturn, StatementKind::NoOp) => write!(f, "{c}{l}"),
                 (AssignOrder::ReturnAssign, StatementKind::NoOp) => write!(f, "{l}{c}"),
@@ -467,6 +479,7 @@ impl StatementKind {
                     _
                 )
                 | StatementKind::Array(..)
+                | StatementKind::Index(..)
                 | StatementKind::Operator(..)
         )
     }
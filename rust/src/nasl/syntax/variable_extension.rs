@@ -37,6 +37,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn member_access() {
+        match result("a.port;").kind() {
+            Array(Some(lookup)) => {
+                assert_eq!(
+                    lookup.as_token().category(),
+                    &TokenCategory::String("port".to_owned())
+                )
+            }
+            kind => panic!("expected Array, but got: {kind:?}"),
+        }
+        assert!(matches!(
+            result("a.port = 22;").kind(),
+            Assign(TokenCategory::Equal, AssignOrder::AssignReturn, _, _)
+        ));
+        assert!(parse("a.1;").next().unwrap().is_err());
+    }
+
     #[test]
     fn anon_function_call() {
         assert!(matches!(result("a(1, 2, 3);").kind(), &Call(..)))
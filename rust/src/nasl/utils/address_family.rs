@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Preferred address family of dual-stack targets.
+//!
+//! When a hostname resolves to IPv4 and IPv6 addresses, connections are raced as described in
+//! RFC 8305 (Happy Eyeballs). The preferred family is tried first and configured via the scan
+//! preference [ADDRESS_FAMILY_PREFERENCE].
+
+use std::{net::IpAddr, str::FromStr};
+
+use crate::models::ScanPreference;
+
+/// Scan preference containing the preferred address family, either `ipv6` or `ipv4`.
+pub const ADDRESS_FAMILY_PREFERENCE: &str = "address_family_preference";
+
+/// Address family that is tried first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Prefer IPv6 as recommended by RFC 8305
+    #[default]
+    Ipv6,
+    /// Prefer IPv4
+    Ipv4,
+}
+
+impl FromStr for AddressFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ipv6" | "6" => Ok(Self::Ipv6),
+            "ipv4" | "4" => Ok(Self::Ipv4),
+            _ => Err(format!("unknown address family {s}")),
+        }
+    }
+}
+
+impl AddressFamily {
+    /// Returns the address family based on the scan preferences.
+    ///
+    /// An invalid value is ignored.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        preferences
+            .iter()
+            .find(|x| x.id == ADDRESS_FAMILY_PREFERENCE)
+            .and_then(|x| match x.value.parse() {
+                Ok(family) => Some(family),
+                Err(e) => {
+                    tracing::warn!(%e, "ignoring invalid address family preference");
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn matches(&self, addr: &IpAddr) -> bool {
+        match self {
+            Self::Ipv6 => addr.is_ipv6(),
+            Self::Ipv4 => addr.is_ipv4(),
+        }
+    }
+
+    /// Orders addresses for connection attempts as described in RFC 8305 section 4.
+    ///
+    /// The families are interleaved, starting with the preferred one. The order within a family
+    /// is kept.
+    pub fn sort(&self, addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+        let (preferred, other): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|x| self.matches(x));
+        let mut result = Vec::with_capacity(preferred.len() + other.len());
+        let mut preferred = preferred.into_iter();
+        let mut other = other.into_iter();
+        loop {
+            match (preferred.next(), other.next()) {
+                (None, None) => break,
+                (a, b) => result.extend(a.into_iter().chain(b)),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::AddressFamily;
+
+    #[test]
+    fn sort() {
+        let addrs: Vec<IpAddr> = ["192.0.2.1", "192.0.2.2", "2001:db8::1", "192.0.2.3"]
+            .iter()
+            .map(|x| x.parse().unwrap())
+            .collect();
+        let sorted = |family: AddressFamily| {
+            family
+                .sort(addrs.clone())
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sorted(AddressFamily::Ipv6),
+            ["2001:db8::1", "192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+        assert_eq!(
+            sorted(AddressFamily::Ipv4),
+            ["192.0.2.1", "2001:db8::1", "192.0.2.2", "192.0.2.3"]
+        );
+    }
+}
@@ -4,10 +4,17 @@
 
 //! Defines the context used within the interpreter and utilized by the builtin functions
 
+use std::sync::Arc;
+
 use crate::nasl::syntax::{Loader, NaslValue, Statement};
 use crate::storage::{ContextKey, Dispatcher, Retriever};
 
-use super::{executor::Executor, lookup_keys::FC_ANON_ARGS};
+use super::{
+    address_family::AddressFamily, dns_cache::DnsCache, executor::Executor,
+    include_cache::IncludeCache, lookup_keys::FC_ANON_ARGS, network_timeout::NetworkTimeout,
+    proxy::Proxy, random::RandomSource, script_stats::ScriptStats, source_binding::SourceBinding,
+    taint::TaintTracker, temp_dir::TempDir, traffic::Traffic,
+};
 
 /// Contexts are responsible to locate, add and delete everything that is declared within a NASL plugin
 
@@ -345,6 +352,28 @@ pub struct Context<'a> {
     loader: &'a dyn Loader,
     /// Default function executor.
     executor: &'a Executor,
+    /// Cache for DNS lookups, shared by all scripts of a scan
+    dns_cache: DnsCache,
+    /// Cache of constants assigned by included files, shared by all scripts of a scan
+    include_cache: IncludeCache,
+    /// Interface and source address outgoing connections are bound to
+    source_binding: SourceBinding,
+    /// Proxy outgoing connections are routed through
+    proxy: Option<Proxy>,
+    /// Address family tried first when connecting to dual-stack hosts
+    address_family: AddressFamily,
+    /// Timeout of connection attempts and probes, shared by all scripts of a scan
+    network_timeout: NetworkTimeout,
+    /// Runtime statistics of the script, only collected when they are reported
+    script_stats: Option<ScriptStats>,
+    /// Taint tracking of network data, registered as hook on interpreters using the context
+    taint: Option<Arc<TaintTracker>>,
+    /// Source of random bytes
+    random: RandomSource,
+    /// Recording or replay of the network traffic of sockets
+    traffic: Option<Traffic>,
+    /// Directory for files written by the script, shared by all scripts of a scan
+    temp_dir: TempDir,
 }
 
 impl<'a> Context<'a> {
@@ -364,9 +393,86 @@ impl<'a> Context<'a> {
             retriever,
             loader,
             executor,
+            dns_cache: DnsCache::default(),
+            include_cache: IncludeCache::default(),
+            source_binding: SourceBinding::default(),
+            proxy: None,
+            address_family: AddressFamily::default(),
+            network_timeout: NetworkTimeout::default(),
+            script_stats: None,
+            taint: None,
+            random: RandomSource::default(),
+            traffic: None,
+            temp_dir: TempDir::default(),
         }
     }
 
+    /// Sets the DNS cache, so that lookups are shared with other contexts using the same cache.
+    pub fn with_dns_cache(mut self, dns_cache: DnsCache) -> Self {
+        self.dns_cache = dns_cache;
+        self
+    }
+
+    /// Sets the cache of include-level constants, so that they are shared with other contexts.
+    pub fn with_include_cache(mut self, include_cache: IncludeCache) -> Self {
+        self.include_cache = include_cache;
+        self
+    }
+
+    /// Sets the interface and source address outgoing connections are bound to.
+    pub fn with_source_binding(mut self, source_binding: SourceBinding) -> Self {
+        self.source_binding = source_binding;
+        self
+    }
+
+    /// Sets the proxy outgoing connections are routed through.
+    pub fn with_proxy(mut self, proxy: Option<Proxy>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Sets the address family tried first when connecting to dual-stack hosts.
+    pub fn with_address_family(mut self, address_family: AddressFamily) -> Self {
+        self.address_family = address_family;
+        self
+    }
+
+    /// Sets the timeout policy, so that observed round trip times are shared with other contexts.
+    pub fn with_network_timeout(mut self, network_timeout: NetworkTimeout) -> Self {
+        self.network_timeout = network_timeout;
+        self
+    }
+
+    /// Enables the collection of runtime statistics that are attached to the results.
+    pub fn with_script_stats(mut self, enabled: bool) -> Self {
+        self.script_stats = enabled.then(ScriptStats::default);
+        self
+    }
+
+    /// Enables the taint tracking of data received from the network.
+    pub fn with_taint_tracking(mut self, enabled: bool) -> Self {
+        self.taint = enabled.then(Arc::default);
+        self
+    }
+
+    /// Sets the source of random bytes, e.g. a deterministic one for reproducible runs.
+    pub fn with_random(mut self, random: RandomSource) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// Records the network traffic of sockets or replays a recording instead of connecting.
+    pub fn with_traffic(mut self, traffic: Option<Traffic>) -> Self {
+        self.traffic = traffic;
+        self
+    }
+
+    /// Sets the temporary directory, so that files are shared with other contexts of the scan.
+    pub fn with_temp_dir(mut self, temp_dir: TempDir) -> Self {
+        self.temp_dir = temp_dir;
+        self
+    }
+
     /// Executes a function by name
     ///
     /// Returns None when the function was not found.
@@ -412,6 +518,61 @@ impl<'a> Context<'a> {
     pub fn loader(&self) -> &dyn Loader {
         self.loader
     }
+
+    /// Get the DNS cache
+    pub fn dns_cache(&self) -> &DnsCache {
+        &self.dns_cache
+    }
+
+    /// Get the cache of include-level constants
+    pub fn include_cache(&self) -> &IncludeCache {
+        &self.include_cache
+    }
+
+    /// Get the source binding
+    pub fn source_binding(&self) -> &SourceBinding {
+        &self.source_binding
+    }
+
+    /// Get the proxy when one is configured
+    pub fn proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref()
+    }
+
+    /// Get the preferred address family
+    pub fn address_family(&self) -> AddressFamily {
+        self.address_family
+    }
+
+    /// Get the timeout policy of connection attempts and probes
+    pub fn network_timeout(&self) -> &NetworkTimeout {
+        &self.network_timeout
+    }
+
+    /// Get the runtime statistics when they are collected
+    pub fn script_stats(&self) -> Option<&ScriptStats> {
+        self.script_stats.as_ref()
+    }
+
+    /// Get the taint tracker when taint tracking is enabled
+    pub fn taint(&self) -> Option<&Arc<TaintTracker>> {
+        self.taint.as_ref()
+    }
+
+    /// Get the source of random bytes
+    pub fn random(&self) -> &RandomSource {
+        &self.random
+    }
+
+    /// Get the recording or replay of the network traffic
+    pub fn traffic(&self) -> Option<&Traffic> {
+        self.traffic.as_ref()
+    }
+
+    /// Get the temporary directory of the scan
+    pub fn temp_dir(&self) -> &TempDir {
+        &self.temp_dir
+    }
 }
 
 impl From<&ContextType> for NaslValue {
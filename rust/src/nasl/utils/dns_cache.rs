@@ -0,0 +1,239 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Caches forward and reverse DNS lookups.
+//!
+//! A single [DnsCache] is shared by all scripts of a scan, so that resolving the same name
+//! thousands of times does not hammer the resolver and all scripts see the same answer. The
+//! lookups are done by the [Resolver] of the scan and expire with the TTL of the records, or for
+//! names that do not exist with the negative caching time of the zone.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    sync::{Arc, RwLock, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
+
+use super::{resolver::Lookup, Resolver};
+
+/// Time to live of a successful lookup without a TTL.
+///
+/// The system resolver does not report the TTL of the records it returns, therefore its
+/// answers are kept for a fixed amount of time.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+/// Time to live of a failed lookup without a TTL.
+pub const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    /// None when the lookup failed
+    value: Option<T>,
+    expires: Instant,
+}
+
+#[derive(Debug)]
+struct Entries<K, V> {
+    entries: HashMap<K, Entry<V>>,
+}
+
+impl<K, V> Default for Entries<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K, V> Entries<K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    fn get(&self, key: &K, now: Instant) -> Option<Option<V>> {
+        self.entries
+            .get(key)
+            .filter(|e| e.expires > now)
+            .map(|e| e.value.clone())
+    }
+
+    /// Stores the value unless another lookup already stored a valid one.
+    ///
+    /// Returns the cached value, so that concurrent lookups get consistent answers.
+    fn insert(&mut self, key: K, value: Option<V>, ttl: Duration, now: Instant) -> Option<V> {
+        self.entries.retain(|_, e| e.expires > now);
+        self.entries
+            .entry(key)
+            .or_insert(Entry {
+                value,
+                expires: now + ttl,
+            })
+            .value
+            .clone()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    forward: Entries<String, Vec<IpAddr>>,
+    reverse: Entries<IpAddr, String>,
+}
+
+/// Cache for DNS lookups
+///
+/// Cloning a DnsCache is cheap and the clone shares its entries with the original.
+#[derive(Debug, Clone)]
+pub struct DnsCache {
+    inner: Arc<RwLock<Inner>>,
+    resolver: Arc<Resolver>,
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL, DEFAULT_NEGATIVE_TTL)
+    }
+}
+
+impl DnsCache {
+    /// Creates a new DnsCache with the given time to live for successful and failed lookups
+    /// whose resolver does not report one.
+    pub fn new(ttl: Duration, negative_ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Inner::default())),
+            resolver: Arc::new(Resolver::default()),
+            ttl,
+            negative_ttl,
+        }
+    }
+
+    /// Sets the resolver used for lookups, the system resolver by default.
+    pub fn with_resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Returns the resolver used for lookups.
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    fn ttl<T>(&self, lookup: &Lookup<T>) -> Duration {
+        match (lookup.ttl, &lookup.value) {
+            (Some(ttl), _) => ttl,
+            (None, Some(_)) => self.ttl,
+            (None, None) => self.negative_ttl,
+        }
+    }
+
+    /// Locks the entries for writing.
+    ///
+    /// A panic while the lock was held leaves the entries in an unknown state. As they are only
+    /// a cache, they are discarded instead of passing the panic on to all scripts of the scan.
+    fn write(&self) -> RwLockWriteGuard<'_, Inner> {
+        self.inner.write().unwrap_or_else(|poisoned| {
+            self.inner.clear_poison();
+            let mut inner = poisoned.into_inner();
+            *inner = Inner::default();
+            inner
+        })
+    }
+
+    /// Returns the IP addresses of the given hostname or None if it cannot be resolved.
+    pub fn lookup_host(&self, hostname: &str) -> Option<Vec<IpAddr>> {
+        let key = hostname.trim_end_matches('.').to_lowercase();
+        let now = Instant::now();
+        // a poisoned lock is a cache miss, the following write discards the entries
+        let cached = self
+            .inner
+            .read()
+            .ok()
+            .and_then(|x| x.forward.get(&key, now));
+        if let Some(cached) = cached {
+            return cached;
+        }
+        let mut lookup = self.resolver.lookup_host(&key);
+        lookup.value = lookup.value.filter(|ips| !ips.is_empty());
+        let ttl = self.ttl(&lookup);
+        self.write().forward.insert(key, lookup.value, ttl, now)
+    }
+
+    /// Returns the hostname of the given IP address or None if it cannot be resolved.
+    pub fn lookup_addr(&self, ip: &IpAddr) -> Option<String> {
+        let now = Instant::now();
+        let cached = self.inner.read().ok().and_then(|x| x.reverse.get(ip, now));
+        if let Some(cached) = cached {
+            return cached;
+        }
+        let lookup = self.resolver.lookup_addr(ip);
+        let ttl = self.ttl(&lookup);
+        self.write().reverse.insert(*ip, lookup.value, ttl, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, time::Duration};
+
+    use super::{DnsCache, Lookup};
+
+    #[test]
+    fn shares_entries() {
+        let cache = DnsCache::default();
+        let clone = cache.clone();
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(cache.lookup_host("127.0.0.1"), Some(vec![ip]));
+        assert_eq!(clone.inner.read().unwrap().forward.entries.len(), 1);
+        assert_eq!(clone.lookup_host("127.0.0.1."), Some(vec![ip]));
+        assert_eq!(cache.inner.read().unwrap().forward.entries.len(), 1);
+    }
+
+    #[test]
+    fn expires() {
+        let cache = DnsCache::new(Duration::ZERO, Duration::ZERO);
+        cache.lookup_host("127.0.0.1");
+        cache.lookup_host("::1");
+        assert_eq!(cache.inner.read().unwrap().forward.entries.len(), 1);
+    }
+
+    #[test]
+    fn poisoned_lock_discards_entries() {
+        let cache = DnsCache::default();
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        cache.lookup_host("127.0.0.1");
+        let clone = cache.clone();
+        std::thread::spawn(move || {
+            let _guard = clone.inner.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join()
+        .unwrap_err();
+        assert!(cache.inner.is_poisoned());
+        assert_eq!(cache.lookup_host("::1"), Some(vec!["::1".parse().unwrap()]));
+        assert!(!cache.inner.is_poisoned());
+        assert_eq!(cache.inner.read().unwrap().forward.entries.len(), 1);
+        assert_eq!(cache.lookup_host("127.0.0.1"), Some(vec![ip]));
+    }
+
+    #[test]
+    fn record_ttls() {
+        let cache = DnsCache::new(Duration::from_secs(300), Duration::from_secs(30));
+        let lookup = |value: Option<u8>, ttl: Option<u64>| Lookup {
+            value,
+            ttl: ttl.map(Duration::from_secs),
+        };
+        assert_eq!(
+            cache.ttl(&lookup(Some(1), Some(60))),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            cache.ttl(&lookup(None, Some(900))),
+            Duration::from_secs(900)
+        );
+        assert_eq!(cache.ttl(&lookup(Some(1), None)), Duration::from_secs(300));
+        assert_eq!(cache.ttl(&lookup(None, None)), Duration::from_secs(30));
+    }
+}
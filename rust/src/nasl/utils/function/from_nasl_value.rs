@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use indexmap::IndexMap;
+
 use crate::nasl::prelude::*;
 
 /// A type that can be converted from a NaslValue.
@@ -82,6 +84,20 @@ impl<'a, T: FromNaslValue<'a>> FromNaslValue<'a> for HashMap<String, T> {
     }
 }
 
+impl<'a, T: FromNaslValue<'a>> FromNaslValue<'a> for IndexMap<String, T> {
+    fn from_nasl_value(value: &'a NaslValue) -> Result<Self, FunctionErrorKind> {
+        match value {
+            NaslValue::Dict(map) => Ok(map
+                .iter()
+                .map(|(k, v)| T::from_nasl_value(v).map(|v| (k.clone(), v)))
+                .collect::<Result<IndexMap<_, _>, _>>()?),
+            _ => Err(FunctionErrorKind::WrongArgument(
+                "Expected a dictionary.".to_string(),
+            )),
+        }
+    }
+}
+
 impl<'a> FromNaslValue<'a> for bool {
     fn from_nasl_value(value: &'a NaslValue) -> Result<Self, FunctionErrorKind> {
         match value {
@@ -112,6 +128,7 @@ macro_rules! impl_from_nasl_value_for_numeric_type {
 }
 
 impl_from_nasl_value_for_numeric_type!(u8);
+impl_from_nasl_value_for_numeric_type!(u16);
 impl_from_nasl_value_for_numeric_type!(i32);
 impl_from_nasl_value_for_numeric_type!(i64);
 impl_from_nasl_value_for_numeric_type!(u32);
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use indexmap::IndexMap;
+
 use crate::nasl::syntax::NaslValue;
 use crate::nasl::{FunctionErrorKind, NaslResult};
 
@@ -93,7 +95,17 @@ impl<T: ToNaslResult> ToNaslResult for HashMap<String, T> {
         Ok(NaslValue::Dict(
             self.into_iter()
                 .map(|(key, s)| s.to_nasl_result().map(|res| (key, res)))
-                .collect::<Result<HashMap<_, _>, FunctionErrorKind>>()?,
+                .collect::<Result<IndexMap<_, _>, FunctionErrorKind>>()?,
+        ))
+    }
+}
+
+impl<T: ToNaslResult> ToNaslResult for IndexMap<String, T> {
+    fn to_nasl_result(self) -> NaslResult {
+        Ok(NaslValue::Dict(
+            self.into_iter()
+                .map(|(key, s)| s.to_nasl_result().map(|res| (key, res)))
+                .collect::<Result<IndexMap<_, _>, FunctionErrorKind>>()?,
         ))
     }
 }
@@ -0,0 +1,293 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Tunnels the TCP connections of socket builtins through an HTTP proxy.
+//!
+//! In networks where only the proxy may connect to the outside, targets are scanned by
+//! configuring it with the scan preference [PROXY_PREFERENCE] and, when the proxy requires
+//! authentication, [USERNAME_PREFERENCE] and [PASSWORD_PREFERENCE]. Connections are established
+//! with the CONNECT method as described in RFC 9110, credentials are sent with the Basic scheme.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::models::ScanPreference;
+
+use super::{
+    proxy::{connect_bound, parse_address, preference},
+    SourceBinding,
+};
+
+/// Scan preference containing the address of the proxy as `host:port`.
+pub const PROXY_PREFERENCE: &str = "http_proxy";
+/// Scan preference containing the username to authenticate at the proxy.
+pub const USERNAME_PREFERENCE: &str = "http_proxy_username";
+/// Scan preference containing the password to authenticate at the proxy.
+pub const PASSWORD_PREFERENCE: &str = "http_proxy_password";
+
+/// Port of the proxy when the preference contains only a host
+pub const DEFAULT_PORT: u16 = 3128;
+/// Maximum length of the response header of the proxy
+const MAX_RESPONSE_HEADER: usize = 8192;
+
+/// HTTP proxy outgoing TCP connections are tunneled through
+#[derive(Clone, PartialEq, Eq)]
+pub struct HttpProxy {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl fmt::Debug for HttpProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the password must not end up in logs
+        f.debug_struct("HttpProxy")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.credentials.as_ref().map(|(x, _)| x))
+            .finish()
+    }
+}
+
+impl HttpProxy {
+    /// Creates a new HttpProxy.
+    pub fn new(host: impl Into<String>, port: u16, credentials: Option<(String, String)>) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            credentials,
+        }
+    }
+
+    /// Creates an HttpProxy based on the scan preferences.
+    ///
+    /// Returns None when no proxy is configured. The address may be given as URL with the
+    /// scheme http, the host may be an IPv6 address in brackets, without a port [DEFAULT_PORT]
+    /// is used.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Option<Self> {
+        let address = preference(preferences, PROXY_PREFERENCE)?;
+        let address = address
+            .strip_prefix("http://")
+            .unwrap_or(address)
+            .trim_end_matches('/');
+        let (host, port) = parse_address(address, DEFAULT_PORT);
+        let credentials = preference(preferences, USERNAME_PREFERENCE).map(|username| {
+            (
+                username.to_string(),
+                preference(preferences, PASSWORD_PREFERENCE)
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        });
+        Some(Self::new(host, port, credentials))
+    }
+
+    /// Returns the host of the proxy
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the port of the proxy
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Connects to the target through the proxy, waiting at most for the timeout for each step.
+    pub fn connect(
+        &self,
+        target: &SocketAddr,
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<TcpStream> {
+        let mut tcp = connect_bound(&self.host, self.port, timeout, binding)?;
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some((username, password)) = &self.credentials {
+            let token = STANDARD.encode(format!("{username}:{password}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+        request.push_str("\r\n");
+        tcp.write_all(request.as_bytes())?;
+        let status = read_status(&mut tcp)?;
+        if !(200..300).contains(&status) {
+            return Err(status_error(status));
+        }
+        tcp.set_read_timeout(None)?;
+        tcp.set_write_timeout(None)?;
+        Ok(tcp)
+    }
+}
+
+/// Reads the response header of the proxy and returns its status code.
+///
+/// The header is read byte by byte, so that data the target sends right away stays in the
+/// stream.
+fn read_status(tcp: &mut TcpStream) -> io::Result<u16> {
+    let mut header = vec![];
+    let mut byte = [0; 1];
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_RESPONSE_HEADER {
+            return Err(protocol_error("response header too long"));
+        }
+        tcp.read_exact(&mut byte)?;
+        header.push(byte[0]);
+    }
+    let header = String::from_utf8_lossy(&header);
+    let status_line = header.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().collect::<Vec<_>>()[..] {
+        [version, status, ..] if version.starts_with("HTTP/") => status
+            .parse()
+            .map_err(|_| protocol_error("invalid status code")),
+        _ => Err(protocol_error("invalid status line")),
+    }
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("HTTP proxy protocol error: {msg}"),
+    )
+}
+
+fn status_error(status: u16) -> io::Error {
+    let (kind, msg) = match status {
+        407 => (
+            io::ErrorKind::PermissionDenied,
+            "authentication required or credentials rejected",
+        ),
+        403 => (io::ErrorKind::PermissionDenied, "connection not allowed"),
+        502 | 503 => (io::ErrorKind::ConnectionRefused, "unable to connect"),
+        504 => (io::ErrorKind::TimedOut, "connection timed out"),
+        _ => (io::ErrorKind::Other, "unexpected status"),
+    };
+    io::Error::new(kind, format!("HTTP proxy: {msg} ({status})"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    use crate::{models::ScanPreference, nasl::utils::SourceBinding};
+
+    use super::{HttpProxy, PASSWORD_PREFERENCE, PROXY_PREFERENCE, USERNAME_PREFERENCE};
+
+    fn handle(mut tcp: TcpStream, credentials: Option<(&str, &str)>) {
+        let mut header = vec![];
+        let mut byte = [0; 1];
+        while !header.ends_with(b"\r\n\r\n") {
+            tcp.read_exact(&mut byte).unwrap();
+            header.push(byte[0]);
+        }
+        let header = String::from_utf8(header).unwrap();
+        let target = header.split_whitespace().nth(1).unwrap().to_string();
+        if let Some((username, password)) = credentials {
+            let expected = format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                STANDARD.encode(format!("{username}:{password}"))
+            );
+            if !header.contains(&expected) {
+                tcp.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"proxy\"\r\nContent-Length: 0\r\n\r\n").unwrap();
+                return;
+            }
+        }
+        let Ok(remote) = TcpStream::connect(target) else {
+            tcp.write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            return;
+        };
+        tcp.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .unwrap();
+        let (mut a, mut b) = (tcp.try_clone().unwrap(), remote.try_clone().unwrap());
+        thread::spawn(move || std::io::copy(&mut a, &mut b));
+        let (mut a, mut b) = (remote, tcp);
+        let _ = std::io::copy(&mut a, &mut b);
+    }
+
+    /// Starts an HTTP proxy on localhost supporting CONNECT.
+    pub fn http_proxy_server(credentials: Option<(&'static str, &'static str)>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for tcp in listener.incoming() {
+                let tcp = tcp.unwrap();
+                thread::spawn(move || handle(tcp, credentials));
+            }
+        });
+        addr
+    }
+
+    fn preference(id: &str, value: &str) -> ScanPreference {
+        ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn from_preferences() {
+        assert_eq!(HttpProxy::from_preferences(&[]), None);
+        let proxy = HttpProxy::from_preferences(&[preference(
+            PROXY_PREFERENCE,
+            "http://proxy.example.com:8080/",
+        )])
+        .unwrap();
+        assert_eq!((proxy.host(), proxy.port()), ("proxy.example.com", 8080));
+        let proxy = HttpProxy::from_preferences(&[
+            preference(PROXY_PREFERENCE, "[2001:db8::1]"),
+            preference(USERNAME_PREFERENCE, "user"),
+            preference(PASSWORD_PREFERENCE, "secret"),
+        ])
+        .unwrap();
+        assert_eq!(
+            proxy,
+            HttpProxy::new(
+                "2001:db8::1",
+                3128,
+                Some(("user".to_string(), "secret".to_string()))
+            )
+        );
+        assert!(!format!("{proxy:?}").contains("secret"));
+    }
+
+    #[test]
+    fn connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            // sent right away, before the client sends anything
+            tcp.write_all(b"hello").unwrap();
+        });
+        let server = http_proxy_server(Some(("user", "secret")));
+        let timeout = Duration::from_secs(5);
+        let binding = SourceBinding::default();
+        let proxy = |password: &str| {
+            HttpProxy::new(
+                "127.0.0.1",
+                server.port(),
+                Some(("user".to_string(), password.to_string())),
+            )
+        };
+        let error = proxy("wrong")
+            .connect(&target, timeout, &binding)
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        let mut tcp = proxy("secret").connect(&target, timeout, &binding).unwrap();
+        let mut buf = [0; 5];
+        tcp.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+}
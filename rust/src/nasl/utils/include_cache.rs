@@ -0,0 +1,56 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Caches constants assigned at the top level of included files.
+//!
+//! Many inc files build large constant arrays, e.g. `known_versions = make_list(...)`, when
+//! they are included. A single [IncludeCache] is shared by all scripts of a scan, so that such
+//! an array is evaluated once per scan instead of once per including script. Entries are keyed
+//! by the hash of the included code and the index of the statement, so that a changed file is
+//! never served a stale value.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::nasl::syntax::NaslValue;
+
+/// Hash of the code of an included file
+pub type IncludeHash = [u8; 32];
+
+/// Returns the hash identifying the code of an included file.
+pub fn include_hash(code: &str) -> IncludeHash {
+    Sha256::digest(code.as_bytes()).into()
+}
+
+/// Evaluated include-level constants, shared by all scripts of a scan
+#[derive(Debug, Clone, Default)]
+pub struct IncludeCache {
+    values: Arc<RwLock<HashMap<(IncludeHash, usize), NaslValue>>>,
+}
+
+impl IncludeCache {
+    /// Returns the value assigned by the statement at index of the included code.
+    pub fn get(&self, hash: &IncludeHash, index: usize) -> Option<NaslValue> {
+        self.values.read().unwrap().get(&(*hash, index)).cloned()
+    }
+
+    /// Stores the value assigned by the statement at index of the included code.
+    pub fn insert(&self, hash: IncludeHash, index: usize, value: NaslValue) {
+        self.values.write().unwrap().insert((hash, index), value);
+    }
+
+    /// Returns the number of cached values
+    pub fn len(&self) -> usize {
+        self.values.read().unwrap().len()
+    }
+
+    /// Returns true when nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -3,16 +3,44 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 #![doc = include_str!("README.md")]
+pub mod address_family;
 pub mod context;
+pub mod dns_cache;
 pub mod error;
 mod executor;
 pub mod function;
+pub mod http_proxy;
+pub mod include_cache;
 pub mod lookup_keys;
+pub mod network_timeout;
+pub mod proxy;
+pub mod random;
+pub mod resolver;
+pub mod script_stats;
+pub mod socks_proxy;
+pub mod source_binding;
+pub mod taint;
+pub mod temp_dir;
+pub mod traffic;
 
 use std::collections::HashMap;
 
+pub use address_family::AddressFamily;
 pub use context::{Context, ContextType, Register};
+pub use dns_cache::DnsCache;
 pub use error::FunctionErrorKind;
+pub use http_proxy::HttpProxy;
+pub use include_cache::IncludeCache;
+pub use network_timeout::NetworkTimeout;
+pub use proxy::{Proxy, SECRET_PREFERENCES};
+pub use random::RandomSource;
+pub use resolver::Resolver;
+pub use script_stats::ScriptStats;
+pub use socks_proxy::SocksProxy;
+pub use source_binding::SourceBinding;
+pub use taint::TaintTracker;
+pub use temp_dir::TempDir;
+pub use traffic::Traffic;
 
 pub use executor::{Executor, IntoFunctionSet, StoredFunctionSet};
 
@@ -0,0 +1,190 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Scan-wide timeout of connection attempts and probes.
+//!
+//! Builtins waiting for a host to answer, e.g. when connecting or sending a probe, ask the
+//! [NetworkTimeout] of the scan for the time to wait unless the script passes a timeout. The
+//! timeout is configured by the scan preference [NETWORK_TIMEOUT_PREFERENCE] and replaces the
+//! default of the builtin.
+//!
+//! With [ADAPTIVE_TIMEOUT_PREFERENCE] enabled the round trip times observed for each host are
+//! tracked as described in RFC 6298. Once enough samples are collected, the timeout for the host
+//! is shortened to the retransmission timeout, so that waiting for filtered ports of a fast host
+//! does not take the full configured time. The timeout is never raised above the configured one.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::models::ScanPreference;
+
+/// Scan preference containing the timeout in seconds
+pub const NETWORK_TIMEOUT_PREFERENCE: &str = "network_timeout";
+/// Scan preference enabling the adaptive timeout
+pub const ADAPTIVE_TIMEOUT_PREFERENCE: &str = "adaptive_timeout";
+
+/// Lower bound of an adaptive timeout
+pub const MIN_ADAPTIVE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Number of round trip times observed before a timeout is shortened
+const MIN_SAMPLES: u32 = 3;
+
+/// Smoothed round trip time of a host as described in RFC 6298
+#[derive(Debug, Clone, Copy)]
+struct RoundTrip {
+    srtt: Duration,
+    rttvar: Duration,
+    samples: u32,
+}
+
+impl RoundTrip {
+    fn new(rtt: Duration) -> Self {
+        Self {
+            srtt: rtt,
+            rttvar: rtt / 2,
+            samples: 1,
+        }
+    }
+
+    fn update(&mut self, rtt: Duration) {
+        let delta = self.srtt.abs_diff(rtt);
+        self.rttvar = (self.rttvar * 3 + delta) / 4;
+        self.srtt = (self.srtt * 7 + rtt) / 8;
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// Returns the retransmission timeout or None when too few samples were observed.
+    fn timeout(&self) -> Option<Duration> {
+        (self.samples >= MIN_SAMPLES)
+            .then(|| (self.srtt + self.rttvar * 4).max(MIN_ADAPTIVE_TIMEOUT))
+    }
+}
+
+/// Timeout policy shared by all scripts of a scan
+#[derive(Debug, Clone, Default)]
+pub struct NetworkTimeout {
+    /// Configured timeout, the default of the builtin is used when not set
+    timeout: Option<Duration>,
+    /// Observed round trip times per host, only tracked in adaptive mode
+    round_trips: Option<Arc<RwLock<HashMap<IpAddr, RoundTrip>>>>,
+}
+
+impl NetworkTimeout {
+    /// Creates a policy with the given timeout, optionally adapted to the observed round trips.
+    pub fn new(timeout: Option<Duration>, adaptive: bool) -> Self {
+        Self {
+            timeout,
+            round_trips: adaptive.then(Arc::default),
+        }
+    }
+
+    /// Returns the policy based on the scan preferences.
+    ///
+    /// A timeout that is not a positive number of seconds is ignored.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let find = |id| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .map(|x| x.value.trim())
+        };
+        let timeout = find(NETWORK_TIMEOUT_PREFERENCE).and_then(|x| match x.parse::<u64>() {
+            Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+            _ => {
+                tracing::warn!(value = x, "ignoring invalid network timeout preference");
+                None
+            }
+        });
+        let adaptive = find(ADAPTIVE_TIMEOUT_PREFERENCE)
+            .is_some_and(|x| matches!(x.to_lowercase().as_str(), "yes" | "true" | "1"));
+        Self::new(timeout, adaptive)
+    }
+
+    /// Returns the time to wait for an answer of the given hosts.
+    ///
+    /// The default of the builtin is used when no timeout is configured. When multiple hosts
+    /// are given, e.g. the addresses of a dual-stack target, the longest timeout is returned.
+    pub fn timeout(&self, hosts: &[IpAddr], default: Duration) -> Duration {
+        let timeout = self.timeout.unwrap_or(default);
+        let Some(round_trips) = &self.round_trips else {
+            return timeout;
+        };
+        let round_trips = round_trips.read().unwrap();
+        hosts
+            .iter()
+            .map(|host| round_trips.get(host).and_then(RoundTrip::timeout))
+            .try_fold(Duration::ZERO, |acc, x| x.map(|x| acc.max(x)))
+            .filter(|x| !x.is_zero())
+            .map_or(timeout, |x| x.min(timeout))
+    }
+
+    /// Records a round trip time observed for the host.
+    ///
+    /// Does nothing unless the adaptive timeout is enabled.
+    pub fn observe(&self, host: IpAddr, rtt: Duration) {
+        if let Some(round_trips) = &self.round_trips {
+            round_trips
+                .write()
+                .unwrap()
+                .entry(host)
+                .and_modify(|x| x.update(rtt))
+                .or_insert_with(|| RoundTrip::new(rtt));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, time::Duration};
+
+    use crate::models::ScanPreference;
+
+    use super::{NetworkTimeout, MIN_ADAPTIVE_TIMEOUT, NETWORK_TIMEOUT_PREFERENCE};
+
+    const DEFAULT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn configured() {
+        let preferences = |value: &str| {
+            vec![ScanPreference {
+                id: NETWORK_TIMEOUT_PREFERENCE.to_string(),
+                value: value.to_string(),
+            }]
+        };
+        let host: IpAddr = "192.0.2.1".parse().unwrap();
+        let timeout = NetworkTimeout::from_preferences(&preferences("3"));
+        assert_eq!(timeout.timeout(&[host], DEFAULT), Duration::from_secs(3));
+        let timeout = NetworkTimeout::from_preferences(&preferences("0"));
+        assert_eq!(timeout.timeout(&[host], DEFAULT), DEFAULT);
+        assert_eq!(NetworkTimeout::default().timeout(&[host], DEFAULT), DEFAULT);
+    }
+
+    #[test]
+    fn adaptive() {
+        let fast: IpAddr = "192.0.2.1".parse().unwrap();
+        let slow: IpAddr = "192.0.2.2".parse().unwrap();
+        let timeout = NetworkTimeout::new(None, true);
+        for _ in 0..2 {
+            timeout.observe(fast, Duration::from_millis(2));
+            timeout.observe(slow, Duration::from_secs(6));
+        }
+        // too few samples
+        assert_eq!(timeout.timeout(&[fast], DEFAULT), DEFAULT);
+        timeout.observe(fast, Duration::from_millis(2));
+        timeout.observe(slow, Duration::from_secs(6));
+        assert_eq!(timeout.timeout(&[fast], DEFAULT), MIN_ADAPTIVE_TIMEOUT);
+        // never above the configured timeout
+        assert_eq!(timeout.timeout(&[slow], DEFAULT), DEFAULT);
+        assert_eq!(timeout.timeout(&[fast, slow], DEFAULT), DEFAULT);
+
+        let fixed = NetworkTimeout::new(None, false);
+        for _ in 0..3 {
+            fixed.observe(fast, Duration::from_millis(2));
+        }
+        assert_eq!(fixed.timeout(&[fast], DEFAULT), DEFAULT);
+    }
+}
@@ -0,0 +1,127 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Proxy the connections of socket builtins are routed through.
+//!
+//! A scan uses either a [SocksProxy] or an [HttpProxy], configured by their scan preferences.
+//! When both are configured the SOCKS5 proxy is used, as it can relay UDP as well.
+
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::models::ScanPreference;
+
+use super::{http_proxy, socks_proxy, HttpProxy, SocksProxy, SourceBinding};
+
+/// Scan preferences of the proxies containing secrets.
+///
+/// They must not leave the scanner, e.g. within support bundles.
+pub const SECRET_PREFERENCES: [&str; 2] = [
+    socks_proxy::PASSWORD_PREFERENCE,
+    http_proxy::PASSWORD_PREFERENCE,
+];
+
+/// Proxy outgoing connections are routed through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proxy {
+    /// Relays TCP connections and UDP datagrams
+    Socks5(SocksProxy),
+    /// Tunnels TCP connections
+    Http(HttpProxy),
+}
+
+impl Proxy {
+    /// Creates the proxy configured by the scan preferences, None when no proxy is configured.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Option<Self> {
+        let socks = SocksProxy::from_preferences(preferences);
+        let http = HttpProxy::from_preferences(preferences);
+        match (socks, http) {
+            (Some(socks), Some(_)) => {
+                tracing::warn!("SOCKS5 and HTTP proxy configured, using the SOCKS5 proxy");
+                Some(Self::Socks5(socks))
+            }
+            (Some(socks), None) => Some(Self::Socks5(socks)),
+            (None, Some(http)) => Some(Self::Http(http)),
+            (None, None) => None,
+        }
+    }
+
+    /// Connects to the target through the proxy, waiting at most for the timeout for each step.
+    pub fn connect(
+        &self,
+        target: &SocketAddr,
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<TcpStream> {
+        match self {
+            Self::Socks5(proxy) => proxy.connect(target, timeout, binding),
+            Self::Http(proxy) => proxy.connect(target, timeout, binding),
+        }
+    }
+}
+
+/// Reads the scan preference, None when it is not set or empty.
+pub(super) fn preference<'a>(preferences: &'a [ScanPreference], id: &str) -> Option<&'a str> {
+    preferences
+        .iter()
+        .find(|x| x.id == id)
+        .map(|x| x.value.trim())
+        .filter(|x| !x.is_empty())
+}
+
+/// Splits the address of a proxy into host and port.
+///
+/// The host may be an IPv6 address in brackets, without a port the default port is used.
+pub(super) fn parse_address(address: &str, default_port: u16) -> (String, u16) {
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+            match port.parse() {
+                Ok(port) => (host, port),
+                Err(_) => {
+                    // connections fail instead of bypassing the proxy
+                    tracing::warn!(address, "invalid port of proxy");
+                    (host, 0)
+                }
+            }
+        }
+        _ => (address, default_port),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    (host.to_string(), port)
+}
+
+/// Connects to the proxy, trying its addresses in order from a socket bound to the source
+/// binding.
+///
+/// The read and write timeouts of the returned stream are set to the timeout.
+pub(super) fn connect_bound(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    binding: &SourceBinding,
+) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("unable to resolve proxy {host}"),
+    );
+    for addr in (host, port).to_socket_addrs()? {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        binding.bind(&socket, &addr.ip())?;
+        match socket.connect_timeout(&addr.into(), timeout) {
+            Ok(()) => {
+                let tcp: TcpStream = socket.into();
+                tcp.set_read_timeout(Some(timeout))?;
+                tcp.set_write_timeout(Some(timeout))?;
+                return Ok(tcp);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
@@ -0,0 +1,89 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Source of random bytes for builtins.
+//!
+//! By default the random bytes are taken from the cryptographically secure random number
+//! generator of the operating system. Tests and replays of recorded scans need reproducible
+//! values, therefore a deterministic generator can be injected into the
+//! [Context](super::Context) instead.
+
+use std::sync::{Arc, Mutex};
+
+use rand::{rngs::OsRng, rngs::StdRng, RngCore, SeedableRng};
+
+/// Provides random bytes to the builtins of a script
+#[derive(Clone, Default)]
+pub struct RandomSource {
+    generator: Option<Arc<Mutex<dyn RngCore + Send>>>,
+}
+
+impl RandomSource {
+    /// Creates a deterministic source that returns the same bytes for the same seed.
+    pub fn seeded(seed: u64) -> Self {
+        Self::from_rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Creates a source using the given generator, e.g. to return recorded values.
+    pub fn from_rng<R>(rng: R) -> Self
+    where
+        R: RngCore + Send + 'static,
+    {
+        Self {
+            generator: Some(Arc::new(Mutex::new(rng))),
+        }
+    }
+
+    /// Returns true when the bytes are taken from the operating system
+    pub fn is_secure(&self) -> bool {
+        self.generator.is_none()
+    }
+
+    /// Fills the buffer with random bytes
+    pub fn fill(&self, buffer: &mut [u8]) {
+        match &self.generator {
+            Some(generator) => generator.lock().unwrap().fill_bytes(buffer),
+            None => OsRng.fill_bytes(buffer),
+        }
+    }
+
+    /// Returns the given amount of random bytes
+    pub fn bytes(&self, len: usize) -> Vec<u8> {
+        let mut result = vec![0; len];
+        self.fill(&mut result);
+        result
+    }
+}
+
+impl std::fmt::Debug for RandomSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RandomSource")
+            .field("secure", &self.is_secure())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RandomSource;
+
+    #[test]
+    fn seeded_is_reproducible() {
+        let a = RandomSource::seeded(42);
+        let b = RandomSource::seeded(42);
+        assert_eq!(a.bytes(32), b.bytes(32));
+        // clones share the state
+        let c = a.clone();
+        assert_ne!(a.bytes(32), c.bytes(32));
+        assert!(!a.is_secure());
+    }
+
+    #[test]
+    fn secure() {
+        let source = RandomSource::default();
+        assert!(source.is_secure());
+        assert_eq!(source.bytes(16).len(), 16);
+        assert_ne!(source.bytes(16), source.bytes(16));
+    }
+}
@@ -0,0 +1,999 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Resolves names via DNS over TLS (RFC 7858) or DNS over HTTPS (RFC 8484).
+//!
+//! Environments prohibiting plaintext DNS from the scanner can configure encrypted resolvers
+//! via the scan preference [RESOLVER_PREFERENCE]. It contains a comma separated list of URLs
+//! like `tls://9.9.9.9` or `https://1.1.1.1/dns-query`, which are asked in order until one
+//! answers. The hostname of an URL is resolved with the system resolver, therefore the
+//! resolvers should be given by IP address when no plaintext lookup may happen at all. The
+//! certificate of a resolver is verified against the system trust store.
+//!
+//! Without configured resolvers the system resolver is used. Queries for other record types
+//! than addresses, see [Resolver::query_records], are sent in plaintext to the nameservers of
+//! the system then.
+
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::models::ScanPreference;
+
+/// Scan preference containing the encrypted resolvers
+pub const RESOLVER_PREFERENCE: &str = "dns_resolver";
+
+/// Timeout for connecting to, writing to and reading from a resolver
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Port of plaintext DNS
+pub const DNS_PORT: u16 = 53;
+const DOT_PORT: u16 = 853;
+const DOH_PORT: u16 = 443;
+const DOH_CONTENT_TYPE: &str = "application/dns-message";
+/// Configuration of the system resolver containing its nameservers
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+/// Maximum size of a response via UDP
+const MAX_UDP_MESSAGE: usize = 4096;
+
+const TYPE_A: u16 = 1;
+const TYPE_NS: u16 = 2;
+const TYPE_CNAME: u16 = 5;
+const TYPE_SOA: u16 = 6;
+const TYPE_PTR: u16 = 12;
+const TYPE_MX: u16 = 15;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_CAA: u16 = 257;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u16 = 3;
+/// Flag of a response that did not fit into the UDP datagram
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const FLAG_RESPONSE: u16 = 0x8000;
+
+/// How queries are sent to an encrypted resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Transport {
+    Tls,
+    Https { path: String },
+}
+
+/// An encrypted resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Server {
+    transport: Transport,
+    host: String,
+    port: u16,
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+            Arc::new(
+                ClientConfig::builder()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+impl Server {
+    fn parse(url: &str) -> Result<Self, String> {
+        let uri: http::Uri = url.parse().map_err(|e| format!("{url}: {e}"))?;
+        let host = uri
+            .host()
+            .ok_or_else(|| format!("{url}: missing host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let (transport, default_port) = match uri.scheme_str() {
+            Some("tls") => (Transport::Tls, DOT_PORT),
+            Some("https") => (
+                Transport::Https {
+                    path: uri
+                        .path_and_query()
+                        .map(|x| x.as_str())
+                        .filter(|x| *x != "/")
+                        .unwrap_or("/dns-query")
+                        .to_string(),
+                },
+                DOH_PORT,
+            ),
+            _ => return Err(format!("{url}: scheme must be tls or https")),
+        };
+        Ok(Self {
+            transport,
+            host,
+            port: uri.port_u16().unwrap_or(default_port),
+        })
+    }
+
+    fn connect(&self) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+        let address = match self.host.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, self.port),
+            Err(_) => (self.host.as_str(), self.port)
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| invalid_data(format!("unable to resolve {}", self.host)))?,
+        };
+        let server_name = ServerName::try_from(self.host.clone())
+            .map_err(|e| invalid_data(format!("{}: {e}", self.host)))?;
+        let connection =
+            ClientConnection::new(tls_config(), server_name).map_err(io::Error::other)?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        Ok(StreamOwned::new(connection, stream))
+    }
+
+    /// Sends the query and returns the response message.
+    fn exchange(&self, query: &[u8]) -> io::Result<Vec<u8>> {
+        let mut stream = self.connect()?;
+        match &self.transport {
+            Transport::Tls => exchange_stream(&mut stream, query),
+            Transport::Https { path } => {
+                let request = format!(
+                    "POST {path} HTTP/1.1\r\nHost: {}\r\nContent-Type: {DOH_CONTENT_TYPE}\r\nAccept: {DOH_CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    self.host,
+                    query.len()
+                );
+                stream.write_all(request.as_bytes())?;
+                stream.write_all(query)?;
+                let mut response = vec![];
+                // servers may close the connection without close_notify
+                if let Err(e) = stream.read_to_end(&mut response) {
+                    if e.kind() != io::ErrorKind::UnexpectedEof || response.is_empty() {
+                        return Err(e);
+                    }
+                }
+                http_body(&response)
+            }
+        }
+    }
+}
+
+/// Sends the query prefixed by its length over a stream and returns the response message.
+fn exchange_stream<S: Read + Write>(stream: &mut S, query: &[u8]) -> io::Result<Vec<u8>> {
+    let mut request = (query.len() as u16).to_be_bytes().to_vec();
+    request.extend_from_slice(query);
+    stream.write_all(&request)?;
+    let mut length = [0u8; 2];
+    stream.read_exact(&mut length)?;
+    let mut response = vec![0u8; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut response)?;
+    Ok(response)
+}
+
+/// Sends the query in plaintext via UDP and repeats it via TCP when the response is truncated.
+fn exchange_plain(server: &SocketAddr, query: &[u8]) -> io::Result<Vec<u8>> {
+    let unspecified = match server {
+        SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+    socket.connect(server)?;
+    socket.send(query)?;
+    let mut response = vec![0u8; MAX_UDP_MESSAGE];
+    let length = socket.recv(&mut response)?;
+    response.truncate(length);
+    if read_u16(&response, 2)? & FLAG_TRUNCATED == 0 {
+        return Ok(response);
+    }
+    let mut stream = TcpStream::connect_timeout(server, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    exchange_stream(&mut stream, query)
+}
+
+/// Returns the nameservers of the system resolver, the local host when none is configured.
+fn system_nameservers() -> Vec<SocketAddr> {
+    let servers: Vec<SocketAddr> = std::fs::read_to_string(RESOLV_CONF)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|x| x.trim().strip_prefix("nameserver"))
+        // scoped IPv6 addresses are not supported
+        .filter_map(|x| x.trim().parse::<IpAddr>().ok())
+        .map(|x| SocketAddr::new(x, DNS_PORT))
+        .collect();
+    match servers.is_empty() {
+        true => vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DNS_PORT)],
+        false => servers,
+    }
+}
+
+/// Returns the body of a successful HTTP/1.1 response.
+fn http_body(response: &[u8]) -> io::Result<Vec<u8>> {
+    let end = response
+        .windows(4)
+        .position(|x| x == b"\r\n\r\n")
+        .ok_or_else(|| invalid_data("incomplete HTTP response"))?;
+    let head = String::from_utf8_lossy(&response[..end]);
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(invalid_data(format!("resolver responded with {status}")));
+    }
+    let chunked = lines.any(|x| {
+        x.to_lowercase()
+            .strip_prefix("transfer-encoding:")
+            .is_some_and(|x| x.contains("chunked"))
+    });
+    let mut body = &response[end + 4..];
+    if !chunked {
+        return Ok(body.to_vec());
+    }
+    let mut result = vec![];
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|x| x == b"\r\n")
+            .ok_or_else(|| invalid_data("invalid chunk"))?;
+        let size = String::from_utf8_lossy(&body[..line_end]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16)
+            .map_err(|_| invalid_data("invalid chunk size"))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(result);
+        }
+        let chunk = body
+            .get(..size)
+            .ok_or_else(|| invalid_data("incomplete chunk"))?;
+        result.extend_from_slice(chunk);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Returns the name queried for the reverse lookup of the address.
+fn reverse_name(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+        }
+        IpAddr::V6(ip) => {
+            let mut name: String = ip
+                .octets()
+                .iter()
+                .rev()
+                .map(|x| format!("{:x}.{:x}.", x & 0xf, x >> 4))
+                .collect();
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+/// Encodes a recursive query for the name.
+fn encode_query(id: u16, name: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    encode_message(id, FLAG_RECURSION_DESIRED, name, qtype)
+}
+
+/// Encodes a query with a single question.
+fn encode_message(id: u16, flags: u16, name: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let mut result = id.to_be_bytes().to_vec();
+    result.extend_from_slice(&flags.to_be_bytes());
+    result.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(invalid_data(format!("invalid name {name}")));
+        }
+        result.push(label.len() as u8);
+        result.extend_from_slice(label.as_bytes());
+    }
+    result.push(0);
+    result.extend_from_slice(&qtype.to_be_bytes());
+    result.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(result)
+}
+
+fn read_u16(message: &[u8], offset: usize) -> io::Result<u16> {
+    message
+        .get(offset..offset + 2)
+        .map(|x| u16::from_be_bytes([x[0], x[1]]))
+        .ok_or_else(|| invalid_data("truncated message"))
+}
+
+/// Reads a possibly compressed name and returns it with the offset after it.
+fn read_name(message: &[u8], mut offset: usize) -> io::Result<(String, usize)> {
+    let mut labels = vec![];
+    let mut end = None;
+    // each pointer has to point backwards, which limits the number of jumps
+    let mut limit = offset;
+    loop {
+        let length = *message
+            .get(offset)
+            .ok_or_else(|| invalid_data("truncated name"))? as usize;
+        match length {
+            0 => break,
+            x if x & 0xc0 == 0xc0 => {
+                let pointer = (read_u16(message, offset)? & 0x3fff) as usize;
+                if pointer >= limit {
+                    return Err(invalid_data("invalid name pointer"));
+                }
+                end.get_or_insert(offset + 2);
+                limit = pointer;
+                offset = pointer;
+            }
+            x => {
+                let label = message
+                    .get(offset + 1..offset + 1 + x)
+                    .ok_or_else(|| invalid_data("truncated label"))?;
+                labels.push(String::from_utf8_lossy(label).to_string());
+                offset += 1 + x;
+            }
+        }
+    }
+    Ok((labels.join("."), end.unwrap_or(offset + 1)))
+}
+
+/// A resource record of an answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Record {
+    Address(IpAddr),
+    Pointer(String),
+    Other,
+}
+
+/// Type of a resource record that can be queried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Cname,
+    Ns,
+    Ptr,
+    Mx,
+    Txt,
+    Srv,
+    Caa,
+}
+
+impl RecordType {
+    const ALL: [Self; 9] = [
+        Self::A,
+        Self::Aaaa,
+        Self::Cname,
+        Self::Ns,
+        Self::Ptr,
+        Self::Mx,
+        Self::Txt,
+        Self::Srv,
+        Self::Caa,
+    ];
+
+    fn code(&self) -> u16 {
+        match self {
+            Self::A => TYPE_A,
+            Self::Aaaa => TYPE_AAAA,
+            Self::Cname => TYPE_CNAME,
+            Self::Ns => TYPE_NS,
+            Self::Ptr => TYPE_PTR,
+            Self::Mx => TYPE_MX,
+            Self::Txt => TYPE_TXT,
+            Self::Srv => TYPE_SRV,
+            Self::Caa => TYPE_CAA,
+        }
+    }
+
+    fn from_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.code() == code)
+    }
+}
+
+impl Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::A => write!(f, "A"),
+            Self::Aaaa => write!(f, "AAAA"),
+            Self::Cname => write!(f, "CNAME"),
+            Self::Ns => write!(f, "NS"),
+            Self::Ptr => write!(f, "PTR"),
+            Self::Mx => write!(f, "MX"),
+            Self::Txt => write!(f, "TXT"),
+            Self::Srv => write!(f, "SRV"),
+            Self::Caa => write!(f, "CAA"),
+        }
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unsupported record type {s}"))
+    }
+}
+
+/// Data of a resource record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    /// Address of an A or AAAA record
+    Address(IpAddr),
+    /// Target of a CNAME, NS or PTR record
+    Name(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    /// Character strings of a TXT record, e.g. the parts of a long SPF policy
+    Txt(Vec<String>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Caa {
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+}
+
+/// A resource record of an answer to [Resolver::query_records]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRecord {
+    /// Owner name, which differs from the queried one for records following a CNAME
+    pub name: String,
+    pub record_type: RecordType,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+/// An undecoded answer record
+struct Answer {
+    name: String,
+    rtype: u16,
+    ttl: u32,
+    /// Offset of the data in the message
+    start: usize,
+    length: usize,
+}
+
+/// Returns the answer records of a response to the query with the given id.
+fn answers(id: u16, message: &[u8]) -> io::Result<Vec<Answer>> {
+    if read_u16(message, 0)? != id {
+        return Err(invalid_data("response does not match query"));
+    }
+    let flags = read_u16(message, 2)?;
+    match flags & 0xf {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(vec![]),
+        x => return Err(invalid_data(format!("resolver responded with rcode {x}"))),
+    }
+    read_records(message, false)
+}
+
+/// Returns the records of the answer section, with all also the ones of the authority and
+/// additional sections.
+fn read_records(message: &[u8], all: bool) -> io::Result<Vec<Answer>> {
+    let questions = read_u16(message, 4)?;
+    let mut records = read_u16(message, 6)? as usize;
+    if all {
+        records += read_u16(message, 8)? as usize + read_u16(message, 10)? as usize;
+    }
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(message, offset)?.1 + 4;
+    }
+    let mut result = Vec::with_capacity(records);
+    for _ in 0..records {
+        let name;
+        (name, offset) = read_name(message, offset)?;
+        let rtype = read_u16(message, offset)?;
+        let ttl =
+            (read_u16(message, offset + 4)? as u32) << 16 | read_u16(message, offset + 6)? as u32;
+        let length = read_u16(message, offset + 8)? as usize;
+        let start = offset + 10;
+        if message.len() < start + length {
+            return Err(invalid_data("truncated record"));
+        }
+        result.push(Answer {
+            name,
+            rtype,
+            ttl,
+            start,
+            length,
+        });
+        offset = start + length;
+    }
+    Ok(result)
+}
+
+/// Answer records of a response with the time they may be cached
+#[derive(Debug, PartialEq, Eq)]
+struct Answered {
+    records: Vec<Record>,
+    /// Smallest TTL of the answer records, for responses without answer records the negative
+    /// caching time of the SOA record in the authority section
+    ttl: Option<u32>,
+}
+
+/// Returns the time a response without answer records may be cached, which is the smaller of
+/// the TTL and the minimum field of the SOA record in the authority section (RFC 2308).
+fn negative_ttl(message: &[u8]) -> Option<u32> {
+    let records = read_records(message, true).ok()?;
+    let soa = records.iter().find(|x| x.rtype == TYPE_SOA)?;
+    let end = soa.start + soa.length;
+    let minimum = message.get(end.checked_sub(4)?..end)?;
+    Some(soa.ttl.min(u32::from_be_bytes(minimum.try_into().unwrap())))
+}
+
+/// Decodes the answer records of a response to the query with the given id.
+fn decode_answers(id: u16, message: &[u8]) -> io::Result<Answered> {
+    let answers = answers(id, message)?;
+    let ttl = match answers.iter().map(|x| x.ttl).min() {
+        Some(x) => Some(x),
+        None => negative_ttl(message),
+    };
+    let records = answers
+        .into_iter()
+        .map(|x| {
+            let data = &message[x.start..x.start + x.length];
+            Ok(match (x.rtype, x.length) {
+                (TYPE_A, 4) => Record::Address(IpAddr::from(<[u8; 4]>::try_from(data).unwrap())),
+                (TYPE_AAAA, 16) => {
+                    Record::Address(IpAddr::from(<[u8; 16]>::try_from(data).unwrap()))
+                }
+                (TYPE_PTR, _) => Record::Pointer(read_name(message, x.start)?.0),
+                _ => Record::Other,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(Answered { records, ttl })
+}
+
+/// Decodes the data of an answer, None for unsupported types.
+fn decode_data(message: &[u8], answer: &Answer) -> io::Result<Option<RecordData>> {
+    let start = answer.start;
+    let data = &message[start..start + answer.length];
+    let truncated = || invalid_data("truncated record");
+    let string = |x: &[u8]| String::from_utf8_lossy(x).to_string();
+    let Some(rtype) = RecordType::from_code(answer.rtype) else {
+        return Ok(None);
+    };
+    Ok(Some(match rtype {
+        RecordType::A | RecordType::Aaaa => RecordData::Address(match data.len() {
+            4 => IpAddr::from(<[u8; 4]>::try_from(data).unwrap()),
+            16 => IpAddr::from(<[u8; 16]>::try_from(data).unwrap()),
+            _ => return Err(invalid_data("invalid address")),
+        }),
+        RecordType::Cname | RecordType::Ns | RecordType::Ptr => {
+            RecordData::Name(read_name(message, start)?.0)
+        }
+        RecordType::Mx => RecordData::Mx {
+            preference: read_u16(message, start)?,
+            exchange: read_name(message, start + 2)?.0,
+        },
+        RecordType::Txt => {
+            let mut strings = vec![];
+            let mut rest = data;
+            while let Some((length, tail)) = rest.split_first() {
+                let value = tail.get(..*length as usize).ok_or_else(truncated)?;
+                strings.push(string(value));
+                rest = &tail[value.len()..];
+            }
+            RecordData::Txt(strings)
+        }
+        RecordType::Srv => RecordData::Srv {
+            priority: read_u16(message, start)?,
+            weight: read_u16(message, start + 2)?,
+            port: read_u16(message, start + 4)?,
+            target: read_name(message, start + 6)?.0,
+        },
+        RecordType::Caa => {
+            let (flags, length) = match data {
+                [flags, length, ..] => (*flags, *length as usize),
+                _ => return Err(truncated()),
+            };
+            let tag = data.get(2..2 + length).ok_or_else(truncated)?;
+            RecordData::Caa {
+                flags,
+                tag: string(tag),
+                value: string(&data[2 + length..]),
+            }
+        }
+    }))
+}
+
+/// Decodes the answer records of the supported types of a response to the query with the
+/// given id.
+fn decode_records(id: u16, message: &[u8]) -> io::Result<Vec<ResourceRecord>> {
+    to_resource_records(message, answers(id, message)?)
+}
+
+fn to_resource_records(message: &[u8], answers: Vec<Answer>) -> io::Result<Vec<ResourceRecord>> {
+    let mut result = vec![];
+    for answer in answers {
+        if let Some(data) = decode_data(message, &answer)? {
+            result.push(ResourceRecord {
+                record_type: RecordType::from_code(answer.rtype).unwrap(),
+                name: answer.name,
+                ttl: answer.ttl,
+                data,
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Encodes a query without recursion as sent to the responders of mDNS and LLMNR.
+pub fn encode_multicast_query(id: u16, name: &str, record_type: RecordType) -> io::Result<Vec<u8>> {
+    encode_message(id, 0, name, record_type.code())
+}
+
+/// Decodes a response of a multicast responder and returns its id with the records of the
+/// supported types of all sections.
+///
+/// Responders put related records like the SRV record of a service instance into the
+/// additional section, so they are returned as well.
+pub fn decode_multicast_response(message: &[u8]) -> io::Result<(u16, Vec<ResourceRecord>)> {
+    if read_u16(message, 2)? & FLAG_RESPONSE == 0 {
+        return Err(invalid_data("message is not a response"));
+    }
+    let records = to_resource_records(message, read_records(message, true)?)?;
+    Ok((read_u16(message, 0)?, records))
+}
+
+/// Result of a lookup with the time it may be cached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lookup<T> {
+    /// None when the lookup failed
+    pub value: Option<T>,
+    /// Time to live of the records or, when there are none, the negative caching time of the
+    /// zone. None when the resolver does not report it, like the system resolver.
+    pub ttl: Option<Duration>,
+}
+
+impl<T> Lookup<T> {
+    fn untimed(value: Option<T>) -> Self {
+        Self { value, ttl: None }
+    }
+}
+
+/// Resolver used for the name lookups of a scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolver {
+    /// Encrypted resolvers asked in order, the system resolver is used when None
+    servers: Option<Vec<Server>>,
+}
+
+impl Resolver {
+    /// Creates a resolver from a comma separated list of `tls://` and `https://` URLs.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let servers: Vec<Server> = value
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(Server::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            servers: (!servers.is_empty()).then_some(servers),
+        })
+    }
+
+    /// Creates the resolver configured in the scan preferences.
+    ///
+    /// An invalid configuration is logged and results in a resolver failing all lookups
+    /// instead of falling back to plaintext DNS.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let value = preferences
+            .iter()
+            .find(|x| x.id == RESOLVER_PREFERENCE)
+            .map(|x| x.value.as_str())
+            .unwrap_or_default();
+        Self::parse(value).unwrap_or_else(|e| {
+            tracing::warn!(%e, "invalid encrypted DNS resolver, all lookups will fail");
+            Self {
+                servers: Some(vec![]),
+            }
+        })
+    }
+
+    /// Returns true when the system resolver is used.
+    pub fn is_system(&self) -> bool {
+        self.servers.is_none()
+    }
+
+    fn query(&self, name: &str, qtype: u16) -> Option<Answered> {
+        let id = rand::random();
+        let query = match encode_query(id, name, qtype) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(name, %e, "unable to encode query");
+                return None;
+            }
+        };
+        self.servers.iter().flatten().find_map(|server| {
+            match server.exchange(&query).and_then(|x| decode_answers(id, &x)) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    tracing::warn!(resolver = server.host, name, %e, "encrypted DNS lookup failed");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Queries the records of the given type of the name.
+    ///
+    /// The query is sent to the given nameserver in plaintext, without one to the encrypted
+    /// resolvers or, when none is configured, the nameservers of the system in order until one
+    /// answers. Returns the records of the answer, which are empty when the name does not exist.
+    pub fn query_records(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        server: Option<SocketAddr>,
+    ) -> io::Result<Vec<ResourceRecord>> {
+        let id = rand::random();
+        let query = encode_query(id, name, record_type.code())?;
+        let plain = match (server, &self.servers) {
+            (Some(server), _) => vec![server],
+            (None, Some(_)) => vec![],
+            (None, None) => system_nameservers(),
+        };
+        let encrypted = match server {
+            Some(_) => &[],
+            None => self.servers.as_deref().unwrap_or_default(),
+        };
+        let responses = plain
+            .iter()
+            .map(|x| exchange_plain(x, &query))
+            .chain(encrypted.iter().map(|x| x.exchange(&query)));
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no resolver configured");
+        for response in responses {
+            match response.and_then(|x| decode_records(id, &x)) {
+                Ok(x) => return Ok(x),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Returns the IP addresses of the given hostname.
+    pub fn lookup_host(&self, hostname: &str) -> Lookup<Vec<IpAddr>> {
+        if self.is_system() {
+            return Lookup::untimed(dns_lookup::lookup_host(hostname).ok());
+        }
+        if let Ok(ip) = hostname.parse() {
+            return Lookup::untimed(Some(vec![ip]));
+        }
+        let answers: Vec<Answered> = [TYPE_A, TYPE_AAAA]
+            .into_iter()
+            .filter_map(|x| self.query(hostname, x))
+            .collect();
+        // a missing address family expires like the existing one
+        let ttl = answers.iter().filter_map(|x| x.ttl).min();
+        let result: Vec<IpAddr> = answers
+            .into_iter()
+            .flat_map(|x| x.records)
+            .filter_map(|x| match x {
+                Record::Address(ip) => Some(ip),
+                _ => None,
+            })
+            .collect();
+        Lookup {
+            value: Some(result),
+            ttl: ttl.map(|x| Duration::from_secs(x.into())),
+        }
+    }
+
+    /// Returns the hostname of the given IP address.
+    pub fn lookup_addr(&self, ip: &IpAddr) -> Lookup<String> {
+        if self.is_system() {
+            return Lookup::untimed(dns_lookup::lookup_addr(ip).ok());
+        }
+        let Some(answered) = self.query(&reverse_name(ip), TYPE_PTR) else {
+            return Lookup::untimed(None);
+        };
+        let value = answered.records.into_iter().find_map(|x| match x {
+            Record::Pointer(name) => Some(name),
+            _ => None,
+        });
+        Lookup {
+            value,
+            ttl: answered.ttl.map(|x| Duration::from_secs(x.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let resolver = Resolver::parse("tls://9.9.9.9, https://[2606:4700::1111]:8443").unwrap();
+        assert_eq!(
+            resolver.servers.unwrap(),
+            vec![
+                Server {
+                    transport: Transport::Tls,
+                    host: "9.9.9.9".to_string(),
+                    port: 853
+                },
+                Server {
+                    transport: Transport::Https {
+                        path: "/dns-query".to_string()
+                    },
+                    host: "2606:4700::1111".to_string(),
+                    port: 8443
+                }
+            ]
+        );
+        assert!(Resolver::parse("").unwrap().is_system());
+        assert!(Resolver::parse("udp://9.9.9.9").is_err());
+        let preferences = vec![ScanPreference {
+            id: RESOLVER_PREFERENCE.to_string(),
+            value: "dns://1.1.1.1".to_string(),
+        }];
+        assert!(!Resolver::from_preferences(&preferences).is_system());
+    }
+
+    #[test]
+    fn reverse() {
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(reverse_name(&ip), "1.2.0.192.in-addr.arpa");
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(
+            reverse_name(&ip),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let mut message = encode_query(0x1234, "example.com", TYPE_A).unwrap();
+        // response flags and two answers
+        message[2..4].copy_from_slice(&[0x81, 0x80]);
+        message[6..8].copy_from_slice(&[0, 2]);
+        // www.example.com CNAME pointing to the question name
+        message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 12]);
+        message.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 30, 0, 4, 192, 0, 2, 1]);
+        assert_eq!(
+            decode_answers(0x1234, &message).unwrap(),
+            Answered {
+                records: vec![Record::Other, Record::Address("192.0.2.1".parse().unwrap())],
+                ttl: Some(30),
+            }
+        );
+        assert!(decode_answers(0x4321, &message).is_err());
+        message[3] = 0x83;
+        assert_eq!(
+            decode_answers(0x1234, &message).unwrap(),
+            Answered {
+                records: vec![],
+                ttl: None
+            }
+        );
+        // NXDOMAIN with the SOA record of the zone in the authority section
+        let mut message = encode_query(5, "missing.example.com", TYPE_A).unwrap();
+        message[2..4].copy_from_slice(&[0x81, 0x83]);
+        message[8..10].copy_from_slice(&[0, 1]);
+        message.extend_from_slice(&[0xc0, 20, 0, 6, 0, 1, 0, 0, 0x0e, 0x10, 0, 22, 0, 0]);
+        message.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0x1c, 0x20, 0, 0, 0x0e, 0x10]);
+        message.extend_from_slice(&[0, 0x09, 0x3a, 0x80, 0, 0, 0x01, 0x2c]);
+        assert_eq!(
+            decode_answers(5, &message).unwrap(),
+            Answered {
+                records: vec![],
+                ttl: Some(300)
+            }
+        );
+        // pointer loop
+        let mut message = encode_query(1, "a", TYPE_PTR).unwrap();
+        message[6..8].copy_from_slice(&[0, 1]);
+        message.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 31]);
+        assert!(decode_answers(1, &message).is_err());
+    }
+
+    #[test]
+    fn decode_record_types() {
+        let mut message = encode_query(7, "example.com", TYPE_MX).unwrap();
+        message[2..4].copy_from_slice(&[0x81, 0x80]);
+        message[6..8].copy_from_slice(&[0, 4]);
+        // MX 10 mail.example.com
+        message.extend_from_slice(&[0xc0, 12, 0, 15, 0, 1, 0, 1, 0, 0, 0, 9, 0, 10, 4]);
+        message.extend_from_slice(b"mail\xc0\x0c");
+        // TXT split into two strings
+        message.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, 9, 3]);
+        message.extend_from_slice(b"v=s\x04pf1 ");
+        // SRV 1 2 5060 with the root as target
+        message.extend_from_slice(&[0xc0, 12, 0, 33, 0, 1, 0, 0, 0, 60, 0, 7, 0, 1, 0, 2, 0x13]);
+        message.extend_from_slice(&[0xc4, 0]);
+        // CAA 0 issue "ca.example"
+        message.extend_from_slice(&[0xc0, 12, 1, 1, 0, 1, 0, 0, 0, 60, 0, 17, 0, 5]);
+        message.extend_from_slice(b"issueca.example");
+        let records = decode_records(7, &message).unwrap();
+        assert_eq!(
+            records.iter().map(|x| x.data.clone()).collect::<Vec<_>>(),
+            vec![
+                RecordData::Mx {
+                    preference: 10,
+                    exchange: "mail.example.com".to_string()
+                },
+                RecordData::Txt(vec!["v=s".to_string(), "pf1 ".to_string()]),
+                RecordData::Srv {
+                    priority: 1,
+                    weight: 2,
+                    port: 5060,
+                    target: String::new()
+                },
+                RecordData::Caa {
+                    flags: 0,
+                    tag: "issue".to_string(),
+                    value: "ca.example".to_string()
+                },
+            ]
+        );
+        assert_eq!(records[0].ttl, 65536);
+        assert_eq!(records[0].name, "example.com");
+        assert_eq!(RecordType::from_str("aaaa"), Ok(RecordType::Aaaa));
+        assert!(RecordType::from_str("AXFR").is_err());
+    }
+
+    #[test]
+    fn decode_multicast() {
+        let mut message = encode_multicast_query(0, "_ssh._tcp.local", RecordType::Ptr).unwrap();
+        assert_eq!(read_u16(&message, 2).unwrap(), 0);
+        assert!(decode_multicast_response(&message).is_err());
+        // authoritative response with the PTR answer and the SRV record as additional record
+        message[2..4].copy_from_slice(&[0x84, 0x00]);
+        message[6..8].copy_from_slice(&[0, 1]);
+        message[10..12].copy_from_slice(&[0, 1]);
+        message.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1, 0, 0, 0x11, 0x94, 0, 7, 4]);
+        message.extend_from_slice(b"host\xc0\x0c");
+        message.extend_from_slice(&[0xc0, 45, 0, 33, 0x80, 1, 0, 0, 0, 120, 0, 8, 0, 0, 0, 0]);
+        message.extend_from_slice(&[0, 22, 0xc0, 22]);
+        let (id, records) = decode_multicast_response(&message).unwrap();
+        assert_eq!(id, 0);
+        assert_eq!(
+            records[0].data,
+            RecordData::Name("host._ssh._tcp.local".to_string())
+        );
+        assert_eq!(records[1].name, "host._ssh._tcp.local");
+        assert_eq!(
+            records[1].data,
+            RecordData::Srv {
+                priority: 0,
+                weight: 0,
+                port: 22,
+                target: "local".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn body() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc";
+        assert_eq!(http_body(response).unwrap(), b"abc");
+        let response =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n1\r\nc\r\n0\r\n\r\n";
+        assert_eq!(http_body(response).unwrap(), b"abc");
+        assert!(http_body(b"HTTP/1.1 400 Bad Request\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Runtime statistics of a script run.
+//!
+//! When the scan preference [SCRIPT_STATS_PREFERENCE] is enabled, the execution duration, the
+//! number of bytes sent and received and the number of retransmissions of a script are
+//! attached to each of its results. This allows feed maintainers to spot scripts that are
+//! expensive on real networks.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use crate::models::{self, ScanPreference};
+
+/// Scan preference enabling the runtime statistics within results.
+pub const SCRIPT_STATS_PREFERENCE: &str = "report_script_stats";
+
+/// Counters of a single script run
+#[derive(Debug)]
+pub struct ScriptStats {
+    start: Instant,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    retransmissions: AtomicU64,
+}
+
+impl Default for ScriptStats {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            bytes_sent: AtomicU64::default(),
+            bytes_received: AtomicU64::default(),
+            retransmissions: AtomicU64::default(),
+        }
+    }
+}
+
+impl ScriptStats {
+    /// Returns true when the runtime statistics are enabled in the scan preferences.
+    pub fn enabled_by_preferences(preferences: &[ScanPreference]) -> bool {
+        preferences
+            .iter()
+            .find(|x| x.id == SCRIPT_STATS_PREFERENCE)
+            .is_some_and(|x| matches!(x.value.trim().to_lowercase().as_str(), "yes" | "true" | "1"))
+    }
+
+    /// Adds the number of bytes sent by the script.
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Adds the number of bytes received by the script.
+    pub fn received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Adds the number of retransmitted packets.
+    pub fn retransmitted(&self, count: u64) {
+        self.retransmissions.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics collected until now.
+    pub fn snapshot(&self) -> models::ScriptStats {
+        models::ScriptStats {
+            duration_ms: self.start.elapsed().as_millis() as u64,
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            retransmissions: self.retransmissions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ScanPreference;
+
+    use super::{ScriptStats, SCRIPT_STATS_PREFERENCE};
+
+    #[test]
+    fn counters() {
+        let stats = ScriptStats::default();
+        stats.sent(10);
+        stats.sent(5);
+        stats.received(7);
+        stats.retransmitted(2);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.bytes_sent, 15);
+        assert_eq!(snapshot.bytes_received, 7);
+        assert_eq!(snapshot.retransmissions, 2);
+    }
+
+    #[test]
+    fn preferences() {
+        let preference = |value: &str| {
+            vec![ScanPreference {
+                id: SCRIPT_STATS_PREFERENCE.to_string(),
+                value: value.to_string(),
+            }]
+        };
+        assert!(ScriptStats::enabled_by_preferences(&preference("yes")));
+        assert!(ScriptStats::enabled_by_preferences(&preference("true")));
+        assert!(!ScriptStats::enabled_by_preferences(&preference("no")));
+        assert!(!ScriptStats::enabled_by_preferences(&[]));
+    }
+}
@@ -0,0 +1,491 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Routes the connections of socket builtins through a SOCKS5 proxy.
+//!
+//! Targets that are only reachable via a jump host are scanned by configuring the proxy with
+//! the scan preference [PROXY_PREFERENCE] and, when the proxy requires authentication,
+//! [USERNAME_PREFERENCE] and [PASSWORD_PREFERENCE]. TCP connections are established with the
+//! CONNECT command and UDP datagrams are relayed via UDP ASSOCIATE as described in RFC 1928.
+//! Username and password are sent as described in RFC 1929.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use crate::models::ScanPreference;
+
+use super::{
+    proxy::{connect_bound, parse_address, preference},
+    SourceBinding,
+};
+
+/// Scan preference containing the address of the proxy as `host:port`.
+pub const PROXY_PREFERENCE: &str = "socks5_proxy";
+/// Scan preference containing the username to authenticate at the proxy.
+pub const USERNAME_PREFERENCE: &str = "socks5_username";
+/// Scan preference containing the password to authenticate at the proxy.
+pub const PASSWORD_PREFERENCE: &str = "socks5_password";
+
+/// Port of the proxy when the preference contains only a host
+pub const DEFAULT_PORT: u16 = 1080;
+/// Maximum length of the header of a relayed UDP datagram
+pub const MAX_UDP_HEADER: usize = 22;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+const USERNAME_PASSWORD_VERSION: u8 = 1;
+const CONNECT: u8 = 1;
+const UDP_ASSOCIATE: u8 = 3;
+const IPV4: u8 = 1;
+const DOMAIN_NAME: u8 = 3;
+const IPV6: u8 = 4;
+
+/// SOCKS5 proxy outgoing connections are routed through
+#[derive(Clone, PartialEq, Eq)]
+pub struct SocksProxy {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl fmt::Debug for SocksProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the password must not end up in logs
+        f.debug_struct("SocksProxy")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.credentials.as_ref().map(|(x, _)| x))
+            .finish()
+    }
+}
+
+impl SocksProxy {
+    /// Creates a new SocksProxy.
+    pub fn new(host: impl Into<String>, port: u16, credentials: Option<(String, String)>) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            credentials,
+        }
+    }
+
+    /// Creates a SocksProxy based on the scan preferences.
+    ///
+    /// Returns None when no proxy is configured. The host may be an IPv6 address in brackets,
+    /// without a port [DEFAULT_PORT] is used.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Option<Self> {
+        let address = preference(preferences, PROXY_PREFERENCE)?;
+        let (host, port) = parse_address(address, DEFAULT_PORT);
+        let credentials = preference(preferences, USERNAME_PREFERENCE).map(|username| {
+            (
+                username.to_string(),
+                preference(preferences, PASSWORD_PREFERENCE)
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        });
+        Some(Self::new(host, port, credentials))
+    }
+
+    /// Returns the host of the proxy
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the port of the proxy
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Opens a connection to the proxy and authenticates.
+    fn open(&self, timeout: Duration, binding: &SourceBinding) -> io::Result<TcpStream> {
+        let mut tcp = connect_bound(&self.host, self.port, timeout, binding)?;
+        self.authenticate(&mut tcp)?;
+        Ok(tcp)
+    }
+
+    fn authenticate(&self, tcp: &mut TcpStream) -> io::Result<()> {
+        let method = match self.credentials {
+            Some(_) => USERNAME_PASSWORD,
+            None => NO_AUTHENTICATION,
+        };
+        tcp.write_all(&[VERSION, 1, method])?;
+        let mut reply = [0; 2];
+        tcp.read_exact(&mut reply)?;
+        match reply {
+            [VERSION, NO_AUTHENTICATION] => Ok(()),
+            [VERSION, USERNAME_PASSWORD] => {
+                let (username, password) = self
+                    .credentials
+                    .as_ref()
+                    .ok_or_else(|| protocol_error("unrequested authentication method"))?;
+                let mut request = vec![USERNAME_PASSWORD_VERSION];
+                for x in [username, password] {
+                    let len = u8::try_from(x.len())
+                        .map_err(|_| protocol_error("username or password too long"))?;
+                    request.push(len);
+                    request.extend_from_slice(x.as_bytes());
+                }
+                tcp.write_all(&request)?;
+                tcp.read_exact(&mut reply)?;
+                if reply[1] != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "SOCKS5 proxy rejected the credentials",
+                    ));
+                }
+                Ok(())
+            }
+            [VERSION, NO_ACCEPTABLE_METHOD] => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "SOCKS5 proxy requires an unsupported authentication method",
+            )),
+            _ => Err(protocol_error("unexpected method selection")),
+        }
+    }
+
+    /// Sends a request and returns the address bound by the proxy.
+    fn request(tcp: &mut TcpStream, command: u8, addr: &SocketAddr) -> io::Result<SocketAddr> {
+        let mut request = vec![VERSION, command, 0];
+        encode_address(addr, &mut request);
+        tcp.write_all(&request)?;
+        let mut reply = [0; 4];
+        tcp.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(protocol_error("unexpected version"));
+        }
+        if reply[1] != 0 {
+            return Err(reply_error(reply[1]));
+        }
+        let ip = match reply[3] {
+            IPV4 => {
+                let mut ip = [0; 4];
+                tcp.read_exact(&mut ip)?;
+                IpAddr::from(ip)
+            }
+            IPV6 => {
+                let mut ip = [0; 16];
+                tcp.read_exact(&mut ip)?;
+                IpAddr::from(ip)
+            }
+            DOMAIN_NAME => {
+                let mut len = [0; 1];
+                tcp.read_exact(&mut len)?;
+                tcp.read_exact(&mut vec![0; len[0] as usize])?;
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+            }
+            _ => return Err(protocol_error("unknown address type")),
+        };
+        let mut port = [0; 2];
+        tcp.read_exact(&mut port)?;
+        Ok(SocketAddr::new(ip, u16::from_be_bytes(port)))
+    }
+
+    /// Connects to the target through the proxy, waiting at most for the timeout for each step.
+    pub fn connect(
+        &self,
+        target: &SocketAddr,
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<TcpStream> {
+        let mut tcp = self.open(timeout, binding)?;
+        Self::request(&mut tcp, CONNECT, target)?;
+        tcp.set_read_timeout(None)?;
+        tcp.set_write_timeout(None)?;
+        Ok(tcp)
+    }
+
+    /// Asks the proxy to relay UDP datagrams.
+    ///
+    /// As the local address is not known yet, the proxy accepts datagrams from any address of
+    /// the client. Returns the control connection, which must be kept open as long as datagrams are
+    /// relayed, and the address of the relay.
+    pub fn associate(
+        &self,
+        timeout: Duration,
+        binding: &SourceBinding,
+    ) -> io::Result<(TcpStream, SocketAddr)> {
+        let mut tcp = self.open(timeout, binding)?;
+        let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        let mut relay = Self::request(&mut tcp, UDP_ASSOCIATE, &any)?;
+        if relay.ip().is_unspecified() {
+            relay.set_ip(tcp.peer_addr()?.ip());
+        }
+        Ok((tcp, relay))
+    }
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("SOCKS5 protocol error: {msg}"),
+    )
+}
+
+fn reply_error(code: u8) -> io::Error {
+    let (kind, msg) = match code {
+        2 => (
+            io::ErrorKind::PermissionDenied,
+            "connection not allowed by ruleset",
+        ),
+        3 => (io::ErrorKind::Other, "network unreachable"),
+        4 => (io::ErrorKind::Other, "host unreachable"),
+        5 => (io::ErrorKind::ConnectionRefused, "connection refused"),
+        6 => (io::ErrorKind::TimedOut, "TTL expired"),
+        7 => (io::ErrorKind::Unsupported, "command not supported"),
+        8 => (io::ErrorKind::Unsupported, "address type not supported"),
+        _ => (io::ErrorKind::Other, "general failure"),
+    };
+    io::Error::new(kind, format!("SOCKS5 proxy: {msg}"))
+}
+
+fn encode_address(addr: &SocketAddr, buf: &mut Vec<u8>) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            buf.push(IPV4);
+            buf.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            buf.push(IPV6);
+            buf.extend_from_slice(&ip.octets());
+        }
+    }
+    buf.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// Prepends the header of a datagram the relay sends to the target.
+pub fn encapsulate(target: &SocketAddr, data: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(MAX_UDP_HEADER + data.len());
+    // reserved and fragment number
+    datagram.extend_from_slice(&[0, 0, 0]);
+    encode_address(target, &mut datagram);
+    datagram.extend_from_slice(data);
+    datagram
+}
+
+/// Returns the origin and the payload of a datagram received from the relay.
+///
+/// Returns None for malformed datagrams and fragments, which are not supported.
+pub fn decapsulate(datagram: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    let (ip, rest) = match datagram {
+        [0, 0, 0, IPV4, rest @ ..] if rest.len() >= 6 => {
+            let (ip, rest) = rest.split_at(4);
+            (IpAddr::from(<[u8; 4]>::try_from(ip).ok()?), rest)
+        }
+        [0, 0, 0, IPV6, rest @ ..] if rest.len() >= 18 => {
+            let (ip, rest) = rest.split_at(16);
+            (
+                IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(ip).ok()?)),
+                rest,
+            )
+        }
+        _ => return None,
+    };
+    let (port, data) = rest.split_at(2);
+    Some((
+        SocketAddr::new(ip, u16::from_be_bytes([port[0], port[1]])),
+        data,
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{models::ScanPreference, nasl::utils::SourceBinding};
+
+    use super::{
+        decapsulate, encapsulate, SocksProxy, PASSWORD_PREFERENCE, PROXY_PREFERENCE,
+        USERNAME_PREFERENCE,
+    };
+
+    fn read_address(tcp: &mut TcpStream) -> SocketAddr {
+        let mut header = [0; 4];
+        tcp.read_exact(&mut header).unwrap();
+        let mut ip = [0; 4];
+        tcp.read_exact(&mut ip).unwrap();
+        let mut port = [0; 2];
+        tcp.read_exact(&mut port).unwrap();
+        SocketAddr::from((ip, u16::from_be_bytes(port)))
+    }
+
+    fn reply(tcp: &mut TcpStream, addr: SocketAddr) {
+        let mut reply = vec![5, 0, 0];
+        super::encode_address(&addr, &mut reply);
+        tcp.write_all(&reply).unwrap();
+    }
+
+    fn relay_udp(relay: UdpSocket) {
+        let mut client = None;
+        let mut buf = [0; 2048];
+        relay
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        while let Ok((size, origin)) = relay.recv_from(&mut buf) {
+            if client.is_none() || client == Some(origin) {
+                client = Some(origin);
+                let Some((target, data)) = decapsulate(&buf[..size]) else {
+                    continue;
+                };
+                relay.send_to(data, target).unwrap();
+            } else if let Some(client) = client {
+                relay
+                    .send_to(&encapsulate(&origin, &buf[..size]), client)
+                    .unwrap();
+            }
+        }
+    }
+
+    fn handle(mut tcp: TcpStream, credentials: Option<(&str, &str)>) {
+        let mut greeting = [0; 2];
+        tcp.read_exact(&mut greeting).unwrap();
+        let mut methods = vec![0; greeting[1] as usize];
+        tcp.read_exact(&mut methods).unwrap();
+        match credentials {
+            Some((username, password)) => {
+                tcp.write_all(&[5, 2]).unwrap();
+                let mut request = vec![];
+                let mut byte = [0; 1];
+                tcp.read_exact(&mut byte).unwrap();
+                for _ in 0..2 {
+                    tcp.read_exact(&mut byte).unwrap();
+                    let mut value = vec![0; byte[0] as usize];
+                    tcp.read_exact(&mut value).unwrap();
+                    request.push(String::from_utf8(value).unwrap());
+                }
+                if request != [username, password] {
+                    tcp.write_all(&[1, 1]).unwrap();
+                    return;
+                }
+                tcp.write_all(&[1, 0]).unwrap();
+            }
+            None => tcp.write_all(&[5, 0]).unwrap(),
+        }
+        let mut command = [0; 2];
+        tcp.peek(&mut command).unwrap();
+        let target = read_address(&mut tcp);
+        match command[1] {
+            1 => {
+                let Ok(remote) = TcpStream::connect(target) else {
+                    tcp.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+                    return;
+                };
+                reply(&mut tcp, remote.local_addr().unwrap());
+                let (mut a, mut b) = (tcp.try_clone().unwrap(), remote.try_clone().unwrap());
+                thread::spawn(move || std::io::copy(&mut a, &mut b));
+                let (mut a, mut b) = (remote, tcp);
+                let _ = std::io::copy(&mut a, &mut b);
+            }
+            3 => {
+                let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+                reply(&mut tcp, relay.local_addr().unwrap());
+                relay_udp(relay);
+            }
+            _ => tcp.write_all(&[5, 7, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap(),
+        }
+    }
+
+    /// Starts a SOCKS5 proxy on localhost supporting CONNECT and UDP ASSOCIATE.
+    pub fn socks_server(credentials: Option<(&'static str, &'static str)>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for tcp in listener.incoming() {
+                let tcp = tcp.unwrap();
+                thread::spawn(move || handle(tcp, credentials));
+            }
+        });
+        addr
+    }
+
+    fn preference(id: &str, value: &str) -> ScanPreference {
+        ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn from_preferences() {
+        assert_eq!(SocksProxy::from_preferences(&[]), None);
+        let proxy =
+            SocksProxy::from_preferences(&[preference(PROXY_PREFERENCE, "jump.example.com")])
+                .unwrap();
+        assert_eq!((proxy.host(), proxy.port()), ("jump.example.com", 1080));
+        let proxy = SocksProxy::from_preferences(&[
+            preference(PROXY_PREFERENCE, "[2001:db8::1]:9050"),
+            preference(USERNAME_PREFERENCE, "user"),
+            preference(PASSWORD_PREFERENCE, "secret"),
+        ])
+        .unwrap();
+        assert_eq!(
+            proxy,
+            SocksProxy::new(
+                "2001:db8::1",
+                9050,
+                Some(("user".to_string(), "secret".to_string()))
+            )
+        );
+        assert!(!format!("{proxy:?}").contains("secret"));
+    }
+
+    #[test]
+    fn connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            tcp.write_all(b"hello").unwrap();
+        });
+        let server = socks_server(Some(("user", "secret")));
+        let timeout = Duration::from_secs(5);
+        let binding = SourceBinding::default();
+        let proxy = |password: &str| {
+            SocksProxy::new(
+                "127.0.0.1",
+                server.port(),
+                Some(("user".to_string(), password.to_string())),
+            )
+        };
+        assert!(proxy("wrong").connect(&target, timeout, &binding).is_err());
+        let mut tcp = proxy("secret").connect(&target, timeout, &binding).unwrap();
+        let mut buf = [0; 5];
+        tcp.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[test]
+    fn associate() {
+        let target = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target_addr = target.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0; 16];
+            let (size, origin) = target.recv_from(&mut buf).unwrap();
+            target.send_to(&buf[..size], origin).unwrap();
+        });
+        let server = socks_server(None);
+        let proxy = SocksProxy::new("127.0.0.1", server.port(), None);
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (_control, relay) = proxy
+            .associate(Duration::from_secs(5), &SourceBinding::default())
+            .unwrap();
+        socket
+            .send_to(&encapsulate(&target_addr, b"ping"), relay)
+            .unwrap();
+        let mut buf = [0; 64];
+        let size = socket.recv(&mut buf).unwrap();
+        assert_eq!(decapsulate(&buf[..size]), Some((target_addr, &b"ping"[..])));
+    }
+}
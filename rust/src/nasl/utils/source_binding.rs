@@ -0,0 +1,150 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Binds outgoing connections to a network interface or source address.
+//!
+//! Scanner appliances with multiple network interfaces must be able to choose which interface
+//! or address a scan originates from. The binding is configured via the scan preferences
+//! [INTERFACE_PREFERENCE] and [ADDRESS_PREFERENCE] and honored by all socket and raw-IP
+//! builtins.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use socket2::{SockAddr, Socket};
+
+use crate::models::ScanPreference;
+
+/// Scan preference containing the name of the network interface to send from.
+pub const INTERFACE_PREFERENCE: &str = "source_iface";
+/// Scan preference containing the source addresses, at most one IPv4 and one IPv6 address
+/// separated by a comma.
+pub const ADDRESS_PREFERENCE: &str = "source_ip";
+
+/// Network interface and source addresses outgoing connections are bound to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceBinding {
+    interface: Option<String>,
+    addresses: Vec<IpAddr>,
+}
+
+impl SourceBinding {
+    /// Creates a new SourceBinding.
+    ///
+    /// Only the first address of each address family is used.
+    pub fn new(interface: Option<String>, addresses: Vec<IpAddr>) -> Self {
+        let mut result: Vec<IpAddr> = Vec::with_capacity(2);
+        for address in addresses {
+            if !result.iter().any(|x| x.is_ipv4() == address.is_ipv4()) {
+                result.push(address);
+            }
+        }
+        Self {
+            interface: interface.filter(|x| !x.is_empty()),
+            addresses: result,
+        }
+    }
+
+    /// Creates a SourceBinding based on the scan preferences.
+    ///
+    /// Invalid addresses are ignored.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let value = |id| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .map(|x| x.value.trim())
+                .filter(|x| !x.is_empty())
+        };
+        let addresses = value(ADDRESS_PREFERENCE)
+            .into_iter()
+            .flat_map(|x| x.split(','))
+            .filter_map(|x| match x.trim().parse() {
+                Ok(ip) => Some(ip),
+                Err(_) => {
+                    tracing::warn!(address = x, "ignoring invalid source address");
+                    None
+                }
+            })
+            .collect();
+        Self::new(
+            value(INTERFACE_PREFERENCE).map(|x| x.to_string()),
+            addresses,
+        )
+    }
+
+    /// Returns the name of the network interface
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    /// Returns the source address with the same address family as the destination.
+    pub fn address(&self, destination: &IpAddr) -> Option<IpAddr> {
+        self.addresses
+            .iter()
+            .find(|x| x.is_ipv4() == destination.is_ipv4())
+            .copied()
+    }
+
+    /// Binds the socket to the interface and the source address matching the destination.
+    pub fn bind(&self, socket: &Socket, destination: &IpAddr) -> io::Result<()> {
+        if let Some(interface) = &self.interface {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        if let Some(address) = self.address(destination) {
+            socket.bind(&SockAddr::from(SocketAddr::new(address, 0)))?;
+        }
+        Ok(())
+    }
+
+    /// Binds the socket to the interface and the given local port.
+    ///
+    /// The port is bound on the source address matching the destination or on the unspecified
+    /// address, when none is configured.
+    pub fn bind_port(&self, socket: &Socket, destination: &IpAddr, port: u16) -> io::Result<()> {
+        if let Some(interface) = &self.interface {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        let address = self.address(destination).unwrap_or(match destination {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        });
+        socket.bind(&SockAddr::from(SocketAddr::new(address, port)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::models::ScanPreference;
+
+    use super::{SourceBinding, ADDRESS_PREFERENCE, INTERFACE_PREFERENCE};
+
+    #[test]
+    fn from_preferences() {
+        let preferences = vec![
+            ScanPreference {
+                id: INTERFACE_PREFERENCE.to_string(),
+                value: "eth1".to_string(),
+            },
+            ScanPreference {
+                id: ADDRESS_PREFERENCE.to_string(),
+                value: "192.0.2.1, invalid, 2001:db8::1, 192.0.2.2".to_string(),
+            },
+        ];
+        let binding = SourceBinding::from_preferences(&preferences);
+        assert_eq!(binding.interface(), Some("eth1"));
+        let v4: IpAddr = "198.51.100.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::2".parse().unwrap();
+        assert_eq!(binding.address(&v4), "192.0.2.1".parse().ok());
+        assert_eq!(binding.address(&v6), "2001:db8::1".parse().ok());
+        assert_eq!(
+            SourceBinding::from_preferences(&[]),
+            SourceBinding::default()
+        );
+    }
+}
@@ -0,0 +1,232 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Experimental taint tracking of data received from the network.
+//!
+//! When the scan preference [TAINT_TRACKING_PREFERENCE] is enabled, the values returned by the
+//! [SOURCES] are marked as tainted. A call of one of the [SINKS] with an argument derived from
+//! tainted data is logged and attached to the results created afterwards, which helps to audit
+//! feed scripts that pass data controlled by the target into files or commands.
+//!
+//! Values do not carry a taint flag. Instead an argument is considered derived from tainted
+//! data when it contains a tainted value, e.g. after a concatenation, or when it is a part of
+//! a tainted value, e.g. after substr or split. Values shorter than [MIN_TAINT_LENGTH] are
+//! ignored as they would match nearly everything.
+
+use std::sync::Mutex;
+
+use crate::models::{self, ScanPreference};
+use crate::nasl::interpreter::{InterpretError, InterpreterHook};
+use crate::nasl::syntax::NaslValue;
+
+use super::{error::FunctionErrorKind, ContextType, Register};
+
+/// Scan preference enabling the taint tracking.
+pub const TAINT_TRACKING_PREFERENCE: &str = "taint_tracking";
+
+/// Builtins returning data controlled by the target
+pub const SOURCES: &[&str] = &[
+    "recv",
+    "recv_line",
+    "http2_req",
+    "send_packet",
+    "send_capture",
+    "pcap_next",
+    "ssh_request_exec",
+    "ssh_shell_read",
+    "ssh_execute_netconf_subsystem",
+];
+
+/// Builtins accessing files or executing commands
+pub const SINKS: &[&str] = &[
+    "pread",
+    "fwrite",
+    "fread",
+    "file_open",
+    "file_write",
+    "unlink",
+    "find_in_path",
+    "ssh_cmd",
+    "ssh_request_exec",
+    "ssh_shell_write",
+    "win_cmd_exec",
+];
+
+/// Minimal length of tainted values and of arguments compared to them
+pub const MIN_TAINT_LENGTH: usize = 4;
+
+/// Marks values of sources and records their flows into sinks
+#[derive(Debug, Default)]
+pub struct TaintTracker {
+    /// Tainted values with the source they were returned by
+    tainted: Mutex<Vec<(String, Vec<u8>)>>,
+    flows: Mutex<Vec<models::TaintFlow>>,
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|x| x == needle)
+}
+
+impl TaintTracker {
+    /// Returns true when the taint tracking is enabled in the scan preferences.
+    pub fn enabled_by_preferences(preferences: &[ScanPreference]) -> bool {
+        preferences
+            .iter()
+            .find(|x| x.id == TAINT_TRACKING_PREFERENCE)
+            .is_some_and(|x| matches!(x.value.trim().to_lowercase().as_str(), "yes" | "true" | "1"))
+    }
+
+    /// Marks a value and all its elements as tainted by the given source.
+    pub fn mark(&self, source: &str, value: &NaslValue) {
+        match value {
+            NaslValue::String(_) | NaslValue::Data(_) => {
+                let data = Vec::<u8>::from(value);
+                if data.len() >= MIN_TAINT_LENGTH {
+                    let mut tainted = self.tainted.lock().unwrap();
+                    if !tainted.iter().any(|(_, x)| x == &data) {
+                        tainted.push((source.to_string(), data));
+                    }
+                }
+            }
+            NaslValue::Array(x) => x.iter().for_each(|x| self.mark(source, x)),
+            NaslValue::Dict(x) => x.values().for_each(|x| self.mark(source, x)),
+            _ => {}
+        }
+    }
+
+    /// Returns the source of the tainted data the value is derived from.
+    pub fn origin(&self, value: &NaslValue) -> Option<String> {
+        match value {
+            NaslValue::String(_) | NaslValue::Data(_) => {
+                let data = Vec::<u8>::from(value);
+                if data.len() < MIN_TAINT_LENGTH {
+                    return None;
+                }
+                self.tainted
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|(_, x)| contains(&data, x) || contains(x, &data))
+                    .map(|(source, _)| source.clone())
+            }
+            NaslValue::Array(x) => x.iter().find_map(|x| self.origin(x)),
+            NaslValue::Dict(x) => x.values().find_map(|x| self.origin(x)),
+            _ => None,
+        }
+    }
+
+    /// Returns the flows into sinks observed until now.
+    pub fn flows(&self) -> Vec<models::TaintFlow> {
+        self.flows.lock().unwrap().clone()
+    }
+
+    fn record(&self, flow: models::TaintFlow) {
+        let mut flows = self.flows.lock().unwrap();
+        if !flows.contains(&flow) {
+            tracing::warn!(
+                source = flow.source,
+                sink = flow.sink,
+                argument = flow.argument,
+                "tainted data flows into sink"
+            );
+            flows.push(flow);
+        }
+    }
+}
+
+impl InterpreterHook for TaintTracker {
+    fn before_builtin(&self, name: &str, register: &Register) -> Result<(), Box<InterpretError>> {
+        if !SINKS.contains(&name) {
+            return Ok(());
+        }
+        let named = register
+            .iter_named_args()
+            .into_iter()
+            .flatten()
+            .filter(|x| *x != super::lookup_keys::FC_ANON_ARGS)
+            .filter_map(|x| match register.named(x) {
+                Some(ContextType::Value(value)) => Some((x.to_string(), value)),
+                _ => None,
+            });
+        let positional = register
+            .positional()
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (i.to_string(), x));
+        for (argument, value) in named.chain(positional) {
+            if let Some(source) = self.origin(value) {
+                self.record(models::TaintFlow {
+                    source,
+                    sink: name.to_string(),
+                    argument,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn after_builtin(&self, name: &str, result: &mut Result<NaslValue, FunctionErrorKind>) {
+        if let (true, Ok(value)) = (SOURCES.contains(&name), result) {
+            self.mark(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{ScanPreference, TaintFlow};
+    use crate::nasl::interpreter::InterpreterHook;
+    use crate::nasl::syntax::NaslValue;
+    use crate::nasl::utils::{ContextType, Register};
+
+    use super::TaintTracker;
+
+    #[test]
+    fn enabled_by_preferences() {
+        let preference = |value: &str| {
+            vec![ScanPreference {
+                id: super::TAINT_TRACKING_PREFERENCE.to_string(),
+                value: value.to_string(),
+            }]
+        };
+        assert!(TaintTracker::enabled_by_preferences(&preference("yes")));
+        assert!(!TaintTracker::enabled_by_preferences(&preference("no")));
+        assert!(!TaintTracker::enabled_by_preferences(&[]));
+    }
+
+    #[test]
+    fn propagation() {
+        let tracker = TaintTracker::default();
+        tracker.mark("recv", &NaslValue::Data(b"HTTP/1.1 200 OK".to_vec()));
+        tracker.mark("recv", &NaslValue::String("ok".to_string()));
+        let origin = |x: &str| tracker.origin(&NaslValue::String(x.to_string()));
+        assert_eq!(origin("/tmp/HTTP/1.1 200 OK"), Some("recv".to_string()));
+        assert_eq!(origin("1.1 200"), Some("recv".to_string()));
+        assert_eq!(origin("ok"), None);
+        assert_eq!(origin("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn hook() {
+        let tracker = TaintTracker::default();
+        let mut result = Ok(NaslValue::String("evil; rm -rf /".to_string()));
+        tracker.after_builtin("recv", &mut result);
+        let register = Register::root_initial(&[(
+            "cmd".to_string(),
+            ContextType::Value(NaslValue::String("echo evil; rm -rf /".to_string())),
+        )]);
+        tracker.before_builtin("strlen", &register).unwrap();
+        assert!(tracker.flows().is_empty());
+        tracker.before_builtin("pread", &register).unwrap();
+        tracker.before_builtin("pread", &register).unwrap();
+        assert_eq!(
+            tracker.flows(),
+            vec![TaintFlow {
+                source: "recv".to_string(),
+                sink: "pread".to_string(),
+                argument: "cmd".to_string(),
+            }]
+        );
+    }
+}
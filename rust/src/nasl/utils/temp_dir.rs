@@ -0,0 +1,243 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Temporary directory shared by all scripts of a scan.
+//!
+//! File builtins like fwrite do not write to arbitrary paths of the scanner host. Instead each
+//! scan gets its own directory below the base directory configured by [TEMP_DIR_PREFERENCE],
+//! so that files of concurrent scans do not collide. The directory is created on first use and
+//! removed with everything in it once the scan is finished, i.e. when the last clone of the
+//! [TempDir] is dropped.
+//!
+//! The size of the files written is limited by [TEMP_DIR_MAX_SIZE_PREFERENCE] in MiB, so that a
+//! script cannot fill the disk of the scanner host.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::models::ScanPreference;
+
+/// Scan preference containing the directory the temporary directories of scans are created in
+pub const TEMP_DIR_PREFERENCE: &str = "temp_dir";
+/// Scan preference containing the maximum size of the files in the directory in MiB
+pub const TEMP_DIR_MAX_SIZE_PREFERENCE: &str = "temp_dir_max_size";
+
+/// Default maximum size of the files in the directory
+pub const DEFAULT_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug)]
+struct Workspace {
+    base: PathBuf,
+    max_size: u64,
+    path: OnceLock<PathBuf>,
+    /// Size of each file written
+    sizes: Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.get() {
+            if let Err(e) = fs::remove_dir_all(path) {
+                tracing::warn!(path = %path.display(), %e, "unable to remove temporary directory");
+            }
+        }
+    }
+}
+
+/// Temporary directory of a scan
+///
+/// Clones share the directory.
+#[derive(Debug, Clone)]
+pub struct TempDir {
+    workspace: Arc<Workspace>,
+}
+
+impl Default for TempDir {
+    fn default() -> Self {
+        Self::new(std::env::temp_dir(), DEFAULT_MAX_SIZE)
+    }
+}
+
+impl TempDir {
+    /// Creates a directory below base on first use holding at most max_size bytes.
+    pub fn new(base: impl Into<PathBuf>, max_size: u64) -> Self {
+        Self {
+            workspace: Arc::new(Workspace {
+                base: base.into(),
+                max_size,
+                path: OnceLock::new(),
+                sizes: Mutex::default(),
+            }),
+        }
+    }
+
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let find = |id| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .map(|x| x.value.trim())
+                .filter(|x| !x.is_empty())
+        };
+        let base = find(TEMP_DIR_PREFERENCE)
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let max_size = find(TEMP_DIR_MAX_SIZE_PREFERENCE).and_then(|x| match x.parse::<u64>() {
+            Ok(mib) => Some(mib.saturating_mul(1024 * 1024)),
+            Err(_) => {
+                tracing::warn!(value = x, "ignoring invalid temporary directory size");
+                None
+            }
+        });
+        Self::new(base, max_size.unwrap_or(DEFAULT_MAX_SIZE))
+    }
+
+    /// Returns the directory, creating it on first use.
+    pub fn path(&self) -> io::Result<&Path> {
+        if let Some(path) = self.workspace.path.get() {
+            return Ok(path);
+        }
+        let path = self.create()?;
+        // when another thread was faster, the directory created here is not used
+        if let Err(path) = self.workspace.path.set(path) {
+            let _ = fs::remove_dir(path);
+        }
+        Ok(self.workspace.path.get().unwrap())
+    }
+
+    fn create(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.workspace.base)?;
+        let path = self
+            .workspace
+            .base
+            .join(format!("openvas-{}", uuid::Uuid::new_v4()));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&path)?;
+        Ok(path)
+    }
+
+    /// Returns the path of a file in the directory.
+    ///
+    /// Relative names are resolved in the directory. Absolute paths, e.g. ones built from the
+    /// directory returned by get_tmp_dir, must point into it.
+    pub fn resolve(&self, name: &str) -> io::Result<PathBuf> {
+        let root = self.path()?;
+        let name = Path::new(name);
+        let relative = if name.is_absolute() {
+            name.strip_prefix(root).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} is outside of the temporary directory", name.display()),
+                )
+            })?
+        } else {
+            name
+        };
+        if relative
+            .components()
+            .any(|x| !matches!(x, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is outside of the temporary directory", name.display()),
+            ));
+        }
+        if relative.as_os_str().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the temporary directory is not a file",
+            ));
+        }
+        Ok(root.join(relative))
+    }
+
+    /// Writes the file, replacing an existing one, and returns the number of bytes written.
+    ///
+    /// Fails when the files in the directory would exceed the maximum size.
+    pub fn write(&self, name: &str, data: &[u8]) -> io::Result<usize> {
+        let path = self.resolve(name)?;
+        let mut sizes = self.workspace.sizes.lock().unwrap();
+        let used: u64 = sizes
+            .iter()
+            .filter(|(x, _)| **x != path)
+            .map(|(_, size)| size)
+            .sum();
+        if used + data.len() as u64 > self.workspace.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::StorageFull,
+                format!(
+                    "writing {} exceeds the maximum size of the temporary directory",
+                    path.display()
+                ),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, data)?;
+        sizes.insert(path, data.len() as u64);
+        Ok(data.len())
+    }
+
+    /// Reads the whole file.
+    pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.resolve(name)?)
+    }
+
+    /// Removes the file.
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        let path = self.resolve(name)?;
+        fs::remove_file(&path)?;
+        self.workspace.sizes.lock().unwrap().remove(&path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::TempDir;
+
+    #[test]
+    fn confined_and_cleaned() {
+        let base = std::env::temp_dir().join(format!("temp-dir-test-{}", std::process::id()));
+        let first = TempDir::new(&base, 8);
+        let second = TempDir::new(&base, 8);
+        let root = first.path().unwrap().to_path_buf();
+        assert_ne!(root, second.path().unwrap());
+
+        assert_eq!(first.write("a", b"1234").unwrap(), 4);
+        let absolute = root.join("dir/b").display().to_string();
+        assert_eq!(first.write(&absolute, b"5678").unwrap(), 4);
+        assert_eq!(first.read("dir/b").unwrap(), b"5678");
+        assert_eq!(
+            first.write("c", b"9").unwrap_err().kind(),
+            io::ErrorKind::StorageFull
+        );
+        // replacing a file only counts its new size
+        assert_eq!(first.write("a", b"12").unwrap(), 2);
+        first.remove("dir/b").unwrap();
+        assert_eq!(first.write("c", b"123456").unwrap(), 6);
+        assert!(second.read("a").is_err());
+
+        for name in ["../escape", "/etc/passwd", ""] {
+            assert!(first.write(name, b"x").is_err(), "{name}");
+        }
+
+        let clone = first.clone();
+        drop(first);
+        assert!(root.exists());
+        drop(clone);
+        assert!(!root.exists());
+        drop(second);
+        std::fs::remove_dir(base).unwrap();
+    }
+}
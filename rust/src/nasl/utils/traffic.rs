@@ -0,0 +1,312 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Recording and replaying of the network traffic of a script.
+//!
+//! When recording, the data sent and received on each socket opened by the socket builtins is
+//! captured in a [Recording]. The recording is stored as JSON:
+//!
+//! ```json
+//! {
+//!   "connections": [
+//!     {
+//!       "transport": "tcp",
+//!       "port": 80,
+//!       "exchanges": [{ "sent": "R0VUIC8gSFRUUC8xLjANCg0K" }, { "received": "SFRUUC8xLjAgMjAw" }]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Data is base64 encoded, as it is usually not UTF-8. When replaying, no connection is opened.
+//! Instead each socket opened to a port gets the next recorded connection with the same
+//! transport and port, so that a detection can be tested against responses captured in a live
+//! scan.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Transport protocol of a recorded connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+/// Data sent or received at once, serialized as base64
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload(pub Vec<u8>);
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD
+            .decode(encoded)
+            .map(Payload)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A request sent or a response received on a connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Exchange {
+    Sent(Payload),
+    Received(Payload),
+}
+
+/// The traffic of a single socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connection {
+    pub transport: Transport,
+    pub port: u16,
+    pub exchanges: Vec<Exchange>,
+}
+
+/// The traffic of all sockets in the order they were opened
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    pub connections: Vec<Connection>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// Records the traffic of a script or replays a recording
+///
+/// Clones share the recording.
+#[derive(Debug, Clone)]
+pub struct Traffic {
+    mode: Mode,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl Traffic {
+    /// Creates an empty recording of the traffic.
+    pub fn record() -> Self {
+        Self {
+            mode: Mode::Record,
+            recording: Arc::default(),
+        }
+    }
+
+    /// Replays the recorded traffic instead of connecting to the target.
+    pub fn replay(recording: Recording) -> Self {
+        Self {
+            mode: Mode::Replay,
+            recording: Arc::new(Mutex::new(recording)),
+        }
+    }
+
+    /// Returns true when the traffic is replayed
+    pub fn is_replay(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    /// Returns the recorded traffic or, when replaying, the connections not replayed yet.
+    pub fn recording(&self) -> Recording {
+        self.recording.lock().unwrap().clone()
+    }
+
+    /// Starts recording a connection and returns its index.
+    ///
+    /// Returns None when replaying.
+    pub fn opened(&self, transport: Transport, port: u16) -> Option<usize> {
+        if self.is_replay() {
+            return None;
+        }
+        let mut recording = self.recording.lock().unwrap();
+        recording.connections.push(Connection {
+            transport,
+            port,
+            exchanges: vec![],
+        });
+        Some(recording.connections.len() - 1)
+    }
+
+    fn push(&self, connection: usize, exchange: Exchange) {
+        if let Some(connection) = self
+            .recording
+            .lock()
+            .unwrap()
+            .connections
+            .get_mut(connection)
+        {
+            connection.exchanges.push(exchange);
+        }
+    }
+
+    /// Records data sent on the connection.
+    pub fn sent(&self, connection: usize, data: &[u8]) {
+        self.push(connection, Exchange::Sent(Payload(data.to_vec())));
+    }
+
+    /// Records data received on the connection, nothing received is not recorded.
+    pub fn received(&self, connection: usize, data: &[u8]) {
+        if !data.is_empty() {
+            self.push(connection, Exchange::Received(Payload(data.to_vec())));
+        }
+    }
+
+    /// Takes the next recorded connection with the transport and port.
+    ///
+    /// Returns None when recording or when no such connection is left.
+    pub fn next_connection(&self, transport: Transport, port: u16) -> Option<ReplayedConnection> {
+        if !self.is_replay() {
+            return None;
+        }
+        let mut recording = self.recording.lock().unwrap();
+        let index = recording
+            .connections
+            .iter()
+            .position(|x| x.transport == transport && x.port == port)?;
+        let connection = recording.connections.remove(index);
+        Some(ReplayedConnection {
+            transport,
+            exchanges: connection.exchanges.into(),
+        })
+    }
+}
+
+/// A connection returning the recorded responses
+///
+/// Reading returns the received data until the next request was sent, after that nothing is
+/// read. Sending data that differs from the next recorded request fails, so that a replay does
+/// not silently diverge from the recording.
+#[derive(Debug)]
+pub struct ReplayedConnection {
+    transport: Transport,
+    exchanges: VecDeque<Exchange>,
+}
+
+impl ReplayedConnection {
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns true when every recorded exchange was replayed
+    pub fn is_finished(&self) -> bool {
+        self.exchanges.is_empty()
+    }
+}
+
+impl Read for ReplayedConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for ReplayedConnection {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self.exchanges.front() {
+            Some(Exchange::Received(data)) => Ok(&data.0),
+            _ => Ok(&[]),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(Exchange::Received(data)) = self.exchanges.front_mut() {
+            data.0.drain(..amt.min(data.0.len()));
+            if data.0.is_empty() {
+                self.exchanges.pop_front();
+            }
+        }
+    }
+}
+
+impl Write for ReplayedConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.exchanges.front() {
+            Some(Exchange::Sent(data)) if data.0 == buf => {
+                self.exchanges.pop_front();
+                Ok(buf.len())
+            }
+            Some(Exchange::Sent(data)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "sent data differs from the recording, expected {:?}",
+                    String::from_utf8_lossy(&data.0)
+                ),
+            )),
+            Some(Exchange::Received(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "data was sent before the recorded response was read",
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no more data was sent in the recording",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read, Write};
+
+    use super::{Recording, Traffic, Transport};
+
+    #[test]
+    fn record_and_replay() {
+        let traffic = Traffic::record();
+        let web = traffic.opened(Transport::Tcp, 80).unwrap();
+        let dns = traffic.opened(Transport::Udp, 53).unwrap();
+        traffic.sent(web, b"GET / HTTP/1.0\r\n\r\n");
+        traffic.received(web, b"HTTP/1.0 200 OK\r\n");
+        traffic.received(web, b"");
+        traffic.received(web, b"Server: test\r\n");
+        traffic.sent(dns, b"query");
+
+        let json = serde_json::to_string(&traffic.recording()).unwrap();
+        assert!(json.contains(r#""transport":"tcp","port":80"#));
+        let recording: Recording = serde_json::from_str(&json).unwrap();
+        assert_eq!(recording, traffic.recording());
+        assert_eq!(recording.connections[0].exchanges.len(), 3);
+
+        let replay = Traffic::replay(recording);
+        assert!(replay.opened(Transport::Tcp, 80).is_none());
+        assert!(replay.next_connection(Transport::Udp, 80).is_none());
+        let mut conn = replay.next_connection(Transport::Tcp, 80).unwrap();
+        let mut buf = [0; 4];
+        // nothing is received before the request is sent
+        assert_eq!(conn.read(&mut buf).unwrap(), 0);
+        assert!(conn.write(b"GET / HTTP/1.1\r\n\r\n").is_err());
+        conn.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(conn.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"HTTP");
+        let mut line = String::new();
+        conn.read_line(&mut line).unwrap();
+        assert_eq!(line, "/1.0 200 OK\r\n");
+        line.clear();
+        conn.read_line(&mut line).unwrap();
+        assert_eq!(line, "Server: test\r\n");
+        assert!(conn.is_finished());
+        assert!(conn.write(b"more").is_err());
+        assert_eq!(replay.recording().connections.len(), 1);
+    }
+}
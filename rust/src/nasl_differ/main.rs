@@ -0,0 +1,165 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#![doc = include_str!("README.md")]
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// A line of output that differs between both implementations
+#[derive(Debug, PartialEq, Eq)]
+struct Divergence {
+    line: usize,
+    openvas: Option<String>,
+    scannerctl: Option<String>,
+}
+
+/// Compares the output of both implementations line by line.
+///
+/// Trailing whitespace is ignored, as the C implementation does not end the output of display
+/// consistently with a newline.
+fn diverging_lines(openvas: &str, scannerctl: &str) -> Vec<Divergence> {
+    let openvas: Vec<&str> = openvas.trim_end().lines().map(str::trim_end).collect();
+    let scannerctl: Vec<&str> = scannerctl.trim_end().lines().map(str::trim_end).collect();
+    (0..openvas.len().max(scannerctl.len()))
+        .filter_map(|i| {
+            let left = openvas.get(i);
+            let right = scannerctl.get(i);
+            (left != right).then(|| Divergence {
+                line: i + 1,
+                openvas: left.map(|x| x.to_string()),
+                scannerctl: right.map(|x| x.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Runs the program and returns its standard output.
+///
+/// The output is converted byte by byte, as scripts may display data that is not UTF-8.
+fn run(program: &Path, args: &[&OsStr]) -> String {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .unwrap_or_else(|e| panic!("{} should be executable: {e}", program.display()));
+    if !output.status.success() {
+        eprintln!(
+            "{} exited with {}: {}",
+            program.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    output.stdout.iter().map(|x| *x as char).collect()
+}
+
+/// Returns the snippets given as arguments, directories are searched for .nasl files.
+fn snippets(args: impl Iterator<Item = String>) -> Vec<PathBuf> {
+    let mut result = vec![];
+    for arg in args {
+        let path = PathBuf::from(arg);
+        if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(&path)
+                .unwrap_or_else(|e| panic!("{} should be readable: {e}", path.display()))
+                .filter_map(|x| x.ok().map(|x| x.path()))
+                .filter(|x| x.extension().is_some_and(|x| x == "nasl"))
+                .collect();
+            files.sort();
+            result.extend(files);
+        } else {
+            result.push(path);
+        }
+    }
+    result
+}
+
+fn main() {
+    let openvas_nasl =
+        PathBuf::from(env::var("OPENVAS_NASL").unwrap_or_else(|_| "openvas-nasl".to_string()));
+    let scannerctl = match env::current_exe() {
+        Ok(mut x) => {
+            x.pop();
+            x.push("scannerctl");
+            x
+        }
+        Err(x) => panic!("This test program is assuming that scannerctl is in the same dir: {x}"),
+    };
+    let target = env::var("NASL_DIFFER_TARGET").unwrap_or_else(|_| "127.0.0.1".to_string());
+
+    let snippets = snippets(env::args().skip(1));
+    if snippets.is_empty() {
+        eprintln!("usage: nasl-differ <SNIPPET|DIRECTORY>...");
+        process::exit(2);
+    }
+
+    let mut errors = 0;
+    for snippet in &snippets {
+        let openvas = run(
+            &openvas_nasl,
+            &[
+                OsStr::new("-X"),
+                OsStr::new("-t"),
+                OsStr::new(&target),
+                snippet.as_os_str(),
+            ],
+        );
+        let rust = run(
+            &scannerctl,
+            &[
+                OsStr::new("execute"),
+                OsStr::new("script"),
+                OsStr::new("-t"),
+                OsStr::new(&target),
+                snippet.as_os_str(),
+            ],
+        );
+        let divergences = diverging_lines(&openvas, &rust);
+        if divergences.is_empty() {
+            println!("{}: ok", snippet.display());
+            continue;
+        }
+        errors += 1;
+        println!("{}: {} lines differ", snippet.display(), divergences.len());
+        for d in divergences {
+            println!(
+                "  {}:\n    openvas-nasl: {}\n    scannerctl:   {}",
+                d.line,
+                d.openvas.as_deref().unwrap_or("<missing>"),
+                d.scannerctl.as_deref().unwrap_or("<missing>")
+            );
+        }
+    }
+    println!("{errors} of {} snippets diverge", snippets.len());
+    process::exit(errors);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diverging_lines, Divergence};
+
+    #[test]
+    fn divergences() {
+        assert!(diverging_lines("a\nb\n", "a  \nb").is_empty());
+        assert_eq!(
+            diverging_lines("1\n2\n3\n", "1\n0\n"),
+            vec![
+                Divergence {
+                    line: 2,
+                    openvas: Some("2".to_string()),
+                    scannerctl: Some("0".to_string()),
+                },
+                Divergence {
+                    line: 3,
+                    openvas: Some("3".to_string()),
+                    scannerctl: None,
+                },
+            ]
+        );
+    }
+}
@@ -340,7 +340,17 @@ where
     }
 
     async fn prepare_host_options_for_openvas(&mut self) -> RedisStorageResult<()> {
-        let excluded_hosts = self.scan_config.target.excluded_hosts.join(",");
+        // openvas does not understand patterns like `*.example.com`, hosts matching them are
+        // removed from the target before the scan is started
+        let excluded_hosts = self
+            .scan_config
+            .target
+            .excluded_hosts
+            .iter()
+            .filter(|x| !x.trim().starts_with("*."))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(",");
         if excluded_hosts.is_empty() {
             return Ok(());
         }
@@ -519,6 +529,11 @@ where
                         ));
                     }
                 }
+
+                Service::TLS => {
+                    // openvas has no preference for client certificates
+                    tracing::warn!("ignoring TLS client certificate, not supported by openvas");
+                }
             }
         }
 
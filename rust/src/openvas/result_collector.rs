@@ -259,6 +259,10 @@ mod tests {
             protocol: None,
             message: Some("HOST_START".to_string()),
             detail: None,
+            stats: None,
+            taint_flows: None,
+            labels: None,
+            severity: None,
         };
         assert_eq!(
             models::Result::from(
@@ -283,6 +287,10 @@ mod tests {
             protocol: None,
             message: Some("NVT timeout".to_string()),
             detail: None,
+            stats: None,
+            taint_flows: None,
+            labels: None,
+            severity: None,
         };
         assert_eq!(
             models::Result::from(
@@ -307,6 +315,10 @@ mod tests {
             protocol: Some(Protocol::TCP),
             message: Some("Something wrong".to_string()),
             detail: None,
+            stats: None,
+            taint_flows: None,
+            labels: None,
+            severity: None,
         };
         assert_eq!(
             models::Result::from(
@@ -0,0 +1,239 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Creates support bundles of a scan.
+//!
+//! A bundle is a gzip compressed tar archive meant to be attached to bug reports. It contains:
+//! - `scan.json` the scan configuration without any secrets of the credentials, the scan
+//!   preferences and the password preferences of the VTs
+//! - `status.json` the current status of the scan
+//! - `journal.json` the events the scheduler recorded for the scan
+//! - `errors.json` the error results reported by the scripts
+//! - `environment.json` the versions of the scanner and the feed as well as the platform
+
+use std::collections::{HashMap, HashSet};
+
+use flate2::{write::GzEncoder, Compression};
+use scannerlib::models::{self, CredentialType, ResultType, Scan};
+use scannerlib::nasl::utils::SECRET_PREFERENCES;
+use scannerlib::storage::item::PreferenceType;
+use serde::Serialize;
+
+use crate::{
+    config,
+    scheduling::Scheduler,
+    storage::{Error, NVTStorer, ProgressGetter, Storage},
+};
+
+pub const CONTENT_TYPE: &str = "application/gzip";
+
+const REDACTED: &str = "***";
+
+#[derive(Serialize, Debug)]
+struct Environment<'a> {
+    scanner_version: &'a str,
+    feed_version: String,
+    mode: &'a config::Mode,
+    os: &'a str,
+    arch: &'a str,
+}
+
+/// Password preferences of VTs by OID, None for VTs without metadata
+type PasswordPreferences = HashMap<String, Option<HashSet<u16>>>;
+
+/// Returns the ids of the password preferences of the VTs of a scan that have parameters.
+async fn password_preferences<T: NVTStorer + Sync>(
+    storage: &T,
+    scan: &Scan,
+) -> Result<PasswordPreferences, Error> {
+    let mut result = HashMap::new();
+    for vt in scan.vts.iter().filter(|x| !x.parameters.is_empty()) {
+        let ids = storage.vt_by_oid(&vt.oid).await?.map(|nvt| {
+            nvt.preferences
+                .iter()
+                .filter(|x| x.class == PreferenceType::Password)
+                .filter_map(|x| x.id.and_then(|id| u16::try_from(id).ok()))
+                .collect()
+        });
+        result.insert(vt.oid.clone(), ids);
+    }
+    Ok(result)
+}
+
+/// Removes all secrets from the credentials, the scan preferences and the VT parameters of a
+/// scan.
+///
+/// All parameters of a VT without metadata are removed, as their types are unknown.
+fn sanitize(mut scan: Scan, passwords: &PasswordPreferences) -> Scan {
+    for preference in scan
+        .scan_preferences
+        .iter_mut()
+        .filter(|x| SECRET_PREFERENCES.contains(&x.id.as_str()))
+    {
+        preference.value = REDACTED.to_string();
+    }
+    for vt in scan.vts.iter_mut() {
+        let ids = passwords.get(&vt.oid).and_then(|x| x.as_ref());
+        for parameter in vt
+            .parameters
+            .iter_mut()
+            .filter(|x| ids.is_none_or(|ids| ids.contains(&x.id)))
+        {
+            parameter.value = REDACTED.to_string();
+        }
+    }
+    scan.target.credentials = scan
+        .target
+        .credentials
+        .into_iter()
+        .map(|c| {
+            let mut c = c
+                .map_password::<_, Error>(|_| Ok(REDACTED.to_string()))
+                .expect("redacting is infallible");
+            if let CredentialType::SNMP { community, .. } = &mut c.credential_type {
+                *community = REDACTED.to_string();
+            }
+            c
+        })
+        .collect();
+    scan
+}
+
+/// Creates a gzip compressed tar archive of the given files.
+fn archive(files: &[(&str, Vec<u8>)], mtime: u64) -> std::io::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, data) in files {
+        let mut header = tar::Header::new_ustar();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+    builder.into_inner()?.finish()
+}
+
+/// Creates the support bundle of the scan with the given id.
+pub async fn create<DB, S>(
+    scheduler: &Scheduler<DB, S>,
+    mode: &config::Mode,
+    id: &str,
+) -> Result<Vec<u8>, Error>
+where
+    DB: Storage + Send + Sync + 'static,
+    S: Send + Sync,
+{
+    let (scan, status) = scheduler.get_scan(id).await?;
+    let passwords = password_preferences(scheduler, &scan).await?;
+    let journal = scheduler.journal(id).await;
+    let errors = scheduler
+        .get_results(id, None, None)
+        .await?
+        .filter_map(|x| serde_json::from_slice::<models::Result>(&x).ok())
+        .filter(|x| x.r_type == ResultType::Error)
+        .collect::<Vec<_>>();
+    let environment = Environment {
+        scanner_version: env!("CARGO_PKG_VERSION"),
+        feed_version: scheduler
+            .feed_version()
+            .read()
+            .map(|x| x.clone())
+            .unwrap_or_default(),
+        mode,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+    };
+
+    let files = [
+        (
+            "scan.json",
+            serde_json::to_vec_pretty(&sanitize(scan, &passwords))?,
+        ),
+        ("status.json", serde_json::to_vec_pretty(&status)?),
+        ("journal.json", serde_json::to_vec_pretty(&journal)?),
+        ("errors.json", serde_json::to_vec_pretty(&errors)?),
+        ("environment.json", serde_json::to_vec_pretty(&environment)?),
+    ];
+    let mtime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+    archive(&files, mtime).map_err(|e| Error::Storage(Box::new(e)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use scannerlib::models::{Parameter, Scan, VT};
+
+    /// Returns the names and contents of the files within a gzip compressed tar archive.
+    pub fn unpack(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut data = vec![];
+                entry.read_to_end(&mut data).unwrap();
+                (name, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn archive() {
+        let data = vec![b'a'; 513];
+        let files = [("a.txt", data.clone()), ("empty.txt", vec![])];
+        let archive = super::archive(&files, 0).unwrap();
+        assert_eq!(
+            unpack(&archive),
+            vec![
+                ("a.txt".to_string(), data),
+                ("empty.txt".to_string(), vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitize_vt_parameters() {
+        let parameter = |id, value: &str| Parameter {
+            id,
+            value: value.to_string(),
+        };
+        let scan = Scan {
+            vts: vec![
+                VT {
+                    oid: "1.3.6.1.4.1.25623.1.0.10".to_string(),
+                    parameters: vec![parameter(1, "admin"), parameter(2, "secret")],
+                },
+                VT {
+                    oid: "1.3.6.1.4.1.25623.1.0.20".to_string(),
+                    parameters: vec![parameter(1, "unknown")],
+                },
+            ],
+            ..Default::default()
+        };
+        let passwords = HashMap::from([
+            (
+                "1.3.6.1.4.1.25623.1.0.10".to_string(),
+                Some(HashSet::from([2])),
+            ),
+            ("1.3.6.1.4.1.25623.1.0.20".to_string(), None),
+        ]);
+        let scan = super::sanitize(scan, &passwords);
+        let values = |i: usize| {
+            scan.vts[i]
+                .parameters
+                .iter()
+                .map(|x| x.value.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(0), vec!["admin", super::REDACTED]);
+        assert_eq!(values(1), vec![super::REDACTED]);
+    }
+}
@@ -10,13 +10,25 @@ use std::{
 };
 
 use clap::{builder::TypedValueParser, ArgAction};
+use scannerlib::{
+    feed::ValidationMode,
+    logging::{syslog::Syslog, LogFormat},
+};
 use serde::{Deserialize, Serialize};
 
+use crate::{coordinator::Worker, deny_list::DenyList, export::Exporter, s3::S3};
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Feed {
     pub path: PathBuf,
     pub check_interval: Duration,
     pub signature_check: bool,
+    /// Directory to persist the description results of unchanged scripts
+    #[serde(default)]
+    pub description_cache: Option<PathBuf>,
+    /// Handling of scripts with invalid metadata
+    #[serde(default)]
+    pub validation: ValidationMode,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -58,6 +70,9 @@ pub struct Scanner {
     pub scanner_type: ScannerType,
     #[serde(default)]
     pub ospd: OspdWrapper,
+    /// Instances the scans are distributed to by the coordinator
+    #[serde(default)]
+    pub workers: Vec<Worker>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -68,6 +83,8 @@ pub enum ScannerType {
     Openvas,
     #[serde(rename = "openvasd")]
     Openvasd,
+    #[serde(rename = "coordinator")]
+    Coordinator,
 }
 
 impl Default for ScannerType {
@@ -89,6 +106,7 @@ impl TypedValueParser for ScannerType {
             "ospd" => ScannerType::OSPD,
             "openvas" => ScannerType::Openvas,
             "openvasd" => ScannerType::Openvasd,
+            "coordinator" => ScannerType::Coordinator,
             x => {
                 let mut cmd = cmd.clone();
                 let err = cmd.error(
@@ -122,6 +140,8 @@ impl Default for Feed {
             path: PathBuf::from("/var/lib/openvas/plugins"),
             check_interval: Duration::from_secs(3600),
             signature_check: false,
+            description_cache: None,
+            validation: ValidationMode::Off,
         }
     }
 }
@@ -213,12 +233,23 @@ pub struct Tls {
 pub struct Logging {
     #[serde(default)]
     pub level: String,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Additionally sends the log messages to a syslog server
+    #[serde(default)]
+    pub syslog: Option<Syslog>,
+    /// Additionally sends the log messages to journald
+    #[serde(default)]
+    pub journald: bool,
 }
 
 impl Default for Logging {
     fn default() -> Self {
         Self {
             level: "INFO".to_string(),
+            format: LogFormat::default(),
+            syslog: None,
+            journald: false,
         }
     }
 }
@@ -306,6 +337,15 @@ pub struct Config {
     pub storage: Storage,
     #[serde(default)]
     pub scheduler: Scheduler,
+    /// Message brokers results are published to
+    #[serde(default)]
+    pub exporters: Vec<Exporter>,
+    /// Bucket the reports of finished scans are uploaded to
+    #[serde(default)]
+    pub s3: Option<S3>,
+    /// Networks and domains that are never scanned
+    #[serde(default)]
+    pub deny_list: DenyList,
 }
 
 impl Display for Config {
@@ -331,15 +371,27 @@ impl Config {
         }
     }
 
-    fn from_file<P>(path: P) -> Self
+    fn from_file<P>(path: P) -> Result<Self, String>
     where
         P: AsRef<std::path::Path> + std::fmt::Display,
     {
-        let config = std::fs::read_to_string(path).unwrap();
-        toml::from_str(&config).unwrap()
+        let config = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+        toml::from_str(&config).map_err(|e| format!("{path}: {e}"))
     }
 
     pub fn load() -> Self {
+        match Self::try_load() {
+            Ok(config) => config,
+            Err(e) => panic!("Unable to load configuration {e}"),
+        }
+    }
+
+    /// Loads the configuration from the configuration file, the environment and the command
+    /// line arguments.
+    ///
+    /// Returns an error instead of panicking when the configuration file cannot be read, so that
+    /// it can be used to reload the configuration of a running instance.
+    pub fn try_load() -> Result<Self, String> {
         let cmds = clap::Command::new("openvasd")
             .arg(
                 clap::Arg::new("config")
@@ -373,6 +425,22 @@ impl Config {
                     .value_name("SECONDS")
                     .help("interval to check for feed updates in seconds"),
             )
+            .arg(
+                clap::Arg::new("feed-description-cache")
+                    .env("FEED_DESCRIPTION_CACHE")
+                    .long("feed-description-cache")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .action(ArgAction::Set)
+                    .help("directory to cache the description results of the feed"),
+            )
+            .arg(
+                clap::Arg::new("feed-validation")
+                    .env("FEED_VALIDATION")
+                    .long("feed-validation")
+                    .value_parser(clap::value_parser!(ValidationMode))
+                    .action(ArgAction::Set)
+                    .help("validation of the VT metadata: off, reject or quarantine"),
+            )
             .arg(
                 clap::Arg::new("notus-advisories")
                     .env("NOTUS_ADVISORIES")
@@ -534,6 +602,14 @@ impl Config {
                     .short('L')
                     .help("Level of log messages to be shown. TRACE > DEBUG > INFO > WARN > ERROR"),
             )
+            .arg(
+                clap::Arg::new("log-format")
+                    .env("OPENVASD_LOG_FORMAT")
+                    .long("log-format")
+                    .value_name("text,json")
+                    .value_parser(|x: &str| x.parse::<LogFormat>())
+                    .help("Format of log messages, either human readable text or JSON lines"),
+            )
             .arg(
                 clap::Arg::new("mode")
                     .env("OPENVASD_MODE")
@@ -544,7 +620,7 @@ impl Config {
             )
             .get_matches();
         let mut config = match cmds.get_one::<String>("config") {
-            Some(path) => Self::from_file(path),
+            Some(path) => Self::from_file(path)?,
             None => {
                 if let Some(config) = Self::load_user() {
                     config
@@ -581,6 +657,12 @@ impl Config {
         if let Some(path) = cmds.get_one::<PathBuf>("feed-path") {
             config.feed.path.clone_from(path);
         }
+        if let Some(path) = cmds.get_one::<PathBuf>("feed-description-cache") {
+            config.feed.description_cache = Some(path.clone());
+        }
+        if let Some(mode) = cmds.get_one::<ValidationMode>("feed-validation") {
+            config.feed.validation = *mode;
+        }
         if let Some(path) = cmds.get_one::<PathBuf>("notus-products") {
             config.notus.products_path.clone_from(path);
         }
@@ -611,6 +693,9 @@ impl Config {
         if let Some(log_level) = cmds.get_one::<String>("log-level") {
             config.log.level.clone_from(log_level);
         }
+        if let Some(log_format) = cmds.get_one::<LogFormat>("log-format") {
+            config.log.format = *log_format;
+        }
         if let Some(stype) = cmds.get_one::<StorageType>("storage_type") {
             config.storage.storage_type = stype.clone();
         }
@@ -625,7 +710,7 @@ impl Config {
                 config.storage.fs.key = Some(key.clone());
             }
         }
-        config
+        Ok(config)
     }
 }
 
@@ -633,7 +718,16 @@ impl Config {
 mod tests {
     use std::{path::PathBuf, time::Duration};
 
-    use crate::config::StorageType;
+    use scannerlib::logging::{
+        syslog::{Facility, Transport},
+        LogFormat,
+    };
+
+    use crate::{
+        config::StorageType,
+        export::{Exporter, Kafka, Nats},
+        report::ReportFormat,
+    };
 
     #[test]
     fn defaults() {
@@ -676,6 +770,13 @@ mod tests {
     fn parse_toml() {
         let cfg = r#"[log]
         level = "DEBUG"
+        format = "json"
+        journald = true
+        [log.syslog]
+        address = "logs.example.com:6514"
+        transport = "tls"
+        facility = "local3"
+        ca_file = "/etc/ssl/certs/logs.pem"
         [storage]
         type = "fs"
         [storage.fs]
@@ -684,6 +785,12 @@ mod tests {
         "#;
         let config: super::Config = toml::from_str(cfg).unwrap();
         assert_eq!(config.log.level, "DEBUG");
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert!(config.log.journald);
+        let syslog = config.log.syslog.unwrap();
+        assert_eq!(syslog.transport, Transport::Tls);
+        assert_eq!(syslog.facility, Facility::Local3);
+        assert_eq!(syslog.app_name, "openvasd");
         assert_eq!(
             config.storage.fs.path,
             PathBuf::from("/var/lib/openvasd/storage/test")
@@ -691,4 +798,73 @@ mod tests {
         assert_eq!(config.storage.fs.key, Some("changeme".to_string()));
         assert_eq!(config.storage.storage_type, StorageType::FileSystem);
     }
+
+    #[test]
+    fn parse_exporters() {
+        let cfg = r#"[[exporters]]
+        type = "kafka"
+        address = "localhost:9092"
+        topic = "results"
+        [[exporters]]
+        type = "nats"
+        address = "localhost:4222"
+        subject = "openvasd.results"
+        "#;
+        let config: super::Config = toml::from_str(cfg).unwrap();
+        assert_eq!(
+            config.exporters,
+            vec![
+                Exporter::Kafka(Kafka {
+                    address: "localhost:9092".to_string(),
+                    topic: "results".to_string(),
+                    partition: 0,
+                    client_id: "openvasd".to_string(),
+                }),
+                Exporter::Nats(Nats {
+                    address: "localhost:4222".to_string(),
+                    subject: "openvasd.results".to_string(),
+                    token: None,
+                }),
+            ]
+        );
+        assert!(toml::to_string_pretty(&config).is_ok());
+    }
+
+    #[test]
+    fn parse_s3() {
+        let cfg = r#"[s3]
+        endpoint = "http://localhost:9000"
+        bucket = "reports"
+        prefix = "openvasd/"
+        access_key = "minio"
+        secret_key = "changeme"
+        formats = ["json", "csv"]
+        "#;
+        let config: super::Config = toml::from_str(cfg).unwrap();
+        let s3 = config.s3.unwrap();
+        assert_eq!(s3.region, "us-east-1");
+        assert!(s3.path_style);
+        assert_eq!(s3.formats, vec![ReportFormat::Json, ReportFormat::Csv]);
+    }
+
+    #[test]
+    fn parse_workers() {
+        let cfg = r#"[scanner]
+        type = "coordinator"
+
+        [[scanner.workers]]
+        url = "https://worker1.example.com"
+        api_key = "changeme"
+
+        [[scanner.workers]]
+        url = "http://worker2.example.com:3000"
+        "#;
+        let config: super::Config = toml::from_str(cfg).unwrap();
+        assert!(matches!(
+            config.scanner.scanner_type,
+            super::ScannerType::Coordinator
+        ));
+        assert_eq!(config.scanner.workers.len(), 2);
+        assert_eq!(config.scanner.workers[1].api_key, None);
+    }
 }
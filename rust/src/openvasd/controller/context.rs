@@ -3,11 +3,21 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 use async_trait::async_trait;
+use scannerlib::scanner::Quarantine;
 use scannerlib::storage::DefaultDispatcher;
 use scannerlib::{feed, nasl::FSPluginLoader};
 use std::sync::{Arc, RwLock};
 
-use crate::{config, notus::NotusWrapper, response, scheduling, tls::TlsConfig};
+use crate::{
+    config,
+    deny_list::DenyList,
+    export::{Exporter, Exporters},
+    notus::NotusWrapper,
+    response,
+    s3::{Uploader, S3},
+    scheduling,
+    tls::TlsConfig,
+};
 
 use scannerlib::models::scanner::{
     Error, ScanDeleter, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper,
@@ -31,6 +41,10 @@ pub struct ContextBuilder<S, DB, T> {
     response: response::Response,
     notus: Option<NotusWrapper>,
     scheduler_config: Option<config::Scheduler>,
+    exporters: Vec<Exporter>,
+    s3: Option<S3>,
+    deny_list: DenyList,
+    quarantine: Option<Arc<Quarantine>>,
     mode: config::Mode,
 }
 
@@ -50,6 +64,10 @@ impl<S>
             response: response::Response::default(),
             notus: None,
             scheduler_config: None,
+            exporters: vec![],
+            s3: None,
+            deny_list: DenyList::default(),
+            quarantine: None,
             mode: config::Mode::default(),
         }
     }
@@ -104,6 +122,30 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
         self
     }
 
+    /// Sets the message brokers results are published to.
+    pub fn exporters(mut self, exporters: Vec<Exporter>) -> Self {
+        self.exporters = exporters;
+        self
+    }
+
+    /// Sets the bucket the reports of finished scans are uploaded to.
+    pub fn s3(mut self, s3: Option<S3>) -> Self {
+        self.s3 = s3;
+        self
+    }
+
+    /// Sets the networks and domains that are never scanned.
+    pub fn deny_list(mut self, deny_list: DenyList) -> Self {
+        self.deny_list = deny_list;
+        self
+    }
+
+    /// Sets the quarantine of the scanner, when it skips misbehaving scripts.
+    pub fn quarantine(mut self, quarantine: Arc<Quarantine>) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
     /// Sets the storage.
     #[allow(dead_code)]
     pub fn storage<NDB>(self, storage: NDB) -> ContextBuilder<S, NDB, T> {
@@ -118,6 +160,10 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
             response,
             notus,
             scheduler_config,
+            exporters,
+            s3,
+            deny_list,
+            quarantine,
             mode,
         } = self;
         ContextBuilder {
@@ -131,6 +177,10 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
             response,
             notus,
             scheduler_config,
+            exporters,
+            s3,
+            deny_list,
+            quarantine,
             mode,
         }
     }
@@ -153,6 +203,10 @@ impl<S, DB> ContextBuilder<S, DB, NoScanner> {
             storage,
             notus,
             scheduler_config,
+            exporters,
+            s3,
+            deny_list,
+            quarantine,
             mode,
         } = self;
         ContextBuilder {
@@ -166,6 +220,10 @@ impl<S, DB> ContextBuilder<S, DB, NoScanner> {
             response,
             notus,
             scheduler_config,
+            exporters,
+            s3,
+            deny_list,
+            quarantine,
             mode,
         }
     }
@@ -201,18 +259,28 @@ impl<S, DB> ContextBuilder<S, DB, Scanner<S>> {
             self.scheduler_config.unwrap_or_default(),
             self.scanner.0,
             self.storage,
-        );
+        )
+        .with_exporters(Exporters::new(&self.exporters))
+        .with_deny_list(self.deny_list)
+        .with_uploader(self.s3.and_then(|x| match Uploader::new(x) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                tracing::warn!(%e, "unable to load root certificates, reports are not uploaded");
+                None
+            }
+        }));
         let shared_feed = Arc::clone(&scheduler.feed_version());
         self.response.add_feed_version(shared_feed);
         Context {
             response: self.response,
             scheduler,
-            feed_config: self.feed_config,
+            feed_config: RwLock::new(self.feed_config),
             abort: Default::default(),
-            api_key: self.api_key,
+            api_key: RwLock::new(self.api_key),
             tls_config: self.tls_config,
             enable_get_scans: self.enable_get_scans,
             notus: self.notus,
+            quarantine: self.quarantine,
             mode: self.mode,
         }
     }
@@ -224,15 +292,22 @@ pub struct Context<S, DB> {
     /// Creates responses
     pub response: response::Response,
     /// Configuration for feed handling.
-    pub feed_config: Option<config::Feed>,
+    ///
+    /// Can be changed by reloading the configuration.
+    pub feed_config: RwLock<Option<config::Feed>>,
     /// The api key that is used to authenticate the client.
     ///
-    /// When none api key is set, no authentication is required.
-    pub api_key: Option<String>,
+    /// When none api key is set, no authentication is required. Can be changed by reloading the
+    /// configuration.
+    pub api_key: RwLock<Option<String>>,
     pub tls_config: Option<TlsConfig>,
     /// Whether to enable the GET /scans endpoint
     pub enable_get_scans: bool,
     pub mode: config::Mode,
+    /// Scripts skipped by the scanner because they repeatedly crashed or timed out.
+    ///
+    /// Only set when the scanner supports quarantining scripts.
+    pub quarantine: Option<Arc<Quarantine>>,
     /// Aborts the background loops
     pub abort: RwLock<bool>,
     /// Notus Scanner
@@ -244,6 +319,24 @@ pub struct Context<S, DB> {
     pub scheduler: scheduling::Scheduler<DB, S>,
 }
 
+impl<S, DB> Context<S, DB> {
+    /// Returns the current api key.
+    pub fn api_key(&self) -> Option<String> {
+        self.api_key.read().unwrap().clone()
+    }
+
+    /// Returns the current feed configuration.
+    pub fn feed_config(&self) -> Option<config::Feed> {
+        self.feed_config.read().unwrap().clone()
+    }
+
+    /// Returns the current deny list, it is applied when a scan is created and again when it
+    /// is started.
+    pub fn deny_list(&self) -> DenyList {
+        self.scheduler.deny_list()
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 /// A scanner without any side effects. Used for testing.
 pub struct NoOpScanner;
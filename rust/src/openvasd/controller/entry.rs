@@ -13,8 +13,11 @@ use super::{context::Context, ClientIdentifier};
 use http::StatusCode;
 use hyper::{Method, Request};
 use scannerlib::models::scanner::{ScanDeleter, ScanResultFetcher, ScanStarter, ScanStopper};
-use scannerlib::models::{scanner::*, Action, Phase, Scan, ScanAction};
+use scannerlib::models::{
+    self, scanner::*, Action, Phase, Scan, ScanAction, SeverityClass, WatchEvent,
+};
 use scannerlib::notus::NotusError;
+use scannerlib::scanner::QuarantinedVt;
 
 use crate::{
     config,
@@ -45,16 +48,38 @@ enum KnownPaths {
     ScanResults(String, Option<String>),
     /// /scans/{id}/status
     ScanStatus(String),
+    /// /scans/{id}/bundle
+    ScanBundle(String),
+    /// /scans/{id}/events
+    ScanEvents(String),
+    /// /scans/{id}/plan
+    ScanPlan(String),
     /// /vts
     Vts(Option<String>),
     /// /health
     Health(HealthOpts),
     /// /notus/{os}
     Notus(Option<String>),
+    /// /quarantine
+    Quarantine,
     /// Not supported
     Unknown,
 }
 
+/// Returns the value of a parameter of a query string
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|x| x.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Parses the `severity` parameter, the minimal severity class of the returned findings
+fn min_severity(query: Option<&str>) -> Result<Option<SeverityClass>, String> {
+    query_param(query, "severity").map(str::parse).transpose()
+}
+
 impl KnownPaths {
     pub fn requires_id(&self) -> bool {
         !matches!(
@@ -78,6 +103,9 @@ impl KnownPaths {
                                 parts.next().map(|s| s.to_string()),
                             ),
                             Some("status") => KnownPaths::ScanStatus(id.to_string()),
+                            Some("bundle") => KnownPaths::ScanBundle(id.to_string()),
+                            Some("events") => KnownPaths::ScanEvents(id.to_string()),
+                            Some("plan") => KnownPaths::ScanPlan(id.to_string()),
                             Some(_) => KnownPaths::Unknown,
                             None => {
                                 if id == "preferences" {
@@ -103,6 +131,7 @@ impl KnownPaths {
                 Some(os) => KnownPaths::Notus(Some(os.to_string())),
                 None => KnownPaths::Notus(None),
             },
+            Some("quarantine") => KnownPaths::Quarantine,
             Some("health") => match parts.next() {
                 Some("ready") => KnownPaths::Health(HealthOpts::Ready),
                 Some("alive") => KnownPaths::Health(HealthOpts::Alive),
@@ -118,7 +147,12 @@ impl KnownPaths {
 
     fn scan_id(&self) -> Option<&str> {
         match self {
-            Self::Scans(Some(id)) | Self::ScanResults(id, _) | Self::ScanStatus(id) => Some(id),
+            Self::Scans(Some(id))
+            | Self::ScanResults(id, _)
+            | Self::ScanStatus(id)
+            | Self::ScanBundle(id)
+            | Self::ScanEvents(id)
+            | Self::ScanPlan(id) => Some(id),
             _ => None,
         }
     }
@@ -134,11 +168,15 @@ impl Display for KnownPaths {
             }
             KnownPaths::ScanResults(id, None) => write!(f, "/scans/{}/results", id),
             KnownPaths::ScanStatus(id) => write!(f, "/scans/{}/status", id),
+            KnownPaths::ScanBundle(id) => write!(f, "/scans/{}/bundle", id),
+            KnownPaths::ScanEvents(id) => write!(f, "/scans/{}/events", id),
+            KnownPaths::ScanPlan(id) => write!(f, "/scans/{}/plan", id),
             KnownPaths::Unknown => write!(f, "Unknown"),
             KnownPaths::Vts(None) => write!(f, "/vts"),
             KnownPaths::Vts(Some(oid)) => write!(f, "/vts/{oid}"),
             KnownPaths::Notus(Some(os)) => write!(f, "/notus/{}", os),
             KnownPaths::Notus(None) => write!(f, "/notus"),
+            KnownPaths::Quarantine => write!(f, "/quarantine"),
             KnownPaths::Health(HealthOpts::Alive) => write!(f, "/health/alive"),
             KnownPaths::Health(HealthOpts::Ready) => write!(f, "/health/ready"),
             KnownPaths::Health(HealthOpts::Started) => write!(f, "/health/started"),
@@ -198,9 +236,9 @@ where
             let cid: Option<ClientHash> = {
                 match &*cid {
                     ClientIdentifier::Disabled => {
-                        if let Some(key) = ctx.api_key.as_ref() {
+                        if let Some(key) = ctx.api_key() {
                             match req.headers().get("x-api-key") {
-                                Some(v) if v == key => ctx.api_key.as_ref().map(|x| x.into()),
+                                Some(v) if v == &key => Some(key.into()),
                                 Some(v) => {
                                     tracing::debug!("{} {} invalid key: {:?}", req.method(), kp, v);
                                     None
@@ -213,9 +251,9 @@ where
                     }
                     ClientIdentifier::Known(cid) => Some(cid.clone()),
                     ClientIdentifier::Unknown => {
-                        if let Some(key) = ctx.api_key.as_ref() {
+                        if let Some(key) = ctx.api_key() {
                             match req.headers().get("x-api-key") {
-                                Some(v) if v == key => ctx.api_key.as_ref().map(|x| x.into()),
+                                Some(v) if v == &key => Some(key.into()),
                                 Some(v) => {
                                     tracing::debug!("{} {} invalid key: {:?}", req.method(), kp, v);
                                     None
@@ -304,11 +342,32 @@ where
                 (&Method::POST, Scans(None)) => {
                     match crate::request::json_request::<Scan, _>(&ctx.response, req).await {
                         Ok(mut scan) => {
+                            if let Some(Err(e)) = scan.schedule.as_ref().map(|x| x.validate()) {
+                                return Ok(ctx.response.bad_request(&e));
+                            }
                             let id = if !scan.scan_id.is_empty() {
                                 scan.scan_id.to_string()
                             } else {
                                 uuid::Uuid::new_v4().to_string()
                             };
+                            let deny_list = ctx.deny_list();
+                            let target = std::mem::take(&mut scan.target);
+                            let (target, denied) = tokio::task::spawn_blocking(move || {
+                                let mut target = target;
+                                let denied = deny_list.apply(&mut target);
+                                (target, denied)
+                            })
+                            .await
+                            .map_err(|e| Error::Unexpected(e.to_string()))?;
+                            scan.target = target;
+                            if !denied.is_empty() {
+                                tracing::warn!(?denied, "removed hosts on the deny list");
+                                if scan.target.hosts.is_empty() {
+                                    return Ok(ctx
+                                        .response
+                                        .bad_request("all hosts are on the deny list"));
+                                }
+                            }
                             let resp = ctx.response.created(&id);
                             scan.scan_id.clone_from(&id);
                             ctx.scheduler.insert_scan(scan).await?;
@@ -364,6 +423,10 @@ where
                         Ok(ctx.response.not_found("scans", "all"))
                     }
                 }
+                (&Method::GET, Quarantine) => match &ctx.quarantine {
+                    Some(quarantine) => Ok(ctx.response.ok(&quarantine.quarantined())),
+                    None => Ok(ctx.response.ok(&Vec::<QuarantinedVt>::new())),
+                },
                 (&Method::GET, ScanPreferences) => Ok(ctx
                     .response
                     .ok_static(crate::preference::PREFERENCES_JSON.as_bytes())),
@@ -393,6 +456,46 @@ where
                     }
                     Err(e) => Ok(ctx.response.internal_server_error(&e)),
                 },
+                (&Method::GET, ScanBundle(id)) => {
+                    match crate::bundle::create(&ctx.scheduler, &ctx.mode, &id).await {
+                        Ok(bundle) => Ok(ctx.response.ok_attachment(
+                            crate::bundle::CONTENT_TYPE,
+                            &format!("{id}.tar.gz"),
+                            bundle,
+                        )),
+                        Err(crate::storage::Error::NotFound) => {
+                            Ok(ctx.response.not_found("scans/bundle", &id))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::POST, ScanPlan(id)) => {
+                    match crate::plan::create(&ctx.scheduler, &id).await {
+                        Ok(plan) => Ok(ctx.response.ok(&plan)),
+                        Err(crate::storage::Error::NotFound) => {
+                            Ok(ctx.response.not_found("scans/plan", &id))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::GET, ScanEvents(id)) => match ctx.scheduler.get_scan(&id).await {
+                    Ok(_) => {
+                        let min_severity = match min_severity(req.uri().query()) {
+                            Ok(x) => x,
+                            Err(e) => return Ok(ctx.response.bad_request(&e)),
+                        };
+                        let events = ctx.scheduler.subscribe_watch_events();
+                        Ok(ctx.response.ok_event_stream(events, move |x: &WatchEvent| {
+                            x.scan_id == id
+                                && min_severity
+                                    .is_none_or(|min| x.result.severity.is_some_and(|x| x >= min))
+                        }))
+                    }
+                    Err(crate::storage::Error::NotFound) => {
+                        Ok(ctx.response.not_found("scans/events", &id))
+                    }
+                    Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                },
                 (&Method::DELETE, Scans(Some(id))) => {
                     match ctx.scheduler.delete_scan_by_id(&id).await {
                         Ok(_) => Ok(ctx.response.no_content()),
@@ -403,32 +506,42 @@ where
                     }
                 }
                 (&Method::GET, ScanResults(id, rid)) => {
+                    let query = req.uri().query();
+                    let min_severity = match min_severity(query) {
+                        Ok(x) => x,
+                        Err(e) => return Ok(ctx.response.bad_request(&e)),
+                    };
                     let (begin, end) = {
                         if let Some(id) = rid {
                             match id.parse::<usize>() {
                                 Ok(id) => (Some(id), Some(id + 1)),
                                 Err(_) => (None, None),
                             }
-                        } else {
-                            let query = req.uri().query().unwrap_or_default();
-                            let mut parts = query.split('=');
-                            if parts.next() == Some("range") {
-                                let mut range = parts.next().unwrap_or_default().split('-');
-                                let begin = range.next().unwrap_or_default().parse::<usize>();
-                                let end = range.next().unwrap_or_default().parse::<usize>();
-                                match (begin, end) {
-                                    (Ok(begin), Ok(end)) => (Some(begin), Some(end + 1)),
-                                    (Ok(begin), Err(_)) => (Some(begin), None),
-                                    _ => (None, None),
-                                }
-                            } else {
-                                (None, None)
+                        } else if let Some(range) = query_param(query, "range") {
+                            let mut range = range.split('-');
+                            let begin = range.next().unwrap_or_default().parse::<usize>();
+                            let end = range.next().unwrap_or_default().parse::<usize>();
+                            match (begin, end) {
+                                (Ok(begin), Ok(end)) => (Some(begin), Some(end + 1)),
+                                (Ok(begin), Err(_)) => (Some(begin), None),
+                                _ => (None, None),
                             }
+                        } else {
+                            (None, None)
                         }
                     };
 
                     match ctx.scheduler.get_results(&id, begin, end).await {
-                        Ok(results) => Ok(ctx.response.byte_stream(StatusCode::OK, results).await),
+                        Ok(results) => match min_severity {
+                            Some(min) => {
+                                let results = results.filter(move |x| {
+                                    serde_json::from_slice::<models::Result>(x)
+                                        .is_ok_and(|x| x.severity.is_some_and(|x| x >= min))
+                                });
+                                Ok(ctx.response.byte_stream(StatusCode::OK, results).await)
+                            }
+                            None => Ok(ctx.response.byte_stream(StatusCode::OK, results).await),
+                        },
                         Err(crate::storage::Error::NotFound) => {
                             Ok(ctx.response.not_found("scans/results", &id))
                         }
@@ -612,6 +725,10 @@ pub mod client {
             self.cid = Arc::new(cid);
         }
 
+        pub fn set_deny_list(&self, deny_list: crate::deny_list::DenyList) {
+            self.ctx.scheduler.set_deny_list(deny_list);
+        }
+
         async fn entrypoint<R>(&self, req: Request<R>) -> HttpResult
         where
             R: hyper::body::Body + Send + 'static,
@@ -690,6 +807,45 @@ pub mod client {
                 .await;
             self.parsed(result, status).await
         }
+        pub async fn scan_results_with_query(
+            &self,
+            id: &str,
+            query: &str,
+            status: StatusCode,
+        ) -> TypeResult<Vec<models::Result>> {
+            let uri = format!("{}?{query}", KnownPaths::ScanResults(id.to_string(), None));
+            let req = Request::builder()
+                .uri(uri)
+                .method(Method::GET)
+                .body(Empty::<Bytes>::new())
+                .map_err(|x| {
+                    scanner::Error::Unexpected(format!("Unable to create request: {x}"))
+                })?;
+            let result = self.entrypoint(req).await;
+            self.parsed(result, status).await
+        }
+        pub async fn scan_bundle(&self, id: &str) -> TypeResult<Vec<u8>> {
+            let resp = self
+                .request_empty(Method::GET, KnownPaths::ScanBundle(id.to_string()))
+                .await?;
+            if resp.status() != StatusCode::OK {
+                return Err(scanner::Error::Unexpected(format!(
+                    "Expected 200 for a bundle but got {}",
+                    resp.status()
+                )));
+            }
+            // infallible
+            let resp = resp.into_body().collect().await.unwrap().to_bytes();
+            Ok(resp.to_vec())
+        }
+
+        pub async fn scan_plan(&self, id: &str) -> TypeResult<crate::plan::Plan> {
+            let result = self
+                .request_empty(Method::POST, KnownPaths::ScanPlan(id.to_string()))
+                .await;
+            self.parsed(result, StatusCode::OK).await
+        }
+
         pub async fn scan_delete(&self, id: &str) -> TypeResult<()> {
             let result = self
                 .request_empty(Method::DELETE, KnownPaths::Scans(Some(id.to_string())))
@@ -817,7 +973,7 @@ pub mod client {
 #[cfg(test)]
 pub(super) mod tests {
     use http::StatusCode;
-    use scannerlib::models::{Scan, VT};
+    use scannerlib::models::{Credential, CredentialType, Scan, ScanPreference, VT};
 
     #[tokio::test]
     #[tracing_test::traced_test]
@@ -850,6 +1006,60 @@ pub(super) mod tests {
         client.scan_delete(&id).await.unwrap();
     }
 
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn bundle_without_secrets() {
+        let client = super::client::fails_to_fetch_results().await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.target.credentials = vec![Credential {
+            credential_type: CredentialType::UP {
+                username: "user".to_string(),
+                password: "secret".to_string(),
+                privilege: None,
+            },
+            ..Default::default()
+        }];
+        scan.scan_preferences = vec![
+            ScanPreference {
+                id: "socks5_password".to_string(),
+                value: "socks5-secret".to_string(),
+            },
+            ScanPreference {
+                id: "http_proxy_password".to_string(),
+                value: "http-proxy-secret".to_string(),
+            },
+        ];
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let bundle = client.scan_bundle(&id).await.unwrap();
+        let files = crate::bundle::tests::unpack(&bundle);
+        let names = files
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "scan.json",
+                "status.json",
+                "journal.json",
+                "errors.json",
+                "environment.json"
+            ]
+        );
+        let scan = String::from_utf8(files[0].1.clone()).unwrap();
+        assert!(scan.contains("user"));
+        assert!(scan.contains("socks5_password"));
+        assert!(!scan.contains("socks5-secret"));
+        assert!(!scan.contains("http-proxy-secret"));
+        assert!(!scan.contains("secret"));
+        let journal = String::from_utf8(files[2].1.clone()).unwrap();
+        assert!(journal.contains("unable to fetch results"));
+        client.scan_delete(&id).await.unwrap();
+        assert!(client.scan_bundle(&id).await.is_err());
+    }
+
     #[tokio::test]
     #[tracing_test::traced_test]
     async fn status_of_internal_error_should_be_reflects() {
@@ -863,4 +1073,137 @@ pub(super) mod tests {
         assert_eq!(0, results.len());
         client.scan_delete(&id).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn hosts_on_deny_list_are_removed() {
+        let client = super::client::in_memory_example_feed().await;
+        client.set_deny_list(
+            toml::from_str("networks = [\"10.0.0.0/8\"]\ndomains = [\"*.example.com\"]").unwrap(),
+        );
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts = vec!["10.0.0.1".to_string(), "www.example.com".to_string()];
+        assert!(client.scan_create(&scan).await.is_err());
+
+        scan.target.hosts.push("192.168.0.0/24".to_string());
+        let id = client.scan_create(&scan).await.unwrap();
+        let scan = client.scan(&id).await.unwrap();
+        assert_eq!(scan.target.hosts, vec!["192.168.0.0/24".to_string()]);
+        assert_eq!(
+            scan.target.excluded_hosts,
+            vec!["10.0.0.0/8".to_string(), "*.example.com".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn plan_without_executing() {
+        let client = super::client::in_memory_example_feed().await;
+        let oids = client.vts().await.unwrap();
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts = vec!["192.168.0.0/29".to_string(), "localhost".to_string()];
+        scan.target.excluded_hosts = vec!["192.168.0.2".to_string()];
+        scan.vts = vec![VT {
+            oid: oids[0].clone(),
+            parameters: vec![],
+        }];
+        let id = client.scan_create(&scan).await.unwrap();
+        let plan = client.scan_plan(&id).await.unwrap();
+        assert_eq!(plan.host_count, 6);
+        assert_eq!(plan.hosts[0], "192.168.0.1");
+        assert_eq!(plan.hosts[1], "192.168.0.3");
+        assert!(plan.vts.contains(&oids[0]));
+        assert!(!plan.phases.is_empty());
+        let status = client.scan_status(&id).await.unwrap();
+        assert_eq!(status.status, scannerlib::models::Phase::Stored);
+
+        assert!(client.scan_plan("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn filter_results_by_severity() {
+        use scannerlib::models::{
+            scanner::ScanResults, Classification, Phase, Result, ResultType, SeverityClass,
+            SeverityOverride, Status,
+        };
+
+        let alarm = |id: usize, oid: &str| Result {
+            id,
+            r_type: ResultType::Alarm,
+            oid: Some(oid.to_string()),
+            ip_address: Some("127.0.0.1".to_string()),
+            ..Default::default()
+        };
+        let scanner = scannerlib::scanner::fake::LambdaScannerBuilder::new()
+            .with_fetch_results(move |id| {
+                Ok(ScanResults {
+                    id: id.to_string(),
+                    status: Status {
+                        status: Phase::Succeeded,
+                        ..Default::default()
+                    },
+                    results: vec![
+                        alarm(0, "1"),
+                        alarm(1, "2"),
+                        Result {
+                            r_type: ResultType::Log,
+                            ..alarm(2, "3")
+                        },
+                        Result {
+                            r_type: ResultType::Error,
+                            ..alarm(3, "4")
+                        },
+                    ],
+                })
+            })
+            .build();
+        let storage = std::sync::Arc::new(crate::storage::UserNASLStorageForKBandVT::new(
+            crate::storage::inmemory::Storage::<crate::crypt::ChaCha20Crypt>::default(),
+        ));
+        let client = super::client::Client::authenticated(scanner, storage);
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("127.0.0.1".to_string());
+        scan.classification = Some(Classification {
+            overrides: vec![SeverityOverride {
+                oid: "2".to_string(),
+                hosts: vec![],
+                port: None,
+                severity: SeverityClass::Critical,
+            }],
+            ..Default::default()
+        });
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let results = client.scan_results(&id, StatusCode::OK).await.unwrap();
+        let severities: Vec<_> = results.iter().map(|x| x.severity).collect();
+        assert_eq!(
+            severities,
+            vec![
+                Some(SeverityClass::Medium),
+                Some(SeverityClass::Critical),
+                Some(SeverityClass::Log),
+                None
+            ]
+        );
+        let ids = |query: &'static str| {
+            let client = &client;
+            let id = &id;
+            async move {
+                client
+                    .scan_results_with_query(id, query, StatusCode::OK)
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|x| x.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(ids("severity=medium").await, vec![0, 1]);
+        assert_eq!(ids("severity=log").await, vec![0, 1, 2]);
+        assert_eq!(ids("range=1-3&severity=high").await, vec![1]);
+        assert!(client
+            .scan_results_with_query(&id, "severity=severe", StatusCode::OK)
+            .await
+            .is_err());
+    }
 }
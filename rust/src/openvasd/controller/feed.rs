@@ -8,7 +8,7 @@ use scannerlib::models::scanner::Scanner;
 
 use crate::{
     feed::FeedIdentifier,
-    storage::{FeedHash, NVTStorer as _},
+    storage::{FeedHash, FeedType, NVTStorer as _},
 };
 
 use super::context::Context;
@@ -54,24 +54,25 @@ where
     DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
 {
     tracing::debug!("Starting VTS synchronization loop");
-    if let Some(cfg) = &ctx.feed_config {
-        let interval = cfg.check_interval;
-        let signature_check = cfg.signature_check;
-        loop {
-            if *ctx.abort.read().unwrap() {
-                tracing::trace!("aborting");
-                break;
-            };
-            let last_hash = ctx.scheduler.feed_hash().await;
-            if let Ok(nh) = changed_hash(signature_check, &last_hash).await {
-                if !nh.is_empty() {
-                    if let Err(err) = ctx.scheduler.synchronize_feeds(nh).await {
-                        tracing::warn!(%err, "Unable to sync feed")
-                    }
+    // the configuration is read on each iteration, as it may be reloaded
+    while let Some(cfg) = ctx.feed_config() {
+        if *ctx.abort.read().unwrap() {
+            tracing::trace!("aborting");
+            break;
+        };
+        let mut last_hash = ctx.scheduler.feed_hash().await;
+        // a changed feed path results in a changed hash and therefore in a synchronization
+        for h in last_hash.iter_mut().filter(|x| x.typus == FeedType::NASL) {
+            h.path.clone_from(&cfg.path);
+        }
+        if let Ok(nh) = changed_hash(cfg.signature_check, &last_hash).await {
+            if !nh.is_empty() {
+                if let Err(err) = ctx.scheduler.synchronize_feeds(nh).await {
+                    tracing::warn!(%err, "Unable to sync feed")
                 }
             }
-
-            tokio::time::sleep(interval).await;
         }
+
+        tokio::time::sleep(cfg.check_interval).await;
     }
 }
@@ -5,6 +5,7 @@
 mod context;
 pub mod entry;
 pub mod feed;
+pub mod reload;
 pub mod results;
 
 use std::{
@@ -83,6 +84,10 @@ where
         tokio::spawn(crate::controller::results::fetch(Arc::clone(&controller)));
     }
     tokio::spawn(crate::controller::feed::fetch(Arc::clone(&controller)));
+    tokio::spawn(crate::controller::reload::watch(
+        Arc::clone(&controller),
+        config.clone(),
+    ));
 
     if let Some(tls_config) = tls_config {
         use hyper::server::conn::http2::Builder;
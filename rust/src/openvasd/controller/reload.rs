@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Reloads the configuration of a running instance.
+//!
+//! On SIGHUP the configuration is loaded again and the settings that can be changed without a
+//! restart are applied: the log level, the API key, the scheduler limits, the feed
+//! configuration and the deny list. Running scans are not interrupted. Changes of other settings are reported as
+//! requiring a restart.
+
+use std::sync::{Arc, OnceLock};
+
+use serde::Serialize;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::metadata::LevelFilter;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::config::{Config, ScannerType};
+
+use super::context::Context;
+
+/// Handle to change the log filter of the global subscriber
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+static LOG_FILTER: OnceLock<LogFilterHandle> = OnceLock::new();
+
+/// Creates the log filter based on the configured log level.
+pub fn log_filter(config: &Config) -> EnvFilter {
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .parse_lossy(format!("{},rustls=info,h2=info", &config.log.level))
+}
+
+/// Registers the handle that is used to change the log level on reload.
+pub fn set_log_filter(handle: LogFilterHandle) {
+    if LOG_FILTER.set(handle).is_err() {
+        tracing::warn!("log filter handle is already set");
+    }
+}
+
+fn changed<T: Serialize>(current: &T, new: &T) -> bool {
+    serde_json::to_value(current).ok() != serde_json::to_value(new).ok()
+}
+
+/// Applies the changes between the current and the new configuration.
+pub fn apply<S, DB>(ctx: &Context<S, DB>, current: &Config, new: &Config) {
+    if new.log.level != current.log.level {
+        match LOG_FILTER.get().map(|x| x.reload(log_filter(new))) {
+            Some(Ok(())) => tracing::info!(level = new.log.level, "changed log level"),
+            Some(Err(e)) => tracing::warn!(%e, "unable to change log level"),
+            None => {}
+        }
+    }
+
+    if new.endpoints.key != current.endpoints.key {
+        // Switching between no authentication, API key and mTLS changes the client ids of the
+        // stored scans and is therefore only possible on start.
+        let mut api_key = ctx.api_key.write().unwrap();
+        match (api_key.is_some(), &new.endpoints.key) {
+            (true, Some(key)) => {
+                *api_key = Some(key.clone());
+                tracing::info!("changed api key");
+            }
+            _ => tracing::warn!("enabling or disabling the api key requires a restart"),
+        }
+    }
+
+    if changed(&current.scheduler, &new.scheduler) {
+        ctx.scheduler.set_config(new.scheduler.clone());
+        tracing::info!(
+            max_queued_scans = new.scheduler.max_queued_scans,
+            max_running_scans = new.scheduler.max_running_scans,
+            "changed scheduler limits"
+        );
+        if new.scheduler.min_free_mem != current.scheduler.min_free_mem {
+            tracing::warn!("changing min_free_mem requires a restart");
+        }
+    }
+
+    if changed(&current.feed, &new.feed) {
+        let mut feed_config = ctx.feed_config.write().unwrap();
+        if feed_config.is_some() {
+            *feed_config = Some(new.feed.clone());
+            tracing::info!(path=%new.feed.path.display(), "changed feed configuration");
+            if new.feed.path != current.feed.path
+                && matches!(new.scanner.scanner_type, ScannerType::Openvasd)
+            {
+                tracing::warn!(
+                    "the scanner executes scripts of the previous feed path until restart"
+                );
+            }
+        }
+    }
+
+    if current.deny_list != new.deny_list {
+        ctx.scheduler.set_deny_list(new.deny_list.clone());
+        tracing::info!(
+            networks = new.deny_list.networks.len(),
+            domains = new.deny_list.domains.len(),
+            "changed deny list, it applies to scans started from now on"
+        );
+    }
+
+    let restart = [
+        ("log.format", current.log.format != new.log.format),
+        ("log.syslog", current.log.syslog != new.log.syslog),
+        ("log.journald", current.log.journald != new.log.journald),
+        ("mode", changed(&current.mode, &new.mode)),
+        ("notus", changed(&current.notus, &new.notus)),
+        (
+            "endpoints.enable_get_scans",
+            current.endpoints.enable_get_scans != new.endpoints.enable_get_scans,
+        ),
+        ("tls", changed(&current.tls, &new.tls)),
+        ("scanner", changed(&current.scanner, &new.scanner)),
+        ("listener", changed(&current.listener, &new.listener)),
+        ("storage", changed(&current.storage, &new.storage)),
+        ("exporters", current.exporters != new.exporters),
+        ("s3", current.s3 != new.s3),
+    ];
+    for (section, _) in restart.iter().filter(|(_, changed)| *changed) {
+        tracing::warn!(section, "changed setting requires a restart");
+    }
+}
+
+/// Reloads the configuration on SIGHUP until the context is aborted.
+pub async fn watch<S, DB>(ctx: Arc<Context<S, DB>>, mut current: Config) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!(%e, "unable to listen for SIGHUP, reloading is disabled");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        if *ctx.abort.read().unwrap() {
+            break;
+        }
+        tracing::info!("reloading configuration");
+        match Config::try_load() {
+            Ok(new) => {
+                apply(&ctx, &current, &new);
+                current = new;
+            }
+            Err(e) => tracing::warn!(%e, "keeping the current configuration"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{Config, Scheduler},
+        controller::{ContextBuilder, NoOpScanner},
+    };
+
+    use super::apply;
+
+    #[test]
+    fn apply_reloadable_settings() {
+        let current = Config {
+            endpoints: crate::config::Endpoints {
+                key: Some("old".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ctx = ContextBuilder::new()
+            .api_key(current.endpoints.key.clone())
+            .scheduler_config(current.scheduler.clone())
+            .scanner(NoOpScanner)
+            .build();
+        let mut new = current.clone();
+        new.endpoints.key = Some("new".to_string());
+        new.scheduler = Scheduler {
+            max_running_scans: Some(2),
+            ..Default::default()
+        };
+        new.deny_list.networks = vec!["10.0.0.0/8".parse().unwrap()];
+        apply(&ctx, &current, &new);
+        assert_eq!(ctx.api_key(), Some("new".to_string()));
+        assert_eq!(ctx.scheduler.config().max_running_scans, Some(2));
+        assert_eq!(ctx.deny_list(), new.deny_list);
+
+        // the api key cannot be disabled at runtime
+        let current = new.clone();
+        new.endpoints.key = None;
+        apply(&ctx, &current, &new);
+        assert_eq!(ctx.api_key(), Some("new".to_string()));
+    }
+}
@@ -0,0 +1,424 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Distributes scans across openvasd workers.
+//!
+//! The hosts of a scan are split evenly across the configured workers. Each part is created and
+//! started as a scan on its worker via the HTTP API. The status and the new results of all parts
+//! are fetched from the workers and presented as a single scan to the client. Watching, scan
+//! windows and the classification of findings are handled by the coordinator and not forwarded.
+//!
+//! The parts of the scans are kept in memory, so that scans running during a restart of the
+//! coordinator fail.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Bytes, Method, StatusCode};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use scannerlib::models::{
+    scanner::{Error, ScanDeleter, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper},
+    Action, HostInfo, Phase, Scan, ScanAction, Status,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::plan::{expand_hosts, MAX_LISTED_HOSTS};
+
+type HttpsClient = Client<hyper_rustls::HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// Time to wait for the response of a worker
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An openvasd instance scans are distributed to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Worker {
+    /// URL of the API, e.g. "https://worker1.example.com"
+    pub url: String,
+    /// Sent as `x-api-key` header
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+/// Part of a scan running on a worker
+#[derive(Debug, Clone)]
+struct Part {
+    /// Index of the worker
+    worker: usize,
+    /// ID of the scan on the worker
+    id: String,
+    /// Amount of results already fetched
+    fetched: usize,
+}
+
+/// Scanner starting the parts of a scan on the workers
+pub struct Coordinator {
+    workers: Vec<Worker>,
+    client: HttpsClient,
+    scans: Mutex<HashMap<String, Vec<Part>>>,
+}
+
+impl std::fmt::Debug for Coordinator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Coordinator")
+            .field(
+                "workers",
+                &self.workers.iter().map(|x| &x.url).collect::<Vec<_>>(),
+            )
+            .field("scans", &self.scans)
+            .finish()
+    }
+}
+
+/// Splits the hosts into at most `parts` chunks of nearly equal size.
+fn split(hosts: Vec<String>, parts: usize) -> Vec<Vec<String>> {
+    let parts = parts.min(hosts.len());
+    if parts == 0 {
+        return vec![];
+    }
+    let (size, remainder) = (hosts.len() / parts, hosts.len() % parts);
+    let mut hosts = hosts.into_iter();
+    (0..parts)
+        .map(|i| {
+            let len = if i < remainder { size + 1 } else { size };
+            hosts.by_ref().take(len).collect()
+        })
+        .collect()
+}
+
+/// Combines the statuses of the parts into the status of the scan.
+///
+/// The scan is running as long as one part is running. Afterwards it failed when one part failed
+/// and it is stopped when one part was stopped.
+fn merge_status(statuses: &[Status]) -> Status {
+    let phase = if statuses
+        .iter()
+        .any(|x| matches!(x.status, Phase::Stored | Phase::Requested | Phase::Running))
+    {
+        Phase::Running
+    } else if statuses.iter().any(|x| x.status == Phase::Failed) {
+        Phase::Failed
+    } else if statuses.iter().any(|x| x.status == Phase::Stopped) {
+        Phase::Stopped
+    } else {
+        Phase::Succeeded
+    };
+    let end_time = match phase {
+        Phase::Running => None,
+        _ => statuses.iter().filter_map(|x| x.end_time).max(),
+    };
+    let host_info = statuses
+        .iter()
+        .filter_map(|x| x.host_info.as_ref())
+        .fold(None, |sum: Option<HostInfo>, x| {
+            Some(sum.unwrap_or_default().merge(x))
+        });
+    Status {
+        start_time: statuses.iter().filter_map(|x| x.start_time).min(),
+        end_time,
+        status: phase,
+        host_info,
+    }
+}
+
+impl Coordinator {
+    pub fn new(workers: Vec<Worker>) -> std::io::Result<Self> {
+        if workers.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the coordinator requires at least one worker",
+            ));
+        }
+        let client = Client::builder(TokioExecutor::new()).build(
+            hyper_rustls::HttpsConnectorBuilder::new()
+                .with_native_roots()?
+                .https_or_http()
+                .enable_http1()
+                .build(),
+        );
+        Ok(Self {
+            workers,
+            client,
+            scans: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sends a request to a worker and returns the body when the status is the expected one.
+    async fn request(
+        &self,
+        worker: usize,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+        expected: StatusCode,
+    ) -> Result<Bytes, Error> {
+        let worker = &self.workers[worker];
+        let url = format!("{}{path}", worker.url.trim_end_matches('/'));
+        let mut builder = hyper::Request::builder().method(method).uri(&url);
+        if body.is_some() {
+            builder = builder.header("Content-Type", "application/json");
+        }
+        if let Some(key) = &worker.api_key {
+            builder = builder.header("x-api-key", key);
+        }
+        let request = builder
+            .body(Full::new(Bytes::from(body.unwrap_or_default())))
+            .map_err(|e| Error::Unexpected(format!("{url}: {e}")))?;
+        let response = tokio::time::timeout(TIMEOUT, self.client.request(request))
+            .await
+            .map_err(|_| Error::Connection(format!("{url}: timed out")))?
+            .map_err(|e| Error::Connection(format!("{url}: {e}")))?;
+        let status = response.status();
+        let body = tokio::time::timeout(TIMEOUT, response.into_body().collect())
+            .await
+            .map_err(|_| Error::Connection(format!("{url}: timed out")))?
+            .map_err(|e| Error::Connection(format!("{url}: {e}")))?
+            .to_bytes();
+        if status != expected {
+            return Err(Error::Unexpected(format!(
+                "{url}: worker responded with {status}: {}",
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        Ok(body)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, worker: usize, path: &str) -> Result<T, Error> {
+        let body = self
+            .request(worker, Method::GET, path, None, StatusCode::OK)
+            .await?;
+        serde_json::from_slice(&body).map_err(|e| Error::Unexpected(format!("{path}: {e}")))
+    }
+
+    async fn action(&self, part: &Part, action: Action) -> Result<(), Error> {
+        let body = serde_json::to_vec(&ScanAction::from(action))
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+        self.request(
+            part.worker,
+            Method::POST,
+            &format!("/scans/{}", part.id),
+            Some(body),
+            StatusCode::NO_CONTENT,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, part: &Part) -> Result<(), Error> {
+        self.request(
+            part.worker,
+            Method::DELETE,
+            &format!("/scans/{}", part.id),
+            None,
+            StatusCode::NO_CONTENT,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Creates and starts a scan on the worker.
+    async fn start_part(&self, worker: usize, scan: &Scan) -> Result<Part, Error> {
+        let body = serde_json::to_vec(scan).map_err(|e| Error::Unexpected(e.to_string()))?;
+        let id = self
+            .request(
+                worker,
+                Method::POST,
+                "/scans",
+                Some(body),
+                StatusCode::CREATED,
+            )
+            .await?;
+        let id: String =
+            serde_json::from_slice(&id).map_err(|e| Error::Unexpected(e.to_string()))?;
+        let part = Part {
+            worker,
+            id,
+            fetched: 0,
+        };
+        if let Err(e) = self.action(&part, Action::Start).await {
+            if let Err(e) = self.delete(&part).await {
+                tracing::warn!(worker = %self.workers[worker].url, %e, "unable to delete part");
+            }
+            return Err(e);
+        }
+        Ok(part)
+    }
+
+    fn parts(&self, id: &str) -> Result<Vec<Part>, Error> {
+        self.scans
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Error::ScanNotFound(id.to_string()))
+    }
+}
+
+#[async_trait]
+impl ScanStarter for Coordinator {
+    async fn start_scan(&self, scan: Scan) -> Result<(), Error> {
+        let (hosts, count) = expand_hosts(&scan.target);
+        if count > hosts.len() as u64 {
+            return Err(Error::SchedulingError {
+                id: scan.scan_id,
+                reason: format!("unable to distribute more than {MAX_LISTED_HOSTS} hosts"),
+            });
+        }
+        let mut parts = vec![];
+        for (worker, hosts) in split(hosts, self.workers.len()).into_iter().enumerate() {
+            let mut part = scan.clone();
+            part.scan_id = String::new();
+            part.target.hosts = hosts;
+            part.target.excluded_hosts.clear();
+            part.vt_filter = None;
+            part.watch = None;
+            part.classification = None;
+            part.schedule = None;
+            match self.start_part(worker, &part).await {
+                Ok(part) => {
+                    tracing::debug!(
+                        scan_id = %scan.scan_id,
+                        worker = %self.workers[worker].url,
+                        part = %part.id,
+                        "started part"
+                    );
+                    parts.push(part);
+                }
+                Err(e) => {
+                    for part in parts {
+                        if let Err(e) = self.action(&part, Action::Stop).await {
+                            tracing::debug!(part = %part.id, %e, "unable to stop part");
+                        }
+                        if let Err(e) = self.delete(&part).await {
+                            tracing::warn!(part = %part.id, %e, "unable to delete part");
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        self.scans.lock().unwrap().insert(scan.scan_id, parts);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScanStopper for Coordinator {
+    async fn stop_scan<I>(&self, id: I) -> Result<(), Error>
+    where
+        I: AsRef<str> + Send + 'static,
+    {
+        for part in self.parts(id.as_ref())? {
+            // a part may already be finished
+            if let Err(e) = self.action(&part, Action::Stop).await {
+                tracing::debug!(part = %part.id, %e, "unable to stop part");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScanDeleter for Coordinator {
+    async fn delete_scan<I>(&self, id: I) -> Result<(), Error>
+    where
+        I: AsRef<str> + Send + 'static,
+    {
+        let parts = self.parts(id.as_ref())?;
+        self.scans.lock().unwrap().remove(id.as_ref());
+        for part in parts {
+            if let Err(e) = self.delete(&part).await {
+                tracing::warn!(part = %part.id, %e, "unable to delete part");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScanResultFetcher for Coordinator {
+    async fn fetch_results<I>(&self, id: I) -> Result<ScanResults, Error>
+    where
+        I: AsRef<str> + Send + 'static,
+    {
+        let id = id.as_ref();
+        let mut parts = self.parts(id)?;
+        let mut statuses = Vec::with_capacity(parts.len());
+        let mut results = vec![];
+        for part in parts.iter_mut() {
+            // the status is fetched first so that the results of a finished part are complete
+            statuses.push(
+                self.get::<Status>(part.worker, &format!("/scans/{}/status", part.id))
+                    .await?,
+            );
+            let new: Vec<scannerlib::models::Result> = self
+                .get(
+                    part.worker,
+                    &format!("/scans/{}/results?range={}-", part.id, part.fetched),
+                )
+                .await?;
+            part.fetched += new.len();
+            results.extend(new);
+        }
+        if let Some(x) = self.scans.lock().unwrap().get_mut(id) {
+            *x = parts;
+        }
+        Ok(ScanResults {
+            id: id.to_string(),
+            status: merge_status(&statuses),
+            results,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::{Phase, Status};
+
+    use super::{merge_status, split};
+
+    fn hosts(amount: usize) -> Vec<String> {
+        (0..amount).map(|x| format!("10.0.0.{x}")).collect()
+    }
+
+    #[test]
+    fn split_hosts() {
+        let parts = split(hosts(5), 2);
+        assert_eq!(parts, vec![hosts(3), hosts(5)[3..].to_vec()]);
+        assert_eq!(split(hosts(2), 4).len(), 2);
+        assert!(split(vec![], 3).is_empty());
+    }
+
+    #[test]
+    fn merge_statuses() {
+        let status = |phase, start_time, end_time| Status {
+            start_time: Some(start_time),
+            end_time,
+            status: phase,
+            host_info: None,
+        };
+        let merged = merge_status(&[
+            status(Phase::Succeeded, 2, Some(5)),
+            status(Phase::Running, 1, None),
+        ]);
+        assert_eq!(merged.status, Phase::Running);
+        assert_eq!(merged.start_time, Some(1));
+        assert_eq!(merged.end_time, None);
+        let merged = merge_status(&[
+            status(Phase::Succeeded, 2, Some(5)),
+            status(Phase::Stopped, 1, Some(3)),
+            status(Phase::Failed, 1, Some(4)),
+        ]);
+        assert_eq!(merged.status, Phase::Failed);
+        assert_eq!(merged.end_time, Some(5));
+        let merged = merge_status(&[
+            status(Phase::Succeeded, 2, Some(5)),
+            status(Phase::Stopped, 1, Some(3)),
+        ]);
+        assert_eq!(merged.status, Phase::Stopped);
+    }
+}
@@ -0,0 +1,401 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Prevents scanning networks and domains that are out of scope.
+//!
+//! The deny list is configured by the operator and applied to each created scan regardless of
+//! its target. Denied hosts are removed from the target and the denied networks and domains are
+//! added to its excluded hosts, so that the scanner skips them when expanding ranges and again
+//! for each host it scans.
+
+use std::{fmt, net::IpAddr};
+
+use scannerlib::models::Target;
+use serde::{Deserialize, Serialize};
+
+use crate::plan::expand_hosts;
+
+/// IP address or network in CIDR notation
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Network {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn width(address: &IpAddr) -> u8 {
+        match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+
+    fn bits(address: &IpAddr) -> u128 {
+        match address {
+            IpAddr::V4(x) => u32::from(*x) as u128,
+            IpAddr::V6(x) => u128::from(*x),
+        }
+    }
+
+    /// Returns true when the address is part of the network.
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        let width = Self::width(&self.address);
+        width == Self::width(address)
+            && (Self::bits(&self.address) ^ Self::bits(address))
+                .checked_shr((width - self.prefix) as u32)
+                .unwrap_or(0)
+                == 0
+    }
+
+    /// Returns true when each address of the other network is part of the network.
+    pub fn covers(&self, other: &Network) -> bool {
+        other.prefix >= self.prefix && self.contains(&other.address)
+    }
+}
+
+impl TryFrom<String> for Network {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::str::FromStr for Network {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{value}` is neither an IP address nor a network");
+        let (address, prefix) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value.trim(), None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let width = Self::width(&address);
+        let prefix = match prefix {
+            Some(x) => x.parse().ok().filter(|x| *x <= width).ok_or_else(invalid)?,
+            None => width,
+        };
+        Ok(Self { address, prefix })
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+impl From<Network> for String {
+    fn from(value: Network) -> Self {
+        value.to_string()
+    }
+}
+
+/// Hostname or, when starting with `*.`, all subdomains of a domain
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Domain(String);
+
+impl Domain {
+    fn normalize(name: &str) -> String {
+        name.trim().trim_end_matches('.').to_ascii_lowercase()
+    }
+
+    /// Returns true when the hostname matches the pattern.
+    pub fn matches(&self, hostname: &str) -> bool {
+        let hostname = Self::normalize(hostname);
+        match self.0.strip_prefix("*.") {
+            Some(domain) => hostname
+                .strip_suffix(domain)
+                .is_some_and(|x| x.len() > 1 && x.ends_with('.')),
+            None => hostname == self.0,
+        }
+    }
+}
+
+impl TryFrom<String> for Domain {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let domain = Self::normalize(&value);
+        let name = domain.strip_prefix("*.").unwrap_or(&domain);
+        if name.is_empty()
+            || name
+                .split('.')
+                .any(|x| x.is_empty() || !x.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        {
+            return Err(format!("`{value}` is not a domain"));
+        }
+        Ok(Self(domain))
+    }
+}
+
+impl From<Domain> for String {
+    fn from(value: Domain) -> Self {
+        value.0
+    }
+}
+
+/// Networks and domains that must never be scanned
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DenyList {
+    /// IP addresses and networks in CIDR notation, e.g. `10.0.0.0/8`
+    #[serde(default)]
+    pub networks: Vec<Network>,
+    /// Hostnames, `*.example.com` matches all subdomains of example.com
+    #[serde(default)]
+    pub domains: Vec<Domain>,
+    /// Checks the addresses hostnames resolve to against the networks and the names IP
+    /// addresses resolve to against the domains.
+    #[serde(default)]
+    pub resolve: bool,
+}
+
+impl DenyList {
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty() && self.domains.is_empty()
+    }
+
+    fn denies_address(&self, address: &IpAddr) -> bool {
+        self.networks.iter().any(|x| x.contains(address))
+    }
+
+    fn denies_name(&self, hostname: &str) -> bool {
+        self.domains.iter().any(|x| x.matches(hostname))
+    }
+
+    /// Returns true when the host of a target must not be scanned.
+    ///
+    /// Ranges, e.g. `192.168.0.1-20`, are not denied as a whole unless a denied network covers
+    /// them; their denied addresses are skipped via the excluded hosts instead.
+    pub fn denies(&self, host: &str) -> bool {
+        self.denies_with(host, &reverse_lookup)
+    }
+
+    fn denies_with(&self, host: &str, lookup_addr: &dyn Fn(&IpAddr) -> Option<String>) -> bool {
+        if let Ok(network) = host.parse::<Network>() {
+            if self.networks.iter().any(|x| x.covers(&network)) {
+                return true;
+            }
+            let single = network.prefix == Network::width(&network.address);
+            return single
+                && self.resolve
+                && !self.domains.is_empty()
+                && lookup_addr(&network.address).is_some_and(|x| self.denies_name(&x));
+        }
+        if self.denies_name(host) {
+            return true;
+        }
+        self.resolve
+            && !self.networks.is_empty()
+            && dns_lookup::lookup_host(host.trim())
+                .is_ok_and(|x| x.iter().any(|x| self.denies_address(x)))
+    }
+
+    /// Removes the denied hosts from the target and adds the denied networks and domains to
+    /// its excluded hosts.
+    ///
+    /// When resolving is enabled the addresses of ranges whose names are denied are excluded
+    /// as well; ranges too large to resolve each address are removed. This blocks on DNS
+    /// lookups. Returns the removed hosts.
+    pub fn apply(&self, target: &mut Target) -> Vec<String> {
+        self.apply_with(target, &reverse_lookup)
+    }
+
+    fn apply_with(
+        &self,
+        target: &mut Target,
+        lookup_addr: &dyn Fn(&IpAddr) -> Option<String>,
+    ) -> Vec<String> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let (mut denied, allowed): (Vec<_>, Vec<_>) = target
+            .hosts
+            .drain(..)
+            .partition(|x| self.denies_with(x, lookup_addr));
+        let mut excluded: Vec<String> = self
+            .networks
+            .iter()
+            .map(|x| x.to_string())
+            .chain(self.domains.iter().map(|x| x.0.clone()))
+            .collect();
+        for host in allowed {
+            if !self.resolve || self.domains.is_empty() {
+                target.hosts.push(host);
+                continue;
+            }
+            let (addresses, count) = expand_hosts(&Target {
+                hosts: vec![host.clone()],
+                ..Default::default()
+            });
+            if count <= 1 {
+                target.hosts.push(host);
+            } else if count > addresses.len() as u64 {
+                tracing::warn!(%host, "unable to resolve each address of the range, denying it");
+                denied.push(host);
+            } else {
+                excluded.extend(addresses.into_iter().filter(|x| {
+                    x.parse()
+                        .ok()
+                        .and_then(|x| lookup_addr(&x))
+                        .is_some_and(|x| self.denies_name(&x))
+                }));
+                target.hosts.push(host);
+            }
+        }
+        for host in excluded {
+            if !target.excluded_hosts.contains(&host) {
+                target.excluded_hosts.push(host);
+            }
+        }
+        denied
+    }
+}
+
+fn reverse_lookup(address: &IpAddr) -> Option<String> {
+    dns_lookup::lookup_addr(address).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use scannerlib::models::Target;
+
+    use super::{DenyList, Domain, Network};
+
+    fn deny_list(networks: &[&str], domains: &[&str]) -> DenyList {
+        DenyList {
+            networks: networks.iter().map(|x| x.parse().unwrap()).collect(),
+            domains: domains
+                .iter()
+                .map(|x| Domain::try_from(x.to_string()).unwrap())
+                .collect(),
+            resolve: false,
+        }
+    }
+
+    #[test]
+    fn networks() {
+        let network: Network = "10.0.0.0/8".parse().unwrap();
+        assert!(network.contains(&"10.255.0.1".parse().unwrap()));
+        assert!(!network.contains(&"11.0.0.1".parse().unwrap()));
+        assert!(!network.contains(&"::ffff:a00:1".parse().unwrap()));
+        assert!(network.covers(&"10.1.0.0/16".parse().unwrap()));
+        assert!(!network.covers(&"10.0.0.0/7".parse().unwrap()));
+        let all: Network = "::/0".parse().unwrap();
+        assert!(all.contains(&"2001:db8::1".parse().unwrap()));
+        assert_eq!(
+            "192.168.0.1".parse::<Network>().unwrap().to_string(),
+            "192.168.0.1/32"
+        );
+        for invalid in ["10.0.0.0/33", "10.0.0/8", "example.com", "::/129"] {
+            assert!(invalid.parse::<Network>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn domains() {
+        let exact = Domain::try_from("Intranet.Example.com.".to_string()).unwrap();
+        assert!(exact.matches("intranet.example.com"));
+        assert!(!exact.matches("www.intranet.example.com"));
+        let wildcard = Domain::try_from("*.example.com".to_string()).unwrap();
+        assert!(wildcard.matches("www.EXAMPLE.com"));
+        assert!(wildcard.matches("a.b.example.com."));
+        assert!(!wildcard.matches("example.com"));
+        assert!(!wildcard.matches("badexample.com"));
+        for invalid in ["", "*.", "a..b", "exa mple.com", "*.*.example.com"] {
+            assert!(Domain::try_from(invalid.to_string()).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let deny_list = deny_list(
+            &["10.0.0.0/8", "2001:db8::/32"],
+            &["*.corp.example.com", "db.example.com"],
+        );
+        let mut target = Target {
+            hosts: [
+                "10.1.2.3",
+                "10.1.0.0/16",
+                "192.168.0.0/16",
+                "192.168.0.1",
+                "2001:db8::1",
+                "www.corp.example.com",
+                "db.example.com",
+                "www.example.com",
+            ]
+            .map(String::from)
+            .to_vec(),
+            excluded_hosts: vec!["10.0.0.0/8".to_string()],
+            ..Default::default()
+        };
+        let denied = deny_list.apply(&mut target);
+        assert_eq!(
+            denied,
+            [
+                "10.1.2.3",
+                "10.1.0.0/16",
+                "2001:db8::1",
+                "www.corp.example.com",
+                "db.example.com"
+            ]
+        );
+        assert_eq!(
+            target.hosts,
+            ["192.168.0.0/16", "192.168.0.1", "www.example.com"]
+        );
+        assert_eq!(
+            target.excluded_hosts,
+            [
+                "10.0.0.0/8",
+                "2001:db8::/32",
+                "*.corp.example.com",
+                "db.example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_ranges() {
+        let mut deny_list = deny_list(&[], &["*.corp.example.com"]);
+        deny_list.resolve = true;
+        let lookup_addr = |x: &IpAddr| {
+            (x.to_string() == "192.0.2.2").then(|| "WWW.corp.example.com.".to_string())
+        };
+        let mut target = Target {
+            hosts: ["192.0.2.0/30", "198.51.100.1", "10.0.0.0/8"]
+                .map(String::from)
+                .to_vec(),
+            ..Default::default()
+        };
+        let denied = deny_list.apply_with(&mut target, &lookup_addr);
+        assert_eq!(denied, ["10.0.0.0/8"]);
+        assert_eq!(target.hosts, ["192.0.2.0/30", "198.51.100.1"]);
+        assert_eq!(target.excluded_hosts, ["*.corp.example.com", "192.0.2.2"]);
+        assert!(target.excludes("192.0.2.2"));
+        assert!(!target.excludes("192.0.2.1"));
+    }
+
+    #[test]
+    fn resolve() {
+        let mut deny_list = deny_list(&["127.0.0.0/8"], &[]);
+        assert!(!deny_list.denies("localhost"));
+        deny_list.resolve = true;
+        assert!(deny_list.denies("localhost"));
+    }
+
+    #[test]
+    fn invalid_entries_fail_to_load() {
+        let valid: DenyList =
+            toml::from_str("networks = [\"10.0.0.0/8\"]\ndomains = [\"*.example.com\"]").unwrap();
+        assert_eq!(valid, deny_list(&["10.0.0.0/8"], &["*.example.com"]));
+        assert!(toml::from_str::<DenyList>("networks = [\"10.0.0.0/40\"]").is_err());
+        assert!(toml::from_str::<DenyList>("domains = [\"exa mple.com\"]").is_err());
+    }
+}
@@ -0,0 +1,625 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Publishes results to message brokers as they arrive.
+//!
+//! Each configured exporter owns a queue and a background task holding the connection to the
+//! broker, so that an unreachable broker does not delay the handling of results. When the
+//! queue is full, results are dropped. A failed batch is sent again once after reconnecting.
+//!
+//! Each result is sent as JSON object containing the `scan_id` and the `result`. Supported are
+//! Kafka, using the produce API with record batches, and NATS core publishing.
+//!
+//! Both protocols are implemented here instead of using rdkafka or async-nats, as publishing
+//! only needs a single request type each. rdkafka builds the C library librdkafka, which would
+//! add a native dependency to openvasd.
+
+use std::{io, time::Duration};
+
+use async_trait::async_trait;
+use scannerlib::models::Result;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::mpsc::{self, error::TrySendError},
+};
+
+/// Number of results that are queued per exporter while the broker is unreachable
+const QUEUE_SIZE: usize = 4096;
+/// Maximum number of results sent at once
+const BATCH_SIZE: usize = 256;
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum size of a Kafka response, a produce response for one partition has a few dozen bytes
+const MAX_KAFKA_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// Configuration of a Kafka exporter
+///
+/// The client is minimal: it does not request metadata to discover the leader of the partition
+/// and supports neither TLS nor SASL. The configured address must therefore be the plaintext
+/// listener of the broker leading the partition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Kafka {
+    /// Address of the broker that leads the partition, e.g. "localhost:9092"
+    pub address: String,
+    pub topic: String,
+    #[serde(default)]
+    pub partition: i32,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+}
+
+/// Configuration of a NATS exporter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Nats {
+    /// Address of the server, e.g. "localhost:4222"
+    pub address: String,
+    pub subject: String,
+    /// Token used to authenticate
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+fn default_client_id() -> String {
+    "openvasd".to_string()
+}
+
+/// A message broker results are published to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Exporter {
+    Kafka(Kafka),
+    Nats(Nats),
+}
+
+impl Exporter {
+    fn address(&self) -> &str {
+        match self {
+            Exporter::Kafka(x) => &x.address,
+            Exporter::Nats(x) => &x.address,
+        }
+    }
+
+    /// Returns the topic or subject the results are published to
+    fn destination(&self) -> &str {
+        match self {
+            Exporter::Kafka(x) => &x.topic,
+            Exporter::Nats(x) => &x.subject,
+        }
+    }
+
+    async fn connect(&self) -> io::Result<Box<dyn Connection>> {
+        let stream = BufStream::new(TcpStream::connect(self.address()).await?);
+        Ok(match self {
+            Exporter::Kafka(config) => Box::new(KafkaConnection {
+                config: config.clone(),
+                stream,
+                correlation_id: 0,
+            }),
+            Exporter::Nats(config) => Box::new(NatsConnection::handshake(config, stream).await?),
+        })
+    }
+
+    /// Sends the queued messages until all senders are dropped
+    async fn run(self, mut receiver: mpsc::Receiver<Message>) {
+        let mut connection = None;
+        let mut messages = Vec::with_capacity(BATCH_SIZE);
+        while receiver.recv_many(&mut messages, BATCH_SIZE).await > 0 {
+            let mut published = false;
+            for _ in 0..2 {
+                if connection.is_none() {
+                    match tokio::time::timeout(TIMEOUT, self.connect()).await {
+                        Ok(Ok(x)) => connection = Some(x),
+                        Ok(Err(e)) => {
+                            tracing::warn!(address = self.address(), %e, "unable to connect to exporter");
+                            break;
+                        }
+                        Err(_) => {
+                            tracing::warn!(
+                                address = self.address(),
+                                "timeout connecting to exporter"
+                            );
+                            break;
+                        }
+                    }
+                }
+                if let Some(conn) = connection.as_mut() {
+                    match tokio::time::timeout(TIMEOUT, conn.publish(&messages)).await {
+                        Ok(Ok(())) => {
+                            published = true;
+                            break;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::warn!(
+                                address = self.address(),
+                                destination = self.destination(),
+                                count = messages.len(),
+                                %e,
+                                "unable to export results"
+                            );
+                            connection = None;
+                        }
+                        Err(_) => connection = None,
+                    }
+                }
+            }
+            if !published {
+                tracing::error!(
+                    address = self.address(),
+                    destination = self.destination(),
+                    count = messages.len(),
+                    "dropping results that could not be exported"
+                );
+            }
+            messages.clear();
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportedResult<'a> {
+    scan_id: &'a str,
+    result: &'a Result,
+}
+
+/// A serialized result with its scan id used as key
+#[derive(Debug, Clone)]
+struct Message {
+    key: String,
+    payload: Vec<u8>,
+}
+
+/// A connection to a message broker
+#[async_trait]
+trait Connection: Send {
+    /// Returns after the broker accepted all messages
+    async fn publish(&mut self, messages: &[Message]) -> io::Result<()>;
+}
+
+struct NatsConnection {
+    subject: String,
+    stream: BufStream<TcpStream>,
+}
+
+impl NatsConnection {
+    async fn handshake(config: &Nats, mut stream: BufStream<TcpStream>) -> io::Result<Self> {
+        let mut info = String::new();
+        stream.read_line(&mut info).await?;
+        if !info.starts_with("INFO") {
+            return Err(io::Error::other(format!(
+                "unexpected greeting {}",
+                info.trim()
+            )));
+        }
+        let connect = serde_json::json!({
+            "verbose": false,
+            "pedantic": false,
+            "name": "openvasd",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+            "auth_token": config.token,
+        });
+        stream
+            .write_all(format!("CONNECT {connect}\r\n").as_bytes())
+            .await?;
+        let mut connection = Self {
+            subject: config.subject.clone(),
+            stream,
+        };
+        connection.ping().await?;
+        Ok(connection)
+    }
+
+    /// Sends a PING and waits for the PONG, so that all previous messages were processed
+    async fn ping(&mut self) -> io::Result<()> {
+        self.stream.write_all(b"PING\r\n").await?;
+        self.stream.flush().await?;
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            match line.trim_end() {
+                "PONG" => return Ok(()),
+                "PING" => self.stream.write_all(b"PONG\r\n").await?,
+                x if x.starts_with("-ERR") => return Err(io::Error::other(x.to_string())),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Connection for NatsConnection {
+    async fn publish(&mut self, messages: &[Message]) -> io::Result<()> {
+        for message in messages {
+            self.stream
+                .write_all(format!("PUB {} {}\r\n", self.subject, message.payload.len()).as_bytes())
+                .await?;
+            self.stream.write_all(&message.payload).await?;
+            self.stream.write_all(b"\r\n").await?;
+        }
+        self.ping().await
+    }
+}
+
+const KAFKA_PRODUCE: i16 = 0;
+/// Oldest version of the produce request supporting record batches
+const KAFKA_PRODUCE_VERSION: i16 = 3;
+
+/// Table of the CRC-32C (Castagnoli) used for record batches
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, x| {
+        CRC32C_TABLE[((crc ^ *x as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Appends a zigzag encoded variable length integer
+fn put_varint(buf: &mut Vec<u8>, value: i64) {
+    let mut x = ((value << 1) ^ (value >> 63)) as u64;
+    while x >= 0x80 {
+        buf.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as i16).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Encodes the messages as record batch of version 2
+fn record_batch(messages: &[Message], timestamp: i64) -> Vec<u8> {
+    let mut batch = vec![];
+    // attributes: no compression, create time
+    batch.extend_from_slice(&0i16.to_be_bytes());
+    batch.extend_from_slice(&(messages.len() as i32 - 1).to_be_bytes());
+    batch.extend_from_slice(&timestamp.to_be_bytes());
+    batch.extend_from_slice(&timestamp.to_be_bytes());
+    // producer id, epoch and base sequence are not used without idempotence
+    batch.extend_from_slice(&(-1i64).to_be_bytes());
+    batch.extend_from_slice(&(-1i16).to_be_bytes());
+    batch.extend_from_slice(&(-1i32).to_be_bytes());
+    batch.extend_from_slice(&(messages.len() as i32).to_be_bytes());
+    for (i, message) in messages.iter().enumerate() {
+        let mut record = vec![0];
+        put_varint(&mut record, 0);
+        put_varint(&mut record, i as i64);
+        put_varint(&mut record, message.key.len() as i64);
+        record.extend_from_slice(message.key.as_bytes());
+        put_varint(&mut record, message.payload.len() as i64);
+        record.extend_from_slice(&message.payload);
+        put_varint(&mut record, 0);
+        put_varint(&mut batch, record.len() as i64);
+        batch.extend_from_slice(&record);
+    }
+    let mut result = Vec::with_capacity(batch.len() + 21);
+    result.extend_from_slice(&0i64.to_be_bytes());
+    // partition leader epoch, magic and crc precede the checksummed part
+    result.extend_from_slice(&(batch.len() as i32 + 9).to_be_bytes());
+    result.extend_from_slice(&(-1i32).to_be_bytes());
+    result.push(2);
+    result.extend_from_slice(&crc32c(&batch).to_be_bytes());
+    result.extend_from_slice(&batch);
+    result
+}
+
+struct KafkaConnection {
+    config: Kafka,
+    stream: BufStream<TcpStream>,
+    correlation_id: i32,
+}
+
+impl KafkaConnection {
+    fn produce_request(&self, messages: &[Message], timestamp: i64) -> Vec<u8> {
+        let mut request = vec![0; 4];
+        request.extend_from_slice(&KAFKA_PRODUCE.to_be_bytes());
+        request.extend_from_slice(&KAFKA_PRODUCE_VERSION.to_be_bytes());
+        request.extend_from_slice(&self.correlation_id.to_be_bytes());
+        put_string(&mut request, &self.config.client_id);
+        // no transactional id
+        request.extend_from_slice(&(-1i16).to_be_bytes());
+        // acks of the leader
+        request.extend_from_slice(&1i16.to_be_bytes());
+        request.extend_from_slice(&(TIMEOUT.as_millis() as i32).to_be_bytes());
+        request.extend_from_slice(&1i32.to_be_bytes());
+        put_string(&mut request, &self.config.topic);
+        request.extend_from_slice(&1i32.to_be_bytes());
+        request.extend_from_slice(&self.config.partition.to_be_bytes());
+        let batch = record_batch(messages, timestamp);
+        request.extend_from_slice(&(batch.len() as i32).to_be_bytes());
+        request.extend_from_slice(&batch);
+        let size = (request.len() - 4) as i32;
+        request[..4].copy_from_slice(&size.to_be_bytes());
+        request
+    }
+}
+
+/// Reads a response, rejecting sizes beyond MAX_KAFKA_RESPONSE_SIZE
+async fn read_response<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let size = reader.read_i32().await?;
+    let size = usize::try_from(size)
+        .ok()
+        .filter(|x| *x <= MAX_KAFKA_RESPONSE_SIZE)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid response size {size}"),
+            )
+        })?;
+    let mut response = vec![0; size];
+    reader.read_exact(&mut response).await?;
+    Ok(response)
+}
+
+/// Returns the error code of the first partition of the produce response to the request with
+/// the correlation id
+fn produce_error_code(response: &[u8], correlation_id: i32) -> io::Result<i16> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid produce response");
+    let id = response.get(..4).ok_or_else(invalid)?;
+    if id != correlation_id.to_be_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "produce response to another request",
+        ));
+    }
+    // number of topics
+    let topic_len = response.get(8..10).ok_or_else(invalid)?;
+    let offset = 10 + i16::from_be_bytes([topic_len[0], topic_len[1]]) as usize;
+    // number of partitions and partition index
+    let code = response.get(offset + 8..offset + 10).ok_or_else(invalid)?;
+    Ok(i16::from_be_bytes([code[0], code[1]]))
+}
+
+#[async_trait]
+impl Connection for KafkaConnection {
+    async fn publish(&mut self, messages: &[Message]) -> io::Result<()> {
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|x| x.as_millis() as i64)
+            .unwrap_or_default();
+        let request = self.produce_request(messages, timestamp);
+        self.stream.write_all(&request).await?;
+        self.stream.flush().await?;
+        let response = read_response(&mut self.stream).await?;
+        match produce_error_code(&response, self.correlation_id)? {
+            0 => Ok(()),
+            code => Err(io::Error::other(format!(
+                "broker rejected records with error code {code}"
+            ))),
+        }
+    }
+}
+
+/// Queues results for all configured exporters
+#[derive(Debug, Default)]
+pub struct Exporters {
+    senders: Vec<(String, mpsc::Sender<Message>)>,
+}
+
+impl Exporters {
+    /// Starts a background task per exporter.
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn new(exporters: &[Exporter]) -> Self {
+        let senders = exporters
+            .iter()
+            .map(|exporter| {
+                let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+                tokio::spawn(exporter.clone().run(receiver));
+                (exporter.address().to_string(), sender)
+            })
+            .collect();
+        Self { senders }
+    }
+
+    /// Queues the results of a scan without waiting for the brokers
+    pub fn export(&self, id: &str, results: &[Result]) {
+        if self.senders.is_empty() {
+            return;
+        }
+        for result in results {
+            let payload = match serde_json::to_vec(&ExportedResult {
+                scan_id: id,
+                result,
+            }) {
+                Ok(x) => x,
+                Err(e) => {
+                    tracing::warn!(%e, "unable to serialize result");
+                    continue;
+                }
+            };
+            let message = Message {
+                key: id.to_string(),
+                payload,
+            };
+            for (address, sender) in self.senders.iter() {
+                match sender.try_send(message.clone()) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        tracing::warn!(address, "export queue is full, dropping result")
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        tracing::warn!(address, "exporter stopped, dropping result")
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::Result;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn result(id: usize) -> Result {
+        Result {
+            id,
+            message: Some("found".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn crc32c() {
+        assert_eq!(super::crc32c(b"123456789"), 0xe306_9283);
+    }
+
+    #[test]
+    fn varint() {
+        let mut buf = vec![];
+        for x in [0, -1, 1, 150] {
+            put_varint(&mut buf, x);
+        }
+        assert_eq!(buf, vec![0, 1, 2, 0xac, 0x02]);
+    }
+
+    #[test]
+    fn record_batch() {
+        let message = Message {
+            key: "a".to_string(),
+            payload: b"{}".to_vec(),
+        };
+        let batch = super::record_batch(&[message.clone(), message], 1);
+        let length = i32::from_be_bytes(batch[8..12].try_into().unwrap());
+        assert_eq!(length as usize, batch.len() - 12);
+        assert_eq!(batch[16], 2);
+        let crc = u32::from_be_bytes(batch[17..21].try_into().unwrap());
+        assert_eq!(crc, super::crc32c(&batch[21..]));
+        // amount of records
+        assert_eq!(&batch[57..61], &[0, 0, 0, 2]);
+        // length, attributes, timestamp and offset delta, key and value of the second record
+        assert_eq!(&batch[71..80], &[18, 0, 0, 2, 2, b'a', 4, b'{', b'}']);
+    }
+
+    #[tokio::test]
+    async fn nats() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufStream::new(stream);
+            stream.write_all(b"INFO {}\r\n").await.unwrap();
+            stream.flush().await.unwrap();
+            let mut lines = vec![];
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if line == "PING\r\n" {
+                    stream.write_all(b"PONG\r\n").await.unwrap();
+                    stream.flush().await.unwrap();
+                    if lines.len() > 1 {
+                        return lines;
+                    }
+                } else {
+                    lines.push(line);
+                }
+            }
+        });
+        let exporters = Exporters::new(&[Exporter::Nats(Nats {
+            address,
+            subject: "results".to_string(),
+            token: Some("secret".to_string()),
+        })]);
+        exporters.export("scan", &[result(1)]);
+        let lines = server.await.unwrap();
+        assert!(lines[0].starts_with("CONNECT {"));
+        assert!(lines[0].contains(r#""auth_token":"secret""#));
+        assert!(lines[1].starts_with("PUB results "));
+        let exported: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(exported["scan_id"], "scan");
+        assert_eq!(exported["result"]["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn kafka() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let size = stream.read_i32().await.unwrap();
+            let mut request = vec![0; size as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            let mut response = vec![];
+            response.extend_from_slice(&request[4..8]);
+            response.extend_from_slice(&1i32.to_be_bytes());
+            put_string(&mut response, "results");
+            response.extend_from_slice(&1i32.to_be_bytes());
+            response.extend_from_slice(&0i32.to_be_bytes());
+            response.extend_from_slice(&0i16.to_be_bytes());
+            response.extend_from_slice(&[0; 20]);
+            stream.write_i32(response.len() as i32).await.unwrap();
+            stream.write_all(&response).await.unwrap();
+            request
+        });
+        let exporters = Exporters::new(&[Exporter::Kafka(Kafka {
+            address,
+            topic: "results".to_string(),
+            partition: 0,
+            client_id: default_client_id(),
+        })]);
+        exporters.export("scan", &[result(1), result(2)]);
+        let request = server.await.unwrap();
+        assert_eq!(&request[..4], &[0, 0, 0, 3]);
+        // client id, transactional id, acks, timeout and number of topics
+        assert_eq!(&request[8..18], b"\0\x08openvasd");
+        assert_eq!(&request[30..39], b"\0\x07results");
+        let batch = &request[51..];
+        let crc = u32::from_be_bytes(batch[17..21].try_into().unwrap());
+        assert_eq!(crc, super::crc32c(&batch[21..]));
+    }
+
+    #[test]
+    fn error_code() {
+        let mut response = vec![0, 0, 0, 1, 0, 0, 0, 1];
+        put_string(&mut response, "t");
+        response.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 3]);
+        assert_eq!(produce_error_code(&response, 1).unwrap(), 3);
+        assert!(produce_error_code(&response, 2).is_err());
+        assert!(produce_error_code(&response[..12], 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn response_size() {
+        let mut response = 3i32.to_be_bytes().to_vec();
+        response.extend_from_slice(b"abc");
+        assert_eq!(
+            read_response(&mut response.as_slice()).await.unwrap(),
+            b"abc"
+        );
+        let oversized = (MAX_KAFKA_RESPONSE_SIZE as i32 + 1).to_be_bytes();
+        assert!(read_response(&mut oversized.as_slice()).await.is_err());
+        assert!(read_response(&mut (-1i32).to_be_bytes().as_slice())
+            .await
+            .is_err());
+    }
+}
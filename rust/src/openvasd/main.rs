@@ -5,10 +5,12 @@
 #![doc = include_str!("README.md")]
 
 use std::marker::{Send, Sync};
+use std::sync::Arc;
 
 use config::{Config, Mode, ScannerType};
 use controller::{Context, ContextBuilder};
 use notus::NotusWrapper;
+use scannerlib::logging::{syslog, JsonFields, JsonLines, LogFormat};
 use scannerlib::models::scanner::{
     ScanDeleter, ScanResultFetcher, ScanStarter, ScanStopper, Scanner,
 };
@@ -16,36 +18,77 @@ use scannerlib::nasl::FSPluginLoader;
 use scannerlib::notus::{HashsumProductLoader, Notus};
 use scannerlib::openvas::{self, cmd};
 use scannerlib::osp;
-use scannerlib::scanner::ScannerStackWithStorage;
+use scannerlib::scanner::{Quarantine, ScannerStackWithStorage};
 use scannerlib::storage::infisto::{ChaCha20IndexFileStorer, IndexedFileStorer};
 use storage::{FromConfigAndFeeds, Storage};
 use tls::tls_config;
-use tracing::{info, metadata::LevelFilter, warn};
-use tracing_subscriber::EnvFilter;
+use tracing::{info, warn};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt};
 
 use crate::{
     config::StorageType,
     crypt::ChaCha20Crypt,
     storage::{file, inmemory, redis, FeedHash},
 };
+mod bundle;
 pub mod config;
 pub mod controller;
+mod coordinator;
 pub mod crypt;
+mod deny_list;
+mod export;
 pub mod feed;
 pub mod notus;
+mod plan;
 pub mod preference;
+pub mod report;
 pub mod request;
 pub mod response;
+mod s3;
 mod scheduling;
 pub mod storage;
 pub mod tls;
+mod vt_filter;
+mod watch;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 fn setup_log(config: &Config) {
-    let filter = EnvFilter::builder()
-        .with_default_directive(LevelFilter::INFO.into())
-        .parse_lossy(format!("{},rustls=info,h2=info", &config.log.level));
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let (filter, handle) = reload::Layer::new(controller::reload::log_filter(config));
+    let (text, json) = match config.log.format {
+        LogFormat::Text => (Some(fmt::layer()), None),
+        LogFormat::Json => (
+            None,
+            Some(fmt::layer().fmt_fields(JsonFields).event_format(JsonLines)),
+        ),
+    };
+    let (syslog, syslog_error) = match config.log.syslog.as_ref().map(syslog::layer) {
+        Some(Ok(layer)) => (Some(layer), None),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+    let (journald, journald_error) = match config.log.journald.then(tracing_journald::layer) {
+        Some(Ok(layer)) => (
+            Some(layer.with_syslog_identifier("openvasd".to_string())),
+            None,
+        ),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(syslog)
+        .with(journald)
+        .init();
+    controller::reload::set_log_filter(handle);
+    // the sinks that failed can only be reported once the subscriber is initialized
+    if let Some(e) = syslog_error {
+        warn!(%e, "unable to send log messages to syslog");
+    }
+    if let Some(e) = journald_error {
+        warn!(%e, "unable to send log messages to journald");
+    }
 }
 
 fn get_feeds(config: &Config) -> Vec<FeedHash> {
@@ -100,6 +143,7 @@ where
 async fn create_context<DB, ScanHandler>(
     db: DB,
     sh: ScanHandler,
+    quarantine: Option<Arc<Quarantine>>,
     config: &Config,
 ) -> Context<ScanHandler, DB>
 where
@@ -117,10 +161,16 @@ where
         Err(e) => warn!("Notus Scanner disabled: {e}"),
     }
     tracing::warn!(enable_get_scans = config.endpoints.enable_get_scans);
+    if let Some(quarantine) = quarantine {
+        ctx_builder = ctx_builder.quarantine(quarantine);
+    }
 
     ctx_builder
         .mode(config.mode.clone())
         .scheduler_config(config.scheduler.clone())
+        .exporters(config.exporters.clone())
+        .s3(config.s3.clone())
+        .deny_list(config.deny_list.clone())
         .feed_config(config.feed.clone())
         .await
         .scanner(sh)
@@ -134,13 +184,14 @@ where
 async fn run_with_scanner_and_storage<Sc, St>(
     scanner: Sc,
     storage: St,
+    quarantine: Option<Arc<Quarantine>>,
     config: &Config,
 ) -> Result<()>
 where
     St: Storage + Send + Sync + 'static,
     Sc: Scanner + Send + Sync + 'static,
 {
-    let ctx = create_context(storage, scanner, config).await;
+    let ctx = create_context(storage, scanner, quarantine, config).await;
     controller::run(ctx, config).await
 }
 
@@ -154,16 +205,21 @@ where
     match config.scanner.scanner_type {
         ScannerType::OSPD => {
             let scanner = make_osp_scanner(config);
-            run_with_scanner_and_storage(scanner, storage, config).await
+            run_with_scanner_and_storage(scanner, storage, None, config).await
         }
         ScannerType::Openvas => {
             let scanner = make_openvas_scanner(config.clone());
-            run_with_scanner_and_storage(scanner, storage, config).await
+            run_with_scanner_and_storage(scanner, storage, None, config).await
         }
         ScannerType::Openvasd => {
             let storage = std::sync::Arc::new(storage::UserNASLStorageForKBandVT::new(storage));
             let scanner = make_openvasd_scanner(config, storage.clone());
-            run_with_scanner_and_storage(scanner, storage, config).await
+            let quarantine = scanner.quarantine();
+            run_with_scanner_and_storage(scanner, storage, Some(quarantine), config).await
+        }
+        ScannerType::Coordinator => {
+            let scanner = coordinator::Coordinator::new(config.scanner.workers.clone())?;
+            run_with_scanner_and_storage(scanner, storage, None, config).await
         }
     }
 }
@@ -0,0 +1,462 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Plans a scan without executing it.
+//!
+//! A plan contains the hosts after expanding the ranges of the target and removing the excluded
+//! hosts, the VTs selected by the scan, its filter and their dependencies, as well as the phases
+//! the VTs are executed in. This allows to validate the scope of a scan before starting it.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use scannerlib::{models::Target, scheduling::Stage, storage::item::Nvt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scheduling::Scheduler,
+    storage::{Error, NVTStorer, ProgressGetter, Storage},
+};
+
+/// Maximum amount of hosts listed in a plan
+pub const MAX_LISTED_HOSTS: usize = 65536;
+
+/// VTs of a stage that are executed concurrently
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlannedPhase {
+    /// Stage of the VTs, e.g. discovery
+    pub stage: String,
+    /// OIDs of the VTs per wave, a wave starts when the previous one is finished
+    pub waves: Vec<Vec<String>>,
+}
+
+/// Plan of a scan
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// Hosts that are scanned, limited to MAX_LISTED_HOSTS
+    pub hosts: Vec<String>,
+    /// Amount of hosts that are scanned
+    pub host_count: u64,
+    /// OIDs of all VTs that are executed
+    pub vts: Vec<String>,
+    /// Amount of VTs that are selected by the VT filter of the scan
+    pub selected_by_filter: usize,
+    /// OIDs of the VTs that are executed as dependency of other VTs
+    pub dependencies: Vec<String>,
+    /// OIDs of the VTs of the scan that are not part of the feed
+    pub unknown_vts: Vec<String>,
+    /// Filenames of dependencies that are not part of the feed
+    pub missing_dependencies: Vec<String>,
+    /// Phases in the order of execution
+    pub phases: Vec<PlannedPhase>,
+}
+
+/// Inclusive range of addresses of the same family
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Range {
+    v6: bool,
+    start: u128,
+    end: u128,
+}
+
+impl Range {
+    fn bits(address: IpAddr) -> (bool, u128) {
+        match address {
+            IpAddr::V4(x) => (false, u32::from(x) as u128),
+            IpAddr::V6(x) => (true, u128::from(x)),
+        }
+    }
+
+    fn address(&self, bits: u128) -> IpAddr {
+        if self.v6 {
+            IpAddr::V6(Ipv6Addr::from(bits))
+        } else {
+            IpAddr::V4(Ipv4Addr::from(bits as u32))
+        }
+    }
+
+    /// Parses an IP address, a network in CIDR notation or a range.
+    ///
+    /// Like openvas the network and broadcast address of networks are not scanned, they are
+    /// only part of the range when it is excluded. Ranges are given by their first and last address or, for IPv4, by the last address
+    /// with the last octet only, e.g. `192.168.0.1-20`.
+    fn parse(host: &str, excluded: bool) -> Option<Self> {
+        let host = host.trim();
+        if let Some((address, prefix)) = host.split_once('/') {
+            let (v6, bits) = Self::bits(address.parse().ok()?);
+            let width = if v6 { 128 } else { 32 };
+            let prefix: u32 = prefix.parse().ok().filter(|x| *x <= width)?;
+            let host_mask = u128::MAX.checked_shr(128 - (width - prefix)).unwrap_or(0);
+            let (start, end) = (bits & !host_mask, bits | host_mask);
+            return Some(if !excluded && width - prefix > 1 {
+                Self {
+                    v6,
+                    start: start + 1,
+                    end: end - 1,
+                }
+            } else {
+                Self { v6, start, end }
+            });
+        }
+        if let Some((first, last)) = host.split_once('-') {
+            let (v6, start) = Self::bits(first.parse().ok()?);
+            let end = match last.parse::<IpAddr>() {
+                Ok(last) => {
+                    let (last_v6, end) = Self::bits(last);
+                    (last_v6 == v6).then_some(end)?
+                }
+                Err(_) if !v6 => start & !0xff | last.parse::<u8>().ok()? as u128,
+                Err(_) => return None,
+            };
+            return (start <= end).then_some(Self { v6, start, end });
+        }
+        let (v6, bits) = Self::bits(host.parse().ok()?);
+        Some(Self {
+            v6,
+            start: bits,
+            end: bits,
+        })
+    }
+
+    fn len(&self) -> u128 {
+        (self.end - self.start).saturating_add(1)
+    }
+}
+
+/// Merges overlapping and adjacent ranges.
+fn merge(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.sort();
+    let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.v6 == range.v6 && range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Removes the excluded ranges from the ranges.
+fn subtract(ranges: Vec<Range>, excluded: &[Range]) -> Vec<Range> {
+    let mut result = ranges;
+    for x in excluded {
+        result = result
+            .into_iter()
+            .flat_map(|r| {
+                if r.v6 != x.v6 || x.end < r.start || x.start > r.end {
+                    return vec![r];
+                }
+                let mut parts = vec![];
+                if x.start > r.start {
+                    parts.push(Range {
+                        end: x.start - 1,
+                        ..r
+                    });
+                }
+                if x.end < r.end {
+                    parts.push(Range {
+                        start: x.end + 1,
+                        ..r
+                    });
+                }
+                parts
+            })
+            .collect();
+    }
+    result
+}
+
+/// Expands the hosts of the target and removes the excluded ones.
+///
+/// Returns at most MAX_LISTED_HOSTS hosts and the amount of all hosts.
+pub fn expand_hosts(target: &Target) -> (Vec<String>, u64) {
+    let mut ranges = vec![];
+    let mut names = vec![];
+    for host in target.hosts.iter().filter(|x| !x.trim().is_empty()) {
+        match Range::parse(host, false) {
+            Some(range) => ranges.push(range),
+            None => {
+                let name = host.trim().to_ascii_lowercase();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    let excluded = target
+        .excluded_hosts
+        .iter()
+        .filter_map(|x| Range::parse(x, true))
+        .collect();
+    // hostnames are excluded by name as well as by patterns like `*.example.com`
+    names.retain(|x| !target.excludes(x));
+    let ranges = subtract(merge(ranges), &merge(excluded));
+
+    let count = ranges
+        .iter()
+        .fold(names.len() as u128, |sum, x| sum.saturating_add(x.len()));
+    let hosts = ranges
+        .iter()
+        .flat_map(|r| (r.start..=r.end).map(|x| r.address(x).to_string()))
+        .chain(names)
+        .take(MAX_LISTED_HOSTS)
+        .collect();
+    (hosts, u64::try_from(count).unwrap_or(u64::MAX))
+}
+
+/// Resolves the VTs of the scan, the ones selected by its filter and their dependencies.
+fn plan_vts(plan: &mut Plan, scan: &scannerlib::models::Scan, feed: &[Nvt]) {
+    let by_oid: HashMap<&str, &Nvt> = feed.iter().map(|x| (x.oid.as_str(), x)).collect();
+    let by_filename: HashMap<&str, &Nvt> = feed.iter().map(|x| (x.filename.as_str(), x)).collect();
+
+    let mut scan = scan.clone();
+    if scan.vt_filter.is_some() {
+        plan.selected_by_filter = crate::vt_filter::resolve(&mut scan, feed.iter().cloned());
+    }
+    let mut selected: Vec<&Nvt> = vec![];
+    let mut known = HashSet::new();
+    for vt in &scan.vts {
+        match by_oid.get(vt.oid.as_str()) {
+            Some(nvt) if known.insert(nvt.filename.as_str()) => selected.push(nvt),
+            Some(_) => {}
+            None => plan.unknown_vts.push(vt.oid.clone()),
+        }
+    }
+
+    let mut dependencies: Vec<&Nvt> = vec![];
+    let mut unresolved: Vec<&str> = selected
+        .iter()
+        .flat_map(|x| x.dependencies.iter().map(|x| x.as_str()))
+        .collect();
+    while let Some(filename) = unresolved.pop() {
+        if known.contains(filename) {
+            continue;
+        }
+        match by_filename.get(filename) {
+            Some(nvt) => {
+                known.insert(filename);
+                unresolved.extend(nvt.dependencies.iter().map(|x| x.as_str()));
+                dependencies.push(nvt);
+            }
+            None if !plan.missing_dependencies.iter().any(|x| x == filename) => {
+                plan.missing_dependencies.push(filename.to_string())
+            }
+            None => {}
+        }
+    }
+
+    // a VT runs in the wave after its latest dependency
+    let mut waves: HashMap<&str, usize> = HashMap::new();
+    fn wave<'a>(
+        nvt: &'a Nvt,
+        by_filename: &HashMap<&str, &'a Nvt>,
+        waves: &mut HashMap<&'a str, usize>,
+    ) -> usize {
+        if let Some(x) = waves.get(nvt.filename.as_str()) {
+            return *x;
+        }
+        // protects against cyclic dependencies
+        waves.insert(&nvt.filename, 0);
+        let result = nvt
+            .dependencies
+            .iter()
+            .filter_map(|x| by_filename.get(x.as_str()))
+            .map(|x| wave(x, by_filename, waves) + 1)
+            .max()
+            .unwrap_or(0);
+        waves.insert(&nvt.filename, result);
+        result
+    }
+
+    let mut phases: BTreeMap<(usize, usize), Vec<String>> = BTreeMap::new();
+    for nvt in selected.iter().chain(dependencies.iter()) {
+        let stage = usize::from(Stage::from(*nvt));
+        let wave = wave(nvt, &by_filename, &mut waves);
+        phases
+            .entry((stage, wave))
+            .or_default()
+            .push(nvt.oid.clone());
+    }
+    for ((stage, _), mut oids) in phases {
+        oids.sort();
+        let stage = Stage::try_from(stage)
+            .expect("stage is created from a Stage")
+            .to_string();
+        match plan.phases.last_mut() {
+            Some(last) if last.stage == stage => last.waves.push(oids),
+            _ => plan.phases.push(PlannedPhase {
+                stage,
+                waves: vec![oids],
+            }),
+        }
+    }
+
+    plan.vts = selected
+        .iter()
+        .chain(dependencies.iter())
+        .map(|x| x.oid.clone())
+        .collect();
+    plan.vts.sort();
+    plan.dependencies = dependencies.iter().map(|x| x.oid.clone()).collect();
+    plan.dependencies.sort();
+}
+
+/// Creates the plan of the scan with the given id.
+pub async fn create<DB, S>(scheduler: &Scheduler<DB, S>, id: &str) -> Result<Plan, Error>
+where
+    DB: Storage + Send + Sync + 'static,
+    S: Send + Sync,
+{
+    let (scan, _) = scheduler.get_scan(id).await?;
+    let feed: Vec<Nvt> = scheduler.vts().await?.collect();
+    let (hosts, host_count) = expand_hosts(&scan.target);
+    let mut plan = Plan {
+        hosts,
+        host_count,
+        ..Default::default()
+    };
+    plan_vts(&mut plan, &scan, &feed);
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::{
+        models::{Scan, Target, VTFilter, VT},
+        nasl::syntax::ACT,
+        storage::item::Nvt,
+    };
+
+    use super::{expand_hosts, plan_vts, Plan, PlannedPhase};
+
+    fn target(hosts: &[&str], excluded_hosts: &[&str]) -> Target {
+        Target {
+            hosts: hosts.iter().map(|x| x.to_string()).collect(),
+            excluded_hosts: excluded_hosts.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hosts() {
+        let (hosts, count) = expand_hosts(&target(
+            &[
+                "192.168.0.0/30",
+                "192.168.0.2-4",
+                "10.0.0.1",
+                "localhost",
+                "LOCALHOST",
+            ],
+            &["192.168.0.3", "example.com"],
+        ));
+        assert_eq!(
+            hosts,
+            [
+                "10.0.0.1",
+                "192.168.0.1",
+                "192.168.0.2",
+                "192.168.0.4",
+                "localhost"
+            ]
+        );
+        assert_eq!(count, 5);
+
+        let (hosts, count) = expand_hosts(&target(
+            &["2001:db8::1-2001:db8::3", "192.168.1.0/31"],
+            &["localhost"],
+        ));
+        assert_eq!(
+            hosts,
+            [
+                "192.168.1.0",
+                "192.168.1.1",
+                "2001:db8::1",
+                "2001:db8::2",
+                "2001:db8::3"
+            ]
+        );
+        assert_eq!(count, 5);
+
+        let (hosts, count) = expand_hosts(&target(
+            &["www.corp.example.com", "corp.example.com", "10.0.0.1"],
+            &["*.corp.example.com"],
+        ));
+        assert_eq!(hosts, ["10.0.0.1", "corp.example.com"]);
+        assert_eq!(count, 2);
+
+        let (hosts, count) = expand_hosts(&target(&["10.0.0.0/8", "::/0"], &["10.0.0.0/9"]));
+        assert_eq!(hosts.len(), super::MAX_LISTED_HOSTS);
+        assert_eq!(hosts[0], "10.128.0.0");
+        assert_eq!(hosts[1], "10.128.0.1");
+        assert_eq!(count, u64::MAX);
+    }
+
+    fn nvt(oid: &str, category: ACT, dependencies: &[&str]) -> Nvt {
+        Nvt {
+            oid: oid.to_string(),
+            filename: format!("{oid}.nasl"),
+            family: if oid.starts_with('w') { "web" } else { "other" }.to_string(),
+            category,
+            dependencies: dependencies.iter().map(|x| format!("{x}.nasl")).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn vts() {
+        let feed = vec![
+            nvt("ports", ACT::Scanner, &[]),
+            nvt("banner", ACT::GatherInfo, &["ports"]),
+            nvt("version", ACT::GatherInfo, &["banner"]),
+            nvt("vuln", ACT::Attack, &["version", "missing"]),
+            nvt("web", ACT::Attack, &["banner"]),
+            nvt("unused", ACT::Attack, &[]),
+        ];
+        let scan = Scan {
+            vts: ["vuln", "unknown"]
+                .map(|oid| VT {
+                    oid: oid.to_string(),
+                    parameters: vec![],
+                })
+                .to_vec(),
+            vt_filter: Some(VTFilter {
+                families: vec!["web".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut plan = Plan::default();
+        plan_vts(&mut plan, &scan, &feed);
+        let strings = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            plan.vts,
+            strings(&["banner", "ports", "version", "vuln", "web"])
+        );
+        assert_eq!(plan.selected_by_filter, 1);
+        assert_eq!(plan.dependencies, strings(&["banner", "ports", "version"]));
+        assert_eq!(plan.unknown_vts, strings(&["unknown"]));
+        assert_eq!(plan.missing_dependencies, strings(&["missing.nasl"]));
+        assert_eq!(
+            plan.phases,
+            vec![
+                PlannedPhase {
+                    stage: "discovery".to_string(),
+                    waves: vec![
+                        strings(&["ports"]),
+                        strings(&["banner"]),
+                        strings(&["version"])
+                    ],
+                },
+                PlannedPhase {
+                    stage: "non_evasive".to_string(),
+                    waves: vec![strings(&["web"]), strings(&["vuln"])],
+                },
+            ]
+        );
+    }
+}
@@ -5,7 +5,7 @@
 use lazy_static::lazy_static;
 use scannerlib::models::{PreferenceValue, ScanPreferenceInformation};
 
-pub const PREFERENCES: [ScanPreferenceInformation; 22] = [
+pub const PREFERENCES: [ScanPreferenceInformation; 31] = [
     ScanPreferenceInformation {
         id: "auto_enable_dependencies",
         name: "Automatic Enable Dependencies",
@@ -83,6 +83,14 @@ pub const PREFERENCES: [ScanPreferenceInformation; 22] = [
         false positives. If you are not sure that the banners of the remote \
         host have been tampered with, you can disable this option.",
     },
+    ScanPreferenceInformation {
+        id: "plugins_max_value_size",
+        name: "Plugins Maximum Value Size",
+        default: PreferenceValue::Int(268435456),
+        description: "This is the maximum size of a value created by a plugin, in bytes for \
+        strings and data or in elements for arrays. A plugin exceeding it is aborted before \
+        it exhausts the memory of the scanner. Set it to 0 to disable the limit.",
+    },
     ScanPreferenceInformation {
         id: "plugins_timeout",
         name: "Plugins Timeout",
@@ -212,6 +220,72 @@ pub const PREFERENCES: [ScanPreferenceInformation; 22] = [
         description: "Amount of fake results generated per each host in the target \
         list for a dry run scan.",
     },
+    ScanPreferenceInformation {
+        id: "source_iface",
+        name: "Source Interface",
+        default: PreferenceValue::String(""),
+        description: "Name of the network interface all connections and packets of the scan \
+        are sent from. This is required for scanner hosts with multiple network interfaces. When \
+        empty the interface is chosen by the routing table.",
+    },
+    ScanPreferenceInformation {
+        id: "source_ip",
+        name: "Source IP",
+        default: PreferenceValue::String(""),
+        description: "Source address of all connections and packets of the scan. At most one \
+        IPv4 and one IPv6 address can be given, separated by a comma. When empty the address is \
+        chosen by the routing table.",
+    },
+    ScanPreferenceInformation {
+        id: "address_family_preference",
+        name: "Preferred Address Family",
+        default: PreferenceValue::String("ipv6"),
+        description: "Address family, either 'ipv6' or 'ipv4', that is tried first when a host \
+        name resolves to IPv4 and IPv6 addresses. Connection attempts to the addresses are raced \
+        as described in RFC 8305 (Happy Eyeballs), so that dual-stack hosts are scanned on the \
+        address family that actually answers.",
+    },
+    ScanPreferenceInformation {
+        id: "report_script_stats",
+        name: "Report Script Statistics",
+        default: PreferenceValue::Bool(false),
+        description: "Attaches the execution duration, the number of bytes sent and received \
+        and the number of retransmissions of the script to each result. This helps to find \
+        scripts that are expensive on real networks.",
+    },
+    ScanPreferenceInformation {
+        id: "taint_tracking",
+        name: "Taint Tracking (experimental)",
+        default: PreferenceValue::Bool(false),
+        description: "Marks data received from the network and logs when a script passes it to \
+        builtins accessing files or executing commands. The observed flows are attached to the \
+        results created afterwards, which helps to audit the safety of feed scripts.",
+    },
+    ScanPreferenceInformation {
+        id: "dns_resolver",
+        name: "Encrypted DNS Resolver",
+        default: PreferenceValue::String(""),
+        description: "Comma separated list of DNS over TLS (e.g. tls://9.9.9.9) or DNS over \
+        HTTPS (e.g. https://1.1.1.1/dns-query) resolvers used for all name lookups of the scan \
+        instead of the system resolver. Resolvers should be given by IP address, as their \
+        hostnames are resolved by the system resolver.",
+    },
+    ScanPreferenceInformation {
+        id: "network_timeout",
+        name: "Network Timeout",
+        default: PreferenceValue::Int(0),
+        description: "Number of seconds to wait for a host when connecting or when waiting for \
+        the answer to a probe, unless the script sets its own timeout. When 0 the default of \
+        each function is used, e.g. 10 seconds to connect.",
+    },
+    ScanPreferenceInformation {
+        id: "adaptive_timeout",
+        name: "Adaptive Network Timeout",
+        default: PreferenceValue::Bool(false),
+        description: "Tracks the round trip times observed for each host and shortens the \
+        network timeout of hosts answering quickly, down to 500 milliseconds. This significantly \
+        reduces the scan duration of large networks with mostly filtered ports.",
+    },
 ];
 
 lazy_static! {
@@ -0,0 +1,618 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Renders the results of a finished scan as report.
+
+use std::collections::HashMap;
+
+use quick_xml::events::BytesText;
+use scannerlib::{
+    models::{Classification, Result, ResultType, SeverityClass, Status},
+    storage::item::{Nvt, TagKey},
+};
+use serde::{Deserialize, Serialize};
+
+/// Format of a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// Object containing the scan id, the status and the results
+    Json,
+    /// A line per result
+    Csv,
+    /// Static Analysis Results Interchange Format 2.1.0 containing the findings
+    Sarif,
+    /// Report XML as exported by gvmd
+    Xml,
+}
+
+/// A finished scan
+#[derive(Debug, Default)]
+pub struct Report {
+    pub id: String,
+    pub status: Status,
+    pub results: Vec<Result>,
+    /// VTs of the results by their OID, unknown VTs are missing
+    pub vts: HashMap<String, Nvt>,
+}
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Sarif => "sarif",
+            ReportFormat::Xml => "xml",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "application/json",
+            ReportFormat::Csv => "text/csv",
+            ReportFormat::Sarif => "application/sarif+json",
+            ReportFormat::Xml => "application/xml",
+        }
+    }
+
+    pub fn render(&self, report: &Report) -> Vec<u8> {
+        match self {
+            ReportFormat::Json => serde_json::to_vec(&serde_json::json!({
+                "scan_id": report.id,
+                "status": report.status,
+                "results": report.results,
+            }))
+            .unwrap_or_default(),
+            ReportFormat::Csv => csv(&report.results).into_bytes(),
+            ReportFormat::Sarif => serde_json::to_vec(&sarif(report)).unwrap_or_default(),
+            ReportFormat::Xml => gvm_xml(report).unwrap_or_default(),
+        }
+    }
+}
+
+/// Quotes a field as described in RFC 4180 when necessary
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(results: &[Result]) -> String {
+    let mut report = "id,type,ip_address,hostname,oid,port,protocol,message\r\n".to_string();
+    for result in results {
+        let r_type = serde_json::to_value(&result.r_type).unwrap_or_default();
+        let fields = [
+            result.id.to_string(),
+            r_type.as_str().unwrap_or_default().to_string(),
+            result.ip_address.clone().unwrap_or_default(),
+            result.hostname.clone().unwrap_or_default(),
+            result.oid.clone().unwrap_or_default(),
+            result.port.map(|x| x.to_string()).unwrap_or_default(),
+            result.protocol.map(|x| x.to_string()).unwrap_or_default(),
+            result.message.clone().unwrap_or_default(),
+        ];
+        let line: Vec<String> = fields.iter().map(|x| csv_field(x)).collect();
+        report.push_str(&line.join(","));
+        report.push_str("\r\n");
+    }
+    report
+}
+
+/// Returns the CVSS base score of a VT
+pub(crate) fn cvss_base(vt: Option<&Nvt>) -> Option<f32> {
+    vt?.tag.get(&TagKey::CvssBase)?.to_string().parse().ok()
+}
+
+/// Returns the stored severity class of a finding or, for results stored without one, the class
+/// derived from the score of its VT
+fn severity_class(result: &Result, vt: Option<&Nvt>) -> SeverityClass {
+    result
+        .severity
+        .or_else(|| Classification::default().classify(result, cvss_base(vt), None))
+        .unwrap_or(SeverityClass::Medium)
+}
+
+/// Maps the severity class of a finding to a SARIF level
+fn sarif_level(class: SeverityClass) -> &'static str {
+    match class {
+        SeverityClass::Critical | SeverityClass::High => "error",
+        SeverityClass::Medium => "warning",
+        SeverityClass::Low | SeverityClass::Log => "note",
+    }
+}
+
+fn sarif_rule(oid: &str, vt: Option<&Nvt>) -> serde_json::Value {
+    let Some(vt) = vt else {
+        return serde_json::json!({ "id": oid });
+    };
+    let text = |key: TagKey| {
+        vt.tag
+            .get(&key)
+            .map(|x| serde_json::json!({ "text": x.to_string() }))
+    };
+    let mut properties = serde_json::json!({ "tags": [vt.family] });
+    if let Some(score) = cvss_base(Some(vt)) {
+        // used by code scanning platforms to rank security findings
+        properties["security-severity"] = format!("{score:.1}").into();
+    }
+    let mut rule = serde_json::json!({
+        "id": oid,
+        "name": vt.name,
+        "shortDescription": { "text": vt.name },
+        "properties": properties,
+    });
+    if let Some(x) = text(TagKey::Summary) {
+        rule["fullDescription"] = x;
+    }
+    if let Some(x) = text(TagKey::Solution) {
+        rule["help"] = x;
+    }
+    rule
+}
+
+/// Converts the alarms and logs of VTs to SARIF results, each VT becomes a rule.
+///
+/// The location of a finding is the host optionally followed by the port.
+fn sarif(report: &Report) -> serde_json::Value {
+    let mut rules: Vec<serde_json::Value> = vec![];
+    let mut rule_index: HashMap<&str, usize> = HashMap::new();
+    let mut results = vec![];
+    for result in report.results.iter() {
+        if !matches!(result.r_type, ResultType::Alarm | ResultType::Log) {
+            continue;
+        }
+        let Some(oid) = result.oid.as_deref() else {
+            continue;
+        };
+        let vt = report.vts.get(oid);
+        let index = *rule_index.entry(oid).or_insert_with(|| {
+            rules.push(sarif_rule(oid, vt));
+            rules.len() - 1
+        });
+        let host = result
+            .ip_address
+            .as_deref()
+            .or(result.hostname.as_deref())
+            .unwrap_or_default();
+        let uri = match result.port {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        let message = result
+            .message
+            .clone()
+            .or_else(|| vt.map(|x| x.name.clone()))
+            .unwrap_or_else(|| oid.to_string());
+        let mut location = serde_json::json!({
+            "physicalLocation": { "artifactLocation": { "uri": uri } },
+        });
+        if let Some(hostname) = &result.hostname {
+            location["logicalLocations"] =
+                serde_json::json!([{ "name": hostname, "kind": "host" }]);
+        }
+        results.push(serde_json::json!({
+            "ruleId": oid,
+            "ruleIndex": index,
+            "level": sarif_level(severity_class(result, vt)),
+            "message": { "text": message },
+            "locations": [location],
+        }));
+    }
+    serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "openvasd",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/greenbone/openvas-scanner",
+                    "rules": rules,
+                }
+            },
+            "automationDetails": { "id": format!("{}/", report.id) },
+            "results": results,
+        }]
+    })
+}
+
+type XmlWriter = quick_xml::Writer<std::io::Cursor<Vec<u8>>>;
+
+/// Formats a unix timestamp as used by gvmd
+fn iso_time(timestamp: Option<u64>) -> String {
+    timestamp
+        .and_then(|x| chrono::DateTime::from_timestamp(x as i64, 0))
+        .map(|x| x.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the threat level of gvmd for a severity class
+fn threat(class: SeverityClass) -> &'static str {
+    // the report format has no critical threat
+    match class {
+        SeverityClass::Critical | SeverityClass::High => "High",
+        SeverityClass::Medium => "Medium",
+        SeverityClass::Low => "Low",
+        SeverityClass::Log => "Log",
+    }
+}
+
+fn gvm_port(result: &Result) -> String {
+    let protocol = result
+        .protocol
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| "tcp".to_string());
+    match result.port {
+        Some(port) if port > 0 => format!("{port}/{protocol}"),
+        _ => format!("general/{protocol}"),
+    }
+}
+
+fn text_element(writer: &mut XmlWriter, name: &str, value: &str) -> quick_xml::Result<()> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(value))?;
+    Ok(())
+}
+
+fn gvm_host(writer: &mut XmlWriter, result: &Result) -> quick_xml::Result<()> {
+    writer
+        .create_element("host")
+        .write_inner_content(|writer| {
+            writer.write_event(quick_xml::events::Event::Text(BytesText::new(
+                result.ip_address.as_deref().unwrap_or_default(),
+            )))?;
+            text_element(
+                writer,
+                "hostname",
+                result.hostname.as_deref().unwrap_or_default(),
+            )
+        })?;
+    Ok(())
+}
+
+fn gvm_nvt(writer: &mut XmlWriter, oid: &str, vt: Option<&Nvt>) -> quick_xml::Result<()> {
+    writer
+        .create_element("nvt")
+        .with_attribute(("oid", oid))
+        .write_inner_content(|writer| {
+            text_element(writer, "type", "nvt")?;
+            let Some(vt) = vt else {
+                return Ok(());
+            };
+            text_element(writer, "name", &vt.name)?;
+            text_element(writer, "family", &vt.family)?;
+            if let Some(score) = cvss_base(Some(vt)) {
+                text_element(writer, "cvss_base", &format!("{score:.1}"))?;
+            }
+            let tags: Vec<String> = vt.tag.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            text_element(writer, "tags", &tags.join("|"))?;
+            writer
+                .create_element("refs")
+                .write_inner_content(|writer| {
+                    for reference in vt.references.iter() {
+                        writer
+                            .create_element("ref")
+                            .with_attribute(("type", reference.class.as_str()))
+                            .with_attribute(("id", reference.id.as_str()))
+                            .write_empty()?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Writes the findings and errors in the structure of a gvmd report export.
+///
+/// The threat is derived from the CVSS base score of the VT. Alarms of unknown VTs or VTs
+/// without score are reported as Medium without severity.
+fn gvm_xml(report: &Report) -> quick_xml::Result<Vec<u8>> {
+    let mut writer = XmlWriter::new(std::io::Cursor::new(Vec::new()));
+    let phase = match report.status.status {
+        scannerlib::models::Phase::Succeeded => "Done",
+        scannerlib::models::Phase::Stopped => "Stopped",
+        scannerlib::models::Phase::Failed => "Interrupted",
+        _ => "Running",
+    };
+    let findings: Vec<&Result> = report
+        .results
+        .iter()
+        .filter(|x| matches!(x.r_type, ResultType::Alarm | ResultType::Log))
+        .collect();
+    let errors: Vec<&Result> = report
+        .results
+        .iter()
+        .filter(|x| x.r_type == ResultType::Error)
+        .collect();
+    let mut hosts: Vec<&str> = vec![];
+    for result in report.results.iter() {
+        if let Some(ip) = result.ip_address.as_deref() {
+            if !hosts.contains(&ip) {
+                hosts.push(ip);
+            }
+        }
+    }
+    let host_time = |ip: &str, r_type: ResultType| {
+        report
+            .results
+            .iter()
+            .find(|x| x.r_type == r_type && x.ip_address.as_deref() == Some(ip))
+            .and_then(|x| x.message.clone())
+            .unwrap_or_default()
+    };
+    writer
+        .create_element("report")
+        .with_attribute(("id", report.id.as_str()))
+        .with_attribute(("extension", "xml"))
+        .with_attribute(("content_type", "text/xml"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("report")
+                .with_attribute(("id", report.id.as_str()))
+                .write_inner_content(|writer| {
+                    text_element(writer, "scan_run_status", phase)?;
+                    text_element(writer, "scan_start", &iso_time(report.status.start_time))?;
+                    text_element(writer, "scan_end", &iso_time(report.status.end_time))?;
+                    writer
+                        .create_element("hosts")
+                        .write_inner_content(|writer| {
+                            text_element(writer, "count", &hosts.len().to_string())
+                        })?;
+                    writer
+                        .create_element("results")
+                        .with_attribute(("start", "1"))
+                        .with_attribute(("max", "-1"))
+                        .write_inner_content(|writer| {
+                            for result in findings.iter() {
+                                let oid = result.oid.as_deref().unwrap_or_default();
+                                let vt = report.vts.get(oid);
+                                let score = cvss_base(vt);
+                                writer
+                                    .create_element("result")
+                                    .with_attribute(("id", result.id.to_string().as_str()))
+                                    .write_inner_content(|writer| {
+                                        let name = vt.map(|x| x.name.as_str()).unwrap_or(oid);
+                                        text_element(writer, "name", name)?;
+                                        gvm_host(writer, result)?;
+                                        text_element(writer, "port", &gvm_port(result))?;
+                                        gvm_nvt(writer, oid, vt)?;
+                                        let threat = threat(severity_class(result, vt));
+                                        text_element(writer, "threat", threat)?;
+                                        match (&result.r_type, score) {
+                                            (ResultType::Log, _) => {
+                                                text_element(writer, "severity", "0.0")?
+                                            }
+                                            (_, Some(x)) => text_element(
+                                                writer,
+                                                "severity",
+                                                &format!("{x:.1}"),
+                                            )?,
+                                            _ => {}
+                                        }
+                                        text_element(
+                                            writer,
+                                            "description",
+                                            result.message.as_deref().unwrap_or_default(),
+                                        )
+                                    })?;
+                            }
+                            Ok(())
+                        })?;
+                    writer
+                        .create_element("result_count")
+                        .write_inner_content(|writer| {
+                            text_element(writer, "full", &findings.len().to_string())
+                        })?;
+                    for ip in hosts.iter() {
+                        writer
+                            .create_element("host")
+                            .write_inner_content(|writer| {
+                                text_element(writer, "ip", ip)?;
+                                text_element(
+                                    writer,
+                                    "start",
+                                    &host_time(ip, ResultType::HostStart),
+                                )?;
+                                text_element(writer, "end", &host_time(ip, ResultType::HostEnd))?;
+                                for result in report.results.iter().filter(|x| {
+                                    matches!(
+                                        x.r_type,
+                                        ResultType::HostDetail | ResultType::Authentication
+                                    ) && x.ip_address.as_deref() == Some(*ip)
+                                }) {
+                                    let Some(detail) = &result.detail else {
+                                        continue;
+                                    };
+                                    writer.create_element("detail").write_inner_content(
+                                        |writer| {
+                                            text_element(writer, "name", &detail.name)?;
+                                            text_element(writer, "value", &detail.value)?;
+                                            writer.create_element("source").write_inner_content(
+                                                |writer| {
+                                                    text_element(
+                                                        writer,
+                                                        "type",
+                                                        &detail.source.s_type,
+                                                    )?;
+                                                    text_element(
+                                                        writer,
+                                                        "name",
+                                                        &detail.source.name,
+                                                    )
+                                                },
+                                            )?;
+                                            Ok(())
+                                        },
+                                    )?;
+                                }
+                                Ok(())
+                            })?;
+                    }
+                    writer
+                        .create_element("errors")
+                        .write_inner_content(|writer| {
+                            text_element(writer, "count", &errors.len().to_string())?;
+                            for result in errors.iter() {
+                                writer
+                                    .create_element("error")
+                                    .write_inner_content(|writer| {
+                                        gvm_host(writer, result)?;
+                                        text_element(writer, "port", &gvm_port(result))?;
+                                        text_element(
+                                            writer,
+                                            "description",
+                                            result.message.as_deref().unwrap_or_default(),
+                                        )?;
+                                        let oid = result.oid.as_deref().unwrap_or_default();
+                                        gvm_nvt(writer, oid, report.vts.get(oid))
+                                    })?;
+                            }
+                            Ok(())
+                        })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(writer.into_inner().into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::{
+        models::{Protocol, Result, ResultType},
+        storage::item::{Nvt, TagKey, TagValue},
+    };
+
+    use super::{Report, ReportFormat};
+
+    fn report() -> Report {
+        let results = vec![
+            Result {
+                id: 0,
+                r_type: ResultType::HostStart,
+                ip_address: Some("127.0.0.1".to_string()),
+                ..Default::default()
+            },
+            Result {
+                id: 1,
+                r_type: ResultType::Alarm,
+                ip_address: Some("127.0.0.1".to_string()),
+                hostname: Some("localhost".to_string()),
+                oid: Some("1.3.6.1.4.1.25623.1.0.1".to_string()),
+                port: Some(443),
+                protocol: Some(Protocol::TCP),
+                message: Some("Weak cipher \"RC4\",\nupdate".to_string()),
+                ..Default::default()
+            },
+            Result {
+                id: 2,
+                r_type: ResultType::Log,
+                ip_address: Some("127.0.0.2".to_string()),
+                oid: Some("1.3.6.1.4.1.25623.1.0.2".to_string()),
+                ..Default::default()
+            },
+        ];
+        let mut vt = Nvt {
+            oid: "1.3.6.1.4.1.25623.1.0.1".to_string(),
+            name: "Weak ciphers".to_string(),
+            family: "SSL and TLS".to_string(),
+            ..Default::default()
+        };
+        vt.tag.insert(TagKey::CvssBase, TagValue::from("7.5"));
+        vt.tag.insert(
+            TagKey::Summary,
+            TagValue::from("Weak ciphers are supported."),
+        );
+        Report {
+            id: "a".to_string(),
+            results,
+            vts: [(vt.oid.clone(), vt)].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let report = ReportFormat::Csv.render(&report());
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "id,type,ip_address,hostname,oid,port,protocol,message\r\n\
+             0,host_start,127.0.0.1,,,,,\r\n\
+             1,alarm,127.0.0.1,localhost,1.3.6.1.4.1.25623.1.0.1,443,tcp,\"Weak cipher \"\"RC4\"\",\nupdate\"\r\n\
+             2,log,127.0.0.2,,1.3.6.1.4.1.25623.1.0.2,,,\r\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let report = ReportFormat::Json.render(&report());
+        let report: serde_json::Value = serde_json::from_slice(&report).unwrap();
+        assert_eq!(report["scan_id"], "a");
+        assert_eq!(report["status"]["status"], "stored");
+        assert_eq!(report["results"][0]["type"], "host_start");
+    }
+
+    #[test]
+    fn sarif() {
+        let report = ReportFormat::Sarif.render(&report());
+        let report: serde_json::Value = serde_json::from_slice(&report).unwrap();
+        assert_eq!(report["version"], "2.1.0");
+        let run = &report["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0]["name"], "Weak ciphers");
+        assert_eq!(rules[0]["properties"]["security-severity"], "7.5");
+        assert_eq!(
+            rules[0]["fullDescription"]["text"],
+            "Weak ciphers are supported."
+        );
+        assert_eq!(rules[1]["id"], "1.3.6.1.4.1.25623.1.0.2");
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "127.0.0.1:443"
+        );
+        assert_eq!(
+            results[0]["locations"][0]["logicalLocations"][0]["name"],
+            "localhost"
+        );
+        assert_eq!(results[1]["ruleIndex"], 1);
+        assert_eq!(results[1]["level"], "note");
+        assert_eq!(results[1]["message"]["text"], "1.3.6.1.4.1.25623.1.0.2");
+    }
+
+    #[test]
+    fn xml() {
+        let mut report = report();
+        report.status.status = scannerlib::models::Phase::Succeeded;
+        report.status.start_time = Some(1700000000);
+        report.results.push(Result {
+            id: 3,
+            r_type: ResultType::Error,
+            ip_address: Some("127.0.0.1".to_string()),
+            message: Some("NVT timed out".to_string()),
+            ..Default::default()
+        });
+        let xml = String::from_utf8(ReportFormat::Xml.render(&report)).unwrap();
+        assert!(xml.starts_with(
+            r#"<report id="a" extension="xml" content_type="text/xml"><report id="a">"#
+        ));
+        assert!(xml.contains("<scan_run_status>Done</scan_run_status><scan_start>2023-11-14T22:13:20Z</scan_start><scan_end></scan_end><hosts><count>2</count></hosts>"));
+        assert!(xml.contains(concat!(
+            r#"<result id="1"><name>Weak ciphers</name><host>127.0.0.1<hostname>localhost</hostname></host>"#,
+            r#"<port>443/tcp</port><nvt oid="1.3.6.1.4.1.25623.1.0.1"><type>nvt</type><name>Weak ciphers</name>"#,
+            r#"<family>SSL and TLS</family><cvss_base>7.5</cvss_base><tags>cvss_base=7.5|summary=Weak ciphers are supported.</tags><refs></refs></nvt>"#,
+            "<threat>High</threat><severity>7.5</severity><description>Weak cipher &quot;RC4&quot;,\nupdate</description></result>"
+        )));
+        assert!(xml.contains(r#"<port>general/tcp</port><nvt oid="1.3.6.1.4.1.25623.1.0.2"><type>nvt</type></nvt><threat>Log</threat><severity>0.0</severity>"#));
+        assert!(xml.contains("<result_count><full>2</full></result_count><host><ip>127.0.0.1</ip>"));
+        assert!(xml.contains("<errors><count>1</count><error><host>127.0.0.1<hostname></hostname></host><port>general/tcp</port><description>NVT timed out</description>"));
+    }
+}
@@ -14,6 +14,7 @@ use std::{
 use http_body::Body;
 use hyper::body::Bytes;
 use serde::Serialize;
+use tokio::sync::broadcast;
 pub type Result = hyper::Response<BodyKind>;
 
 #[derive(Debug, Clone)]
@@ -58,6 +59,8 @@ pub enum BodyKind {
     /// self.ok_json_response(BodyKind::BinaryStream(rx))
     /// ```
     BinaryStream(Receiver<SendState>),
+    /// Server-sent events, each received chunk is sent as is until the sender is dropped.
+    EventStream(tokio::sync::mpsc::Receiver<Bytes>),
 }
 
 #[derive(Debug)]
@@ -81,7 +84,7 @@ impl Body for BodyKind {
     fn is_end_stream(&self) -> bool {
         match self {
             BodyKind::Empty => true,
-            BodyKind::BinaryStream(..) | BodyKind::Binary(_) => false,
+            BodyKind::BinaryStream(..) | BodyKind::EventStream(..) | BodyKind::Binary(_) => false,
         }
     }
 
@@ -90,14 +93,16 @@ impl Body for BodyKind {
             BodyKind::Empty => http_body::SizeHint::with_exact(0),
             BodyKind::Binary(b) => http_body::SizeHint::with_exact(b.len() as u64),
             // we don't know
-            BodyKind::BinaryStream(..) => http_body::SizeHint::default(),
+            BodyKind::BinaryStream(..) | BodyKind::EventStream(..) => {
+                http_body::SizeHint::default()
+            }
         }
     }
 
     #[inline]
     fn poll_frame(
         self: Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
+        cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<std::result::Result<http_body::Frame<Self::Data>, Self::Error>>> {
         let kind = self.get_mut();
 
@@ -133,6 +138,14 @@ impl Body for BodyKind {
                     }
                 }
             }),
+            BodyKind::EventStream(rec) => match rec.poll_recv(cx) {
+                Poll::Ready(Some(b)) => Poll::Ready(Some(Ok(http_body::Frame::data(b)))),
+                Poll::Ready(None) => {
+                    *kind = BodyKind::Empty;
+                    Poll::Ready(None)
+                }
+                Poll::Pending => Poll::Pending,
+            },
         }
     }
 }
@@ -282,6 +295,58 @@ impl Response {
         }
     }
 
+    /// Returns a stream of server-sent events.
+    ///
+    /// Each broadcasted item matching the filter is sent as a data line containing its JSON
+    /// representation. The stream ends when the sender is dropped or the client disconnects.
+    pub fn ok_event_stream<T, F>(&self, mut events: broadcast::Receiver<T>, filter: F) -> Result
+    where
+        T: Serialize + Clone + Send + 'static,
+        F: Fn(&T) -> bool + Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::mpsc::channel::<Bytes>(16);
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(x) if filter(&x) => x,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(%skipped, "event receiver is too slow, skipping events");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let json = match serde_json::to_string(&event) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        tracing::warn!(%e, "unable to serialize event");
+                        continue;
+                    }
+                };
+                if tx.send(format!("data: {json}\n\n").into()).await.is_err() {
+                    tracing::trace!("event receiver is not available anymore");
+                    break;
+                }
+            }
+        });
+        match self
+            .default_response_builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .status(hyper::StatusCode::OK)
+            .body(BodyKind::EventStream(rx))
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::error!("Error creating response: {}", e);
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(BodyKind::Empty)
+                    .unwrap()
+            }
+        }
+    }
+
     pub fn ok<T>(&self, value: &T) -> Result
     where
         T: ?Sized + Serialize + std::fmt::Debug,
@@ -296,6 +361,30 @@ impl Response {
         )
     }
 
+    /// Returns the given data as file download.
+    pub fn ok_attachment(&self, content_type: &str, filename: &str, value: Vec<u8>) -> Result {
+        match self
+            .default_response_builder()
+            .header("Content-Type", content_type)
+            .header("Content-Length", value.len())
+            .header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{filename}\""),
+            )
+            .status(hyper::StatusCode::OK)
+            .body(BodyKind::Binary(value.into()))
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::error!("Error creating response: {}", e);
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(BodyKind::Empty)
+                    .unwrap()
+            }
+        }
+    }
+
     pub fn created<T>(&self, value: &T) -> Result
     where
         T: ?Sized + Serialize + std::fmt::Debug,
@@ -2,18 +2,26 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::SystemTime;
 
 use crate::storage::{Error as StorageError, FeedHash, Storage};
 use async_trait::async_trait;
+use chrono::Local;
 use scannerlib::models::scanner::Error as ScanError;
 use scannerlib::models::scanner::{ScanResultFetcher, ScanResults, ScanStopper};
-use scannerlib::models::{Phase, Scan, Status};
-use scannerlib::storage::item::Nvt;
-use tokio::sync::RwLock;
+use scannerlib::models::{Phase, ResultType, Scan, Status, WatchEvent};
+use scannerlib::storage::item::{Nvt, QodType, TagKey};
+use tokio::sync::{broadcast, RwLock};
 
+use crate::deny_list::DenyList;
+use crate::export::Exporters;
+use crate::report::{cvss_base, Report};
+use crate::s3::Uploader;
+use crate::watch::Watcher;
 use crate::{
     config,
     controller::ClientHash,
@@ -80,6 +88,19 @@ impl From<Error> for ScanError {
         ScanError::Unexpected(format!("{}", val))
     }
 }
+
+/// Maximum amount of journal entries kept per scan, older entries are dropped.
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+/// An event the scheduler recorded for a scan.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct JournalEntry {
+    /// Seconds since UNIX epoch
+    pub timestamp: u64,
+    /// Description of the event
+    pub event: String,
+}
+
 /// Scheduler is a core component of managing scans.
 ///
 /// It follows the scanner traits of models so that a entry point does not have to differentiate
@@ -104,9 +125,20 @@ pub struct Scheduler<DB, Scanner> {
     is_synchronizing_feed: RwLock<bool>,
     /// Is used to start, stop, ... scan.
     scanner: Scanner,
-    config: config::Scheduler,
+    /// Limits of the scheduler, which can be changed by reloading the configuration.
+    config: std::sync::RwLock<config::Scheduler>,
     /// Feed version shared with response.
     feed_version: Arc<std::sync::RwLock<String>>,
+    /// Contains the scheduling events per scan id.
+    journal: RwLock<HashMap<String, Vec<JournalEntry>>>,
+    /// Contains the state of watched scans.
+    watcher: Watcher,
+    /// Publishes results to message brokers.
+    exporters: Exporters,
+    /// Uploads the reports of finished scans.
+    uploader: Option<Uploader>,
+    /// Hosts that are removed from each scan when it is started.
+    deny_list: std::sync::RwLock<DenyList>,
 }
 
 impl<DB, Scanner> Scheduler<DB, Scanner> {
@@ -118,19 +150,96 @@ impl<DB, Scanner> Scheduler<DB, Scanner> {
             running: RwLock::new(Vec::with_capacity(assumed_running)),
             db,
             scanner,
-            config,
+            config: std::sync::RwLock::new(config),
             is_synchronizing_feed: RwLock::new(false),
             feed_version: Arc::new(std::sync::RwLock::new(String::from("UNDEFINED"))),
+            journal: RwLock::new(HashMap::new()),
+            watcher: Watcher::default(),
+            exporters: Exporters::default(),
+            uploader: None,
+            deny_list: Default::default(),
         }
     }
 
-    pub fn config(&self) -> &config::Scheduler {
-        &self.config
+    /// Sets the message brokers results are published to as they arrive.
+    pub fn with_exporters(mut self, exporters: Exporters) -> Self {
+        self.exporters = exporters;
+        self
+    }
+
+    /// Sets the uploader of the reports of finished scans.
+    pub fn with_uploader(mut self, uploader: Option<Uploader>) -> Self {
+        self.uploader = uploader;
+        self
+    }
+
+    /// Sets the hosts that are never scanned.
+    pub fn with_deny_list(self, deny_list: DenyList) -> Self {
+        self.set_deny_list(deny_list);
+        self
+    }
+
+    pub fn deny_list(&self) -> DenyList {
+        self.deny_list.read().unwrap().clone()
+    }
+
+    /// Replaces the deny list, running scans are not affected.
+    pub fn set_deny_list(&self, deny_list: DenyList) {
+        *self.deny_list.write().unwrap() = deny_list;
+    }
+
+    pub fn config(&self) -> config::Scheduler {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replaces the limits of the scheduler.
+    ///
+    /// Running and queued scans are kept, even when they exceed the new limits.
+    pub fn set_config(&self, config: config::Scheduler) {
+        *self.config.write().unwrap() = config;
     }
 
     pub fn feed_version(&self) -> Arc<std::sync::RwLock<String>> {
         self.feed_version.clone()
     }
+
+    /// Returns the scheduling events of a scan, oldest first.
+    pub async fn journal(&self, id: &str) -> Vec<JournalEntry> {
+        self.journal
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns a receiver of the new and changed findings of all watched scans.
+    pub fn subscribe_watch_events(&self) -> broadcast::Receiver<WatchEvent> {
+        self.watcher.subscribe()
+    }
+
+    /// Records an event of a scan.
+    ///
+    /// An event that is equal to the previous one is ignored, so that e.g. a scan waiting for
+    /// resources does not flood the journal.
+    async fn record(&self, id: &str, event: impl Into<String>) {
+        let event = event.into();
+        let mut journal = self.journal.write().await;
+        let entries = journal.entry(id.to_string()).or_default();
+        if entries.last().is_some_and(|x| x.event == event) {
+            return;
+        }
+        if entries.len() == MAX_JOURNAL_ENTRIES {
+            entries.remove(0);
+        }
+        entries.push(JournalEntry {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+            event,
+        });
+    }
 }
 
 impl<DB, Scanner> Scheduler<DB, Scanner>
@@ -156,7 +265,7 @@ where
 
         let mut queued = self.queued.write().await;
         if let Some(max_queuing) = self.config().max_queued_scans {
-            if queued.len() == max_queuing {
+            if queued.len() >= max_queuing {
                 return Err(Error::QueueFull);
             }
         }
@@ -165,6 +274,7 @@ where
         }
         self.update_status(id, status).await?;
         queued.push(id.to_string());
+        self.record(id, "queued").await;
         Ok(())
     }
 
@@ -183,6 +293,8 @@ where
         self.db.remove_scan(id).await?;
         // TODO change from I to &str so that we don't have to clone everywhere
         self.db.remove_scan_id(id.to_string()).await?;
+        self.journal.write().await.remove(id);
+        self.watcher.remove(id);
         Ok(())
     }
 
@@ -195,44 +307,84 @@ where
         }
         let config = self.config();
         let mut queued = self.queued.write().await;
+        for scan_id in self.watcher.due() {
+            // the previous run is removed from the scanner to be able to start it again
+            if let Err(e) = self.scanner.delete_scan(scan_id.clone()).await {
+                tracing::debug!(%scan_id, %e, "unable to delete previous run");
+            }
+            self.db
+                .update_status(
+                    &scan_id,
+                    Status {
+                        status: Phase::Requested,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            self.record(&scan_id, "queued next watch run").await;
+            queued.push(scan_id);
+        }
         let mut running = self.running.write().await;
         let amount_to_start = if let Some(mrs) = config.max_running_scans {
-            mrs - running.len()
+            mrs.saturating_sub(running.len())
         } else {
             queued.len()
         };
 
         tracing::trace!(%amount_to_start, "handling scans");
+        let mut outside_window = vec![];
         for _ in 0..amount_to_start {
             if let Some(scan_id) = queued.pop() {
-                let (scan, status) = self.db.get_decrypted_scan(&scan_id).await?;
+                let (mut scan, status) = self.db.get_decrypted_scan(&scan_id).await?;
+                if outside_window_of(&scan) {
+                    tracing::trace!(%scan_id, "waiting for scan window");
+                    self.record(&scan_id, "waiting for scan window").await;
+                    outside_window.push(scan_id);
+                    continue;
+                }
+                if scan.schedule.is_some() {
+                    let finished = self.finished_hosts(&scan_id).await?;
+                    if !finished.is_empty() {
+                        tracing::debug!(%scan_id, finished = finished.len(), "resuming scan");
+                        self.record(
+                            &scan_id,
+                            format!("resumed without {} finished hosts", finished.len()),
+                        )
+                        .await;
+                        scan.target.excluded_hosts.extend(finished);
+                    }
+                }
+                if scan.vt_filter.is_some() {
+                    let added = crate::vt_filter::resolve(&mut scan, self.db.vts().await?);
+                    tracing::debug!(%scan_id, %added, "resolved VT filter");
+                    self.record(&scan_id, format!("selected {added} VTs by filter"))
+                        .await;
+                }
+                if let Err(e) = self.apply_deny_list(&mut scan).await {
+                    tracing::warn!(%scan_id, %e, "unable to start, set status to failed");
+                    self.fail(&scan_id, e).await?;
+                    continue;
+                }
                 if !self.scanner.can_start_scan(&scan).await {
                     tracing::debug!(?status, %scan_id, "unable to start scan");
+                    self.record(&scan_id, "waiting for resources").await;
                     queued.push(scan_id);
                 } else {
                     tracing::debug!(?status, %scan_id, "starting scan");
                     match self.scanner.start_scan(scan).await {
                         Ok(_) => {
                             tracing::debug!(%scan_id, "started");
+                            self.record(&scan_id, "started").await;
                             running.push(scan_id.clone());
                         }
                         Err(ScanError::Connection(e)) => {
                             tracing::warn!(%scan_id, %e, "requeuing because of a connection error");
+                            self.record(&scan_id, format!("requeued: {e}")).await;
                             queued.push(scan_id);
                         }
                         Err(e) => {
                             tracing::warn!(%scan_id, %e, "unable to start, removing from queue and set status to failed. Verify that scan using the API");
-                            self.db
-                                .update_status(
-                                    &scan_id,
-                                    Status {
-                                        start_time: None,
-                                        end_time: None,
-                                        status: Phase::Failed,
-                                        host_info: None,
-                                    },
-                                )
-                                .await?;
+                            self.fail(&scan_id, e).await?;
                         }
                     };
                 }
@@ -240,6 +392,154 @@ where
                 break;
             }
         }
+        // the waiting scans are put at the bottom so that they don't block the other scans
+        queued.splice(0..0, outside_window);
+        Ok(())
+    }
+
+    /// Sets the status of a scan that is unable to start to failed.
+    async fn fail(&self, scan_id: &str, reason: impl Display) -> Result<(), Error> {
+        self.record(scan_id, format!("unable to start: {reason}"))
+            .await;
+        self.db
+            .update_status(
+                scan_id,
+                Status {
+                    start_time: None,
+                    end_time: None,
+                    status: Phase::Failed,
+                    host_info: None,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Removes the hosts on the deny list from the target of a scan that is about to start.
+    ///
+    /// The list is applied again on each start, so that changes made by reloading the
+    /// configuration apply to queued and resumed scans as well.
+    async fn apply_deny_list(&self, scan: &mut Scan) -> Result<(), String> {
+        let deny_list = self.deny_list();
+        if deny_list.is_empty() {
+            return Ok(());
+        }
+        let mut target = scan.target.clone();
+        // resolving the hosts blocks on DNS lookups
+        let (target, denied) = tokio::task::spawn_blocking(move || {
+            let denied = deny_list.apply(&mut target);
+            (target, denied)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        scan.target = target;
+        if !denied.is_empty() {
+            tracing::info!(
+                scan_id = scan.scan_id,
+                ?denied,
+                "removed hosts on the deny list"
+            );
+            self.record(
+                &scan.scan_id,
+                format!("removed {} hosts on the deny list", denied.len()),
+            )
+            .await;
+            if scan.target.hosts.is_empty() {
+                return Err("all hosts are on the deny list".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the hosts the current run of a scan already finished.
+    async fn finished_hosts(&self, id: &str) -> Result<Vec<String>, Error> {
+        let offset = self.watcher.offset(id);
+        Ok(self
+            .db
+            .get_results(id, Some(offset), None)
+            .await?
+            .filter_map(|x| serde_json::from_slice::<scannerlib::models::Result>(&x).ok())
+            .filter(|x| matches!(x.r_type, ResultType::HostEnd))
+            .filter_map(|x| x.ip_address)
+            .collect())
+    }
+
+    /// Pauses the running scans that left their scan window.
+    ///
+    /// The results fetched so far are stored as checkpoint and the scan is removed from the
+    /// scanner. It is queued again as requested, so that it is resumed within the next window
+    /// without the hosts that were already finished.
+    async fn pause_scans(&self) -> Result<(), Error> {
+        let running = self.running.read().await.clone();
+        for scan_id in running {
+            let (scan, _) = self.db.get_scan(&scan_id).await?;
+            if !outside_window_of(&scan) {
+                continue;
+            }
+            self.handle_result(scan_id.clone()).await?;
+            if !self.running.read().await.contains(&scan_id) {
+                // finished with the last results
+                continue;
+            }
+            if let Err(e) = self.scanner.stop_scan(scan_id.clone()).await {
+                tracing::warn!(%scan_id, %e, "unable to pause scan");
+                self.record(&scan_id, format!("unable to pause: {e}")).await;
+                continue;
+            }
+            let mut running = self.running.write().await;
+            if let Some(idx) = running.iter().position(|x| x == &scan_id) {
+                running.swap_remove(idx);
+            }
+            drop(running);
+            if let Err(e) = self.scanner.delete_scan(scan_id.clone()).await {
+                tracing::debug!(%scan_id, %e, "unable to delete paused scan");
+            }
+            let mut status = self.db.get_status(&scan_id).await?;
+            status.status = Phase::Requested;
+            self.db.update_status(&scan_id, status).await?;
+            let finished = self.finished_hosts(&scan_id).await?.len();
+            tracing::debug!(%scan_id, %finished, "paused scan outside of its scan window");
+            self.record(
+                &scan_id,
+                format!("paused outside of scan window after {finished} finished hosts"),
+            )
+            .await;
+            self.queued.write().await.push(scan_id);
+        }
+        Ok(())
+    }
+
+    async fn handle_result(&self, scan_id: String) -> Result<(), Error> {
+        match self.fetch_results(scan_id.clone()).await {
+            // using self.append_fetch_result instead of db to keep track of the status
+            // and may remove them from running.
+            Ok(mut results) => {
+                if self.scanner.do_addition() {
+                    let scan_status = self.db.get_status(&scan_id).await?;
+                    results.status.update_with(&scan_status);
+                }
+                match self.append_fetched_result(vec![results]).await {
+                    Ok(()) => {
+                        tracing::trace!(%scan_id, "fetched and append results");
+                    }
+                    Err(e) => {
+                        tracing::warn!(%scan_id, %e, "unable to append results");
+                        self.record(&scan_id, format!("unable to append results: {e}"))
+                            .await;
+                    }
+                };
+            }
+            Err(e) => {
+                // TODO: set scan to failed and inform entry to return 500 instead of 200
+                // Also may remove from running
+                tracing::warn!(%scan_id, %e, "unable to fetch results, setting scan to failed");
+                self.record(&scan_id, format!("unable to fetch results: {e}"))
+                    .await;
+                let mut status = self.db.get_status(&scan_id).await?;
+                status.status = Phase::Failed;
+                self.db.update_status(&scan_id, status).await?;
+            }
+        };
         Ok(())
     }
 
@@ -247,37 +547,13 @@ where
         // we clone to drop the lock
         let running = self.running.read().await.clone();
         for scan_id in running {
-            match self.fetch_results(scan_id.clone()).await {
-                // using self.append_fetch_result instead of db to keep track of the status
-                // and may remove them from running.
-                Ok(mut results) => {
-                    if self.scanner.do_addition() {
-                        let scan_status = self.db.get_status(&scan_id).await?;
-                        results.status.update_with(&scan_status);
-                    }
-                    match self.append_fetched_result(vec![results]).await {
-                        Ok(()) => {
-                            tracing::trace!(%scan_id, "fetched and append results");
-                        }
-                        Err(e) => {
-                            tracing::warn!(%scan_id, %e, "unable to append results");
-                        }
-                    };
-                }
-                Err(e) => {
-                    // TODO: set scan to failed and inform entry to return 500 instead of 200
-                    // Also may remove from running
-                    tracing::warn!(%scan_id, %e, "unable to fetch results, setting scan to failed");
-                    let mut status = self.db.get_status(&scan_id).await?;
-                    status.status = Phase::Failed;
-                    self.db.update_status(&scan_id, status).await?;
-                }
-            };
+            self.handle_result(scan_id).await?;
         }
         Ok(())
     }
 
     pub async fn sync_scans(&self) -> Result<(), Error> {
+        self.pause_scans().await?;
         let coordination = self.coordinate_scans();
         let results = self.handle_results();
         let cr = coordination.await;
@@ -318,7 +594,9 @@ where
         I: AsRef<str> + Send + 'static,
     {
         let cid = id.as_ref().to_string();
+        self.watcher.remove(&cid);
         self.scanner.stop_scan(id).await?;
+        self.record(&cid, "stopped").await;
         let mut queued = self.queued.write().await;
         if let Some(idx) = queued.iter().position(|x| x == &cid) {
             queued.swap_remove(idx);
@@ -492,28 +770,153 @@ where
     }
 }
 
+impl<DB, S> Scheduler<DB, S>
+where
+    DB: Storage + Sync + Send + 'static,
+    S: Sync + Send,
+{
+    /// Publishes the new and changed findings of a finished run of a watched scan and
+    /// schedules its next run.
+    ///
+    /// A stopped scan is not watched anymore.
+    async fn finish_watch_run(&self, id: &str, phase: Phase) -> Result<(), StorageError> {
+        let (scan, _) = self.db.get_scan(id).await?;
+        let Some(watch) = scan.watch else {
+            return Ok(());
+        };
+        let offset = self.watcher.offset(id);
+        let results: Vec<scannerlib::models::Result> = self
+            .db
+            .get_results(id, Some(offset), None)
+            .await?
+            .filter_map(|x| serde_json::from_slice(&x).ok())
+            .collect();
+        match phase {
+            Phase::Succeeded => {
+                let events = self.watcher.finish(id, &watch, results);
+                self.record(id, format!("watch run found {} changes", events.len()))
+                    .await;
+                self.watcher.publish(&watch, events);
+            }
+            Phase::Failed => self.watcher.skip(id, &watch, results.len()),
+            _ => self.watcher.remove(id),
+        }
+        Ok(())
+    }
+}
+
+impl<DB, S> Scheduler<DB, S>
+where
+    DB: Storage + Sync + Send + 'static,
+    S: Sync + Send,
+{
+    /// Uploads the report of a finished scan when a bucket is configured
+    async fn upload_report(&self, id: &str) -> Result<(), StorageError> {
+        let Some(uploader) = &self.uploader else {
+            return Ok(());
+        };
+        let status = self.db.get_status(id).await?;
+        let results: Vec<scannerlib::models::Result> = self
+            .db
+            .get_results(id, None, None)
+            .await?
+            .filter_map(|x| serde_json::from_slice(&x).ok())
+            .collect();
+        let mut vts = HashMap::new();
+        for oid in results.iter().filter_map(|x| x.oid.as_ref()) {
+            if vts.contains_key(oid) {
+                continue;
+            }
+            if let Some(vt) = self.db.vt_by_oid(oid).await? {
+                vts.insert(oid.clone(), vt);
+            }
+        }
+        uploader.upload(&Report {
+            id: id.to_string(),
+            status,
+            results,
+            vts,
+        });
+        Ok(())
+    }
+}
+
+/// Returns true when the schedule of a scan does not allow it to run now.
+fn outside_window_of(scan: &Scan) -> bool {
+    scan.schedule
+        .as_ref()
+        .is_some_and(|x| !x.allows(&Local::now()))
+}
+
+/// Returns the quality of detection of a VT, derived from its QoD type when not set explicitly.
+fn qod(vt: Option<&Nvt>) -> Option<u8> {
+    let vt = vt?;
+    match vt.tag.get(&TagKey::Qod) {
+        Some(x) => x.to_string().parse().ok(),
+        None => vt
+            .tag
+            .get(&TagKey::QodType)
+            .and_then(|x| QodType::from_str(&x.to_string()).ok())
+            .and_then(|x| u8::try_from(i64::from(x)).ok()),
+    }
+}
+
 #[async_trait]
 impl<DB, S> AppendFetchResult for Scheduler<DB, S>
 where
     DB: Storage + Sync + Send + 'static,
     S: Sync + Send,
 {
-    async fn append_fetched_result(&self, results: Vec<ScanResults>) -> Result<(), StorageError> {
+    async fn append_fetched_result(
+        &self,
+        mut results: Vec<ScanResults>,
+    ) -> Result<(), StorageError> {
+        let mut vts: HashMap<String, Option<Nvt>> = HashMap::new();
+        for x in results.iter_mut().filter(|x| !x.results.is_empty()) {
+            let (scan, _) = self.db.get_scan(&x.id).await?;
+            let classification = scan.classification.clone().unwrap_or_default();
+            for result in x.results.iter_mut() {
+                if !scan.target.host_labels.is_empty() {
+                    result.labels = scan.target.labels_of(result).cloned();
+                }
+                let vt = match (&result.r_type, &result.oid) {
+                    (ResultType::Alarm, Some(oid)) => {
+                        if !vts.contains_key(oid) {
+                            vts.insert(oid.clone(), self.db.vt_by_oid(oid).await?);
+                        }
+                        vts[oid].as_ref()
+                    }
+                    _ => None,
+                };
+                result.severity = classification.classify(result, cvss_base(vt), qod(vt));
+            }
+        }
         let mut running = self.running.write().await;
+        let mut finished = vec![];
         for x in results.iter() {
             match x.status.status {
                 Phase::Stored | Phase::Requested | Phase::Running => {}
                 Phase::Stopped | Phase::Failed | Phase::Succeeded => {
                     if let Some(idx) = running.iter().position(|y| y == &x.id) {
                         running.swap_remove(idx);
+                        self.record(&x.id, x.status.status.to_string()).await;
+                        finished.push((x.id.clone(), x.status.status.clone()));
                     }
                 }
             };
         }
         drop(running);
 
+        for x in results.iter() {
+            self.exporters.export(&x.id, &x.results);
+        }
         tracing::trace!("appending results");
-        self.db.append_fetched_result(results).await
+        self.db.append_fetched_result(results).await?;
+        for (id, phase) in finished {
+            self.upload_report(&id).await?;
+            self.finish_watch_run(&id, phase).await?;
+        }
+        Ok(())
     }
 }
 
@@ -761,6 +1164,220 @@ mod tests {
             assert_eq!(scheduler.queued.read().await.len(), 0);
             assert_eq!(scheduler.running.read().await.len(), 0);
         }
+
+        #[tokio::test]
+        async fn attach_host_labels() {
+            use crate::storage::{AppendFetchResult as _, ProgressGetter as _};
+
+            let mut scan = Scan {
+                scan_id: "labeled".to_string(),
+                ..Default::default()
+            };
+            scan.target.host_labels.insert(
+                "127.0.0.1".to_string(),
+                [("owner".to_string(), "ops".to_string())].into(),
+            );
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan).await.unwrap();
+            let scheduler = Scheduler::new(config::Scheduler::default(), Lambda::default(), db);
+            let result = |ip: &str| scannerlib::models::Result {
+                ip_address: Some(ip.to_string()),
+                ..Default::default()
+            };
+            scheduler
+                .append_fetched_result(vec![ScanResults {
+                    id: "labeled".to_string(),
+                    status: Status::default(),
+                    results: vec![result("127.0.0.1"), result("127.0.0.2")],
+                }])
+                .await
+                .unwrap();
+            let results: Vec<scannerlib::models::Result> = scheduler
+                .get_results("labeled", None, None)
+                .await
+                .unwrap()
+                .map(|x| serde_json::from_slice(&x).unwrap())
+                .collect();
+            assert_eq!(
+                results[0].labels,
+                Some([("owner".to_string(), "ops".to_string())].into())
+            );
+            assert_eq!(results[1].labels, None);
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn rerun_watched_scan() {
+            let scan = Scan {
+                scan_id: "watched".to_string(),
+                watch: Some(scannerlib::models::Watch {
+                    interval: 0,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan).await.unwrap();
+            let scanner = LambdaBuilder::default()
+                .with_fetch(|s| {
+                    Ok(ScanResults {
+                        id: s.to_string(),
+                        status: Status {
+                            status: Phase::Succeeded,
+                            ..Default::default()
+                        },
+                        results: vec![scannerlib::models::Result {
+                            r_type: scannerlib::models::ResultType::Alarm,
+                            oid: Some("1".to_string()),
+                            ..Default::default()
+                        }],
+                    })
+                })
+                .build();
+            let scheduler = Scheduler::new(config::Scheduler::default(), scanner, db);
+            let mut events = scheduler.subscribe_watch_events();
+            scheduler.start_scan_by_id("watched").await.unwrap();
+            scheduler.coordinate_scans().await.unwrap();
+            scheduler.handle_results().await.unwrap();
+            assert_eq!(scheduler.running.read().await.len(), 0);
+            let event = events.try_recv().unwrap();
+            assert_eq!(event.run, 1);
+            assert_eq!(event.change, scannerlib::models::FindingChange::New);
+
+            // the same finding again is not emitted
+            scheduler.coordinate_scans().await.unwrap();
+            assert_eq!(scheduler.running.read().await.len(), 1);
+            scheduler.handle_results().await.unwrap();
+            assert!(events.try_recv().is_err());
+            assert_eq!(scheduler.watcher.offset("watched"), 2);
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn pause_and_resume_outside_window() {
+            use crate::storage::ProgressGetter as _;
+            use scannerlib::models::{Blackout, ResultType, Schedule};
+
+            let scan = |blackouts| Scan {
+                scan_id: "windowed".to_string(),
+                schedule: Some(Schedule {
+                    blackouts,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan(vec![])).await.unwrap();
+            let scanner = LambdaBuilder::default()
+                .with_fetch(|s| {
+                    Ok(ScanResults {
+                        id: s.to_string(),
+                        status: Status {
+                            status: Phase::Running,
+                            ..Default::default()
+                        },
+                        results: vec![scannerlib::models::Result {
+                            r_type: ResultType::HostEnd,
+                            ip_address: Some("10.0.0.1".to_string()),
+                            ..Default::default()
+                        }],
+                    })
+                })
+                .with_start(|scan| match scan.target.excluded_hosts.as_slice() {
+                    [] => Ok(()),
+                    [x] if x == "10.0.0.1" => Ok(()),
+                    x => Err(scanner::Error::Unexpected(format!("excluded {x:?}"))),
+                })
+                .build();
+            let scheduler = Scheduler::new(config::Scheduler::default(), scanner, db);
+            scheduler.start_scan_by_id("windowed").await.unwrap();
+            scheduler.coordinate_scans().await.unwrap();
+            assert_eq!(scheduler.running.read().await.len(), 1);
+
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let blackout = Blackout {
+                start: now - 60,
+                end: now + 3600,
+                description: None,
+            };
+            scheduler.insert_scan(scan(vec![blackout])).await.unwrap();
+            scheduler.sync_scans().await.unwrap();
+            assert_eq!(scheduler.running.read().await.len(), 0);
+            assert_eq!(scheduler.queued.read().await.len(), 1);
+            let status = scheduler.get_status("windowed").await.unwrap();
+            assert_eq!(status.status, Phase::Requested);
+
+            scheduler.insert_scan(scan(vec![])).await.unwrap();
+            scheduler.coordinate_scans().await.unwrap();
+            assert_eq!(scheduler.queued.read().await.len(), 0);
+            assert_eq!(scheduler.running.read().await.len(), 1);
+            let journal = scheduler.journal("windowed").await;
+            assert_eq!(journal.last().unwrap().event, "started", "{journal:?}");
+            assert!(journal
+                .iter()
+                .any(|x| x.event == "resumed without 1 finished hosts"));
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn apply_deny_list_at_start() {
+            use crate::storage::ProgressGetter as _;
+
+            let scan = |id: &str, hosts: &[&str]| {
+                let mut scan = Scan {
+                    scan_id: id.to_string(),
+                    ..Default::default()
+                };
+                scan.target.hosts = hosts.iter().map(|x| x.to_string()).collect();
+                scan
+            };
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan("partly", &["10.0.0.1", "192.168.0.1"]))
+                .await
+                .unwrap();
+            db.insert_scan(scan("denied", &["10.0.0.2"])).await.unwrap();
+            let scanner = LambdaBuilder::default()
+                .with_start(|scan| {
+                    match (
+                        scan.target.hosts.as_slice(),
+                        scan.target.excluded_hosts.as_slice(),
+                    ) {
+                        ([host], [excluded])
+                            if host == "192.168.0.1" && excluded == "10.0.0.0/8" =>
+                        {
+                            Ok(())
+                        }
+                        x => Err(scanner::Error::Unexpected(format!("{x:?}"))),
+                    }
+                })
+                .build();
+            let scheduler = Scheduler::new(config::Scheduler::default(), scanner, db);
+            scheduler.start_scan_by_id("partly").await.unwrap();
+            scheduler.start_scan_by_id("denied").await.unwrap();
+            // scans that are already queued are affected by a reloaded deny list as well
+            scheduler.set_deny_list(crate::deny_list::DenyList {
+                networks: vec!["10.0.0.0/8".parse().unwrap()],
+                ..Default::default()
+            });
+            scheduler.coordinate_scans().await.unwrap();
+            assert_eq!(*scheduler.running.read().await, ["partly"]);
+            assert_eq!(scheduler.queued.read().await.len(), 0);
+            let journal = scheduler.journal("partly").await;
+            assert!(journal
+                .iter()
+                .any(|x| x.event == "removed 1 hosts on the deny list"));
+            assert_eq!(journal.last().unwrap().event, "started", "{journal:?}");
+            let status = scheduler.get_status("denied").await.unwrap();
+            assert_eq!(status.status, Phase::Failed);
+            let journal = scheduler.journal("denied").await;
+            assert_eq!(
+                journal.last().unwrap().event,
+                "unable to start: all hosts are on the deny list"
+            );
+        }
     }
 
     mod start {
@@ -69,6 +69,18 @@ impl<S> Storage<S> {
             underlying: inmemory::Storage::new(ChaCha20Crypt::default(), feeds),
         }
     }
+
+    /// Persists the description results of the feed within the given directory.
+    pub fn with_description_cache(mut self, path: Option<PathBuf>) -> Self {
+        self.underlying = self.underlying.with_description_cache(path);
+        self
+    }
+
+    /// Validates the metadata of the scripts on a feed update.
+    pub fn with_validation(mut self, mode: ValidationMode) -> Self {
+        self.underlying = self.underlying.with_validation(mode);
+        self
+    }
 }
 
 impl<S> Storage<S>
@@ -413,7 +425,9 @@ impl FromConfigAndFeeds for Storage<ChaCha20IndexFileStorer<IndexedFileStorer>>
     ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
         // If this is even being called, we can assume we have a key
         let key = config.storage.fs.key.as_ref().unwrap();
-        Ok(file::encrypted(&config.storage.fs.path, key, feeds)?)
+        Ok(file::encrypted(&config.storage.fs.path, key, feeds)?
+            .with_description_cache(config.feed.description_cache.clone())
+            .with_validation(config.feed.validation))
     }
 }
 
@@ -422,7 +436,9 @@ impl FromConfigAndFeeds for Storage<IndexedFileStorer> {
         config: &Config,
         feeds: Vec<FeedHash>,
     ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
-        Ok(file::unencrypted(&config.storage.fs.path, feeds)?)
+        Ok(file::unencrypted(&config.storage.fs.path, feeds)?
+            .with_description_cache(config.feed.description_cache.clone())
+            .with_validation(config.feed.validation))
     }
 }
 
@@ -31,6 +31,8 @@ pub struct Storage<E> {
     underlying: Arc<DefaultDispatcher>,
     crypter: Arc<E>,
     feed_version: Arc<RwLock<String>>,
+    description_cache: Option<PathBuf>,
+    validation: ValidationMode,
 }
 
 impl<E> Storage<E>
@@ -45,9 +47,23 @@ where
             crypter: crypter.into(),
             underlying: DefaultDispatcher::default().into(),
             feed_version: Arc::new(RwLock::new(String::new())),
+            description_cache: None,
+            validation: ValidationMode::Off,
         }
     }
 
+    /// Persists the description results of the feed within the given directory.
+    pub fn with_description_cache(mut self, path: Option<PathBuf>) -> Self {
+        self.description_cache = path;
+        self
+    }
+
+    /// Validates the metadata of the scripts on a feed update.
+    pub fn with_validation(mut self, mode: ValidationMode) -> Self {
+        self.validation = mode;
+        self
+    }
+
     fn new_progress(crypter: &E, mut scan: models::Scan) -> Result<Progress, Error> {
         let credentials = scan
             .target
@@ -288,10 +304,12 @@ where
     E: crate::crypt::Crypt + Send + Sync + 'static + Default,
 {
     fn from_config_and_feeds(
-        _: &Config,
+        config: &Config,
         feeds: Vec<FeedHash>,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(inmemory::Storage::new(E::default(), feeds))
+        Ok(inmemory::Storage::new(E::default(), feeds)
+            .with_description_cache(config.feed.description_cache.clone())
+            .with_validation(config.feed.validation))
     }
 }
 
@@ -336,7 +354,12 @@ where
             let path = h.path;
             match h.typus {
                 FeedType::NASL => {
-                    _ = updates.spawn(super::update_nasl_feed(path, self.underlying.clone()))
+                    _ = updates.spawn(super::update_nasl_feed(
+                        path,
+                        self.underlying.clone(),
+                        self.description_cache.clone(),
+                        self.validation,
+                    ))
                 }
                 FeedType::Advisories => {
                     _ = updates.spawn(super::update_notus_feed(path, self.underlying.clone()))
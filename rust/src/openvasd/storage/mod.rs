@@ -9,8 +9,8 @@ pub use scannerlib::storage::Storage as NaslStorage;
 use scannerlib::{
     models::{self, Scan, Status, VulnerabilityData},
     storage::{
-        item::Nvt, ContextKey, DefaultDispatcher, Dispatcher, Field, FieldKeyResult, Kb, Remover,
-        Retrieve, Retriever, StorageError,
+        infisto::IndexedFileStorer, item::Nvt, ContextKey, DefaultDispatcher, Dispatcher, Field,
+        FieldKeyResult, Kb, Remover, Retrieve, Retriever, StorageError,
     },
 };
 
@@ -25,7 +25,7 @@ use scannerlib::models::scanner::ScanResults;
 
 use crate::{config::Config, controller::ClientHash, crypt};
 use scannerlib::{
-    feed::{self, HashSumNameLoader, Update},
+    feed::{self, DescriptionCache, HashSumNameLoader, Update, Validation, ValidationMode},
     nasl::FSPluginLoader,
     notus::{AdvisoryLoader, HashsumAdvisoryLoader},
 };
@@ -396,7 +396,12 @@ async fn update_notus_feed(p: PathBuf, store: Arc<DefaultDispatcher>) -> Result<
     .expect("notus handler to be executed.")
 }
 
-async fn update_nasl_feed(p: PathBuf, store: Arc<DefaultDispatcher>) -> Result<(), Error> {
+async fn update_nasl_feed(
+    p: PathBuf,
+    store: Arc<DefaultDispatcher>,
+    description_cache: Option<PathBuf>,
+    validation: ValidationMode,
+) -> Result<(), Error> {
     let nasl_feed_path = p;
     store.as_ref().clean_vts()?;
 
@@ -405,8 +410,25 @@ async fn update_nasl_feed(p: PathBuf, store: Arc<DefaultDispatcher>) -> Result<(
     let loader = FSPluginLoader::new(nasl_feed_path);
     let verifier = HashSumNameLoader::sha256(&loader)?;
 
-    let fu = Update::init(oversion, 5, &loader, &store, verifier);
-    fu.perform_update().await?;
+    let validation = Validation::new(validation);
+    let fu = Update::init(oversion, 5, &loader, &store, verifier).with_validation(&validation);
+    match description_cache {
+        Some(path) => {
+            let mut ifs = IndexedFileStorer::init(path)?;
+            let cache = DescriptionCache::load(oversion, &ifs);
+            tracing::debug!(cached = cache.len(), "loaded description cache");
+            fu.with_cache(&cache).perform_update().await?;
+            cache.store(&mut ifs)?;
+        }
+        None => fu.perform_update().await?,
+    }
+    let quarantined = validation.quarantined();
+    if !quarantined.is_empty() {
+        tracing::warn!(
+            quarantined = quarantined.len(),
+            "skipped scripts with invalid metadata"
+        );
+    }
     tracing::debug!("finished nasl feed update");
     Ok(())
 }
@@ -0,0 +1,163 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Resolves the VT filter of a scan into the VTs to execute.
+
+use std::collections::HashSet;
+
+use scannerlib::models::{Scan, VTFilter, VT};
+use scannerlib::storage::item::{Nvt, TagKey, TagValue};
+
+/// Returns true when the VT matches each criterion of the filter
+pub fn matches(filter: &VTFilter, vt: &Nvt) -> bool {
+    let family = filter.families.is_empty()
+        || filter
+            .families
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(&vt.family));
+    let tags = filter.tags.iter().all(|f| {
+        vt.tag
+            .iter()
+            .any(|(k, v)| k.as_ref() == f.name && v.to_string().eq_ignore_ascii_case(&f.value))
+    });
+    let cves = filter.cves.is_empty()
+        || vt
+            .references
+            .iter()
+            .any(|r| r.class == "cve" && filter.cves.iter().any(|x| x.eq_ignore_ascii_case(&r.id)));
+    let modified = match filter.modified_since {
+        None => true,
+        Some(since) => matches!(
+            vt.tag.get(&TagKey::LastModification),
+            Some(TagValue::Number(x)) if *x >= since
+        ),
+    };
+    family && tags && cves && modified
+}
+
+/// Adds the VTs selected by the filter of the scan to its VTs.
+///
+/// VTs that are already part of the scan are kept with their parameters. Returns the amount of
+/// added VTs.
+pub fn resolve<I>(scan: &mut Scan, vts: I) -> usize
+where
+    I: Iterator<Item = Nvt>,
+{
+    let Some(filter) = &scan.vt_filter else {
+        return 0;
+    };
+    let known: HashSet<String> = scan.vts.iter().map(|x| x.oid.clone()).collect();
+    let selected: Vec<VT> = vts
+        .filter(|x| !known.contains(&x.oid) && matches(filter, x))
+        .map(|x| VT {
+            oid: x.oid,
+            parameters: vec![],
+        })
+        .collect();
+    let added = selected.len();
+    scan.vts.extend(selected);
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::{Scan, VTFilter, VTTagFilter, VT};
+    use scannerlib::storage::item::{Nvt, NvtRef, TagKey, TagValue};
+
+    fn vts() -> Vec<Nvt> {
+        let mut a = Nvt {
+            oid: "1".to_string(),
+            family: "Web application abuses".to_string(),
+            references: vec![NvtRef::from(("cve", "CVE-2021-44228"))],
+            ..Default::default()
+        };
+        a.tag
+            .insert(TagKey::SolutionType, TagValue::from("VendorFix"));
+        a.tag
+            .insert(TagKey::LastModification, TagValue::from(1700000000i64));
+        let mut b = Nvt {
+            oid: "2".to_string(),
+            family: "Web application abuses".to_string(),
+            ..Default::default()
+        };
+        b.tag
+            .insert(TagKey::SolutionType, TagValue::from("WillNotFix"));
+        b.tag
+            .insert(TagKey::LastModification, TagValue::from(1600000000i64));
+        let c = Nvt {
+            oid: "3".to_string(),
+            family: "Windows".to_string(),
+            ..Default::default()
+        };
+        vec![a, b, c]
+    }
+
+    fn selected(filter: VTFilter) -> Vec<String> {
+        let mut scan = Scan {
+            vt_filter: Some(filter),
+            ..Default::default()
+        };
+        super::resolve(&mut scan, vts().into_iter());
+        scan.vts.into_iter().map(|x| x.oid).collect()
+    }
+
+    #[test]
+    fn filter() {
+        assert_eq!(selected(VTFilter::default()), vec!["1", "2", "3"]);
+        assert_eq!(
+            selected(VTFilter {
+                families: vec!["web application abuses".to_string()],
+                ..Default::default()
+            }),
+            vec!["1", "2"]
+        );
+        assert_eq!(
+            selected(VTFilter {
+                tags: vec![VTTagFilter {
+                    name: "solution_type".to_string(),
+                    value: "WillNotFix".to_string()
+                }],
+                ..Default::default()
+            }),
+            vec!["2"]
+        );
+        assert_eq!(
+            selected(VTFilter {
+                cves: vec!["CVE-2021-44228".to_string()],
+                ..Default::default()
+            }),
+            vec!["1"]
+        );
+        assert_eq!(
+            selected(VTFilter {
+                families: vec!["Windows".to_string(), "Web application abuses".to_string()],
+                modified_since: Some(1650000000),
+                ..Default::default()
+            }),
+            vec!["1"]
+        );
+    }
+
+    #[test]
+    fn keeps_given_vts() {
+        let given = VT {
+            oid: "1".to_string(),
+            parameters: vec![scannerlib::models::Parameter {
+                id: 1,
+                value: "yes".to_string(),
+            }],
+        };
+        let mut scan = Scan {
+            vts: vec![given.clone()],
+            vt_filter: Some(VTFilter {
+                families: vec!["Web application abuses".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(super::resolve(&mut scan, vts().into_iter()), 1);
+        assert_eq!(scan.vts[0], given);
+        assert_eq!(scan.vts[1].oid, "2");
+    }
+}
@@ -0,0 +1,316 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Keeps track of watched scans and the findings of their previous run.
+//!
+//! A watched scan is re-queued after its interval elapsed. When a run finished its findings are
+//! compared to the previous run and only new or changed findings are published to subscribers
+//! of the event stream and to the webhooks of the scan. The state is kept in memory, after a
+//! restart the first run reports every finding again.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use scannerlib::models::{FindingChange, Protocol, Result, ResultType, Watch, WatchEvent};
+use tokio::sync::broadcast;
+
+/// Amount of events kept for slow subscribers
+const EVENT_CAPACITY: usize = 1024;
+
+type HttpsClient = Client<hyper_rustls::HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// Identifies a finding across runs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FindingKey {
+    oid: Option<String>,
+    ip_address: Option<String>,
+    hostname: Option<String>,
+    port: Option<i16>,
+    protocol: Option<Protocol>,
+    alarm: bool,
+}
+
+impl FindingKey {
+    /// Returns the key of a result or None when it is not a finding
+    fn new(result: &Result) -> Option<Self> {
+        let alarm = match result.r_type {
+            ResultType::Alarm => true,
+            ResultType::Log => false,
+            _ => return None,
+        };
+        Some(Self {
+            oid: result.oid.clone(),
+            ip_address: result.ip_address.clone(),
+            hostname: result.hostname.clone(),
+            port: result.port,
+            protocol: result.protocol,
+            alarm,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Amount of finished runs
+    run: u64,
+    /// Amount of stored results of all previous runs
+    offset: usize,
+    /// Messages of the findings of the previous run
+    findings: HashMap<FindingKey, Option<String>>,
+    /// Start of the next run, None while a run is queued or running
+    next_run: Option<Instant>,
+}
+
+/// State of all watched scans
+pub struct Watcher {
+    scans: Mutex<HashMap<String, State>>,
+    events: broadcast::Sender<WatchEvent>,
+    client: Option<HttpsClient>,
+}
+
+impl std::fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher")
+            .field("scans", &self.scans)
+            .finish()
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        let client = match hyper_rustls::HttpsConnectorBuilder::new().with_native_roots() {
+            Ok(builder) => Some(
+                Client::builder(TokioExecutor::new())
+                    .build(builder.https_or_http().enable_http1().build()),
+            ),
+            Err(e) => {
+                tracing::warn!(%e, "unable to load root certificates, webhooks are disabled");
+                None
+            }
+        };
+        Self {
+            scans: Default::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            client,
+        }
+    }
+}
+
+impl Watcher {
+    /// Returns the index of the first result of the current run of a scan
+    pub fn offset(&self, id: &str) -> usize {
+        self.scans
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|x| x.offset)
+            .unwrap_or_default()
+    }
+
+    /// Compares the results of a finished run with the previous run.
+    ///
+    /// Returns the new and changed findings and schedules the next run after the interval.
+    pub fn finish(&self, id: &str, watch: &Watch, results: Vec<Result>) -> Vec<WatchEvent> {
+        let mut scans = self.scans.lock().unwrap();
+        let state = scans.entry(id.to_string()).or_default();
+        state.run += 1;
+        state.offset += results.len();
+        state.next_run = Some(Instant::now() + Duration::from_secs(watch.interval));
+        let mut findings = HashMap::with_capacity(state.findings.len());
+        let mut events = vec![];
+        for result in results {
+            let Some(key) = FindingKey::new(&result) else {
+                continue;
+            };
+            let change = match state.findings.get(&key) {
+                None => Some(FindingChange::New),
+                Some(message) if message != &result.message => Some(FindingChange::Changed),
+                Some(_) => None,
+            };
+            findings.insert(key, result.message.clone());
+            if let Some(change) = change {
+                events.push(WatchEvent {
+                    scan_id: id.to_string(),
+                    run: state.run,
+                    change,
+                    result,
+                });
+            }
+        }
+        state.findings = findings;
+        events
+    }
+
+    /// Schedules the next run of a scan without comparing findings, e.g. after a failed run
+    pub fn skip(&self, id: &str, watch: &Watch, stored_results: usize) {
+        let mut scans = self.scans.lock().unwrap();
+        let state = scans.entry(id.to_string()).or_default();
+        state.offset += stored_results;
+        state.next_run = Some(Instant::now() + Duration::from_secs(watch.interval));
+    }
+
+    /// Returns the scans whose next run is due.
+    ///
+    /// A returned scan is not returned again until its run finished.
+    pub fn due(&self) -> Vec<String> {
+        let now = Instant::now();
+        self.scans
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|(_, state)| state.next_run.is_some_and(|x| x <= now))
+            .map(|(id, state)| {
+                state.next_run = None;
+                id.clone()
+            })
+            .collect()
+    }
+
+    /// Stops watching a scan
+    pub fn remove(&self, id: &str) {
+        self.scans.lock().unwrap().remove(id);
+    }
+
+    /// Returns a receiver of the events of all watched scans
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
+        self.events.subscribe()
+    }
+
+    /// Sends the events to the subscribers and the webhooks of the scan
+    pub fn publish(&self, watch: &Watch, events: Vec<WatchEvent>) {
+        for event in events {
+            if let Some(client) = &self.client {
+                let body = match serde_json::to_vec(&event) {
+                    Ok(x) => Bytes::from(x),
+                    Err(e) => {
+                        tracing::warn!(%e, "unable to serialize watch event");
+                        continue;
+                    }
+                };
+                for url in watch.webhooks.iter() {
+                    let request = hyper::Request::post(url)
+                        .header("Content-Type", "application/json")
+                        .body(Full::new(body.clone()));
+                    let request = match request {
+                        Ok(x) => x,
+                        Err(e) => {
+                            tracing::warn!(%url, %e, "invalid webhook");
+                            continue;
+                        }
+                    };
+                    let client = client.clone();
+                    let url = url.clone();
+                    tokio::spawn(async move {
+                        match client.request(request).await {
+                            Ok(r) if !r.status().is_success() => {
+                                tracing::warn!(%url, status=%r.status(), "webhook rejected event")
+                            }
+                            Ok(_) => {}
+                            Err(e) => tracing::warn!(%url, %e, "unable to send event to webhook"),
+                        }
+                    });
+                }
+            }
+            // there may be no subscriber
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::{FindingChange, Result, ResultType, Watch};
+
+    use super::Watcher;
+
+    fn finding(oid: &str, message: &str) -> Result {
+        Result {
+            r_type: ResultType::Alarm,
+            ip_address: Some("127.0.0.1".to_string()),
+            oid: Some(oid.to_string()),
+            port: Some(80),
+            message: Some(message.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn changes(events: Vec<scannerlib::models::WatchEvent>) -> Vec<(String, FindingChange)> {
+        events
+            .into_iter()
+            .map(|x| (x.result.oid.unwrap(), x.change))
+            .collect()
+    }
+
+    #[test]
+    fn emits_new_and_changed_findings() {
+        let watcher = Watcher::default();
+        let watch = Watch {
+            interval: 3600,
+            ..Default::default()
+        };
+        let host_start = Result {
+            r_type: ResultType::HostStart,
+            ..Default::default()
+        };
+        let first = watcher.finish(
+            "a",
+            &watch,
+            vec![host_start.clone(), finding("1", "a"), finding("2", "b")],
+        );
+        assert_eq!(
+            changes(first),
+            vec![
+                ("1".to_string(), FindingChange::New),
+                ("2".to_string(), FindingChange::New)
+            ]
+        );
+        assert_eq!(watcher.offset("a"), 3);
+        let second = watcher.finish(
+            "a",
+            &watch,
+            vec![finding("1", "a"), finding("2", "c"), finding("3", "d")],
+        );
+        assert_eq!(
+            changes(second),
+            vec![
+                ("2".to_string(), FindingChange::Changed),
+                ("3".to_string(), FindingChange::New)
+            ]
+        );
+        // a finding that disappeared is new when it is reported again
+        let third = watcher.finish("a", &watch, vec![finding("1", "a")]);
+        assert!(third.is_empty());
+        let fourth = watcher.finish("a", &watch, vec![finding("1", "a"), finding("3", "d")]);
+        assert_eq!(fourth[0].run, 4);
+        assert_eq!(changes(fourth), vec![("3".to_string(), FindingChange::New)]);
+    }
+
+    #[test]
+    fn due() {
+        let watcher = Watcher::default();
+        watcher.finish("a", &Watch::default(), vec![]);
+        watcher.skip(
+            "b",
+            &Watch {
+                interval: 3600,
+                ..Default::default()
+            },
+            2,
+        );
+        assert_eq!(watcher.due(), vec!["a".to_string()]);
+        assert!(watcher.due().is_empty());
+        assert_eq!(watcher.offset("b"), 2);
+        watcher.remove("b");
+        assert_eq!(watcher.offset("b"), 0);
+    }
+}
@@ -307,6 +307,20 @@ fn write_credentials(scan: &Scan, writer: &mut Writer) -> Result<()> {
                             write_str_element(writer, "priv_password", &p.password)?;
                         }
                     }
+                    CredentialType::Agent {
+                        username,
+                        socket,
+                        privilege,
+                    } => {
+                        write_str_element(writer, "username", username)?;
+                        if let Some(socket) = socket {
+                            write_str_element(writer, "agent_socket", socket)?;
+                        }
+                        if let Some(p) = privilege {
+                            write_str_element(writer, "priv_username", &p.username)?;
+                            write_str_element(writer, "priv_password", &p.password)?;
+                        }
+                    }
                     CredentialType::SNMP {
                         username,
                         password,
@@ -322,6 +336,15 @@ fn write_credentials(scan: &Scan, writer: &mut Writer) -> Result<()> {
                         write_str_element(writer, "privacy_password", privacy_password)?;
                         write_str_element(writer, "privacy_algorithm", privacy_algorithm)?;
                     }
+                    CredentialType::Certificate {
+                        certificate,
+                        private_key,
+                        password,
+                    } => {
+                        write_str_element(writer, "certificate", certificate)?;
+                        write_str_element(writer, "private", private_key)?;
+                        write_str_element(writer, "password", password)?;
+                    }
                 }
 
                 Ok(())
@@ -431,6 +431,10 @@ impl From<&ScanResult> for crate::models::Result {
             r_type,
             message,
             detail: detail.extract(),
+            stats: None,
+            taint_flows: None,
+            labels: None,
+            severity: None,
         }
     }
 }
@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use std::time::Duration;
+
 use crate::models::{Host, Protocol};
 
 use crate::nasl::interpreter::InterpretError;
@@ -44,6 +46,16 @@ pub enum ScriptResultKind {
     MissingMandatoryKey(String),
     /// Contains the error the script returned
     Error(InterpretError),
+    /// Script was aborted after exceeding its maximum lifetime
+    Timeout(Duration),
+    /// Script execution panicked with the given message
+    Panic(String),
+    /// Script was aborted after exceeding a resource limit
+    ResourceLimit(String),
+    /// Script did not run because it is quarantined
+    ///
+    /// It contains the reason of the quarantine.
+    Quarantined(String),
 }
 
 #[derive(Debug, Clone)]
@@ -79,6 +91,7 @@ impl ScriptResult {
                 | ScriptResultKind::MissingMandatoryKey(_)
                 | ScriptResultKind::ContainsExcludedKey(_)
                 | ScriptResultKind::MissingPort(..)
+                | ScriptResultKind::Quarantined(_)
         )
     }
 }
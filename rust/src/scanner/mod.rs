@@ -12,15 +12,24 @@
 //! VT is then run to completion using the `VTRunner`.
 
 mod error;
+mod quarantine;
+mod resource_limit;
 mod running_scan;
+mod scan_environment;
 mod scan_runner;
 mod scanner_stack;
+mod script_timeout;
 mod vt_runner;
 
 pub use error::ExecuteError;
+pub use quarantine::{Failure, Quarantine, QuarantinedVt, ScanQuarantine, DEFAULT_THRESHOLD};
+pub use resource_limit::{ValueLimit, DEFAULT_MAX_VALUE_SIZE, PLUGINS_MAX_VALUE_SIZE_PREFERENCE};
 pub use scan_runner::ScanRunner;
 pub use scanner_stack::ScannerStack;
 pub use scanner_stack::ScannerStackWithStorage;
+pub use script_timeout::{
+    ScriptTimeout, PLUGINS_TIMEOUT_PREFERENCE, SCANNER_PLUGINS_TIMEOUT_PREFERENCE,
+};
 
 use async_trait::async_trait;
 use std::{collections::HashMap, path::Path, sync::Arc};
@@ -205,6 +214,7 @@ pub struct Scanner<S: ScannerStack> {
     storage: Arc<S::Storage>,
     loader: Arc<S::Loader>,
     function_executor: Arc<Executor>,
+    quarantine: Arc<Quarantine>,
 }
 
 impl<St, L> Scanner<(St, L)>
@@ -218,10 +228,24 @@ where
             storage: Arc::new(storage),
             loader: Arc::new(loader),
             function_executor: Arc::new(executor),
+            quarantine: Arc::default(),
         }
     }
 }
 
+impl<S: ScannerStack> Scanner<S> {
+    /// Sets the quarantine of scripts that repeatedly crash or time out.
+    pub fn with_quarantine(mut self, quarantine: Arc<Quarantine>) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Returns the quarantine shared by all scans.
+    pub fn quarantine(&self) -> Arc<Quarantine> {
+        self.quarantine.clone()
+    }
+}
+
 impl Scanner<DefaultScannerStack> {
     /// Create a new scanner with the default stack.
     /// Requires the root path for the loader.
@@ -253,9 +277,15 @@ impl<S: ScannerStack + 'static> ScanStarter for Scanner<S> {
         let storage = self.storage.clone();
         let loader = self.loader.clone();
         let function_executor = self.function_executor.clone();
+        let quarantine = self.quarantine.clone();
         let id = scan.scan_id.clone();
-        let handle =
-            RunningScan::<S>::start::<WaveExecutionPlan>(scan, storage, loader, function_executor);
+        let handle = RunningScan::<S>::start::<WaveExecutionPlan>(
+            scan,
+            storage,
+            loader,
+            function_executor,
+            quarantine,
+        );
         self.running.write().await.insert(id, handle);
         Ok(())
     }
@@ -0,0 +1,347 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Quarantine of scripts that repeatedly crash, time out or exceed their resource limits.
+//!
+//! The [Quarantine] is shared by all scans of a scanner. The failures of a script are recorded
+//! together with the hash of its code and the scan they happened in. A script failing on many
+//! hosts of one scan counts once, after failing in [DEFAULT_THRESHOLD] scans in a row it is
+//! skipped by all following runs. A successful run resets the failures of the other scans.
+//!
+//! Timeouts often depend on the host rather than on the script, so a timeout only counts when
+//! the script did not finish on any other host of the same scan, see [ScanQuarantine].
+//!
+//! As the hash changes when the feed updates the script, the updated script is released and runs
+//! again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+
+/// Number of scans in a row a script has to fail in before it is quarantined
+pub const DEFAULT_THRESHOLD: usize = 3;
+
+/// The misbehavior of a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The execution panicked with the given message
+    Panic(String),
+    /// The script exceeded its maximum lifetime
+    Timeout(Duration),
+    /// The script exceeded a resource limit
+    ResourceLimit(String),
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Panic(msg) => write!(f, "panicked: {msg}"),
+            Self::Timeout(x) => write!(f, "timed out after {} seconds", x.as_secs()),
+            Self::ResourceLimit(x) => write!(f, "exceeded its resource limits: {x}"),
+        }
+    }
+}
+
+/// A script skipped because of its failures
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct QuarantinedVt {
+    /// Object identifier of the script
+    pub oid: String,
+    /// Relative filename of the script
+    pub filename: String,
+    /// The last failure of the script
+    pub reason: String,
+    /// Number of scans in a row the script failed in
+    pub failures: usize,
+    /// Unix timestamp of the last failure
+    pub since: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    filename: String,
+    hash: String,
+    /// The failure of each scan
+    failures: HashMap<String, Failure>,
+    last: Failure,
+    time: u64,
+}
+
+/// Tracks the failures of scripts across scans
+#[derive(Debug)]
+pub struct Quarantine {
+    threshold: usize,
+    entries: RwLock<HashMap<String, Entry>>,
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD)
+    }
+}
+
+impl Quarantine {
+    /// Creates a quarantine for scripts failing in the given number of scans in a row.
+    ///
+    /// A threshold of 0 disables the quarantine.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            entries: RwLock::default(),
+        }
+    }
+
+    /// Returns the hash identifying the version of a script.
+    pub fn hash(code: &str) -> String {
+        hex::encode(Sha256::digest(code.as_bytes()))
+    }
+
+    /// Returns the reason when the script with the given hash is quarantined.
+    ///
+    /// The failures of a previous version of the script are discarded.
+    pub fn reason(&self, oid: &str, hash: &str) -> Option<String> {
+        if self.threshold == 0 {
+            return None;
+        }
+        let entries = self.entries.read().unwrap();
+        match entries.get(oid) {
+            Some(x) if x.hash == hash => {
+                (x.failures.len() >= self.threshold).then(|| x.last.to_string())
+            }
+            Some(_) => {
+                drop(entries);
+                tracing::debug!(oid, "script changed, discarding its failures");
+                self.entries.write().unwrap().remove(oid);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Records a failure of the script with the given hash within a scan.
+    ///
+    /// Each scan counts once, a timeout is replaced by a later failure of another kind.
+    pub fn record_failure(
+        &self,
+        scan_id: &str,
+        oid: &str,
+        filename: &str,
+        hash: &str,
+        failure: Failure,
+    ) {
+        if self.threshold == 0 {
+            return;
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries
+            .entry(oid.to_string())
+            .and_modify(|x| {
+                if x.hash != hash {
+                    x.hash = hash.to_string();
+                    x.failures.clear();
+                }
+            })
+            .or_insert_with(|| Entry {
+                filename: filename.to_string(),
+                hash: hash.to_string(),
+                failures: HashMap::new(),
+                last: failure.clone(),
+                time,
+            });
+        let before = entry.failures.len();
+        match entry.failures.get(scan_id) {
+            Some(x) if !matches!(x, Failure::Timeout(_)) => {}
+            _ => {
+                entry.failures.insert(scan_id.to_string(), failure.clone());
+                entry.last = failure;
+            }
+        }
+        entry.time = time;
+        if before < self.threshold && entry.failures.len() == self.threshold {
+            tracing::warn!(
+                oid,
+                filename,
+                reason = %entry.last,
+                failures = entry.failures.len(),
+                "quarantined script"
+            );
+        }
+    }
+
+    /// Records a run of the script within a scan that did not fail.
+    ///
+    /// This resets the failures of the other scans and the timeouts of the given scan.
+    pub fn record_success(&self, scan_id: &str, oid: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+        if !self.entries.read().unwrap().contains_key(oid) {
+            return;
+        }
+        let mut entries = self.entries.write().unwrap();
+        if let Some(entry) = entries.get_mut(oid) {
+            entry
+                .failures
+                .retain(|id, x| id == scan_id && !matches!(x, Failure::Timeout(_)));
+            if entry.failures.is_empty() {
+                entries.remove(oid);
+            }
+        }
+    }
+
+    /// Returns the quarantined scripts.
+    pub fn quarantined(&self) -> Vec<QuarantinedVt> {
+        let mut result = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, x)| self.threshold > 0 && x.failures.len() >= self.threshold)
+            .map(|(oid, x)| QuarantinedVt {
+                oid: oid.clone(),
+                filename: x.filename.clone(),
+                reason: x.last.to_string(),
+                failures: x.failures.len(),
+                since: x.time,
+            })
+            .collect::<Vec<_>>();
+        result.sort_by(|a, b| a.oid.cmp(&b.oid));
+        result
+    }
+}
+
+/// The quarantine as seen by a single scan.
+///
+/// Remembers the scripts that finished on a host of the scan, a later timeout of such a script
+/// on another host is not recorded as it depends on the host.
+#[derive(Debug)]
+pub struct ScanQuarantine {
+    quarantine: Arc<Quarantine>,
+    scan_id: String,
+    finished: RwLock<HashSet<String>>,
+}
+
+impl ScanQuarantine {
+    /// Creates the view of the given scan on the shared quarantine.
+    pub fn new(quarantine: Arc<Quarantine>, scan_id: &str) -> Self {
+        Self {
+            quarantine,
+            scan_id: scan_id.to_string(),
+            finished: RwLock::default(),
+        }
+    }
+
+    /// Returns the reason when the script with the given hash is quarantined.
+    pub fn reason(&self, oid: &str, hash: &str) -> Option<String> {
+        self.quarantine.reason(oid, hash)
+    }
+
+    /// Records a failure of the script with the given hash on a host of the scan.
+    pub fn record_failure(&self, oid: &str, filename: &str, hash: &str, failure: Failure) {
+        if matches!(failure, Failure::Timeout(_)) && self.finished.read().unwrap().contains(oid) {
+            tracing::debug!(oid, "script finished on another host, ignoring the timeout");
+            return;
+        }
+        self.quarantine
+            .record_failure(&self.scan_id, oid, filename, hash, failure)
+    }
+
+    /// Records a run of the script on a host of the scan that did not fail.
+    pub fn record_success(&self, oid: &str) {
+        self.finished.write().unwrap().insert(oid.to_string());
+        self.quarantine.record_success(&self.scan_id, oid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use std::sync::Arc;
+
+    use super::{Failure, Quarantine, ScanQuarantine};
+
+    #[test]
+    fn quarantine_until_changed() {
+        let quarantine = Quarantine::new(2);
+        let timeout = Failure::Timeout(Duration::from_secs(5));
+        quarantine.record_failure("s1", "1", "a.nasl", "h1", timeout.clone());
+        assert_eq!(quarantine.reason("1", "h1"), None);
+        quarantine.record_failure("s2", "1", "a.nasl", "h1", timeout.clone());
+        assert_eq!(
+            quarantine.reason("1", "h1"),
+            Some("timed out after 5 seconds".to_string())
+        );
+        let quarantined = quarantine.quarantined();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].failures, 2);
+        // the feed updated the script
+        assert_eq!(quarantine.reason("1", "h2"), None);
+        assert!(quarantine.quarantined().is_empty());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let quarantine = Quarantine::new(2);
+        let panic = Failure::Panic("oops".to_string());
+        quarantine.record_failure("s1", "1", "a.nasl", "h1", panic.clone());
+        quarantine.record_success("s2", "1");
+        quarantine.record_failure("s3", "1", "a.nasl", "h1", panic.clone());
+        assert_eq!(quarantine.reason("1", "h1"), None);
+        let disabled = Quarantine::new(0);
+        disabled.record_failure("s1", "1", "a.nasl", "h1", panic.clone());
+        assert_eq!(disabled.reason("1", "h1"), None);
+    }
+
+    #[test]
+    fn one_failure_per_scan() {
+        let quarantine = Quarantine::new(2);
+        let limit = Failure::ResourceLimit("too large".to_string());
+        for _ in 0..3 {
+            quarantine.record_failure("s1", "1", "a.nasl", "h1", limit.clone());
+        }
+        assert_eq!(quarantine.reason("1", "h1"), None);
+        quarantine.record_failure("s2", "1", "a.nasl", "h1", limit.clone());
+        assert_eq!(
+            quarantine.reason("1", "h1"),
+            Some("exceeded its resource limits: too large".to_string())
+        );
+    }
+
+    #[test]
+    fn host_dependent_timeouts() {
+        let quarantine = Arc::new(Quarantine::new(2));
+        let timeout = Failure::Timeout(Duration::from_secs(5));
+        for scan_id in ["s1", "s2"] {
+            let scan = ScanQuarantine::new(quarantine.clone(), scan_id);
+            // times out on the first host, finishes on the second and times out on the third
+            scan.record_failure("1", "a.nasl", "h1", timeout.clone());
+            scan.record_success("1");
+            scan.record_failure("1", "a.nasl", "h1", timeout.clone());
+        }
+        assert_eq!(quarantine.reason("1", "h1"), None);
+        for scan_id in ["s3", "s4"] {
+            let scan = ScanQuarantine::new(quarantine.clone(), scan_id);
+            scan.record_failure("1", "a.nasl", "h1", timeout.clone());
+            scan.record_failure("1", "a.nasl", "h1", timeout.clone());
+        }
+        assert_eq!(
+            quarantine.reason("1", "h1"),
+            Some("timed out after 5 seconds".to_string())
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Resource limits of a script.
+//!
+//! The size of the values a script creates is limited by the scan preference
+//! [PLUGINS_MAX_VALUE_SIZE_PREFERENCE], otherwise by [DEFAULT_MAX_VALUE_SIZE]. A value of 0
+//! disables the limit.
+
+use crate::models::ScanPreference;
+use crate::nasl::interpreter::{InterpretError, InterpretErrorKind, InterpreterHook};
+use crate::nasl::syntax::{NaslValue, Statement};
+
+/// Scan preference containing the maximum size of a value created by a script
+pub const PLUGINS_MAX_VALUE_SIZE_PREFERENCE: &str = "plugins_max_value_size";
+
+/// Maximum size of a value without the scan preference, 256 MiB
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Returns the bytes of strings and data or the number of elements of arrays and dictionaries.
+fn size(value: &NaslValue) -> usize {
+    match value {
+        NaslValue::String(x) => x.len(),
+        NaslValue::Data(x) => x.len(),
+        NaslValue::Array(x) => x.len(),
+        NaslValue::Dict(x) => x.len(),
+        NaslValue::Return(x) => size(x),
+        _ => 0,
+    }
+}
+
+/// Aborts the interpretation once a statement results in a value exceeding the maximum size.
+///
+/// This stops scripts before they exhaust the memory of the scanner, e.g. by doubling a string
+/// in an endless loop.
+#[derive(Debug, Clone, Copy)]
+pub struct ValueLimit {
+    max: usize,
+}
+
+impl Default for ValueLimit {
+    fn default() -> Self {
+        Self {
+            max: DEFAULT_MAX_VALUE_SIZE,
+        }
+    }
+}
+
+impl ValueLimit {
+    /// Returns the limit based on the scan preferences.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        preferences
+            .iter()
+            .find(|x| x.id == PLUGINS_MAX_VALUE_SIZE_PREFERENCE)
+            .and_then(|x| x.value.trim().parse().ok())
+            .map(|max| Self { max })
+            .unwrap_or_default()
+    }
+
+    /// Returns true when the limit is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.max > 0
+    }
+}
+
+impl InterpreterHook for ValueLimit {
+    fn after_statement(&self, _: &Statement, result: &mut Result<NaslValue, InterpretError>) {
+        if let Ok(value) = result {
+            let size = size(value);
+            if size > self.max {
+                *result = Err(InterpretError::new(
+                    InterpretErrorKind::ResourceLimit(format!(
+                        "value of size {size} exceeds the maximum of {}",
+                        self.max
+                    )),
+                    None,
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ScanPreference;
+
+    use super::{ValueLimit, DEFAULT_MAX_VALUE_SIZE};
+
+    #[test]
+    fn from_preferences() {
+        assert_eq!(
+            ValueLimit::from_preferences(&[]).max,
+            DEFAULT_MAX_VALUE_SIZE
+        );
+        let limit = ValueLimit::from_preferences(&[ScanPreference {
+            id: "plugins_max_value_size".to_string(),
+            value: "0".to_string(),
+        }]);
+        assert!(!limit.is_enabled());
+    }
+}
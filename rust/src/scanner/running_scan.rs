@@ -6,8 +6,11 @@ use std::{
     time::SystemTime,
 };
 
-use crate::models::{scanner::Error, HostInfo, Phase, Scan, Status};
+use crate::models::{
+    scanner::Error, AuthProtocol, Authentication, Host, HostInfo, Phase, Scan, Status,
+};
 use crate::nasl::utils::Executor;
+use crate::storage::{ContextKey, Dispatcher, Field, Retrieve, Retriever};
 use crate::{
     scanner::scan_runner::ScanRunner,
     scheduling::{ExecutionPlan, ExecutionPlaner, VTError},
@@ -16,7 +19,7 @@ use futures::StreamExt;
 use tokio::{sync::RwLock, task::JoinHandle};
 use tracing::{debug, trace, warn};
 
-use super::ScannerStack;
+use super::{quarantine::Quarantine, ScannerStack};
 
 /// Takes care of running a single scan to completion.
 /// Also provides methods for stopping the scan and
@@ -26,6 +29,7 @@ pub struct RunningScan<S: ScannerStack> {
     storage: Arc<S::Storage>,
     loader: Arc<S::Loader>,
     function_executor: Arc<Executor>,
+    quarantine: Arc<Quarantine>,
     keep_running: Arc<AtomicBool>,
     status: Arc<RwLock<Status>>,
 }
@@ -46,6 +50,7 @@ impl<S: ScannerStack> RunningScan<S> {
         storage: Arc<S::Storage>,
         loader: Arc<S::Loader>,
         function_executor: Arc<Executor>,
+        quarantine: Arc<Quarantine>,
     ) -> RunningScanHandle
     where
         S: 'static,
@@ -61,6 +66,7 @@ impl<S: ScannerStack> RunningScan<S> {
                     storage,
                     loader,
                     function_executor,
+                    quarantine,
                     keep_running: keep_running.clone(),
                     status: status.clone(),
                 }
@@ -106,6 +112,7 @@ impl<S: ScannerStack> RunningScan<S> {
             schedule,
             &self.scan,
         )
+        .map(|x| x.with_quarantine(self.quarantine.clone()))
         .map_err(make_scheduling_error)
     }
 
@@ -118,7 +125,10 @@ impl<S: ScannerStack> RunningScan<S> {
                     trace!(target = result.target, targets=?self.scan.target.hosts);
                     let mut status = self.status.write().await;
                     if let Some(host_info) = status.host_info.as_mut() {
-                        host_info.register_finished_script(&result.target);
+                        if host_info.register_finished_script(&result.target) {
+                            let authentication = self.report_authentication(&result.target);
+                            host_info.register_authentication(&result.target, authentication);
+                        }
                     }
                     debug!(result=?result, "script finished");
 
@@ -139,6 +149,32 @@ impl<S: ScannerStack> RunningScan<S> {
         end_phase
     }
 
+    /// Stores a result for each protocol the scan tried to log into the host with, based on the
+    /// KB items set by the authorization VTs, and returns the outcomes.
+    fn report_authentication(&self, host: &Host) -> Vec<Authentication> {
+        let key = ContextKey::Scan(self.scan.scan_id.clone(), Some(host.clone()));
+        let kb = |name: &str| {
+            self.storage
+                .retrieve(&key, Retrieve::KB(name.to_string()))
+                .ok()?
+                .find_map(|x| match x {
+                    Field::KB(kb) => Some(kb.value.to_string()),
+                    _ => None,
+                })
+        };
+        let authentication = AuthProtocol::ALL
+            .into_iter()
+            .filter_map(|x| Authentication::from_kb(x, kb))
+            .collect::<Vec<_>>();
+        for x in &authentication {
+            let result = Field::Result(Box::new(x.to_result(host)));
+            if let Err(e) = self.storage.dispatch(&key, result) {
+                warn!(%host, error=?e, "unable to store the authentication result");
+            }
+        }
+        authentication
+    }
+
     async fn update_status_at_beginning_of_run(&self, host_info: HostInfo) {
         let mut status = self.status.write().await;
         status.status = Phase::Running;
@@ -0,0 +1,52 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::sync::Arc;
+
+use crate::models::Scan;
+use crate::nasl::utils::{
+    AddressFamily, DnsCache, IncludeCache, NetworkTimeout, Proxy, Resolver, ScriptStats,
+    SourceBinding, TaintTracker, TempDir,
+};
+
+use super::{
+    quarantine::ScanQuarantine, resource_limit::ValueLimit, script_timeout::ScriptTimeout,
+};
+
+/// State of a single scan that is shared between all VTs run on its behalf.
+#[derive(Clone)]
+pub struct ScanEnvironment {
+    pub dns_cache: DnsCache,
+    pub source_binding: SourceBinding,
+    pub address_family: AddressFamily,
+    pub script_stats: bool,
+    pub taint_tracking: bool,
+    pub network_timeout: NetworkTimeout,
+    pub include_cache: IncludeCache,
+    pub temp_dir: TempDir,
+    pub proxy: Option<Proxy>,
+    pub script_timeout: ScriptTimeout,
+    pub value_limit: ValueLimit,
+    pub quarantine: Arc<ScanQuarantine>,
+}
+
+impl ScanEnvironment {
+    pub fn new(scan: &Scan) -> Self {
+        Self {
+            dns_cache: DnsCache::default()
+                .with_resolver(Resolver::from_preferences(&scan.scan_preferences)),
+            source_binding: SourceBinding::from_preferences(&scan.scan_preferences),
+            address_family: AddressFamily::from_preferences(&scan.scan_preferences),
+            script_stats: ScriptStats::enabled_by_preferences(&scan.scan_preferences),
+            taint_tracking: TaintTracker::enabled_by_preferences(&scan.scan_preferences),
+            network_timeout: NetworkTimeout::from_preferences(&scan.scan_preferences),
+            include_cache: IncludeCache::default(),
+            temp_dir: TempDir::from_preferences(&scan.scan_preferences),
+            proxy: Proxy::from_preferences(&scan.scan_preferences),
+            script_timeout: ScriptTimeout::from_preferences(&scan.scan_preferences),
+            value_limit: ValueLimit::from_preferences(&scan.scan_preferences),
+            quarantine: Arc::new(ScanQuarantine::new(Arc::default(), &scan.scan_id)),
+        }
+    }
+}
@@ -2,14 +2,19 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use crate::models::{Host, HostInfo, Scan};
-use crate::nasl::utils::Executor;
+use crate::models::{CredentialType, Host, HostInfo, Scan, Service, Target};
+use crate::nasl::utils::{DnsCache, Executor, TempDir};
 use futures::{stream, Stream};
+use std::{net::IpAddr, sync::Arc};
+use tracing::{error_span, Instrument};
 
 use crate::scanner::ScannerStack;
 use crate::scheduling::{ConcurrentVT, VTError};
+use crate::storage::{ContextKey, Dispatcher, Field, Storage};
 
 use super::error::{ExecuteError, ScriptResult};
+use super::quarantine::{Quarantine, ScanQuarantine};
+use super::scan_environment::ScanEnvironment;
 use super::scanner_stack::Schedule;
 use super::vt_runner::VTRunner;
 
@@ -34,6 +39,141 @@ fn all_positions(hosts: Vec<Host>, vts: Vec<ConcurrentVT>) -> impl Iterator<Item
     })
 }
 
+/// Stores the client certificate of the scan credentials in the temporary directory of the scan
+/// and sets the KB items of each host that TLS sockets load the certificate from.
+fn store_client_certificate(dispatcher: &dyn Dispatcher, scan: &Scan, temp_dir: &TempDir) {
+    let Some((certificate, private_key, password)) =
+        scan.target
+            .credentials
+            .iter()
+            .find_map(|x| match &x.credential_type {
+                CredentialType::Certificate {
+                    certificate,
+                    private_key,
+                    password,
+                } => Some((certificate, private_key, password)),
+                _ => None,
+            })
+    else {
+        return;
+    };
+    let store = |name: &str, data: &str| {
+        temp_dir.write(name, data.as_bytes())?;
+        temp_dir.resolve(name)
+    };
+    let paths = store("credentials/client.crt", certificate)
+        .and_then(|cert| Ok((cert, store("credentials/client.key", private_key)?)));
+    let (cert, key) = match paths {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::warn!(%e, "unable to store the client certificate");
+            return;
+        }
+    };
+    for host in &scan.target.hosts {
+        let key_context = ContextKey::Scan(scan.scan_id.clone(), Some(host.clone()));
+        for (name, value) in [
+            ("SSL/cert", cert.display().to_string()),
+            ("SSL/key", key.display().to_string()),
+            ("SSL/password", password.clone()),
+        ] {
+            if let Err(e) = dispatcher.dispatch(&key_context, Field::KB((name, value).into())) {
+                tracing::warn!(%host, %e, "unable to set {name}");
+            }
+        }
+    }
+}
+
+/// Sets the KB items of each host that `ssh_userauth` falls back to, from the first SSH
+/// credential of the scan.
+fn store_ssh_credentials(dispatcher: &dyn Dispatcher, scan: &Scan) {
+    let Some(credential) = scan
+        .target
+        .credentials
+        .iter()
+        .find(|x| x.service == Service::SSH)
+    else {
+        return;
+    };
+    let items = match &credential.credential_type {
+        CredentialType::UP {
+            username, password, ..
+        } => vec![
+            ("Secret/SSH/login", username.clone()),
+            ("Secret/SSH/password", password.clone()),
+        ],
+        CredentialType::USK {
+            username,
+            password,
+            private_key,
+            ..
+        } => vec![
+            ("Secret/SSH/login", username.clone()),
+            ("Secret/SSH/privatekey", private_key.clone()),
+            ("Secret/SSH/passphrase", password.clone()),
+        ],
+        CredentialType::Agent {
+            username, socket, ..
+        } => {
+            let Some(socket) = socket
+                .clone()
+                .or_else(|| std::env::var("SSH_AUTH_SOCK").ok())
+            else {
+                tracing::warn!("no SSH agent socket given and SSH_AUTH_SOCK is not set");
+                return;
+            };
+            vec![
+                ("Secret/SSH/login", username.clone()),
+                ("Secret/SSH/agent", socket),
+            ]
+        }
+        _ => return,
+    };
+    for host in &scan.target.hosts {
+        let key_context = ContextKey::Scan(scan.scan_id.clone(), Some(host.clone()));
+        for (name, value) in &items {
+            if let Err(e) =
+                dispatcher.dispatch(&key_context, Field::KB((*name, value.as_str()).into()))
+            {
+                tracing::warn!(%host, %e, "unable to set {name}");
+            }
+        }
+    }
+}
+
+/// Returns the hosts of the target without the excluded ones.
+///
+/// Besides the host itself, the name an IP address resolves to and the addresses a hostname
+/// resolves to are checked against the excluded hosts.
+fn scanned_hosts(target: &Target, dns_cache: &DnsCache) -> Vec<Host> {
+    let excluded = |host: &Host| {
+        if target.excluded_hosts.is_empty() {
+            return false;
+        }
+        target.excludes(host)
+            || match host.trim().parse::<IpAddr>() {
+                Ok(address) => dns_cache
+                    .lookup_addr(&address)
+                    .is_some_and(|x| target.excludes(&x)),
+                Err(_) => dns_cache
+                    .lookup_host(host.trim())
+                    .is_some_and(|x| x.iter().any(|x| target.excludes(&x.to_string()))),
+            }
+    };
+    target
+        .hosts
+        .iter()
+        .filter(|x| {
+            let excluded = excluded(x);
+            if excluded {
+                tracing::info!(host = %x, "skipping excluded host");
+            }
+            !excluded
+        })
+        .cloned()
+        .collect()
+}
+
 /// Runs a single scan by executing all the VTs within a given schedule.
 /// This does not provide any control over the scan but merely executes the
 /// necessary instructions. In order to have control over the scan (such as
@@ -44,6 +184,8 @@ pub struct ScanRunner<'a, S: ScannerStack> {
     loader: &'a S::Loader,
     executor: &'a Executor,
     concurrent_vts: Vec<ConcurrentVT>,
+    hosts: Vec<Host>,
+    env: ScanEnvironment,
 }
 
 impl<'a, Stack: ScannerStack> ScanRunner<'a, Stack> {
@@ -58,54 +200,69 @@ impl<'a, Stack: ScannerStack> ScanRunner<'a, Stack> {
         Sched: Schedule + 'a,
     {
         let concurrent_vts = schedule.cache()?;
+        let env = ScanEnvironment::new(scan);
+        store_client_certificate(storage.as_dispatcher(), scan, &env.temp_dir);
+        store_ssh_credentials(storage.as_dispatcher(), scan);
         Ok(Self {
             scan,
             storage,
             loader,
             executor,
             concurrent_vts,
+            hosts: scanned_hosts(&scan.target, &env.dns_cache),
+            env,
         })
     }
 
+    /// Sets the quarantine shared with other scans, by default each scan has its own.
+    pub fn with_quarantine(mut self, quarantine: Arc<Quarantine>) -> Self {
+        self.env.quarantine = Arc::new(ScanQuarantine::new(quarantine, &self.scan.scan_id));
+        self
+    }
+
     pub fn host_info(&self) -> HostInfo {
-        HostInfo::from_hosts_and_num_vts(&self.scan.target.hosts, self.concurrent_vts.len())
+        HostInfo::from_hosts_and_num_vts(&self.hosts, self.concurrent_vts.len())
     }
 
     pub fn stream(self) -> impl Stream<Item = Result<ScriptResult, ExecuteError>> + 'a {
-        let data = all_positions(self.scan.target.hosts.clone(), self.concurrent_vts.clone()).map(
-            move |pos| {
-                let (stage, vts) = &self.concurrent_vts[pos.stage];
-                let (vt, param) = &vts[pos.vt];
-                let host = &self.scan.target.hosts[pos.host];
-                (
-                    *stage,
-                    vt.clone(),
-                    param.clone(),
-                    host.clone(),
-                    self.scan.scan_id.clone(),
-                )
-            },
-        );
+        let data = all_positions(self.hosts.clone(), self.concurrent_vts.clone()).map(move |pos| {
+            let (stage, vts) = &self.concurrent_vts[pos.stage];
+            let (vt, param) = &vts[pos.vt];
+            let host = &self.hosts[pos.host];
+            (
+                *stage,
+                vt.clone(),
+                param.clone(),
+                host.clone(),
+                self.scan.scan_id.clone(),
+            )
+        });
         // The usage of unfold here will prevent any real asynchronous running of VTs
         // and automatically guarantee that we stick to the scheduling requirements.
         // If this is changed, make sure to uphold the scheduling requirements in the
         // new implementation.
-        stream::unfold(data, move |mut data| async move {
-            if let Some((stage, vt, param, host, scan_id)) = data.next() {
-                let result = VTRunner::<Stack>::run(
-                    self.storage,
-                    self.loader,
-                    self.executor,
-                    &host,
-                    &vt,
-                    stage,
-                    param.as_ref(),
-                    &scan_id,
-                )
-                .await;
-                Some((result, data))
-            } else {
-                None
+        stream::unfold(data, move |mut data| {
+            let env = self.env.clone();
+            async move {
+                if let Some((stage, vt, param, host, scan_id)) = data.next() {
+                    let span = error_span!("vt", %scan_id, oid = vt.oid, %host);
+                    let result = VTRunner::<Stack>::run(
+                        self.storage,
+                        self.loader,
+                        self.executor,
+                        &env,
+                        &host,
+                        &vt,
+                        stage,
+                        param.as_ref(),
+                        &scan_id,
+                    )
+                    .instrument(span)
+                    .await;
+                    Some((result, data))
+                } else {
+                    None
+                }
             }
         })
     }
@@ -179,6 +336,10 @@ pub(super) mod tests {
                     parameters: vec![],
                 })
                 .collect(),
+            vt_filter: None,
+            watch: None,
+            classification: None,
+            schedule: None,
         };
         let executor = nasl_std_functions();
         ((storage, loader, executor), scan)
@@ -327,7 +488,9 @@ exit({rc});
 
         let context = Context::new(key, target, &storage, &storage, &loader, &functions);
         let interpreter = CodeInterpreter::new(code, register, &context);
-        for stmt in interpreter.iter_blocking() {
+        // stops at the exit of the description block instead of collecting all results
+        let mut results = interpreter.stream();
+        while let Some(stmt) = futures::executor::block_on(results.next()) {
             if let NaslValue::Exit(_) = stmt.expect("stmt success") {
                 storage.on_exit(context.key()).expect("result");
                 let result = storage
@@ -378,6 +541,10 @@ exit({rc});
                     parameters: vec![],
                 })
                 .collect(),
+            vt_filter: None,
+            watch: None,
+            classification: None,
+            schedule: None,
         };
 
         let executor = nasl_std_functions();
@@ -510,4 +677,187 @@ exit({rc});
         assert_eq!(success.len(), 1);
         assert_eq!(failure.len(), 1);
     }
+
+    #[tokio::test]
+    async fn quarantine_endless_script() {
+        use crate::scanner::{error::ScriptResultKind, quarantine::Quarantine};
+        use std::sync::Arc;
+
+        let code = r#"
+if (description)
+{
+  script_oid("0");
+  script_category(ACT_GATHER_INFO);
+  script_timeout(1);
+  exit(0);
+}
+i = 0;
+while (1) { i = i + 1; }
+"#;
+        let vts = [(code.to_string(), parse_meta_data("0.nasl", code).unwrap())];
+        let ((_, _, executor), scan) = setup(&vts);
+        let storage = prepare_vt_storage(&vts);
+        let loader = |_: &str| code.to_string();
+        let quarantine = Arc::new(Quarantine::new(2));
+        let mut kinds = vec![];
+        for i in 0..3 {
+            // failures count once per scan
+            let mut scan = scan.clone();
+            scan.scan_id = format!("sid{i}");
+            let schedule = storage.execution_plan::<WaveExecutionPlan>(&scan).unwrap();
+            let runner: ScanRunner<(_, _)> =
+                ScanRunner::new(&storage, &loader, &executor, schedule, &scan)
+                    .unwrap()
+                    .with_quarantine(quarantine.clone());
+            let results = runner.stream().collect::<Vec<_>>().await;
+            kinds.push(results[0].as_ref().unwrap().kind.clone());
+        }
+        assert!(matches!(kinds[0], ScriptResultKind::Timeout(_)));
+        assert!(matches!(kinds[1], ScriptResultKind::Timeout(_)));
+        assert!(matches!(kinds[2], ScriptResultKind::Quarantined(_)));
+        assert_eq!(quarantine.quarantined()[0].oid, "0");
+    }
+
+    #[tokio::test]
+    async fn exceeded_value_limit() {
+        use crate::models::ScanPreference;
+        use crate::scanner::error::ScriptResultKind;
+
+        let code = r#"
+if (description)
+{
+  script_oid("0");
+  script_category(ACT_GATHER_INFO);
+  exit(0);
+}
+s = "a";
+while (1) { s = s + s; }
+"#;
+        let vts = [(code.to_string(), parse_meta_data("0.nasl", code).unwrap())];
+        let ((_, _, executor), mut scan) = setup(&vts);
+        scan.scan_preferences.push(ScanPreference {
+            id: "plugins_max_value_size".to_string(),
+            value: "1024".to_string(),
+        });
+        let storage = prepare_vt_storage(&vts);
+        let loader = |_: &str| code.to_string();
+        let schedule = storage.execution_plan::<WaveExecutionPlan>(&scan).unwrap();
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).unwrap();
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert!(matches!(
+            results[0].as_ref().unwrap().kind,
+            ScriptResultKind::ResourceLimit(_)
+        ));
+    }
+
+    #[test]
+    fn excluded_hosts_are_skipped() {
+        use crate::nasl::utils::DnsCache;
+
+        let target = Target {
+            hosts: ["10.0.0.1", "www.corp.example.com", "localhost", "::1"]
+                .map(String::from)
+                .to_vec(),
+            excluded_hosts: ["10.0.0.0/8", "*.corp.example.com", "127.0.0.0/8"]
+                .map(String::from)
+                .to_vec(),
+            ..Default::default()
+        };
+        let hosts = super::scanned_hosts(&target, &DnsCache::default());
+        assert_eq!(hosts, ["::1"]);
+    }
+
+    #[test]
+    fn client_certificate() {
+        use crate::models::{Credential, CredentialType, Service};
+        use crate::nasl::utils::TempDir;
+
+        let mut scan = Scan {
+            scan_id: "sid".into(),
+            ..Default::default()
+        };
+        scan.target.hosts = vec!["a.host".into(), "b.host".into()];
+        scan.target.credentials = vec![Credential {
+            service: Service::TLS,
+            port: None,
+            credential_type: CredentialType::Certificate {
+                certificate: "CERT".into(),
+                private_key: "KEY".into(),
+                password: "secret".into(),
+            },
+        }];
+        let dispatcher = DefaultDispatcher::new();
+        let temp_dir = TempDir::default();
+        super::store_client_certificate(&dispatcher, &scan, &temp_dir);
+        let kb = |host: &str, name: &str| {
+            dispatcher
+                .retrieve(
+                    &ContextKey::Scan("sid".into(), Some(host.into())),
+                    Retrieve::KB(name.into()),
+                )
+                .unwrap()
+                .find_map(|x| match x {
+                    Field::KB(kb) => Some(kb.value.to_string()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        for host in ["a.host", "b.host"] {
+            assert_eq!(std::fs::read(kb(host, "SSL/cert")).unwrap(), b"CERT");
+            assert_eq!(std::fs::read(kb(host, "SSL/key")).unwrap(), b"KEY");
+            assert_eq!(kb(host, "SSL/password"), "secret");
+        }
+    }
+
+    #[test]
+    fn ssh_credentials() {
+        use crate::models::{Credential, CredentialType, Service};
+
+        let mut scan = Scan {
+            scan_id: "sid".into(),
+            ..Default::default()
+        };
+        scan.target.hosts = vec!["a.host".into()];
+        scan.target.credentials = vec![
+            Credential {
+                service: Service::SMB,
+                port: None,
+                credential_type: CredentialType::UP {
+                    username: "smb".into(),
+                    password: "smb".into(),
+                    privilege: None,
+                },
+            },
+            Credential {
+                service: Service::SSH,
+                port: None,
+                credential_type: CredentialType::USK {
+                    username: "scan".into(),
+                    password: "passphrase".into(),
+                    private_key: "KEY".into(),
+                    privilege: None,
+                },
+            },
+        ];
+        let dispatcher = DefaultDispatcher::new();
+        super::store_ssh_credentials(&dispatcher, &scan);
+        let kb = |name: &str| {
+            dispatcher
+                .retrieve(
+                    &ContextKey::Scan("sid".into(), Some("a.host".into())),
+                    Retrieve::KB(name.into()),
+                )
+                .unwrap()
+                .find_map(|x| match x {
+                    Field::KB(kb) => Some(kb.value.to_string()),
+                    _ => None,
+                })
+        };
+        assert_eq!(kb("Secret/SSH/login").unwrap(), "scan");
+        assert_eq!(kb("Secret/SSH/privatekey").unwrap(), "KEY");
+        assert_eq!(kb("Secret/SSH/passphrase").unwrap(), "passphrase");
+        assert_eq!(kb("Secret/SSH/password"), None);
+        assert_eq!(kb("Secret/SSH/agent"), None);
+    }
 }
@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Maximum lifetime of a script.
+//!
+//! The timeout set by the script via `script_timeout` takes precedence, otherwise the scan
+//! preference [PLUGINS_TIMEOUT_PREFERENCE] or, for ACT_SCANNER scripts,
+//! [SCANNER_PLUGINS_TIMEOUT_PREFERENCE] applies. Without any of them a script runs until it
+//! finishes.
+
+use std::time::{Duration, Instant};
+
+use crate::models::ScanPreference;
+use crate::nasl::interpreter::{InterpretError, InterpretErrorKind, InterpreterHook};
+use crate::nasl::syntax::Statement;
+use crate::storage::item::{Nvt, ACT};
+
+/// Scan preference containing the timeout of scripts in seconds
+pub const PLUGINS_TIMEOUT_PREFERENCE: &str = "plugins_timeout";
+/// Scan preference containing the timeout of ACT_SCANNER scripts in seconds
+pub const SCANNER_PLUGINS_TIMEOUT_PREFERENCE: &str = "scanner_plugins_timeout";
+
+/// Id of the script preference set by `script_timeout`
+const TIMEOUT_PREFERENCE_ID: i32 = 0;
+
+/// Returns the timeout of a positive number of seconds.
+fn seconds(value: &str) -> Option<Duration> {
+    match value.trim().parse::<i64>() {
+        Ok(x) if x > 0 => Some(Duration::from_secs(x as u64)),
+        _ => None,
+    }
+}
+
+/// Timeouts of the scripts of a scan
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScriptTimeout {
+    plugins: Option<Duration>,
+    scanner_plugins: Option<Duration>,
+}
+
+impl ScriptTimeout {
+    /// Returns the timeouts based on the scan preferences.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let find = |id| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .and_then(|x| seconds(&x.value))
+        };
+        Self {
+            plugins: find(PLUGINS_TIMEOUT_PREFERENCE),
+            scanner_plugins: find(SCANNER_PLUGINS_TIMEOUT_PREFERENCE),
+        }
+    }
+
+    /// Returns the maximum lifetime of the script.
+    pub fn of(&self, vt: &Nvt) -> Option<Duration> {
+        vt.preferences
+            .iter()
+            .find(|x| x.id == Some(TIMEOUT_PREFERENCE_ID))
+            .and_then(|x| seconds(&x.default))
+            .or(match vt.category {
+                ACT::Scanner => self.scanner_plugins,
+                _ => self.plugins,
+            })
+    }
+}
+
+/// Aborts the interpretation once the deadline is reached.
+///
+/// As builtins are not interrupted, the deadline is checked before each statement.
+pub(super) struct Deadline {
+    timeout: Duration,
+    deadline: Instant,
+}
+
+impl Deadline {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl InterpreterHook for Deadline {
+    fn before_statement(&self, _: &Statement) -> Result<(), Box<InterpretError>> {
+        if Instant::now() >= self.deadline {
+            Err(InterpretError::new(InterpretErrorKind::Timeout(self.timeout), None).into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::models::ScanPreference;
+    use crate::storage::item::{Nvt, NvtPreference, PreferenceType, ACT};
+
+    use super::ScriptTimeout;
+
+    #[test]
+    fn precedence() {
+        let timeout = ScriptTimeout::from_preferences(&[
+            ScanPreference {
+                id: "plugins_timeout".to_string(),
+                value: "20".to_string(),
+            },
+            ScanPreference {
+                id: "scanner_plugins_timeout".to_string(),
+                value: "0".to_string(),
+            },
+        ]);
+        let mut vt = Nvt::default();
+        assert_eq!(timeout.of(&vt), Some(Duration::from_secs(20)));
+        vt.category = ACT::Scanner;
+        assert_eq!(timeout.of(&vt), None);
+        vt.preferences.push(NvtPreference {
+            id: Some(0),
+            class: PreferenceType::Entry,
+            name: "timeout".to_string(),
+            default: "600".to_string(),
+        });
+        assert_eq!(timeout.of(&vt), Some(Duration::from_secs(600)));
+    }
+}
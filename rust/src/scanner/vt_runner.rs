@@ -5,15 +5,19 @@ use crate::scheduling::Stage;
 use crate::storage::item::Nvt;
 use crate::storage::{types::Primitive, Retriever, Storage};
 use crate::storage::{ContextKey, Field, Retrieve, StorageError};
-use futures::StreamExt;
+use futures::{FutureExt, StreamExt};
+use std::{panic::AssertUnwindSafe, sync::Arc, time::Duration};
 use tracing::{error_span, trace, warn};
 
-use crate::nasl::interpreter::CodeInterpreter;
+use crate::nasl::interpreter::{CodeInterpreter, InterpretError, InterpretErrorKind};
 use crate::nasl::prelude::*;
 
 use super::ExecuteError;
 use super::{
     error::{ScriptResult, ScriptResultKind},
+    quarantine::{Failure, Quarantine},
+    scan_environment::ScanEnvironment,
+    script_timeout::Deadline,
     ScannerStack,
 };
 
@@ -22,6 +26,7 @@ pub struct VTRunner<'a, S: ScannerStack> {
     storage: &'a S::Storage,
     loader: &'a S::Loader,
     executor: &'a Executor,
+    env: &'a ScanEnvironment,
 
     target: &'a Host,
     vt: &'a Nvt,
@@ -36,6 +41,7 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
         storage: &'a Stack::Storage,
         loader: &'a Stack::Loader,
         executor: &'a Executor,
+        env: &'a ScanEnvironment,
         target: &'a Host,
         vt: &'a Nvt,
         stage: Stage,
@@ -46,6 +52,7 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
             storage,
             loader,
             executor,
+            env,
             target,
             vt,
             stage,
@@ -189,7 +196,12 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
         ContextKey::Scan(self.scan_id.clone(), Some(self.target.clone()))
     }
 
-    async fn get_result_kind(&self, code: &str, register: Register) -> ScriptResultKind {
+    async fn get_result_kind(
+        &self,
+        code: &str,
+        register: Register,
+        timeout: Option<Duration>,
+    ) -> ScriptResultKind {
         if let Err(e) = self.check_keys(self.vt) {
             return e;
         }
@@ -201,11 +213,35 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
             self.storage.as_retriever(),
             self.loader,
             self.executor,
-        );
-        let mut results = Box::pin(CodeInterpreter::new(code, register, &context).stream());
+        )
+        .with_dns_cache(self.env.dns_cache.clone())
+        .with_source_binding(self.env.source_binding.clone())
+        .with_address_family(self.env.address_family)
+        .with_script_stats(self.env.script_stats)
+        .with_taint_tracking(self.env.taint_tracking)
+        .with_network_timeout(self.env.network_timeout.clone())
+        .with_include_cache(self.env.include_cache.clone())
+        .with_temp_dir(self.env.temp_dir.clone())
+        .with_proxy(self.env.proxy.clone());
+        let mut interpreter = CodeInterpreter::new(code, register, &context);
+        if let Some(timeout) = timeout {
+            interpreter.add_hook(Arc::new(Deadline::new(timeout)));
+        }
+        if self.env.value_limit.is_enabled() {
+            interpreter.add_hook(Arc::new(self.env.value_limit));
+        }
+        let mut results = Box::pin(interpreter.stream());
         while let Some(r) = results.next().await {
             match r {
                 Ok(NaslValue::Exit(x)) => return ScriptResultKind::ReturnCode(x),
+                Err(InterpretError {
+                    kind: InterpretErrorKind::Timeout(x),
+                    ..
+                }) => return ScriptResultKind::Timeout(x),
+                Err(InterpretError {
+                    kind: InterpretErrorKind::ResourceLimit(x),
+                    ..
+                }) => return ScriptResultKind::ResourceLimit(x),
                 Err(e) => return ScriptResultKind::Error(e.clone()),
                 Ok(x) => {
                     trace!(statement_result=?x);
@@ -215,6 +251,64 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
         ScriptResultKind::ReturnCode(0)
     }
 
+    /// Runs the script within its maximum lifetime and turns a panic into a result.
+    ///
+    /// Timeouts, panics and exceeded resource limits are recorded in the quarantine, a finished
+    /// run resets its failures.
+    async fn get_guarded_result_kind(
+        &self,
+        code: &str,
+        hash: &str,
+        register: Register,
+    ) -> ScriptResultKind {
+        let timeout = self.env.script_timeout.of(self.vt);
+        let run = AssertUnwindSafe(self.get_result_kind(code, register, timeout)).catch_unwind();
+        let result = match timeout {
+            // the deadline hook only takes effect between statements
+            Some(x) => tokio::time::timeout(x, run)
+                .await
+                .unwrap_or(Ok(ScriptResultKind::Timeout(x))),
+            None => run.await,
+        };
+        let kind = result.unwrap_or_else(|e| {
+            let message = e
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown reason".to_string());
+            ScriptResultKind::Panic(message)
+        });
+        let (oid, filename) = (&self.vt.oid, &self.vt.filename);
+        match &kind {
+            ScriptResultKind::Timeout(x) => {
+                warn!(timeout = x.as_secs(), "script timed out");
+                self.env
+                    .quarantine
+                    .record_failure(oid, filename, hash, Failure::Timeout(*x))
+            }
+            ScriptResultKind::Panic(x) => {
+                warn!(panic = x, "script panicked");
+                self.env
+                    .quarantine
+                    .record_failure(oid, filename, hash, Failure::Panic(x.clone()))
+            }
+            ScriptResultKind::ResourceLimit(x) => {
+                warn!(limit = x, "script exceeded its resource limits");
+                self.env.quarantine.record_failure(
+                    oid,
+                    filename,
+                    hash,
+                    Failure::ResourceLimit(x.clone()),
+                )
+            }
+            ScriptResultKind::ReturnCode(_) | ScriptResultKind::Error(_) => {
+                self.env.quarantine.record_success(oid)
+            }
+            _ => {}
+        }
+        kind
+    }
+
     async fn execute(mut self) -> Result<ScriptResult, ExecuteError> {
         let code = self.loader.load(&self.vt.filename)?;
         let mut register = Register::default();
@@ -222,7 +316,14 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
 
         // currently scans are limited to the target as well as the id.
         tracing::debug!("running");
-        let kind = self.get_result_kind(&code, register).await;
+        let hash = Quarantine::hash(&code);
+        let kind = match self.env.quarantine.reason(&self.vt.oid, &hash) {
+            Some(reason) => {
+                warn!(reason, "skipping quarantined script");
+                ScriptResultKind::Quarantined(reason)
+            }
+            None => self.get_guarded_result_kind(&code, &hash, register).await,
+        };
         tracing::debug!(result=?kind, "finished");
         Ok(ScriptResult {
             oid: self.vt.oid.clone(),
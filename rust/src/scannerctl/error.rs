@@ -191,6 +191,7 @@ impl From<feed::UpdateError> for CliError {
                 CliErrorKind::Corrupt("description run without exit.".to_string())
             }
             feed::UpdateErrorKind::VerifyError(e) => CliErrorKind::Corrupt(e.to_string()),
+            e @ feed::UpdateErrorKind::InvalidMetadata(_) => CliErrorKind::Corrupt(e.to_string()),
         };
         CliError {
             filename: value.key,
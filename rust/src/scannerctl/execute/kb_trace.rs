@@ -0,0 +1,159 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Prints the knowledge base accesses of the executed scripts.
+//!
+//! The lines are written to stdout, like the output of `display`, so that they appear in the
+//! order the script accessed the knowledge base.
+
+use scannerlib::models;
+use scannerlib::storage::{
+    item::Nvt, types::Primitive, ContextKey, Dispatcher, Field, FieldKeyResult, FieldResult, Kb,
+    Remover, Retrieve, Retriever, StorageError,
+};
+
+/// Storage printing every read and write of a KB item before passing it to the wrapped storage
+pub struct KbTrace<S> {
+    storage: S,
+}
+
+impl<S> KbTrace<S>
+where
+    S: Dispatcher + Retriever,
+{
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    fn kbs(&self, key: &ContextKey, name: &str) -> Result<Vec<Kb>, StorageError> {
+        Ok(self
+            .storage
+            .retrieve(key, Retrieve::KB(name.to_string()))?
+            .filter_map(|x| match x {
+                Field::KB(kb) => Some(kb),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Writes the KB item and prints the values of its name before and after the write.
+    fn write(
+        &self,
+        action: &str,
+        key: &ContextKey,
+        field: Field,
+        write: impl FnOnce(&S, Field) -> Result<(), StorageError>,
+    ) -> Result<(), StorageError> {
+        let name = match &field {
+            Field::KB(kb) => kb.key.clone(),
+            _ => return write(&self.storage, field),
+        };
+        let before = self.kbs(key, &name)?;
+        write(&self.storage, field)?;
+        let after = self.kbs(key, &name)?;
+        println!(
+            "KB {action} {name}: {} -> {}",
+            values(&name, &before),
+            values(&name, &after)
+        );
+        Ok(())
+    }
+}
+
+fn value(value: &Primitive) -> String {
+    match value {
+        Primitive::String(x) => format!("{x:?}"),
+        Primitive::Data(x) => format!("{:?}", String::from_utf8_lossy(x)),
+        Primitive::Number(x) => x.to_string(),
+        Primitive::Boolean(x) => x.to_string(),
+        Primitive::Null => "NULL".to_string(),
+        x => format!("{x:?}"),
+    }
+}
+
+/// Formats the values of the KB items, prefixed by their name when it differs from the requested
+/// one, e.g. for a pattern like `Ports/tcp/*`.
+fn values(name: &str, kbs: &[Kb]) -> String {
+    let values: Vec<_> = kbs
+        .iter()
+        .map(|kb| match kb.key == name {
+            true => value(&kb.value),
+            false => format!("{}={}", kb.key, value(&kb.value)),
+        })
+        .collect();
+    format!("[{}]", values.join(", "))
+}
+
+impl<S> Dispatcher for KbTrace<S>
+where
+    S: Dispatcher + Retriever,
+{
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.write("set", key, scope, |s, f| s.dispatch(key, f))
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.write("replace", key, scope, |s, f| s.dispatch_replace(key, f))
+    }
+
+    fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError> {
+        self.storage.on_exit(key)
+    }
+}
+
+impl<S> Retriever for KbTrace<S>
+where
+    S: Dispatcher + Retriever,
+{
+    fn retrieve(&self, key: &ContextKey, scope: Retrieve) -> FieldResult {
+        match scope {
+            Retrieve::KB(name) => {
+                let kbs = self.kbs(key, &name)?;
+                println!("KB get {name}: {}", values(&name, &kbs));
+                Ok(Box::new(kbs.into_iter().map(Field::KB)))
+            }
+            scope => self.storage.retrieve(key, scope),
+        }
+    }
+
+    fn retrieve_by_field(&self, field: Field, scope: Retrieve) -> FieldKeyResult {
+        self.storage.retrieve_by_field(field, scope)
+    }
+
+    fn retrieve_by_fields(&self, field: Vec<Field>, scope: Retrieve) -> FieldKeyResult {
+        self.storage.retrieve_by_fields(field, scope)
+    }
+
+    fn vts(&self) -> Result<Box<dyn Iterator<Item = Nvt>>, StorageError> {
+        self.storage.vts()
+    }
+}
+
+impl<S> Remover for KbTrace<S>
+where
+    S: Dispatcher + Retriever + Remover,
+{
+    fn remove_kb(
+        &self,
+        key: &ContextKey,
+        kb_key: Option<String>,
+    ) -> Result<Option<Vec<Kb>>, StorageError> {
+        let removed = self.storage.remove_kb(key, kb_key.clone())?;
+        if let Some(name) = kb_key {
+            println!(
+                "KB remove {name}: {} -> []",
+                values(&name, removed.as_deref().unwrap_or_default())
+            );
+        }
+        Ok(removed)
+    }
+
+    fn remove_result(
+        &self,
+        key: &ContextKey,
+        result_id: Option<usize>,
+    ) -> Result<Option<Vec<models::Result>>, StorageError> {
+        self.storage.remove_result(key, result_id)
+    }
+}
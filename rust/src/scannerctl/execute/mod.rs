@@ -2,18 +2,27 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::{fs, path::PathBuf};
+pub mod kb_trace;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use clap::{arg, value_parser, Arg, ArgAction, Command};
 use futures::StreamExt;
 use scannerlib::feed::{HashSumNameLoader, Update};
 use scannerlib::models::Scan;
+use scannerlib::nasl::utils::{traffic::Recording, Traffic};
 use scannerlib::nasl::{nasl_std_functions, FSPluginLoader};
 use scannerlib::scanner::ScanRunner;
-use scannerlib::scheduling::{ExecutionPlaner, WaveExecutionPlan};
+use scannerlib::scheduling::{ConcurrentVTResult, ExecutionPlaner, WaveExecutionPlan};
+use scannerlib::storage::{DefaultDispatcher, Storage};
 use tracing::{info, warn, warn_span};
 
 use crate::{interpret, CliError, CliErrorKind, Db};
+use kb_trace::KbTrace;
 
 pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
     let (args, _) = crate::get_args_set_logging(root, "execute")?;
@@ -51,12 +60,16 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
         .get_one::<bool>("schedule")
         .cloned()
         .unwrap_or_default();
+    let trace_kb = args
+        .get_one::<bool>("trace-kb")
+        .cloned()
+        .unwrap_or_default();
 
     let feed = args
         .get_one::<PathBuf>("path")
         .expect("A feed path is required to run a scan")
         .clone();
-    let storage = scannerlib::storage::DefaultDispatcher::new();
+    let storage = Arc::new(DefaultDispatcher::new());
     info!("loading feed. This may take a while.");
 
     let loader = FSPluginLoader::new(feed);
@@ -80,31 +93,41 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
                     .join(", ")
             );
         }
+    } else if trace_kb {
+        run_scan(&KbTrace::new(storage.clone()), &loader, schedule, &scan).await;
     } else {
-        let executor = nasl_std_functions();
-        let runner: ScanRunner<(_, _)> =
-            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).unwrap();
-        let mut results = Box::pin(runner.stream());
-        while let Some(x) = results.next().await {
-            match x {
-                Ok(x) => {
-                    let _span =
-                        warn_span!("script_result", filename=x.filename, oid=x.oid, stage=%x.stage)
-                            .entered();
-                    if x.has_succeeded() {
-                        info!("success")
-                    } else {
-                        warn!(kind=?x.kind, "failed")
-                    }
-                }
-                Err(e) => {
-                    warn!(error=?e, "failed to execute script.");
+        run_scan(&storage, &loader, schedule, &scan).await;
+    }
+
+    Ok(())
+}
+
+async fn run_scan<S, Sched>(storage: &S, loader: &FSPluginLoader, schedule: Sched, scan: &Scan)
+where
+    S: Storage + Send + 'static,
+    Sched: Iterator<Item = ConcurrentVTResult>,
+{
+    let executor = nasl_std_functions();
+    let runner: ScanRunner<(_, _)> =
+        ScanRunner::new(storage, loader, &executor, schedule, scan).unwrap();
+    let mut results = Box::pin(runner.stream());
+    while let Some(x) = results.next().await {
+        match x {
+            Ok(x) => {
+                let _span =
+                    warn_span!("script_result", filename=x.filename, oid=x.oid, stage=%x.stage)
+                        .entered();
+                if x.has_succeeded() {
+                    info!("success")
+                } else {
+                    warn!(kind=?x.kind, "failed")
                 }
             }
+            Err(e) => {
+                warn!(error=?e, "failed to execute script.");
+            }
         }
     }
-
-    Ok(())
 }
 
 async fn script(args: &clap::ArgMatches) -> Option<Result<(), CliError>> {
@@ -114,15 +137,54 @@ async fn script(args: &clap::ArgMatches) -> Option<Result<(), CliError>> {
         .cloned()
         .expect("script is set to required");
     let target = args.get_one::<String>("target").cloned();
-    Some(
-        interpret::run(
-            &Db::InMemory,
-            feed.clone(),
-            &script.to_string(),
-            target.clone(),
-        )
-        .await,
+    // not available on the deprecated execute command without subcommand
+    let taint = args
+        .try_get_one::<bool>("taint")
+        .ok()
+        .flatten()
+        .cloned()
+        .unwrap_or_default();
+    let record = args.try_get_one::<PathBuf>("record").ok().flatten();
+    let replay = args.try_get_one::<PathBuf>("replay").ok().flatten();
+    let trace_kb = args
+        .try_get_one::<bool>("trace-kb")
+        .ok()
+        .flatten()
+        .cloned()
+        .unwrap_or_default();
+    let traffic = match (record, replay) {
+        (_, Some(path)) => match load_recording(path) {
+            Ok(recording) => Some(Traffic::replay(recording)),
+            Err(e) => return Some(Err(CliError::load_error(e, path))),
+        },
+        (Some(_), None) => Some(Traffic::record()),
+        (None, None) => None,
+    };
+    let result = interpret::run(
+        &Db::InMemory,
+        feed.clone(),
+        &script.to_string(),
+        target.clone(),
+        taint,
+        traffic.clone(),
+        trace_kb,
     )
+    .await;
+    if let (Some(path), Some(traffic)) = (record, traffic) {
+        if let Err(e) = save_recording(path, &traffic.recording()) {
+            return Some(Err(CliError::load_error(e, path)));
+        }
+    }
+    Some(result)
+}
+
+pub(crate) fn load_recording(path: &Path) -> io::Result<Recording> {
+    Ok(serde_json::from_reader(fs::File::open(path)?)?)
+}
+
+fn save_recording(path: &Path, recording: &Recording) -> io::Result<()> {
+    serde_json::to_writer_pretty(fs::File::create(path)?, recording)?;
+    Ok(())
 }
 pub fn extend_args(cmd: Command) -> Command {
     cmd.subcommand(crate::add_verbose(
@@ -141,7 +203,11 @@ When ID is used than a valid feed path must be given within the path parameter."
                             .value_parser(value_parser!(PathBuf)),
                     )
                     .arg(Arg::new("script").required(true))
-                    .arg(arg!(-t --target <HOST> "Target to scan").required(false)),
+                    .arg(arg!(-t --target <HOST> "Target to scan").required(false))
+                    .arg(arg!(--taint "Logs when data received from the network is passed to builtins accessing files or executing commands (experimental)").required(false).action(ArgAction::SetTrue))
+                    .arg(arg!(--record <FILE> "Records the traffic of the sockets opened by the script into the file").required(false).value_parser(value_parser!(PathBuf)))
+                    .arg(arg!(--replay <FILE> "Replays the traffic recorded into the file instead of connecting to the target").required(false).value_parser(value_parser!(PathBuf)).conflicts_with("record"))
+                    .arg(arg!(--"trace-kb" "Prints every read and write of a KB item with the values before and after it").required(false).action(ArgAction::SetTrue)),
             )
             .subcommand(
                 Command::new("scan")
@@ -155,6 +221,7 @@ When ID is used than a valid feed path must be given within the path parameter."
                     )
                     .arg(arg!(--schedule "Prints just the schedule without executing the scan").required(false).action(ArgAction::SetTrue))
                     .arg(arg!(-i --input "Parses scan json from stdin.").required(false).action(ArgAction::SetTrue))
+                    .arg(arg!(--"trace-kb" "Prints every read and write of a KB item with the values before and after it").required(false).action(ArgAction::SetTrue))
                     .arg(Arg::new("json").required(false).value_parser(value_parser!(PathBuf)))
             )
             // this is here for downwards compatible reasons and should be moved to the script
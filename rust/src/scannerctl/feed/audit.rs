@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{io, path::PathBuf};
+
+use scannerlib::feed::{Audit, NaslFileFinder};
+use scannerlib::nasl::{syntax::parse, Loader};
+
+use crate::CliError;
+
+/// Audits each nasl script and inc file within path and prints the scripts calling raw IP,
+/// process execution or file builtins or containing host or port literals as json array to
+/// stdout.
+///
+/// Files containing syntax errors are skipped with a warning.
+pub async fn run(path: PathBuf) -> Result<(), CliError> {
+    let base = path.to_string_lossy().to_string();
+    let finder = NaslFileFinder::new(&base, true);
+    let loader = NaslFileFinder::new(&base, true);
+    let (mut audited, mut skipped) = (0, 0);
+    let mut findings = vec![];
+    'files: for filename in finder {
+        let filename = filename?;
+        let code = loader.load(&filename).map_err(|e| CliError {
+            filename: filename.clone(),
+            kind: e.into(),
+        })?;
+        let mut audit = Audit::new(&filename);
+        for statement in parse(&code) {
+            match statement {
+                Ok(x) => audit.add(&x),
+                Err(e) => {
+                    tracing::warn!(filename, %e, "skipped");
+                    skipped += 1;
+                    continue 'files;
+                }
+            }
+        }
+        audited += 1;
+        if !audit.is_empty() {
+            findings.push(audit);
+        }
+    }
+    serde_json::to_writer_pretty(io::stdout(), &findings)?;
+    tracing::info!(audited, skipped, findings = findings.len(), "audited feed");
+    Ok(())
+}
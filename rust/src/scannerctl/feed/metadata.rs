@@ -0,0 +1,60 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{io, path::PathBuf};
+
+use scannerlib::feed::{MetadataExtractor, NaslFileFinder};
+use scannerlib::nasl::Loader;
+use scannerlib::storage::item::ItemDispatcher as _;
+use scannerlib::storage::json::{ArrayWrapper, ItemDispatcher};
+use scannerlib::storage::StorageError;
+
+use crate::CliError;
+
+/// Extracts the metadata of each nasl script within path without executing them and prints
+/// them as json array to stdout.
+///
+/// Scripts whose description block cannot be evaluated statically are skipped with a warning.
+pub async fn run(path: PathBuf) -> Result<(), CliError> {
+    let base = path.to_string_lossy().to_string();
+    let finder = NaslFileFinder::new(&base, true);
+    let loader = NaslFileFinder::new(&base, true);
+    let extractor = MetadataExtractor::new("1");
+    let mut output = ArrayWrapper::new(io::stdout());
+    let dispatcher = ItemDispatcher::new(&mut output);
+    let (mut extracted, mut skipped) = (0, 0);
+    for filename in finder {
+        let filename = filename?;
+        if !filename.ends_with(".nasl") {
+            continue;
+        }
+        let code = loader.load(&filename).map_err(|e| CliError {
+            filename: filename.clone(),
+            kind: e.into(),
+        })?;
+        match extractor.extract(&filename, &code).await {
+            Ok(nvt) => {
+                dispatcher.dispatch_nvt(nvt).map_err(|e| CliError {
+                    filename: filename.clone(),
+                    kind: e.into(),
+                })?;
+                extracted += 1;
+            }
+            Err(e) => {
+                tracing::warn!(filename, %e, "skipped");
+                skipped += 1;
+            }
+        }
+    }
+    drop(dispatcher);
+    output
+        .end()
+        .map_err(StorageError::from)
+        .map_err(|e| CliError {
+            filename: String::default(),
+            kind: e.into(),
+        })?;
+    tracing::info!(extracted, skipped, "extracted metadata");
+    Ok(())
+}
@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+pub mod audit;
+pub mod metadata;
 pub mod update;
 use std::{
     io,
@@ -21,7 +23,7 @@ use scannerlib::{
     },
 };
 
-use scannerlib::feed::{FeedReplacer, ReplaceCommand};
+use scannerlib::feed::{FeedReplacer, ReplaceCommand, ValidationMode};
 use scannerlib::storage::{item::PerItemDispatcher, StorageError};
 
 use crate::{get_path_from_openvas, notusupdate, read_openvas_config, CliError, CliErrorKind};
@@ -42,12 +44,24 @@ pub fn extend_args(cmd: Command) -> Command {
                      .value_parser(value_parser!(PathBuf)))
                 .arg(arg!(-x --"signature-check" "Enable NASL signature check.").required(false).action(ArgAction::SetTrue))
                 .arg(arg!(-r --redis <VALUE> "Redis url. Must either start `unix://` or `redis://`.").required(false))
+                .arg(arg!(--validate <MODE> "Validation of the VT metadata: off, reject or quarantine.").required(false)
+                     .value_parser(value_parser!(ValidationMode)).default_value("off"))
                 )
                 .subcommand(Command::new("transform")
                 .about("Runs nasl scripts in description mode and returns it as a json array into stdout")
                 .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
                     .value_parser(value_parser!(PathBuf)))
                 )
+                .subcommand(Command::new("metadata")
+                .about("Extracts the metadata of nasl scripts without executing them and returns it as a json array into stdout")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                )
+                .subcommand(Command::new("audit")
+                .about("Reports the raw IP, process execution and file builtins as well as host and port literals used by nasl scripts as a json array into stdout")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                )
                 .subcommand(Command::new("transpile")
                 .about("Transforms each nasl script and inc file based on the given rules.")
                 .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
@@ -78,7 +92,11 @@ pub async fn update_vts(
 ) -> Result<(), CliError> {
     let path = get_vts_path("vts-path", args);
     let dispatcher = get_dispatcher(redis, &path, FEEDUPDATE_SELECTOR)?;
-    update::run(dispatcher, path, signature_check).await
+    let validation = args
+        .get_one::<ValidationMode>("validate")
+        .cloned()
+        .unwrap_or_default();
+    update::run(dispatcher, path, signature_check, validation).await
 }
 
 pub async fn update_notus(
@@ -164,15 +182,21 @@ pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
 
             let mut o = ArrayWrapper::new(io::stdout());
             let dispatcher = ItemDispatcher::as_dispatcher(&mut o);
-            Some(match update::run(dispatcher, path, false).await {
-                Ok(_) => o.end().map_err(StorageError::from).map_err(|se| CliError {
-                    filename: "".to_string(),
-                    kind: se.into(),
-                }),
-                Err(e) => Err(e),
-            })
+            Some(
+                match update::run(dispatcher, path, false, ValidationMode::Off).await {
+                    Ok(_) => o.end().map_err(StorageError::from).map_err(|se| CliError {
+                        filename: "".to_string(),
+                        kind: se.into(),
+                    }),
+                    Err(e) => Err(e),
+                },
+            )
         }
 
+        Some(("metadata", args)) => Some(metadata::run(get_vts_path("path", args)).await),
+
+        Some(("audit", args)) => Some(audit::run(get_vts_path("path", args)).await),
+
         Some(("transpile", args)) => {
             let path = get_vts_path("path", args);
             let rules = match args.get_one::<PathBuf>("rules").cloned() {
@@ -12,7 +12,12 @@ use scannerlib::{
 
 use crate::{CliError, CliErrorKind};
 
-pub async fn run<S>(storage: S, path: PathBuf, signature_check: bool) -> Result<(), CliError>
+pub async fn run<S>(
+    storage: S,
+    path: PathBuf,
+    signature_check: bool,
+    validation: feed::ValidationMode,
+) -> Result<(), CliError>
 where
     S: Sync + Send + Dispatcher,
 {
@@ -21,7 +26,9 @@ where
     // e.g. 2006/something.nasl
     let loader = FSPluginLoader::new(path);
     let verifier = feed::HashSumNameLoader::sha256(&loader)?;
-    let updater = feed::Update::init("1", 5, &loader, &storage, verifier);
+    let validation = feed::Validation::new(validation);
+    let updater =
+        feed::Update::init("1", 5, &loader, &storage, verifier).with_validation(&validation);
 
     if signature_check {
         match updater.verify_signature() {
@@ -50,6 +57,15 @@ where
     }
 
     updater.perform_update().await?;
+    for vt in validation.quarantined() {
+        let violations = vt
+            .violations
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        eprintln!("{} ({}): {violations}", vt.filename, vt.oid);
+    }
 
     Ok(())
 }
@@ -13,6 +13,7 @@ use scannerlib::nasl::{
     interpreter::{FunctionError, InterpretErrorKind},
     prelude::*,
     syntax::{load_non_utf8_path, LoadError},
+    utils::Traffic,
     Loader, NoOpLoader,
 };
 use scannerlib::storage::redis::FEEDUPDATE_SELECTOR;
@@ -27,12 +28,14 @@ use scannerlib::{
     },
 };
 
+use crate::execute::kb_trace::KbTrace;
 use crate::{CliError, CliErrorKind, Db};
 
 struct Run<L, S> {
     context_builder: ContextFactory<L, S>,
     target: String,
     scan_id: String,
+    taint: bool,
 }
 
 struct RunBuilder<L, S> {
@@ -40,6 +43,9 @@ struct RunBuilder<L, S> {
     storage: S,
     target: String,
     scan_id: String,
+    taint: bool,
+    traffic: Option<Traffic>,
+    trace_kb: bool,
 }
 
 impl Default for RunBuilder<NoOpLoader, DefaultDispatcher> {
@@ -49,6 +55,9 @@ impl Default for RunBuilder<NoOpLoader, DefaultDispatcher> {
             loader: NoOpLoader::default(),
             target: String::default(),
             scan_id: "scannerctl".to_string(),
+            taint: false,
+            traffic: None,
+            trace_kb: false,
         }
     }
 }
@@ -59,11 +68,18 @@ where
     L: Loader,
 {
     pub fn storage<S2>(self, s: S2) -> RunBuilder<L, S2> {
+        self.map_storage(|_| s)
+    }
+
+    fn map_storage<S2>(self, f: impl FnOnce(S) -> S2) -> RunBuilder<L, S2> {
         RunBuilder {
             loader: self.loader,
-            storage: s,
+            storage: f(self.storage),
             target: self.target,
             scan_id: self.scan_id,
+            taint: self.taint,
+            traffic: self.traffic,
+            trace_kb: self.trace_kb,
         }
     }
 
@@ -73,6 +89,9 @@ where
             storage: self.storage,
             target: self.target,
             scan_id: self.scan_id,
+            taint: self.taint,
+            traffic: self.traffic,
+            trace_kb: self.trace_kb,
         }
     }
 
@@ -86,11 +105,38 @@ where
         self
     }
 
+    pub fn taint(mut self, taint: bool) -> RunBuilder<L, S> {
+        self.taint = taint;
+        self
+    }
+
+    pub fn traffic(mut self, traffic: Option<Traffic>) -> RunBuilder<L, S> {
+        self.traffic = traffic;
+        self
+    }
+
+    pub fn trace_kb(mut self, trace_kb: bool) -> RunBuilder<L, S> {
+        self.trace_kb = trace_kb;
+        self
+    }
+
     pub fn build(self) -> Run<L, S> {
+        let mut context_builder = ContextFactory::new(self.loader, self.storage);
+        context_builder.traffic = self.traffic;
         Run {
-            context_builder: ContextFactory::new(self.loader, self.storage),
+            context_builder,
             scan_id: self.scan_id,
             target: self.target,
+            taint: self.taint,
+        }
+    }
+
+    /// Builds the run and executes the script, printing the KB accesses when enabled.
+    async fn run(self, script: &str) -> Result<(), CliErrorKind> {
+        if self.trace_kb {
+            self.map_storage(KbTrace::new).build().run(script).await
+        } else {
+            self.build().run(script).await
         }
     }
 }
@@ -125,10 +171,13 @@ where
     }
 
     async fn run(&self, script: &str) -> Result<(), CliErrorKind> {
-        let context = self.context_builder.build(ContextKey::Scan(
-            self.scan_id.clone(),
-            Some(self.target.clone()),
-        ));
+        let context = self
+            .context_builder
+            .build(ContextKey::Scan(
+                self.scan_id.clone(),
+                Some(self.target.clone()),
+            ))
+            .with_taint_tracking(self.taint);
         let register = RegisterBuilder::build();
         let code = self.load(script)?;
         let results: Vec<_> = CodeInterpreter::new(&code, register, &context)
@@ -198,25 +247,29 @@ pub async fn run(
     feed: Option<PathBuf>,
     script: &str,
     target: Option<String>,
+    taint: bool,
+    traffic: Option<Traffic>,
+    trace_kb: bool,
 ) -> Result<(), CliError> {
     let builder = RunBuilder::default()
         .target(target.unwrap_or_default())
-        .scan_id(format!("scannerctl-{script}"));
+        .scan_id(format!("scannerctl-{script}"))
+        .taint(taint)
+        .traffic(traffic.clone())
+        .trace_kb(trace_kb);
     let result = match (db, feed) {
-        (Db::Redis(url), None) => {
-            builder
-                .storage(create_redis_storage(url))
-                .build()
-                .run(script)
-                .await
-        }
-        (Db::InMemory, None) => builder.build().run(script).await,
+        (Db::Redis(url), None) => builder.storage(create_redis_storage(url)).run(script).await,
+        (Db::InMemory, None) => builder.run(script).await,
         (Db::Redis(url), Some(path)) => {
             let storage = create_redis_storage(url);
             let loader = FSPluginLoader::new(path);
             load_feed_by_exec(&storage, &loader).await?;
-            let builder = RunBuilder::default().loader(loader);
-            builder.storage(storage).build().run(script).await
+            let builder = RunBuilder::default()
+                .loader(loader)
+                .taint(taint)
+                .traffic(traffic)
+                .trace_kb(trace_kb);
+            builder.storage(storage).run(script).await
         }
         (Db::InMemory, Some(path)) => {
             let storage = DefaultDispatcher::new();
@@ -228,8 +281,12 @@ pub async fn run(
                 load_feed_by_exec(&storage, &loader).await?
             }
 
-            let builder = RunBuilder::default().loader(loader);
-            builder.storage(storage).build().run(script).await
+            let builder = RunBuilder::default()
+                .loader(loader)
+                .taint(taint)
+                .traffic(traffic)
+                .trace_kb(trace_kb);
+            builder.storage(storage).run(script).await
         }
     };
 
@@ -7,6 +7,7 @@ mod error;
 mod execute;
 mod feed;
 mod interpret;
+mod mutate;
 mod notusupdate;
 mod scanconfig;
 mod syntax;
@@ -14,6 +15,7 @@ mod syntax;
 use configparser::ini::Ini;
 pub use error::*;
 
+use scannerlib::logging::{JsonFields, JsonLines, LogFormat};
 use scannerlib::storage::StorageError;
 use std::{path::PathBuf, process};
 
@@ -53,15 +55,16 @@ fn get_path_from_openvas(config: Ini) -> PathBuf {
 
 #[tokio::main]
 async fn main() {
-    let matches = add_verbose(
+    let matches = add_verbose(add_log_format(
         Command::new("scannerctl")
             .version("1.0")
             .about("Is a CLI tool around NASL.")
             .subcommand_required(true),
-    );
+    ));
     let matches = syntax::extend_args(matches);
     let matches = scanconfig::extend_args(matches);
     let matches = execute::extend_args(matches);
+    let matches = mutate::extend_args(matches);
     let matches = notusupdate::scanner::extend_args(matches);
     let matches = feed::extend_args(matches).get_matches();
     let result = run(&matches).await;
@@ -92,6 +95,9 @@ async fn run(matches: &ArgMatches) -> Result<(), CliError> {
     if let Some(result) = execute::run(matches).await {
         return result;
     }
+    if let Some(result) = mutate::run(matches).await {
+        return result;
+    }
     if let Some(result) = scanconfig::run(matches).await {
         return result;
     }
@@ -107,7 +113,7 @@ async fn run(matches: &ArgMatches) -> Result<(), CliError> {
     })
 }
 
-pub fn set_logging(level: u8) {
+pub fn set_logging(level: u8, format: LogFormat) {
     let lv = if level > 1 {
         tracing::Level::TRACE
     } else if level > 0 {
@@ -115,10 +121,16 @@ pub fn set_logging(level: u8) {
     } else {
         tracing::Level::INFO
     };
-    tracing_subscriber::fmt()
+    let builder = tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
-        .with_max_level(lv)
-        .init();
+        .with_max_level(lv);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .fmt_fields(JsonFields)
+            .event_format(JsonLines)
+            .init(),
+    }
 }
 
 pub fn add_verbose(cmd: Command) -> Command {
@@ -129,6 +141,15 @@ pub fn add_verbose(cmd: Command) -> Command {
     )
 }
 
+fn add_log_format(cmd: Command) -> Command {
+    cmd.arg(
+        arg!(--"log-format" <FORMAT> "Format of log messages: text or json")
+            .required(false)
+            .global(true)
+            .value_parser(|x: &str| x.parse::<LogFormat>()),
+    )
+}
+
 pub fn get_args_set_logging<'a>(
     root: &'a ArgMatches,
     name: &'a str,
@@ -136,6 +157,10 @@ pub fn get_args_set_logging<'a>(
     let verbose = root.get_one::<u8>("verbose").cloned().unwrap_or_default();
     let args = root.subcommand_matches(name)?;
     let verbose = args.get_one::<u8>("verbose").cloned().unwrap_or(verbose);
-    set_logging(verbose);
+    let format = args
+        .get_one::<LogFormat>("log-format")
+        .cloned()
+        .unwrap_or_default();
+    set_logging(verbose, format);
     Some((args, verbose))
 }
@@ -0,0 +1,109 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use clap::{arg, value_parser, Arg, ArgAction, Command};
+use scannerlib::feed::{MutateError, MutationTester, MutationVerdict};
+use scannerlib::nasl::utils::traffic::Recording;
+use scannerlib::nasl::{syntax::load_non_utf8_path, FSPluginLoader, Loader, NoOpLoader};
+
+use crate::{CliError, CliErrorKind};
+
+pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let (args, _) = crate::get_args_set_logging(root, "mutate")?;
+    let script = args
+        .get_one::<PathBuf>("script")
+        .cloned()
+        .expect("script is set to required");
+    let feed = args.get_one::<PathBuf>("path").cloned();
+    let target = args
+        .get_one::<String>("target")
+        .cloned()
+        .unwrap_or_default();
+    let quiet = args.get_one::<bool>("quiet").cloned().unwrap_or_default();
+    let replay = match args.get_one::<PathBuf>("replay") {
+        Some(path) => match crate::execute::load_recording(path) {
+            Ok(recording) => Some(recording),
+            Err(e) => return Some(Err(CliError::load_error(e, path))),
+        },
+        None => None,
+    };
+    Some(match feed {
+        Some(feed) => mutate(&FSPluginLoader::new(feed), &script, target, replay, quiet).await,
+        None => mutate(&NoOpLoader::default(), &script, target, replay, quiet).await,
+    })
+}
+
+async fn mutate(
+    loader: &dyn Loader,
+    script: &PathBuf,
+    target: String,
+    replay: Option<Recording>,
+    quiet: bool,
+) -> Result<(), CliError> {
+    let filename = script.to_string_lossy().to_string();
+    let code = load_non_utf8_path(script).map_err(|e| CliError {
+        filename: filename.clone(),
+        kind: e.into(),
+    })?;
+    let mut tester = MutationTester::new(loader, target);
+    if let Some(recording) = replay {
+        tester = tester.with_replay(recording);
+    }
+    let report = tester.run(&code).await.map_err(|e| CliError {
+        filename: filename.clone(),
+        kind: match e {
+            MutateError::Syntax(e) => CliErrorKind::SyntaxError(*e),
+            MutateError::Replace(e) => CliErrorKind::Corrupt(e.to_string()),
+        },
+    })?;
+    for (mutation, verdict) in &report.mutants {
+        match verdict {
+            MutationVerdict::Survived => println!("{filename}:{mutation} survived"),
+            MutationVerdict::Timeout if !quiet => println!("{filename}:{mutation} timeout"),
+            MutationVerdict::Killed(reason) if !quiet => {
+                println!("{filename}:{mutation} killed by {reason}")
+            }
+            _ => {}
+        }
+    }
+    println!(
+        "{} mutants, {} survived, score: {:.2}",
+        report.mutants.len(),
+        report.survived().count(),
+        report.score()
+    );
+    Ok(())
+}
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(crate::add_verbose(
+        Command::new("mutate")
+            .about(
+                "Changes comparisons and constants of a nasl-script one at a time and reports the changes that do not alter the behavior against the target.",
+            )
+            .arg(
+                arg!(-p --path <FILE> "Path to the feed used for includes.")
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(arg!(-t --target <HOST> "Target to run the script against").required(false))
+            .arg(
+                arg!(--replay <FILE> "Replays the traffic recorded by `execute script --record` in each run instead of connecting to the target")
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(-q --quiet "Prints only surviving mutants and the summary.")
+                    .required(false)
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("script")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    ))
+}
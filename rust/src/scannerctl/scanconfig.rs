@@ -87,7 +87,13 @@ async fn execute(
     };
 
     tracing::info!("loading feed. This may take a while.");
-    crate::feed::update::run(Arc::clone(&storage), feed.to_owned(), false).await?;
+    crate::feed::update::run(
+        Arc::clone(&storage),
+        feed.to_owned(),
+        false,
+        scannerlib::feed::ValidationMode::Off,
+    )
+    .await?;
     tracing::info!("feed loaded.");
     let ports = match port_list {
         Some(ports) => {